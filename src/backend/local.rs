@@ -1,6 +1,8 @@
 use std::io::{BufReader, BufWriter};
 use std::path::Path;
+use std::time::SystemTime;
 
+use crate::backend::readonly;
 use crate::backend::{BackendFeatures, FileEntry, FileStat, FluxBackend};
 use crate::error::FluxError;
 
@@ -106,12 +108,14 @@ impl FluxBackend for LocalBackend {
     }
 
     fn open_write(&self, path: &Path) -> Result<Box<dyn std::io::Write + Send>, FluxError> {
+        readonly::check(path, "write")?;
         let file =
             std::fs::File::create(path).map_err(|e| map_io_error(e, path, IoContext::Write))?;
         Ok(Box::new(BufWriter::with_capacity(BUF_SIZE, file)))
     }
 
     fn create_dir_all(&self, path: &Path) -> Result<(), FluxError> {
+        readonly::check(path, "create")?;
         std::fs::create_dir_all(path)
             .map_err(|e| map_io_error(e, path, IoContext::CreateDir))?;
         Ok(())
@@ -122,8 +126,49 @@ impl FluxBackend for LocalBackend {
             supports_seek: true,
             supports_parallel: true,
             supports_permissions: cfg!(unix),
+            supports_remove: true,
+            supports_rename: true,
+            supports_set_mtime: true,
         }
     }
+
+    fn remove(&self, path: &Path) -> Result<(), FluxError> {
+        readonly::check(path, "delete")?;
+        let meta =
+            std::fs::symlink_metadata(path).map_err(|e| map_io_error(e, path, IoContext::Stat))?;
+        let result = if meta.is_dir() {
+            std::fs::remove_dir(path)
+        } else {
+            std::fs::remove_file(path)
+        };
+        result.map_err(|e| map_io_error(e, path, IoContext::Write))
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> Result<(), FluxError> {
+        readonly::check(from, "rename")?;
+        readonly::check(to, "write")?;
+        std::fs::rename(from, to).map_err(|e| map_io_error(e, from, IoContext::Write))
+    }
+
+    fn set_mtime(&self, path: &Path, mtime: SystemTime) -> Result<(), FluxError> {
+        readonly::check(path, "change the modification time of")?;
+        // Unix can set times through a read-only handle; Windows needs write access
+        let file = std::fs::OpenOptions::new()
+            .read(cfg!(unix))
+            .write(!cfg!(unix))
+            .open(path)
+            .map_err(|e| map_io_error(e, path, IoContext::Write))?;
+        file.set_modified(mtime)
+            .map_err(|e| map_io_error(e, path, IoContext::Write))
+    }
+
+    #[cfg(unix)]
+    fn set_permissions(&self, path: &Path, mode: u32) -> Result<(), FluxError> {
+        use std::os::unix::fs::PermissionsExt;
+        readonly::check(path, "change the permissions of")?;
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode))
+            .map_err(|e| map_io_error(e, path, IoContext::Write))
+    }
 }
 
 #[cfg(test)]
@@ -199,6 +244,61 @@ mod tests {
         assert!(!features.supports_permissions);
     }
 
+    #[test]
+    fn remove_file_and_empty_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("a.txt");
+        let sub = dir.path().join("sub");
+        std::fs::write(&file, "a").unwrap();
+        std::fs::create_dir(&sub).unwrap();
+
+        let backend = LocalBackend::new();
+        backend.remove(&file).unwrap();
+        backend.remove(&sub).unwrap();
+        assert!(!file.exists() && !sub.exists());
+        assert!(matches!(
+            backend.remove(&file),
+            Err(FluxError::SourceNotFound { .. })
+        ));
+    }
+
+    #[test]
+    fn rename_replaces_destination() {
+        let dir = tempfile::tempdir().unwrap();
+        let from = dir.path().join("new.txt");
+        let to = dir.path().join("old.txt");
+        std::fs::write(&from, "new").unwrap();
+        std::fs::write(&to, "old").unwrap();
+
+        LocalBackend::new().rename(&from, &to).unwrap();
+        assert!(!from.exists());
+        assert_eq!(std::fs::read_to_string(&to).unwrap(), "new");
+    }
+
+    #[test]
+    fn set_mtime_is_reported_by_stat() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("a.txt");
+        std::fs::write(&file, "a").unwrap();
+        let mtime = SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1_600_000_000);
+
+        let backend = LocalBackend::new();
+        backend.set_mtime(&file, mtime).unwrap();
+        assert_eq!(backend.stat(&file).unwrap().modified, Some(mtime));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn set_permissions_is_reported_by_stat() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("a.txt");
+        std::fs::write(&file, "a").unwrap();
+
+        let backend = LocalBackend::new();
+        backend.set_permissions(&file, 0o600).unwrap();
+        assert_eq!(backend.stat(&file).unwrap().permissions.unwrap() & 0o777, 0o600);
+    }
+
     #[test]
     fn list_dir_on_project_root() {
         let backend = LocalBackend::new();
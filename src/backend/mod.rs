@@ -1,9 +1,22 @@
 pub mod local;
+#[cfg(any(feature = "backends-sftp", feature = "backends-smb"))]
+pub mod pool;
+pub mod readonly;
+#[cfg(feature = "backends-rclone")]
+pub mod rclone;
+pub mod retry;
+#[cfg(feature = "backends-sftp")]
 pub mod sftp;
+#[cfg(feature = "backends-smb")]
 pub mod smb;
+#[cfg(feature = "backends-webdav")]
+pub mod tls;
+#[cfg(feature = "backends-webdav")]
 pub mod webdav;
 
+use std::io::Read;
 use std::path::Path;
+use std::time::SystemTime;
 
 use crate::error::FluxError;
 use crate::protocol::Protocol;
@@ -26,11 +39,21 @@ pub struct FileEntry {
 }
 
 /// What capabilities a backend supports.
+///
+/// Callers check these before using the optional trait methods; a method
+/// whose flag is false returns an error.
 #[derive(Debug, Clone)]
 pub struct BackendFeatures {
     pub supports_seek: bool,
     pub supports_parallel: bool,
+    /// `set_permissions` works and `stat` reports permission bits
     pub supports_permissions: bool,
+    /// `remove` works
+    pub supports_remove: bool,
+    /// `rename` works (replacing an existing destination)
+    pub supports_rename: bool,
+    /// `set_mtime` works
+    pub supports_set_mtime: bool,
 }
 
 /// Core abstraction for all file backends.
@@ -55,30 +78,217 @@ pub trait FluxBackend: Send + Sync {
 
     /// Check backend capabilities.
     fn features(&self) -> BackendFeatures;
+
+    /// Remove a file or an empty directory.
+    ///
+    /// Optional: see `BackendFeatures::supports_remove`.
+    fn remove(&self, path: &Path) -> Result<(), FluxError> {
+        Err(unsupported("remove", path))
+    }
+
+    /// Rename `from` to `to`, replacing `to` if it exists.
+    ///
+    /// Optional: see `BackendFeatures::supports_rename`.
+    fn rename(&self, from: &Path, _to: &Path) -> Result<(), FluxError> {
+        Err(unsupported("rename", from))
+    }
+
+    /// Set the modification time of a file.
+    ///
+    /// Optional: see `BackendFeatures::supports_set_mtime`.
+    fn set_mtime(&self, path: &Path, _mtime: SystemTime) -> Result<(), FluxError> {
+        Err(unsupported("set_mtime", path))
+    }
+
+    /// Set Unix permission bits (e.g. `0o644`).
+    ///
+    /// Optional: see `BackendFeatures::supports_permissions`.
+    fn set_permissions(&self, path: &Path, _mode: u32) -> Result<(), FluxError> {
+        Err(unsupported("set_permissions", path))
+    }
+
+    /// Whether the connection still works, checked before a pooled
+    /// connection is reused (see `pool`).
+    ///
+    /// Backends without a session to lose keep the default.
+    fn is_alive(&self) -> bool {
+        true
+    }
+
+    /// Open a file for reading from byte `offset` on, used to resume a read
+    /// that broke off (see `retry`).
+    ///
+    /// The default reads and discards the first `offset` bytes; backends
+    /// that can seek or request a range override it.
+    fn open_read_range(&self, path: &Path, offset: u64) -> Result<Box<dyn Read + Send>, FluxError> {
+        let mut reader = self.open_read(path)?;
+        let skipped = std::io::copy(&mut (&mut reader).take(offset), &mut std::io::sink())
+            .map_err(|e| FluxError::Io { source: e })?;
+        if skipped < offset {
+            return Err(FluxError::Io {
+                source: std::io::Error::new(
+                    std::io::ErrorKind::UnexpectedEof,
+                    format!("{} ends before byte {}", path.display(), offset),
+                ),
+            });
+        }
+        Ok(reader)
+    }
+}
+
+/// Error for an optional operation a backend does not implement.
+fn unsupported(operation: &str, path: &Path) -> FluxError {
+    FluxError::ProtocolError(format!(
+        "'{}' is not supported by this backend ({})",
+        operation,
+        path.display()
+    ))
 }
 
 /// Create the appropriate backend for a detected protocol.
 ///
 /// Returns `LocalBackend` for local paths, `SftpBackend` for SFTP,
-/// `SmbBackend` for SMB, and `WebDavBackend` for WebDAV.
+/// `SmbBackend` for SMB, `WebDavBackend` for WebDAV and `RcloneBackend` for
+/// rclone remotes. SFTP and SMB
+/// connections come from the process-wide `pool`, so repeated calls for the
+/// same server reuse them. Network backends are wrapped in a
+/// `RetryingBackend` that retries transient failures as set in the
+/// `[backends]` config table. Protocols whose `backends-*` feature was
+/// compiled out return a `ProtocolError`.
 pub fn create_backend(protocol: &Protocol) -> Result<Box<dyn FluxBackend>, FluxError> {
+    let backend = connect(protocol)?;
+    if protocol.is_local() {
+        return Ok(backend);
+    }
+    let config = crate::config::types::load_config().unwrap_or_default();
+    let policy = retry::RetryPolicy::from_config(&config.backends);
+    let protocol = protocol.clone();
+    let backend = retry::RetryingBackend::new(backend, policy, move || connect(&protocol));
+    Ok(Box::new(backend))
+}
+
+/// The backend for `protocol`, without retries.
+fn connect(protocol: &Protocol) -> Result<Box<dyn FluxBackend>, FluxError> {
     match protocol {
         Protocol::Local { .. } => Ok(Box::new(local::LocalBackend::new())),
+        #[cfg(feature = "backends-sftp")]
         Protocol::Sftp {
             user, host, port, path,
         } => {
-            let backend = sftp::SftpBackend::connect(user, host, *port, path, None)?;
+            let key = pool::PoolKey {
+                protocol: protocol.name(),
+                host: format!("{}:{}", host, port),
+                user: user.clone(),
+            };
+            let backend = pool::global().checkout(key, path, || {
+                Ok(Box::new(sftp::SftpBackend::connect(user, host, *port, "", None)?))
+            })?;
             Ok(Box::new(backend))
         }
+        #[cfg(feature = "backends-smb")]
         Protocol::Smb {
             server, share, ..
         } => {
-            let backend = smb::SmbBackend::connect(server, share)?;
+            let key = pool::PoolKey {
+                protocol: protocol.name(),
+                host: format!("{}/{}", server, share),
+                user: String::new(),
+            };
+            let backend = pool::global().checkout(key, "", || {
+                Ok(Box::new(smb::SmbBackend::connect(server, share)?))
+            })?;
             Ok(Box::new(backend))
         }
+        #[cfg(feature = "backends-webdav")]
         Protocol::WebDav { url, auth } => {
-            let backend = webdav::WebDavBackend::new(url, auth.clone())?;
+            let config = crate::config::types::load_config().unwrap_or_default();
+            let proxy = config.proxy.as_deref();
+            let auth = webdav::stored_auth(url, auth.clone());
+            let backend = webdav::WebDavBackend::new(url, auth, proxy, &config.webdav)?;
             Ok(Box::new(backend))
         }
+        #[cfg(feature = "backends-rclone")]
+        Protocol::Rclone { remote, path } => {
+            let config = crate::config::types::load_config().unwrap_or_default();
+            let binary = config.backends.rclone_binary.as_deref();
+            Ok(Box::new(rclone::RcloneBackend::new(remote, path, binary)?))
+        }
+        #[allow(unreachable_patterns)]
+        other => Err(not_compiled_in(other)),
+    }
+}
+
+/// Error for a protocol whose backend was left out of this build.
+fn not_compiled_in(protocol: &Protocol) -> FluxError {
+    let (name, feature) = match protocol {
+        Protocol::Local { .. } => ("local", "default"),
+        Protocol::Sftp { .. } => ("SFTP", "backends-sftp"),
+        Protocol::Smb { .. } => ("SMB", "backends-smb"),
+        Protocol::WebDav { .. } => ("WebDAV", "backends-webdav"),
+        Protocol::Rclone { .. } => ("rclone", "backends-rclone"),
+    };
+    FluxError::ProtocolError(format!(
+        "This build of flux has no {} support. Rebuild with: cargo build --features {}",
+        name, feature
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A backend implementing only the required methods.
+    struct MinimalBackend;
+
+    impl FluxBackend for MinimalBackend {
+        fn stat(&self, path: &Path) -> Result<FileStat, FluxError> {
+            Err(FluxError::SourceNotFound {
+                path: path.to_path_buf(),
+            })
+        }
+
+        fn list_dir(&self, _path: &Path) -> Result<Vec<FileEntry>, FluxError> {
+            Ok(Vec::new())
+        }
+
+        fn open_read(&self, _path: &Path) -> Result<Box<dyn std::io::Read + Send>, FluxError> {
+            Ok(Box::new(std::io::empty()))
+        }
+
+        fn open_write(&self, _path: &Path) -> Result<Box<dyn std::io::Write + Send>, FluxError> {
+            Ok(Box::new(std::io::sink()))
+        }
+
+        fn create_dir_all(&self, _path: &Path) -> Result<(), FluxError> {
+            Ok(())
+        }
+
+        fn features(&self) -> BackendFeatures {
+            BackendFeatures {
+                supports_seek: false,
+                supports_parallel: false,
+                supports_permissions: false,
+                supports_remove: false,
+                supports_rename: false,
+                supports_set_mtime: false,
+            }
+        }
+    }
+
+    #[test]
+    fn optional_operations_default_to_unsupported() {
+        let backend = MinimalBackend;
+        let path = Path::new("a.txt");
+        for result in [
+            backend.remove(path),
+            backend.rename(path, Path::new("b.txt")),
+            backend.set_mtime(path, SystemTime::UNIX_EPOCH),
+            backend.set_permissions(path, 0o644),
+        ] {
+            match result {
+                Err(FluxError::ProtocolError(msg)) => assert!(msg.contains("not supported")),
+                other => panic!("Expected ProtocolError, got {:?}", other),
+            }
+        }
     }
 }
@@ -0,0 +1,367 @@
+//! Reuse of network backend connections within a process.
+//!
+//! Connecting an SFTP backend means a TCP connect, an SSH handshake and
+//! authentication; doing that for every file of a recursive remote copy
+//! would dominate small-file transfers. `create_backend` therefore checks
+//! SFTP and SMB backends out of a process-wide `BackendPool`, keyed by
+//! protocol, host and user (`PoolKey`). Dropping the `PooledBackend` hands
+//! the connection back for the next checkout of the same key.
+//!
+//! An idle connection is checked with `FluxBackend::is_alive` before it is
+//! handed out again and replaced if the check fails; connections idle for
+//! longer than `MAX_IDLE` are closed, and at most `MAX_IDLE_PER_KEY` are
+//! kept per key. Pooled connections are opened at the server's root (or
+//! the login directory); each checkout resolves relative paths against its
+//! own URI path, so files anywhere on the same server share connections.
+//! Local and WebDAV backends are cheap to create and not pooled.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::backend::{BackendFeatures, FileEntry, FileStat, FluxBackend};
+use crate::error::FluxError;
+
+/// Idle connections older than this are closed rather than reused.
+const MAX_IDLE: Duration = Duration::from_secs(60);
+
+/// Idle connections kept per key; more are closed when returned.
+const MAX_IDLE_PER_KEY: usize = 4;
+
+/// What makes two connections interchangeable.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PoolKey {
+    /// `Protocol::name()`
+    pub protocol: &'static str,
+    /// Host and port, or server and share
+    pub host: String,
+    pub user: String,
+}
+
+/// A connection waiting in the pool.
+struct IdleConnection {
+    backend: Box<dyn FluxBackend>,
+    since: Instant,
+}
+
+/// Idle backend connections by key.
+pub struct BackendPool {
+    max_idle: Duration,
+    idle: Mutex<HashMap<PoolKey, Vec<IdleConnection>>>,
+}
+
+/// The pool `create_backend` uses.
+pub fn global() -> &'static Arc<BackendPool> {
+    static POOL: OnceLock<Arc<BackendPool>> = OnceLock::new();
+    POOL.get_or_init(|| Arc::new(BackendPool::new(MAX_IDLE)))
+}
+
+impl BackendPool {
+    pub fn new(max_idle: Duration) -> Self {
+        Self {
+            max_idle,
+            idle: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Check out a connection for `key`, rooted at `root`: a healthy idle
+    /// one if there is any, otherwise a new one from `connect`.
+    pub fn checkout(
+        self: &Arc<Self>,
+        key: PoolKey,
+        root: &str,
+        connect: impl FnOnce() -> Result<Box<dyn FluxBackend>, FluxError>,
+    ) -> Result<PooledBackend, FluxError> {
+        let backend = match self.take_idle(&key) {
+            Some(backend) => {
+                tracing::debug!("Reusing {} connection to {}", key.protocol, key.host);
+                backend
+            }
+            None => connect()?,
+        };
+        Ok(PooledBackend {
+            backend: Some(backend),
+            root: PathBuf::from(root),
+            key,
+            pool: Arc::clone(self),
+        })
+    }
+
+    /// The most recently returned healthy connection for `key`. Expired and
+    /// dead ones are dropped on the way.
+    fn take_idle(&self, key: &PoolKey) -> Option<Box<dyn FluxBackend>> {
+        loop {
+            let conn = {
+                let mut idle = self.lock();
+                self.evict(&mut idle);
+                idle.get_mut(key)?.pop()?
+            };
+            // Checked without the lock: it is a round trip to the server
+            if conn.backend.is_alive() {
+                return Some(conn.backend);
+            }
+            tracing::debug!("Dropping dead {} connection to {}", key.protocol, key.host);
+        }
+    }
+
+    fn put_back(&self, key: PoolKey, backend: Box<dyn FluxBackend>) {
+        let mut idle = self.lock();
+        self.evict(&mut idle);
+        let conns = idle.entry(key).or_default();
+        if conns.len() < MAX_IDLE_PER_KEY {
+            conns.push(IdleConnection {
+                backend,
+                since: Instant::now(),
+            });
+        }
+    }
+
+    /// Close connections idle for longer than `max_idle`.
+    fn evict(&self, idle: &mut HashMap<PoolKey, Vec<IdleConnection>>) {
+        for conns in idle.values_mut() {
+            conns.retain(|conn| conn.since.elapsed() <= self.max_idle);
+        }
+        idle.retain(|_, conns| !conns.is_empty());
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<PoolKey, Vec<IdleConnection>>> {
+        // The map stays consistent even if a holder panicked
+        self.idle.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// A backend checked out of a `BackendPool`, returned to it on drop.
+pub struct PooledBackend {
+    /// Only `None` while being returned
+    backend: Option<Box<dyn FluxBackend>>,
+    /// URI path that relative paths are resolved against
+    root: PathBuf,
+    key: PoolKey,
+    pool: Arc<BackendPool>,
+}
+
+impl PooledBackend {
+    fn conn(&self) -> &dyn FluxBackend {
+        self.backend.as_deref().expect("backend is only taken on drop")
+    }
+
+    /// `path` relative to this checkout's root; absolute paths are kept.
+    fn resolve(&self, path: &Path) -> PathBuf {
+        if path.has_root() {
+            path.to_path_buf()
+        } else {
+            self.root.join(path)
+        }
+    }
+}
+
+impl Drop for PooledBackend {
+    fn drop(&mut self) {
+        if let Some(backend) = self.backend.take() {
+            self.pool.put_back(self.key.clone(), backend);
+        }
+    }
+}
+
+impl FluxBackend for PooledBackend {
+    fn stat(&self, path: &Path) -> Result<FileStat, FluxError> {
+        self.conn().stat(&self.resolve(path))
+    }
+
+    fn list_dir(&self, path: &Path) -> Result<Vec<FileEntry>, FluxError> {
+        self.conn().list_dir(&self.resolve(path))
+    }
+
+    fn open_read(&self, path: &Path) -> Result<Box<dyn std::io::Read + Send>, FluxError> {
+        self.conn().open_read(&self.resolve(path))
+    }
+
+    fn open_write(&self, path: &Path) -> Result<Box<dyn std::io::Write + Send>, FluxError> {
+        self.conn().open_write(&self.resolve(path))
+    }
+
+    fn create_dir_all(&self, path: &Path) -> Result<(), FluxError> {
+        self.conn().create_dir_all(&self.resolve(path))
+    }
+
+    fn features(&self) -> BackendFeatures {
+        self.conn().features()
+    }
+
+    fn remove(&self, path: &Path) -> Result<(), FluxError> {
+        self.conn().remove(&self.resolve(path))
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> Result<(), FluxError> {
+        self.conn().rename(&self.resolve(from), &self.resolve(to))
+    }
+
+    fn set_mtime(&self, path: &Path, mtime: SystemTime) -> Result<(), FluxError> {
+        self.conn().set_mtime(&self.resolve(path), mtime)
+    }
+
+    fn set_permissions(&self, path: &Path, mode: u32) -> Result<(), FluxError> {
+        self.conn().set_permissions(&self.resolve(path), mode)
+    }
+
+    fn is_alive(&self) -> bool {
+        self.conn().is_alive()
+    }
+
+    fn open_read_range(
+        &self,
+        path: &Path,
+        offset: u64,
+    ) -> Result<Box<dyn std::io::Read + Send>, FluxError> {
+        self.conn().open_read_range(&self.resolve(path), offset)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+    /// A connection that records the paths it is asked about.
+    struct FakeConnection {
+        alive: Arc<AtomicBool>,
+        seen: Arc<Mutex<Vec<PathBuf>>>,
+    }
+
+    impl FluxBackend for FakeConnection {
+        fn stat(&self, path: &Path) -> Result<FileStat, FluxError> {
+            self.seen.lock().unwrap().push(path.to_path_buf());
+            Ok(FileStat {
+                size: 0,
+                is_dir: true,
+                is_file: false,
+                modified: None,
+                permissions: None,
+            })
+        }
+
+        fn list_dir(&self, _path: &Path) -> Result<Vec<FileEntry>, FluxError> {
+            Ok(Vec::new())
+        }
+
+        fn open_read(&self, _path: &Path) -> Result<Box<dyn std::io::Read + Send>, FluxError> {
+            Ok(Box::new(std::io::empty()))
+        }
+
+        fn open_write(&self, _path: &Path) -> Result<Box<dyn std::io::Write + Send>, FluxError> {
+            Ok(Box::new(std::io::sink()))
+        }
+
+        fn create_dir_all(&self, _path: &Path) -> Result<(), FluxError> {
+            Ok(())
+        }
+
+        fn features(&self) -> BackendFeatures {
+            BackendFeatures {
+                supports_seek: false,
+                supports_parallel: false,
+                supports_permissions: false,
+                supports_remove: false,
+                supports_rename: false,
+                supports_set_mtime: false,
+            }
+        }
+
+        fn is_alive(&self) -> bool {
+            self.alive.load(Ordering::SeqCst)
+        }
+    }
+
+    struct Server {
+        connects: AtomicUsize,
+        alive: Arc<AtomicBool>,
+        seen: Arc<Mutex<Vec<PathBuf>>>,
+    }
+
+    impl Server {
+        fn new() -> Self {
+            Self {
+                connects: AtomicUsize::new(0),
+                alive: Arc::new(AtomicBool::new(true)),
+                seen: Arc::new(Mutex::new(Vec::new())),
+            }
+        }
+
+        fn checkout(&self, pool: &Arc<BackendPool>, user: &str, root: &str) -> PooledBackend {
+            let key = PoolKey {
+                protocol: "sftp",
+                host: "nas:22".into(),
+                user: user.into(),
+            };
+            pool.checkout(key, root, || {
+                self.connects.fetch_add(1, Ordering::SeqCst);
+                Ok(Box::new(FakeConnection {
+                    alive: Arc::clone(&self.alive),
+                    seen: Arc::clone(&self.seen),
+                }))
+            })
+            .unwrap()
+        }
+    }
+
+    #[test]
+    fn returned_connections_are_reused_per_key() {
+        let pool = Arc::new(BackendPool::new(MAX_IDLE));
+        let server = Server::new();
+        for _ in 0..3 {
+            drop(server.checkout(&pool, "alice", "/data"));
+        }
+        assert_eq!(server.connects.load(Ordering::SeqCst), 1);
+
+        // Another user gets their own connection, and so does a second
+        // checkout while the first is still out
+        drop(server.checkout(&pool, "bob", "/data"));
+        let first = server.checkout(&pool, "alice", "/data");
+        let second = server.checkout(&pool, "alice", "/data");
+        assert_eq!(server.connects.load(Ordering::SeqCst), 3);
+        drop((first, second));
+        drop(server.checkout(&pool, "alice", "/data"));
+        assert_eq!(server.connects.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn dead_and_expired_connections_are_replaced() {
+        let pool = Arc::new(BackendPool::new(MAX_IDLE));
+        let server = Server::new();
+        drop(server.checkout(&pool, "alice", ""));
+        server.alive.store(false, Ordering::SeqCst);
+        drop(server.checkout(&pool, "alice", ""));
+        assert_eq!(server.connects.load(Ordering::SeqCst), 2);
+
+        let pool = Arc::new(BackendPool::new(Duration::ZERO));
+        let server = Server::new();
+        drop(server.checkout(&pool, "alice", ""));
+        std::thread::sleep(Duration::from_millis(5));
+        drop(server.checkout(&pool, "alice", ""));
+        assert_eq!(server.connects.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn paths_resolve_against_each_checkouts_root() {
+        let pool = Arc::new(BackendPool::new(MAX_IDLE));
+        let server = Server::new();
+        let backend = server.checkout(&pool, "alice", "/data");
+        backend.stat(Path::new("a.txt")).unwrap();
+        backend.stat(Path::new("/etc/hosts")).unwrap();
+        drop(backend);
+        let backend = server.checkout(&pool, "alice", "");
+        backend.stat(Path::new("b.txt")).unwrap();
+
+        assert_eq!(server.connects.load(Ordering::SeqCst), 1);
+        let seen = server.seen.lock().unwrap();
+        assert_eq!(
+            *seen,
+            [
+                PathBuf::from("/data/a.txt"),
+                PathBuf::from("/etc/hosts"),
+                PathBuf::from("b.txt"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,463 @@
+//! rclone backend: files on any remote of an installed rclone.
+//!
+//! `rclone:remote:path` runs the `rclone` binary (or `[backends]
+//! rclone_binary`) against a remote configured in rclone.conf, or an
+//! on-the-fly one like `:s3,provider=AWS:bucket`. Credentials, retries of
+//! the provider's API and the provider itself are rclone's business; flux
+//! only drives the commands:
+//!
+//! - `lsjson --stat` / `lsjson` for `stat` and `list_dir`
+//! - `cat` (with `--offset` for resumed reads) streams a file
+//! - `rcat` uploads what is written, finishing on `flush`
+//! - `mkdir`, `deletefile`, `rmdir`, `moveto` and `touch` for the rest
+//!
+//! Exit codes 3 and 4 (directory or file not found) become
+//! `FluxError::SourceNotFound`; other failures carry rclone's last stderr
+//! line.
+
+use std::io::{self, Read, Write};
+use std::path::{Component, Path, PathBuf};
+use std::process::{Child, ChildStdin, ChildStdout, Command, ExitStatus, Stdio};
+use std::time::SystemTime;
+
+use serde::Deserialize;
+
+use crate::backend::{BackendFeatures, FileEntry, FileStat, FluxBackend};
+use crate::error::FluxError;
+
+/// rclone's exit codes for a missing directory and a missing file.
+const EXIT_DIR_NOT_FOUND: i32 = 3;
+const EXIT_FILE_NOT_FOUND: i32 = 4;
+
+/// An entry of `rclone lsjson` output.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ListEntry {
+    name: String,
+    /// -1 for directories on some remotes
+    size: i64,
+    mod_time: Option<String>,
+    is_dir: bool,
+}
+
+impl ListEntry {
+    fn stat(&self) -> FileStat {
+        FileStat {
+            size: self.size.max(0) as u64,
+            is_dir: self.is_dir,
+            is_file: !self.is_dir,
+            modified: self.mod_time.as_deref().and_then(parse_mod_time),
+            permissions: None,
+        }
+    }
+}
+
+/// Files on one rclone remote. Paths are relative to the remote path given
+/// in the `rclone:` location.
+pub struct RcloneBackend {
+    binary: String,
+    remote: String,
+    root: String,
+}
+
+impl RcloneBackend {
+    /// Backend for `remote` (a name from rclone.conf or an on-the-fly
+    /// `:backend` spec), rooted at `root` on it. Fails if `binary` (`rclone`
+    /// when `None`) cannot be run.
+    pub fn new(remote: &str, root: &str, binary: Option<&str>) -> Result<Self, FluxError> {
+        let backend = Self {
+            binary: binary.unwrap_or("rclone").to_string(),
+            remote: remote.to_string(),
+            root: match root.strip_suffix('/') {
+                Some(trimmed) if !trimmed.is_empty() => trimmed.to_string(),
+                _ => root.to_string(),
+            },
+        };
+        backend.run("version", Path::new(""), &["version"])?;
+        Ok(backend)
+    }
+
+    /// `remote:path` for `path` below the root. Absolute paths pass
+    /// through, as with SFTP.
+    fn target(&self, path: &Path) -> String {
+        let mut joined = if path.has_root() {
+            "/".to_string()
+        } else {
+            self.root.clone()
+        };
+        for component in path.components() {
+            if let Component::Normal(part) = component {
+                if !joined.is_empty() && !joined.ends_with('/') {
+                    joined.push('/');
+                }
+                joined.push_str(&part.to_string_lossy());
+            }
+        }
+        format!("{}:{}", self.remote, joined)
+    }
+
+    fn command(&self) -> Command {
+        let mut command = Command::new(&self.binary);
+        command.stdin(Stdio::null());
+        command
+    }
+
+    /// Run `rclone args...` and return its stdout. `path` names the file in
+    /// errors.
+    fn run(&self, operation: &str, path: &Path, args: &[&str]) -> Result<Vec<u8>, FluxError> {
+        let output = self
+            .command()
+            .args(args)
+            .output()
+            .map_err(|e| self.spawn_failed(e))?;
+        if output.status.success() {
+            return Ok(output.stdout);
+        }
+        Err(command_failed(operation, path, output.status, &output.stderr))
+    }
+
+    /// Start `rclone args...` with stdout piped.
+    fn spawn(&self, args: &[&str], stdin: Stdio) -> Result<Child, FluxError> {
+        self.command()
+            .args(args)
+            .stdin(stdin)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| self.spawn_failed(e))
+    }
+
+    fn spawn_failed(&self, error: io::Error) -> FluxError {
+        if error.kind() == io::ErrorKind::NotFound {
+            FluxError::ProtocolError(format!(
+                "'{}' not found. Install rclone (https://rclone.org/install/) or set \
+                 rclone_binary in the [backends] config table",
+                self.binary
+            ))
+        } else {
+            FluxError::ProtocolError(format!("Failed to run {}: {}", self.binary, error))
+        }
+    }
+
+    fn read_from(&self, path: &Path, offset: u64) -> Result<Box<dyn Read + Send>, FluxError> {
+        let target = self.target(path);
+        let offset_arg = offset.to_string();
+        let mut args = vec!["cat", target.as_str()];
+        if offset > 0 {
+            args.extend(["--offset", offset_arg.as_str()]);
+        }
+        let mut child = self.spawn(&args, Stdio::null())?;
+        let stdout = child.stdout.take().expect("stdout is piped");
+        Ok(Box::new(RcloneReader {
+            child,
+            stdout,
+            path: path.to_path_buf(),
+            done: false,
+        }))
+    }
+}
+
+impl FluxBackend for RcloneBackend {
+    fn stat(&self, path: &Path) -> Result<FileStat, FluxError> {
+        let target = self.target(path);
+        let output = self.run("stat", path, &["lsjson", "--stat", &target])?;
+        let entry: ListEntry = serde_json::from_slice(&output).map_err(|e| {
+            FluxError::ProtocolError(format!("Unexpected rclone lsjson output: {}", e))
+        })?;
+        Ok(entry.stat())
+    }
+
+    fn list_dir(&self, path: &Path) -> Result<Vec<FileEntry>, FluxError> {
+        let target = self.target(path);
+        let output = self.run("list", path, &["lsjson", &target])?;
+        let entries: Vec<ListEntry> = serde_json::from_slice(&output).map_err(|e| {
+            FluxError::ProtocolError(format!("Unexpected rclone lsjson output: {}", e))
+        })?;
+        Ok(entries
+            .into_iter()
+            .map(|entry| FileEntry {
+                path: path.join(&entry.name),
+                stat: entry.stat(),
+            })
+            .collect())
+    }
+
+    fn open_read(&self, path: &Path) -> Result<Box<dyn Read + Send>, FluxError> {
+        self.read_from(path, 0)
+    }
+
+    fn open_read_range(&self, path: &Path, offset: u64) -> Result<Box<dyn Read + Send>, FluxError> {
+        self.read_from(path, offset)
+    }
+
+    fn open_write(&self, path: &Path) -> Result<Box<dyn Write + Send>, FluxError> {
+        let target = self.target(path);
+        let mut child = self.spawn(&["rcat", &target], Stdio::piped())?;
+        let stdin = child.stdin.take();
+        Ok(Box::new(RcloneWriter {
+            child,
+            stdin,
+            path: path.to_path_buf(),
+            finished: false,
+        }))
+    }
+
+    fn create_dir_all(&self, path: &Path) -> Result<(), FluxError> {
+        // `mkdir` creates parents; remotes without directories accept it too
+        self.run("mkdir", path, &["mkdir", &self.target(path)])?;
+        Ok(())
+    }
+
+    fn features(&self) -> BackendFeatures {
+        BackendFeatures {
+            supports_seek: false,
+            supports_parallel: false,
+            supports_permissions: false,
+            supports_remove: true,
+            supports_rename: true,
+            supports_set_mtime: true,
+        }
+    }
+
+    fn remove(&self, path: &Path) -> Result<(), FluxError> {
+        let command = if self.stat(path)?.is_dir {
+            "rmdir"
+        } else {
+            "deletefile"
+        };
+        self.run("remove", path, &[command, &self.target(path)])?;
+        Ok(())
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> Result<(), FluxError> {
+        self.run("rename", from, &["moveto", &self.target(from), &self.target(to)])?;
+        Ok(())
+    }
+
+    fn set_mtime(&self, path: &Path, mtime: SystemTime) -> Result<(), FluxError> {
+        // UTC unless --localtime is given
+        let timestamp = chrono::DateTime::<chrono::Utc>::from(mtime)
+            .format("%Y-%m-%dT%H:%M:%S")
+            .to_string();
+        let target = self.target(path);
+        self.run(
+            "set_mtime",
+            path,
+            &["touch", "--no-create", "--timestamp", &timestamp, &target],
+        )?;
+        Ok(())
+    }
+}
+
+/// The error for a failed rclone command.
+fn command_failed(operation: &str, path: &Path, status: ExitStatus, stderr: &[u8]) -> FluxError {
+    match status.code() {
+        Some(EXIT_DIR_NOT_FOUND | EXIT_FILE_NOT_FOUND) => FluxError::SourceNotFound {
+            path: path.to_path_buf(),
+        },
+        _ => {
+            let stderr = String::from_utf8_lossy(stderr);
+            let reason = stderr
+                .lines()
+                .rev()
+                .find(|line| !line.trim().is_empty())
+                .unwrap_or("no error output")
+                .trim();
+            FluxError::ProtocolError(format!(
+                "rclone {} '{}' failed ({}): {}",
+                operation,
+                path.display(),
+                status,
+                reason
+            ))
+        }
+    }
+}
+
+/// `ModTime` of lsjson (RFC 3339 with nanoseconds).
+fn parse_mod_time(s: &str) -> Option<SystemTime> {
+    chrono::DateTime::parse_from_rfc3339(s).ok().map(SystemTime::from)
+}
+
+/// The output of `rclone cat`. Reaching the end checks how rclone exited,
+/// so a failed download is an error rather than a short file.
+struct RcloneReader {
+    child: Child,
+    stdout: ChildStdout,
+    path: PathBuf,
+    done: bool,
+}
+
+impl Read for RcloneReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.stdout.read(buf)?;
+        if n == 0 && !buf.is_empty() && !self.done {
+            self.done = true;
+            finish(&mut self.child, "read", &self.path)?;
+        }
+        Ok(n)
+    }
+}
+
+impl Drop for RcloneReader {
+    fn drop(&mut self) {
+        if !self.done {
+            let _ = self.child.kill();
+            let _ = self.child.wait();
+        }
+    }
+}
+
+/// The input of `rclone rcat`. `flush` closes it and waits for the upload,
+/// so write everything before flushing.
+struct RcloneWriter {
+    child: Child,
+    stdin: Option<ChildStdin>,
+    path: PathBuf,
+    finished: bool,
+}
+
+impl RcloneWriter {
+    fn finish(&mut self) -> io::Result<()> {
+        if self.finished {
+            return Ok(());
+        }
+        self.finished = true;
+        // Closing stdin ends the upload
+        drop(self.stdin.take());
+        finish(&mut self.child, "write", &self.path)
+    }
+}
+
+impl Write for RcloneWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self.stdin.as_mut() {
+            Some(stdin) => stdin.write(buf),
+            None => Err(io::Error::new(
+                io::ErrorKind::BrokenPipe,
+                format!("upload of {} already finished", self.path.display()),
+            )),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.finish()
+    }
+}
+
+impl Drop for RcloneWriter {
+    fn drop(&mut self) {
+        if let Err(e) = self.finish() {
+            tracing::error!("rclone upload failed during drop: {}", e);
+        }
+    }
+}
+
+/// Wait for `child` and turn a failure into an I/O error with its stderr.
+fn finish(child: &mut Child, operation: &str, path: &Path) -> io::Result<()> {
+    let mut stderr = Vec::new();
+    if let Some(mut pipe) = child.stderr.take() {
+        pipe.read_to_end(&mut stderr)?;
+    }
+    let status = child.wait()?;
+    if status.success() {
+        return Ok(());
+    }
+    let error = command_failed(operation, path, status, &stderr);
+    let kind = match error {
+        FluxError::SourceNotFound { .. } => io::ErrorKind::NotFound,
+        _ => io::ErrorKind::Other,
+    };
+    Err(io::Error::new(kind, error.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn backend(remote: &str, root: &str) -> RcloneBackend {
+        RcloneBackend {
+            binary: "rclone".to_string(),
+            remote: remote.to_string(),
+            root: root.to_string(),
+        }
+    }
+
+    #[test]
+    fn targets_join_the_root_and_path() {
+        let b = backend("gdrive", "Photos");
+        assert_eq!(b.target(Path::new("2024/a.jpg")), "gdrive:Photos/2024/a.jpg");
+        assert_eq!(b.target(Path::new("")), "gdrive:Photos");
+        let b = backend(":local", "");
+        assert_eq!(b.target(Path::new("/tmp/out")), ":local:/tmp/out");
+        assert_eq!(b.target(Path::new("/")), ":local:/");
+        assert_eq!(b.target(Path::new("./docs")), ":local:docs");
+    }
+
+    #[test]
+    fn lsjson_entries_become_stats() {
+        let json = r#"[
+            {"Path":"a.txt","Name":"a.txt","Size":6,"MimeType":"text/plain",
+             "ModTime":"2024-01-02T03:04:05.123456789Z","IsDir":false},
+            {"Path":"sub","Name":"sub","Size":-1,"MimeType":"inode/directory",
+             "ModTime":"2024-01-02T03:04:05Z","IsDir":true}
+        ]"#;
+        let entries: Vec<ListEntry> = serde_json::from_str(json).unwrap();
+        let file = entries[0].stat();
+        assert!(file.is_file && !file.is_dir);
+        assert_eq!(file.size, 6);
+        let secs = file.modified.unwrap().duration_since(SystemTime::UNIX_EPOCH).unwrap();
+        assert_eq!(secs.as_secs(), 1_704_164_645);
+        let dir = entries[1].stat();
+        assert!(dir.is_dir);
+        assert_eq!(dir.size, 0);
+    }
+
+    /// A stand-in `rclone` script answering like the real one.
+    #[cfg(unix)]
+    fn fake_rclone(dir: &Path, script: &str) -> String {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = dir.join("rclone");
+        std::fs::write(&path, format!("#!/bin/sh\n{}", script)).unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        path.display().to_string()
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn commands_stream_and_report_failures() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let uploaded = dir.path().join("uploaded");
+        let script = format!(
+            r#"case "$1" in
+  version) echo "rclone v1.66.0" ;;
+  cat) [ "$2" = "r:in/a.txt" ] && printf 'hello' && exit 0
+       echo "ERROR : file not found" >&2; exit 4 ;;
+  rcat) cat > '{}' ;;
+  *) echo "ERROR : unsupported" >&2; exit 1 ;;
+esac"#,
+            uploaded.display()
+        );
+        let binary = fake_rclone(dir.path(), &script);
+        let b = RcloneBackend::new("r", "in", Some(&binary)).unwrap();
+
+        let mut content = String::new();
+        b.open_read(Path::new("a.txt")).unwrap().read_to_string(&mut content).unwrap();
+        assert_eq!(content, "hello");
+        let mut missing = b.open_read(Path::new("b.txt")).unwrap();
+        let err = missing.read_to_end(&mut Vec::new()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+
+        let mut writer = b.open_write(Path::new("out.txt")).unwrap();
+        writer.write_all(b"uploaded data").unwrap();
+        writer.flush().unwrap();
+        assert_eq!(std::fs::read(&uploaded).unwrap(), b"uploaded data");
+
+        match b.create_dir_all(Path::new("sub")) {
+            Err(FluxError::ProtocolError(msg)) => assert!(msg.contains("unsupported"), "{}", msg),
+            other => panic!("Expected ProtocolError, got {:?}", other),
+        }
+        assert!(RcloneBackend::new("r", "", Some("/nonexistent/rclone")).is_err());
+    }
+}
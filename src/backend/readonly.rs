@@ -0,0 +1,225 @@
+//! `--read-only`: a guarantee that `cp` and `sync` never change their source.
+//!
+//! `protect` registers the source root until the returned `Protection` is
+//! dropped, and every write flux makes to a local path calls `check` first:
+//! `LocalBackend`'s writes, the copy functions (`copy`, `parallel`, `mmap`),
+//! atomic renames, hard links, and sync's deletions, backups and
+//! transactions. A path inside a protected root is refused with
+//! `FluxError::ReadOnlySource`, whatever the caller meant to do, so a bug
+//! that aims a deletion at the source fails instead of losing data. Symlinks
+//! are resolved, so a destination that links back into the source is caught
+//! as well.
+//!
+//! Remote sources have no local path to check; their backend is wrapped in a
+//! `ReadOnlyBackend`, which refuses every write.
+
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+use std::time::SystemTime;
+
+use crate::backend::{BackendFeatures, FileEntry, FileStat, FluxBackend};
+use crate::error::FluxError;
+
+/// Canonical roots that must not be changed.
+static PROTECTED: RwLock<Vec<PathBuf>> = RwLock::new(Vec::new());
+
+/// Refuse every write under `root`, which must exist, while the returned
+/// guard is alive.
+pub fn protect(root: &Path) -> Result<Protection, FluxError> {
+    let root = std::fs::canonicalize(root)?;
+    tracing::debug!("Read-only: {}", root.display());
+    let mut protected = PROTECTED.write().unwrap_or_else(|e| e.into_inner());
+    protected.push(root.clone());
+    Ok(Protection(root))
+}
+
+/// A protected root; dropping it lifts the protection.
+#[must_use = "the root is only protected while the guard is alive"]
+#[derive(Debug)]
+pub struct Protection(PathBuf);
+
+impl Drop for Protection {
+    fn drop(&mut self) {
+        let mut protected = PROTECTED.write().unwrap_or_else(|e| e.into_inner());
+        if let Some(i) = protected.iter().position(|root| *root == self.0) {
+            protected.remove(i);
+        }
+    }
+}
+
+/// Fail if `operation` on `path` would change a protected tree.
+pub fn check(path: &Path, operation: &'static str) -> Result<(), FluxError> {
+    let protected = PROTECTED.read().unwrap_or_else(|e| e.into_inner());
+    if protected.is_empty() {
+        return Ok(());
+    }
+    // The entry itself (a symlink is removed or renamed, not its target) and
+    // what it points to (a write through a symlink lands in the target)
+    let entry = resolve(path);
+    let target = std::fs::canonicalize(path).ok();
+    let inside = |root: &PathBuf| {
+        entry.starts_with(root) || target.as_ref().is_some_and(|t| t.starts_with(root))
+    };
+    if protected.iter().any(inside) {
+        tracing::warn!("Refused to {} {} (--read-only)", operation, path.display());
+        return Err(FluxError::ReadOnlySource {
+            path: path.to_path_buf(),
+            operation,
+        });
+    }
+    Ok(())
+}
+
+/// `path` with its parent canonicalized as far as it exists. The last
+/// component is kept as is.
+fn resolve(path: &Path) -> PathBuf {
+    let absolute = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+    let Some(name) = absolute.file_name() else {
+        return std::fs::canonicalize(&absolute).unwrap_or(absolute);
+    };
+    let mut missing = vec![name.to_os_string()];
+    let mut existing = absolute.parent();
+    while let Some(dir) = existing {
+        if let Ok(real) = std::fs::canonicalize(dir) {
+            return missing.iter().rev().fold(real, |path, part| path.join(part));
+        }
+        missing.extend(dir.file_name().map(|n| n.to_os_string()));
+        existing = dir.parent();
+    }
+    absolute
+}
+
+/// A source backend that can only be read.
+pub struct ReadOnlyBackend(Box<dyn FluxBackend>);
+
+impl ReadOnlyBackend {
+    pub fn new(inner: Box<dyn FluxBackend>) -> Self {
+        Self(inner)
+    }
+}
+
+fn refused(path: &Path, operation: &'static str) -> FluxError {
+    FluxError::ReadOnlySource {
+        path: path.to_path_buf(),
+        operation,
+    }
+}
+
+impl FluxBackend for ReadOnlyBackend {
+    fn stat(&self, path: &Path) -> Result<FileStat, FluxError> {
+        self.0.stat(path)
+    }
+
+    fn list_dir(&self, path: &Path) -> Result<Vec<FileEntry>, FluxError> {
+        self.0.list_dir(path)
+    }
+
+    fn open_read(&self, path: &Path) -> Result<Box<dyn Read + Send>, FluxError> {
+        self.0.open_read(path)
+    }
+
+    fn open_read_range(&self, path: &Path, offset: u64) -> Result<Box<dyn Read + Send>, FluxError> {
+        self.0.open_read_range(path, offset)
+    }
+
+    fn open_write(&self, path: &Path) -> Result<Box<dyn std::io::Write + Send>, FluxError> {
+        Err(refused(path, "write"))
+    }
+
+    fn create_dir_all(&self, path: &Path) -> Result<(), FluxError> {
+        Err(refused(path, "create"))
+    }
+
+    /// Reads as the wrapped backend does; no write is supported. Permission
+    /// bits are still reported, for the copy to restore them.
+    fn features(&self) -> BackendFeatures {
+        BackendFeatures {
+            supports_remove: false,
+            supports_rename: false,
+            supports_set_mtime: false,
+            ..self.0.features()
+        }
+    }
+
+    fn remove(&self, path: &Path) -> Result<(), FluxError> {
+        Err(refused(path, "delete"))
+    }
+
+    fn rename(&self, from: &Path, _to: &Path) -> Result<(), FluxError> {
+        Err(refused(from, "rename"))
+    }
+
+    fn set_mtime(&self, path: &Path, _mtime: SystemTime) -> Result<(), FluxError> {
+        Err(refused(path, "change the modification time of"))
+    }
+
+    fn set_permissions(&self, path: &Path, _mode: u32) -> Result<(), FluxError> {
+        Err(refused(path, "change the permissions of"))
+    }
+
+    fn is_alive(&self) -> bool {
+        self.0.is_alive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::local::LocalBackend;
+
+    #[test]
+    fn writes_inside_a_protected_root_are_refused() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("source");
+        let dest = dir.path().join("dest");
+        std::fs::create_dir_all(source.join("sub")).unwrap();
+        std::fs::create_dir_all(&dest).unwrap();
+        let protection = protect(&source).unwrap();
+
+        assert!(check(&dest.join("a.txt"), "write").is_ok());
+        assert!(check(&source.join("sub/a.txt"), "write").is_err());
+        // Not yet existing folders, and `..` detours, are resolved too
+        assert!(check(&source.join("new/deeper/a.txt"), "write").is_err());
+        assert!(check(&dest.join("../source/a.txt"), "delete").is_err());
+        let err = check(&source.join("sub"), "delete").unwrap_err();
+        assert!(matches!(err, FluxError::ReadOnlySource { operation: "delete", .. }));
+
+        drop(protection);
+        assert!(check(&source.join("sub/a.txt"), "write").is_ok());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn writes_through_a_symlink_into_the_source_are_refused() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("source");
+        let dest = dir.path().join("dest");
+        std::fs::create_dir_all(&source).unwrap();
+        std::fs::create_dir_all(&dest).unwrap();
+        std::fs::write(source.join("a.txt"), "a").unwrap();
+        std::os::unix::fs::symlink(source.join("a.txt"), dest.join("a.txt")).unwrap();
+        std::os::unix::fs::symlink(&source, dest.join("linked")).unwrap();
+        let _protection = protect(&source).unwrap();
+
+        assert!(check(&dest.join("a.txt"), "write").is_err());
+        assert!(check(&dest.join("linked/b.txt"), "write").is_err());
+    }
+
+    #[test]
+    fn read_only_backend_reads_but_never_writes() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("a.txt");
+        std::fs::write(&file, "data").unwrap();
+        let backend = ReadOnlyBackend::new(Box::new(LocalBackend::new()));
+
+        assert_eq!(backend.stat(&file).unwrap().size, 4);
+        assert!(!backend.features().supports_remove);
+        assert!(matches!(
+            backend.remove(&file),
+            Err(FluxError::ReadOnlySource { .. })
+        ));
+        assert!(backend.open_write(&file).is_err());
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "data");
+    }
+}
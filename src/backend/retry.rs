@@ -0,0 +1,490 @@
+//! Retries of network backend operations that fail on a connection error.
+//!
+//! `create_backend` wraps SFTP, SMB and WebDAV backends in a
+//! `RetryingBackend`. An operation that fails with a transient error
+//! (`is_transient`: connection refused, reset or aborted, timeouts) is tried
+//! again after an exponential backoff with jitter, up to `[backends] retries`
+//! times (`RetryPolicy`). Before a retry, a connection that fails
+//! `FluxBackend::is_alive` is replaced by a new one.
+//!
+//! Reads resume rather than restart: a stream from `open_read` that breaks
+//! off is reopened with `open_read_range` at the byte it stopped at, so a
+//! large download only fetches the rest. Writers are buffered by the
+//! backends and sent whole on flush, so only opening them is retried.
+//!
+//! Retries are counted per file (`record`, `take_counts`) and shown with the
+//! completion summary of the command (`TransferStats::retries`).
+
+use std::collections::BTreeMap;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, SystemTime};
+
+use crate::backend::{BackendFeatures, FileEntry, FileStat, FluxBackend};
+use crate::config::types::BackendsConfig;
+use crate::error::FluxError;
+use crate::transfer::cancel;
+
+/// Longest wait between two attempts.
+const MAX_DELAY: Duration = Duration::from_secs(30);
+
+/// Retries per file since the last `take_counts`.
+static COUNTS: Mutex<BTreeMap<PathBuf, u32>> = Mutex::new(BTreeMap::new());
+
+/// Count one retry of an operation on `path`.
+pub fn record(path: &Path) {
+    let mut counts = COUNTS.lock().unwrap_or_else(|e| e.into_inner());
+    *counts.entry(path.to_path_buf()).or_default() += 1;
+}
+
+/// The retries counted so far, by file, and start counting afresh.
+pub fn take_counts() -> Vec<(PathBuf, u32)> {
+    let mut counts = COUNTS.lock().unwrap_or_else(|e| e.into_inner());
+    std::mem::take(&mut *counts).into_iter().collect()
+}
+
+/// How often and how patiently to retry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    pub retries: u32,
+    /// Delay before the first retry; doubled for each further one
+    pub backoff: Duration,
+}
+
+impl RetryPolicy {
+    pub fn from_config(config: &BackendsConfig) -> Self {
+        Self {
+            retries: config.retries,
+            backoff: Duration::from_millis(config.retry_backoff_ms),
+        }
+    }
+
+    /// Wait before retry number `retry` (from 1): the backoff doubled per
+    /// earlier retry, capped at `MAX_DELAY`, then a random 50-100% of that
+    /// so clients that failed together do not retry together.
+    pub fn delay(&self, retry: u32) -> Duration {
+        let full = self
+            .backoff
+            .saturating_mul(1 << retry.saturating_sub(1).min(16))
+            .min(MAX_DELAY);
+        full.mul_f64(rand::random_range(0.5..=1.0))
+    }
+}
+
+/// Whether `e` may go away by trying again: the connection failed or broke,
+/// rather than the server refusing the operation.
+pub fn is_transient(e: &FluxError) -> bool {
+    match e {
+        #[cfg(any(
+            feature = "net",
+            feature = "backends-sftp",
+            feature = "backends-webdav",
+            all(windows, feature = "backends-smb")
+        ))]
+        FluxError::ConnectionFailed { .. } => true,
+        FluxError::Io { source } => matches!(
+            source.kind(),
+            io::ErrorKind::ConnectionRefused
+                | io::ErrorKind::ConnectionReset
+                | io::ErrorKind::ConnectionAborted
+                | io::ErrorKind::NotConnected
+                | io::ErrorKind::BrokenPipe
+                | io::ErrorKind::TimedOut
+        ),
+        _ => false,
+    }
+}
+
+/// Opens a new connection to replace a dead one.
+type Connect = Box<dyn Fn() -> Result<Box<dyn FluxBackend>, FluxError> + Send + Sync>;
+
+/// State shared by a `RetryingBackend` and the readers it opened.
+struct Shared {
+    conn: RwLock<Arc<dyn FluxBackend>>,
+    connect: Connect,
+    policy: RetryPolicy,
+}
+
+impl Shared {
+    fn backend(&self) -> Arc<dyn FluxBackend> {
+        Arc::clone(&self.conn.read().unwrap_or_else(|e| e.into_inner()))
+    }
+
+    /// Run `op` on the backend, retrying transient failures.
+    fn run<T>(
+        &self,
+        path: &Path,
+        op: impl Fn(&dyn FluxBackend) -> Result<T, FluxError>,
+    ) -> Result<T, FluxError> {
+        let mut retry = 0;
+        loop {
+            let backend = self.backend();
+            match op(backend.as_ref()) {
+                Err(e) if retry < self.policy.retries && is_transient(&e) => {
+                    retry += 1;
+                    self.before_retry(path, retry, &e, &backend)?;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Count and log retry number `retry` of an operation on `path` that
+    /// failed with `err`, wait out its backoff and, if `failed` lost its
+    /// connection, replace it.
+    fn before_retry(
+        &self,
+        path: &Path,
+        retry: u32,
+        err: &FluxError,
+        failed: &Arc<dyn FluxBackend>,
+    ) -> Result<(), FluxError> {
+        let delay = self.policy.delay(retry);
+        tracing::warn!(
+            "{}: {} (retry {}/{} in {}ms)",
+            path.display(),
+            err,
+            retry,
+            self.policy.retries,
+            delay.as_millis()
+        );
+        record(path);
+        std::thread::sleep(delay);
+        cancel::check()?;
+
+        if !failed.is_alive() {
+            match (self.connect)() {
+                Ok(fresh) => {
+                    let mut conn = self.conn.write().unwrap_or_else(|e| e.into_inner());
+                    // Another thread may have reconnected already
+                    if Arc::ptr_eq(&*conn, failed) {
+                        *conn = Arc::from(fresh);
+                    }
+                }
+                // The retry fails again and uses up another attempt
+                Err(e) => tracing::debug!("Reconnecting failed: {}", e),
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A network backend whose operations are retried (see the module docs).
+pub struct RetryingBackend(Arc<Shared>);
+
+impl RetryingBackend {
+    /// Wrap `backend`; `connect` opens a replacement when its connection
+    /// is lost.
+    pub fn new(
+        backend: Box<dyn FluxBackend>,
+        policy: RetryPolicy,
+        connect: impl Fn() -> Result<Box<dyn FluxBackend>, FluxError> + Send + Sync + 'static,
+    ) -> Self {
+        Self(Arc::new(Shared {
+            conn: RwLock::new(Arc::from(backend)),
+            connect: Box::new(connect),
+            policy,
+        }))
+    }
+}
+
+impl FluxBackend for RetryingBackend {
+    fn stat(&self, path: &Path) -> Result<FileStat, FluxError> {
+        self.0.run(path, |b| b.stat(path))
+    }
+
+    fn list_dir(&self, path: &Path) -> Result<Vec<FileEntry>, FluxError> {
+        self.0.run(path, |b| b.list_dir(path))
+    }
+
+    fn open_read(&self, path: &Path) -> Result<Box<dyn Read + Send>, FluxError> {
+        self.open_read_range(path, 0)
+    }
+
+    fn open_read_range(&self, path: &Path, offset: u64) -> Result<Box<dyn Read + Send>, FluxError> {
+        let reader = self.0.run(path, |b| b.open_read_range(path, offset))?;
+        Ok(Box::new(ResumingReader {
+            shared: Arc::clone(&self.0),
+            path: path.to_path_buf(),
+            reader,
+            pos: offset,
+            retries: 0,
+        }))
+    }
+
+    fn open_write(&self, path: &Path) -> Result<Box<dyn io::Write + Send>, FluxError> {
+        self.0.run(path, |b| b.open_write(path))
+    }
+
+    fn create_dir_all(&self, path: &Path) -> Result<(), FluxError> {
+        self.0.run(path, |b| b.create_dir_all(path))
+    }
+
+    fn features(&self) -> BackendFeatures {
+        self.0.backend().features()
+    }
+
+    fn remove(&self, path: &Path) -> Result<(), FluxError> {
+        self.0.run(path, |b| b.remove(path))
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> Result<(), FluxError> {
+        self.0.run(from, |b| b.rename(from, to))
+    }
+
+    fn set_mtime(&self, path: &Path, mtime: SystemTime) -> Result<(), FluxError> {
+        self.0.run(path, |b| b.set_mtime(path, mtime))
+    }
+
+    fn set_permissions(&self, path: &Path, mode: u32) -> Result<(), FluxError> {
+        self.0.run(path, |b| b.set_permissions(path, mode))
+    }
+
+    fn is_alive(&self) -> bool {
+        self.0.backend().is_alive()
+    }
+}
+
+/// A read stream that reopens itself where it broke off.
+struct ResumingReader {
+    shared: Arc<Shared>,
+    path: PathBuf,
+    reader: Box<dyn Read + Send>,
+    /// Offset of the next byte, where a reopened stream starts
+    pos: u64,
+    /// Retries of this stream so far
+    retries: u32,
+}
+
+impl ResumingReader {
+    /// Reopen the stream at `pos` after `err`, as long as retries are left.
+    fn resume(&mut self, mut err: FluxError) -> Result<(), FluxError> {
+        loop {
+            if self.retries >= self.shared.policy.retries || !is_transient(&err) {
+                return Err(err);
+            }
+            self.retries += 1;
+            let failed = self.shared.backend();
+            self.shared.before_retry(&self.path, self.retries, &err, &failed)?;
+            match self.shared.backend().open_read_range(&self.path, self.pos) {
+                Ok(reader) => {
+                    tracing::debug!("Resuming {} at byte {}", self.path.display(), self.pos);
+                    self.reader = reader;
+                    return Ok(());
+                }
+                Err(e) => err = e,
+            }
+        }
+    }
+}
+
+impl Read for ResumingReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            let err = match self.reader.read(buf) {
+                Ok(n) => {
+                    self.pos += n as u64;
+                    return Ok(n);
+                }
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => return Err(e),
+                Err(e) => FluxError::Io { source: e },
+            };
+            self.resume(err).map_err(|e| match e {
+                FluxError::Io { source } => source,
+                other => io::Error::other(other),
+            })?;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    const QUICK: RetryPolicy = RetryPolicy {
+        retries: 3,
+        backoff: Duration::from_millis(1),
+    };
+
+    fn broken() -> FluxError {
+        FluxError::Io {
+            source: io::Error::new(io::ErrorKind::ConnectionReset, "reset by peer"),
+        }
+    }
+
+    /// A backend serving `data` whose first `failures` calls fail, and
+    /// whose streams break off after `break_after` bytes.
+    struct Flaky {
+        data: Vec<u8>,
+        failures: AtomicUsize,
+        calls: AtomicUsize,
+        break_after: usize,
+        opened_at: Mutex<Vec<u64>>,
+    }
+
+    impl Flaky {
+        fn new(failures: usize, break_after: usize) -> Self {
+            Self {
+                data: (0..=255).cycle().take(1000).collect(),
+                failures: AtomicUsize::new(failures),
+                calls: AtomicUsize::new(0),
+                break_after,
+                opened_at: Mutex::new(Vec::new()),
+            }
+        }
+
+        fn fail_once(&self) -> Result<(), FluxError> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            let left = self.failures.load(Ordering::SeqCst);
+            if left > 0 {
+                self.failures.store(left - 1, Ordering::SeqCst);
+                return Err(broken());
+            }
+            Ok(())
+        }
+    }
+
+    /// Reads up to a limit, then fails like a dropped connection.
+    struct BreakingReader {
+        data: io::Cursor<Vec<u8>>,
+        left: usize,
+    }
+
+    impl Read for BreakingReader {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.left == 0 {
+                return Err(io::Error::new(io::ErrorKind::ConnectionAborted, "dropped"));
+            }
+            let want = buf.len().min(self.left);
+            let n = self.data.read(&mut buf[..want])?;
+            self.left -= n;
+            if n == 0 {
+                self.left = usize::MAX;
+            }
+            Ok(n)
+        }
+    }
+
+    impl FluxBackend for Arc<Flaky> {
+        fn stat(&self, _path: &Path) -> Result<FileStat, FluxError> {
+            self.fail_once()?;
+            Ok(FileStat {
+                size: self.data.len() as u64,
+                is_dir: false,
+                is_file: true,
+                modified: None,
+                permissions: None,
+            })
+        }
+
+        fn list_dir(&self, _path: &Path) -> Result<Vec<FileEntry>, FluxError> {
+            Err(FluxError::PermissionDenied {
+                path: PathBuf::from("secret"),
+            })
+        }
+
+        fn open_read(&self, path: &Path) -> Result<Box<dyn Read + Send>, FluxError> {
+            self.open_read_range(path, 0)
+        }
+
+        fn open_read_range(
+            &self,
+            _path: &Path,
+            offset: u64,
+        ) -> Result<Box<dyn Read + Send>, FluxError> {
+            self.fail_once()?;
+            self.opened_at.lock().unwrap().push(offset);
+            Ok(Box::new(BreakingReader {
+                data: io::Cursor::new(self.data[offset as usize..].to_vec()),
+                left: self.break_after,
+            }))
+        }
+
+        fn open_write(&self, _path: &Path) -> Result<Box<dyn io::Write + Send>, FluxError> {
+            Ok(Box::new(io::sink()))
+        }
+
+        fn create_dir_all(&self, _path: &Path) -> Result<(), FluxError> {
+            Ok(())
+        }
+
+        fn features(&self) -> BackendFeatures {
+            BackendFeatures {
+                supports_seek: false,
+                supports_parallel: false,
+                supports_permissions: false,
+                supports_remove: false,
+                supports_rename: false,
+                supports_set_mtime: false,
+            }
+        }
+    }
+
+    fn retrying(flaky: &Arc<Flaky>, policy: RetryPolicy) -> RetryingBackend {
+        let reconnect = Arc::clone(flaky);
+        RetryingBackend::new(Box::new(Arc::clone(flaky)), policy, move || {
+            Ok(Box::new(Arc::clone(&reconnect)))
+        })
+    }
+
+    #[test]
+    fn transient_failures_are_retried_until_the_budget_runs_out() {
+        let flaky = Arc::new(Flaky::new(2, usize::MAX));
+        let backend = retrying(&flaky, QUICK);
+        assert_eq!(backend.stat(Path::new("a")).unwrap().size, 1000);
+        assert_eq!(flaky.calls.load(Ordering::SeqCst), 3);
+
+        let flaky = Arc::new(Flaky::new(5, usize::MAX));
+        let backend = retrying(&flaky, QUICK);
+        assert!(is_transient(&backend.stat(Path::new("a")).unwrap_err()));
+        assert_eq!(flaky.calls.load(Ordering::SeqCst), 4);
+    }
+
+    #[test]
+    fn other_errors_fail_at_once() {
+        let flaky = Arc::new(Flaky::new(0, usize::MAX));
+        let backend = retrying(&flaky, QUICK);
+        assert!(matches!(
+            backend.list_dir(Path::new("a")),
+            Err(FluxError::PermissionDenied { .. })
+        ));
+    }
+
+    #[test]
+    fn broken_reads_resume_where_they_stopped() {
+        let flaky = Arc::new(Flaky::new(0, 300));
+        let backend = retrying(&flaky, QUICK);
+        let mut data = Vec::new();
+        backend
+            .open_read(Path::new("a"))
+            .unwrap()
+            .read_to_end(&mut data)
+            .unwrap();
+        assert_eq!(data, flaky.data);
+        assert_eq!(*flaky.opened_at.lock().unwrap(), [0, 300, 600, 900]);
+
+        // One break too many
+        let flaky = Arc::new(Flaky::new(0, 200));
+        let backend = retrying(&flaky, QUICK);
+        let mut reader = backend.open_read(Path::new("a")).unwrap();
+        let err = reader.read_to_end(&mut Vec::new()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::ConnectionAborted);
+    }
+
+    #[test]
+    fn delays_grow_and_stay_capped() {
+        let policy = RetryPolicy {
+            retries: 10,
+            backoff: Duration::from_millis(100),
+        };
+        for _ in 0..20 {
+            let first = policy.delay(1);
+            assert!(first >= Duration::from_millis(50) && first <= Duration::from_millis(100));
+            let third = policy.delay(3);
+            assert!(third >= Duration::from_millis(200) && third <= Duration::from_millis(400));
+            assert!(policy.delay(40) <= MAX_DELAY);
+        }
+    }
+}
@@ -12,13 +12,15 @@
 //! a `Mutex<SftpInner>`. All `FluxBackend` methods acquire the lock before
 //! calling into libssh2 and release it before returning.
 
-use std::io::{BufRead, Read, Write};
+use std::io::{BufRead, Read, Seek, SeekFrom, Write};
 use std::net::TcpStream;
 use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex, MutexGuard};
-use std::time::{Duration, UNIX_EPOCH};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
-use ssh2::{CheckResult, HashType, KnownHostFileKind, OpenFlags, OpenType, Session, Sftp};
+use ssh2::{
+    CheckResult, HashType, KnownHostFileKind, OpenFlags, OpenType, RenameFlags, Session, Sftp,
+};
 
 use crate::backend::{BackendFeatures, FileEntry, FileStat, FluxBackend};
 use crate::error::FluxError;
@@ -80,7 +82,11 @@ impl SftpBackend {
     /// 1. SSH agent (if running)
     /// 2. Key files: ~/.ssh/id_ed25519, ~/.ssh/id_rsa, ~/.ssh/id_ecdsa
     /// 3. Password (if provided as argument)
-    /// 4. Password prompt via rpassword
+    /// 4. Password stored with `flux creds add`
+    /// 5. Password prompt via rpassword
+    ///
+    /// Without a user in the URL, the user of a stored credential for the host
+    /// is used, then the current user.
     ///
     /// Returns an error if connection or authentication fails.
     pub fn connect(
@@ -131,7 +137,10 @@ impl SftpBackend {
 
         // Determine the effective username
         let effective_user = if user.is_empty() {
-            get_current_username()?
+            match crate::security::creds::stored_user("sftp", host) {
+                Some(stored) => stored,
+                None => get_current_username()?,
+            }
         } else {
             user.to_string()
         };
@@ -241,6 +250,10 @@ impl FluxBackend for SftpBackend {
     }
 
     fn open_read(&self, path: &Path) -> Result<Box<dyn Read + Send>, FluxError> {
+        self.open_read_range(path, 0)
+    }
+
+    fn open_read_range(&self, path: &Path, offset: u64) -> Result<Box<dyn Read + Send>, FluxError> {
         let resolved = self.resolve_path(path);
         let guard = self.lock()?;
 
@@ -257,6 +270,10 @@ impl FluxBackend for SftpBackend {
             .sftp
             .open_mode(&resolved, OpenFlags::READ, 0o644, OpenType::File)
             .map_err(sftp_err)?;
+        if offset > 0 {
+            file.seek(SeekFrom::Start(offset))
+                .map_err(|e| FluxError::Io { source: e })?;
+        }
 
         let mut buf = Vec::new();
         file.read_to_end(&mut buf)
@@ -319,10 +336,85 @@ impl FluxBackend for SftpBackend {
             supports_seek: false,
             supports_parallel: false,
             supports_permissions: true,
+            supports_remove: true,
+            supports_rename: true,
+            supports_set_mtime: true,
+        }
+    }
+
+    fn remove(&self, path: &Path) -> Result<(), FluxError> {
+        let resolved = self.resolve_path(path);
+        let guard = self.lock()?;
+        let stat = guard.sftp.lstat(&resolved).map_err(sftp_err)?;
+        if stat.is_dir() {
+            guard.sftp.rmdir(&resolved).map_err(sftp_err)
+        } else {
+            guard.sftp.unlink(&resolved).map_err(sftp_err)
+        }
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> Result<(), FluxError> {
+        let (from, to) = (self.resolve_path(from), self.resolve_path(to));
+        let guard = self.lock()?;
+        // OVERWRITE needs the posix-rename extension, which most servers
+        // (OpenSSH included) provide; ATOMIC and NATIVE let the server pick it
+        let flags = RenameFlags::OVERWRITE | RenameFlags::ATOMIC | RenameFlags::NATIVE;
+        guard
+            .sftp
+            .rename(&from, &to, Some(flags))
+            .map_err(sftp_err)
+    }
+
+    fn set_mtime(&self, path: &Path, mtime: SystemTime) -> Result<(), FluxError> {
+        let resolved = self.resolve_path(path);
+        let secs = mtime
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        let guard = self.lock()?;
+        // SETSTAT sets access and modification time together; keep atime
+        let atime = guard.sftp.stat(&resolved).map_err(sftp_err)?.atime;
+        guard
+            .sftp
+            .setstat(&resolved, times_only(atime.unwrap_or(secs), secs))
+            .map_err(sftp_err)
+    }
+
+    fn set_permissions(&self, path: &Path, mode: u32) -> Result<(), FluxError> {
+        let resolved = self.resolve_path(path);
+        let guard = self.lock()?;
+        let stat = ssh2::FileStat {
+            size: None,
+            uid: None,
+            gid: None,
+            perm: Some(mode),
+            atime: None,
+            mtime: None,
+        };
+        guard.sftp.setstat(&resolved, stat).map_err(sftp_err)
+    }
+
+    fn is_alive(&self) -> bool {
+        // A REALPATH round trip; fails once the server has dropped us
+        match self.lock() {
+            Ok(guard) => guard.sftp.realpath(Path::new(".")).is_ok(),
+            Err(_) => false,
         }
     }
 }
 
+/// A SETSTAT request that only changes access and modification times.
+fn times_only(atime: u64, mtime: u64) -> ssh2::FileStat {
+    ssh2::FileStat {
+        size: None,
+        uid: None,
+        gid: None,
+        perm: None,
+        atime: Some(atime),
+        mtime: Some(mtime),
+    }
+}
+
 /// A `Write` implementation that buffers bytes in memory and flushes them
 /// to the remote SFTP path in a single operation.
 ///
@@ -673,7 +765,8 @@ fn format_sha256_fingerprint(session: &Session) -> String {
 
 /// Authenticate the SSH session using a cascade of methods.
 ///
-/// Tries in order: SSH agent, key files, provided password, password prompt.
+/// Tries in order: SSH agent, key files, provided password, stored password,
+/// password prompt.
 fn authenticate(
     session: &Session,
     user: &str,
@@ -720,7 +813,16 @@ fn authenticate(
         }
     }
 
-    // 4. Try interactive password prompt
+    // 4. Try a password stored with `flux creds add`
+    if let Some((_, stored)) = crate::security::creds::lookup("sftp", host, Some(user)) {
+        if session.userauth_password(user, &stored).is_ok() && session.authenticated() {
+            tracing::debug!("SFTP: Authenticated via stored password for {}@{}", user, host);
+            return Ok(());
+        }
+        tracing::warn!("Stored password for {}@{} was rejected", user, host);
+    }
+
+    // 5. Try interactive password prompt
     match rpassword::prompt_password(format!("Password for {}@{}: ", user, host)) {
         Ok(prompted_pwd) => {
             if session.userauth_password(user, &prompted_pwd).is_ok() && session.authenticated() {
@@ -764,7 +866,15 @@ fn get_ssh_key_paths() -> Vec<PathBuf> {
 ///
 /// ssh2::Error implements Into<std::io::Error>, so we convert through that.
 fn sftp_err(e: ssh2::Error) -> FluxError {
+    // Session-level failures (negative codes) mean the connection broke;
+    // mark them as such so `backend::retry` tries again on a new one
+    let lost = matches!(e.code(), ssh2::ErrorCode::Session(_));
     let io_err: std::io::Error = e.into();
+    let io_err = if lost && io_err.kind() == std::io::ErrorKind::Other {
+        std::io::Error::new(std::io::ErrorKind::ConnectionAborted, io_err)
+    } else {
+        io_err
+    };
     FluxError::Io { source: io_err }
 }
 
@@ -809,12 +919,23 @@ mod tests {
             supports_seek: false,
             supports_parallel: false,
             supports_permissions: true,
+            supports_remove: true,
+            supports_rename: true,
+            supports_set_mtime: true,
         };
         assert!(!features.supports_seek);
         assert!(!features.supports_parallel);
         assert!(features.supports_permissions);
     }
 
+    #[test]
+    fn times_only_leaves_other_attributes_alone() {
+        let stat = times_only(10, 20);
+        assert_eq!((stat.atime, stat.mtime), (Some(10), Some(20)));
+        assert!(stat.size.is_none() && stat.perm.is_none());
+        assert!(stat.uid.is_none() && stat.gid.is_none());
+    }
+
     #[test]
     fn sftp_err_converts_to_flux_io_error() {
         // Create an ssh2 error and verify it converts to FluxError::Io
@@ -39,7 +39,8 @@ impl SmbBackend {
     ///
     /// Constructs the UNC path `\\server\share` and relies on the Windows OS
     /// to handle authentication (using the current user's session or cached
-    /// credentials).
+    /// credentials). A password stored for the server with `flux creds add` is
+    /// handed to the OS first, as `net use` would.
     ///
     /// # Arguments
     /// * `server` - The SMB server hostname or IP address.
@@ -57,6 +58,9 @@ impl SmbBackend {
         }
 
         let base_unc = PathBuf::from(format!("\\\\{}\\{}", server, share));
+        if let Some((user, password)) = crate::security::creds::lookup("smb", server, None) {
+            add_connection(&base_unc, server, &user, &password)?;
+        }
         Ok(SmbBackend { base_unc })
     }
 
@@ -73,6 +77,39 @@ impl SmbBackend {
     }
 }
 
+/// Register a stored login for the share with the Windows SMB client
+/// (`WNetAddConnection2W`). A session the server already has under other
+/// credentials (error 1219) is kept as it is.
+#[cfg(windows)]
+fn add_connection(unc: &Path, server: &str, user: &str, password: &str) -> Result<(), FluxError> {
+    use std::ffi::OsStr;
+    use std::os::windows::ffi::OsStrExt;
+    use windows_sys::Win32::Foundation::{ERROR_SESSION_CREDENTIAL_CONFLICT, NO_ERROR};
+    use windows_sys::Win32::NetworkManagement::WNet::{
+        WNetAddConnection2W, NETRESOURCEW, RESOURCETYPE_DISK,
+    };
+
+    let wide = |s: &OsStr| s.encode_wide().chain(Some(0)).collect::<Vec<u16>>();
+    let mut remote = wide(unc.as_os_str());
+    let user = wide(OsStr::new(user));
+    let password = zeroize::Zeroizing::new(wide(OsStr::new(password)));
+
+    // SAFETY: NETRESOURCEW is plain data; all-zero is "no value" for every field.
+    let mut resource: NETRESOURCEW = unsafe { std::mem::zeroed() };
+    resource.dwType = RESOURCETYPE_DISK;
+    resource.lpRemoteName = remote.as_mut_ptr();
+    // SAFETY: every string is NUL-terminated and outlives the call.
+    let status = unsafe { WNetAddConnection2W(&resource, password.as_ptr(), user.as_ptr(), 0) };
+    match status {
+        NO_ERROR | ERROR_SESSION_CREDENTIAL_CONFLICT => Ok(()),
+        code => Err(FluxError::ConnectionFailed {
+            protocol: "smb".to_string(),
+            host: server.to_string(),
+            reason: format!("Windows rejected the stored credentials (error {})", code),
+        }),
+    }
+}
+
 /// Buffer size for BufReader/BufWriter: 256KB (matching LocalBackend).
 #[cfg(windows)]
 const BUF_SIZE: usize = 256 * 1024;
@@ -112,6 +149,21 @@ impl FluxBackend for SmbBackend {
         Ok(Box::new(std::io::BufReader::with_capacity(BUF_SIZE, file)))
     }
 
+    fn open_read_range(
+        &self,
+        path: &Path,
+        offset: u64,
+    ) -> Result<Box<dyn std::io::Read + Send>, FluxError> {
+        use std::io::{Seek, SeekFrom};
+
+        let full_path = self.resolve(path);
+        let mut file =
+            std::fs::File::open(&full_path).map_err(|e| map_smb_io_error(e, &full_path))?;
+        file.seek(SeekFrom::Start(offset))
+            .map_err(|e| map_smb_io_error(e, &full_path))?;
+        Ok(Box::new(std::io::BufReader::with_capacity(BUF_SIZE, file)))
+    }
+
     fn open_write(&self, path: &Path) -> Result<Box<dyn std::io::Write + Send>, FluxError> {
         let full_path = self.resolve(path);
         let file =
@@ -134,8 +186,38 @@ impl FluxBackend for SmbBackend {
             supports_parallel: false,
             // Windows does not expose Unix-style permission bits
             supports_permissions: false,
+            supports_remove: true,
+            supports_rename: true,
+            supports_set_mtime: true,
         }
     }
+
+    fn remove(&self, path: &Path) -> Result<(), FluxError> {
+        let full_path = self.resolve(path);
+        let meta =
+            std::fs::symlink_metadata(&full_path).map_err(|e| map_smb_io_error(e, &full_path))?;
+        let result = if meta.is_dir() {
+            std::fs::remove_dir(&full_path)
+        } else {
+            std::fs::remove_file(&full_path)
+        };
+        result.map_err(|e| map_smb_io_error(e, &full_path))
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> Result<(), FluxError> {
+        let (from, to) = (self.resolve(from), self.resolve(to));
+        std::fs::rename(&from, &to).map_err(|e| map_smb_io_error(e, &from))
+    }
+
+    fn set_mtime(&self, path: &Path, mtime: std::time::SystemTime) -> Result<(), FluxError> {
+        let full_path = self.resolve(path);
+        let file = std::fs::OpenOptions::new()
+            .write(true)
+            .open(&full_path)
+            .map_err(|e| map_smb_io_error(e, &full_path))?;
+        file.set_modified(mtime)
+            .map_err(|e| map_smb_io_error(e, &full_path))
+    }
 }
 
 /// Convert std::fs::Metadata to FileStat (Windows SMB variant).
@@ -219,6 +301,9 @@ impl FluxBackend for SmbBackend {
             supports_seek: false,
             supports_parallel: false,
             supports_permissions: false,
+            supports_remove: false,
+            supports_rename: false,
+            supports_set_mtime: false,
         }
     }
 }
@@ -297,6 +382,9 @@ mod tests {
             assert!(!features.supports_seek);
             assert!(!features.supports_parallel);
             assert!(!features.supports_permissions);
+            assert!(features.supports_remove);
+            assert!(features.supports_rename);
+            assert!(features.supports_set_mtime);
         }
 
         #[test]
@@ -0,0 +1,369 @@
+//! TLS settings of HTTP-based backends (WebDAV).
+//!
+//! By default the HTTP client verifies servers against the system's CA
+//! certificates. The `[webdav]` config table (`WebDavConfig`) can add a CA
+//! bundle for servers with a private CA, a client certificate for servers
+//! that require mutual TLS, and SHA-256 fingerprints the server's
+//! certificate must match. With any of these set, `client_config` builds a
+//! rustls configuration for the client instead: system roots plus
+//! `ca_bundle`, the client identity, and a `PinnedVerifier` that checks
+//! pins after the normal CA and host name checks, so a pinned connection
+//! is never weaker than an unpinned one.
+//!
+//! `certificate_problem` tells rejected certificates apart (untrusted CA,
+//! wrong host name, expired, pin mismatch) for `FluxError::Certificate`,
+//! whichever TLS stack reported them.
+
+use std::path::Path;
+use std::sync::Arc;
+
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::client::WebPkiServerVerifier;
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName, UnixTime};
+use rustls::{ClientConfig, DigitallySignedStruct, RootCertStore, SignatureScheme};
+use sha2::{Digest, Sha256};
+
+use crate::config::types::WebDavConfig;
+use crate::error::{CertificateProblem, FluxError};
+
+/// Start of the handshake error `PinnedVerifier` reports, found again by
+/// `certificate_problem`.
+const PIN_MISMATCH: &str = "pinned certificate mismatch, server presented sha256 ";
+
+/// A rustls configuration for `config`, or `None` when it sets nothing and
+/// the client's default TLS applies.
+pub fn client_config(config: &WebDavConfig) -> Result<Option<ClientConfig>, FluxError> {
+    if *config == WebDavConfig::default() {
+        return Ok(None);
+    }
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+
+    let mut roots = RootCertStore::empty();
+    let native = rustls_native_certs::load_native_certs();
+    for error in &native.errors {
+        tracing::debug!("Skipping system CA certificates: {}", error);
+    }
+    let (_, unparsable) = roots.add_parsable_certificates(native.certs);
+    if unparsable > 0 {
+        tracing::debug!("Skipped {} unparsable system CA certificates", unparsable);
+    }
+    if let Some(bundle) = &config.ca_bundle {
+        for cert in read_certs(Path::new(bundle))? {
+            roots
+                .add(cert)
+                .map_err(|e| invalid("ca_bundle", bundle, &e.to_string()))?;
+        }
+    }
+    let webpki = WebPkiServerVerifier::builder_with_provider(Arc::new(roots), provider.clone())
+        .build()
+        .map_err(|e| FluxError::Config(format!("webdav: no usable CA certificates: {}", e)))?;
+
+    let builder = ClientConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()
+        .map_err(|e| FluxError::Config(format!("webdav: TLS setup failed: {}", e)))?;
+    let builder = match &config.pinned_sha256 {
+        Some(pins) => builder
+            .dangerous()
+            .with_custom_certificate_verifier(Arc::new(PinnedVerifier {
+                webpki,
+                pins: parse_pins(pins)?,
+            })),
+        None => builder.with_webpki_verifier(webpki),
+    };
+
+    let tls = match (&config.client_cert, &config.client_key) {
+        (Some(cert), Some(key)) => {
+            let certs = read_certs(Path::new(cert))?;
+            let key = PrivateKeyDer::from_pem_file(key)
+                .map_err(|e| invalid("client_key", key, &e.to_string()))?;
+            builder
+                .with_client_auth_cert(certs, key)
+                .map_err(|e| invalid("client_cert", cert, &e.to_string()))?
+        }
+        (None, None) => builder.with_no_client_auth(),
+        _ => {
+            return Err(FluxError::Config(
+                "webdav: client_cert and client_key must be set together".into(),
+            ))
+        }
+    };
+    Ok(Some(tls))
+}
+
+/// The certificates of a PEM file.
+fn read_certs(path: &Path) -> Result<Vec<CertificateDer<'static>>, FluxError> {
+    let shown = path.display().to_string();
+    let certs = CertificateDer::pem_file_iter(path)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .map_err(|e| invalid("certificate file", &shown, &e.to_string()))?;
+    if certs.is_empty() {
+        return Err(invalid("certificate file", &shown, "no PEM certificates in it"));
+    }
+    Ok(certs)
+}
+
+fn invalid(what: &str, path: &str, reason: &str) -> FluxError {
+    FluxError::Config(format!("webdav: unusable {} {}: {}", what, path, reason))
+}
+
+/// SHA-256 fingerprints, comma separated.
+fn parse_pins(pins: &str) -> Result<Vec<[u8; 32]>, FluxError> {
+    let parsed = pins
+        .split(',')
+        .map(str::trim)
+        .filter(|pin| !pin.is_empty())
+        .map(|pin| {
+            parse_fingerprint(pin).ok_or_else(|| {
+                FluxError::Config(format!(
+                    "webdav: pinned_sha256 '{}' is not a SHA-256 fingerprint (64 hex digits)",
+                    pin
+                ))
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    if parsed.is_empty() {
+        return Err(FluxError::Config("webdav: pinned_sha256 is empty".into()));
+    }
+    Ok(parsed)
+}
+
+/// 64 hex digits, with or without `:` between the bytes (as `openssl x509
+/// -fingerprint` prints them) and an optional `sha256:` prefix.
+fn parse_fingerprint(pin: &str) -> Option<[u8; 32]> {
+    let digits: Vec<char> = pin
+        .trim_start_matches("sha256:")
+        .chars()
+        .filter(|c| *c != ':')
+        .collect();
+    if digits.len() != 64 || !digits.iter().all(char::is_ascii_hexdigit) {
+        return None;
+    }
+    let mut fingerprint = [0u8; 32];
+    for (byte, pair) in fingerprint.iter_mut().zip(digits.chunks(2)) {
+        let hex: String = pair.iter().collect();
+        *byte = u8::from_str_radix(&hex, 16).ok()?;
+    }
+    Some(fingerprint)
+}
+
+/// Uppercase, colon-separated form of a fingerprint.
+fn format_fingerprint(fingerprint: &[u8]) -> String {
+    fingerprint
+        .iter()
+        .map(|byte| format!("{:02X}", byte))
+        .collect::<Vec<_>>()
+        .join(":")
+}
+
+/// Verifies the server like rustls does, then requires its certificate to
+/// be one of the pinned ones.
+#[derive(Debug)]
+struct PinnedVerifier {
+    webpki: Arc<WebPkiServerVerifier>,
+    pins: Vec<[u8; 32]>,
+}
+
+impl ServerCertVerifier for PinnedVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        self.webpki
+            .verify_server_cert(end_entity, intermediates, server_name, ocsp_response, now)?;
+        let presented: [u8; 32] = Sha256::digest(end_entity.as_ref()).into();
+        if self.pins.contains(&presented) {
+            Ok(ServerCertVerified::assertion())
+        } else {
+            Err(rustls::Error::General(format!(
+                "{}{}",
+                PIN_MISMATCH,
+                format_fingerprint(&presented)
+            )))
+        }
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.webpki.verify_tls12_signature(message, cert, dss)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.webpki.verify_tls13_signature(message, cert, dss)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.webpki.supported_verify_schemes()
+    }
+}
+
+/// Why the server's certificate was rejected, if that is why `e` failed.
+pub fn certificate_problem(e: &reqwest::Error) -> Option<CertificateProblem> {
+    let mut chain = String::new();
+    let mut source: Option<&dyn std::error::Error> = Some(e);
+    while let Some(err) = source {
+        chain.push_str(&err.to_string());
+        chain.push('\n');
+        source = err.source();
+    }
+    classify(&chain)
+}
+
+/// Recognise the certificate errors of rustls, OpenSSL, SChannel and
+/// Security.framework in an error message chain.
+fn classify(chain: &str) -> Option<CertificateProblem> {
+    if let Some(at) = chain.find(PIN_MISMATCH) {
+        let presented = chain[at + PIN_MISMATCH.len()..]
+            .split(|c: char| c != ':' && !c.is_ascii_hexdigit())
+            .next()
+            .unwrap_or_default();
+        return Some(CertificateProblem::PinMismatch {
+            presented: presented.to_string(),
+        });
+    }
+    let lower = chain.to_ascii_lowercase();
+    let has = |needles: &[&str]| needles.iter().any(|needle| lower.contains(needle));
+    if has(&[
+        "notvalidforname",
+        "not valid for name",
+        "hostname mismatch",
+        "principal name is incorrect",
+    ]) {
+        Some(CertificateProblem::HostnameMismatch)
+    } else if has(&[
+        "expired",
+        "notvalidyet",
+        "not yet valid",
+        "not within its validity period",
+    ]) {
+        Some(CertificateProblem::Expired)
+    } else if has(&[
+        "unknownissuer",
+        "unknown issuer",
+        "unable to get local issuer",
+        "self-signed certificate",
+        "self signed certificate",
+        "issued by an authority that is not trusted",
+        "certificate was not trusted",
+    ]) {
+        Some(CertificateProblem::UntrustedIssuer)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PIN: &str = "AB:CD:EF:01:23:45:67:89:AB:CD:EF:01:23:45:67:89:\
+                       AB:CD:EF:01:23:45:67:89:AB:CD:EF:01:23:45:67:89";
+
+    #[test]
+    fn pins_parse_with_and_without_colons() {
+        let plain = PIN.replace(':', "").to_lowercase();
+        let pins = parse_pins(&format!("{}, sha256:{}", PIN, plain)).unwrap();
+        assert_eq!(pins.len(), 2);
+        assert_eq!(pins[0], pins[1]);
+        assert_eq!(format_fingerprint(&pins[0]), PIN);
+
+        let bad = [
+            String::new(),
+            "AB:CD".to_string(),
+            PIN.replace("AB", "ZZ"),
+            format!("{}:00", PIN),
+        ];
+        for pin in &bad {
+            assert!(matches!(parse_pins(pin), Err(FluxError::Config(_))), "{}", pin);
+        }
+    }
+
+    #[test]
+    fn certificate_errors_are_told_apart() {
+        let cases = [
+            (
+                "invalid peer certificate: UnknownIssuer",
+                CertificateProblem::UntrustedIssuer,
+            ),
+            (
+                "error:0A000086:SSL routines:tls_post_process_server_certificate:certificate \
+                 verify failed:../ssl/statem/statem_clnt.c:1889: (unable to get local issuer \
+                 certificate)",
+                CertificateProblem::UntrustedIssuer,
+            ),
+            (
+                "invalid peer certificate: NotValidForName",
+                CertificateProblem::HostnameMismatch,
+            ),
+            (
+                "certificate verify failed: (Hostname mismatch)",
+                CertificateProblem::HostnameMismatch,
+            ),
+            (
+                "invalid peer certificate: Expired",
+                CertificateProblem::Expired,
+            ),
+        ];
+        for (message, expected) in cases {
+            assert_eq!(classify(message), Some(expected), "{}", message);
+        }
+        assert_eq!(
+            classify(&format!("error sending request\nunexpected error: {}{}", PIN_MISMATCH, PIN)),
+            Some(CertificateProblem::PinMismatch {
+                presented: PIN.to_string()
+            })
+        );
+        assert_eq!(classify("connection refused"), None);
+    }
+
+    #[test]
+    fn default_settings_keep_the_default_client() {
+        assert!(client_config(&WebDavConfig::default()).unwrap().is_none());
+    }
+
+    #[test]
+    fn bad_settings_are_config_errors() {
+        let dir = tempfile::tempdir().unwrap();
+        let empty = dir.path().join("empty.pem");
+        std::fs::write(&empty, "").unwrap();
+        let missing = dir.path().join("missing.pem").display().to_string();
+
+        for config in [
+            WebDavConfig {
+                ca_bundle: Some(missing.clone()),
+                ..Default::default()
+            },
+            WebDavConfig {
+                ca_bundle: Some(empty.display().to_string()),
+                ..Default::default()
+            },
+            WebDavConfig {
+                client_cert: Some(missing),
+                ..Default::default()
+            },
+            WebDavConfig {
+                pinned_sha256: Some("not-a-fingerprint".into()),
+                ..Default::default()
+            },
+        ] {
+            assert!(
+                matches!(client_config(&config), Err(FluxError::Config(_))),
+                "{:?}",
+                config
+            );
+        }
+    }
+}
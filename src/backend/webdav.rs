@@ -1,35 +1,65 @@
 //! WebDAV backend using reqwest::blocking with raw HTTP methods.
 //!
-//! WebDAV is HTTP-based: GET=read, PUT=write, PROPFIND=stat/list, MKCOL=mkdir.
+//! WebDAV is HTTP-based: GET=read, PUT=write, PROPFIND=stat/list, MKCOL=mkdir,
+//! DELETE=remove, MOVE=rename.
 //! Uses reqwest's blocking client directly -- no async runtime needed.
 
-use std::io::{self, Cursor, Read, Write};
+use std::io::{self, Read, Write};
 use std::path::Path;
 use std::sync::Arc;
 
 use reqwest::blocking::Client;
-use reqwest::header::{HeaderMap, HeaderValue, CONTENT_TYPE};
+use reqwest::header::{HeaderMap, HeaderValue, CONTENT_TYPE, RANGE};
 use reqwest::StatusCode;
 
 use crate::backend::{BackendFeatures, FileEntry, FileStat, FluxBackend};
+use crate::config::types::WebDavConfig;
 use crate::error::FluxError;
 use crate::protocol::Auth;
 
 /// WebDAV backend implementing FluxBackend over HTTP/HTTPS.
 ///
 /// Uses reqwest::blocking::Client for synchronous HTTP requests.
-/// WebDAV methods used: GET (read), PUT (write), PROPFIND (stat/list), MKCOL (mkdir).
+/// WebDAV methods used: GET (read), PUT (write), PROPFIND (stat/list), MKCOL (mkdir),
+/// DELETE (remove), MOVE (rename).
 pub struct WebDavBackend {
     client: Arc<Client>,
     base_url: String,
     auth: Option<Auth>,
 }
 
+/// Fill in a password stored with `flux creds add` when the URL carries none.
+///
+/// A URL user without a password (`https://alice@host/`) looks up that user;
+/// a URL without userinfo takes the first credential stored for the host.
+pub fn stored_auth(url: &str, auth: Option<Auth>) -> Option<Auth> {
+    let user = match &auth {
+        None => None,
+        Some(Auth::Password { user, password }) if password.is_empty() => Some(user.clone()),
+        Some(_) => return auth,
+    };
+    let host = match url::Url::parse(url).ok().and_then(|u| u.host_str().map(str::to_string)) {
+        Some(host) => host,
+        None => return auth,
+    };
+    match crate::security::creds::lookup("webdav", &host, user.as_deref()) {
+        Some((user, password)) => Some(Auth::Password {
+            user,
+            password: (*password).clone(),
+        }),
+        None => auth,
+    }
+}
+
 impl WebDavBackend {
     /// Create a new WebDAV backend for the given base URL.
     ///
     /// The URL should be the WebDAV server root (e.g. "https://server/webdav/").
     /// Optional auth credentials are used for Basic authentication.
+    /// Requests go through `proxy` (the `proxy` config key) if given, else
+    /// through `HTTP_PROXY`/`HTTPS_PROXY` from the environment; either way
+    /// hosts in `NO_PROXY` are reached directly. `tls` (the `[webdav]` config
+    /// table) adds CAs, a client certificate or pins; see `backend::tls`.
     ///
     /// # Security
     ///
@@ -37,9 +67,23 @@ impl WebDavBackend {
     /// credentials (sent as HTTP Basic auth) and all transferred file data are
     /// transmitted in cleartext.  A prominent warning is printed to stderr and
     /// recorded via `tracing::warn!` to alert the operator at connection time.
-    pub fn new(url: &str, auth: Option<Auth>) -> Result<Self, FluxError> {
-        let client = Client::builder()
-            .timeout(std::time::Duration::from_secs(30))
+    pub fn new(
+        url: &str,
+        auth: Option<Auth>,
+        proxy: Option<&str>,
+        tls: &WebDavConfig,
+    ) -> Result<Self, FluxError> {
+        let mut builder = Client::builder().timeout(std::time::Duration::from_secs(30));
+        if let Some(tls) = crate::backend::tls::client_config(tls)? {
+            builder = builder.use_preconfigured_tls(tls);
+        }
+        if let Some(proxy) = proxy {
+            let proxy = reqwest::Proxy::all(proxy)
+                .map_err(|e| FluxError::Config(format!("Invalid proxy '{}': {}", proxy, e)))?
+                .no_proxy(reqwest::NoProxy::from_env());
+            builder = builder.proxy(proxy);
+        }
+        let client = builder
             .build()
             .map_err(|e| FluxError::ProtocolError(format!("Failed to create HTTP client: {}", e)))?;
 
@@ -112,8 +156,7 @@ impl WebDavBackend {
             .body(propfind_body);
         let request = self.apply_auth(request);
 
-        let response = request.send()
-            .map_err(|e| FluxError::ProtocolError(format!("WebDAV PROPFIND failed: {}", e)))?;
+        let response = request.send().map_err(|e| request_failed("PROPFIND", e))?;
 
         let status = response.status();
         if status == StatusCode::NOT_FOUND {
@@ -293,13 +336,21 @@ impl FluxBackend for WebDavBackend {
     }
 
     fn open_read(&self, path: &Path) -> Result<Box<dyn Read + Send>, FluxError> {
+        self.open_read_range(path, 0)
+    }
+
+    /// GET with a `Range` header, streaming the body. A server that ignores
+    /// the range (200 instead of 206) has the skipped bytes read and dropped.
+    fn open_read_range(&self, path: &Path, offset: u64) -> Result<Box<dyn Read + Send>, FluxError> {
         let url = self.url_for(path);
 
-        let request = self.client.get(&url);
+        let mut request = self.client.get(&url);
+        if offset > 0 {
+            request = request.header(RANGE, format!("bytes={}-", offset));
+        }
         let request = self.apply_auth(request);
 
-        let response = request.send()
-            .map_err(|e| FluxError::ProtocolError(format!("WebDAV GET failed: {}", e)))?;
+        let response = request.send().map_err(|e| request_failed("GET", e))?;
 
         let status = response.status();
         if status == StatusCode::NOT_FOUND {
@@ -313,13 +364,18 @@ impl FluxBackend for WebDavBackend {
             ));
         }
 
-        // Buffer entire response into memory.
-        // Limitation: files larger than available RAM will OOM.
-        // Future improvement: stream via temp file or channel.
-        let bytes = response.bytes()
-            .map_err(|e| FluxError::ProtocolError(format!("Failed to read response body: {}", e)))?;
-
-        Ok(Box::new(Cursor::new(bytes.to_vec())))
+        let mut body = ResponseBody(response);
+        if offset > 0 && status != StatusCode::PARTIAL_CONTENT {
+            let skipped = io::copy(&mut (&mut body).take(offset), &mut io::sink())
+                .map_err(|e| FluxError::Io { source: e })?;
+            if skipped < offset {
+                return Err(FluxError::ProtocolError(format!(
+                    "'{}' is shorter than when the download started",
+                    path.display()
+                )));
+            }
+        }
+        Ok(Box::new(body))
     }
 
     fn open_write(&self, path: &Path) -> Result<Box<dyn Write + Send>, FluxError> {
@@ -365,11 +421,7 @@ impl FluxBackend for WebDavBackend {
                         format!("WebDAV MKCOL '{}' returned HTTP {}", url, status),
                     ));
                 }
-                Err(e) => {
-                    return Err(FluxError::ProtocolError(
-                        format!("WebDAV MKCOL failed for '{}': {}", url, e),
-                    ));
-                }
+                Err(e) => return Err(request_failed("MKCOL", e)),
             }
         }
 
@@ -381,10 +433,99 @@ impl FluxBackend for WebDavBackend {
             supports_seek: false,
             supports_parallel: false,
             supports_permissions: false,
+            supports_remove: true,
+            supports_rename: true,
+            // getlastmodified is a protected property on most servers
+            supports_set_mtime: false,
+        }
+    }
+
+    fn remove(&self, path: &Path) -> Result<(), FluxError> {
+        // DELETE on a collection is recursive; only allow it when empty
+        if self.stat(path)?.is_dir && !self.list_dir(path)?.is_empty() {
+            return Err(FluxError::ProtocolError(format!(
+                "Cannot remove '{}': directory is not empty",
+                path.display()
+            )));
+        }
+
+        let url = self.url_for(path);
+        let request = self.apply_auth(self.client.delete(&url));
+        let response = request.send().map_err(|e| request_failed("DELETE", e))?;
+
+        match response.status() {
+            StatusCode::NOT_FOUND => Err(FluxError::SourceNotFound {
+                path: path.to_path_buf(),
+            }),
+            status if status.is_success() => Ok(()),
+            status => Err(FluxError::ProtocolError(
+                format!("WebDAV DELETE '{}' returned HTTP {}", url, status),
+            )),
+        }
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> Result<(), FluxError> {
+        let url = self.url_for(from);
+        let destination = self.url_for(to);
+
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "Destination",
+            HeaderValue::from_str(&destination).map_err(|_| {
+                FluxError::ProtocolError(format!("Invalid WebDAV destination '{}'", destination))
+            })?,
+        );
+        headers.insert("Overwrite", HeaderValue::from_static("T"));
+
+        let request = self.client.request(
+            reqwest::Method::from_bytes(b"MOVE").expect("MOVE is a valid HTTP method"),
+            &url,
+        )
+        .headers(headers);
+        let request = self.apply_auth(request);
+        let response = request.send().map_err(|e| request_failed("MOVE", e))?;
+
+        // 201 Created (new destination) or 204 No Content (replaced)
+        match response.status() {
+            StatusCode::NOT_FOUND => Err(FluxError::SourceNotFound {
+                path: from.to_path_buf(),
+            }),
+            status if status.is_success() => Ok(()),
+            status => Err(FluxError::ProtocolError(
+                format!("WebDAV MOVE '{}' -> '{}' returned HTTP {}", url, destination, status),
+            )),
         }
     }
 }
 
+/// Error for a request that got no response: a rejected server certificate,
+/// or a connection failure (refused or reset, timeout) which the retry
+/// layer (`backend::retry`) tries again.
+fn request_failed(method: &str, e: reqwest::Error) -> FluxError {
+    let host = e.url().and_then(|url| url.host_str()).unwrap_or_default().to_string();
+    match crate::backend::tls::certificate_problem(&e) {
+        Some(problem) => FluxError::Certificate { host, problem },
+        None => FluxError::ConnectionFailed {
+            protocol: "webdav".to_string(),
+            host,
+            reason: format!("{} failed: {}", method, e),
+        },
+    }
+}
+
+/// A streamed GET response. A body that breaks off reads as a connection
+/// error, so `backend::retry` resumes the download from where it stopped.
+struct ResponseBody(reqwest::blocking::Response);
+
+impl Read for ResponseBody {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.0.read(buf).map_err(|e| match e.kind() {
+            io::ErrorKind::Other => io::Error::new(io::ErrorKind::ConnectionAborted, e),
+            _ => e,
+        })
+    }
+}
+
 /// A writer that buffers data in memory and uploads via PUT on flush/drop.
 ///
 /// Implements `Write + Send` for use as the return type of `open_write()`.
@@ -496,6 +637,10 @@ fn percent_decode(input: &str) -> String {
 mod tests {
     use super::*;
 
+    fn connect(url: &str, auth: Option<Auth>) -> WebDavBackend {
+        WebDavBackend::new(url, auth, None, &WebDavConfig::default()).unwrap()
+    }
+
     #[test]
     fn features_reports_no_parallel_no_seek_no_permissions() {
         let backend = WebDavBackend {
@@ -507,6 +652,9 @@ mod tests {
         assert!(!features.supports_seek);
         assert!(!features.supports_parallel);
         assert!(!features.supports_permissions);
+        assert!(features.supports_remove);
+        assert!(features.supports_rename);
+        assert!(!features.supports_set_mtime);
     }
 
     #[test]
@@ -562,17 +710,31 @@ mod tests {
 
     #[test]
     fn new_creates_backend_with_normalized_url() {
-        let backend = WebDavBackend::new("https://server.com/dav/", None).unwrap();
+        let backend = connect("https://server.com/dav/", None);
         assert_eq!(backend.base_url, "https://server.com/dav");
     }
 
+    #[test]
+    fn stored_auth_keeps_a_password_from_the_url() {
+        let auth = Auth::Password {
+            user: "admin".to_string(),
+            password: "secret".to_string(),
+        };
+        match stored_auth("https://server.com/dav", Some(auth)) {
+            Some(Auth::Password { user, password }) => {
+                assert_eq!((user.as_str(), password.as_str()), ("admin", "secret"));
+            }
+            other => panic!("Expected Password auth, got {:?}", other),
+        }
+    }
+
     #[test]
     fn new_creates_backend_with_auth() {
         let auth = Auth::Password {
             user: "admin".to_string(),
             password: "secret".to_string(),
         };
-        let backend = WebDavBackend::new("https://server.com/dav", Some(auth)).unwrap();
+        let backend = connect("https://server.com/dav", Some(auth));
         assert!(backend.auth.is_some());
         match &backend.auth {
             Some(Auth::Password { user, .. }) => assert_eq!(user, "admin"),
@@ -589,7 +751,7 @@ mod tests {
             password: "pass".to_string(),
         };
         // This emits a warning to stderr; the constructor must still succeed.
-        let backend = WebDavBackend::new("http://nas.local/dav", Some(auth)).unwrap();
+        let backend = connect("http://nas.local/dav", Some(auth));
         assert_eq!(backend.base_url, "http://nas.local/dav");
         assert!(backend.auth.is_some());
     }
@@ -598,7 +760,7 @@ mod tests {
     /// code path (no credentials means no secret is at risk).
     #[test]
     fn new_http_without_auth_no_warning() {
-        let backend = WebDavBackend::new("http://nas.local/dav", None).unwrap();
+        let backend = connect("http://nas.local/dav", None);
         assert_eq!(backend.base_url, "http://nas.local/dav");
         assert!(backend.auth.is_none());
     }
@@ -610,11 +772,23 @@ mod tests {
             user: "admin".to_string(),
             password: "hunter2".to_string(),
         };
-        let backend = WebDavBackend::new("https://secure.server.com/dav", Some(auth)).unwrap();
+        let backend = connect("https://secure.server.com/dav", Some(auth));
         assert_eq!(backend.base_url, "https://secure.server.com/dav");
         assert!(backend.auth.is_some());
     }
 
+    #[test]
+    fn new_with_proxy() {
+        for proxy in ["http://proxy.corp:3128", "socks5://proxy.corp:1080"] {
+            let tls = WebDavConfig::default();
+            assert!(WebDavBackend::new("https://nas.local/dav", None, Some(proxy), &tls).is_ok());
+        }
+        assert!(matches!(
+            WebDavBackend::new("https://nas.local/dav", None, Some("http://[bad"), &Default::default()),
+            Err(FluxError::Config(_))
+        ));
+    }
+
     #[test]
     fn writer_buffers_writes() {
         let mut writer = WebDavWriter {
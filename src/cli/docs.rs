@@ -0,0 +1,59 @@
+//! Longer guides printed by the hidden `flux docs <topic>` command. The
+//! text lives in docs/guides/ so it can be read in the repository too.
+
+use clap::ValueEnum;
+
+/// A guide (`flux docs TOPIC`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum DocsTopic {
+    /// Choosing files with --exclude, --include and the hidden and system options
+    Filters,
+    /// Path and URI forms for local, SFTP, SMB, WebDAV and rclone locations
+    Protocols,
+    /// How receivers decide whom to accept: trust on first use, allowlists,
+    /// pre-shared keys
+    #[value(alias = "trust-model")]
+    Trust,
+}
+
+impl DocsTopic {
+    /// The guide's text (Markdown).
+    pub fn guide(self) -> &'static str {
+        match self {
+            Self::Filters => include_str!("../../docs/guides/filters.md"),
+            Self::Protocols => include_str!("../../docs/guides/protocols.md"),
+            Self::Trust => include_str!("../../docs/guides/trust.md"),
+        }
+    }
+}
+
+/// Execute `flux docs`: print the guide, or list the topics without one.
+pub fn execute_docs(topic: Option<DocsTopic>) {
+    match topic {
+        Some(topic) => print!("{}", topic.guide()),
+        None => {
+            println!("Guides (flux docs TOPIC):");
+            for topic in DocsTopic::value_variants() {
+                if let Some(value) = topic.to_possible_value() {
+                    let help = value.get_help().map(|h| h.to_string()).unwrap_or_default();
+                    println!("  {:<10} {}", value.get_name(), help);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_topic_has_a_guide() {
+        for topic in DocsTopic::value_variants() {
+            let guide = topic.guide();
+            assert!(guide.starts_with("# "), "{:?}", topic);
+            assert!(guide.lines().count() > 20, "{:?}", topic);
+        }
+        assert_eq!(DocsTopic::from_str("trust-model", false), Ok(DocsTopic::Trust));
+    }
+}
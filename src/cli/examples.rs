@@ -0,0 +1,343 @@
+//! Examples for each subcommand, kept in one registry.
+//!
+//! `attach` adds them to the end of every subcommand's `--help` (not `-h`),
+//! and `flux manpages` renders them as each page's EXAMPLES section. A test
+//! parses every example, so they cannot drift from the real flags.
+
+use clap::Command;
+
+/// One example: what it does and the command line.
+pub struct Example {
+    pub about: &'static str,
+    pub command: &'static str,
+}
+
+const fn ex(about: &'static str, command: &'static str) -> Example {
+    Example { about, command }
+}
+
+/// Examples per subcommand, in the order of `flux --help`.
+pub const EXAMPLES: &[(&str, &[Example])] = &[
+    (
+        "cp",
+        &[
+            ex("Copy a file into a directory", "flux cp report.pdf /mnt/backup/"),
+            ex("Copy a directory with progress, verifying every file", "flux cp -r --verify ./photos/ nas:photos/"),
+            ex("Upload to an SFTP server, skipping logs", "flux cp -r --exclude \"*.log\" ./site/ sftp://me@web/srv/site/"),
+            ex("Resume an interrupted copy of a large file", "flux cp --resume big.iso /mnt/external/"),
+            ex("Keep the network usable while copying", "flux cp -r --limit 20MB/s ./video/ nas:media/"),
+            ex("Preview a copy without writing anything", "flux cp -r --dry-run ./project/ /backup/"),
+        ],
+    ),
+    (
+        "mv",
+        &[
+            ex("Rename a file, or move it into an existing directory", "flux mv report.pdf archive/"),
+            ex("Move a directory to another disk (copied, verified, then deleted)", "flux mv ./exports /mnt/usb/"),
+            ex("Move to a server, replacing what is there", "flux mv -f ./build/ sftp://me@web/srv/site"),
+        ],
+    ),
+    (
+        "add",
+        &[
+            ex("Save an alias for an SFTP directory", "flux add nas sftp://me@nas/srv"),
+            ex("Then use it wherever a path is accepted", "flux cp file.txt nas:backups/"),
+        ],
+    ),
+    (
+        "alias",
+        &[
+            ex("List saved aliases", "flux alias"),
+            ex("Show what a path through an alias resolves to", "flux alias resolve nas:photos/2024"),
+            ex("Remove an alias", "flux alias rm nas"),
+        ],
+    ),
+    (
+        "save",
+        &[ex("Save the last cp or sync as \"nightly\"", "flux save nightly")],
+    ),
+    (
+        "saved",
+        &[
+            ex("List saved transfers", "flux saved list"),
+            ex("Save a command without running it first", "flux saved add docs -- sync ~/docs nas:docs"),
+        ],
+    ),
+    ("run", &[ex("Run a saved transfer", "flux run nightly")]),
+    (
+        "queue",
+        &[
+            ex("Queue a large copy for the bulk window", "flux queue add -r --class bulk ./media/ nas:media/"),
+            ex("Show the queue", "flux queue list"),
+            ex("Run everything pending", "flux queue run"),
+        ],
+    ),
+    (
+        "history",
+        &[
+            ex("Show the last 50 transfers", "flux history -n 50"),
+            ex("Show what sync run 12 changed", "flux history diff 12"),
+        ],
+    ),
+    (
+        "usage",
+        &[
+            ex("Bytes moved per backend over the last 30 days", "flux usage"),
+            ex("Per day over the last week", "flux usage --by day --days 7"),
+        ],
+    ),
+    (
+        "completions",
+        &[
+            ex("Bash", "flux completions bash > ~/.local/share/bash-completion/completions/flux"),
+            ex("Zsh", "flux completions zsh > ~/.zfunc/_flux"),
+        ],
+    ),
+    (
+        "manpages",
+        &[ex("Write man pages where man finds them", "flux manpages --out ~/.local/share/man/man1")],
+    ),
+    (
+        "discover",
+        &[ex("Look for devices for ten seconds", "flux discover --timeout 10")],
+    ),
+    (
+        "send",
+        &[
+            ex("Send a file to a device found on the network", "flux send photo.jpg @laptop"),
+            ex("Send to several devices at once", "flux send file.iso @laptop @desktop"),
+            ex("Send with a code phrase to type on the other end", "flux send notes.md"),
+            ex("Send to an unattended device with a shared key", "flux send report.pdf @kiosk-12 --psk-file fleet.key"),
+        ],
+    ),
+    (
+        "receive",
+        &[
+            ex("Receive into ~/Incoming", "flux receive -o ~/Incoming"),
+            ex("Receive the file announced with a code phrase", "flux receive 3847-ace-dog-elk"),
+            ex("Run unattended, also accepting drops from browsers", "flux receive --daemon --web"),
+        ],
+    ),
+    (
+        "push",
+        &[
+            ex("Mirror a directory to a receiver", "flux push @nas ./photos backup/photos"),
+            ex("Also delete what was removed locally", "flux push --delete @nas ./photos backup/photos"),
+        ],
+    ),
+    (
+        "pull",
+        &[ex("Fetch the changes from a receiver's directory", "flux pull @nas backup/photos ./photos")],
+    ),
+    (
+        "trust",
+        &[
+            ex("List trusted devices and their fingerprints", "flux trust"),
+            ex("Forget a device (e.g. after it was reinstalled)", "flux trust rm old-laptop"),
+        ],
+    ),
+    (
+        "audit",
+        &[
+            ex("Show the latest events", "flux audit tail"),
+            ex("Refused connections since a day", "flux audit search --event refused --since 2026-01-01"),
+        ],
+    ),
+    (
+        "creds",
+        &[
+            ex("Store a password for an SFTP host", "flux creds add sftp://me@server"),
+            ex("For a WebDAV server, naming the user", "flux creds add https://cloud.example.com --user alice"),
+            ex("List what is stored", "flux creds list"),
+        ],
+    ),
+    ("ui", &[ex("Open the dashboard", "flux ui")]),
+    (
+        "sync",
+        &[
+            ex("Make dest/ a copy of src/, deleting what is gone", "flux sync --delete src/ dest/"),
+            ex("Keep syncing as files change", "flux sync --watch ~/Documents/ nas:documents/"),
+            ex("Every night at 2:00", "flux sync --schedule \"0 2 * * *\" ~/Documents/ nas:documents/"),
+            ex("Keep replaced and deleted files", "flux sync --delete --trash src/ dest/"),
+        ],
+    ),
+    (
+        "verify",
+        &[ex("Check that two directories have the same contents", "flux verify ~/photos/ nas:photos/")],
+    ),
+    (
+        "diff",
+        &[
+            ex("What a sync from src/ to dest/ would touch", "flux diff src/ dest/"),
+            ex("One line per file, comparing contents", "flux diff --flat --checksum src/ dest/"),
+        ],
+    ),
+    (
+        "tree",
+        &[
+            ex("Two levels of a remote directory", "flux tree --depth 2 sftp://me@nas/srv"),
+            ex("Where the space goes", "flux tree --du ~/Downloads"),
+        ],
+    ),
+    (
+        "ls",
+        &[
+            ex("List a remote directory", "flux ls sftp://me@nas/srv/documents"),
+            ex("Everything below, largest first, with sizes and times", "flux ls -R -l --sort size nas:photos"),
+        ],
+    ),
+    (
+        "clean",
+        &[
+            ex("List leftovers of aborted transfers", "flux clean /mnt/backup"),
+            ex("Remove them", "flux clean --yes /mnt/backup"),
+        ],
+    ),
+    (
+        "status",
+        &[ex("Follow the running transfers", "flux status --watch")],
+    ),
+    (
+        "daemon",
+        &[ex("Drain the queue, checking every minute", "flux daemon --interval 60")],
+    ),
+    (
+        "watch-folder",
+        &[
+            ex("Send whatever lands in ~/Outbox to a device", "flux watch-folder ~/Outbox @nas"),
+            ex("Copy it to a server, verified", "flux watch-folder --verify ~/Outbox sftp://me@nas/inbox"),
+        ],
+    ),
+    (
+        "decrypt",
+        &[
+            ex("Decrypt a file written by cp --encrypt-to", "flux decrypt report.pdf.fluxenc"),
+            ex("Export this device's key for others to encrypt to", "flux decrypt --export-key laptop.key"),
+        ],
+    ),
+    (
+        "service",
+        &[
+            ex("Run the receiver at boot", "flux service install receiver"),
+            ex("Check on it", "flux service status receiver"),
+        ],
+    ),
+    (
+        "config",
+        &[
+            ex("Show every setting", "flux config list"),
+            ex("Change one, checking the value", "flux config set receive.daily_quota 50GB"),
+        ],
+    ),
+    (
+        "docs",
+        &[
+            ex("List the guides", "flux docs"),
+            ex("Read the one on filters", "flux docs filters"),
+        ],
+    ),
+];
+
+/// Examples of the subcommand `name` (none if it has no entry).
+pub fn for_command(name: &str) -> &'static [Example] {
+    EXAMPLES
+        .iter()
+        .find(|(command, _)| *command == name)
+        .map_or(&[], |(_, examples)| examples)
+}
+
+/// The `Examples:` block shown at the end of `--help`.
+pub fn render(examples: &[Example]) -> String {
+    let mut text = String::from("Examples:\n");
+    for (i, example) in examples.iter().enumerate() {
+        if i > 0 {
+            text.push('\n');
+        }
+        text.push_str(&format!("  # {}\n  {}\n", example.about, example.command));
+    }
+    text
+}
+
+/// `cmd` with the examples of each subcommand it has after its long help.
+/// Subcommands compiled out of this build are passed over.
+pub fn attach(mut cmd: Command) -> Command {
+    for (name, examples) in EXAMPLES {
+        if cmd.find_subcommand(name).is_some() {
+            cmd = cmd.mut_subcommand(*name, |sub| sub.after_long_help(render(examples)));
+        }
+    }
+    cmd
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::args::Cli;
+    use clap::CommandFactory;
+    #[cfg(all(feature = "tui", feature = "net", feature = "watch"))]
+    use clap::Parser;
+
+    /// Split an example into arguments as a shell would, up to a redirect.
+    #[cfg(all(feature = "tui", feature = "net", feature = "watch"))]
+    fn split(command: &str) -> Vec<String> {
+        let mut args = Vec::new();
+        let mut current = String::new();
+        let mut quoted = false;
+        for c in command.chars() {
+            match c {
+                '"' => quoted = !quoted,
+                ' ' if !quoted => {
+                    if !current.is_empty() {
+                        args.push(std::mem::take(&mut current));
+                    }
+                }
+                _ => current.push(c),
+            }
+        }
+        args.push(current);
+        args.into_iter().take_while(|arg| arg != ">").collect()
+    }
+
+    // The examples show the full build
+    #[cfg(all(feature = "tui", feature = "net", feature = "watch"))]
+    #[test]
+    fn every_example_parses() {
+        for (name, examples) in EXAMPLES {
+            for example in *examples {
+                let args = split(example.command);
+                assert_eq!(args[0], "flux", "{}", example.command);
+                // `flux add` examples also show using the alias
+                if args[1] != *name && *name != "add" {
+                    panic!("{} example runs {}", name, args[1]);
+                }
+                if let Err(e) = Cli::try_parse_from(&args) {
+                    panic!("{}: {}", example.command, e);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn every_visible_subcommand_has_examples() {
+        let cmd = attach(Cli::command());
+        for sub in cmd.get_subcommands().filter(|s| !s.is_hide_set()) {
+            assert!(
+                !for_command(sub.get_name()).is_empty(),
+                "flux {} has no examples",
+                sub.get_name()
+            );
+            let help = sub.get_after_long_help().map(|h| h.to_string());
+            assert!(help.is_some_and(|h| h.starts_with("Examples:")));
+        }
+    }
+
+    #[test]
+    fn examples_render_as_commented_commands() {
+        let text = render(for_command("trust"));
+        assert_eq!(
+            text,
+            "Examples:\n  # List trusted devices and their fingerprints\n  flux trust\n\n  \
+             # Forget a device (e.g. after it was reinstalled)\n  flux trust rm old-laptop\n"
+        );
+    }
+}
@@ -0,0 +1,111 @@
+//! Man pages (`flux manpages`), rendered by clap_mangen from the same
+//! command definition as `--help`, with the examples registry as each
+//! subcommand's EXAMPLES section.
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use clap::Command;
+use clap_mangen::roff::{bold, roman, Roff};
+use clap_mangen::Man;
+
+use super::examples::{self, Example};
+use crate::error::FluxError;
+
+/// Write `flux.1` and a page for every visible subcommand (`flux-cp.1`,
+/// `flux-queue-add.1`, ...) into `out`, creating it. Returns the files
+/// written.
+pub fn generate(cmd: Command, out: &Path) -> Result<Vec<PathBuf>, FluxError> {
+    std::fs::create_dir_all(out)?;
+    let mut cmd = cmd.disable_help_subcommand(true);
+    // Gives subcommands their `flux-cp` page names and `flux cp` synopses
+    cmd.build();
+
+    let mut written = vec![write_page(&cmd, &[], out)?];
+    for sub in visible(&cmd) {
+        written.push(write_page(sub, examples::for_command(sub.get_name()), out)?);
+        write_nested(sub, out, &mut written)?;
+    }
+    Ok(written)
+}
+
+/// Pages for the subcommands of a subcommand (`flux queue add`), which have
+/// no examples of their own.
+fn write_nested(cmd: &Command, out: &Path, written: &mut Vec<PathBuf>) -> Result<(), FluxError> {
+    for sub in visible(cmd) {
+        written.push(write_page(sub, &[], out)?);
+        write_nested(sub, out, written)?;
+    }
+    Ok(())
+}
+
+fn visible(cmd: &Command) -> impl Iterator<Item = &Command> {
+    cmd.get_subcommands().filter(|sub| !sub.is_hide_set())
+}
+
+fn write_page(cmd: &Command, examples: &[Example], out: &Path) -> Result<PathBuf, FluxError> {
+    let name = cmd.get_display_name().unwrap_or_else(|| cmd.get_name());
+    let path = out.join(format!("{}.1", name));
+    let mut file = BufWriter::new(File::create(&path)?);
+    render(cmd, examples, &mut file)?;
+    file.flush()?;
+    Ok(path)
+}
+
+/// clap_mangen's sections, with EXAMPLES in place of its EXTRA section
+/// (the long help's closing text, which is where `--help` shows them).
+pub fn render(cmd: &Command, examples: &[Example], w: &mut dyn Write) -> std::io::Result<()> {
+    let man = Man::new(cmd.clone());
+    man.render_title(w)?;
+    man.render_name_section(w)?;
+    man.render_synopsis_section(w)?;
+    man.render_description_section(w)?;
+    if cmd.get_arguments().any(|arg| !arg.is_hide_set()) {
+        man.render_options_section(w)?;
+    }
+    if visible(cmd).next().is_some() {
+        man.render_subcommands_section(w)?;
+    }
+    if !examples.is_empty() {
+        let mut roff = Roff::default();
+        roff.control("SH", ["EXAMPLES"]);
+        for example in examples {
+            roff.control("TP", []);
+            roff.text([bold(example.command)]);
+            roff.text([roman(example.about)]);
+        }
+        roff.to_writer(w)?;
+    }
+    if cmd.get_version().is_some() {
+        man.render_version_section(w)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::args::Cli;
+    use clap::CommandFactory;
+
+    #[test]
+    fn writes_a_page_per_visible_subcommand_with_examples() {
+        let dir = tempfile::tempdir().unwrap();
+        let written = generate(crate::cli::command(), dir.path()).unwrap();
+
+        assert!(written.contains(&dir.path().join("flux.1")));
+        assert!(written.contains(&dir.path().join("flux-queue-add.1")));
+        // Hidden commands get no page
+        assert!(!dir.path().join("flux-docs.1").exists());
+        let visible = Cli::command().get_subcommands().filter(|s| !s.is_hide_set()).count();
+        assert!(written.len() > visible);
+
+        let cp = std::fs::read_to_string(dir.path().join("flux-cp.1")).unwrap();
+        assert!(cp.starts_with(".ie"));
+        assert!(cp.contains(".SH EXAMPLES"));
+        assert!(cp.contains("Resume an interrupted copy of a large file"));
+        assert!(!cp.contains(".SH EXTRA"));
+        assert!(cp.contains("flux cp"));
+    }
+}
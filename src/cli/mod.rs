@@ -1 +1,20 @@
 pub mod args;
+pub mod docs;
+pub mod examples;
+pub mod manpages;
+
+use clap::{CommandFactory, FromArgMatches};
+
+use self::args::Cli;
+
+/// The `flux` command as parsed, completed and documented: the derived
+/// definition plus each subcommand's examples.
+pub fn command() -> clap::Command {
+    examples::attach(Cli::command())
+}
+
+/// Parse the command line against `command()`.
+pub fn try_parse() -> Result<Cli, clap::Error> {
+    let mut matches = command().try_get_matches()?;
+    Cli::from_arg_matches_mut(&mut matches)
+}
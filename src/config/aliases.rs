@@ -1,7 +1,9 @@
 //! Alias store for named path aliases.
 //!
 //! Persists aliases in `aliases.toml` within the Flux config directory.
-//! Provides CRUD operations and alias resolution for use in transfer commands.
+//! Provides CRUD operations and alias resolution for use in transfer commands,
+//! plus expansion of built-in variables (`{hostname}`, `{date}`, `{time}`,
+//! `{user}`) in destination templates.
 
 use std::collections::BTreeMap;
 use std::path::{Path, PathBuf};
@@ -25,7 +27,7 @@ pub struct AliasStore {
 
 /// Known URL schemes that must not be used as alias names.
 const RESERVED_SCHEMES: &[&str] = &[
-    "sftp", "ssh", "smb", "https", "http", "webdav", "dav", "ftp",
+    "sftp", "ssh", "smb", "https", "http", "webdav", "dav", "ftp", "rclone",
 ];
 
 impl AliasStore {
@@ -73,6 +75,17 @@ impl AliasStore {
     pub fn list(&self) -> &BTreeMap<String, String> {
         &self.data.aliases
     }
+
+    /// The aliases in the config directory, or none if they cannot be read.
+    pub fn current() -> Self {
+        match crate::config::paths::flux_config_dir().and_then(|dir| Self::load(&dir)) {
+            Ok(store) => store,
+            Err(e) => {
+                tracing::debug!("Aliases not available: {}", e);
+                Self::default()
+            }
+        }
+    }
 }
 
 impl Default for AliasStore {
@@ -91,50 +104,178 @@ impl Default for AliasStore {
 ///
 /// Patterns:
 /// - `"name:"` -> base path from alias
-/// - `"name:subpath"` -> base path + separator + subpath
+/// - `"name:subpath"` (or `"name:/subpath"`) -> base path + separator + subpath
 /// - `"sftp://host/path"` -> unchanged (URL scheme)
 /// - `"C:\path"` -> unchanged (drive letter)
 /// - `"unknown-alias:"` -> unchanged (alias not found)
+///
+/// Only one level is resolved: alias values cannot refer to other aliases
+/// (`flux add` refuses them, see `check_nesting`).
 pub fn resolve_alias(input: &str, aliases: &AliasStore) -> String {
-    if let Some(colon_pos) = input.find(':') {
-        let name = &input[..colon_pos];
-        let rest = &input[colon_pos + 1..];
-
-        // Skip empty names
-        if name.is_empty() {
-            return input.to_string();
+    let Some((name, rest)) = alias_reference(input) else {
+        return input.to_string();
+    };
+    let Some(base_path) = aliases.get(name) else {
+        return input.to_string();
+    };
+    if let Some((nested, _)) = alias_reference(base_path) {
+        if aliases.get(nested).is_some() {
+            tracing::warn!(
+                "Alias '{}' points at alias '{}', which is not expanded again",
+                name,
+                nested
+            );
         }
+    }
+    // `nas:/photos` and `nas:photos` are the same place
+    let rest = rest.trim_start_matches(['/', '\\']);
+    if rest.is_empty() {
+        return base_path.clone();
+    }
+    // Join with appropriate separator
+    let separator = if base_path.contains('\\') {
+        "\\"
+    } else {
+        "/"
+    };
+    let base = base_path.trim_end_matches(['/', '\\']);
+    format!("{}{}{}", base, separator, rest)
+}
 
-        // Skip single-char names (Windows drive letters like C:)
-        if name.len() == 1 {
-            return input.to_string();
-        }
+/// Resolve alias references in `input` with the aliases in the config
+/// directory (only read if `input` looks like an alias reference).
+pub fn resolve(input: &str) -> String {
+    if alias_reference(input).is_none() {
+        return input.to_string();
+    }
+    resolve_alias(input, &AliasStore::current())
+}
+
+/// The alias name and the rest of `input`, if it has the form of an alias
+/// reference (`name:rest`). The alias need not exist.
+fn alias_reference(input: &str) -> Option<(&str, &str)> {
+    let (name, rest) = input.split_once(':')?;
+
+    // Skip empty names and single-char names (Windows drive letters like C:)
+    if name.len() < 2 {
+        return None;
+    }
+
+    // Skip URL schemes (rest starts with "//")
+    if rest.starts_with("//") {
+        return None;
+    }
 
-        // Skip URL schemes (rest starts with "//")
-        if rest.starts_with("//") {
-            return input.to_string();
+    // Skip names containing path separators
+    if name.contains('/') || name.contains('\\') {
+        return None;
+    }
+    Some((name, rest))
+}
+
+/// Refuse to save alias `name` -> `path` if it would nest aliases: `path`
+/// refers to an existing alias, or an existing alias refers to `name`.
+pub fn check_nesting(name: &str, path: &str, aliases: &AliasStore) -> Result<(), FluxError> {
+    if let Some((target, _)) = alias_reference(path) {
+        if target == name || aliases.get(target).is_some() {
+            return Err(FluxError::AliasError(format!(
+                "'{}' refers to alias '{}'; aliases cannot point at other aliases \
+                 (use its value, `flux alias resolve {}`)",
+                path, target, path
+            )));
         }
+    }
+    let referring = aliases
+        .list()
+        .iter()
+        .find(|(other, value)| {
+            other.as_str() != name && alias_reference(value).is_some_and(|(n, _)| n == name)
+        });
+    if let Some((other, value)) = referring {
+        return Err(FluxError::AliasError(format!(
+            "alias '{}' ({}) would then point at alias '{}'; aliases cannot be nested",
+            other, value, name
+        )));
+    }
+    Ok(())
+}
 
-        // Skip names containing path separators
-        if name.contains('/') || name.contains('\\') {
-            return input.to_string();
+/// Resolve aliases in a destination and expand its template variables.
+///
+/// Variables are expanded after alias resolution, so an alias value may
+/// itself be a template (e.g. `backup = "/mnt/backup/{hostname}"`).
+pub fn resolve_destination(input: &str, aliases: &AliasStore) -> String {
+    expand_variables(&resolve_alias(input, aliases))
+}
+
+/// Values for the built-in destination template variables.
+pub struct TemplateVars {
+    pub hostname: String,
+    pub user: String,
+    pub now: chrono::DateTime<chrono::Local>,
+}
+
+impl TemplateVars {
+    /// Values for this machine, the current user and the local time.
+    pub fn current() -> Self {
+        let user = std::env::var("USER")
+            .or_else(|_| std::env::var("USERNAME"))
+            .unwrap_or_else(|_| "unknown".to_string());
+        Self {
+            hostname: gethostname::gethostname().to_string_lossy().to_string(),
+            user,
+            now: chrono::Local::now(),
         }
+    }
 
-        // Look up alias
-        if let Some(base_path) = aliases.get(name) {
-            if rest.is_empty() {
-                return base_path.clone();
+    fn value(&self, name: &str) -> Option<String> {
+        let value = match name {
+            "hostname" => self.hostname.clone(),
+            "user" => self.user.clone(),
+            "date" => self.now.format("%Y-%m-%d").to_string(),
+            // No colons: they are not valid in Windows or SMB paths
+            "time" => self.now.format("%H%M%S").to_string(),
+            _ => return None,
+        };
+        // A value must stay a single path component
+        Some(value.replace(['/', '\\'], "_"))
+    }
+}
+
+/// Expand `{hostname}`, `{date}`, `{time}` and `{user}` with current values.
+///
+/// Unknown `{...}` sequences are left untouched, so paths that happen to
+/// contain braces keep working.
+pub fn expand_variables(input: &str) -> String {
+    if !input.contains('{') {
+        return input.to_string();
+    }
+    expand_variables_with(input, &TemplateVars::current())
+}
+
+/// Expand template variables using the given values.
+pub fn expand_variables_with(input: &str, vars: &TemplateVars) -> String {
+    let mut out = String::with_capacity(input.len());
+    let mut rest = input;
+    while let Some(start) = rest.find('{') {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let expanded = after
+            .find('}')
+            .and_then(|end| vars.value(&after[..end]).map(|value| (end, value)));
+        match expanded {
+            Some((end, value)) => {
+                out.push_str(&value);
+                rest = &after[end + 1..];
+            }
+            None => {
+                out.push('{');
+                rest = after;
             }
-            // Join with appropriate separator
-            let separator = if base_path.contains('\\') {
-                "\\"
-            } else {
-                "/"
-            };
-            return format!("{}{}{}", base_path, separator, rest);
         }
     }
-    input.to_string()
+    out.push_str(rest);
+    out
 }
 
 /// Validate that an alias name is acceptable.
@@ -270,6 +411,74 @@ mod tests {
         );
     }
 
+    #[test]
+    fn resolve_url_alias_with_leading_separator() {
+        let store = make_store(&[("nas", "smb://server/share/"), ("home", "/home/sam")]);
+        assert_eq!(resolve_alias("nas:/photos", &store), "smb://server/share/photos");
+        assert_eq!(resolve_alias("nas:/", &store), "smb://server/share/");
+        assert_eq!(resolve_alias("home:/docs/a.txt", &store), "/home/sam/docs/a.txt");
+    }
+
+    #[test]
+    fn nested_aliases_are_refused() {
+        let store = make_store(&[("nas", "smb://server/share"), ("photos", "pics:2024")]);
+        // Pointing at an existing alias, or at itself
+        assert!(check_nesting("archive", "nas:archive", &store).is_err());
+        assert!(check_nesting("loop", "loop:x", &store).is_err());
+        // An existing alias already points at the new name
+        assert!(check_nesting("pics", "/mnt/pics", &store).is_err());
+        assert!(check_nesting("archive", "sftp://host/archive", &store).is_ok());
+        assert!(check_nesting("nas", "smb://other/share", &store).is_ok());
+        // Values are only expanded once
+        let store = make_store(&[("nas", "smb://server/share"), ("photos", "nas:photos")]);
+        assert_eq!(resolve_alias("photos:2024", &store), "nas:photos/2024");
+    }
+
+    // --- template variable tests ---
+
+    fn fixed_vars() -> TemplateVars {
+        use chrono::TimeZone;
+        TemplateVars {
+            hostname: "laptop".to_string(),
+            user: "sam".to_string(),
+            now: chrono::Local.with_ymd_and_hms(2024, 3, 9, 14, 5, 7).unwrap(),
+        }
+    }
+
+    #[test]
+    fn expand_builtin_variables() {
+        assert_eq!(
+            expand_variables_with("/backups/{hostname}/{date}/{user}-{time}/", &fixed_vars()),
+            "/backups/laptop/2024-03-09/sam-140507/"
+        );
+    }
+
+    #[test]
+    fn expand_leaves_unknown_and_unclosed_braces() {
+        let vars = fixed_vars();
+        assert_eq!(expand_variables_with("/data/{project}/x", &vars), "/data/{project}/x");
+        assert_eq!(expand_variables_with("/data/{date", &vars), "/data/{date");
+        assert_eq!(expand_variables_with("{{date}}", &vars), "{2024-03-09}");
+    }
+
+    #[test]
+    fn expand_keeps_values_in_one_component() {
+        let mut vars = fixed_vars();
+        vars.user = "DOMAIN\\sam".to_string();
+        assert_eq!(expand_variables_with("/home/{user}", &vars), "/home/DOMAIN_sam");
+    }
+
+    #[test]
+    fn resolve_destination_expands_alias_templates() {
+        let store = make_store(&[("backup", "/mnt/backup/{unknown}")]);
+        assert_eq!(
+            resolve_destination("backup:docs", &store),
+            "/mnt/backup/{unknown}/docs"
+        );
+        let plain = resolve_destination("/tmp/out", &store);
+        assert_eq!(plain, "/tmp/out");
+    }
+
     // --- validate_alias_name tests ---
 
     #[test]
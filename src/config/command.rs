@@ -0,0 +1,257 @@
+//! `flux config`: read, change and check config.toml.
+//!
+//! `set` and `unset` edit the file with `toml_edit`, so comments and layout
+//! written by hand survive. Values are checked against `config::schema`
+//! before anything is written. `get` and `list` show effective values: the
+//! file, then `FLUX_*` environment overrides, then built-in defaults.
+
+use std::path::Path;
+
+use serde::Deserialize;
+
+use crate::cli::args::ConfigAction;
+use crate::config::paths::config_file;
+use crate::config::schema::{self, ConfigKey};
+use crate::config::types::{read_config_table, FluxConfig};
+use crate::error::FluxError;
+
+/// Entry point for `flux config`.
+pub fn execute_config(action: ConfigAction, quiet: bool) -> Result<(), FluxError> {
+    match action {
+        ConfigAction::Get(args) => get(&args.key),
+        ConfigAction::Set(args) => set(&args.key, &args.value, quiet),
+        ConfigAction::Unset(args) => unset(&args.key, quiet),
+        ConfigAction::List => list(),
+        ConfigAction::Edit => edit(quiet),
+        ConfigAction::Validate => validate(quiet),
+    }
+}
+
+/// Where a value shown by `get` or `list` comes from.
+enum Source {
+    File,
+    Env(String),
+    Default,
+}
+
+/// Effective value of `name` and where it comes from.
+fn effective(
+    name: &str,
+    key: &ConfigKey,
+    file: &toml::Table,
+    env: &toml::Table,
+    overridden: &[&ConfigKey],
+    defaults: &toml::Table,
+) -> Option<(toml::Value, Source)> {
+    if overridden.iter().any(|k| k.name == key.name) {
+        return schema::lookup(env, name).map(|v| (v.clone(), Source::Env(key.env_var())));
+    }
+    if let Some(value) = schema::lookup(file, name) {
+        return Some((value.clone(), Source::File));
+    }
+    schema::lookup(defaults, name)
+        .filter(|v| !v.is_table())
+        .map(|v| (v.clone(), Source::Default))
+}
+
+fn default_table() -> toml::Table {
+    match toml::Value::try_from(FluxConfig::default()) {
+        Ok(toml::Value::Table(table)) => table,
+        _ => toml::Table::new(),
+    }
+}
+
+fn get(name: &str) -> Result<(), FluxError> {
+    let key = schema::find_key(name)?;
+    let file = read_config_table()?;
+    let mut env = file.clone();
+    let overridden = schema::apply_env_overrides(&mut env, |var| std::env::var(var).ok())?;
+    match effective(name, key, &file, &env, &overridden, &default_table()) {
+        Some((toml::Value::String(s), _)) => println!("{}", s),
+        Some((value, _)) => println!("{}", value),
+        None => eprintln!("{} is not set", name),
+    }
+    Ok(())
+}
+
+fn list() -> Result<(), FluxError> {
+    let file = read_config_table()?;
+    let mut env = file.clone();
+    let overridden = schema::apply_env_overrides(&mut env, |var| std::env::var(var).ok())?;
+    let defaults = default_table();
+    let entries = schema::flatten(&file);
+
+    for key in schema::KEYS {
+        // Keys under user-named tables are listed as they appear in the file
+        let names: Vec<String> = if key.is_fixed() {
+            vec![key.name.to_string()]
+        } else {
+            entries
+                .iter()
+                .map(|(name, _)| name.clone())
+                .filter(|name| schema::find_key(name).is_ok_and(|k| k.name == key.name))
+                .collect()
+        };
+        for name in names {
+            match effective(&name, key, &file, &env, &overridden, &defaults) {
+                Some((value, Source::File)) => println!("{} = {}", name, value),
+                Some((value, Source::Env(var))) => {
+                    println!("{} = {}  # from {}", name, value, var)
+                }
+                Some((value, Source::Default)) => println!("{} = {}  # default", name, value),
+                None => println!("# {} (not set): {}", name, key.help),
+            }
+        }
+    }
+    Ok(())
+}
+
+fn set(name: &str, raw: &str, quiet: bool) -> Result<(), FluxError> {
+    let key = schema::find_key(name)?;
+    let value = schema::parse_value(key, raw)?;
+    let path = config_file()?;
+    let mut doc = read_document(&path)?;
+
+    let segments: Vec<&str> = name.split('.').collect();
+    let (last, parents) = segments.split_last().expect("split yields one segment");
+    let mut table: &mut dyn toml_edit::TableLike = doc.as_table_mut();
+    for segment in parents {
+        let entry = table.entry(segment).or_insert_with(|| {
+            let mut new = toml_edit::Table::new();
+            new.set_implicit(true);
+            toml_edit::Item::Table(new)
+        });
+        table = entry.as_table_like_mut().ok_or_else(|| {
+            FluxError::Config(format!(
+                "Cannot set {}: '{}' in {} is not a table",
+                name,
+                segment,
+                path.display()
+            ))
+        })?;
+    }
+    table.insert(last, value.to_item());
+
+    // Refuse to write a file the next command could not load (e.g. an
+    // allowlisted device without a fingerprint)
+    let table: toml::Table = toml::from_str(&doc.to_string())
+        .map_err(|e| FluxError::Config(format!("Invalid config.toml: {}", e)))?;
+    FluxConfig::deserialize(toml::Value::Table(table)).map_err(|e| {
+        FluxError::Config(format!("Not saved, config.toml would be invalid: {}", e))
+    })?;
+    write_document(&path, &doc)?;
+
+    if !quiet {
+        eprintln!("Set {} = {}", name, value.to_toml());
+        if std::env::var_os(key.env_var()).is_some() && key.is_fixed() {
+            eprintln!("Note: {} is set and overrides this value", key.env_var());
+        }
+    }
+    Ok(())
+}
+
+fn unset(name: &str, quiet: bool) -> Result<(), FluxError> {
+    schema::find_key(name)?;
+    let path = config_file()?;
+    if !path.exists() {
+        if !quiet {
+            eprintln!("{} is not set", name);
+        }
+        return Ok(());
+    }
+    let mut doc = read_document(&path)?;
+
+    let segments: Vec<&str> = name.split('.').collect();
+    let (last, parents) = segments.split_last().expect("split yields one segment");
+    let mut table: Option<&mut dyn toml_edit::TableLike> = Some(doc.as_table_mut());
+    for segment in parents {
+        table = table
+            .and_then(|t| t.get_mut(segment))
+            .and_then(|item| item.as_table_like_mut());
+    }
+    let removed = table.and_then(|t| t.remove(last)).is_some();
+    if removed {
+        write_document(&path, &doc)?;
+    }
+    if !quiet {
+        if removed {
+            eprintln!("Removed {}", name);
+        } else {
+            eprintln!("{} is not set", name);
+        }
+    }
+    Ok(())
+}
+
+fn edit(quiet: bool) -> Result<(), FluxError> {
+    let path = config_file()?;
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        std::fs::create_dir_all(parent)?;
+    }
+    open_in_editor(&path)?;
+    validate(quiet)
+}
+
+/// Open `path` in $VISUAL or $EDITOR and wait for it to close.
+pub fn open_in_editor(path: &Path) -> Result<(), FluxError> {
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .unwrap_or_else(|_| if cfg!(windows) { "notepad" } else { "vi" }.to_string());
+    // Through the shell, so EDITOR="code --wait" works
+    let command = format!("{} \"{}\"", editor, path.display());
+    let status = crate::transfer::hooks::shell_command(&command)
+        .status()
+        .map_err(|e| FluxError::Config(format!("Failed to start editor '{}': {}", editor, e)))?;
+    if !status.success() {
+        return Err(FluxError::Config(format!("Editor '{}' exited with {}", editor, status)));
+    }
+    Ok(())
+}
+
+fn validate(quiet: bool) -> Result<(), FluxError> {
+    let path = config_file()?;
+    let table = read_config_table()?;
+    let mut problems = schema::validate(&table);
+    if problems.is_empty() {
+        // Shape errors the per-key checks cannot see (e.g. a device table
+        // without a fingerprint), and bad FLUX_* overrides
+        if let Err(FluxError::Config(msg)) = crate::config::types::load_config() {
+            problems.push(msg);
+        }
+    }
+    if problems.is_empty() {
+        if !quiet {
+            eprintln!("{}: OK", path.display());
+        }
+        return Ok(());
+    }
+    for problem in &problems {
+        eprintln!("  {}", problem);
+    }
+    Err(FluxError::Config(format!(
+        "{} problem(s) in {}",
+        problems.len(),
+        path.display()
+    )))
+}
+
+/// config.toml as an editable document (empty if it does not exist yet).
+fn read_document(path: &Path) -> Result<toml_edit::DocumentMut, FluxError> {
+    if !path.exists() {
+        return Ok(toml_edit::DocumentMut::new());
+    }
+    std::fs::read_to_string(path)?
+        .parse::<toml_edit::DocumentMut>()
+        .map_err(|e| FluxError::Config(format!("Invalid config.toml: {}", e)))
+}
+
+/// Write atomically (tmp file, then rename), like the alias store.
+fn write_document(path: &Path, doc: &toml_edit::DocumentMut) -> Result<(), FluxError> {
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        std::fs::create_dir_all(parent)?;
+    }
+    let tmp_path = path.with_extension("toml.tmp");
+    std::fs::write(&tmp_path, doc.to_string())?;
+    std::fs::rename(&tmp_path, path)?;
+    Ok(())
+}
@@ -1,3 +1,7 @@
 pub mod aliases;
+pub mod command;
 pub mod paths;
+pub mod saved;
+pub mod schema;
 pub mod types;
+pub mod versioned;
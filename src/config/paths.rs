@@ -39,6 +39,17 @@ pub fn flux_config_dir() -> Result<PathBuf, FluxError> {
     Ok(flux_dir)
 }
 
+/// Path of config.toml.
+///
+/// `FLUX_CONFIG` (set by the global `--config PATH` flag) names the file
+/// directly; otherwise it is `config.toml` in `flux_config_dir()`.
+pub fn config_file() -> Result<PathBuf, FluxError> {
+    match std::env::var_os("FLUX_CONFIG") {
+        Some(path) if !path.is_empty() => Ok(PathBuf::from(path)),
+        _ => Ok(flux_config_dir()?.join("config.toml")),
+    }
+}
+
 /// Get the Flux data directory, creating it if needed.
 ///
 /// Returns the platform-specific data directory with a `flux` subdirectory.
@@ -0,0 +1,385 @@
+//! Saved transfers: named `cp` and `sync` command lines.
+//!
+//! Every `cp` and `sync` run is recorded in `last-command.json` in the data
+//! directory; `flux save <name>` keeps it in `saved.toml` in the config
+//! directory (`flux saved add <name> -- <command>` stores one directly), and
+//! `flux run <name>` runs it again. Global flags (`-v`, `-q`, `--json`,
+//! `--config`) are not stored: they apply to the `flux run` that uses them.
+//! Relative paths are resolved against the directory the command was run
+//! from, which is stored with it.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use clap::Parser;
+use serde::{Deserialize, Serialize};
+
+use crate::cli::args::{Cli, Commands, SaveArgs, SavedAction, SavedArgs};
+use crate::error::FluxError;
+
+/// A saved `cp` or `sync` invocation.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SavedTransfer {
+    /// Arguments after `flux`, starting with `cp` or `sync`.
+    pub args: Vec<String>,
+    /// Working directory the command was run from.
+    pub dir: PathBuf,
+}
+
+impl SavedTransfer {
+    /// The command as it would be typed, with arguments quoted as needed.
+    pub fn command_line(&self) -> String {
+        let mut line = String::from("flux");
+        for arg in &self.args {
+            line.push(' ');
+            line.push_str(&quote(arg));
+        }
+        line
+    }
+
+    /// Parse the stored arguments. Only `cp` and `sync` commands are valid.
+    pub fn command(&self) -> Result<Commands, FluxError> {
+        let argv = std::iter::once("flux").chain(self.args.iter().map(String::as_str));
+        let cli = Cli::try_parse_from(argv).map_err(|e| {
+            let rendered = e.render().to_string();
+            let reason = rendered.lines().next().unwrap_or_default();
+            FluxError::SavedError(format!(
+                "`{}` is not a valid command: {}",
+                self.command_line(),
+                reason.trim_start_matches("error: ")
+            ))
+        })?;
+        match cli.command {
+            Commands::Cp(_) | Commands::Sync(_) => Ok(cli.command),
+            _ => Err(FluxError::SavedError(format!(
+                "`{}` is not a cp or sync command",
+                self.command_line()
+            ))),
+        }
+    }
+}
+
+/// Serialized `saved.toml` format.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct SavedFile {
+    #[serde(default)]
+    pub transfers: BTreeMap<String, SavedTransfer>,
+}
+
+/// Saved transfers backed by `saved.toml`.
+pub struct SavedStore {
+    path: PathBuf,
+    data: SavedFile,
+}
+
+impl SavedStore {
+    /// Load saved transfers from `saved.toml` in the given config directory.
+    ///
+    /// Returns an empty store if the file does not exist.
+    pub fn load(config_dir: &Path) -> Result<Self, FluxError> {
+        let path = config_dir.join("saved.toml");
+        let data = if path.exists() {
+            let contents = std::fs::read_to_string(&path)?;
+            toml::from_str(&contents)
+                .map_err(|e| FluxError::Config(format!("Invalid saved.toml: {}", e)))?
+        } else {
+            SavedFile::default()
+        };
+        Ok(Self { path, data })
+    }
+
+    /// Save to disk atomically (write to tmp file, then rename).
+    pub fn save(&self) -> Result<(), FluxError> {
+        let contents = toml::to_string_pretty(&self.data).map_err(|e| {
+            FluxError::Config(format!("Failed to serialize saved transfers: {}", e))
+        })?;
+        let tmp_path = self.path.with_extension("toml.tmp");
+        std::fs::write(&tmp_path, &contents)?;
+        std::fs::rename(&tmp_path, &self.path)?;
+        Ok(())
+    }
+
+    /// Add or replace a saved transfer. Returns whether it replaced one.
+    pub fn add(&mut self, name: String, transfer: SavedTransfer) -> bool {
+        self.data.transfers.insert(name, transfer).is_some()
+    }
+
+    /// Remove a saved transfer by name. Returns whether it existed.
+    pub fn remove(&mut self, name: &str) -> bool {
+        self.data.transfers.remove(name).is_some()
+    }
+
+    /// Look up a saved transfer by name.
+    pub fn get(&self, name: &str) -> Option<&SavedTransfer> {
+        self.data.transfers.get(name)
+    }
+
+    /// All saved transfers, by name.
+    pub fn list(&self) -> &BTreeMap<String, SavedTransfer> {
+        &self.data.transfers
+    }
+
+    /// Path of `saved.toml`.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+/// Check that `name` can be used with `flux run`.
+pub fn validate_name(name: &str) -> Result<(), FluxError> {
+    if name.is_empty()
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err(FluxError::SavedError(format!(
+            "'{}' is not a valid name: use letters, digits, hyphens and underscores",
+            name
+        )));
+    }
+    Ok(())
+}
+
+/// The transfer part of a command line (`argv` without the program name):
+/// the arguments without global flags, if the command is `cp` or `sync`.
+pub fn transfer_args<I>(argv: I) -> Option<Vec<String>>
+where
+    I: IntoIterator<Item = String>,
+{
+    let mut args = Vec::new();
+    let mut argv = argv.into_iter();
+    while let Some(arg) = argv.next() {
+        if arg == "--" {
+            args.push(arg);
+            args.extend(argv.by_ref());
+            break;
+        }
+        match arg.as_str() {
+            "--verbose" | "--quiet" | "--json" | "--tui" => {}
+            "--config" => {
+                argv.next();
+            }
+            _ if arg.starts_with("--config=") => {}
+            // -v, -vv, -q, -qv, ...
+            _ if arg.len() > 1
+                && arg.starts_with('-')
+                && arg[1..].chars().all(|c| c == 'v' || c == 'q') => {}
+            _ => args.push(arg),
+        }
+    }
+    matches!(args.first().map(String::as_str), Some("cp" | "sync")).then_some(args)
+}
+
+/// Remember the `cp` or `sync` command being run, for `flux save`. Failures
+/// are only logged.
+pub fn record_last<I>(argv: I)
+where
+    I: IntoIterator<Item = String>,
+{
+    let Some(args) = transfer_args(argv) else {
+        return;
+    };
+    let result = std::env::current_dir()
+        .map_err(FluxError::from)
+        .and_then(|dir| {
+            let data_dir = crate::config::paths::flux_data_dir()?;
+            write_last(&data_dir, &SavedTransfer { args, dir })
+        });
+    if let Err(e) = result {
+        tracing::debug!("Could not record the command for `flux save`: {}", e);
+    }
+}
+
+/// Write the last command to `last-command.json` in `data_dir`.
+fn write_last(data_dir: &Path, transfer: &SavedTransfer) -> Result<(), FluxError> {
+    let json = serde_json::to_string_pretty(transfer)
+        .map_err(|e| FluxError::Config(format!("Failed to serialize command: {}", e)))?;
+    let path = data_dir.join("last-command.json");
+    let tmp_path = path.with_extension("json.tmp");
+    std::fs::write(&tmp_path, json)?;
+    std::fs::rename(&tmp_path, &path)?;
+    Ok(())
+}
+
+/// The last `cp` or `sync` command run, if any.
+fn read_last(data_dir: &Path) -> Result<Option<SavedTransfer>, FluxError> {
+    let path = data_dir.join("last-command.json");
+    if !path.exists() {
+        return Ok(None);
+    }
+    let contents = std::fs::read_to_string(&path)?;
+    serde_json::from_str(&contents)
+        .map(Some)
+        .map_err(|e| FluxError::SavedError(format!("Invalid {}: {}", path.display(), e)))
+}
+
+/// `flux save <name>`: keep the last `cp` or `sync` command.
+pub fn execute_save(args: SaveArgs, quiet: bool) -> Result<(), FluxError> {
+    validate_name(&args.name)?;
+    let data_dir = crate::config::paths::flux_data_dir()?;
+    let transfer = read_last(&data_dir)?.ok_or_else(|| {
+        FluxError::SavedError("No cp or sync command has been run yet".into())
+    })?;
+    store(args.name, transfer, quiet)
+}
+
+/// `flux saved`: list, add, edit or remove saved transfers.
+pub fn execute_saved(args: SavedArgs, quiet: bool) -> Result<(), FluxError> {
+    let config_dir = crate::config::paths::flux_config_dir()?;
+    match args.action.unwrap_or(SavedAction::List) {
+        SavedAction::List => {
+            let saved = SavedStore::load(&config_dir)?;
+            if saved.list().is_empty() {
+                eprintln!("No saved transfers (save one with `flux save <name>` after cp or sync)");
+            }
+            for (name, transfer) in saved.list() {
+                println!("{:<16} {}", name, transfer.command_line());
+            }
+            Ok(())
+        }
+        SavedAction::Add(add) => {
+            validate_name(&add.name)?;
+            let dir = std::env::current_dir()?;
+            store(add.name, SavedTransfer { args: add.command, dir }, quiet)
+        }
+        SavedAction::Edit => {
+            let saved = SavedStore::load(&config_dir)?;
+            crate::config::command::open_in_editor(saved.path())?;
+            let saved = SavedStore::load(&config_dir)?;
+            for (name, transfer) in saved.list() {
+                validate_name(name)?;
+                transfer.command()?;
+            }
+            if !quiet {
+                eprintln!("{}: OK", saved.path().display());
+            }
+            Ok(())
+        }
+        SavedAction::Rm(rm) => {
+            let mut saved = SavedStore::load(&config_dir)?;
+            if !saved.remove(&rm.name) {
+                return Err(FluxError::SavedError(format!("No saved transfer '{}'", rm.name)));
+            }
+            saved.save()?;
+            if !quiet {
+                eprintln!("Removed saved transfer: {}", rm.name);
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Check `transfer` and store it as `name`.
+fn store(name: String, transfer: SavedTransfer, quiet: bool) -> Result<(), FluxError> {
+    transfer.command()?;
+    let config_dir = crate::config::paths::flux_config_dir()?;
+    let mut saved = SavedStore::load(&config_dir)?;
+    let line = transfer.command_line();
+    let replaced = saved.add(name.clone(), transfer);
+    saved.save()?;
+    if !quiet {
+        let verb = if replaced { "Updated" } else { "Saved" };
+        eprintln!("{} {}: {}", verb, name, line);
+    }
+    Ok(())
+}
+
+/// The command saved as `name`, and the directory to run it from, for
+/// `flux run`.
+pub fn load_command(name: &str) -> Result<(Commands, PathBuf), FluxError> {
+    let config_dir = crate::config::paths::flux_config_dir()?;
+    let saved = SavedStore::load(&config_dir)?;
+    let transfer = saved
+        .get(name)
+        .ok_or_else(|| FluxError::SavedError(format!("No saved transfer '{}'", name)))?;
+    Ok((transfer.command()?, transfer.dir.clone()))
+}
+
+/// Quote `arg` for a POSIX shell if it needs it.
+fn quote(arg: &str) -> String {
+    let plain = !arg.is_empty()
+        && arg
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_./:@%+=,{}~\\".contains(c));
+    if plain {
+        arg.to_string()
+    } else {
+        format!("'{}'", arg.replace('\'', "'\\''"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn argv(line: &str) -> Vec<String> {
+        line.split_whitespace().map(String::from).collect()
+    }
+
+    #[test]
+    fn global_flags_are_not_saved() {
+        assert_eq!(
+            transfer_args(argv("-v cp a b --verify -q")),
+            Some(argv("cp a b --verify"))
+        );
+        assert_eq!(
+            transfer_args(argv("--config /tmp/c.toml sync src dst --json --delete")),
+            Some(argv("sync src dst --delete"))
+        );
+        assert_eq!(
+            transfer_args(argv("cp --exclude=*.tmp a b -- -vv")),
+            Some(argv("cp --exclude=*.tmp a b -- -vv"))
+        );
+        assert_eq!(transfer_args(argv("-q queue run")), None);
+        assert_eq!(transfer_args(Vec::new()), None);
+    }
+
+    #[test]
+    fn only_transfers_parse() {
+        let transfer = |line: &str| SavedTransfer {
+            args: argv(line),
+            dir: PathBuf::from("/"),
+        };
+        assert!(matches!(transfer("cp -r a b").command(), Ok(Commands::Cp(_))));
+        assert!(matches!(transfer("sync a b --delete").command(), Ok(Commands::Sync(_))));
+        assert!(transfer("history").command().is_err());
+        assert!(transfer("cp --no-such-flag a b").command().is_err());
+    }
+
+    #[test]
+    fn store_round_trips_and_quotes() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let mut saved = SavedStore::load(dir.path()).unwrap();
+        let transfer = SavedTransfer {
+            args: vec!["cp".into(), "My Docs".into(), "nas:backup/{date}".into()],
+            dir: PathBuf::from("/home/me"),
+        };
+        assert!(!saved.add("docs".into(), transfer.clone()));
+        saved.save().unwrap();
+
+        let saved = SavedStore::load(dir.path()).unwrap();
+        assert_eq!(saved.get("docs"), Some(&transfer));
+        assert_eq!(
+            transfer.command_line(),
+            "flux cp 'My Docs' nas:backup/{date}"
+        );
+        assert_eq!(quote("it's"), "'it'\\''s'");
+        assert_eq!(quote(""), "''");
+
+        assert!(validate_name("nightly-docs_2").is_ok());
+        assert!(validate_name("two words").is_err());
+        assert!(validate_name("").is_err());
+    }
+
+    #[test]
+    fn last_command_is_kept() {
+        let dir = tempfile::TempDir::new().unwrap();
+        assert_eq!(read_last(dir.path()).unwrap(), None);
+        let transfer = SavedTransfer {
+            args: argv("sync a b"),
+            dir: PathBuf::from("/srv"),
+        };
+        write_last(dir.path(), &transfer).unwrap();
+        assert_eq!(read_last(dir.path()).unwrap(), Some(transfer));
+    }
+}
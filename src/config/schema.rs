@@ -0,0 +1,637 @@
+//! Schema of config.toml: every key, its type, and value checks.
+//!
+//! `flux config` uses it to read and write single keys with typed
+//! validation, `load_config` to apply `FLUX_*` environment overrides, and
+//! `flux config validate` to report unknown keys and bad values. Keys under
+//! user-named tables (`receive.devices.NAME.fingerprint`) are matched with
+//! `*` segments.
+
+use crate::error::FluxError;
+
+/// Type and constraints of a config value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValueKind {
+    Bool,
+    /// Integer >= 0
+    Count,
+    /// Integer >= 1
+    Positive,
+    /// TCP port, 1-65535
+    Port,
+    Str,
+    /// One of a fixed set of names
+    Choice(&'static [&'static str]),
+    /// Size like "50GB" or "500MiB"
+    Size,
+    /// Daily window `HH:MM-HH:MM`
+    Window,
+    /// `PORT` or `FIRST-LAST`
+    PortRange,
+}
+
+impl ValueKind {
+    /// Short description used in `flux config list` and errors.
+    pub fn describe(self) -> String {
+        match self {
+            ValueKind::Bool => "true or false".to_string(),
+            ValueKind::Count => "a whole number".to_string(),
+            ValueKind::Positive => "a whole number of at least 1".to_string(),
+            ValueKind::Port => "a port number (1-65535)".to_string(),
+            ValueKind::Str => "text".to_string(),
+            ValueKind::Choice(names) => format!("one of {}", names.join(", ")),
+            ValueKind::Size => "a size like 10GB or 500MiB".to_string(),
+            ValueKind::Window => "a time window HH:MM-HH:MM (e.g. 00:00-06:00)".to_string(),
+            ValueKind::PortRange => "PORT or FIRST-LAST (e.g. 9741-9745)".to_string(),
+        }
+    }
+}
+
+/// One key of config.toml.
+#[derive(Debug, Clone, Copy)]
+pub struct ConfigKey {
+    /// Dotted path; `*` matches any single segment
+    pub name: &'static str,
+    pub kind: ValueKind,
+    pub help: &'static str,
+}
+
+impl ConfigKey {
+    /// Whether the key has a fixed path (no `*`), so it can have an
+    /// environment override and a default.
+    pub fn is_fixed(&self) -> bool {
+        !self.name.contains('*')
+    }
+
+    /// Environment variable overriding this key, e.g. `FLUX_RECEIVE_PORT`.
+    pub fn env_var(&self) -> String {
+        format!("FLUX_{}", self.name.replace('.', "_").to_uppercase())
+    }
+
+    fn matches(&self, key: &str) -> bool {
+        let pattern: Vec<&str> = self.name.split('.').collect();
+        let segments: Vec<&str> = key.split('.').collect();
+        pattern.len() == segments.len()
+            && pattern
+                .iter()
+                .zip(&segments)
+                .all(|(p, s)| !s.is_empty() && (*p == "*" || p == s))
+    }
+}
+
+const CONFLICT: &[&str] = &["overwrite", "skip", "rename", "ask", "prompt"];
+const FAILURE: &[&str] = &["retry", "skip", "continue", "pause", "abort"];
+const VERBOSITY: &[&str] = &["quiet", "normal", "verbose", "trace"];
+const IO_PROFILE: &[&str] = &["auto", "hdd", "ssd", "network"];
+const STORAGE_FULL: &[&str] = &["reject", "prune"];
+
+/// Every key `FluxConfig` reads, in config.toml order.
+pub const KEYS: &[ConfigKey] = &[
+    ConfigKey {
+        name: "verbosity",
+        kind: ValueKind::Choice(VERBOSITY),
+        help: "Default output level",
+    },
+    ConfigKey {
+        name: "conflict",
+        kind: ValueKind::Choice(CONFLICT),
+        help: "What to do when the destination file exists",
+    },
+    ConfigKey {
+        name: "conflict_fallback",
+        kind: ValueKind::Choice(CONFLICT),
+        help: "Conflict strategy for `ask` when stdin is not a terminal",
+    },
+    ConfigKey {
+        name: "failure",
+        kind: ValueKind::Choice(FAILURE),
+        help: "What to do when copying a file fails",
+    },
+    ConfigKey {
+        name: "retry_count",
+        kind: ValueKind::Count,
+        help: "Retries per file with the retry strategy",
+    },
+    ConfigKey {
+        name: "retry_backoff_ms",
+        kind: ValueKind::Count,
+        help: "Initial retry delay in milliseconds (doubles each attempt)",
+    },
+    ConfigKey {
+        name: "default_destination",
+        kind: ValueKind::Str,
+        help: "Destination used when none is given",
+    },
+    ConfigKey {
+        name: "history_limit",
+        kind: ValueKind::Count,
+        help: "Most entries kept in the transfer history",
+    },
+    ConfigKey {
+        name: "exclude_hidden",
+        kind: ValueKind::Bool,
+        help: "Skip hidden files and directories by default",
+    },
+    ConfigKey {
+        name: "notify",
+        kind: ValueKind::Bool,
+        help: "Desktop notification when a long transfer finishes",
+    },
+    ConfigKey {
+        name: "notify_after_secs",
+        kind: ValueKind::Count,
+        help: "Shortest transfer, in seconds, that notifies",
+    },
+    ConfigKey {
+        name: "proxy",
+        kind: ValueKind::Str,
+        help: "Proxy URL for WebDAV (http://, https://, socks5://); socks5:// also for send",
+    },
+    ConfigKey {
+        name: "io_profile",
+        kind: ValueKind::Choice(IO_PROFILE),
+        help: "Storage tuning of cp and sync: auto detects spinning disks and network mounts",
+    },
+    ConfigKey {
+        name: "queue.bulk_window",
+        kind: ValueKind::Window,
+        help: "Local time window in which `flux daemon` runs bulk entries",
+    },
+    ConfigKey {
+        name: "hooks.on_success",
+        kind: ValueKind::Str,
+        help: "Command run after a transfer succeeds",
+    },
+    ConfigKey {
+        name: "hooks.on_failure",
+        kind: ValueKind::Str,
+        help: "Command run after a transfer fails",
+    },
+    ConfigKey {
+        name: "hooks.on_complete",
+        kind: ValueKind::Str,
+        help: "Command run after every transfer",
+    },
+    ConfigKey {
+        name: "receive.output_dir",
+        kind: ValueKind::Str,
+        help: "Directory for received files ({date}, {hostname}, ... expanded)",
+    },
+    ConfigKey {
+        name: "receive.port",
+        kind: ValueKind::Port,
+        help: "Port `flux receive` listens on",
+    },
+    ConfigKey {
+        name: "receive.device_name",
+        kind: ValueKind::Str,
+        help: "Device name the receiver advertises",
+    },
+    ConfigKey {
+        name: "receive.daily_quota",
+        kind: ValueKind::Size,
+        help: "Most bytes accepted per day from all senders",
+    },
+    ConfigKey {
+        name: "receive.device_daily_quota",
+        kind: ValueKind::Size,
+        help: "Most bytes accepted per day from any one sender",
+    },
+    ConfigKey {
+        name: "receive.device_quotas.*",
+        kind: ValueKind::Size,
+        help: "Daily quota of one sender device",
+    },
+    ConfigKey {
+        name: "receive.free_space_margin",
+        kind: ValueKind::Size,
+        help: "Space that must stay free on the output disk after a file",
+    },
+    ConfigKey {
+        name: "receive.preallocate",
+        kind: ValueKind::Bool,
+        help: "Allocate incoming files at full size before receiving",
+    },
+    ConfigKey {
+        name: "receive.per_sender_dirs",
+        kind: ValueKind::Bool,
+        help: "Put each verified sender's files in a subdirectory named after it",
+    },
+    ConfigKey {
+        name: "receive.storage_quota",
+        kind: ValueKind::Size,
+        help: "Most bytes stored in the output directory at once",
+    },
+    ConfigKey {
+        name: "receive.device_storage_quota",
+        kind: ValueKind::Size,
+        help: "Most bytes stored in any one sender's directory at once",
+    },
+    ConfigKey {
+        name: "receive.device_storage_quotas.*",
+        kind: ValueKind::Size,
+        help: "Storage quota of one sender device",
+    },
+    ConfigKey {
+        name: "receive.storage_full",
+        kind: ValueKind::Choice(STORAGE_FULL),
+        help: "When a file does not fit a storage quota: reject it or prune the oldest",
+    },
+    ConfigKey {
+        name: "receive.connections_per_minute",
+        kind: ValueKind::Positive,
+        help: "Most connections accepted from one IP address per minute",
+    },
+    ConfigKey {
+        name: "receive.ban_after_failures",
+        kind: ValueKind::Count,
+        help: "Failed handshakes in a row that ban an IP address (default 5, 0 = never)",
+    },
+    ConfigKey {
+        name: "receive.ban_secs",
+        kind: ValueKind::Positive,
+        help: "How long a banned IP address is refused, in seconds (default 600)",
+    },
+    ConfigKey {
+        name: "receive.max_receive_rate",
+        kind: ValueKind::Str,
+        help: "Most bytes per second received from all senders together (e.g. 50MB/s)",
+    },
+    ConfigKey {
+        name: "receive.devices.*.fingerprint",
+        kind: ValueKind::Str,
+        help: "Key fingerprint of a sender accepted without a prompt",
+    },
+    ConfigKey {
+        name: "receive.devices.*.output_dir",
+        kind: ValueKind::Str,
+        help: "Directory for files from this sender",
+    },
+    ConfigKey {
+        name: "discovery.subnet_scan",
+        kind: ValueKind::Bool,
+        help: "Scan the local subnet when mDNS finds no devices",
+    },
+    ConfigKey {
+        name: "discovery.scan_ports",
+        kind: ValueKind::PortRange,
+        help: "Ports probed by the subnet scan",
+    },
+    ConfigKey {
+        name: "discovery.scan_timeout_ms",
+        kind: ValueKind::Count,
+        help: "Timeout of each subnet probe in milliseconds",
+    },
+    ConfigKey {
+        name: "discovery.scan_concurrency",
+        kind: ValueKind::Positive,
+        help: "Most subnet probes in flight at once",
+    },
+    ConfigKey {
+        name: "backends.retries",
+        kind: ValueKind::Count,
+        help: "Retries of an SFTP/SMB/WebDAV operation after a connection error",
+    },
+    ConfigKey {
+        name: "backends.retry_backoff_ms",
+        kind: ValueKind::Count,
+        help: "First backend retry delay in milliseconds (doubles, with jitter)",
+    },
+    ConfigKey {
+        name: "backends.rclone_binary",
+        kind: ValueKind::Str,
+        help: "rclone executable used for rclone: locations (default: rclone on the PATH)",
+    },
+    ConfigKey {
+        name: "backends.max_monthly_gb.*",
+        kind: ValueKind::Positive,
+        help: "GB a backend (sftp, smb, webdav, rclone, p2p) may move per month",
+    },
+    ConfigKey {
+        name: "webdav.ca_bundle",
+        kind: ValueKind::Str,
+        help: "PEM file of extra CA certificates the WebDAV backend trusts",
+    },
+    ConfigKey {
+        name: "webdav.client_cert",
+        kind: ValueKind::Str,
+        help: "PEM client certificate for WebDAV servers requiring mTLS",
+    },
+    ConfigKey {
+        name: "webdav.client_key",
+        kind: ValueKind::Str,
+        help: "PEM private key of webdav.client_cert",
+    },
+    ConfigKey {
+        name: "webdav.pinned_sha256",
+        kind: ValueKind::Str,
+        help: "SHA-256 fingerprints (comma separated) WebDAV server certificates must match",
+    },
+    ConfigKey {
+        name: "audit.enabled",
+        kind: ValueKind::Bool,
+        help: "Record network handshakes, trust decisions and transfers in the audit log",
+    },
+    ConfigKey {
+        name: "audit.max_size",
+        kind: ValueKind::Size,
+        help: "Size at which the audit log is rotated",
+    },
+    ConfigKey {
+        name: "audit.keep",
+        kind: ValueKind::Count,
+        help: "Rotated audit logs to keep",
+    },
+];
+
+/// Look up a key, suggesting close names when it does not exist.
+pub fn find_key(name: &str) -> Result<&'static ConfigKey, FluxError> {
+    if let Some(key) = KEYS.iter().find(|k| k.matches(name)) {
+        return Ok(key);
+    }
+    let similar: Vec<&str> = KEYS
+        .iter()
+        .filter(|k| k.is_fixed())
+        .map(|k| k.name)
+        .filter(|k| {
+            let last = name.rsplit('.').next().unwrap_or(name);
+            !last.is_empty() && (k.contains(last) || name.contains(k))
+        })
+        .collect();
+    let hint = if similar.is_empty() {
+        "run `flux config list` to see all keys".to_string()
+    } else {
+        format!("did you mean {}?", similar.join(" or "))
+    };
+    Err(FluxError::Config(format!("Unknown config key '{}': {}", name, hint)))
+}
+
+/// A checked config value, ready to be written to config.toml.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConfigValue {
+    Bool(bool),
+    Int(i64),
+    Str(String),
+}
+
+impl ConfigValue {
+    pub fn to_toml(&self) -> toml::Value {
+        match self {
+            ConfigValue::Bool(b) => toml::Value::Boolean(*b),
+            ConfigValue::Int(i) => toml::Value::Integer(*i),
+            ConfigValue::Str(s) => toml::Value::String(s.clone()),
+        }
+    }
+
+    pub fn to_item(&self) -> toml_edit::Item {
+        match self {
+            ConfigValue::Bool(b) => toml_edit::value(*b),
+            ConfigValue::Int(i) => toml_edit::value(*i),
+            ConfigValue::Str(s) => toml_edit::value(s.as_str()),
+        }
+    }
+}
+
+/// Parse a value given on the command line (or in an environment
+/// variable) for `key`.
+pub fn parse_value(key: &ConfigKey, raw: &str) -> Result<ConfigValue, FluxError> {
+    let invalid = |why: String| {
+        FluxError::Config(format!("Invalid value '{}' for {}: {}", raw, key.name, why))
+    };
+    let value = match key.kind {
+        ValueKind::Bool => match raw.trim().to_lowercase().as_str() {
+            "true" | "yes" | "on" | "1" => ConfigValue::Bool(true),
+            "false" | "no" | "off" | "0" => ConfigValue::Bool(false),
+            _ => return Err(invalid(format!("expected {}", key.kind.describe()))),
+        },
+        ValueKind::Count | ValueKind::Positive | ValueKind::Port => {
+            let n: i64 = raw
+                .trim()
+                .parse()
+                .map_err(|_| invalid(format!("expected {}", key.kind.describe())))?;
+            ConfigValue::Int(n)
+        }
+        _ => ConfigValue::Str(raw.to_string()),
+    };
+    check_value(key, &value.to_toml()).map_err(invalid)?;
+    Ok(value)
+}
+
+/// Check a value read from config.toml against `key`'s type and
+/// constraints. The error says what was expected.
+pub fn check_value(key: &ConfigKey, value: &toml::Value) -> Result<(), String> {
+    let expected = || format!("expected {}", key.kind.describe());
+    match (key.kind, value) {
+        (ValueKind::Bool, toml::Value::Boolean(_)) => Ok(()),
+        (ValueKind::Count, toml::Value::Integer(n)) if *n >= 0 => Ok(()),
+        (ValueKind::Positive, toml::Value::Integer(n)) if *n >= 1 => Ok(()),
+        (ValueKind::Port, toml::Value::Integer(n)) if (1..=65535).contains(n) => Ok(()),
+        (ValueKind::Str, toml::Value::String(_)) => Ok(()),
+        (ValueKind::Choice(names), toml::Value::String(s)) => {
+            if names.contains(&s.as_str()) {
+                Ok(())
+            } else {
+                Err(expected())
+            }
+        }
+        (ValueKind::Size, toml::Value::String(s)) => s
+            .trim()
+            .parse::<bytesize::ByteSize>()
+            .map(|_| ())
+            .map_err(|_| expected()),
+        (ValueKind::Window, toml::Value::String(s)) => {
+            crate::queue::policy::TimeWindow::parse(s).map(|_| ()).map_err(|_| expected())
+        }
+        (ValueKind::PortRange, toml::Value::String(s)) => {
+            check_port_range(s).map_err(|_| expected())
+        }
+        _ => Err(expected()),
+    }
+}
+
+#[cfg(feature = "net")]
+fn check_port_range(s: &str) -> Result<(), FluxError> {
+    crate::discovery::scan::parse_port_range(s).map(|_| ())
+}
+
+/// Without network support nothing reads `discovery`; any text will do.
+#[cfg(not(feature = "net"))]
+fn check_port_range(_s: &str) -> Result<(), FluxError> {
+    Ok(())
+}
+
+/// Every leaf of a TOML table as `(dotted.key, value)`, sorted by key.
+pub fn flatten(table: &toml::Table) -> Vec<(String, toml::Value)> {
+    let mut out = Vec::new();
+    flatten_into(table, "", &mut out);
+    out
+}
+
+fn flatten_into(table: &toml::Table, prefix: &str, out: &mut Vec<(String, toml::Value)>) {
+    for (name, value) in table {
+        let key = if prefix.is_empty() {
+            name.clone()
+        } else {
+            format!("{}.{}", prefix, name)
+        };
+        match value {
+            toml::Value::Table(inner) => flatten_into(inner, &key, out),
+            _ => out.push((key, value.clone())),
+        }
+    }
+}
+
+/// Value at a dotted key, if present.
+pub fn lookup<'a>(table: &'a toml::Table, key: &str) -> Option<&'a toml::Value> {
+    let mut segments = key.split('.');
+    let mut current = table.get(segments.next()?)?;
+    for segment in segments {
+        current = current.as_table()?.get(segment)?;
+    }
+    Some(current)
+}
+
+/// Set a dotted key, creating tables on the way.
+pub fn insert(table: &mut toml::Table, key: &str, value: toml::Value) {
+    match key.split_once('.') {
+        None => {
+            table.insert(key.to_string(), value);
+        }
+        Some((head, rest)) => {
+            let entry = table
+                .entry(head.to_string())
+                .or_insert_with(|| toml::Value::Table(toml::Table::new()));
+            if !entry.is_table() {
+                *entry = toml::Value::Table(toml::Table::new());
+            }
+            if let toml::Value::Table(inner) = entry {
+                insert(inner, rest, value);
+            }
+        }
+    }
+}
+
+/// Apply `FLUX_<KEY>` environment overrides to a parsed config file.
+///
+/// `env` looks up a variable (`std::env::var` outside tests). Returns the
+/// keys that were overridden.
+pub fn apply_env_overrides(
+    table: &mut toml::Table,
+    env: impl Fn(&str) -> Option<String>,
+) -> Result<Vec<&'static ConfigKey>, FluxError> {
+    let mut applied = Vec::new();
+    for key in KEYS.iter().filter(|k| k.is_fixed()) {
+        let var = key.env_var();
+        let Some(raw) = env(&var) else {
+            continue;
+        };
+        let value = parse_value(key, &raw).map_err(|e| match e {
+            FluxError::Config(msg) => FluxError::Config(format!("{} (from {})", msg, var)),
+            other => other,
+        })?;
+        insert(table, key.name, value.to_toml());
+        applied.push(key);
+    }
+    Ok(applied)
+}
+
+/// Problems in a parsed config file: unknown keys and bad values.
+pub fn validate(table: &toml::Table) -> Vec<String> {
+    let mut problems = Vec::new();
+    for (name, value) in flatten(table) {
+        match find_key(&name) {
+            Ok(key) => {
+                if let Err(why) = check_value(key, &value) {
+                    problems.push(format!("{}: {}, got {}", name, why, value));
+                }
+            }
+            Err(FluxError::Config(msg)) => problems.push(msg),
+            Err(e) => problems.push(e.to_string()),
+        }
+    }
+    problems
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table(s: &str) -> toml::Table {
+        toml::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn find_key_matches_wildcards_and_suggests() {
+        assert_eq!(find_key("receive.port").unwrap().kind, ValueKind::Port);
+        assert_eq!(
+            find_key("receive.devices.nas.fingerprint").unwrap().name,
+            "receive.devices.*.fingerprint"
+        );
+        let err = find_key("retry").unwrap_err().to_string();
+        assert!(err.contains("did you mean retry_count"), "{}", err);
+        assert!(find_key("receive.devices.nas").is_err());
+    }
+
+    #[test]
+    fn parse_value_checks_types_and_formats() {
+        let port = find_key("receive.port").unwrap();
+        assert_eq!(parse_value(port, "9750").unwrap(), ConfigValue::Int(9750));
+        assert!(parse_value(port, "70000").is_err());
+        assert!(parse_value(port, "http").is_err());
+
+        let notify = find_key("notify").unwrap();
+        assert_eq!(parse_value(notify, "yes").unwrap(), ConfigValue::Bool(true));
+
+        let quota = find_key("receive.daily_quota").unwrap();
+        assert!(parse_value(quota, "50GB").is_ok());
+        let err = parse_value(quota, "lots").unwrap_err().to_string();
+        assert!(err.contains("a size like 10GB"), "{}", err);
+
+        let conflict = find_key("conflict").unwrap();
+        assert!(parse_value(conflict, "rename").is_ok());
+        assert!(parse_value(conflict, "merge").is_err());
+
+        let window = find_key("queue.bulk_window").unwrap();
+        assert!(parse_value(window, "22:00-06:00").is_ok());
+        assert!(parse_value(window, "night").is_err());
+    }
+
+    #[test]
+    fn env_overrides_replace_file_values() {
+        let mut config = table("retry_count = 3\n[receive]\nport = 9741\n");
+        let applied = apply_env_overrides(&mut config, |var| match var {
+            "FLUX_RECEIVE_PORT" => Some("9800".to_string()),
+            "FLUX_NOTIFY" => Some("true".to_string()),
+            _ => None,
+        })
+        .unwrap();
+        assert_eq!(applied.len(), 2);
+        assert_eq!(lookup(&config, "receive.port"), Some(&toml::Value::Integer(9800)));
+        assert_eq!(lookup(&config, "notify"), Some(&toml::Value::Boolean(true)));
+        assert_eq!(lookup(&config, "retry_count"), Some(&toml::Value::Integer(3)));
+
+        let err = apply_env_overrides(&mut config, |var| {
+            (var == "FLUX_RETRY_COUNT").then(|| "many".to_string())
+        })
+        .unwrap_err()
+        .to_string();
+        assert!(err.contains("FLUX_RETRY_COUNT"), "{}", err);
+    }
+
+    #[test]
+    fn validate_reports_unknown_keys_and_bad_values() {
+        let problems = validate(&table(
+            "retry_count = -1\nretries = 2\n[receive]\ndaily_quota = \"lots\"\n\
+             [receive.devices.nas]\nfingerprint = \"q83vEjRWeJCrze8S\"\n",
+        ));
+        assert_eq!(problems.len(), 3, "{:?}", problems);
+        assert!(problems.iter().any(|p| p.starts_with("retry_count: expected a whole number")));
+        assert!(problems.iter().any(|p| p.contains("Unknown config key 'retries'")));
+        assert!(problems.iter().any(|p| p.starts_with("receive.daily_quota: expected a size")));
+    }
+
+    #[test]
+    fn every_fixed_key_has_an_env_var() {
+        assert_eq!(find_key("receive.port").unwrap().env_var(), "FLUX_RECEIVE_PORT");
+        assert_eq!(find_key("history_limit").unwrap().env_var(), "FLUX_HISTORY_LIMIT");
+    }
+}
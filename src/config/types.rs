@@ -1,6 +1,9 @@
+use std::collections::BTreeMap;
+
 use serde::{Deserialize, Serialize};
 
 use crate::error::FluxError;
+use crate::transfer::io_profile::IoProfile;
 
 /// Verbosity level controlling tracing output.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
@@ -56,9 +59,12 @@ pub enum ConflictStrategy {
     Overwrite,
     /// Skip the file (do not copy)
     Skip,
-    /// Rename the new file with a numeric suffix (e.g., file_1.txt)
+    /// Rename the new file with a numeric suffix (e.g., `file (1).txt`)
     Rename,
-    /// Ask the user interactively (falls back to Skip if stdin is not a TTY)
+    /// Ask the user interactively when the files differ (falls back to
+    /// `conflict_fallback` if stdin is not a TTY)
+    #[serde(alias = "prompt")]
+    #[value(alias = "prompt")]
     Ask,
 }
 
@@ -69,9 +75,49 @@ pub enum FailureStrategy {
     /// Retry the operation with exponential backoff
     Retry,
     /// Skip the failed file and continue
+    #[serde(alias = "continue")]
+    #[value(alias = "continue")]
     Skip,
     /// Pause and prompt the user before continuing
     Pause,
+    /// Stop the whole copy at the first failed file
+    Abort,
+}
+
+/// `--on-error` value: a strategy, with an optional retry count for
+/// `retry:N` (e.g. `retry:3`) overriding `retry_count` from config.toml.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FailurePolicy {
+    pub strategy: FailureStrategy,
+    pub retries: Option<u32>,
+}
+
+impl std::str::FromStr for FailurePolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        use clap::ValueEnum;
+
+        let (name, retries) = match s.split_once(':') {
+            Some((name, count)) => {
+                let count = count
+                    .parse::<u32>()
+                    .map_err(|_| format!("invalid retry count '{}'", count))?;
+                (name, Some(count))
+            }
+            None => (s, None),
+        };
+        let strategy = FailureStrategy::from_str(name, true).map_err(|_| {
+            format!(
+                "invalid policy '{}' (expected retry, retry:N, skip/continue, pause or abort)",
+                s
+            )
+        })?;
+        if retries.is_some() && strategy != FailureStrategy::Retry {
+            return Err(format!("only retry takes a count, got '{}'", s));
+        }
+        Ok(Self { strategy, retries })
+    }
 }
 
 /// Application configuration loaded from config.toml with serde defaults.
@@ -83,11 +129,227 @@ pub enum FailureStrategy {
 pub struct FluxConfig {
     pub verbosity: Verbosity,
     pub conflict: ConflictStrategy,
+    /// What `--on-conflict ask` does when stdin is not a terminal.
+    pub conflict_fallback: ConflictStrategy,
     pub failure: FailureStrategy,
     pub retry_count: u32,
     pub retry_backoff_ms: u64,
     pub default_destination: Option<String>,
     pub history_limit: usize,
+    /// Skip hidden files and directories by default (`--include-hidden` overrides).
+    pub exclude_hidden: bool,
+    /// Show a desktop notification when a long transfer finishes (`--notify`).
+    pub notify: bool,
+    /// Minimum transfer duration in seconds before a notification is shown.
+    pub notify_after_secs: u64,
+    /// Proxy URL (`http://`, `https://` or `socks5://`) for WebDAV, used
+    /// instead of `HTTP_PROXY`/`HTTPS_PROXY`; a `socks5://` proxy also
+    /// carries `send` and code-phrase `receive` connections (`--socks5`).
+    pub proxy: Option<String>,
+    /// Storage tuning of `cp` and `sync` when `--io-profile` is not given:
+    /// auto (detect), hdd, ssd or network; see `transfer::io_profile`.
+    pub io_profile: IoProfile,
+    pub queue: QueueConfig,
+    pub hooks: HooksConfig,
+    pub receive: ReceiveConfig,
+    pub discovery: DiscoveryConfig,
+    pub backends: BackendsConfig,
+    pub webdav: WebDavConfig,
+    pub audit: AuditConfig,
+}
+
+/// Queue draining policy (`[queue]` table in config.toml).
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct QueueConfig {
+    /// Daily window in which `flux daemon` drains bulk entries, `HH:MM-HH:MM`
+    /// in local time (e.g. "00:00-06:00"). Unset means bulk entries run anytime.
+    pub bulk_window: Option<String>,
+}
+
+/// Post-transfer hook commands (`[hooks]` table in config.toml).
+///
+/// Each command runs through the platform shell with `FLUX_*` variables
+/// describing the transfer; see `transfer::hooks`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HooksConfig {
+    /// Runs after a transfer completes (or had nothing to do)
+    pub on_success: Option<String>,
+    /// Runs after a transfer fails
+    pub on_failure: Option<String>,
+    /// Runs after every transfer, whatever the outcome
+    pub on_complete: Option<String>,
+}
+
+/// Discovery fallback for networks that block mDNS (`[discovery]` table in
+/// config.toml).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DiscoveryConfig {
+    /// Probe the local subnet when mDNS finds no devices
+    pub subnet_scan: bool,
+    /// Ports to probe on each address, `PORT` or `FIRST-LAST` (e.g. "9741-9745")
+    pub scan_ports: String,
+    /// Connect and answer timeout of each probe, in milliseconds
+    pub scan_timeout_ms: u64,
+    /// Most probes in flight at once
+    pub scan_concurrency: usize,
+}
+
+impl Default for DiscoveryConfig {
+    fn default() -> Self {
+        Self {
+            subnet_scan: true,
+            scan_ports: "9741".to_string(),
+            scan_timeout_ms: 300,
+            scan_concurrency: 64,
+        }
+    }
+}
+
+/// Retries of network backend operations and monthly usage caps
+/// (`[backends]` table in config.toml); see `backend::retry` and
+/// `transfer::usage`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BackendsConfig {
+    /// Retries of an SFTP, SMB or WebDAV operation that failed on a
+    /// connection error
+    pub retries: u32,
+    /// Delay before the first retry, in milliseconds; doubled for each
+    /// further one and jittered
+    pub retry_backoff_ms: u64,
+    /// rclone executable for `rclone:` locations (`rclone` on the PATH when
+    /// unset)
+    pub rclone_binary: Option<String>,
+    /// GB a backend (`sftp`, `smb`, `webdav`, `rclone` or `p2p`) may move
+    /// per calendar month before its transfers are refused
+    /// (`[backends.max_monthly_gb]`)
+    pub max_monthly_gb: BTreeMap<String, u64>,
+}
+
+impl Default for BackendsConfig {
+    fn default() -> Self {
+        Self {
+            retries: 3,
+            retry_backoff_ms: 500,
+            rclone_binary: None,
+            max_monthly_gb: BTreeMap::new(),
+        }
+    }
+}
+
+/// TLS settings of the WebDAV backend (`[webdav]` table in config.toml);
+/// see `backend::tls`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WebDavConfig {
+    /// PEM file of extra CA certificates to trust (e.g. a private CA)
+    pub ca_bundle: Option<String>,
+    /// PEM certificate (chain) presented to servers that ask for one (mTLS)
+    pub client_cert: Option<String>,
+    /// PEM private key of `client_cert`
+    pub client_key: Option<String>,
+    /// SHA-256 fingerprints of the server certificates to accept, comma
+    /// separated (`openssl x509 -noout -fingerprint -sha256`); checked on
+    /// top of the CA and host name
+    pub pinned_sha256: Option<String>,
+}
+
+/// Audit log of network transfer events (`[audit]` table in config.toml);
+/// see `net::audit`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AuditConfig {
+    /// Record handshakes, trust decisions and transfers in `audit.jsonl`
+    pub enabled: bool,
+    /// Size at which the log is rotated (default 10MiB)
+    pub max_size: Option<String>,
+    /// Rotated logs kept next to the current one
+    pub keep: usize,
+}
+
+impl Default for AuditConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_size: None,
+            keep: 5,
+        }
+    }
+}
+
+/// Receiver settings (`[receive]` table in config.toml).
+///
+/// Sizes are strings like "50GB" or "500MiB"; see `net::quota`. A running
+/// `flux receive` re-reads this table on SIGHUP.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ReceiveConfig {
+    /// Directory for received files when `--output` is not given
+    /// ({date}, {hostname}, ... expanded per transfer)
+    pub output_dir: Option<String>,
+    /// Port to listen on when `--port` is not given
+    pub port: Option<u16>,
+    /// Device name to advertise when `--name` is not given
+    pub device_name: Option<String>,
+    /// Most bytes accepted per day from all senders combined
+    pub daily_quota: Option<String>,
+    /// Most bytes accepted per day from any one sender device
+    pub device_daily_quota: Option<String>,
+    /// Per-device overrides of `device_daily_quota`, keyed by device name
+    pub device_quotas: BTreeMap<String, String>,
+    /// Space that must stay free on the output disk after a file is
+    /// received (default 100MiB); larger files are refused up front
+    pub free_space_margin: Option<String>,
+    /// Allocate incoming files at their full size before receiving
+    pub preallocate: bool,
+    /// Give each verified sender its own subdirectory of the output
+    /// directory, named after its device (unless it has an `output_dir`)
+    pub per_sender_dirs: bool,
+    /// Most bytes stored in the output directory at once
+    pub storage_quota: Option<String>,
+    /// Most bytes stored at once in any one sender's own directory
+    pub device_storage_quota: Option<String>,
+    /// Per-device overrides of `device_storage_quota`, keyed by device name
+    pub device_storage_quotas: BTreeMap<String, String>,
+    /// What to do with a file that does not fit a storage quota
+    pub storage_full: StorageFull,
+    /// Most connections accepted from one IP address per minute
+    pub connections_per_minute: Option<u32>,
+    /// Failed handshakes in a row after which an IP address is banned
+    /// (default 5, 0 never bans)
+    pub ban_after_failures: Option<u32>,
+    /// How long a ban lasts, in seconds (default 600)
+    pub ban_secs: Option<u64>,
+    /// Most bytes per second received from all senders together ("50MB/s")
+    pub max_receive_rate: Option<String>,
+    /// Senders accepted without a trust prompt, keyed by device name
+    /// (`[receive.devices.NAME]`)
+    pub devices: BTreeMap<String, AllowedDevice>,
+}
+
+/// What a receiver does with a file that does not fit its storage quota
+/// (`[receive] storage_full`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StorageFull {
+    /// Refuse the file
+    #[default]
+    Reject,
+    /// Delete the oldest stored files until it fits
+    Prune,
+}
+
+/// An allowlisted sender (`[receive.devices.NAME]` in config.toml).
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct AllowedDevice {
+    /// Public key fingerprint as shown by `flux trust list` (the base64 key
+    /// or a prefix of at least 16 characters)
+    pub fingerprint: String,
+    /// Directory for this sender's files, in place of `output_dir`
+    pub output_dir: Option<String>,
 }
 
 impl Default for FluxConfig {
@@ -95,11 +357,24 @@ impl Default for FluxConfig {
         Self {
             verbosity: Verbosity::Normal,
             conflict: ConflictStrategy::Ask,
+            conflict_fallback: ConflictStrategy::Skip,
             failure: FailureStrategy::Retry,
             retry_count: 3,
             retry_backoff_ms: 1000,
             default_destination: None,
             history_limit: 1000,
+            exclude_hidden: false,
+            notify: false,
+            notify_after_secs: 30,
+            proxy: None,
+            io_profile: IoProfile::Auto,
+            queue: QueueConfig::default(),
+            hooks: HooksConfig::default(),
+            receive: ReceiveConfig::default(),
+            discovery: DiscoveryConfig::default(),
+            backends: BackendsConfig::default(),
+            webdav: WebDavConfig::default(),
+            audit: AuditConfig::default(),
         }
     }
 }
@@ -108,17 +383,25 @@ impl Default for FluxConfig {
 ///
 /// Config is NOT auto-created on first run. Only written when the user
 /// explicitly configures something. Invalid TOML produces a Config error.
+/// `FLUX_<KEY>` environment variables (e.g. `FLUX_RECEIVE_PORT`) override
+/// values from the file; see `config::schema`.
 pub fn load_config() -> Result<FluxConfig, FluxError> {
-    let config_dir = crate::config::paths::flux_config_dir()?;
-    let config_path = config_dir.join("config.toml");
-    if config_path.exists() {
-        let contents = std::fs::read_to_string(&config_path)?;
-        let config: FluxConfig = toml::from_str(&contents)
-            .map_err(|e| FluxError::Config(format!("Invalid config.toml: {}", e)))?;
-        Ok(config)
-    } else {
-        Ok(FluxConfig::default())
+    let mut table = read_config_table()?;
+    crate::config::schema::apply_env_overrides(&mut table, |var| std::env::var(var).ok())?;
+    FluxConfig::deserialize(toml::Value::Table(table))
+        .map_err(|e| FluxError::Config(format!("Invalid config.toml: {}", e)))
+}
+
+/// Parse config.toml without applying defaults or environment overrides.
+/// An absent file is an empty table.
+pub fn read_config_table() -> Result<toml::Table, FluxError> {
+    let config_path = crate::config::paths::config_file()?;
+    if !config_path.exists() {
+        return Ok(toml::Table::new());
     }
+    let contents = std::fs::read_to_string(&config_path)?;
+    toml::from_str(&contents)
+        .map_err(|e| FluxError::Config(format!("Invalid config.toml: {}", e)))
 }
 
 #[cfg(test)]
@@ -134,6 +417,9 @@ mod tests {
         assert_eq!(config.retry_backoff_ms, 1000);
         assert!(config.default_destination.is_none());
         assert_eq!(config.history_limit, 1000);
+        assert!(!config.exclude_hidden);
+        assert!(!config.notify);
+        assert_eq!(config.notify_after_secs, 30);
         assert_eq!(config.verbosity, Verbosity::Normal);
     }
 
@@ -142,21 +428,85 @@ mod tests {
         let config = FluxConfig {
             verbosity: Verbosity::Verbose,
             conflict: ConflictStrategy::Skip,
+            conflict_fallback: ConflictStrategy::Rename,
             failure: FailureStrategy::Pause,
             retry_count: 5,
             retry_backoff_ms: 2000,
             default_destination: Some("/tmp/dest".to_string()),
             history_limit: 500,
+            exclude_hidden: true,
+            notify: true,
+            notify_after_secs: 300,
+            proxy: Some("socks5://proxy.corp:1080".to_string()),
+            io_profile: IoProfile::Hdd,
+            queue: QueueConfig {
+                bulk_window: Some("00:00-06:00".to_string()),
+            },
+            hooks: HooksConfig {
+                on_success: Some("notify-send done".to_string()),
+                ..Default::default()
+            },
+            receive: ReceiveConfig {
+                daily_quota: Some("50GB".to_string()),
+                free_space_margin: Some("2GB".to_string()),
+                preallocate: true,
+                per_sender_dirs: true,
+                storage_quota: Some("1TB".to_string()),
+                storage_full: StorageFull::Prune,
+                port: Some(9750),
+                devices: BTreeMap::from([(
+                    "nas".to_string(),
+                    AllowedDevice {
+                        fingerprint: "q83vEjRWeJCrze8S".to_string(),
+                        output_dir: Some("/srv/inbox/nas".to_string()),
+                    },
+                )]),
+                ..Default::default()
+            },
+            discovery: DiscoveryConfig {
+                scan_ports: "9741-9745".to_string(),
+                ..Default::default()
+            },
+            backends: BackendsConfig {
+                retries: 6,
+                ..Default::default()
+            },
+            webdav: WebDavConfig {
+                ca_bundle: Some("/etc/flux/corp-ca.pem".to_string()),
+                ..Default::default()
+            },
+            audit: AuditConfig {
+                max_size: Some("50MB".to_string()),
+                ..Default::default()
+            },
         };
         let toml_str = toml::to_string_pretty(&config).expect("serialize");
         let loaded: FluxConfig = toml::from_str(&toml_str).expect("deserialize");
         assert_eq!(loaded.conflict, ConflictStrategy::Skip);
+        assert_eq!(loaded.conflict_fallback, ConflictStrategy::Rename);
         assert_eq!(loaded.failure, FailureStrategy::Pause);
         assert_eq!(loaded.retry_count, 5);
         assert_eq!(loaded.retry_backoff_ms, 2000);
         assert_eq!(loaded.default_destination, Some("/tmp/dest".to_string()));
         assert_eq!(loaded.history_limit, 500);
+        assert!(loaded.exclude_hidden);
+        assert!(loaded.notify);
+        assert_eq!(loaded.notify_after_secs, 300);
+        assert_eq!(loaded.proxy.as_deref(), Some("socks5://proxy.corp:1080"));
         assert_eq!(loaded.verbosity, Verbosity::Verbose);
+        assert_eq!(loaded.queue.bulk_window.as_deref(), Some("00:00-06:00"));
+        assert_eq!(loaded.backends.retries, 6);
+        assert_eq!(loaded.backends.retry_backoff_ms, 500);
+        assert_eq!(loaded.webdav.ca_bundle.as_deref(), Some("/etc/flux/corp-ca.pem"));
+        assert!(loaded.webdav.pinned_sha256.is_none());
+        assert_eq!(loaded.hooks.on_success.as_deref(), Some("notify-send done"));
+        assert!(loaded.hooks.on_failure.is_none());
+        assert_eq!(loaded.receive.daily_quota.as_deref(), Some("50GB"));
+        assert_eq!(loaded.receive, config.receive);
+        assert_eq!(loaded.discovery.scan_ports, "9741-9745");
+        assert!(loaded.discovery.subnet_scan);
+        assert_eq!(loaded.audit, config.audit);
+        assert!(loaded.audit.enabled);
     }
 
     #[test]
@@ -209,6 +559,8 @@ retry_count = 10
             let loaded: Wrapper = toml::from_str(&toml_str).expect("deserialize");
             assert_eq!(w, loaded);
         }
+        let prompt: Wrapper = toml::from_str("conflict = \"prompt\"").expect("alias");
+        assert_eq!(prompt.conflict, ConflictStrategy::Ask);
     }
 
     #[test]
@@ -220,6 +572,7 @@ retry_count = 10
             FailureStrategy::Retry,
             FailureStrategy::Skip,
             FailureStrategy::Pause,
+            FailureStrategy::Abort,
         ] {
             let w = Wrapper { failure: *strategy };
             let toml_str = toml::to_string(&w).expect("serialize");
@@ -227,4 +580,27 @@ retry_count = 10
             assert_eq!(w, loaded);
         }
     }
+
+    #[test]
+    fn failure_policy_parses_retry_count_and_aliases() {
+        let retry: FailurePolicy = "retry:3".parse().unwrap();
+        assert_eq!(retry.strategy, FailureStrategy::Retry);
+        assert_eq!(retry.retries, Some(3));
+
+        let cont: FailurePolicy = "continue".parse().unwrap();
+        assert_eq!(cont.strategy, FailureStrategy::Skip);
+        assert_eq!("abort".parse::<FailurePolicy>().unwrap().retries, None);
+
+        assert!("abort:2".parse::<FailurePolicy>().is_err());
+        assert!("retry:x".parse::<FailurePolicy>().is_err());
+        assert!("explode".parse::<FailurePolicy>().is_err());
+    }
+
+    #[test]
+    fn continue_is_an_alias_for_skip_in_config() {
+        #[derive(Deserialize)]
+        struct Wrapper { failure: FailureStrategy }
+        let loaded: Wrapper = toml::from_str("failure = \"continue\"").unwrap();
+        assert_eq!(loaded.failure, FailureStrategy::Skip);
+    }
 }
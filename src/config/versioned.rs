@@ -0,0 +1,126 @@
+//! Format versions of the state files flux writes for itself.
+//!
+//! Resume manifests carry a `version` number, as did `queue.json` and
+//! `history.json` before they moved into the state database (`crate::state`),
+//! which imports them through these formats. Loading goes through
+//! `Format::load`: a file from an older flux is migrated step by step to the
+//! current format in memory, while a file from a newer flux is refused with
+//! `FluxError::NewerFormat` rather than misread or replaced. Files written
+//! before versioning count as version 1.
+
+use std::path::Path;
+
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+use crate::error::FluxError;
+
+/// Rewrites a document of one version into the next.
+pub type Migration = fn(Value) -> Result<Value, String>;
+
+/// A versioned state file format.
+#[derive(Debug, Clone, Copy)]
+pub struct Format {
+    /// Version this build writes
+    pub current: u32,
+    /// `migrations[i]` upgrades version `i + 1` to `i + 2`, so there is one
+    /// fewer than `current`
+    pub migrations: &'static [Migration],
+}
+
+impl Format {
+    /// Parse the file at `path` (read into `json`), migrate it to the
+    /// current version and deserialize it.
+    pub fn load<T: DeserializeOwned>(&self, path: &Path, json: &str) -> Result<T, FluxError> {
+        Ok(serde_json::from_value(self.upgrade(path, json)?)?)
+    }
+
+    /// Parse `json` and bring it up to the current version.
+    pub fn upgrade(&self, path: &Path, json: &str) -> Result<Value, FluxError> {
+        let mut doc: Value = serde_json::from_str(json)?;
+        let found = version_of(&doc)?;
+        if found > self.current {
+            return Err(FluxError::NewerFormat {
+                path: path.to_path_buf(),
+                found,
+                supported: self.current,
+            });
+        }
+        for migrate in &self.migrations[found as usize - 1..self.current as usize - 1] {
+            doc = migrate(doc).map_err(|e| {
+                FluxError::Config(format!("Cannot upgrade {}: {}", path.display(), e))
+            })?;
+        }
+        if let Value::Object(ref mut fields) = doc {
+            fields.insert("version".into(), self.current.into());
+        }
+        Ok(doc)
+    }
+}
+
+/// Version of a parsed document: its `version` field, or 1 for files
+/// written before versioning (including list stores saved as bare arrays).
+fn version_of(doc: &Value) -> Result<u32, FluxError> {
+    match doc.get("version") {
+        None => Ok(1),
+        Some(version) => version
+            .as_u64()
+            .and_then(|v| u32::try_from(v).ok())
+            .filter(|&v| v >= 1)
+            .ok_or_else(|| FluxError::Config(format!("Invalid format version {}", version))),
+    }
+}
+
+/// Version 1 to 2 of a JSON list store: wrap the bare array of version 1 in
+/// a `{"version": 2, "entries": [...]}` object.
+pub fn wrap_entries(doc: Value) -> Result<Value, String> {
+    match doc {
+        Value::Array(_) => Ok(serde_json::json!({ "entries": doc })),
+        _ => Err("expected a list of entries".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LIST: Format = Format {
+        current: 2,
+        migrations: &[wrap_entries],
+    };
+
+    #[test]
+    fn bare_arrays_migrate_to_entries() {
+        let path = Path::new("list.json");
+        let doc = LIST.upgrade(path, "[1, 2, 3]").unwrap();
+        assert_eq!(doc, serde_json::json!({ "version": 2, "entries": [1, 2, 3] }));
+
+        let doc = LIST.upgrade(path, r#"{"version": 2, "entries": [4]}"#).unwrap();
+        assert_eq!(doc["entries"], serde_json::json!([4]));
+    }
+
+    #[test]
+    fn newer_versions_are_refused() {
+        let path = Path::new("list.json");
+        let err = LIST
+            .upgrade(path, r#"{"version": 3, "entries": [], "extra": true}"#)
+            .unwrap_err();
+        assert!(
+            matches!(err, FluxError::NewerFormat { found: 3, supported: 2, .. }),
+            "{:?}",
+            err
+        );
+        assert!(err.to_string().contains("list.json"), "{}", err);
+        assert!(err.suggestion().unwrap().contains("Upgrade flux"));
+    }
+
+    #[test]
+    fn malformed_documents_are_errors() {
+        let path = Path::new("list.json");
+        assert!(LIST.upgrade(path, "not json").is_err());
+        assert!(LIST.upgrade(path, r#"{"version": 0}"#).is_err());
+        assert!(LIST.upgrade(path, r#"{"version": "2"}"#).is_err());
+        // Version 1 must be a list
+        assert!(LIST.upgrade(path, r#"{"entries": []}"#).is_err());
+    }
+}
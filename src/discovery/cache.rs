@@ -0,0 +1,157 @@
+//! Addresses of recently discovered devices, for fast `@name` lookups.
+//!
+//! An mDNS browse waits out its whole timeout (and a subnet scan follows
+//! when nothing answers), so every `flux send @name` used to take seconds
+//! before the first byte. Devices found by discovery are kept in the
+//! `devices` table of `state.db` for `TTL`; `@name` tries the cached address
+//! first with one `Ping` probe and only browses again if the device is not
+//! answering there under the same name.
+
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use rusqlite::{params, Connection};
+
+use crate::discovery::service::DiscoveredDevice;
+use crate::error::FluxError;
+
+/// How long a discovered address is tried before discovery runs again.
+pub const TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Cached device addresses in the state database.
+pub struct DeviceCache {
+    db: Connection,
+}
+
+impl DeviceCache {
+    /// Open the cache in the state database in `data_dir`.
+    pub fn load(data_dir: &Path) -> Result<Self, FluxError> {
+        Ok(Self {
+            db: crate::state::open(data_dir)?,
+        })
+    }
+
+    /// The cache in the data directory, or `None` if it cannot be opened;
+    /// lookups then always run discovery.
+    pub fn open() -> Option<Self> {
+        match crate::config::paths::flux_data_dir().and_then(|dir| Self::load(&dir)) {
+            Ok(cache) => Some(cache),
+            Err(e) => {
+                tracing::debug!("Device addresses will not be cached: {}", e);
+                None
+            }
+        }
+    }
+
+    /// Devices seen within `TTL`, most recently seen first.
+    pub fn fresh(&self) -> Result<Vec<DiscoveredDevice>, FluxError> {
+        let cutoff = now() - TTL.as_secs() as i64;
+        let mut stmt = self.db.prepare(
+            "SELECT name, host, port, version FROM devices WHERE seen >= ?1
+             ORDER BY seen DESC",
+        )?;
+        let devices = stmt
+            .query_map(params![cutoff], |row| {
+                Ok(DiscoveredDevice {
+                    name: row.get(0)?,
+                    host: row.get(1)?,
+                    port: row.get(2)?,
+                    version: row.get(3)?,
+                    public_key: None,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(devices)
+    }
+
+    /// Record `devices` as seen now and drop entries past `TTL`.
+    pub fn remember(&self, devices: &[DiscoveredDevice]) -> Result<(), FluxError> {
+        let now = now();
+        let tx = self.db.unchecked_transaction()?;
+        for device in devices {
+            tx.execute(
+                "INSERT OR REPLACE INTO devices (name, host, port, version, seen)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![device.name, device.host, device.port, device.version, now],
+            )?;
+        }
+        tx.execute(
+            "DELETE FROM devices WHERE seen < ?1",
+            params![now - TTL.as_secs() as i64],
+        )?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Drop the entry of the device called `name` (case-insensitive).
+    pub fn forget(&self, name: &str) -> Result<(), FluxError> {
+        self.db.execute("DELETE FROM devices WHERE name = ?1", params![name])?;
+        Ok(())
+    }
+}
+
+/// Record `devices` in the cache of the data directory. Failures are only
+/// logged: the next lookup runs discovery again.
+pub fn record(devices: &[DiscoveredDevice]) {
+    if devices.is_empty() {
+        return;
+    }
+    if let Some(cache) = DeviceCache::open() {
+        if let Err(e) = cache.remember(devices) {
+            tracing::debug!("Could not cache device addresses: {}", e);
+        }
+    }
+}
+
+/// Seconds since the Unix epoch.
+fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn device(name: &str, host: &str) -> DiscoveredDevice {
+        DiscoveredDevice {
+            name: name.to_string(),
+            host: host.to_string(),
+            port: 9741,
+            version: Some("1.0.0".into()),
+            public_key: None,
+        }
+    }
+
+    #[test]
+    fn remembered_devices_are_fresh_until_the_ttl() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let cache = DeviceCache::load(dir.path()).unwrap();
+        cache
+            .remember(&[device("Laptop", "192.168.1.20"), device("nas", "192.168.1.5")])
+            .unwrap();
+        // A later sighting replaces the address
+        cache.remember(&[device("laptop", "192.168.1.21")]).unwrap();
+
+        let mut fresh = cache.fresh().unwrap();
+        fresh.sort_by(|a, b| a.name.cmp(&b.name));
+        assert_eq!(fresh.len(), 2);
+        assert_eq!(fresh[0].name, "laptop");
+        assert_eq!(fresh[0].host, "192.168.1.21");
+        assert_eq!(fresh[1].version.as_deref(), Some("1.0.0"));
+
+        let stale = now() - TTL.as_secs() as i64 - 1;
+        cache
+            .db
+            .execute("UPDATE devices SET seen = ?1 WHERE name = 'nas'", params![stale])
+            .unwrap();
+        let fresh = cache.fresh().unwrap();
+        assert_eq!(fresh.len(), 1);
+        assert_eq!(fresh[0].name, "laptop");
+
+        cache.forget("LAPTOP").unwrap();
+        assert!(cache.fresh().unwrap().is_empty());
+    }
+}
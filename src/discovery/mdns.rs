@@ -2,7 +2,9 @@ use crate::discovery::service::{DiscoveredDevice, FluxService, SERVICE_TYPE};
 use crate::error::FluxError;
 use gethostname::gethostname;
 use mdns_sd::{ServiceDaemon, ServiceEvent, ServiceInfo};
+use crate::net::addr::is_link_local_v6;
 use std::collections::HashMap;
+use std::net::IpAddr;
 use std::time::{Duration, Instant};
 
 /// Register this device as a Flux service on the local network via mDNS.
@@ -12,6 +14,8 @@ use std::time::{Duration, Instant};
 /// managed by `mdns-sd` -- the caller must keep the returned daemon alive
 /// to maintain the registration.
 ///
+/// Every IPv4 and IPv6 address of the host is advertised.
+///
 /// TXT properties advertised:
 /// - `version`: the Flux package version (from Cargo.toml)
 /// - `pubkey`: base64-encoded public key (optional, for TOFU)
@@ -100,14 +104,10 @@ pub fn discover_by_code_hash(
                 if hash_match {
                     let instance_name = extract_instance_name(&info.fullname);
 
-                    let addr = info
-                        .addresses
-                        .iter()
-                        .find(|a| a.is_ipv4())
-                        .or_else(|| info.addresses.iter().next());
+                    let addr = preferred_address(info.addresses.iter().map(|a| a.to_ip_addr()));
 
-                    if let Some(scoped_ip) = addr {
-                        let host = scoped_ip.to_ip_addr().to_string();
+                    if let Some(ip) = addr {
+                        let host = ip.to_string();
                         let version = info
                             .txt_properties
                             .get("version")
@@ -169,15 +169,11 @@ pub fn discover_flux_devices(timeout_secs: u64) -> Result<Vec<DiscoveredDevice>,
                 // fullname format: "instance-name._flux._tcp.local."
                 let instance_name = extract_instance_name(&info.fullname);
 
-                // Get the first IP address (prefer IPv4)
-                let addr = info
-                    .addresses
-                    .iter()
-                    .find(|a| a.is_ipv4())
-                    .or_else(|| info.addresses.iter().next());
+                // Prefer IPv4, then global IPv6, then link-local IPv6
+                let addr = preferred_address(info.addresses.iter().map(|a| a.to_ip_addr()));
 
-                if let Some(scoped_ip) = addr {
-                    let host = scoped_ip.to_ip_addr().to_string();
+                if let Some(ip) = addr {
+                    let host = ip.to_string();
 
                     // Extract TXT properties
                     let version = info
@@ -219,6 +215,17 @@ pub fn discover_flux_devices(timeout_secs: u64) -> Result<Vec<DiscoveredDevice>,
     Ok(seen.into_values().collect())
 }
 
+/// The address to reach a resolved service at: IPv4 if it has one, else a
+/// global IPv6 address, else a link-local one (connecting then tries each
+/// interface, see `net::addr`).
+fn preferred_address(addresses: impl IntoIterator<Item = IpAddr>) -> Option<IpAddr> {
+    addresses.into_iter().min_by_key(|ip| match ip {
+        IpAddr::V4(_) => 0,
+        IpAddr::V6(v6) if !is_link_local_v6(v6) => 1,
+        IpAddr::V6(_) => 2,
+    })
+}
+
 /// Extract the instance name from an mDNS fullname.
 ///
 /// The fullname format is: `instance-name._flux._tcp.local.`
@@ -261,6 +268,17 @@ mod tests {
         );
     }
 
+    #[test]
+    fn preferred_address_order() {
+        let v4: IpAddr = "192.168.1.5".parse().unwrap();
+        let global: IpAddr = "2001:db8::5".parse().unwrap();
+        let link_local: IpAddr = "fe80::5".parse().unwrap();
+        assert_eq!(preferred_address([link_local, global, v4]), Some(v4));
+        assert_eq!(preferred_address([link_local, global]), Some(global));
+        assert_eq!(preferred_address([link_local]), Some(link_local));
+        assert_eq!(preferred_address([]), None);
+    }
+
     // Note: Actual mDNS network tests require a network interface and cannot
     // reliably run in CI environments. The following tests are marked #[ignore]
     // and should be run manually on a machine with network access.
@@ -1,2 +1,4 @@
+pub mod cache;
 pub mod mdns;
+pub mod scan;
 pub mod service;
@@ -0,0 +1,276 @@
+//! mDNS-free discovery: probe the local subnet for Flux receivers.
+//!
+//! Networks that block multicast (many corporate Wi-Fis) never answer mDNS
+//! browses. As a fallback, every address of each local IPv4 subnet is tried
+//! on the ports from the `[discovery]` config table; a Flux receiver answers
+//! the `Ping` protocol message with `Pong`. Probes run on a bounded thread
+//! pool with short timeouts, so a /24 scan of one port takes a few seconds.
+
+use std::io::{Read, Write};
+use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4, TcpStream};
+use std::ops::RangeInclusive;
+use std::time::Duration;
+
+use rayon::prelude::*;
+
+use crate::config::types::{load_config, DiscoveryConfig};
+use crate::discovery::mdns::discover_flux_devices;
+use crate::discovery::service::DiscoveredDevice;
+use crate::error::FluxError;
+use crate::net::protocol::{decode_message, encode_message, FluxMessage, PROTOCOL_VERSION};
+
+/// Smallest prefix length scanned. Larger subnets are narrowed to the /22
+/// around our own address (at most 1022 hosts per interface).
+const MIN_SCAN_PREFIX: u32 = 22;
+
+/// Largest `Pong` frame accepted from a probed host.
+const MAX_PONG_SIZE: usize = 4096;
+
+/// Subnet scan settings.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScanOptions {
+    pub ports: RangeInclusive<u16>,
+    /// Connect and answer timeout of each probe
+    pub timeout: Duration,
+    /// Most probes in flight at once
+    pub concurrency: usize,
+}
+
+impl ScanOptions {
+    pub fn from_config(config: &DiscoveryConfig) -> Result<Self, FluxError> {
+        Ok(Self {
+            ports: parse_port_range(&config.scan_ports)?,
+            timeout: Duration::from_millis(config.scan_timeout_ms.max(1)),
+            concurrency: config.scan_concurrency.max(1),
+        })
+    }
+}
+
+/// Parse `PORT` or `FIRST-LAST` (inclusive).
+pub fn parse_port_range(s: &str) -> Result<RangeInclusive<u16>, FluxError> {
+    let invalid = || {
+        FluxError::Config(format!(
+            "Invalid discovery.scan_ports '{}': expected PORT or FIRST-LAST (e.g. 9741-9745)",
+            s
+        ))
+    };
+    let (first, last) = match s.split_once('-') {
+        Some((first, last)) => (first.trim(), last.trim()),
+        None => (s.trim(), s.trim()),
+    };
+    let first: u16 = first.parse().map_err(|_| invalid())?;
+    let last: u16 = last.parse().map_err(|_| invalid())?;
+    if first == 0 || first > last {
+        return Err(invalid());
+    }
+    Ok(first..=last)
+}
+
+/// Discover Flux devices: mDNS first, then a subnet scan if mDNS found
+/// nothing and `discovery.subnet_scan` is enabled.
+pub fn discover_devices(timeout_secs: u64) -> Result<Vec<DiscoveredDevice>, FluxError> {
+    let devices = discover_flux_devices(timeout_secs)?;
+    let config = load_config().unwrap_or_default().discovery;
+    if !devices.is_empty() || !config.subnet_scan {
+        return Ok(devices);
+    }
+    let options = ScanOptions::from_config(&config)?;
+    eprintln!("No devices found via mDNS, scanning the local subnet...");
+    scan_subnet(&options)
+}
+
+/// Probe every host of the local IPv4 subnets on `options.ports`.
+///
+/// Devices are deduplicated by name (first address wins) and sorted by name.
+pub fn scan_subnet(options: &ScanOptions) -> Result<Vec<DiscoveredDevice>, FluxError> {
+    let interfaces = if_addrs::get_if_addrs().map_err(|e| {
+        FluxError::DiscoveryError(format!("Failed to list network interfaces: {}", e))
+    })?;
+
+    let mut hosts: Vec<Ipv4Addr> = Vec::new();
+    for iface in interfaces.iter().filter(|i| !i.is_loopback()) {
+        if let if_addrs::IfAddr::V4(ref v4) = iface.addr {
+            if v4.ip.is_link_local() {
+                continue;
+            }
+            for host in subnet_hosts(v4.ip, v4.netmask) {
+                if !hosts.contains(&host) {
+                    hosts.push(host);
+                }
+            }
+        }
+    }
+    if hosts.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let targets: Vec<SocketAddr> = hosts
+        .iter()
+        .flat_map(|&ip| {
+            options
+                .ports
+                .clone()
+                .map(move |port| SocketAddr::V4(SocketAddrV4::new(ip, port)))
+        })
+        .collect();
+    tracing::debug!("Scanning {} address(es) for Flux receivers", targets.len());
+
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(options.concurrency)
+        .build()
+        .map_err(|e| FluxError::DiscoveryError(format!("Failed to start scan: {}", e)))?;
+    let found: Vec<DiscoveredDevice> = pool.install(|| {
+        targets
+            .par_iter()
+            .filter_map(|addr| probe(*addr, options.timeout))
+            .collect()
+    });
+
+    let mut devices: Vec<DiscoveredDevice> = Vec::new();
+    for device in found {
+        if !devices.iter().any(|d| d.name == device.name) {
+            devices.push(device);
+        }
+    }
+    devices.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(devices)
+}
+
+/// Host addresses of the subnet `ip`/`netmask`, without the network and
+/// broadcast addresses. Subnets larger than a /22 are narrowed to the /22
+/// containing `ip`.
+fn subnet_hosts(ip: Ipv4Addr, netmask: Ipv4Addr) -> Vec<Ipv4Addr> {
+    let prefix = u32::from(netmask).count_ones().max(MIN_SCAN_PREFIX);
+    if prefix >= 31 {
+        // Point-to-point links have no other hosts to find
+        return Vec::new();
+    }
+    let mask = u32::MAX << (32 - prefix);
+    let network = u32::from(ip) & mask;
+    let broadcast = network | !mask;
+    (network + 1..broadcast).map(Ipv4Addr::from).collect()
+}
+
+/// Send `Ping` to `addr` and wait for a `Pong`.
+///
+/// Frames use the same 4-byte big-endian length prefix as the transfer
+/// protocol. Any failure (closed port, timeout, other service) means no
+/// Flux receiver is listening there.
+pub(crate) fn probe(addr: SocketAddr, timeout: Duration) -> Option<DiscoveredDevice> {
+    let mut stream = TcpStream::connect_timeout(&addr, timeout).ok()?;
+    stream.set_read_timeout(Some(timeout)).ok()?;
+    stream.set_write_timeout(Some(timeout)).ok()?;
+
+    let ping = encode_message(&FluxMessage::Ping {
+        version: PROTOCOL_VERSION,
+    })
+    .ok()?;
+    let mut frame = Vec::with_capacity(4 + ping.len());
+    frame.extend_from_slice(&(ping.len() as u32).to_be_bytes());
+    frame.extend_from_slice(&ping);
+    stream.write_all(&frame).ok()?;
+
+    let mut len = [0u8; 4];
+    stream.read_exact(&mut len).ok()?;
+    let len = u32::from_be_bytes(len) as usize;
+    if len > MAX_PONG_SIZE {
+        return None;
+    }
+    let mut payload = vec![0u8; len];
+    stream.read_exact(&mut payload).ok()?;
+
+    match decode_message(&payload).ok()? {
+        FluxMessage::Pong {
+            device_name,
+            flux_version,
+            ..
+        } => Some(DiscoveredDevice {
+            name: device_name,
+            host: addr.ip().to_string(),
+            port: addr.port(),
+            version: Some(flux_version),
+            public_key: None,
+        }),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_port_range_single_and_range() {
+        assert_eq!(parse_port_range("9741").unwrap(), 9741..=9741);
+        assert_eq!(parse_port_range("9741-9745").unwrap(), 9741..=9745);
+        assert_eq!(parse_port_range(" 9741 - 9742 ").unwrap(), 9741..=9742);
+    }
+
+    #[test]
+    fn parse_port_range_rejects_invalid() {
+        for bad in ["", "0", "9745-9741", "abc", "9741-", "70000"] {
+            assert!(parse_port_range(bad).is_err(), "{} should be rejected", bad);
+        }
+    }
+
+    #[test]
+    fn subnet_hosts_of_a_24() {
+        let hosts = subnet_hosts(
+            Ipv4Addr::new(192, 168, 1, 42),
+            Ipv4Addr::new(255, 255, 255, 0),
+        );
+        assert_eq!(hosts.len(), 254);
+        assert_eq!(hosts[0], Ipv4Addr::new(192, 168, 1, 1));
+        assert_eq!(hosts[253], Ipv4Addr::new(192, 168, 1, 254));
+    }
+
+    #[test]
+    fn subnet_hosts_narrows_large_subnets() {
+        let hosts = subnet_hosts(Ipv4Addr::new(10, 1, 6, 9), Ipv4Addr::new(255, 0, 0, 0));
+        assert_eq!(hosts.len(), 1022);
+        assert_eq!(hosts[0], Ipv4Addr::new(10, 1, 4, 1));
+        assert!(subnet_hosts(Ipv4Addr::new(10, 0, 0, 1), Ipv4Addr::BROADCAST).is_empty());
+    }
+
+    #[test]
+    fn probe_identifies_receiver_by_pong() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut len = [0u8; 4];
+            stream.read_exact(&mut len).unwrap();
+            let mut payload = vec![0u8; u32::from_be_bytes(len) as usize];
+            stream.read_exact(&mut payload).unwrap();
+            assert!(matches!(
+                decode_message(&payload).unwrap(),
+                FluxMessage::Ping { .. }
+            ));
+            let pong = encode_message(&FluxMessage::Pong {
+                version: PROTOCOL_VERSION,
+                device_name: "nas".to_string(),
+                flux_version: "1.0.0".to_string(),
+            })
+            .unwrap();
+            stream
+                .write_all(&(pong.len() as u32).to_be_bytes())
+                .unwrap();
+            stream.write_all(&pong).unwrap();
+        });
+
+        let device = probe(addr, Duration::from_secs(2)).expect("receiver found");
+        server.join().unwrap();
+        assert_eq!(device.name, "nas");
+        assert_eq!(device.host, "127.0.0.1");
+        assert_eq!(device.port, addr.port());
+        assert_eq!(device.version.as_deref(), Some("1.0.0"));
+    }
+
+    #[test]
+    fn probe_ignores_closed_ports() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        drop(listener);
+        assert!(probe(addr, Duration::from_millis(200)).is_none());
+    }
+}
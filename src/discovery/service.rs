@@ -22,6 +22,7 @@ pub struct DiscoveredDevice {
     /// Flux version from TXT record, if available
     pub version: Option<String>,
     /// Base64-encoded public key from TXT record, for TOFU verification
+    #[cfg_attr(not(feature = "tui"), allow(dead_code))]
     pub public_key: Option<String>,
 }
 
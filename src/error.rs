@@ -12,6 +12,12 @@ pub enum FluxError {
     #[error("Permission denied: {}", path.display())]
     PermissionDenied { path: PathBuf },
 
+    #[error("Refusing to {operation} {}: it is inside a read-only source", path.display())]
+    ReadOnlySource {
+        path: PathBuf,
+        operation: &'static str,
+    },
+
     #[error("Source is a directory, use -r flag for recursive copy")]
     IsDirectory { path: PathBuf },
 
@@ -27,6 +33,12 @@ pub enum FluxError {
     #[error("Configuration error: {0}")]
     Config(String),
 
+    #[error("Destination already exists: {}", path.display())]
+    DestinationExists { path: PathBuf },
+
+    #[error("Move error: {0}")]
+    MoveError(String),
+
     #[error("Destination is inside source directory: {} -> {}", src.display(), dst.display())]
     DestinationIsSubdirectory { src: PathBuf, dst: PathBuf },
 
@@ -37,15 +49,38 @@ pub enum FluxError {
         actual: String,
     },
 
+    #[error("Source changed during copy: {} {change}", path.display())]
+    SourceChanged { path: PathBuf, change: String },
+
     #[error("Resume error: {0}")]
     ResumeError(String),
 
+    #[error(
+        "{} was written by a newer flux (format version {found}; this one reads up to {supported})",
+        path.display()
+    )]
+    NewerFormat {
+        path: PathBuf,
+        found: u32,
+        supported: u32,
+    },
+
     #[error("Compression error: {0}")]
     CompressionError(String),
 
     #[error("Protocol error: {0}")]
     ProtocolError(String),
 
+    #[cfg(feature = "net")]
+    #[error("{failures} conformance check(s) failed")]
+    ConformanceFailed { failures: usize },
+
+    #[cfg(any(
+        feature = "net",
+        feature = "backends-sftp",
+        feature = "backends-webdav",
+        all(windows, feature = "backends-smb")
+    ))]
     #[error("Connection failed to {protocol}://{host}: {reason}")]
     ConnectionFailed {
         protocol: String,
@@ -53,18 +88,39 @@ pub enum FluxError {
         reason: String,
     },
 
+    #[cfg(feature = "backends-webdav")]
+    #[error("TLS certificate of {host} rejected: {problem}")]
+    Certificate {
+        host: String,
+        problem: CertificateProblem,
+    },
+
     #[error("Alias error: {0}")]
     AliasError(String),
 
     #[error("Queue error: {0}")]
     QueueError(String),
 
+    #[error("Saved transfer error: {0}")]
+    SavedError(String),
+
+    #[error("History error: {0}")]
+    HistoryError(String),
+
+    #[error("State database error: {0}")]
+    StateError(String),
+
+    #[cfg(feature = "net")]
     #[error("Discovery error: {0}")]
     DiscoveryError(String),
 
+    #[error("Service error: {0}")]
+    ServiceError(String),
+
     #[error("Encryption error: {0}")]
     EncryptionError(String),
 
+    #[cfg(feature = "net")]
     #[error("Trust error: {0}")]
     TrustError(String),
 
@@ -73,9 +129,257 @@ pub enum FluxError {
 
     #[error("Sync error: {0}")]
     SyncError(String),
+
+    #[cfg(feature = "net")]
+    #[error("Receive quota exceeded: {0}")]
+    QuotaExceeded(String),
+
+    #[cfg(feature = "net")]
+    #[error("Not enough disk space: {0}")]
+    InsufficientSpace(String),
+
+    #[error(
+        "Not enough disk space for {}: needs {}, {} free",
+        path.display(),
+        bytesize::ByteSize(*needed),
+        bytesize::ByteSize(*available)
+    )]
+    DiskFull {
+        path: PathBuf,
+        needed: u64,
+        available: u64,
+    },
+
+    #[error("File is locked by another program: {}", path.display())]
+    FileLocked { path: PathBuf },
+
+    #[error("Snapshot error: {0}")]
+    SnapshotError(String),
+
+    #[error("File encryption error: {0}")]
+    FileEncryptionError(String),
+
+    #[cfg(feature = "creds")]
+    #[error("Credential error: {0}")]
+    CredentialError(String),
+
+    #[error("{} file(s) failed to copy", failed.len())]
+    PartialFailure {
+        /// Failed files and why
+        failed: Vec<(PathBuf, String)>,
+    },
+
+    #[error("{} file(s) of {} were not moved and were kept", kept.len(), root.display())]
+    MoveIncomplete {
+        root: PathBuf,
+        /// Source files not found at the destination after the copy
+        kept: Vec<PathBuf>,
+    },
+
+    #[error("Copy aborted at {}: {source}", path.display())]
+    Aborted {
+        path: PathBuf,
+        source: Box<FluxError>,
+    },
+
+    #[error("{0}")]
+    Differences(String),
+
+    #[error("Transfer paused")]
+    Paused,
+
+    #[error(
+        "Monthly cap for {backend} reached: {} of {} used this month",
+        bytesize::ByteSize(*used),
+        bytesize::ByteSize(*cap)
+    )]
+    UsageCapReached { backend: String, used: u64, cap: u64 },
+
+    #[error("Transfer cancelled")]
+    Cancelled,
+}
+
+/// Why a server's TLS certificate was rejected (`FluxError::Certificate`).
+#[cfg(feature = "backends-webdav")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CertificateProblem {
+    /// Not signed by a CA we trust (e.g. a private CA)
+    UntrustedIssuer,
+    /// Valid, but issued for another host name
+    HostnameMismatch,
+    /// Expired or not valid yet
+    Expired,
+    /// Trusted, but not one of the pinned certificates
+    PinMismatch {
+        /// SHA-256 fingerprint of the certificate the server presented
+        presented: String,
+    },
+}
+
+#[cfg(feature = "backends-webdav")]
+impl std::fmt::Display for CertificateProblem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CertificateProblem::UntrustedIssuer => {
+                write!(f, "it is not signed by a trusted certificate authority")
+            }
+            CertificateProblem::HostnameMismatch => {
+                write!(f, "it was issued for a different host name")
+            }
+            CertificateProblem::Expired => write!(f, "it has expired or is not valid yet"),
+            CertificateProblem::PinMismatch { presented } => write!(
+                f,
+                "its SHA-256 fingerprint {} matches none of the pinned ones",
+                presented
+            ),
+        }
+    }
+}
+
+/// Failure category of a `FluxError`, which decides the process exit code.
+///
+/// The codes are stable so scripts can branch on them; clap's own argument
+/// errors are reported as `Usage` too.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorCategory {
+    /// Anything not covered below (exit 1)
+    General,
+    /// The source path does not exist (exit 2)
+    SourceNotFound,
+    /// Access denied or the file is locked (exit 3)
+    Permission,
+    /// A checksum did not match (exit 4)
+    Integrity,
+    /// Connection, protocol, encryption or trust failure (exit 5)
+    Network,
+    /// Some files of a directory copy failed, the rest were copied (exit 6)
+    PartialFailure,
+    /// Invalid arguments, patterns, aliases or config.toml (exit 7)
+    Usage,
+    /// `flux verify` or `sync --verify-mirror` found differences (exit 8)
+    Differences,
+    /// The transfer was paused and can be resumed (exit 9)
+    Paused,
+    /// The transfer was cancelled with Ctrl+C (exit 130, as for SIGINT)
+    Cancelled,
+}
+
+impl ErrorCategory {
+    /// Process exit code.
+    pub fn code(self) -> i32 {
+        match self {
+            ErrorCategory::General => 1,
+            ErrorCategory::SourceNotFound => 2,
+            ErrorCategory::Permission => 3,
+            ErrorCategory::Integrity => 4,
+            ErrorCategory::Network => 5,
+            ErrorCategory::PartialFailure => 6,
+            ErrorCategory::Usage => 7,
+            ErrorCategory::Differences => 8,
+            ErrorCategory::Paused => 9,
+            ErrorCategory::Cancelled => 130,
+        }
+    }
+
+    /// Name used as `kind` in `--json` error output.
+    pub fn name(self) -> &'static str {
+        match self {
+            ErrorCategory::General => "error",
+            ErrorCategory::SourceNotFound => "source_not_found",
+            ErrorCategory::Permission => "permission_denied",
+            ErrorCategory::Integrity => "checksum_mismatch",
+            ErrorCategory::Network => "network",
+            ErrorCategory::PartialFailure => "partial_failure",
+            ErrorCategory::Usage => "usage",
+            ErrorCategory::Differences => "differences",
+            ErrorCategory::Paused => "paused",
+            ErrorCategory::Cancelled => "cancelled",
+        }
+    }
 }
 
 impl FluxError {
+    /// Category of this error, which decides the exit code.
+    pub fn category(&self) -> ErrorCategory {
+        match self {
+            FluxError::SourceNotFound { .. } => ErrorCategory::SourceNotFound,
+            FluxError::PermissionDenied { .. }
+            | FluxError::DestinationNotWritable { .. }
+            | FluxError::ReadOnlySource { .. }
+            | FluxError::FileLocked { .. } => ErrorCategory::Permission,
+            FluxError::Io { source } if source.kind() == std::io::ErrorKind::PermissionDenied => {
+                ErrorCategory::Permission
+            }
+            FluxError::ChecksumMismatch { .. } | FluxError::SourceChanged { .. } => {
+                ErrorCategory::Integrity
+            }
+            FluxError::ProtocolError(_)
+            | FluxError::EncryptionError(_)
+            | FluxError::TransferError(_) => ErrorCategory::Network,
+            #[cfg(any(
+                feature = "net",
+                feature = "backends-sftp",
+                feature = "backends-webdav",
+                all(windows, feature = "backends-smb")
+            ))]
+            FluxError::ConnectionFailed { .. } => ErrorCategory::Network,
+            #[cfg(feature = "backends-webdav")]
+            FluxError::Certificate { .. } => ErrorCategory::Network,
+            #[cfg(feature = "net")]
+            FluxError::TrustError(_) => ErrorCategory::Network,
+            #[cfg(feature = "net")]
+            FluxError::DiscoveryError(_)
+            | FluxError::QuotaExceeded(_)
+            | FluxError::InsufficientSpace(_) => ErrorCategory::Network,
+            FluxError::PartialFailure { .. } | FluxError::MoveIncomplete { .. } => {
+                ErrorCategory::PartialFailure
+            }
+            FluxError::Config(_)
+            | FluxError::InvalidPattern { .. }
+            | FluxError::AliasError(_)
+            | FluxError::SavedError(_)
+            | FluxError::IsDirectory { .. }
+            | FluxError::DestinationExists { .. }
+            | FluxError::MoveError(_)
+            | FluxError::DestinationIsSubdirectory { .. } => ErrorCategory::Usage,
+            FluxError::Differences(_) => ErrorCategory::Differences,
+            FluxError::Paused | FluxError::UsageCapReached { .. } => ErrorCategory::Paused,
+            FluxError::Cancelled => ErrorCategory::Cancelled,
+            FluxError::Aborted { source, .. } => source.category(),
+            _ => ErrorCategory::General,
+        }
+    }
+
+    /// Extra fields for `--json` error output.
+    pub fn details(&self) -> Option<serde_json::Value> {
+        match self {
+            FluxError::PartialFailure { failed } => Some(serde_json::json!({
+                "failed": failed
+                    .iter()
+                    .map(|(path, error)| serde_json::json!({
+                        "path": path.display().to_string(),
+                        "error": error,
+                    }))
+                    .collect::<Vec<_>>(),
+            })),
+            FluxError::MoveIncomplete { kept, .. } => Some(serde_json::json!({
+                "kept": kept
+                    .iter()
+                    .map(|path| path.display().to_string())
+                    .collect::<Vec<_>>(),
+            })),
+            FluxError::Aborted { path, .. } => Some(serde_json::json!({
+                "on_error": "abort",
+                "path": path.display().to_string(),
+            })),
+            #[cfg(feature = "net")]
+            FluxError::ConformanceFailed { failures } => Some(serde_json::json!({
+                "failures": failures,
+            })),
+            _ => None,
+        }
+    }
+
     /// Returns a user-friendly suggestion for how to fix the error.
     pub fn suggestion(&self) -> Option<&str> {
         match self {
@@ -88,6 +392,9 @@ impl FluxError {
             FluxError::IsDirectory { .. } => {
                 Some("Use 'flux cp -r <source> <dest>' for directory copies.")
             }
+            FluxError::ReadOnlySource { .. } => {
+                Some("--read-only refuses every change under the source. Choose a destination outside the source tree.")
+            }
             FluxError::DestinationNotWritable { .. } => {
                 Some("Check that the destination directory exists and you have write permission.")
             }
@@ -97,32 +404,79 @@ impl FluxError {
             FluxError::DestinationIsSubdirectory { .. } => {
                 Some("Choose a destination outside the source directory.")
             }
+            FluxError::DestinationExists { .. } => {
+                Some("Use --force to replace it, or choose another destination.")
+            }
+            FluxError::MoveError(_) => {
+                Some("Check the source and destination. `flux cp` copies without deleting the source.")
+            }
             FluxError::ChecksumMismatch { .. } => {
                 Some("The file may be corrupted. Try re-transferring.")
             }
+            FluxError::SourceChanged { .. } => {
+                Some("Something is writing to the file. Copy it once it is finished, copy from a snapshot (--snapshot-source), or use --changed-source retry or warn.")
+            }
             FluxError::ResumeError(_) => {
                 Some("Delete the .flux-resume.json manifest file and restart the transfer.")
             }
+            FluxError::NewerFormat { .. } => {
+                Some("Upgrade flux to read it, or move the file aside to start without it.")
+            }
             FluxError::ProtocolError(_) => {
                 Some("Check the URL format. Examples: sftp://user@host/path, \\\\server\\share, https://server/webdav/")
             }
+            #[cfg(any(
+                feature = "net",
+                feature = "backends-sftp",
+                feature = "backends-webdav",
+                all(windows, feature = "backends-smb")
+            ))]
             FluxError::ConnectionFailed { .. } => {
                 Some("Check that the host is reachable and the port is correct.")
             }
+            #[cfg(feature = "backends-webdav")]
+            FluxError::Certificate { problem, .. } => Some(match problem {
+                CertificateProblem::UntrustedIssuer => {
+                    "For a server with a private CA, set ca_bundle in the [webdav] table of config.toml to the CA certificate (PEM)."
+                }
+                CertificateProblem::HostnameMismatch => {
+                    "Connect with the host name the certificate was issued for; trusting another CA does not fix a name mismatch."
+                }
+                CertificateProblem::Expired => {
+                    "Renew the server's certificate, or check this machine's clock."
+                }
+                CertificateProblem::PinMismatch { .. } => {
+                    "If the server's certificate was replaced on purpose, put its new fingerprint in pinned_sha256 in the [webdav] table of config.toml."
+                }
+            }),
             FluxError::AliasError(_) => {
                 Some("Check alias name with `flux alias`.")
             }
             FluxError::QueueError(_) => {
                 Some("Check queue status with `flux queue`.")
             }
+            FluxError::SavedError(_) => {
+                Some("List saved transfers with `flux saved`; fix one with `flux saved edit`.")
+            }
+            FluxError::HistoryError(_) => {
+                Some("List entries and their IDs with `flux history`.")
+            }
+            FluxError::StateError(_) => {
+                Some("The queue, history and checksum cache are kept in state.db in the data directory. Check that it is writable; a damaged one is moved aside automatically.")
+            }
+            FluxError::ServiceError(_) => {
+                Some("Check `flux service status`; installing with --system needs root (Administrator on Windows).")
+            }
+            #[cfg(feature = "net")]
             FluxError::DiscoveryError(_) => {
                 Some("Check that your firewall allows mDNS (UDP port 5353) and no other Flux instance is running.")
             }
             FluxError::EncryptionError(_) => {
                 Some("The encryption handshake failed. Ensure both devices are using compatible Flux versions.")
             }
+            #[cfg(feature = "net")]
             FluxError::TrustError(_) => {
-                Some("Check trusted devices with `flux trust list`. Use `flux trust rm <device>` to remove stale entries.")
+                Some("Check trusted devices with `flux trust list`. Use `flux trust rm <device>` to remove stale entries. With --psk-file, both ends must use the same key file.")
             }
             FluxError::TransferError(_) => {
                 Some("Check that the receiver is running (`flux receive`) and reachable on the network.")
@@ -130,6 +484,46 @@ impl FluxError {
             FluxError::SyncError(_) => {
                 Some("Check that source and destination directories exist and are accessible.")
             }
+            #[cfg(feature = "net")]
+            FluxError::QuotaExceeded(_) => {
+                Some("Check today's usage with `flux receive --show-quota`, or raise the limits (or set storage_full = \"prune\") in the [receive] table of config.toml.")
+            }
+            #[cfg(feature = "net")]
+            FluxError::InsufficientSpace(_) => {
+                Some("Free up space on the receiving disk, receive into another --output, or lower free_space_margin in the [receive] table of config.toml.")
+            }
+            FluxError::DiskFull { .. } => {
+                Some("Free up space on the destination disk, or copy to another one.")
+            }
+            FluxError::FileLocked { .. } => {
+                Some("Close the program using it, or copy with --vss from an elevated prompt to read a shadow copy.")
+            }
+            FluxError::SnapshotError(_) => {
+                Some("Snapshots need root (Linux, btrfs or LVM) or an elevated prompt (Windows). Run without --snapshot-source to copy the live files.")
+            }
+            FluxError::FileEncryptionError(_) => {
+                Some("Recipient keys come from `flux decrypt --export-key` on the device that will decrypt; only that device can run `flux decrypt`.")
+            }
+            #[cfg(feature = "creds")]
+            FluxError::CredentialError(_) => {
+                Some("See what is stored with `flux creds list`; replace an entry with `flux creds add <url>` or drop it with `flux creds rm <url>`.")
+            }
+            FluxError::Aborted { source, .. } => source.suggestion(),
+            FluxError::PartialFailure { .. } => {
+                Some("The failed files are listed above and saved to a list. Run the same copy with `--retry-from <list>` to retry only them.")
+            }
+            FluxError::MoveIncomplete { .. } => {
+                Some("The kept files were not found at the destination after the copy. Check the destination, then run the same `flux mv` with --force to move the rest.")
+            }
+            FluxError::Paused => {
+                Some("Continue with `flux queue resume <id>` followed by `flux queue run`.")
+            }
+            FluxError::UsageCapReached { .. } => {
+                Some("Transfers to this backend continue next month. See the totals with `flux usage`, or raise [backends.max_monthly_gb] in config.toml.")
+            }
+            FluxError::Cancelled => {
+                Some("Run the same command again to start over; chunked copies with --resume continue where they stopped.")
+            }
             _ => None,
         }
     }
@@ -171,6 +565,12 @@ impl From<serde_json::Error> for FluxError {
     }
 }
 
+impl From<rusqlite::Error> for FluxError {
+    fn from(err: rusqlite::Error) -> Self {
+        FluxError::StateError(err.to_string())
+    }
+}
+
 impl From<toml::ser::Error> for FluxError {
     fn from(err: toml::ser::Error) -> Self {
         FluxError::Config(format!("TOML serialization error: {}", err))
@@ -190,6 +590,67 @@ mod tests {
     use super::*;
     use std::path::PathBuf;
 
+    #[test]
+    fn categories_map_to_stable_exit_codes() {
+        let missing = FluxError::SourceNotFound {
+            path: PathBuf::from("a"),
+        };
+        assert_eq!(missing.category().code(), 2);
+        let denied = FluxError::Io {
+            source: std::io::Error::from(std::io::ErrorKind::PermissionDenied),
+        };
+        assert_eq!(denied.category(), ErrorCategory::Permission);
+        let partial = FluxError::PartialFailure {
+            failed: vec![(PathBuf::from("a"), "boom".into())],
+        };
+        assert_eq!(partial.category().code(), 6);
+        assert_eq!(partial.details().unwrap()["failed"][0]["path"], "a");
+        let aborted = FluxError::Aborted {
+            path: PathBuf::from("a"),
+            source: Box::new(FluxError::ChecksumMismatch {
+                path: PathBuf::from("a"),
+                expected: "x".into(),
+                actual: "y".into(),
+            }),
+        };
+        assert_eq!(aborted.category(), ErrorCategory::Integrity);
+        let changed = FluxError::SourceChanged {
+            path: PathBuf::from("app.log"),
+            change: "grew from 10 B to 20 B".into(),
+        };
+        assert_eq!(changed.category(), ErrorCategory::Integrity);
+        assert_eq!(
+            changed.to_string(),
+            "Source changed during copy: app.log grew from 10 B to 20 B"
+        );
+        assert_eq!(FluxError::Config("x".into()).category().name(), "usage");
+        let exists = FluxError::DestinationExists {
+            path: PathBuf::from("b.txt"),
+        };
+        assert_eq!(exists.category().code(), 7);
+        let kept = FluxError::MoveIncomplete {
+            root: PathBuf::from("src"),
+            kept: vec![PathBuf::from("src/a")],
+        };
+        assert_eq!(kept.category().code(), 6);
+        assert_eq!(kept.details().unwrap()["kept"][0], "src/a");
+        assert_eq!(FluxError::SyncError("x".into()).category().code(), 1);
+        assert_eq!(FluxError::Cancelled.category().code(), 130);
+    }
+
+    #[test]
+    fn disk_full_names_the_sizes() {
+        let err = FluxError::DiskFull {
+            path: PathBuf::from("/mnt/backup/big.iso"),
+            needed: 2_000_000_000,
+            available: 500_000_000,
+        };
+        let msg = err.to_string();
+        assert!(msg.contains("big.iso"), "{}", msg);
+        assert!(msg.starts_with("Not enough disk space"), "{}", msg);
+        assert_eq!(err.category(), ErrorCategory::General);
+    }
+
     #[test]
     fn source_not_found_display_and_suggestion() {
         let err = FluxError::SourceNotFound {
@@ -204,6 +665,25 @@ mod tests {
         );
     }
 
+    #[cfg(feature = "backends-webdav")]
+    #[test]
+    fn certificate_errors_name_the_problem() {
+        let untrusted = FluxError::Certificate {
+            host: "dav.corp".into(),
+            problem: CertificateProblem::UntrustedIssuer,
+        };
+        assert!(untrusted.to_string().contains("not signed by a trusted"));
+        assert!(untrusted.suggestion().unwrap().contains("ca_bundle"));
+        assert_eq!(untrusted.category(), ErrorCategory::Network);
+
+        let mismatch = FluxError::Certificate {
+            host: "dav.corp".into(),
+            problem: CertificateProblem::HostnameMismatch,
+        };
+        assert!(mismatch.to_string().contains("different host name"));
+        assert!(!mismatch.suggestion().unwrap().contains("ca_bundle"));
+    }
+
     #[test]
     fn permission_denied_suggestion() {
         let err = FluxError::PermissionDenied {
@@ -228,6 +708,17 @@ mod tests {
         );
     }
 
+    #[test]
+    fn read_only_source_is_a_permission_error() {
+        let err = FluxError::ReadOnlySource {
+            path: PathBuf::from("/data/photos/a.jpg"),
+            operation: "delete",
+        };
+        assert!(err.to_string().starts_with("Refusing to delete"), "{}", err);
+        assert_eq!(err.category().code(), 3);
+        assert!(err.suggestion().unwrap().contains("--read-only"));
+    }
+
     #[test]
     fn destination_not_writable_suggestion() {
         let err = FluxError::DestinationNotWritable {
@@ -1,30 +1,57 @@
-use clap::Parser;
 use tracing_subscriber::EnvFilter;
 
 mod backend;
 mod cli;
 mod config;
+#[cfg(feature = "net")]
 mod discovery;
 mod error;
+#[cfg(feature = "net")]
 mod net;
 mod progress;
 mod protocol;
 mod queue;
 mod security;
+mod service;
+mod state;
 mod sync;
 mod transfer;
+#[cfg(feature = "tui")]
 mod tui;
 
-use cli::args::{Cli, Commands, CpArgs, QueueAction, TrustAction};
+use cli::args::{Cli, Commands, HistoryAction, QueueAction, ServiceAction};
+#[cfg(feature = "net")]
+use cli::args::{AuditAction, ProtocolAction, TrustAction};
 use config::types::Verbosity;
-use error::FluxError;
-use queue::state::QueueStatus;
+use error::{ErrorCategory, FluxError};
 use bytesize::ByteSize;
 
 use std::path::Path;
 
 fn main() {
-    let cli = Cli::parse();
+    let cli = cli::try_parse().unwrap_or_else(|e| {
+        // --help and --version exit 0; argument errors get the usage exit code
+        if !e.use_stderr() {
+            e.exit();
+        }
+        let code = ErrorCategory::Usage.code();
+        if std::env::args().any(|arg| arg == "--json") {
+            let rendered = e.render().to_string();
+            let message = rendered.lines().next().unwrap_or_default();
+            let value = serde_json::json!({
+                "error": {
+                    "code": code,
+                    "kind": ErrorCategory::Usage.name(),
+                    "message": message.trim_start_matches("error: "),
+                    "hint": "Run with --help for usage.",
+                }
+            });
+            eprintln!("{}", value);
+        } else {
+            let _ = e.print();
+        }
+        std::process::exit(code);
+    });
 
     // Convert CLI flags to verbosity level
     let verbosity = Verbosity::from((cli.quiet, cli.verbose));
@@ -43,21 +70,63 @@ fn main() {
 
     tracing::debug!("Verbosity level: {:?}", verbosity);
 
+    // `--config PATH` reaches every load_config() call (and child processes
+    // such as hooks) through FLUX_CONFIG
+    if let Some(path) = &cli.config {
+        let path = std::path::absolute(path).unwrap_or_else(|_| path.clone());
+        std::env::set_var("FLUX_CONFIG", path);
+    }
+
+    // Remember the transfer for `flux save` (`flux run` is not recorded:
+    // what it runs is already saved)
+    if matches!(cli.command, Commands::Cp(_) | Commands::Sync(_)) {
+        config::saved::record_last(
+            std::env::args_os()
+                .skip(1)
+                .map(|arg| arg.to_string_lossy().into_owned()),
+        );
+    }
+
+    let json = cli.json;
     if let Err(err) = run(cli) {
-        display_error(&err);
-        std::process::exit(1);
+        display_error(&err, json);
+        std::process::exit(err.category().code());
+    }
+}
+
+/// Commands that stop cleanly on Ctrl+C, removing partial files and telling
+/// the peer (see `transfer::cancel`). Others keep the default behaviour.
+fn cancellable(command: &Commands) -> bool {
+    match command {
+        Commands::Cp(_) | Commands::Mv(_) | Commands::Sync(_) => true,
+        #[cfg(feature = "net")]
+        Commands::Send(_) | Commands::Receive(_) => true,
+        #[cfg(feature = "net")]
+        Commands::Push(_) | Commands::Pull(_) => true,
+        #[cfg(feature = "watch")]
+        Commands::WatchFolder(_) => true,
+        Commands::Queue(args) => matches!(args.action, Some(QueueAction::Run)),
+        _ => false,
     }
 }
 
 /// Execute the dispatched command.
 fn run(cli: Cli) -> Result<(), FluxError> {
     // Check --tui flag before dispatching commands
+    #[cfg(feature = "tui")]
     if cli.tui {
         return tui::launch_tui().map_err(|e| FluxError::Io {
             source: std::io::Error::new(std::io::ErrorKind::Other, e.to_string()),
         });
     }
 
+    if cancellable(&cli.command) {
+        transfer::cancel::install_handler();
+    }
+    if cli.json {
+        transfer::stats::use_json();
+    }
+
     match cli.command {
         Commands::Cp(args) => {
             tracing::debug!(
@@ -74,10 +143,12 @@ fn run(cli: Cli) -> Result<(), FluxError> {
             transfer::execute_copy(args, cli.quiet)?;
             Ok(())
         }
+        Commands::Mv(args) => transfer::mv::execute_move(args, cli.quiet),
         Commands::Add(args) => {
             let config_dir = config::paths::flux_config_dir()?;
             config::aliases::validate_alias_name(&args.name)?;
             let mut store = config::aliases::AliasStore::load(&config_dir)?;
+            config::aliases::check_nesting(&args.name, &args.path, &store)?;
             store.add(args.name.clone(), args.path.clone());
             store.save()?;
             eprintln!("Alias saved: {} -> {}", args.name, args.path);
@@ -99,6 +170,26 @@ fn run(cli: Cli) -> Result<(), FluxError> {
                         }
                     }
                 }
+                Some(cli::args::AliasAction::Resolve(resolve_args)) => {
+                    // A bare name stands for the alias itself
+                    let target = if resolve_args.target.contains(':') {
+                        resolve_args.target.clone()
+                    } else {
+                        format!("{}:", resolve_args.target)
+                    };
+                    let resolved = config::aliases::resolve_alias(&target, &store);
+                    if resolved == target {
+                        return Err(FluxError::AliasError(format!(
+                            "'{}' does not refer to a saved alias",
+                            resolve_args.target
+                        )));
+                    }
+                    println!("{}", resolved);
+                    if !cli.quiet {
+                        let protocol = protocol::detect_protocol(&resolved);
+                        eprintln!("({})", protocol.name());
+                    }
+                }
                 Some(cli::args::AliasAction::Rm(rm_args)) => {
                     if store.remove(&rm_args.name) {
                         store.save()?;
@@ -110,11 +201,39 @@ fn run(cli: Cli) -> Result<(), FluxError> {
             }
             Ok(())
         }
+        Commands::Save(args) => config::saved::execute_save(args, cli.quiet),
+        Commands::Saved(args) => config::saved::execute_saved(args, cli.quiet),
+        Commands::Run(args) => {
+            let (command, dir) = config::saved::load_command(&args.name)?;
+            // Relative paths in the command are relative to where it was saved
+            if let Err(e) = std::env::set_current_dir(&dir) {
+                tracing::warn!(
+                    "Cannot change to {} ({}); relative paths use the current directory",
+                    dir.display(),
+                    e
+                );
+            }
+            run(Cli { command, ..cli })
+        }
         Commands::Queue(args) => {
             let data_dir = config::paths::flux_data_dir()?;
+            let action = args.action.unwrap_or(QueueAction::List);
+
+            // A running entry is owned by `flux queue run`, which holds the
+            // queue lock until it finishes, so ask it over the control socket.
+            if let QueueAction::Pause(ref id_args) = action {
+                if transfer::control::request_pause(&data_dir, id_args.id)? {
+                    eprintln!(
+                        "Pausing transfer #{} at the next chunk boundary",
+                        id_args.id
+                    );
+                    return Ok(());
+                }
+            }
+
             let mut store = queue::state::QueueStore::load(&data_dir)?;
 
-            match args.action.unwrap_or(QueueAction::List) {
+            match action {
                 QueueAction::Add(add_args) => {
                     let id = store.add(
                         add_args.source,
@@ -123,6 +242,10 @@ fn run(cli: Cli) -> Result<(), FluxError> {
                         add_args.verify,
                         add_args.compress,
                     );
+                    if let Some(entry) = store.get_mut(id) {
+                        entry.class = add_args.class;
+                        entry.priority = add_args.priority;
+                    }
                     store.save()?;
                     eprintln!("Queued transfer #{}", id);
                 }
@@ -132,16 +255,16 @@ fn run(cli: Cli) -> Result<(), FluxError> {
                         eprintln!("Queue is empty");
                     } else {
                         println!(
-                            "{:<4} {:<10} {:<30} {:<30}",
-                            "ID", "STATUS", "SOURCE", "DEST"
+                            "{:<4} {:<10} {:<8} {:<30} {:<30}",
+                            "ID", "STATUS", "PRIORITY", "SOURCE", "DEST"
                         );
-                        println!("{}", "-".repeat(76));
+                        println!("{}", "-".repeat(85));
                         for entry in entries {
                             let source = truncate_str(&entry.source, 28);
                             let dest = truncate_str(&entry.dest, 28);
                             println!(
-                                "{:<4} {:<10} {:<30} {:<30}",
-                                entry.id, entry.status, source, dest
+                                "{:<4} {:<10} {:<8} {:<30} {:<30}",
+                                entry.id, entry.status, entry.priority, source, dest
                             );
                         }
                     }
@@ -161,6 +284,25 @@ fn run(cli: Cli) -> Result<(), FluxError> {
                     store.save()?;
                     eprintln!("Cancelled transfer #{}", id_args.id);
                 }
+                QueueAction::Move(move_args) => {
+                    let (target, after) = match (move_args.before, move_args.after) {
+                        (Some(before), _) => (before, false),
+                        (None, Some(after)) => (after, true),
+                        (None, None) => {
+                            return Err(FluxError::QueueError(
+                                "Give --before or --after".to_string(),
+                            ))
+                        }
+                    };
+                    store.move_entry(move_args.id, target, after)?;
+                    store.save()?;
+                    eprintln!(
+                        "Moved transfer #{} {} #{}",
+                        move_args.id,
+                        if after { "after" } else { "before" },
+                        target
+                    );
+                }
                 QueueAction::Run => {
                     let pending: Vec<u64> =
                         store.pending_entries().iter().map(|e| e.id).collect();
@@ -170,58 +312,42 @@ fn run(cli: Cli) -> Result<(), FluxError> {
                     }
                     eprintln!("Processing {} transfer(s)...", pending.len());
 
+                    let estimates = pending
+                        .iter()
+                        .filter_map(|id| store.get(*id))
+                        .map(|e| queue::runner::estimate_size(&e.source))
+                        .collect();
+                    let bars = progress::bar::QueueProgress::new(estimates, cli.quiet);
+                    let mut outcomes = Vec::new();
+                    let mut cancelled = false;
                     for id in pending {
-                        // Mark as running
-                        if let Some(entry) = store.get_mut(id) {
-                            entry.status = QueueStatus::Running;
-                            entry.started_at = Some(chrono::Utc::now());
-                        }
-                        store.save()?;
-
-                        // Clone entry details for CpArgs construction
-                        let entry = store.get(id).ok_or_else(|| {
-                            FluxError::QueueError(format!("Queue entry {} not found", id))
-                        })?.clone();
-
-                        eprintln!("\n[#{}] {} -> {}", id, entry.source, entry.dest);
-
-                        // Build CpArgs from queue entry
-                        let cp_args = CpArgs {
-                            source: entry.source.clone(),
-                            dest: entry.dest.clone(),
-                            recursive: entry.recursive,
-                            verify: entry.verify,
-                            compress: entry.compress,
-                            chunks: 0,
-                            exclude: vec![],
-                            include: vec![],
-                            limit: None,
-                            resume: false,
-                            on_conflict: None,
-                            on_error: None,
-                            dry_run: false,
-                        };
-
-                        match transfer::execute_copy(cp_args, cli.quiet) {
-                            Ok(()) => {
-                                if let Some(e) = store.get_mut(id) {
-                                    e.status = QueueStatus::Completed;
-                                    e.completed_at = Some(chrono::Utc::now());
-                                }
-                                store.save()?;
-                                eprintln!("[#{}] Completed", id);
-                            }
-                            Err(err) => {
-                                if let Some(e) = store.get_mut(id) {
-                                    e.status = QueueStatus::Failed;
-                                    e.completed_at = Some(chrono::Utc::now());
-                                    e.error = Some(format!("{}", err));
-                                }
-                                store.save()?;
-                                eprintln!("[#{}] Failed: {}", id, err);
+                        match queue::runner::run_entry(
+                            &mut store,
+                            &data_dir,
+                            id,
+                            cli.quiet,
+                            None,
+                            Some(&bars),
+                        ) {
+                            Ok(outcome) => outcomes.push(outcome),
+                            Err(FluxError::Cancelled) => {
+                                cancelled = true;
+                                break;
                             }
+                            Err(e) => return Err(e),
+                        }
+                    }
+                    bars.finish();
+                    drop(bars);
+                    if !outcomes.is_empty() {
+                        eprintln!();
+                        for line in queue::runner::summary_table(&outcomes) {
+                            eprintln!("{}", line);
                         }
                     }
+                    if cancelled {
+                        return Err(FluxError::Cancelled);
+                    }
                     eprintln!("\nQueue processing complete");
                 }
                 QueueAction::Clear => {
@@ -238,6 +364,32 @@ fn run(cli: Cli) -> Result<(), FluxError> {
             let mut store =
                 queue::history::HistoryStore::load(&data_dir, flux_config.history_limit)?;
 
+            if let Some(HistoryAction::Diff(diff)) = args.action {
+                let entry = store.get(diff.id).ok_or_else(|| {
+                    FluxError::HistoryError(format!("Entry #{} not found", diff.id))
+                })?;
+                let changes = entry.changes.as_ref().ok_or_else(|| {
+                    FluxError::HistoryError(format!(
+                        "Entry #{} ({}) has no recorded file changes; only sync runs do",
+                        entry.id, entry.operation
+                    ))
+                })?;
+                println!(
+                    "#{} {} {} {} -> {} ({})",
+                    entry.id,
+                    entry.operation,
+                    entry.timestamp.format("%Y-%m-%d %H:%M:%S"),
+                    entry.source,
+                    entry.dest,
+                    entry.status
+                );
+                if entry.status == "failed" {
+                    eprintln!("Note: the run failed; some of these changes were not made");
+                }
+                print!("{}", changes.render());
+                return Ok(());
+            }
+
             if args.clear {
                 store.clear();
                 store.save()?;
@@ -245,44 +397,63 @@ fn run(cli: Cli) -> Result<(), FluxError> {
                 return Ok(());
             }
 
-            let entries = store.list();
+            // Only the most recent N entries are read
+            let entries = store.recent(args.count)?;
             if entries.is_empty() {
                 eprintln!("No transfer history");
                 return Ok(());
             }
 
-            // Show most recent N entries
-            let start = if entries.len() > args.count {
-                entries.len() - args.count
-            } else {
-                0
-            };
+            if args.receipts {
+                let receipts: Vec<_> = entries
+                    .iter()
+                    .filter_map(|e| e.receipt.as_ref())
+                    .collect();
+                if receipts.is_empty() {
+                    eprintln!("No receipts in the last {} entries", args.count);
+                    return Ok(());
+                }
+                let json = serde_json::to_string_pretty(&receipts)
+                    .map_err(|e| FluxError::Config(format!("Failed to serialize receipts: {}", e)))?;
+                println!("{}", json);
+                return Ok(());
+            }
+
             println!(
-                "{:<20} {:<10} {:<30} {:<30} {:<10}",
-                "TIMESTAMP", "STATUS", "SOURCE", "DEST", "SIZE"
+                "{:<6} {:<20} {:<10} {:<30} {:<30} {:<10}",
+                "ID", "TIMESTAMP", "STATUS", "SOURCE", "DEST", "SIZE"
             );
-            println!("{}", "-".repeat(102));
-            for entry in &entries[start..] {
+            println!("{}", "-".repeat(109));
+            for entry in &entries {
                 let ts = entry.timestamp.format("%Y-%m-%d %H:%M:%S").to_string();
                 let size = format_bytes(entry.bytes);
                 let source = truncate_str(&entry.source, 28);
                 let dest = truncate_str(&entry.dest, 28);
                 println!(
-                    "{:<20} {:<10} {:<30} {:<30} {:<10}",
-                    ts, entry.status, source, dest, size
+                    "{:<6} {:<20} {:<10} {:<30} {:<30} {:<10}",
+                    entry.id, ts, entry.status, source, dest, size
                 );
             }
             Ok(())
         }
+        Commands::Usage(args) => transfer::usage::execute_usage(args, cli.json),
         Commands::Completions(args) => {
-            use clap::CommandFactory;
             use clap_complete::generate;
-            let mut cmd = Cli::command();
+            let mut cmd = cli::command();
             generate(args.shell, &mut cmd, "flux", &mut std::io::stdout());
             Ok(())
         }
+        Commands::Manpages(args) => {
+            let written = cli::manpages::generate(cli::command(), &args.out)?;
+            if !cli.quiet {
+                eprintln!("Wrote {} man pages to {}", written.len(), args.out.display());
+            }
+            Ok(())
+        }
+        #[cfg(feature = "net")]
         Commands::Discover(args) => {
-            let devices = discovery::mdns::discover_flux_devices(args.timeout)?;
+            let devices = discovery::scan::discover_devices(args.timeout)?;
+            discovery::cache::record(&devices);
             if devices.is_empty() {
                 eprintln!("No Flux devices found on the local network");
             } else {
@@ -305,8 +476,11 @@ fn run(cli: Cli) -> Result<(), FluxError> {
             }
             Ok(())
         }
-        Commands::Send(args) => {
-            let file_path = Path::new(&args.file);
+        #[cfg(feature = "net")]
+        Commands::Send(mut args) => {
+            args.put_file_first();
+            let file = config::aliases::resolve(&args.file);
+            let file_path = Path::new(&file);
             if !file_path.exists() {
                 return Err(FluxError::SourceNotFound {
                     path: file_path.to_path_buf(),
@@ -316,45 +490,173 @@ fn run(cli: Cli) -> Result<(), FluxError> {
             let device_name = args.name.unwrap_or_else(|| {
                 gethostname::gethostname().to_string_lossy().to_string()
             });
+            let limit = net::ratelimit::RateLimit::from_args(
+                args.limit_up.as_deref(),
+                args.adaptive_limit,
+            )?;
+            let flux_config = config::types::load_config().unwrap_or_default();
+            net::socks::set_proxy(net::socks::Socks5Proxy::choose(
+                args.socks5,
+                flux_config.proxy.as_deref(),
+            )?);
+            if args.no_zero_copy {
+                net::zerocopy::disable();
+            }
+            net::resume::set_stall_timeout(std::time::Duration::from_secs(args.stall_timeout));
 
-            if let Some(target) = &args.target {
+            let mut targets = args.targets;
+            if args.all_trusted {
+                let config_dir = config::paths::flux_config_dir()?;
+                let store = security::trust::TrustStore::load(&config_dir)?;
+                if store.list_devices().is_empty() {
+                    return Err(FluxError::TrustError(
+                        "--all-trusted: no devices are trusted yet".into(),
+                    ));
+                }
+                let trusted = store.list_devices().into_iter();
+                targets.extend(trusted.map(|(name, _)| format!("@{}", name)));
+            }
+
+            let psk = args
+                .psk_file
+                .as_deref()
+                .map(security::psk::PreSharedKey::load)
+                .transpose()?;
+            let options = net::sender::SendOptions {
+                encrypt: !args.no_encrypt,
+                device_name: &device_name,
+                receipt: args.receipt,
+                limit,
+                checksum: args.checksum,
+                psk: psk.as_ref(),
+            };
+            if targets.len() > 1 || args.all_trusted {
+                // Group send: the same file to every target at once
+                if args.receipt {
+                    tracing::warn!("--receipt is not supported when sending to several devices");
+                }
+                net::group::send_to_group_sync(&targets, file_path, &options)?;
+            } else if let Some(target) = targets.first() {
                 // Direct send mode (existing behavior)
-                net::sender::send_file_sync(target, file_path, !args.no_encrypt, &device_name)?;
+                net::sender::send_file_sync(target, file_path, &options)?;
             } else {
                 // Code-phrase mode (Croc-like UX)
                 net::sender::send_with_code_sync(
                     file_path,
                     &device_name,
                     args.code.as_deref(),
+                    args.receipt,
+                    limit,
+                    args.checksum,
                 )?;
             }
             Ok(())
         }
+        #[cfg(feature = "net")]
         Commands::Receive(args) => {
-            let device_name = args.name.unwrap_or_else(|| {
-                gethostname::gethostname().to_string_lossy().to_string()
-            });
+            if args.show_quota {
+                let quota = net::quota::load_receive_quota()?;
+                for line in net::quota::format_quota_status(quota.limits(), &quota.usage()) {
+                    println!("{}", line);
+                }
+                return Ok(());
+            }
+
+            let flux_config = config::types::load_config().unwrap_or_default();
+            let device_name = args
+                .name
+                .or_else(|| flux_config.receive.device_name.clone())
+                .unwrap_or_else(|| gethostname::gethostname().to_string_lossy().to_string());
 
-            let output_dir = Path::new(&args.output);
+            // `--output` (or `[receive] output_dir`) may be a template
+            // ({date}, {hostname}, ...). The listener expands it per
+            // connection; code-phrase mode receives a single file, so it is
+            // expanded once here.
+            let output =
+                net::receiver::output_template(args.output.as_deref(), &flux_config.receive);
+            let expanded = config::aliases::expand_variables(&output);
+            let output_dir = Path::new(&expanded);
             if !output_dir.exists() {
                 std::fs::create_dir_all(output_dir)?;
             }
 
+            let limit = net::ratelimit::RateLimit::from_args(
+                args.limit_down.as_deref(),
+                args.adaptive_limit,
+            )?;
+            net::resume::set_stall_timeout(std::time::Duration::from_secs(args.stall_timeout));
+
             if let Some(code) = &args.code {
                 // Code-phrase mode (Croc-like UX)
-                net::receiver::receive_with_code_sync(code, output_dir, &device_name)?;
-            } else {
-                // Direct receive mode (existing behavior)
-                net::receiver::start_receiver_sync(
-                    args.port,
+                net::socks::set_proxy(net::socks::Socks5Proxy::choose(
+                    args.socks5.clone(),
+                    flux_config.proxy.as_deref(),
+                )?);
+                let low_memory = net::lowmem::should_use_low_memory(args.low_memory);
+                net::receiver::receive_with_code_sync(
+                    code,
                     output_dir,
-                    !args.no_encrypt,
                     &device_name,
-                    &args.bind,
+                    low_memory,
+                    limit,
                 )?;
+            } else {
+                // Direct receive mode (existing behavior)
+                let port = args
+                    .port
+                    .or(flux_config.receive.port)
+                    .unwrap_or(discovery::service::DEFAULT_PORT);
+                let psk = args
+                    .psk_file
+                    .as_deref()
+                    .map(security::psk::PreSharedKey::load)
+                    .transpose()?;
+                let web = if args.web {
+                    let web_port = args.web_port.unwrap_or(net::web::DEFAULT_WEB_PORT);
+                    Some(net::web::WebDrop::new(web_port, &args.offer)?)
+                } else {
+                    None
+                };
+                let options = net::receiver::ReceiveOptions {
+                    output: args.output.as_deref(),
+                    encrypt: !args.no_encrypt,
+                    device_name: &device_name,
+                    bind_addr: &args.bind,
+                    daemon: args.daemon,
+                    limit,
+                    psk,
+                    web,
+                    allow_mirror: args.allow_mirror,
+                };
+                net::receiver::start_receiver_sync(port, options)?;
             }
             Ok(())
         }
+        #[cfg(feature = "net")]
+        Commands::Push(args) => {
+            let local = config::aliases::resolve(&args.local);
+            run_mirror(
+                net::tree::Direction::Push,
+                &args.target,
+                Path::new(&local),
+                &args.remote,
+                args.mirror,
+                cli.quiet,
+            )
+        }
+        #[cfg(feature = "net")]
+        Commands::Pull(args) => {
+            let local = config::aliases::resolve(&args.local);
+            run_mirror(
+                net::tree::Direction::Pull,
+                &args.target,
+                Path::new(&local),
+                &args.remote,
+                args.mirror,
+                cli.quiet,
+            )
+        }
+        #[cfg(feature = "net")]
         Commands::Trust(args) => {
             let config_dir = config::paths::flux_config_dir()?;
             let mut store = security::trust::TrustStore::load(&config_dir)?;
@@ -371,11 +673,7 @@ fn run(cli: Cli) -> Result<(), FluxError> {
                         );
                         println!("{}", "-".repeat(82));
                         for (name, device) in &devices {
-                            let fingerprint = if device.public_key.len() > 16 {
-                                format!("{}...", &device.public_key[..16])
-                            } else {
-                                device.public_key.clone()
-                            };
+                            let fingerprint = security::trust::fingerprint(&device.public_key);
                             let first = device.first_seen.format("%Y-%m-%d %H:%M").to_string();
                             let last = device.last_seen.format("%Y-%m-%d %H:%M").to_string();
                             println!(
@@ -399,6 +697,30 @@ fn run(cli: Cli) -> Result<(), FluxError> {
             }
             Ok(())
         }
+        #[cfg(feature = "net")]
+        Commands::Audit(args) => {
+            let events = net::audit::AuditLog::load()?.events()?;
+            let shown: Vec<&net::audit::AuditEvent> = match args.action {
+                AuditAction::Tail(tail) => {
+                    events[events.len().saturating_sub(tail.count)..].iter().collect()
+                }
+                AuditAction::Search(search) => {
+                    let filter = net::audit::AuditFilter {
+                        text: search.text,
+                        peer: search.peer,
+                        event: search.event,
+                        since: search.since,
+                    };
+                    let found: Vec<_> = events.iter().filter(|e| filter.matches(e)).collect();
+                    let skip = search.count.map_or(0, |n| found.len().saturating_sub(n));
+                    found.into_iter().skip(skip).collect()
+                }
+            };
+            net::audit::print_events(&shown, cli.json)
+        }
+        #[cfg(feature = "creds")]
+        Commands::Creds(args) => security::creds::execute_creds(args, cli.quiet),
+        #[cfg(feature = "tui")]
         Commands::Ui => {
             tui::launch_tui().map_err(|e| FluxError::Io {
                 source: std::io::Error::new(std::io::ErrorKind::Other, e.to_string()),
@@ -409,24 +731,175 @@ fn run(cli: Cli) -> Result<(), FluxError> {
             sync::execute_sync(args, cli.quiet)
         }
         Commands::Verify(args) => {
-            let source = Path::new(&args.source);
-            let dest = Path::new(&args.dest);
-            let filter = transfer::filter::TransferFilter::new(&args.exclude, &args.include)?;
-            let result = transfer::verify::verify_directories(source, dest, &filter, cli.quiet)?;
-
-            // Exit with code 1 if there are any differences
-            if !result.differs.is_empty()
-                || !result.source_only.is_empty()
-                || !result.dest_only.is_empty()
-                || !result.errors.is_empty()
-            {
-                std::process::exit(1);
+            let source_resolved = config::aliases::resolve(&args.source);
+            let dest_resolved = config::aliases::resolve(&args.dest);
+            let source = Path::new(&source_resolved);
+            let dest = Path::new(&dest_resolved);
+            let exclude_hidden = config::types::load_config()
+                .map(|c| c.exclude_hidden)
+                .unwrap_or_default();
+            let filter = transfer::filter::TransferFilter::new(&args.exclude, &args.include)?
+                .skip_hidden(args.hidden.skip_hidden(exclude_hidden))
+                .skip_system(!args.hidden.include_system);
+            let result = transfer::verify::verify_directories(
+                source,
+                dest,
+                &filter,
+                args.checksum,
+                cli.quiet,
+            )?;
+
+            let differences = result.differs.len()
+                + result.source_only.len()
+                + result.dest_only.len()
+                + result.errors.len();
+            if differences > 0 {
+                return Err(FluxError::Differences(format!(
+                    "{} difference(s) between source and destination",
+                    differences
+                )));
+            }
+            Ok(())
+        }
+        Commands::Diff(args) => sync::diff::execute_diff(args, cli.quiet, cli.json),
+        Commands::Tree(args) => transfer::tree::execute_tree(args, cli.quiet),
+        Commands::Ls(args) => transfer::ls::execute_ls(args, cli.json),
+        Commands::Clean(args) => transfer::clean::execute_clean(args, cli.quiet),
+        Commands::Status(args) => transfer::status::execute_status(args, cli.json),
+        Commands::Daemon(args) => {
+            let data_dir = config::paths::flux_data_dir()?;
+            let flux_config = config::types::load_config()?;
+            let window = flux_config
+                .queue
+                .bulk_window
+                .as_deref()
+                .map(queue::policy::TimeWindow::parse)
+                .transpose()?;
+
+            match flux_config.queue.bulk_window.as_deref() {
+                Some(w) => eprintln!("Queue daemon started (bulk window {})", w),
+                None => eprintln!("Queue daemon started (no bulk window, all entries run)"),
+            }
+
+            loop {
+                // Reload each pass and release the lock between entries so
+                // `flux queue add` can enqueue work while the daemon runs.
+                // Entries for a capped backend wait without being retried.
+                let mut store = queue::state::QueueStore::load(&data_dir)?;
+                let now = chrono::Local::now().time();
+                let next = queue::policy::next_entry(
+                    store.list(),
+                    window.as_ref(),
+                    now,
+                    queue::runner::over_cap,
+                );
+                match next {
+                    Some(id) => {
+                        let bulk = store
+                            .get(id)
+                            .is_some_and(|e| e.class == queue::policy::QueueClass::Bulk);
+                        let entry_window = if bulk { window } else { None };
+                        queue::runner::run_entry(
+                            &mut store,
+                            &data_dir,
+                            id,
+                            cli.quiet,
+                            entry_window,
+                            None,
+                        )?;
+                    }
+                    None => {
+                        drop(store);
+                        std::thread::sleep(std::time::Duration::from_secs(args.interval));
+                    }
+                }
             }
+        }
+        #[cfg(feature = "watch")]
+        Commands::WatchFolder(args) => sync::drop_folder::execute_watch_folder(args, cli.quiet),
+        Commands::Decrypt(args) => security::at_rest::execute_decrypt(args, cli.quiet),
+        Commands::Config(args) => config::command::execute_config(args.action, cli.quiet),
+        Commands::Service(args) => match args.action {
+            ServiceAction::Install(install) => service::install(&install),
+            ServiceAction::Status(target) => service::status(&target),
+            ServiceAction::Uninstall(target) => service::uninstall(&target),
+        },
+        Commands::Docs(args) => {
+            cli::docs::execute_docs(args.topic);
             Ok(())
         }
+        #[cfg(feature = "net")]
+        Commands::Protocol(args) => match args.action {
+            ProtocolAction::Conformance(conf) => match conf.verify {
+                Some(path) => {
+                    let contents = std::fs::read_to_string(&path)?;
+                    let vectors: net::conformance::ConformanceVectors =
+                        serde_json::from_str(&contents).map_err(|e| {
+                            FluxError::Config(format!(
+                                "Invalid conformance vectors in {}: {}",
+                                path.display(),
+                                e
+                            ))
+                        })?;
+                    let failures = net::conformance::verify(&vectors);
+                    if failures.is_empty() {
+                        eprintln!(
+                            "All {} messages and the sample session match protocol v{}",
+                            vectors.messages.len(),
+                            net::protocol::PROTOCOL_VERSION
+                        );
+                        Ok(())
+                    } else {
+                        for failure in &failures {
+                            eprintln!("FAIL {}", failure);
+                        }
+                        Err(FluxError::ConformanceFailed {
+                            failures: failures.len(),
+                        })
+                    }
+                }
+                None => {
+                    let vectors = net::conformance::generate()?;
+                    let json = serde_json::to_string_pretty(&vectors).map_err(|e| {
+                        FluxError::Config(format!("Failed to serialize vectors: {}", e))
+                    })?;
+                    println!("{}", json);
+                    Ok(())
+                }
+            },
+        },
     }
 }
 
+/// `flux push` and `flux pull`: mirror `local` with `remote` on `target`.
+#[cfg(feature = "net")]
+fn run_mirror(
+    direction: net::tree::Direction,
+    target: &str,
+    local: &Path,
+    remote: &str,
+    args: cli::args::MirrorArgs,
+    quiet: bool,
+) -> Result<(), FluxError> {
+    let psk = args
+        .psk_file
+        .as_deref()
+        .map(security::psk::PreSharedKey::load)
+        .transpose()?;
+    let options = net::tree::MirrorOptions {
+        encrypt: !args.no_encrypt,
+        device_name: args
+            .name
+            .unwrap_or_else(|| gethostname::gethostname().to_string_lossy().to_string()),
+        psk,
+        delete: args.delete,
+        force: args.force,
+        dry_run: args.dry_run,
+        quiet,
+    };
+    net::tree::mirror_sync(direction, target, local, remote, &options)
+}
+
 /// Truncate a string to `max` characters, appending "..." if truncated.
 /// Uses char boundaries to avoid panics on multi-byte UTF-8 strings.
 fn truncate_str(s: &str, max: usize) -> String {
@@ -444,7 +917,24 @@ fn format_bytes(bytes: u64) -> String {
 }
 
 /// Display a FluxError with optional suggestion hint to stderr.
-fn display_error(err: &FluxError) {
+///
+/// With `--json`, prints one JSON object instead:
+/// `{"error": {"code": 2, "kind": "source_not_found", "message": ..., "hint": ...}}`.
+fn display_error(err: &FluxError, json: bool) {
+    if json {
+        let category = err.category();
+        let value = serde_json::json!({
+            "error": {
+                "code": category.code(),
+                "kind": category.name(),
+                "message": err.to_string(),
+                "hint": err.suggestion(),
+                "details": err.details(),
+            }
+        });
+        eprintln!("{}", value);
+        return;
+    }
     eprintln!("error: {}", err);
     if let Some(suggestion) = err.suggestion() {
         eprintln!("  hint: {}", suggestion);
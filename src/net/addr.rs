@@ -0,0 +1,256 @@
+//! Host and port handling for IPv4 and IPv6 peers.
+//!
+//! Targets are `host:port` strings, which are ambiguous for IPv6 literals
+//! (`fe80::1:9741`). IPv6 hosts with a port are written in brackets
+//! (`[::1]:9741`); a bare IPv6 address uses the default port. Link-local
+//! addresses need the interface they are reached through: a zone given as
+//! `fe80::1%eth0` (or the index, `%3`) is used as is, and without one every
+//! interface with a link-local address is tried in turn, since mDNS answers
+//! carry no zone.
+//!
+//! Receivers bound to `::` (the default) accept IPv4 and IPv6 on one
+//! dual-stack socket; IPv4 peers then show up as `::ffff:a.b.c.d`, which
+//! `canonical` turns back into plain IPv4.
+
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV6, ToSocketAddrs};
+
+use tokio::net::{TcpListener, TcpStream};
+
+/// Split `target` into host and port (`default_port` if it has none).
+/// Brackets around an IPv6 host are removed.
+pub fn split_host_port(target: &str, default_port: u16) -> (String, u16) {
+    if let Some(rest) = target.strip_prefix('[') {
+        if let Some((host, after)) = rest.split_once(']') {
+            let port = after
+                .strip_prefix(':')
+                .and_then(|port| port.parse().ok())
+                .unwrap_or(default_port);
+            return (host.to_string(), port);
+        }
+    }
+    // More than one colon: a bare IPv6 address
+    if target.matches(':').count() > 1 {
+        return (target.to_string(), default_port);
+    }
+    match target.rsplit_once(':') {
+        Some((host, port)) => match port.parse() {
+            Ok(port) => (host.to_string(), port),
+            // Could not parse port -- treat whole thing as host
+            Err(_) => (target.to_string(), default_port),
+        },
+        None => (target.to_string(), default_port),
+    }
+}
+
+/// `host:port`, with IPv6 hosts in brackets.
+pub fn join_host_port(host: &str, port: u16) -> String {
+    if host.contains(':') {
+        format!("[{}]:{}", host, port)
+    } else {
+        format!("{}:{}", host, port)
+    }
+}
+
+/// IPv4-mapped IPv6 addresses (from a dual-stack listener) as IPv4.
+pub fn canonical(addr: SocketAddr) -> SocketAddr {
+    match addr {
+        SocketAddr::V6(v6) => match v6.ip().to_ipv4_mapped() {
+            Some(v4) => SocketAddr::new(IpAddr::V4(v4), v6.port()),
+            None => addr,
+        },
+        SocketAddr::V4(_) => addr,
+    }
+}
+
+/// Whether `ip` is an IPv6 link-local address (`fe80::/10`).
+pub fn is_link_local_v6(ip: &Ipv6Addr) -> bool {
+    ip.segments()[0] & 0xffc0 == 0xfe80
+}
+
+/// Addresses to try for `host` (a name, an IP address, or a scoped IPv6
+/// address) and `port`. Names are looked up with the system resolver.
+pub fn socket_addrs(host: &str, port: u16) -> io::Result<Vec<SocketAddr>> {
+    match literal_addrs(host, port)? {
+        Some(addrs) => Ok(addrs),
+        None => Ok((host, port).to_socket_addrs()?.collect()),
+    }
+}
+
+/// Connect to the first of `host`'s addresses that accepts.
+pub async fn connect(host: &str, port: u16) -> io::Result<TcpStream> {
+    let addrs: Vec<SocketAddr> = match literal_addrs(host, port)? {
+        Some(addrs) => addrs,
+        None => tokio::net::lookup_host((host, port)).await?.collect(),
+    };
+    let mut last_error = None;
+    for addr in addrs {
+        match TcpStream::connect(addr).await {
+            Ok(stream) => return Ok(stream),
+            Err(e) => {
+                tracing::debug!("Could not connect to {}: {}", addr, e);
+                last_error = Some(e);
+            }
+        }
+    }
+    Err(last_error.unwrap_or_else(|| {
+        io::Error::new(io::ErrorKind::NotFound, format!("no address found for {}", host))
+    }))
+}
+
+/// Listen on `bind_addr` and `port`. `::` listens on IPv4 and IPv6 (IPv4
+/// only where IPv6 is unavailable); `0.0.0.0` on IPv4 only.
+pub async fn listen(bind_addr: &str, port: u16) -> io::Result<TcpListener> {
+    let host = bind_addr.trim_start_matches('[').trim_end_matches(']');
+    if host == "::" {
+        match dual_stack(port) {
+            Ok(listener) => return Ok(listener),
+            Err(e) => {
+                tracing::debug!("Cannot listen on IPv6 ({}), using IPv4 only", e);
+                return TcpListener::bind((Ipv4Addr::UNSPECIFIED, port)).await;
+            }
+        }
+    }
+    let addrs = socket_addrs(host, port)?;
+    TcpListener::bind(&addrs[..]).await
+}
+
+/// Whether `bind_addr` means every interface.
+pub fn is_unspecified(bind_addr: &str) -> bool {
+    let host = bind_addr.trim_start_matches('[').trim_end_matches(']');
+    host.parse::<IpAddr>().is_ok_and(|ip| ip.is_unspecified())
+}
+
+/// An IPv6 socket on `[::]:port` that also accepts IPv4 (`IPV6_V6ONLY`
+/// is on by default on Windows and some BSDs).
+fn dual_stack(port: u16) -> io::Result<TcpListener> {
+    use socket2::{Domain, Protocol, Socket, Type};
+
+    let socket = Socket::new(Domain::IPV6, Type::STREAM, Some(Protocol::TCP))?;
+    socket.set_only_v6(false)?;
+    // As `TcpListener::bind` does: restarts need not wait for TIME_WAIT
+    #[cfg(unix)]
+    socket.set_reuse_address(true)?;
+    socket.set_nonblocking(true)?;
+    socket.bind(&SocketAddr::from((Ipv6Addr::UNSPECIFIED, port)).into())?;
+    socket.listen(1024)?;
+    TcpListener::from_std(socket.into())
+}
+
+/// The addresses of an IP literal (`None` for host names). An unscoped
+/// link-local IPv6 address gets one candidate per local interface that has
+/// a link-local address.
+fn literal_addrs(host: &str, port: u16) -> io::Result<Option<Vec<SocketAddr>>> {
+    let (ip, zone) = match host.split_once('%') {
+        Some((ip, zone)) => (ip, Some(zone)),
+        None => (host, None),
+    };
+    let ip: Ipv6Addr = match (ip.parse::<IpAddr>(), zone) {
+        (Ok(IpAddr::V4(v4)), None) => return Ok(Some(vec![SocketAddr::from((v4, port))])),
+        (Ok(IpAddr::V6(v6)), _) => v6,
+        _ => return Ok(None),
+    };
+    let scopes = match zone {
+        Some(zone) => vec![scope_id(zone)?],
+        None if is_link_local_v6(&ip) => link_local_scopes(),
+        None => Vec::new(),
+    };
+    if scopes.is_empty() {
+        return Ok(Some(vec![SocketAddr::from((ip, port))]));
+    }
+    Ok(Some(
+        scopes
+            .into_iter()
+            .map(|scope| SocketAddr::V6(SocketAddrV6::new(ip, port, 0, scope)))
+            .collect(),
+    ))
+}
+
+/// The interface index a zone (`eth0`, or `3`) names.
+fn scope_id(zone: &str) -> io::Result<u32> {
+    if let Ok(index) = zone.parse() {
+        return Ok(index);
+    }
+    if_addrs::get_if_addrs()?
+        .into_iter()
+        .find(|iface| iface.name == zone)
+        .and_then(|iface| iface.index)
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("unknown network interface '{}'", zone),
+            )
+        })
+}
+
+/// Indexes of the interfaces with an IPv6 link-local address.
+fn link_local_scopes() -> Vec<u32> {
+    let mut scopes = Vec::new();
+    for iface in if_addrs::get_if_addrs().unwrap_or_default() {
+        if iface.is_loopback() {
+            continue;
+        }
+        if let (if_addrs::IfAddr::V6(v6), Some(index)) = (&iface.addr, iface.index) {
+            if is_link_local_v6(&v6.ip) && !scopes.contains(&index) {
+                scopes.push(index);
+            }
+        }
+    }
+    scopes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn targets_split_with_ipv6_literals() {
+        let split = |s| split_host_port(s, 9741);
+        assert_eq!(split("nas.local:8000"), ("nas.local".to_string(), 8000));
+        assert_eq!(split("192.168.1.5"), ("192.168.1.5".to_string(), 9741));
+        assert_eq!(split("[::1]:9000"), ("::1".to_string(), 9000));
+        assert_eq!(split("[fe80::1%eth0]:9000"), ("fe80::1%eth0".to_string(), 9000));
+        assert_eq!(split("[2001:db8::7]"), ("2001:db8::7".to_string(), 9741));
+        assert_eq!(split("2001:db8::7"), ("2001:db8::7".to_string(), 9741));
+        assert_eq!(split("fe80::1%3"), ("fe80::1%3".to_string(), 9741));
+        assert_eq!(split("host:notaport"), ("host:notaport".to_string(), 9741));
+
+        assert_eq!(join_host_port("::1", 9000), "[::1]:9000");
+        assert_eq!(join_host_port("nas.local", 9000), "nas.local:9000");
+    }
+
+    #[test]
+    fn literals_keep_their_zone() {
+        let addrs = socket_addrs("fe80::1%7", 9741).unwrap();
+        assert_eq!(addrs.len(), 1);
+        match addrs[0] {
+            SocketAddr::V6(v6) => assert_eq!((v6.scope_id(), v6.port()), (7, 9741)),
+            SocketAddr::V4(_) => panic!("expected an IPv6 address"),
+        }
+        assert!(socket_addrs("fe80::1%no-such-interface0", 9741).is_err());
+        assert_eq!(
+            socket_addrs("::1", 80).unwrap(),
+            vec![SocketAddr::from((Ipv6Addr::LOCALHOST, 80))]
+        );
+    }
+
+    #[test]
+    fn mapped_addresses_become_ipv4() {
+        let mapped: SocketAddr = "[::ffff:192.168.1.5]:9741".parse().unwrap();
+        assert_eq!(canonical(mapped), "192.168.1.5:9741".parse().unwrap());
+        let v6: SocketAddr = "[2001:db8::7]:9741".parse().unwrap();
+        assert_eq!(canonical(v6), v6);
+    }
+
+    #[tokio::test]
+    async fn dual_stack_listener_accepts_both_families() {
+        let listener = listen("::", 0).await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let accept = tokio::spawn(async move {
+            let (_, peer) = listener.accept().await.unwrap();
+            canonical(peer)
+        });
+        connect("127.0.0.1", port).await.unwrap();
+        assert_eq!(accept.await.unwrap().ip(), IpAddr::V4(Ipv4Addr::LOCALHOST));
+    }
+}
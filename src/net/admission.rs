@@ -0,0 +1,374 @@
+//! Admission control of a receiver (`[receive]` table in config.toml).
+//!
+//! The listener already caps concurrent connections at 8. On top of that,
+//! `connections_per_minute` limits how often one IP address may connect,
+//! and an address whose handshakes fail `ban_after_failures` times in a
+//! row (wrong key, refused device, garbage instead of a Handshake) is
+//! banned for `ban_secs`. Refused connections are closed right after
+//! `accept`, before anything is read. `max_receive_rate` caps the bytes per
+//! second of all connections together, so one peer can't saturate the
+//! receiver's disk or uplink.
+//!
+//! The state lives in memory only: a restarted receiver forgets its bans.
+
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::config::types::ReceiveConfig;
+use crate::error::FluxError;
+use crate::transfer::throttle::{parse_bandwidth, SharedLimiter};
+
+/// Failed handshakes in a row that get an address banned, by default.
+pub const DEFAULT_BAN_AFTER_FAILURES: u32 = 5;
+
+/// How long a ban lasts, by default.
+pub const DEFAULT_BAN: Duration = Duration::from_secs(10 * 60);
+
+/// Window of `connections_per_minute`.
+const RATE_WINDOW: Duration = Duration::from_secs(60);
+
+/// Addresses tracked before idle ones are forgotten.
+const MAX_TRACKED_PEERS: usize = 4096;
+
+/// Configured admission limits.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AdmissionLimits {
+    /// Connections per IP address and minute, `None` if unlimited
+    pub per_minute: Option<u32>,
+    /// Failed handshakes in a row before a ban, `None` to never ban
+    pub ban_after: Option<u32>,
+    /// Length of a ban
+    pub ban_for: Duration,
+    /// Bytes per second of all connections together, `None` if unlimited
+    pub max_rate: Option<u64>,
+}
+
+impl Default for AdmissionLimits {
+    fn default() -> Self {
+        Self {
+            per_minute: None,
+            ban_after: Some(DEFAULT_BAN_AFTER_FAILURES),
+            ban_for: DEFAULT_BAN,
+            max_rate: None,
+        }
+    }
+}
+
+impl AdmissionLimits {
+    /// Parse the `[receive]` admission settings. A `ban_after_failures` of 0
+    /// turns bans off; a `connections_per_minute` of 0 is refused.
+    pub fn from_config(config: &ReceiveConfig) -> Result<Self, FluxError> {
+        if config.connections_per_minute == Some(0) {
+            return Err(FluxError::Config(
+                "[receive] connections_per_minute must be at least 1 (leave it out for no limit)"
+                    .into(),
+            ));
+        }
+        let max_rate = config
+            .max_receive_rate
+            .as_deref()
+            .map(|rate| {
+                parse_bandwidth(rate).map_err(|_| {
+                    FluxError::Config(format!(
+                        "[receive] max_receive_rate '{}' is not a rate like 50MB/s",
+                        rate
+                    ))
+                })
+            })
+            .transpose()?;
+        Ok(Self {
+            per_minute: config.connections_per_minute,
+            ban_after: match config.ban_after_failures {
+                Some(0) => None,
+                Some(n) => Some(n),
+                None => Some(DEFAULT_BAN_AFTER_FAILURES),
+            },
+            ban_for: config.ban_secs.map(Duration::from_secs).unwrap_or(DEFAULT_BAN),
+            max_rate,
+        })
+    }
+}
+
+/// Why a connection was refused.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Refusal {
+    /// The address is banned for this much longer
+    Banned(Duration),
+    /// The address connected `connections_per_minute` times in the last minute
+    TooFrequent,
+}
+
+impl fmt::Display for Refusal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Refusal::Banned(left) => write!(f, "banned for another {}s", left.as_secs().max(1)),
+            Refusal::TooFrequent => write!(f, "too many connections per minute"),
+        }
+    }
+}
+
+/// What is known about one address.
+#[derive(Debug, Default)]
+struct Peer {
+    /// Accepted connections in the last `RATE_WINDOW`
+    recent: VecDeque<Instant>,
+    /// Failed handshakes since the last successful one
+    failures: u32,
+    banned_until: Option<Instant>,
+}
+
+impl Peer {
+    fn forget_before(&mut self, cutoff: Instant) {
+        while self.recent.front().is_some_and(|&at| at < cutoff) {
+            self.recent.pop_front();
+        }
+    }
+
+    fn is_idle(&self, now: Instant) -> bool {
+        self.recent.is_empty()
+            && self.failures == 0
+            && self.banned_until.is_none_or(|until| until <= now)
+    }
+}
+
+/// Admission state shared by all connections of a receiver.
+#[derive(Clone)]
+pub struct Admission {
+    limits: Arc<AdmissionLimits>,
+    peers: Arc<Mutex<HashMap<IpAddr, Peer>>>,
+    /// `max_receive_rate`, shared by every connection
+    rate: Arc<SharedLimiter>,
+}
+
+impl Admission {
+    /// Admission under `limits`, with no address seen yet.
+    pub fn new(limits: AdmissionLimits) -> Self {
+        Self {
+            rate: Arc::new(SharedLimiter::new(limits.max_rate)),
+            limits: Arc::new(limits),
+            peers: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// The same bans and connection counts under new limits (config
+    /// reload). The rate cap changes for running transfers too.
+    pub fn with_limits(&self, limits: AdmissionLimits) -> Self {
+        if limits.max_rate != self.limits.max_rate {
+            self.rate.set_rate(limits.max_rate);
+        }
+        Self {
+            limits: Arc::new(limits),
+            peers: Arc::clone(&self.peers),
+            rate: Arc::clone(&self.rate),
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<IpAddr, Peer>> {
+        self.peers.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Limiter of the bytes all connections receive together, `None` if
+    /// there is no `max_receive_rate`.
+    pub fn rate(&self) -> Option<Arc<SharedLimiter>> {
+        self.limits.max_rate.map(|_| Arc::clone(&self.rate))
+    }
+
+    /// Count a new connection from `ip`, or refuse it.
+    pub fn admit(&self, ip: IpAddr) -> Result<(), Refusal> {
+        self.admit_at(ip, Instant::now())
+    }
+
+    fn admit_at(&self, ip: IpAddr, now: Instant) -> Result<(), Refusal> {
+        let mut peers = self.lock();
+        if peers.len() >= MAX_TRACKED_PEERS {
+            let cutoff = now.checked_sub(RATE_WINDOW).unwrap_or(now);
+            peers.retain(|_, peer| {
+                peer.forget_before(cutoff);
+                !peer.is_idle(now)
+            });
+        }
+        let peer = peers.entry(ip).or_default();
+        match peer.banned_until {
+            Some(until) if until > now => return Err(Refusal::Banned(until - now)),
+            Some(_) => peer.banned_until = None,
+            None => {}
+        }
+        if let Some(limit) = self.limits.per_minute {
+            peer.forget_before(now.checked_sub(RATE_WINDOW).unwrap_or(now));
+            if peer.recent.len() >= limit as usize {
+                return Err(Refusal::TooFrequent);
+            }
+        }
+        peer.recent.push_back(now);
+        Ok(())
+    }
+
+    /// Record a failed handshake from `ip`. Returns true if it got the
+    /// address banned.
+    pub fn handshake_failed(&self, ip: IpAddr) -> bool {
+        self.handshake_failed_at(ip, Instant::now())
+    }
+
+    fn handshake_failed_at(&self, ip: IpAddr, now: Instant) -> bool {
+        let Some(ban_after) = self.limits.ban_after else {
+            return false;
+        };
+        let mut peers = self.lock();
+        let peer = peers.entry(ip).or_default();
+        peer.failures += 1;
+        if peer.failures < ban_after {
+            return false;
+        }
+        peer.failures = 0;
+        peer.banned_until = Some(now + self.limits.ban_for);
+        true
+    }
+
+    /// Record a successful handshake from `ip`, which clears its failures.
+    pub fn handshake_succeeded(&self, ip: IpAddr) {
+        if let Some(peer) = self.lock().get_mut(&ip) {
+            peer.failures = 0;
+        }
+    }
+}
+
+/// Whether a connection that ended with `error` counts as a failed
+/// handshake. `handshaken` says whether the peer sent a valid Handshake.
+pub fn is_handshake_failure(error: &FluxError, handshaken: bool) -> bool {
+    !handshaken || matches!(error, FluxError::TrustError(_) | FluxError::EncryptionError(_))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(last: u8) -> IpAddr {
+        IpAddr::from([192, 168, 1, last])
+    }
+
+    #[test]
+    fn limits_parse_from_config() {
+        let config = ReceiveConfig {
+            connections_per_minute: Some(30),
+            ban_after_failures: Some(3),
+            ban_secs: Some(60),
+            max_receive_rate: Some("50MB/s".into()),
+            ..Default::default()
+        };
+        let limits = AdmissionLimits::from_config(&config).unwrap();
+        assert_eq!(limits.per_minute, Some(30));
+        assert_eq!(limits.ban_after, Some(3));
+        assert_eq!(limits.ban_for, Duration::from_secs(60));
+        assert_eq!(limits.max_rate, Some(50_000_000));
+
+        let defaults = AdmissionLimits::from_config(&ReceiveConfig::default()).unwrap();
+        assert_eq!(defaults, AdmissionLimits::default());
+
+        let never = ReceiveConfig {
+            ban_after_failures: Some(0),
+            ..Default::default()
+        };
+        assert_eq!(AdmissionLimits::from_config(&never).unwrap().ban_after, None);
+
+        for bad in [
+            ReceiveConfig {
+                connections_per_minute: Some(0),
+                ..Default::default()
+            },
+            ReceiveConfig {
+                max_receive_rate: Some("fast".into()),
+                ..Default::default()
+            },
+        ] {
+            assert!(AdmissionLimits::from_config(&bad).is_err());
+        }
+    }
+
+    #[test]
+    fn connections_per_minute_are_limited_per_address() {
+        let admission = Admission::new(AdmissionLimits {
+            per_minute: Some(2),
+            ..Default::default()
+        });
+        let start = Instant::now();
+        assert!(admission.admit_at(ip(1), start).is_ok());
+        assert!(admission.admit_at(ip(1), start + Duration::from_secs(1)).is_ok());
+        assert_eq!(
+            admission.admit_at(ip(1), start + Duration::from_secs(2)),
+            Err(Refusal::TooFrequent)
+        );
+        // Another address has its own budget
+        assert!(admission.admit_at(ip(2), start + Duration::from_secs(2)).is_ok());
+        // Once the first connection is a minute old, there is room again
+        assert!(admission.admit_at(ip(1), start + Duration::from_secs(61)).is_ok());
+    }
+
+    #[test]
+    fn repeated_handshake_failures_ban_the_address() {
+        let admission = Admission::new(AdmissionLimits {
+            ban_after: Some(3),
+            ban_for: Duration::from_secs(60),
+            ..Default::default()
+        });
+        let start = Instant::now();
+        assert!(!admission.handshake_failed_at(ip(1), start));
+        assert!(!admission.handshake_failed_at(ip(1), start));
+        assert!(admission.handshake_failed_at(ip(1), start));
+
+        let later = start + Duration::from_secs(20);
+        assert_eq!(
+            admission.admit_at(ip(1), later),
+            Err(Refusal::Banned(Duration::from_secs(40)))
+        );
+        assert!(admission.admit_at(ip(2), later).is_ok());
+        assert!(admission.admit_at(ip(1), start + Duration::from_secs(60)).is_ok());
+    }
+
+    #[test]
+    fn successful_handshake_clears_failures() {
+        let admission = Admission::new(AdmissionLimits {
+            ban_after: Some(2),
+            ..Default::default()
+        });
+        let now = Instant::now();
+        assert!(!admission.handshake_failed_at(ip(1), now));
+        admission.handshake_succeeded(ip(1));
+        assert!(!admission.handshake_failed_at(ip(1), now));
+        assert!(admission.admit_at(ip(1), now).is_ok());
+
+        let lenient = Admission::new(AdmissionLimits {
+            ban_after: None,
+            ..Default::default()
+        });
+        assert!((0..100).all(|_| !lenient.handshake_failed_at(ip(1), now)));
+    }
+
+    #[test]
+    fn reload_keeps_bans_and_retunes_the_rate() {
+        let admission = Admission::new(AdmissionLimits {
+            ban_after: Some(1),
+            ..Default::default()
+        });
+        assert!(admission.rate().is_none());
+        admission.handshake_failed(ip(1));
+
+        let reloaded = admission.with_limits(AdmissionLimits {
+            max_rate: Some(1_000_000),
+            ..Default::default()
+        });
+        assert!(matches!(reloaded.admit(ip(1)), Err(Refusal::Banned(_))));
+        assert_eq!(reloaded.rate().unwrap().rate(), Some(1_000_000));
+    }
+
+    #[test]
+    fn handshake_failures_are_refusals_or_garbage() {
+        let trust = FluxError::TrustError("refused".into());
+        let quota = FluxError::QuotaExceeded("full".into());
+        assert!(is_handshake_failure(&trust, true));
+        assert!(!is_handshake_failure(&quota, true));
+        assert!(is_handshake_failure(&quota, false));
+    }
+}
@@ -0,0 +1,503 @@
+//! Audit log of network transfer events (`audit.jsonl` in the data dir).
+//!
+//! Every handshake a receiver gets, its trust decision, each file it accepts
+//! or refuses and how each transfer ends are appended as one JSON object
+//! per line, with the peer's device name, address and key. Sends record how
+//! they ended. The log is append-only; when it reaches `[audit] max_size`
+//! it is renamed to `audit.1.jsonl` (older logs move up to `audit.N.jsonl`,
+//! up to `[audit] keep`) and a new one is started.
+//!
+//! Recording is best effort: a log that cannot be written is reported as a
+//! warning and never fails a transfer. `flux audit tail` and
+//! `flux audit search` read the log back.
+
+use std::fmt;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use bytesize::ByteSize;
+use chrono::{DateTime, Local, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::config::types::AuditConfig;
+use crate::error::FluxError;
+use crate::security::trust::fingerprint;
+
+/// File name of the current log in the data directory.
+const AUDIT_FILE: &str = "audit.jsonl";
+
+/// Size at which the log is rotated when `[audit] max_size` is not set.
+const DEFAULT_MAX_SIZE: u64 = 10 * 1024 * 1024;
+
+/// Serializes appends (and rotation) of the connections of one process.
+static APPEND: Mutex<()> = Mutex::new(());
+
+/// What happened.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum AuditKind {
+    /// A peer opened a connection with a handshake
+    Handshake,
+    /// The peer's key was accepted (allowlist, trust store, prompt or
+    /// pre-shared key)
+    Trusted,
+    /// The peer was turned away (unknown or changed key, wrong pre-shared
+    /// key or code phrase)
+    Refused,
+    /// A file was accepted, before any of its data
+    Accepted,
+    /// A file was refused (quota or disk space)
+    Rejected,
+    /// A file was transferred completely
+    Completed,
+    /// A connection or transfer failed
+    Failed,
+    /// A file was deleted by a mirror (`flux push --delete`)
+    Deleted,
+}
+
+impl AuditKind {
+    /// The event for a connection that ended with `error`.
+    pub fn of_error(error: &FluxError) -> Self {
+        match error {
+            FluxError::TrustError(_) => AuditKind::Refused,
+            FluxError::QuotaExceeded(_) | FluxError::InsufficientSpace(_) => AuditKind::Rejected,
+            _ => AuditKind::Failed,
+        }
+    }
+}
+
+impl fmt::Display for AuditKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(match self {
+            AuditKind::Handshake => "handshake",
+            AuditKind::Trusted => "trusted",
+            AuditKind::Refused => "refused",
+            AuditKind::Accepted => "accepted",
+            AuditKind::Rejected => "rejected",
+            AuditKind::Completed => "completed",
+            AuditKind::Failed => "failed",
+            AuditKind::Deleted => "deleted",
+        })
+    }
+}
+
+/// One line of the audit log.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditEvent {
+    pub time: DateTime<Utc>,
+    pub event: AuditKind,
+    /// "receive", "send" or "web" (browser drops and downloads)
+    pub role: String,
+    /// Device name the peer gave (or the target of a send)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub peer: Option<String>,
+    /// Peer's IP address and port
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub addr: Option<String>,
+    /// Peer's public key (base64)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fingerprint: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+    /// Declared size of an accepted file, bytes moved by a finished one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bytes: Option<u64>,
+    /// Checksum verdict, `None` when no checksum was checked
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub verified: Option<bool>,
+    /// Why: the trust decision, refusal or error
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+impl AuditEvent {
+    pub fn file(mut self, name: &str, bytes: u64) -> Self {
+        self.file = Some(name.to_string());
+        self.bytes = Some(bytes);
+        self
+    }
+
+    pub fn verified(mut self, verified: Option<bool>) -> Self {
+        self.verified = verified;
+        self
+    }
+
+    pub fn reason(mut self, reason: impl ToString) -> Self {
+        self.reason = Some(reason.to_string());
+        self
+    }
+
+    /// Append the event to the audit log, unless `[audit] enabled = false`.
+    /// A failure is logged as a warning.
+    pub fn record(self) {
+        let config = crate::config::types::load_config().unwrap_or_default();
+        if !config.audit.enabled {
+            return;
+        }
+        let result = crate::config::paths::flux_data_dir()
+            .and_then(|data_dir| AuditLog::open(&data_dir, &config.audit))
+            .and_then(|log| log.append(&self));
+        if let Err(e) = result {
+            tracing::warn!("Audit event not recorded: {}", e);
+        }
+    }
+}
+
+impl fmt::Display for AuditEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut line = format!(
+            "{}  {:<9}  {:<7}",
+            self.time.with_timezone(&Local).format("%Y-%m-%d %H:%M:%S"),
+            self.event,
+            self.role
+        );
+        match (&self.peer, &self.addr) {
+            (Some(peer), Some(addr)) => line.push_str(&format!("  {} ({})", peer, addr)),
+            (Some(who), None) | (None, Some(who)) => line.push_str(&format!("  {}", who)),
+            (None, None) => {}
+        }
+        if let Some(ref key) = self.fingerprint {
+            line.push_str(&format!("  key {}", fingerprint(key)));
+        }
+        if let Some(ref file) = self.file {
+            line.push_str(&format!("  {}", file));
+        }
+        if let Some(bytes) = self.bytes {
+            line.push_str(&format!(" ({})", ByteSize(bytes)));
+        }
+        match self.verified {
+            Some(true) => line.push_str("  checksum ok"),
+            Some(false) => line.push_str("  checksum MISMATCH"),
+            None => {}
+        }
+        if let Some(ref reason) = self.reason {
+            line.push_str(&format!(": {}", reason));
+        }
+        f.pad(&line)
+    }
+}
+
+/// What the events of one connection share, filled in as it goes.
+#[derive(Debug, Clone)]
+pub struct AuditTrail {
+    role: &'static str,
+    pub addr: Option<String>,
+    pub peer: Option<String>,
+    /// Peer's public key (base64), once it sent one
+    pub fingerprint: Option<String>,
+    /// The file being transferred
+    pub file: Option<String>,
+    /// Its declared size
+    pub size: Option<u64>,
+}
+
+impl AuditTrail {
+    pub fn new(role: &'static str, addr: Option<SocketAddr>) -> Self {
+        Self {
+            role,
+            addr: addr.map(|addr| addr.to_string()),
+            peer: None,
+            fingerprint: None,
+            file: None,
+            size: None,
+        }
+    }
+
+    /// An event of this connection, happening now.
+    pub fn event(&self, event: AuditKind) -> AuditEvent {
+        AuditEvent {
+            time: Utc::now(),
+            event,
+            role: self.role.to_string(),
+            peer: self.peer.clone(),
+            addr: self.addr.clone(),
+            fingerprint: self.fingerprint.clone(),
+            file: self.file.clone(),
+            bytes: self.size,
+            verified: None,
+            reason: None,
+        }
+    }
+
+    /// Record how a transfer ended: `Completed` with the bytes moved and
+    /// the checksum verdict, or the event for the error that ended it.
+    pub fn finish(&self, result: Result<(u64, Option<bool>), &FluxError>) {
+        match result {
+            Ok((bytes, verified)) => {
+                let mut event = self.event(AuditKind::Completed).verified(verified);
+                event.bytes = Some(bytes);
+                event.record();
+            }
+            Err(e) => self.event(AuditKind::of_error(e)).reason(e).record(),
+        }
+    }
+}
+
+/// The audit log files in the data directory.
+pub struct AuditLog {
+    path: PathBuf,
+    max_size: u64,
+    keep: usize,
+}
+
+impl AuditLog {
+    /// The log in `data_dir` with the `[audit]` rotation settings.
+    pub fn open(data_dir: &Path, config: &AuditConfig) -> Result<Self, FluxError> {
+        let max_size = match config.max_size.as_deref() {
+            Some(size) => size.trim().parse::<ByteSize>().map_err(|_| {
+                FluxError::Config(format!(
+                    "Invalid audit max_size '{}'. Use sizes like '10MB' or '500KiB'",
+                    size
+                ))
+            })?,
+            None => ByteSize(DEFAULT_MAX_SIZE),
+        };
+        Ok(Self {
+            path: data_dir.join(AUDIT_FILE),
+            max_size: max_size.as_u64(),
+            keep: config.keep,
+        })
+    }
+
+    /// The log configured in config.toml.
+    pub fn load() -> Result<Self, FluxError> {
+        let config = crate::config::types::load_config()?;
+        Self::open(&crate::config::paths::flux_data_dir()?, &config.audit)
+    }
+
+    /// Append `event` as one line, rotating the log first if the line would
+    /// take it past `max_size`.
+    pub fn append(&self, event: &AuditEvent) -> Result<(), FluxError> {
+        let mut line = serde_json::to_string(event)?;
+        line.push('\n');
+
+        let _guard = APPEND.lock().unwrap_or_else(|e| e.into_inner());
+        let size = std::fs::metadata(&self.path).map(|m| m.len()).unwrap_or(0);
+        if size > 0 && size + line.len() as u64 > self.max_size {
+            self.rotate()?;
+        }
+        let mut options = OpenOptions::new();
+        options.create(true).append(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        options.open(&self.path)?.write_all(line.as_bytes())?;
+        Ok(())
+    }
+
+    fn rotated(&self, n: usize) -> PathBuf {
+        self.path.with_file_name(format!("audit.{}.jsonl", n))
+    }
+
+    /// Move the current log to `audit.1.jsonl`, the older ones up by one,
+    /// and drop the oldest beyond `keep`.
+    fn rotate(&self) -> Result<(), FluxError> {
+        if self.keep == 0 {
+            std::fs::remove_file(&self.path)?;
+            return Ok(());
+        }
+        let _ = std::fs::remove_file(self.rotated(self.keep));
+        for n in (1..self.keep).rev() {
+            let from = self.rotated(n);
+            if from.exists() {
+                std::fs::rename(&from, self.rotated(n + 1))?;
+            }
+        }
+        std::fs::rename(&self.path, self.rotated(1))?;
+        Ok(())
+    }
+
+    /// Every event in the log, oldest first. Lines that do not parse are
+    /// skipped.
+    pub fn events(&self) -> Result<Vec<AuditEvent>, FluxError> {
+        let mut files: Vec<PathBuf> = (1..=self.keep).rev().map(|n| self.rotated(n)).collect();
+        files.push(self.path.clone());
+
+        let mut events = Vec::new();
+        for file in files {
+            let text = match std::fs::read_to_string(&file) {
+                Ok(text) => text,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(e.into()),
+            };
+            events.extend(text.lines().filter_map(|line| serde_json::from_str(line).ok()));
+        }
+        Ok(events)
+    }
+}
+
+/// Which events `flux audit search` shows.
+#[derive(Debug, Default)]
+pub struct AuditFilter {
+    /// Text in the peer, address, key, file or reason (any case)
+    pub text: Option<String>,
+    /// Device name or IP address of the peer
+    pub peer: Option<String>,
+    pub event: Option<AuditKind>,
+    /// Events on or after this (local) day
+    pub since: Option<NaiveDate>,
+}
+
+impl AuditFilter {
+    pub fn matches(&self, event: &AuditEvent) -> bool {
+        if self.event.is_some_and(|kind| kind != event.event) {
+            return false;
+        }
+        if let Some(since) = self.since {
+            if event.time.with_timezone(&Local).date_naive() < since {
+                return false;
+            }
+        }
+        if let Some(ref peer) = self.peer {
+            let ip = event
+                .addr
+                .as_deref()
+                .and_then(|addr| addr.parse::<SocketAddr>().ok())
+                .map(|addr| addr.ip().to_string());
+            if event.peer.as_ref() != Some(peer)
+                && event.addr.as_ref() != Some(peer)
+                && ip.as_ref() != Some(peer)
+            {
+                return false;
+            }
+        }
+        if let Some(ref text) = self.text {
+            let text = text.to_lowercase();
+            let found = [
+                &event.peer,
+                &event.addr,
+                &event.fingerprint,
+                &event.file,
+                &event.reason,
+            ]
+            .into_iter()
+            .flatten()
+            .any(|field| field.to_lowercase().contains(&text));
+            if !found {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// Print `events` for `flux audit`: one line each, or their JSON lines.
+pub fn print_events(events: &[&AuditEvent], json: bool) -> Result<(), FluxError> {
+    if events.is_empty() {
+        eprintln!("No audit events");
+        return Ok(());
+    }
+    for event in events {
+        if json {
+            println!("{}", serde_json::to_string(event)?);
+        } else {
+            println!("{}", event);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn log(dir: &Path, max_size: &str, keep: usize) -> AuditLog {
+        let config = AuditConfig {
+            enabled: true,
+            max_size: Some(max_size.to_string()),
+            keep,
+        };
+        AuditLog::open(dir, &config).unwrap()
+    }
+
+    fn received(n: u64) -> AuditEvent {
+        let mut trail = AuditTrail::new("receive", Some("192.168.1.5:50122".parse().unwrap()));
+        trail.peer = Some("laptop".to_string());
+        trail.fingerprint = Some("q83vEjRWeJCrze8SjyEFuuA4yq6Uyu5oXjqkN7xgRXM=".to_string());
+        trail.file = Some(format!("report-{}.pdf", n));
+        trail.size = Some(n);
+        trail.event(AuditKind::Completed).verified(Some(true))
+    }
+
+    #[test]
+    fn events_are_read_back_in_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = log(dir.path(), "1MB", 5);
+        assert!(log.events().unwrap().is_empty());
+
+        let written: Vec<AuditEvent> = (1..=3).map(received).collect();
+        for event in &written {
+            log.append(event).unwrap();
+        }
+        assert_eq!(log.events().unwrap(), written);
+    }
+
+    #[test]
+    fn full_log_is_rotated_and_oldest_dropped() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = log(dir.path(), "1KiB", 2);
+        for n in 1..=40 {
+            log.append(&received(n)).unwrap();
+        }
+
+        assert!(log.rotated(1).exists());
+        assert!(log.rotated(2).exists());
+        assert!(!log.rotated(3).exists());
+        for path in [log.path.clone(), log.rotated(1), log.rotated(2)] {
+            assert!(std::fs::metadata(&path).unwrap().len() <= 1024);
+        }
+        let events = log.events().unwrap();
+        assert!(events.len() < 40);
+        assert_eq!(events.last().unwrap().file.as_deref(), Some("report-40.pdf"));
+        assert!(events.windows(2).all(|pair| pair[0].bytes < pair[1].bytes));
+    }
+
+    #[test]
+    fn filter_matches_peer_text_kind_and_day() {
+        let event = received(7);
+        let matches = |filter: AuditFilter| filter.matches(&event);
+
+        assert!(matches(AuditFilter::default()));
+        assert!(matches(AuditFilter {
+            peer: Some("laptop".into()),
+            ..Default::default()
+        }));
+        assert!(matches(AuditFilter {
+            peer: Some("192.168.1.5".into()),
+            ..Default::default()
+        }));
+        assert!(!matches(AuditFilter {
+            peer: Some("192.168.1.50".into()),
+            ..Default::default()
+        }));
+        assert!(matches(AuditFilter {
+            text: Some("REPORT-7".into()),
+            ..Default::default()
+        }));
+        assert!(!matches(AuditFilter {
+            event: Some(AuditKind::Refused),
+            ..Default::default()
+        }));
+        let tomorrow = Local::now().date_naive().succ_opt().unwrap();
+        assert!(!matches(AuditFilter {
+            since: Some(tomorrow),
+            ..Default::default()
+        }));
+    }
+
+    #[test]
+    fn errors_map_to_events() {
+        let refused = FluxError::TrustError("Refused unknown device 'x'".into());
+        assert_eq!(AuditKind::of_error(&refused), AuditKind::Refused);
+        let full = FluxError::QuotaExceeded("storage limit".into());
+        assert_eq!(AuditKind::of_error(&full), AuditKind::Rejected);
+        let dropped = FluxError::TransferError("Connection closed".into());
+        assert_eq!(AuditKind::of_error(&dropped), AuditKind::Failed);
+    }
+}
@@ -0,0 +1,554 @@
+//! Content-defined chunking for repeated sends.
+//!
+//! Sending a slightly changed version of a large file again would resend
+//! every byte. Instead the sender cuts the file into FastCDC chunks, whose
+//! boundaries follow the content so an edit only changes the chunks around
+//! it, and lists their BLAKE3 hashes in `ChunkList` messages after the
+//! `FileHeader`. The receiver looks them up in its chunk index and answers
+//! with `ChunksHave`; the sender then sends a `ChunkRef` in place of the
+//! data of each chunk the receiver already has, and the receiver copies it
+//! from the earlier file (checking its hash) into the new one.
+//!
+//! The index is the `chunks` table of the receiver's state database
+//! (`crate::state`): for every chunk of a file received with a chunk list,
+//! the file, offset and length it can be read back from. It is kept per
+//! sending device, so a sender cannot probe for content another device
+//! sent. Rows that point at a file that was moved or changed are dropped
+//! when found.
+//!
+//! The chunk list and the answer are encrypted like `DataChunk`s when the
+//! session is, so chunk hashes never show on the wire.
+
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+use fastcdc::v2020::StreamCDC;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+
+use crate::error::FluxError;
+use crate::net::protocol::{FluxMessage, MAX_FRAME_SIZE};
+use crate::security::crypto::EncryptedChannel;
+
+/// Smallest FastCDC chunk (256 KB); only the last chunk of a file may be
+/// shorter.
+const MIN_CHUNK: u32 = 256 * 1024;
+
+/// Average FastCDC chunk (1 MB).
+const AVG_CHUNK: u32 = 1024 * 1024;
+
+/// Largest FastCDC chunk (4 MB).
+const MAX_CHUNK: u32 = 4 * 1024 * 1024;
+
+/// Files smaller than this are sent as they are: a handful of chunks does
+/// not save enough to be worth the extra round trip.
+pub const MIN_FILE_SIZE: u64 = 4 * 1024 * 1024;
+
+/// Chunks per `ChunkList` message (about 600 KB encoded).
+pub const LIST_BATCH: usize = 16 * 1024;
+
+/// Index rows kept at most; the oldest are dropped first.
+const MAX_INDEXED_CHUNKS: i64 = 1_000_000;
+
+/// One chunk of a file, as listed in a `ChunkList`.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChunkInfo {
+    /// BLAKE3 hash of the chunk's data
+    pub hash: [u8; 32],
+    pub len: u32,
+}
+
+/// Cut the file at `path` into content-defined chunks.
+pub fn chunk_file(path: &Path) -> Result<Vec<ChunkInfo>, FluxError> {
+    let file = File::open(path).map_err(|e| {
+        FluxError::TransferError(format!("Failed to open '{}': {}", path.display(), e))
+    })?;
+    let mut chunks = Vec::new();
+    for chunk in StreamCDC::new(file, MIN_CHUNK, AVG_CHUNK, MAX_CHUNK) {
+        let chunk = chunk.map_err(|e| {
+            FluxError::TransferError(format!("Failed to read '{}': {}", path.display(), e))
+        })?;
+        chunks.push(ChunkInfo {
+            hash: *blake3::hash(&chunk.data).as_bytes(),
+            len: chunk.length as u32,
+        });
+    }
+    Ok(chunks)
+}
+
+/// A stretch of the file as the sender streams it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Segment {
+    /// Bytes sent as `DataChunk`s
+    Data { offset: u64, len: u64 },
+    /// A chunk the receiver has, sent as a `ChunkRef`
+    Ref { offset: u64, index: u32, len: u32 },
+}
+
+/// Plan the stream of a file cut into `chunks`; `have` marks the chunks the
+/// receiver already holds. Neighbouring missing chunks become one `Data`
+/// segment.
+pub fn segments(chunks: &[ChunkInfo], have: &[bool]) -> Vec<Segment> {
+    let mut segments = Vec::new();
+    let mut offset = 0;
+    for (index, (chunk, &had)) in chunks.iter().zip(have).enumerate() {
+        if had {
+            segments.push(Segment::Ref {
+                offset,
+                index: index as u32,
+                len: chunk.len,
+            });
+        } else if let Some(Segment::Data { len, .. }) = segments.last_mut() {
+            *len += chunk.len as u64;
+        } else {
+            segments.push(Segment::Data {
+                offset,
+                len: chunk.len as u64,
+            });
+        }
+        offset += chunk.len as u64;
+    }
+    segments
+}
+
+/// Bytes of `segments` the receiver copies from its own files.
+pub fn reused_bytes(segments: &[Segment]) -> u64 {
+    segments
+        .iter()
+        .map(|segment| match *segment {
+            Segment::Ref { len, .. } => len as u64,
+            Segment::Data { .. } => 0,
+        })
+        .sum()
+}
+
+/// A `ChunkList` message carrying `batch`; `last` ends the list.
+pub fn list_message(
+    batch: &[ChunkInfo],
+    last: bool,
+    channel: Option<&EncryptedChannel>,
+) -> Result<FluxMessage, FluxError> {
+    let encoded = bincode::serde::encode_to_vec(batch, bincode::config::standard())
+        .map_err(|e| FluxError::TransferError(format!("Failed to encode chunk list: {}", e)))?;
+    let (data, nonce) = seal(channel, encoded)?;
+    Ok(FluxMessage::ChunkList { data, nonce, last })
+}
+
+/// Which of `count` listed chunks the receiver has, from its `ChunksHave`.
+pub fn read_have(
+    data: Vec<u8>,
+    nonce: Option<Vec<u8>>,
+    channel: Option<&EncryptedChannel>,
+    count: usize,
+) -> Result<Vec<bool>, FluxError> {
+    let bits = unseal(channel, data, nonce)?;
+    if bits.len() != count.div_ceil(8) {
+        return Err(FluxError::TransferError(format!(
+            "Chunk answer has {} bytes for {} chunks",
+            bits.len(),
+            count
+        )));
+    }
+    Ok((0..count).map(|i| (bits[i / 8] & (1 << (i % 8))) != 0).collect())
+}
+
+/// A `ChunksHave` message: one bit per chunk, lowest bit first.
+fn have_message(
+    have: &[bool],
+    channel: Option<&EncryptedChannel>,
+) -> Result<FluxMessage, FluxError> {
+    let mut bits = vec![0u8; have.len().div_ceil(8)];
+    for (i, &had) in have.iter().enumerate() {
+        if had {
+            bits[i / 8] |= 1 << (i % 8);
+        }
+    }
+    let (data, nonce) = seal(channel, bits)?;
+    Ok(FluxMessage::ChunksHave { data, nonce })
+}
+
+/// Encrypt `plain` with `channel`, if there is one.
+pub(crate) fn seal(
+    channel: Option<&EncryptedChannel>,
+    plain: Vec<u8>,
+) -> Result<(Vec<u8>, Option<Vec<u8>>), FluxError> {
+    match channel {
+        Some(channel) => {
+            let (data, nonce) = channel.encrypt(&plain)?;
+            Ok((data, Some(nonce.to_vec())))
+        }
+        None => Ok((plain, None)),
+    }
+}
+
+/// Decrypt what `seal` produced on the other side.
+pub(crate) fn unseal(
+    channel: Option<&EncryptedChannel>,
+    data: Vec<u8>,
+    nonce: Option<Vec<u8>>,
+) -> Result<Vec<u8>, FluxError> {
+    match channel {
+        Some(channel) => {
+            let nonce: [u8; 24] = nonce
+                .ok_or_else(|| {
+                    FluxError::EncryptionError("Encrypted chunk list missing nonce".into())
+                })?
+                .try_into()
+                .map_err(|_| FluxError::EncryptionError("Nonce must be 24 bytes".into()))?;
+            channel.decrypt(&data, &nonce)
+        }
+        None => Ok(data),
+    }
+}
+
+/// Where a chunk can be read back from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ChunkSource {
+    path: PathBuf,
+    start: u64,
+}
+
+/// Chunks of received files, in the state database.
+pub struct ChunkIndex {
+    db: Connection,
+}
+
+impl ChunkIndex {
+    /// Open the index in the state database in `data_dir`.
+    pub fn load(data_dir: &Path) -> Result<Self, FluxError> {
+        Ok(Self {
+            db: crate::state::open(data_dir)?,
+        })
+    }
+
+    /// The index in the data directory, or `None` (with a warning) if it
+    /// cannot be opened; files are then received without one.
+    pub fn open() -> Option<Self> {
+        match crate::config::paths::flux_data_dir().and_then(|dir| Self::load(&dir)) {
+            Ok(index) => Some(index),
+            Err(e) => {
+                tracing::warn!("Repeated sends will not be deduplicated: {}", e);
+                None
+            }
+        }
+    }
+
+    /// Where `chunk` from `peer` can be read back, if anywhere. A row whose
+    /// file is gone or too short is dropped.
+    fn locate(&self, peer: &str, chunk: &ChunkInfo) -> Result<Option<ChunkSource>, FluxError> {
+        let row: Option<(String, i64)> = self
+            .db
+            .query_row(
+                "SELECT path, start FROM chunks WHERE peer = ?1 AND hash = ?2 AND len = ?3",
+                params![peer, &chunk.hash[..], chunk.len],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()?;
+        let Some((path, start)) = row else {
+            return Ok(None);
+        };
+        let source = ChunkSource {
+            path: PathBuf::from(path),
+            start: start as u64,
+        };
+        let end = source.start + chunk.len as u64;
+        if std::fs::metadata(&source.path).is_ok_and(|meta| meta.len() >= end) {
+            Ok(Some(source))
+        } else {
+            self.forget(peer, chunk)?;
+            Ok(None)
+        }
+    }
+
+    /// Read `chunk` from `source` and check its hash. A chunk whose file
+    /// changed since it was indexed is dropped from the index.
+    fn read(
+        &self,
+        peer: &str,
+        chunk: &ChunkInfo,
+        source: &ChunkSource,
+    ) -> Result<Vec<u8>, FluxError> {
+        let mut data = vec![0u8; chunk.len as usize];
+        let read = File::open(&source.path).and_then(|mut file| {
+            file.seek(SeekFrom::Start(source.start))?;
+            file.read_exact(&mut data)
+        });
+        if read.is_ok() && blake3::hash(&data).as_bytes() == &chunk.hash {
+            return Ok(data);
+        }
+        self.forget(peer, chunk)?;
+        Err(FluxError::TransferError(format!(
+            "'{}' changed since it was received; send the file again",
+            source.path.display()
+        )))
+    }
+
+    fn forget(&self, peer: &str, chunk: &ChunkInfo) -> Result<(), FluxError> {
+        self.db.execute(
+            "DELETE FROM chunks WHERE peer = ?1 AND hash = ?2",
+            params![peer, &chunk.hash[..]],
+        )?;
+        Ok(())
+    }
+
+    /// Index the chunks of `path`, received from `peer`, dropping the
+    /// oldest rows past `MAX_INDEXED_CHUNKS`.
+    fn record(&mut self, peer: &str, path: &Path, chunks: &[ChunkInfo]) -> Result<(), FluxError> {
+        let path = path.to_str().ok_or_else(|| {
+            FluxError::TransferError(format!("Cannot index non-UTF-8 path '{}'", path.display()))
+        })?;
+        let tx = self.db.transaction()?;
+        {
+            let mut insert = tx.prepare(
+                "INSERT OR REPLACE INTO chunks (peer, hash, path, start, len)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+            )?;
+            let mut start = 0i64;
+            for chunk in chunks {
+                insert.execute(params![peer, &chunk.hash[..], path, start, chunk.len])?;
+                start += chunk.len as i64;
+            }
+        }
+        tx.execute(
+            "DELETE FROM chunks WHERE rowid IN
+                (SELECT rowid FROM chunks ORDER BY rowid DESC LIMIT -1 OFFSET ?1)",
+            params![MAX_INDEXED_CHUNKS],
+        )?;
+        tx.commit()?;
+        Ok(())
+    }
+}
+
+/// The chunk list of a file being received, and where its known chunks can
+/// be copied from.
+pub struct IncomingChunks {
+    index: ChunkIndex,
+    /// Sending device, whose part of the index is used
+    peer: String,
+    size: u64,
+    chunks: Vec<ChunkInfo>,
+    /// Source of each listed chunk, once the list is complete
+    sources: Vec<Option<ChunkSource>>,
+    complete: bool,
+}
+
+impl IncomingChunks {
+    pub fn new(index: ChunkIndex, peer: &str, size: u64) -> Self {
+        Self {
+            index,
+            peer: peer.to_string(),
+            size,
+            chunks: Vec::new(),
+            sources: Vec::new(),
+            complete: false,
+        }
+    }
+
+    /// Take one `ChunkList` message. Once the list is complete, returns the
+    /// `ChunksHave` answer for the sender.
+    pub fn add_list(
+        &mut self,
+        data: Vec<u8>,
+        nonce: Option<Vec<u8>>,
+        last: bool,
+        channel: Option<&EncryptedChannel>,
+    ) -> Result<Option<FluxMessage>, FluxError> {
+        if self.complete {
+            return Err(FluxError::TransferError("Unexpected chunk list".into()));
+        }
+        let encoded = unseal(channel, data, nonce)?;
+        let config = bincode::config::standard().with_limit::<MAX_FRAME_SIZE>();
+        let (batch, _): (Vec<ChunkInfo>, usize) =
+            bincode::serde::decode_from_slice(&encoded, config).map_err(|e| {
+                FluxError::TransferError(format!("Failed to decode chunk list: {}", e))
+            })?;
+        // Every chunk but the last is at least MIN_CHUNK long
+        let most = self.size / MIN_CHUNK as u64 + 1;
+        if (self.chunks.len() + batch.len()) as u64 > most
+            || batch.iter().any(|c| c.len == 0 || c.len > MAX_CHUNK)
+        {
+            return Err(FluxError::TransferError("Invalid chunk list".into()));
+        }
+        self.chunks.extend(batch);
+        if !last {
+            return Ok(None);
+        }
+
+        let listed: u64 = self.chunks.iter().map(|c| c.len as u64).sum();
+        if listed != self.size {
+            return Err(FluxError::TransferError(format!(
+                "Chunk list covers {} bytes of a {} byte file",
+                listed, self.size
+            )));
+        }
+        self.sources = self
+            .chunks
+            .iter()
+            .map(|chunk| self.index.locate(&self.peer, chunk))
+            .collect::<Result<_, _>>()?;
+        self.complete = true;
+        let have: Vec<bool> = self.sources.iter().map(Option::is_some).collect();
+        have_message(&have, channel).map(Some)
+    }
+
+    /// Data of listed chunk `index`, copied from an earlier file.
+    pub fn read(&self, index: u32) -> Result<Vec<u8>, FluxError> {
+        let i = index as usize;
+        match self.sources.get(i) {
+            Some(Some(source)) => self.index.read(&self.peer, &self.chunks[i], source),
+            _ => Err(FluxError::TransferError(format!(
+                "Sender referred to chunk {} the receiver does not have",
+                index
+            ))),
+        }
+    }
+
+    /// Index the chunks of the finished file, now at `path`, for later
+    /// sends. Failures are logged: only deduplication is lost.
+    pub fn record(mut self, path: &Path) {
+        if !self.complete {
+            return;
+        }
+        let path = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+        if let Err(e) = self.index.record(&self.peer, &path, &self.chunks) {
+            tracing::warn!("Failed to index chunks of '{}': {}", path.display(), e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    /// Deterministic incompressible bytes (xorshift).
+    fn noise(len: usize, seed: u64) -> Vec<u8> {
+        let mut state = seed | 1;
+        (0..len)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state as u8
+            })
+            .collect()
+    }
+
+    fn list_of(index: &mut IncomingChunks, chunks: &[ChunkInfo]) -> Vec<bool> {
+        let FluxMessage::ChunkList { data, nonce, last } = list_message(chunks, true, None).unwrap()
+        else {
+            panic!("expected a ChunkList");
+        };
+        match index.add_list(data, nonce, last, None).unwrap() {
+            Some(FluxMessage::ChunksHave { data, nonce }) => {
+                read_have(data, nonce, None, chunks.len()).unwrap()
+            }
+            other => panic!("expected ChunksHave, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn an_edit_changes_only_nearby_chunks() {
+        let dir = TempDir::new().unwrap();
+        let original = noise(12 * 1024 * 1024, 7);
+        let mut edited = original.clone();
+        edited.splice(5_000_000..5_000_000, b"inserted".iter().copied());
+        std::fs::write(dir.path().join("a"), &original).unwrap();
+        std::fs::write(dir.path().join("b"), &edited).unwrap();
+
+        let a = chunk_file(&dir.path().join("a")).unwrap();
+        let b = chunk_file(&dir.path().join("b")).unwrap();
+        assert_eq!(a.iter().map(|c| c.len as usize).sum::<usize>(), original.len());
+        assert!(a.iter().all(|c| c.len <= MAX_CHUNK));
+        let shared = b.iter().filter(|c| a.contains(c)).count();
+        assert!(shared + 2 >= b.len(), "{} of {} chunks shared", shared, b.len());
+    }
+
+    #[test]
+    fn segments_merge_missing_chunks() {
+        let chunk = |len| ChunkInfo { hash: [0; 32], len };
+        let chunks = [chunk(10), chunk(20), chunk(30), chunk(40)];
+        let plan = segments(&chunks, &[false, false, true, false]);
+        assert_eq!(
+            plan,
+            [
+                Segment::Data { offset: 0, len: 30 },
+                Segment::Ref { offset: 30, index: 2, len: 30 },
+                Segment::Data { offset: 60, len: 40 },
+            ]
+        );
+        assert_eq!(reused_bytes(&plan), 30);
+    }
+
+    #[test]
+    fn have_flags_roundtrip_encrypted() {
+        let (secret_a, public_a) = EncryptedChannel::initiate();
+        let (secret_b, public_b) = EncryptedChannel::initiate();
+        let a = EncryptedChannel::complete(secret_a, &public_b);
+        let b = EncryptedChannel::complete(secret_b, &public_a);
+
+        let have = [true, false, false, true, true, false, true, false, true];
+        let FluxMessage::ChunksHave { data, nonce } = have_message(&have, Some(&a)).unwrap() else {
+            panic!("expected ChunksHave");
+        };
+        assert!(nonce.is_some());
+        assert_eq!(read_have(data.clone(), nonce.clone(), Some(&b), 9).unwrap(), have);
+        assert!(read_have(data, nonce, Some(&b), 20).is_err());
+    }
+
+    #[test]
+    fn received_chunks_are_found_per_peer() {
+        let data_dir = TempDir::new().unwrap();
+        let files = TempDir::new().unwrap();
+        let content = noise(6 * 1024 * 1024, 3);
+        let received = files.path().join("received.bin");
+        std::fs::write(&received, &content).unwrap();
+        let chunks = chunk_file(&received).unwrap();
+        let size = content.len() as u64;
+
+        // First transfer: nothing known yet, then indexed
+        let index = ChunkIndex::load(data_dir.path()).unwrap();
+        let mut incoming = IncomingChunks::new(index, "laptop", size);
+        assert!(list_of(&mut incoming, &chunks).iter().all(|&had| !had));
+        incoming.record(&received);
+
+        // The same sender finds every chunk; another one none
+        let index = ChunkIndex::load(data_dir.path()).unwrap();
+        let mut incoming = IncomingChunks::new(index, "laptop", size);
+        assert!(list_of(&mut incoming, &chunks).iter().all(|&had| had));
+        let first = chunks[0].len as usize;
+        assert_eq!(incoming.read(0).unwrap(), &content[..first]);
+        let index = ChunkIndex::load(data_dir.path()).unwrap();
+        let mut other = IncomingChunks::new(index, "phone", size);
+        assert!(list_of(&mut other, &chunks).iter().all(|&had| !had));
+
+        // A changed file is caught on read and dropped from the index
+        let mut changed = content.clone();
+        changed[10] ^= 0xff;
+        std::fs::write(&received, &changed).unwrap();
+        assert!(incoming.read(0).is_err());
+        let index = ChunkIndex::load(data_dir.path()).unwrap();
+        let mut again = IncomingChunks::new(index, "laptop", size);
+        let have = list_of(&mut again, &chunks);
+        assert!(!have[0]);
+        assert!(have[1..].iter().all(|&had| had));
+    }
+
+    #[test]
+    fn lists_that_do_not_add_up_are_refused() {
+        let data_dir = TempDir::new().unwrap();
+        let chunk = ChunkInfo {
+            hash: [1; 32],
+            len: MIN_CHUNK,
+        };
+        let index = ChunkIndex::load(data_dir.path()).unwrap();
+        let mut incoming = IncomingChunks::new(index, "laptop", MIN_CHUNK as u64 * 3);
+        let FluxMessage::ChunkList { data, nonce, last } =
+            list_message(&[chunk, chunk], true, None).unwrap()
+        else {
+            panic!("expected a ChunkList");
+        };
+        assert!(incoming.add_list(data, nonce, last, None).is_err());
+    }
+}
@@ -0,0 +1,748 @@
+//! Canonical test vectors for the Flux wire protocol.
+//!
+//! Third-party implementations (e.g. the mobile app) validate compatibility
+//! against the vectors emitted by `flux protocol conformance`. The vectors are
+//! produced by the crate's real codec and crypto, so they can never drift from
+//! what `flux send` / `flux receive` put on the wire.
+//!
+//! The output contains:
+//! - One encoded sample of every `FluxMessage` variant (bincode payload and the
+//!   length-delimited frame as sent over TCP)
+//! - A complete encrypted code-phrase session built from fixed X25519 secrets
+//!   and fixed nonces, so every byte is reproducible
+//!
+//! `flux protocol conformance --verify FILE` checks a vectors file written by
+//! another implementation against this codec.
+
+use serde::{Deserialize, Serialize};
+use x25519_dalek::{PublicKey, StaticSecret};
+
+use crate::error::FluxError;
+use crate::net::protocol::{
+    decode_message, encode_message, negotiated_chunk_size, FluxMessage, MIN_CHUNK_SIZE,
+    PROTOCOL_VERSION,
+};
+use crate::security::crypto::{EncryptedChannel, KDF_CONTEXT};
+use crate::security::receipt::{ReceiptKey, TransferReceipt};
+
+/// Code phrase used by the sample session.
+pub const SAMPLE_CODE_PHRASE: &str = "4242-ace-bad-bee-age";
+
+/// Fixed sender X25519 secret for the sample session. Never use outside vectors.
+const SAMPLE_SENDER_SECRET: [u8; 32] = [0x11; 32];
+
+/// Fixed receiver X25519 secret for the sample session. Never use outside vectors.
+const SAMPLE_RECEIVER_SECRET: [u8; 32] = [0x22; 32];
+
+/// One encoded protocol message.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MessageVector {
+    /// Human-readable label (e.g. "handshake_with_key")
+    pub name: String,
+    /// Hex-encoded bincode payload
+    pub encoded: String,
+    /// Hex-encoded frame: 4-byte big-endian length prefix + payload
+    pub framed: String,
+}
+
+/// A complete encrypted code-phrase transfer built from fixed key material.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SessionVector {
+    pub code_phrase: String,
+    /// Hex-encoded X25519 secrets and public keys
+    pub sender_secret: String,
+    pub sender_public: String,
+    pub receiver_secret: String,
+    pub receiver_public: String,
+    /// Hex-encoded file contents carried by the session
+    pub plaintext: String,
+    /// Frames in wire order (handshake, ack, header, chunks, completion)
+    pub frames: Vec<MessageVector>,
+}
+
+/// The full conformance document emitted as JSON.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ConformanceVectors {
+    pub protocol_version: u8,
+    /// BLAKE3 `derive_key` context used for session keys
+    pub kdf_context: String,
+    pub messages: Vec<MessageVector>,
+    pub session: SessionVector,
+}
+
+/// Build the canonical sample of every message variant.
+pub fn canonical_messages() -> Vec<(&'static str, FluxMessage)> {
+    vec![
+        (
+            "handshake_plain",
+            FluxMessage::Handshake {
+                version: PROTOCOL_VERSION,
+                device_name: "flux-sender".to_string(),
+                public_key: None,
+            },
+        ),
+        (
+            "handshake_with_key",
+            FluxMessage::Handshake {
+                version: PROTOCOL_VERSION,
+                device_name: "flux-sender".to_string(),
+                public_key: Some(vec![0xAB; 32]),
+            },
+        ),
+        (
+            "handshake_ack_accepted",
+            FluxMessage::HandshakeAck {
+                accepted: true,
+                public_key: Some(vec![0xCD; 32]),
+                reason: None,
+                max_chunk_size: None,
+                chunk_index: false,
+                zero_copy: false,
+            },
+        ),
+        (
+            "handshake_ack_rejected",
+            FluxMessage::HandshakeAck {
+                accepted: false,
+                public_key: None,
+                reason: Some("Transfer rejected by user".to_string()),
+                max_chunk_size: None,
+                chunk_index: false,
+                zero_copy: false,
+            },
+        ),
+        (
+            "handshake_ack_low_memory",
+            FluxMessage::HandshakeAck {
+                accepted: true,
+                public_key: Some(vec![0xCD; 32]),
+                reason: None,
+                max_chunk_size: Some(64 * 1024),
+                chunk_index: false,
+                zero_copy: false,
+            },
+        ),
+        (
+            "handshake_ack_chunk_index",
+            FluxMessage::HandshakeAck {
+                accepted: true,
+                public_key: Some(vec![0xCD; 32]),
+                reason: None,
+                max_chunk_size: None,
+                chunk_index: true,
+                zero_copy: false,
+            },
+        ),
+        (
+            "handshake_ack_zero_copy",
+            FluxMessage::HandshakeAck {
+                accepted: true,
+                public_key: None,
+                reason: None,
+                max_chunk_size: None,
+                chunk_index: false,
+                zero_copy: true,
+            },
+        ),
+        (
+            "file_header",
+            FluxMessage::FileHeader {
+                filename: "report.pdf".to_string(),
+                size: 1_048_576,
+                checksum: Some(blake3::hash(b"report").to_hex().to_string()),
+                encrypted: false,
+                footer: false,
+            },
+        ),
+        (
+            "file_header_encrypted",
+            FluxMessage::FileHeader {
+                filename: "secret.docx".to_string(),
+                size: 5_000_000,
+                checksum: None,
+                encrypted: true,
+                footer: false,
+            },
+        ),
+        (
+            "file_header_footer",
+            FluxMessage::FileHeader {
+                filename: "movie.mkv".to_string(),
+                size: 4_000_000_000,
+                checksum: None,
+                encrypted: true,
+                footer: true,
+            },
+        ),
+        (
+            "data_chunk_plain",
+            FluxMessage::DataChunk {
+                offset: 0,
+                data: b"hello flux".to_vec(),
+                nonce: None,
+            },
+        ),
+        (
+            "data_chunk_encrypted",
+            FluxMessage::DataChunk {
+                offset: 262_144,
+                data: vec![0xFF; 32],
+                nonce: Some(vec![0x42; 24]),
+            },
+        ),
+        (
+            "raw_data",
+            FluxMessage::RawData {
+                offset: 524_288,
+                len: 262_144,
+            },
+        ),
+        (
+            "chunk_list",
+            FluxMessage::ChunkList {
+                data: vec![0x33; 48],
+                nonce: Some(vec![0x42; 24]),
+                last: true,
+            },
+        ),
+        (
+            "chunks_have",
+            FluxMessage::ChunksHave {
+                data: vec![0x05],
+                nonce: None,
+            },
+        ),
+        (
+            "chunk_ref",
+            FluxMessage::ChunkRef {
+                offset: 1_048_576,
+                index: 2,
+            },
+        ),
+        (
+            "transfer_complete",
+            FluxMessage::TransferComplete {
+                filename: "report.pdf".to_string(),
+                bytes_received: 1_048_576,
+                checksum_verified: Some(true),
+                checksum: Some("xxh3:0123456789abcdef".to_string()),
+            },
+        ),
+        (
+            "transfer_complete_no_checksum",
+            FluxMessage::TransferComplete {
+                filename: "photo.jpg".to_string(),
+                bytes_received: 500_000,
+                checksum_verified: None,
+                checksum: Some(blake3::hash(b"photo").to_hex().to_string()),
+            },
+        ),
+        (
+            "error",
+            FluxMessage::Error {
+                message: "Disk full: cannot write file".to_string(),
+            },
+        ),
+        ("receipt_countersigned", canonical_receipt()),
+        (
+            "resume_request",
+            FluxMessage::ResumeRequest {
+                filename: "report.pdf".to_string(),
+                size: 1_048_576,
+                checksum: blake3::hash(b"report").to_hex().to_string(),
+                encrypted: true,
+            },
+        ),
+        (
+            "resume_ack",
+            FluxMessage::ResumeAck { offset: 524_288 },
+        ),
+        (
+            "ping",
+            FluxMessage::Ping {
+                version: PROTOCOL_VERSION,
+            },
+        ),
+        (
+            "pong",
+            FluxMessage::Pong {
+                version: PROTOCOL_VERSION,
+                device_name: "flux-receiver".to_string(),
+                flux_version: "1.0.0".to_string(),
+            },
+        ),
+        (
+            "cancel",
+            FluxMessage::Cancel {
+                reason: "interrupted with Ctrl+C".to_string(),
+            },
+        ),
+        (
+            "key_confirm",
+            FluxMessage::KeyConfirm {
+                proof: vec![0x5A; 40],
+                nonce: vec![0x07; 24],
+            },
+        ),
+        ("keepalive", FluxMessage::Keepalive),
+        (
+            "stat_path",
+            FluxMessage::StatPath {
+                path: "photos".to_string(),
+            },
+        ),
+        (
+            "path_stat",
+            FluxMessage::PathStat {
+                exists: true,
+                dir: true,
+            },
+        ),
+        (
+            "list_tree",
+            FluxMessage::ListTree {
+                path: "photos".to_string(),
+            },
+        ),
+        (
+            "tree_listing",
+            FluxMessage::TreeListing {
+                data: vec![0x33; 48],
+                nonce: Some(vec![0x42; 24]),
+                last: true,
+            },
+        ),
+        (
+            "put_file",
+            FluxMessage::PutFile {
+                path: "photos/a.jpg".to_string(),
+                size: 1_048_576,
+                modified: Some(1_700_000_000),
+                checksum: blake3::hash(b"report").to_hex().to_string(),
+            },
+        ),
+        (
+            "get_file",
+            FluxMessage::GetFile {
+                path: "photos/a.jpg".to_string(),
+            },
+        ),
+        (
+            "delete_path",
+            FluxMessage::DeletePath {
+                path: "photos/old.jpg".to_string(),
+            },
+        ),
+        (
+            "path_deleted",
+            FluxMessage::PathDeleted {
+                path: "photos/old.jpg".to_string(),
+            },
+        ),
+        (
+            "file_footer",
+            FluxMessage::FileFooter {
+                checksum: blake3::hash(b"report").to_hex().to_string(),
+            },
+        ),
+    ]
+}
+
+/// Countersigned receipt with keys derived from the session's static secrets
+/// and a fixed timestamp. Ed25519 signatures are deterministic, so the
+/// encoding is stable.
+fn canonical_receipt() -> FluxMessage {
+    let sender = ReceiptKey::from_secret(&StaticSecret::from(SAMPLE_SENDER_SECRET));
+    let receiver = ReceiptKey::from_secret(&StaticSecret::from(SAMPLE_RECEIVER_SECRET));
+    let timestamp = chrono::DateTime::from_timestamp(1_700_000_000, 0)
+        .expect("fixed timestamp is valid");
+    let mut receipt = TransferReceipt::new_signed_at(
+        &sender,
+        "flux-sender",
+        "report.pdf",
+        1_048_576,
+        &blake3::hash(b"report").to_hex().to_string(),
+        timestamp,
+    );
+    receipt.countersign(&receiver, "flux-receiver");
+    FluxMessage::Receipt { receipt }
+}
+
+/// Generate the full set of conformance vectors.
+pub fn generate() -> Result<ConformanceVectors, FluxError> {
+    let messages = canonical_messages()
+        .iter()
+        .map(|(name, msg)| message_vector(name, msg))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(ConformanceVectors {
+        protocol_version: PROTOCOL_VERSION,
+        kdf_context: KDF_CONTEXT.to_string(),
+        messages,
+        session: sample_session()?,
+    })
+}
+
+/// Check a vectors document against this implementation.
+///
+/// Returns one description per failed check; an empty list means the
+/// document is fully compatible.
+pub fn verify(vectors: &ConformanceVectors) -> Vec<String> {
+    let mut failures = Vec::new();
+
+    if vectors.protocol_version != PROTOCOL_VERSION {
+        failures.push(format!(
+            "protocol_version: expected {}, got {}",
+            PROTOCOL_VERSION, vectors.protocol_version
+        ));
+    }
+    if vectors.kdf_context != KDF_CONTEXT {
+        failures.push(format!(
+            "kdf_context: expected '{}', got '{}'",
+            KDF_CONTEXT, vectors.kdf_context
+        ));
+    }
+
+    let canonical = canonical_messages();
+    for vector in &vectors.messages {
+        match check_message(vector) {
+            Ok(decoded) => {
+                let expected = canonical.iter().find(|(name, _)| *name == vector.name);
+                if let Some((_, expected)) = expected {
+                    if *expected != decoded {
+                        failures.push(format!(
+                            "{}: decodes to a different message than the canonical sample",
+                            vector.name
+                        ));
+                    }
+                }
+            }
+            Err(e) => failures.push(format!("{}: {}", vector.name, e)),
+        }
+    }
+
+    if let Err(e) = check_session(&vectors.session) {
+        failures.push(format!("session: {}", e));
+    }
+
+    failures
+}
+
+/// Encode a message into its payload and frame vector.
+fn message_vector(name: &str, msg: &FluxMessage) -> Result<MessageVector, FluxError> {
+    let encoded = encode_message(msg)?;
+    Ok(MessageVector {
+        name: name.to_string(),
+        encoded: to_hex(&encoded),
+        framed: to_hex(&frame(&encoded)),
+    })
+}
+
+/// Prefix a payload with its 4-byte big-endian length, as LengthDelimitedCodec does.
+fn frame(payload: &[u8]) -> Vec<u8> {
+    let mut framed = Vec::with_capacity(4 + payload.len());
+    framed.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+    framed.extend_from_slice(payload);
+    framed
+}
+
+/// Decode a vector and check that it re-encodes byte-for-byte.
+fn check_message(vector: &MessageVector) -> Result<FluxMessage, FluxError> {
+    let encoded = from_hex(&vector.encoded)?;
+    let decoded = decode_message(&encoded)?;
+    if encode_message(&decoded)? != encoded {
+        return Err(FluxError::TransferError(
+            "re-encoding does not reproduce the same bytes".into(),
+        ));
+    }
+    if from_hex(&vector.framed)? != frame(&encoded) {
+        return Err(FluxError::TransferError(
+            "frame is not a 4-byte big-endian length prefix followed by the payload".into(),
+        ));
+    }
+    Ok(decoded)
+}
+
+/// Deterministic file contents for the sample session (spans two chunks).
+fn sample_plaintext() -> Vec<u8> {
+    (0..MIN_CHUNK_SIZE + 100).map(|i| (i % 251) as u8).collect()
+}
+
+/// Build the encrypted code-phrase sample session.
+fn sample_session() -> Result<SessionVector, FluxError> {
+    let sender_secret = StaticSecret::from(SAMPLE_SENDER_SECRET);
+    let receiver_secret = StaticSecret::from(SAMPLE_RECEIVER_SECRET);
+    let sender_public = PublicKey::from(&sender_secret);
+    let receiver_public = PublicKey::from(&receiver_secret);
+
+    let channel =
+        EncryptedChannel::from_static(&sender_secret, &receiver_public, Some(SAMPLE_CODE_PHRASE));
+    let plaintext = sample_plaintext();
+    let requested = MIN_CHUNK_SIZE as u32;
+    let chunk_size = negotiated_chunk_size(Some(requested));
+
+    let mut messages = vec![
+        FluxMessage::Handshake {
+            version: PROTOCOL_VERSION,
+            device_name: "flux-sender".to_string(),
+            public_key: Some(sender_public.as_bytes().to_vec()),
+        },
+        FluxMessage::HandshakeAck {
+            accepted: true,
+            public_key: Some(receiver_public.as_bytes().to_vec()),
+            reason: None,
+            max_chunk_size: Some(requested),
+            chunk_index: false,
+            zero_copy: false,
+        },
+        FluxMessage::FileHeader {
+            filename: "sample.bin".to_string(),
+            size: plaintext.len() as u64,
+            checksum: Some(blake3::hash(&plaintext).to_hex().to_string()),
+            encrypted: true,
+            footer: false,
+        },
+    ];
+    for (index, chunk) in plaintext.chunks(chunk_size).enumerate() {
+        let nonce = [index as u8 + 1; 24];
+        messages.push(FluxMessage::DataChunk {
+            offset: (index * chunk_size) as u64,
+            data: channel.encrypt_with_nonce(chunk, &nonce)?,
+            nonce: Some(nonce.to_vec()),
+        });
+    }
+    messages.push(FluxMessage::TransferComplete {
+        filename: "sample.bin".to_string(),
+        bytes_received: plaintext.len() as u64,
+        checksum_verified: Some(true),
+        checksum: Some(blake3::hash(&plaintext).to_hex().to_string()),
+    });
+
+    let frames = messages
+        .iter()
+        .enumerate()
+        .map(|(i, msg)| message_vector(&format!("frame_{}", i), msg))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(SessionVector {
+        code_phrase: SAMPLE_CODE_PHRASE.to_string(),
+        sender_secret: to_hex(&SAMPLE_SENDER_SECRET),
+        sender_public: to_hex(sender_public.as_bytes()),
+        receiver_secret: to_hex(&SAMPLE_RECEIVER_SECRET),
+        receiver_public: to_hex(receiver_public.as_bytes()),
+        plaintext: to_hex(&plaintext),
+        frames,
+    })
+}
+
+/// Replay a session as the receiver: derive the key, decrypt every chunk and
+/// check the reassembled file against the header checksum and the plaintext.
+fn check_session(session: &SessionVector) -> Result<(), FluxError> {
+    let receiver_secret = StaticSecret::from(key_from_hex(&session.receiver_secret)?);
+    if to_hex(PublicKey::from(&receiver_secret).as_bytes()) != session.receiver_public {
+        return Err(FluxError::EncryptionError(
+            "receiver_public does not match receiver_secret".into(),
+        ));
+    }
+    let sender_public = PublicKey::from(key_from_hex(&session.sender_public)?);
+    let channel =
+        EncryptedChannel::from_static(&receiver_secret, &sender_public, Some(&session.code_phrase));
+
+    let mut file = Vec::new();
+    let mut checksum = None;
+    for vector in &session.frames {
+        let msg = check_message(vector)
+            .map_err(|e| FluxError::TransferError(format!("{}: {}", vector.name, e)))?;
+        match msg {
+            FluxMessage::FileHeader { checksum: c, .. } => checksum = c,
+            FluxMessage::DataChunk {
+                offset,
+                data,
+                nonce,
+            } => {
+                if offset != file.len() as u64 {
+                    return Err(FluxError::TransferError(format!(
+                        "{}: chunk offset {} does not follow previous data ({} bytes)",
+                        vector.name,
+                        offset,
+                        file.len()
+                    )));
+                }
+                let nonce: [u8; 24] = nonce
+                    .ok_or_else(|| {
+                        FluxError::EncryptionError(format!("{}: chunk has no nonce", vector.name))
+                    })?
+                    .try_into()
+                    .map_err(|_| {
+                        FluxError::EncryptionError(format!("{}: nonce must be 24 bytes", vector.name))
+                    })?;
+                file.extend_from_slice(&channel.decrypt(&data, &nonce)?);
+            }
+            _ => {}
+        }
+    }
+
+    if file != from_hex(&session.plaintext)? {
+        return Err(FluxError::TransferError(
+            "decrypted data does not match plaintext".into(),
+        ));
+    }
+    if checksum.as_deref() != Some(blake3::hash(&file).to_hex().as_str()) {
+        return Err(FluxError::TransferError(
+            "BLAKE3 checksum in file header does not match decrypted data".into(),
+        ));
+    }
+    Ok(())
+}
+
+/// Lowercase hex encoding.
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Decode a hex string (case-insensitive).
+fn from_hex(hex: &str) -> Result<Vec<u8>, FluxError> {
+    let invalid = || FluxError::TransferError(format!("Invalid hex string: '{}'", hex));
+    let digit = |c: u8| (c as char).to_digit(16).ok_or_else(invalid);
+    hex.as_bytes()
+        .chunks(2)
+        .map(|pair| match pair {
+            [hi, lo] => Ok((digit(*hi)? << 4 | digit(*lo)?) as u8),
+            _ => Err(invalid()),
+        })
+        .collect()
+}
+
+/// Decode a hex string into a 32-byte X25519 key.
+fn key_from_hex(hex: &str) -> Result<[u8; 32], FluxError> {
+    from_hex(hex)?
+        .try_into()
+        .map_err(|_| FluxError::EncryptionError("X25519 keys must be 32 bytes".into()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generated_vectors_verify() {
+        let vectors = generate().unwrap();
+        assert!(verify(&vectors).is_empty(), "{:?}", verify(&vectors));
+    }
+
+    #[test]
+    fn generation_is_deterministic() {
+        assert_eq!(generate().unwrap(), generate().unwrap());
+    }
+
+    #[test]
+    fn canonical_messages_cover_every_variant() {
+        let names: Vec<&str> = canonical_messages()
+            .iter()
+            .map(|(_, msg)| match msg {
+                FluxMessage::Handshake { .. } => "Handshake",
+                FluxMessage::HandshakeAck { .. } => "HandshakeAck",
+                FluxMessage::FileHeader { .. } => "FileHeader",
+                FluxMessage::DataChunk { .. } => "DataChunk",
+                FluxMessage::ChunkList { .. } => "ChunkList",
+                FluxMessage::ChunksHave { .. } => "ChunksHave",
+                FluxMessage::ChunkRef { .. } => "ChunkRef",
+                FluxMessage::TransferComplete { .. } => "TransferComplete",
+                FluxMessage::Error { .. } => "Error",
+                FluxMessage::Receipt { .. } => "Receipt",
+                FluxMessage::ResumeRequest { .. } => "ResumeRequest",
+                FluxMessage::ResumeAck { .. } => "ResumeAck",
+                FluxMessage::Ping { .. } => "Ping",
+                FluxMessage::Pong { .. } => "Pong",
+                FluxMessage::RawData { .. } => "RawData",
+                FluxMessage::Cancel { .. } => "Cancel",
+                FluxMessage::KeyConfirm { .. } => "KeyConfirm",
+                FluxMessage::Keepalive => "Keepalive",
+                FluxMessage::StatPath { .. } => "StatPath",
+                FluxMessage::PathStat { .. } => "PathStat",
+                FluxMessage::ListTree { .. } => "ListTree",
+                FluxMessage::TreeListing { .. } => "TreeListing",
+                FluxMessage::PutFile { .. } => "PutFile",
+                FluxMessage::GetFile { .. } => "GetFile",
+                FluxMessage::DeletePath { .. } => "DeletePath",
+                FluxMessage::PathDeleted { .. } => "PathDeleted",
+                FluxMessage::FileFooter { .. } => "FileFooter",
+            })
+            .collect();
+        for variant in [
+            "Handshake",
+            "HandshakeAck",
+            "FileHeader",
+            "DataChunk",
+            "ChunkList",
+            "ChunksHave",
+            "ChunkRef",
+            "TransferComplete",
+            "Error",
+            "Receipt",
+            "ResumeRequest",
+            "ResumeAck",
+            "Ping",
+            "Pong",
+            "RawData",
+            "Cancel",
+            "KeyConfirm",
+            "Keepalive",
+            "StatPath",
+            "PathStat",
+            "ListTree",
+            "TreeListing",
+            "PutFile",
+            "GetFile",
+            "DeletePath",
+            "PathDeleted",
+            "FileFooter",
+        ] {
+            assert!(names.contains(&variant), "missing {}", variant);
+        }
+    }
+
+    #[test]
+    fn session_spans_multiple_chunks() {
+        let session = generate().unwrap().session;
+        // handshake + ack + header + 2 chunks + complete
+        assert_eq!(session.frames.len(), 6);
+    }
+
+    #[test]
+    fn tampered_message_is_reported() {
+        let mut vectors = generate().unwrap();
+        vectors.messages[0].encoded.push_str("00");
+        let failures = verify(&vectors);
+        assert_eq!(failures.len(), 1);
+        assert!(failures[0].starts_with("handshake_plain"));
+    }
+
+    #[test]
+    fn tampered_ciphertext_is_reported() {
+        let mut vectors = generate().unwrap();
+        let chunk = vectors.session.frames[3].clone();
+        let mut msg = decode_message(&from_hex(&chunk.encoded).unwrap()).unwrap();
+        if let FluxMessage::DataChunk { ref mut data, .. } = msg {
+            data[0] ^= 0x01;
+        }
+        vectors.session.frames[3] = message_vector(&chunk.name, &msg).unwrap();
+        let failures = verify(&vectors);
+        assert_eq!(failures.len(), 1);
+        assert!(failures[0].starts_with("session"));
+    }
+
+    #[test]
+    fn hex_roundtrip() {
+        let bytes = vec![0x00, 0x0f, 0xa5, 0xff];
+        assert_eq!(to_hex(&bytes), "000fa5ff");
+        assert_eq!(from_hex("000FA5ff").unwrap(), bytes);
+        assert!(from_hex("abc").is_err());
+        assert!(from_hex("zz").is_err());
+    }
+
+    #[test]
+    fn frame_has_big_endian_length_prefix() {
+        assert_eq!(frame(&[0xAA, 0xBB]), vec![0, 0, 0, 2, 0xAA, 0xBB]);
+    }
+}
@@ -0,0 +1,164 @@
+//! Free-space check of incoming files (`[receive]` table in config.toml).
+//!
+//! Before accepting a `FileHeader` (or `ResumeRequest`), the receiver checks
+//! that the bytes still to come fit on the output disk with
+//! `free_space_margin` (default 100 MiB) to spare. A file that does not fit
+//! is refused with a protocol `Error`, instead of failing partway through
+//! and leaving the disk full.
+//!
+//! With `preallocate = true`, accepted files are allocated at their full
+//! size up front, which keeps large files contiguous and turns a disk that
+//! fills up anyway (another writer) into an early error.
+
+use std::path::Path;
+
+use bytesize::ByteSize;
+
+use crate::config::types::ReceiveConfig;
+use crate::error::FluxError;
+
+/// Space kept free when `free_space_margin` is not set.
+pub const DEFAULT_MARGIN: u64 = 100 * 1024 * 1024;
+
+/// How the receiver treats the space on its output disk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SpacePolicy {
+    /// Bytes that must stay free after a file is received
+    pub margin: u64,
+    /// Allocate accepted files at their full size before receiving
+    pub preallocate: bool,
+}
+
+impl Default for SpacePolicy {
+    fn default() -> Self {
+        Self {
+            margin: DEFAULT_MARGIN,
+            preallocate: false,
+        }
+    }
+}
+
+impl SpacePolicy {
+    /// Parse the `[receive]` space settings.
+    pub fn from_config(config: &ReceiveConfig) -> Result<Self, FluxError> {
+        let margin = match config.free_space_margin.as_deref() {
+            Some(margin) => margin.trim().parse::<ByteSize>().map_err(|_| {
+                FluxError::Config(format!(
+                    "Invalid free_space_margin '{}'. Use sizes like '1GB' or '500MiB'",
+                    margin
+                ))
+            })?,
+            None => ByteSize(DEFAULT_MARGIN),
+        };
+        Ok(Self {
+            margin: margin.as_u64(),
+            preallocate: config.preallocate,
+        })
+    }
+
+    /// Check that `needed` more bytes fit into `dir` (or, if it does not
+    /// exist yet, the closest existing parent).
+    ///
+    /// Fails with `FluxError::InsufficientSpace` if they do not. When the
+    /// free space cannot be determined, the file is accepted.
+    pub fn check(&self, dir: &Path, needed: u64) -> Result<(), FluxError> {
+        let Some(existing) = dir.ancestors().find(|p| p.exists()) else {
+            return Ok(());
+        };
+        match fs2::available_space(existing) {
+            Ok(available) => self.check_available(existing, available, needed),
+            Err(e) => {
+                tracing::warn!("Cannot read free space of {}: {}", existing.display(), e);
+                Ok(())
+            }
+        }
+    }
+
+    fn check_available(&self, dir: &Path, available: u64, needed: u64) -> Result<(), FluxError> {
+        if needed.saturating_add(self.margin) <= available {
+            return Ok(());
+        }
+        Err(FluxError::InsufficientSpace(format!(
+            "file needs {}, {} free on {} and {} must stay free",
+            ByteSize(needed),
+            ByteSize(available),
+            dir.display(),
+            ByteSize(self.margin)
+        )))
+    }
+}
+
+/// Allocate `file` to `size` bytes, keeping the write position.
+///
+/// Best effort: filesystems that cannot preallocate just grow the file as
+/// data arrives.
+pub fn preallocate(file: &std::fs::File, size: u64, path: &Path) {
+    use fs2::FileExt;
+    if let Err(e) = file.allocate(size) {
+        tracing::warn!("Cannot preallocate {}: {}", path.display(), e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn margin_parses_and_defaults() {
+        let mut config = ReceiveConfig::default();
+        assert_eq!(SpacePolicy::from_config(&config).unwrap(), SpacePolicy::default());
+
+        config.free_space_margin = Some("2GB".to_string());
+        config.preallocate = true;
+        let policy = SpacePolicy::from_config(&config).unwrap();
+        assert_eq!(policy.margin, 2_000_000_000);
+        assert!(policy.preallocate);
+
+        config.free_space_margin = Some("lots".to_string());
+        assert!(matches!(
+            SpacePolicy::from_config(&config),
+            Err(FluxError::Config(_))
+        ));
+    }
+
+    #[test]
+    fn files_must_leave_the_margin_free() {
+        let policy = SpacePolicy {
+            margin: 1_000,
+            preallocate: false,
+        };
+        let dir = Path::new("/srv/inbox");
+        assert!(policy.check_available(dir, 10_000, 9_000).is_ok());
+        let err = policy.check_available(dir, 10_000, 9_001).unwrap_err();
+        assert!(matches!(err, FluxError::InsufficientSpace(_)));
+        assert!(err.to_string().contains("/srv/inbox"), "{}", err);
+        assert!(policy.check_available(dir, 10, u64::MAX).is_err());
+    }
+
+    #[test]
+    fn check_uses_closest_existing_parent() {
+        let dir = TempDir::new().unwrap();
+        let missing = dir.path().join("not/yet/created");
+        let policy = SpacePolicy {
+            margin: 0,
+            preallocate: false,
+        };
+        assert!(policy.check(&missing, 1).is_ok());
+        assert!(policy.check(&missing, u64::MAX).is_err());
+    }
+
+    #[test]
+    fn preallocate_keeps_write_position() {
+        use std::io::Write;
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("big.bin");
+        let mut file = std::fs::File::create(&path).unwrap();
+        preallocate(&file, 4096, &path);
+        file.write_all(b"head").unwrap();
+        drop(file);
+        let data = std::fs::read(&path).unwrap();
+        assert!(data.starts_with(b"head"));
+        assert!(data.len() == 4 || data.len() == 4096);
+    }
+}
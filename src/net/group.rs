@@ -0,0 +1,319 @@
+//! Group send: one file to several devices at once.
+//!
+//! `flux send @laptop @desktop @nas file.iso` (or `--all-trusted`) connects
+//! to every receiver concurrently. The source is read once: a single reader
+//! hands each buffer to every connection, and each connection encrypts it
+//! under its own session key. A device whose queue is full holds the reader
+//! back, so the group moves at the pace of its slowest connected device.
+//!
+//! There is no reconnect: a device that fails (or cannot be found) is
+//! reported on its progress line and dropped, and the others carry on. Each
+//! device gets its own history record.
+
+use std::io::Read;
+use std::path::Path;
+use std::time::Instant;
+
+use indicatif::ProgressBar;
+use tokio::sync::mpsc;
+use tokio_util::bytes::Bytes;
+
+use crate::discovery::cache;
+use crate::discovery::scan::discover_devices;
+use crate::error::FluxError;
+use crate::net::addr;
+use crate::net::protocol::{FluxMessage, CHUNK_SIZE};
+use crate::net::resume::AttemptError;
+use crate::net::sender::{self, Connection, OutgoingFile, SendOptions, SendReport};
+use crate::progress::bar::GroupProgress;
+use crate::transfer::cancel;
+use crate::transfer::history::HistoryRecord;
+
+/// Buffers queued for each device ahead of its connection.
+const QUEUE_DEPTH: usize = 4;
+
+/// What every connection in a group send shares.
+struct GroupSend<'a> {
+    file: &'a OutgoingFile,
+    options: &'a SendOptions<'a>,
+    progress: &'a GroupProgress,
+}
+
+/// Send `file_path` to every target (`@name`, `host:port` or `host`) at
+/// once, and record one history entry per device.
+///
+/// The `limit` applies to each connection; `receipt` is not supported.
+/// Fails if any device did not receive the file; the others keep their copy.
+pub fn send_to_group_sync(
+    targets: &[String],
+    file_path: &Path,
+    options: &SendOptions<'_>,
+) -> Result<(), FluxError> {
+    let started = Instant::now();
+    let mut unique: Vec<String> = Vec::with_capacity(targets.len());
+    for target in targets {
+        if !unique.contains(target) {
+            unique.push(target.clone());
+        }
+    }
+    let file = OutgoingFile::open(file_path, options.checksum)?;
+    let addrs = resolve_all(&unique)?;
+
+    let rt = tokio::runtime::Runtime::new()
+        .map_err(|e| FluxError::TransferError(format!("Failed to create async runtime: {}", e)))?;
+    let progress = GroupProgress::new(file.size, unique.len() as u64, false);
+    let send = GroupSend {
+        file: &file,
+        options,
+        progress: &progress,
+    };
+    let (results, read) = rt.block_on(send.to_all(&unique, addrs));
+    progress.finish();
+
+    let source = file_path.display().to_string();
+    for (target, result) in unique.iter().zip(&results) {
+        let mut record = HistoryRecord::new("send", &source, target);
+        sender::finish_send_record(&mut record, result);
+    }
+    if cancel::requested() {
+        return Err(FluxError::Cancelled);
+    }
+    read?;
+
+    let failed = results.iter().filter(|r| r.is_err()).count();
+    eprintln!(
+        "Sent {} to {} of {} devices in {:.1}s",
+        file.filename,
+        results.len() - failed,
+        results.len(),
+        started.elapsed().as_secs_f64()
+    );
+    if failed > 0 {
+        return Err(FluxError::TransferError(format!(
+            "{} of {} devices did not receive {}",
+            failed,
+            results.len(),
+            file.filename
+        )));
+    }
+    Ok(())
+}
+
+/// A target's `(host, port)`, or why it could not be resolved.
+type Resolved = Result<(String, u16), FluxError>;
+
+/// Resolve each target to `(host, port)`. `@name` targets use their cached
+/// address when the device still answers there; the others share one
+/// discovery pass. A target that cannot be resolved fails on its own.
+fn resolve_all(targets: &[String]) -> Result<Vec<Resolved>, FluxError> {
+    let cached: Vec<Option<(String, u16)>> = targets
+        .iter()
+        .map(|target| match target.strip_prefix('@') {
+            Some(name) if !name.is_empty() => sender::cached_address(name),
+            _ => None,
+        })
+        .collect();
+    let undiscovered = targets
+        .iter()
+        .zip(&cached)
+        .any(|(target, addr)| target.starts_with('@') && addr.is_none());
+    let devices = if undiscovered {
+        eprintln!("Discovering devices...");
+        let devices = discover_devices(3)?;
+        cache::record(&devices);
+        devices
+    } else {
+        Vec::new()
+    };
+    Ok(targets
+        .iter()
+        .zip(cached)
+        .map(|(target, cached)| match (target.strip_prefix('@'), cached) {
+            (_, Some(addr)) => Ok(addr),
+            (Some(""), None) => Err(FluxError::TransferError("Empty device name after @".into())),
+            (Some(name), None) => sender::find_device(&devices, name)
+                .map(|device| (device.host.clone(), device.port))
+                .ok_or_else(|| sender::device_not_found(name, devices.len())),
+            (None, None) => sender::resolve_target(target, true),
+        })
+        .collect())
+}
+
+impl GroupSend<'_> {
+    /// Send to every device concurrently while one reader feeds them all.
+    /// Returns each device's outcome, in target order, and the reader's.
+    async fn to_all(
+        &self,
+        targets: &[String],
+        addrs: Vec<Result<(String, u16), FluxError>>,
+    ) -> (Vec<Result<SendReport, FluxError>>, Result<(), FluxError>) {
+        let mut queues = Vec::with_capacity(targets.len());
+        let mut sends = Vec::with_capacity(targets.len());
+        for (target, addr) in targets.iter().zip(addrs) {
+            let (queue, buffers) = mpsc::channel(QUEUE_DEPTH);
+            queues.push(queue);
+            let bar = self.progress.device_bar(target, self.file.size);
+            sends.push(async move {
+                let result = match addr {
+                    Ok((host, port)) => self
+                        .to_device(&host, port, buffers, &bar)
+                        .await
+                        .map_err(AttemptError::into_inner),
+                    Err(e) => Err(e),
+                };
+                let error = result.as_ref().err().map(|e| e.to_string());
+                self.progress.device_done(&bar, target, error.as_deref());
+                result
+            });
+        }
+
+        let path = self.file.path.clone();
+        let reader = tokio::task::spawn_blocking(move || read_source(&path, queues));
+        let (results, read) = tokio::join!(futures::future::join_all(sends), reader);
+        let read = read.unwrap_or_else(|e| {
+            Err(FluxError::TransferError(format!("Source reader failed: {}", e)))
+        });
+        (results, read)
+    }
+
+    /// Connect to one device and send it the file as the reader queues it.
+    async fn to_device(
+        &self,
+        host: &str,
+        port: u16,
+        buffers: mpsc::Receiver<Bytes>,
+        bar: &ProgressBar,
+    ) -> Result<SendReport, AttemptError> {
+        let SendOptions {
+            encrypt,
+            device_name,
+            limit,
+            psk,
+            ..
+        } = *self.options;
+        let mut conn = sender::connect(host, port, encrypt, device_name, limit, psk).await?;
+        let header = FluxMessage::FileHeader {
+            filename: self.file.filename.clone(),
+            size: self.file.size,
+            checksum: Some(self.file.checksum.clone()),
+            encrypted: encrypt,
+            footer: false,
+        };
+        sender::send_message(&mut conn.framed, &header, "file header").await?;
+        self.stream(&mut conn, buffers, bar).await?;
+        let peer = addr::join_host_port(host, port);
+        sender::await_completion(&mut conn.framed, peer, self.file).await
+    }
+
+    /// Send the queued buffers as DataChunks of the size negotiated with
+    /// this device, encrypted with its session key.
+    async fn stream(
+        &self,
+        conn: &mut Connection,
+        mut buffers: mpsc::Receiver<Bytes>,
+        bar: &ProgressBar,
+    ) -> Result<(), AttemptError> {
+        let mut offset = 0u64;
+        while offset < self.file.size {
+            if cancel::requested() {
+                sender::send_cancel(&mut conn.framed).await;
+                return Err(FluxError::Cancelled.into());
+            }
+            sender::check_receiver(&mut conn.framed)?;
+
+            // Waits while a slower device holds the reader back
+            let buf = match sender::with_keepalives(&mut conn.framed, buffers.recv()).await? {
+                Some(buf) => buf,
+                // The reader stops on Ctrl+C too; the check above tells
+                None if cancel::requested() => continue,
+                None => {
+                    return Err(FluxError::TransferError(format!(
+                        "Reading '{}' stopped after {} of {} bytes",
+                        self.file.path.display(),
+                        offset,
+                        self.file.size
+                    ))
+                    .into())
+                }
+            };
+            for piece in buf.chunks(conn.chunk_size) {
+                let (data, nonce) = match conn.channel {
+                    Some(ref channel) => {
+                        let (ct, nc) = channel.encrypt(piece)?;
+                        (ct, Some(nc.to_vec()))
+                    }
+                    None => (piece.to_vec(), None),
+                };
+                let chunk = FluxMessage::DataChunk {
+                    offset,
+                    data,
+                    nonce,
+                };
+                if let Err(e) = sender::send_message(&mut conn.framed, &chunk, "data chunk").await {
+                    // A cancelled receiver says so before closing the connection
+                    sender::check_receiver(&mut conn.framed)?;
+                    return Err(e);
+                }
+                sender::pace(&mut conn.framed, conn.limiter.as_ref(), piece.len() as u64).await?;
+                offset += piece.len() as u64;
+                self.progress.inc(bar, piece.len() as u64);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Read the file once, handing every buffer to each device still taking
+/// them. Stops early on Ctrl+C or once no device is left.
+fn read_source(path: &Path, mut queues: Vec<mpsc::Sender<Bytes>>) -> Result<(), FluxError> {
+    let mut reader = std::fs::File::open(path).map_err(|e| {
+        FluxError::TransferError(format!("Failed to open '{}': {}", path.display(), e))
+    })?;
+    let mut buf = vec![0u8; CHUNK_SIZE];
+    while !queues.is_empty() && !cancel::requested() {
+        let n = match reader.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => {
+                return Err(FluxError::TransferError(format!(
+                    "Failed to read '{}': {}",
+                    path.display(),
+                    e
+                )))
+            }
+        };
+        let data = Bytes::copy_from_slice(&buf[..n]);
+        // A device that failed has dropped its queue
+        queues.retain(|queue| queue.blocking_send(data.clone()).is_ok());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reader_feeds_every_queue_the_whole_file() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("group.bin");
+        let data: Vec<u8> = (0..CHUNK_SIZE * 2 + 100).map(|i| (i % 251) as u8).collect();
+        std::fs::write(&path, &data).unwrap();
+
+        let (a, mut a_rx) = mpsc::channel(8);
+        let (b, mut b_rx) = mpsc::channel(8);
+        // A device that failed before the first buffer
+        let (gone, gone_rx) = mpsc::channel(8);
+        drop(gone_rx);
+        read_source(&path, vec![a, gone, b]).unwrap();
+
+        for rx in [&mut a_rx, &mut b_rx] {
+            let mut received = Vec::new();
+            while let Ok(buf) = rx.try_recv() {
+                received.extend_from_slice(&buf);
+            }
+            assert_eq!(received, data);
+        }
+    }
+}
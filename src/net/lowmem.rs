@@ -0,0 +1,94 @@
+//! Low-memory receive mode for constrained devices.
+//!
+//! On small receivers (e.g. a Raspberry Pi) the codec frame buffer, the
+//! decrypted plaintext and the page cache filled by buffered writes can add up
+//! to a noticeable memory spike. Low-memory mode asks the sender for smaller
+//! chunks, decrypts each frame in place, and periodically flushes written data
+//! to disk so dirty pages do not accumulate.
+//!
+//! The mode is selected with `flux receive --low-memory`, or enabled
+//! automatically when the available RAM is below `LOW_MEMORY_THRESHOLD`.
+
+/// Available RAM below which low-memory mode is enabled automatically (1 GB).
+pub const LOW_MEMORY_THRESHOLD: u64 = 1024 * 1024 * 1024;
+
+/// Number of bytes written between explicit flushes to disk (8 MB).
+pub const LOW_MEMORY_FLUSH_INTERVAL: u64 = 8 * 1024 * 1024;
+
+/// Decide whether low-memory mode should be used.
+///
+/// An explicit `--low-memory` flag always wins. Otherwise the mode is enabled
+/// when the available memory can be detected and is below the threshold.
+pub fn should_use_low_memory(requested: bool) -> bool {
+    if requested {
+        return true;
+    }
+    match available_memory() {
+        Some(bytes) if bytes < LOW_MEMORY_THRESHOLD => {
+            tracing::info!(
+                "Low available memory ({} bytes), enabling low-memory receive mode",
+                bytes
+            );
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Return the memory available to new allocations, in bytes.
+///
+/// Reads `MemAvailable` from `/proc/meminfo` on Linux. Returns `None` on other
+/// platforms or when the value cannot be determined.
+pub fn available_memory() -> Option<u64> {
+    #[cfg(target_os = "linux")]
+    {
+        let contents = std::fs::read_to_string("/proc/meminfo").ok()?;
+        parse_meminfo_available(&contents)
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    {
+        None
+    }
+}
+
+/// Parse the `MemAvailable` line of `/proc/meminfo` into bytes.
+///
+/// The kernel reports the value in kibibytes (`MemAvailable:  123456 kB`).
+pub fn parse_meminfo_available(contents: &str) -> Option<u64> {
+    contents.lines().find_map(|line| {
+        let rest = line.strip_prefix("MemAvailable:")?;
+        let kb: u64 = rest.trim().trim_end_matches("kB").trim().parse().ok()?;
+        kb.checked_mul(1024)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_meminfo_reads_mem_available() {
+        let contents = "MemTotal:        3884480 kB\n\
+                        MemFree:          153232 kB\n\
+                        MemAvailable:     524288 kB\n\
+                        Buffers:           61240 kB\n";
+        assert_eq!(parse_meminfo_available(contents), Some(524288 * 1024));
+    }
+
+    #[test]
+    fn parse_meminfo_missing_field_returns_none() {
+        let contents = "MemTotal:        3884480 kB\nMemFree:          153232 kB\n";
+        assert_eq!(parse_meminfo_available(contents), None);
+    }
+
+    #[test]
+    fn parse_meminfo_garbage_returns_none() {
+        assert_eq!(parse_meminfo_available("MemAvailable: lots kB"), None);
+    }
+
+    #[test]
+    fn explicit_request_always_enables() {
+        assert!(should_use_low_memory(true));
+    }
+}
@@ -1,4 +1,21 @@
+pub mod addr;
+pub mod admission;
+pub mod audit;
+pub mod chunking;
 pub mod codephrase;
+pub mod conformance;
+pub mod diskspace;
+pub mod group;
+pub mod lowmem;
 pub mod protocol;
+pub mod quota;
+pub mod ratelimit;
+pub mod receipt;
 pub mod receiver;
+pub mod resume;
 pub mod sender;
+pub mod socks;
+pub mod storage;
+pub mod tree;
+pub mod web;
+pub mod zerocopy;
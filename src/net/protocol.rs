@@ -7,9 +7,15 @@
 use serde::{Deserialize, Serialize};
 
 use crate::error::FluxError;
+use crate::security::receipt::TransferReceipt;
 
 /// Current protocol version. Incremented on breaking changes.
-pub const PROTOCOL_VERSION: u8 = 1;
+///
+/// Messages are encoded by field position, so adding a field to a variant
+/// is a breaking change. Version 2 added the `HandshakeAck` capabilities
+/// (`max_chunk_size` to `zero_copy`), `TransferComplete::checksum` and
+/// `FileHeader::footer`.
+pub const PROTOCOL_VERSION: u8 = 2;
 
 /// Maximum frame size for LengthDelimitedCodec (2 MB).
 ///
@@ -25,6 +31,24 @@ pub const MAX_FRAME_SIZE: usize = 2 * 1024 * 1024;
 /// carries at most this many bytes of file data.
 pub const CHUNK_SIZE: usize = 256 * 1024;
 
+/// Chunk size requested by receivers running in low-memory mode (64 KB).
+///
+/// Smaller chunks keep the codec frame buffer, the decrypted plaintext and
+/// the hasher input small on constrained devices such as a Raspberry Pi.
+pub const LOW_MEMORY_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Smallest chunk size a sender will honour when a receiver requests one (4 KB).
+///
+/// Prevents a peer from requesting absurdly small chunks that would turn the
+/// transfer into a flood of tiny frames.
+pub const MIN_CHUNK_SIZE: usize = 4 * 1024;
+
+/// `Cancel` reason sent when the user presses Ctrl+C.
+pub const CANCEL_REASON: &str = "interrupted with Ctrl+C";
+
+/// How long a cancelled side tries to deliver `Cancel` before closing.
+pub const CANCEL_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(2);
+
 /// Protocol messages exchanged between Flux peers during file transfer.
 ///
 /// The transfer lifecycle follows this sequence:
@@ -32,8 +56,58 @@ pub const CHUNK_SIZE: usize = 256 * 1024;
 /// 2. Receiver replies with `HandshakeAck` (accept/reject, optional public key)
 /// 3. Sender sends `FileHeader` with file metadata
 /// 4. Sender sends one or more `DataChunk` messages with file data
+///    (with a `ChunkList` first when the receiver keeps a chunk index; see
+///    below)
 /// 5. Receiver sends `TransferComplete` acknowledgement
-/// 6. Either side may send `Error` at any point to abort
+/// 6. Either side may send `Error` at any point to abort, or `Cancel` when
+///    its user cancelled the transfer
+/// 7. Optionally (`flux send --receipt`), the sender sends a signed `Receipt`
+///    and the receiver answers with the same receipt countersigned
+///
+/// With pre-shared keys (`--psk-file`), each side sends `KeyConfirm` right
+/// after the `HandshakeAck` (sender first) to prove it holds the key.
+///
+/// A sender that lost the connection mid-transfer reconnects, handshakes
+/// again and sends `ResumeRequest` in place of `FileHeader`; the receiver
+/// answers with `ResumeAck` and the `DataChunk`s continue from that offset.
+///
+/// A sender may leave a BLAKE3 checksum out of the `FileHeader` and send it
+/// in a `FileFooter` after the data instead, hashing the file as it reads it
+/// for sending. Code-phrase senders and `flux push`/`flux pull` don't.
+///
+/// When an unencrypted `HandshakeAck` announces `zero_copy`, the sender may
+/// send each data chunk as a `RawData` frame followed by the raw bytes.
+///
+/// While the data flows, a sender may send `Keepalive` at any time; the
+/// receiver skips it.
+///
+/// When the `HandshakeAck` announces a chunk index, the sender of a large
+/// file may list its content-defined chunks in `ChunkList` messages right
+/// after the `FileHeader`; the receiver answers with `ChunksHave`, and each
+/// chunk it has is sent as a `ChunkRef` instead of `DataChunk`s (see
+/// `net::chunking`).
+///
+/// Discovery probes (`flux discover` when mDNS finds nothing) send `Ping`
+/// instead of `Handshake`; the receiver answers with `Pong` and closes.
+///
+/// `flux push` and `flux pull` open a tree session instead of sending one
+/// file: in place of the `FileHeader` the client sends any number of
+/// requests about paths below the receiver's output directory, each
+/// answered before the next (see `net::tree`):
+/// - `StatPath`, answered with `PathStat`
+/// - `ListTree`, answered with `TreeListing`s
+/// - `PutFile` followed by the file's `DataChunk`s, answered with
+///   `TransferComplete`
+/// - `GetFile`, answered with a `FileHeader` and `DataChunk`s, which the
+///   client acknowledges with `TransferComplete`
+/// - `DeletePath`, answered with `PathDeleted`
+///
+/// A request the receiver refuses or fails is answered with `Error` and
+/// the session goes on; it ends when the client closes the connection.
+///
+/// Peers of another `PROTOCOL_VERSION` are refused in the handshake, with a
+/// rejecting `HandshakeAck`; `decode_handshake_ack` also reads the shorter
+/// one a version 1 receiver sends.
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub enum FluxMessage {
     /// Initial handshake from sender to receiver.
@@ -60,6 +134,15 @@ pub enum FluxMessage {
         public_key: Option<Vec<u8>>,
         /// Reason for rejection (when accepted is false)
         reason: Option<String>,
+        /// Largest DataChunk payload the receiver wants to handle, in bytes.
+        /// `None` means the sender's default (`CHUNK_SIZE`).
+        max_chunk_size: Option<u32>,
+        /// Whether the receiver keeps a chunk index, so a `ChunkList` may
+        /// follow the `FileHeader`
+        chunk_index: bool,
+        /// Whether the receiver accepts `RawData` in place of `DataChunk`s.
+        /// Only offered on unencrypted sessions
+        zero_copy: bool,
     },
 
     /// File metadata sent before data transfer begins.
@@ -71,10 +154,14 @@ pub enum FluxMessage {
         filename: String,
         /// Total file size in bytes
         size: u64,
-        /// Optional BLAKE3 checksum for verification (hex-encoded)
+        /// Optional checksum for verification (hex-encoded). BLAKE3 unless
+        /// prefixed with another algorithm's name, e.g. `xxh3:<hex>`
         checksum: Option<String>,
         /// Whether the data chunks are encrypted
         encrypted: bool,
+        /// Whether the BLAKE3 checksum follows the data in a `FileFooter`
+        /// (`checksum` is then `None`)
+        footer: bool,
     },
 
     /// A chunk of file data.
@@ -91,10 +178,41 @@ pub enum FluxMessage {
         nonce: Option<Vec<u8>>,
     },
 
+    /// Content-defined chunks of the file, sent after the `FileHeader` to a
+    /// receiver with a chunk index. Long lists span several messages.
+    ChunkList {
+        /// bincode-encoded `Vec<net::chunking::ChunkInfo>`, encrypted like a
+        /// `DataChunk` when the session is
+        data: Vec<u8>,
+        /// XChaCha20 nonce (24 bytes) when encrypted
+        nonce: Option<Vec<u8>>,
+        /// Whether this is the end of the list
+        last: bool,
+    },
+
+    /// Receiver's answer to the last `ChunkList`.
+    ChunksHave {
+        /// One bit per listed chunk (lowest bit first), set for the chunks
+        /// the receiver already has; encrypted like the list
+        data: Vec<u8>,
+        /// XChaCha20 nonce (24 bytes) when encrypted
+        nonce: Option<Vec<u8>>,
+    },
+
+    /// Stands in for the `DataChunk`s of a listed chunk the receiver has;
+    /// the receiver copies it from its own earlier file.
+    ChunkRef {
+        /// Byte offset within the file, as a `DataChunk` would carry
+        offset: u64,
+        /// Position of the chunk in the `ChunkList`
+        index: u32,
+    },
+
     /// Acknowledgement from receiver after all data has been received.
     ///
-    /// Confirms the transfer is complete and optionally reports whether
-    /// the checksum was verified.
+    /// Confirms the transfer is complete, reports whether the checksum in
+    /// the `FileHeader` was verified and carries the receiver's own checksum
+    /// of what it wrote, which the sender compares against its own.
     TransferComplete {
         /// The filename that was transferred
         filename: String,
@@ -102,6 +220,10 @@ pub enum FluxMessage {
         bytes_received: u64,
         /// Whether the checksum matched (None if no checksum was provided)
         checksum_verified: Option<bool>,
+        /// Checksum of the received data, tagged as in `FileHeader`: the
+        /// header's algorithm, BLAKE3 if it had no checksum. The sender
+        /// refuses a `TransferComplete` without it
+        checksum: Option<String>,
     },
 
     /// Error message that can be sent by either side to abort the transfer.
@@ -109,6 +231,178 @@ pub enum FluxMessage {
         /// Human-readable error description
         message: String,
     },
+
+    /// Signed delivery receipt, exchanged after `TransferComplete`.
+    ///
+    /// Sent by the sender with only its own signature; the receiver replies
+    /// with the receipt countersigned. Receivers that don't support receipts
+    /// simply close the connection.
+    Receipt {
+        receipt: TransferReceipt,
+    },
+
+    /// Sent instead of `FileHeader` when a sender reconnects after losing the
+    /// connection mid-transfer.
+    ///
+    /// The checksum identifies the interrupted transfer; the receiver looks
+    /// for a partial file from the same device with the same checksum.
+    ResumeRequest {
+        /// File name (as in the original `FileHeader`)
+        filename: String,
+        /// Total file size in bytes
+        size: u64,
+        /// Checksum of the whole file, exactly as in the `FileHeader`, or
+        /// `footer_key(filename)` when it follows in a `FileFooter`
+        checksum: String,
+        /// Whether the data chunks are encrypted
+        encrypted: bool,
+    },
+
+    /// Receiver's answer to `ResumeRequest`.
+    ResumeAck {
+        /// Bytes the receiver already has; the sender continues from here.
+        /// 0 when the partial file is gone (the transfer starts over).
+        offset: u64,
+    },
+
+    /// Discovery probe sent in place of `Handshake` by a subnet scan.
+    Ping {
+        /// Protocol version of the prober
+        version: u8,
+    },
+
+    /// Receiver's answer to `Ping`; the connection is closed afterwards.
+    Pong {
+        /// Protocol version of the receiver
+        version: u8,
+        /// Friendly device name the receiver advertises
+        device_name: String,
+        /// Flux package version (as in the mDNS `version` TXT property)
+        flux_version: String,
+    },
+
+    /// Unencrypted file data sent outside the framing: this frame is
+    /// followed on the stream by exactly `len` raw bytes of the file from
+    /// `offset`, with no length prefix. Lets the sender hand the file to the
+    /// kernel (`sendfile`/`TransmitFile`) instead of copying it through
+    /// userspace; see `net::zerocopy`.
+    RawData {
+        /// Byte offset within the file, as a `DataChunk` would carry
+        offset: u64,
+        /// Number of raw bytes following the frame (at most the negotiated
+        /// chunk size)
+        len: u32,
+    },
+
+    /// The transfer was cancelled (Ctrl+C) by the side sending this; the
+    /// connection is closed afterwards and the receiver deletes its partial
+    /// file.
+    Cancel {
+        /// Why, for the peer's error message
+        reason: String,
+    },
+
+    /// Proof of a pre-shared key (`--psk-file`), exchanged after
+    /// `HandshakeAck`: a fixed per-side plaintext encrypted with the session
+    /// key, which only a peer holding the same key can derive.
+    KeyConfirm {
+        /// Encrypted proof (ciphertext + 16-byte tag)
+        proof: Vec<u8>,
+        /// XChaCha20 nonce (24 bytes)
+        nonce: Vec<u8>,
+    },
+
+    /// Sent by a sender with nothing else to send for `keepalive_interval`
+    /// (see `net::resume`), so the receiver does not take the quiet
+    /// connection for a stalled one. Carries nothing and is never answered.
+    Keepalive,
+
+    /// Tree session request: whether `path` exists and is a directory.
+    StatPath {
+        /// `/`-separated path relative to the receiver's output directory
+        path: String,
+    },
+
+    /// Receiver's answer to `StatPath`.
+    PathStat {
+        exists: bool,
+        dir: bool,
+    },
+
+    /// Tree session request: every file below the directory `path`.
+    ListTree {
+        /// `/`-separated path relative to the receiver's output directory
+        path: String,
+    },
+
+    /// Part of the answer to `ListTree`. Long listings span several
+    /// messages; a missing directory lists nothing.
+    TreeListing {
+        /// bincode-encoded `Vec<sync::plan::ListedFile>`, paths relative to
+        /// the listed directory, encrypted like a `DataChunk` when the
+        /// session is
+        data: Vec<u8>,
+        /// XChaCha20 nonce (24 bytes) when encrypted
+        nonce: Option<Vec<u8>>,
+        /// Whether this is the end of the listing
+        last: bool,
+    },
+
+    /// Tree session request: write the file that follows in `DataChunk`s
+    /// to `path`, replacing any file there.
+    PutFile {
+        /// `/`-separated path relative to the receiver's output directory
+        path: String,
+        /// Total file size in bytes
+        size: u64,
+        /// Modification time to give the file, in seconds since the Unix
+        /// epoch
+        modified: Option<i64>,
+        /// Checksum of the file, tagged as in `FileHeader`
+        checksum: String,
+    },
+
+    /// Tree session request: send the file at `path`.
+    GetFile {
+        /// `/`-separated path relative to the receiver's output directory
+        path: String,
+    },
+
+    /// Tree session request: delete the file at `path`.
+    DeletePath {
+        /// `/`-separated path relative to the receiver's output directory
+        path: String,
+    },
+
+    /// Receiver's answer to `DeletePath`.
+    PathDeleted {
+        path: String,
+    },
+
+    /// Checksum of a file whose `FileHeader` announced a `footer`, sent
+    /// after its last data chunk.
+    FileFooter {
+        /// BLAKE3 checksum of the whole file (hex-encoded)
+        checksum: String,
+    },
+}
+
+/// Identifies a transfer whose checksum comes in a `FileFooter` where a
+/// checksum would: in the `ResumeRequest` of a reconnecting sender, which
+/// has no checksum to send before the data is complete.
+pub fn footer_key(filename: &str) -> String {
+    format!("footer:{}", filename)
+}
+
+/// Resolve the chunk size a sender should use from the receiver's request.
+///
+/// Requests are clamped to `MIN_CHUNK_SIZE..=CHUNK_SIZE` so a receiver can
+/// only shrink chunks, never grow them past the frame budget.
+pub fn negotiated_chunk_size(requested: Option<u32>) -> usize {
+    match requested {
+        Some(size) => (size as usize).clamp(MIN_CHUNK_SIZE, CHUNK_SIZE),
+        None => CHUNK_SIZE,
+    }
 }
 
 /// Encode a FluxMessage into bytes using bincode 2.x (serde mode).
@@ -127,23 +421,58 @@ pub fn encode_message(msg: &FluxMessage) -> Result<Vec<u8>, FluxError> {
 /// and a byte limit matching MAX_FRAME_SIZE. This prevents a malicious peer
 /// from sending a crafted message that causes unbounded memory allocation
 /// during deserialization (e.g., a Vec with a claimed length of 2^64).
+///
+/// A frame that ends before the message does is an error.
 pub fn decode_message(bytes: &[u8]) -> Result<FluxMessage, FluxError> {
     let config = bincode::config::standard()
         .with_limit::<{ 2 * 1024 * 1024 }>(); // MAX_FRAME_SIZE = 2 MB
-    let (msg, _bytes_read): (FluxMessage, usize) =
-        bincode::serde::decode_from_slice(bytes, config).map_err(|e| {
-            FluxError::TransferError(format!("Failed to decode message: {}", e))
-        })?;
+    let (msg, _bytes_read) = bincode::serde::decode_from_slice(bytes, config).map_err(|e| {
+        FluxError::TransferError(format!("Failed to decode message: {}", e))
+    })?;
     Ok(msg)
 }
 
+/// A version 1 `HandshakeAck` as bincode reads it: the variant index, then
+/// `accepted`, `public_key` and `reason`. A version 1 receiver refuses a
+/// newer sender with one.
+type Version1Ack = (u32, bool, Option<Vec<u8>>, Option<String>);
+
+/// Variant index of `FluxMessage::HandshakeAck`.
+const HANDSHAKE_ACK_INDEX: u32 = 1;
+
+/// Decode the receiver's reply to a `Handshake`.
+///
+/// Like `decode_message`, but a version 1 receiver's rejection (of our
+/// protocol version) is read as a rejecting `HandshakeAck` too, so the
+/// sender can report its reason.
+pub fn decode_handshake_ack(bytes: &[u8]) -> Result<FluxMessage, FluxError> {
+    let error = match decode_message(bytes) {
+        Ok(msg) => return Ok(msg),
+        Err(e) => e,
+    };
+    let config = bincode::config::standard().with_limit::<{ 2 * 1024 * 1024 }>();
+    match bincode::serde::decode_from_slice::<Version1Ack, _>(bytes, config) {
+        Ok(((HANDSHAKE_ACK_INDEX, false, _, reason), read)) if read == bytes.len() => {
+            Ok(FluxMessage::HandshakeAck {
+                accepted: false,
+                public_key: None,
+                reason,
+                max_chunk_size: None,
+                chunk_index: false,
+                zero_copy: false,
+            })
+        }
+        _ => Err(error),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     #[test]
-    fn protocol_version_is_one() {
-        assert_eq!(PROTOCOL_VERSION, 1);
+    fn protocol_version_is_two() {
+        assert_eq!(PROTOCOL_VERSION, 2);
     }
 
     #[test]
@@ -203,6 +532,9 @@ mod tests {
             accepted: true,
             public_key: Some(vec![0xCD; 32]),
             reason: None,
+            max_chunk_size: None,
+            chunk_index: true,
+            zero_copy: false,
         };
         let encoded = encode_message(&msg).unwrap();
         let decoded = decode_message(&encoded).unwrap();
@@ -215,12 +547,41 @@ mod tests {
             accepted: false,
             public_key: None,
             reason: Some("Transfer rejected by user".to_string()),
+            max_chunk_size: None,
+            chunk_index: false,
+            zero_copy: false,
+        };
+        let encoded = encode_message(&msg).unwrap();
+        let decoded = decode_message(&encoded).unwrap();
+        assert_eq!(msg, decoded);
+    }
+
+    #[test]
+    fn roundtrip_handshake_ack_with_chunk_size() {
+        let msg = FluxMessage::HandshakeAck {
+            accepted: true,
+            public_key: Some(vec![0xCD; 32]),
+            reason: None,
+            max_chunk_size: Some(LOW_MEMORY_CHUNK_SIZE as u32),
+            chunk_index: false,
+            zero_copy: false,
         };
         let encoded = encode_message(&msg).unwrap();
         let decoded = decode_message(&encoded).unwrap();
         assert_eq!(msg, decoded);
     }
 
+    #[test]
+    fn negotiated_chunk_size_defaults_and_clamps() {
+        assert_eq!(negotiated_chunk_size(None), CHUNK_SIZE);
+        assert_eq!(
+            negotiated_chunk_size(Some(LOW_MEMORY_CHUNK_SIZE as u32)),
+            LOW_MEMORY_CHUNK_SIZE
+        );
+        assert_eq!(negotiated_chunk_size(Some(1)), MIN_CHUNK_SIZE);
+        assert_eq!(negotiated_chunk_size(Some(u32::MAX)), CHUNK_SIZE);
+    }
+
     #[test]
     fn roundtrip_file_header() {
         let msg = FluxMessage::FileHeader {
@@ -228,6 +589,7 @@ mod tests {
             size: 1_048_576, // 1 MB
             checksum: Some("abc123def456".to_string()),
             encrypted: false,
+            footer: false,
         };
         let encoded = encode_message(&msg).unwrap();
         let decoded = decode_message(&encoded).unwrap();
@@ -241,12 +603,32 @@ mod tests {
             size: 5_000_000,
             checksum: None,
             encrypted: true,
+            footer: false,
         };
         let encoded = encode_message(&msg).unwrap();
         let decoded = decode_message(&encoded).unwrap();
         assert_eq!(msg, decoded);
     }
 
+    #[test]
+    fn roundtrip_file_header_with_footer() {
+        let header = FluxMessage::FileHeader {
+            filename: "movie.mkv".to_string(),
+            size: 4_000_000_000,
+            checksum: None,
+            encrypted: true,
+            footer: true,
+        };
+        let footer = FluxMessage::FileFooter {
+            checksum: "abc123def456".to_string(),
+        };
+        for msg in [header, footer] {
+            let encoded = encode_message(&msg).unwrap();
+            assert_eq!(decode_message(&encoded).unwrap(), msg);
+        }
+        assert_eq!(footer_key("movie.mkv"), "footer:movie.mkv");
+    }
+
     #[test]
     fn roundtrip_data_chunk_unencrypted() {
         let data = vec![0u8; CHUNK_SIZE];
@@ -287,6 +669,7 @@ mod tests {
             filename: "report.pdf".to_string(),
             bytes_received: 1_048_576,
             checksum_verified: Some(true),
+            checksum: Some("xxh3:0123456789abcdef".to_string()),
         };
         let encoded = encode_message(&msg).unwrap();
         let decoded = decode_message(&encoded).unwrap();
@@ -299,12 +682,121 @@ mod tests {
             filename: "photo.jpg".to_string(),
             bytes_received: 500_000,
             checksum_verified: None,
+            checksum: None,
         };
         let encoded = encode_message(&msg).unwrap();
         let decoded = decode_message(&encoded).unwrap();
         assert_eq!(msg, decoded);
     }
 
+    #[test]
+    fn version_1_peers_are_told_apart_by_the_handshake() {
+        let config = bincode::config::standard();
+        // A version 1 sender's Handshake has not changed: the receiver reads
+        // its version and refuses it
+        let v1_handshake = bincode::serde::encode_to_vec(
+            (0u32, 1u8, "old-laptop".to_string(), None::<Vec<u8>>),
+            config,
+        )
+        .unwrap();
+        assert_eq!(
+            decode_message(&v1_handshake).unwrap(),
+            FluxMessage::Handshake {
+                version: 1,
+                device_name: "old-laptop".to_string(),
+                public_key: None,
+            }
+        );
+
+        // ... with a HandshakeAck a version 1 sender reads as a rejection
+        let reject = FluxMessage::HandshakeAck {
+            accepted: false,
+            public_key: None,
+            reason: Some("Protocol version mismatch: expected 2, got 1".to_string()),
+            max_chunk_size: None,
+            chunk_index: false,
+            zero_copy: false,
+        };
+        let encoded = encode_message(&reject).unwrap();
+        let (v1_ack, _): (Version1Ack, usize) =
+            bincode::serde::decode_from_slice(&encoded, config).unwrap();
+        assert_eq!(
+            v1_ack,
+            (
+                HANDSHAKE_ACK_INDEX,
+                false,
+                None,
+                Some("Protocol version mismatch: expected 2, got 1".to_string())
+            )
+        );
+
+        // A version 1 receiver's rejection of a newer sender is too short
+        // for this version's HandshakeAck, but the sender still gets its reason
+        let v1_reject = bincode::serde::encode_to_vec(
+            (
+                HANDSHAKE_ACK_INDEX,
+                false,
+                None::<Vec<u8>>,
+                Some("Protocol version mismatch: expected 1, got 2".to_string()),
+            ),
+            config,
+        )
+        .unwrap();
+        assert!(decode_message(&v1_reject).is_err());
+        match decode_handshake_ack(&v1_reject).unwrap() {
+            FluxMessage::HandshakeAck { accepted, reason, .. } => {
+                assert!(!accepted);
+                assert_eq!(reason.unwrap(), "Protocol version mismatch: expected 1, got 2");
+            }
+            other => panic!("Expected HandshakeAck, got {:?}", other),
+        }
+        // ... unlike a version 1 acceptance, which can't be used
+        let v1_accept = bincode::serde::encode_to_vec(
+            (HANDSHAKE_ACK_INDEX, true, None::<Vec<u8>>, None::<String>),
+            config,
+        )
+        .unwrap();
+        assert!(decode_handshake_ack(&v1_accept).is_err());
+    }
+
+    #[test]
+    fn truncated_messages_are_rejected() {
+        let messages = [
+            FluxMessage::TransferComplete {
+                filename: "photo.jpg".to_string(),
+                bytes_received: 500_000,
+                checksum_verified: Some(true),
+                checksum: None,
+            },
+            FluxMessage::HandshakeAck {
+                accepted: true,
+                public_key: None,
+                reason: None,
+                max_chunk_size: None,
+                chunk_index: true,
+                zero_copy: false,
+            },
+            FluxMessage::FileHeader {
+                filename: "report.pdf".to_string(),
+                size: 1_048_576,
+                checksum: Some("abc123def456".to_string()),
+                encrypted: false,
+                footer: false,
+            },
+        ];
+        for msg in messages {
+            let encoded = encode_message(&msg).unwrap();
+            for len in 0..encoded.len() {
+                assert!(
+                    decode_message(&encoded[..len]).is_err(),
+                    "{:?} cut to {} bytes decoded",
+                    msg,
+                    len
+                );
+            }
+        }
+    }
+
     #[test]
     fn roundtrip_error() {
         let msg = FluxMessage::Error {
@@ -315,6 +807,137 @@ mod tests {
         assert_eq!(msg, decoded);
     }
 
+    #[test]
+    fn roundtrip_resume_request_and_ack() {
+        let request = FluxMessage::ResumeRequest {
+            filename: "movie.mkv".to_string(),
+            size: 4_000_000_000,
+            checksum: "abc123def456".to_string(),
+            encrypted: true,
+        };
+        let ack = FluxMessage::ResumeAck { offset: 1_048_576 };
+        for msg in [request, ack] {
+            let encoded = encode_message(&msg).unwrap();
+            assert_eq!(decode_message(&encoded).unwrap(), msg);
+        }
+    }
+
+    #[test]
+    fn roundtrip_chunk_messages() {
+        let list = FluxMessage::ChunkList {
+            data: vec![0x33; 370],
+            nonce: Some(vec![0x42; 24]),
+            last: true,
+        };
+        let have = FluxMessage::ChunksHave {
+            data: vec![0b0000_0101],
+            nonce: None,
+        };
+        let reference = FluxMessage::ChunkRef {
+            offset: 3_145_728,
+            index: 2,
+        };
+        for msg in [list, have, reference] {
+            let encoded = encode_message(&msg).unwrap();
+            assert_eq!(decode_message(&encoded).unwrap(), msg);
+        }
+    }
+
+    #[test]
+    fn roundtrip_ping_and_pong() {
+        let ping = FluxMessage::Ping {
+            version: PROTOCOL_VERSION,
+        };
+        let pong = FluxMessage::Pong {
+            version: PROTOCOL_VERSION,
+            device_name: "nas".to_string(),
+            flux_version: "1.0.0".to_string(),
+        };
+        for msg in [ping, pong] {
+            let encoded = encode_message(&msg).unwrap();
+            assert_eq!(decode_message(&encoded).unwrap(), msg);
+        }
+    }
+
+    #[test]
+    fn roundtrip_cancel() {
+        let msg = FluxMessage::Cancel {
+            reason: CANCEL_REASON.to_string(),
+        };
+        let encoded = encode_message(&msg).unwrap();
+        assert_eq!(decode_message(&encoded).unwrap(), msg);
+    }
+
+    #[test]
+    fn roundtrip_key_confirm() {
+        let msg = FluxMessage::KeyConfirm {
+            proof: vec![0x5A; 40],
+            nonce: vec![0x07; 24],
+        };
+        let encoded = encode_message(&msg).unwrap();
+        assert_eq!(decode_message(&encoded).unwrap(), msg);
+    }
+
+    #[test]
+    fn roundtrip_tree_requests() {
+        let messages = [
+            FluxMessage::StatPath {
+                path: "photos/2024".to_string(),
+            },
+            FluxMessage::PathStat {
+                exists: true,
+                dir: true,
+            },
+            FluxMessage::ListTree {
+                path: "photos".to_string(),
+            },
+            FluxMessage::TreeListing {
+                data: vec![0x33; 48],
+                nonce: Some(vec![0x42; 24]),
+                last: false,
+            },
+            FluxMessage::PutFile {
+                path: "photos/a.jpg".to_string(),
+                size: 1_048_576,
+                modified: Some(1_700_000_000),
+                checksum: "xxh3:abcd".to_string(),
+            },
+            FluxMessage::GetFile {
+                path: "photos/a.jpg".to_string(),
+            },
+            FluxMessage::DeletePath {
+                path: "photos/old.jpg".to_string(),
+            },
+            FluxMessage::PathDeleted {
+                path: "photos/old.jpg".to_string(),
+            },
+        ];
+        for msg in messages {
+            let encoded = encode_message(&msg).unwrap();
+            assert_eq!(decode_message(&encoded).unwrap(), msg);
+        }
+    }
+
+    #[test]
+    fn roundtrip_keepalive() {
+        let encoded = encode_message(&FluxMessage::Keepalive).unwrap();
+        assert_eq!(decode_message(&encoded).unwrap(), FluxMessage::Keepalive);
+    }
+
+    #[test]
+    fn roundtrip_receipt() {
+        use crate::security::crypto::DeviceIdentity;
+        use crate::security::receipt::ReceiptKey;
+
+        let key = ReceiptKey::from_identity(&DeviceIdentity::generate());
+        let msg = FluxMessage::Receipt {
+            receipt: TransferReceipt::new_signed(&key, "laptop", "report.pdf", 42, "abcd"),
+        };
+        let encoded = encode_message(&msg).unwrap();
+        let decoded = decode_message(&encoded).unwrap();
+        assert_eq!(msg, decoded);
+    }
+
     #[test]
     fn encode_produces_compact_binary() {
         let msg = FluxMessage::Handshake {
@@ -357,12 +980,16 @@ mod tests {
                 accepted: true,
                 public_key: None,
                 reason: None,
+                max_chunk_size: None,
+                chunk_index: false,
+                zero_copy: false,
             },
             FluxMessage::FileHeader {
                 filename: "a".to_string(),
                 size: 0,
                 checksum: None,
                 encrypted: false,
+                footer: false,
             },
             FluxMessage::DataChunk {
                 offset: 0,
@@ -373,6 +1000,7 @@ mod tests {
                 filename: "a".to_string(),
                 bytes_received: 0,
                 checksum_verified: None,
+                checksum: None,
             },
             FluxMessage::Error {
                 message: "a".to_string(),
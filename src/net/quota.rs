@@ -0,0 +1,463 @@
+//! Daily receive quotas (`[receive]` table in config.toml).
+//!
+//! The receiver counts the bytes it accepts per local calendar day, in total
+//! and per sender device, in `receive_usage.json` in the data directory. A
+//! `FileHeader` (or `ResumeRequest`) that would take today's usage past a
+//! configured limit is refused with a protocol `Error` before any data is
+//! written, so a misbehaving sender cannot fill the disk overnight.
+//!
+//! Accepted files reserve their declared size up front; the reservation is
+//! settled with the bytes actually written when the transfer ends. That way
+//! concurrent transfers cannot overrun a limit together.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use bytesize::ByteSize;
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+
+use crate::config::types::ReceiveConfig;
+use crate::error::FluxError;
+
+/// File name of the usage counters in the data directory.
+const USAGE_FILE: &str = "receive_usage.json";
+
+/// Configured daily limits in bytes.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct QuotaLimits {
+    /// All senders combined
+    pub total: Option<u64>,
+    /// Any one sender device without an override
+    pub per_device: Option<u64>,
+    /// Per-device overrides, keyed by device name
+    pub devices: BTreeMap<String, u64>,
+}
+
+impl QuotaLimits {
+    /// Parse the `[receive]` quota settings ("50GB", "500 MiB", ...).
+    pub fn from_config(config: &ReceiveConfig) -> Result<Self, FluxError> {
+        let devices = config
+            .device_quotas
+            .iter()
+            .map(|(device, limit)| Ok((device.clone(), parse_quota(limit)?)))
+            .collect::<Result<_, FluxError>>()?;
+        Ok(Self {
+            total: config.daily_quota.as_deref().map(parse_quota).transpose()?,
+            per_device: config
+                .device_daily_quota
+                .as_deref()
+                .map(parse_quota)
+                .transpose()?,
+            devices,
+        })
+    }
+
+    /// Daily limit for one sender device.
+    pub fn device_limit(&self, device: &str) -> Option<u64> {
+        self.devices.get(device).copied().or(self.per_device)
+    }
+
+    pub fn is_unlimited(&self) -> bool {
+        self.total.is_none() && self.per_device.is_none() && self.devices.is_empty()
+    }
+}
+
+/// Parse a quota size such as "10GB" or "500 MiB".
+pub(crate) fn parse_quota(s: &str) -> Result<u64, FluxError> {
+    s.trim()
+        .parse::<ByteSize>()
+        .map(|size| size.as_u64())
+        .map_err(|_| {
+            FluxError::Config(format!(
+                "Invalid receive quota '{}'. Use sizes like '10GB' or '500MiB'",
+                s
+            ))
+        })
+}
+
+/// Bytes received on one day.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DailyUsage {
+    pub date: NaiveDate,
+    pub total: u64,
+    pub devices: BTreeMap<String, u64>,
+}
+
+impl DailyUsage {
+    fn empty(date: NaiveDate) -> Self {
+        Self {
+            date,
+            total: 0,
+            devices: BTreeMap::new(),
+        }
+    }
+
+    fn device(&self, device: &str) -> u64 {
+        self.devices.get(device).copied().unwrap_or(0)
+    }
+}
+
+struct QuotaState {
+    usage: DailyUsage,
+    /// Declared sizes of transfers in progress, not yet in `usage`
+    reserved_total: u64,
+    reserved: BTreeMap<String, u64>,
+    /// Where usage is persisted (`None` keeps it in memory)
+    path: Option<PathBuf>,
+}
+
+impl QuotaState {
+    /// Start a new day's counters when the date has changed.
+    fn roll_over(&mut self, today: NaiveDate) {
+        if self.usage.date != today {
+            self.usage = DailyUsage::empty(today);
+        }
+    }
+
+    fn save(&self) {
+        let Some(ref path) = self.path else { return };
+        let result = serde_json::to_string_pretty(&self.usage)
+            .map_err(FluxError::from)
+            .and_then(|json| {
+                let tmp = path.with_extension("json.tmp");
+                std::fs::write(&tmp, json)?;
+                std::fs::rename(&tmp, path)?;
+                Ok(())
+            });
+        if let Err(e) = result {
+            tracing::warn!("Failed to save receive usage: {}", e);
+        }
+    }
+}
+
+/// Daily quota accounting shared by all connections of a receiver.
+#[derive(Clone)]
+pub struct ReceiveQuota {
+    limits: Arc<QuotaLimits>,
+    state: Arc<Mutex<QuotaState>>,
+}
+
+impl ReceiveQuota {
+    /// Load today's usage from `data_dir` (a missing or unreadable file
+    /// starts from zero).
+    pub fn load(data_dir: &Path, limits: QuotaLimits) -> Self {
+        let path = data_dir.join(USAGE_FILE);
+        let today = today();
+        let usage = std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|json| serde_json::from_str::<DailyUsage>(&json).ok())
+            .filter(|usage| usage.date == today)
+            .unwrap_or_else(|| DailyUsage::empty(today));
+        Self::with_state(limits, usage, Some(path))
+    }
+
+    /// Quota accounting kept in memory only.
+    pub fn in_memory(limits: QuotaLimits) -> Self {
+        Self::with_state(limits, DailyUsage::empty(today()), None)
+    }
+
+    fn with_state(limits: QuotaLimits, usage: DailyUsage, path: Option<PathBuf>) -> Self {
+        Self {
+            limits: Arc::new(limits),
+            state: Arc::new(Mutex::new(QuotaState {
+                usage,
+                reserved_total: 0,
+                reserved: BTreeMap::new(),
+                path,
+            })),
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, QuotaState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Reserve `bytes` of today's quota for a file from `device`.
+    ///
+    /// Fails with `FluxError::QuotaExceeded` if the file would take the
+    /// device's or the overall usage past its limit.
+    pub fn reserve(&self, device: &str, bytes: u64) -> Result<QuotaReservation, FluxError> {
+        self.reserve_on(today(), device, bytes)
+    }
+
+    fn reserve_on(
+        &self,
+        today: NaiveDate,
+        device: &str,
+        bytes: u64,
+    ) -> Result<QuotaReservation, FluxError> {
+        let mut state = self.lock();
+        state.roll_over(today);
+
+        let device_used =
+            state.usage.device(device) + state.reserved.get(device).copied().unwrap_or(0);
+        if let Some(limit) = self.limits.device_limit(device) {
+            check_limit(&format!("device '{}'", device), device_used, bytes, limit)?;
+        }
+        if let Some(limit) = self.limits.total {
+            let used = state.usage.total + state.reserved_total;
+            check_limit("all devices", used, bytes, limit)?;
+        }
+
+        state.reserved_total += bytes;
+        *state.reserved.entry(device.to_string()).or_default() += bytes;
+        Ok(QuotaReservation {
+            quota: self.clone(),
+            device: device.to_string(),
+            reserved: bytes,
+            date: today,
+            settled: false,
+        })
+    }
+
+    /// Today's usage (completed transfers only).
+    pub fn usage(&self) -> DailyUsage {
+        let mut state = self.lock();
+        state.roll_over(today());
+        state.usage.clone()
+    }
+
+    pub fn limits(&self) -> &QuotaLimits {
+        &self.limits
+    }
+
+    /// The same usage counters under new limits (config reload). Clones of
+    /// `self` keep the old limits; all of them share today's usage.
+    pub fn with_limits(&self, limits: QuotaLimits) -> Self {
+        Self {
+            limits: Arc::new(limits),
+            state: Arc::clone(&self.state),
+        }
+    }
+
+    fn settle(&self, date: NaiveDate, device: &str, reserved: u64, received: u64) {
+        let mut state = self.lock();
+        state.reserved_total = state.reserved_total.saturating_sub(reserved);
+        if let Some(r) = state.reserved.get_mut(device) {
+            *r = r.saturating_sub(reserved);
+        }
+        // A transfer counts for the day it started; once the counters have
+        // moved on to a later day it no longer affects them
+        if state.usage.date < date {
+            state.roll_over(date);
+        }
+        if received > 0 && state.usage.date == date {
+            state.usage.total += received;
+            *state.usage.devices.entry(device.to_string()).or_default() += received;
+            state.save();
+        }
+    }
+}
+
+/// Quota accounting from config.toml, with usage kept in the data directory.
+pub fn load_receive_quota() -> Result<ReceiveQuota, FluxError> {
+    let config = crate::config::types::load_config()?;
+    let limits = QuotaLimits::from_config(&config.receive)?;
+    Ok(match crate::config::paths::flux_data_dir() {
+        Ok(data_dir) => ReceiveQuota::load(&data_dir, limits),
+        Err(e) => {
+            tracing::warn!("Receive usage will not be saved: {}", e);
+            ReceiveQuota::in_memory(limits)
+        }
+    })
+}
+
+fn check_limit(who: &str, used: u64, bytes: u64, limit: u64) -> Result<(), FluxError> {
+    if used.saturating_add(bytes) > limit {
+        return Err(FluxError::QuotaExceeded(format!(
+            "daily limit for {} is {}, {} already received today, file needs {}",
+            who,
+            ByteSize(limit),
+            ByteSize(used),
+            ByteSize(bytes)
+        )));
+    }
+    Ok(())
+}
+
+fn today() -> NaiveDate {
+    chrono::Local::now().date_naive()
+}
+
+/// Quota held by a transfer in progress.
+///
+/// `settle` records the bytes actually written; dropping an unsettled
+/// reservation releases it without counting anything.
+pub struct QuotaReservation {
+    quota: ReceiveQuota,
+    device: String,
+    reserved: u64,
+    date: NaiveDate,
+    settled: bool,
+}
+
+impl QuotaReservation {
+    /// Release the reservation and count `received` bytes against today.
+    pub fn settle(mut self, received: u64) {
+        self.quota
+            .settle(self.date, &self.device, self.reserved, received);
+        self.settled = true;
+    }
+}
+
+impl Drop for QuotaReservation {
+    fn drop(&mut self) {
+        if !self.settled {
+            self.quota.settle(self.date, &self.device, self.reserved, 0);
+        }
+    }
+}
+
+/// Lines for `flux receive --show-quota`.
+pub fn format_quota_status(limits: &QuotaLimits, usage: &DailyUsage) -> Vec<String> {
+    let amount = |used: u64, limit: Option<u64>| match limit {
+        Some(limit) => format!(
+            "{} of {} ({} left)",
+            ByteSize(used),
+            ByteSize(limit),
+            ByteSize(limit.saturating_sub(used))
+        ),
+        None => format!("{} (no limit)", ByteSize(used)),
+    };
+
+    let mut lines = vec![format!("Received on {}:", usage.date)];
+    lines.push(format!("  all devices: {}", amount(usage.total, limits.total)));
+
+    let mut devices: Vec<&String> = usage.devices.keys().collect();
+    devices.extend(limits.devices.keys().filter(|d| !usage.devices.contains_key(*d)));
+    devices.sort();
+    for device in devices {
+        lines.push(format!(
+            "  {}: {}",
+            device,
+            amount(usage.device(device), limits.device_limit(device))
+        ));
+    }
+    if let Some(limit) = limits.per_device {
+        lines.push(format!("  (other devices: {} per day each)", ByteSize(limit)));
+    }
+    if limits.is_unlimited() {
+        lines.push("No receive quotas configured (see [receive] in config.toml)".to_string());
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn day(d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2026, 3, d).unwrap()
+    }
+
+    fn quota(total: Option<u64>, per_device: Option<u64>) -> ReceiveQuota {
+        let limits = QuotaLimits {
+            total,
+            per_device,
+            devices: BTreeMap::from([("nas".to_string(), 5000)]),
+        };
+        ReceiveQuota::with_state(limits, DailyUsage::empty(day(1)), None)
+    }
+
+    #[test]
+    fn limits_parse_from_config() {
+        let config = ReceiveConfig {
+            daily_quota: Some("50GB".into()),
+            device_daily_quota: Some("1 KiB".into()),
+            device_quotas: BTreeMap::from([("laptop".to_string(), "2MB".to_string())]),
+            ..Default::default()
+        };
+        let limits = QuotaLimits::from_config(&config).unwrap();
+        assert_eq!(limits.total, Some(50_000_000_000));
+        assert_eq!(limits.device_limit("phone"), Some(1024));
+        assert_eq!(limits.device_limit("laptop"), Some(2_000_000));
+
+        let bad = ReceiveConfig {
+            daily_quota: Some("lots".into()),
+            ..Default::default()
+        };
+        assert!(QuotaLimits::from_config(&bad).is_err());
+    }
+
+    #[test]
+    fn device_limit_refuses_file_that_does_not_fit() {
+        let quota = quota(None, Some(1000));
+        quota.reserve_on(day(1), "phone", 600).unwrap().settle(600);
+        let err = quota.reserve_on(day(1), "phone", 500).err().unwrap();
+        assert!(matches!(err, FluxError::QuotaExceeded(_)));
+        // Other devices have their own allowance; overrides apply by name
+        assert!(quota.reserve_on(day(1), "tablet", 500).is_ok());
+        assert!(quota.reserve_on(day(1), "nas", 4000).is_ok());
+    }
+
+    #[test]
+    fn reservations_count_until_settled() {
+        let quota = quota(Some(1000), None);
+        let first = quota.reserve_on(day(1), "a", 800).unwrap();
+        // In-flight transfers hold their declared size
+        assert!(quota.reserve_on(day(1), "b", 300).is_err());
+        // Only the bytes actually written are kept
+        first.settle(100);
+        assert!(quota.reserve_on(day(1), "b", 300).is_ok());
+
+        let dropped = quota.reserve_on(day(1), "c", 900).unwrap();
+        drop(dropped);
+        assert_eq!(quota.lock().usage.total, 100);
+    }
+
+    #[test]
+    fn usage_resets_on_a_new_day() {
+        let quota = quota(Some(1000), None);
+        quota.reserve_on(day(1), "a", 1000).unwrap().settle(1000);
+        assert!(quota.reserve_on(day(1), "a", 1).is_err());
+        assert!(quota.reserve_on(day(2), "a", 1000).is_ok());
+    }
+
+    #[test]
+    fn new_limits_keep_todays_usage() {
+        let quota = quota(Some(1000), None);
+        quota.reserve_on(day(1), "a", 800).unwrap().settle(800);
+
+        let raised = quota.with_limits(QuotaLimits {
+            total: Some(2000),
+            ..Default::default()
+        });
+        let _held = raised.reserve_on(day(1), "a", 1000).unwrap();
+        assert!(raised.reserve_on(day(1), "a", 500).is_err());
+        // Usage and reservations are shared; the old limits stay with `quota`
+        assert!(quota.reserve_on(day(1), "a", 1).is_err());
+        assert!(raised.reserve_on(day(1), "a", 200).is_ok());
+    }
+
+    #[test]
+    fn usage_persists_in_data_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let quota = ReceiveQuota::load(dir.path(), QuotaLimits::default());
+        quota.reserve("laptop", 4096).unwrap().settle(4096);
+
+        let reloaded = ReceiveQuota::load(dir.path(), QuotaLimits::default());
+        let usage = reloaded.usage();
+        assert_eq!(usage.total, 4096);
+        assert_eq!(usage.devices.get("laptop"), Some(&4096));
+    }
+
+    #[test]
+    fn status_lists_devices_and_limits() {
+        let limits = QuotaLimits {
+            total: Some(10_000),
+            per_device: None,
+            devices: BTreeMap::from([("nas".to_string(), 5000)]),
+        };
+        let mut usage = DailyUsage::empty(day(1));
+        usage.total = 1000;
+        usage.devices.insert("laptop".into(), 1000);
+
+        let lines = format_quota_status(&limits, &usage);
+        assert_eq!(lines[0], "Received on 2026-03-01:");
+        assert!(lines[1].contains("left"));
+        assert!(lines[2].starts_with("  laptop:") && lines[2].ends_with("(no limit)"));
+        assert!(lines[3].starts_with("  nas:") && lines[3].contains("left"));
+    }
+}
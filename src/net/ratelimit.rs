@@ -0,0 +1,262 @@
+//! Bandwidth limits of peer-to-peer transfers.
+//!
+//! `flux send --limit-up` caps how fast the sender streams chunks; `flux
+//! receive --limit-down` caps how fast the receiver reads them, which TCP
+//! flow control passes back to the sender. Each connection gets its own
+//! `SharedLimiter`; a receiver with `max_receive_rate` also charges every
+//! connection to one limiter shared by all of them (`net::admission`).
+//!
+//! With `--adaptive-limit`, a background task measures the round-trip time
+//! to the peer once per `PROBE_INTERVAL` and lets `AdaptiveRate` lower the
+//! limit while queues on the path fill up (a video call on the same Wi-Fi
+//! would otherwise see the same delay), raising it again once they drain.
+//! A probe is a TCP connect to the peer's Flux port, timed from SYN to
+//! SYN-ACK (or the refusal), followed by a `Ping` so a listening receiver
+//! closes the connection quietly.
+
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use futures::{SinkExt, StreamExt};
+use tokio::net::TcpStream;
+use tokio_util::bytes::Bytes;
+use tokio_util::codec::{Framed, LengthDelimitedCodec};
+
+use crate::error::FluxError;
+use crate::net::protocol::{encode_message, FluxMessage, MAX_FRAME_SIZE, PROTOCOL_VERSION};
+use crate::transfer::throttle::{parse_bandwidth, AdaptiveRate, SharedLimiter};
+
+/// Time between latency probes.
+const PROBE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Probes slower than this are dropped (likely a firewall eating SYNs).
+const PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Bandwidth limit of one direction of a transfer.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RateLimit {
+    /// Upper bound in bytes/sec, `None` if unlimited
+    pub ceiling: Option<u64>,
+    /// Back off when the latency to the peer rises
+    pub adaptive: bool,
+}
+
+impl RateLimit {
+    /// Limit from the CLI flags (`--limit-up`/`--limit-down` and
+    /// `--adaptive-limit`).
+    pub fn from_args(limit: Option<&str>, adaptive: bool) -> Result<Self, FluxError> {
+        let ceiling = limit.map(parse_bandwidth).transpose()?;
+        Ok(Self { ceiling, adaptive })
+    }
+
+    /// Limiter for one connection, `None` if nothing is limited.
+    ///
+    /// `probe` is the address latency probes go to; adaptive limiting needs
+    /// one and is skipped without it. Must be called inside a tokio runtime.
+    pub fn start(&self, probe: Option<SocketAddr>) -> Option<ConnectionLimiter> {
+        self.start_shared(probe, None)
+    }
+
+    /// Like `start`, but the connection is also charged to `total`, a
+    /// limiter shared with other connections.
+    pub fn start_shared(
+        &self,
+        probe: Option<SocketAddr>,
+        total: Option<Arc<SharedLimiter>>,
+    ) -> Option<ConnectionLimiter> {
+        let probe = probe.filter(|_| self.adaptive);
+        if self.ceiling.is_none() && probe.is_none() && total.is_none() {
+            return None;
+        }
+        let limiter = Arc::new(SharedLimiter::new(self.ceiling));
+        let moved = Arc::new(AtomicU64::new(0));
+        let prober = probe.map(|addr| {
+            tokio::spawn(adapt(
+                AdaptiveRate::new(self.ceiling),
+                Arc::clone(&limiter),
+                Arc::clone(&moved),
+                addr,
+            ))
+        });
+        Some(ConnectionLimiter {
+            limiter,
+            total,
+            moved,
+            prober,
+        })
+    }
+}
+
+/// Rate limit of a running connection. Dropping it stops the probes.
+pub struct ConnectionLimiter {
+    limiter: Arc<SharedLimiter>,
+    /// Limiter shared with the other connections of a receiver
+    total: Option<Arc<SharedLimiter>>,
+    /// Bytes passed through `consume`, for the throughput of each probe interval
+    moved: Arc<AtomicU64>,
+    prober: Option<tokio::task::JoinHandle<()>>,
+}
+
+impl ConnectionLimiter {
+    /// Account for `bytes` just sent or received, waiting while over the limit.
+    pub async fn consume(&self, bytes: u64) {
+        self.moved.fetch_add(bytes, Ordering::Relaxed);
+        let mut wait = self.charge(bytes);
+        while !wait.is_zero() {
+            tokio::time::sleep(wait).await;
+            wait = self.charge(0);
+        }
+    }
+
+    /// The longer wait of the connection's own and the shared limiter.
+    fn charge(&self, bytes: u64) -> Duration {
+        let own = self.limiter.charge(bytes);
+        match &self.total {
+            Some(total) => own.max(total.charge(bytes)),
+            None => own,
+        }
+    }
+}
+
+impl Drop for ConnectionLimiter {
+    fn drop(&mut self) {
+        if let Some(prober) = &self.prober {
+            prober.abort();
+        }
+    }
+}
+
+/// Probe `peer` and retune `limiter` until aborted.
+async fn adapt(
+    mut control: AdaptiveRate,
+    limiter: Arc<SharedLimiter>,
+    moved: Arc<AtomicU64>,
+    peer: SocketAddr,
+) {
+    let mut last = (Instant::now(), 0u64);
+    loop {
+        tokio::time::sleep(PROBE_INTERVAL).await;
+        let Some(rtt) = probe_rtt(peer).await else {
+            continue;
+        };
+        let now = Instant::now();
+        let total = moved.load(Ordering::Relaxed);
+        let elapsed = now.duration_since(last.0).as_secs_f64().max(0.001);
+        let throughput = (total.saturating_sub(last.1) as f64 / elapsed) as u64;
+        last = (now, total);
+
+        let before = control.rate();
+        let rate = control.on_sample(rtt, throughput);
+        if rate != before {
+            tracing::debug!(
+                "RTT {:?} at {}/s: limit {}",
+                rtt,
+                bytesize::ByteSize(throughput),
+                rate.map_or("off".to_string(), |r| format!("{}/s", bytesize::ByteSize(r)))
+            );
+            limiter.set_rate(rate);
+        }
+    }
+}
+
+/// Round-trip time to `peer`, measured by a TCP connect.
+///
+/// A refused connection answers in one round trip too. `None` if the probe
+/// timed out or failed otherwise.
+async fn probe_rtt(peer: SocketAddr) -> Option<Duration> {
+    let started = Instant::now();
+    match tokio::time::timeout(PROBE_TIMEOUT, TcpStream::connect(peer)).await {
+        Ok(Ok(stream)) => {
+            let rtt = started.elapsed();
+            // A Flux receiver answers Ping with Pong and closes; anything
+            // else just sees a short-lived connection
+            let codec = LengthDelimitedCodec::builder()
+                .max_frame_length(MAX_FRAME_SIZE)
+                .new_codec();
+            let mut framed = Framed::new(stream, codec);
+            if let Ok(ping) = encode_message(&FluxMessage::Ping {
+                version: PROTOCOL_VERSION,
+            }) {
+                if framed.send(Bytes::from(ping)).await.is_ok() {
+                    let _ = tokio::time::timeout(PROBE_TIMEOUT, framed.next()).await;
+                }
+            }
+            Some(rtt)
+        }
+        Ok(Err(e)) if e.kind() == std::io::ErrorKind::ConnectionRefused => Some(started.elapsed()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn no_limit_without_ceiling_or_probe() {
+        let limit = RateLimit::from_args(None, true).unwrap();
+        assert_eq!(limit.ceiling, None);
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async {
+            assert!(limit.start(None).is_none());
+            assert!(RateLimit::default().start("127.0.0.1:9".parse().ok()).is_none());
+        });
+    }
+
+    #[test]
+    fn fixed_limit_parses_and_throttles() {
+        let limit = RateLimit::from_args(Some("100KB/s"), false).unwrap();
+        assert_eq!(limit.ceiling, Some(100_000));
+        assert!(RateLimit::from_args(Some("fast"), false).is_err());
+
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async {
+            let limiter = limit.start(None).expect("a ceiling starts a limiter");
+            assert!(limiter.prober.is_none());
+            let started = Instant::now();
+            // 1s of initial tokens, then 50KB of debt at 100KB/s
+            limiter.consume(150_000).await;
+            assert!(started.elapsed() >= Duration::from_millis(400));
+        });
+    }
+
+    #[test]
+    fn shared_limit_throttles_connections_together() {
+        let total = Arc::new(SharedLimiter::new(Some(100_000)));
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async {
+            let first = RateLimit::default()
+                .start_shared(None, Some(Arc::clone(&total)))
+                .expect("a shared limit starts a limiter");
+            let second = RateLimit::default()
+                .start_shared(None, Some(Arc::clone(&total)))
+                .unwrap();
+            let started = Instant::now();
+            // Each alone is within the initial tokens; together they are 50KB over
+            first.consume(75_000).await;
+            second.consume(75_000).await;
+            assert!(started.elapsed() >= Duration::from_millis(400));
+        });
+    }
+
+    #[test]
+    fn probe_measures_refused_and_listening_peers() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async {
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let addr = listener.local_addr().unwrap();
+            tokio::spawn(async move {
+                let _ = listener.accept().await;
+            });
+            assert!(probe_rtt(addr).await.is_some());
+
+            // Nothing listens on a port just released
+            let closed = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+            let closed_addr = closed.local_addr().unwrap();
+            drop(closed);
+            assert!(probe_rtt(closed_addr).await.is_some());
+        });
+    }
+}
@@ -0,0 +1,214 @@
+//! Receipt exchange at the end of a P2P transfer (see `security::receipt`).
+//!
+//! After `TransferComplete`, a sender running with `--receipt` sends a signed
+//! `Receipt`; the receiver checks it against the file it received,
+//! countersigns it and sends it back. Receivers without receipt support close
+//! the connection instead, which the sender reports as "no receipt".
+
+use std::path::Path;
+use std::time::Duration;
+
+use futures::{SinkExt, StreamExt};
+use tokio::net::TcpStream;
+use tokio_util::bytes::Bytes;
+use tokio_util::codec::{Framed, LengthDelimitedCodec};
+
+use crate::error::FluxError;
+use crate::net::protocol::{decode_message, encode_message, FluxMessage};
+use crate::security::crypto::DeviceIdentity;
+use crate::security::receipt::{ReceiptKey, TransferReceipt, MAX_CLOCK_SKEW_SECS};
+
+/// How long the sender waits for the countersigned receipt.
+const COUNTERSIGN_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How long a receiver waits after `TransferComplete` for a receipt request.
+/// Senders that don't want a receipt close the connection right away.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Sender side: sign a receipt for the delivered file and have the receiver
+/// countersign it.
+///
+/// Returns `Ok(None)` if the receiver closed the connection without
+/// answering (a flux version without receipt support).
+pub async fn request_receipt(
+    framed: &mut Framed<TcpStream, LengthDelimitedCodec>,
+    config_dir: &Path,
+    sender: &str,
+    filename: &str,
+    size: u64,
+    checksum: &str,
+) -> Result<Option<TransferReceipt>, FluxError> {
+    let identity = DeviceIdentity::load_or_create(config_dir)?;
+    let key = ReceiptKey::from_identity(&identity);
+    let receipt = TransferReceipt::new_signed(&key, sender, filename, size, checksum);
+
+    let request = FluxMessage::Receipt {
+        receipt: receipt.clone(),
+    };
+    framed
+        .send(Bytes::from(encode_message(&request)?))
+        .await
+        .map_err(|e| FluxError::TransferError(format!("Failed to send receipt: {}", e)))?;
+
+    let reply = match tokio::time::timeout(COUNTERSIGN_TIMEOUT, framed.next()).await {
+        Err(_) => {
+            return Err(FluxError::TransferError(
+                "Timed out waiting for the receipt countersignature".into(),
+            ))
+        }
+        Ok(None) => return Ok(None),
+        Ok(Some(frame)) => frame.map_err(|e| {
+            FluxError::TransferError(format!("Failed to receive receipt: {}", e))
+        })?,
+    };
+
+    match decode_message(&reply)? {
+        FluxMessage::Receipt { receipt: signed } => {
+            // The receiver must countersign exactly what we signed
+            let mut unsigned = signed.clone();
+            unsigned.receiver = None;
+            if unsigned != receipt {
+                return Err(FluxError::EncryptionError(
+                    "Receiver returned a modified receipt".into(),
+                ));
+            }
+            signed.verify()?;
+            Ok(Some(signed))
+        }
+        FluxMessage::Error { message } => Err(FluxError::TransferError(format!(
+            "Receiver refused to sign the receipt: {}",
+            message
+        ))),
+        _ => Err(FluxError::TransferError(
+            "Unexpected message while exchanging receipt".into(),
+        )),
+    }
+}
+
+/// Receiver side: wait briefly for a receipt request and countersign it.
+///
+/// `filename`, `size` and `checksum` describe what was actually received;
+/// a receipt claiming anything else is refused. Returns `Ok(None)` when the
+/// sender closes the connection without asking for a receipt.
+pub async fn countersign_receipt(
+    framed: &mut Framed<TcpStream, LengthDelimitedCodec>,
+    config_dir: &Path,
+    receiver: &str,
+    filename: &str,
+    size: u64,
+    checksum: &str,
+) -> Result<Option<TransferReceipt>, FluxError> {
+    let frame = match tokio::time::timeout(REQUEST_TIMEOUT, framed.next()).await {
+        Ok(Some(Ok(frame))) => frame,
+        // Timeout, close or reset: the sender did not ask for a receipt
+        _ => return Ok(None),
+    };
+
+    let mut receipt = match decode_message(&frame)? {
+        FluxMessage::Receipt { receipt } => receipt,
+        _ => {
+            return Err(FluxError::TransferError(
+                "Unexpected message after transfer complete".into(),
+            ))
+        }
+    };
+
+    if let Err(reason) = check_request(&receipt, filename, size, checksum, chrono::Utc::now()) {
+        let reject = FluxMessage::Error {
+            message: reason.clone(),
+        };
+        framed
+            .send(Bytes::from(encode_message(&reject)?))
+            .await
+            .ok();
+        return Err(FluxError::TransferError(format!(
+            "Refused to sign receipt: {}",
+            reason
+        )));
+    }
+
+    let identity = DeviceIdentity::load_or_create(config_dir)?;
+    receipt.countersign(&ReceiptKey::from_identity(&identity), receiver);
+
+    let reply = FluxMessage::Receipt {
+        receipt: receipt.clone(),
+    };
+    framed
+        .send(Bytes::from(encode_message(&reply)?))
+        .await
+        .map_err(|e| FluxError::TransferError(format!("Failed to send receipt: {}", e)))?;
+
+    Ok(Some(receipt))
+}
+
+/// Check a receipt request against what the receiver actually got.
+fn check_request(
+    receipt: &TransferReceipt,
+    filename: &str,
+    size: u64,
+    checksum: &str,
+    now: chrono::DateTime<chrono::Utc>,
+) -> Result<(), String> {
+    receipt.verify_sender().map_err(|e| e.to_string())?;
+    if receipt.receiver.is_some() {
+        return Err("receipt is already countersigned".into());
+    }
+    if receipt.filename != filename {
+        return Err(format!("filename '{}' does not match", receipt.filename));
+    }
+    if receipt.size != size {
+        return Err(format!(
+            "size {} does not match {} bytes received",
+            receipt.size, size
+        ));
+    }
+    if receipt.checksum != checksum {
+        return Err("checksum does not match the received file".into());
+    }
+    if (now - receipt.timestamp).num_seconds().abs() > MAX_CLOCK_SKEW_SECS {
+        return Err(format!(
+            "timestamp {} is too far from the local clock",
+            receipt.timestamp.to_rfc3339()
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request() -> TransferReceipt {
+        let key = ReceiptKey::from_identity(&DeviceIdentity::generate());
+        TransferReceipt::new_signed(&key, "laptop", "report.pdf", 1024, "abcd")
+    }
+
+    #[test]
+    fn matching_request_is_accepted() {
+        let receipt = request();
+        assert!(check_request(&receipt, "report.pdf", 1024, "abcd", receipt.timestamp).is_ok());
+    }
+
+    #[test]
+    fn mismatched_request_is_refused() {
+        let receipt = request();
+        let now = receipt.timestamp;
+        assert!(check_request(&receipt, "other.pdf", 1024, "abcd", now).is_err());
+        assert!(check_request(&receipt, "report.pdf", 1000, "abcd", now).is_err());
+        assert!(check_request(&receipt, "report.pdf", 1024, "ffff", now).is_err());
+    }
+
+    #[test]
+    fn stale_timestamp_is_refused() {
+        let receipt = request();
+        let later = receipt.timestamp + chrono::Duration::seconds(MAX_CLOCK_SKEW_SECS + 1);
+        assert!(check_request(&receipt, "report.pdf", 1024, "abcd", later).is_err());
+    }
+
+    #[test]
+    fn forged_sender_signature_is_refused() {
+        let mut receipt = request();
+        receipt.size = 2048;
+        assert!(check_request(&receipt, "report.pdf", 2048, "abcd", receipt.timestamp).is_err());
+    }
+}
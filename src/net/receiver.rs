@@ -3,28 +3,279 @@
 //! Binds a TCP listener, registers the device via mDNS, and accepts incoming
 //! connections. Each connection follows the Flux transfer protocol: handshake,
 //! optional encryption key exchange, file header, data chunks, completion ack.
+//!
+//! On Unix, SIGHUP makes the listener re-read the `[receive]` table of
+//! config.toml (output directory, daily quotas, free space, allowlist).
+//! Connections accepted after the reload use the new settings; transfers in
+//! progress finish with the ones they started with.
+//!
+//! Senders in the `[receive.devices]` allowlist are accepted without a trust
+//! prompt when their key matches the configured fingerprint, and their files
+//! may go to a directory of their own. `flux receive --daemon` never prompts
+//! and refuses every sender that is neither allowlisted nor already trusted.
+//! With `--psk-file`, senders prove they hold a pre-shared key instead, and
+//! neither the allowlist nor the trust store is consulted.
+//!
+//! Addresses that connect too often or keep failing the handshake are
+//! refused before anything is read (see `net::admission`).
 
+use std::collections::BTreeMap;
 use std::path::{Path, PathBuf};
-use std::sync::Arc;
+use std::sync::{Arc, RwLock};
 
 use base64::engine::general_purpose::STANDARD as BASE64;
 use base64::Engine;
 use futures::{SinkExt, StreamExt};
 use tokio_util::bytes::Bytes;
-use tokio::net::{TcpListener, TcpStream};
+use tokio::net::TcpStream;
 use tokio::sync::Semaphore;
 use tokio_util::codec::{Framed, LengthDelimitedCodec};
 
+use crate::config::aliases::expand_variables;
 use crate::config::paths::flux_config_dir;
+use crate::config::types::AllowedDevice;
 use crate::discovery::mdns::register_flux_service;
-use crate::discovery::service::FluxService;
+use crate::discovery::service::{FluxService, DEFAULT_PORT};
 use crate::error::FluxError;
+use crate::net::addr;
+use crate::net::admission::{is_handshake_failure, Admission, AdmissionLimits};
+use crate::net::audit::{AuditKind, AuditTrail};
+use crate::net::chunking::{ChunkIndex, IncomingChunks};
+use crate::net::lowmem::LOW_MEMORY_FLUSH_INTERVAL;
 use crate::net::protocol::{
-    decode_message, encode_message, FluxMessage, MAX_FRAME_SIZE, PROTOCOL_VERSION,
+    decode_message, encode_message, footer_key, FluxMessage, CANCEL_REASON, CANCEL_TIMEOUT,
+    LOW_MEMORY_CHUNK_SIZE, MAX_FRAME_SIZE, PROTOCOL_VERSION,
+};
+use crate::net::diskspace::{preallocate, SpacePolicy};
+use crate::net::quota::{load_receive_quota, QuotaLimits, ReceiveQuota};
+use crate::net::ratelimit::{ConnectionLimiter, RateLimit};
+use crate::net::receipt::countersign_receipt;
+use crate::net::resume::{
+    reopen_partial, stall_timeout, ActiveGuard, AttemptError, PartialReceive, PendingReceives,
+    ReconnectWindow, StallNotice, RECONNECT_DELAY, RECONNECT_GRACE,
 };
+use crate::net::storage::{ReceiveStorage, StorageLimits};
+use crate::net::tree::TreeSession;
+use crate::net::web::{self, WebDrop};
+use crate::net::zerocopy;
+use crate::progress::bar::create_network_progress;
 use crate::security::crypto::{DeviceIdentity, EncryptedChannel};
-use crate::security::trust::{TrustStatus, TrustStore};
+use crate::security::psk::{self, PreSharedKey, PskRole};
+use crate::security::receipt::TransferReceipt;
+use crate::security::trust::{
+    fingerprint, fingerprint_matches, is_valid_fingerprint, TrustStatus, TrustStore,
+    MIN_FINGERPRINT_LEN,
+};
+use crate::transfer::atomic::{temp_path, AtomicFile};
+use crate::transfer::cancel;
+use crate::transfer::checksum::{ChecksumAlgorithm, ChecksumHasher};
+use crate::transfer::conflict;
+use crate::transfer::history::{record_history, HistoryRecord};
 use crate::transfer::stats::TransferStats;
+use crate::transfer::status::StatusPublisher;
+
+/// Summary of a completed receive.
+#[derive(Debug, Clone)]
+pub struct ReceiveReport {
+    /// Where the file was written
+    pub output_path: PathBuf,
+    /// Bytes received and written
+    pub bytes: u64,
+    /// Sanitized device name of the sender
+    pub peer: String,
+    /// Checksum verdict (None if the sender sent no checksum)
+    pub checksum_verified: Option<bool>,
+    /// Receipt countersigned for the sender, if it asked for one
+    pub receipt: Option<TransferReceipt>,
+}
+
+/// Fill a receive history record from the transfer result and persist it,
+/// and record the outcome in the connection's audit trail.
+pub(crate) fn finish_receive_record(
+    record: &mut HistoryRecord,
+    audit: &AuditTrail,
+    result: &Result<ReceiveReport, FluxError>,
+) {
+    match result {
+        Ok(report) => {
+            // Code-phrase and web receives learn the sender's name only here
+            let mut audit = audit.clone();
+            audit.peer.get_or_insert_with(|| report.peer.clone());
+            audit.finish(Ok((report.bytes, report.checksum_verified)));
+            record.source = report.peer.clone();
+            record.dest = report.output_path.display().to_string();
+            record.bytes = report.bytes;
+            record.files = 1;
+            record.verified = report.checksum_verified;
+            record.peer = Some(report.peer.clone());
+            record.receipt = report.receipt.clone();
+            record_history(record, None);
+        }
+        Err(e) => {
+            audit.finish(Err(e));
+            record_history(record, Some(e));
+        }
+    }
+}
+
+/// Receiver settings that a reload (SIGHUP) can change.
+#[derive(Clone)]
+pub struct ReceiverSettings {
+    /// `--output`, which takes precedence over config.toml across reloads
+    output_override: Option<String>,
+    /// Output directory template ({date}, ... expanded per connection)
+    pub output: String,
+    /// Daily quotas; a reload keeps today's usage
+    pub quota: ReceiveQuota,
+    /// Free space to keep on the output disk, and preallocation
+    pub space: SpacePolicy,
+    /// Storage quotas; a reload keeps the reservations of running transfers
+    pub storage: ReceiveStorage,
+    /// Allowlisted senders (`[receive.devices]`)
+    pub devices: BTreeMap<String, AllowedDevice>,
+    /// `[receive] per_sender_dirs`: a subdirectory per verified sender
+    pub per_sender_dirs: bool,
+    /// `--daemon`: refuse unknown senders instead of prompting
+    pub refuse_unknown: bool,
+    /// `--limit-down`/`--adaptive-limit`, applied to each connection
+    pub limit: RateLimit,
+    /// `--psk-file`: authenticate senders with this key instead of TOFU
+    pub psk: Option<Arc<PreSharedKey>>,
+    /// `--allow-mirror`: serve `flux push`/`flux pull` tree sessions
+    pub allow_mirror: bool,
+    /// Per-IP connection rates, bans and the total rate cap; a reload keeps
+    /// the bans
+    pub admission: Admission,
+}
+
+impl ReceiverSettings {
+    /// Settings from config.toml, with `output_override` (`--output`) in
+    /// place of `[receive] output_dir`.
+    pub fn load(
+        output_override: Option<&str>,
+        refuse_unknown: bool,
+        limit: RateLimit,
+    ) -> Result<Self, FluxError> {
+        let config = crate::config::types::load_config()?;
+        Ok(Self {
+            output_override: output_override.map(str::to_string),
+            output: output_template(output_override, &config.receive),
+            quota: load_receive_quota()?,
+            space: SpacePolicy::from_config(&config.receive)?,
+            storage: ReceiveStorage::new(StorageLimits::from_config(&config.receive)?),
+            devices: load_allowlist(&config.receive)?,
+            per_sender_dirs: config.receive.per_sender_dirs,
+            refuse_unknown,
+            limit,
+            psk: None,
+            allow_mirror: false,
+            admission: Admission::new(AdmissionLimits::from_config(&config.receive)?),
+        })
+    }
+
+    /// Re-read config.toml. Fails (leaving `self` unchanged) if the new
+    /// config is invalid.
+    pub fn reload(&self) -> Result<Self, FluxError> {
+        let config = crate::config::types::load_config()?;
+        let limits = QuotaLimits::from_config(&config.receive)?;
+        Ok(Self {
+            output_override: self.output_override.clone(),
+            output: output_template(self.output_override.as_deref(), &config.receive),
+            quota: self.quota.with_limits(limits),
+            space: SpacePolicy::from_config(&config.receive)?,
+            storage: self
+                .storage
+                .with_limits(StorageLimits::from_config(&config.receive)?),
+            devices: load_allowlist(&config.receive)?,
+            per_sender_dirs: config.receive.per_sender_dirs,
+            refuse_unknown: self.refuse_unknown,
+            limit: self.limit,
+            psk: self.psk.clone(),
+            allow_mirror: self.allow_mirror,
+            admission: self
+                .admission
+                .with_limits(AdmissionLimits::from_config(&config.receive)?),
+        })
+    }
+
+    /// Output directory template for a sender: its own `output_dir` if it
+    /// is allowlisted (and verified), else the shared one.
+    fn output_for(&self, allowlisted: Option<&str>) -> &str {
+        allowlisted
+            .and_then(|name| self.devices.get(name))
+            .and_then(|device| device.output_dir.as_deref())
+            .unwrap_or(&self.output)
+    }
+
+    /// Directory for the files of a sender, given `shared`, the expanded
+    /// shared output directory. With `per_sender_dirs`, a verified sender
+    /// without an `output_dir` of its own gets a subdirectory named after
+    /// its device.
+    fn sender_dir(&self, shared: &Path, verified: Option<&str>) -> PathBuf {
+        let template = self.output_for(verified);
+        if template != self.output {
+            return PathBuf::from(expand_variables(template));
+        }
+        match verified {
+            Some(name) if self.per_sender_dirs => shared.join(sanitize_filename(name)),
+            _ => shared.to_path_buf(),
+        }
+    }
+}
+
+/// The `[receive.devices]` allowlist, with every fingerprint checked.
+fn load_allowlist(
+    config: &crate::config::types::ReceiveConfig,
+) -> Result<BTreeMap<String, AllowedDevice>, FluxError> {
+    for (name, device) in &config.devices {
+        if !is_valid_fingerprint(&device.fingerprint) {
+            return Err(FluxError::Config(format!(
+                "[receive.devices.{}] fingerprint '{}' is too short: use at least {} characters of the key",
+                name, device.fingerprint, MIN_FINGERPRINT_LEN
+            )));
+        }
+    }
+    Ok(config.devices.clone())
+}
+
+/// Output directory template: `--output`, else `[receive] output_dir`, else
+/// the current directory, with aliases resolved (`nas:inbox`).
+pub fn output_template(
+    output_override: Option<&str>,
+    config: &crate::config::types::ReceiveConfig,
+) -> String {
+    let output = output_override.or(config.output_dir.as_deref()).unwrap_or(".");
+    crate::config::aliases::resolve(output)
+}
+
+/// Re-read the receiver settings whenever the process gets SIGHUP.
+#[cfg(unix)]
+fn reload_on_sighup(settings: Arc<RwLock<ReceiverSettings>>) -> Result<(), FluxError> {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut hangup = signal(SignalKind::hangup()).map_err(|e| {
+        FluxError::TransferError(format!("Failed to listen for SIGHUP: {}", e))
+    })?;
+    tokio::spawn(async move {
+        while hangup.recv().await.is_some() {
+            let current = settings.read().unwrap_or_else(|e| e.into_inner()).clone();
+            match current.reload() {
+                Ok(reloaded) => {
+                    eprintln!("Reloaded settings (output: {})", reloaded.output);
+                    *settings.write().unwrap_or_else(|e| e.into_inner()) = reloaded;
+                }
+                Err(e) => eprintln!("Reload failed, keeping the current settings: {}", e),
+            }
+        }
+    });
+    Ok(())
+}
+
+/// SIGHUP does not exist here; settings stay as they were at startup.
+#[cfg(not(unix))]
+fn reload_on_sighup(_settings: Arc<RwLock<ReceiverSettings>>) -> Result<(), FluxError> {
+    Ok(())
+}
 
 /// Start the Flux file receiver.
 ///
@@ -33,23 +284,30 @@ use crate::transfer::stats::TransferStats;
 /// in a spawned task. At most 8 connections are handled concurrently; additional
 /// connections wait until a slot is available.
 ///
-/// This function runs until cancelled (Ctrl+C).
+/// Files that would exceed the daily or storage quotas in `settings` are refused at
+/// FileHeader time. On Unix, SIGHUP reloads `settings` for later connections.
+///
+/// With `web`, a page for browser drops is served as well (`net::web`).
+///
+/// This function runs until cancelled (Ctrl+C): running transfers then send
+/// `Cancel` to their senders and delete their partial files, and
+/// `FluxError::Cancelled` is returned.
 pub async fn start_receiver(
     port: u16,
-    output_dir: &Path,
+    settings: ReceiverSettings,
     encrypt: bool,
     device_name: &str,
     config_dir: &Path,
     bind_addr: &str,
+    web: Option<WebDrop>,
 ) -> Result<(), FluxError> {
-    let listener = TcpListener::bind(format!("{}:{}", bind_addr, port))
-        .await
-        .map_err(|e| {
-            FluxError::TransferError(format!(
-                "Failed to bind {}:{}: {}. Try a different address with --bind or port with --port.",
-                bind_addr, port, e
-            ))
-        })?;
+    let listener = addr::listen(bind_addr, port).await.map_err(|e| {
+        FluxError::TransferError(format!(
+            "Failed to bind {}: {}. Try a different address with --bind or port with --port.",
+            addr::join_host_port(bind_addr, port),
+            e
+        ))
+    })?;
 
     let local_addr = listener.local_addr().map_err(|e| {
         FluxError::TransferError(format!("Failed to get local address: {}", e))
@@ -74,24 +332,65 @@ pub async fn start_receiver(
     if encrypt {
         eprintln!("Encryption: enabled");
     }
+    if let Some(psk) = &settings.psk {
+        eprintln!("Pre-shared key: {} (senders need the same --psk-file)", psk.id());
+    }
+    #[cfg(unix)]
+    eprintln!(
+        "Reload config.toml without dropping transfers: kill -HUP {}",
+        std::process::id()
+    );
 
-    let output_dir = output_dir.to_path_buf();
+    let settings = Arc::new(RwLock::new(settings));
+    reload_on_sighup(Arc::clone(&settings))?;
+    if let Some(web) = web {
+        web::start(web, bind_addr, &service.device_name, Arc::clone(&settings)).await?;
+    }
     let config_dir = config_dir.to_path_buf();
 
     // Limit concurrent connections to 8 to prevent resource exhaustion.
     // Connections beyond this limit wait until an active transfer finishes.
     let semaphore = Arc::new(Semaphore::new(8));
 
+    // Partial files of dropped connections, kept for their senders to resume
+    let pending = PendingReceives::new();
+
     loop {
-        let (stream, peer_addr) = listener.accept().await.map_err(|e| {
+        let accepted = tokio::select! {
+            accepted = listener.accept() => accepted,
+            _ = cancel::cancelled() => {
+                // Give running transfers time to tell their senders and
+                // delete their partial files
+                let finished = semaphore.acquire_many(8);
+                let _ = tokio::time::timeout(CANCEL_TIMEOUT * 2, finished).await;
+                return Err(FluxError::Cancelled);
+            }
+        };
+        let (stream, peer_addr) = accepted.map_err(|e| {
             FluxError::TransferError(format!("Failed to accept connection: {}", e))
         })?;
-
-        eprintln!("Connection from {}", peer_addr);
-
-        let out = output_dir.clone();
+        let peer_addr = addr::canonical(peer_addr);
+
+        // Each connection keeps the settings current when it was accepted.
+        // Destination templates ({date}, ...) are expanded per connection.
+        let current = settings.read().unwrap_or_else(|e| e.into_inner()).clone();
+
+        // Banned and too frequent addresses are dropped before they take a slot
+        if let Err(refusal) = current.admission.admit(peer_addr.ip()) {
+            tracing::warn!("Refusing connection from {}: {}", peer_addr, refusal);
+            AuditTrail::new("receive", Some(peer_addr))
+                .event(AuditKind::Refused)
+                .reason(refusal)
+                .record();
+            drop(stream);
+            continue;
+        }
+        let admission = current.admission.clone();
+        let out = PathBuf::from(expand_variables(&current.output));
         let cfg = config_dir.clone();
         let enc = encrypt;
+        let name = service.device_name.clone();
+        let resumable = pending.clone();
 
         // Acquire a permit before spawning. The permit is moved into the task
         // and released automatically when the task completes (via Drop).
@@ -112,6 +411,10 @@ pub async fn start_receiver(
             }
         };
 
+        let mut record = HistoryRecord::new("receive", &peer_addr.to_string(), &out.display().to_string());
+        record.peer = Some(peer_addr.to_string());
+        let mut audit = AuditTrail::new("receive", Some(peer_addr));
+
         tokio::spawn(async move {
             // Hold the permit for the duration of the connection.
             let _permit = permit;
@@ -120,14 +423,36 @@ pub async fn start_receiver(
             // The handshake must complete within 30 seconds; the entire transfer within 30 minutes.
             let result = tokio::time::timeout(
                 std::time::Duration::from_secs(30 * 60),
-                handle_connection(stream, out, enc, cfg),
+                handle_connection(stream, current, enc, cfg, name, resumable, &mut audit),
             )
             .await;
-            match result {
-                Ok(Err(e)) => eprintln!("Transfer error from {}: {}", peer_addr, e),
-                Err(_) => eprintln!("Connection from {} timed out", peer_addr),
-                Ok(Ok(())) => {}
+            let result = match result {
+                Ok(Err(e)) => {
+                    eprintln!("Transfer error from {}: {}", peer_addr, e);
+                    Err(e)
+                }
+                Err(_) => {
+                    eprintln!("Connection from {} timed out", peer_addr);
+                    Err(FluxError::TransferError(format!(
+                        "Connection from {} timed out",
+                        peer_addr
+                    )))
+                }
+                // Discovery probe: nothing to record
+                Ok(Ok(None)) => return,
+                Ok(Ok(Some(report))) => Ok(report),
+            };
+            match &result {
+                Ok(_) => admission.handshake_succeeded(peer_addr.ip()),
+                Err(e) if is_handshake_failure(e, audit.peer.is_some()) => {
+                    if admission.handshake_failed(peer_addr.ip()) {
+                        eprintln!("Banning {} after repeated failed handshakes", peer_addr.ip());
+                        audit.event(AuditKind::Refused).reason("banned").record();
+                    }
+                }
+                Err(_) => {}
             }
+            finish_receive_record(&mut record, &audit, &result);
         });
     }
 }
@@ -136,18 +461,36 @@ pub async fn start_receiver(
 ///
 /// Protocol flow:
 /// 1. Read Handshake, verify version
-/// 2. If encrypting: key exchange + TOFU check
+/// 2. If encrypting: allowlist/TOFU check + key exchange (with a pre-shared
+///    key: no check, but the sender must prove it holds the key right after
+///    the HandshakeAck)
 /// 3. Send HandshakeAck
-/// 4. Read FileHeader, create output file
+/// 4. Read FileHeader, create output file (or ResumeRequest: reopen the
+///    partial file from an interrupted connection and send ResumeAck)
 /// 5. Read DataChunks, decrypt if needed, write to file
 /// 6. Send TransferComplete
+/// 7. Countersign a delivery receipt if the sender asks for one
+///
+/// A discovery probe sends `Ping` instead of a Handshake; it is answered
+/// with `Pong` and the connection returns `None`. So does a `flux push` or
+/// `flux pull` tree session, which starts with a request in place of the
+/// FileHeader (see `net::tree`).
+///
+/// If the connection drops mid-transfer, the partial file is parked in
+/// `pending` for `RECONNECT_GRACE` so the sender can resume it. A file that
+/// does not fit in the sender's remaining daily quota is refused with an
+/// `Error` before any data is written.
 async fn handle_connection(
     stream: TcpStream,
-    output_dir: PathBuf,
+    settings: ReceiverSettings,
     encrypt: bool,
     config_dir: PathBuf,
-) -> Result<(), FluxError> {
+    device_name: String,
+    pending: PendingReceives,
+    audit: &mut AuditTrail,
+) -> Result<Option<ReceiveReport>, FluxError> {
     let started = std::time::Instant::now();
+    let peer_addr = stream.peer_addr().ok().map(addr::canonical);
 
     let codec = LengthDelimitedCodec::builder()
         .max_frame_length(MAX_FRAME_SIZE)
@@ -169,6 +512,14 @@ async fn handle_connection(
             device_name,
             public_key,
         } => {
+            audit.peer = Some(sanitize_peer_device_name(&device_name));
+            audit.fingerprint = public_key.as_deref().map(|key| BASE64.encode(key));
+            let handshake = audit.event(AuditKind::Handshake);
+            if public_key.is_some() {
+                handshake.record();
+            } else {
+                handshake.reason("unencrypted").record();
+            }
             if version != PROTOCOL_VERSION {
                 let reject = FluxMessage::HandshakeAck {
                     accepted: false,
@@ -177,6 +528,9 @@ async fn handle_connection(
                         "Protocol version mismatch: expected {}, got {}",
                         PROTOCOL_VERSION, version
                     )),
+                    max_chunk_size: None,
+                    chunk_index: false,
+                    zero_copy: false,
                 };
                 framed
                     .send(Bytes::from(encode_message(&reject)?))
@@ -189,19 +543,38 @@ async fn handle_connection(
             }
             (device_name, public_key)
         }
+        FluxMessage::Ping { .. } => {
+            let pong = FluxMessage::Pong {
+                version: PROTOCOL_VERSION,
+                device_name,
+                flux_version: env!("CARGO_PKG_VERSION").to_string(),
+            };
+            framed.send(Bytes::from(encode_message(&pong)?)).await.ok();
+            return Ok(None);
+        }
         _ => {
             return Err(FluxError::TransferError(
                 "Expected Handshake as first message".into(),
             ));
         }
     };
+    // Announced only now: discovery scans and latency probes also connect
+    if let Some(addr) = peer_addr {
+        eprintln!("Connection from {}", addr);
+    }
 
     // Sanitize the peer-supplied device name before it is used as a trust store
     // key or displayed to the user. The name arrives untrusted from the network
     // and could contain control characters or be excessively long.
     let peer_device_name = sanitize_peer_device_name(&peer_device_name);
 
-    // --- Encryption / TOFU ---
+    // Announced in the HandshakeAck, so a sender may list its chunks
+    let chunk_index = ChunkIndex::open();
+
+    // --- Encryption / allowlist / TOFU ---
+    // Only senders verified against the allowlist or the trust store get
+    // their own output dir
+    let mut verified = false;
     let channel = if encrypt {
         let peer_pub_bytes: [u8; 32] = peer_public_key
             .ok_or_else(|| {
@@ -212,75 +585,121 @@ async fn handle_connection(
             .try_into()
             .map_err(|_| FluxError::EncryptionError("Sender public key must be 32 bytes".into()))?;
 
-        // TOFU check
+        // Allowlist / TOFU check
         let peer_pub_b64 = BASE64.encode(peer_pub_bytes);
-        let mut trust_store = TrustStore::load(&config_dir)?;
 
-        match trust_store.is_trusted(&peer_device_name, &peer_pub_b64) {
-            TrustStatus::Trusted => {
-                eprintln!("Verified: {} (trusted)", peer_device_name);
-            }
-            TrustStatus::Unknown => {
-                // Prompt user for trust confirmation
-                let fingerprint = &peer_pub_b64[..std::cmp::min(16, peer_pub_b64.len())];
+        // With a pre-shared key, the key confirmation below authenticates
+        // the sender. Allowlisted senders skip the trust store; a key that
+        // doesn't match the configured fingerprint is refused
+        if settings.psk.is_some() {
+            tracing::debug!("{} will be checked against the pre-shared key", peer_device_name);
+        } else if let Some(allowed) = settings.devices.get(&peer_device_name) {
+            if !fingerprint_matches(&allowed.fingerprint, &peer_pub_b64) {
                 eprintln!(
-                    "New device: {} (fingerprint: {}...)",
-                    peer_device_name, fingerprint
+                    "Refusing {}: key (fingerprint: {}) does not match the allowlist",
+                    peer_device_name,
+                    fingerprint(&peer_pub_b64)
                 );
-                // Interactive confirmation: ask the user before trusting
-                eprint!("Trust this device? [y/N]: ");
-                let mut input = String::new();
-                if std::io::stdin().read_line(&mut input).is_ok()
-                    && input.trim().eq_ignore_ascii_case("y")
-                {
-                    trust_store.add_device(
-                        peer_device_name.clone(),
-                        peer_pub_b64,
-                        peer_device_name.clone(),
+                reject_handshake(&mut framed, "Device key does not match the receiver's allowlist")
+                    .await?;
+                return Err(FluxError::TrustError(format!(
+                    "Key of '{}' does not match its allowlisted fingerprint",
+                    peer_device_name
+                )));
+            }
+            eprintln!("Verified: {} (allowlisted)", peer_device_name);
+            audit.event(AuditKind::Trusted).reason("allowlisted").record();
+            verified = true;
+        } else {
+            let mut trust_store = TrustStore::load(&config_dir)?;
+            match trust_store.is_trusted(&peer_device_name, &peer_pub_b64) {
+                TrustStatus::Trusted => {
+                    eprintln!("Verified: {} (trusted)", peer_device_name);
+                    audit.event(AuditKind::Trusted).reason("trust store").record();
+                    verified = true;
+                }
+                TrustStatus::Unknown if settings.refuse_unknown => {
+                    eprintln!(
+                        "Refusing unknown device: {} (fingerprint: {})",
+                        peer_device_name,
+                        fingerprint(&peer_pub_b64)
                     );
-                    trust_store.save()?;
-                    eprintln!("Device trusted.");
-                } else {
+                    reject_handshake(&mut framed, "Connection rejected: device not allowed").await?;
+                    return Err(FluxError::TrustError(format!(
+                        "Refused unknown device '{}'",
+                        peer_device_name
+                    )));
+                }
+                TrustStatus::Unknown => {
+                    // Prompt user for trust confirmation
+                    let fingerprint = &peer_pub_b64[..std::cmp::min(16, peer_pub_b64.len())];
+                    eprintln!(
+                        "New device: {} (fingerprint: {}...)",
+                        peer_device_name, fingerprint
+                    );
+                    // Interactive confirmation: ask the user before trusting
+                    eprint!("Trust this device? [y/N]: ");
+                    let mut input = String::new();
+                    if std::io::stdin().read_line(&mut input).is_ok()
+                        && input.trim().eq_ignore_ascii_case("y")
+                    {
+                        trust_store.add_device(
+                            peer_device_name.clone(),
+                            peer_pub_b64,
+                            peer_device_name.clone(),
+                        );
+                        trust_store.save()?;
+                        eprintln!("Device trusted.");
+                        audit.event(AuditKind::Trusted).reason("trusted at the prompt").record();
+                        verified = true;
+                    } else {
+                        let reject = FluxMessage::HandshakeAck {
+                            accepted: false,
+                            public_key: None,
+                            reason: Some("Connection rejected: device not trusted".into()),
+                            max_chunk_size: None,
+                            chunk_index: false,
+                            zero_copy: false,
+                        };
+                        framed
+                            .send(Bytes::from(encode_message(&reject)?))
+                            .await
+                            .ok();
+                        return Err(FluxError::TrustError(format!(
+                            "Rejected untrusted device '{}'",
+                            peer_device_name
+                        )));
+                    }
+                }
+                TrustStatus::KeyChanged => {
+                    eprintln!("@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@");
+                    eprintln!("@    WARNING: DEVICE IDENTIFICATION HAS CHANGED!          @");
+                    eprintln!("@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@");
+                    eprintln!(
+                        "The public key for '{}' has changed.",
+                        peer_device_name
+                    );
+                    eprintln!("This could indicate a man-in-the-middle attack.");
+                    eprintln!("Connection rejected. Use `flux trust rm {}` to remove the old key.", peer_device_name);
+
                     let reject = FluxMessage::HandshakeAck {
                         accepted: false,
                         public_key: None,
-                        reason: Some("Connection rejected: device not trusted".into()),
+                        reason: Some("Device key has changed - possible impersonation".into()),
+                        max_chunk_size: None,
+                        chunk_index: false,
+                        zero_copy: false,
                     };
                     framed
                         .send(Bytes::from(encode_message(&reject)?))
                         .await
                         .ok();
                     return Err(FluxError::TrustError(format!(
-                        "Rejected untrusted device '{}'",
+                        "Key changed for device '{}'",
                         peer_device_name
                     )));
                 }
             }
-            TrustStatus::KeyChanged => {
-                eprintln!("@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@");
-                eprintln!("@    WARNING: DEVICE IDENTIFICATION HAS CHANGED!          @");
-                eprintln!("@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@");
-                eprintln!(
-                    "The public key for '{}' has changed.",
-                    peer_device_name
-                );
-                eprintln!("This could indicate a man-in-the-middle attack.");
-                eprintln!("Connection rejected. Use `flux trust rm {}` to remove the old key.", peer_device_name);
-
-                let reject = FluxMessage::HandshakeAck {
-                    accepted: false,
-                    public_key: None,
-                    reason: Some("Device key has changed - possible impersonation".into()),
-                };
-                framed
-                    .send(Bytes::from(encode_message(&reject)?))
-                    .await
-                    .ok();
-                return Err(FluxError::TrustError(format!(
-                    "Key changed for device '{}'",
-                    peer_device_name
-                )));
-            }
         }
 
         // Generate our ephemeral key pair for this session
@@ -292,6 +711,9 @@ async fn handle_connection(
             accepted: true,
             public_key: Some(our_pub_bytes),
             reason: None,
+            max_chunk_size: None,
+            chunk_index: chunk_index.is_some(),
+            zero_copy: false,
         };
         framed
             .send(Bytes::from(encode_message(&ack)?))
@@ -300,7 +722,16 @@ async fn handle_connection(
 
         // Complete key exchange
         let peer_public = x25519_dalek::PublicKey::from(peer_pub_bytes);
-        Some(EncryptedChannel::complete(our_secret, &peer_public))
+        match &settings.psk {
+            Some(psk) => {
+                let channel =
+                    EncryptedChannel::complete_with_psk(our_secret, &peer_public, psk.as_bytes());
+                confirm_psk(&mut framed, &channel, &peer_device_name).await?;
+                audit.event(AuditKind::Trusted).reason("pre-shared key").record();
+                Some(channel)
+            }
+            None => Some(EncryptedChannel::complete(our_secret, &peer_public)),
+        }
     } else {
         // Not encrypting -- reject if sender expected encryption to prevent silent downgrade.
         // A MITM could strip the sender's key, but we cannot detect that here.
@@ -318,6 +749,9 @@ async fn handle_connection(
                 reason: Some(
                     "Receiver was started with --no-encrypt. Remove --no-encrypt to enable encryption.".into(),
                 ),
+                max_chunk_size: None,
+                chunk_index: false,
+                zero_copy: false,
             };
             framed
                 .send(Bytes::from(encode_message(&reject)?))
@@ -331,6 +765,9 @@ async fn handle_connection(
             accepted: true,
             public_key: None,
             reason: None,
+            max_chunk_size: None,
+            chunk_index: chunk_index.is_some(),
+            zero_copy: true,
         };
         framed
             .send(Bytes::from(encode_message(&ack)?))
@@ -339,7 +776,11 @@ async fn handle_connection(
         None
     };
 
-    // --- Read FileHeader ---
+    let shared_dir = PathBuf::from(expand_variables(&settings.output));
+    let output_dir =
+        settings.sender_dir(&shared_dir, verified.then_some(peer_device_name.as_str()));
+
+    // --- Read FileHeader (or ResumeRequest from a reconnecting sender) ---
     let fh_bytes = framed
         .next()
         .await
@@ -347,25 +788,82 @@ async fn handle_connection(
         .map_err(|e| FluxError::TransferError(format!("Failed to read file header: {}", e)))?;
 
     let file_header = decode_message(&fh_bytes)?;
-    let (filename, file_size, _encrypted, expected_checksum) = match file_header {
+    let (filename, file_size, mut expected_checksum, footer, resuming) = match file_header {
         FluxMessage::FileHeader {
             filename,
             size,
-            encrypted,
             checksum,
-        } => (filename, size, encrypted, checksum),
+            footer,
+            ..
+        } => (filename, size, checksum.filter(|_| !footer), footer, false),
+        FluxMessage::ResumeRequest {
+            filename,
+            size,
+            checksum,
+            ..
+        } => {
+            let footer = checksum == footer_key(&filename);
+            (filename, size, (!footer).then_some(checksum), footer, true)
+        }
         FluxMessage::Error { message } => {
             return Err(FluxError::TransferError(format!(
                 "Sender error: {}",
                 message
             )));
         }
+        request @ (FluxMessage::StatPath { .. }
+        | FluxMessage::ListTree { .. }
+        | FluxMessage::PutFile { .. }
+        | FluxMessage::GetFile { .. }
+        | FluxMessage::DeletePath { .. }) => {
+            let session = TreeSession {
+                root: &output_dir,
+                shared: &shared_dir,
+                settings: &settings,
+                peer: &peer_device_name,
+                channel: channel.as_ref(),
+                audit,
+            };
+            session.serve(&mut framed, request).await?;
+            return Ok(None);
+        }
+        FluxMessage::KeyConfirm { .. } => {
+            let reject = FluxMessage::Error {
+                message: "This receiver does not use a pre-shared key".into(),
+            };
+            framed
+                .send(Bytes::from(encode_message(&reject)?))
+                .await
+                .ok();
+            return Err(FluxError::TrustError(format!(
+                "'{}' sent a pre-shared key proof, but no --psk-file was given",
+                peer_device_name
+            )));
+        }
         _ => {
             return Err(FluxError::TransferError(
                 "Expected FileHeader message".into(),
             ));
         }
     };
+    audit.file = Some(filename.clone());
+    audit.size = Some(file_size);
+
+    // Non-BLAKE3 checksums name their algorithm ("xxh3:<hex>")
+    let algorithm = match expected_checksum.as_deref().map(ChecksumAlgorithm::of_tagged) {
+        Some(Ok(algorithm)) => algorithm,
+        Some(Err(e)) => {
+            let reject = FluxMessage::Error {
+                message: e.to_string(),
+            };
+            framed
+                .send(Bytes::from(encode_message(&reject)?))
+                .await
+                .ok();
+            return Err(e);
+        }
+        None => ChecksumAlgorithm::Blake3,
+    };
 
     // Validate file size to prevent memory exhaustion from malicious senders
     if file_size > MAX_RECEIVE_SIZE {
@@ -396,165 +894,191 @@ async fn handle_connection(
         );
     }
 
-    // Create output file with auto-rename if it exists (filename is sanitized inside)
-    let output_path = find_unique_path(&output_dir, &filename);
-    let display_name = output_path
-        .file_name()
-        .map(|n| n.to_string_lossy().to_string())
-        .unwrap_or_else(|| filename.clone());
-
-    // Progress bar
-    let pb = indicatif::ProgressBar::new(file_size);
-    pb.set_style(
-        indicatif::ProgressStyle::with_template(
-            "{spinner:.green} [{bar:40.cyan/blue}] {bytes}/{total_bytes} ({bytes_per_sec})",
-        )
-        .expect("static progress template is valid")
-        .progress_chars("#>-"),
-    );
-    pb.set_draw_target(indicatif::ProgressDrawTarget::stderr());
+    // Partial files are kept by checksum, or by name until a footer has it
+    let resume_key = match footer {
+        true => Some(footer_key(&filename)),
+        false => expected_checksum.clone(),
+    };
 
-    // --- Receive DataChunks: stream directly to disk ---
-    let mut received_bytes: u64 = 0;
-    let mut expected_offset: u64 = 0;
-    let mut hasher = blake3::Hasher::new();
+    // A reconnecting sender continues its partial file if we still have it
+    let partial = match (resuming, &resume_key) {
+        (true, Some(key)) => pending.take(&peer_device_name, key, file_size).await,
+        _ => None,
+    };
+    let mut incoming = match partial.map(IncomingFile::resume) {
+        Some(Ok(incoming)) => incoming,
+        Some(Err(e)) => {
+            tracing::warn!("Cannot resume '{}', starting over: {}", filename, e);
+            IncomingFile::create(&output_dir, &filename, file_size, algorithm)?
+        }
+        None => IncomingFile::create(&output_dir, &filename, file_size, algorithm)?,
+    };
 
-    // Open output file exclusively (atomic create, prevents TOCTOU/symlink)
-    let mut out_file = std::fs::OpenOptions::new()
-        .write(true)
-        .create_new(true)
-        .open(&output_path)
-        .map_err(|e| {
-            FluxError::TransferError(format!(
-                "Failed to create file '{}': {}",
-                output_path.display(), e
-            ))
-        })?;
+    // Check free space and enforce the daily and storage quotas; on refusal,
+    // dropping `incoming` removes its (partial) temp file. Storage quotas
+    // prune last, so nothing is deleted for a file refused anyway
+    let resumed_from = incoming.received;
+    let own_dir = (output_dir != shared_dir).then_some(output_dir.as_path());
+    let reserved = incoming
+        .check_space(&settings.space)
+        .and_then(|()| settings.quota.reserve(&peer_device_name, file_size - resumed_from))
+        .and_then(|reservation| {
+            let stored = settings.storage.reserve(
+                &shared_dir,
+                own_dir,
+                &peer_device_name,
+                file_size - resumed_from,
+            )?;
+            Ok((reservation, stored))
+        });
+    let (reservation, _stored) = match reserved {
+        Ok(reserved) => reserved,
+        Err(e) => {
+            eprintln!("Refusing {} from {}: {}", filename, peer_device_name, e);
+            let reject = FluxMessage::Error {
+                message: e.to_string(),
+            };
+            framed
+                .send(Bytes::from(encode_message(&reject)?))
+                .await
+                .ok();
+            return Err(e);
+        }
+    };
+    let accepted = audit.event(AuditKind::Accepted);
+    if resumed_from > 0 {
+        accepted.reason(format!("resumed at {} bytes", resumed_from)).record();
+    } else {
+        accepted.record();
+    }
+    if settings.space.preallocate {
+        incoming.preallocate();
+    }
 
-    while received_bytes < file_size {
-        let chunk_bytes = framed
-            .next()
+    if resuming {
+        let ack = FluxMessage::ResumeAck {
+            offset: incoming.received,
+        };
+        framed
+            .send(Bytes::from(encode_message(&ack)?))
             .await
-            .ok_or_else(|| {
-                FluxError::TransferError("Connection closed during data transfer".into())
-            })?
-            .map_err(|e| {
-                FluxError::TransferError(format!("Failed to read data chunk: {}", e))
-            })?;
-
-        let chunk = decode_message(&chunk_bytes)?;
-        match chunk {
-            FluxMessage::DataChunk { offset, data, nonce } => {
-                // Validate chunk offset matches expected sequential position
-                if offset != expected_offset {
-                    pb.finish_and_clear();
-                    drop(out_file);
-                    let _ = std::fs::remove_file(&output_path);
-                    return Err(FluxError::TransferError(format!(
-                        "Unexpected chunk offset: expected {}, got {}",
-                        expected_offset, offset
-                    )));
-                }
-
-                let plaintext = if let Some(ref ch) = channel {
-                    let nonce_bytes: [u8; 24] = nonce
-                        .ok_or_else(|| {
-                            FluxError::EncryptionError(
-                                "Encrypted chunk missing nonce".into(),
-                            )
-                        })?
-                        .try_into()
-                        .map_err(|_| {
-                            FluxError::EncryptionError("Nonce must be 24 bytes".into())
-                        })?;
-                    ch.decrypt(&data, &nonce_bytes)?
-                } else {
-                    data
-                };
-
-                let chunk_len = plaintext.len() as u64;
-
-                // Prevent data overflow: reject if sender sends more than declared size
-                if received_bytes + chunk_len > file_size {
-                    pb.finish_and_clear();
-                    drop(out_file);
-                    let _ = std::fs::remove_file(&output_path);
-                    return Err(FluxError::TransferError(format!(
-                        "Data overflow: received {} + chunk {} exceeds declared size {}",
-                        received_bytes, chunk_len, file_size
-                    )));
-                }
+            .map_err(|e| FluxError::TransferError(format!("Failed to send resume ack: {}", e)))?;
+        eprintln!(
+            "Resuming {} from {} at {}",
+            filename,
+            peer_device_name,
+            bytesize::ByteSize(incoming.received)
+        );
+    }
+    let display_name = incoming.display_name(&filename);
 
-                // Stream to disk + incremental hash (no full-file buffering)
-                {
-                    use std::io::Write;
-                    out_file.write_all(&plaintext).map_err(|e| {
-                        FluxError::TransferError(format!(
-                            "Failed to write chunk to '{}': {}",
-                            output_path.display(), e
-                        ))
-                    })?;
-                }
-                hasher.update(&plaintext);
+    // Register the transfer so a sender that reconnects before we notice the
+    // drop can take it over
+    let active = resume_key
+        .as_ref()
+        .map(|key| pending.begin(&peer_device_name, key));
 
-                received_bytes += chunk_len;
-                expected_offset += chunk_len;
-                pb.set_position(received_bytes);
+    // --- Receive DataChunks: stream directly to disk ---
+    // Adaptive probes go to the sender's address on the default Flux port;
+    // a sender without a receiver of its own answers with a refusal. The
+    // address keeps its IPv6 zone, if any. `max_receive_rate` is shared
+    // with every other connection
+    let limiter = settings.limit.start_shared(
+        peer_addr.map(|mut addr| {
+            addr.set_port(DEFAULT_PORT);
+            addr
+        }),
+        settings.admission.rate(),
+    );
+    // A fresh transfer of a large file may come with a chunk list
+    let mut chunks = match chunk_index {
+        Some(index) if !resuming => Some(IncomingChunks::new(index, &peer_device_name, file_size)),
+        _ => None,
+    };
+    let pb = receive_progress(file_size, incoming.received);
+    let _status = incoming.publish_status(&peer_device_name, &pb);
+    let _stall = StallNotice::watch(&pb);
+    let inbound = Inbound {
+        channel: channel.as_ref(),
+        low_memory: false,
+        limiter: limiter.as_ref(),
+    };
+    let received = receive_chunks(
+        &mut framed,
+        &mut incoming,
+        inbound,
+        &pb,
+        active.as_ref(),
+        chunks.as_mut(),
+    )
+    .await;
+    let received = match received {
+        Ok(()) if footer => receive_footer(&mut framed, active.as_ref()).await.map(Some),
+        received => received.map(|()| None),
+    };
+    reservation.settle(incoming.received - resumed_from);
+    match received {
+        Ok(footer_checksum) => {
+            pb.finish_and_clear();
+            if footer_checksum.is_some() {
+                expected_checksum = footer_checksum;
             }
-            FluxMessage::Error { message } => {
-                pb.finish_and_clear();
-                drop(out_file);
-                let _ = std::fs::remove_file(&output_path);
+        }
+        Err(AttemptError::Disconnected(e)) => {
+            pb.finish_and_clear();
+            if let (Some(key), Some(active)) = (&resume_key, &active) {
+                active
+                    .pending()
+                    .park(incoming.park(&peer_device_name, key));
                 return Err(FluxError::TransferError(format!(
-                    "Sender error during transfer: {}",
-                    message
+                    "{}; keeping the partial file for {}s so the sender can resume",
+                    e,
+                    RECONNECT_GRACE.as_secs()
                 )));
             }
-            _ => {
-                pb.finish_and_clear();
-                drop(out_file);
-                let _ = std::fs::remove_file(&output_path);
-                return Err(FluxError::TransferError(
-                    "Unexpected message during data transfer".into(),
-                ));
-            }
+            return Err(e);
+        }
+        Err(AttemptError::Fatal(e)) => {
+            pb.finish_and_clear();
+            return Err(e);
         }
     }
+    drop(active);
 
-    pb.finish_and_clear();
-    drop(out_file);
-
-    // --- Verify BLAKE3 checksum (computed incrementally during receive) ---
-    let checksum_verified = if let Some(ref expected) = expected_checksum {
-        let actual = hasher.finalize().to_hex().to_string();
-        if actual != *expected {
-            // Checksum mismatch — delete the corrupted file
-            let _ = std::fs::remove_file(&output_path);
-            let reject = FluxMessage::Error {
-                message: format!(
-                    "Checksum mismatch: expected {}, got {}",
-                    expected, actual
-                ),
-            };
-            framed
-                .send(Bytes::from(encode_message(&reject)?))
-                .await
-                .ok();
-            return Err(FluxError::TransferError(format!(
-                "BLAKE3 checksum mismatch for '{}': file may be corrupted or tampered",
-                filename
-            )));
-        }
-        Some(true)
-    } else {
-        None
-    };
+    // --- Verify the checksum (computed incrementally during receive) ---
+    let received_bytes = incoming.received;
+    if let Err(actual) = incoming.verify(expected_checksum.as_deref()) {
+        // Checksum mismatch — dropping `incoming` deletes the corrupted temp file
+        let reject = FluxMessage::Error {
+            message: format!(
+                "Checksum mismatch: expected {}, got {}",
+                expected_checksum.as_deref().unwrap_or_default(),
+                actual
+            ),
+        };
+        framed
+            .send(Bytes::from(encode_message(&reject)?))
+            .await
+            .ok();
+        return Err(FluxError::TransferError(format!(
+            "{} checksum mismatch for '{}': file may be corrupted or tampered",
+            algorithm.label(),
+            filename
+        )));
+    }
+    let checksum_verified = expected_checksum.as_ref().map(|_| true);
+    let checksum = incoming.checksum();
+    let output_path = incoming.commit()?;
+    if let Some(chunks) = chunks {
+        chunks.record(&output_path);
+    }
 
-    // --- Send TransferComplete ---
+    // --- Send TransferComplete, with our checksum for the sender to compare ---
     let complete = FluxMessage::TransferComplete {
         filename: display_name.clone(),
         bytes_received: received_bytes,
         checksum_verified,
+        checksum: Some(checksum),
     };
     framed
         .send(Bytes::from(encode_message(&complete)?))
@@ -570,53 +1094,412 @@ async fn handle_connection(
         stats.print_file_summary(&display_name, false);
     }
 
-    Ok(())
+    let receipt = sign_requested_receipt(
+        &mut framed,
+        &config_dir,
+        &device_name,
+        &filename,
+        received_bytes,
+        expected_checksum.as_deref(),
+        checksum_verified,
+    )
+    .await;
+
+    Ok(Some(ReceiveReport {
+        output_path,
+        bytes: received_bytes,
+        peer: peer_device_name,
+        checksum_verified,
+        receipt,
+    }))
 }
 
-/// Receive a file using code-phrase mode (Croc-like UX).
-///
-/// The receiver is a TCP client:
-/// 1. Validate code phrase, compute code_hash
-/// 2. Discover sender via mDNS code_hash match
-/// 3. TCP connect to discovered sender
-/// 4. Receive Handshake, generate ephemeral keypair, send HandshakeAck
-/// 5. Receive FileHeader + encrypted DataChunks
-/// 6. Verify BLAKE3 checksum, write file
-/// 7. Send TransferComplete
-pub async fn receive_with_code(
-    code: &str,
-    output_dir: &Path,
-    _device_name: &str,
+/// Check the sender's proof of the pre-shared key and answer with ours.
+/// Senders without the key (including ones that send a FileHeader right
+/// away) are refused before any data is written.
+async fn confirm_psk(
+    framed: &mut Framed<TcpStream, LengthDelimitedCodec>,
+    channel: &EncryptedChannel,
+    peer_device_name: &str,
 ) -> Result<(), FluxError> {
-    use crate::discovery::mdns::discover_by_code_hash;
-    use crate::net::codephrase;
-
-    let started = std::time::Instant::now();
-
-    // Validate code phrase
-    codephrase::validate(code).map_err(FluxError::TransferError)?;
+    let bytes = framed
+        .next()
+        .await
+        .ok_or_else(|| {
+            FluxError::TransferError("Connection closed before key confirmation".into())
+        })?
+        .map_err(|e| FluxError::TransferError(format!("Failed to read key confirmation: {}", e)))?;
+    let proven = match decode_message(&bytes)? {
+        FluxMessage::KeyConfirm { proof, nonce } => {
+            psk::check_proof(channel, PskRole::Sender, &proof, &nonce)
+        }
+        _ => false,
+    };
+    if !proven {
+        eprintln!("Refusing {}: it does not hold the pre-shared key", peer_device_name);
+        let reject = FluxMessage::Error {
+            message: "Pre-shared key does not match".into(),
+        };
+        framed
+            .send(Bytes::from(encode_message(&reject)?))
+            .await
+            .ok();
+        return Err(FluxError::TrustError(format!(
+            "'{}' does not hold the pre-shared key",
+            peer_device_name
+        )));
+    }
+
+    let (proof, nonce) = psk::make_proof(channel, PskRole::Receiver)?;
+    let confirm = FluxMessage::KeyConfirm {
+        proof,
+        nonce: nonce.to_vec(),
+    };
+    framed
+        .send(Bytes::from(encode_message(&confirm)?))
+        .await
+        .map_err(|e| FluxError::TransferError(format!("Failed to send key confirmation: {}", e)))?;
+    eprintln!("Verified: {} (pre-shared key)", peer_device_name);
+    Ok(())
+}
+
+/// Refuse a sender with a `HandshakeAck { accepted: false }`.
+async fn reject_handshake(
+    framed: &mut Framed<TcpStream, LengthDelimitedCodec>,
+    reason: &str,
+) -> Result<(), FluxError> {
+    let reject = FluxMessage::HandshakeAck {
+        accepted: false,
+        public_key: None,
+        reason: Some(reason.to_string()),
+        max_chunk_size: None,
+        chunk_index: false,
+        zero_copy: false,
+    };
+    framed
+        .send(Bytes::from(encode_message(&reject)?))
+        .await
+        .ok();
+    Ok(())
+}
+
+/// Receive a file using code-phrase mode (Croc-like UX).
+///
+/// The receiver is a TCP client:
+/// 1. Validate code phrase, compute code_hash
+/// 2. Discover sender via mDNS code_hash match
+/// 3. TCP connect to discovered sender
+/// 4. Receive Handshake, generate ephemeral keypair, send HandshakeAck
+/// 5. Receive FileHeader + encrypted DataChunks
+/// 6. Verify the checksum, write file
+/// 7. Send TransferComplete
+/// 8. Countersign a delivery receipt if the sender asks for one
+///
+/// If the connection drops mid-transfer (e.g. either side changed networks),
+/// the sender keeps listening for `RECONNECT_GRACE`; the receiver looks up
+/// the code again, reconnects and answers the sender's `ResumeRequest` with
+/// the bytes it already has.
+///
+/// With `low_memory` set, the HandshakeAck requests `LOW_MEMORY_CHUNK_SIZE`
+/// chunks, each chunk is decrypted in place, and written data is flushed to
+/// disk every `LOW_MEMORY_FLUSH_INTERVAL` bytes.
+///
+/// The file counts against the daily receive `quota` and is checked against
+/// the free `space` like a direct transfer.
+pub async fn receive_with_code(
+    code: &str,
+    output_dir: &Path,
+    device_name: &str,
+    low_memory: bool,
+    quota: &ReceiveQuota,
+    space: SpacePolicy,
+    limit: RateLimit,
+) -> Result<ReceiveReport, FluxError> {
+    use crate::net::codephrase;
+
+    let started = std::time::Instant::now();
+
+    // Validate code phrase
+    codephrase::validate(code).map_err(FluxError::TransferError)?;
 
     // Compute code hash for mDNS matching
     let hash = codephrase::code_hash(code);
 
     eprintln!("Looking for sender...");
 
-    // Discover sender by code hash (30s timeout)
-    let device = discover_by_code_hash(&hash, 30)?
+    let (mut framed, mut channel, peer_device_name) =
+        connect_with_code(&hash, code, low_memory, 30).await?;
+
+    // Receive FileHeader
+    let fh_bytes = framed
+        .next()
+        .await
+        .ok_or_else(|| FluxError::TransferError("Connection closed before file header".into()))?
+        .map_err(|e| FluxError::TransferError(format!("Failed to read file header: {}", e)))?;
+
+    let file_header = decode_message(&fh_bytes)?;
+    let (filename, file_size, expected_checksum) = match file_header {
+        FluxMessage::FileHeader {
+            filename,
+            size,
+            checksum,
+            ..
+        } => (filename, size, checksum),
+        FluxMessage::Error { message } => {
+            return Err(FluxError::TransferError(format!(
+                "Sender error: {}",
+                message
+            )));
+        }
+        _ => {
+            return Err(FluxError::TransferError(
+                "Expected FileHeader message".into(),
+            ));
+        }
+    };
+
+    // Non-BLAKE3 checksums name their algorithm ("xxh3:<hex>")
+    let algorithm = match expected_checksum.as_deref().map(ChecksumAlgorithm::of_tagged) {
+        Some(Ok(algorithm)) => algorithm,
+        Some(Err(e)) => {
+            let reject = FluxMessage::Error {
+                message: e.to_string(),
+            };
+            framed
+                .send(Bytes::from(encode_message(&reject)?))
+                .await
+                .ok();
+            return Err(e);
+        }
+        None => ChecksumAlgorithm::Blake3,
+    };
+
+    // Validate file size
+    if file_size > MAX_RECEIVE_SIZE {
+        let reject = FluxMessage::Error {
+            message: format!(
+                "File too large: {} bytes exceeds maximum {} bytes",
+                file_size, MAX_RECEIVE_SIZE
+            ),
+        };
+        framed
+            .send(Bytes::from(encode_message(&reject)?))
+            .await
+            .ok();
+        return Err(FluxError::TransferError(format!(
+            "Rejected file '{}': size {} exceeds maximum {}",
+            filename, file_size, MAX_RECEIVE_SIZE
+        )));
+    }
+
+    // Warn when the declared size is unusually large (>2 GB).
+    if file_size > 2 * 1024 * 1024 * 1024 {
+        tracing::info!(
+            file = %filename,
+            size_bytes = file_size,
+            "Large incoming transfer declared ({} bytes); this will take significant time and disk space",
+            file_size,
+        );
+    }
+
+    let human_size = bytesize::ByteSize(file_size).to_string();
+    eprintln!(
+        "Receiving {} ({}) from {}",
+        filename, human_size, peer_device_name
+    );
+
+    // Check free space and enforce the daily receive quotas before creating
+    // the output file
+    let peer = sanitize_peer_device_name(&peer_device_name);
+    let reserved = space
+        .check(output_dir, file_size)
+        .and_then(|()| quota.reserve(&peer, file_size));
+    let reservation = match reserved {
+        Ok(reservation) => reservation,
+        Err(e) => {
+            let reject = FluxMessage::Error {
+                message: e.to_string(),
+            };
+            framed
+                .send(Bytes::from(encode_message(&reject)?))
+                .await
+                .ok();
+            return Err(e);
+        }
+    };
+
+    // Prepare output path
+    let mut incoming = IncomingFile::create(output_dir, &filename, file_size, algorithm)?;
+    if space.preallocate {
+        incoming.preallocate();
+    }
+    let display_name = incoming.display_name(&filename);
+
+    // --- Receive DataChunks, reconnecting if the connection drops ---
+    let pb = receive_progress(file_size, 0);
+    let _status = incoming.publish_status(&peer_device_name, &pb);
+    let _stall = StallNotice::watch(&pb);
+    let mut window = ReconnectWindow::default();
+    // The sender listens only for this transfer, so no latency probes
+    let limiter = limit.start(None);
+    loop {
+        let inbound = Inbound {
+            channel: Some(&channel),
+            low_memory,
+            limiter: limiter.as_ref(),
+        };
+        let attempt = receive_chunks(&mut framed, &mut incoming, inbound, &pb, None, None).await;
+        let dropped = match (attempt, &expected_checksum) {
+            (Ok(()), _) => break,
+            (Err(AttemptError::Disconnected(e)), Some(_)) => e,
+            (Err(e), _) => {
+                pb.finish_and_clear();
+                return Err(e.into_inner());
+            }
+        };
+        let checksum = expected_checksum.as_deref().unwrap_or_default();
+
+        pb.suspend(|| {
+            eprintln!(
+                "Connection lost ({}), looking for the sender again...",
+                dropped
+            )
+        });
+        (framed, channel) = loop {
+            if cancel::requested() {
+                pb.finish_and_clear();
+                return Err(FluxError::Cancelled);
+            }
+            if !window.allow_retry(incoming.received, RECONNECT_GRACE) {
+                pb.finish_and_clear();
+                return Err(FluxError::TransferError(format!(
+                    "{} (sender did not come back within {}s)",
+                    dropped,
+                    RECONNECT_GRACE.as_secs()
+                )));
+            }
+            tokio::time::sleep(RECONNECT_DELAY).await;
+            match reconnect_with_code(&hash, code, low_memory, &incoming, checksum).await {
+                Ok(connection) => break connection,
+                Err(AttemptError::Disconnected(e)) => tracing::debug!("Reconnect failed: {}", e),
+                Err(AttemptError::Fatal(e)) => {
+                    pb.finish_and_clear();
+                    return Err(e);
+                }
+            }
+        };
+        pb.suspend(|| {
+            eprintln!(
+                "Reconnected, resuming at {}",
+                bytesize::ByteSize(incoming.received)
+            )
+        });
+    }
+
+    pb.finish_and_clear();
+    reservation.settle(incoming.received);
+
+    // --- Verify the checksum (computed incrementally during receive) ---
+    let received_bytes = incoming.received;
+    if let Err(actual) = incoming.verify(expected_checksum.as_deref()) {
+        // Checksum mismatch — dropping `incoming` deletes the corrupted temp file
+        let reject = FluxMessage::Error {
+            message: format!(
+                "Checksum mismatch: expected {}, got {}",
+                expected_checksum.as_deref().unwrap_or_default(),
+                actual
+            ),
+        };
+        framed
+            .send(Bytes::from(encode_message(&reject)?))
+            .await
+            .ok();
+        return Err(FluxError::TransferError(format!(
+            "{} checksum mismatch for '{}': file may be corrupted or tampered",
+            algorithm.label(),
+            filename
+        )));
+    }
+    let checksum_verified = expected_checksum.as_ref().map(|_| true);
+    let checksum = incoming.checksum();
+    let output_path = incoming.commit()?;
+
+    // Send TransferComplete, with our checksum for the sender to compare
+    let complete = FluxMessage::TransferComplete {
+        filename: display_name.clone(),
+        bytes_received: received_bytes,
+        checksum_verified,
+        checksum: Some(checksum),
+    };
+    framed
+        .send(Bytes::from(encode_message(&complete)?))
+        .await
+        .map_err(|e| {
+            FluxError::TransferError(format!("Failed to send transfer complete: {}", e))
+        })?;
+
+    {
+        let mut stats = TransferStats::new(1, file_size);
+        stats.started = started;
+        stats.add_done(received_bytes);
+        stats.print_file_summary(&display_name, false);
+    }
+
+    let receipt = match flux_config_dir() {
+        Ok(config_dir) => {
+            sign_requested_receipt(
+                &mut framed,
+                &config_dir,
+                device_name,
+                &filename,
+                received_bytes,
+                expected_checksum.as_deref(),
+                checksum_verified,
+            )
+            .await
+        }
+        Err(e) => {
+            tracing::debug!("Not waiting for a receipt request: {}", e);
+            None
+        }
+    };
+
+    Ok(ReceiveReport {
+        output_path,
+        bytes: received_bytes,
+        peer,
+        checksum_verified,
+        receipt,
+    })
+}
+
+/// Find the code-phrase sender via mDNS, connect and complete the encrypted
+/// handshake. Returns the connection, the session channel and the sender's
+/// device name.
+async fn connect_with_code(
+    hash: &str,
+    code: &str,
+    low_memory: bool,
+    discover_secs: u64,
+) -> Result<(Framed<TcpStream, LengthDelimitedCodec>, EncryptedChannel, String), FluxError> {
+    use crate::discovery::mdns::discover_by_code_hash;
+
+    // Discover sender by code hash
+    let device = discover_by_code_hash(hash, discover_secs)?
         .ok_or_else(|| {
             FluxError::TransferError(
                 "Could not find sender on the network. Make sure the sender is running and you're on the same LAN.".into(),
             )
         })?;
 
-    tracing::debug!("Found sender at {}:{}", device.host, device.port);
+    tracing::debug!("Found sender at {}", addr::join_host_port(&device.host, device.port));
 
     // TCP connect to sender
-    let stream = tokio::net::TcpStream::connect(format!("{}:{}", device.host, device.port))
+    let stream = crate::net::socks::connect(&device.host, device.port)
         .await
         .map_err(|e| FluxError::ConnectionFailed {
             protocol: "flux".to_string(),
-            host: format!("{}:{}", device.host, device.port),
+            host: addr::join_host_port(&device.host, device.port),
             reason: e.to_string(),
         })?;
 
@@ -667,11 +1550,15 @@ pub async fn receive_with_code(
     let (our_secret, our_public) = EncryptedChannel::initiate();
     let our_pub_bytes = our_public.as_bytes().to_vec();
 
-    // Send HandshakeAck with our public key
+    // Send HandshakeAck with our public key (and a smaller chunk size in
+    // low-memory mode)
     let ack = FluxMessage::HandshakeAck {
         accepted: true,
         public_key: Some(our_pub_bytes),
         reason: None,
+        max_chunk_size: low_memory.then_some(LOW_MEMORY_CHUNK_SIZE as u32),
+        chunk_index: false,
+        zero_copy: false,
     };
     framed
         .send(Bytes::from(encode_message(&ack)?))
@@ -685,247 +1572,575 @@ pub async fn receive_with_code(
     let peer_public = x25519_dalek::PublicKey::from(peer_pub_bytes);
     let channel = EncryptedChannel::complete_with_code(our_secret, &peer_public, code);
 
-    // Receive FileHeader
-    let fh_bytes = framed
-        .next()
+    Ok((framed, channel, peer_device_name))
+}
+
+/// Reconnect to a code-phrase sender after a dropped connection and agree to
+/// resume at the bytes already received.
+async fn reconnect_with_code(
+    hash: &str,
+    code: &str,
+    low_memory: bool,
+    incoming: &IncomingFile,
+    checksum: &str,
+) -> Result<(Framed<TcpStream, LengthDelimitedCodec>, EncryptedChannel), AttemptError> {
+    let (mut framed, channel, _) = connect_with_code(hash, code, low_memory, 5)
         .await
-        .ok_or_else(|| FluxError::TransferError("Connection closed before file header".into()))?
-        .map_err(|e| FluxError::TransferError(format!("Failed to read file header: {}", e)))?;
+        .map_err(AttemptError::Disconnected)?;
 
-    let file_header = decode_message(&fh_bytes)?;
-    let (filename, file_size, expected_checksum) = match file_header {
-        FluxMessage::FileHeader {
-            filename,
+    let request = match framed.next().await {
+        Some(Ok(bytes)) => decode_message(&bytes)?,
+        _ => {
+            return Err(AttemptError::Disconnected(FluxError::TransferError(
+                "Connection closed before resume request".into(),
+            )))
+        }
+    };
+    match request {
+        FluxMessage::ResumeRequest {
             size,
-            checksum,
+            checksum: ref requested,
             ..
-        } => (filename, size, checksum),
+        } if size == incoming.size && requested == checksum => {}
         FluxMessage::Error { message } => {
-            return Err(FluxError::TransferError(format!(
-                "Sender error: {}",
-                message
-            )));
+            return Err(FluxError::TransferError(format!("Sender error: {}", message)).into())
         }
         _ => {
             return Err(FluxError::TransferError(
-                "Expected FileHeader message".into(),
-            ));
+                "Sender did not resume the interrupted transfer".into(),
+            )
+            .into())
         }
+    }
+
+    let ack = FluxMessage::ResumeAck {
+        offset: incoming.received,
     };
+    framed
+        .send(Bytes::from(encode_message(&ack)?))
+        .await
+        .map_err(|e| {
+            AttemptError::Disconnected(FluxError::TransferError(format!(
+                "Failed to send resume ack: {}",
+                e
+            )))
+        })?;
+    Ok((framed, channel))
+}
 
-    // Validate file size
-    if file_size > MAX_RECEIVE_SIZE {
-        let reject = FluxMessage::Error {
-            message: format!(
-                "File too large: {} bytes exceeds maximum {} bytes",
-                file_size, MAX_RECEIVE_SIZE
-            ),
-        };
-        framed
-            .send(Bytes::from(encode_message(&reject)?))
-            .await
-            .ok();
-        return Err(FluxError::TransferError(format!(
-            "Rejected file '{}': size {} exceeds maximum {}",
-            filename, file_size, MAX_RECEIVE_SIZE
-        )));
+/// A file being received into its atomic temp file.
+pub(crate) struct IncomingFile {
+    // Field order matters: the file is closed before the guard removes the
+    // temp file on drop (required on Windows).
+    file: std::fs::File,
+    atomic: AtomicFile,
+    output_path: PathBuf,
+    size: u64,
+    /// Bytes written (and hashed) so far
+    received: u64,
+    hasher: Box<dyn ChecksumHasher>,
+    /// Bytes written since the last low-memory flush
+    unflushed: u64,
+}
+
+impl IncomingFile {
+    /// Start a new file in `output_dir` (auto-renamed if the name is taken),
+    /// hashed with `algorithm`.
+    pub(crate) fn create(
+        output_dir: &Path,
+        filename: &str,
+        size: u64,
+        algorithm: ChecksumAlgorithm,
+    ) -> Result<Self, FluxError> {
+        if !output_dir.exists() {
+            std::fs::create_dir_all(output_dir)?;
+        }
+        let output_path = find_unique_path(output_dir, filename);
+        Self::at(output_path, size, algorithm)
     }
 
-    // Warn when the declared size is unusually large (>2 GB).
-    if file_size > 2 * 1024 * 1024 * 1024 {
-        tracing::info!(
-            file = %filename,
-            size_bytes = file_size,
-            "Large incoming transfer declared ({} bytes); this will take significant time and disk space",
-            file_size,
-        );
+    /// Start a new file that replaces whatever is at `output_path` once
+    /// committed (`flux push`, `flux pull`).
+    pub(crate) fn replace(
+        output_path: &Path,
+        size: u64,
+        algorithm: ChecksumAlgorithm,
+    ) -> Result<Self, FluxError> {
+        if let Some(parent) = output_path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        // Left behind by an interrupted mirror; it would block the new one
+        let _ = std::fs::remove_file(temp_path(output_path));
+        Self::at(output_path.to_path_buf(), size, algorithm)
     }
 
-    let human_size = bytesize::ByteSize(file_size).to_string();
-    eprintln!(
-        "Receiving {} ({}) from {}",
-        filename, human_size, peer_device_name
-    );
+    fn at(
+        output_path: PathBuf,
+        size: u64,
+        algorithm: ChecksumAlgorithm,
+    ) -> Result<Self, FluxError> {
+        // Data goes to a temp file that is renamed into place once verified;
+        // dropping the guard on any error path removes the partial file.
+        let atomic = AtomicFile::new(&output_path);
+        let file = create_temp_output(&atomic)?;
+        Ok(Self {
+            file,
+            atomic,
+            output_path,
+            size,
+            received: 0,
+            hasher: algorithm.hasher(),
+            unflushed: 0,
+        })
+    }
 
-    // Prepare output path
-    let output_path = find_unique_path(output_dir, &filename);
-    let display_name = output_path
-        .file_name()
-        .map(|n| n.to_string_lossy().to_string())
-        .unwrap_or_else(|| filename.clone());
-
-    // Progress bar
-    let pb = indicatif::ProgressBar::new(file_size);
-    pb.set_style(
-        indicatif::ProgressStyle::with_template(
-            "{spinner:.green} [{bar:40.cyan/blue}] {bytes}/{total_bytes} ({bytes_per_sec})",
-        )
-        .expect("static progress template is valid")
-        .progress_chars("#>-"),
-    );
-    pb.set_draw_target(indicatif::ProgressDrawTarget::stderr());
+    /// Continue a partial file left by an earlier connection.
+    fn resume(partial: PartialReceive) -> Result<Self, FluxError> {
+        // Guard first, so an unusable temp file is removed
+        let atomic = AtomicFile::new(&partial.output_path);
+        let file = reopen_partial(&partial.output_path, partial.received)?;
+        Ok(Self {
+            file,
+            atomic,
+            output_path: partial.output_path,
+            size: partial.size,
+            received: partial.received,
+            hasher: partial.hasher,
+            unflushed: 0,
+        })
+    }
 
-    // --- Receive DataChunks: stream directly to disk ---
-    let mut received_bytes: u64 = 0;
-    let mut expected_offset: u64 = 0;
-    let mut hasher = blake3::Hasher::new();
+    /// Check that the rest of the file fits on the output disk.
+    pub(crate) fn check_space(&self, space: &SpacePolicy) -> Result<(), FluxError> {
+        let dir = self.output_path.parent().unwrap_or(Path::new("."));
+        space.check(dir, self.size - self.received)
+    }
 
-    // Open output file exclusively (atomic create, prevents TOCTOU/symlink)
-    let mut out_file = std::fs::OpenOptions::new()
-        .write(true)
-        .create_new(true)
-        .open(&output_path)
-        .map_err(|e| {
+    /// Allocate the temp file at the full size of the file.
+    pub(crate) fn preallocate(&self) {
+        preallocate(&self.file, self.size, self.atomic.path());
+    }
+
+    /// Publish the transfer from `peer` for `flux status`, tracked by `pb`.
+    fn publish_status(&self, peer: &str, pb: &indicatif::ProgressBar) -> Option<StatusPublisher> {
+        let dest = self.output_path.display().to_string();
+        StatusPublisher::for_bar("receive", peer, &dest, pb)
+    }
+
+    pub(crate) fn display_name(&self, fallback: &str) -> String {
+        self.output_path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| fallback.to_string())
+    }
+
+    /// Append data to disk and to the running hash (no full-file buffering).
+    pub(crate) fn write(&mut self, data: &[u8], low_memory: bool) -> Result<(), FluxError> {
+        use std::io::Write;
+        self.file.write_all(data).map_err(|e| {
             FluxError::TransferError(format!(
-                "Failed to create file '{}': {}",
-                output_path.display(), e
+                "Failed to write chunk to '{}': {}",
+                self.output_path.display(),
+                e
             ))
         })?;
+        self.hasher.update(data);
+        self.received += data.len() as u64;
+
+        // Low-memory mode: push written data to disk periodically so
+        // dirty pages don't pile up in the page cache.
+        if low_memory {
+            self.unflushed += data.len() as u64;
+            if self.unflushed >= LOW_MEMORY_FLUSH_INTERVAL {
+                self.file.sync_data().map_err(|e| {
+                    FluxError::TransferError(format!(
+                        "Failed to flush '{}': {}",
+                        self.output_path.display(),
+                        e
+                    ))
+                })?;
+                self.unflushed = 0;
+            }
+        }
+        Ok(())
+    }
 
-    while received_bytes < file_size {
-        let chunk_bytes = framed
-            .next()
-            .await
-            .ok_or_else(|| {
-                FluxError::TransferError("Connection closed during data transfer".into())
-            })?
-            .map_err(|e| {
-                FluxError::TransferError(format!("Failed to read data chunk: {}", e))
-            })?;
+    /// Checksum of the data received so far, tagged like the header's.
+    pub(crate) fn checksum(&self) -> String {
+        self.hasher.algorithm().tag(&self.hasher.finish_hex())
+    }
 
-        let chunk = decode_message(&chunk_bytes)?;
-        match chunk {
-            FluxMessage::DataChunk { offset, data, nonce } => {
-                if offset != expected_offset {
-                    pb.finish_and_clear();
-                    drop(out_file);
-                    let _ = std::fs::remove_file(&output_path);
-                    return Err(FluxError::TransferError(format!(
-                        "Unexpected chunk offset: expected {}, got {}",
-                        expected_offset, offset
-                    )));
+    /// Check the checksum (as tagged in the header); on mismatch returns
+    /// the actual one.
+    pub(crate) fn verify(&self, expected: Option<&str>) -> Result<(), String> {
+        match expected {
+            Some(expected) => {
+                let actual = self.checksum();
+                if actual == expected {
+                    Ok(())
+                } else {
+                    Err(actual)
                 }
+            }
+            None => Ok(()),
+        }
+    }
 
-                let nonce_bytes: [u8; 24] = nonce
-                    .ok_or_else(|| {
-                        FluxError::EncryptionError("Encrypted chunk missing nonce".into())
-                    })?
-                    .try_into()
-                    .map_err(|_| {
-                        FluxError::EncryptionError("Nonce must be 24 bytes".into())
-                    })?;
-                let plaintext = channel.decrypt(&data, &nonce_bytes)?;
+    /// Move the finished file into place.
+    pub(crate) fn commit(self) -> Result<PathBuf, FluxError> {
+        let IncomingFile {
+            file,
+            atomic,
+            output_path,
+            ..
+        } = self;
+        drop(file);
+        atomic.commit()?;
+        Ok(output_path)
+    }
 
-                let chunk_len = plaintext.len() as u64;
+    /// Stop receiving but keep the temp file for the sender to resume.
+    fn park(self, peer: &str, checksum: &str) -> PartialReceive {
+        let IncomingFile {
+            file,
+            atomic,
+            output_path,
+            size,
+            received,
+            hasher,
+            ..
+        } = self;
+        drop(file);
+        drop(atomic.keep_on_failure(true));
+        PartialReceive::new(peer, checksum, size, output_path, received, hasher)
+    }
+}
 
-                if received_bytes + chunk_len > file_size {
-                    pb.finish_and_clear();
-                    drop(out_file);
-                    let _ = std::fs::remove_file(&output_path);
+/// Progress bar for an incoming file, starting at `position` when resuming.
+fn receive_progress(size: u64, position: u64) -> indicatif::ProgressBar {
+    let pb = create_network_progress(size);
+    pb.set_position(position);
+    pb
+}
+
+/// How file data comes in on a connection.
+#[derive(Clone, Copy)]
+pub(crate) struct Inbound<'a> {
+    /// Session encryption, when encrypted
+    pub channel: Option<&'a EncryptedChannel>,
+    /// Decrypt in place and flush written data regularly (`--low-memory`)
+    pub low_memory: bool,
+    /// Pacing for `--limit-down`/`--adaptive-limit`
+    pub limiter: Option<&'a ConnectionLimiter>,
+}
+
+/// Receive `DataChunk`s (or, unencrypted, `RawData`) until the file is
+/// complete.
+///
+/// A closed, failed or stalled connection, or the sender reconnecting on a
+/// new one (signalled through `active`), ends the attempt with
+/// `AttemptError::Disconnected` and leaves `incoming` ready to resume.
+/// A `Cancel` from the sender, or Ctrl+C here (which sends one), is fatal.
+///
+/// With a `limiter` in `inbound`, reading pauses after each chunk while over
+/// the limit; TCP flow control then slows the sender down.
+///
+/// With `chunks` (a fresh transfer to a receiver with a chunk index), the
+/// sender may list its chunks before any data and then refer to the ones
+/// this side has with `ChunkRef`s, which are copied from the earlier files.
+pub(crate) async fn receive_chunks(
+    framed: &mut Framed<TcpStream, LengthDelimitedCodec>,
+    incoming: &mut IncomingFile,
+    inbound: Inbound<'_>,
+    pb: &indicatif::ProgressBar,
+    active: Option<&ActiveGuard>,
+    mut chunks: Option<&mut IncomingChunks>,
+) -> Result<(), AttemptError> {
+    let Inbound {
+        channel,
+        low_memory,
+        limiter,
+    } = inbound;
+    let disconnected = |reason: String| AttemptError::Disconnected(FluxError::TransferError(reason));
+
+    while incoming.received < incoming.size {
+        let next = tokio::select! {
+            next = tokio::time::timeout(stall_timeout(), framed.next()) => Some(next),
+            _ = superseded(active) => {
+                return Err(disconnected("Sender reconnected on a new connection".into()));
+            }
+            _ = cancel::cancelled() => None,
+        };
+        let Some(next) = next else {
+            // Tell the sender; dropping `incoming` deletes the partial file
+            let cancel = FluxMessage::Cancel {
+                reason: CANCEL_REASON.to_string(),
+            };
+            let frame = Bytes::from(encode_message(&cancel)?);
+            let _ = tokio::time::timeout(CANCEL_TIMEOUT, framed.send(frame)).await;
+            return Err(FluxError::Cancelled.into());
+        };
+        let chunk_bytes = match next {
+            Err(_) => {
+                return Err(disconnected(format!(
+                    "No data from sender for {}s",
+                    stall_timeout().as_secs()
+                )))
+            }
+            Ok(None) => return Err(disconnected("Connection closed during data transfer".into())),
+            Ok(Some(Err(e))) => return Err(disconnected(format!("Failed to read data chunk: {}", e))),
+            Ok(Some(Ok(bytes))) => bytes,
+        };
+
+        let chunk = decode_message(&chunk_bytes)?;
+        // Release the raw frame before decrypting so only one copy of the
+        // chunk is alive at a time.
+        drop(chunk_bytes);
+        let (offset, plaintext, on_wire) = match (chunk, chunks.as_deref_mut()) {
+            (FluxMessage::DataChunk { offset, mut data, nonce }, _) => {
+                let plaintext = match channel {
+                    Some(ch) => {
+                        let nonce_bytes: [u8; 24] = nonce
+                            .ok_or_else(|| {
+                                FluxError::EncryptionError("Encrypted chunk missing nonce".into())
+                            })?
+                            .try_into()
+                            .map_err(|_| {
+                                FluxError::EncryptionError("Nonce must be 24 bytes".into())
+                            })?;
+                        if low_memory {
+                            ch.decrypt_in_place(&mut data, &nonce_bytes)?;
+                            data
+                        } else {
+                            ch.decrypt(&data, &nonce_bytes)?
+                        }
+                    }
+                    None => data,
+                };
+                (offset, plaintext, true)
+            }
+            (FluxMessage::RawData { offset, len }, _) if channel.is_none() => {
+                if len as usize > MAX_FRAME_SIZE {
                     return Err(FluxError::TransferError(format!(
-                        "Data overflow: received {} + chunk {} exceeds declared size {}",
-                        received_bytes, chunk_len, file_size
-                    )));
+                        "Raw chunk of {} bytes exceeds the frame limit",
+                        len
+                    ))
+                    .into());
                 }
-
-                // Stream to disk + incremental hash (no full-file buffering)
-                {
-                    use std::io::Write;
-                    out_file.write_all(&plaintext).map_err(|e| {
-                        FluxError::TransferError(format!(
-                            "Failed to write chunk to '{}': {}",
-                            output_path.display(), e
-                        ))
-                    })?;
+                let raw = zerocopy::read_raw(framed, len as usize);
+                let data = match tokio::time::timeout(stall_timeout(), raw).await {
+                    Ok(Ok(data)) => data,
+                    Ok(Err(e)) => return Err(disconnected(format!("Failed to read data: {}", e))),
+                    Err(_) => return Err(disconnected("Timed out reading raw data".into())),
+                };
+                (offset, data, true)
+            }
+            (FluxMessage::ChunkRef { offset, index }, Some(chunks)) => {
+                (offset, chunks.read(index)?, false)
+            }
+            (FluxMessage::ChunkList { data, nonce, last }, Some(chunks))
+                if incoming.received == 0 =>
+            {
+                if let Some(answer) = chunks.add_list(data, nonce, last, channel)? {
+                    let frame = Bytes::from(encode_message(&answer)?);
+                    match tokio::time::timeout(stall_timeout(), framed.send(frame)).await {
+                        Ok(Ok(())) => {}
+                        _ => return Err(disconnected("Failed to answer chunk list".into())),
+                    }
                 }
-                hasher.update(&plaintext);
-
-                received_bytes += chunk_len;
-                expected_offset += chunk_len;
-                pb.set_position(received_bytes);
+                continue;
             }
-            FluxMessage::Error { message } => {
-                pb.finish_and_clear();
-                drop(out_file);
-                let _ = std::fs::remove_file(&output_path);
+            // The sender is alive but busy; the timeout restarts with the next read
+            (FluxMessage::Keepalive, _) => continue,
+            (FluxMessage::Error { message }, _) => {
                 return Err(FluxError::TransferError(format!(
                     "Sender error during transfer: {}",
                     message
-                )));
+                ))
+                .into());
+            }
+            (FluxMessage::Cancel { reason }, _) => {
+                // Fatal, so the partial file is deleted rather than parked
+                return Err(FluxError::TransferError(format!(
+                    "Sender cancelled the transfer ({})",
+                    reason
+                ))
+                .into());
             }
             _ => {
-                pb.finish_and_clear();
-                drop(out_file);
-                let _ = std::fs::remove_file(&output_path);
                 return Err(FluxError::TransferError(
                     "Unexpected message during data transfer".into(),
-                ));
+                )
+                .into());
             }
+        };
+
+        // Validate chunk offset matches expected sequential position
+        if offset != incoming.received {
+            return Err(FluxError::TransferError(format!(
+                "Unexpected chunk offset: expected {}, got {}",
+                incoming.received, offset
+            ))
+            .into());
         }
-    }
 
-    pb.finish_and_clear();
-    drop(out_file);
-
-    // --- Verify BLAKE3 checksum (computed incrementally during receive) ---
-    let checksum_verified = if let Some(ref expected) = expected_checksum {
-        let actual = hasher.finalize().to_hex().to_string();
-        if actual != *expected {
-            // Checksum mismatch — delete the corrupted file
-            let _ = std::fs::remove_file(&output_path);
-            let reject = FluxMessage::Error {
-                message: format!(
-                    "Checksum mismatch: expected {}, got {}",
-                    expected, actual
-                ),
-            };
-            framed
-                .send(Bytes::from(encode_message(&reject)?))
-                .await
-                .ok();
+        // Prevent data overflow: reject if sender sends more than declared size
+        let chunk_len = plaintext.len() as u64;
+        if incoming.received + chunk_len > incoming.size {
             return Err(FluxError::TransferError(format!(
-                "BLAKE3 checksum mismatch for '{}': file may be corrupted or tampered",
-                filename
-            )));
+                "Data overflow: received {} + chunk {} exceeds declared size {}",
+                incoming.received, chunk_len, incoming.size
+            ))
+            .into());
         }
-        Some(true)
-    } else {
-        None
-    };
 
-    // Send TransferComplete
-    let complete = FluxMessage::TransferComplete {
-        filename: display_name.clone(),
-        bytes_received: received_bytes,
-        checksum_verified,
+        incoming.write(&plaintext, low_memory)?;
+        pb.set_position(incoming.received);
+        if let (Some(limiter), true) = (limiter, on_wire) {
+            limiter.consume(chunk_len).await;
+        }
+    }
+    Ok(())
+}
+
+/// After the data of a file whose header announced a `footer`, wait for the
+/// `FileFooter` and return its checksum. The sender may send `Keepalive`s
+/// while it reads the parts of the file it did not hash while sending.
+///
+/// Ends the attempt like `receive_chunks`: a lost or stalled connection is
+/// `AttemptError::Disconnected`, a `Cancel` or `Error` is fatal.
+async fn receive_footer(
+    framed: &mut Framed<TcpStream, LengthDelimitedCodec>,
+    active: Option<&ActiveGuard>,
+) -> Result<String, AttemptError> {
+    let disconnected = |reason: String| AttemptError::Disconnected(FluxError::TransferError(reason));
+
+    loop {
+        let next = tokio::select! {
+            next = tokio::time::timeout(stall_timeout(), framed.next()) => Some(next),
+            _ = superseded(active) => {
+                return Err(disconnected("Sender reconnected on a new connection".into()));
+            }
+            _ = cancel::cancelled() => None,
+        };
+        let bytes = match next {
+            None => {
+                let cancel = FluxMessage::Cancel {
+                    reason: CANCEL_REASON.to_string(),
+                };
+                let frame = Bytes::from(encode_message(&cancel)?);
+                let _ = tokio::time::timeout(CANCEL_TIMEOUT, framed.send(frame)).await;
+                return Err(FluxError::Cancelled.into());
+            }
+            Some(Err(_)) => {
+                return Err(disconnected(format!(
+                    "No checksum from sender for {}s",
+                    stall_timeout().as_secs()
+                )))
+            }
+            Some(Ok(None)) => return Err(disconnected("Connection closed before checksum".into())),
+            Some(Ok(Some(Err(e)))) => {
+                return Err(disconnected(format!("Failed to read checksum: {}", e)))
+            }
+            Some(Ok(Some(Ok(bytes)))) => bytes,
+        };
+        match decode_message(&bytes)? {
+            FluxMessage::FileFooter { checksum } => return Ok(checksum),
+            FluxMessage::Keepalive => continue,
+            FluxMessage::Error { message } => {
+                return Err(FluxError::TransferError(format!(
+                    "Sender error during transfer: {}",
+                    message
+                ))
+                .into());
+            }
+            FluxMessage::Cancel { reason } => {
+                return Err(FluxError::TransferError(format!(
+                    "Sender cancelled the transfer ({})",
+                    reason
+                ))
+                .into());
+            }
+            _ => {
+                return Err(FluxError::TransferError(
+                    "Expected FileFooter after the file data".into(),
+                )
+                .into());
+            }
+        }
+    }
+}
+
+/// Completes when `active` is superseded by a reconnected sender; never
+/// completes without a registration.
+async fn superseded(active: Option<&ActiveGuard>) {
+    match active {
+        Some(active) => active.superseded().await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Countersign the sender's receipt request, if one follows `TransferComplete`.
+///
+/// Only verified transfers can be receipted. The file is already saved, so
+/// a refused or failed receipt is reported without failing the receive.
+async fn sign_requested_receipt(
+    framed: &mut Framed<TcpStream, LengthDelimitedCodec>,
+    config_dir: &Path,
+    device_name: &str,
+    filename: &str,
+    received_bytes: u64,
+    checksum: Option<&str>,
+    checksum_verified: Option<bool>,
+) -> Option<TransferReceipt> {
+    let checksum = match (checksum, checksum_verified) {
+        (Some(checksum), Some(true)) => checksum,
+        _ => return None,
     };
-    framed
-        .send(Bytes::from(encode_message(&complete)?))
+    match countersign_receipt(framed, config_dir, device_name, filename, received_bytes, checksum)
         .await
-        .map_err(|e| {
-            FluxError::TransferError(format!("Failed to send transfer complete: {}", e))
-        })?;
-
     {
-        let mut stats = TransferStats::new(1, file_size);
-        stats.started = started;
-        stats.add_done(received_bytes);
-        stats.print_file_summary(&display_name, false);
+        Ok(receipt) => {
+            if let Some(ref r) = receipt {
+                eprintln!("Signed delivery receipt for {}", r.sender.device);
+            }
+            receipt
+        }
+        Err(e) => {
+            eprintln!("Receipt not signed: {}", e);
+            None
+        }
     }
-
-    Ok(())
 }
 
 /// Synchronous wrapper for code-phrase receive mode.
+///
+/// Records the outcome in transfer history.
 pub fn receive_with_code_sync(
     code: &str,
     output_dir: &Path,
     device_name: &str,
+    low_memory: bool,
+    limit: RateLimit,
 ) -> Result<(), FluxError> {
+    let quota = load_receive_quota()?;
+    let space = SpacePolicy::from_config(&crate::config::types::load_config()?.receive)?;
     let rt = tokio::runtime::Runtime::new()
         .map_err(|e| FluxError::TransferError(format!("Failed to create async runtime: {}", e)))?;
 
-    rt.block_on(receive_with_code(code, output_dir, device_name))
+    let mut record = HistoryRecord::new("receive", "code-phrase", &output_dir.display().to_string());
+    let audit = AuditTrail::new("receive", None);
+    let result = rt.block_on(receive_with_code(
+        code,
+        output_dir,
+        device_name,
+        low_memory,
+        &quota,
+        space,
+        limit,
+    ));
+    finish_receive_record(&mut record, &audit, &result);
+    result.map(|_| ())
 }
 
 /// Sanitize a peer device name received over the network before using it as a
@@ -1023,68 +2238,100 @@ fn write_file_exclusive(path: &Path, data: &[u8]) -> Result<(), FluxError> {
 /// Maximum file size the receiver will accept (4 GB).
 /// This prevents a malicious sender from claiming an enormous file size
 /// and causing the receiver to allocate unbounded memory.
-const MAX_RECEIVE_SIZE: u64 = 4 * 1024 * 1024 * 1024;
+pub(crate) const MAX_RECEIVE_SIZE: u64 = 4 * 1024 * 1024 * 1024;
+
+/// Create the temp file an incoming transfer is written to.
+///
+/// Opened exclusively (prevents TOCTOU/symlink attacks).
+fn create_temp_output(atomic_file: &AtomicFile) -> Result<std::fs::File, FluxError> {
+    std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(atomic_file.path())
+        .map_err(|e| {
+            FluxError::TransferError(format!(
+                "Failed to create file '{}': {}",
+                atomic_file.path().display(),
+                e
+            ))
+        })
+}
 
 /// Find a unique file path in the output directory.
 ///
-/// If `output_dir/filename` does not exist, return it as-is.
-/// Otherwise, try `filename_1.ext`, `filename_2.ext`, etc. up to 9999.
+/// If `output_dir/filename` is free (no file and no in-progress temp file),
+/// return it as-is. Otherwise, try `filename (1).ext`, `filename (2).ext`,
+/// etc., the same names `cp --on-conflict rename` uses.
 fn find_unique_path(output_dir: &Path, filename: &str) -> PathBuf {
-    let safe_name = sanitize_filename(filename);
-    let base = output_dir.join(&safe_name);
-    if !base.exists() {
+    let base = output_dir.join(sanitize_filename(filename));
+    if !is_taken(&base) {
         return base;
     }
+    conflict::find_unique_path(&base, is_taken)
+}
 
-    let stem = Path::new(&safe_name)
-        .file_stem()
-        .map(|s| s.to_string_lossy().to_string())
-        .unwrap_or_else(|| safe_name.clone());
-    let ext = Path::new(&safe_name)
-        .extension()
-        .map(|e| format!(".{}", e.to_string_lossy()));
-
-    for i in 1..=9999 {
-        let candidate = match &ext {
-            Some(e) => output_dir.join(format!("{}_{}{}", stem, i, e)),
-            None => output_dir.join(format!("{}_{}", stem, i)),
-        };
-        if !candidate.exists() {
-            return candidate;
-        }
-    }
+/// Whether a destination name is in use, either by a file or by the temp
+/// file of a transfer in progress (possibly parked for a resume).
+fn is_taken(path: &Path) -> bool {
+    path.exists() || temp_path(path).exists()
+}
 
-    // Fallback with timestamp
-    let ts = chrono::Utc::now().timestamp();
-    match &ext {
-        Some(e) => output_dir.join(format!("{}_{}{}", stem, ts, e)),
-        None => output_dir.join(format!("{}_{}", stem, ts)),
-    }
+/// How `flux receive` listens, from its command-line flags.
+pub struct ReceiveOptions<'a> {
+    /// Output directory, overriding config.toml's
+    pub output: Option<&'a str>,
+    /// Require encrypted transfers
+    pub encrypt: bool,
+    /// Name announced to senders
+    pub device_name: &'a str,
+    /// Address to listen on
+    pub bind_addr: &'a str,
+    /// Refuse unknown senders instead of prompting
+    pub daemon: bool,
+    /// Download rate limit
+    pub limit: RateLimit,
+    /// Accept only senders holding this key
+    pub psk: Option<PreSharedKey>,
+    /// Also serve a page for browser drops
+    pub web: Option<WebDrop>,
+    /// Serve `flux push` and `flux pull`
+    pub allow_mirror: bool,
 }
 
 /// Synchronous wrapper for starting the receiver.
 ///
-/// Creates a local tokio runtime and blocks on the receiver loop.
-/// This is the entry point called from main.rs.
-pub fn start_receiver_sync(
-    port: u16,
-    output_dir: &Path,
-    encrypt: bool,
-    device_name: &str,
-    bind_addr: &str,
-) -> Result<(), FluxError> {
+/// Creates a local tokio runtime and blocks on the receiver loop, with the
+/// output directory, daily quotas and allowlist from config.toml (the
+/// `output` option overrides the directory). This is the entry point called
+/// from main.rs.
+pub fn start_receiver_sync(port: u16, options: ReceiveOptions<'_>) -> Result<(), FluxError> {
+    let ReceiveOptions {
+        output,
+        encrypt,
+        device_name,
+        bind_addr,
+        daemon,
+        limit,
+        psk,
+        web,
+        allow_mirror,
+    } = options;
     let config_dir = flux_config_dir()?;
+    let mut settings = ReceiverSettings::load(output, daemon, limit)?;
+    settings.psk = psk.map(Arc::new);
+    settings.allow_mirror = allow_mirror;
 
     let rt = tokio::runtime::Runtime::new()
         .map_err(|e| FluxError::TransferError(format!("Failed to create async runtime: {}", e)))?;
 
     rt.block_on(start_receiver(
         port,
-        output_dir,
+        settings,
         encrypt,
         device_name,
         &config_dir,
         bind_addr,
+        web,
     ))
 }
 
@@ -1092,6 +2339,75 @@ pub fn start_receiver_sync(
 mod tests {
     use super::*;
 
+    #[test]
+    fn output_flag_overrides_config_output_dir() {
+        let mut config = crate::config::types::ReceiveConfig::default();
+        assert_eq!(output_template(None, &config), ".");
+        config.output_dir = Some("/srv/inbox/{date}".to_string());
+        assert_eq!(output_template(None, &config), "/srv/inbox/{date}");
+        assert_eq!(output_template(Some("out"), &config), "out");
+    }
+
+    #[test]
+    fn allowlisted_sender_gets_its_own_output_dir() {
+        let mut config = crate::config::types::ReceiveConfig::default();
+        config.devices.insert(
+            "nas".to_string(),
+            AllowedDevice {
+                fingerprint: "q83vEjRWeJCrze8S...".to_string(),
+                output_dir: Some("/srv/inbox/nas".to_string()),
+            },
+        );
+        let mut settings = ReceiverSettings {
+            output_override: None,
+            output: "/srv/inbox".to_string(),
+            quota: ReceiveQuota::in_memory(QuotaLimits::default()),
+            space: SpacePolicy::default(),
+            storage: ReceiveStorage::new(StorageLimits::default()),
+            devices: load_allowlist(&config).unwrap(),
+            per_sender_dirs: false,
+            refuse_unknown: true,
+            psk: None,
+            limit: RateLimit::default(),
+            allow_mirror: false,
+            admission: Admission::new(AdmissionLimits::default()),
+        };
+        assert_eq!(settings.output_for(Some("nas")), "/srv/inbox/nas");
+        // Unverified senders always use the shared directory
+        assert_eq!(settings.output_for(None), "/srv/inbox");
+
+        // With per_sender_dirs, other verified senders get a subdirectory
+        settings.per_sender_dirs = true;
+        let shared = Path::new("/srv/inbox");
+        assert_eq!(settings.sender_dir(shared, Some("nas")), Path::new("/srv/inbox/nas"));
+        assert_eq!(settings.sender_dir(shared, Some("phone")), shared.join("phone"));
+        assert_eq!(settings.sender_dir(shared, Some("../etc")), shared.join("etc"));
+        assert_eq!(settings.sender_dir(shared, None), shared);
+
+        config.devices.get_mut("nas").unwrap().fingerprint = "q83v".to_string();
+        assert!(load_allowlist(&config).is_err());
+    }
+
+    #[test]
+    fn incoming_file_verifies_tagged_checksums() {
+        let dir = tempfile::tempdir().unwrap();
+        let data = b"checksum me";
+        let xxh3 = format!("{:032x}", xxhash_rust::xxh3::xxh3_128(data));
+
+        let mut incoming =
+            IncomingFile::create(dir.path(), "a.bin", 11, ChecksumAlgorithm::Xxh3).unwrap();
+        incoming.write(data, false).unwrap();
+        assert!(incoming.verify(Some(&format!("xxh3:{}", xxh3))).is_ok());
+        // The bare hex would claim BLAKE3
+        assert!(incoming.verify(Some(&xxh3)).is_err());
+
+        let mut incoming =
+            IncomingFile::create(dir.path(), "b.bin", 11, ChecksumAlgorithm::Blake3).unwrap();
+        incoming.write(data, false).unwrap();
+        let blake3 = blake3::hash(data).to_hex().to_string();
+        assert!(incoming.verify(Some(&blake3)).is_ok());
+    }
+
     #[test]
     fn find_unique_path_no_conflict() {
         let dir = tempfile::tempdir().unwrap();
@@ -1105,18 +2421,26 @@ mod tests {
         std::fs::write(dir.path().join("test.txt"), "existing").unwrap();
 
         let result = find_unique_path(dir.path(), "test.txt");
-        assert_eq!(result, dir.path().join("test_1.txt"));
+        assert_eq!(result, dir.path().join("test (1).txt"));
+    }
+
+    #[test]
+    fn find_unique_path_skips_in_progress_temp_files() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(temp_path(&dir.path().join("test.txt")), "partial").unwrap();
+        let result = find_unique_path(dir.path(), "test.txt");
+        assert_eq!(result, dir.path().join("test (1).txt"));
     }
 
     #[test]
     fn find_unique_path_multiple_conflicts() {
         let dir = tempfile::tempdir().unwrap();
         std::fs::write(dir.path().join("test.txt"), "existing").unwrap();
-        std::fs::write(dir.path().join("test_1.txt"), "existing").unwrap();
-        std::fs::write(dir.path().join("test_2.txt"), "existing").unwrap();
+        std::fs::write(dir.path().join("test (1).txt"), "existing").unwrap();
+        std::fs::write(dir.path().join("test (2).txt"), "existing").unwrap();
 
         let result = find_unique_path(dir.path(), "test.txt");
-        assert_eq!(result, dir.path().join("test_3.txt"));
+        assert_eq!(result, dir.path().join("test (3).txt"));
     }
 
     #[test]
@@ -1125,7 +2449,7 @@ mod tests {
         std::fs::write(dir.path().join("README"), "existing").unwrap();
 
         let result = find_unique_path(dir.path(), "README");
-        assert_eq!(result, dir.path().join("README_1"));
+        assert_eq!(result, dir.path().join("README (1)"));
     }
 
     #[test]
@@ -0,0 +1,470 @@
+//! Resuming P2P transfers after a dropped connection.
+//!
+//! A laptop that moves between Wi-Fi and Ethernet mid-send gets a new address
+//! and its TCP session dies. Instead of failing, the sender reconnects (finding
+//! the device or code phrase again via mDNS), handshakes again and sends
+//! `ResumeRequest` in place of `FileHeader`. The receiver answers with
+//! `ResumeAck` carrying how many bytes it already wrote, and the transfer
+//! continues from that offset.
+//!
+//! The receiver keeps a partial file (its atomic temp file plus the running
+//! hash state) for `RECONNECT_GRACE`, keyed by the sender's device name and
+//! the file checksum. The sender gives up after the same window.
+//!
+//! A connection that goes quiet (a sleeping Wi-Fi link often stops passing
+//! traffic without closing) is noticed by `stall_timeout`: no chunk for that
+//! long counts as a drop. A sender busy with something other than sending
+//! data (pacing to `--limit-up`, cutting the file for a chunk list) sends
+//! `Keepalive` every `keepalive_interval` so it is not taken for stalled, and
+//! `StallNotice` shows a stall on the progress bar while it lasts.
+
+use std::io::{Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use indicatif::ProgressBar;
+use tokio::sync::Notify;
+
+use crate::error::FluxError;
+use crate::transfer::atomic::temp_path;
+use crate::transfer::checksum::ChecksumHasher;
+
+/// How long either side waits for a dropped transfer to be resumed.
+pub const RECONNECT_GRACE: Duration = Duration::from_secs(120);
+
+/// Pause between reconnection attempts.
+pub const RECONNECT_DELAY: Duration = Duration::from_secs(2);
+
+/// Default `stall_timeout`.
+pub const DEFAULT_STALL_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// `stall_timeout` in seconds (`--stall-timeout`).
+static STALL_SECS: AtomicU64 = AtomicU64::new(DEFAULT_STALL_TIMEOUT.as_secs());
+
+/// No progress for this long shows on the progress bar.
+const STALL_NOTICE: Duration = Duration::from_secs(5);
+
+/// A chunk send or read that makes no progress for this long is treated as a
+/// dropped connection. Roaming often leaves the old socket hanging rather
+/// than failing outright.
+pub fn stall_timeout() -> Duration {
+    Duration::from_secs(STALL_SECS.load(Ordering::Relaxed))
+}
+
+/// Change `stall_timeout` for this process (`--stall-timeout`).
+pub fn set_stall_timeout(timeout: Duration) {
+    STALL_SECS.store(timeout.as_secs().max(1), Ordering::Relaxed);
+}
+
+/// How often a side with nothing else to send sends `Keepalive`: a third of
+/// the stall timeout, so one lost or late keepalive is not a stall.
+pub fn keepalive_interval() -> Duration {
+    stall_timeout() / 3
+}
+
+/// How long a receiver waits for a stalled connection to hand over its
+/// partial file when the sender has already reconnected.
+const SUPERSEDE_WAIT: Duration = Duration::from_secs(5);
+
+/// Why a transfer attempt ended early.
+#[derive(Debug)]
+pub enum AttemptError {
+    /// The connection dropped or stalled; the transfer can be resumed on a
+    /// new connection.
+    Disconnected(FluxError),
+    /// Anything else (rejection, corrupt data, local I/O); retrying would
+    /// fail the same way.
+    Fatal(FluxError),
+}
+
+impl AttemptError {
+    pub fn into_inner(self) -> FluxError {
+        match self {
+            AttemptError::Disconnected(e) | AttemptError::Fatal(e) => e,
+        }
+    }
+}
+
+impl From<FluxError> for AttemptError {
+    fn from(e: FluxError) -> Self {
+        AttemptError::Fatal(e)
+    }
+}
+
+/// Tracks how long a sender has been trying to get a transfer going again.
+///
+/// The grace window restarts whenever the transfer makes progress, so a
+/// laptop that roams several times during a long send keeps going.
+#[derive(Debug, Default)]
+pub struct ReconnectWindow {
+    outage: Option<(Instant, u64)>,
+}
+
+impl ReconnectWindow {
+    /// Record a dropped connection with `position` bytes acknowledged so far.
+    ///
+    /// Returns false once the transfer has been stuck for `grace`.
+    pub fn allow_retry(&mut self, position: u64, grace: Duration) -> bool {
+        match self.outage {
+            Some((since, at)) if at == position => since.elapsed() < grace,
+            _ => {
+                self.outage = Some((Instant::now(), position));
+                true
+            }
+        }
+    }
+}
+
+/// Shows on a transfer's progress bar how long it has been stalled.
+pub struct StallNotice {
+    pb: ProgressBar,
+    task: tokio::task::JoinHandle<()>,
+}
+
+impl StallNotice {
+    /// Watch `pb` until dropped: once its position has not moved for
+    /// `STALL_NOTICE`, its message says so, until it moves again.
+    pub fn watch(pb: &ProgressBar) -> Self {
+        let watched = pb.clone();
+        let task = tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(Duration::from_secs(1));
+            let (mut position, mut since) = (watched.position(), Instant::now());
+            let mut shown = false;
+            loop {
+                ticker.tick().await;
+                if watched.position() != position {
+                    (position, since) = (watched.position(), Instant::now());
+                    if shown {
+                        watched.set_message("");
+                        shown = false;
+                    }
+                } else if since.elapsed() >= STALL_NOTICE {
+                    watched.set_message(stall_message(since.elapsed()));
+                    shown = true;
+                }
+            }
+        });
+        StallNotice {
+            pb: pb.clone(),
+            task,
+        }
+    }
+}
+
+impl Drop for StallNotice {
+    fn drop(&mut self) {
+        self.task.abort();
+        self.pb.set_message("");
+    }
+}
+
+/// Progress bar message for a transfer without progress for `idle`.
+fn stall_message(idle: Duration) -> String {
+    if idle < stall_timeout() {
+        format!("stalled {}s", idle.as_secs())
+    } else {
+        format!("stalled {}s, reconnecting", idle.as_secs())
+    }
+}
+
+/// A partially received file waiting for its sender to reconnect.
+#[derive(Debug)]
+pub struct PartialReceive {
+    /// Sanitized device name of the sender
+    pub peer: String,
+    /// Checksum of the whole file, as tagged in the `FileHeader`
+    pub checksum: String,
+    pub size: u64,
+    /// Final destination; the data so far is in its atomic temp file
+    pub output_path: PathBuf,
+    /// Bytes written to the temp file
+    pub received: u64,
+    /// Hash state over the bytes received so far
+    pub hasher: Box<dyn ChecksumHasher>,
+    parked_at: Instant,
+}
+
+impl PartialReceive {
+    pub fn new(
+        peer: &str,
+        checksum: &str,
+        size: u64,
+        output_path: PathBuf,
+        received: u64,
+        hasher: Box<dyn ChecksumHasher>,
+    ) -> Self {
+        Self {
+            peer: peer.to_string(),
+            checksum: checksum.to_string(),
+            size,
+            output_path,
+            received,
+            hasher,
+            parked_at: Instant::now(),
+        }
+    }
+
+    fn matches(&self, peer: &str, checksum: &str) -> bool {
+        self.peer == peer && self.checksum == checksum
+    }
+}
+
+/// A receive in progress that a reconnecting sender may take over.
+struct ActiveReceive {
+    id: u64,
+    peer: String,
+    checksum: String,
+    superseded: Arc<Notify>,
+}
+
+#[derive(Default)]
+struct Pending {
+    parked: Vec<PartialReceive>,
+    active: Vec<ActiveReceive>,
+    next_id: u64,
+}
+
+/// Partial receives shared by all connections of a `flux receive` server.
+#[derive(Clone, Default)]
+pub struct PendingReceives {
+    inner: Arc<Mutex<Pending>>,
+    grace: Option<Duration>,
+}
+
+impl PendingReceives {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[cfg(test)]
+    fn with_grace(grace: Duration) -> Self {
+        Self {
+            grace: Some(grace),
+            ..Self::default()
+        }
+    }
+
+    fn grace(&self) -> Duration {
+        self.grace.unwrap_or(RECONNECT_GRACE)
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Pending> {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Register a receive in progress. A later `take` for the same sender and
+    /// checksum signals it (through the guard) to stop and park its file.
+    pub fn begin(&self, peer: &str, checksum: &str) -> ActiveGuard {
+        let superseded = Arc::new(Notify::new());
+        let mut pending = self.lock();
+        let id = pending.next_id;
+        pending.next_id += 1;
+        pending.active.push(ActiveReceive {
+            id,
+            peer: peer.to_string(),
+            checksum: checksum.to_string(),
+            superseded: superseded.clone(),
+        });
+        ActiveGuard {
+            pending: self.clone(),
+            id,
+            superseded,
+        }
+    }
+
+    /// Keep a partial file for the sender to resume within the grace window.
+    pub fn park(&self, partial: PartialReceive) {
+        let mut pending = self.lock();
+        expire(&mut pending.parked, self.grace());
+        pending.parked.push(partial);
+    }
+
+    /// Claim the partial file for a reconnecting sender.
+    ///
+    /// If the old connection has not noticed the drop yet, it is told to stop
+    /// and given a few seconds to park its file.
+    pub async fn take(&self, peer: &str, checksum: &str, size: u64) -> Option<PartialReceive> {
+        let deadline = Instant::now() + SUPERSEDE_WAIT;
+        loop {
+            let still_active = {
+                let mut pending = self.lock();
+                expire(&mut pending.parked, self.grace());
+                if let Some(i) = pending
+                    .parked
+                    .iter()
+                    .position(|p| p.matches(peer, checksum))
+                {
+                    let partial = pending.parked.swap_remove(i);
+                    return (partial.size == size).then_some(partial);
+                }
+                let active = pending
+                    .active
+                    .iter()
+                    .find(|a| a.peer == peer && a.checksum == checksum);
+                if let Some(active) = active {
+                    active.superseded.notify_one();
+                }
+                active.is_some()
+            };
+            if !still_active || Instant::now() >= deadline {
+                return None;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+    }
+}
+
+/// Drop parked receives older than `grace`, deleting their temp files.
+fn expire(parked: &mut Vec<PartialReceive>, grace: Duration) {
+    parked.retain(|p| {
+        let keep = p.parked_at.elapsed() < grace;
+        if !keep {
+            tracing::debug!("Discarding unresumed partial '{}'", p.output_path.display());
+            let _ = std::fs::remove_file(temp_path(&p.output_path));
+        }
+        keep
+    });
+}
+
+/// Registration of a receive in progress; unregisters on drop.
+pub struct ActiveGuard {
+    pending: PendingReceives,
+    id: u64,
+    superseded: Arc<Notify>,
+}
+
+impl ActiveGuard {
+    /// Completes when a reconnected sender wants to take this transfer over.
+    pub async fn superseded(&self) {
+        self.superseded.notified().await
+    }
+
+    pub fn pending(&self) -> &PendingReceives {
+        &self.pending
+    }
+}
+
+impl Drop for ActiveGuard {
+    fn drop(&mut self) {
+        self.pending.lock().active.retain(|a| a.id != self.id);
+    }
+}
+
+/// Reopen the temp file of a partial receive for appending, cut back to the
+/// bytes that were received (and hashed).
+pub fn reopen_partial(output_path: &Path, received: u64) -> Result<std::fs::File, FluxError> {
+    let temp = temp_path(output_path);
+    let mut file = std::fs::OpenOptions::new()
+        .write(true)
+        .open(&temp)
+        .map_err(|e| {
+            FluxError::TransferError(format!(
+                "Cannot reopen partial file '{}': {}",
+                temp.display(),
+                e
+            ))
+        })?;
+    if file.metadata()?.len() < received {
+        return Err(FluxError::TransferError(format!(
+            "Partial file '{}' is shorter than recorded",
+            temp.display()
+        )));
+    }
+    file.set_len(received)?;
+    file.seek(SeekFrom::End(0))?;
+    Ok(file)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transfer::checksum::ChecksumAlgorithm;
+
+    fn partial(dir: &Path, received: u64) -> PartialReceive {
+        let output = dir.join("movie.mkv");
+        std::fs::write(temp_path(&output), vec![7u8; received as usize + 10]).unwrap();
+        PartialReceive::new(
+            "laptop",
+            "abcd",
+            1000,
+            output,
+            received,
+            ChecksumAlgorithm::Blake3.hasher(),
+        )
+    }
+
+    #[test]
+    fn stall_messages_say_when_a_reconnect_is_due() {
+        assert_eq!(stall_message(Duration::from_secs(7)), "stalled 7s");
+        let late = stall_timeout() + Duration::from_secs(2);
+        assert!(stall_message(late).ends_with(", reconnecting"));
+        assert!(keepalive_interval() < stall_timeout());
+    }
+
+    #[tokio::test]
+    async fn take_matches_peer_checksum_and_size() {
+        let dir = tempfile::tempdir().unwrap();
+        let pending = PendingReceives::new();
+
+        pending.park(partial(dir.path(), 100));
+        assert!(pending.take("other-device", "abcd", 1000).await.is_none());
+        assert!(pending.take("laptop", "ffff", 1000).await.is_none());
+        let taken = pending.take("laptop", "abcd", 1000).await.unwrap();
+        assert_eq!(taken.received, 100);
+        // Claimed once only
+        assert!(pending.take("laptop", "abcd", 1000).await.is_none());
+
+        pending.park(partial(dir.path(), 100));
+        assert!(pending.take("laptop", "abcd", 2000).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn expired_partials_are_deleted() {
+        let dir = tempfile::tempdir().unwrap();
+        let pending = PendingReceives::with_grace(Duration::ZERO);
+        let parked = partial(dir.path(), 100);
+        let temp = temp_path(&parked.output_path);
+        pending.park(parked);
+
+        assert!(pending.take("laptop", "abcd", 1000).await.is_none());
+        assert!(!temp.exists());
+    }
+
+    #[tokio::test]
+    async fn take_supersedes_a_stalled_receive() {
+        let dir = tempfile::tempdir().unwrap();
+        let pending = PendingReceives::new();
+        let guard = pending.begin("laptop", "abcd");
+
+        let path = dir.path().to_path_buf();
+        let old_connection = tokio::spawn(async move {
+            guard.superseded().await;
+            guard.pending().park(partial(&path, 64));
+        });
+
+        let taken = pending.take("laptop", "abcd", 1000).await.unwrap();
+        assert_eq!(taken.received, 64);
+        old_connection.await.unwrap();
+    }
+
+    #[test]
+    fn reopen_truncates_to_received() {
+        let dir = tempfile::tempdir().unwrap();
+        let parked = partial(dir.path(), 100);
+        let file = reopen_partial(&parked.output_path, 100).unwrap();
+        assert_eq!(file.metadata().unwrap().len(), 100);
+        assert!(reopen_partial(&parked.output_path, 500).is_err());
+    }
+
+    #[test]
+    fn reconnect_window_restarts_on_progress() {
+        let mut window = ReconnectWindow::default();
+        assert!(window.allow_retry(0, Duration::ZERO));
+        // No progress since the last drop and the window has passed
+        assert!(!window.allow_retry(0, Duration::ZERO));
+        // Progress made: a new outage starts
+        assert!(window.allow_retry(4096, Duration::ZERO));
+    }
+}
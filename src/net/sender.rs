@@ -1,29 +1,227 @@
 //! TCP client for sending files to Flux receivers.
 //!
 //! Connects to a Flux receiver, performs protocol handshake (with optional
-//! encryption key exchange), and streams file data in chunks.
+//! encryption key exchange), and streams file data in chunks. If the
+//! connection drops mid-transfer, the sender reconnects and resumes (see
+//! `net::resume`).
 
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::time::Instant;
 
-use futures::{SinkExt, StreamExt};
+use futures::{FutureExt, SinkExt, StreamExt};
 use tokio_util::bytes::Bytes;
 use tokio::net::TcpStream;
 use tokio_util::codec::{Framed, LengthDelimitedCodec};
 
+use crate::config::paths::flux_config_dir;
+use crate::discovery::cache::{self, DeviceCache};
 use crate::discovery::mdns::discover_flux_devices;
-use crate::discovery::service::DEFAULT_PORT;
+use crate::discovery::scan::{discover_devices, probe};
+use crate::discovery::service::{DiscoveredDevice, DEFAULT_PORT};
 use crate::error::FluxError;
+use crate::net::addr;
+use crate::net::audit::AuditTrail;
+use crate::net::chunking::{self, Segment};
 use crate::net::protocol::{
-    decode_message, encode_message, FluxMessage, CHUNK_SIZE, MAX_FRAME_SIZE, PROTOCOL_VERSION,
+    decode_handshake_ack, decode_message, encode_message, footer_key, negotiated_chunk_size,
+    FluxMessage, CANCEL_REASON, CANCEL_TIMEOUT, CHUNK_SIZE, MAX_FRAME_SIZE, PROTOCOL_VERSION,
 };
+use crate::net::ratelimit::{ConnectionLimiter, RateLimit};
+use crate::net::receipt::request_receipt;
+use crate::net::resume::{
+    keepalive_interval, stall_timeout, AttemptError, ReconnectWindow, StallNotice,
+    RECONNECT_DELAY, RECONNECT_GRACE,
+};
+use crate::net::socks;
+use crate::net::zerocopy;
+use crate::progress::bar::{create_network_progress, stderr_target};
 use crate::security::crypto::EncryptedChannel;
+use crate::security::psk::{self, PreSharedKey, PskRole};
+use crate::security::receipt::TransferReceipt;
+use crate::transfer::cancel;
+use crate::transfer::checksum::{ChecksumAlgorithm, ChecksumHasher};
+use crate::transfer::history::{record_history, HistoryRecord};
 use crate::transfer::stats::TransferStats;
+use crate::transfer::status::StatusPublisher;
+use crate::transfer::usage;
+
+/// Summary of a completed send, as acknowledged by the receiver.
+#[derive(Debug, Clone)]
+pub struct SendReport {
+    /// Bytes the receiver reported as received
+    pub bytes: u64,
+    /// Receiver's checksum verdict (None if it did not check)
+    pub checksum_verified: Option<bool>,
+    /// Address of the receiving peer
+    pub peer: String,
+    /// Countersigned receipt, when one was requested and obtained
+    pub receipt: Option<TransferReceipt>,
+}
 
 /// Timeout for receiving HandshakeAck from the receiver.
 const HANDSHAKE_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
 /// Timeout for receiving TransferComplete from the receiver after all data is sent.
 const COMPLETION_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(300);
+/// How long the cached address of an `@name` gets to answer a `Ping`.
+const CACHED_PROBE_TIMEOUT: std::time::Duration = std::time::Duration::from_millis(500);
+
+pub(crate) type FluxFramed = Framed<TcpStream, LengthDelimitedCodec>;
+
+/// The file being sent: metadata and checksum, computed once for all
+/// connection attempts.
+pub(crate) struct OutgoingFile {
+    pub path: PathBuf,
+    pub filename: String,
+    pub size: u64,
+    /// Checksum as sent in the FileHeader (hex, tagged with the algorithm
+    /// unless BLAKE3); also identifies the transfer when resuming. Empty
+    /// until computed when the file was only `stat`ed
+    pub checksum: String,
+}
+
+impl OutgoingFile {
+    /// Read the metadata and compute the `algorithm` checksum by streaming
+    /// from disk (no full-file buffering).
+    pub fn open(file_path: &Path, algorithm: ChecksumAlgorithm) -> Result<Self, FluxError> {
+        let mut file = Self::stat(file_path)?;
+        file.checksum = StreamHash::new(algorithm).catch_up(&file.path, file.size)?;
+        Ok(file)
+    }
+
+    /// Read the metadata only; the checksum is left to the caller.
+    pub fn stat(file_path: &Path) -> Result<Self, FluxError> {
+        let file_meta = std::fs::metadata(file_path).map_err(|e| {
+            FluxError::TransferError(format!(
+                "Cannot read file '{}': {}",
+                file_path.display(),
+                e
+            ))
+        })?;
+
+        let filename = file_path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| "unnamed".to_string());
+
+        Ok(Self {
+            path: file_path.to_path_buf(),
+            filename,
+            size: file_meta.len(),
+            checksum: String::new(),
+        })
+    }
+}
+
+/// Checksum of a file, computed from the data as `stream_chunks` reads it
+/// for sending so the file is read once. Parts that are not read for sending
+/// (chunks the receiver already has, `RawData`) are read from disk to fill
+/// the gap.
+pub(crate) struct StreamHash {
+    algorithm: ChecksumAlgorithm,
+    /// Hash of the file and how many bytes from its start went in. `None`
+    /// when lost with an interrupted `catch_up_hash`; hashing starts over
+    state: Option<(Box<dyn ChecksumHasher>, u64)>,
+}
+
+impl StreamHash {
+    pub fn new(algorithm: ChecksumAlgorithm) -> Self {
+        Self {
+            algorithm,
+            state: Some((algorithm.hasher(), 0)),
+        }
+    }
+
+    /// Bytes hashed so far, from the start of the file.
+    fn position(&self) -> u64 {
+        self.state.as_ref().map_or(0, |(_, position)| *position)
+    }
+
+    /// Hash `data`, read from `offset` in the file, as far as it continues
+    /// what was hashed so far.
+    fn update(&mut self, offset: u64, data: &[u8]) {
+        if let Some((hasher, position)) = &mut self.state {
+            let end = offset + data.len() as u64;
+            if offset <= *position && end > *position {
+                hasher.update(&data[(*position - offset) as usize..]);
+                *position = end;
+            }
+        }
+    }
+
+    /// Read the file at `path` into the hash up to `end` (blocking) and
+    /// return the checksum so far, tagged with the algorithm unless BLAKE3.
+    fn catch_up(&mut self, path: &Path, end: u64) -> Result<String, FluxError> {
+        use std::io::{Read, Seek, SeekFrom};
+
+        let algorithm = self.algorithm;
+        let (hasher, position) = self.state.get_or_insert_with(|| (algorithm.hasher(), 0));
+        if *position < end {
+            let mut file = std::fs::File::open(path).map_err(|e| {
+                FluxError::TransferError(format!("Failed to open '{}': {}", path.display(), e))
+            })?;
+            file.seek(SeekFrom::Start(*position)).map_err(|e| {
+                FluxError::TransferError(format!("Failed to seek '{}': {}", path.display(), e))
+            })?;
+            let mut buf = vec![0u8; CHUNK_SIZE];
+            while *position < end {
+                let want = (end - *position).min(CHUNK_SIZE as u64) as usize;
+                let n = file.read(&mut buf[..want]).map_err(|e| {
+                    FluxError::TransferError(format!("Failed to read '{}': {}", path.display(), e))
+                })?;
+                if n == 0 {
+                    return Err(FluxError::TransferError(format!(
+                        "'{}' shrank while it was being sent",
+                        path.display()
+                    )));
+                }
+                hasher.update(&buf[..n]);
+                *position += n as u64;
+            }
+        }
+        Ok(algorithm.tag(&hasher.finish_hex()))
+    }
+}
+
+/// Bring `hash` up to `end` from disk off the async runtime, sending
+/// Keepalives while it reads, and return the checksum so far. An
+/// interrupted read loses the hash state.
+async fn catch_up_hash(
+    framed: &mut FluxFramed,
+    hash: &mut StreamHash,
+    path: &Path,
+    end: u64,
+) -> Result<String, AttemptError> {
+    let mut owned = StreamHash {
+        algorithm: hash.algorithm,
+        state: hash.state.take(),
+    };
+    let path = path.to_path_buf();
+    let reading = tokio::task::spawn_blocking(move || {
+        let checksum = owned.catch_up(&path, end);
+        (owned, checksum)
+    });
+    let (owned, checksum) = with_keepalives(framed, reading).await?.map_err(|e| {
+        FluxError::TransferError(format!("Hashing task failed: {}", e))
+    })?;
+    *hash = owned;
+    Ok(checksum?)
+}
+
+/// How a file is sent, from the `flux send` flags.
+pub struct SendOptions<'a> {
+    /// Encrypt the session (unless `--no-encrypt`)
+    pub encrypt: bool,
+    /// Name this device announces to the receiver
+    pub device_name: &'a str,
+    /// Exchange a signed delivery receipt (`--receipt`)
+    pub receipt: bool,
+    /// `--limit-up`/`--adaptive-limit`
+    pub limit: RateLimit,
+    /// End-to-end checksum (`--checksum`)
+    pub checksum: ChecksumAlgorithm,
+    /// `--psk-file`: prove this key instead of trust on first use
+    pub psk: Option<&'a PreSharedKey>,
+}
 
 /// Send a file to a remote Flux receiver over TCP.
 ///
@@ -32,31 +230,232 @@ const COMPLETION_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(3
 /// 2. Send Handshake (with optional public key for encryption)
 /// 3. Receive HandshakeAck (reject => error)
 /// 4. If encrypting: complete key exchange to create EncryptedChannel
+///    (bound to the `psk` if given, after which both sides exchange
+///    KeyConfirm)
 /// 5. Send FileHeader with filename and size
 /// 6. Stream DataChunks (encrypted if requested)
-/// 7. Wait for TransferComplete acknowledgement
+/// 7. Send the checksum in a FileFooter, if it was left out of the header
+/// 8. Wait for TransferComplete acknowledgement
+/// 9. With `receipt`, exchange a signed delivery receipt
+///
+/// A BLAKE3 checksum is computed while streaming when the receiver takes it
+/// in a `FileFooter`, so the file is read once; otherwise the file is
+/// hashed before the FileHeader.
+///
+/// If the connection drops after the FileHeader was sent, `target` is
+/// resolved again (an `@device` may have a new address), and the transfer
+/// continues on a new connection from the offset the receiver acknowledges
+/// in its `ResumeAck`. Reconnecting gives up after `RECONNECT_GRACE`
+/// without progress.
+///
+/// With `progress`, the caller owns the terminal (the TUI): bytes are
+/// reported on that bar and nothing is printed.
+///
+/// Chunks are paced by the `limit` (`--limit-up`, `--adaptive-limit`);
+/// adaptive latency probes go to the receiver's listening port. The file is
+/// checked end to end with a `checksum` hash (`--checksum`).
 pub async fn send_file(
+    target: &str,
     host: &str,
     port: u16,
     file_path: &Path,
-    encrypt: bool,
-    device_name: &str,
-) -> Result<(), FluxError> {
+    progress: Option<&indicatif::ProgressBar>,
+    options: &SendOptions<'_>,
+) -> Result<SendReport, FluxError> {
     let started = Instant::now();
+    let mut file = OutgoingFile::stat(file_path)?;
+    let quiet = progress.is_some();
+    let pb = match progress {
+        Some(pb) => {
+            pb.set_length(file.size);
+            pb.clone()
+        }
+        None => create_network_progress(file.size),
+    };
+    let source = file.path.display().to_string();
+    let _status = StatusPublisher::for_bar("send", &source, target, &pb);
+    let _stall = StallNotice::watch(&pb);
+
+    let mut addr = (host.to_string(), port);
+    let mut transfer_started = false;
+    let mut hash = None;
+    let mut window = ReconnectWindow::default();
+    let (mut framed, mut report) = loop {
+        let attempt = send_attempt(
+            (&addr.0, addr.1),
+            &mut file,
+            options,
+            &mut transfer_started,
+            &mut hash,
+            &pb,
+            quiet,
+        )
+        .await;
+        let dropped = match attempt {
+            Ok(done) => break done,
+            Err(AttemptError::Disconnected(e)) if transfer_started => e,
+            Err(e) => {
+                pb.finish_and_clear();
+                return Err(e.into_inner());
+            }
+        };
+
+        if cancel::requested() {
+            pb.finish_and_clear();
+            return Err(FluxError::Cancelled);
+        }
+        if !window.allow_retry(pb.position(), RECONNECT_GRACE) {
+            pb.finish_and_clear();
+            return Err(FluxError::TransferError(format!(
+                "{} (could not resume within {}s)",
+                dropped,
+                RECONNECT_GRACE.as_secs()
+            )));
+        }
+        if !quiet {
+            pb.suspend(|| eprintln!("Connection lost ({}), reconnecting...", dropped));
+        }
+        tokio::time::sleep(RECONNECT_DELAY).await;
+
+        // The device may have come back on a different address
+        let lookup = target.to_string();
+        match tokio::task::spawn_blocking(move || resolve_target(&lookup, quiet)).await {
+            Ok(Ok(resolved)) => addr = resolved,
+            Ok(Err(e)) => tracing::debug!("Rediscovery failed, retrying last address: {}", e),
+            Err(e) => tracing::debug!("Rediscovery task failed: {}", e),
+        }
+    };
+    pb.finish_and_clear();
+
+    if !quiet {
+        print_send_summary(&file, &report, started);
+    }
+    if options.receipt {
+        report.receipt = obtain_receipt(
+            &mut framed,
+            options.device_name,
+            &file.filename,
+            &file.checksum,
+            &report,
+        )
+        .await;
+    }
+
+    Ok(report)
+}
+
+/// One connection attempt of `send_file`.
+///
+/// `transfer_started` is set once the receiver has the FileHeader, after
+/// which later attempts send a ResumeRequest instead. The first attempt
+/// decides how the file is checked: `hash` is set when its checksum goes in
+/// a FileFooter, and kept for the attempts that resume; otherwise the
+/// `checksum` of the options is computed before the FileHeader.
+async fn send_attempt(
+    (host, port): (&str, u16),
+    file: &mut OutgoingFile,
+    options: &SendOptions<'_>,
+    transfer_started: &mut bool,
+    hash: &mut Option<StreamHash>,
+    pb: &indicatif::ProgressBar,
+    quiet: bool,
+) -> Result<(FluxFramed, SendReport), AttemptError> {
+    let SendOptions {
+        encrypt,
+        device_name,
+        limit,
+        checksum: algorithm,
+        psk,
+        ..
+    } = *options;
+    let mut conn = connect(host, port, encrypt, device_name, limit, psk).await?;
+    let fresh = !*transfer_started;
+    if fresh && file.checksum.is_empty() {
+        if algorithm == ChecksumAlgorithm::Blake3 {
+            *hash = Some(StreamHash::new(algorithm));
+        } else {
+            // Only BLAKE3 goes in a FileFooter; other algorithms hash up front
+            *hash = None;
+            let path = file.path.clone();
+            let hashing = tokio::task::spawn_blocking(move || OutgoingFile::open(&path, algorithm));
+            *file = hashing.await.map_err(|e| {
+                FluxError::TransferError(format!("Hashing task failed: {}", e))
+            })??;
+        }
+    }
+    let offset = start_or_resume(
+        &mut conn.framed,
+        file,
+        hash.is_some(),
+        encrypt,
+        transfer_started,
+        quiet,
+    )
+    .await?;
+    let dedup = fresh && conn.chunk_index;
+    let channel = conn.channel.as_ref();
+    let segments = plan_stream(&mut conn.framed, file, offset, dedup, channel, quiet).await?;
+    let outbound = Outbound {
+        chunk_size: conn.chunk_size,
+        channel: conn.channel.as_ref(),
+        zero_copy: conn.zero_copy,
+        limiter: conn.limiter.as_ref(),
+    };
+    stream_chunks(&mut conn.framed, file, &segments, outbound, pb, hash.as_mut()).await?;
+    if let Some(hash) = hash.as_mut() {
+        file.checksum = catch_up_hash(&mut conn.framed, hash, &file.path, file.size).await?;
+        let footer = FluxMessage::FileFooter {
+            checksum: file.checksum.clone(),
+        };
+        send_streamed(&mut conn.framed, &footer, "file checksum").await?;
+    }
+    let peer = addr::join_host_port(host, port);
+    let report = await_completion(&mut conn.framed, peer, file).await?;
+    Ok((conn.framed, report))
+}
+
+/// A connection to a receiver that has accepted our handshake.
+pub(crate) struct Connection {
+    pub framed: FluxFramed,
+    /// Session encryption, when encrypting
+    pub channel: Option<EncryptedChannel>,
+    /// DataChunk size negotiated with the receiver
+    pub chunk_size: usize,
+    /// Whether the receiver keeps a chunk index (`net::chunking`)
+    pub chunk_index: bool,
+    /// Whether to send file data as `RawData` (`net::zerocopy`)
+    pub zero_copy: bool,
+    /// Pacing for `--limit-up`/`--adaptive-limit`
+    pub limiter: Option<ConnectionLimiter>,
+}
 
+/// Connect to the receiver at `host:port` and handshake: key exchange when
+/// encrypting (bound to `psk` and confirmed, if given) and chunk size.
+pub(crate) async fn connect(
+    host: &str,
+    port: u16,
+    encrypt: bool,
+    device_name: &str,
+    limit: RateLimit,
+    psk: Option<&PreSharedKey>,
+) -> Result<Connection, AttemptError> {
     // Connect to the receiver
-    let stream = TcpStream::connect(format!("{}:{}", host, port))
+    let stream = socks::connect(host, port)
         .await
-        .map_err(|e| FluxError::ConnectionFailed {
-            protocol: "flux".to_string(),
-            host: format!("{}:{}", host, port),
-            reason: e.to_string(),
+        .map_err(|e| {
+            AttemptError::Disconnected(FluxError::ConnectionFailed {
+                protocol: "flux".to_string(),
+                host: addr::join_host_port(host, port),
+                reason: e.to_string(),
+            })
         })?;
-
-    let codec = LengthDelimitedCodec::builder()
-        .max_frame_length(MAX_FRAME_SIZE)
-        .new_codec();
-    let mut framed = Framed::new(stream, codec);
+    // Latency probes would bypass a proxy; adaptive limiting is off then
+    let probe = match socks::proxy_for(host) {
+        Some(_) => None,
+        None => stream.peer_addr().ok(),
+    };
+    let limiter = limit.start(probe);
+    let mut framed = new_framed(stream);
 
     // --- Handshake ---
     let (ephemeral_secret, our_public_key) = if encrypt {
@@ -71,31 +470,32 @@ pub async fn send_file(
         device_name: device_name.to_string(),
         public_key: our_public_key,
     };
-    framed
-        .send(Bytes::from(encode_message(&handshake)?))
-        .await
-        .map_err(|e| FluxError::TransferError(format!("Failed to send handshake: {}", e)))?;
+    send_message(&mut framed, &handshake, "handshake").await?;
 
     // Wait for HandshakeAck (with timeout to prevent indefinite stalls)
-    let ack_bytes = tokio::time::timeout(HANDSHAKE_TIMEOUT, framed.next())
-        .await
-        .map_err(|_| FluxError::TransferError("Timed out waiting for handshake response".into()))?
-        .ok_or_else(|| FluxError::TransferError("Connection closed during handshake".into()))?
-        .map_err(|e| FluxError::TransferError(format!("Failed to receive handshake ack: {}", e)))?;
-
-    let ack = decode_message(&ack_bytes)?;
+    let ack = receive_handshake_ack(&mut framed).await?;
+    let chunk_size;
+    let receiver_index;
+    let raw_offered;
     let channel = match ack {
         FluxMessage::HandshakeAck {
             accepted,
             public_key: peer_key,
             reason,
+            max_chunk_size,
+            chunk_index,
+            zero_copy,
         } => {
             if !accepted {
                 return Err(FluxError::TransferError(format!(
                     "Connection rejected: {}",
                     reason.unwrap_or_else(|| "unknown reason".into())
-                )));
+                ))
+                .into());
             }
+            chunk_size = negotiated_chunk_size(max_chunk_size);
+            receiver_index = chunk_index;
+            raw_offered = zero_copy;
             if encrypt {
                 // Complete key exchange
                 let peer_pub_bytes: [u8; 32] = peer_key
@@ -109,153 +509,80 @@ pub async fn send_file(
                         FluxError::EncryptionError("Peer public key must be 32 bytes".into())
                     })?;
                 let peer_public = x25519_dalek::PublicKey::from(peer_pub_bytes);
-                Some(EncryptedChannel::complete(
-                    ephemeral_secret.expect("ephemeral_secret is Some when encrypt is true"),
-                    &peer_public,
-                ))
+                let secret =
+                    ephemeral_secret.expect("ephemeral_secret is Some when encrypt is true");
+                Some(match psk {
+                    Some(psk) => {
+                        EncryptedChannel::complete_with_psk(secret, &peer_public, psk.as_bytes())
+                    }
+                    None => EncryptedChannel::complete(secret, &peer_public),
+                })
             } else {
                 None
             }
         }
         FluxMessage::Error { message } => {
-            return Err(FluxError::TransferError(format!("Peer error: {}", message)));
+            return Err(FluxError::TransferError(format!("Peer error: {}", message)).into());
         }
         _ => {
             return Err(FluxError::TransferError(
                 "Unexpected message during handshake".into(),
-            ));
+            )
+            .into());
         }
     };
 
-    // --- File metadata ---
-    let file_meta = std::fs::metadata(file_path).map_err(|e| {
-        FluxError::TransferError(format!(
-            "Cannot read file '{}': {}",
-            file_path.display(),
-            e
-        ))
-    })?;
-    let file_size = file_meta.len();
-
-    let filename = file_path
-        .file_name()
-        .map(|n| n.to_string_lossy().to_string())
-        .unwrap_or_else(|| "unnamed".to_string());
-
-    // --- Pass 1: Compute BLAKE3 checksum by streaming from disk ---
-    let checksum = {
-        use std::io::Read;
-        let mut file = std::fs::File::open(file_path).map_err(|e| {
-            FluxError::TransferError(format!("Failed to open '{}': {}", file_path.display(), e))
-        })?;
-        let mut hasher = blake3::Hasher::new();
-        let mut buf = vec![0u8; CHUNK_SIZE];
-        loop {
-            let n = file.read(&mut buf).map_err(|e| {
-                FluxError::TransferError(format!("Failed to read '{}': {}", file_path.display(), e))
-            })?;
-            if n == 0 { break; }
-            hasher.update(&buf[..n]);
-        }
-        hasher.finalize().to_hex().to_string()
-    };
-
-    let header = FluxMessage::FileHeader {
-        filename: filename.clone(),
-        size: file_size,
-        checksum: Some(checksum),
-        encrypted: encrypt,
-    };
-    framed
-        .send(Bytes::from(encode_message(&header)?))
-        .await
-        .map_err(|e| FluxError::TransferError(format!("Failed to send file header: {}", e)))?;
-
-    // --- Pass 2: Stream file data in chunks ---
-    let mut offset: u64 = 0;
-    let mut buf = vec![0u8; CHUNK_SIZE];
-
-    let pb = indicatif::ProgressBar::new(file_size);
-    pb.set_style(
-        indicatif::ProgressStyle::with_template(
-            "{spinner:.green} [{bar:40.cyan/blue}] {bytes}/{total_bytes} ({bytes_per_sec})",
-        )
-        .expect("static progress template is valid")
-        .progress_chars("#>-"),
-    );
-    pb.set_draw_target(indicatif::ProgressDrawTarget::stderr());
-
-    {
-        use std::io::Read;
-        let mut file = std::fs::File::open(file_path).map_err(|e| {
-            FluxError::TransferError(format!("Failed to open '{}': {}", file_path.display(), e))
-        })?;
-        loop {
-            let n = file.read(&mut buf).map_err(|e| {
-                FluxError::TransferError(format!("Failed to read '{}': {}", file_path.display(), e))
-            })?;
-            if n == 0 { break; }
-
-            let raw_data = &buf[..n];
-            let (data, nonce) = if let Some(ref ch) = channel {
-                let (ct, nc) = ch.encrypt(raw_data)?;
-                (ct, Some(nc.to_vec()))
-            } else {
-                (raw_data.to_vec(), None)
-            };
-
-            let chunk_msg = FluxMessage::DataChunk {
-                offset,
-                data,
-                nonce,
-            };
-            framed
-                .send(Bytes::from(encode_message(&chunk_msg)?))
-                .await
-                .map_err(|e| FluxError::TransferError(format!("Failed to send data chunk: {}", e)))?;
-
-            offset += n as u64;
-            pb.set_position(offset);
-        }
+    if let (Some(psk), Some(channel)) = (psk, channel.as_ref()) {
+        confirm_psk(&mut framed, channel, psk).await?;
     }
+    // Raw file bytes only make sense on a plaintext stream
+    let zero_copy = raw_offered && channel.is_none() && zerocopy::enabled();
+    if zero_copy {
+        tracing::debug!("Receiver accepts raw data, sending without copying");
+    }
+    Ok(Connection {
+        framed,
+        channel,
+        chunk_size,
+        chunk_index: receiver_index,
+        zero_copy,
+        limiter,
+    })
+}
 
-    pb.finish_and_clear();
-
-    // --- Wait for TransferComplete (with timeout) ---
-    let complete_bytes = tokio::time::timeout(COMPLETION_TIMEOUT, framed.next())
-        .await
-        .map_err(|_| FluxError::TransferError("Timed out waiting for transfer confirmation".into()))?
-        .ok_or_else(|| {
-            FluxError::TransferError("Connection closed before transfer complete".into())
-        })?
-        .map_err(|e| {
-            FluxError::TransferError(format!("Failed to receive transfer complete: {}", e))
-        })?;
-
-    let complete = decode_message(&complete_bytes)?;
-    match complete {
-        FluxMessage::TransferComplete {
-            bytes_received, ..
-        } => {
-            let mut stats = TransferStats::new(1, file_size);
-            stats.started = started;
-            stats.add_done(bytes_received);
-            stats.print_file_summary(&filename, false);
-        }
-        FluxMessage::Error { message } => {
-            return Err(FluxError::TransferError(format!(
-                "Receiver error: {}",
-                message
-            )));
-        }
-        _ => {
-            return Err(FluxError::TransferError(
-                "Unexpected message after data transfer".into(),
-            ));
+/// Prove to the receiver that we hold the pre-shared key, then check its
+/// proof. A receiver without the same key ends the transfer.
+async fn confirm_psk(
+    framed: &mut FluxFramed,
+    channel: &EncryptedChannel,
+    psk: &PreSharedKey,
+) -> Result<(), AttemptError> {
+    let (proof, nonce) = psk::make_proof(channel, PskRole::Sender)?;
+    let confirm = FluxMessage::KeyConfirm {
+        proof,
+        nonce: nonce.to_vec(),
+    };
+    send_message(framed, &confirm, "key confirmation").await?;
+    match receive_handshake_ack(framed).await? {
+        FluxMessage::KeyConfirm { proof, nonce }
+            if psk::check_proof(channel, PskRole::Receiver, &proof, &nonce) =>
+        {
+            Ok(())
         }
+        FluxMessage::KeyConfirm { .. } => Err(FluxError::TrustError(format!(
+            "Receiver does not hold pre-shared key {}",
+            psk.id()
+        ))
+        .into()),
+        FluxMessage::Error { message } => Err(FluxError::TrustError(format!(
+            "Receiver refused pre-shared key {}: {}",
+            psk.id(),
+            message
+        ))
+        .into()),
+        _ => Err(FluxError::TransferError("Unexpected message during key confirmation".into())
+            .into()),
     }
-
-    Ok(())
 }
 
 /// Send a file using code-phrase mode (Croc-like UX).
@@ -267,16 +594,26 @@ pub async fn send_file(
 /// 4. Print code phrase and wait for receiver
 /// 5. Accept one connection, perform encrypted transfer
 ///
-/// Always encrypted -- no `--encrypt` flag needed.
+/// Always encrypted -- no `--encrypt` flag needed. With `receipt`, a signed
+/// delivery receipt is exchanged after the transfer.
+///
+/// If the connection drops mid-transfer, the listener and mDNS registration
+/// stay up for `RECONNECT_GRACE` so the receiver can find the code again
+/// (possibly at a new address) and resume.
+///
+/// `limit` caps the upload rate. The receiver has no listening port to probe
+/// here, so adaptive limiting does not apply.
 pub async fn send_with_code(
     file_path: &Path,
     device_name: &str,
     code_override: Option<&str>,
-) -> Result<(), FluxError> {
+    receipt: bool,
+    limit: RateLimit,
+    checksum: ChecksumAlgorithm,
+) -> Result<SendReport, FluxError> {
     use crate::discovery::mdns::register_flux_service;
     use crate::discovery::service::FluxService;
     use crate::net::codephrase;
-    use tokio::net::TcpListener;
 
     let started = Instant::now();
 
@@ -294,41 +631,10 @@ pub async fn send_with_code(
             path: file_path.to_path_buf(),
         });
     }
+    let file = OutgoingFile::open(file_path, checksum)?;
 
-    let file_meta = std::fs::metadata(file_path).map_err(|e| {
-        FluxError::TransferError(format!(
-            "Cannot read file '{}': {}",
-            file_path.display(),
-            e
-        ))
-    })?;
-    let file_size = file_meta.len();
-
-    let filename = file_path
-        .file_name()
-        .map(|n| n.to_string_lossy().to_string())
-        .unwrap_or_else(|| "unnamed".to_string());
-
-    // Compute BLAKE3 checksum by streaming from disk (no full-file buffering)
-    let checksum = {
-        use std::io::Read;
-        let mut file = std::fs::File::open(file_path).map_err(|e| {
-            FluxError::TransferError(format!("Failed to open '{}': {}", file_path.display(), e))
-        })?;
-        let mut hasher = blake3::Hasher::new();
-        let mut buf = vec![0u8; CHUNK_SIZE];
-        loop {
-            let n = file.read(&mut buf).map_err(|e| {
-                FluxError::TransferError(format!("Failed to read '{}': {}", file_path.display(), e))
-            })?;
-            if n == 0 { break; }
-            hasher.update(&buf[..n]);
-        }
-        hasher.finalize().to_hex().to_string()
-    };
-
-    // Bind TCP on port 0 (OS-assigned)
-    let listener = TcpListener::bind("0.0.0.0:0")
+    // Bind TCP on port 0 (OS-assigned), IPv4 and IPv6
+    let listener = addr::listen("::", 0)
         .await
         .map_err(|e| FluxError::TransferError(format!("Failed to bind TCP listener: {}", e)))?;
 
@@ -337,72 +643,160 @@ pub async fn send_with_code(
     })?;
     let actual_port = local_addr.port();
 
-    // Generate ephemeral X25519 keypair (always encrypted in code mode)
-    let (ephemeral_secret, our_public) = EncryptedChannel::initiate();
-    let our_pub_bytes = our_public.as_bytes().to_vec();
-
     // Register mDNS with code_hash TXT property
     let hash = codephrase::code_hash(&code);
     let service = FluxService::new(Some(device_name.to_string()), actual_port);
     let _mdns_daemon = register_flux_service(&service, None, Some(&hash))?;
 
     // Print code phrase and instructions
-    let human_size = bytesize::ByteSize(file_size).to_string();
+    let human_size = bytesize::ByteSize(file.size).to_string();
     eprintln!("Code phrase: {}", code);
     eprintln!("On the other device run:");
     eprintln!("  flux receive {}", code);
     eprintln!(
         "Sending {} ({}) - waiting for receiver...",
-        filename, human_size
+        file.filename, human_size
     );
 
-    // Accept one connection (with timeout)
-    let (stream, peer_addr) = tokio::time::timeout(
-        std::time::Duration::from_secs(5 * 60),
-        listener.accept(),
-    )
-    .await
-    .map_err(|_| FluxError::TransferError("Timed out waiting for receiver (5 minutes)".into()))?
-    .map_err(|e| FluxError::TransferError(format!("Failed to accept connection: {}", e)))?;
+    let pb = create_network_progress(file.size);
+    pb.set_draw_target(indicatif::ProgressDrawTarget::hidden());
+    let source = file.path.display().to_string();
+    let _status = StatusPublisher::for_bar("send", &source, "code phrase receiver", &pb);
+
+    let mut transfer_started = false;
+    let mut window = ReconnectWindow::default();
+    let (mut framed, mut report) = loop {
+        // Accept one connection (with timeout); after a drop, only wait as
+        // long as the reconnect grace window
+        let wait = if transfer_started {
+            RECONNECT_GRACE
+        } else {
+            std::time::Duration::from_secs(5 * 60)
+        };
+        let accepted = tokio::select! {
+            accepted = tokio::time::timeout(wait, listener.accept()) => accepted,
+            _ = cancel::cancelled() => {
+                pb.finish_and_clear();
+                return Err(FluxError::Cancelled);
+            }
+        };
+        let (stream, peer_addr) = accepted
+            .map_err(|_| {
+                pb.finish_and_clear();
+                if transfer_started {
+                    FluxError::TransferError(format!(
+                        "Receiver did not reconnect within {}s",
+                        RECONNECT_GRACE.as_secs()
+                    ))
+                } else {
+                    FluxError::TransferError("Timed out waiting for receiver (5 minutes)".into())
+                }
+            })?
+            .map_err(|e| FluxError::TransferError(format!("Failed to accept connection: {}", e)))?;
+
+        let peer_addr = addr::canonical(peer_addr);
+        tracing::debug!("Connection from {}", peer_addr);
+        pb.set_draw_target(stderr_target());
+
+        let attempt = code_attempt(
+            (stream, peer_addr.to_string()),
+            &file,
+            device_name,
+            &code,
+            &mut transfer_started,
+            &pb,
+            limit,
+        )
+        .await;
+        let dropped = match attempt {
+            Ok(done) => break done,
+            Err(AttemptError::Disconnected(e)) if transfer_started => e,
+            Err(e) => {
+                pb.finish_and_clear();
+                return Err(e.into_inner());
+            }
+        };
 
-    tracing::debug!("Connection from {}", peer_addr);
+        if !window.allow_retry(pb.position(), RECONNECT_GRACE) {
+            pb.finish_and_clear();
+            return Err(FluxError::TransferError(format!(
+                "{} (could not resume within {}s)",
+                dropped,
+                RECONNECT_GRACE.as_secs()
+            )));
+        }
+        pb.suspend(|| {
+            eprintln!(
+                "Connection lost ({}), waiting for the receiver to reconnect...",
+                dropped
+            )
+        });
+    };
+    pb.finish_and_clear();
 
-    let codec = LengthDelimitedCodec::builder()
-        .max_frame_length(MAX_FRAME_SIZE)
-        .new_codec();
-    let mut framed = Framed::new(stream, codec);
+    print_send_summary(&file, &report, started);
+    if receipt {
+        report.receipt = obtain_receipt(
+            &mut framed,
+            device_name,
+            &file.filename,
+            &file.checksum,
+            &report,
+        )
+        .await;
+    }
+
+    Ok(report)
+}
+
+/// One accepted connection of `send_with_code` (the stream and the peer's
+/// address).
+async fn code_attempt(
+    (stream, peer): (TcpStream, String),
+    file: &OutgoingFile,
+    device_name: &str,
+    code: &str,
+    transfer_started: &mut bool,
+    pb: &indicatif::ProgressBar,
+    limit: RateLimit,
+) -> Result<(FluxFramed, SendReport), AttemptError> {
+    let limiter = limit.start(None);
+    let mut framed = new_framed(stream);
+
+    // Generate ephemeral X25519 keypair (always encrypted in code mode)
+    let (ephemeral_secret, our_public) = EncryptedChannel::initiate();
 
     // Send Handshake with public key
     let handshake = FluxMessage::Handshake {
         version: PROTOCOL_VERSION,
         device_name: device_name.to_string(),
-        public_key: Some(our_pub_bytes),
+        public_key: Some(our_public.as_bytes().to_vec()),
     };
-    framed
-        .send(Bytes::from(encode_message(&handshake)?))
-        .await
-        .map_err(|e| FluxError::TransferError(format!("Failed to send handshake: {}", e)))?;
+    send_message(&mut framed, &handshake, "handshake").await?;
 
     // Wait for HandshakeAck (with timeout)
-    let ack_bytes = tokio::time::timeout(HANDSHAKE_TIMEOUT, framed.next())
-        .await
-        .map_err(|_| FluxError::TransferError("Timed out waiting for handshake response".into()))?
-        .ok_or_else(|| FluxError::TransferError("Connection closed during handshake".into()))?
-        .map_err(|e| FluxError::TransferError(format!("Failed to receive handshake ack: {}", e)))?;
-
-    let ack = decode_message(&ack_bytes)?;
+    let ack = receive_handshake_ack(&mut framed).await?;
+    let chunk_size;
+    let receiver_index;
     let channel = match ack {
         FluxMessage::HandshakeAck {
             accepted,
             public_key: peer_key,
             reason,
+            max_chunk_size,
+            chunk_index,
+            ..
         } => {
             if !accepted {
                 return Err(FluxError::TransferError(format!(
                     "Connection rejected: {}",
                     reason.unwrap_or_else(|| "unknown reason".into())
-                )));
+                ))
+                .into());
             }
+            // Honour a smaller chunk size requested by low-memory receivers
+            chunk_size = negotiated_chunk_size(max_chunk_size);
+            receiver_index = chunk_index;
             let peer_pub_bytes: [u8; 32] = peer_key
                 .ok_or_else(|| {
                     FluxError::EncryptionError(
@@ -415,131 +809,608 @@ pub async fn send_with_code(
                 })?;
             let peer_public = x25519_dalek::PublicKey::from(peer_pub_bytes);
             // Bind code phrase to key exchange (PAKE-like authentication)
-            EncryptedChannel::complete_with_code(ephemeral_secret, &peer_public, &code)
+            EncryptedChannel::complete_with_code(ephemeral_secret, &peer_public, code)
         }
         FluxMessage::Error { message } => {
-            return Err(FluxError::TransferError(format!("Peer error: {}", message)));
+            return Err(FluxError::TransferError(format!("Peer error: {}", message)).into());
         }
         _ => {
             return Err(FluxError::TransferError(
                 "Unexpected message during handshake".into(),
-            ));
+            )
+            .into());
         }
     };
 
-    // Send FileHeader
-    let header = FluxMessage::FileHeader {
-        filename: filename.clone(),
-        size: file_size,
-        checksum: Some(checksum),
-        encrypted: true,
+    let fresh = !*transfer_started;
+    let offset = start_or_resume(&mut framed, file, false, true, transfer_started, false).await?;
+    let dedup = fresh && receiver_index;
+    let segments = plan_stream(&mut framed, file, offset, dedup, Some(&channel), false).await?;
+    let outbound = Outbound {
+        chunk_size,
+        channel: Some(&channel),
+        zero_copy: false,
+        limiter: limiter.as_ref(),
     };
-    framed
-        .send(Bytes::from(encode_message(&header)?))
+    stream_chunks(&mut framed, file, &segments, outbound, pb, None).await?;
+    let report = await_completion(&mut framed, peer, file).await?;
+    Ok((framed, report))
+}
+
+fn new_framed(stream: TcpStream) -> FluxFramed {
+    let codec = LengthDelimitedCodec::builder()
+        .max_frame_length(MAX_FRAME_SIZE)
+        .new_codec();
+    Framed::new(stream, codec)
+}
+
+
+/// Send one message. A failed or stalled send means the connection is gone.
+pub(crate) async fn send_message(
+    framed: &mut FluxFramed,
+    msg: &FluxMessage,
+    what: &str,
+) -> Result<(), AttemptError> {
+    let frame = Bytes::from(encode_message(msg)?);
+    match tokio::time::timeout(stall_timeout(), framed.send(frame)).await {
+        Ok(Ok(())) => Ok(()),
+        Ok(Err(e)) => Err(AttemptError::Disconnected(FluxError::TransferError(format!(
+            "Failed to send {}: {}",
+            what, e
+        )))),
+        Err(_) => Err(AttemptError::Disconnected(FluxError::TransferError(format!(
+            "Timed out sending {}",
+            what
+        )))),
+    }
+}
+
+/// Wait for the receiver's HandshakeAck (or Error).
+async fn receive_handshake_ack(framed: &mut FluxFramed) -> Result<FluxMessage, AttemptError> {
+    let disconnected = |reason: String| AttemptError::Disconnected(FluxError::TransferError(reason));
+    let ack_bytes = tokio::time::timeout(HANDSHAKE_TIMEOUT, framed.next())
         .await
-        .map_err(|e| FluxError::TransferError(format!("Failed to send file header: {}", e)))?;
+        .map_err(|_| disconnected("Timed out waiting for handshake response".into()))?
+        .ok_or_else(|| disconnected("Connection closed during handshake".into()))?
+        .map_err(|e| disconnected(format!("Failed to receive handshake ack: {}", e)))?;
+    Ok(decode_handshake_ack(&ack_bytes)?)
+}
+
+/// Send the FileHeader, or after a reconnect a ResumeRequest, and return the
+/// offset to stream from. With `footer`, the checksum follows the data in a
+/// FileFooter.
+async fn start_or_resume(
+    framed: &mut FluxFramed,
+    file: &OutgoingFile,
+    footer: bool,
+    encrypted: bool,
+    transfer_started: &mut bool,
+    quiet: bool,
+) -> Result<u64, AttemptError> {
+    if !*transfer_started {
+        let header = FluxMessage::FileHeader {
+            filename: file.filename.clone(),
+            size: file.size,
+            checksum: (!footer).then(|| file.checksum.clone()),
+            encrypted,
+            footer,
+        };
+        send_message(framed, &header, "file header").await?;
+        *transfer_started = true;
+        return Ok(0);
+    }
 
-    // Stream encrypted DataChunks from disk (no full-file buffering)
-    let mut offset: u64 = 0;
-    let mut buf = vec![0u8; CHUNK_SIZE];
+    let request = FluxMessage::ResumeRequest {
+        filename: file.filename.clone(),
+        size: file.size,
+        checksum: match footer {
+            true => footer_key(&file.filename),
+            false => file.checksum.clone(),
+        },
+        encrypted,
+    };
+    send_message(framed, &request, "resume request").await?;
 
-    let pb = indicatif::ProgressBar::new(file_size);
-    pb.set_style(
-        indicatif::ProgressStyle::with_template(
-            "{spinner:.green} [{bar:40.cyan/blue}] {bytes}/{total_bytes} ({bytes_per_sec})",
-        )
-        .expect("static progress template is valid")
-        .progress_chars("#>-"),
-    );
-    pb.set_draw_target(indicatif::ProgressDrawTarget::stderr());
+    let reply = tokio::time::timeout(HANDSHAKE_TIMEOUT, framed.next()).await;
+    let reply_bytes = match reply {
+        Ok(Some(Ok(bytes))) => bytes,
+        _ => {
+            return Err(AttemptError::Disconnected(FluxError::TransferError(
+                "No answer to resume request".into(),
+            )))
+        }
+    };
+    match decode_message(&reply_bytes)? {
+        FluxMessage::ResumeAck { offset } if offset <= file.size => {
+            if !quiet {
+                if offset > 0 {
+                    eprintln!("Resuming at {}", bytesize::ByteSize(offset));
+                } else {
+                    eprintln!("Receiver lost the partial file, starting over");
+                }
+            }
+            Ok(offset)
+        }
+        FluxMessage::Error { message } => Err(FluxError::TransferError(format!(
+            "Receiver cannot resume: {}",
+            message
+        ))
+        .into()),
+        _ => Err(FluxError::TransferError("Unexpected answer to resume request".into()).into()),
+    }
+}
+
+/// Plan what to stream from `offset`: the rest of the file as data, or,
+/// when `dedup` (a fresh transfer to a receiver with a chunk index) and the
+/// file is large enough, only the chunks the receiver does not have yet
+/// (see `net::chunking`).
+async fn plan_stream(
+    framed: &mut FluxFramed,
+    file: &OutgoingFile,
+    offset: u64,
+    dedup: bool,
+    channel: Option<&EncryptedChannel>,
+    quiet: bool,
+) -> Result<Vec<Segment>, AttemptError> {
+    if !dedup || file.size < chunking::MIN_FILE_SIZE {
+        return Ok(vec![Segment::Data {
+            offset,
+            len: file.size - offset,
+        }]);
+    }
+
+    // Cutting a large file takes a while; the receiver is already waiting
+    let path = file.path.clone();
+    let cutting = tokio::task::spawn_blocking(move || chunking::chunk_file(&path));
+    let chunks = with_keepalives(framed, cutting).await?.map_err(|e| {
+        FluxError::TransferError(format!("Chunking task failed: {}", e))
+    })??;
+    let batches = chunks.chunks(chunking::LIST_BATCH);
+    let count = batches.len();
+    for (i, batch) in batches.enumerate() {
+        let list = chunking::list_message(batch, i + 1 == count, channel)?;
+        send_message(framed, &list, "chunk list").await?;
+    }
+    let reply = match tokio::time::timeout(HANDSHAKE_TIMEOUT, framed.next()).await {
+        Ok(Some(Ok(bytes))) => bytes,
+        _ => {
+            return Err(AttemptError::Disconnected(FluxError::TransferError(
+                "No answer to chunk list".into(),
+            )))
+        }
+    };
+    let have = match decode_message(&reply)? {
+        FluxMessage::ChunksHave { data, nonce } => {
+            chunking::read_have(data, nonce, channel, chunks.len())?
+        }
+        other => return Err(receiver_stopped(other).into()),
+    };
+
+    let segments = chunking::segments(&chunks, &have);
+    let reused = chunking::reused_bytes(&segments);
+    if reused > 0 && !quiet {
+        eprintln!(
+            "Receiver already has {} of {}, sending the rest",
+            bytesize::ByteSize(reused),
+            bytesize::ByteSize(file.size)
+        );
+    }
+    Ok(segments)
+}
 
-    {
-        use std::io::Read;
-        let mut file = std::fs::File::open(file_path).map_err(|e| {
-            FluxError::TransferError(format!("Failed to open '{}': {}", file_path.display(), e))
+/// How file data goes out on a connection.
+#[derive(Clone, Copy)]
+pub(crate) struct Outbound<'a> {
+    /// DataChunk size negotiated in the handshake
+    pub chunk_size: usize,
+    /// Session encryption, when encrypting
+    pub channel: Option<&'a EncryptedChannel>,
+    /// Send RawData frames followed by the file bytes
+    pub zero_copy: bool,
+    /// Pacing for `--limit-up`/`--adaptive-limit`
+    pub limiter: Option<&'a ConnectionLimiter>,
+}
+
+/// Stream the planned `segments` of the file as `outbound` says: data as
+/// DataChunks, encrypted when there is a channel and paced by the limiter,
+/// and chunks the receiver has as ChunkRefs. With `zero_copy`, data goes
+/// out as RawData frames followed by the file bytes.
+///
+/// With a `hash`, the data read for DataChunks is hashed on the way; what
+/// is not read here is left for the caller to catch up on.
+pub(crate) async fn stream_chunks(
+    framed: &mut FluxFramed,
+    file: &OutgoingFile,
+    segments: &[Segment],
+    outbound: Outbound<'_>,
+    pb: &indicatif::ProgressBar,
+    mut hash: Option<&mut StreamHash>,
+) -> Result<(), AttemptError> {
+    use std::io::{Read, Seek, SeekFrom};
+
+    let Outbound {
+        chunk_size,
+        channel,
+        zero_copy,
+        limiter,
+    } = outbound;
+
+    let mut reader = std::fs::File::open(&file.path).map_err(|e| {
+        FluxError::TransferError(format!("Failed to open '{}': {}", file.path.display(), e))
+    })?;
+    let mut buf = vec![0u8; chunk_size];
+    for segment in segments {
+        let (mut offset, end) = match *segment {
+            Segment::Ref { offset, index, len } => {
+                check_stream(framed).await?;
+                let reference = FluxMessage::ChunkRef { offset, index };
+                send_streamed(framed, &reference, "chunk reference").await?;
+                pb.set_position(offset + len as u64);
+                continue;
+            }
+            Segment::Data { offset, len } => (offset, offset + len),
+        };
+        // Hashing must continue where this segment starts
+        if let Some(hash) = hash.as_deref_mut() {
+            if !zero_copy && hash.position() < offset {
+                catch_up_hash(framed, hash, &file.path, offset).await?;
+            }
+        }
+        reader.seek(SeekFrom::Start(offset)).map_err(|e| {
+            FluxError::TransferError(format!("Failed to seek '{}': {}", file.path.display(), e))
         })?;
-        loop {
-            let n = file.read(&mut buf).map_err(|e| {
-                FluxError::TransferError(format!("Failed to read '{}': {}", file_path.display(), e))
+        pb.set_position(offset);
+
+        while offset < end {
+            check_stream(framed).await?;
+
+            let want = (end - offset).min(chunk_size as u64) as usize;
+            if zero_copy {
+                send_raw(framed, file, &reader, offset, want).await?;
+                pace(framed, limiter, want as u64).await?;
+                offset += want as u64;
+                pb.set_position(offset);
+                continue;
+            }
+            let n = reader.read(&mut buf[..want]).map_err(|e| {
+                FluxError::TransferError(format!("Failed to read '{}': {}", file.path.display(), e))
             })?;
-            if n == 0 { break; }
+            if n == 0 {
+                return Err(FluxError::TransferError(format!(
+                    "'{}' shrank while it was being sent",
+                    file.path.display()
+                ))
+                .into());
+            }
 
             let raw_data = &buf[..n];
-            let (data, nonce) = channel.encrypt(raw_data)?;
+            if let Some(hash) = hash.as_deref_mut() {
+                hash.update(offset, raw_data);
+            }
+            let (data, nonce) = if let Some(ch) = channel {
+                let (ct, nc) = ch.encrypt(raw_data)?;
+                (ct, Some(nc.to_vec()))
+            } else {
+                (raw_data.to_vec(), None)
+            };
 
             let chunk_msg = FluxMessage::DataChunk {
                 offset,
                 data,
-                nonce: Some(nonce.to_vec()),
+                nonce,
             };
-            framed
-                .send(Bytes::from(encode_message(&chunk_msg)?))
-                .await
-                .map_err(|e| FluxError::TransferError(format!("Failed to send data chunk: {}", e)))?;
+            send_streamed(framed, &chunk_msg, "data chunk").await?;
+            pace(framed, limiter, n as u64).await?;
 
             offset += n as u64;
             pb.set_position(offset);
         }
     }
+    Ok(())
+}
 
-    pb.finish_and_clear();
+/// Send `len` bytes of the file at `offset` as a RawData frame followed by
+/// the bytes themselves, handed from the page cache to the socket.
+async fn send_raw(
+    framed: &mut FluxFramed,
+    file: &OutgoingFile,
+    reader: &std::fs::File,
+    offset: u64,
+    len: usize,
+) -> Result<(), AttemptError> {
+    let raw = FluxMessage::RawData {
+        offset,
+        len: len as u32,
+    };
+    send_streamed(framed, &raw, "data chunk").await?;
+    let sent = zerocopy::send_file_range(framed.get_ref(), reader, offset, len);
+    match tokio::time::timeout(stall_timeout(), sent).await {
+        Ok(Ok(())) => Ok(()),
+        Ok(Err(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
+            Err(FluxError::TransferError(format!(
+                "'{}' shrank while it was being sent",
+                file.path.display()
+            ))
+            .into())
+        }
+        Ok(Err(e)) => Err(AttemptError::Disconnected(FluxError::TransferError(format!(
+            "Failed to send data chunk: {}",
+            e
+        )))),
+        Err(_) => Err(AttemptError::Disconnected(FluxError::TransferError(
+            "Timed out sending data chunk".into(),
+        ))),
+    }
+}
 
-    // Wait for TransferComplete (with timeout)
-    let complete_bytes = tokio::time::timeout(COMPLETION_TIMEOUT, framed.next())
-        .await
-        .map_err(|_| FluxError::TransferError("Timed out waiting for transfer confirmation".into()))?
-        .ok_or_else(|| {
-            FluxError::TransferError("Connection closed before transfer complete".into())
-        })?
-        .map_err(|e| {
-            FluxError::TransferError(format!("Failed to receive transfer complete: {}", e))
-        })?;
+/// Before each message of the stream: stop on Ctrl+C (telling the receiver)
+/// or when the receiver has cancelled or failed.
+async fn check_stream(framed: &mut FluxFramed) -> Result<(), AttemptError> {
+    if cancel::requested() {
+        send_cancel(framed).await;
+        return Err(FluxError::Cancelled.into());
+    }
+    check_receiver(framed)
+}
 
-    let complete = decode_message(&complete_bytes)?;
-    match complete {
-        FluxMessage::TransferComplete {
-            bytes_received, ..
-        } => {
-            let mut stats = TransferStats::new(1, file_size);
-            stats.started = started;
-            stats.add_done(bytes_received);
-            stats.print_file_summary(&filename, false);
+/// Send one message of the stream.
+async fn send_streamed(
+    framed: &mut FluxFramed,
+    msg: &FluxMessage,
+    what: &str,
+) -> Result<(), AttemptError> {
+    if let Err(e) = send_message(framed, msg, what).await {
+        // A cancelled receiver says so before closing the connection
+        check_receiver(framed)?;
+        return Err(e);
+    }
+    Ok(())
+}
+
+/// Account for `bytes` just sent with `limiter`, if any, sending Keepalives
+/// while it holds the stream back.
+pub(crate) async fn pace(
+    framed: &mut FluxFramed,
+    limiter: Option<&ConnectionLimiter>,
+    bytes: u64,
+) -> Result<(), AttemptError> {
+    match limiter {
+        Some(limiter) => with_keepalives(framed, limiter.consume(bytes)).await,
+        None => Ok(()),
+    }
+}
+
+/// Wait for `work`, sending a Keepalive every `keepalive_interval` until it
+/// is done, so the receiver does not take the quiet connection for a stall.
+pub(crate) async fn with_keepalives<T>(
+    framed: &mut FluxFramed,
+    work: impl std::future::Future<Output = T>,
+) -> Result<T, AttemptError> {
+    tokio::pin!(work);
+    loop {
+        tokio::select! {
+            done = &mut work => return Ok(done),
+            _ = tokio::time::sleep(keepalive_interval()) => {
+                send_streamed(framed, &FluxMessage::Keepalive, "keepalive").await?;
+            }
+        }
+    }
+}
+
+/// Tell the receiver the transfer was cancelled, so it deletes its partial
+/// file. Best effort: the transfer ends either way.
+pub(crate) async fn send_cancel(framed: &mut FluxFramed) {
+    let cancel = FluxMessage::Cancel {
+        reason: CANCEL_REASON.to_string(),
+    };
+    if let Ok(frame) = encode_message(&cancel) {
+        let _ = tokio::time::timeout(CANCEL_TIMEOUT, framed.send(Bytes::from(frame))).await;
+    }
+}
+
+/// Stop streaming if the receiver has sent something: mid-transfer it only
+/// does so to cancel or to report an error.
+pub(crate) fn check_receiver(framed: &mut FluxFramed) -> Result<(), AttemptError> {
+    match framed.next().now_or_never() {
+        Some(Some(Ok(bytes))) => Err(receiver_stopped(decode_message(&bytes)?).into()),
+        _ => Ok(()),
+    }
+}
+
+/// Error for a receiver that answered with something other than
+/// `TransferComplete`.
+fn receiver_stopped(msg: FluxMessage) -> FluxError {
+    match msg {
+        FluxMessage::Cancel { reason } => {
+            FluxError::TransferError(format!("Receiver cancelled the transfer ({})", reason))
         }
         FluxMessage::Error { message } => {
-            return Err(FluxError::TransferError(format!(
-                "Receiver error: {}",
-                message
-            )));
+            FluxError::TransferError(format!("Receiver error: {}", message))
         }
-        _ => {
+        _ => FluxError::TransferError("Unexpected message from the receiver".into()),
+    }
+}
+
+/// Wait for TransferComplete (with timeout) and build the report.
+///
+/// The receiver's checksum is compared against the file's: a mismatch is
+/// `FluxError::ChecksumMismatch`, and a match counts as verified whatever
+/// the receiver's own verdict. A receiver that sends none is refused.
+pub(crate) async fn await_completion(
+    framed: &mut FluxFramed,
+    peer: String,
+    file: &OutgoingFile,
+) -> Result<SendReport, AttemptError> {
+    let complete_bytes = match tokio::time::timeout(COMPLETION_TIMEOUT, framed.next()).await {
+        Err(_) => {
             return Err(FluxError::TransferError(
-                "Unexpected message after data transfer".into(),
-            ));
+                "Timed out waiting for transfer confirmation".into(),
+            )
+            .into())
+        }
+        Ok(None) => {
+            return Err(AttemptError::Disconnected(FluxError::TransferError(
+                "Connection closed before transfer complete".into(),
+            )))
+        }
+        Ok(Some(Err(e))) => {
+            return Err(AttemptError::Disconnected(FluxError::TransferError(format!(
+                "Failed to receive transfer complete: {}",
+                e
+            ))))
+        }
+        Ok(Some(Ok(bytes))) => bytes,
+    };
+
+    match decode_message(&complete_bytes)? {
+        FluxMessage::TransferComplete {
+            bytes_received,
+            checksum,
+            ..
+        } => {
+            let Some(actual) = checksum else {
+                return Err(FluxError::TransferError(
+                    "Receiver did not return its checksum".into(),
+                )
+                .into());
+            };
+            if actual != file.checksum {
+                return Err(FluxError::ChecksumMismatch {
+                    path: file.path.clone(),
+                    expected: file.checksum.clone(),
+                    actual,
+                }
+                .into());
+            }
+            Ok(SendReport {
+                bytes: bytes_received,
+                checksum_verified: Some(true),
+                peer,
+                receipt: None,
+            })
         }
+        other => Err(receiver_stopped(other).into()),
     }
+}
 
-    Ok(())
+fn print_send_summary(file: &OutgoingFile, report: &SendReport, started: Instant) {
+    let mut stats = TransferStats::new(1, file.size);
+    stats.started = started;
+    stats.add_done(report.bytes);
+    stats.print_file_summary(&file.filename, false);
 }
 
 /// Synchronous wrapper for code-phrase send mode.
+///
+/// Records the outcome in transfer history.
 pub fn send_with_code_sync(
     file_path: &Path,
     device_name: &str,
     code_override: Option<&str>,
+    receipt: bool,
+    limit: RateLimit,
+    checksum: ChecksumAlgorithm,
 ) -> Result<(), FluxError> {
     let rt = tokio::runtime::Runtime::new()
         .map_err(|e| FluxError::TransferError(format!("Failed to create async runtime: {}", e)))?;
 
-    rt.block_on(send_with_code(file_path, device_name, code_override))
+    let mut record = HistoryRecord::new("send", &file_path.display().to_string(), "code-phrase");
+    let result = rt.block_on(send_with_code(
+        file_path,
+        device_name,
+        code_override,
+        receipt,
+        limit,
+        checksum,
+    ));
+    finish_send_record(&mut record, &result);
+    result.map(|_| ())
+}
+
+/// Have the receiver countersign a delivery receipt.
+///
+/// The file is already delivered at this point, so a missing receipt is
+/// reported but does not fail the send.
+async fn obtain_receipt(
+    framed: &mut FluxFramed,
+    device_name: &str,
+    filename: &str,
+    checksum: &str,
+    report: &SendReport,
+) -> Option<TransferReceipt> {
+    if report.checksum_verified != Some(true) {
+        eprintln!("No receipt: the receiver did not verify the checksum");
+        return None;
+    }
+    let result = match flux_config_dir() {
+        Ok(config_dir) => {
+            request_receipt(framed, &config_dir, device_name, filename, report.bytes, checksum)
+                .await
+        }
+        Err(e) => Err(e),
+    };
+    match result {
+        Ok(Some(receipt)) => {
+            if let Some(ref receiver) = receipt.receiver {
+                eprintln!("Receipt countersigned by {}", receiver.device);
+            }
+            Some(receipt)
+        }
+        Ok(None) => {
+            eprintln!("No receipt: the receiver does not support receipts");
+            None
+        }
+        Err(e) => {
+            eprintln!("No receipt: {}", e);
+            None
+        }
+    }
+}
+
+/// Fill a send history record from the transfer result and persist it,
+/// along with the audit event for the outcome.
+pub(crate) fn finish_send_record(
+    record: &mut HistoryRecord,
+    result: &Result<SendReport, FluxError>,
+) {
+    let mut audit = AuditTrail::new("send", None);
+    audit.peer = Some(record.dest.clone());
+    audit.file = Some(record.source.clone());
+    match result {
+        Ok(report) => {
+            record.bytes = report.bytes;
+            record.files = 1;
+            record.verified = report.checksum_verified;
+            record.peer = Some(report.peer.clone());
+            record.receipt = report.receipt.clone();
+            record_history(record, None);
+            audit.addr = Some(report.peer.clone());
+            audit.finish(Ok((report.bytes, report.checksum_verified)));
+        }
+        Err(e) => {
+            record_history(record, Some(e));
+            audit.finish(Err(e));
+        }
+    }
 }
 
 /// Resolve a target string to (host, port).
 ///
 /// Formats supported:
-/// - `@devicename` -- discover device via mDNS, resolve to its IP:port
-/// - `host:port` -- direct address
-/// - `host` -- use DEFAULT_PORT
+/// - `@devicename` -- the device's cached address if it still answers
+///   there, else discover it via mDNS (or a subnet scan if mDNS finds
+///   nothing) and resolve to its IP:port
+/// - `host:port` or `[ipv6]:port` -- direct address
+/// - `host` or a bare IPv6 address -- use DEFAULT_PORT
+///
+/// IPv6 link-local addresses may carry a zone (`fe80::1%eth0`).
 pub fn resolve_device_target(target: &str) -> Result<(String, u16), FluxError> {
+    resolve_target(target, false)
+}
+
+/// `resolve_device_target`, optionally without output. Quiet lookups only
+/// ask mDNS, since the subnet scan fallback reports on stderr.
+pub(crate) fn resolve_target(target: &str, quiet: bool) -> Result<(String, u16), FluxError> {
     if target.starts_with('@') {
         let name = &target[1..];
         if name.is_empty() {
@@ -548,55 +1419,136 @@ pub fn resolve_device_target(target: &str) -> Result<(String, u16), FluxError> {
             ));
         }
 
-        eprintln!("Discovering device '{}'...", name);
-        let devices = discover_flux_devices(3)?;
-
-        // Case-insensitive prefix match
-        let name_lower = name.to_lowercase();
-        let found = devices.iter().find(|d| {
-            d.name.to_lowercase() == name_lower
-                || d.name.to_lowercase().starts_with(&name_lower)
-        });
+        if let Some(addr) = cached_address(name) {
+            return Ok(addr);
+        }
+        let devices = if quiet {
+            discover_flux_devices(3)?
+        } else {
+            eprintln!("Discovering device '{}'...", name);
+            discover_devices(3)?
+        };
+        cache::record(&devices);
 
-        match found {
+        match find_device(&devices, name) {
             Some(device) => Ok((device.host.clone(), device.port)),
-            None => Err(FluxError::TransferError(format!(
-                "Device '{}' not found on the network. Found {} device(s).",
-                name,
-                devices.len()
-            ))),
-        }
-    } else if let Some(colon_pos) = target.rfind(':') {
-        // Check if it looks like host:port (not just IPv6)
-        let port_str = &target[colon_pos + 1..];
-        if let Ok(port) = port_str.parse::<u16>() {
-            let host = &target[..colon_pos];
-            Ok((host.to_string(), port))
-        } else {
-            // Could not parse port -- treat whole thing as host
-            Ok((target.to_string(), DEFAULT_PORT))
+            None => Err(device_not_found(name, devices.len())),
         }
     } else {
-        Ok((target.to_string(), DEFAULT_PORT))
+        Ok(addr::split_host_port(target, DEFAULT_PORT))
     }
 }
 
+/// The cached address of the device `@name` refers to, if that device still
+/// answers a `Ping` there under the same name. One that does not is dropped
+/// from the cache.
+pub(crate) fn cached_address(name: &str) -> Option<(String, u16)> {
+    let cache = DeviceCache::open()?;
+    let devices = cache
+        .fresh()
+        .map_err(|e| tracing::debug!("Could not read cached device addresses: {}", e))
+        .ok()?;
+    let device = find_device(&devices, name)?;
+    let addr = *addr::socket_addrs(&device.host, device.port).ok()?.first()?;
+    match probe(addr, CACHED_PROBE_TIMEOUT) {
+        Some(found) if found.name.eq_ignore_ascii_case(&device.name) => {
+            tracing::debug!("Using cached address {} of '{}'", addr, found.name);
+            if let Err(e) = cache.remember(&[found]) {
+                tracing::debug!("Could not cache device addresses: {}", e);
+            }
+            Some((device.host.clone(), device.port))
+        }
+        _ => {
+            tracing::debug!("'{}' does not answer at {} any more", device.name, addr);
+            if let Err(e) = cache.forget(&device.name) {
+                tracing::debug!("Could not update cached device addresses: {}", e);
+            }
+            None
+        }
+    }
+}
+
+/// The discovered device an `@name` refers to (case-insensitive prefix
+/// match).
+pub(crate) fn find_device<'a>(
+    devices: &'a [DiscoveredDevice],
+    name: &str,
+) -> Option<&'a DiscoveredDevice> {
+    let name_lower = name.to_lowercase();
+    devices.iter().find(|d| {
+        d.name.to_lowercase() == name_lower || d.name.to_lowercase().starts_with(&name_lower)
+    })
+}
+
+/// Error for an `@name` that matches none of the `found` devices.
+pub(crate) fn device_not_found(name: &str, found: usize) -> FluxError {
+    FluxError::TransferError(format!(
+        "Device '{}' not found on the network. Found {} device(s).",
+        name, found
+    ))
+}
+
 /// Synchronous wrapper for sending a file.
 ///
 /// Creates a local tokio runtime, resolves the target, and sends the file.
-/// This is the entry point called from main.rs.
+/// This is the entry point called from main.rs. Records the outcome in
+/// transfer history. With a `psk`, both ends prove they hold the pre-shared
+/// key instead of the receiver asking whether to trust this device.
 pub fn send_file_sync(
     target: &str,
     file_path: &Path,
-    encrypt: bool,
-    device_name: &str,
+    options: &SendOptions<'_>,
 ) -> Result<(), FluxError> {
-    let (host, port) = resolve_device_target(target)?;
+    let mut record = HistoryRecord::new("send", &file_path.display().to_string(), target);
+    if let Err(e) = usage::check_cap(usage::P2P) {
+        record_history(&record, Some(&e));
+        return Err(e);
+    }
+    let (host, port) = match resolve_device_target(target) {
+        Ok(resolved) => resolved,
+        Err(e) => {
+            record_history(&record, Some(&e));
+            return Err(e);
+        }
+    };
 
     let rt = tokio::runtime::Runtime::new()
         .map_err(|e| FluxError::TransferError(format!("Failed to create async runtime: {}", e)))?;
 
-    rt.block_on(send_file(&host, port, file_path, encrypt, device_name))
+    let result = rt.block_on(send_file(target, &host, port, file_path, None, options));
+    finish_send_record(&mut record, &result);
+    result.map(|_| ())
+}
+
+/// Send a file to an already resolved device without printing anything.
+///
+/// Progress goes to `progress` (usually a hidden bar the caller polls). Used
+/// by the TUI Devices tab; records the outcome in transfer history.
+#[cfg(feature = "tui")]
+pub fn send_file_quiet(
+    target: &str,
+    host: &str,
+    port: u16,
+    file_path: &Path,
+    encrypt: bool,
+    device_name: &str,
+    progress: &indicatif::ProgressBar,
+) -> Result<SendReport, FluxError> {
+    let mut record = HistoryRecord::new("send", &file_path.display().to_string(), target);
+    let rt = tokio::runtime::Runtime::new()
+        .map_err(|e| FluxError::TransferError(format!("Failed to create async runtime: {}", e)))?;
+
+    let options = SendOptions {
+        encrypt,
+        device_name,
+        receipt: false,
+        limit: RateLimit::default(),
+        checksum: ChecksumAlgorithm::Blake3,
+        psk: None,
+    };
+    let result = rt.block_on(send_file(target, host, port, file_path, Some(progress), &options));
+    finish_send_record(&mut record, &result);
+    result
 }
 
 #[cfg(test)]
@@ -638,4 +1590,87 @@ mod tests {
         assert_eq!(host, "myhost:notaport");
         assert_eq!(port, DEFAULT_PORT);
     }
+
+    #[test]
+    fn resolve_ipv6_targets() {
+        let (host, port) = resolve_device_target("[::1]:8080").unwrap();
+        assert_eq!((host.as_str(), port), ("::1", 8080));
+        let (host, port) = resolve_device_target("fe80::1%eth0").unwrap();
+        assert_eq!((host.as_str(), port), ("fe80::1%eth0", DEFAULT_PORT));
+    }
+
+    /// Answer with `complete` as the receiver and return what
+    /// `await_completion` makes of it.
+    async fn completion(complete: FluxMessage) -> Result<SendReport, AttemptError> {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let receiver = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut framed = new_framed(stream);
+            send_message(&mut framed, &complete, "transfer complete").await.unwrap();
+            framed
+        });
+        let mut framed = new_framed(TcpStream::connect(addr).await.unwrap());
+        let file = OutgoingFile {
+            path: PathBuf::from("a.txt"),
+            filename: "a.txt".into(),
+            size: 3,
+            checksum: blake3::hash(b"abc").to_hex().to_string(),
+        };
+        let report = await_completion(&mut framed, "peer".into(), &file).await;
+        drop(receiver.await.unwrap());
+        report
+    }
+
+    fn complete(checksum: Option<String>, checksum_verified: Option<bool>) -> FluxMessage {
+        FluxMessage::TransferComplete {
+            filename: "a.txt".into(),
+            bytes_received: 3,
+            checksum_verified,
+            checksum,
+        }
+    }
+
+    #[tokio::test]
+    async fn completion_checks_the_receiver_checksum() {
+        let ours = blake3::hash(b"abc").to_hex().to_string();
+        let report = completion(complete(Some(ours), None)).await.unwrap();
+        assert_eq!(report.checksum_verified, Some(true));
+
+        let theirs = blake3::hash(b"abd").to_hex().to_string();
+        let err = completion(complete(Some(theirs), Some(true))).await.unwrap_err();
+        assert!(matches!(err.into_inner(), FluxError::ChecksumMismatch { .. }));
+
+        // The receiver's verdict alone is not enough
+        assert!(completion(complete(None, Some(true))).await.is_err());
+    }
+
+    #[test]
+    fn stream_hash_fills_gaps_from_disk() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("data.bin");
+        let data: Vec<u8> = (0..3 * CHUNK_SIZE + 17).map(|i| (i % 251) as u8).collect();
+        std::fs::write(&path, &data).unwrap();
+        let whole = blake3::hash(&data).to_hex().to_string();
+
+        // Streamed with a gap (a ChunkRef), a resent overlap and a missing end
+        let mut hash = StreamHash::new(ChecksumAlgorithm::Blake3);
+        hash.update(0, &data[..1000]);
+        hash.update(5000, &data[5000..6000]);
+        hash.catch_up(&path, 5000).unwrap();
+        hash.update(4000, &data[4000..8000]);
+        assert_eq!(hash.position(), 8000);
+        assert_eq!(hash.catch_up(&path, data.len() as u64).unwrap(), whole);
+
+        // A lost state starts over
+        let mut lost = StreamHash {
+            algorithm: ChecksumAlgorithm::Blake3,
+            state: None,
+        };
+        lost.update(0, &data[..1000]);
+        assert_eq!(lost.catch_up(&path, data.len() as u64).unwrap(), whole);
+
+        let file = OutgoingFile::open(&path, ChecksumAlgorithm::Blake3).unwrap();
+        assert_eq!(file.checksum, whole);
+    }
 }
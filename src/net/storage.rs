@@ -0,0 +1,376 @@
+//! Storage quotas of a shared drop-box receiver (`[receive]` table in
+//! config.toml).
+//!
+//! Where `net::quota` limits the bytes received per day, these limit what
+//! the output directory holds at once: `storage_quota` for everything in it,
+//! and `device_storage_quota` (or `[receive.device_storage_quotas]`) for a
+//! sender with a directory of its own (`per_sender_dirs`, or an allowlisted
+//! sender's `output_dir`). A file that does not fit is refused with a
+//! protocol `Error`; with `storage_full = "prune"`, the oldest stored files
+//! are deleted to make room for it instead.
+//!
+//! Usage is measured by walking the directory when a file arrives. Files
+//! still being received hold their declared size until they are committed,
+//! so concurrent transfers cannot overrun a limit together.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+use bytesize::ByteSize;
+
+use crate::config::types::{ReceiveConfig, StorageFull};
+use crate::error::FluxError;
+use crate::net::quota::parse_quota;
+use crate::transfer::atomic::is_temp_file;
+
+/// Configured storage limits in bytes.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StorageLimits {
+    /// The whole output directory
+    pub total: Option<u64>,
+    /// Any one sender's directory without an override
+    pub per_device: Option<u64>,
+    /// Per-device overrides, keyed by device name
+    pub devices: BTreeMap<String, u64>,
+    /// Refuse files that do not fit, or prune the oldest files
+    pub when_full: StorageFull,
+}
+
+impl StorageLimits {
+    /// Parse the `[receive]` storage settings ("1TB", "20 GiB", ...).
+    pub fn from_config(config: &ReceiveConfig) -> Result<Self, FluxError> {
+        let devices = config
+            .device_storage_quotas
+            .iter()
+            .map(|(device, limit)| Ok((device.clone(), parse_quota(limit)?)))
+            .collect::<Result<_, FluxError>>()?;
+        Ok(Self {
+            total: config.storage_quota.as_deref().map(parse_quota).transpose()?,
+            per_device: config
+                .device_storage_quota
+                .as_deref()
+                .map(parse_quota)
+                .transpose()?,
+            devices,
+            when_full: config.storage_full,
+        })
+    }
+
+    /// Storage limit of one sender's directory.
+    pub fn device_limit(&self, device: &str) -> Option<u64> {
+        self.devices.get(device).copied().or(self.per_device)
+    }
+}
+
+/// Storage accounting shared by all connections of a receiver.
+#[derive(Clone)]
+pub struct ReceiveStorage {
+    limits: Arc<StorageLimits>,
+    /// Declared sizes of files being received, per directory they count in
+    in_flight: Arc<Mutex<BTreeMap<PathBuf, u64>>>,
+}
+
+impl ReceiveStorage {
+    /// Storage accounting under `limits`, with nothing reserved yet.
+    pub fn new(limits: StorageLimits) -> Self {
+        Self {
+            limits: Arc::new(limits),
+            in_flight: Arc::new(Mutex::new(BTreeMap::new())),
+        }
+    }
+
+    /// The same reservations under new limits (config reload).
+    pub fn with_limits(&self, limits: StorageLimits) -> Self {
+        Self {
+            limits: Arc::new(limits),
+            in_flight: Arc::clone(&self.in_flight),
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, BTreeMap<PathBuf, u64>> {
+        self.in_flight.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Make room for a file of `bytes` from `device`, stored in `shared`
+    /// (the output directory) or in `own`, the sender's own directory.
+    ///
+    /// Fails with `FluxError::QuotaExceeded` if the file does not fit, after
+    /// pruning the oldest files when `storage_full = "prune"`.
+    pub fn reserve(
+        &self,
+        shared: &Path,
+        own: Option<&Path>,
+        device: &str,
+        bytes: u64,
+    ) -> Result<StorageReservation, FluxError> {
+        self.reserve_as(shared, own, device, bytes, self.limits.when_full)
+    }
+
+    /// Like `reserve`, but refuses instead of pruning: the files of a
+    /// mirror (`flux push`) are not the receiver's to delete.
+    pub fn reserve_without_pruning(
+        &self,
+        shared: &Path,
+        own: Option<&Path>,
+        device: &str,
+        bytes: u64,
+    ) -> Result<StorageReservation, FluxError> {
+        self.reserve_as(shared, own, device, bytes, StorageFull::Reject)
+    }
+
+    fn reserve_as(
+        &self,
+        shared: &Path,
+        own: Option<&Path>,
+        device: &str,
+        bytes: u64,
+        when_full: StorageFull,
+    ) -> Result<StorageReservation, FluxError> {
+        let mut in_flight = self.lock();
+        let reserved = |dir: &Path| in_flight.get(dir).copied().unwrap_or(0);
+
+        if let (Some(dir), Some(limit)) = (own, self.limits.device_limit(device)) {
+            let who = format!("device '{}'", device);
+            make_room(dir, &who, limit, reserved(dir), bytes, when_full)?;
+        }
+        if let Some(limit) = self.limits.total {
+            make_room(shared, "all devices", limit, reserved(shared), bytes, when_full)?;
+        }
+
+        let dirs: Vec<PathBuf> =
+            std::iter::once(shared).chain(own).map(Path::to_path_buf).collect();
+        for dir in &dirs {
+            *in_flight.entry(dir.clone()).or_default() += bytes;
+        }
+        Ok(StorageReservation {
+            storage: self.clone(),
+            dirs,
+            bytes,
+        })
+    }
+
+    fn release(&self, dirs: &[PathBuf], bytes: u64) {
+        let mut in_flight = self.lock();
+        for dir in dirs {
+            if let Some(reserved) = in_flight.get_mut(dir) {
+                *reserved = reserved.saturating_sub(bytes);
+                if *reserved == 0 {
+                    in_flight.remove(dir);
+                }
+            }
+        }
+    }
+}
+
+/// Storage held by a file being received, released when dropped. Keep it
+/// until the file is committed, from then on it is counted on disk.
+pub struct StorageReservation {
+    storage: ReceiveStorage,
+    dirs: Vec<PathBuf>,
+    bytes: u64,
+}
+
+impl Drop for StorageReservation {
+    fn drop(&mut self) {
+        self.storage.release(&self.dirs, self.bytes);
+    }
+}
+
+/// A stored file that pruning may delete.
+struct StoredFile {
+    path: PathBuf,
+    size: u64,
+    modified: SystemTime,
+}
+
+/// Regular files below `dir` (none if it does not exist yet), without
+/// temp files of transfers in progress.
+fn stored_files(dir: &Path) -> Vec<StoredFile> {
+    walkdir::WalkDir::new(dir)
+        .into_iter()
+        .filter_map(Result::ok)
+        .filter(|entry| entry.file_type().is_file() && !is_temp_file(entry.path()))
+        .filter_map(|entry| {
+            let meta = entry.metadata().ok()?;
+            Some(StoredFile {
+                path: entry.into_path(),
+                size: meta.len(),
+                modified: meta.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+            })
+        })
+        .collect()
+}
+
+/// Check that `bytes` more fit into `dir` under `limit`, counting the files
+/// stored there and `reserved` bytes on their way in. With
+/// `StorageFull::Prune`, delete the oldest files until they do.
+fn make_room(
+    dir: &Path,
+    who: &str,
+    limit: u64,
+    reserved: u64,
+    bytes: u64,
+    when_full: StorageFull,
+) -> Result<(), FluxError> {
+    // Pruning cannot help a file that is too large by itself
+    if reserved.saturating_add(bytes) > limit {
+        return Err(over_limit(who, limit, reserved, bytes));
+    }
+    let mut files = stored_files(dir);
+    let mut used = reserved + files.iter().map(|f| f.size).sum::<u64>();
+    if used.saturating_add(bytes) <= limit {
+        return Ok(());
+    }
+    if when_full == StorageFull::Reject {
+        return Err(over_limit(who, limit, used, bytes));
+    }
+
+    files.sort_by_key(|f| f.modified);
+    for file in files {
+        if used.saturating_add(bytes) <= limit {
+            break;
+        }
+        match std::fs::remove_file(&file.path) {
+            Ok(()) => {
+                eprintln!(
+                    "Pruned {} ({}) to stay within the storage limit for {}",
+                    file.path.display(),
+                    ByteSize(file.size),
+                    who
+                );
+                used -= file.size;
+            }
+            Err(e) => tracing::warn!("Failed to prune {}: {}", file.path.display(), e),
+        }
+    }
+    if used.saturating_add(bytes) > limit {
+        return Err(over_limit(who, limit, used, bytes));
+    }
+    Ok(())
+}
+
+fn over_limit(who: &str, limit: u64, used: u64, bytes: u64) -> FluxError {
+    FluxError::QuotaExceeded(format!(
+        "storage limit for {} is {}, {} already stored, file needs {}",
+        who,
+        ByteSize(limit),
+        ByteSize(used),
+        ByteSize(bytes)
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn storage(
+        total: Option<u64>,
+        per_device: Option<u64>,
+        when_full: StorageFull,
+    ) -> ReceiveStorage {
+        ReceiveStorage::new(StorageLimits {
+            total,
+            per_device,
+            devices: BTreeMap::new(),
+            when_full,
+        })
+    }
+
+    /// Store `size` bytes at `dir/name`, modified `age` seconds ago.
+    fn store(dir: &Path, name: &str, size: usize, age: u64) -> PathBuf {
+        let path = dir.join(name);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, vec![0u8; size]).unwrap();
+        let modified = SystemTime::now() - Duration::from_secs(age);
+        std::fs::File::options()
+            .write(true)
+            .open(&path)
+            .and_then(|file| file.set_modified(modified))
+            .unwrap();
+        path
+    }
+
+    #[test]
+    fn limits_parse_from_config() {
+        let config = ReceiveConfig {
+            storage_quota: Some("1TB".into()),
+            device_storage_quota: Some("2 KiB".into()),
+            device_storage_quotas: BTreeMap::from([("nas".to_string(), "5MB".to_string())]),
+            storage_full: StorageFull::Prune,
+            ..Default::default()
+        };
+        let limits = StorageLimits::from_config(&config).unwrap();
+        assert_eq!(limits.total, Some(1_000_000_000_000));
+        assert_eq!(limits.device_limit("phone"), Some(2048));
+        assert_eq!(limits.device_limit("nas"), Some(5_000_000));
+        assert_eq!(limits.when_full, StorageFull::Prune);
+
+        let bad = ReceiveConfig {
+            storage_quota: Some("plenty".into()),
+            ..Default::default()
+        };
+        assert!(StorageLimits::from_config(&bad).is_err());
+    }
+
+    #[test]
+    fn full_directory_refuses_files_that_do_not_fit() {
+        let dir = tempfile::tempdir().unwrap();
+        store(dir.path(), "a.bin", 600, 10);
+        // Temp files of transfers in progress are not counted
+        store(dir.path(), ".b.bin.flux-tmp", 600, 10);
+        let storage = storage(Some(1000), None, StorageFull::Reject);
+
+        assert!(storage.reserve(dir.path(), None, "phone", 400).is_ok());
+        let err = storage.reserve(dir.path(), None, "phone", 500).err().unwrap();
+        assert!(matches!(err, FluxError::QuotaExceeded(_)));
+        assert!(dir.path().join("a.bin").exists());
+    }
+
+    #[test]
+    fn prune_deletes_oldest_files_first() {
+        let dir = tempfile::tempdir().unwrap();
+        let oldest = store(dir.path(), "old/1.bin", 400, 300);
+        let older = store(dir.path(), "2.bin", 400, 200);
+        let newest = store(dir.path(), "3.bin", 400, 100);
+        let storage = storage(Some(1400), None, StorageFull::Prune);
+
+        let _held = storage.reserve(dir.path(), None, "phone", 700).unwrap();
+        assert!(!oldest.exists());
+        assert!(!older.exists());
+        assert!(newest.exists());
+
+        // Nothing is deleted for a file larger than the limit itself
+        assert!(storage.reserve(dir.path(), None, "phone", 2000).is_err());
+        assert!(newest.exists());
+    }
+
+    #[test]
+    fn sender_directory_has_its_own_limit() {
+        let dir = tempfile::tempdir().unwrap();
+        let own = dir.path().join("phone");
+        store(&own, "a.bin", 800, 10);
+        let storage = storage(None, Some(1000), StorageFull::Reject);
+
+        assert!(storage.reserve(dir.path(), Some(&own), "phone", 300).is_err());
+        // Senders without a directory of their own only count in the total
+        assert!(storage.reserve(dir.path(), None, "phone", 300).is_ok());
+        let laptop = dir.path().join("laptop");
+        assert!(storage.reserve(dir.path(), Some(&laptop), "laptop", 300).is_ok());
+    }
+
+    #[test]
+    fn reservations_count_until_dropped() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = storage(Some(1000), None, StorageFull::Prune);
+
+        let first = storage.reserve(dir.path(), None, "a", 800).unwrap();
+        // A file being received cannot be pruned to make room
+        assert!(storage.reserve(dir.path(), None, "b", 300).is_err());
+        drop(first);
+        assert!(storage.reserve(dir.path(), None, "b", 300).is_ok());
+        assert!(storage.lock().is_empty());
+    }
+}
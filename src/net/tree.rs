@@ -0,0 +1,1036 @@
+//! Directory mirroring over the flux protocol (`flux push`, `flux pull`).
+//!
+//! `flux push @nas ./photos backup/photos` makes `backup/photos`, below the
+//! receiver's output directory, a copy of `./photos`; `flux pull @nas
+//! backup/photos ./photos` goes the other way. The client connects and
+//! handshakes as `flux send` does (encrypted unless `--no-encrypt`, trusted
+//! on first use or with `--psk-file`) and then holds a tree session (see
+//! `FluxMessage`): the receiver lists its side with sizes and mtimes, the
+//! sync planner (`sync::engine::compute_listed_plan`) compares it with the
+//! local side, and only new and changed files are sent. Each one is
+//! checksummed, written through a temp file and given its source's mtime,
+//! so the next run sees it unchanged. Files missing from the source are
+//! only deleted with `--delete`.
+//!
+//! Receivers serve tree sessions only when started with `--allow-mirror`:
+//! unlike a plain receive, a session replaces, deletes and hands out files
+//! in the output directory. Requested paths are relative to it; absolute
+//! paths, `..` and symlinks leading out of it are refused.
+
+
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
+use std::time::{Duration, Instant, UNIX_EPOCH};
+
+use bytesize::ByteSize;
+use futures::StreamExt;
+use indicatif::ProgressBar;
+use walkdir::WalkDir;
+
+use crate::error::FluxError;
+use crate::net::audit::{AuditKind, AuditTrail};
+use crate::net::chunking::{self, Segment};
+use crate::net::protocol::{decode_message, FluxMessage, CHUNK_SIZE, MAX_FRAME_SIZE};
+use crate::net::quota::QuotaReservation;
+use crate::net::ratelimit::RateLimit;
+use crate::net::receiver::{
+    receive_chunks, Inbound, IncomingFile, ReceiverSettings, MAX_RECEIVE_SIZE,
+};
+use crate::net::resume::{stall_timeout, AttemptError};
+use crate::net::sender::{
+    await_completion, connect, resolve_target, send_message, stream_chunks, with_keepalives,
+    Connection, FluxFramed, Outbound, OutgoingFile,
+};
+use crate::net::storage::StorageReservation;
+use crate::progress::bar::create_network_progress;
+use crate::security::crypto::EncryptedChannel;
+use crate::security::psk::PreSharedKey;
+use crate::sync::engine::compute_listed_plan;
+use crate::sync::plan::{ListedFile, SyncAction, SyncPlan, SyncResult};
+use crate::transfer::atomic::is_temp_file;
+use crate::transfer::cancel;
+use crate::transfer::checksum::ChecksumAlgorithm;
+use crate::transfer::history::{record_history, HistoryRecord};
+use crate::transfer::stats::TransferStats;
+
+/// Listed path bytes per `TreeListing` message, far below the frame limit.
+const LISTING_BATCH_BYTES: usize = 256 * 1024;
+
+/// The receiver's side of a tree session, in its output directory `root`.
+pub(crate) struct TreeSession<'a> {
+    pub root: &'a Path,
+    /// The receiver's shared output directory, for storage quotas
+    pub shared: &'a Path,
+    pub settings: &'a ReceiverSettings,
+    /// Device name of the client, for quotas and messages
+    pub peer: &'a str,
+    pub channel: Option<&'a EncryptedChannel>,
+    /// The connection's audit trail; files pushed, pulled and deleted are
+    /// recorded in it
+    pub audit: &'a AuditTrail,
+}
+
+impl TreeSession<'_> {
+    /// Answer `first` and the requests after it until the client closes the
+    /// connection. Refused outright without `--allow-mirror`.
+    pub async fn serve(
+        &self,
+        framed: &mut FluxFramed,
+        first: FluxMessage,
+    ) -> Result<(), FluxError> {
+        if !self.settings.allow_mirror {
+            let refuse = FluxMessage::Error {
+                message: "This receiver does not accept push or pull (start it with --allow-mirror)"
+                    .into(),
+            };
+            let _ = send_message(framed, &refuse, "refusal").await;
+            return Err(FluxError::TrustError(format!(
+                "Refused a push or pull from '{}' (start with --allow-mirror to accept them)",
+                self.peer
+            )));
+        }
+        eprintln!("Mirroring with {} in {}", self.peer, self.root.display());
+        let mut request = Some(first);
+        while let Some(msg) = request {
+            self.answer(framed, msg).await?;
+            request = read_message(framed).await?;
+        }
+        Ok(())
+    }
+
+    async fn answer(&self, framed: &mut FluxFramed, request: FluxMessage) -> Result<(), FluxError> {
+        match request {
+            FluxMessage::StatPath { path } => {
+                let stat = resolve(self.root, &path).map(|full| FluxMessage::PathStat {
+                    exists: full.exists(),
+                    dir: full.is_dir(),
+                });
+                reply(framed, stat).await
+            }
+            FluxMessage::ListTree { path } => self.list(framed, &path).await,
+            FluxMessage::PutFile {
+                path,
+                size,
+                modified,
+                checksum,
+            } => self.put(framed, &path, size, modified, &checksum).await,
+            FluxMessage::GetFile { path } => self.get(framed, &path).await,
+            FluxMessage::DeletePath { path } => {
+                let deleted = self.delete(&path).map(|()| FluxMessage::PathDeleted { path });
+                reply(framed, deleted).await
+            }
+            _ => Err(FluxError::ProtocolError(
+                "Unexpected message in a push or pull session".into(),
+            )),
+        }
+    }
+
+    /// Send the listing of the directory `path` in `TreeListing`s.
+    async fn list(&self, framed: &mut FluxFramed, path: &str) -> Result<(), FluxError> {
+        let listed = resolve(self.root, path).and_then(|dir| {
+            if dir.exists() && !dir.is_dir() {
+                return Err(FluxError::TransferError(format!("{} is not a directory", path)));
+            }
+            list_files(&dir)
+        });
+        let files = match listed {
+            Ok(files) => files,
+            Err(e) => return reply(framed, Err(e)).await,
+        };
+        let batches = listing_batches(files);
+        let count = batches.len();
+        for (i, batch) in batches.iter().enumerate() {
+            let encoded = bincode::serde::encode_to_vec(batch, bincode::config::standard())
+                .map_err(|e| FluxError::TransferError(format!("Failed to encode listing: {}", e)))?;
+            let (data, nonce) = chunking::seal(self.channel, encoded)?;
+            let listing = FluxMessage::TreeListing {
+                data,
+                nonce,
+                last: i + 1 == count,
+            };
+            send_message(framed, &listing, "listing")
+                .await
+                .map_err(AttemptError::into_inner)?;
+        }
+        Ok(())
+    }
+
+    /// Receive a file into `path`. The client streams it right after its
+    /// request, so a refusal ends the session.
+    async fn put(
+        &self,
+        framed: &mut FluxFramed,
+        path: &str,
+        size: u64,
+        modified: Option<i64>,
+        checksum: &str,
+    ) -> Result<(), FluxError> {
+        let (mut incoming, reservation, _stored) = match self.prepare_put(path, size, checksum) {
+            Ok(prepared) => prepared,
+            Err(e) => {
+                eprintln!("Refusing {} from {}: {}", path, self.peer, e);
+                refuse(framed, &e).await;
+                return Err(e);
+            }
+        };
+        self.audit
+            .event(AuditKind::Accepted)
+            .file(path, size)
+            .reason("push")
+            .record();
+        let pb = ProgressBar::hidden();
+        let limiter = self
+            .settings
+            .limit
+            .start_shared(None, self.settings.admission.rate());
+        let inbound = Inbound {
+            channel: self.channel,
+            low_memory: false,
+            limiter: limiter.as_ref(),
+        };
+        receive_chunks(framed, &mut incoming, inbound, &pb, None, None)
+            .await
+            .map_err(AttemptError::into_inner)?;
+        reservation.settle(size);
+        if let Err(actual) = incoming.verify(Some(checksum)) {
+            // Dropping `incoming` deletes the corrupted temp file
+            let e = FluxError::TransferError(format!(
+                "Checksum mismatch for {}: expected {}, got {}",
+                path, checksum, actual
+            ));
+            refuse(framed, &e).await;
+            return Err(e);
+        }
+        let actual = incoming.checksum();
+        let output_path = incoming.commit()?;
+        set_modified(&output_path, modified);
+        eprintln!("Received {} ({}) from {}", path, ByteSize(size), self.peer);
+        self.audit
+            .event(AuditKind::Completed)
+            .file(path, size)
+            .verified(Some(true))
+            .reason("push")
+            .record();
+
+        let complete = FluxMessage::TransferComplete {
+            filename: path.to_string(),
+            bytes_received: size,
+            checksum_verified: Some(true),
+            checksum: Some(actual),
+        };
+        send_message(framed, &complete, "transfer complete")
+            .await
+            .map_err(AttemptError::into_inner)
+    }
+
+    /// Check a `PutFile` request and open the file it writes, with the
+    /// client's daily and storage quotas reserved for it.
+    fn prepare_put(
+        &self,
+        path: &str,
+        size: u64,
+        checksum: &str,
+    ) -> Result<(IncomingFile, QuotaReservation, StorageReservation), FluxError> {
+        if size > MAX_RECEIVE_SIZE {
+            return Err(FluxError::TransferError(format!(
+                "File too large: {} bytes exceeds maximum {} bytes",
+                size, MAX_RECEIVE_SIZE
+            )));
+        }
+        let algorithm = ChecksumAlgorithm::of_tagged(checksum)?;
+        let output_path = resolve(self.root, path)?;
+        if output_path.is_dir() {
+            return Err(FluxError::IsDirectory {
+                path: PathBuf::from(path),
+            });
+        }
+        let incoming = IncomingFile::replace(&output_path, size, algorithm)?;
+        incoming.check_space(&self.settings.space)?;
+        let reservation = self.settings.quota.reserve(self.peer, size)?;
+        let own = (self.root != self.shared).then_some(self.root);
+        let stored = self
+            .settings
+            .storage
+            .reserve_without_pruning(self.shared, own, self.peer, size)?;
+        if self.settings.space.preallocate {
+            incoming.preallocate();
+        }
+        Ok((incoming, reservation, stored))
+    }
+
+    /// Send the file at `path`, answered with `Error` if there is none.
+    async fn get(&self, framed: &mut FluxFramed, path: &str) -> Result<(), FluxError> {
+        let found = resolve(self.root, path).and_then(|full| {
+            if full.is_file() {
+                Ok(full)
+            } else {
+                Err(FluxError::SourceNotFound {
+                    path: PathBuf::from(path),
+                })
+            }
+        });
+        let full = match found {
+            Ok(full) => full,
+            Err(e) => return reply(framed, Err(e)).await,
+        };
+        let hashing = tokio::task::spawn_blocking(move || {
+            OutgoingFile::open(&full, ChecksumAlgorithm::Blake3)
+        });
+        let file = match with_keepalives(framed, hashing)
+            .await
+            .map_err(AttemptError::into_inner)?
+        {
+            Ok(Ok(file)) => file,
+            Ok(Err(e)) => return reply(framed, Err(e)).await,
+            Err(e) => {
+                return Err(FluxError::TransferError(format!("Checksum task failed: {}", e)))
+            }
+        };
+
+        let header = FluxMessage::FileHeader {
+            filename: path.to_string(),
+            size: file.size,
+            checksum: Some(file.checksum.clone()),
+            encrypted: self.channel.is_some(),
+            footer: false,
+        };
+        send_message(framed, &header, "file header")
+            .await
+            .map_err(AttemptError::into_inner)?;
+        let segments = [Segment::Data {
+            offset: 0,
+            len: file.size,
+        }];
+        let pb = ProgressBar::hidden();
+        let outbound = Outbound {
+            chunk_size: CHUNK_SIZE,
+            channel: self.channel,
+            zero_copy: false,
+            limiter: None,
+        };
+        stream_chunks(framed, &file, &segments, outbound, &pb, None)
+            .await
+            .map_err(AttemptError::into_inner)?;
+        let report = await_completion(framed, self.peer.to_string(), &file)
+            .await
+            .map_err(AttemptError::into_inner)?;
+        eprintln!("Sent {} ({}) to {}", path, ByteSize(file.size), self.peer);
+        self.audit
+            .event(AuditKind::Completed)
+            .file(path, report.bytes)
+            .verified(report.checksum_verified)
+            .reason("pull")
+            .record();
+        Ok(())
+    }
+
+    fn delete(&self, path: &str) -> Result<(), FluxError> {
+        let full = resolve(self.root, path)?;
+        if full.is_dir() {
+            return Err(FluxError::IsDirectory {
+                path: PathBuf::from(path),
+            });
+        }
+        std::fs::remove_file(&full)?;
+        eprintln!("Deleted {} for {}", path, self.peer);
+        let mut deleted = self.audit.event(AuditKind::Deleted);
+        deleted.file = Some(path.to_string());
+        deleted.record();
+        Ok(())
+    }
+}
+
+/// Send `answer`, or the error it failed with; the session goes on either
+/// way.
+async fn reply(
+    framed: &mut FluxFramed,
+    answer: Result<FluxMessage, FluxError>,
+) -> Result<(), FluxError> {
+    let msg = answer.unwrap_or_else(|e| FluxMessage::Error {
+        message: e.to_string(),
+    });
+    send_message(framed, &msg, "answer")
+        .await
+        .map_err(AttemptError::into_inner)
+}
+
+/// Tell the other side why a transfer stopped. Best effort: it ends either
+/// way.
+async fn refuse(framed: &mut FluxFramed, e: &FluxError) {
+    let msg = FluxMessage::Error {
+        message: e.to_string(),
+    };
+    let _ = send_message(framed, &msg, "error").await;
+}
+
+/// Read the next message, skipping Keepalives. `None` once the other side
+/// has closed the connection.
+async fn read_message(framed: &mut FluxFramed) -> Result<Option<FluxMessage>, FluxError> {
+    loop {
+        let next = tokio::time::timeout(stall_timeout(), framed.next())
+            .await
+            .map_err(|_| {
+                FluxError::TransferError(format!(
+                    "Nothing from the other side for {}s",
+                    stall_timeout().as_secs()
+                ))
+            })?;
+        let bytes = match next {
+            None => return Ok(None),
+            Some(Err(e)) => {
+                return Err(FluxError::TransferError(format!("Failed to read message: {}", e)))
+            }
+            Some(Ok(bytes)) => bytes,
+        };
+        match decode_message(&bytes)? {
+            FluxMessage::Keepalive => continue,
+            msg => return Ok(Some(msg)),
+        }
+    }
+}
+
+/// `path`, as sent by the other side, as a relative path of plain names.
+/// `None` for absolute paths and paths with `..`; `.` and empty parts are
+/// dropped, so `""` and `"."` name the root.
+fn relative_path(path: &str) -> Option<PathBuf> {
+    if path.starts_with('/') {
+        return None;
+    }
+    let mut relative = PathBuf::new();
+    for part in path.split('/').filter(|part| !part.is_empty() && *part != ".") {
+        let mut components = Path::new(part).components();
+        match (components.next(), components.next()) {
+            (Some(Component::Normal(name)), None) => relative.push(name),
+            _ => return None,
+        }
+    }
+    Some(relative)
+}
+
+/// Where `path` is below `root`. Refused if it is not a plain relative path
+/// or a symlink on the way leads out of `root`.
+fn resolve(root: &Path, path: &str) -> Result<PathBuf, FluxError> {
+    let refused = || {
+        FluxError::TransferError(format!(
+            "Refused path '{}': it is not inside the output directory",
+            path
+        ))
+    };
+    let full = root.join(relative_path(path).ok_or_else(refused)?);
+    let Ok(real_root) = std::fs::canonicalize(root) else {
+        // Nothing below a missing root can be a symlink
+        return Ok(full);
+    };
+    // The deepest part of the path that exists, with its symlinks resolved
+    let mut existing = full.as_path();
+    while existing.symlink_metadata().is_err() {
+        match existing.parent() {
+            Some(parent) => existing = parent,
+            None => break,
+        }
+    }
+    let real = std::fs::canonicalize(existing).map_err(|_| refused())?;
+    if !real.starts_with(&real_root) {
+        return Err(refused());
+    }
+    Ok(full)
+}
+
+/// Every regular file below `dir`, with `/`-separated paths relative to it.
+/// Symlinks and flux's temp files are left out; a missing `dir` lists
+/// nothing.
+fn list_files(dir: &Path) -> Result<Vec<ListedFile>, FluxError> {
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut files = Vec::new();
+    for entry in WalkDir::new(dir).follow_links(false).sort_by_file_name() {
+        let entry = entry?;
+        if !entry.file_type().is_file() || is_temp_file(entry.path()) {
+            continue;
+        }
+        let relative = entry.path().strip_prefix(dir)?;
+        files.push(ListedFile::new(slash_path(relative), &entry.metadata()?));
+    }
+    Ok(files)
+}
+
+/// Cut a listing into `TreeListing` batches; there is always at least one.
+fn listing_batches(files: Vec<ListedFile>) -> Vec<Vec<ListedFile>> {
+    let mut batches = Vec::new();
+    let mut batch = Vec::new();
+    let mut bytes = 0;
+    for file in files {
+        // The path, plus the size and mtime
+        let len = file.path.len() + 16;
+        if bytes + len > LISTING_BATCH_BYTES && !batch.is_empty() {
+            batches.push(std::mem::take(&mut batch));
+            bytes = 0;
+        }
+        bytes += len;
+        batch.push(file);
+    }
+    batches.push(batch);
+    batches
+}
+
+/// `path` with `/` between its parts, as paths are sent.
+fn slash_path(path: &Path) -> String {
+    path.components()
+        .map(|part| part.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Give `path` the modification time of its source (Unix seconds), so the
+/// next plan sees it unchanged. Best effort.
+fn set_modified(path: &Path, modified: Option<i64>) {
+    let Some(secs) = modified.and_then(|secs| u64::try_from(secs).ok()) else {
+        return;
+    };
+    let time = UNIX_EPOCH + Duration::from_secs(secs);
+    let set = std::fs::File::options()
+        .write(true)
+        .open(path)
+        .and_then(|file| file.set_modified(time));
+    if let Err(e) = set {
+        tracing::warn!("Cannot set the modification time of {}: {}", path.display(), e);
+    }
+}
+
+/// Which way `mirror_sync` copies.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    /// Local directory to the receiver (`flux push`)
+    Push,
+    /// Receiver to the local directory (`flux pull`)
+    Pull,
+}
+
+impl Direction {
+    fn name(self) -> &'static str {
+        match self {
+            Direction::Push => "push",
+            Direction::Pull => "pull",
+        }
+    }
+}
+
+/// How `flux push` and `flux pull` run.
+pub struct MirrorOptions {
+    pub encrypt: bool,
+    /// Device name to identify as
+    pub device_name: String,
+    /// `--psk-file`: prove this key instead of relying on the trust prompt
+    pub psk: Option<PreSharedKey>,
+    /// `--delete`: delete files that are not in the source
+    pub delete: bool,
+    /// `--force`: allow `--delete` with an empty source
+    pub force: bool,
+    /// `--dry-run`: print the plan without changing anything
+    pub dry_run: bool,
+    pub quiet: bool,
+}
+
+/// Mirror the local directory `local` to (`Push`) or from (`Pull`) the
+/// directory `remote` below the output directory of the receiver at
+/// `target`. This is the entry point called from main.rs; runs that change
+/// something are recorded in transfer history.
+pub fn mirror_sync(
+    direction: Direction,
+    target: &str,
+    local: &Path,
+    remote: &str,
+    options: &MirrorOptions,
+) -> Result<(), FluxError> {
+    if local.exists() && !local.is_dir() {
+        return Err(FluxError::Config(format!(
+            "{} is not a directory: {} mirrors directories (use flux send for a file)",
+            local.display(),
+            direction.name()
+        )));
+    }
+    if direction == Direction::Push && !local.exists() {
+        return Err(FluxError::SourceNotFound {
+            path: local.to_path_buf(),
+        });
+    }
+    if relative_path(remote).is_none() {
+        return Err(FluxError::Config(format!(
+            "Remote path '{}' must be relative to the receiver's output directory, without '..'",
+            remote
+        )));
+    }
+
+    crate::transfer::usage::check_cap(crate::transfer::usage::P2P)?;
+
+    let (host, port) = resolve_target(target, options.quiet)?;
+    let rt = tokio::runtime::Runtime::new()
+        .map_err(|e| FluxError::TransferError(format!("Failed to create async runtime: {}", e)))?;
+    rt.block_on(async {
+        let conn = connect(
+            &host,
+            port,
+            options.encrypt,
+            &options.device_name,
+            RateLimit::default(),
+            options.psk.as_ref(),
+        )
+        .await
+        .map_err(AttemptError::into_inner)?;
+        let mut client = Client {
+            conn,
+            peer: target.to_string(),
+            quiet: options.quiet,
+        };
+        client.mirror(direction, local, remote, options).await
+    })
+}
+
+/// The client's side of a tree session.
+struct Client {
+    conn: Connection,
+    /// The target as given, for messages and history
+    peer: String,
+    quiet: bool,
+}
+
+/// What became of one file of the plan.
+enum Outcome {
+    /// Copied (its size) or deleted (0)
+    Done(u64),
+    /// Refused or failed on its own; the run goes on
+    Failed(String),
+}
+
+impl Client {
+    async fn mirror(
+        &mut self,
+        direction: Direction,
+        local: &Path,
+        remote: &str,
+        options: &MirrorOptions,
+    ) -> Result<(), FluxError> {
+        let remote_name = format!("{}:{}", self.peer, remote);
+        let (exists, dir) = self.stat(remote).await?;
+        if exists && !dir {
+            return Err(FluxError::TransferError(format!(
+                "{} is a file: {} mirrors directories",
+                remote_name,
+                direction.name()
+            )));
+        }
+        if !exists && direction == Direction::Pull {
+            return Err(FluxError::TransferError(format!("{} does not exist", remote_name)));
+        }
+        let remote_files = if exists {
+            self.list(remote).await?
+        } else {
+            Vec::new()
+        };
+        let local_files = list_files(local)?;
+
+        let remote_root = Path::new(remote);
+        let (source_root, source_files, dest_root, dest_files) = match direction {
+            Direction::Push => (local, &local_files, remote_root, &remote_files),
+            Direction::Pull => (remote_root, &remote_files, local, &local_files),
+        };
+        let plan = compute_listed_plan(
+            source_root,
+            source_files,
+            dest_root,
+            dest_files,
+            options.delete,
+            options.force,
+        )?;
+        if options.dry_run {
+            plan.print_summary();
+            return Ok(());
+        }
+        if !plan.has_changes() {
+            if !self.quiet {
+                eprintln!("Already in sync. Nothing to do.");
+            }
+            return Ok(());
+        }
+
+        let (source_name, dest_name) = match direction {
+            Direction::Push => (local.display().to_string(), remote_name),
+            Direction::Pull => (remote_name, local.display().to_string()),
+        };
+        let mut record = HistoryRecord::new(direction.name(), &source_name, &dest_name);
+        record.peer = Some(self.peer.clone());
+        record.changes = Some(plan.changes(dest_root));
+        // Source mtimes by the paths in the plan, for the copies
+        let sources: HashMap<PathBuf, &ListedFile> = source_files
+            .iter()
+            .map(|file| (source_root.join(&file.path), file))
+            .collect();
+
+        let started = Instant::now();
+        let (result, failed) = match self.execute(direction, &plan, &sources).await {
+            Ok(executed) => executed,
+            Err(e) => {
+                record_history(&record, Some(&e));
+                return Err(e);
+            }
+        };
+        record.bytes = result.bytes_transferred;
+        record.files = result.files_copied + result.files_updated + result.files_deleted;
+        record.verified = Some(true);
+        let outcome = if failed.is_empty() {
+            Ok(())
+        } else {
+            Err(FluxError::PartialFailure { failed })
+        };
+        record_history(&record, outcome.as_ref().err());
+
+        if !self.quiet {
+            let mut stats = TransferStats::new(plan.actions.len() as u64, plan.total_copy_bytes);
+            stats.started = started;
+            stats.bytes_done = result.bytes_transferred;
+            eprintln!(
+                "{} complete: {} copied, {} updated, {} deleted, {} skipped ({}) in {:.1}s @ {}/s",
+                match direction {
+                    Direction::Push => "Push",
+                    Direction::Pull => "Pull",
+                },
+                result.files_copied,
+                result.files_updated,
+                result.files_deleted,
+                result.files_skipped,
+                ByteSize(result.bytes_transferred),
+                stats.elapsed().as_secs_f64(),
+                ByteSize(stats.throughput_bps()),
+            );
+        }
+        outcome
+    }
+
+    /// Carry out `plan`. Files that fail on their own are returned with why;
+    /// a broken connection or a refused upload ends the run.
+    async fn execute(
+        &mut self,
+        direction: Direction,
+        plan: &SyncPlan,
+        sources: &HashMap<PathBuf, &ListedFile>,
+    ) -> Result<(SyncResult, Vec<(PathBuf, String)>), FluxError> {
+        let mut result = SyncResult::default();
+        let mut failed = Vec::new();
+        for action in &plan.actions {
+            cancel::check()?;
+            if !self.quiet && !matches!(action, SyncAction::Skip { .. }) {
+                eprintln!("{}", action);
+            }
+            match action {
+                SyncAction::CopyNew { src, dest, .. }
+                | SyncAction::UpdateChanged { src, dest, .. } => {
+                    let source = sources.get(src);
+                    let modified = source.and_then(|file| file.modified);
+                    let pb = file_progress(source.map_or(0, |file| file.size), self.quiet);
+                    let outcome = match direction {
+                        Direction::Push => self.put(src, &slash_path(dest), modified, &pb).await?,
+                        Direction::Pull => self.get(&slash_path(src), dest, modified, &pb).await?,
+                    };
+                    pb.finish_and_clear();
+                    match outcome {
+                        Outcome::Done(bytes) if matches!(action, SyncAction::CopyNew { .. }) => {
+                            result.files_copied += 1;
+                            result.bytes_transferred += bytes;
+                        }
+                        Outcome::Done(bytes) => {
+                            result.files_updated += 1;
+                            result.bytes_transferred += bytes;
+                        }
+                        Outcome::Failed(reason) => failed.push((src.clone(), reason)),
+                    }
+                }
+                SyncAction::DeleteOrphan { path, .. } => {
+                    let outcome = match direction {
+                        Direction::Push => self.delete(&slash_path(path)).await?,
+                        Direction::Pull => match std::fs::remove_file(path) {
+                            Ok(()) => Outcome::Done(0),
+                            Err(e) => Outcome::Failed(e.to_string()),
+                        },
+                    };
+                    match outcome {
+                        Outcome::Done(_) => result.files_deleted += 1,
+                        Outcome::Failed(reason) => failed.push((path.clone(), reason)),
+                    }
+                }
+                SyncAction::Skip { .. } => result.files_skipped += 1,
+                // Listings carry no link information, so none are planned
+                SyncAction::Link { .. } => {}
+            }
+        }
+        Ok((result, failed))
+    }
+
+    /// Send `msg` and wait for the answer.
+    async fn request(&mut self, msg: &FluxMessage, what: &str) -> Result<FluxMessage, FluxError> {
+        send_message(&mut self.conn.framed, msg, what)
+            .await
+            .map_err(AttemptError::into_inner)?;
+        self.next_answer().await
+    }
+
+    async fn next_answer(&mut self) -> Result<FluxMessage, FluxError> {
+        read_message(&mut self.conn.framed)
+            .await?
+            .ok_or_else(|| FluxError::TransferError("Receiver closed the connection".into()))
+    }
+
+    /// Whether `path` exists on the receiver, and is a directory.
+    async fn stat(&mut self, path: &str) -> Result<(bool, bool), FluxError> {
+        let stat = FluxMessage::StatPath {
+            path: path.to_string(),
+        };
+        match self.request(&stat, "stat request").await? {
+            FluxMessage::PathStat { exists, dir } => Ok((exists, dir)),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    /// Every file below the directory `path` on the receiver.
+    async fn list(&mut self, path: &str) -> Result<Vec<ListedFile>, FluxError> {
+        let list = FluxMessage::ListTree {
+            path: path.to_string(),
+        };
+        let mut answer = self.request(&list, "list request").await?;
+        let mut files = Vec::new();
+        loop {
+            let FluxMessage::TreeListing { data, nonce, last } = answer else {
+                return Err(unexpected(answer));
+            };
+            let encoded = chunking::unseal(self.conn.channel.as_ref(), data, nonce)?;
+            let config = bincode::config::standard().with_limit::<MAX_FRAME_SIZE>();
+            let (batch, _): (Vec<ListedFile>, usize) =
+                bincode::serde::decode_from_slice(&encoded, config).map_err(|e| {
+                    FluxError::TransferError(format!("Failed to decode listing: {}", e))
+                })?;
+            // Listed paths become local paths when pulling
+            let invalid = |file: &&ListedFile| {
+                !relative_path(&file.path).is_some_and(|path| path.components().next().is_some())
+            };
+            if let Some(file) = batch.iter().find(invalid) {
+                return Err(FluxError::ProtocolError(format!(
+                    "Receiver listed an invalid path: '{}'",
+                    file.path
+                )));
+            }
+            files.extend(batch);
+            if last {
+                return Ok(files);
+            }
+            answer = self.next_answer().await?;
+        }
+    }
+
+    /// Send the local file `src` to `dest` on the receiver.
+    async fn put(
+        &mut self,
+        src: &Path,
+        dest: &str,
+        modified: Option<i64>,
+        pb: &ProgressBar,
+    ) -> Result<Outcome, FluxError> {
+        let path = src.to_path_buf();
+        let hashing = tokio::task::spawn_blocking(move || {
+            OutgoingFile::open(&path, ChecksumAlgorithm::Blake3)
+        });
+        let file = match with_keepalives(&mut self.conn.framed, hashing)
+            .await
+            .map_err(AttemptError::into_inner)?
+        {
+            Ok(Ok(file)) => file,
+            Ok(Err(e)) => return Ok(Outcome::Failed(e.to_string())),
+            Err(e) => {
+                return Err(FluxError::TransferError(format!("Checksum task failed: {}", e)))
+            }
+        };
+
+        let put = FluxMessage::PutFile {
+            path: dest.to_string(),
+            size: file.size,
+            modified,
+            checksum: file.checksum.clone(),
+        };
+        let conn = &mut self.conn;
+        send_message(&mut conn.framed, &put, "file request")
+            .await
+            .map_err(AttemptError::into_inner)?;
+        let segments = [Segment::Data {
+            offset: 0,
+            len: file.size,
+        }];
+        let outbound = Outbound {
+            chunk_size: conn.chunk_size,
+            channel: conn.channel.as_ref(),
+            zero_copy: conn.zero_copy,
+            limiter: conn.limiter.as_ref(),
+        };
+        stream_chunks(&mut conn.framed, &file, &segments, outbound, pb, None)
+            .await
+            .map_err(AttemptError::into_inner)?;
+        let report = await_completion(&mut conn.framed, self.peer.clone(), &file)
+            .await
+            .map_err(AttemptError::into_inner)?;
+        Ok(Outcome::Done(report.bytes))
+    }
+
+    /// Fetch `src` from the receiver into the local file `dest`.
+    async fn get(
+        &mut self,
+        src: &str,
+        dest: &Path,
+        modified: Option<i64>,
+        pb: &ProgressBar,
+    ) -> Result<Outcome, FluxError> {
+        let get = FluxMessage::GetFile {
+            path: src.to_string(),
+        };
+        let (size, checksum) = match self.request(&get, "file request").await? {
+            FluxMessage::FileHeader { size, checksum, .. } => (size, checksum),
+            FluxMessage::Error { message } => return Ok(Outcome::Failed(message)),
+            other => return Err(unexpected(other)),
+        };
+        let algorithm = match checksum.as_deref().map(ChecksumAlgorithm::of_tagged) {
+            Some(algorithm) => algorithm?,
+            None => ChecksumAlgorithm::Blake3,
+        };
+        let mut incoming = IncomingFile::replace(dest, size, algorithm)?;
+        pb.set_length(size);
+        let conn = &mut self.conn;
+        let inbound = Inbound {
+            channel: conn.channel.as_ref(),
+            low_memory: false,
+            limiter: None,
+        };
+        receive_chunks(&mut conn.framed, &mut incoming, inbound, pb, None, None)
+            .await
+            .map_err(AttemptError::into_inner)?;
+        if let Err(actual) = incoming.verify(checksum.as_deref()) {
+            // Dropping `incoming` deletes the corrupted temp file
+            let e = FluxError::TransferError(format!(
+                "Checksum mismatch for {}: expected {}, got {}",
+                src,
+                checksum.as_deref().unwrap_or_default(),
+                actual
+            ));
+            refuse(&mut conn.framed, &e).await;
+            return Err(e);
+        }
+        let actual = incoming.checksum();
+        let output_path = incoming.commit()?;
+        set_modified(&output_path, modified);
+
+        let complete = FluxMessage::TransferComplete {
+            filename: src.to_string(),
+            bytes_received: size,
+            checksum_verified: checksum.map(|_| true),
+            checksum: Some(actual),
+        };
+        send_message(&mut conn.framed, &complete, "transfer complete")
+            .await
+            .map_err(AttemptError::into_inner)?;
+        Ok(Outcome::Done(size))
+    }
+
+    /// Delete `path` on the receiver.
+    async fn delete(&mut self, path: &str) -> Result<Outcome, FluxError> {
+        let delete = FluxMessage::DeletePath {
+            path: path.to_string(),
+        };
+        match self.request(&delete, "delete request").await? {
+            FluxMessage::PathDeleted { .. } => Ok(Outcome::Done(0)),
+            FluxMessage::Error { message } => Ok(Outcome::Failed(message)),
+            other => Err(unexpected(other)),
+        }
+    }
+}
+
+/// Error for an answer the request did not expect.
+fn unexpected(answer: FluxMessage) -> FluxError {
+    match answer {
+        FluxMessage::Error { message } => {
+            FluxError::TransferError(format!("Receiver error: {}", message))
+        }
+        _ => FluxError::ProtocolError("Unexpected answer from the receiver".into()),
+    }
+}
+
+/// Progress bar for one file of `size` bytes; hidden when quiet.
+fn file_progress(size: u64, quiet: bool) -> ProgressBar {
+    if quiet {
+        ProgressBar::hidden()
+    } else {
+        create_network_progress(size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_plain_relative_paths_are_accepted() {
+        assert_eq!(relative_path("photos/2024/a.jpg"), Some(PathBuf::from("photos/2024/a.jpg")));
+        assert_eq!(relative_path("./photos//a.jpg"), Some(PathBuf::from("photos/a.jpg")));
+        assert_eq!(relative_path(""), Some(PathBuf::new()));
+        assert_eq!(relative_path("/etc/passwd"), None);
+        assert_eq!(relative_path("photos/../../etc"), None);
+        assert_eq!(relative_path(".."), None);
+    }
+
+    #[test]
+    fn listings_skip_temp_files() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("sub")).unwrap();
+        std::fs::write(dir.path().join("a.txt"), "aaaa").unwrap();
+        std::fs::write(dir.path().join("sub/b.txt"), "bb").unwrap();
+        std::fs::write(dir.path().join("sub/.b.txt.flux-tmp"), "b").unwrap();
+
+        let files = list_files(dir.path()).unwrap();
+        let paths: Vec<&str> = files.iter().map(|file| file.path.as_str()).collect();
+        assert_eq!(paths, ["a.txt", "sub/b.txt"]);
+        assert_eq!(files[0].size, 4);
+        assert!(files[0].modified.is_some());
+        assert!(list_files(&dir.path().join("missing")).unwrap().is_empty());
+    }
+
+    #[test]
+    fn long_listings_span_several_batches() {
+        assert_eq!(listing_batches(Vec::new()).len(), 1);
+
+        let file = |i: usize| ListedFile {
+            path: format!("{}/{:04}.jpg", "d".repeat(1000), i),
+            size: 1,
+            modified: None,
+        };
+        let batches = listing_batches((0..1000).map(file).collect());
+        assert!(batches.len() > 1);
+        assert_eq!(batches.iter().map(Vec::len).sum::<usize>(), 1000);
+        for batch in &batches {
+            let bytes: usize = batch.iter().map(|file| file.path.len() + 16).sum();
+            assert!(bytes <= LISTING_BATCH_BYTES);
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinks_out_of_the_root_are_refused() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("inbox");
+        let outside = dir.path().join("outside");
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::create_dir_all(&outside).unwrap();
+        std::os::unix::fs::symlink(&outside, root.join("escape")).unwrap();
+
+        assert_eq!(resolve(&root, "photos/a.jpg").unwrap(), root.join("photos/a.jpg"));
+        assert!(resolve(&root, "escape/a.jpg").is_err());
+        assert!(resolve(&root, "../outside/a.jpg").is_err());
+        // A missing root has nothing to escape through
+        let missing = dir.path().join("missing");
+        assert_eq!(resolve(&missing, "a.jpg").unwrap(), missing.join("a.jpg"));
+    }
+}
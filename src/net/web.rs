@@ -0,0 +1,632 @@
+//! Browser drops for `flux receive --web`.
+//!
+//! Next to the native listener, the receiver serves a small page over HTTP
+//! so phones and laptops without flux can drop files on it, and download
+//! the files the host offers with `--offer`. The page asks for the code
+//! phrase printed at startup, and every request other than the page itself
+//! must carry it (the `X-Flux-Code` header, or `?code=` on download links).
+//! After `MAX_CODE_FAILURES` wrong codes the page stays locked until the
+//! receiver restarts.
+//!
+//! Uploads take the same path as native transfers: the output directory
+//! template, sanitized and de-duplicated names, an atomic temp file, the
+//! free-space check and the daily quota (as the device `web:<address>`),
+//! and a history entry. The server is deliberately small: one request per
+//! connection, raw request bodies (no multipart), `Connection: close`.
+//!
+//! Plain HTTP is not encrypted; use `--web` on networks you trust.
+
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+use subtle::ConstantTimeEq;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite};
+use tokio::io::{AsyncWriteExt, BufReader};
+use tokio::sync::Semaphore;
+
+use crate::config::aliases::expand_variables;
+use crate::error::FluxError;
+use crate::net::addr;
+use crate::net::audit::{AuditKind, AuditTrail};
+use crate::net::codephrase;
+use crate::net::receiver::{
+    finish_receive_record, IncomingFile, ReceiveReport, ReceiverSettings, MAX_RECEIVE_SIZE,
+};
+use crate::net::resume::stall_timeout;
+use crate::transfer::cancel;
+use crate::transfer::checksum::ChecksumAlgorithm;
+use crate::transfer::history::{record_history, HistoryRecord};
+use crate::transfer::stats::TransferStats;
+
+/// Port of the web page unless `--web-port` says otherwise.
+pub const DEFAULT_WEB_PORT: u16 = 9742;
+
+/// Wrong code phrases accepted before the page locks.
+const MAX_CODE_FAILURES: u32 = 10;
+
+/// Longest request line plus headers.
+const MAX_HEAD: u64 = 16 * 1024;
+
+/// Time allowed for a client to send its request line and headers.
+const HEAD_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Upload and download buffer size.
+const BUF_SIZE: usize = 256 * 1024;
+
+/// Browser connections handled at once.
+const MAX_CONNECTIONS: usize = 8;
+
+const TEXT: &str = "text/plain; charset=utf-8";
+const JSON: &str = "application/json";
+
+/// The page itself. `{device}` is replaced with the receiver's name.
+const PAGE: &str = include_str!("web_page.html");
+
+/// What `flux receive --web` serves.
+pub struct WebDrop {
+    port: u16,
+    /// Code phrase browsers must enter
+    code: String,
+    /// `--offer` files, downloadable by index
+    offered: Vec<PathBuf>,
+}
+
+impl WebDrop {
+    /// A web page on `port` with a fresh code phrase, offering `offered`
+    /// for download.
+    pub fn new(port: u16, offered: &[PathBuf]) -> Result<Self, FluxError> {
+        for path in offered {
+            if !path.exists() {
+                return Err(FluxError::SourceNotFound { path: path.clone() });
+            }
+            if path.is_dir() {
+                return Err(FluxError::IsDirectory { path: path.clone() });
+            }
+        }
+        Ok(Self {
+            port,
+            code: codephrase::generate(),
+            offered: offered.to_vec(),
+        })
+    }
+}
+
+/// State shared by the browser connections.
+struct WebState {
+    code: CodeCheck,
+    offered: Vec<PathBuf>,
+    device_name: String,
+    settings: Arc<RwLock<ReceiverSettings>>,
+}
+
+/// The code phrase, and how many wrong ones were tried.
+struct CodeCheck {
+    code: String,
+    failures: AtomicU32,
+}
+
+/// Bind the web page on `bind_addr` and serve it in the background for as
+/// long as the runtime lives. Uploads use the current `settings`, so a
+/// reload (SIGHUP) applies to them too.
+pub(crate) async fn start(
+    web: WebDrop,
+    bind_addr: &str,
+    device_name: &str,
+    settings: Arc<RwLock<ReceiverSettings>>,
+) -> Result<(), FluxError> {
+    let listener = addr::listen(bind_addr, web.port).await.map_err(|e| {
+        FluxError::TransferError(format!(
+            "Failed to bind the web page on {}: {}. Try a different port with --web-port.",
+            addr::join_host_port(bind_addr, web.port),
+            e
+        ))
+    })?;
+    let port = listener
+        .local_addr()
+        .map_err(|e| FluxError::TransferError(format!("Failed to get local address: {}", e)))?
+        .port();
+    for url in page_urls(bind_addr, port) {
+        eprintln!("Web drop: {}", url);
+    }
+    eprintln!("Web code phrase: {}", web.code);
+
+    let state = Arc::new(WebState {
+        code: CodeCheck {
+            code: web.code,
+            failures: AtomicU32::new(0),
+        },
+        offered: web.offered,
+        device_name: device_name.to_string(),
+        settings,
+    });
+    let slots = Arc::new(Semaphore::new(MAX_CONNECTIONS));
+    tokio::spawn(async move {
+        loop {
+            let (stream, peer) = match listener.accept().await {
+                Ok((stream, peer)) => (stream, addr::canonical(peer)),
+                Err(e) => {
+                    tracing::warn!("Web page: failed to accept a connection: {}", e);
+                    continue;
+                }
+            };
+            let Ok(permit) = Arc::clone(&slots).acquire_owned().await else {
+                return;
+            };
+            let state = Arc::clone(&state);
+            tokio::spawn(async move {
+                let _permit = permit;
+                let (read, mut write) = stream.into_split();
+                let mut read = BufReader::new(read);
+                let handled = tokio::time::timeout(
+                    Duration::from_secs(30 * 60),
+                    handle(&mut read, &mut write, peer, &state),
+                )
+                .await;
+                match handled {
+                    Ok(Err(e)) => eprintln!("Web request from {}: {}", peer.ip(), e),
+                    Err(_) => eprintln!("Web connection from {} timed out", peer.ip()),
+                    Ok(Ok(())) => {}
+                }
+            });
+        }
+    });
+    Ok(())
+}
+
+/// Addresses to open the page at: every non-loopback IPv4 address when
+/// bound to all interfaces (`0.0.0.0` or `::`), else the bound address.
+fn page_urls(bind_addr: &str, port: u16) -> Vec<String> {
+    let mut hosts = Vec::new();
+    if addr::is_unspecified(bind_addr) {
+        if let Ok(interfaces) = if_addrs::get_if_addrs() {
+            for iface in interfaces.iter().filter(|i| !i.is_loopback()) {
+                if let if_addrs::IfAddr::V4(ref v4) = iface.addr {
+                    hosts.push(v4.ip.to_string());
+                }
+            }
+        }
+        if hosts.is_empty() {
+            hosts.push("localhost".to_string());
+        }
+    } else {
+        hosts.push(bind_addr.trim_start_matches('[').trim_end_matches(']').to_string());
+    }
+    hosts
+        .into_iter()
+        .map(|host| format!("http://{}/", addr::join_host_port(&host, port)))
+        .collect()
+}
+
+/// A parsed request line and headers.
+#[derive(Debug)]
+struct Request {
+    method: String,
+    path: String,
+    query: Vec<(String, String)>,
+    /// Header names lowercased
+    headers: Vec<(String, String)>,
+}
+
+impl Request {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    fn param(&self, name: &str) -> Option<&str> {
+        self.query
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// Read the request line and headers, leaving the body in `reader`.
+/// `None` if the client closed the connection without sending anything.
+async fn read_request<R: AsyncBufRead + Unpin>(
+    reader: &mut R,
+) -> Result<Option<Request>, FluxError> {
+    let mut head = reader.take(MAX_HEAD);
+    let mut line = String::new();
+    if head.read_line(&mut line).await? == 0 {
+        return Ok(None);
+    }
+    let mut parts = line.split_whitespace();
+    let (Some(method), Some(target), Some(_version)) = (parts.next(), parts.next(), parts.next())
+    else {
+        return Err(bad_request("malformed request line"));
+    };
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let mut request = Request {
+        method: method.to_string(),
+        path: path.to_string(),
+        query: url::form_urlencoded::parse(query.as_bytes()).into_owned().collect(),
+        headers: Vec::new(),
+    };
+
+    loop {
+        line.clear();
+        if head.read_line(&mut line).await? == 0 {
+            return Err(bad_request("request headers are too long or incomplete"));
+        }
+        let header = line.trim_end();
+        if header.is_empty() {
+            return Ok(Some(request));
+        }
+        let (name, value) = header
+            .split_once(':')
+            .ok_or_else(|| bad_request("malformed header"))?;
+        request
+            .headers
+            .push((name.trim().to_ascii_lowercase(), value.trim().to_string()));
+    }
+}
+
+/// Answer one request.
+async fn handle<R, W>(
+    reader: &mut R,
+    out: &mut W,
+    peer: SocketAddr,
+    state: &WebState,
+) -> Result<(), FluxError>
+where
+    R: AsyncBufRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let request = match tokio::time::timeout(HEAD_TIMEOUT, read_request(reader)).await {
+        Ok(Ok(Some(request))) => request,
+        Ok(Ok(None)) | Err(_) => return Ok(()),
+        Ok(Err(e)) => {
+            let message = e.to_string();
+            return respond(out, "400 Bad Request", TEXT, message.as_bytes()).await;
+        }
+    };
+
+    if (request.method.as_str(), request.path.as_str()) == ("GET", "/") {
+        let page = PAGE.replace("{device}", &html_escape(&state.device_name));
+        return respond(out, "200 OK", "text/html; charset=utf-8", page.as_bytes()).await;
+    }
+    if let Err((status, message)) = state.code.check(&request) {
+        // A missing code is the page asking for one, not an attempt
+        if request.header("x-flux-code").or_else(|| request.param("code")).is_some() {
+            AuditTrail::new("web", Some(peer))
+                .event(AuditKind::Refused)
+                .reason(message)
+                .record();
+        }
+        return respond(out, status, TEXT, message.as_bytes()).await;
+    }
+    match (request.method.as_str(), request.path.as_str()) {
+        ("GET", "/files") => {
+            let files: Vec<_> = state
+                .offered
+                .iter()
+                .map(|path| {
+                    serde_json::json!({
+                        "name": file_name(path),
+                        "size": std::fs::metadata(path).map(|m| m.len()).unwrap_or(0),
+                    })
+                })
+                .collect();
+            let body = serde_json::Value::Array(files).to_string();
+            respond(out, "200 OK", JSON, body.as_bytes()).await
+        }
+        ("GET", path) if path.starts_with("/files/") => {
+            let offered = path["/files/".len()..]
+                .parse::<usize>()
+                .ok()
+                .and_then(|index| state.offered.get(index));
+            match offered {
+                Some(file) => send_offered(out, file, peer).await,
+                None => respond(out, "404 Not Found", TEXT, b"No such file").await,
+            }
+        }
+        ("POST", "/upload") => receive_upload(reader, out, &request, peer, state).await,
+        _ => respond(out, "404 Not Found", TEXT, b"Not found").await,
+    }
+}
+
+impl CodeCheck {
+    /// Whether the request carries the code phrase; else the status and
+    /// message to answer with. Wrong codes count towards the lock.
+    fn check(&self, request: &Request) -> Result<(), (&'static str, &'static str)> {
+        if self.failures.load(Ordering::SeqCst) >= MAX_CODE_FAILURES {
+            return Err((
+                "403 Forbidden",
+                "Too many wrong code phrases; restart flux receive --web",
+            ));
+        }
+        let given = request.header("x-flux-code").or_else(|| request.param("code"));
+        match given {
+            Some(code) if bool::from(code.trim().as_bytes().ct_eq(self.code.as_bytes())) => {
+                Ok(())
+            }
+            Some(_) => {
+                self.failures.fetch_add(1, Ordering::SeqCst);
+                Err(("403 Forbidden", "Wrong code phrase"))
+            }
+            None => Err(("401 Unauthorized", "Enter the code phrase shown by flux receive")),
+        }
+    }
+}
+
+/// Write an upload into the output directory, as a native receive would.
+async fn receive_upload<R, W>(
+    reader: &mut R,
+    out: &mut W,
+    request: &Request,
+    peer: SocketAddr,
+    state: &WebState,
+) -> Result<(), FluxError>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let Some(size) = request.header("content-length").and_then(|v| v.parse::<u64>().ok()) else {
+        return respond(out, "411 Length Required", TEXT, b"Uploads need a Content-Length").await;
+    };
+    if size > MAX_RECEIVE_SIZE {
+        let message = format!("File too large: the limit is {} bytes", MAX_RECEIVE_SIZE);
+        return respond(out, "413 Payload Too Large", TEXT, message.as_bytes()).await;
+    }
+    let filename = request.param("name").unwrap_or("unnamed");
+    let device = format!("web:{}", peer.ip());
+    let started = Instant::now();
+
+    let settings = state.settings.read().unwrap_or_else(|e| e.into_inner()).clone();
+    let output_dir = PathBuf::from(expand_variables(&settings.output));
+    let mut record = HistoryRecord::new("receive", &device, &output_dir.display().to_string());
+    record.peer = Some(device.clone());
+    let mut audit = AuditTrail::new("web", Some(peer));
+    audit.file = Some(filename.to_string());
+    audit.size = Some(size);
+
+    let result = write_upload(reader, &settings, &output_dir, filename, size, &device).await;
+    let report = result.map(|output_path| ReceiveReport {
+        output_path,
+        bytes: size,
+        peer: device.clone(),
+        checksum_verified: None,
+        receipt: None,
+    });
+    finish_receive_record(&mut record, &audit, &report);
+    match report {
+        Ok(report) => {
+            let name = file_name(&report.output_path);
+            let mut stats = TransferStats::new(1, size);
+            stats.started = started;
+            stats.add_done(size);
+            stats.print_file_summary(&name, false);
+            let body = serde_json::json!({ "name": name, "bytes": size }).to_string();
+            respond(out, "200 OK", JSON, body.as_bytes()).await
+        }
+        Err(e) => {
+            eprintln!("Refusing {} from {}: {}", filename, device, e);
+            let status = match e {
+                FluxError::QuotaExceeded(_) | FluxError::InsufficientSpace(_) => {
+                    "507 Insufficient Storage"
+                }
+                _ => "500 Internal Server Error",
+            };
+            respond(out, status, TEXT, e.to_string().as_bytes()).await?;
+            Err(e)
+        }
+    }
+}
+
+/// Stream `size` bytes of request body into a new file in `output_dir`.
+/// Returns where the file was written.
+async fn write_upload<R: AsyncRead + Unpin>(
+    reader: &mut R,
+    settings: &ReceiverSettings,
+    output_dir: &Path,
+    filename: &str,
+    size: u64,
+    device: &str,
+) -> Result<PathBuf, FluxError> {
+    let mut incoming = IncomingFile::create(output_dir, filename, size, ChecksumAlgorithm::Blake3)?;
+    incoming.check_space(&settings.space)?;
+    let reservation = settings.quota.reserve(device, size)?;
+    // Browser drops have no verified device, so only the total storage
+    // quota applies to them
+    let _stored = settings.storage.reserve(output_dir, None, device, size)?;
+    if settings.space.preallocate {
+        incoming.preallocate();
+    }
+    eprintln!("Receiving {} from {}", incoming.display_name(filename), device);
+
+    let mut buf = vec![0u8; BUF_SIZE];
+    let mut remaining = size;
+    let received = async {
+        while remaining > 0 {
+            cancel::check()?;
+            let want = buf.len().min(remaining as usize);
+            let n = match tokio::time::timeout(stall_timeout(), reader.read(&mut buf[..want]))
+                .await
+            {
+                Ok(Ok(0)) | Err(_) => {
+                    return Err(FluxError::TransferError(format!(
+                        "Upload of '{}' stopped after {} of {} bytes",
+                        filename,
+                        size - remaining,
+                        size
+                    )))
+                }
+                Ok(Ok(n)) => n,
+                Ok(Err(e)) => return Err(e.into()),
+            };
+            incoming.write(&buf[..n], false)?;
+            remaining -= n as u64;
+        }
+        Ok::<(), FluxError>(())
+    }
+    .await;
+    reservation.settle(size - remaining);
+    // On error, dropping `incoming` removes the temp file
+    received?;
+    incoming.commit()
+}
+
+/// Stream an offered file to the browser as a download.
+async fn send_offered<W: AsyncWrite + Unpin>(
+    out: &mut W,
+    path: &Path,
+    peer: SocketAddr,
+) -> Result<(), FluxError> {
+    let device = format!("web:{}", peer.ip());
+    let mut record = HistoryRecord::new("send", &path.display().to_string(), &device);
+    record.peer = Some(device.clone());
+    let mut audit = AuditTrail::new("web", Some(peer));
+    audit.file = Some(file_name(path));
+    let sent = async {
+        let mut file = tokio::fs::File::open(path).await?;
+        let size = file.metadata().await?.len();
+        let name = file_name(path);
+        let encoded: String = url::form_urlencoded::byte_serialize(name.as_bytes()).collect();
+        let head = format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: application/octet-stream\r\n\
+             Content-Length: {}\r\nContent-Disposition: attachment; filename*=UTF-8''{}\r\n\
+             Cache-Control: no-store\r\nConnection: close\r\n\r\n",
+            size, encoded
+        );
+        out.write_all(head.as_bytes()).await?;
+        let mut buf = vec![0u8; BUF_SIZE];
+        let mut bytes = 0u64;
+        loop {
+            cancel::check()?;
+            let n = file.read(&mut buf).await?;
+            if n == 0 {
+                break;
+            }
+            tokio::time::timeout(stall_timeout(), out.write_all(&buf[..n]))
+                .await
+                .map_err(|_| FluxError::TransferError(format!("{} stopped reading", device)))??;
+            bytes += n as u64;
+        }
+        out.flush().await?;
+        eprintln!("Sent {} to {}", name, device);
+        Ok::<u64, FluxError>(bytes)
+    }
+    .await;
+    match sent {
+        Ok(bytes) => {
+            record.bytes = bytes;
+            record.files = 1;
+            record_history(&record, None);
+            audit.finish(Ok((bytes, None)));
+            Ok(())
+        }
+        Err(e) => {
+            record_history(&record, Some(&e));
+            audit.finish(Err(&e));
+            Err(e)
+        }
+    }
+}
+
+/// Write a complete response and close.
+async fn respond<W: AsyncWrite + Unpin>(
+    out: &mut W,
+    status: &str,
+    content_type: &str,
+    body: &[u8],
+) -> Result<(), FluxError> {
+    let head = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\n\
+         Cache-Control: no-store\r\nConnection: close\r\n\r\n",
+        status,
+        content_type,
+        body.len()
+    );
+    out.write_all(head.as_bytes()).await?;
+    out.write_all(body).await?;
+    out.flush().await?;
+    Ok(())
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+fn html_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn bad_request(message: &str) -> FluxError {
+    FluxError::TransferError(format!("Bad request: {}", message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn requests_parse_query_and_headers() {
+        let raw = b"POST /upload?name=holiday%20photo.jpg HTTP/1.1\r\n\
+                    Host: 192.168.1.20:9742\r\nX-Flux-Code: 1234-a-b-c-d\r\n\
+                    Content-Length: 5\r\n\r\nhello";
+        let mut reader = &raw[..];
+        let request = read_request(&mut reader).await.unwrap().unwrap();
+        assert_eq!(request.method, "POST");
+        assert_eq!(request.path, "/upload");
+        assert_eq!(request.param("name"), Some("holiday photo.jpg"));
+        assert_eq!(request.header("x-flux-code"), Some("1234-a-b-c-d"));
+        assert_eq!(request.header("content-length"), Some("5"));
+        // The body is left for the upload
+        assert_eq!(reader, b"hello");
+
+        let mut empty = &b""[..];
+        assert!(read_request(&mut empty).await.unwrap().is_none());
+        let mut garbage = &b"HELLO\r\n\r\n"[..];
+        assert!(read_request(&mut garbage).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn oversized_headers_are_refused() {
+        let mut raw = b"GET / HTTP/1.1\r\nX-Pad: ".to_vec();
+        raw.extend(vec![b'a'; MAX_HEAD as usize]);
+        raw.extend_from_slice(b"\r\n\r\n");
+        let mut reader = &raw[..];
+        assert!(read_request(&mut reader).await.is_err());
+    }
+
+    #[test]
+    fn wrong_codes_lock_the_page() {
+        let check = CodeCheck {
+            code: "1234-apple-brave-cedar-delta".to_string(),
+            failures: AtomicU32::new(0),
+        };
+        let with_code = |code: Option<&str>| Request {
+            method: "GET".to_string(),
+            path: "/files".to_string(),
+            query: code.map(|c| ("code".to_string(), c.to_string())).into_iter().collect(),
+            headers: Vec::new(),
+        };
+        assert!(check.check(&with_code(Some("1234-apple-brave-cedar-delta"))).is_ok());
+        assert_eq!(check.check(&with_code(None)).unwrap_err().0, "401 Unauthorized");
+
+        for _ in 0..MAX_CODE_FAILURES {
+            assert!(check.check(&with_code(Some("1111-apple-brave-cedar-delta"))).is_err());
+        }
+        // Locked, even for the right code
+        assert!(check.check(&with_code(Some("1234-apple-brave-cedar-delta"))).is_err());
+    }
+
+    #[test]
+    fn device_names_are_escaped_in_the_page() {
+        assert_eq!(
+            html_escape("<b>\"desk\" & co</b>"),
+            "&lt;b&gt;&quot;desk&quot; &amp; co&lt;/b&gt;"
+        );
+    }
+}
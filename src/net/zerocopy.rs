@@ -0,0 +1,222 @@
+//! Zero-copy sending of file data on unencrypted sessions.
+//!
+//! Encrypted chunks have to pass through userspace, but plain ones don't: when
+//! the receiver announces `zero_copy` in its `HandshakeAck`, the sender writes
+//! each chunk as a `RawData` frame followed by the file bytes, which the
+//! kernel moves from the page cache to the socket (`sendfile` on Linux,
+//! `TransmitFile` on Windows). Other platforms, encrypted sessions and
+//! `flux send --no-zero-copy` keep sending `DataChunk`s.
+//!
+//! The receiver reads the raw bytes straight after the frame, taking what
+//! the codec has already buffered first (`read_raw`).
+
+use std::fs::File;
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use tokio::io::AsyncReadExt;
+use tokio::net::TcpStream;
+use tokio_util::codec::{Framed, LengthDelimitedCodec};
+
+/// Set by `--no-zero-copy`, for comparing both paths on the same link.
+static DISABLED: AtomicBool = AtomicBool::new(false);
+
+/// Whether this platform can send file data without copying it.
+pub fn supported() -> bool {
+    cfg!(any(target_os = "linux", windows))
+}
+
+/// Send `DataChunk`s for the rest of the process (`--no-zero-copy`).
+pub fn disable() {
+    DISABLED.store(true, Ordering::Relaxed);
+}
+
+/// Whether sends should use zero-copy when the receiver offers it.
+pub fn enabled() -> bool {
+    supported() && !DISABLED.load(Ordering::Relaxed)
+}
+
+/// Send `len` bytes of `file` from `offset` on `stream`, all or nothing.
+///
+/// Fails with `UnexpectedEof` if the file ends first (it shrank while being
+/// sent); the stream is then out of step and must not be used further.
+#[cfg(target_os = "linux")]
+pub async fn send_file_range(
+    stream: &TcpStream,
+    file: &File,
+    offset: u64,
+    len: usize,
+) -> io::Result<()> {
+    use std::os::unix::io::AsRawFd;
+    use tokio::io::Interest;
+
+    let mut sent = 0;
+    while sent < len {
+        stream.writable().await?;
+        let result = stream.try_io(Interest::WRITABLE, || {
+            let mut position = (offset + sent as u64) as libc::off_t;
+            // SAFETY: both descriptors are open for the duration of the call
+            // and `position` outlives it
+            let n = unsafe {
+                libc::sendfile(stream.as_raw_fd(), file.as_raw_fd(), &mut position, len - sent)
+            };
+            if n < 0 {
+                Err(io::Error::last_os_error())
+            } else {
+                Ok(n as usize)
+            }
+        });
+        match result {
+            Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+            Ok(n) => sent += n,
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(())
+}
+
+/// Send `len` bytes of `file` from `offset` on `stream`, all or nothing.
+///
+/// Fails with `UnexpectedEof` if the file ends first (it shrank while being
+/// sent); the stream is then out of step and must not be used further.
+#[cfg(windows)]
+pub async fn send_file_range(
+    stream: &TcpStream,
+    file: &File,
+    offset: u64,
+    len: usize,
+) -> io::Result<()> {
+    use std::os::windows::io::{AsRawHandle, AsRawSocket};
+
+    let len = u32::try_from(len).map_err(|_| io::Error::from(io::ErrorKind::InvalidInput))?;
+    let socket = stream.as_raw_socket() as usize;
+    let handle = file.as_raw_handle() as usize;
+    // Both stay open: this future holds their borrows until the call returns
+    tokio::task::spawn_blocking(move || transmit_file(socket, handle, offset, len))
+        .await
+        .map_err(io::Error::other)?
+}
+
+/// Blocking `TransmitFile` of one range. Overlapped, so the position comes
+/// from `offset` rather than the file pointer and the socket's non-blocking
+/// mode does not matter; waits for completion on the socket handle.
+#[cfg(windows)]
+fn transmit_file(socket: usize, handle: usize, offset: u64, len: u32) -> io::Result<()> {
+    use windows_sys::Win32::Networking::WinSock::{
+        TransmitFile, WSAGetLastError, WSAGetOverlappedResult, WSA_IO_PENDING,
+    };
+    use windows_sys::Win32::System::IO::OVERLAPPED;
+
+    // SAFETY: an all-zero OVERLAPPED (no event) is valid
+    let mut overlapped: OVERLAPPED = unsafe { std::mem::zeroed() };
+    overlapped.Anonymous.Anonymous.Offset = offset as u32;
+    overlapped.Anonymous.Anonymous.OffsetHigh = (offset >> 32) as u32;
+    // SAFETY: socket and file handle are open, `overlapped` lives until the
+    // operation has completed below, and no head/tail buffers are passed
+    let ok = unsafe {
+        TransmitFile(socket, handle as _, len, 0, &mut overlapped, std::ptr::null(), 0)
+    };
+    if ok == 0 {
+        // SAFETY: reads the calling thread's last socket error
+        let error = unsafe { WSAGetLastError() };
+        if error != WSA_IO_PENDING {
+            return Err(io::Error::from_raw_os_error(error));
+        }
+    }
+    let (mut sent, mut flags) = (0u32, 0u32);
+    // SAFETY: `overlapped` belongs to the operation started above; waiting
+    // (fWait = TRUE) returns only once it has completed
+    let ok = unsafe { WSAGetOverlappedResult(socket, &overlapped, &mut sent, 1, &mut flags) };
+    if ok == 0 {
+        // SAFETY: reads the calling thread's last socket error
+        return Err(io::Error::from_raw_os_error(unsafe { WSAGetLastError() }));
+    }
+    if sent < len {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(())
+}
+
+/// Without kernel support (never called: `supported()` is false).
+#[cfg(not(any(target_os = "linux", windows)))]
+pub async fn send_file_range(
+    _stream: &TcpStream,
+    _file: &File,
+    _offset: u64,
+    _len: usize,
+) -> io::Result<()> {
+    Err(io::ErrorKind::Unsupported.into())
+}
+
+/// Read the `len` raw bytes that follow a `RawData` frame: first what the
+/// codec read ahead into its buffer, then the rest from the socket.
+pub async fn read_raw(
+    framed: &mut Framed<TcpStream, LengthDelimitedCodec>,
+    len: usize,
+) -> io::Result<Vec<u8>> {
+    let buffered = framed.read_buffer_mut();
+    let ahead = buffered.len().min(len);
+    let mut data = buffered.split_to(ahead).to_vec();
+    data.resize(len, 0);
+    framed.get_mut().read_exact(&mut data[ahead..]).await?;
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::{SinkExt, StreamExt};
+    use std::io::Write;
+    use tokio_util::bytes::Bytes;
+
+    #[tokio::test]
+    async fn raw_bytes_follow_their_frame() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let mut source = tempfile::NamedTempFile::new().unwrap();
+        let content: Vec<u8> = (0..200_000u32).map(|i| (i % 251) as u8).collect();
+        source.write_all(&content).unwrap();
+        let file = source.reopen().unwrap();
+
+        let sender = tokio::spawn(async move {
+            let stream = TcpStream::connect(addr).await.unwrap();
+            let mut framed = Framed::new(stream, LengthDelimitedCodec::new());
+            framed.send(Bytes::from_static(b"head")).await.unwrap();
+            if supported() {
+                send_file_range(framed.get_ref(), &file, 1000, 150_000).await.unwrap();
+            } else {
+                use tokio::io::AsyncWriteExt;
+                let slice = std::fs::read(source.path()).unwrap()[1000..151_000].to_vec();
+                framed.get_mut().write_all(&slice).await.unwrap();
+            }
+            framed.send(Bytes::from_static(b"tail")).await.unwrap();
+            framed
+        });
+
+        let (stream, _) = listener.accept().await.unwrap();
+        let mut framed = Framed::new(stream, LengthDelimitedCodec::new());
+        assert_eq!(&framed.next().await.unwrap().unwrap()[..], b"head");
+        let data = read_raw(&mut framed, 150_000).await.unwrap();
+        assert_eq!(data, content[1000..151_000]);
+        assert_eq!(&framed.next().await.unwrap().unwrap()[..], b"tail");
+        drop(sender.await.unwrap());
+    }
+
+    #[cfg(any(target_os = "linux", windows))]
+    #[tokio::test]
+    async fn short_file_is_reported() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let accept = tokio::spawn(async move { listener.accept().await.unwrap() });
+
+        let mut source = tempfile::NamedTempFile::new().unwrap();
+        source.write_all(&[7u8; 100]).unwrap();
+        let file = source.reopen().unwrap();
+        let stream = TcpStream::connect(addr).await.unwrap();
+        let err = send_file_range(&stream, &file, 50, 100).await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+        drop(accept.await.unwrap());
+    }
+}
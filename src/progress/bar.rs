@@ -1,4 +1,198 @@
-use indicatif::{ProgressBar, ProgressDrawTarget, ProgressStyle};
+//! Progress bars for transfers, syncs and scans.
+//!
+//! Every bar is built here so that all commands adapt the same way to the
+//! terminal they run in: the layout is picked from the terminal width, and
+//! dumb consoles (`TERM=dumb`) get a plain, colourless line redrawn at a low
+//! rate instead of an animated bar.
+//!
+//! Directory copies and syncs use `BatchProgress`: one bytes-based line with
+//! the total ETA, plus a line with its own ETA for each large file in flight.
+//! Sends to several devices use `GroupProgress`, with a line per device.
+//! `flux queue run` uses `QueueProgress`, a line for the whole queue that the
+//! running entry's bars are drawn below.
+
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use indicatif::{MultiProgress, ProgressBar, ProgressDrawTarget, ProgressStyle};
+
+use crate::transfer::monitor::TransferMonitor;
+
+/// Terminals at least this wide get the full layout.
+const FULL_MIN_WIDTH: u16 = 100;
+
+/// Terminals at least this wide get the compact layout.
+const COMPACT_MIN_WIDTH: u16 = 60;
+
+/// While a `QueueProgress` is on screen, every new bar joins its
+/// MultiProgress instead of drawing on its own, so the running entry's bars
+/// appear below the queue line rather than over it.
+static PARENT: Mutex<Option<MultiProgress>> = Mutex::new(None);
+
+/// How often the queue line samples the running entry's monitor.
+const QUEUE_TICK: Duration = Duration::from_millis(200);
+
+/// Files at least this large get their own line in a `BatchProgress`.
+/// Smaller files finish too quickly for a line of their own to be readable.
+const FILE_LINE_MIN_BYTES: u64 = 16 * 1024 * 1024;
+
+/// How much of the progress line is shown.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProgressLayout {
+    /// Spinner, elapsed time, 40-column bar, totals, rate and ETA
+    Full,
+    /// Shorter bar and no elapsed time; the message is truncated to fit
+    Compact,
+    /// Percentage and totals only, no bar, spinner or colours
+    Minimal,
+}
+
+/// What a bar counts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ProgressKind {
+    Bytes,
+    /// Bytes of one file within a `BatchProgress`
+    File,
+    Scan,
+    /// Bytes of a stream whose length is unknown (`cp -`)
+    Stream,
+}
+
+/// Capabilities of the terminal progress is drawn on (stderr).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TerminalInfo {
+    /// Columns, if known
+    pub width: Option<u16>,
+    /// `TERM=dumb`: no cursor movement or colours
+    pub dumb: bool,
+    /// Colours allowed (`NO_COLOR` unset)
+    pub color: bool,
+}
+
+impl TerminalInfo {
+    /// Inspect the current terminal. `COLUMNS` overrides the detected width.
+    pub fn detect() -> Self {
+        let width = std::env::var("COLUMNS")
+            .ok()
+            .and_then(|c| c.trim().parse::<u16>().ok())
+            .filter(|&c| c > 0)
+            .or_else(|| crossterm::terminal::size().ok().map(|(cols, _)| cols));
+        let dumb = std::env::var("TERM").is_ok_and(|t| t == "dumb");
+        let color = !std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty());
+        Self { width, dumb, color }
+    }
+
+    /// Layout for this terminal. An unknown width keeps the full layout.
+    pub fn layout(&self) -> ProgressLayout {
+        if self.dumb {
+            return ProgressLayout::Minimal;
+        }
+        match self.width {
+            Some(w) if w < COMPACT_MIN_WIDTH => ProgressLayout::Minimal,
+            Some(w) if w < FULL_MIN_WIDTH => ProgressLayout::Compact,
+            _ => ProgressLayout::Full,
+        }
+    }
+
+    fn draw_target(&self) -> ProgressDrawTarget {
+        if self.dumb {
+            // Each redraw prints a new line on a dumb console; keep it rare
+            ProgressDrawTarget::stderr_with_hz(1)
+        } else {
+            ProgressDrawTarget::stderr()
+        }
+    }
+}
+
+/// Draw target for a bar that was created hidden and is shown later.
+#[cfg(feature = "net")]
+pub fn stderr_target() -> ProgressDrawTarget {
+    TerminalInfo::detect().draw_target()
+}
+
+/// Template for `kind` in `layout`. Colour specs are dropped without `color`.
+fn template(kind: ProgressKind, layout: ProgressLayout, color: bool) -> String {
+    let (spinner, bar) = if color {
+        ("{spinner:.green}", ".cyan/blue")
+    } else {
+        ("{spinner}", "")
+    };
+    match (kind, layout) {
+        (ProgressKind::Bytes, ProgressLayout::Full) => format!(
+            "{} [{{elapsed_precise}}] [{{bar:40{}}}] \
+             {{bytes}}/{{total_bytes}} ({{bytes_per_sec}}, ETA {{eta}}) {{msg}}",
+            spinner, bar
+        ),
+        (ProgressKind::Bytes, ProgressLayout::Compact) => format!(
+            "{} [{{bar:20{}}}] {{bytes}}/{{total_bytes}} ({{bytes_per_sec}}) {{wide_msg}}",
+            spinner, bar
+        ),
+        (ProgressKind::Bytes, ProgressLayout::Minimal) => {
+            "{percent:>3}% {bytes}/{total_bytes} {wide_msg}".to_string()
+        }
+        (ProgressKind::File, ProgressLayout::Full) => format!(
+            "  [{{bar:30{}}}] {{bytes}}/{{total_bytes}} (ETA {{eta}}) {{wide_msg}}",
+            bar
+        ),
+        (ProgressKind::File, ProgressLayout::Compact) => {
+            "  {percent:>3}% ETA {eta} {wide_msg}".to_string()
+        }
+        (ProgressKind::File, ProgressLayout::Minimal) => "  {percent:>3}% {wide_msg}".to_string(),
+        (ProgressKind::Scan, ProgressLayout::Full) => {
+            format!("{} [{{elapsed_precise}}] {{pos}} files {{msg}}", spinner)
+        }
+        (ProgressKind::Scan, ProgressLayout::Compact) => {
+            format!("{} {{pos}} files {{wide_msg}}", spinner)
+        }
+        (ProgressKind::Scan, ProgressLayout::Minimal) => "{pos} files {wide_msg}".to_string(),
+        (ProgressKind::Stream, ProgressLayout::Full) => format!(
+            "{} [{{elapsed_precise}}] {{bytes}} ({{bytes_per_sec}}) {{msg}}",
+            spinner
+        ),
+        (ProgressKind::Stream, ProgressLayout::Compact) => {
+            format!("{} {{bytes}} ({{bytes_per_sec}}) {{wide_msg}}", spinner)
+        }
+        (ProgressKind::Stream, ProgressLayout::Minimal) => "{bytes} {wide_msg}".to_string(),
+    }
+}
+
+fn style(kind: ProgressKind, layout: ProgressLayout, color: bool) -> ProgressStyle {
+    ProgressStyle::with_template(&template(kind, layout, color))
+        .expect("built-in progress template is valid")
+        .progress_chars("=>-")
+}
+
+/// Build a visible bar of `kind` for the current terminal.
+fn create_progress(kind: ProgressKind, len: Option<u64>) -> ProgressBar {
+    let terminal = TerminalInfo::detect();
+    let layout = terminal.layout();
+    let pb = match len {
+        Some(len) => ProgressBar::new(len),
+        None => ProgressBar::new_spinner(),
+    };
+    pb.set_draw_target(terminal.draw_target());
+    pb.set_style(style(kind, layout, terminal.color));
+    match parent() {
+        Some(multi) => multi.add(pb),
+        None => pb,
+    }
+}
+
+fn parent() -> Option<MultiProgress> {
+    lock(&PARENT).clone()
+}
+
+/// Run `f`, which prints to stderr, with the queue's bars cleared first and
+/// redrawn after, so the output is not drawn over. Without a `QueueProgress`
+/// on screen, just runs `f`.
+pub fn suspend<R>(f: impl FnOnce() -> R) -> R {
+    match parent() {
+        Some(multi) => multi.suspend(f),
+        None => f(),
+    }
+}
 
 /// Create a progress bar for tracking bytes during a single file copy.
 ///
@@ -8,40 +202,29 @@ pub fn create_file_progress(total_bytes: u64, quiet: bool) -> ProgressBar {
     if quiet {
         return ProgressBar::hidden();
     }
-
-    let pb = ProgressBar::new(total_bytes);
-    pb.set_draw_target(ProgressDrawTarget::stderr());
-    pb.set_style(
-        ProgressStyle::with_template(
-            "{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] \
-             {bytes}/{total_bytes} ({bytes_per_sec}, ETA {eta}) {msg}",
-        )
-        .expect("static progress template is valid")
-        .progress_chars("=>-"),
-    );
-    pb
+    create_progress(ProgressKind::Bytes, Some(total_bytes))
 }
 
-/// Create a progress bar for tracking files during a directory copy.
+/// Create a progress bar for a piped copy (`cp -`).
 ///
-/// Renders to stderr. Returns a hidden bar if quiet mode is active.
-/// Defined now for Plan 03 (directory copy) to avoid touching this file later.
-pub fn create_directory_progress(total_files: u64, quiet: bool) -> ProgressBar {
+/// With a known `total_bytes` this is the usual bytes bar; without one
+/// (reading stdin) a spinner shows the bytes so far and the rate. Renders to
+/// stderr, so it never mixes with data written to stdout. Returns a hidden
+/// bar if quiet mode is active.
+pub fn create_stream_progress(total_bytes: Option<u64>, quiet: bool) -> ProgressBar {
     if quiet {
         return ProgressBar::hidden();
     }
-
-    let pb = ProgressBar::new(total_files);
-    pb.set_draw_target(ProgressDrawTarget::stderr());
-    pb.set_style(
-        ProgressStyle::with_template(
-            "{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] \
-             {pos}/{len} files ({per_sec}, ETA {eta}) {msg}",
-        )
-        .expect("static progress template is valid")
-        .progress_chars("=>-"),
-    );
-    pb
+    match total_bytes {
+        Some(total) => create_progress(ProgressKind::Bytes, Some(total)),
+        None => {
+            let pb = create_progress(ProgressKind::Stream, None);
+            if TerminalInfo::detect().layout() != ProgressLayout::Minimal {
+                pb.enable_steady_tick(std::time::Duration::from_millis(120));
+            }
+            pb
+        }
+    }
 }
 
 /// Create a progress bar tracking bytes for directory transfers.
@@ -54,16 +237,628 @@ pub fn create_transfer_progress(total_bytes: u64, quiet: bool) -> ProgressBar {
     if quiet {
         return ProgressBar::hidden();
     }
+    create_progress(ProgressKind::Bytes, Some(total_bytes))
+}
 
-    let pb = ProgressBar::new(total_bytes);
-    pb.set_draw_target(ProgressDrawTarget::stderr());
-    pb.set_style(
-        ProgressStyle::with_template(
-            "{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] \
-             {bytes}/{total_bytes} ({bytes_per_sec}, ETA {eta}) {msg}",
-        )
-        .expect("static progress template is valid")
-        .progress_chars("=>-"),
-    );
+/// Create a progress bar for a P2P send or receive of `total_bytes`.
+///
+/// Renders to stderr. P2P transfers always show progress.
+#[cfg(feature = "net")]
+pub fn create_network_progress(total_bytes: u64) -> ProgressBar {
+    create_progress(ProgressKind::Bytes, Some(total_bytes))
+}
+
+/// Create a spinner for scans whose total is unknown up front.
+///
+/// Position counts files found; callers put the running byte total in the
+/// message. Renders to stderr. Returns a hidden bar if quiet mode is active.
+pub fn create_scan_progress(quiet: bool) -> ProgressBar {
+    if quiet {
+        return ProgressBar::hidden();
+    }
+    let pb = create_progress(ProgressKind::Scan, None);
+    if TerminalInfo::detect().layout() != ProgressLayout::Minimal {
+        pb.enable_steady_tick(std::time::Duration::from_millis(120));
+    }
     pb
 }
+
+/// Progress for copying a list of files: directory `cp` and `sync`.
+///
+/// The total line counts bytes across all files, with throughput and the
+/// overall ETA; its message shows how many files are done. Each file of at
+/// least `FILE_LINE_MIN_BYTES` also gets a line of its own while it is being
+/// copied, with its own ETA. Safe to share between copy workers.
+pub struct BatchProgress {
+    /// `None` when quiet or when the layout has room for one line only
+    multi: Option<MultiProgress>,
+    /// `multi` belongs to a `QueueProgress`; leave its other lines alone
+    nested: bool,
+    total: ProgressBar,
+    layout: ProgressLayout,
+    files: AtomicU64,
+    files_done: AtomicU64,
+    /// Files being copied, by start order, for `sample()`
+    active: Arc<Mutex<Vec<(u64, String, ProgressBar)>>>,
+    next_file: AtomicU64,
+}
+
+impl BatchProgress {
+    /// Progress for `files` files totalling `total_bytes`. Hidden if quiet.
+    pub fn new(total_bytes: u64, files: u64, quiet: bool) -> Self {
+        let terminal = TerminalInfo::detect();
+        let layout = terminal.layout();
+        let nested = !quiet && parent().is_some();
+        let (multi, total) = if quiet {
+            // Hidden, but sized for `sampler`
+            let total = ProgressBar::hidden();
+            total.set_length(total_bytes);
+            (None, total)
+        } else {
+            let total = create_progress(ProgressKind::Bytes, Some(total_bytes));
+            if nested {
+                (parent(), total)
+            } else if layout == ProgressLayout::Minimal {
+                (None, total)
+            } else {
+                let multi = MultiProgress::with_draw_target(terminal.draw_target());
+                let total = multi.add(total);
+                (Some(multi), total)
+            }
+        };
+        let progress = Self {
+            multi,
+            nested,
+            total,
+            layout,
+            files: AtomicU64::new(files),
+            files_done: AtomicU64::new(0),
+            active: Arc::default(),
+            next_file: AtomicU64::new(0),
+        };
+        progress.update_message();
+        progress
+    }
+
+    /// Start copying a file of `size` bytes. Pass `FileProgress::bar()` to
+    /// the copy routine, then call `done()` whatever the outcome.
+    pub fn start_file(&self, name: &str, size: u64) -> FileProgress<'_> {
+        let bar = match self.multi {
+            Some(ref multi) if size >= FILE_LINE_MIN_BYTES => {
+                let color = TerminalInfo::detect().color;
+                let bar = multi.add(ProgressBar::new(size));
+                bar.set_style(style(ProgressKind::File, self.layout, color));
+                bar.set_message(name.to_string());
+                bar
+            }
+            _ => ProgressBar::hidden(),
+        };
+        let id = self.next_file.fetch_add(1, Ordering::Relaxed);
+        lock(&self.active).push((id, name.to_string(), bar.clone()));
+        FileProgress {
+            batch: self,
+            bar,
+            size,
+            id,
+        }
+    }
+
+    /// A function returning the progress of the batch, for publishing it
+    /// from another thread (`flux status`).
+    ///
+    /// Bytes done include the files in flight; the current file is the one
+    /// started last.
+    pub fn sampler(&self) -> impl Fn() -> crate::transfer::status::Sample + Send + 'static {
+        let total = self.total.clone();
+        let active = Arc::clone(&self.active);
+        move || {
+            let active = lock(&active);
+            crate::transfer::status::Sample {
+                bytes_done: total.position() + active.iter().map(|f| f.2.position()).sum::<u64>(),
+                total_bytes: total.length().unwrap_or(0),
+                current_file: active.last().map(|f| f.1.clone()),
+            }
+        }
+    }
+
+    /// Add a file of `size` bytes to the totals, for batches whose files
+    /// are found while they are copied (`sync --low-memory`).
+    pub fn add_file(&self, size: u64) {
+        self.total.inc_length(size);
+        self.files.fetch_add(1, Ordering::Relaxed);
+        self.update_message();
+    }
+
+    /// Count a file that was not copied (skipped, or failed before starting).
+    pub fn skip_file(&self, size: u64) {
+        self.total.inc(size);
+        self.file_done();
+    }
+
+    /// Clear all lines once the batch is complete.
+    pub fn finish(&self) {
+        self.total.finish_and_clear();
+        if let Some(ref multi) = self.multi {
+            if self.nested {
+                multi.remove(&self.total);
+            } else {
+                let _ = multi.clear();
+            }
+        }
+    }
+
+    /// Leave the lines as they are (e.g. when paused).
+    pub fn abandon(&self) {
+        self.total.abandon();
+    }
+
+    fn file_done(&self) {
+        self.files_done.fetch_add(1, Ordering::Relaxed);
+        self.update_message();
+    }
+
+    fn update_message(&self) {
+        self.total.set_message(format!(
+            "{}/{} files",
+            self.files_done.load(Ordering::Relaxed),
+            self.files.load(Ordering::Relaxed)
+        ));
+    }
+}
+
+/// One file being copied within a `BatchProgress`.
+pub struct FileProgress<'a> {
+    batch: &'a BatchProgress,
+    bar: ProgressBar,
+    size: u64,
+    id: u64,
+}
+
+impl FileProgress<'_> {
+    /// Bar for the copy routine; hidden for small files.
+    pub fn bar(&self) -> &ProgressBar {
+        &self.bar
+    }
+
+    /// Remove the file's line and add its bytes to the total.
+    pub fn done(self) {
+        self.bar.finish_and_clear();
+        if let Some(ref multi) = self.batch.multi {
+            multi.remove(&self.bar);
+        }
+        // Under the lock, so a sample never misses the file's bytes
+        let mut active = lock(&self.batch.active);
+        active.retain(|f| f.0 != self.id);
+        self.batch.skip_file(self.size);
+    }
+}
+
+/// Progress for sending one file to several devices at once.
+///
+/// The total line counts bytes across all devices; its message shows how
+/// many devices are done and how many failed. Each device has a line of its
+/// own, left on screen with the outcome once the device is finished. Safe to
+/// share between the connections.
+#[cfg(feature = "net")]
+pub struct GroupProgress {
+    /// `None` when quiet or when the layout has room for one line only
+    multi: Option<MultiProgress>,
+    total: ProgressBar,
+    layout: ProgressLayout,
+    devices: u64,
+    done: AtomicU64,
+    failed: AtomicU64,
+}
+
+#[cfg(feature = "net")]
+impl GroupProgress {
+    /// Progress for sending `size` bytes to each of `devices` devices.
+    /// Hidden if quiet.
+    pub fn new(size: u64, devices: u64, quiet: bool) -> Self {
+        let terminal = TerminalInfo::detect();
+        let layout = terminal.layout();
+        let total_bytes = size * devices;
+        let (multi, total) = if quiet {
+            let hidden = ProgressDrawTarget::hidden();
+            (None, ProgressBar::with_draw_target(Some(total_bytes), hidden))
+        } else {
+            let total = create_progress(ProgressKind::Bytes, Some(total_bytes));
+            if layout == ProgressLayout::Minimal {
+                (None, total)
+            } else {
+                let multi = MultiProgress::with_draw_target(terminal.draw_target());
+                let total = multi.add(total);
+                (Some(multi), total)
+            }
+        };
+        let progress = Self {
+            multi,
+            total,
+            layout,
+            devices,
+            done: AtomicU64::new(0),
+            failed: AtomicU64::new(0),
+        };
+        progress.update_message();
+        progress
+    }
+
+    /// Line for the device `name`, receiving `size` bytes. Hidden when
+    /// there is no room for per-device lines.
+    pub fn device_bar(&self, name: &str, size: u64) -> ProgressBar {
+        match self.multi {
+            Some(ref multi) => {
+                let color = TerminalInfo::detect().color;
+                let bar = multi.add(ProgressBar::new(size));
+                bar.set_style(style(ProgressKind::File, self.layout, color));
+                bar.set_message(name.to_string());
+                bar
+            }
+            None => ProgressBar::with_draw_target(Some(size), ProgressDrawTarget::hidden()),
+        }
+    }
+
+    /// Count `bytes` sent to the device whose line is `bar`.
+    pub fn inc(&self, bar: &ProgressBar, bytes: u64) {
+        bar.inc(bytes);
+        self.total.inc(bytes);
+    }
+
+    /// Record the outcome for the device `name`: its line keeps the result,
+    /// and a failed device's remaining bytes leave the total.
+    pub fn device_done(&self, bar: &ProgressBar, name: &str, error: Option<&str>) {
+        let outcome = match error {
+            None => {
+                self.done.fetch_add(1, Ordering::Relaxed);
+                format!("{}: done", name)
+            }
+            Some(error) => {
+                self.failed.fetch_add(1, Ordering::Relaxed);
+                let remaining = bar.length().unwrap_or(0).saturating_sub(bar.position());
+                let total = self.total.length().unwrap_or(0);
+                self.total.set_length(total.saturating_sub(remaining));
+                format!("{}: failed ({})", name, error)
+            }
+        };
+        if self.multi.is_some() {
+            bar.abandon_with_message(outcome);
+        } else if !self.total.is_hidden() {
+            self.total.suspend(|| eprintln!("  {}", outcome));
+        }
+        self.update_message();
+    }
+
+    /// Clear the total line; the device lines stay.
+    pub fn finish(&self) {
+        self.total.finish_and_clear();
+    }
+
+    fn update_message(&self) {
+        let failed = self.failed.load(Ordering::Relaxed);
+        let mut message = format!(
+            "{}/{} devices",
+            self.done.load(Ordering::Relaxed),
+            self.devices
+        );
+        if failed > 0 {
+            message.push_str(&format!(", {} failed", failed));
+        }
+        self.total.set_message(message);
+    }
+}
+
+/// Progress for `flux queue run`.
+///
+/// The queue line counts bytes across all entries, with the overall rate and
+/// ETA; its message shows how many entries are done and how many failed. The
+/// running entry's own bars are drawn below it. Bytes come from each entry's
+/// `TransferMonitor`, sampled by a background thread; sizes not known up front
+/// (remote sources) are added when the entry starts.
+pub struct QueueProgress {
+    /// `None` when quiet or when the layout has room for one line only
+    multi: Option<MultiProgress>,
+    total: ProgressBar,
+    entries: u64,
+    done: AtomicU64,
+    failed: AtomicU64,
+    tally: Arc<Mutex<QueueTally>>,
+    stop: Arc<AtomicBool>,
+    ticker: Option<std::thread::JoinHandle<()>>,
+}
+
+/// Bytes of a queue run so far.
+#[derive(Debug, Default)]
+struct QueueTally {
+    /// Estimated size of each entry not started yet, in run order
+    pending: VecDeque<u64>,
+    /// Bytes copied by finished entries
+    finished: u64,
+    /// The running entry and its estimated size
+    current: Option<(TransferMonitor, u64)>,
+}
+
+impl QueueTally {
+    /// Bytes done and total bytes. The running entry counts at its real size
+    /// once its copy has measured the source.
+    fn sample(&self) -> (u64, u64) {
+        let pending: u64 = self.pending.iter().sum();
+        let (done, size) = match self.current {
+            Some((ref monitor, estimate)) => {
+                let snapshot = monitor.snapshot();
+                let size = if snapshot.total_bytes > 0 {
+                    snapshot.total_bytes
+                } else {
+                    estimate
+                };
+                (snapshot.bytes_done, size)
+            }
+            None => (0, 0),
+        };
+        (self.finished + done, self.finished + size + pending)
+    }
+}
+
+impl QueueProgress {
+    /// Progress for running entries with the estimated sizes `estimates`
+    /// (0 where unknown), in the order they will run. Hidden if quiet.
+    pub fn new(estimates: Vec<u64>, quiet: bool) -> Self {
+        let terminal = TerminalInfo::detect();
+        let entries = estimates.len() as u64;
+        let tally = Arc::new(Mutex::new(QueueTally {
+            pending: estimates.into(),
+            ..QueueTally::default()
+        }));
+        let (_, length) = lock(&tally).sample();
+        let (multi, total) = if quiet {
+            (None, ProgressBar::hidden())
+        } else {
+            let total = create_progress(ProgressKind::Bytes, Some(length));
+            if terminal.layout() == ProgressLayout::Minimal {
+                (None, total)
+            } else {
+                let multi = MultiProgress::with_draw_target(terminal.draw_target());
+                let total = multi.add(total);
+                *lock(&PARENT) = Some(multi.clone());
+                (Some(multi), total)
+            }
+        };
+        let stop = Arc::new(AtomicBool::new(false));
+        let ticker = (!quiet).then(|| {
+            let (tally, total, stop) = (Arc::clone(&tally), total.clone(), Arc::clone(&stop));
+            std::thread::spawn(move || {
+                while !stop.load(Ordering::Relaxed) {
+                    let (done, length) = lock(&tally).sample();
+                    total.set_length(length);
+                    total.set_position(done);
+                    std::thread::sleep(QUEUE_TICK);
+                }
+            })
+        });
+        let progress = Self {
+            multi,
+            total,
+            entries,
+            done: AtomicU64::new(0),
+            failed: AtomicU64::new(0),
+            tally,
+            stop,
+            ticker,
+        };
+        progress.update_message();
+        progress
+    }
+
+    /// The next entry starts; its bytes are read from `monitor`.
+    pub fn start_entry(&self, monitor: &TransferMonitor) {
+        let mut tally = lock(&self.tally);
+        let estimate = tally.pending.pop_front().unwrap_or(0);
+        tally.current = Some((monitor.clone(), estimate));
+    }
+
+    /// The running entry ended; `ok` is false if it failed.
+    pub fn finish_entry(&self, ok: bool) {
+        let mut tally = lock(&self.tally);
+        if let Some((monitor, _)) = tally.current.take() {
+            tally.finished += monitor.snapshot().bytes_done;
+        }
+        let (done, length) = tally.sample();
+        drop(tally);
+        self.total.set_length(length);
+        self.total.set_position(done);
+        let counter = if ok { &self.done } else { &self.failed };
+        counter.fetch_add(1, Ordering::Relaxed);
+        self.update_message();
+    }
+
+    /// Print a line above the bars.
+    pub fn println(&self, line: &str) {
+        match self.multi {
+            Some(ref multi) => {
+                let _ = multi.println(line);
+            }
+            None => self.total.suspend(|| eprintln!("{}", line)),
+        }
+    }
+
+    /// Clear the queue line and everything below it.
+    pub fn finish(&self) {
+        self.total.finish_and_clear();
+        if let Some(ref multi) = self.multi {
+            let _ = multi.clear();
+        }
+    }
+
+    fn update_message(&self) {
+        let failed = self.failed.load(Ordering::Relaxed);
+        let finished = self.done.load(Ordering::Relaxed) + failed;
+        let mut message = format!("{}/{} entries", finished, self.entries);
+        if failed > 0 {
+            message.push_str(&format!(", {} failed", failed));
+        }
+        self.total.set_message(message);
+    }
+}
+
+impl Drop for QueueProgress {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(ticker) = self.ticker.take() {
+            let _ = ticker.join();
+        }
+        if self.multi.is_some() {
+            *lock(&PARENT) = None;
+        }
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn terminal(width: Option<u16>, dumb: bool) -> TerminalInfo {
+        TerminalInfo {
+            width,
+            dumb,
+            color: true,
+        }
+    }
+
+    #[test]
+    fn layout_follows_terminal_width() {
+        assert_eq!(terminal(Some(120), false).layout(), ProgressLayout::Full);
+        assert_eq!(terminal(Some(100), false).layout(), ProgressLayout::Full);
+        assert_eq!(terminal(Some(80), false).layout(), ProgressLayout::Compact);
+        assert_eq!(terminal(Some(40), false).layout(), ProgressLayout::Minimal);
+        // Unknown width (e.g. not a tty): keep the full layout
+        assert_eq!(terminal(None, false).layout(), ProgressLayout::Full);
+    }
+
+    #[test]
+    fn dumb_terminal_is_minimal_at_any_width() {
+        assert_eq!(terminal(Some(200), true).layout(), ProgressLayout::Minimal);
+        assert_eq!(terminal(None, true).layout(), ProgressLayout::Minimal);
+    }
+
+    #[test]
+    fn every_template_parses() {
+        for kind in [
+            ProgressKind::Bytes,
+            ProgressKind::File,
+            ProgressKind::Scan,
+            ProgressKind::Stream,
+        ] {
+            for layout in [
+                ProgressLayout::Full,
+                ProgressLayout::Compact,
+                ProgressLayout::Minimal,
+            ] {
+                for color in [true, false] {
+                    let t = template(kind, layout, color);
+                    assert!(ProgressStyle::with_template(&t).is_ok(), "{}", t);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn narrower_layouts_drop_fields() {
+        let full = template(ProgressKind::Bytes, ProgressLayout::Full, true);
+        let compact = template(ProgressKind::Bytes, ProgressLayout::Compact, true);
+        let minimal = template(ProgressKind::Bytes, ProgressLayout::Minimal, true);
+        assert!(full.contains("{elapsed_precise}") && full.contains("{bar:40"));
+        assert!(!compact.contains("{elapsed_precise}") && compact.contains("{bar:20"));
+        assert!(!minimal.contains("{bar") && !minimal.contains("{spinner"));
+    }
+
+    #[test]
+    fn no_color_strips_styles() {
+        let t = template(ProgressKind::Bytes, ProgressLayout::Full, false);
+        assert!(!t.contains(".green") && !t.contains(".cyan"));
+        assert!(t.contains("{spinner}") && t.contains("{bar:40}"));
+    }
+
+    #[test]
+    fn batch_counts_bytes_and_files() {
+        let batch = BatchProgress::new(300, 3, true);
+        let file = batch.start_file("a", 100);
+        file.bar().inc(100);
+        file.done();
+        batch.skip_file(200);
+        assert_eq!(batch.total.position(), 300);
+        assert_eq!(batch.total.message(), "2/3 files");
+    }
+
+    #[test]
+    fn sampler_counts_files_in_flight() {
+        let batch = BatchProgress::new(300, 3, true);
+        let sample = batch.sampler();
+        let first = batch.start_file("a", 100);
+        first.done();
+        let second = batch.start_file("dir/b", 150);
+        second.bar().inc(50);
+        let status = sample();
+        assert_eq!(status.bytes_done, 150);
+        assert_eq!(status.total_bytes, 300);
+        assert_eq!(status.current_file.as_deref(), Some("dir/b"));
+        second.done();
+        assert_eq!(sample().bytes_done, 250);
+        assert_eq!(sample().current_file, None);
+    }
+
+    #[cfg(feature = "net")]
+    #[test]
+    fn group_drops_the_bytes_of_failed_devices() {
+        let group = GroupProgress::new(100, 3, true);
+        let (a, b, c) = (
+            group.device_bar("a", 100),
+            group.device_bar("b", 100),
+            group.device_bar("c", 100),
+        );
+        group.inc(&a, 100);
+        group.device_done(&a, "a", None);
+        group.inc(&b, 40);
+        group.device_done(&b, "b", Some("connection lost"));
+        assert_eq!(group.total.length(), Some(240));
+        assert_eq!(group.total.message(), "1/3 devices, 1 failed");
+        group.inc(&c, 100);
+        group.device_done(&c, "c", None);
+        assert_eq!(group.total.position(), 240);
+        assert_eq!(group.total.message(), "2/3 devices, 1 failed");
+    }
+
+    #[test]
+    fn quiet_batch_shows_no_file_lines() {
+        let batch = BatchProgress::new(1 << 30, 1, true);
+        assert!(batch.start_file("big", 1 << 30).bar().is_hidden());
+    }
+
+    #[test]
+    fn queue_swaps_estimates_for_measured_sizes() {
+        let queue = QueueProgress::new(vec![100, 50, 0], true);
+        let sample = || lock(&queue.tally).sample();
+        assert_eq!(sample(), (0, 150));
+
+        let first = TransferMonitor::new("a", "b");
+        queue.start_entry(&first);
+        assert_eq!(sample(), (0, 150));
+        first.start(200, 0);
+        first.add_bytes(80);
+        assert_eq!(sample(), (80, 250));
+        queue.finish_entry(false);
+        assert_eq!(sample(), (80, 130));
+        assert_eq!(queue.total.message(), "1/3 entries, 1 failed");
+
+        let second = TransferMonitor::new("c", "d");
+        queue.start_entry(&second);
+        second.start(50, 0);
+        second.add_bytes(50);
+        queue.finish_entry(true);
+        assert_eq!(queue.total.position(), 130);
+        assert_eq!(queue.total.length(), Some(130));
+        assert_eq!(queue.total.message(), "2/3 entries, 1 failed");
+    }
+}
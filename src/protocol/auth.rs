@@ -14,6 +14,8 @@ pub enum Auth {
     /// Username + password authentication.
     Password {
         user: String,
+        // Parsed from any URL, but only WebDAV sends it
+        #[cfg_attr(not(feature = "backends-webdav"), allow(dead_code))]
         password: String,
     },
 
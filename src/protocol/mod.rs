@@ -2,15 +2,18 @@
 //!
 //! This module provides the `Protocol` enum that represents the detected
 //! transfer protocol from a user-provided path or URI, along with parsing
-//! logic and authentication types.
+//! logic and authentication types. `FluxPath` pairs a protocol with the
+//! normalized path on it and the trailing-slash flag.
 
 pub mod auth;
 pub mod parser;
+pub mod path;
 
 use std::path::PathBuf;
 
 pub use auth::Auth;
 pub use parser::detect_protocol;
+pub use path::FluxPath;
 
 /// A detected transfer protocol with parsed connection parameters.
 ///
@@ -42,6 +45,13 @@ pub enum Protocol {
         url: String,
         auth: Option<Auth>,
     },
+
+    /// A remote of an installed rclone (`rclone:remote:path`).
+    Rclone {
+        /// Remote name from rclone.conf, or an on-the-fly `:backend,opts`
+        remote: String,
+        path: String,
+    },
 }
 
 impl Protocol {
@@ -66,6 +76,7 @@ impl Protocol {
             Protocol::Sftp { .. } => "sftp",
             Protocol::Smb { .. } => "smb",
             Protocol::WebDav { .. } => "webdav",
+            Protocol::Rclone { .. } => "rclone",
         }
     }
 }
@@ -15,8 +15,9 @@ use super::Protocol;
 /// Detection order:
 /// 1. Windows UNC path: `\\server\share\path` -> SMB
 /// 2. Unix UNC path: `//server/share/path` (but not `///`) -> SMB
-/// 3. URL with recognized scheme (`sftp`, `ssh`, `smb`, `https`, `http`, `webdav`, `dav`) -> respective protocol
-/// 4. Everything else -> Local
+/// 3. `rclone:remote:path` -> Rclone
+/// 4. URL with recognized scheme (`sftp`, `ssh`, `smb`, `https`, `http`, `webdav`, `dav`) -> respective protocol
+/// 5. Everything else -> Local
 pub fn detect_protocol(input: &str) -> Protocol {
     // 1. Windows UNC path: \\server\share\path
     if input.starts_with("\\\\") {
@@ -28,7 +29,12 @@ pub fn detect_protocol(input: &str) -> Protocol {
         return parse_unc_forward(input);
     }
 
-    // 3. Try URL parsing for scheme-based detection
+    // 3. A remote of an installed rclone
+    if let Some(spec) = input.strip_prefix("rclone:") {
+        return parse_rclone(spec);
+    }
+
+    // 4. Try URL parsing for scheme-based detection
     if let Ok(url) = Url::parse(input) {
         match url.scheme() {
             "sftp" | "ssh" => return parse_sftp_url(&url),
@@ -51,7 +57,7 @@ pub fn detect_protocol(input: &str) -> Protocol {
         }
     }
 
-    // 4. Fallback: local filesystem path
+    // 5. Fallback: local filesystem path
     Protocol::Local {
         path: PathBuf::from(input),
     }
@@ -141,6 +147,20 @@ fn parse_smb_url(url: &Url) -> Protocol {
     }
 }
 
+/// Parse the `remote:path` after `rclone:` into Protocol::Rclone. On-the-fly
+/// remotes start with a colon (`:local:`, `:s3,provider=AWS:`).
+fn parse_rclone(spec: &str) -> Protocol {
+    let skip = usize::from(spec.starts_with(':'));
+    let (remote, path) = match spec[skip..].find(':') {
+        Some(idx) => (&spec[..skip + idx], &spec[skip + idx + 1..]),
+        None => (spec, ""),
+    };
+    Protocol::Rclone {
+        remote: remote.to_string(),
+        path: path.to_string(),
+    }
+}
+
 /// Extract inline WebDAV credentials from URL userinfo, if present.
 fn extract_webdav_auth(url: &Url) -> Option<Auth> {
     let user = url.username();
@@ -377,6 +397,31 @@ mod tests {
         }
     }
 
+    #[test]
+    fn detect_rclone_remotes() {
+        match detect_protocol("rclone:gdrive:Photos/2024") {
+            Protocol::Rclone { remote, path } => {
+                assert_eq!(remote, "gdrive");
+                assert_eq!(path, "Photos/2024");
+            }
+            other => panic!("Expected Rclone, got {:?}", other),
+        }
+        match detect_protocol("rclone::local:/tmp/out") {
+            Protocol::Rclone { remote, path } => {
+                assert_eq!(remote, ":local");
+                assert_eq!(path, "/tmp/out");
+            }
+            other => panic!("Expected Rclone, got {:?}", other),
+        }
+        match detect_protocol("rclone:s3") {
+            Protocol::Rclone { remote, path } => {
+                assert_eq!(remote, "s3");
+                assert_eq!(path, "");
+            }
+            other => panic!("Expected Rclone, got {:?}", other),
+        }
+    }
+
     #[test]
     fn detect_local_path_that_looks_like_url() {
         // A path like "file.sftp" should not be detected as SFTP
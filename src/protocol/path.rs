@@ -0,0 +1,205 @@
+//! `FluxPath`: a location on any backend, as given on the command line.
+//!
+//! Commands take a path or URI and need three things from it: the backend
+//! to connect to, the path on that backend, and whether it ended in a
+//! separator. The last one carries rsync's convention for directory
+//! sources, on every backend alike:
+//!
+//! ```text
+//! flux cp -r photos        /backup   ->  /backup/photos/...
+//! flux cp -r photos/       /backup   ->  /backup/...
+//! flux cp -r sftp://nas/photos  out  ->  out/photos/...
+//! flux cp -r sftp://nas/photos/ out  ->  out/...
+//! ```
+//!
+//! The path is kept without the trailing separator (except for a root such
+//! as `/`), so it can be joined and walked as is.
+
+use std::path::{Path, PathBuf};
+
+use super::{detect_protocol, Protocol};
+
+/// A source or destination: backend, normalized path and trailing slash.
+#[derive(Debug, Clone)]
+pub struct FluxPath {
+    /// What to connect to. WebDAV URLs are cut back to the parent
+    /// collection and rclone remotes to their root, which backends for
+    /// those protocols are rooted at
+    pub protocol: Protocol,
+    /// Path on the backend, without a trailing separator
+    pub path: PathBuf,
+    /// The input ended in `/` (or `\`): a directory's contents, not the
+    /// directory itself
+    pub trailing_slash: bool,
+}
+
+impl FluxPath {
+    /// Parse a path or URI as typed (after alias resolution).
+    pub fn parse(input: &str) -> Self {
+        let protocol = detect_protocol(input);
+        match &protocol {
+            Protocol::Local { path } => Self::local(path),
+            Protocol::Sftp { path, .. } | Protocol::Smb { path, .. } => {
+                let (path, trailing_slash) = split_trailing(path);
+                Self {
+                    protocol: protocol.clone(),
+                    path,
+                    trailing_slash,
+                }
+            }
+            Protocol::Rclone { remote, path } => {
+                let (path, trailing_slash) = split_trailing(path);
+                Self {
+                    protocol: Protocol::Rclone {
+                        remote: remote.clone(),
+                        path: String::new(),
+                    },
+                    path,
+                    trailing_slash,
+                }
+            }
+            Protocol::WebDav { url, auth } => {
+                let trailing_slash = url.ends_with('/');
+                let (root, path) = match url.trim_end_matches('/').rsplit_once('/') {
+                    Some((parent, name)) if !parent.ends_with('/') && !name.is_empty() => {
+                        (parent.to_string(), PathBuf::from(name))
+                    }
+                    _ => (url.clone(), PathBuf::new()),
+                };
+                Self {
+                    protocol: Protocol::WebDav {
+                        url: root,
+                        auth: auth.clone(),
+                    },
+                    path,
+                    trailing_slash,
+                }
+            }
+        }
+    }
+
+    /// A local path, checking it for a trailing separator.
+    pub fn local(path: &Path) -> Self {
+        let (path, trailing_slash) = split_trailing(&path.to_string_lossy());
+        Self {
+            protocol: Protocol::Local { path: path.clone() },
+            path,
+            trailing_slash,
+        }
+    }
+
+    /// Whether this is on the local filesystem.
+    pub fn is_local(&self) -> bool {
+        self.protocol.is_local()
+    }
+
+    /// Last component of the path, if it has one (a root does not).
+    pub fn name(&self) -> Option<String> {
+        if self.is_local() {
+            return self.path.file_name().map(|n| n.to_string_lossy().to_string());
+        }
+        let path = self.path.to_string_lossy();
+        path.rsplit(['/', '\\'])
+            .next()
+            .filter(|n| !n.is_empty())
+            .map(str::to_string)
+    }
+
+    /// Where this directory's files go when copied into `dest`: `dest`
+    /// itself with a trailing slash (or for a root), else `dest/<name>`.
+    pub fn target_in(&self, dest: &Path) -> PathBuf {
+        match self.name() {
+            Some(name) if !self.trailing_slash => dest.join(name),
+            _ => dest.to_path_buf(),
+        }
+    }
+}
+
+/// Strip trailing separators from `raw`, keeping a root (`/`) whole.
+/// Returns the path and whether there were any.
+fn split_trailing(raw: &str) -> (PathBuf, bool) {
+    let trimmed = raw.trim_end_matches(['/', '\\']);
+    let trailing = trimmed.len() < raw.len();
+    if trimmed.is_empty() && trailing {
+        // "/" (or "\\"): the root, which has no name to keep or drop
+        return (PathBuf::from(&raw[..1]), true);
+    }
+    (PathBuf::from(trimmed), trailing)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn trailing_slash_is_split_off_local_paths() {
+        let with = FluxPath::parse("photos/");
+        assert!(with.trailing_slash);
+        assert_eq!(with.path, Path::new("photos"));
+        assert_eq!(with.target_in(Path::new("/backup")), Path::new("/backup"));
+
+        let without = FluxPath::parse("photos");
+        assert!(!without.trailing_slash);
+        assert_eq!(
+            without.target_in(Path::new("/backup")),
+            Path::new("/backup/photos")
+        );
+
+        let root = FluxPath::parse("/");
+        assert_eq!(root.path, Path::new("/"));
+        assert_eq!(root.target_in(Path::new("/backup")), Path::new("/backup"));
+    }
+
+    #[test]
+    fn remote_paths_follow_the_same_rule() {
+        for (input, target) in [
+            ("sftp://nas/srv/photos", "out/photos"),
+            ("sftp://nas/srv/photos/", "out"),
+            ("smb://server/share/photos", "out/photos"),
+            ("smb://server/share/photos/", "out"),
+            ("\\\\server\\share\\photos\\", "out"),
+            ("rclone:gdrive:photos", "out/photos"),
+            ("rclone:gdrive:photos/", "out"),
+            ("https://dav.example.com/files/photos", "out/photos"),
+            ("https://dav.example.com/files/photos/", "out"),
+        ] {
+            let path = FluxPath::parse(input);
+            assert_eq!(path.target_in(Path::new("out")), Path::new(target), "{}", input);
+        }
+
+        let sftp = FluxPath::parse("sftp://nas/srv/photos/");
+        assert_eq!(sftp.path, Path::new("/srv/photos"));
+        // The share itself has no name
+        let share = FluxPath::parse("smb://server/share");
+        assert_eq!(share.name(), None);
+        assert_eq!(share.target_in(Path::new("out")), Path::new("out"));
+    }
+
+    #[test]
+    fn webdav_urls_split_into_collection_and_path() {
+        let path = FluxPath::parse("https://dav.example.com/backups/db.sql");
+        match &path.protocol {
+            Protocol::WebDav { url, .. } => assert_eq!(url, "https://dav.example.com/backups"),
+            other => panic!("Expected WebDav, got {:?}", other),
+        }
+        assert_eq!(path.path, Path::new("db.sql"));
+
+        // A bare server has no path to split off
+        let root = FluxPath::parse("https://dav.example.com/");
+        assert_eq!(root.path, PathBuf::new());
+        assert_eq!(root.name(), None);
+    }
+
+    #[test]
+    fn rclone_paths_are_relative_to_the_remote_root() {
+        let path = FluxPath::parse("rclone:s3:bucket/dir/file.bin");
+        match &path.protocol {
+            Protocol::Rclone { remote, path } => {
+                assert_eq!(remote, "s3");
+                assert!(path.is_empty());
+            }
+            other => panic!("Expected Rclone, got {:?}", other),
+        }
+        assert_eq!(path.path, Path::new("bucket/dir/file.bin"));
+    }
+}
@@ -1,10 +1,15 @@
 use chrono::{DateTime, Utc};
-use fs2::FileExt;
+use rusqlite::{params, Connection, Transaction};
 use serde::{Deserialize, Serialize};
+use std::cell::OnceCell;
+use std::collections::BTreeMap;
 use std::fs::File;
-use std::path::{Path, PathBuf};
+use std::path::Path;
 
+use crate::config::versioned::{wrap_entries, Format};
 use crate::error::FluxError;
+use crate::queue::store;
+use crate::security::receipt::TransferReceipt;
 
 /// A single transfer history entry recording what was transferred and its outcome.
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -15,22 +20,140 @@ pub struct HistoryEntry {
     pub files: u64,
     pub duration_secs: f64,
     pub timestamp: DateTime<Utc>,
-    pub status: String, // "completed", "failed", "cancelled"
+    pub status: String, // "completed", "failed", "cancelled", "skipped", "paused"
     pub error: Option<String>,
+    /// Operation that produced this entry: "cp", "sync", "send", "receive", "push",
+    /// "pull", "queue".
+    #[serde(default = "default_operation")]
+    pub operation: String,
+    /// Peer device or network backend involved, if any.
+    #[serde(default)]
+    pub peer: Option<String>,
+    /// Checksum outcome: `Some(true)` verified, `Some(false)` mismatch, `None` not checked.
+    #[serde(default)]
+    pub verified: Option<bool>,
+    /// Signed delivery receipt for P2P transfers made with `--receipt`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub receipt: Option<TransferReceipt>,
+    /// Stable ID used by `flux history diff`, assigned when the entry is stored.
+    #[serde(default)]
+    pub id: u64,
+    /// Files a sync run copied, updated or deleted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub changes: Option<ChangeSet>,
 }
 
-/// Persistent history store backed by a JSON file.
+/// Maximum number of paths kept per change kind. Past this, paths are only
+/// counted per parent directory.
+pub const MAX_RECORDED_PATHS: usize = 500;
+
+/// Maximum number of directories counted for paths past `MAX_RECORDED_PATHS`.
+const MAX_OMITTED_DIRS: usize = 200;
+
+/// Paths touched by one kind of change, relative to the destination root.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PathList {
+    /// The first `MAX_RECORDED_PATHS` paths, in plan order
+    pub paths: Vec<String>,
+    /// Number of paths past the limit, per parent directory
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub omitted: BTreeMap<String, u64>,
+    /// Total number of paths, including omitted ones
+    pub total: u64,
+}
+
+impl PathList {
+    /// Record one path ("/"-separated, relative to the destination root).
+    pub fn push(&mut self, path: String) {
+        self.total += 1;
+        if self.paths.len() < MAX_RECORDED_PATHS {
+            self.paths.push(path);
+            return;
+        }
+        let dir = match path.rsplit_once('/') {
+            Some((dir, _)) => dir.to_string(),
+            None => ".".to_string(),
+        };
+        if self.omitted.len() < MAX_OMITTED_DIRS || self.omitted.contains_key(&dir) {
+            *self.omitted.entry(dir).or_default() += 1;
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.total == 0
+    }
+}
+
+/// What a sync run changed in the destination.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ChangeSet {
+    pub copied: PathList,
+    pub updated: PathList,
+    pub deleted: PathList,
+}
+
+impl ChangeSet {
+    /// Render for `flux history diff`: one line per path, `+` copied,
+    /// `~` updated, `-` deleted.
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        for (label, marker, list) in [
+            ("Copied", '+', &self.copied),
+            ("Updated", '~', &self.updated),
+            ("Deleted", '-', &self.deleted),
+        ] {
+            if list.is_empty() {
+                continue;
+            }
+            out.push_str(&format!("{} ({}):\n", label, list.total));
+            for path in &list.paths {
+                out.push_str(&format!("  {} {}\n", marker, path));
+            }
+            for (dir, count) in &list.omitted {
+                out.push_str(&format!("  {} ... {} more in {}/\n", marker, count, dir));
+            }
+            let listed = list.paths.len() as u64 + list.omitted.values().sum::<u64>();
+            if list.total > listed {
+                out.push_str(&format!("  {} ... {} more\n", marker, list.total - listed));
+            }
+        }
+        if out.is_empty() {
+            out.push_str("No files changed\n");
+        }
+        out
+    }
+}
+
+/// Entries written before `operation` existed were all produced by `flux cp`.
+fn default_operation() -> String {
+    "cp".to_string()
+}
+
+/// Format versions of the `history.json` of earlier flux versions, read
+/// when it is imported: 1 was a bare array of entries, 2 wraps them in a
+/// `{"version": 2, "entries": [...]}` object.
+pub const HISTORY_FORMAT: Format = Format {
+    current: 2,
+    migrations: &[wrap_entries],
+};
+
+/// Persistent history store backed by the state database.
 ///
-/// Stores transfer history in `history.json` within the Flux data directory.
-/// Entries are capped at a configurable limit; oldest entries are removed
-/// when the limit is exceeded.
+/// Entries are rows of the `history` table in `state.db` (see
+/// `crate::state`), oldest first, capped at a configurable limit; oldest
+/// entries are removed when the limit is exceeded. `append` writes one row,
+/// and the full list is only read when asked for, so recording a transfer
+/// stays cheap however long the history is.
 ///
 /// An exclusive advisory lock on `history.lock` is held for the entire
 /// lifetime of this struct and released automatically on drop, preventing
-/// concurrent writers from corrupting the history file.
+/// concurrent writers from interleaving their changes.
 pub struct HistoryStore {
-    path: PathBuf,
-    entries: Vec<HistoryEntry>,
+    db: Connection,
+    /// All entries, read on first use
+    entries: OnceCell<Vec<HistoryEntry>>,
+    /// `clear` was called and `save` has not run yet
+    cleared: bool,
     limit: usize,
     /// Holds the open lock file. The `fs2` exclusive lock is tied to the file
     /// descriptor; dropping this field releases the lock.
@@ -38,95 +161,146 @@ pub struct HistoryStore {
 }
 
 impl HistoryStore {
-    /// Load history from `data_dir/history.json`.
+    /// Open the history in the state database in `data_dir`.
     ///
-    /// Acquires an exclusive advisory lock on `data_dir/history.lock` before
-    /// reading the state file. The lock is held until the returned
-    /// `HistoryStore` is dropped. If another process already holds the lock
-    /// this call blocks until that process releases it.
+    /// Acquires an exclusive advisory lock on `data_dir/history.lock` first.
+    /// The lock is held until the returned `HistoryStore` is dropped. If
+    /// another process already holds the lock this call blocks until that
+    /// process releases it.
     ///
-    /// If the history file does not exist, returns an empty history. If the
-    /// file is corrupted, logs a warning and starts fresh (graceful
-    /// degradation).
+    /// The `history.json` of an earlier version is imported the first time;
+    /// see `crate::state`.
     pub fn load(data_dir: &Path, limit: usize) -> Result<Self, FluxError> {
-        let lock_path = data_dir.join("history.lock");
-        let lock_file = File::options()
-            .read(true)
-            .write(true)
-            .create(true)
-            .truncate(false)
-            .open(&lock_path)
-            .map_err(|e| FluxError::Io { source: e })?;
-        lock_file
-            .lock_exclusive()
-            .map_err(|e| FluxError::Io { source: e })?;
-
-        let path = data_dir.join("history.json");
-
-        if path.exists() {
-            let contents = std::fs::read_to_string(&path).map_err(|e| FluxError::Io {
-                source: e,
-            })?;
-            match serde_json::from_str::<Vec<HistoryEntry>>(&contents) {
-                Ok(entries) => Ok(Self {
-                    path,
-                    entries,
-                    limit,
-                    _lock_file: lock_file,
-                }),
-                Err(e) => {
-                    tracing::warn!("Corrupted history.json, starting fresh: {}", e);
-                    Ok(Self {
-                        path,
-                        entries: Vec::new(),
-                        limit,
-                        _lock_file: lock_file,
-                    })
-                }
-            }
-        } else {
-            Ok(Self {
-                path,
-                entries: Vec::new(),
-                limit,
-                _lock_file: lock_file,
-            })
-        }
+        let lock_file = store::lock(&data_dir.join("history.lock"))?;
+        let db = crate::state::open(data_dir)?;
+        Ok(Self {
+            db,
+            entries: OnceCell::new(),
+            cleared: false,
+            limit,
+            _lock_file: lock_file,
+        })
     }
 
     /// Append a new entry to the history, truncating oldest if over limit.
     ///
-    /// Automatically saves to disk after appending.
-    pub fn append(&mut self, entry: HistoryEntry) -> Result<(), FluxError> {
-        self.entries.push(entry);
-
+    /// The entry gets the next free ID. Automatically saves to disk after
+    /// appending.
+    pub fn append(&mut self, mut entry: HistoryEntry) -> Result<(), FluxError> {
+        self.save()?;
+        let tx = self.db.unchecked_transaction()?;
+        let last: i64 = tx.query_row("SELECT COALESCE(MAX(id), 0) FROM history", [], |row| {
+            row.get(0)
+        })?;
+        entry.id = last as u64 + 1;
+        insert_entries(&tx, std::slice::from_ref(&entry))?;
         // Truncate oldest if over limit
-        if self.entries.len() > self.limit {
-            let excess = self.entries.len() - self.limit;
-            self.entries.drain(..excess);
-        }
+        tx.execute(
+            "DELETE FROM history WHERE seq NOT IN
+                (SELECT seq FROM history ORDER BY seq DESC LIMIT ?1)",
+            params![self.limit as i64],
+        )?;
+        tx.commit()?;
 
-        self.save()
+        if let Some(entries) = self.entries.get_mut() {
+            entries.push(entry);
+            if entries.len() > self.limit {
+                let excess = entries.len() - self.limit;
+                entries.drain(..excess);
+            }
+        }
+        Ok(())
     }
 
-    /// Return a slice of all history entries.
+    /// Return a slice of all history entries, oldest first.
+    ///
+    /// Entries that cannot be read are left out with a warning.
     pub fn list(&self) -> &[HistoryEntry] {
-        &self.entries
+        self.entries.get_or_init(|| {
+            self.query("SELECT id, entry FROM history ORDER BY seq", params![])
+                .unwrap_or_else(|e| {
+                    tracing::warn!("Failed to read history: {}", e);
+                    Vec::new()
+                })
+        })
+    }
+
+    /// The last `count` entries, oldest first, without reading the rest.
+    pub fn recent(&self, count: usize) -> Result<Vec<HistoryEntry>, FluxError> {
+        if let Some(entries) = self.entries.get() {
+            return Ok(entries[entries.len().saturating_sub(count)..].to_vec());
+        }
+        self.query(
+            "SELECT id, entry FROM
+                (SELECT seq, id, entry FROM history ORDER BY seq DESC LIMIT ?1)
+             ORDER BY seq",
+            params![count as i64],
+        )
+    }
+
+    /// Look up an entry by ID.
+    pub fn get(&self, id: u64) -> Option<&HistoryEntry> {
+        self.list().iter().find(|e| e.id == id)
     }
 
     /// Clear all history entries.
     pub fn clear(&mut self) {
-        self.entries.clear();
+        self.entries = OnceCell::from(Vec::new());
+        self.cleared = true;
     }
 
-    /// Save history to disk atomically (write temp, rename).
-    pub fn save(&self) -> Result<(), FluxError> {
-        let tmp_path = self.path.with_extension("json.tmp");
-        let json = serde_json::to_string_pretty(&self.entries)?;
-        std::fs::write(&tmp_path, json).map_err(|e| FluxError::Io { source: e })?;
-        std::fs::rename(&tmp_path, &self.path).map_err(|e| FluxError::Io { source: e })?;
+    /// Write pending changes (a `clear`) to the database. Appends are
+    /// written as they happen.
+    pub fn save(&mut self) -> Result<(), FluxError> {
+        if self.cleared {
+            self.db.execute("DELETE FROM history", [])?;
+            self.cleared = false;
+        }
         Ok(())
     }
+
+    /// Entries selected by `sql` (columns `id, entry`).
+    fn query(
+        &self,
+        sql: &str,
+        params: &[&dyn rusqlite::ToSql],
+    ) -> Result<Vec<HistoryEntry>, FluxError> {
+        let mut select = self.db.prepare(sql)?;
+        let rows = select.query_map(params, |row| {
+            Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?))
+        })?;
+        let mut entries = Vec::new();
+        for row in rows {
+            let (id, json) = row?;
+            match serde_json::from_str(&json) {
+                Ok(entry) => entries.push(entry),
+                Err(e) => tracing::warn!("Skipping unreadable history entry #{}: {}", id, e),
+            }
+        }
+        Ok(entries)
+    }
+}
+
+/// Add `entries` after the existing ones.
+fn insert_entries(db: &Connection, entries: &[HistoryEntry]) -> Result<(), FluxError> {
+    let mut insert = db.prepare("INSERT INTO history (id, entry) VALUES (?1, ?2)")?;
+    for entry in entries {
+        insert.execute(params![entry.id as i64, serde_json::to_string(entry)?])?;
+    }
+    Ok(())
+}
+
+/// Import the `history.json` of an earlier version (see `crate::state`).
+///
+/// Entries written before IDs existed get an ID after the highest one.
+pub(crate) fn import(tx: &Transaction, path: &Path) -> Result<(), FluxError> {
+    let mut entries: Vec<HistoryEntry> = store::load_entries(path, &HISTORY_FORMAT)?;
+    let mut next = entries.iter().map(|e| e.id).max().unwrap_or(0) + 1;
+    for entry in entries.iter_mut().filter(|e| e.id == 0) {
+        entry.id = next;
+        next += 1;
+    }
+    insert_entries(tx, &entries)
 }
 
 #[cfg(test)]
@@ -154,6 +328,12 @@ mod tests {
             timestamp: Utc::now(),
             status: "completed".to_string(),
             error: None,
+            operation: "cp".into(),
+            peer: None,
+            verified: None,
+            receipt: None,
+            id: 0,
+            changes: None,
         };
 
         store.append(entry).unwrap();
@@ -177,6 +357,12 @@ mod tests {
                 timestamp: Utc::now(),
                 status: "completed".to_string(),
                 error: None,
+                operation: "cp".into(),
+                peer: None,
+                verified: None,
+                receipt: None,
+                id: 0,
+                changes: None,
             };
             store.append(entry).unwrap();
         }
@@ -203,6 +389,12 @@ mod tests {
                 timestamp: Utc::now(),
                 status: "completed".to_string(),
                 error: None,
+                operation: "cp".into(),
+                peer: None,
+                verified: None,
+                receipt: None,
+                id: 0,
+                changes: None,
             };
             store.append(entry).unwrap();
         }
@@ -229,6 +421,12 @@ mod tests {
             timestamp: Utc::now(),
             status: "completed".to_string(),
             error: None,
+            operation: "cp".into(),
+            peer: None,
+            verified: None,
+            receipt: None,
+            id: 0,
+            changes: None,
         };
 
         store.append(entry).unwrap();
@@ -236,6 +434,50 @@ mod tests {
 
         store.clear();
         assert!(store.list().is_empty());
+        store.save().unwrap();
+        drop(store);
+        assert!(HistoryStore::load(dir.path(), 1000).unwrap().list().is_empty());
+    }
+
+    #[test]
+    fn recent_returns_the_last_entries_in_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut store = HistoryStore::load(dir.path(), 1000).unwrap();
+        let legacy = r#"{
+            "source": "a", "dest": "b", "bytes": 1, "files": 1,
+            "duration_secs": 0.1, "timestamp": "2024-01-01T00:00:00Z",
+            "status": "completed", "error": null
+        }"#;
+        for i in 0..5 {
+            let mut entry: HistoryEntry = serde_json::from_str(legacy).unwrap();
+            entry.source = format!("src_{}", i);
+            store.append(entry).unwrap();
+        }
+        drop(store);
+
+        let store = HistoryStore::load(dir.path(), 1000).unwrap();
+        let recent = store.recent(2).unwrap();
+        let sources: Vec<&str> = recent.iter().map(|e| e.source.as_str()).collect();
+        assert_eq!(sources, ["src_3", "src_4"]);
+        assert_eq!(recent[1].id, 5);
+        assert_eq!(store.list().len(), 5);
+        assert_eq!(store.recent(10).unwrap().len(), 5);
+    }
+
+    #[test]
+    fn legacy_entries_without_operation_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let legacy = r#"[{
+            "source": "a", "dest": "b", "bytes": 1, "files": 1,
+            "duration_secs": 0.1, "timestamp": "2024-01-01T00:00:00Z",
+            "status": "completed", "error": null
+        }]"#;
+        std::fs::write(dir.path().join("history.json"), legacy).unwrap();
+        let store = HistoryStore::load(dir.path(), 1000).unwrap();
+        assert_eq!(store.list().len(), 1);
+        assert_eq!(store.list()[0].operation, "cp");
+        assert!(store.list()[0].peer.is_none());
+        assert!(store.list()[0].verified.is_none());
     }
 
     #[test]
@@ -246,6 +488,19 @@ mod tests {
         assert!(store.list().is_empty());
     }
 
+    #[test]
+    fn history_from_a_newer_flux_is_refused_and_kept() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("history.json");
+        let newer = r#"{"version": 7, "entries": []}"#;
+        std::fs::write(&path, newer).unwrap();
+        assert!(matches!(
+            HistoryStore::load(dir.path(), 1000),
+            Err(FluxError::NewerFormat { found: 7, .. })
+        ));
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), newer);
+    }
+
     #[test]
     fn failed_entry_with_error_message() {
         let dir = tempfile::tempdir().unwrap();
@@ -260,6 +515,12 @@ mod tests {
             timestamp: Utc::now(),
             status: "failed".to_string(),
             error: Some("Permission denied".to_string()),
+            operation: "cp".into(),
+            peer: None,
+            verified: None,
+            receipt: None,
+            id: 0,
+            changes: None,
         };
 
         store.append(entry).unwrap();
@@ -267,4 +528,55 @@ mod tests {
         assert_eq!(entries[0].status, "failed");
         assert_eq!(entries[0].error, Some("Permission denied".to_string()));
     }
+
+    #[test]
+    fn append_assigns_increasing_ids() {
+        let dir = tempfile::tempdir().unwrap();
+        let legacy = r#"[{
+            "source": "a", "dest": "b", "bytes": 1, "files": 1,
+            "duration_secs": 0.1, "timestamp": "2024-01-01T00:00:00Z",
+            "status": "completed", "error": null
+        }]"#;
+        std::fs::write(dir.path().join("history.json"), legacy).unwrap();
+        let mut store = HistoryStore::load(dir.path(), 1000).unwrap();
+        assert_eq!(store.list()[0].id, 1);
+
+        let mut entry = store.list()[0].clone();
+        entry.id = 0;
+        store.append(entry).unwrap();
+        assert_eq!(store.list()[1].id, 2);
+        // Now kept in the state database
+        assert!(!dir.path().join("history.json").exists());
+        assert!(store.get(2).is_some());
+        assert!(store.get(3).is_none());
+    }
+
+    #[test]
+    fn path_list_counts_omitted_paths_per_directory() {
+        let mut list = PathList::default();
+        for i in 0..MAX_RECORDED_PATHS {
+            list.push(format!("a/{}.txt", i));
+        }
+        list.push("photos/2024/x.jpg".to_string());
+        list.push("photos/2024/y.jpg".to_string());
+        list.push("top.txt".to_string());
+
+        assert_eq!(list.total, MAX_RECORDED_PATHS as u64 + 3);
+        assert_eq!(list.paths.len(), MAX_RECORDED_PATHS);
+        assert_eq!(list.omitted.get("photos/2024"), Some(&2));
+        assert_eq!(list.omitted.get("."), Some(&1));
+    }
+
+    #[test]
+    fn change_set_render_marks_each_kind() {
+        let mut changes = ChangeSet::default();
+        changes.copied.push("new.txt".to_string());
+        changes.deleted.push("old/gone.txt".to_string());
+
+        let text = changes.render();
+        assert!(text.contains("Copied (1):\n  + new.txt"));
+        assert!(text.contains("Deleted (1):\n  - old/gone.txt"));
+        assert!(!text.contains("Updated"));
+        assert_eq!(ChangeSet::default().render(), "No files changed\n");
+    }
 }
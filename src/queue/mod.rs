@@ -1,2 +1,5 @@
 pub mod history;
+pub mod policy;
+pub mod runner;
 pub mod state;
+pub(crate) mod store;
@@ -0,0 +1,267 @@
+//! Queue draining policy: entry classes and the bulk time window.
+//!
+//! Entries are tagged `interactive` (the default) or `bulk`. `flux daemon`
+//! runs interactive entries as soon as they are queued, but only drains bulk
+//! entries inside the configured `[queue] bulk_window` (e.g. `"00:00-06:00"`,
+//! local time), so large transfers stay out of office hours.
+//!
+//! Within a class, entries run by priority (`high`, `normal`, `low`), then in
+//! queue order. `flux queue move` changes the order.
+
+use chrono::NaiveTime;
+use serde::{Deserialize, Serialize};
+
+use crate::error::FluxError;
+use crate::queue::state::{QueueEntry, QueueStatus};
+
+/// Scheduling class of a queued transfer.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum QueueClass {
+    /// Run as soon as possible
+    #[default]
+    Interactive,
+    /// Run only inside the bulk window
+    Bulk,
+}
+
+impl std::fmt::Display for QueueClass {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            QueueClass::Interactive => write!(f, "interactive"),
+            QueueClass::Bulk => write!(f, "bulk"),
+        }
+    }
+}
+
+/// Priority of a queued transfer. Higher priorities run first.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Serialize,
+    Deserialize,
+    clap::ValueEnum,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum QueuePriority {
+    Low,
+    #[default]
+    Normal,
+    High,
+}
+
+impl std::fmt::Display for QueuePriority {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            QueuePriority::Low => write!(f, "low"),
+            QueuePriority::Normal => write!(f, "normal"),
+            QueuePriority::High => write!(f, "high"),
+        }
+    }
+}
+
+/// Daily time window, `start` inclusive and `end` exclusive.
+///
+/// A window whose end is before its start wraps past midnight
+/// (e.g. `22:00-06:00`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimeWindow {
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+}
+
+impl TimeWindow {
+    /// Parse a window in `HH:MM-HH:MM` form.
+    pub fn parse(s: &str) -> Result<Self, FluxError> {
+        let invalid = || {
+            FluxError::Config(format!(
+                "Invalid time window '{}': expected HH:MM-HH:MM (e.g. 00:00-06:00)",
+                s
+            ))
+        };
+        let (start, end) = s.split_once('-').ok_or_else(invalid)?;
+        let start = NaiveTime::parse_from_str(start.trim(), "%H:%M").map_err(|_| invalid())?;
+        let end = NaiveTime::parse_from_str(end.trim(), "%H:%M").map_err(|_| invalid())?;
+        if start == end {
+            return Err(invalid());
+        }
+        Ok(Self { start, end })
+    }
+
+    /// Whether `time` falls inside the window.
+    pub fn contains(&self, time: NaiveTime) -> bool {
+        if self.start < self.end {
+            self.start <= time && time < self.end
+        } else {
+            time >= self.start || time < self.end
+        }
+    }
+}
+
+/// Whether an entry of `class` may start at `now`.
+///
+/// Bulk entries are unrestricted when no window is configured.
+pub fn is_eligible(class: QueueClass, window: Option<&TimeWindow>, now: NaiveTime) -> bool {
+    match (class, window) {
+        (QueueClass::Interactive, _) | (QueueClass::Bulk, None) => true,
+        (QueueClass::Bulk, Some(window)) => window.contains(now),
+    }
+}
+
+/// Pick the next pending entry to run: interactive entries first, then bulk
+/// entries if the window is open. Within a class, the highest priority wins
+/// and ties go to the entry ahead in the queue.
+///
+/// Entries for which `held` is true are passed over, e.g. those waiting for
+/// a backend's monthly usage cap to reset.
+pub fn next_entry(
+    entries: &[QueueEntry],
+    window: Option<&TimeWindow>,
+    now: NaiveTime,
+    held: impl Fn(&QueueEntry) -> bool,
+) -> Option<u64> {
+    let pending: Vec<&QueueEntry> = run_order(entries)
+        .into_iter()
+        .filter(|e| !held(e))
+        .collect();
+    pending
+        .iter()
+        .find(|e| e.class == QueueClass::Interactive)
+        .or_else(|| pending.iter().find(|e| is_eligible(e.class, window, now)))
+        .map(|e| e.id)
+}
+
+/// Pending entries in the order they run: by priority, then queue order.
+pub fn run_order(entries: &[QueueEntry]) -> Vec<&QueueEntry> {
+    let mut pending: Vec<&QueueEntry> = entries
+        .iter()
+        .filter(|e| e.status == QueueStatus::Pending)
+        .collect();
+    // Stable, so equal priorities keep their queue order
+    pending.sort_by_key(|e| std::cmp::Reverse(e.priority));
+    pending
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::queue::state::QueueStore;
+
+    fn t(h: u32, m: u32) -> NaiveTime {
+        NaiveTime::from_hms_opt(h, m, 0).unwrap()
+    }
+
+    #[test]
+    fn parse_window() {
+        let window = TimeWindow::parse("00:00-06:00").unwrap();
+        assert_eq!(window.start, t(0, 0));
+        assert_eq!(window.end, t(6, 0));
+    }
+
+    #[test]
+    fn parse_rejects_bad_windows() {
+        assert!(TimeWindow::parse("midnight").is_err());
+        assert!(TimeWindow::parse("25:00-06:00").is_err());
+        assert!(TimeWindow::parse("06:00-06:00").is_err());
+    }
+
+    #[test]
+    fn window_contains_same_day() {
+        let window = TimeWindow::parse("00:00-06:00").unwrap();
+        assert!(window.contains(t(0, 0)));
+        assert!(window.contains(t(5, 59)));
+        assert!(!window.contains(t(6, 0)));
+        assert!(!window.contains(t(12, 0)));
+    }
+
+    #[test]
+    fn window_wraps_midnight() {
+        let window = TimeWindow::parse("22:00-06:00").unwrap();
+        assert!(window.contains(t(23, 0)));
+        assert!(window.contains(t(3, 0)));
+        assert!(!window.contains(t(12, 0)));
+    }
+
+    #[test]
+    fn bulk_without_window_is_always_eligible() {
+        assert!(is_eligible(QueueClass::Bulk, None, t(12, 0)));
+    }
+
+    #[test]
+    fn next_entry_prefers_interactive_and_respects_window() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut store = QueueStore::load(dir.path()).unwrap();
+        let bulk = store.add("a".into(), "b".into(), false, false, false);
+        store.get_mut(bulk).unwrap().class = QueueClass::Bulk;
+        let window = TimeWindow::parse("00:00-06:00").unwrap();
+
+        // Outside the window only interactive work runs
+        assert_eq!(
+            next_entry(store.list(), Some(&window), t(12, 0), |_| false),
+            None
+        );
+        assert_eq!(
+            next_entry(store.list(), Some(&window), t(1, 0), |_| false),
+            Some(bulk)
+        );
+
+        let interactive = store.add("c".into(), "d".into(), false, false, false);
+        assert_eq!(
+            next_entry(store.list(), Some(&window), t(1, 0), |_| false),
+            Some(interactive)
+        );
+    }
+
+    #[test]
+    fn next_entry_picks_priority_then_queue_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut store = QueueStore::load(dir.path()).unwrap();
+        let first = store.add("a".into(), "b".into(), false, false, false);
+        let low = store.add("c".into(), "d".into(), false, false, false);
+        let urgent = store.add("e".into(), "f".into(), false, false, false);
+        let bulk = store.add("g".into(), "h".into(), false, false, false);
+        store.get_mut(low).unwrap().priority = QueuePriority::Low;
+        store.get_mut(urgent).unwrap().priority = QueuePriority::High;
+        let bulk_entry = store.get_mut(bulk).unwrap();
+        bulk_entry.priority = QueuePriority::High;
+        bulk_entry.class = QueueClass::Bulk;
+
+        let order: Vec<u64> = run_order(store.list()).iter().map(|e| e.id).collect();
+        assert_eq!(order, vec![urgent, bulk, first, low]);
+        // Interactive entries still go before bulk ones
+        assert_eq!(
+            next_entry(store.list(), None, t(12, 0), |_| false),
+            Some(urgent)
+        );
+        store.get_mut(urgent).unwrap().status = QueueStatus::Completed;
+        assert_eq!(
+            next_entry(store.list(), None, t(12, 0), |_| false),
+            Some(first)
+        );
+    }
+
+    #[test]
+    fn next_entry_passes_over_held_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut store = QueueStore::load(dir.path()).unwrap();
+        let capped = store.add("a".into(), "sftp://nas/a".into(), false, false, false);
+        let local = store.add("b".into(), "c".into(), false, false, false);
+
+        let held = |e: &QueueEntry| e.dest.starts_with("sftp://");
+        assert_eq!(next_entry(store.list(), None, t(12, 0), held), Some(local));
+        store.get_mut(local).unwrap().status = QueueStatus::Completed;
+        assert_eq!(next_entry(store.list(), None, t(12, 0), held), None);
+        assert_eq!(
+            next_entry(store.list(), None, t(12, 0), |_| false),
+            Some(capped)
+        );
+    }
+}
@@ -0,0 +1,386 @@
+//! Execution of queue entries, shared by `flux queue run` and `flux daemon`.
+
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use bytesize::ByteSize;
+
+use crate::cli::args::{AttrArgs, CpArgs, HiddenArgs, HookArgs};
+use crate::config::aliases::{resolve_alias, resolve_destination, AliasStore};
+use crate::config::types::ConflictStrategy;
+use crate::error::FluxError;
+use crate::progress::bar::QueueProgress;
+use crate::queue::policy::TimeWindow;
+use crate::queue::state::{QueueEntry, QueueStatus, QueueStore};
+use crate::transfer;
+use crate::transfer::changed::ChangedSource;
+use crate::transfer::checksum::ChecksumAlgorithm;
+use crate::transfer::control::{ControlListener, PauseSignal};
+use crate::transfer::monitor::TransferMonitor;
+use crate::transfer::status::StatusPublisher;
+use crate::transfer::usage::{self, UsageKey};
+
+/// How a queue entry ended, for the `flux queue run` summary.
+#[derive(Debug, Clone)]
+pub struct EntryOutcome {
+    pub id: u64,
+    pub source: String,
+    pub dest: String,
+    /// Status the entry was left in
+    pub status: QueueStatus,
+    pub bytes: u64,
+    pub elapsed: Duration,
+    pub error: Option<String>,
+}
+
+/// Run queue entry `id` to completion, failure or pause.
+///
+/// The entry is marked Running and saved before the copy starts, then
+/// updated with the outcome. With a `window` (bulk entries run by the
+/// daemon), the copy is paused when the window closes and the entry goes
+/// back to Pending so it continues in the next window.
+///
+/// Only errors from saving the queue are returned; transfer errors are
+/// recorded on the entry. A transfer cancelled with Ctrl+C goes back to
+/// Pending (continuing from its manifest next time) and returns
+/// `FluxError::Cancelled`, so the rest of the queue is not started.
+///
+/// With `progress` (`flux queue run`), the entry's bytes count towards the
+/// queue line and its messages are printed above the bars.
+pub fn run_entry(
+    store: &mut QueueStore,
+    data_dir: &Path,
+    id: u64,
+    quiet: bool,
+    window: Option<TimeWindow>,
+    progress: Option<&QueueProgress>,
+) -> Result<EntryOutcome, FluxError> {
+    // Mark as running
+    if let Some(entry) = store.get_mut(id) {
+        entry.status = QueueStatus::Running;
+        entry.started_at = Some(chrono::Utc::now());
+    }
+    store.save()?;
+
+    // Clone entry details for CpArgs construction
+    let entry = store
+        .get(id)
+        .ok_or_else(|| FluxError::QueueError(format!("Queue entry {} not found", id)))?
+        .clone();
+
+    say(progress, format!("\n[#{}] {} -> {}", id, entry.source, entry.dest));
+    let started = Instant::now();
+
+    // Build CpArgs from queue entry. An entry paused mid-transfer resumes
+    // from its manifest; a paused directory copy skips the files it already
+    // wrote.
+    let cp_args = CpArgs {
+        source: entry.source.clone(),
+        dest: entry.dest.clone(),
+        recursive: entry.recursive,
+        verify: entry.verify,
+        checksum: ChecksumAlgorithm::Blake3,
+        compress: entry.compress,
+        chunks: 0,
+        exclude: vec![],
+        include: vec![],
+        hidden: HiddenArgs::default(),
+        attrs: AttrArgs::default(),
+        limit: None,
+        resume: entry.interrupted,
+        resume_verify: false,
+        on_conflict: (entry.interrupted && entry.recursive).then_some(ConflictStrategy::Skip),
+        on_error: None,
+        dry_run: false,
+        no_clone: false,
+        atomic: false,
+        mmap: false,
+        no_preallocate: false,
+        encrypt_to: None,
+        decrypt: false,
+        snapshot_source: false,
+        vss: false,
+        jobs: 0,
+        io_profile: None,
+        dedup: None,
+        hard_links: false,
+        read_only: false,
+        stats: false,
+        failed_out: None,
+        retry_from: None,
+        changed_source: ChangedSource::Warn,
+        hooks: HookArgs::default(),
+    };
+
+    // Live stats and bandwidth changes for the TUI, over the control socket
+    let monitor = TransferMonitor::new(&entry.source, &entry.dest);
+    let control = ControlListener::bind(data_dir, id, monitor.clone())?;
+    let _status = StatusPublisher::for_monitor("queue", &monitor);
+    let pause = match (&control, window) {
+        (Some(control), _) => Some(control.signal().clone()),
+        (None, Some(_)) => Some(PauseSignal::new()),
+        (None, None) => None,
+    };
+    let watcher = match (window, &pause) {
+        (Some(window), Some(pause)) => Some(WindowWatcher::start(window, pause.clone())),
+        _ => None,
+    };
+
+    if let Some(progress) = progress {
+        progress.start_entry(&monitor);
+    }
+    let result =
+        transfer::execute_copy_as("queue", cp_args, quiet, pause.as_ref(), Some(&monitor));
+    let window_closed = watcher.is_some_and(|w| w.finish());
+    drop(control);
+    if let Some(progress) = progress {
+        progress.finish_entry(!matches!(result, Err(ref e) if is_failure(e)));
+    }
+    let mut outcome = EntryOutcome {
+        id,
+        source: entry.source.clone(),
+        dest: entry.dest.clone(),
+        status: QueueStatus::Completed,
+        bytes: monitor.snapshot().bytes_done,
+        elapsed: started.elapsed(),
+        error: None,
+    };
+
+    match result {
+        Ok(()) => {
+            if let Some(e) = store.get_mut(id) {
+                e.status = QueueStatus::Completed;
+                e.completed_at = Some(chrono::Utc::now());
+                e.interrupted = false;
+            }
+            store.save()?;
+            say(progress, format!("[#{}] Completed", id));
+        }
+        Err(FluxError::Paused) if window_closed => {
+            if let Some(e) = store.get_mut(id) {
+                e.status = QueueStatus::Pending;
+                e.interrupted = true;
+            }
+            store.save()?;
+            outcome.status = QueueStatus::Pending;
+            say(
+                progress,
+                format!("[#{}] Bulk window closed, will continue in the next window", id),
+            );
+        }
+        Err(FluxError::Paused) => {
+            if let Some(e) = store.get_mut(id) {
+                e.status = QueueStatus::Paused;
+                e.interrupted = true;
+            }
+            store.save()?;
+            outcome.status = QueueStatus::Paused;
+            say(
+                progress,
+                format!("[#{}] Paused (continue with `flux queue resume {}`)", id, id),
+            );
+        }
+        Err(err @ FluxError::UsageCapReached { .. }) => {
+            if let Some(e) = store.get_mut(id) {
+                e.status = QueueStatus::Pending;
+                e.error = Some(err.to_string());
+            }
+            store.save()?;
+            outcome.status = QueueStatus::Pending;
+            outcome.error = Some(err.to_string());
+            say(progress, format!("[#{}] {}, left in the queue", id, err));
+        }
+        Err(FluxError::Cancelled) => {
+            if let Some(e) = store.get_mut(id) {
+                e.status = QueueStatus::Pending;
+                e.interrupted = true;
+            }
+            store.save()?;
+            say(
+                progress,
+                format!("[#{}] Cancelled, will continue on the next `flux queue run`", id),
+            );
+            return Err(FluxError::Cancelled);
+        }
+        Err(err) => {
+            if let Some(e) = store.get_mut(id) {
+                e.status = QueueStatus::Failed;
+                e.completed_at = Some(chrono::Utc::now());
+                e.error = Some(format!("{}", err));
+            }
+            store.save()?;
+            say(progress, format!("[#{}] Failed: {}", id, err));
+            outcome.status = QueueStatus::Failed;
+            outcome.error = Some(err.to_string());
+        }
+    }
+    Ok(outcome)
+}
+
+/// Whether `entry` goes to or from a backend that has reached its monthly
+/// usage cap. `flux daemon` passes over such entries until the month
+/// changes or the cap is raised, instead of retrying them on every pass.
+pub fn over_cap(entry: &QueueEntry) -> bool {
+    let aliases = AliasStore::current();
+    let source = resolve_alias(&entry.source, &aliases);
+    let dest = resolve_destination(&entry.dest, &aliases);
+    UsageKey::for_copy(&source, &dest).is_some_and(|key| {
+        matches!(
+            usage::check_cap(&key.backend),
+            Err(FluxError::UsageCapReached { .. })
+        )
+    })
+}
+
+/// Whether a copy result counts as a failed entry (pauses, reached usage
+/// caps and Ctrl+C don't).
+fn is_failure(err: &FluxError) -> bool {
+    !matches!(
+        err,
+        FluxError::Paused | FluxError::UsageCapReached { .. } | FluxError::Cancelled
+    )
+}
+
+/// Print an entry message, above the queue bars when there are any.
+fn say(progress: Option<&QueueProgress>, line: String) {
+    match progress {
+        Some(progress) => progress.println(&line),
+        None => eprintln!("{}", line),
+    }
+}
+
+/// Estimated size of a queue entry's source: the size of a local file or
+/// directory tree, 0 for remote sources (measured when the entry starts).
+pub fn estimate_size(source: &str) -> u64 {
+    let resolved = crate::config::aliases::resolve(source);
+    match crate::protocol::detect_protocol(&resolved) {
+        crate::protocol::Protocol::Local { path } => walkdir::WalkDir::new(path)
+            .into_iter()
+            .filter_map(|e| e.ok())
+            .filter(|e| e.file_type().is_file())
+            .filter_map(|e| e.metadata().ok())
+            .map(|m| m.len())
+            .sum(),
+        _ => 0,
+    }
+}
+
+/// Table of what a `flux queue run` did, one row per entry.
+pub fn summary_table(outcomes: &[EntryOutcome]) -> Vec<String> {
+    let mut lines = vec![
+        format!(
+            "{:<4} {:<10} {:>10} {:>8}  {}",
+            "ID", "RESULT", "SIZE", "TIME", "TRANSFER"
+        ),
+        "-".repeat(80),
+    ];
+    for outcome in outcomes {
+        let mut transfer = format!("{} -> {}", outcome.source, outcome.dest);
+        if let Some(ref error) = outcome.error {
+            transfer.push_str(&format!(" ({})", error));
+        }
+        // QueueStatus and ByteSize ignore width, so pad their strings
+        let status = outcome.status.to_string();
+        let size = ByteSize(outcome.bytes).to_string();
+        lines.push(format!(
+            "{:<4} {:<10} {:>10} {:>7.1}s  {}",
+            outcome.id,
+            status,
+            size,
+            outcome.elapsed.as_secs_f64(),
+            transfer
+        ));
+    }
+    let count = |status: QueueStatus| outcomes.iter().filter(|o| o.status == status).count();
+    lines.push(format!(
+        "{} completed, {} failed, {} not finished",
+        count(QueueStatus::Completed),
+        count(QueueStatus::Failed),
+        outcomes.len() - count(QueueStatus::Completed) - count(QueueStatus::Failed)
+    ));
+    lines
+}
+
+/// Background thread that pauses a bulk transfer when its window closes.
+struct WindowWatcher {
+    stop: Arc<AtomicBool>,
+    closed: Arc<AtomicBool>,
+    thread: std::thread::JoinHandle<()>,
+}
+
+impl WindowWatcher {
+    fn start(window: TimeWindow, pause: PauseSignal) -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+        let closed = Arc::new(AtomicBool::new(false));
+        let thread = {
+            let stop = Arc::clone(&stop);
+            let closed = Arc::clone(&closed);
+            std::thread::spawn(move || {
+                while !stop.load(Ordering::SeqCst) {
+                    if !window.contains(chrono::Local::now().time()) {
+                        closed.store(true, Ordering::SeqCst);
+                        pause.request();
+                        return;
+                    }
+                    std::thread::sleep(std::time::Duration::from_secs(1));
+                }
+            })
+        };
+        Self {
+            stop,
+            closed,
+            thread,
+        }
+    }
+
+    /// Stop watching; returns whether the window closed during the transfer.
+    fn finish(self) -> bool {
+        self.stop.store(true, Ordering::SeqCst);
+        let _ = self.thread.join();
+        self.closed.load(Ordering::SeqCst)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn outcome(id: u64, status: QueueStatus, error: Option<&str>) -> EntryOutcome {
+        EntryOutcome {
+            id,
+            source: format!("src{}", id),
+            dest: "/backup".to_string(),
+            status,
+            bytes: 2048,
+            elapsed: Duration::from_millis(1500),
+            error: error.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn summary_table_lists_each_entry_and_totals() {
+        let lines = summary_table(&[
+            outcome(1, QueueStatus::Completed, None),
+            outcome(2, QueueStatus::Failed, Some("Permission denied")),
+            outcome(3, QueueStatus::Paused, None),
+        ]);
+        assert_eq!(lines.len(), 6);
+        assert!(lines[0].starts_with("ID   RESULT"));
+        assert!(lines[2].starts_with("1    completed "));
+        assert!(lines[2].contains("1.5s  src1 -> /backup"));
+        assert!(lines[3].ends_with("src2 -> /backup (Permission denied)"));
+        assert_eq!(lines[5], "1 completed, 1 failed, 1 not finished");
+    }
+
+    #[test]
+    fn estimate_size_sums_a_local_tree() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("a"), vec![0u8; 100]).unwrap();
+        std::fs::create_dir(dir.path().join("sub")).unwrap();
+        std::fs::write(dir.path().join("sub").join("b"), vec![0u8; 50]).unwrap();
+        assert_eq!(estimate_size(dir.path().to_str().unwrap()), 150);
+        assert_eq!(estimate_size(dir.path().join("a").to_str().unwrap()), 100);
+    }
+}
@@ -1,10 +1,13 @@
 use chrono::{DateTime, Utc};
-use fs2::FileExt;
+use rusqlite::{params, Connection, Transaction};
 use serde::{Deserialize, Serialize};
 use std::fs::File;
-use std::path::{Path, PathBuf};
+use std::path::Path;
 
+use crate::config::versioned::{wrap_entries, Format};
 use crate::error::FluxError;
+use crate::queue::policy::{run_order, QueueClass, QueuePriority};
+use crate::queue::store;
 
 /// Status of a queued transfer job.
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -46,19 +49,37 @@ pub struct QueueEntry {
     pub completed_at: Option<DateTime<Utc>>,
     pub bytes_transferred: u64,
     pub error: Option<String>,
+    /// Paused while running; the next run resumes from where it stopped.
+    #[serde(default)]
+    pub interrupted: bool,
+    /// Scheduling class used by `flux daemon`.
+    #[serde(default)]
+    pub class: QueueClass,
+    /// Entries of higher priority run first.
+    #[serde(default)]
+    pub priority: QueuePriority,
 }
 
-/// Persistent queue store backed by a JSON file.
+/// Format versions of the `queue.json` of earlier flux versions, read when
+/// it is imported: 1 was a bare array of entries, 2 wraps them in a
+/// `{"version": 2, "entries": [...]}` object.
+pub const QUEUE_FORMAT: Format = Format {
+    current: 2,
+    migrations: &[wrap_entries],
+};
+
+/// Persistent queue store backed by the state database.
 ///
-/// Stores transfer jobs in `queue.json` within the Flux data directory.
-/// Uses atomic writes (write to temp file, then rename) for crash safety.
+/// Jobs are rows of the `queue` table in `state.db` (see `crate::state`),
+/// in queue order. `save` rewrites them in one transaction, so a crash
+/// leaves either the old or the new queue.
 ///
 /// An exclusive advisory lock on `queue.lock` is held for the entire lifetime
 /// of this struct. The lock is released automatically when the `QueueStore` is
 /// dropped, preventing concurrent modification from multiple `flux queue run`
 /// invocations.
 pub struct QueueStore {
-    path: PathBuf,
+    db: Connection,
     entries: Vec<QueueEntry>,
     next_id: u64,
     /// Holds the open lock file. The `fs2` exclusive lock is tied to the file
@@ -67,73 +88,48 @@ pub struct QueueStore {
 }
 
 impl QueueStore {
-    /// Load the queue from `data_dir/queue.json`.
+    /// Load the queue from the state database in `data_dir`.
     ///
     /// Acquires an exclusive advisory lock on `data_dir/queue.lock` before
-    /// reading the state file. The lock is held until the returned `QueueStore`
+    /// reading the queue. The lock is held until the returned `QueueStore`
     /// is dropped. If another process already holds the lock this call blocks
     /// until that process releases it (i.e. finishes its own load-modify-save
     /// cycle).
     ///
-    /// If the queue file does not exist, returns an empty queue starting at
-    /// id 1. If the file is corrupted, logs a warning and starts fresh.
+    /// An empty queue starts at id 1. The `queue.json` of an earlier version
+    /// is imported the first time; see `crate::state`.
     pub fn load(data_dir: &Path) -> Result<Self, FluxError> {
-        let lock_path = data_dir.join("queue.lock");
-        let lock_file = File::options()
-            .read(true)
-            .write(true)
-            .create(true)
-            .truncate(false)
-            .open(&lock_path)
-            .map_err(|e| FluxError::Io { source: e })?;
-        lock_file
-            .lock_exclusive()
-            .map_err(|e| FluxError::Io { source: e })?;
-
-        let path = data_dir.join("queue.json");
-
-        if path.exists() {
-            let contents = std::fs::read_to_string(&path).map_err(|e| FluxError::Io {
-                source: e,
-            })?;
-            match serde_json::from_str::<Vec<QueueEntry>>(&contents) {
-                Ok(entries) => {
-                    let next_id = entries.iter().map(|e| e.id).max().unwrap_or(0) + 1;
-                    Ok(Self {
-                        path,
-                        entries,
-                        next_id,
-                        _lock_file: lock_file,
-                    })
-                }
-                Err(e) => {
-                    tracing::warn!("Corrupted queue.json, starting fresh: {}", e);
-                    Ok(Self {
-                        path,
-                        entries: Vec::new(),
-                        next_id: 1,
-                        _lock_file: lock_file,
-                    })
-                }
-            }
-        } else {
-            Ok(Self {
-                path,
-                entries: Vec::new(),
-                next_id: 1,
-                _lock_file: lock_file,
-            })
+        let lock_file = store::lock(&data_dir.join("queue.lock"))?;
+        Self::open(data_dir, lock_file)
+    }
+
+    /// Like `load`, but returns `None` instead of waiting while another
+    /// process holds the queue (e.g. `flux daemon` running an entry).
+    #[cfg(feature = "watch")]
+    pub fn try_load(data_dir: &Path) -> Result<Option<Self>, FluxError> {
+        match store::try_lock(&data_dir.join("queue.lock"))? {
+            Some(lock_file) => Self::open(data_dir, lock_file).map(Some),
+            None => Ok(None),
         }
     }
 
-    /// Atomically save the queue to `queue.json`.
-    ///
-    /// Writes to a temporary file first, then renames for crash safety.
+    fn open(data_dir: &Path, lock_file: File) -> Result<Self, FluxError> {
+        let db = crate::state::open(data_dir)?;
+        let entries = read_entries(&db)?;
+        let next_id = entries.iter().map(|e| e.id).max().unwrap_or(0) + 1;
+        Ok(Self {
+            db,
+            entries,
+            next_id,
+            _lock_file: lock_file,
+        })
+    }
+
+    /// Save the queue in one transaction.
     pub fn save(&self) -> Result<(), FluxError> {
-        let tmp_path = self.path.with_extension("json.tmp");
-        let json = serde_json::to_string_pretty(&self.entries)?;
-        std::fs::write(&tmp_path, json).map_err(|e| FluxError::Io { source: e })?;
-        std::fs::rename(&tmp_path, &self.path).map_err(|e| FluxError::Io { source: e })?;
+        let tx = self.db.unchecked_transaction()?;
+        write_entries(&tx, &self.entries)?;
+        tx.commit()?;
         Ok(())
     }
 
@@ -164,6 +160,9 @@ impl QueueStore {
             completed_at: None,
             bytes_transferred: 0,
             error: None,
+            interrupted: false,
+            class: QueueClass::default(),
+            priority: QueuePriority::default(),
         });
 
         id
@@ -248,12 +247,36 @@ impl QueueStore {
         }
     }
 
-    /// Return entries with Pending status, ordered by ID.
+    /// Return entries with Pending status in the order they run: by
+    /// priority, then queue order.
     pub fn pending_entries(&self) -> Vec<&QueueEntry> {
+        run_order(&self.entries)
+    }
+
+    /// Move entry `id` right before `target` (or right after it, with
+    /// `after`) in the queue.
+    ///
+    /// The entry takes over `target`'s priority, so that it also runs in its
+    /// new place.
+    pub fn move_entry(&mut self, id: u64, target: u64, after: bool) -> Result<(), FluxError> {
+        let from = self.position(id)?;
+        let priority = self.entries[self.position(target)?].priority;
+        if id == target {
+            return Ok(());
+        }
+        let mut entry = self.entries.remove(from);
+        entry.priority = priority;
+        let to = self.position(target)? + usize::from(after);
+        self.entries.insert(to, entry);
+        Ok(())
+    }
+
+    /// Index of entry `id` in the queue.
+    fn position(&self, id: u64) -> Result<usize, FluxError> {
         self.entries
             .iter()
-            .filter(|e| e.status == QueueStatus::Pending)
-            .collect()
+            .position(|e| e.id == id)
+            .ok_or_else(|| FluxError::QueueError(format!("Job #{} not found", id)))
     }
 
     /// Remove all Completed, Failed, and Cancelled entries from the queue.
@@ -267,6 +290,38 @@ impl QueueStore {
     }
 }
 
+/// Queue entries in queue order. Rows that do not parse are skipped.
+fn read_entries(db: &Connection) -> Result<Vec<QueueEntry>, FluxError> {
+    let mut select = db.prepare("SELECT id, entry FROM queue ORDER BY position")?;
+    let rows = select.query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)))?;
+    let mut entries = Vec::new();
+    for row in rows {
+        let (id, json) = row?;
+        match serde_json::from_str(&json) {
+            Ok(entry) => entries.push(entry),
+            Err(e) => tracing::warn!("Dropping unreadable queue entry #{}: {}", id, e),
+        }
+    }
+    Ok(entries)
+}
+
+/// Replace the queue with `entries`, in this order.
+fn write_entries(db: &Connection, entries: &[QueueEntry]) -> Result<(), FluxError> {
+    db.execute("DELETE FROM queue", [])?;
+    let mut insert = db.prepare("INSERT INTO queue (position, id, entry) VALUES (?1, ?2, ?3)")?;
+    for (position, entry) in entries.iter().enumerate() {
+        let json = serde_json::to_string(entry)?;
+        insert.execute(params![position as i64, entry.id as i64, json])?;
+    }
+    Ok(())
+}
+
+/// Import the `queue.json` of an earlier version (see `crate::state`).
+pub(crate) fn import(tx: &Transaction, path: &Path) -> Result<(), FluxError> {
+    let entries: Vec<QueueEntry> = store::load_entries(path, &QUEUE_FORMAT)?;
+    write_entries(tx, &entries)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -380,6 +435,42 @@ mod tests {
         assert_eq!(pending[1].id, 3);
     }
 
+    #[test]
+    fn pending_entries_run_by_priority() {
+        let (_dir, mut store) = temp_store();
+        store.add("a".into(), "b".into(), false, false, false);
+        store.add("c".into(), "d".into(), false, false, false);
+        store.add("e".into(), "f".into(), false, false, false);
+        store.get_mut(3).unwrap().priority = QueuePriority::High;
+        store.get_mut(1).unwrap().priority = QueuePriority::Low;
+        let ids: Vec<u64> = store.pending_entries().iter().map(|e| e.id).collect();
+        assert_eq!(ids, vec![3, 2, 1]);
+    }
+
+    #[test]
+    fn move_entry_reorders_and_takes_target_priority() {
+        let (_dir, mut store) = temp_store();
+        for _ in 0..4 {
+            store.add("a".into(), "b".into(), false, false, false);
+        }
+        store.get_mut(1).unwrap().priority = QueuePriority::High;
+        let ids = |store: &QueueStore| store.list().iter().map(|e| e.id).collect::<Vec<_>>();
+
+        store.move_entry(4, 1, false).unwrap();
+        assert_eq!(ids(&store), vec![4, 1, 2, 3]);
+        assert_eq!(store.get(4).unwrap().priority, QueuePriority::High);
+        assert_eq!(store.pending_entries()[0].id, 4);
+
+        store.move_entry(4, 3, true).unwrap();
+        assert_eq!(ids(&store), vec![1, 2, 3, 4]);
+        assert_eq!(store.get(4).unwrap().priority, QueuePriority::Normal);
+
+        store.move_entry(2, 2, false).unwrap();
+        assert_eq!(ids(&store), vec![1, 2, 3, 4]);
+        assert!(store.move_entry(2, 9, false).is_err());
+        assert!(store.move_entry(9, 2, false).is_err());
+    }
+
     #[test]
     fn clear_completed_removes_finished_entries() {
         let (_dir, mut store) = temp_store();
@@ -415,7 +506,17 @@ mod tests {
             assert_eq!(store.get(1).unwrap().source, "src1");
             assert_eq!(store.get(1).unwrap().status, QueueStatus::Pending);
             assert_eq!(store.get(2).unwrap().status, QueueStatus::Paused);
+            assert_eq!(store.get(2).unwrap().priority, QueuePriority::Normal);
         }
+
+        // The order survives a reload
+        {
+            let mut store = QueueStore::load(dir.path()).unwrap();
+            store.move_entry(2, 1, false).unwrap();
+            store.save().unwrap();
+        }
+        let store = QueueStore::load(dir.path()).unwrap();
+        assert_eq!(store.list()[0].id, 2);
     }
 
     #[test]
@@ -451,6 +552,47 @@ mod tests {
         assert!(store.list().is_empty());
     }
 
+    #[test]
+    fn legacy_queue_json_is_imported() {
+        let dir = tempfile::tempdir().unwrap();
+        let (_old, mut old) = temp_store();
+        old.add("a".into(), "b".into(), false, false, false);
+        old.add("c".into(), "d".into(), false, false, false);
+        old.move_entry(2, 1, false).unwrap();
+        // A version 1 file: a bare array
+        let path = dir.path().join("queue.json");
+        std::fs::write(&path, serde_json::to_string(old.list()).unwrap()).unwrap();
+
+        let store = QueueStore::load(dir.path()).unwrap();
+        let ids: Vec<u64> = store.list().iter().map(|e| e.id).collect();
+        assert_eq!(ids, [2, 1]);
+        assert!(!path.exists());
+        assert!(dir.path().join("queue.json.imported").exists());
+    }
+
+    #[test]
+    fn queues_from_a_newer_flux_are_refused_and_kept() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("queue.json");
+        let newer = r#"{"version": 3, "jobs": []}"#;
+        std::fs::write(&path, newer).unwrap();
+        assert!(matches!(
+            QueueStore::load(dir.path()),
+            Err(FluxError::NewerFormat { found: 3, .. })
+        ));
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), newer);
+    }
+
+    #[cfg(feature = "watch")]
+    #[test]
+    fn try_load_does_not_wait_for_a_held_queue() {
+        let dir = tempfile::tempdir().unwrap();
+        let held = QueueStore::load(dir.path()).unwrap();
+        assert!(QueueStore::try_load(dir.path()).unwrap().is_none());
+        drop(held);
+        assert!(QueueStore::try_load(dir.path()).unwrap().is_some());
+    }
+
     #[test]
     fn queue_status_display() {
         assert_eq!(format!("{}", QueueStatus::Pending), "pending");
@@ -0,0 +1,218 @@
+//! Locking and damaged-file handling shared by the queue and history stores.
+//!
+//! A store holds an exclusive `fs2` lock on its `.lock` file for its whole
+//! load-modify-save cycle, so concurrent `flux` processes take turns instead
+//! of overwriting each other's changes; the rows themselves live in the
+//! state database (`crate::state`).
+//!
+//! `load_entries` reads the JSON list files (`queue.json`, `history.json`)
+//! of earlier versions when they are imported. A file that cannot be read
+//! back is copied to `<name>.corrupt-<timestamp>` before the import carries
+//! on with the entries that still parse (none if the file is not JSON at
+//! all).
+
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+use fs2::FileExt;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+use crate::config::versioned::Format;
+use crate::error::FluxError;
+
+/// Open `lock_path` and take an exclusive lock on it, waiting for any other
+/// process that holds it. The lock lasts as long as the returned file.
+pub(crate) fn lock(lock_path: &Path) -> Result<File, FluxError> {
+    let lock_file = open_lock(lock_path)?;
+    lock_file
+        .lock_exclusive()
+        .map_err(|e| FluxError::Io { source: e })?;
+    Ok(lock_file)
+}
+
+/// Like `lock`, but returns `None` instead of waiting when another process
+/// holds the lock.
+#[cfg(feature = "watch")]
+pub(crate) fn try_lock(lock_path: &Path) -> Result<Option<File>, FluxError> {
+    let lock_file = open_lock(lock_path)?;
+    match lock_file.try_lock_exclusive() {
+        Ok(()) => Ok(Some(lock_file)),
+        Err(e) if e.kind() == fs2::lock_contended_error().kind() => Ok(None),
+        Err(e) => Err(FluxError::Io { source: e }),
+    }
+}
+
+fn open_lock(lock_path: &Path) -> Result<File, FluxError> {
+    File::options()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(lock_path)
+        .map_err(|e| FluxError::Io { source: e })
+}
+
+/// Load the entries of the JSON list store at `path`.
+///
+/// A missing file is an empty store; a file from a newer flux is refused
+/// with `FluxError::NewerFormat`. Damaged files are backed up (see the
+/// module docs) and yield the entries that could be read.
+pub(crate) fn load_entries<T: DeserializeOwned>(
+    path: &Path,
+    format: &Format,
+) -> Result<Vec<T>, FluxError> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let contents = std::fs::read_to_string(path).map_err(|e| FluxError::Io { source: e })?;
+    let mut doc = match format.upgrade(path, &contents) {
+        Ok(doc) => doc,
+        Err(e @ FluxError::NewerFormat { .. }) => return Err(e),
+        Err(e) => {
+            back_up(path, &e.to_string());
+            return Ok(Vec::new());
+        }
+    };
+    let items = match doc.get_mut("entries").map(Value::take) {
+        Some(Value::Array(items)) => items,
+        _ => {
+            back_up(path, "no list of entries");
+            return Ok(Vec::new());
+        }
+    };
+    let total = items.len();
+    let mut entries = Vec::with_capacity(total);
+    let mut unreadable = 0;
+    for item in items {
+        match serde_json::from_value(item) {
+            Ok(entry) => entries.push(entry),
+            Err(_) => unreadable += 1,
+        }
+    }
+    if unreadable > 0 {
+        back_up(
+            path,
+            &format!("{} of {} entries unreadable, kept the rest", unreadable, total),
+        );
+    }
+    Ok(entries)
+}
+
+/// Move the damaged file at `path` to `<name>.corrupt-<timestamp>` and
+/// warn about it, so a fresh one can take its place.
+pub(crate) fn move_aside(path: &Path, reason: &str) -> Result<(), FluxError> {
+    let backup = backup_path(path)?;
+    std::fs::rename(path, &backup).map_err(|e| FluxError::Io { source: e })?;
+    tracing::warn!(
+        "Corrupted {} ({}); moved it to {} and started over",
+        path.display(),
+        reason,
+        backup.display()
+    );
+    Ok(())
+}
+
+/// Copy the damaged file at `path` to `<name>.corrupt-<timestamp>` and warn
+/// about it.
+fn back_up(path: &Path, reason: &str) {
+    let name = path.file_name().map(|n| n.to_string_lossy()).unwrap_or_default();
+    match backup_path(path).and_then(|backup| {
+        std::fs::copy(path, &backup)?;
+        Ok(backup)
+    }) {
+        Ok(backup) => tracing::warn!(
+            "Corrupted {} ({}); the damaged file was saved as {}",
+            name,
+            reason,
+            backup.display()
+        ),
+        Err(e) => tracing::warn!("Corrupted {} ({}); could not back it up: {}", name, reason, e),
+    }
+}
+
+/// A free `<path>.corrupt-<timestamp>[-N]` name.
+fn backup_path(path: &Path) -> std::io::Result<PathBuf> {
+    let stamp = chrono::Local::now().format("%Y%m%d-%H%M%S");
+    let base = format!("{}.corrupt-{}", path.display(), stamp);
+    let mut backup = PathBuf::from(&base);
+    let mut n = 1;
+    while backup.exists() {
+        n += 1;
+        backup = PathBuf::from(format!("{}-{}", base, n));
+    }
+    Ok(backup)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::versioned::wrap_entries;
+
+    const FORMAT: Format = Format {
+        current: 2,
+        migrations: &[wrap_entries],
+    };
+
+    fn backups(dir: &Path) -> Vec<String> {
+        let mut names: Vec<String> = std::fs::read_dir(dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+            .filter(|name| name.contains(".corrupt-"))
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn entries_load_in_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("list.json");
+        std::fs::write(&path, "[3, 1, 2]").unwrap();
+        assert_eq!(load_entries::<u32>(&path, &FORMAT).unwrap(), [3, 1, 2]);
+        assert!(backups(dir.path()).is_empty());
+    }
+
+    #[test]
+    fn truncated_files_are_backed_up() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("list.json");
+        std::fs::write(&path, r#"{"version": 2, "entries": [1, 2"#).unwrap();
+        assert!(load_entries::<u32>(&path, &FORMAT).unwrap().is_empty());
+        assert_eq!(backups(dir.path()).len(), 1);
+
+        // A second damaged load in the same second gets its own backup
+        assert!(load_entries::<u32>(&path, &FORMAT).unwrap().is_empty());
+        let names = backups(dir.path());
+        assert_eq!(names.len(), 2);
+        let saved = std::fs::read_to_string(dir.path().join(&names[0])).unwrap();
+        assert_eq!(saved, r#"{"version": 2, "entries": [1, 2"#);
+    }
+
+    #[test]
+    fn unreadable_entries_are_dropped_and_the_rest_kept() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("list.json");
+        std::fs::write(&path, r#"{"version": 2, "entries": [1, "two", 3]}"#).unwrap();
+        assert_eq!(load_entries::<u32>(&path, &FORMAT).unwrap(), [1, 3]);
+        assert_eq!(backups(dir.path()).len(), 1);
+    }
+
+    #[test]
+    fn damaged_files_can_be_moved_aside() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state.db");
+        std::fs::write(&path, "garbage").unwrap();
+        move_aside(&path, "not a database").unwrap();
+        assert!(!path.exists());
+        assert_eq!(backups(dir.path()).len(), 1);
+    }
+
+    #[test]
+    fn missing_files_are_empty_without_a_backup() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("list.json");
+        assert!(load_entries::<u32>(&path, &FORMAT).unwrap().is_empty());
+        assert!(backups(dir.path()).is_empty());
+    }
+}
@@ -0,0 +1,617 @@
+//! Encryption at rest for `cp --encrypt-to` and `flux decrypt`.
+//!
+//! Files copied to storage that should not see their contents (a shared NAS,
+//! WebDAV, cloud buckets) are encrypted to a device key as they are written,
+//! in the style of age: a fresh X25519 key per file is combined with the
+//! recipient's identity key into a file key, and the data is sealed in
+//! numbered XChaCha20-Poly1305 segments so it can be streamed in and out.
+//!
+//! ```text
+//! "FLUXENC1" | ephemeral X25519 public key (32 bytes) | segment | segment ...
+//! segment = 64 KiB of plaintext (the last may be shorter) + 16-byte tag
+//! nonce   = 15 zero bytes | segment counter (u64, big-endian) | last flag
+//! ```
+//!
+//! The last-segment flag makes a file cut at a segment boundary fail to
+//! decrypt instead of silently coming out short. Only the device owning the
+//! identity key (`identity.json`) can decrypt; `flux decrypt --export-key`
+//! writes the public half for the machines that encrypt.
+
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use chacha20poly1305::aead::OsRng;
+use indicatif::ProgressBar;
+use walkdir::WalkDir;
+use x25519_dalek::{PublicKey, StaticSecret};
+
+use crate::cli::args::DecryptArgs;
+use crate::config::paths::flux_config_dir;
+use crate::error::FluxError;
+use crate::progress::bar::create_file_progress;
+use crate::security::crypto::{DeviceIdentity, EncryptedChannel};
+use crate::transfer::atomic::AtomicFile;
+
+/// Suffix appended to the names of encrypted files.
+pub const ENCRYPTED_SUFFIX: &str = ".fluxenc";
+
+/// File signature (format version 1).
+const MAGIC: &[u8; 8] = b"FLUXENC1";
+
+/// Plaintext bytes per segment.
+const SEGMENT_SIZE: usize = 64 * 1024;
+
+/// Poly1305 tag appended to every segment.
+const TAG_SIZE: usize = 16;
+
+/// Signature and ephemeral public key.
+const HEADER_SIZE: usize = MAGIC.len() + 32;
+
+/// Load a recipient public key written by `flux decrypt --export-key`.
+///
+/// The key is the first line that is neither blank nor a `#` comment.
+pub fn load_recipient(path: &Path) -> Result<PublicKey, FluxError> {
+    let content = std::fs::read_to_string(path).map_err(|e| {
+        FluxError::FileEncryptionError(format!(
+            "Cannot read recipient key '{}': {}",
+            path.display(),
+            e
+        ))
+    })?;
+    let line = content
+        .lines()
+        .map(str::trim)
+        .find(|l| !l.is_empty() && !l.starts_with('#'))
+        .unwrap_or_default();
+    let bytes: [u8; 32] = BASE64
+        .decode(line)
+        .ok()
+        .and_then(|b| b.try_into().ok())
+        .ok_or_else(|| {
+            FluxError::FileEncryptionError(format!(
+                "'{}' does not contain a recipient key",
+                path.display()
+            ))
+        })?;
+    Ok(PublicKey::from(bytes))
+}
+
+/// Contents of a recipient key file for this device.
+pub fn recipient_key_file(identity: &DeviceIdentity) -> String {
+    format!(
+        "# Flux recipient key, fingerprint {}\n\
+         # Encrypt to this device with: flux cp --encrypt-to <this file> <src> <dest>\n\
+         {}\n",
+        identity.fingerprint(),
+        identity.public_key_base64()
+    )
+}
+
+/// Name of the encrypted copy of `path`: `<name>.fluxenc`.
+pub fn encrypted_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(ENCRYPTED_SUFFIX);
+    PathBuf::from(name)
+}
+
+/// Name of the decrypted copy of `path`, if it ends in `.fluxenc`.
+pub fn decrypted_path(path: &Path) -> Option<PathBuf> {
+    let name = path.file_name()?.to_str()?;
+    let stem = name.strip_suffix(ENCRYPTED_SUFFIX)?;
+    (!stem.is_empty()).then(|| path.with_file_name(stem))
+}
+
+/// Nonce of segment `counter`.
+fn segment_nonce(counter: u64, last: bool) -> [u8; 24] {
+    let mut nonce = [0u8; 24];
+    nonce[15..23].copy_from_slice(&counter.to_be_bytes());
+    nonce[23] = u8::from(last);
+    nonce
+}
+
+/// Read until `buf` is full or the input ends. Returns the bytes read.
+fn read_full(reader: &mut impl Read, buf: &mut [u8]) -> std::io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(filled)
+}
+
+/// Size of the encrypted file for `plaintext` bytes.
+pub fn encrypted_len(plaintext: u64) -> u64 {
+    let segments = plaintext.div_ceil(SEGMENT_SIZE as u64).max(1);
+    HEADER_SIZE as u64 + plaintext + segments * TAG_SIZE as u64
+}
+
+/// Plaintext size of an encrypted file of `len` bytes, `None` if no
+/// encrypted file has that size.
+pub fn plaintext_len(len: u64) -> Option<u64> {
+    let body = len.checked_sub(HEADER_SIZE as u64)?;
+    let segments = body.div_ceil((SEGMENT_SIZE + TAG_SIZE) as u64).max(1);
+    let plaintext = body.checked_sub(segments * TAG_SIZE as u64)?;
+    (encrypted_len(plaintext) == len).then_some(plaintext)
+}
+
+/// Reads a plaintext stream as the encrypted file for `recipient`.
+///
+/// Sealing needs to know which segment is the last, so one segment of
+/// plaintext is read ahead.
+pub struct EncryptingReader<R> {
+    inner: R,
+    channel: EncryptedChannel,
+    /// Encrypted bytes not yet handed out, from `pos`
+    out: Vec<u8>,
+    pos: usize,
+    /// The next segment's plaintext, read ahead
+    next: Vec<u8>,
+    counter: u64,
+    done: bool,
+}
+
+impl<R: Read> EncryptingReader<R> {
+    pub fn new(mut inner: R, recipient: &PublicKey) -> Result<Self, FluxError> {
+        let ephemeral = StaticSecret::random_from_rng(OsRng);
+        let ephemeral_public = PublicKey::from(&ephemeral);
+        let channel =
+            EncryptedChannel::for_file(&ephemeral, recipient, &ephemeral_public, recipient);
+        let mut out = Vec::with_capacity(SEGMENT_SIZE + TAG_SIZE);
+        out.extend_from_slice(MAGIC);
+        out.extend_from_slice(ephemeral_public.as_bytes());
+        let mut next = vec![0u8; SEGMENT_SIZE];
+        let len = read_full(&mut inner, &mut next)?;
+        next.truncate(len);
+        Ok(Self {
+            inner,
+            channel,
+            out,
+            pos: 0,
+            next,
+            counter: 0,
+            done: false,
+        })
+    }
+
+    /// Seal the read-ahead segment into `out`, reading the one after it.
+    fn seal_next(&mut self) -> std::io::Result<()> {
+        let current = std::mem::replace(&mut self.next, vec![0u8; SEGMENT_SIZE]);
+        let next_len = if current.len() == SEGMENT_SIZE {
+            read_full(&mut self.inner, &mut self.next)?
+        } else {
+            0
+        };
+        self.next.truncate(next_len);
+        let last = next_len == 0;
+        self.out = self
+            .channel
+            .encrypt_with_nonce(&current, &segment_nonce(self.counter, last))
+            .map_err(std::io::Error::other)?;
+        self.pos = 0;
+        self.counter += 1;
+        self.done = last;
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for EncryptingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.pos == self.out.len() {
+            if self.done {
+                return Ok(0);
+            }
+            self.seal_next()?;
+        }
+        let n = buf.len().min(self.out.len() - self.pos);
+        buf[..n].copy_from_slice(&self.out[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+/// Reads an encrypted file as its plaintext, decrypting with `identity`.
+///
+/// A segment that fails to authenticate (or a file cut short) is an
+/// `InvalidData` error carrying `FluxError::FileEncryptionError`; see
+/// `from_io`.
+pub struct DecryptingReader<R> {
+    inner: R,
+    channel: EncryptedChannel,
+    /// Plaintext not yet handed out, from `pos`
+    out: Vec<u8>,
+    pos: usize,
+    /// The next sealed segment, read ahead
+    next: Vec<u8>,
+    counter: u64,
+    done: bool,
+}
+
+impl<R: Read> DecryptingReader<R> {
+    pub fn new(mut inner: R, identity: &DeviceIdentity) -> Result<Self, FluxError> {
+        let mut header = [0u8; HEADER_SIZE];
+        if read_full(&mut inner, &mut header)? < header.len() || &header[..MAGIC.len()] != MAGIC
+        {
+            return Err(FluxError::FileEncryptionError("Not a Flux-encrypted file".into()));
+        }
+        let mut key = [0u8; 32];
+        key.copy_from_slice(&header[MAGIC.len()..]);
+        let ephemeral_public = PublicKey::from(key);
+        let channel = EncryptedChannel::for_file(
+            identity.secret_key(),
+            &ephemeral_public,
+            &ephemeral_public,
+            identity.public_key(),
+        );
+        let mut next = vec![0u8; SEGMENT_SIZE + TAG_SIZE];
+        let len = read_full(&mut inner, &mut next)?;
+        next.truncate(len);
+        Ok(Self {
+            inner,
+            channel,
+            out: Vec::new(),
+            pos: 0,
+            next,
+            counter: 0,
+            done: false,
+        })
+    }
+
+    /// Open the read-ahead segment into `out`, reading the one after it.
+    fn open_next(&mut self) -> std::io::Result<()> {
+        let segment = SEGMENT_SIZE + TAG_SIZE;
+        let current = std::mem::replace(&mut self.next, vec![0u8; segment]);
+        let next_len = if current.len() == segment {
+            read_full(&mut self.inner, &mut self.next)?
+        } else {
+            0
+        };
+        self.next.truncate(next_len);
+        let last = next_len == 0;
+        self.out = self
+            .channel
+            .decrypt(&current, &segment_nonce(self.counter, last))
+            .map_err(|_| {
+                std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    FluxError::FileEncryptionError(
+                        "Decryption failed: the file is corrupted, truncated, or encrypted to a different device key"
+                            .into(),
+                    ),
+                )
+            })?;
+        self.pos = 0;
+        self.counter += 1;
+        self.done = last;
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for DecryptingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        // Loop: an empty file is one empty (but authenticated) segment
+        while self.pos == self.out.len() {
+            if self.done {
+                return Ok(0);
+            }
+            self.open_next()?;
+        }
+        let n = buf.len().min(self.out.len() - self.pos);
+        buf[..n].copy_from_slice(&self.out[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+/// Convert an I/O error from `EncryptingReader`/`DecryptingReader` to a
+/// `FluxError`, restoring the `FileEncryptionError` it carries.
+pub fn from_io(err: std::io::Error) -> FluxError {
+    let encryption = err
+        .get_ref()
+        .and_then(|inner| inner.downcast_ref::<FluxError>())
+        .and_then(|inner| match inner {
+            FluxError::FileEncryptionError(msg) => Some(msg.clone()),
+            _ => None,
+        });
+    match encryption {
+        Some(msg) => FluxError::FileEncryptionError(msg),
+        None => crate::transfer::cancel::from_io(err),
+    }
+}
+
+/// Encrypt everything from `reader` to `recipient`, writing the encrypted
+/// file to `writer`. Returns the number of plaintext bytes.
+pub fn encrypt_stream(
+    reader: impl Read,
+    mut writer: impl Write,
+    recipient: &PublicKey,
+) -> Result<u64, FluxError> {
+    let mut counted = CountingReader { inner: reader, bytes: 0 };
+    let mut sealed = EncryptingReader::new(&mut counted, recipient)?;
+    std::io::copy(&mut sealed, &mut writer).map_err(from_io)?;
+    writer.flush()?;
+    Ok(counted.bytes)
+}
+
+/// Decrypt a file encrypted to `identity` from `reader` into `writer`.
+/// Returns the number of plaintext bytes.
+pub fn decrypt_stream(
+    reader: impl Read,
+    mut writer: impl Write,
+    identity: &DeviceIdentity,
+) -> Result<u64, FluxError> {
+    let mut plain = DecryptingReader::new(reader, identity)?;
+    let total = std::io::copy(&mut plain, &mut writer).map_err(from_io)?;
+    writer.flush()?;
+    Ok(total)
+}
+
+/// Counts the bytes read through it.
+struct CountingReader<R> {
+    inner: R,
+    bytes: u64,
+}
+
+impl<R: Read> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.bytes += n as u64;
+        Ok(n)
+    }
+}
+
+/// Encrypt `source` into `dest`, counting plaintext bytes on `progress`.
+pub fn encrypt_file(
+    source: &Path,
+    dest: &Path,
+    recipient: &PublicKey,
+    progress: &ProgressBar,
+) -> Result<u64, FluxError> {
+    let src_file = std::fs::File::open(source)?;
+    let reader = progress.wrap_read(BufReader::with_capacity(256 * 1024, src_file));
+    let dst_file = std::fs::File::create(dest)?;
+    encrypt_stream(reader, BufWriter::with_capacity(256 * 1024, dst_file), recipient)
+}
+
+/// Decrypt `source` into `dest`. Nothing is left at `dest` unless the whole
+/// file authenticated.
+fn decrypt_file(
+    source: &Path,
+    dest: &Path,
+    identity: &DeviceIdentity,
+    progress: &ProgressBar,
+) -> Result<u64, FluxError> {
+    if dest.exists() {
+        return Err(FluxError::FileEncryptionError(format!(
+            "Output '{}' already exists",
+            dest.display()
+        )));
+    }
+    if let Some(parent) = dest.parent() {
+        if !parent.as_os_str().is_empty() {
+            std::fs::create_dir_all(parent)?;
+        }
+    }
+    let atomic = AtomicFile::new(dest);
+    let src_file = std::fs::File::open(source)?;
+    let reader = progress.wrap_read(BufReader::with_capacity(256 * 1024, src_file));
+    let dst_file = std::fs::File::create(atomic.path())?;
+    let bytes = decrypt_stream(
+        reader,
+        BufWriter::with_capacity(256 * 1024, dst_file),
+        identity,
+    )
+    .map_err(|e| match e {
+        FluxError::FileEncryptionError(msg) => {
+            FluxError::FileEncryptionError(format!("{}: {}", source.display(), msg))
+        }
+        other => other,
+    })?;
+    atomic.commit()?;
+    Ok(bytes)
+}
+
+/// Execute `flux decrypt`.
+pub fn execute_decrypt(args: DecryptArgs, quiet: bool) -> Result<(), FluxError> {
+    let identity = DeviceIdentity::load_or_create(&flux_config_dir()?)?;
+
+    if let Some(path) = args.export_key {
+        std::fs::write(&path, recipient_key_file(&identity))?;
+        eprintln!(
+            "Wrote recipient key {} to {}",
+            identity.fingerprint(),
+            path.display()
+        );
+        return Ok(());
+    }
+
+    let input = args
+        .input
+        .expect("clap requires an input unless --export-key is given");
+
+    if !input.is_dir() {
+        let output = match args.output {
+            Some(output) if output.is_dir() => output.join(decrypted_name(&input)?),
+            Some(output) => output,
+            None => input.with_file_name(decrypted_name(&input)?),
+        };
+        let size = std::fs::metadata(&input)?.len();
+        let progress = create_file_progress(size, quiet);
+        let bytes = decrypt_file(&input, &output, &identity, &progress)?;
+        progress.finish_and_clear();
+        if !quiet {
+            eprintln!(
+                "Decrypted {} -> {} ({})",
+                input.display(),
+                output.display(),
+                bytesize::ByteSize(bytes)
+            );
+        }
+        return Ok(());
+    }
+
+    // Directory: decrypt every `.fluxenc` file, keeping the layout
+    let output_root = args.output.unwrap_or_else(|| input.clone());
+    let mut files = 0u64;
+    let mut bytes = 0u64;
+    for entry in WalkDir::new(&input).follow_links(false) {
+        let entry = entry?;
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        let Some(plain) = decrypted_path(entry.path()) else {
+            continue;
+        };
+        let output = output_root.join(plain.strip_prefix(&input)?);
+        bytes += decrypt_file(entry.path(), &output, &identity, &ProgressBar::hidden())?;
+        files += 1;
+        if !quiet {
+            eprintln!("Decrypted {}", output.display());
+        }
+    }
+    if files == 0 {
+        return Err(FluxError::FileEncryptionError(format!(
+            "No {} files found in '{}'",
+            ENCRYPTED_SUFFIX,
+            input.display()
+        )));
+    }
+    if !quiet {
+        eprintln!("Decrypted {} file(s) ({})", files, bytesize::ByteSize(bytes));
+    }
+    Ok(())
+}
+
+/// File name of the decrypted copy of `input`, or an error asking for an
+/// explicit output path.
+fn decrypted_name(input: &Path) -> Result<PathBuf, FluxError> {
+    decrypted_path(input)
+        .and_then(|p| p.file_name().map(PathBuf::from))
+        .ok_or_else(|| {
+            FluxError::FileEncryptionError(format!(
+                "'{}' does not end in {}; give an output path",
+                input.display(),
+                ENCRYPTED_SUFFIX
+            ))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn roundtrip(data: &[u8]) {
+        let identity = DeviceIdentity::generate();
+        let mut encrypted = Vec::new();
+        let n = encrypt_stream(data, &mut encrypted, identity.public_key()).unwrap();
+        assert_eq!(n, data.len() as u64);
+        let segments = data.len().div_ceil(SEGMENT_SIZE).max(1);
+        assert_eq!(encrypted.len(), 40 + data.len() + segments * TAG_SIZE);
+        assert_eq!(encrypted_len(data.len() as u64), encrypted.len() as u64);
+        assert_eq!(plaintext_len(encrypted.len() as u64), Some(data.len() as u64));
+
+        let mut decrypted = Vec::new();
+        decrypt_stream(encrypted.as_slice(), &mut decrypted, &identity).unwrap();
+        assert_eq!(decrypted, data);
+    }
+
+    #[test]
+    fn roundtrip_across_segment_boundaries() {
+        roundtrip(b"");
+        roundtrip(b"hello");
+        roundtrip(&vec![7u8; SEGMENT_SIZE]);
+        roundtrip(&vec![9u8; SEGMENT_SIZE * 2 + 100]);
+    }
+
+    #[test]
+    fn readers_stream_in_small_reads() {
+        let identity = DeviceIdentity::generate();
+        let data: Vec<u8> = (0..SEGMENT_SIZE * 2 + 7).map(|i| i as u8).collect();
+        let mut sealed = EncryptingReader::new(data.as_slice(), identity.public_key()).unwrap();
+        let mut encrypted = Vec::new();
+        let mut buf = [0u8; 1000];
+        loop {
+            let n = sealed.read(&mut buf).unwrap();
+            if n == 0 {
+                break;
+            }
+            encrypted.extend_from_slice(&buf[..n]);
+        }
+        assert_eq!(encrypted.len() as u64, encrypted_len(data.len() as u64));
+
+        let mut plain = DecryptingReader::new(encrypted.as_slice(), &identity).unwrap();
+        let mut decrypted = Vec::new();
+        plain.read_to_end(&mut decrypted).unwrap();
+        assert_eq!(decrypted, data);
+
+        // A bad segment surfaces as an encryption error, not plain I/O
+        encrypted[60] ^= 1;
+        let mut plain = DecryptingReader::new(encrypted.as_slice(), &identity).unwrap();
+        let err = plain.read_to_end(&mut Vec::new()).unwrap_err();
+        assert!(matches!(from_io(err), FluxError::FileEncryptionError(_)));
+    }
+
+    #[test]
+    fn sizes_that_cannot_be_encrypted_files() {
+        assert_eq!(plaintext_len(0), None);
+        assert_eq!(plaintext_len(40 + 15), None);
+        // A second segment shorter than its tag
+        assert_eq!(plaintext_len(encrypted_len(SEGMENT_SIZE as u64) + 5), None);
+    }
+
+    #[test]
+    fn wrong_key_fails() {
+        let recipient = DeviceIdentity::generate();
+        let other = DeviceIdentity::generate();
+        let mut encrypted = Vec::new();
+        encrypt_stream(&b"secret"[..], &mut encrypted, recipient.public_key()).unwrap();
+        assert!(decrypt_stream(encrypted.as_slice(), std::io::sink(), &other).is_err());
+    }
+
+    #[test]
+    fn truncation_and_tampering_are_detected() {
+        let identity = DeviceIdentity::generate();
+        let data = vec![1u8; SEGMENT_SIZE * 2];
+        let mut encrypted = Vec::new();
+        encrypt_stream(data.as_slice(), &mut encrypted, identity.public_key()).unwrap();
+
+        // Cut after the first segment: it was not sealed as the last one
+        let cut = &encrypted[..40 + SEGMENT_SIZE + TAG_SIZE];
+        assert!(decrypt_stream(cut, std::io::sink(), &identity).is_err());
+
+        let mut flipped = encrypted.clone();
+        flipped[100] ^= 1;
+        assert!(decrypt_stream(flipped.as_slice(), std::io::sink(), &identity).is_err());
+
+        assert!(decrypt_stream(&b"plain text"[..], std::io::sink(), &identity).is_err());
+    }
+
+    #[test]
+    fn recipient_key_file_roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+        let identity = DeviceIdentity::generate();
+        let path = dir.path().join("recipient.key");
+        std::fs::write(&path, recipient_key_file(&identity)).unwrap();
+        assert_eq!(
+            load_recipient(&path).unwrap().as_bytes(),
+            identity.public_key().as_bytes()
+        );
+
+        std::fs::write(&path, "# empty\n").unwrap();
+        assert!(load_recipient(&path).is_err());
+    }
+
+    #[test]
+    fn encrypted_names() {
+        let enc = encrypted_path(Path::new("out/report.pdf"));
+        assert_eq!(enc, Path::new("out/report.pdf.fluxenc"));
+        assert_eq!(decrypted_path(&enc).unwrap(), Path::new("out/report.pdf"));
+        assert!(decrypted_path(Path::new("out/report.pdf")).is_none());
+        assert!(decrypted_path(Path::new("out/.fluxenc")).is_none());
+    }
+}
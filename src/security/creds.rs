@@ -0,0 +1,582 @@
+//! Stored backend credentials (`flux creds add/rm/list`).
+//!
+//! Passwords for `sftp://`, `smb://` and WebDAV hosts are kept in the OS
+//! keychain (Keychain on macOS, Credential Manager on Windows, the Secret
+//! Service on Linux) under the service name `flux`, one entry per
+//! `protocol://user@host`. Keychains cannot be enumerated portably, so
+//! `credentials.json` in the config directory indexes what was stored; it
+//! never holds a keychain password.
+//!
+//! Where no keychain is reachable (headless Linux without a Secret Service,
+//! containers), the password is sealed with XChaCha20-Poly1305 into
+//! `credentials.json` itself, under a key derived from the device identity
+//! (`identity.json`). That keeps passwords out of plain sight and out of
+//! copies of the index alone; anyone who can read both files can decrypt.
+//!
+//! Backends consult the store when a URL carries no password: SFTP after the
+//! agent and key files and before prompting, WebDAV and SMB before connecting.
+
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use chacha20poly1305::XChaCha20Poly1305;
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroizing;
+
+use crate::cli::args::{CredsAction, CredsArgs};
+use crate::config::paths::flux_config_dir;
+use crate::error::FluxError;
+use crate::protocol::auth::Auth;
+use crate::protocol::parser::detect_protocol;
+use crate::protocol::Protocol;
+use crate::security::crypto::DeviceIdentity;
+
+/// Keychain service name all entries are stored under.
+const SERVICE: &str = "flux";
+
+/// Index file in the config directory.
+const INDEX_FILE: &str = "credentials.json";
+
+/// Domain separation string for the file-fallback key.
+const FILE_KEY_CONTEXT: &str = "flux v1 credential file key";
+
+/// XChaCha20 nonce length.
+#[cfg(any(
+    feature = "backends-sftp",
+    feature = "backends-webdav",
+    all(windows, feature = "backends-smb")
+))]
+const NONCE_LEN: usize = 24;
+
+/// Where a credential's password lives.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Store {
+    /// The OS keychain.
+    Keychain,
+    /// Encrypted inside `credentials.json`.
+    File,
+}
+
+impl std::fmt::Display for Store {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Store::Keychain => f.pad("keychain"),
+            Store::File => f.pad("file"),
+        }
+    }
+}
+
+/// The host a credential is for: `flux creds add sftp://alice@nas`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Target {
+    /// Backend name, as in `Protocol::name()`.
+    pub protocol: &'static str,
+    /// Host name, lowercased. Ports and paths are ignored.
+    pub host: String,
+    /// User name, if the target or `--user` named one.
+    pub user: Option<String>,
+}
+
+impl Target {
+    /// Parse a backend URL or UNC path; `user` overrides the URL's user.
+    pub fn parse(input: &str, user: Option<&str>) -> Result<Self, FluxError> {
+        let (protocol, host, url_user) = match detect_protocol(input) {
+            Protocol::Sftp { user, host, .. } => ("sftp", host, user),
+            Protocol::Smb { server, .. } => ("smb", server, String::new()),
+            Protocol::WebDav { url, auth } => {
+                let host = url::Url::parse(&url)
+                    .ok()
+                    .and_then(|u| u.host_str().map(str::to_string))
+                    .unwrap_or_default();
+                let user = match auth {
+                    Some(Auth::Password { user, .. }) => user,
+                    _ => String::new(),
+                };
+                ("webdav", host, user)
+            }
+            Protocol::Rclone { .. } => {
+                return Err(FluxError::CredentialError(
+                    "rclone remotes keep their credentials in rclone.conf (see `rclone config`)"
+                        .into(),
+                ));
+            }
+            Protocol::Local { .. } => {
+                return Err(FluxError::CredentialError(format!(
+                    "'{}' is not a remote location. Use sftp://user@host, smb://server \
+                     or https://host",
+                    input
+                )));
+            }
+        };
+        if host.is_empty() {
+            return Err(FluxError::CredentialError(format!("No host in '{}'", input)));
+        }
+        let user = user
+            .map(str::to_string)
+            .or_else(|| Some(url_user).filter(|u| !u.is_empty()));
+        Ok(Self {
+            protocol,
+            host: host.to_lowercase(),
+            user,
+        })
+    }
+}
+
+/// One stored credential. The password itself is in the keychain or sealed in
+/// `secret`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Credential {
+    pub protocol: String,
+    pub host: String,
+    pub user: String,
+    pub store: Store,
+    /// Base64 of nonce and ciphertext, for `Store::File` only.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    secret: Option<String>,
+}
+
+impl Credential {
+    /// Keychain account name, also bound into the file-fallback ciphertext.
+    fn account(&self) -> String {
+        format!("{}://{}@{}", self.protocol, self.user, self.host)
+    }
+
+    fn matches(&self, protocol: &str, host: &str, user: Option<&str>) -> bool {
+        self.protocol == protocol
+            && self.host.eq_ignore_ascii_case(host)
+            && (user.is_none() || user == Some(self.user.as_str()))
+    }
+}
+
+/// The credential index, `config_dir/credentials.json`.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct CredentialStore {
+    entries: Vec<Credential>,
+    #[serde(skip)]
+    path: PathBuf,
+    /// Whether to try the OS keychain; off in tests.
+    #[serde(skip)]
+    keychain: bool,
+}
+
+impl CredentialStore {
+    /// Load the index from `config_dir`. Returns an empty store if it does not
+    /// exist.
+    pub fn load(config_dir: &Path) -> Result<Self, FluxError> {
+        Self::open(config_dir, true)
+    }
+
+    fn open(config_dir: &Path, keychain: bool) -> Result<Self, FluxError> {
+        let path = config_dir.join(INDEX_FILE);
+        let mut store = if path.exists() {
+            let data = std::fs::read_to_string(&path).map_err(|e| {
+                FluxError::CredentialError(format!("Failed to read {}: {}", INDEX_FILE, e))
+            })?;
+            serde_json::from_str::<CredentialStore>(&data).map_err(|e| {
+                FluxError::CredentialError(format!("{} is corrupted: {}", INDEX_FILE, e))
+            })?
+        } else {
+            Self::default()
+        };
+        store.path = path;
+        store.keychain = keychain;
+        Ok(store)
+    }
+
+    /// Save the index using an atomic write, readable by the owner only on Unix.
+    pub fn save(&self) -> Result<(), FluxError> {
+        let tmp_path = self.path.with_extension("json.tmp");
+        let json = serde_json::to_string_pretty(&self).map_err(|e| {
+            FluxError::CredentialError(format!("Failed to serialize credentials: {}", e))
+        })?;
+        std::fs::write(&tmp_path, json).map_err(|e| {
+            FluxError::CredentialError(format!("Failed to write {}: {}", INDEX_FILE, e))
+        })?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let perms = std::fs::Permissions::from_mode(0o600);
+            std::fs::set_permissions(&tmp_path, perms).map_err(|e| {
+                FluxError::CredentialError(format!("Failed to set permissions: {}", e))
+            })?;
+        }
+        std::fs::rename(&tmp_path, &self.path).map_err(|e| {
+            FluxError::CredentialError(format!("Failed to save {}: {}", INDEX_FILE, e))
+        })
+    }
+
+    /// All stored credentials, in the order they were added.
+    pub fn entries(&self) -> &[Credential] {
+        &self.entries
+    }
+
+    /// The credential for `user@host`, or the first one for `host` when no user
+    /// is given.
+    pub fn find(&self, protocol: &str, host: &str, user: Option<&str>) -> Option<&Credential> {
+        self.entries.iter().find(|c| c.matches(protocol, host, user))
+    }
+
+    /// Store `password` for `target`, replacing any earlier entry. Returns
+    /// where it went.
+    pub fn add(&mut self, target: &Target, password: &str) -> Result<Store, FluxError> {
+        let user = target.user.clone().ok_or_else(|| {
+            FluxError::CredentialError(format!(
+                "No user for {}. Name one in the URL ({}://user@{}) or with --user",
+                target.host, target.protocol, target.host
+            ))
+        })?;
+        let mut credential = Credential {
+            protocol: target.protocol.to_string(),
+            host: target.host.clone(),
+            user,
+            store: Store::Keychain,
+            secret: None,
+        };
+        let account = credential.account();
+
+        let in_keychain = self.keychain && save_in_keychain(&account, password)?;
+        if !in_keychain {
+            credential.store = Store::File;
+            credential.secret = Some(self.seal(&account, password)?);
+        }
+
+        if let Some(old) = self.take(&credential.protocol, &credential.host, &credential.user) {
+            if old.store == Store::Keychain && credential.store == Store::File {
+                forget_keychain(&account);
+            }
+        }
+        let store = credential.store;
+        self.entries.push(credential);
+        self.save()?;
+        Ok(store)
+    }
+
+    /// Remove the credential for `target` from the index and the keychain.
+    /// Returns the entry that was removed, if any.
+    pub fn remove(&mut self, target: &Target) -> Result<Option<Credential>, FluxError> {
+        let found = self
+            .find(target.protocol, &target.host, target.user.as_deref())
+            .map(|c| (c.protocol.clone(), c.host.clone(), c.user.clone()));
+        let Some((protocol, host, user)) = found else {
+            return Ok(None);
+        };
+        let removed = self.take(&protocol, &host, &user);
+        if let Some(credential) = &removed {
+            if credential.store == Store::Keychain && self.keychain {
+                forget_keychain(&credential.account());
+            }
+        }
+        self.save()?;
+        Ok(removed)
+    }
+
+    /// Read the password for `credential` from wherever it is stored.
+    #[cfg(any(
+        feature = "backends-sftp",
+        feature = "backends-webdav",
+        all(windows, feature = "backends-smb")
+    ))]
+    pub fn password(&self, credential: &Credential) -> Result<Zeroizing<String>, FluxError> {
+        let account = credential.account();
+        match credential.store {
+            Store::Keychain => keyring::Entry::new(SERVICE, &account)
+                .and_then(|e| e.get_password())
+                .map(Zeroizing::new)
+                .map_err(|e| {
+                    let reason = format!("Keychain lookup for {} failed: {}", account, e);
+                    FluxError::CredentialError(reason)
+                }),
+            Store::File => {
+                let sealed = credential.secret.as_deref().ok_or_else(|| {
+                    FluxError::CredentialError(format!("No stored secret for {}", account))
+                })?;
+                self.open_sealed(&account, sealed)
+            }
+        }
+    }
+
+    fn take(&mut self, protocol: &str, host: &str, user: &str) -> Option<Credential> {
+        let index = self
+            .entries
+            .iter()
+            .position(|c| c.matches(protocol, host, Some(user)))?;
+        Some(self.entries.remove(index))
+    }
+
+    /// Cipher for the file fallback, keyed from the device identity next to the
+    /// index.
+    fn cipher(&self) -> Result<XChaCha20Poly1305, FluxError> {
+        let config_dir = self.path.parent().unwrap_or_else(|| Path::new("."));
+        let identity = DeviceIdentity::load_or_create(config_dir)?;
+        let secret = Zeroizing::new(identity.secret_key().to_bytes());
+        let key = Zeroizing::new(blake3::derive_key(FILE_KEY_CONTEXT, &*secret));
+        Ok(XChaCha20Poly1305::new((&*key).into()))
+    }
+
+    fn seal(&self, account: &str, password: &str) -> Result<String, FluxError> {
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let payload = Payload { msg: password.as_bytes(), aad: account.as_bytes() };
+        let ciphertext = self.cipher()?.encrypt(&nonce, payload).map_err(|e| {
+            FluxError::CredentialError(format!("Failed to encrypt password: {}", e))
+        })?;
+        let mut sealed = nonce.to_vec();
+        sealed.extend_from_slice(&ciphertext);
+        Ok(BASE64.encode(sealed))
+    }
+
+    #[cfg(any(
+        feature = "backends-sftp",
+        feature = "backends-webdav",
+        all(windows, feature = "backends-smb")
+    ))]
+    fn open_sealed(&self, account: &str, sealed: &str) -> Result<Zeroizing<String>, FluxError> {
+        let undecryptable = || {
+            FluxError::CredentialError(format!(
+                "Cannot decrypt the stored password for {} (was identity.json replaced?)",
+                account
+            ))
+        };
+        let bytes = BASE64.decode(sealed).map_err(|_| undecryptable())?;
+        if bytes.len() < NONCE_LEN {
+            return Err(undecryptable());
+        }
+        let (nonce, ciphertext) = bytes.split_at(NONCE_LEN);
+        let payload = Payload { msg: ciphertext, aad: account.as_bytes() };
+        let plaintext = self
+            .cipher()?
+            .decrypt(chacha20poly1305::XNonce::from_slice(nonce), payload)
+            .map_err(|_| undecryptable())?;
+        String::from_utf8(plaintext)
+            .map(Zeroizing::new)
+            .map_err(|_| undecryptable())
+    }
+}
+
+/// Store a password in the OS keychain. Returns `false` when no keychain is
+/// reachable, so the caller can fall back to the file.
+fn save_in_keychain(account: &str, password: &str) -> Result<bool, FluxError> {
+    match keyring::Entry::new(SERVICE, account).and_then(|e| e.set_password(password)) {
+        Ok(()) => Ok(true),
+        Err(keyring::Error::NoStorageAccess(e)) | Err(keyring::Error::PlatformFailure(e)) => {
+            tracing::warn!("OS keychain unavailable ({}); storing in {}", e, INDEX_FILE);
+            Ok(false)
+        }
+        Err(e) => Err(FluxError::CredentialError(format!(
+            "Keychain refused the password for {}: {}",
+            account, e
+        ))),
+    }
+}
+
+/// Delete a keychain entry, logging rather than failing: the index is what
+/// decides whether a credential exists.
+fn forget_keychain(account: &str) {
+    match keyring::Entry::new(SERVICE, account).and_then(|e| e.delete_credential()) {
+        Ok(()) | Err(keyring::Error::NoEntry) => {}
+        Err(e) => tracing::warn!("Could not delete keychain entry {}: {}", account, e),
+    }
+}
+
+/// Look up a stored login for a backend connection.
+///
+/// With `user` set, only that user's entry matches; otherwise the first entry
+/// for `host` does. Returns the user and password, or `None` when nothing is
+/// stored or the store cannot be read (the failure is logged and the backend
+/// falls back to its usual prompt or error).
+#[cfg(any(
+    feature = "backends-sftp",
+    feature = "backends-webdav",
+    all(windows, feature = "backends-smb")
+))]
+pub fn lookup(
+    protocol: &str,
+    host: &str,
+    user: Option<&str>,
+) -> Option<(String, Zeroizing<String>)> {
+    let found = flux_config_dir().and_then(|dir| {
+        let store = CredentialStore::load(&dir)?;
+        let login = match store.find(protocol, host, user) {
+            Some(c) => Some((c.user.clone(), store.password(c)?)),
+            None => None,
+        };
+        Ok(login)
+    });
+    match found {
+        Ok(login) => login,
+        Err(e) => {
+            tracing::debug!("No stored credential for {}://{}: {}", protocol, host, e);
+            None
+        }
+    }
+}
+
+/// The user of the first stored credential for `host`, without reading the
+/// password. Lets `sftp://host` pick up a stored login before deciding which
+/// user to authenticate as.
+#[cfg(feature = "backends-sftp")]
+pub fn stored_user(protocol: &str, host: &str) -> Option<String> {
+    let dir = flux_config_dir().ok()?;
+    let store = CredentialStore::load(&dir).ok()?;
+    store.find(protocol, host, None).map(|c| c.user.clone())
+}
+
+/// Execute `flux creds`.
+pub fn execute_creds(args: CredsArgs, quiet: bool) -> Result<(), FluxError> {
+    let config_dir = flux_config_dir()?;
+    let mut store = CredentialStore::load(&config_dir)?;
+
+    match args.action.unwrap_or(CredsAction::List) {
+        CredsAction::List => {
+            if store.entries().is_empty() {
+                eprintln!("No stored credentials");
+                return Ok(());
+            }
+            println!("{:<8} {:<30} {:<20} {:<8}", "PROTOCOL", "HOST", "USER", "STORE");
+            println!("{}", "-".repeat(69));
+            for c in store.entries() {
+                println!("{:<8} {:<30} {:<20} {:<8}", c.protocol, c.host, c.user, c.store);
+            }
+        }
+        CredsAction::Add(add) => {
+            let target = Target::parse(&add.target, add.user.as_deref())?;
+            let user = target.user.as_deref().unwrap_or_default();
+            let password = if add.password_stdin {
+                let mut input = Zeroizing::new(String::new());
+                std::io::stdin().read_to_string(&mut input)?;
+                Zeroizing::new(input.trim_end_matches(['\r', '\n']).to_string())
+            } else {
+                let prompt = format!("Password for {}@{}: ", user, target.host);
+                Zeroizing::new(rpassword::prompt_password(prompt)?)
+            };
+            if password.is_empty() {
+                return Err(FluxError::CredentialError("Empty password, nothing stored".into()));
+            }
+            let where_stored = store.add(&target, &password)?;
+            if !quiet {
+                eprintln!(
+                    "Stored {}://{}@{} in the {}",
+                    target.protocol,
+                    user,
+                    target.host,
+                    match where_stored {
+                        Store::Keychain => "OS keychain",
+                        Store::File => "encrypted credentials file",
+                    }
+                );
+            }
+        }
+        CredsAction::Rm(rm) => {
+            let target = Target::parse(&rm.target, rm.user.as_deref())?;
+            match store.remove(&target)? {
+                Some(c) => eprintln!("Removed {}", c.account()),
+                None => eprintln!("No stored credential for {}", rm.target),
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file_store(dir: &Path) -> CredentialStore {
+        CredentialStore::open(dir, false).unwrap()
+    }
+
+    fn target(input: &str) -> Target {
+        Target::parse(input, None).unwrap()
+    }
+
+    #[test]
+    fn parses_targets_of_every_backend() {
+        let sftp = target("sftp://alice@NAS.local:2222/home");
+        assert_eq!(sftp.protocol, "sftp");
+        assert_eq!(sftp.host, "nas.local");
+        assert_eq!(sftp.user.as_deref(), Some("alice"));
+
+        let dav = Target::parse("https://dav.example.com/files", Some("bob")).unwrap();
+        assert_eq!((dav.protocol, dav.host.as_str()), ("webdav", "dav.example.com"));
+        assert_eq!(dav.user.as_deref(), Some("bob"));
+
+        let smb = target("\\\\fileserver\\share");
+        assert_eq!((smb.protocol, smb.host.as_str()), ("smb", "fileserver"));
+        assert_eq!(smb.user, None);
+
+        assert!(Target::parse("/tmp/local", None).is_err());
+    }
+
+    #[cfg(any(
+        feature = "backends-sftp",
+        feature = "backends-webdav",
+        all(windows, feature = "backends-smb")
+    ))]
+    #[test]
+    fn file_fallback_roundtrips_and_hides_the_password() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut store = file_store(dir.path());
+        let stored = store.add(&target("sftp://alice@nas"), "hunter2").unwrap();
+        assert_eq!(stored, Store::File);
+
+        let index = std::fs::read_to_string(dir.path().join(INDEX_FILE)).unwrap();
+        assert!(!index.contains("hunter2"));
+
+        let store = file_store(dir.path());
+        let credential = store.find("sftp", "NAS", None).unwrap();
+        assert_eq!(credential.user, "alice");
+        assert_eq!(store.password(credential).unwrap().as_str(), "hunter2");
+    }
+
+    #[cfg(any(
+        feature = "backends-sftp",
+        feature = "backends-webdav",
+        all(windows, feature = "backends-smb")
+    ))]
+    #[test]
+    fn add_replaces_and_remove_deletes() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut store = file_store(dir.path());
+        store.add(&target("sftp://alice@nas"), "old").unwrap();
+        store.add(&target("sftp://alice@nas"), "new").unwrap();
+        store.add(&target("sftp://bob@nas"), "other").unwrap();
+        assert_eq!(store.entries().len(), 2);
+
+        let alice = store.find("sftp", "nas", Some("alice")).unwrap();
+        assert_eq!(store.password(alice).unwrap().as_str(), "new");
+
+        let removed = store.remove(&target("sftp://alice@nas")).unwrap().unwrap();
+        assert_eq!(removed.user, "alice");
+        assert!(store.remove(&target("sftp://alice@nas")).unwrap().is_none());
+        assert_eq!(file_store(dir.path()).entries().len(), 1);
+    }
+
+    #[cfg(any(
+        feature = "backends-sftp",
+        feature = "backends-webdav",
+        all(windows, feature = "backends-smb")
+    ))]
+    #[test]
+    fn sealed_password_is_bound_to_its_account() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut store = file_store(dir.path());
+        store.add(&target("sftp://alice@nas"), "hunter2").unwrap();
+        store.add(&target("sftp://bob@nas"), "swordfish").unwrap();
+
+        // Swapping ciphertexts between entries must not decrypt.
+        let mut swapped = store.find("sftp", "nas", Some("bob")).unwrap().clone();
+        swapped.secret = store.find("sftp", "nas", Some("alice")).unwrap().secret.clone();
+        assert!(store.password(&swapped).is_err());
+    }
+
+    #[test]
+    fn add_without_user_is_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut store = file_store(dir.path());
+        assert!(store.add(&target("smb://fileserver/share"), "pw").is_err());
+        assert!(store.entries().is_empty());
+    }
+}
@@ -14,10 +14,16 @@ use std::path::Path;
 
 use base64::engine::general_purpose::STANDARD as BASE64;
 use base64::Engine;
+#[cfg(feature = "net")]
+use chacha20poly1305::aead::AeadInPlace;
 use chacha20poly1305::aead::{Aead, KeyInit, OsRng};
-use chacha20poly1305::{AeadCore, XChaCha20Poly1305};
+#[cfg(feature = "net")]
+use chacha20poly1305::AeadCore;
+use chacha20poly1305::XChaCha20Poly1305;
 use serde::{Deserialize, Serialize};
-use x25519_dalek::{EphemeralSecret, PublicKey, StaticSecret};
+#[cfg(feature = "net")]
+use x25519_dalek::EphemeralSecret;
+use x25519_dalek::{PublicKey, StaticSecret};
 use zeroize::{Zeroize, Zeroizing};
 
 use crate::error::FluxError;
@@ -25,7 +31,17 @@ use crate::error::FluxError;
 /// Domain separation string for deriving symmetric keys from DH shared secrets.
 /// This ensures the derived key is bound to the Flux protocol and cannot be
 /// confused with keys derived for other purposes from the same shared secret.
-const KDF_CONTEXT: &str = "flux v1 xchacha20poly1305 session key";
+#[cfg(feature = "net")]
+pub(crate) const KDF_CONTEXT: &str = "flux v1 xchacha20poly1305 session key";
+
+/// Domain separation string for file keys of `cp --encrypt-to` (see
+/// `security::at_rest`), kept apart from session keys.
+pub(crate) const FILE_KDF_CONTEXT: &str = "flux v1 xchacha20poly1305 file key";
+
+/// Domain separation string for session keys bound to a pre-shared key
+/// (`--psk-file`, see `security::psk`), kept apart from code-phrase sessions.
+#[cfg(feature = "net")]
+pub(crate) const PSK_KDF_CONTEXT: &str = "flux v1 xchacha20poly1305 psk session key";
 
 /// Persistent device identity key pair for TOFU authentication.
 ///
@@ -256,6 +272,7 @@ pub struct EncryptedChannel {
 impl EncryptedChannel {
     /// Create an ephemeral key pair for key exchange.
     /// Returns `(secret, public_key)` -- send `public_key` to the peer.
+    #[cfg(feature = "net")]
     pub fn initiate() -> (EphemeralSecret, PublicKey) {
         let secret = EphemeralSecret::random_from_rng(OsRng);
         let public = PublicKey::from(&secret);
@@ -269,6 +286,7 @@ impl EncryptedChannel {
     /// domain separation before use as the XChaCha20-Poly1305 key. This ensures:
     /// - The symmetric key is uniformly distributed (DH output may not be)
     /// - The key is domain-separated and cannot be confused with other uses
+    #[cfg(feature = "net")]
     pub fn complete(secret: EphemeralSecret, peer_public: &PublicKey) -> Self {
         let shared = secret.diffie_hellman(peer_public);
 
@@ -289,6 +307,7 @@ impl EncryptedChannel {
     /// derive the same session key. An attacker who intercepts the DH exchange
     /// but doesn't know the code phrase will derive a different key, causing
     /// Poly1305 authentication to fail on the first encrypted chunk.
+    #[cfg(feature = "net")]
     pub fn complete_with_code(
         secret: EphemeralSecret,
         peer_public: &PublicKey,
@@ -313,8 +332,108 @@ impl EncryptedChannel {
         Self { cipher }
     }
 
+    /// Complete the key exchange bound to a pre-shared key (`--psk-file`).
+    ///
+    /// Like `complete_with_code()`, with the key file's contents in place of
+    /// the code phrase and a KDF context of its own. Only a peer holding the
+    /// same key derives the same session key.
+    #[cfg(feature = "net")]
+    pub fn complete_with_psk(
+        secret: EphemeralSecret,
+        peer_public: &PublicKey,
+        psk: &[u8],
+    ) -> Self {
+        let shared = secret.diffie_hellman(peer_public);
+
+        let mut kdf_input = Vec::with_capacity(32 + psk.len());
+        kdf_input.extend_from_slice(shared.as_bytes());
+        kdf_input.extend_from_slice(psk);
+
+        let mut derived_key = blake3::derive_key(PSK_KDF_CONTEXT, &kdf_input);
+        let cipher = XChaCha20Poly1305::new((&derived_key).into());
+
+        derived_key.zeroize();
+        kdf_input.zeroize();
+
+        Self { cipher }
+    }
+
+    /// Build a channel from a static secret and the peer's public key.
+    ///
+    /// Derives the same key as `complete()` (or `complete_with_code()` when a
+    /// code phrase is given), but from fixed key material. Used to produce the
+    /// deterministic sample session in the protocol conformance vectors; live
+    /// transfers always use ephemeral secrets.
+    #[cfg(feature = "net")]
+    pub fn from_static(
+        secret: &StaticSecret,
+        peer_public: &PublicKey,
+        code_phrase: Option<&str>,
+    ) -> Self {
+        let shared = secret.diffie_hellman(peer_public);
+
+        let mut kdf_input = Vec::with_capacity(32 + code_phrase.map_or(0, str::len));
+        kdf_input.extend_from_slice(shared.as_bytes());
+        if let Some(code) = code_phrase {
+            kdf_input.extend_from_slice(code.as_bytes());
+        }
+
+        let mut derived_key = blake3::derive_key(KDF_CONTEXT, &kdf_input);
+        let cipher = XChaCha20Poly1305::new((&derived_key).into());
+
+        derived_key.zeroize();
+        kdf_input.zeroize();
+
+        Self { cipher }
+    }
+
+    /// Build a channel for a file encrypted at rest.
+    ///
+    /// `secret` is the sender's per-file ephemeral key (encrypting) or the
+    /// recipient's identity key (decrypting); `peer_public` is the other
+    /// side. Both public keys are bound into the derived key, so a file header
+    /// with a swapped ephemeral key fails to decrypt.
+    pub fn for_file(
+        secret: &StaticSecret,
+        peer_public: &PublicKey,
+        ephemeral_public: &PublicKey,
+        recipient: &PublicKey,
+    ) -> Self {
+        let shared = secret.diffie_hellman(peer_public);
+
+        let mut kdf_input = Vec::with_capacity(96);
+        kdf_input.extend_from_slice(shared.as_bytes());
+        kdf_input.extend_from_slice(ephemeral_public.as_bytes());
+        kdf_input.extend_from_slice(recipient.as_bytes());
+
+        let mut derived_key = blake3::derive_key(FILE_KDF_CONTEXT, &kdf_input);
+        let cipher = XChaCha20Poly1305::new((&derived_key).into());
+
+        derived_key.zeroize();
+        kdf_input.zeroize();
+
+        Self { cipher }
+    }
+
+    /// Encrypt plaintext with a caller-supplied nonce.
+    ///
+    /// Reusing a nonce with the same key breaks confidentiality. Only used for
+    /// deterministic test vectors and for files encrypted at rest, where every
+    /// file has its own key and segments are numbered; transfers must use
+    /// `encrypt()`.
+    pub fn encrypt_with_nonce(
+        &self,
+        plaintext: &[u8],
+        nonce: &[u8; 24],
+    ) -> Result<Vec<u8>, FluxError> {
+        self.cipher
+            .encrypt(nonce.into(), plaintext)
+            .map_err(|e| FluxError::EncryptionError(format!("Encrypt failed: {}", e)))
+    }
+
     /// Encrypt plaintext with a random nonce.
     /// Returns `(ciphertext, nonce)`.
+    #[cfg(feature = "net")]
     pub fn encrypt(&self, plaintext: &[u8]) -> Result<(Vec<u8>, [u8; 24]), FluxError> {
         let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
         let ciphertext = self
@@ -330,10 +449,23 @@ impl EncryptedChannel {
             .decrypt(nonce.into(), ciphertext)
             .map_err(|e| FluxError::EncryptionError(format!("Decrypt failed: {}", e)))
     }
+
+    /// Decrypt `buffer` in place using the provided nonce.
+    ///
+    /// On success the buffer holds the plaintext (the 16-byte tag is removed).
+    /// Avoids allocating a second buffer per chunk, which matters on receivers
+    /// running in low-memory mode.
+    #[cfg(feature = "net")]
+    pub fn decrypt_in_place(&self, buffer: &mut Vec<u8>, nonce: &[u8; 24]) -> Result<(), FluxError> {
+        self.cipher
+            .decrypt_in_place(nonce.into(), b"", buffer)
+            .map_err(|e| FluxError::EncryptionError(format!("Decrypt failed: {}", e)))
+    }
 }
 
 /// Convenience: perform key exchange between two parties (for testing).
 /// Takes party A's secret and party B's public key, returns a channel.
+#[cfg(feature = "net")]
 pub fn key_exchange(
     our_secret: EphemeralSecret,
     peer_public: &PublicKey,
@@ -342,6 +474,7 @@ pub fn key_exchange(
 }
 
 /// Convenience: perform code-phrase-bound key exchange (for testing).
+#[cfg(feature = "net")]
 pub fn key_exchange_with_code(
     our_secret: EphemeralSecret,
     peer_public: &PublicKey,
@@ -396,6 +529,7 @@ mod tests {
         assert!(err.contains("Failed to parse identity file"));
     }
 
+    #[cfg(feature = "net")]
     #[test]
     fn encrypt_decrypt_roundtrip() {
         // Simulate two parties
@@ -417,6 +551,7 @@ mod tests {
         assert_eq!(plaintext.as_slice(), decrypted.as_slice());
     }
 
+    #[cfg(feature = "net")]
     #[test]
     fn encrypt_produces_different_ciphertext_each_time() {
         let (secret_a, _public_a) = EncryptedChannel::initiate();
@@ -433,6 +568,7 @@ mod tests {
         assert_ne!(ct1, ct2);
     }
 
+    #[cfg(feature = "net")]
     #[test]
     fn decrypt_with_wrong_key_fails() {
         let (secret_a, _public_a) = EncryptedChannel::initiate();
@@ -451,6 +587,7 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[cfg(feature = "net")]
     #[test]
     fn decrypt_with_wrong_nonce_fails() {
         let (secret_a, public_a) = EncryptedChannel::initiate();
@@ -468,6 +605,22 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[cfg(feature = "net")]
+    #[test]
+    fn decrypt_in_place_matches_decrypt() {
+        let (secret_a, public_a) = EncryptedChannel::initiate();
+        let (secret_b, public_b) = EncryptedChannel::initiate();
+
+        let channel_a = EncryptedChannel::complete(secret_a, &public_b);
+        let channel_b = EncryptedChannel::complete(secret_b, &public_a);
+
+        let plaintext = vec![0x5Au8; 64 * 1024];
+        let (mut buffer, nonce) = channel_a.encrypt(&plaintext).unwrap();
+        channel_b.decrypt_in_place(&mut buffer, &nonce).unwrap();
+        assert_eq!(buffer, plaintext);
+    }
+
+    #[cfg(feature = "net")]
     #[test]
     fn encrypt_empty_data() {
         let (secret_a, public_a) = EncryptedChannel::initiate();
@@ -482,6 +635,7 @@ mod tests {
         assert!(decrypted.is_empty());
     }
 
+    #[cfg(feature = "net")]
     #[test]
     fn encrypt_large_data() {
         let (secret_a, public_a) = EncryptedChannel::initiate();
@@ -497,6 +651,7 @@ mod tests {
         assert_eq!(plaintext, decrypted);
     }
 
+    #[cfg(feature = "net")]
     #[test]
     fn key_exchange_function_works() {
         let (secret_a, public_a) = EncryptedChannel::initiate();
@@ -511,6 +666,7 @@ mod tests {
         assert_eq!(plaintext.as_slice(), decrypted.as_slice());
     }
 
+    #[cfg(feature = "net")]
     #[test]
     fn complete_with_code_roundtrip() {
         let (secret_a, public_a) = EncryptedChannel::initiate();
@@ -526,6 +682,7 @@ mod tests {
         assert_eq!(plaintext.as_slice(), decrypted.as_slice());
     }
 
+    #[cfg(feature = "net")]
     #[test]
     fn complete_with_code_wrong_code_fails_decrypt() {
         let (secret_a, public_a) = EncryptedChannel::initiate();
@@ -540,4 +697,37 @@ mod tests {
         let result = channel_b.decrypt(&ct, &nonce);
         assert!(result.is_err());
     }
+
+    #[cfg(feature = "net")]
+    #[test]
+    fn complete_with_psk_differs_from_the_same_code_phrase() {
+        let (secret_a, public_a) = EncryptedChannel::initiate();
+        let (secret_b, public_b) = EncryptedChannel::initiate();
+        let key = "shared fleet key, not a phrase";
+
+        let channel_a = EncryptedChannel::complete_with_psk(secret_a, &public_b, key.as_bytes());
+        let channel_b = EncryptedChannel::complete_with_code(secret_b, &public_a, key);
+
+        // Same DH output and bytes, different KDF context
+        let (ct, nonce) = channel_a.encrypt(b"psk session").unwrap();
+        assert!(channel_b.decrypt(&ct, &nonce).is_err());
+    }
+
+    #[cfg(feature = "net")]
+    #[test]
+    fn from_static_matches_on_both_sides() {
+        let secret_a = StaticSecret::from([0x11; 32]);
+        let secret_b = StaticSecret::from([0x22; 32]);
+        let public_a = PublicKey::from(&secret_a);
+        let public_b = PublicKey::from(&secret_b);
+        let code = Some("4242-ace-bad-bee-age");
+
+        let channel_a = EncryptedChannel::from_static(&secret_a, &public_b, code);
+        let channel_b = EncryptedChannel::from_static(&secret_b, &public_a, code);
+
+        let nonce = [0x01; 24];
+        let ct = channel_a.encrypt_with_nonce(b"fixed vector", &nonce).unwrap();
+        assert_eq!(ct, channel_a.encrypt_with_nonce(b"fixed vector", &nonce).unwrap());
+        assert_eq!(channel_b.decrypt(&ct, &nonce).unwrap(), b"fixed vector");
+    }
 }
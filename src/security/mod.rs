@@ -1,2 +1,9 @@
+pub mod at_rest;
+#[cfg(feature = "creds")]
+pub mod creds;
 pub mod crypto;
+#[cfg(feature = "net")]
+pub mod psk;
+pub mod receipt;
+#[cfg(feature = "net")]
 pub mod trust;
@@ -0,0 +1,198 @@
+//! Pre-shared keys for unattended devices (`--psk-file`).
+//!
+//! Trust prompts need someone at the receiver. With
+//! `flux receive --psk-file KEY` and `flux send --psk-file KEY`, both ends mix
+//! the contents of the same key file into the session key (on top of the
+//! ephemeral X25519 exchange, as code phrases are), then prove to each other
+//! that they hold it before any file data moves. A peer without the key is
+//! refused; the trust store and the allowlist are not consulted.
+//!
+//! Any file of at least `MIN_PSK_LEN` bytes is a key. Leading and trailing
+//! whitespace is ignored, so text keys (`openssl rand -base64 32 > fleet.key`)
+//! survive editors that add a final newline.
+
+use std::path::Path;
+
+use zeroize::Zeroizing;
+
+use crate::error::FluxError;
+use crate::security::crypto::EncryptedChannel;
+
+/// Shortest key accepted, in bytes.
+pub const MIN_PSK_LEN: usize = 16;
+
+/// Domain separation string for key ids, kept apart from session keys.
+const PSK_ID_CONTEXT: &str = "flux v1 pre-shared key id";
+
+/// Plaintext each side encrypts to prove it derived the same session key.
+/// Distinct per side, so a proof cannot be reflected back to its sender.
+const SENDER_PROOF: &[u8] = b"flux psk proof: sender";
+const RECEIVER_PROOF: &[u8] = b"flux psk proof: receiver";
+
+/// Which end of the transfer a key proof comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PskRole {
+    Sender,
+    Receiver,
+}
+
+impl PskRole {
+    fn plaintext(self) -> &'static [u8] {
+        match self {
+            PskRole::Sender => SENDER_PROOF,
+            PskRole::Receiver => RECEIVER_PROOF,
+        }
+    }
+}
+
+/// The contents of a `--psk-file`, zeroed on drop.
+pub struct PreSharedKey {
+    key: Zeroizing<Vec<u8>>,
+}
+
+impl std::fmt::Debug for PreSharedKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PreSharedKey")
+            .field("id", &self.id())
+            .field("key", &"[REDACTED]")
+            .finish()
+    }
+}
+
+impl PreSharedKey {
+    /// Read a key file.
+    pub fn load(path: &Path) -> Result<Self, FluxError> {
+        let data = Zeroizing::new(std::fs::read(path).map_err(|e| {
+            FluxError::EncryptionError(format!(
+                "Failed to read pre-shared key {}: {}",
+                path.display(),
+                e
+            ))
+        })?);
+        warn_if_shared(path);
+        Self::from_bytes(&data).map_err(|e| match e {
+            FluxError::EncryptionError(msg) => {
+                FluxError::EncryptionError(format!("{}: {}", path.display(), msg))
+            }
+            other => other,
+        })
+    }
+
+    /// A key from raw bytes, with surrounding whitespace removed.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, FluxError> {
+        let key = bytes.trim_ascii();
+        if key.len() < MIN_PSK_LEN {
+            return Err(FluxError::EncryptionError(format!(
+                "pre-shared key is {} bytes; use at least {}",
+                key.len(),
+                MIN_PSK_LEN
+            )));
+        }
+        Ok(Self {
+            key: Zeroizing::new(key.to_vec()),
+        })
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.key
+    }
+
+    /// Short public id of the key (8 hex digits), printed by both ends so an
+    /// operator can tell whether they were given the same file.
+    pub fn id(&self) -> String {
+        let id = blake3::derive_key(PSK_ID_CONTEXT, &self.key);
+        id[..4].iter().map(|b| format!("{:02x}", b)).collect()
+    }
+}
+
+/// Encrypt the proof for `role` with the session `channel`.
+/// Returns `(proof, nonce)`.
+pub fn make_proof(
+    channel: &EncryptedChannel,
+    role: PskRole,
+) -> Result<(Vec<u8>, [u8; 24]), FluxError> {
+    channel.encrypt(role.plaintext())
+}
+
+/// Whether `proof` is `role`'s proof under the session `channel`, i.e. the
+/// peer derived the same session key and so holds the same pre-shared key.
+pub fn check_proof(
+    channel: &EncryptedChannel,
+    role: PskRole,
+    proof: &[u8],
+    nonce: &[u8],
+) -> bool {
+    let Ok(nonce) = <[u8; 24]>::try_from(nonce) else {
+        return false;
+    };
+    channel
+        .decrypt(proof, &nonce)
+        .is_ok_and(|plaintext| plaintext == role.plaintext())
+}
+
+/// Warn when other users can read the key file.
+#[cfg(unix)]
+fn warn_if_shared(path: &Path) {
+    use std::os::unix::fs::PermissionsExt;
+    if let Ok(meta) = std::fs::metadata(path) {
+        if meta.permissions().mode() & 0o077 != 0 {
+            tracing::warn!(
+                "Pre-shared key {} is readable by other users (chmod 600 it)",
+                path.display()
+            );
+        }
+    }
+}
+
+#[cfg(not(unix))]
+fn warn_if_shared(_path: &Path) {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn channels(a: &[u8], b: &[u8]) -> (EncryptedChannel, EncryptedChannel) {
+        let (secret_a, public_a) = EncryptedChannel::initiate();
+        let (secret_b, public_b) = EncryptedChannel::initiate();
+        (
+            EncryptedChannel::complete_with_psk(secret_a, &public_b, a),
+            EncryptedChannel::complete_with_psk(secret_b, &public_a, b),
+        )
+    }
+
+    #[test]
+    fn keys_ignore_surrounding_whitespace() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("fleet.key");
+        std::fs::write(&path, "  c2VjcmV0IGZsZWV0IGtleSBmb3Iga2lvc2tz\n").unwrap();
+        let key = PreSharedKey::load(&path).unwrap();
+        assert_eq!(key.as_bytes(), b"c2VjcmV0IGZsZWV0IGtleSBmb3Iga2lvc2tz");
+
+        let same = PreSharedKey::from_bytes(b"c2VjcmV0IGZsZWV0IGtleSBmb3Iga2lvc2tz").unwrap();
+        assert_eq!(key.id(), same.id());
+        assert_eq!(key.id().len(), 8);
+        assert!(!format!("{:?}", key).contains("c2Vj"));
+    }
+
+    #[test]
+    fn short_keys_are_refused() {
+        let err = PreSharedKey::from_bytes(b"  hunter2  \n").unwrap_err();
+        assert!(err.to_string().contains("7 bytes"), "{}", err);
+        assert!(PreSharedKey::from_bytes(&[0u8; MIN_PSK_LEN]).is_ok());
+    }
+
+    #[test]
+    fn proofs_verify_only_with_the_same_key_and_role() {
+        let key = [7u8; 32];
+        let (sender, receiver) = channels(&key, &key);
+        let (proof, nonce) = make_proof(&sender, PskRole::Sender).unwrap();
+        assert!(check_proof(&receiver, PskRole::Sender, &proof, &nonce));
+        // A proof reflected back is not the receiver's
+        assert!(!check_proof(&receiver, PskRole::Receiver, &proof, &nonce));
+        assert!(!check_proof(&receiver, PskRole::Sender, &proof, &nonce[..12]));
+
+        let (sender, receiver) = channels(&key, &[8u8; 32]);
+        let (proof, nonce) = make_proof(&sender, PskRole::Sender).unwrap();
+        assert!(!check_proof(&receiver, PskRole::Sender, &proof, &nonce));
+    }
+}
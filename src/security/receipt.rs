@@ -0,0 +1,292 @@
+//! Signed transfer receipts (proof of delivery).
+//!
+//! After a verified P2P transfer, `flux send --receipt` asks the receiver to
+//! countersign a receipt describing the file (name, size, BLAKE3 hash) and
+//! the time of delivery. The sender signs first; the receiver checks the
+//! receipt against what it actually received and adds its own signature,
+//! which also covers the sender's. Both sides store the receipt in history
+//! (`flux history --receipts` exports them as JSON).
+//!
+//! Signatures are Ed25519. The signing key is derived from the device's X25519
+//! identity secret with BLAKE3 (domain-separated), so a device keeps the same
+//! receipt key for as long as it keeps its identity.
+
+#[cfg(feature = "net")]
+use base64::engine::general_purpose::STANDARD as BASE64;
+#[cfg(feature = "net")]
+use base64::Engine;
+use chrono::{DateTime, Utc};
+#[cfg(feature = "net")]
+use ed25519_dalek::{Signature, Signer, SigningKey, VerifyingKey};
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "net")]
+use x25519_dalek::StaticSecret;
+#[cfg(feature = "net")]
+use zeroize::Zeroizing;
+
+#[cfg(feature = "net")]
+use crate::error::FluxError;
+#[cfg(feature = "net")]
+use crate::security::crypto::DeviceIdentity;
+
+/// Domain separation string for deriving the receipt signing key.
+#[cfg(feature = "net")]
+const RECEIPT_KEY_CONTEXT: &str = "flux v1 ed25519 receipt signing key";
+
+/// Domain separation prefixes for the two signed payloads.
+#[cfg(feature = "net")]
+const SENDER_DOMAIN: &[u8] = b"flux receipt v1 sender";
+#[cfg(feature = "net")]
+const RECEIVER_DOMAIN: &[u8] = b"flux receipt v1 receiver";
+
+/// Largest difference between the sender's timestamp and the receiver's clock
+/// that the receiver will countersign.
+#[cfg(feature = "net")]
+pub const MAX_CLOCK_SKEW_SECS: i64 = 60 * 60;
+
+/// Ed25519 key used to sign receipts, derived from a `DeviceIdentity`.
+#[cfg(feature = "net")]
+pub struct ReceiptKey {
+    signing: SigningKey,
+}
+
+#[cfg(feature = "net")]
+impl ReceiptKey {
+    /// Derive the receipt signing key from a device identity.
+    pub fn from_identity(identity: &DeviceIdentity) -> Self {
+        Self::from_secret(identity.secret_key())
+    }
+
+    /// Derive the receipt signing key from an X25519 identity secret.
+    pub fn from_secret(secret: &StaticSecret) -> Self {
+        let secret = Zeroizing::new(secret.to_bytes());
+        let seed = Zeroizing::new(blake3::derive_key(RECEIPT_KEY_CONTEXT, secret.as_ref()));
+        Self {
+            signing: SigningKey::from_bytes(&seed),
+        }
+    }
+
+    /// Base64-encoded Ed25519 verifying key.
+    pub fn public_key_base64(&self) -> String {
+        BASE64.encode(self.signing.verifying_key().as_bytes())
+    }
+
+    fn sign(&self, device: &str, payload: &[u8]) -> ReceiptSignature {
+        ReceiptSignature {
+            device: device.to_string(),
+            key: self.public_key_base64(),
+            signature: BASE64.encode(self.signing.sign(payload).to_bytes()),
+        }
+    }
+}
+
+/// One party's signature over a receipt.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReceiptSignature {
+    /// Device name of the signer
+    pub device: String,
+    /// Base64-encoded Ed25519 verifying key
+    pub key: String,
+    /// Base64-encoded Ed25519 signature
+    pub signature: String,
+}
+
+#[cfg(feature = "net")]
+impl ReceiptSignature {
+    fn verify(&self, payload: &[u8]) -> Result<(), FluxError> {
+        let invalid = |what: &str| {
+            FluxError::EncryptionError(format!(
+                "Invalid receipt signature from '{}': {}",
+                self.device, what
+            ))
+        };
+        let key: [u8; 32] = BASE64
+            .decode(&self.key)
+            .map_err(|_| invalid("key is not base64"))?
+            .try_into()
+            .map_err(|_| invalid("key must be 32 bytes"))?;
+        let signature: [u8; 64] = BASE64
+            .decode(&self.signature)
+            .map_err(|_| invalid("signature is not base64"))?
+            .try_into()
+            .map_err(|_| invalid("signature must be 64 bytes"))?;
+        let key = VerifyingKey::from_bytes(&key).map_err(|_| invalid("malformed key"))?;
+        key.verify_strict(payload, &Signature::from_bytes(&signature))
+            .map_err(|_| invalid("signature does not match"))
+    }
+}
+
+/// Receipt for one delivered file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TransferReceipt {
+    /// File name as sent by the sender
+    pub filename: String,
+    /// File size in bytes
+    pub size: u64,
+    /// BLAKE3 hash of the file (hex)
+    pub checksum: String,
+    /// Delivery time chosen by the sender (second precision)
+    pub timestamp: DateTime<Utc>,
+    pub sender: ReceiptSignature,
+    /// Receiver's countersignature; `None` until the receiver signs
+    pub receiver: Option<ReceiptSignature>,
+}
+
+#[cfg(feature = "net")]
+impl TransferReceipt {
+    /// Create a receipt signed by the sender, timestamped now.
+    pub fn new_signed(
+        key: &ReceiptKey,
+        sender: &str,
+        filename: &str,
+        size: u64,
+        checksum: &str,
+    ) -> Self {
+        let now = Utc::now();
+        let timestamp = DateTime::from_timestamp(now.timestamp(), 0).unwrap_or(now);
+        Self::new_signed_at(key, sender, filename, size, checksum, timestamp)
+    }
+
+    /// Create a receipt signed by the sender with an explicit timestamp.
+    pub fn new_signed_at(
+        key: &ReceiptKey,
+        sender: &str,
+        filename: &str,
+        size: u64,
+        checksum: &str,
+        timestamp: DateTime<Utc>,
+    ) -> Self {
+        let mut receipt = Self {
+            filename: filename.to_string(),
+            size,
+            checksum: checksum.to_string(),
+            timestamp,
+            sender: ReceiptSignature {
+                device: sender.to_string(),
+                key: key.public_key_base64(),
+                signature: String::new(),
+            },
+            receiver: None,
+        };
+        receipt.sender = key.sign(sender, &receipt.sender_payload());
+        receipt
+    }
+
+    /// Add the receiver's countersignature.
+    pub fn countersign(&mut self, key: &ReceiptKey, receiver: &str) {
+        let unsigned = ReceiptSignature {
+            device: receiver.to_string(),
+            key: key.public_key_base64(),
+            signature: String::new(),
+        };
+        let payload = self.receiver_payload(&unsigned);
+        self.receiver = Some(key.sign(receiver, &payload));
+    }
+
+    /// Check the sender's signature.
+    pub fn verify_sender(&self) -> Result<(), FluxError> {
+        self.sender.verify(&self.sender_payload())
+    }
+
+    /// Check both signatures; fails if the receipt is not countersigned.
+    pub fn verify(&self) -> Result<(), FluxError> {
+        self.verify_sender()?;
+        let receiver = self.receiver.as_ref().ok_or_else(|| {
+            FluxError::EncryptionError("Receipt has no receiver countersignature".into())
+        })?;
+        receiver.verify(&self.receiver_payload(receiver))
+    }
+
+    /// Bytes signed by the sender.
+    fn sender_payload(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        push_field(&mut buf, SENDER_DOMAIN);
+        push_field(&mut buf, self.filename.as_bytes());
+        push_field(&mut buf, &self.size.to_be_bytes());
+        push_field(&mut buf, self.checksum.as_bytes());
+        push_field(&mut buf, &self.timestamp.timestamp().to_be_bytes());
+        push_field(&mut buf, self.sender.device.as_bytes());
+        push_field(&mut buf, self.sender.key.as_bytes());
+        buf
+    }
+
+    /// Bytes signed by the receiver: the sender's payload and signature plus
+    /// the receiver's identity.
+    fn receiver_payload(&self, receiver: &ReceiptSignature) -> Vec<u8> {
+        let mut buf = Vec::new();
+        push_field(&mut buf, RECEIVER_DOMAIN);
+        push_field(&mut buf, &self.sender_payload());
+        push_field(&mut buf, self.sender.signature.as_bytes());
+        push_field(&mut buf, receiver.device.as_bytes());
+        push_field(&mut buf, receiver.key.as_bytes());
+        buf
+    }
+}
+
+/// Append a length-prefixed field so payloads are unambiguous.
+#[cfg(feature = "net")]
+fn push_field(buf: &mut Vec<u8>, field: &[u8]) {
+    buf.extend_from_slice(&(field.len() as u64).to_be_bytes());
+    buf.extend_from_slice(field);
+}
+
+#[cfg(all(test, feature = "net"))]
+mod tests {
+    use super::*;
+
+    fn signed_pair() -> (ReceiptKey, ReceiptKey, TransferReceipt) {
+        let sender = ReceiptKey::from_identity(&DeviceIdentity::generate());
+        let receiver = ReceiptKey::from_identity(&DeviceIdentity::generate());
+        let mut receipt =
+            TransferReceipt::new_signed(&sender, "laptop", "report.pdf", 1024, "abcd");
+        receipt.countersign(&receiver, "nas");
+        (sender, receiver, receipt)
+    }
+
+    #[test]
+    fn countersigned_receipt_verifies() {
+        let (_, _, receipt) = signed_pair();
+        receipt.verify().unwrap();
+        assert_eq!(receipt.receiver.as_ref().unwrap().device, "nas");
+    }
+
+    #[test]
+    fn receipt_key_is_stable_per_identity() {
+        let identity = DeviceIdentity::generate();
+        assert_eq!(
+            ReceiptKey::from_identity(&identity).public_key_base64(),
+            ReceiptKey::from_identity(&identity).public_key_base64()
+        );
+    }
+
+    #[test]
+    fn unsigned_receipt_fails_full_verification() {
+        let key = ReceiptKey::from_identity(&DeviceIdentity::generate());
+        let receipt = TransferReceipt::new_signed(&key, "laptop", "a.bin", 1, "00");
+        receipt.verify_sender().unwrap();
+        assert!(receipt.verify().is_err());
+    }
+
+    #[test]
+    fn tampering_breaks_signatures() {
+        let (_, _, receipt) = signed_pair();
+
+        let mut bigger = receipt.clone();
+        bigger.size += 1;
+        assert!(bigger.verify_sender().is_err());
+
+        let mut renamed = receipt.clone();
+        renamed.receiver.as_mut().unwrap().device = "someone-else".into();
+        assert!(renamed.verify_sender().is_ok());
+        assert!(renamed.verify().is_err());
+    }
+
+    #[test]
+    fn json_roundtrip_keeps_signatures_valid() {
+        let (_, _, receipt) = signed_pair();
+        let json = serde_json::to_string(&receipt).unwrap();
+        let loaded: TransferReceipt = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded, receipt);
+        loaded.verify().unwrap();
+    }
+}
@@ -201,10 +201,52 @@ impl TrustStore {
     }
 }
 
+/// Shortest fingerprint accepted in the `[receive.devices]` allowlist.
+pub const MIN_FINGERPRINT_LEN: usize = 16;
+
+/// Fingerprint of a base64 public key as shown by `flux trust list`.
+pub fn fingerprint(public_key_b64: &str) -> String {
+    if public_key_b64.len() > MIN_FINGERPRINT_LEN {
+        format!("{}...", &public_key_b64[..MIN_FINGERPRINT_LEN])
+    } else {
+        public_key_b64.to_string()
+    }
+}
+
+/// Whether an allowlist fingerprint is usable: the full base64 key or a
+/// prefix of at least `MIN_FINGERPRINT_LEN` characters (a trailing "..." is
+/// ignored, so `flux trust list` output can be pasted as is).
+pub fn is_valid_fingerprint(fingerprint: &str) -> bool {
+    normalize_fingerprint(fingerprint).len() >= MIN_FINGERPRINT_LEN
+}
+
+/// Whether an allowlist fingerprint matches a base64 public key.
+pub fn fingerprint_matches(fingerprint: &str, public_key_b64: &str) -> bool {
+    is_valid_fingerprint(fingerprint)
+        && public_key_b64.starts_with(normalize_fingerprint(fingerprint))
+}
+
+fn normalize_fingerprint(fingerprint: &str) -> &str {
+    fingerprint.trim().trim_end_matches("...")
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn fingerprint_matches_full_key_and_prefixes() {
+        let key = "q83vEjRWeJCrze8SNFZ4kKvN7xI0VniQq83vEjRWeJA=";
+        assert!(fingerprint_matches(key, key));
+        assert!(fingerprint_matches(&fingerprint(key), key));
+        assert!(fingerprint_matches(" q83vEjRWeJCrze8S ", key));
+        assert!(!fingerprint_matches("q83vEjRWeJCrze8T", key));
+        // Too short to identify a key
+        assert!(!fingerprint_matches("q83v", key));
+        assert!(!is_valid_fingerprint("q83v..."));
+        assert!(is_valid_fingerprint("q83vEjRWeJCrze8S..."));
+    }
+
     #[test]
     fn load_empty_store_when_file_missing() {
         let dir = tempfile::tempdir().unwrap();
@@ -0,0 +1,273 @@
+//! `flux service`: run the long-running daemons under the OS service manager.
+//!
+//! - Linux: a systemd unit (`~/.config/systemd/user/` or, with `--system`,
+//!   `/etc/systemd/system/`), enabled and started with `systemctl`
+//! - macOS: a launchd plist (`~/Library/LaunchAgents/` or
+//!   `/Library/LaunchDaemons/`), loaded with `launchctl`
+//! - Windows: a scheduled task that starts the daemon at logon (or at boot
+//!   as SYSTEM), managed with `schtasks`
+//!
+//! The config and data directories in effect at install time are pinned into
+//! the service environment, so the daemon sees the same config.toml, trust
+//! store and queue as the user who installed it.
+
+pub mod units;
+
+use std::path::PathBuf;
+use std::process::Command;
+
+use crate::cli::args::{ServiceInstallArgs, ServiceTargetArgs};
+use crate::error::FluxError;
+
+use self::units::ServiceSpec;
+
+/// A daemon that can be installed as a service.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ServiceKind {
+    /// `flux receive --daemon` (needs the `net` feature)
+    Receiver,
+    /// `flux daemon` (drains the transfer queue)
+    Queue,
+}
+
+impl ServiceKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            ServiceKind::Receiver => "receiver",
+            ServiceKind::Queue => "queue",
+        }
+    }
+
+    fn description(self) -> &'static str {
+        match self {
+            ServiceKind::Receiver => "Flux receiver",
+            ServiceKind::Queue => "Flux queue daemon",
+        }
+    }
+
+    /// Arguments that start the daemon.
+    fn daemon_args(self) -> Vec<String> {
+        let args: &[&str] = match self {
+            ServiceKind::Receiver => &["receive", "--daemon"],
+            ServiceKind::Queue => &["daemon"],
+        };
+        args.iter().map(|a| a.to_string()).collect()
+    }
+
+    /// Whether SIGHUP reloads the daemon's settings (see `net::receiver`).
+    fn reloads_on_sighup(self) -> bool {
+        match self {
+            ServiceKind::Receiver => true,
+            ServiceKind::Queue => false,
+        }
+    }
+
+    fn systemd_name(self) -> String {
+        format!("flux-{}.service", self.name())
+    }
+
+    fn launchd_label(self) -> &'static str {
+        match self {
+            ServiceKind::Receiver => "dev.flux.receiver",
+            ServiceKind::Queue => "dev.flux.queue",
+        }
+    }
+
+    fn windows_task_name(self) -> &'static str {
+        match self {
+            ServiceKind::Receiver => "Flux\\Receiver",
+            ServiceKind::Queue => "Flux\\Queue",
+        }
+    }
+}
+
+/// `flux service install`
+pub fn install(args: &ServiceInstallArgs) -> Result<(), FluxError> {
+    let target = &args.target;
+    let spec = build_spec(target, &args.args)?;
+
+    if args.print {
+        if cfg!(windows) {
+            println!("schtasks {}", units::schtasks_create_args(&spec).join(" "));
+        } else if cfg!(target_os = "macos") {
+            print!("{}", units::launchd_plist(&spec));
+        } else {
+            print!("{}", units::systemd_unit(&spec));
+        }
+        return Ok(());
+    }
+
+    if cfg!(windows) {
+        run("schtasks", &units::schtasks_create_args(&spec))?;
+        run("schtasks", &["/Run", "/TN", target.kind.windows_task_name()])?;
+        eprintln!(
+            "Installed scheduled task {} ({})",
+            target.kind.windows_task_name(),
+            if target.system { "at boot" } else { "at logon" }
+        );
+        return Ok(());
+    }
+
+    let path = definition_path(target)?;
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    if cfg!(target_os = "macos") {
+        std::fs::write(&path, units::launchd_plist(&spec))?;
+        std::fs::create_dir_all(&spec.log_dir)?;
+        // Reinstalling replaces a loaded job
+        launchctl(&["unload", &path.display().to_string()]).ok();
+        launchctl(&["load", "-w", &path.display().to_string()])?;
+    } else {
+        std::fs::write(&path, units::systemd_unit(&spec))?;
+        systemctl(target, &["daemon-reload"])?;
+        systemctl(target, &["enable", "--now", &target.kind.systemd_name()])?;
+    }
+    eprintln!("Installed {} service: {}", target.kind.name(), path.display());
+    if cfg!(target_os = "linux") && !target.system {
+        eprintln!(
+            "To keep it running while you are logged out: loginctl enable-linger {}",
+            std::env::var("USER").unwrap_or_else(|_| "$USER".to_string())
+        );
+    }
+    Ok(())
+}
+
+/// `flux service status`
+pub fn status(target: &ServiceTargetArgs) -> Result<(), FluxError> {
+    if cfg!(windows) {
+        let name = target.kind.windows_task_name();
+        return run("schtasks", &["/Query", "/TN", name, "/V", "/FO", "LIST"]).map_err(|_| {
+            FluxError::ServiceError(format!("{} service is not installed", target.kind.name()))
+        });
+    }
+
+    let path = definition_path(target)?;
+    if !path.exists() {
+        return Err(FluxError::ServiceError(format!(
+            "{} service is not installed ({} not found)",
+            target.kind.name(),
+            path.display()
+        )));
+    }
+    println!("Installed: {}", path.display());
+    // Both tools exit non-zero for stopped services; the output says why
+    if cfg!(target_os = "macos") {
+        launchctl(&["list", target.kind.launchd_label()]).ok();
+    } else {
+        systemctl(target, &["status", "--no-pager", &target.kind.systemd_name()]).ok();
+    }
+    Ok(())
+}
+
+/// `flux service uninstall`
+pub fn uninstall(target: &ServiceTargetArgs) -> Result<(), FluxError> {
+    if cfg!(windows) {
+        let name = target.kind.windows_task_name();
+        run("schtasks", &["/End", "/TN", name]).ok();
+        run("schtasks", &["/Delete", "/TN", name, "/F"])?;
+        eprintln!("Removed scheduled task {}", name);
+        return Ok(());
+    }
+
+    let path = definition_path(target)?;
+    if !path.exists() {
+        return Err(FluxError::ServiceError(format!(
+            "{} service is not installed ({} not found)",
+            target.kind.name(),
+            path.display()
+        )));
+    }
+    if cfg!(target_os = "macos") {
+        launchctl(&["unload", "-w", &path.display().to_string()]).ok();
+        std::fs::remove_file(&path)?;
+    } else {
+        systemctl(target, &["disable", "--now", &target.kind.systemd_name()]).ok();
+        std::fs::remove_file(&path)?;
+        systemctl(target, &["daemon-reload"])?;
+    }
+    eprintln!("Removed {} service: {}", target.kind.name(), path.display());
+    Ok(())
+}
+
+/// Service definition for this executable and the current config/data dirs.
+fn build_spec(target: &ServiceTargetArgs, extra: &[String]) -> Result<ServiceSpec, FluxError> {
+    if target.kind == ServiceKind::Receiver && !cfg!(feature = "net") {
+        return Err(FluxError::ServiceError(
+            "This build has no network support, so it cannot run the receiver".into(),
+        ));
+    }
+    let program = std::env::current_exe().map_err(|e| {
+        FluxError::ServiceError(format!("Cannot locate the flux executable: {}", e))
+    })?;
+    let config_dir = crate::config::paths::flux_config_dir()?;
+    let data_dir = crate::config::paths::flux_data_dir()?;
+
+    let mut args = target.kind.daemon_args();
+    args.extend(extra.iter().cloned());
+    let mut env = vec![
+        ("FLUX_CONFIG_DIR".to_string(), config_dir.display().to_string()),
+        ("FLUX_DATA_DIR".to_string(), data_dir.display().to_string()),
+    ];
+    // Keep a `--config PATH` given at install time
+    if let Some(path) = std::env::var_os("FLUX_CONFIG").filter(|p| !p.is_empty()) {
+        env.push(("FLUX_CONFIG".to_string(), PathBuf::from(path).display().to_string()));
+    }
+    Ok(ServiceSpec {
+        kind: target.kind,
+        program,
+        args,
+        env,
+        log_dir: data_dir.join("logs"),
+        system: target.system,
+    })
+}
+
+/// Where the unit file or plist lives.
+fn definition_path(target: &ServiceTargetArgs) -> Result<PathBuf, FluxError> {
+    let kind = target.kind;
+    if cfg!(target_os = "macos") {
+        let file = format!("{}.plist", kind.launchd_label());
+        if target.system {
+            return Ok(PathBuf::from("/Library/LaunchDaemons").join(file));
+        }
+        let home = dirs::home_dir()
+            .ok_or_else(|| FluxError::ServiceError("Cannot determine home directory".into()))?;
+        return Ok(home.join("Library/LaunchAgents").join(file));
+    }
+    if !cfg!(target_os = "linux") {
+        return Err(FluxError::ServiceError(
+            "Services are supported on Linux (systemd), macOS (launchd) and Windows".into(),
+        ));
+    }
+    if target.system {
+        return Ok(PathBuf::from("/etc/systemd/system").join(kind.systemd_name()));
+    }
+    let config = dirs::config_dir()
+        .ok_or_else(|| FluxError::ServiceError("Cannot determine config directory".into()))?;
+    Ok(config.join("systemd/user").join(kind.systemd_name()))
+}
+
+fn systemctl(target: &ServiceTargetArgs, args: &[&str]) -> Result<(), FluxError> {
+    let mut full: Vec<&str> = Vec::with_capacity(args.len() + 1);
+    if !target.system {
+        full.push("--user");
+    }
+    full.extend_from_slice(args);
+    run("systemctl", &full)
+}
+
+fn launchctl(args: &[&str]) -> Result<(), FluxError> {
+    run("launchctl", args)
+}
+
+/// Run a service manager command with inherited output.
+fn run<S: AsRef<std::ffi::OsStr>>(program: &str, args: &[S]) -> Result<(), FluxError> {
+    let status = Command::new(program).args(args).status().map_err(|e| {
+        FluxError::ServiceError(format!("Failed to run {}: {}", program, e))
+    })?;
+    if !status.success() {
+        return Err(FluxError::ServiceError(format!("{} exited with {}", program, status)));
+    }
+    Ok(())
+}
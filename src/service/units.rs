@@ -0,0 +1,247 @@
+//! Service definitions for each service manager.
+//!
+//! Pure rendering of the systemd unit, launchd plist and Windows scheduled
+//! task from a `ServiceSpec`; `service::mod` writes and registers them.
+
+use std::path::PathBuf;
+
+use super::ServiceKind;
+
+/// Everything a service manager needs to run one daemon.
+#[derive(Debug, Clone)]
+pub struct ServiceSpec {
+    pub kind: ServiceKind,
+    /// Absolute path of the flux executable
+    pub program: PathBuf,
+    /// Arguments after the program (e.g. `receive --daemon`)
+    pub args: Vec<String>,
+    /// Environment pinned into the service (config and data dirs)
+    pub env: Vec<(String, String)>,
+    /// Where launchd writes stdout/stderr (systemd uses the journal)
+    pub log_dir: PathBuf,
+    /// System-wide instead of per-user
+    pub system: bool,
+}
+
+impl ServiceSpec {
+    /// Program followed by its arguments.
+    fn command_line(&self) -> Vec<String> {
+        std::iter::once(self.program.display().to_string())
+            .chain(self.args.iter().cloned())
+            .collect()
+    }
+}
+
+/// systemd unit file (`flux-<kind>.service`).
+pub fn systemd_unit(spec: &ServiceSpec) -> String {
+    let exec = spec
+        .command_line()
+        .iter()
+        .map(|arg| systemd_quote(arg))
+        .collect::<Vec<_>>()
+        .join(" ");
+    let mut unit = format!(
+        "[Unit]\n\
+         Description={}\n\
+         After=network-online.target\n\
+         Wants=network-online.target\n\
+         \n\
+         [Service]\n\
+         Type=simple\n\
+         ExecStart={}\n",
+        spec.kind.description(),
+        exec
+    );
+    if spec.kind.reloads_on_sighup() {
+        unit.push_str("ExecReload=/bin/kill -HUP $MAINPID\n");
+    }
+    unit.push_str("Restart=on-failure\nRestartSec=5\n");
+    for (key, value) in &spec.env {
+        unit.push_str(&format!(
+            "Environment={}\n",
+            systemd_quote(&format!("{}={}", key, value))
+        ));
+    }
+    let target = if spec.system {
+        "multi-user.target"
+    } else {
+        "default.target"
+    };
+    unit.push_str(&format!("\n[Install]\nWantedBy={}\n", target));
+    unit
+}
+
+/// Quote one word of a systemd command line or assignment.
+///
+/// `%` starts a specifier and `$` a variable, so both are doubled.
+fn systemd_quote(word: &str) -> String {
+    let escaped = word.replace('%', "%%").replace('$', "$$");
+    if !escaped.is_empty()
+        && !escaped
+            .chars()
+            .any(|c| c.is_whitespace() || matches!(c, '"' | '\'' | '\\' | ';'))
+    {
+        return escaped;
+    }
+    let escaped = escaped.replace('\\', "\\\\").replace('"', "\\\"");
+    format!("\"{}\"", escaped)
+}
+
+/// launchd property list (`<label>.plist`).
+pub fn launchd_plist(spec: &ServiceSpec) -> String {
+    let label = spec.kind.launchd_label();
+    let mut plist = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <!DOCTYPE plist PUBLIC \"-//Apple//DTD PLIST 1.0//EN\" \
+         \"http://www.apple.com/DTDs/PropertyList-1.0.dtd\">\n\
+         <plist version=\"1.0\">\n\
+         <dict>\n",
+    );
+    plist.push_str(&format!(
+        "  <key>Label</key>\n  <string>{}</string>\n",
+        xml_escape(label)
+    ));
+    plist.push_str("  <key>ProgramArguments</key>\n  <array>\n");
+    for arg in spec.command_line() {
+        plist.push_str(&format!("    <string>{}</string>\n", xml_escape(&arg)));
+    }
+    plist.push_str("  </array>\n");
+    if !spec.env.is_empty() {
+        plist.push_str("  <key>EnvironmentVariables</key>\n  <dict>\n");
+        for (key, value) in &spec.env {
+            plist.push_str(&format!(
+                "    <key>{}</key>\n    <string>{}</string>\n",
+                xml_escape(key),
+                xml_escape(value)
+            ));
+        }
+        plist.push_str("  </dict>\n");
+    }
+    plist.push_str("  <key>RunAtLoad</key>\n  <true/>\n");
+    plist.push_str("  <key>KeepAlive</key>\n  <true/>\n");
+    let log = spec
+        .log_dir
+        .join(format!("{}.log", spec.kind.name()))
+        .display()
+        .to_string();
+    plist.push_str(&format!(
+        "  <key>StandardOutPath</key>\n  <string>{0}</string>\n\
+         \x20 <key>StandardErrorPath</key>\n  <string>{0}</string>\n",
+        xml_escape(&log)
+    ));
+    plist.push_str("</dict>\n</plist>\n");
+    plist
+}
+
+fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// `schtasks /Create` arguments for a task that starts the daemon at logon
+/// (per-user) or at boot as SYSTEM (`--system`).
+///
+/// Scheduled tasks can't carry environment variables, so the task runs the
+/// daemon through `cmd /c` with the config and data dirs set first.
+pub fn schtasks_create_args(spec: &ServiceSpec) -> Vec<String> {
+    let command = spec
+        .command_line()
+        .iter()
+        .map(|arg| windows_quote(arg))
+        .collect::<Vec<_>>()
+        .join(" ");
+    let action = if spec.env.is_empty() {
+        command
+    } else {
+        let sets: Vec<String> = spec
+            .env
+            .iter()
+            .map(|(key, value)| format!("set \"{}={}\"", key, value))
+            .collect();
+        format!("cmd /c \"{} && {}\"", sets.join(" && "), command)
+    };
+    let mut args: Vec<String> = vec![
+        "/Create".into(),
+        "/F".into(),
+        "/TN".into(),
+        spec.kind.windows_task_name().into(),
+        "/TR".into(),
+        action,
+        "/SC".into(),
+    ];
+    if spec.system {
+        args.extend(["ONSTART".into(), "/RU".into(), "SYSTEM".into()]);
+    } else {
+        args.push("ONLOGON".into());
+    }
+    args.extend(["/RL".into(), "LIMITED".into()]);
+    args
+}
+
+/// Quote one word of a Windows command line.
+fn windows_quote(word: &str) -> String {
+    if !word.is_empty() && !word.chars().any(|c| c.is_whitespace() || c == '"') {
+        return word.to_string();
+    }
+    format!("\"{}\"", word.replace('"', "\\\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spec(system: bool) -> ServiceSpec {
+        ServiceSpec {
+            kind: ServiceKind::Queue,
+            program: PathBuf::from("/opt/flux tools/flux"),
+            args: vec!["daemon".to_string(), "--interval".to_string(), "60".to_string()],
+            env: vec![("FLUX_CONFIG_DIR".to_string(), "/home/me/.config/flux".to_string())],
+            log_dir: PathBuf::from("/home/me/.local/share/flux/logs"),
+            system,
+        }
+    }
+
+    #[test]
+    fn systemd_unit_quotes_and_targets() {
+        let unit = systemd_unit(&spec(false));
+        assert!(unit.contains("ExecStart=\"/opt/flux tools/flux\" daemon --interval 60\n"));
+        assert!(unit.contains("Environment=FLUX_CONFIG_DIR=/home/me/.config/flux\n"));
+        assert!(unit.contains("WantedBy=default.target"));
+        // Only the receiver reloads its settings on SIGHUP
+        assert!(!unit.contains("ExecReload"));
+        assert!(systemd_unit(&spec(true)).contains("WantedBy=multi-user.target"));
+    }
+
+    #[test]
+    fn systemd_quote_escapes_specifiers() {
+        assert_eq!(systemd_quote("plain"), "plain");
+        assert_eq!(systemd_quote("50%"), "50%%");
+        assert_eq!(systemd_quote("a \"b\""), "\"a \\\"b\\\"\"");
+        assert_eq!(systemd_quote(""), "\"\"");
+    }
+
+    #[test]
+    fn launchd_plist_lists_arguments_and_env() {
+        let plist = launchd_plist(&spec(false));
+        assert!(plist.contains("<string>dev.flux.queue</string>"));
+        assert!(plist.contains("<string>/opt/flux tools/flux</string>"));
+        assert!(plist.contains("<string>daemon</string>"));
+        assert!(plist.contains("<key>FLUX_CONFIG_DIR</key>"));
+        assert!(plist.contains("/home/me/.local/share/flux/logs/queue.log"));
+        assert!(plist.contains("<key>KeepAlive</key>"));
+    }
+
+    #[test]
+    fn schtasks_runs_at_logon_or_boot() {
+        let args = schtasks_create_args(&spec(false));
+        assert!(args.contains(&"ONLOGON".to_string()));
+        let action = &args[args.iter().position(|a| a == "/TR").unwrap() + 1];
+        assert!(action.starts_with("cmd /c \"set \"FLUX_CONFIG_DIR="));
+        assert!(action.contains("\"/opt/flux tools/flux\" daemon --interval 60"));
+
+        let system = schtasks_create_args(&spec(true));
+        assert!(system.windows(2).any(|w| w == ["/RU", "SYSTEM"]));
+    }
+}
@@ -0,0 +1,251 @@
+//! Embedded SQLite database for flux's own state (`state.db` in the data
+//! directory).
+//!
+//! The transfer queue (`QueueStore`), the history (`HistoryStore`), the
+//! checksum cache (`ChecksumCache`), the chunk index of received files
+//! (`net::chunking`), the addresses of discovered devices
+//! (`discovery::cache`) and the bandwidth usage (`transfer::usage`) keep their rows here, so a long history or a large
+//! cache is read and written a row at a time rather than as one JSON file,
+//! and concurrent processes go through SQLite transactions. Each store
+//! keeps its API. Queue and history rows hold the entry as JSON next
+//! to the columns they are looked up and ordered by, so a new entry field
+//! needs no schema change.
+//!
+//! The schema version is SQLite's `user_version`. `open` applies the
+//! `MIGRATIONS` a database has not seen yet in one transaction; the first
+//! one also imports the `queue.json`, `history.json` and
+//! `checksum_cache.json` of earlier versions, which are then renamed to
+//! `<name>.imported`. A database from a newer flux is refused with
+//! `FluxError::NewerFormat`; a file that is not a database any more is
+//! moved aside to `state.db.corrupt-<timestamp>` and started over.
+
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use rusqlite::{Connection, ErrorCode, Transaction, TransactionBehavior};
+
+use crate::error::FluxError;
+use crate::queue::store;
+
+/// File name of the database in the data directory.
+pub const DB_FILE: &str = "state.db";
+
+/// How long to wait for another process's write to finish.
+const BUSY_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Schema changes in order; `user_version` counts those applied.
+const MIGRATIONS: &[&str] = &[
+    // 1: the queue, history and checksum cache
+    "CREATE TABLE queue (
+        position INTEGER PRIMARY KEY,
+        id INTEGER NOT NULL UNIQUE,
+        entry TEXT NOT NULL
+    );
+    CREATE TABLE history (
+        seq INTEGER PRIMARY KEY,
+        id INTEGER NOT NULL UNIQUE,
+        entry TEXT NOT NULL
+    );
+    CREATE TABLE checksums (
+        path TEXT PRIMARY KEY,
+        size INTEGER NOT NULL,
+        mtime_ns INTEGER NOT NULL,
+        algorithm TEXT NOT NULL,
+        checksum TEXT NOT NULL,
+        used INTEGER NOT NULL
+    );
+    CREATE INDEX checksums_by_use ON checksums (used);",
+    // 2: chunks of received files, for deduplicated sends (`net::chunking`)
+    "CREATE TABLE chunks (
+        peer TEXT NOT NULL,
+        hash BLOB NOT NULL,
+        path TEXT NOT NULL,
+        start INTEGER NOT NULL,
+        len INTEGER NOT NULL,
+        PRIMARY KEY (peer, hash)
+    );",
+    // 3: addresses of discovered devices (`discovery::cache`)
+    "CREATE TABLE devices (
+        name TEXT PRIMARY KEY COLLATE NOCASE,
+        host TEXT NOT NULL,
+        port INTEGER NOT NULL,
+        version TEXT,
+        seen INTEGER NOT NULL
+    );",
+    // 4: bytes moved per day, backend and host (`transfer::usage`)
+    "CREATE TABLE usage (
+        day TEXT NOT NULL,
+        backend TEXT NOT NULL,
+        host TEXT NOT NULL,
+        bytes_out INTEGER NOT NULL,
+        bytes_in INTEGER NOT NULL,
+        transfers INTEGER NOT NULL,
+        PRIMARY KEY (day, backend, host)
+    );",
+];
+
+/// Imports a JSON state file of an earlier version into the database.
+type Import = fn(&Transaction, &Path) -> Result<(), FluxError>;
+
+/// JSON state files imported by the first migration.
+const LEGACY_FILES: &[(&str, Import)] = &[
+    ("queue.json", crate::queue::state::import),
+    ("history.json", crate::queue::history::import),
+    ("checksum_cache.json", crate::transfer::checksum_cache::import),
+];
+
+/// Open the state database in `data_dir`, creating or upgrading it.
+pub fn open(data_dir: &Path) -> Result<Connection, FluxError> {
+    let path = data_dir.join(DB_FILE);
+    let mut conn = match connect(&path) {
+        Err(e) if is_damaged(&e) => {
+            store::move_aside(&path, &e.to_string())?;
+            for suffix in ["-wal", "-shm"] {
+                let _ = std::fs::remove_file(sidecar(&path, suffix));
+            }
+            connect(&path)?
+        }
+        result => result?,
+    };
+    migrate(&mut conn, &path, data_dir)?;
+    Ok(conn)
+}
+
+/// Open the database file and set up the connection.
+fn connect(path: &Path) -> rusqlite::Result<Connection> {
+    let conn = Connection::open(path)?;
+    conn.busy_timeout(BUSY_TIMEOUT)?;
+    // Readers and a writer do not block each other; this also reads the
+    // header, so a damaged file shows here
+    conn.pragma_update_and_check(None, "journal_mode", "WAL", |row| row.get::<_, String>(0))?;
+    Ok(conn)
+}
+
+/// Whether `e` means the file is not (or no longer) a usable database.
+fn is_damaged(e: &rusqlite::Error) -> bool {
+    matches!(
+        e.sqlite_error_code(),
+        Some(ErrorCode::NotADatabase | ErrorCode::DatabaseCorrupt)
+    )
+}
+
+/// `state.db-wal` and the like.
+fn sidecar(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_os_string();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+/// Apply the migrations the database at `path` has not seen yet.
+fn migrate(conn: &mut Connection, path: &Path, data_dir: &Path) -> Result<(), FluxError> {
+    let supported = MIGRATIONS.len() as u32;
+    if user_version(conn)? == supported {
+        return Ok(());
+    }
+    // Another process may be migrating too; the write lock settles it
+    let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+    let found = user_version(&tx)?;
+    if found > supported {
+        return Err(FluxError::NewerFormat {
+            path: path.to_path_buf(),
+            found,
+            supported,
+        });
+    }
+    for sql in &MIGRATIONS[found as usize..] {
+        tx.execute_batch(sql)?;
+    }
+    let mut imported = Vec::new();
+    if found == 0 {
+        for (name, import) in LEGACY_FILES {
+            let file = data_dir.join(name);
+            if file.exists() {
+                import(&tx, &file)?;
+                imported.push(file);
+            }
+        }
+    }
+    tx.pragma_update(None, "user_version", supported)?;
+    tx.commit()?;
+
+    for file in imported {
+        let done = sidecar(&file, ".imported");
+        if let Err(e) = std::fs::rename(&file, &done) {
+            tracing::warn!("Imported {} but could not rename it: {}", file.display(), e);
+        }
+    }
+    Ok(())
+}
+
+fn user_version(conn: &Connection) -> rusqlite::Result<u32> {
+    conn.pragma_query_value(None, "user_version", |row| row.get(0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn open_creates_the_schema_once() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let conn = open(dir.path()).unwrap();
+        assert_eq!(user_version(&conn).unwrap(), MIGRATIONS.len() as u32);
+        drop(conn);
+        // Opening again is a no-op
+        let conn = open(dir.path()).unwrap();
+        let tables: i64 = conn
+            .query_row("SELECT COUNT(*) FROM sqlite_master WHERE type = 'table'", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(tables, 6);
+    }
+
+    #[test]
+    fn databases_from_a_newer_flux_are_refused() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let conn = open(dir.path()).unwrap();
+        conn.pragma_update(None, "user_version", 99).unwrap();
+        drop(conn);
+        assert!(matches!(
+            open(dir.path()),
+            Err(FluxError::NewerFormat { found: 99, .. })
+        ));
+        assert!(dir.path().join(DB_FILE).exists());
+    }
+
+    #[test]
+    fn damaged_databases_are_moved_aside() {
+        let dir = tempfile::TempDir::new().unwrap();
+        std::fs::write(dir.path().join(DB_FILE), "not a database, just some text").unwrap();
+        let conn = open(dir.path()).unwrap();
+        assert_eq!(user_version(&conn).unwrap(), MIGRATIONS.len() as u32);
+        let moved = std::fs::read_dir(dir.path())
+            .unwrap()
+            .filter(|e| {
+                let name = e.as_ref().unwrap().file_name();
+                name.to_string_lossy().starts_with("state.db.corrupt-")
+            })
+            .count();
+        assert_eq!(moved, 1);
+    }
+
+    #[test]
+    fn legacy_json_files_are_imported_once() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let legacy = r#"[{
+            "source": "a", "dest": "b", "bytes": 1, "files": 1,
+            "duration_secs": 0.1, "timestamp": "2024-01-01T00:00:00Z",
+            "status": "completed", "error": null
+        }]"#;
+        std::fs::write(dir.path().join("history.json"), legacy).unwrap();
+
+        let conn = open(dir.path()).unwrap();
+        let rows: i64 = conn
+            .query_row("SELECT COUNT(*) FROM history", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(rows, 1);
+        assert!(!dir.path().join("history.json").exists());
+        assert!(dir.path().join("history.json.imported").exists());
+    }
+}
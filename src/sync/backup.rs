@@ -0,0 +1,239 @@
+//! Recoverable deletes for `flux sync` (`--backup-dir`, `--trash`).
+//!
+//! Instead of removing orphans (`--delete`) and overwriting changed files,
+//! sync moves the old destination files into a timestamped folder, keeping
+//! their path relative to the destination root:
+//!
+//! ```text
+//! <backup root>/2026-03-01_143000/photos/img.jpg
+//! ```
+//!
+//! `--trash` uses `.flux-trash` inside the destination as the backup root,
+//! so moves are renames on the same filesystem. `--backup-keep N` prunes all
+//! but the N most recent folders after each run.
+
+use std::path::{Path, PathBuf};
+
+use crate::backend::readonly;
+use crate::error::FluxError;
+
+/// Backup root used by `--trash`, relative to the destination root.
+/// Never considered an orphan by the sync plan.
+pub const TRASH_DIR: &str = ".flux-trash";
+
+/// Name format of the per-run folders (local time; sorts chronologically).
+const RUN_DIR_FORMAT: &str = "%Y-%m-%d_%H%M%S";
+
+/// Where replaced and deleted files go.
+#[derive(Debug, Clone, PartialEq)]
+pub enum BackupLocation {
+    /// `--backup-dir DIR`
+    Dir(PathBuf),
+    /// `--trash`: `.flux-trash` in the destination
+    Trash,
+}
+
+/// Backup settings for a sync, shared by every run of watch/schedule mode.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BackupPolicy {
+    pub location: BackupLocation,
+    /// Number of run folders to keep (`None` keeps all)
+    pub keep: Option<usize>,
+}
+
+impl BackupPolicy {
+    /// Backup root for a destination.
+    pub fn root(&self, dest: &Path) -> PathBuf {
+        match self.location {
+            BackupLocation::Dir(ref dir) => dir.clone(),
+            BackupLocation::Trash => dest.join(TRASH_DIR),
+        }
+    }
+
+    /// Start backing up files of one sync run into `dest`.
+    pub fn for_dest(&self, dest: &Path) -> BackupRun {
+        BackupRun {
+            dest: dest.to_path_buf(),
+            root: self.root(dest),
+            keep: self.keep,
+            run_dir: None,
+            stashed: 0,
+        }
+    }
+}
+
+/// Backups of one sync run. The run folder is created on first use.
+#[derive(Debug)]
+pub struct BackupRun {
+    dest: PathBuf,
+    root: PathBuf,
+    keep: Option<usize>,
+    run_dir: Option<PathBuf>,
+    stashed: u64,
+}
+
+impl BackupRun {
+    /// Move `path` (a file under the destination) into the run folder.
+    /// Does nothing if `path` does not exist.
+    pub fn stash(&mut self, path: &Path) -> Result<(), FluxError> {
+        if !path.exists() {
+            return Ok(());
+        }
+        readonly::check(path, "move")?;
+        readonly::check(&self.root, "write into")?;
+        let relative = path.strip_prefix(&self.dest)?;
+        let target = self.run_dir()?.join(relative);
+        if let Some(parent) = target.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        move_file(path, &target)?;
+        self.stashed += 1;
+        Ok(())
+    }
+
+    /// Number of files moved into the backup.
+    pub fn stashed(&self) -> u64 {
+        self.stashed
+    }
+
+    /// The run folder, if anything was backed up.
+    pub fn run_dir_path(&self) -> Option<&Path> {
+        self.run_dir.as_deref()
+    }
+
+    /// Apply `--backup-keep` once the run is done. Returns the number of
+    /// folders removed.
+    pub fn finish(self) -> Result<usize, FluxError> {
+        match self.keep {
+            Some(keep) => prune(&self.root, keep),
+            None => Ok(0),
+        }
+    }
+
+    fn run_dir(&mut self) -> Result<&Path, FluxError> {
+        if self.run_dir.is_none() {
+            let stamp = chrono::Local::now().format(RUN_DIR_FORMAT).to_string();
+            // Two runs within the same second get a numeric suffix
+            let mut dir = self.root.join(&stamp);
+            let mut n = 1;
+            while dir.exists() {
+                dir = self.root.join(format!("{}-{}", stamp, n));
+                n += 1;
+            }
+            std::fs::create_dir_all(&dir)?;
+            self.run_dir = Some(dir);
+        }
+        Ok(self.run_dir.as_deref().expect("run_dir was just set"))
+    }
+}
+
+/// Rename `from` to `to`, copying across filesystems if needed.
+fn move_file(from: &Path, to: &Path) -> Result<(), FluxError> {
+    if let Err(rename_err) = std::fs::rename(from, to) {
+        std::fs::copy(from, to).map_err(|_| FluxError::Io { source: rename_err })?;
+        std::fs::remove_file(from)?;
+    }
+    Ok(())
+}
+
+/// Whether a folder name is a backup run folder (a timestamp, optionally
+/// followed by a "-N" collision suffix).
+fn is_run_dir(name: &str) -> bool {
+    let Some(stamp) = name.get(..17) else {
+        return false;
+    };
+    let suffix = &name[17..];
+    chrono::NaiveDateTime::parse_from_str(stamp, RUN_DIR_FORMAT).is_ok()
+        && (suffix.is_empty()
+            || suffix
+                .strip_prefix('-')
+                .is_some_and(|n| !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit())))
+}
+
+/// Remove all but the `keep` most recent run folders in `root`.
+///
+/// Only folders named like run folders are touched, so pointing
+/// `--backup-dir` at a directory with other content is safe.
+pub fn prune(root: &Path, keep: usize) -> Result<usize, FluxError> {
+    let entries = match std::fs::read_dir(root) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+        Err(e) => return Err(e.into()),
+    };
+    let mut runs: Vec<(String, PathBuf)> = entries
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_ok_and(|t| t.is_dir()))
+        .filter_map(|e| {
+            let name = e.file_name().to_str()?.to_string();
+            is_run_dir(&name).then(|| (name, e.path()))
+        })
+        .collect();
+    if runs.len() <= keep {
+        return Ok(0);
+    }
+    runs.sort();
+    let excess = runs.len() - keep;
+    for (_, path) in runs.into_iter().take(excess) {
+        readonly::check(&path, "delete")?;
+        std::fs::remove_dir_all(&path)?;
+    }
+    Ok(excess)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stash_keeps_relative_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let dest = dir.path().join("dst");
+        std::fs::create_dir_all(dest.join("photos")).unwrap();
+        std::fs::write(dest.join("photos/img.jpg"), "old").unwrap();
+
+        let policy = BackupPolicy {
+            location: BackupLocation::Trash,
+            keep: None,
+        };
+        let mut run = policy.for_dest(&dest);
+        run.stash(&dest.join("photos/img.jpg")).unwrap();
+        run.stash(&dest.join("missing.txt")).unwrap();
+
+        assert_eq!(run.stashed(), 1);
+        assert!(!dest.join("photos/img.jpg").exists());
+        let run_dir = run.run_dir_path().unwrap();
+        assert!(run_dir.starts_with(dest.join(TRASH_DIR)));
+        assert_eq!(
+            std::fs::read_to_string(run_dir.join("photos/img.jpg")).unwrap(),
+            "old"
+        );
+    }
+
+    #[test]
+    fn prune_keeps_most_recent_runs() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in [
+            "2026-01-01_100000",
+            "2026-01-02_100000",
+            "2026-01-02_100000-1",
+            "2026-01-03_100000",
+            "notes",
+        ] {
+            std::fs::create_dir_all(dir.path().join(name)).unwrap();
+        }
+
+        assert_eq!(prune(dir.path(), 2).unwrap(), 2);
+        assert!(!dir.path().join("2026-01-01_100000").exists());
+        assert!(!dir.path().join("2026-01-02_100000").exists());
+        assert!(dir.path().join("2026-01-02_100000-1").exists());
+        assert!(dir.path().join("2026-01-03_100000").exists());
+        // Unrelated folders are never pruned
+        assert!(dir.path().join("notes").exists());
+    }
+
+    #[test]
+    fn prune_missing_root_is_a_no_op() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(prune(&dir.path().join("none"), 1).unwrap(), 0);
+    }
+}
@@ -0,0 +1,420 @@
+//! Directory comparison report (`flux diff A B`).
+//!
+//! Runs the sync planner from A to B with orphan detection on and turns the
+//! plan into a report instead of executing it: files that would be copied are
+//! only in A, orphans are only in B, and updates differ. Nothing is written to
+//! either tree, so it is safe to run before a `sync --delete`.
+//!
+//! Unlike `sync`, a file that is newer in B counts as different, and the
+//! report says why: size, modification time or (with `--checksum`) content.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use bytesize::ByteSize;
+use serde::Serialize;
+
+use crate::cli::args::DiffArgs;
+use crate::config::aliases::{resolve_alias, AliasStore};
+use crate::error::FluxError;
+use crate::transfer::checksum::ChecksumAlgorithm;
+use crate::transfer::filter::TransferFilter;
+
+use super::engine::{compute_sync_plan, CompareMode, FileComparer};
+use super::plan::{SyncAction, SyncPlan};
+
+/// Why a file present on both sides differs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DiffReason {
+    /// Different sizes.
+    Size,
+    /// Same size, A modified later.
+    NewerInA,
+    /// Same size, B modified later.
+    NewerInB,
+    /// Same size, different checksums (`--checksum`).
+    Content,
+}
+
+impl std::fmt::Display for DiffReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DiffReason::Size => write!(f, "size"),
+            DiffReason::NewerInA => write!(f, "newer in A"),
+            DiffReason::NewerInB => write!(f, "newer in B"),
+            DiffReason::Content => write!(f, "content"),
+        }
+    }
+}
+
+/// Where a file was found, and how it differs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum DiffStatus {
+    OnlyInA,
+    OnlyInB,
+    Differs { reason: DiffReason },
+}
+
+/// One file that is not identical in both trees.
+#[derive(Debug, Clone, Serialize)]
+pub struct DiffEntry {
+    /// Path relative to A and B.
+    pub path: PathBuf,
+    #[serde(flatten)]
+    pub status: DiffStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub size_a: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub size_b: Option<u64>,
+}
+
+/// Differences between two directory trees, sorted by path.
+#[derive(Debug, Default, Serialize)]
+pub struct DiffReport {
+    pub entries: Vec<DiffEntry>,
+    /// Files present and equal on both sides.
+    pub identical: u64,
+}
+
+impl DiffReport {
+    /// Turn a plan from A to B (computed with orphan detection) into a report.
+    pub fn from_plan(plan: &SyncPlan, a: &Path, b: &Path, checksum: bool) -> Self {
+        let relative = |path: &Path, root: &Path| {
+            path.strip_prefix(root).unwrap_or(path).to_path_buf()
+        };
+        let mut report = DiffReport::default();
+        for action in &plan.actions {
+            let entry = match action {
+                SyncAction::CopyNew { src, size, .. } => DiffEntry {
+                    path: relative(src, a),
+                    status: DiffStatus::OnlyInA,
+                    size_a: Some(*size),
+                    size_b: None,
+                },
+                SyncAction::DeleteOrphan { path, size } => DiffEntry {
+                    path: relative(path, b),
+                    status: DiffStatus::OnlyInB,
+                    size_a: None,
+                    size_b: Some(*size),
+                },
+                SyncAction::UpdateChanged {
+                    src,
+                    dest,
+                    src_size,
+                    dest_size,
+                } => DiffEntry {
+                    path: relative(src, a),
+                    status: DiffStatus::Differs {
+                        reason: reason(src, dest, *src_size, *dest_size, checksum),
+                    },
+                    size_a: Some(*src_size),
+                    size_b: Some(*dest_size),
+                },
+                SyncAction::Link { .. } | SyncAction::Skip { .. } => {
+                    report.identical += 1;
+                    continue;
+                }
+            };
+            report.entries.push(entry);
+        }
+        report.entries.sort_by(|x, y| x.path.cmp(&y.path));
+        report
+    }
+
+    /// Number of files only in A, only in B, and differing.
+    pub fn counts(&self) -> (usize, usize, usize) {
+        let count = |wanted: fn(&DiffStatus) -> bool| {
+            self.entries.iter().filter(|e| wanted(&e.status)).count()
+        };
+        (
+            count(|s| *s == DiffStatus::OnlyInA),
+            count(|s| *s == DiffStatus::OnlyInB),
+            count(|s| matches!(s, DiffStatus::Differs { .. })),
+        )
+    }
+
+    /// One line per difference: marker, path, detail.
+    pub fn render_flat(&self) -> Vec<String> {
+        self.entries
+            .iter()
+            .map(|e| format!("  {:<8} {}  ({})", marker(e), e.path.display(), detail(e)))
+            .collect()
+    }
+
+    /// The differences as a tree of the directories that contain them.
+    pub fn render_tree(&self, a: &str, b: &str) -> Vec<String> {
+        let mut root = Dir::default();
+        for entry in &self.entries {
+            let mut dir = &mut root;
+            let components: Vec<_> = entry.path.iter().collect();
+            let Some((name, parents)) = components.split_last() else {
+                continue;
+            };
+            for parent in parents {
+                dir = dir.dirs.entry(parent.to_string_lossy().into_owned()).or_default();
+            }
+            dir.files.push((name.to_string_lossy().into_owned(), entry));
+        }
+        let mut lines = vec![format!("{} vs {}", a, b)];
+        render_dir(&root, "", &mut lines);
+        lines
+    }
+
+    /// Summary line, e.g. `2 only in A, 1 only in B, 3 differ, 40 identical`.
+    pub fn summary(&self) -> String {
+        let (only_a, only_b, differ) = self.counts();
+        format!(
+            "{} only in A, {} only in B, {} differ, {} identical",
+            only_a, only_b, differ, self.identical
+        )
+    }
+}
+
+/// Classify an update from the planner.
+fn reason(a: &Path, b: &Path, size_a: u64, size_b: u64, checksum: bool) -> DiffReason {
+    if size_a != size_b {
+        return DiffReason::Size;
+    }
+    if checksum {
+        return DiffReason::Content;
+    }
+    let modified = |p: &Path| std::fs::metadata(p).and_then(|m| m.modified()).ok();
+    match (modified(a), modified(b)) {
+        (Some(ma), Some(mb)) if mb > ma => DiffReason::NewerInB,
+        _ => DiffReason::NewerInA,
+    }
+}
+
+fn marker(entry: &DiffEntry) -> &'static str {
+    match entry.status {
+        DiffStatus::OnlyInA => "A ONLY",
+        DiffStatus::OnlyInB => "B ONLY",
+        DiffStatus::Differs { .. } => "DIFFERS",
+    }
+}
+
+/// The size of a one-sided file, or why a file differs.
+fn detail(entry: &DiffEntry) -> String {
+    match (entry.status, entry.size_a, entry.size_b) {
+        (DiffStatus::Differs { reason: DiffReason::Size }, Some(a), Some(b)) => {
+            format!("size {} vs {}", ByteSize(a), ByteSize(b))
+        }
+        (DiffStatus::Differs { reason }, _, _) => reason.to_string(),
+        (_, Some(size), _) | (_, _, Some(size)) => ByteSize(size).to_string(),
+        _ => String::new(),
+    }
+}
+
+/// Annotation of a file in the tree, where there is no marker column.
+fn tree_detail(entry: &DiffEntry) -> String {
+    match entry.status {
+        DiffStatus::OnlyInA => format!("only in A, {}", detail(entry)),
+        DiffStatus::OnlyInB => format!("only in B, {}", detail(entry)),
+        DiffStatus::Differs { .. } => detail(entry),
+    }
+}
+
+/// A directory in the rendered tree.
+#[derive(Default)]
+struct Dir<'a> {
+    dirs: BTreeMap<String, Dir<'a>>,
+    files: Vec<(String, &'a DiffEntry)>,
+}
+
+fn render_dir(dir: &Dir<'_>, prefix: &str, lines: &mut Vec<String>) {
+    let count = dir.dirs.len() + dir.files.len();
+    let children = dir
+        .dirs
+        .iter()
+        .map(|(name, sub)| (format!("{}/", name), Some(sub), None))
+        .chain(dir.files.iter().map(|(name, e)| (name.clone(), None, Some(*e))));
+    for (i, (name, sub, entry)) in children.enumerate() {
+        let last = i + 1 == count;
+        let connector = if last { "└── " } else { "├── " };
+        let annotation = entry.map(|e| format!("  [{}]", tree_detail(e))).unwrap_or_default();
+        lines.push(format!("{}{}{}{}", prefix, connector, name, annotation));
+        if let Some(sub) = sub {
+            let child_prefix = format!("{}{}", prefix, if last { "    " } else { "│   " });
+            render_dir(sub, &child_prefix, lines);
+        }
+    }
+}
+
+/// Execute `flux diff`.
+///
+/// Prints the report (JSON with `json`) and fails with
+/// `FluxError::Differences` when the trees differ, like `flux verify`.
+pub fn execute_diff(args: DiffArgs, quiet: bool, json: bool) -> Result<(), FluxError> {
+    let alias_store = AliasStore::current();
+    let a_resolved = resolve_alias(&args.a, &alias_store);
+    let b_resolved = resolve_alias(&args.b, &alias_store);
+    let (a, b) = (Path::new(&a_resolved), Path::new(&b_resolved));
+    for dir in [a, b] {
+        if !dir.exists() {
+            return Err(FluxError::SourceNotFound {
+                path: dir.to_path_buf(),
+            });
+        }
+        if !dir.is_dir() {
+            return Err(FluxError::SyncError(format!(
+                "'{}' is not a directory",
+                dir.display()
+            )));
+        }
+    }
+
+    let exclude_hidden = crate::config::types::load_config()
+        .map(|c| c.exclude_hidden)
+        .unwrap_or_default();
+    let filter = TransferFilter::new(&args.exclude, &args.include)?
+        .skip_hidden(args.hidden.skip_hidden(exclude_hidden))
+        .skip_system(!args.hidden.include_system);
+    let mode = if args.checksum {
+        CompareMode::Checksum
+    } else {
+        CompareMode::Mtime
+    };
+    let mut compare = FileComparer::new(mode, ChecksumAlgorithm::Xxh3).either_newer(true);
+    let plan = compute_sync_plan(a, b, &filter, &mut compare, true, true)?;
+    let report = DiffReport::from_plan(&plan, a, b, args.checksum);
+
+    if json {
+        println!("{}", serde_json::to_string(&report)?);
+    } else if !quiet {
+        let lines = if args.flat {
+            report.render_flat()
+        } else {
+            report.render_tree(&args.a, &args.b)
+        };
+        if !report.entries.is_empty() {
+            for line in lines {
+                println!("{}", line);
+            }
+            println!();
+        }
+        println!("{}", report.summary());
+    }
+
+    if report.entries.is_empty() {
+        Ok(())
+    } else {
+        Err(FluxError::Differences(format!(
+            "{} difference(s) between {} and {}",
+            report.entries.len(),
+            args.a,
+            args.b
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn create_file(dir: &Path, name: &str, content: &str) {
+        let path = dir.join(name);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, content).unwrap();
+    }
+
+    fn diff(a: &Path, b: &Path) -> DiffReport {
+        let filter = TransferFilter::new(&[], &[]).unwrap();
+        let mut compare = FileComparer::default().either_newer(true);
+        let plan = compute_sync_plan(a, b, &filter, &mut compare, true, true).unwrap();
+        DiffReport::from_plan(&plan, a, b, false)
+    }
+
+    fn sample() -> (TempDir, PathBuf, PathBuf) {
+        let dir = TempDir::new().unwrap();
+        let (a, b) = (dir.path().join("a"), dir.path().join("b"));
+        create_file(&a, "same.txt", "same");
+        create_file(&b, "same.txt", "same");
+        create_file(&a, "docs/new.txt", "only here");
+        create_file(&b, "old/gone.txt", "only there");
+        create_file(&a, "grown.txt", "short");
+        create_file(&b, "grown.txt", "much longer");
+        (dir, a, b)
+    }
+
+    #[test]
+    fn classifies_files_on_each_side() {
+        let (_dir, a, b) = sample();
+        let report = diff(&a, &b);
+
+        let statuses: Vec<_> = report
+            .entries
+            .iter()
+            .map(|e| (e.path.to_string_lossy().replace('\\', "/"), e.status))
+            .collect();
+        assert_eq!(
+            statuses,
+            [
+                ("docs/new.txt".to_string(), DiffStatus::OnlyInA),
+                (
+                    "grown.txt".to_string(),
+                    DiffStatus::Differs {
+                        reason: DiffReason::Size
+                    }
+                ),
+                ("old/gone.txt".to_string(), DiffStatus::OnlyInB),
+            ]
+        );
+        assert_eq!(report.identical, 1);
+        assert_eq!(report.summary(), "1 only in A, 1 only in B, 1 differ, 1 identical");
+    }
+
+    #[test]
+    fn diff_leaves_both_trees_untouched() {
+        let (_dir, a, b) = sample();
+        diff(&a, &b);
+        assert!(!b.join("docs/new.txt").exists());
+        assert!(b.join("old/gone.txt").exists());
+        assert_eq!(std::fs::read_to_string(b.join("grown.txt")).unwrap(), "much longer");
+    }
+
+    #[test]
+    fn newer_file_in_b_differs() {
+        let dir = TempDir::new().unwrap();
+        let (a, b) = (dir.path().join("a"), dir.path().join("b"));
+        create_file(&a, "notes.txt", "v1");
+        create_file(&b, "notes.txt", "v2");
+        let old = std::time::SystemTime::now() - std::time::Duration::from_secs(3600);
+        let file = std::fs::File::options().write(true).open(a.join("notes.txt")).unwrap();
+        file.set_modified(old).unwrap();
+
+        let report = diff(&a, &b);
+        assert_eq!(
+            report.entries[0].status,
+            DiffStatus::Differs {
+                reason: DiffReason::NewerInB
+            }
+        );
+    }
+
+    #[test]
+    fn tree_groups_by_directory() {
+        let (_dir, a, b) = sample();
+        let lines = diff(&a, &b).render_tree("a", "b");
+        assert_eq!(lines[0], "a vs b");
+        assert_eq!(lines[1], "├── docs/");
+        assert_eq!(lines[2], format!("│   └── new.txt  [only in A, {}]", ByteSize(9)));
+        assert_eq!(lines[3], "├── old/");
+        assert_eq!(lines[4], format!("│   └── gone.txt  [only in B, {}]", ByteSize(10)));
+        let grown = format!("└── grown.txt  [size {} vs {}]", ByteSize(5), ByteSize(11));
+        assert_eq!(lines[5], grown);
+    }
+
+    #[test]
+    fn json_names_status_and_reason() {
+        let (_dir, a, b) = sample();
+        let value = serde_json::to_value(diff(&a, &b)).unwrap();
+        let grown = &value["entries"][1];
+        assert_eq!(grown["status"], "differs");
+        assert_eq!(grown["reason"], "size");
+        assert_eq!(grown["size_a"], 5);
+        assert_eq!(value["identical"], 1);
+    }
+}
@@ -0,0 +1,506 @@
+//! `flux watch-folder`: send every file placed in a drop folder.
+//!
+//! Files are picked up once they have stopped changing for the settle time,
+//! handed to the target (a `flux cp` to a destination, a queue entry for
+//! `flux daemon`, or a `flux send` to a device) and moved to `Sent/` or
+//! `Failed/` inside the folder, keeping their relative path.
+
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::RecvTimeoutError;
+use std::time::{Duration, SystemTime};
+
+use notify::RecursiveMode;
+use notify_debouncer_full::{new_debouncer, DebounceEventResult};
+
+use crate::cli::args::{AttrArgs, CpArgs, HiddenArgs, HookArgs, WatchFolderArgs};
+use crate::config::aliases::{resolve_alias, AliasStore};
+use crate::config::types::ConflictStrategy;
+use crate::error::FluxError;
+use crate::protocol::FluxPath;
+use crate::queue::policy::QueueClass;
+use crate::queue::state::QueueStore;
+use crate::transfer;
+use crate::transfer::cancel;
+use crate::transfer::changed::ChangedSource;
+use crate::transfer::checksum::ChecksumAlgorithm;
+use crate::transfer::conflict::find_unique_path;
+use crate::transfer::filter::TransferFilter;
+
+use super::watch::{changed_files, dest_path};
+
+/// Folder in the drop folder that handled files are moved to.
+pub const SENT_DIR: &str = "Sent";
+
+/// Folder in the drop folder that files are moved to when sending fails.
+pub const FAILED_DIR: &str = "Failed";
+
+/// Where `flux watch-folder` sends files.
+pub enum DropTarget {
+    /// Copy into a destination directory (path, URI or alias-resolved).
+    Copy { dest: String, verify: bool },
+    /// Queue a copy into `dest` for `flux daemon`; local destinations are
+    /// absolute, since entries run from the daemon's working directory.
+    Queue {
+        data_dir: PathBuf,
+        dest: String,
+        verify: bool,
+        class: QueueClass,
+    },
+    /// Send to a device (`@name`, like `flux send`).
+    #[cfg(feature = "net")]
+    Device {
+        target: String,
+        encrypt: bool,
+        name: String,
+    },
+}
+
+/// A watched drop folder and what to do with the files placed in it.
+pub struct DropFolder {
+    /// Absolute path of the folder
+    root: PathBuf,
+    target: DropTarget,
+    filter: TransferFilter,
+    settle: Duration,
+    quiet: bool,
+}
+
+/// Entry point for the `flux watch-folder` command.
+pub fn execute_watch_folder(args: WatchFolderArgs, quiet: bool) -> Result<(), FluxError> {
+    let alias_store = AliasStore::current();
+    let folder = resolve_alias(&args.folder, &alias_store);
+    let folder = Path::new(&folder);
+    if !folder.is_dir() {
+        return Err(FluxError::SourceNotFound {
+            path: folder.to_path_buf(),
+        });
+    }
+
+    let target = if args.target.starts_with('@') {
+        if args.queue {
+            return Err(FluxError::Config(
+                "--queue needs a destination directory; sends to a device cannot be queued"
+                    .to_string(),
+            ));
+        }
+        device_target(&args)?
+    } else {
+        let dest = resolve_alias(&args.target, &alias_store);
+        if args.queue {
+            let dest = if FluxPath::parse(&dest).is_local() {
+                std::path::absolute(&dest)?.display().to_string()
+            } else {
+                dest
+            };
+            DropTarget::Queue {
+                data_dir: crate::config::paths::flux_data_dir()?,
+                dest,
+                verify: args.verify,
+                class: args.queue_class.unwrap_or_default(),
+            }
+        } else {
+            DropTarget::Copy {
+                dest,
+                verify: args.verify,
+            }
+        }
+    };
+
+    let filter = TransferFilter::new(&args.exclude, &[])?.skip_hidden(true);
+    let settle = Duration::from_secs(args.settle);
+    DropFolder::new(folder, target, filter, settle, quiet)?.run()
+}
+
+#[cfg(feature = "net")]
+fn device_target(args: &WatchFolderArgs) -> Result<DropTarget, FluxError> {
+    let name = args
+        .name
+        .clone()
+        .unwrap_or_else(|| gethostname::gethostname().to_string_lossy().to_string());
+    Ok(DropTarget::Device {
+        target: args.target.clone(),
+        encrypt: !args.no_encrypt,
+        name,
+    })
+}
+
+#[cfg(not(feature = "net"))]
+fn device_target(args: &WatchFolderArgs) -> Result<DropTarget, FluxError> {
+    Err(FluxError::Config(format!(
+        "Sending to {} needs the net feature (rebuild with --features net)",
+        args.target
+    )))
+}
+
+impl DropFolder {
+    /// Set up `root` as a drop folder, creating its `Sent/` and `Failed/`
+    /// folders.
+    pub fn new(
+        root: &Path,
+        target: DropTarget,
+        filter: TransferFilter,
+        settle: Duration,
+        quiet: bool,
+    ) -> Result<Self, FluxError> {
+        let root = std::path::absolute(root)?;
+        std::fs::create_dir_all(root.join(SENT_DIR))?;
+        std::fs::create_dir_all(root.join(FAILED_DIR))?;
+        Ok(Self {
+            root,
+            target,
+            filter,
+            settle,
+            quiet,
+        })
+    }
+
+    /// Watch the folder until Ctrl+C, sending the files already in it first.
+    pub fn run(&self) -> Result<(), FluxError> {
+        let (tx, rx) = std::sync::mpsc::channel();
+
+        let mut debouncer = new_debouncer(
+            Duration::from_secs(2),
+            None,
+            move |result: DebounceEventResult| {
+                let _ = tx.send(result);
+            },
+        )
+        .map_err(|e| FluxError::SyncError(format!("Failed to create file watcher: {}", e)))?;
+
+        let root = &self.root;
+        debouncer
+            .watch(root, RecursiveMode::Recursive)
+            .map_err(|e| FluxError::SyncError(format!("Failed to watch '{}': {}", root.display(), e)))?;
+
+        eprintln!(
+            "Watching {} for files to send... (press Ctrl+C to stop)",
+            root.display()
+        );
+
+        let existing: Vec<PathBuf> = std::fs::read_dir(root)?
+            .filter_map(Result::ok)
+            .map(|e| e.path())
+            .collect();
+        let mut pending = self.files(&existing);
+
+        loop {
+            self.send_ready(&mut pending)?;
+            match rx.recv_timeout(Duration::from_millis(500)) {
+                Ok(Ok(events)) => {
+                    let paths: Vec<PathBuf> =
+                        events.iter().flat_map(|e| e.paths.iter().cloned()).collect();
+                    pending.extend(self.files(&paths));
+                }
+                Ok(Err(errors)) => {
+                    for e in errors {
+                        tracing::warn!("Watch error: {}", e);
+                    }
+                }
+                Err(RecvTimeoutError::Timeout) => {
+                    if cancel::requested() {
+                        break;
+                    }
+                }
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+        }
+
+        if !pending.is_empty() {
+            eprintln!("{} file(s) were left in the folder", pending.len());
+        }
+        Ok(())
+    }
+
+    /// The files to send among changed `paths`: those `changed_files` keeps,
+    /// minus anything already in `Sent/` or `Failed/`.
+    fn files(&self, paths: &[PathBuf]) -> BTreeSet<PathBuf> {
+        let mut files = changed_files(&self.root, paths, &self.filter);
+        files.retain(|f| {
+            f.strip_prefix(&self.root)
+                .is_ok_and(|rel| !rel.starts_with(SENT_DIR) && !rel.starts_with(FAILED_DIR))
+        });
+        files
+    }
+
+    /// Handle the pending files that have settled. Files that are gone are
+    /// dropped; the rest wait for the next call.
+    fn send_ready(&self, pending: &mut BTreeSet<PathBuf>) -> Result<(), FluxError> {
+        let files: Vec<PathBuf> = pending.iter().cloned().collect();
+        for file in files {
+            let Ok(meta) = std::fs::metadata(&file) else {
+                pending.remove(&file);
+                continue;
+            };
+            let settled = meta
+                .modified()
+                .ok()
+                .and_then(|m| SystemTime::now().duration_since(m).ok())
+                .is_none_or(|age| age >= self.settle);
+            if !settled {
+                continue;
+            }
+            if !self.handle(&file)? {
+                // The queue is busy; try again on the next tick
+                break;
+            }
+            pending.remove(&file);
+        }
+        Ok(())
+    }
+
+    /// Send one file and move it to `Sent/` or `Failed/`. Returns `false`
+    /// if it was left in place because the queue is held by another process.
+    ///
+    /// Only Ctrl+C and errors moving the file are returned; a failed send
+    /// is reported and the file goes to `Failed/`.
+    fn handle(&self, file: &Path) -> Result<bool, FluxError> {
+        let rel = file.strip_prefix(&self.root).unwrap_or(file);
+        let result = match &self.target {
+            DropTarget::Copy { dest, verify } => transfer::execute_copy_as(
+                "cp",
+                copy_args(file, &dest_path(dest, rel), *verify),
+                self.quiet,
+                None,
+                None,
+            ),
+            DropTarget::Queue {
+                data_dir,
+                dest,
+                verify,
+                class,
+            } => {
+                let Some(mut store) = QueueStore::try_load(data_dir)? else {
+                    tracing::debug!("Queue busy, {} waiting", file.display());
+                    return Ok(false);
+                };
+                // The entry copies the file from where it is moved to
+                let sent = self.move_to(file, SENT_DIR)?;
+                let id = store.add(
+                    sent.display().to_string(),
+                    dest_path(dest, rel),
+                    false,
+                    *verify,
+                    false,
+                );
+                if let Some(entry) = store.get_mut(id) {
+                    entry.class = *class;
+                }
+                store.save()?;
+                self.report(format!("Queued {} (#{})", rel.display(), id));
+                return Ok(true);
+            }
+            #[cfg(feature = "net")]
+            DropTarget::Device {
+                target,
+                encrypt,
+                name,
+            } => crate::net::sender::send_file_sync(
+                target,
+                file,
+                &crate::net::sender::SendOptions {
+                    encrypt: *encrypt,
+                    device_name: name,
+                    receipt: false,
+                    limit: crate::net::ratelimit::RateLimit::default(),
+                    checksum: ChecksumAlgorithm::Blake3,
+                    psk: None,
+                },
+            ),
+        };
+
+        match result {
+            Ok(()) => {
+                self.move_to(file, SENT_DIR)?;
+                self.report(format!("Sent {}", rel.display()));
+            }
+            Err(FluxError::Cancelled) => return Err(FluxError::Cancelled),
+            Err(e) => {
+                self.move_to(file, FAILED_DIR)?;
+                eprintln!("Failed to send {}: {}", rel.display(), e);
+            }
+        }
+        Ok(true)
+    }
+
+    /// Move `file` to the same relative path under `folder`, renaming it to
+    /// `name (1).ext` and so on if that is taken. Returns the new path.
+    fn move_to(&self, file: &Path, folder: &str) -> Result<PathBuf, FluxError> {
+        let rel = file.strip_prefix(&self.root).unwrap_or(file);
+        let mut to = self.root.join(folder).join(rel);
+        if let Some(parent) = to.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        if to.exists() {
+            to = find_unique_path(&to, |p| p.exists());
+        }
+        std::fs::rename(file, &to)?;
+        Ok(to)
+    }
+
+    fn report(&self, message: String) {
+        if !self.quiet {
+            let timestamp = chrono::Local::now().format("%H:%M:%S");
+            eprintln!("[{}] {}", timestamp, message);
+        }
+    }
+}
+
+/// `CpArgs` copying the single file `source` to `dest`. A file of the same
+/// name at the destination is kept and the new one renamed, since nobody is
+/// there to answer a prompt.
+fn copy_args(source: &Path, dest: &str, verify: bool) -> CpArgs {
+    CpArgs {
+        source: source.display().to_string(),
+        dest: dest.to_string(),
+        recursive: false,
+        verify,
+        checksum: ChecksumAlgorithm::Blake3,
+        compress: false,
+        chunks: 0,
+        exclude: vec![],
+        include: vec![],
+        hidden: HiddenArgs::default(),
+        attrs: AttrArgs::default(),
+        limit: None,
+        resume: false,
+        resume_verify: false,
+        on_conflict: Some(ConflictStrategy::Rename),
+        on_error: None,
+        dry_run: false,
+        no_clone: false,
+        atomic: true,
+        mmap: false,
+        no_preallocate: false,
+        encrypt_to: None,
+        decrypt: false,
+        snapshot_source: false,
+        vss: false,
+        jobs: 0,
+        io_profile: None,
+        dedup: None,
+        hard_links: false,
+        read_only: false,
+        stats: false,
+        failed_out: None,
+        retry_from: None,
+        changed_source: ChangedSource::Fail,
+        hooks: HookArgs::default(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn drop_folder(root: &Path, target: DropTarget) -> DropFolder {
+        let filter = TransferFilter::new(&[], &[]).unwrap().skip_hidden(true);
+        DropFolder::new(root, target, filter, Duration::ZERO, true).unwrap()
+    }
+
+    #[test]
+    fn sent_files_move_to_sent_with_their_relative_path() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path().join("outbox");
+        let dest = dir.path().join("dest");
+        std::fs::create_dir_all(root.join("scans")).unwrap();
+        std::fs::create_dir_all(&dest).unwrap();
+        std::fs::write(root.join("scans/page.pdf"), "pdf").unwrap();
+        std::fs::write(root.join(".partial"), "tmp").unwrap();
+
+        let target = DropTarget::Copy {
+            dest: dest.display().to_string(),
+            verify: true,
+        };
+        let folder = drop_folder(&root, target);
+        let mut pending = folder.files(&[root.join("scans"), root.join(".partial")]);
+        assert_eq!(pending.len(), 1);
+        folder.send_ready(&mut pending).unwrap();
+
+        assert!(pending.is_empty());
+        assert_eq!(std::fs::read_to_string(dest.join("scans/page.pdf")).unwrap(), "pdf");
+        assert!(root.join("Sent/scans/page.pdf").exists());
+        assert!(!root.join("scans/page.pdf").exists());
+        assert!(root.join(".partial").exists());
+    }
+
+    #[test]
+    fn failed_sends_move_to_failed_without_clobbering() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path().join("outbox");
+        std::fs::create_dir_all(root.join("Failed")).unwrap();
+        std::fs::write(root.join("Failed/a.txt"), "old").unwrap();
+        std::fs::write(root.join("a.txt"), "new").unwrap();
+        // A file where the destination directory should be
+        let blocker = dir.path().join("blocker");
+        std::fs::write(&blocker, "").unwrap();
+
+        let target = DropTarget::Copy {
+            dest: blocker.join("sub").display().to_string(),
+            verify: false,
+        };
+        let folder = drop_folder(&root, target);
+        let mut pending = folder.files(&[root.join("a.txt"), root.join("Failed/a.txt")]);
+        folder.send_ready(&mut pending).unwrap();
+
+        assert_eq!(std::fs::read_to_string(root.join("Failed/a.txt")).unwrap(), "old");
+        assert_eq!(std::fs::read_to_string(root.join("Failed/a (1).txt")).unwrap(), "new");
+    }
+
+    #[test]
+    fn unsettled_files_wait() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path().join("outbox");
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(root.join("a.txt"), "a").unwrap();
+
+        let target = DropTarget::Copy {
+            dest: dir.path().join("dest").display().to_string(),
+            verify: false,
+        };
+        let mut folder = drop_folder(&root, target);
+        folder.settle = Duration::from_secs(3600);
+        let mut pending = folder.files(&[root.join("a.txt")]);
+        folder.send_ready(&mut pending).unwrap();
+        assert_eq!(pending.len(), 1);
+        assert!(root.join("a.txt").exists());
+    }
+
+    #[test]
+    fn queued_files_point_at_their_sent_copy() {
+        let data = TempDir::new().unwrap();
+        let dir = TempDir::new().unwrap();
+        let root = dir.path().join("outbox");
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(root.join("a.txt"), "a").unwrap();
+
+        let target = DropTarget::Queue {
+            data_dir: data.path().to_path_buf(),
+            dest: "sftp://nas/inbox".into(),
+            verify: true,
+            class: QueueClass::Bulk,
+        };
+        let folder = drop_folder(&root, target);
+        let mut pending = folder.files(&[root.join("a.txt")]);
+
+        // Held by the daemon: nothing moves
+        let held = QueueStore::load(data.path()).unwrap();
+        folder.send_ready(&mut pending).unwrap();
+        assert_eq!(pending.len(), 1);
+        assert!(root.join("a.txt").exists());
+        drop(held);
+
+        folder.send_ready(&mut pending).unwrap();
+        assert!(pending.is_empty());
+        let store = QueueStore::load(data.path()).unwrap();
+        let entry = &store.list()[0];
+        assert_eq!(
+            entry.source,
+            std::path::absolute(root.join("Sent/a.txt")).unwrap().display().to_string()
+        );
+        assert_eq!(entry.dest, "sftp://nas/inbox/a.txt");
+        assert!(entry.verify);
+        assert_eq!(entry.class, QueueClass::Bulk);
+    }
+}
@@ -1,23 +1,36 @@
+use std::collections::HashSet;
 use std::path::Path;
-use std::time::Duration;
+use std::time::{Duration, SystemTime};
 
 use indicatif::ProgressBar;
 use walkdir::WalkDir;
 
+use crate::backend::readonly;
 use crate::error::FluxError;
-use crate::progress::bar::create_directory_progress;
-use crate::transfer::checksum::hash_file;
+use crate::progress::bar::BatchProgress;
+use crate::transfer::atomic::AtomicFile;
+use crate::transfer::attrs::AttrCopier;
+use crate::transfer::cancel::{self, PartialFile};
+use crate::transfer::checksum::{hash_pair_with, ChecksumAlgorithm};
+use crate::transfer::checksum_cache::ChecksumCache;
 use crate::transfer::copy::copy_file_with_progress;
 use crate::transfer::filter::TransferFilter;
+use crate::transfer::hardlink::{self, same_file, LinkTracker};
+use crate::transfer::status::StatusPublisher;
 
-use super::plan::{SyncAction, SyncPlan, SyncResult};
+use super::backup::{BackupRun, TRASH_DIR};
+use super::names::{NameIndex, NameMatching};
+#[cfg(feature = "net")]
+use super::plan::ListedFile;
+use super::plan::{NameCollision, SyncAction, SyncPlan, SyncResult};
+use super::transaction::is_staging_dir;
 
 /// Decision for a single file comparison.
 #[derive(Debug, PartialEq)]
 pub enum SyncDecision {
     /// Destination does not exist -- copy the file.
     CopyNew,
-    /// File differs (size or mtime) -- update the dest.
+    /// File differs (size, mtime or content) -- update the dest.
     Update,
     /// File is identical -- skip.
     Skip,
@@ -40,15 +53,25 @@ pub fn needs_sync(src_meta: &std::fs::Metadata, dest_path: &Path) -> SyncDecisio
         Ok(m) => m,
         Err(_) => return SyncDecision::CopyNew,
     };
+    compare_stats(
+        (src_meta.len(), src_meta.modified().ok()),
+        (dest_meta.len(), dest_meta.modified().ok()),
+    )
+}
 
+/// Steps 2-4 of `needs_sync` on the size and mtime of both sides.
+fn compare_stats(
+    (src_len, src_mtime): (u64, Option<SystemTime>),
+    (dest_len, dest_mtime): (u64, Option<SystemTime>),
+) -> SyncDecision {
     // Different size -> definitely changed
-    if src_meta.len() != dest_meta.len() {
+    if src_len != dest_len {
         return SyncDecision::Update;
     }
 
     // Compare modification times with tolerance for cross-filesystem sync
-    match (src_meta.modified(), dest_meta.modified()) {
-        (Ok(src_mtime), Ok(dest_mtime)) => {
+    match (src_mtime, dest_mtime) {
+        (Some(src_mtime), Some(dest_mtime)) => {
             if let Ok(diff) = src_mtime.duration_since(dest_mtime) {
                 if diff > MTIME_TOLERANCE {
                     return SyncDecision::Update;
@@ -60,9 +83,127 @@ pub fn needs_sync(src_meta: &std::fs::Metadata, dest_path: &Path) -> SyncDecisio
     }
 }
 
+/// How files present on both sides are compared (`flux sync --compare`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum CompareMode {
+    /// Update when the sizes differ
+    Size,
+    /// Update when the sizes differ or the source is newer
+    #[default]
+    Mtime,
+    /// Update when the sizes or the checksums differ
+    Checksum,
+}
+
+/// Decides whether files that exist in dest need an update.
+///
+/// In checksum mode both sides are hashed through the `ChecksumCache`, so
+/// files unchanged since the last run are not read again.
+///
+/// With `hard_links(true)`, further names of a multiply-linked source file
+/// are planned as links to the first name's destination instead. With
+/// `names`, source and destination names are paired by `NameMatching::key`.
+#[derive(Debug, Default)]
+pub struct FileComparer {
+    mode: CompareMode,
+    algorithm: ChecksumAlgorithm,
+    cache: Option<ChecksumCache>,
+    hard_links: bool,
+    either_newer: bool,
+    names: NameMatching,
+}
+
+impl FileComparer {
+    /// Comparer for `mode`, hashing with `algorithm` in checksum mode.
+    pub fn new(mode: CompareMode, algorithm: ChecksumAlgorithm) -> Self {
+        let cache = (mode == CompareMode::Checksum).then(ChecksumCache::open);
+        Self {
+            mode,
+            algorithm,
+            cache,
+            hard_links: false,
+            either_newer: false,
+            names: NameMatching::default(),
+        }
+    }
+
+    /// Recreate hard links between source files (`--hard-links`).
+    pub fn hard_links(mut self, hard_links: bool) -> Self {
+        self.hard_links = hard_links;
+        self
+    }
+
+    /// Match names that differ in case or normalization
+    /// (`--case-insensitive`, `--normalize`).
+    pub fn names(mut self, names: NameMatching) -> Self {
+        self.names = names;
+        self
+    }
+
+    /// In mtime mode, also count a destination newer than its source as
+    /// changed. Sync leaves such files alone; `flux diff` reports them.
+    pub fn either_newer(mut self, either_newer: bool) -> Self {
+        self.either_newer = either_newer;
+        self
+    }
+
+    fn compare(
+        &mut self,
+        src_path: &Path,
+        src_meta: &std::fs::Metadata,
+        dest_path: &Path,
+    ) -> SyncDecision {
+        if self.mode == CompareMode::Mtime {
+            let decision = needs_sync(src_meta, dest_path);
+            if decision == SyncDecision::Skip && self.either_newer {
+                let dest_newer = std::fs::metadata(dest_path)
+                    .and_then(|m| m.modified())
+                    .ok()
+                    .zip(src_meta.modified().ok())
+                    .and_then(|(dest, src)| dest.duration_since(src).ok())
+                    .is_some_and(|diff| diff > MTIME_TOLERANCE);
+                if dest_newer {
+                    return SyncDecision::Update;
+                }
+            }
+            return decision;
+        }
+        let dest_meta = match std::fs::metadata(dest_path) {
+            Ok(m) => m,
+            Err(_) => return SyncDecision::CopyNew,
+        };
+        if src_meta.len() != dest_meta.len() {
+            return SyncDecision::Update;
+        }
+        // --compare size: the same size is enough
+        let Some(cache) = self.cache.as_mut() else {
+            return SyncDecision::Skip;
+        };
+        match (
+            cache.checksum(src_path, self.algorithm),
+            cache.checksum(dest_path, self.algorithm),
+        ) {
+            (Ok(src), Ok(dest)) if src == dest => SyncDecision::Skip,
+            (Ok(_), Ok(_)) => SyncDecision::Update,
+            // Copy anyway; an unreadable source fails there with a clear error
+            (Err(e), _) | (_, Err(e)) => {
+                tracing::warn!("Cannot compare {}: {}", src_path.display(), e);
+                SyncDecision::Update
+            }
+        }
+    }
+
+    /// Persist the checksums hashed so far.
+    fn save(&mut self) {
+        if let Some(cache) = self.cache.as_mut() {
+            cache.save();
+        }
+    }
+}
+
 /// Compute a sync plan by diffing source and dest directory trees.
 ///
-/// Phase 1: Walk source tree, compare each file against dest.
+/// Phase 1: Walk source tree, compare each file against dest with `compare`.
 /// Phase 2: If delete_orphans, walk dest tree and find files not in source.
 /// Safety: refuses to proceed if source is empty and delete_orphans is true
 /// (unless force is true).
@@ -70,18 +211,85 @@ pub fn compute_sync_plan(
     source: &Path,
     dest: &Path,
     filter: &TransferFilter,
+    compare: &mut FileComparer,
     delete_orphans: bool,
     force: bool,
 ) -> Result<SyncPlan, FluxError> {
     let mut actions = Vec::new();
+    let collisions = walk_sync_plan(
+        source,
+        dest,
+        filter,
+        compare,
+        delete_orphans,
+        force,
+        &mut |action| {
+            actions.push(action);
+            Ok(())
+        },
+    )?;
+    let mut plan = SyncPlan::from_actions(actions);
+    plan.collisions = collisions;
+    Ok(plan)
+}
+
+/// The walk of `compute_sync_plan`, handing each action to `sink` as soon
+/// as it is decided instead of collecting them (`sync --low-memory` runs
+/// them right away). An error from `sink` stops the walk. Returns the name
+/// collisions found.
+pub fn walk_sync_plan(
+    source: &Path,
+    dest: &Path,
+    filter: &TransferFilter,
+    compare: &mut FileComparer,
+    delete_orphans: bool,
+    force: bool,
+    sink: &mut dyn FnMut(SyncAction) -> Result<(), FluxError>,
+) -> Result<Vec<NameCollision>, FluxError> {
+    let mut collisions = Vec::new();
+
+    // The `--trash` folder and `--transactional` staging in dest are never
+    // part of the sync
+    let trash = dest.join(TRASH_DIR);
+    let internal = |e: &walkdir::DirEntry| {
+        e.path() == trash || (e.depth() == 1 && is_staging_dir(e.file_name()))
+    };
+
+    // Phase 0 (--case-insensitive/--normalize): index the names in dest
+    let names = compare.names;
+    let mut dest_names = NameIndex::new(names);
+    let mut source_names = NameIndex::new(names);
+    if names.is_active() && dest.exists() {
+        for entry in WalkDir::new(dest)
+            .min_depth(1)
+            .follow_links(false)
+            .sort_by_file_name()
+            .into_iter()
+            .filter_entry(|e| !internal(e))
+            .filter_map(|e| e.ok())
+        {
+            let relative = entry.path().strip_prefix(dest)?;
+            if let Some(kept) = dest_names.insert(relative) {
+                collisions.push(NameCollision {
+                    side: "destination",
+                    kept: dest.join(kept),
+                    other: entry.path().to_path_buf(),
+                });
+            }
+        }
+    }
 
     // Phase 1: Walk source tree, compare against dest
     let mut source_file_count = 0u64;
-    for entry in WalkDir::new(source)
-        .follow_links(false)
-        .into_iter()
-        .filter_entry(|e| !filter.is_excluded_dir(e))
-    {
+    let mut links = LinkTracker::default();
+    // Destinations written by this plan, which a link must be renewed to
+    let mut rewritten = HashSet::new();
+    let mut walker = WalkDir::new(source).follow_links(false);
+    if names.is_active() {
+        // Clashing names are resolved in the same order every run
+        walker = walker.sort_by_file_name();
+    }
+    for entry in walker.into_iter().filter_entry(|e| !filter.is_excluded_dir(e)) {
         let entry = entry?;
         if !entry.file_type().is_file() {
             continue;
@@ -93,37 +301,83 @@ pub fn compute_sync_plan(
         source_file_count += 1;
 
         let relative = entry.path().strip_prefix(source)?;
-        let dest_path = dest.join(relative);
+        let dest_path = if names.is_active() {
+            if let Some(kept) = source_names.insert(relative) {
+                collisions.push(NameCollision {
+                    side: "source",
+                    kept: source.join(kept),
+                    other: entry.path().to_path_buf(),
+                });
+                sink(SyncAction::Skip {
+                    path: entry.path().to_path_buf(),
+                    reason: "name collision",
+                })?;
+                continue;
+            }
+            dest.join(dest_names.resolve(relative))
+        } else {
+            dest.join(relative)
+        };
         let src_meta = entry.metadata()?;
 
-        match needs_sync(&src_meta, &dest_path) {
+        // --hard-links: a further name of a file is linked to the first
+        // name's destination, unless it already is
+        let first = if compare.hard_links {
+            links.first_name(&src_meta, &dest_path)
+        } else {
+            None
+        };
+        if let Some(target) = first {
+            if same_file(&target, &dest_path) && !rewritten.contains(&target) {
+                sink(SyncAction::Skip {
+                    path: entry.path().to_path_buf(),
+                    reason: "hard link",
+                })?;
+            } else {
+                sink(SyncAction::Link {
+                    src: entry.path().to_path_buf(),
+                    dest: dest_path,
+                    target,
+                    size: src_meta.len(),
+                })?;
+            }
+            continue;
+        }
+
+        let decision = compare.compare(entry.path(), &src_meta, &dest_path);
+        if compare.hard_links && decision != SyncDecision::Skip {
+            rewritten.insert(dest_path.clone());
+        }
+        match decision {
             SyncDecision::CopyNew => {
-                actions.push(SyncAction::CopyNew {
+                sink(SyncAction::CopyNew {
                     src: entry.path().to_path_buf(),
                     dest: dest_path,
                     size: src_meta.len(),
-                });
+                })?;
             }
             SyncDecision::Update => {
                 let dest_size = std::fs::metadata(&dest_path)
                     .map(|m| m.len())
                     .unwrap_or(0);
-                actions.push(SyncAction::UpdateChanged {
+                sink(SyncAction::UpdateChanged {
                     src: entry.path().to_path_buf(),
                     dest: dest_path,
                     src_size: src_meta.len(),
                     dest_size,
-                });
+                })?;
             }
             SyncDecision::Skip => {
-                actions.push(SyncAction::Skip {
+                sink(SyncAction::Skip {
                     path: entry.path().to_path_buf(),
                     reason: "unchanged",
-                });
+                })?;
             }
         }
     }
 
+    compare.save();
+
     // Phase 2: Walk dest tree, find orphans (if --delete)
     if delete_orphans && dest.exists() {
         // Safety check: empty source + delete is dangerous
@@ -133,9 +387,11 @@ pub fn compute_sync_plan(
             ));
         }
 
+        // Excluded, hidden and system directories in dest are left alone
         for entry in WalkDir::new(dest)
             .follow_links(false)
             .into_iter()
+            .filter_entry(|e| !filter.is_excluded_dir(e) && !internal(e))
             .filter_map(|e| e.ok())
         {
             if !entry.file_type().is_file() {
@@ -149,19 +405,88 @@ pub fn compute_sync_plan(
 
             // Only mark as orphan if not in source AND passes filter
             // (don't delete files that were merely excluded from sync)
-            if !src_path.exists() {
+            let in_source = if names.is_active() {
+                source_names.contains(relative)
+            } else {
+                src_path.exists()
+            };
+            if !in_source {
                 // Check if the file would have been filtered out of the source walk
                 // If so, it's not truly an orphan -- it was just excluded
                 if filter.should_transfer(&src_path) {
-                    actions.push(SyncAction::DeleteOrphan {
+                    sink(SyncAction::DeleteOrphan {
                         path: entry.path().to_path_buf(),
                         size: entry.metadata().map(|m| m.len()).unwrap_or(0),
-                    });
+                    })?;
                 }
             }
         }
     }
 
+    Ok(collisions)
+}
+
+/// Compute a sync plan between two listed trees, one of which is remote
+/// (`flux push`, `flux pull`). Files are compared as `needs_sync` does, by
+/// size and mtime; action paths are the listed paths below `source_root`
+/// and `dest_root`. Orphans are only planned with `delete_orphans`, with the
+/// same empty-source safety check as `compute_sync_plan`.
+#[cfg(feature = "net")]
+pub fn compute_listed_plan(
+    source_root: &Path,
+    source: &[ListedFile],
+    dest_root: &Path,
+    dest: &[ListedFile],
+    delete_orphans: bool,
+    force: bool,
+) -> Result<SyncPlan, FluxError> {
+    let existing: std::collections::HashMap<&str, &ListedFile> =
+        dest.iter().map(|file| (file.path.as_str(), file)).collect();
+    let mut actions = Vec::new();
+    for file in source {
+        let src = source_root.join(&file.path);
+        let dest_path = dest_root.join(&file.path);
+        let Some(other) = existing.get(file.path.as_str()) else {
+            actions.push(SyncAction::CopyNew {
+                src,
+                dest: dest_path,
+                size: file.size,
+            });
+            continue;
+        };
+        let decision = compare_stats(
+            (file.size, file.modified_time()),
+            (other.size, other.modified_time()),
+        );
+        if decision == SyncDecision::Skip {
+            actions.push(SyncAction::Skip {
+                path: src,
+                reason: "unchanged",
+            });
+        } else {
+            actions.push(SyncAction::UpdateChanged {
+                src,
+                dest: dest_path,
+                src_size: file.size,
+                dest_size: other.size,
+            });
+        }
+    }
+
+    if delete_orphans {
+        if source.is_empty() && !dest.is_empty() && !force {
+            return Err(FluxError::SyncError(
+                "Source directory is empty but --delete is set. Use --force to proceed.".to_string(),
+            ));
+        }
+        let listed: HashSet<&str> = source.iter().map(|file| file.path.as_str()).collect();
+        for file in dest.iter().filter(|file| !listed.contains(file.path.as_str())) {
+            actions.push(SyncAction::DeleteOrphan {
+                path: dest_root.join(&file.path),
+                size: file.size,
+            });
+        }
+    }
     Ok(SyncPlan::from_actions(actions))
 }
 
@@ -170,29 +495,92 @@ pub fn compute_sync_plan(
 /// For CopyNew and UpdateChanged: ensures parent dirs exist, copies using
 /// existing `copy_file_with_progress`. For DeleteOrphan: removes the file.
 /// Skip actions are ignored.
+///
+/// With `atomic`, each file is written to a temp file next to its
+/// destination and renamed into place after the copy (and verification)
+/// succeeds, so an interrupted sync never leaves a truncated file that the
+/// next run would consider up to date.
+///
+/// With `backup` (`--backup-dir`/`--trash`), orphans and the old versions of
+/// updated files are moved into the backup run folder instead of being
+/// removed or overwritten.
+///
+/// With `status` (the source and destination directories), progress is
+/// published for `flux status` while the plan runs.
+///
+/// `attrs` copies extended attributes and ACLs (`--xattrs`, `--acls`) to
+/// each file written; files that could not take them are listed at the end.
 pub fn execute_sync_plan(
     plan: &SyncPlan,
     quiet: bool,
-    verify: bool,
+    verify: Option<ChecksumAlgorithm>,
+    atomic: bool,
+    backup: Option<BackupRun>,
+    status: Option<(&Path, &Path)>,
+    attrs: &AttrCopier,
 ) -> Result<SyncResult, FluxError> {
-    let actionable = plan.files_to_copy + plan.files_to_update + plan.files_to_delete;
-    let progress = create_directory_progress(actionable, quiet);
-    let mut result = SyncResult::default();
-
+    let actionable =
+        plan.files_to_copy + plan.files_to_update + plan.files_to_link + plan.files_to_delete;
+    let progress = BatchProgress::new(plan.total_copy_bytes, actionable, quiet);
+    let _status = status.and_then(|(source, dest)| {
+        let (source, dest) = (source.display().to_string(), dest.display().to_string());
+        StatusPublisher::start("sync", &source, &dest, progress.sampler())
+    });
+    let mut runner = ActionRunner {
+        progress,
+        quiet,
+        verify,
+        atomic,
+        backup,
+        attrs,
+        grow: false,
+        result: SyncResult::default(),
+    };
     for action in &plan.actions {
-        match action {
-            SyncAction::CopyNew { src, dest, size } => {
-                ensure_parent_exists(dest)?;
-                let file_progress = ProgressBar::hidden();
-                copy_file_with_progress(src, dest, &file_progress)?;
+        cancel::check()?;
+        runner.run(action)?;
+    }
+    runner.finish()
+}
 
-                if verify && *size > 0 {
-                    verify_copy(src, dest)?;
-                }
+/// Carries out sync actions one at a time: the body of `execute_sync_plan`,
+/// also fed straight from the walk by `sync --low-memory`.
+pub(super) struct ActionRunner<'a> {
+    pub progress: BatchProgress,
+    pub quiet: bool,
+    pub verify: Option<ChecksumAlgorithm>,
+    pub atomic: bool,
+    pub backup: Option<BackupRun>,
+    pub attrs: &'a AttrCopier,
+    /// The progress totals are not known up front: add each action to them
+    /// as it comes
+    pub grow: bool,
+    pub result: SyncResult,
+}
 
+impl ActionRunner<'_> {
+    /// Carry out one action.
+    pub fn run(&mut self, action: &SyncAction) -> Result<(), FluxError> {
+        let (verify, atomic, attrs) = (self.verify, self.atomic, self.attrs);
+        let progress = &self.progress;
+        let result = &mut self.result;
+        if self.grow {
+            match action {
+                SyncAction::CopyNew { size, .. } => progress.add_file(*size),
+                SyncAction::UpdateChanged { src_size, .. } => progress.add_file(*src_size),
+                SyncAction::Link { .. } | SyncAction::DeleteOrphan { .. } => progress.add_file(0),
+                SyncAction::Skip { .. } => {}
+            }
+        }
+        match action {
+            SyncAction::CopyNew { src, dest, size } => {
+                let file_progress = progress.start_file(&file_name(src), *size);
+                let copied = sync_file(src, dest, *size, verify, atomic, None, file_progress.bar());
+                file_progress.done();
+                copied?;
+                attrs.apply(src, dest);
                 result.files_copied += 1;
                 result.bytes_transferred += size;
-                progress.inc(1);
             }
             SyncAction::UpdateChanged {
                 src,
@@ -200,31 +588,131 @@ pub fn execute_sync_plan(
                 src_size,
                 ..
             } => {
-                ensure_parent_exists(dest)?;
-                let file_progress = ProgressBar::hidden();
-                copy_file_with_progress(src, dest, &file_progress)?;
-
-                if verify && *src_size > 0 {
-                    verify_copy(src, dest)?;
-                }
-
+                let file_progress = progress.start_file(&file_name(src), *src_size);
+                let backup = self.backup.as_mut();
+                let copied =
+                    sync_file(src, dest, *src_size, verify, atomic, backup, file_progress.bar());
+                file_progress.done();
+                copied?;
+                attrs.apply(src, dest);
                 result.files_updated += 1;
                 result.bytes_transferred += src_size;
-                progress.inc(1);
+            }
+            SyncAction::Link {
+                src,
+                dest,
+                target,
+                size,
+            } => {
+                ensure_parent_exists(dest)?;
+                if let Some(backup) = self.backup.as_mut() {
+                    backup.stash(dest)?;
+                }
+                match hardlink::link(target, dest) {
+                    Ok(()) => {
+                        result.files_linked += 1;
+                        progress.skip_file(0);
+                    }
+                    Err(e) => {
+                        // e.g. a mount point inside the destination
+                        tracing::warn!("Cannot link {}: {}", dest.display(), e);
+                        let file_progress = progress.start_file(&file_name(src), *size);
+                        let copied =
+                            sync_file(src, dest, *size, verify, atomic, None, file_progress.bar());
+                        file_progress.done();
+                        copied?;
+                        attrs.apply(src, dest);
+                        result.files_copied += 1;
+                        result.bytes_transferred += size;
+                    }
+                }
             }
             SyncAction::DeleteOrphan { path, .. } => {
-                std::fs::remove_file(path)?;
+                readonly::check(path, "delete")?;
+                match self.backup.as_mut() {
+                    Some(backup) => backup.stash(path)?,
+                    None => std::fs::remove_file(path)?,
+                }
                 result.files_deleted += 1;
-                progress.inc(1);
+                progress.skip_file(0);
             }
             SyncAction::Skip { .. } => {
                 result.files_skipped += 1;
             }
         }
+        Ok(())
     }
 
-    progress.finish_with_message("done");
-    Ok(result)
+    /// Clear the progress, report attributes and backups, and return the
+    /// counts.
+    pub fn finish(self) -> Result<SyncResult, FluxError> {
+        self.progress.finish();
+        self.attrs.report();
+
+        if let Some(backup) = self.backup {
+            if let (Some(dir), false) = (backup.run_dir_path(), self.quiet) {
+                eprintln!(
+                    "Moved {} replaced or deleted file(s) to {}",
+                    backup.stashed(),
+                    dir.display()
+                );
+            }
+            let pruned = backup.finish()?;
+            if pruned > 0 && !self.quiet {
+                eprintln!("Pruned {} old backup folder(s)", pruned);
+            }
+        }
+        Ok(self.result)
+    }
+}
+
+/// Copy one file for a sync, optionally verifying it and writing atomically.
+///
+/// With `backup`, an existing destination file is moved into the backup
+/// first: right before the commit when writing atomically, so a failed copy
+/// leaves it in place.
+fn sync_file(
+    src: &Path,
+    dest: &Path,
+    size: u64,
+    verify: Option<ChecksumAlgorithm>,
+    atomic: bool,
+    mut backup: Option<&mut BackupRun>,
+    progress: &ProgressBar,
+) -> Result<(), FluxError> {
+    ensure_parent_exists(dest)?;
+    let atomic_file = atomic.then(|| AtomicFile::new(dest));
+    let write_dest = atomic_file.as_ref().map_or(dest, |f| f.path());
+    if atomic_file.is_none() {
+        if let Some(backup) = backup.as_mut() {
+            backup.stash(dest)?;
+        }
+    }
+
+    // A cancelled non-atomic copy removes what it wrote
+    let partial = atomic_file.is_none().then(|| PartialFile::new(dest));
+    copy_file_with_progress(src, write_dest, progress)?;
+
+    if let Some(algorithm) = verify.filter(|_| size > 0) {
+        verify_copy(src, write_dest, algorithm)?;
+    }
+    if let Some(file) = atomic_file {
+        if let Some(backup) = backup {
+            backup.stash(dest)?;
+        }
+        file.commit()?;
+    }
+    if let Some(partial) = partial {
+        partial.done();
+    }
+    Ok(())
+}
+
+/// File name shown in the progress line.
+pub(super) fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default()
 }
 
 /// Ensure a file's parent directory exists.
@@ -237,10 +725,15 @@ fn ensure_parent_exists(path: &Path) -> Result<(), FluxError> {
     Ok(())
 }
 
-/// Verify a copy with BLAKE3 checksums.
-fn verify_copy(src: &Path, dest: &Path) -> Result<(), FluxError> {
-    let src_hash = hash_file(src)?;
-    let dest_hash = hash_file(dest)?;
+/// Verify a copy by comparing `algorithm` checksums, hashing both files at
+/// once.
+pub(super) fn verify_copy(
+    src: &Path,
+    dest: &Path,
+    algorithm: ChecksumAlgorithm,
+) -> Result<(), FluxError> {
+    let (src_hash, dest_hash) = hash_pair_with(src, dest, algorithm);
+    let (src_hash, dest_hash) = (src_hash?, dest_hash?);
     if src_hash != dest_hash {
         return Err(FluxError::ChecksumMismatch {
             path: dest.to_path_buf(),
@@ -261,6 +754,10 @@ mod tests {
         TransferFilter::new(&[], &[]).unwrap()
     }
 
+    fn mtime() -> FileComparer {
+        FileComparer::default()
+    }
+
     /// Helper: create a file with given content.
     fn create_file(dir: &Path, name: &str, content: &str) {
         let path = dir.join(name);
@@ -309,6 +806,75 @@ mod tests {
         assert_eq!(needs_sync(&src_meta, &dst), SyncDecision::Skip);
     }
 
+    #[test]
+    fn test_compare_modes_same_size() {
+        let dir = TempDir::new().unwrap();
+        let source = dir.path().join("src");
+        let dest = dir.path().join("dst");
+        std::fs::create_dir_all(&source).unwrap();
+        std::fs::create_dir_all(&dest).unwrap();
+
+        // Same size, different content, dest written last
+        create_file(&source, "edited.txt", "version 2");
+        create_file(&dest, "edited.txt", "version 1");
+        // Same content, source newer (as after a cp without preserved mtimes)
+        create_file(&dest, "copied.txt", "same bytes");
+        create_file(&source, "copied.txt", "same bytes");
+        let old = std::time::SystemTime::now() - Duration::from_secs(3600);
+        let file = std::fs::File::options()
+            .write(true)
+            .open(dest.join("copied.txt"))
+            .unwrap();
+        file.set_modified(old).unwrap();
+
+        // Names of the files each mode would update
+        let updated = |compare: &mut FileComparer| -> Vec<String> {
+            let plan = compute_sync_plan(&source, &dest, &no_filter(), compare, false, false);
+            plan.unwrap()
+                .actions
+                .iter()
+                .filter_map(|a| match a {
+                    SyncAction::UpdateChanged { src, .. } => Some(file_name(src)),
+                    _ => None,
+                })
+                .collect()
+        };
+
+        // mtime misses the edit and re-copies the identical file
+        assert_eq!(updated(&mut mtime()), ["copied.txt"]);
+        let mut by_size = FileComparer::new(CompareMode::Size, ChecksumAlgorithm::Xxh3);
+        assert!(updated(&mut by_size).is_empty());
+        let mut by_checksum = FileComparer {
+            mode: CompareMode::Checksum,
+            algorithm: ChecksumAlgorithm::Xxh3,
+            cache: Some(ChecksumCache::in_memory()),
+            ..FileComparer::default()
+        };
+        assert_eq!(updated(&mut by_checksum), ["edited.txt"]);
+    }
+
+    #[test]
+    fn test_either_newer_reports_a_newer_dest() {
+        let dir = TempDir::new().unwrap();
+        let source = dir.path().join("src");
+        let dest = dir.path().join("dst");
+        create_file(&source, "notes.txt", "same size");
+        create_file(&dest, "notes.txt", "same size");
+        let old = std::time::SystemTime::now() - Duration::from_secs(3600);
+        let file = std::fs::File::options()
+            .write(true)
+            .open(source.join("notes.txt"))
+            .unwrap();
+        file.set_modified(old).unwrap();
+
+        let changed = |compare: &mut FileComparer| {
+            let plan = compute_sync_plan(&source, &dest, &no_filter(), compare, false, false);
+            plan.unwrap().files_to_update
+        };
+        assert_eq!(changed(&mut mtime()), 0);
+        assert_eq!(changed(&mut mtime().either_newer(true)), 1);
+    }
+
     #[test]
     fn test_compute_sync_plan_new_files() {
         let dir = TempDir::new().unwrap();
@@ -321,7 +887,8 @@ mod tests {
         create_file(&source, "b.txt", "bbb");
         create_file(&source, "sub/c.txt", "ccc");
 
-        let plan = compute_sync_plan(&source, &dest, &no_filter(), false, false).unwrap();
+        let plan =
+            compute_sync_plan(&source, &dest, &no_filter(), &mut mtime(), false, false).unwrap();
 
         assert_eq!(plan.files_to_copy, 3);
         assert_eq!(plan.files_to_update, 0);
@@ -350,7 +917,8 @@ mod tests {
         // Copy to ensure same size and mtime
         std::fs::copy(source.join("same.txt"), dest.join("same.txt")).unwrap();
 
-        let plan = compute_sync_plan(&source, &dest, &no_filter(), false, false).unwrap();
+        let plan =
+            compute_sync_plan(&source, &dest, &no_filter(), &mut mtime(), false, false).unwrap();
 
         assert_eq!(plan.files_to_copy, 1); // new.txt
         assert_eq!(plan.files_to_update, 1); // changed.txt
@@ -373,7 +941,8 @@ mod tests {
         // Orphan: only in dest
         create_file(&dest, "orphan.txt", "delete me");
 
-        let plan = compute_sync_plan(&source, &dest, &no_filter(), true, false).unwrap();
+        let plan =
+            compute_sync_plan(&source, &dest, &no_filter(), &mut mtime(), true, false).unwrap();
 
         assert_eq!(plan.files_to_delete, 1);
         // Check the orphan action is for the right file
@@ -388,6 +957,44 @@ mod tests {
         }
     }
 
+    // Needs names that differ only in case or form to be distinct files
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_compute_sync_plan_matches_names() {
+        let dir = TempDir::new().unwrap();
+        let source = dir.path().join("src");
+        let dest = dir.path().join("dst");
+        create_file(&source, "Photos/IMG.JPG", "jpeg");
+        create_file(&source, "caf\u{e9}.txt", "nfc");
+        create_file(&source, "README.md", "one");
+        create_file(&source, "readme.md", "two");
+        create_file(&dest, "photos/img.jpg", "jpeg");
+        create_file(&dest, "cafe\u{301}.txt", "nfc");
+
+        // Byte for byte, everything is new and the dest files are orphans
+        let plan =
+            compute_sync_plan(&source, &dest, &no_filter(), &mut mtime(), true, false).unwrap();
+        assert_eq!(plan.files_to_copy, 4);
+        assert_eq!(plan.files_to_delete, 2);
+
+        let names = NameMatching {
+            case_insensitive: true,
+            normalization: Some(crate::sync::names::Normalization::Nfc),
+        };
+        let mut compare =
+            FileComparer::new(CompareMode::Size, ChecksumAlgorithm::Xxh3).names(names);
+        let plan =
+            compute_sync_plan(&source, &dest, &no_filter(), &mut compare, true, false).unwrap();
+        assert_eq!(plan.files_to_delete, 0);
+        assert_eq!(plan.files_to_copy, 1);
+        assert!(plan.actions.iter().any(|a| matches!(
+            a,
+            SyncAction::CopyNew { dest: d, .. } if d.ends_with("README.md")
+        )));
+        assert_eq!(plan.collisions.len(), 1);
+        assert!(plan.collisions[0].other.ends_with("readme.md"));
+    }
+
     #[test]
     fn test_compute_sync_plan_empty_source_delete_safety() {
         let dir = TempDir::new().unwrap();
@@ -399,7 +1006,7 @@ mod tests {
         // Dest has files but source is empty
         create_file(&dest, "important.txt", "don't delete me");
 
-        let result = compute_sync_plan(&source, &dest, &no_filter(), true, false);
+        let result = compute_sync_plan(&source, &dest, &no_filter(), &mut mtime(), true, false);
         assert!(result.is_err());
         let err = result.unwrap_err();
         let msg = format!("{}", err);
@@ -418,7 +1025,8 @@ mod tests {
         create_file(&dest, "file.txt", "content");
 
         // With force=true, should succeed
-        let plan = compute_sync_plan(&source, &dest, &no_filter(), true, true).unwrap();
+        let plan =
+            compute_sync_plan(&source, &dest, &no_filter(), &mut mtime(), true, true).unwrap();
         assert_eq!(plan.files_to_delete, 1);
     }
 
@@ -432,10 +1040,20 @@ mod tests {
 
         create_file(&source, "file.txt", "hello sync");
 
-        let plan = compute_sync_plan(&source, &dest, &no_filter(), false, false).unwrap();
+        let plan =
+            compute_sync_plan(&source, &dest, &no_filter(), &mut mtime(), false, false).unwrap();
         assert_eq!(plan.files_to_copy, 1);
 
-        let result = execute_sync_plan(&plan, true, false).unwrap();
+        let result = execute_sync_plan(
+            &plan,
+            true,
+            None,
+            true,
+            None,
+            None,
+            &AttrCopier::default(),
+        )
+        .unwrap();
         assert_eq!(result.files_copied, 1);
         assert_eq!(result.bytes_transferred, 10); // "hello sync" = 10 bytes
 
@@ -444,6 +1062,85 @@ mod tests {
         assert_eq!(dest_content, "hello sync");
     }
 
+    #[cfg(unix)]
+    #[test]
+    fn test_sync_recreates_hard_links() {
+        let dir = TempDir::new().unwrap();
+        let source = dir.path().join("src");
+        let dest = dir.path().join("dst");
+        create_file(&source, "snap1/big.bin", "shared data");
+        std::fs::create_dir_all(source.join("snap2")).unwrap();
+        std::fs::hard_link(source.join("snap1/big.bin"), source.join("snap2/big.bin")).unwrap();
+
+        let mut compare = mtime().hard_links(true);
+        let plan = compute_sync_plan(&source, &dest, &no_filter(), &mut compare, false, false)
+            .unwrap();
+        assert_eq!((plan.files_to_copy, plan.files_to_link), (1, 1));
+        assert_eq!(plan.total_copy_bytes, 11);
+        let result = execute_sync_plan(
+            &plan,
+            true,
+            None,
+            true,
+            None,
+            None,
+            &AttrCopier::default(),
+        )
+        .unwrap();
+        assert_eq!((result.files_copied, result.files_linked), (1, 1));
+        assert!(same_file(&dest.join("snap1/big.bin"), &dest.join("snap2/big.bin")));
+
+        // Already linked: nothing to do on the next run
+        let plan = compute_sync_plan(&source, &dest, &no_filter(), &mut compare, false, false)
+            .unwrap();
+        assert!(!plan.has_changes());
+
+        // Without --hard-links each name is copied
+        let plain = dir.path().join("plain");
+        let plan =
+            compute_sync_plan(&source, &plain, &no_filter(), &mut mtime(), false, false).unwrap();
+        assert_eq!((plan.files_to_copy, plan.files_to_link), (2, 0));
+    }
+
+    #[test]
+    fn test_execute_sync_plan_atomic_leaves_no_temp_files() {
+        let dir = TempDir::new().unwrap();
+        let source = dir.path().join("src");
+        let dest = dir.path().join("dst");
+        std::fs::create_dir_all(&source).unwrap();
+        std::fs::create_dir_all(&dest).unwrap();
+
+        create_file(&source, "sub/new.txt", "new content");
+        create_file(&source, "changed.txt", "updated content");
+        create_file(&dest, "changed.txt", "old");
+
+        let plan =
+            compute_sync_plan(&source, &dest, &no_filter(), &mut mtime(), false, false).unwrap();
+        let verify = Some(ChecksumAlgorithm::Blake3);
+        let result = execute_sync_plan(
+            &plan,
+            true,
+            verify,
+            true,
+            None,
+            None,
+            &AttrCopier::default(),
+        )
+        .unwrap();
+        assert_eq!((result.files_copied, result.files_updated), (1, 1));
+
+        assert_eq!(
+            std::fs::read_to_string(dest.join("changed.txt")).unwrap(),
+            "updated content"
+        );
+        let leftovers: Vec<_> = WalkDir::new(&dest)
+            .into_iter()
+            .filter_map(|e| e.ok())
+            .filter(|e| crate::transfer::atomic::is_temp_file(e.path()))
+            .collect();
+        assert!(leftovers.is_empty());
+    }
+
     #[test]
     fn test_execute_sync_plan_deletes_orphans() {
         let dir = TempDir::new().unwrap();
@@ -459,14 +1156,81 @@ mod tests {
         // Orphan in dest
         create_file(&dest, "orphan.txt", "bye");
 
-        let plan = compute_sync_plan(&source, &dest, &no_filter(), true, false).unwrap();
-        let result = execute_sync_plan(&plan, true, false).unwrap();
+        let plan =
+            compute_sync_plan(&source, &dest, &no_filter(), &mut mtime(), true, false).unwrap();
+        let result = execute_sync_plan(
+            &plan,
+            true,
+            None,
+            true,
+            None,
+            None,
+            &AttrCopier::default(),
+        )
+        .unwrap();
 
         assert_eq!(result.files_deleted, 1);
         assert!(!dest.join("orphan.txt").exists());
         assert!(dest.join("keep.txt").exists());
     }
 
+    #[test]
+    fn test_execute_sync_plan_backs_up_instead_of_deleting() {
+        use crate::sync::backup::{BackupLocation, BackupPolicy};
+
+        let dir = TempDir::new().unwrap();
+        let source = dir.path().join("src");
+        let dest = dir.path().join("dst");
+        std::fs::create_dir_all(&source).unwrap();
+        std::fs::create_dir_all(&dest).unwrap();
+
+        create_file(&source, "changed.txt", "new version");
+        create_file(&dest, "changed.txt", "old");
+        create_file(&dest, "sub/orphan.txt", "bye");
+
+        let policy = BackupPolicy {
+            location: BackupLocation::Trash,
+            keep: None,
+        };
+        for atomic in [true, false] {
+            let plan =
+                compute_sync_plan(&source, &dest, &no_filter(), &mut mtime(), true, false).unwrap();
+            let backup = Some(policy.for_dest(&dest));
+            execute_sync_plan(
+                &plan,
+                true,
+                None,
+                atomic,
+                backup,
+                None,
+                &AttrCopier::default(),
+            )
+            .unwrap();
+            // Reset for the second pass
+            create_file(&dest, "changed.txt", "old");
+            create_file(&dest, "sub/orphan.txt", "bye");
+        }
+
+        let runs: Vec<_> = std::fs::read_dir(dest.join(TRASH_DIR))
+            .unwrap()
+            .map(|e| e.unwrap().path())
+            .collect();
+        assert_eq!(runs.len(), 2);
+        for run in runs {
+            assert_eq!(std::fs::read_to_string(run.join("changed.txt")).unwrap(), "old");
+            assert_eq!(std::fs::read_to_string(run.join("sub/orphan.txt")).unwrap(), "bye");
+        }
+
+        // The trash itself is never an orphan
+        let trash = dest.join(TRASH_DIR);
+        let plan =
+            compute_sync_plan(&source, &dest, &no_filter(), &mut mtime(), true, false).unwrap();
+        assert!(plan.actions.iter().all(|a| match a {
+            SyncAction::DeleteOrphan { path, .. } => !path.starts_with(&trash),
+            _ => true,
+        }));
+    }
+
     #[test]
     fn test_compute_sync_plan_with_filter() {
         let dir = TempDir::new().unwrap();
@@ -479,8 +1243,50 @@ mod tests {
         create_file(&source, "file.log", "exclude me");
 
         let filter = TransferFilter::new(&["*.log".to_string()], &[]).unwrap();
-        let plan = compute_sync_plan(&source, &dest, &filter, false, false).unwrap();
+        let plan = compute_sync_plan(&source, &dest, &filter, &mut mtime(), false, false).unwrap();
 
         assert_eq!(plan.files_to_copy, 1); // only file.txt
     }
+
+    #[cfg(feature = "net")]
+    #[test]
+    fn test_compute_listed_plan() {
+        let file = |path: &str, size, modified| ListedFile {
+            path: path.to_string(),
+            size,
+            modified: Some(modified),
+        };
+        let source = [
+            file("new.txt", 10, 1_000),
+            file("sub/same.txt", 20, 1_000),
+            file("grown.txt", 30, 1_000),
+            file("touched.txt", 40, 2_000),
+        ];
+        let dest = [
+            // Within the mtime tolerance
+            file("sub/same.txt", 20, 1_001),
+            file("grown.txt", 25, 1_000),
+            file("touched.txt", 40, 1_000),
+            file("orphan.txt", 5, 1_000),
+        ];
+        let (local, remote) = (Path::new("/data/photos"), Path::new("photos"));
+
+        let plan = compute_listed_plan(local, &source, remote, &dest, false, false).unwrap();
+        assert_eq!(plan.files_to_copy, 1);
+        assert_eq!(plan.files_to_update, 2);
+        assert_eq!(plan.files_to_skip, 1);
+        assert_eq!(plan.files_to_delete, 0);
+        assert!(plan.actions.iter().any(|a| matches!(
+            a,
+            SyncAction::CopyNew { src, dest, .. }
+                if src == Path::new("/data/photos/new.txt") && dest == Path::new("photos/new.txt")
+        )));
+
+        // Orphans only with --delete, and never from an empty source unforced
+        let plan = compute_listed_plan(local, &source, remote, &dest, true, false).unwrap();
+        assert_eq!(plan.files_to_delete, 1);
+        assert!(compute_listed_plan(local, &[], remote, &dest, true, false).is_err());
+        let plan = compute_listed_plan(local, &[], remote, &dest, true, true).unwrap();
+        assert_eq!(plan.files_to_delete, 4);
+    }
 }
@@ -0,0 +1,396 @@
+//! Mirror verification for `flux sync --verify-mirror`.
+//!
+//! After a sync (or on its own with `--mirror-only`), source and destination
+//! are compared file by file and every difference is written to a JSON drift
+//! report: the relative path, what kind of drift it is, and the size, mtime
+//! and content hash of each side. Files present on both sides with equal
+//! sizes are hashed in full, or only a random `--sample` percentage of them
+//! for a quicker spot check. Hashes are XXH3 unless `--checksum` asks for a
+//! cryptographic one; the report names the algorithm.
+//!
+//! Reports go to `mirror-reports/<timestamp>.json` in the data directory
+//! unless `--report` names a file. Any drift makes the command fail, so a
+//! scheduled check shows up in history and hooks.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use walkdir::WalkDir;
+
+use crate::config::paths::flux_data_dir;
+use crate::error::FluxError;
+use crate::progress::bar::create_transfer_progress;
+use crate::transfer::checksum::{hash_file_with, ChecksumAlgorithm};
+use crate::transfer::filter::TransferFilter;
+
+use super::backup::TRASH_DIR;
+
+/// Folder for drift reports in the data directory.
+const REPORT_DIR: &str = "mirror-reports";
+
+/// How to run a mirror check.
+#[derive(Debug, Clone, Default)]
+pub struct MirrorCheck {
+    /// Percentage of same-size files to hash (`None` hashes all of them)
+    pub sample: Option<u8>,
+    /// Report file (`None` for a timestamped file in the data directory)
+    pub report: Option<PathBuf>,
+    /// Hash used to compare contents
+    pub algorithm: ChecksumAlgorithm,
+}
+
+/// What is wrong with a file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DriftKind {
+    /// In source, not in destination
+    MissingInDest,
+    /// In destination, not in source
+    ExtraInDest,
+    SizeMismatch,
+    /// Same size, different content hash
+    ContentMismatch,
+    /// One side could not be read
+    Error,
+}
+
+impl DriftKind {
+    fn label(self) -> &'static str {
+        match self {
+            DriftKind::MissingInDest => "missing in dest",
+            DriftKind::ExtraInDest => "extra in dest",
+            DriftKind::SizeMismatch => "size differs",
+            DriftKind::ContentMismatch => "content differs",
+            DriftKind::Error => "unreadable",
+        }
+    }
+}
+
+/// One side of a drifted file.
+#[derive(Debug, Clone, Serialize)]
+pub struct FileState {
+    pub size: u64,
+    pub modified: Option<DateTime<Utc>>,
+    /// Content hash (hex, see `DriftReport::algorithm`), if the file could
+    /// be hashed
+    pub checksum: Option<String>,
+}
+
+/// A file that differs between source and destination.
+#[derive(Debug, Clone, Serialize)]
+pub struct DriftEntry {
+    /// Path relative to the sync roots
+    pub path: String,
+    pub kind: DriftKind,
+    pub source: Option<FileState>,
+    pub dest: Option<FileState>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Machine-readable result of a mirror check.
+#[derive(Debug, Clone, Serialize)]
+pub struct DriftReport {
+    pub generated: DateTime<Utc>,
+    pub source: String,
+    pub dest: String,
+    /// Sampling percentage, or `None` for a full comparison
+    pub sample_percent: Option<u8>,
+    /// Algorithm of the content hashes
+    pub algorithm: ChecksumAlgorithm,
+    /// Files seen on either side
+    pub files_compared: u64,
+    /// Files whose contents were hashed on both sides
+    pub files_hashed: u64,
+    /// Files hashed and found identical
+    pub matched: u64,
+    pub drift: Vec<DriftEntry>,
+}
+
+impl DriftReport {
+    pub fn is_clean(&self) -> bool {
+        self.drift.is_empty()
+    }
+
+    /// Write the report as pretty-printed JSON, creating parent folders.
+    pub fn save(&self, path: &Path) -> Result<(), FluxError> {
+        if let Some(parent) = path.parent() {
+            if !parent.as_os_str().is_empty() {
+                std::fs::create_dir_all(parent)?;
+            }
+        }
+        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+/// Compare `source` and `dest` and list every drifted file.
+///
+/// `sample` limits hashing to that percentage of the files present on both
+/// sides with equal sizes; missing, extra and resized files are always found.
+/// Contents are compared with `algorithm`.
+pub fn check_mirror(
+    source: &Path,
+    dest: &Path,
+    filter: &TransferFilter,
+    sample: Option<u8>,
+    algorithm: ChecksumAlgorithm,
+    quiet: bool,
+) -> Result<DriftReport, FluxError> {
+    let source_files = list_files(source, filter);
+    let dest_files = list_files(dest, filter);
+    let paths: BTreeSet<&PathBuf> = source_files.keys().chain(dest_files.keys()).collect();
+
+    let hash_bytes: u64 = source_files
+        .iter()
+        .filter(|(p, size)| dest_files.get(*p) == Some(size))
+        .map(|(_, size)| size)
+        .sum();
+    let progress = create_transfer_progress(hash_bytes, quiet);
+
+    let mut report = DriftReport {
+        generated: Utc::now(),
+        source: source.display().to_string(),
+        dest: dest.display().to_string(),
+        sample_percent: sample,
+        algorithm,
+        files_compared: paths.len() as u64,
+        files_hashed: 0,
+        matched: 0,
+        drift: Vec::new(),
+    };
+
+    for relative in paths {
+        let src_path = source.join(relative);
+        let dst_path = dest.join(relative);
+        let drift = |kind, error: Option<String>| DriftEntry {
+            path: relative.to_string_lossy().replace('\\', "/"),
+            kind,
+            source: source_files
+                .contains_key(relative)
+                .then(|| file_state(&src_path, algorithm)),
+            dest: dest_files
+                .contains_key(relative)
+                .then(|| file_state(&dst_path, algorithm)),
+            error,
+        };
+
+        let (src_size, dst_size) = match (source_files.get(relative), dest_files.get(relative)) {
+            (Some(s), Some(d)) => (*s, *d),
+            (Some(_), None) => {
+                report.drift.push(drift(DriftKind::MissingInDest, None));
+                continue;
+            }
+            (None, _) => {
+                report.drift.push(drift(DriftKind::ExtraInDest, None));
+                continue;
+            }
+        };
+        if src_size != dst_size {
+            report.drift.push(drift(DriftKind::SizeMismatch, None));
+            continue;
+        }
+
+        progress.set_message(relative.to_string_lossy().to_string());
+        let sampled = match sample {
+            Some(pct) if pct < 100 => rand::random_range(0..100u8) < pct,
+            _ => true,
+        };
+        if sampled {
+            report.files_hashed += 1;
+            match (
+                hash_file_with(&src_path, algorithm),
+                hash_file_with(&dst_path, algorithm),
+            ) {
+                (Ok(a), Ok(b)) if a == b => report.matched += 1,
+                (Ok(_), Ok(_)) => report.drift.push(drift(DriftKind::ContentMismatch, None)),
+                (Err(e), _) | (_, Err(e)) => {
+                    report.drift.push(drift(DriftKind::Error, Some(e.to_string())))
+                }
+            }
+        }
+        progress.inc(src_size);
+    }
+    progress.finish_and_clear();
+    Ok(report)
+}
+
+/// Regular files under `root` that sync would consider, with their sizes.
+/// Temp files and the `--trash` folder are skipped.
+fn list_files(root: &Path, filter: &TransferFilter) -> BTreeMap<PathBuf, u64> {
+    let trash = root.join(TRASH_DIR);
+    WalkDir::new(root)
+        .follow_links(false)
+        .into_iter()
+        .filter_entry(|e| !filter.is_excluded_dir(e) && e.path() != trash)
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file() && filter.should_transfer(e.path()))
+        .filter_map(|e| {
+            let relative = e.path().strip_prefix(root).ok()?.to_path_buf();
+            let size = e.metadata().ok()?.len();
+            Some((relative, size))
+        })
+        .collect()
+}
+
+/// Size, mtime and hash of a file for the report.
+fn file_state(path: &Path, algorithm: ChecksumAlgorithm) -> FileState {
+    let meta = std::fs::metadata(path).ok();
+    FileState {
+        size: meta.as_ref().map_or(0, |m| m.len()),
+        modified: meta
+            .and_then(|m| m.modified().ok())
+            .map(DateTime::<Utc>::from),
+        checksum: hash_file_with(path, algorithm).ok(),
+    }
+}
+
+/// Run a mirror check, save its report and print a summary.
+///
+/// Fails with the number of drifted files if the mirror is not clean.
+pub fn run_mirror_check(
+    check: &MirrorCheck,
+    source: &Path,
+    dest: &Path,
+    filter: &TransferFilter,
+    quiet: bool,
+) -> Result<(), FluxError> {
+    if !quiet {
+        let mode = match check.sample {
+            Some(pct) => format!("sampling {}% of files", pct),
+            None => "full comparison".to_string(),
+        };
+        let mode = format!("{}, {}", mode, check.algorithm.label());
+        eprintln!(
+            "Verifying mirror ({}): {} <-> {}",
+            mode,
+            source.display(),
+            dest.display()
+        );
+    }
+    let report = check_mirror(source, dest, filter, check.sample, check.algorithm, quiet)?;
+    let path = match check.report {
+        Some(ref path) => path.clone(),
+        None => flux_data_dir()?
+            .join(REPORT_DIR)
+            .join(format!("{}.json", chrono::Local::now().format("%Y-%m-%d_%H%M%S"))),
+    };
+    report.save(&path)?;
+
+    if !quiet {
+        eprintln!(
+            "Mirror check: {} compared, {} hashed, {} matched, {} drifted",
+            report.files_compared,
+            report.files_hashed,
+            report.matched,
+            report.drift.len()
+        );
+        for entry in &report.drift {
+            eprintln!("  {:<16} {}", entry.kind.label(), entry.path);
+        }
+        eprintln!("Drift report: {}", path.display());
+    }
+
+    if report.is_clean() {
+        Ok(())
+    } else {
+        Err(FluxError::Differences(format!(
+            "Mirror drift: {} file(s) differ (see {})",
+            report.drift.len(),
+            path.display()
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const QUICK: ChecksumAlgorithm = ChecksumAlgorithm::Xxh3;
+
+    fn no_filter() -> TransferFilter {
+        TransferFilter::new(&[], &[]).unwrap()
+    }
+
+    fn write(root: &Path, name: &str, content: &str) {
+        let path = root.join(name);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, content).unwrap();
+    }
+
+    fn kinds(report: &DriftReport) -> Vec<(&str, DriftKind)> {
+        report
+            .drift
+            .iter()
+            .map(|d| (d.path.as_str(), d.kind))
+            .collect()
+    }
+
+    #[test]
+    fn reports_every_kind_of_drift() {
+        let dir = tempfile::tempdir().unwrap();
+        let (src, dst) = (dir.path().join("src"), dir.path().join("dst"));
+        write(&src, "same.txt", "same");
+        write(&dst, "same.txt", "same");
+        write(&src, "docs/rotted.txt", "aaaa");
+        write(&dst, "docs/rotted.txt", "bbbb");
+        write(&src, "grown.txt", "short");
+        write(&dst, "grown.txt", "much longer");
+        write(&src, "new.txt", "new");
+        write(&dst, "stale.txt", "old");
+        write(&dst, ".flux-trash/2026-01-01_100000/old.txt", "trash");
+
+        let report = check_mirror(&src, &dst, &no_filter(), None, QUICK, true).unwrap();
+        assert_eq!(
+            kinds(&report),
+            vec![
+                ("docs/rotted.txt", DriftKind::ContentMismatch),
+                ("grown.txt", DriftKind::SizeMismatch),
+                ("new.txt", DriftKind::MissingInDest),
+                ("stale.txt", DriftKind::ExtraInDest),
+            ]
+        );
+        assert_eq!(report.files_compared, 5);
+        assert_eq!((report.files_hashed, report.matched), (2, 1));
+
+        // Both sides of a content mismatch carry their hash and mtime
+        let rotted = &report.drift[0];
+        let (a, b) = (rotted.source.as_ref().unwrap(), rotted.dest.as_ref().unwrap());
+        assert_ne!(a.checksum, b.checksum);
+        assert!(a.modified.is_some() && b.modified.is_some());
+        assert!(report.drift[2].dest.is_none());
+    }
+
+    #[test]
+    fn full_sample_hashes_everything() {
+        let dir = tempfile::tempdir().unwrap();
+        let (src, dst) = (dir.path().join("src"), dir.path().join("dst"));
+        for i in 0..5 {
+            write(&src, &format!("{}.txt", i), "x");
+            write(&dst, &format!("{}.txt", i), "x");
+        }
+        let report = check_mirror(&src, &dst, &no_filter(), Some(100), QUICK, true).unwrap();
+        assert_eq!(report.files_hashed, 5);
+        assert!(report.is_clean());
+    }
+
+    #[test]
+    fn report_is_json_with_snake_case_kinds() {
+        let dir = tempfile::tempdir().unwrap();
+        let (src, dst) = (dir.path().join("src"), dir.path().join("dst"));
+        write(&src, "new.txt", "new");
+        std::fs::create_dir_all(&dst).unwrap();
+
+        let report = check_mirror(&src, &dst, &no_filter(), None, QUICK, true).unwrap();
+        let path = dir.path().join("reports/drift.json");
+        report.save(&path).unwrap();
+        let json: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(json["drift"][0]["kind"], "missing_in_dest");
+        assert_eq!(json["drift"][0]["path"], "new.txt");
+        assert_eq!(json["algorithm"], "xxh3");
+        assert!(json["drift"][0]["dest"].is_null());
+    }
+}
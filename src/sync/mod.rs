@@ -1,18 +1,56 @@
+pub mod backup;
+pub mod diff;
+#[cfg(feature = "watch")]
+pub mod drop_folder;
 pub mod engine;
+pub mod mirror;
+pub mod names;
 pub mod plan;
 pub mod schedule;
+pub mod stream;
+pub mod transaction;
+#[cfg(feature = "watch")]
 pub mod watch;
 
 use std::path::Path;
 
 use bytesize::ByteSize;
 
-use crate::cli::args::SyncArgs;
+use crate::backend::readonly;
+use crate::cli::args::{HookArgs, SyncArgs};
+use crate::config::aliases::{expand_variables, resolve_alias, AliasStore};
 use crate::error::FluxError;
+use crate::protocol::FluxPath;
+use crate::queue::history::ChangeSet;
+use crate::transfer::attrs::AttrCopier;
+use crate::transfer::checksum::ChecksumAlgorithm;
 use crate::transfer::filter::TransferFilter;
+use crate::transfer::history::{record_history, HistoryRecord};
+use crate::transfer::io_profile;
 use crate::transfer::stats::TransferStats;
 
-use self::engine::{compute_sync_plan, execute_sync_plan};
+use self::backup::{BackupLocation, BackupPolicy};
+use self::engine::{compute_sync_plan, execute_sync_plan, FileComparer};
+use self::mirror::{run_mirror_check, MirrorCheck};
+use self::names::NameMatching;
+use self::plan::SyncResult;
+
+/// How each pass of a watched or scheduled sync runs, from the `flux sync`
+/// flags.
+pub struct SyncOptions<'a> {
+    /// Remove destination files that are not in the source (`--delete`)
+    pub delete_orphans: bool,
+    /// Allow `--delete` with an empty source (`--force`)
+    pub force: bool,
+    pub quiet: bool,
+    /// Checksum every copy (`--verify`)
+    pub verify: Option<ChecksumAlgorithm>,
+    /// Write through temp files renamed into place (unless `--no-atomic`)
+    pub atomic: bool,
+    pub backup: Option<&'a BackupPolicy>,
+    pub hooks: &'a HookArgs,
+    pub attrs: &'a AttrCopier,
+}
 
 /// Entry point for the `flux sync` command.
 ///
@@ -20,8 +58,13 @@ use self::engine::{compute_sync_plan, execute_sync_plan};
 /// prints it (dry-run) or executes it. Dispatches to watch mode or
 /// schedule mode if the corresponding flags are set.
 pub fn execute_sync(args: SyncArgs, quiet: bool) -> Result<(), FluxError> {
-    let source = Path::new(&args.source);
-    let dest = Path::new(&args.dest);
+    // Scheduled syncs expand destination templates ({date}, ...) on each run
+    let alias_store = AliasStore::current();
+    let source_resolved = resolve_alias(&args.source, &alias_store);
+    let source = Path::new(&source_resolved);
+    let dest_template = resolve_alias(&args.dest, &alias_store);
+    let dest_expanded = expand_variables(&dest_template);
+    let dest = Path::new(&dest_expanded);
 
     // Validate source exists and is a directory
     if !source.exists() {
@@ -36,32 +79,125 @@ pub fn execute_sync(args: SyncArgs, quiet: bool) -> Result<(), FluxError> {
         )));
     }
 
-    // Create dest directory if it doesn't exist
-    if !dest.exists() {
+    #[cfg(feature = "watch")]
+    let (watch, via_queue) = (args.watch, args.via_queue);
+    #[cfg(not(feature = "watch"))]
+    let (watch, via_queue) = (false, false);
+    let local_dest = FluxPath::parse(&dest_expanded).is_local();
+
+    // --read-only: refuse writes under the source while the sync runs; a
+    // destination inside the source fails here, before anything is written
+    let _protection = args.read_only.then(|| readonly::protect(source)).transpose()?;
+    if local_dest {
+        readonly::check(dest, "write into")?;
+    }
+
+    // Create dest directory if it doesn't exist (queued remote destinations
+    // are left to the queue entries)
+    if args.schedule.is_none() && (local_dest || !via_queue) && !dest.exists() {
         std::fs::create_dir_all(dest)?;
     }
 
     // Validate --watch and --schedule are mutually exclusive
-    if args.watch && args.schedule.is_some() {
+    if watch && args.schedule.is_some() {
         return Err(FluxError::SyncError(
             "--watch and --schedule are mutually exclusive. Use one or the other.".to_string(),
         ));
     }
 
+    let backup = backup_policy(&args, dest)?;
+    // --verify guards integrity and defaults to BLAKE3; --compare checksum
+    // and the mirror check are comparisons and default to the faster XXH3
+    let verify = args
+        .verify
+        .then(|| args.checksum.unwrap_or(ChecksumAlgorithm::Blake3));
+    let mut compare = FileComparer::new(
+        args.compare,
+        args.checksum.unwrap_or(ChecksumAlgorithm::Xxh3),
+    )
+    .hard_links(args.hard_links)
+    .names(NameMatching {
+        case_insensitive: args.case_insensitive,
+        normalization: args.normalize,
+    });
+    let mirror = args.verify_mirror.then(|| MirrorCheck {
+        sample: args.sample,
+        report: args.report.clone(),
+        algorithm: args.checksum.unwrap_or(ChecksumAlgorithm::Xxh3),
+    });
+    if args.transactional && watch {
+        return Err(FluxError::SyncError(
+            "--transactional cannot be used with --watch".to_string(),
+        ));
+    }
+    if args.low_memory && watch {
+        return Err(FluxError::SyncError(
+            "--low-memory cannot be used with --watch".to_string(),
+        ));
+    }
+    if mirror.is_some() && watch {
+        return Err(FluxError::SyncError(
+            "--verify-mirror cannot be used with --watch. Use --schedule for periodic checks."
+                .to_string(),
+        ));
+    }
+
     // Build filter from --exclude/--include patterns
-    let filter = TransferFilter::new(&args.exclude, &args.include)?;
+    let flux_config = crate::config::types::load_config().unwrap_or_default();
+    let exclude_hidden = flux_config.exclude_hidden;
+    let filter = TransferFilter::new(&args.exclude, &args.include)?
+        .skip_hidden(args.hidden.skip_hidden(exclude_hidden))
+        .skip_system(!args.hidden.include_system);
 
-    // Dispatch to watch mode
-    if args.watch {
-        return watch::watch_and_sync(
-            source,
-            dest,
-            &filter,
-            args.delete,
-            quiet,
-            args.verify,
-            args.force,
-        );
+    // Buffers and read-ahead for the storage at both ends
+    let profile = io_profile::resolve(
+        args.io_profile.unwrap_or(flux_config.io_profile),
+        [Some(source), Some(dest)],
+    );
+    io_profile::apply(&profile.tuning());
+    tracing::debug!("I/O profile: {}", profile);
+
+    let attr_options = args.attrs.options();
+    if via_queue && attr_options.any() {
+        tracing::warn!("--xattrs and --acls do not apply to queued copies; ignoring them");
+    }
+    if via_queue && args.read_only {
+        tracing::warn!("--read-only covers the watcher, not the copies `flux daemon` runs");
+    }
+    let attrs = AttrCopier::new(attr_options);
+    let options = SyncOptions {
+        delete_orphans: args.delete,
+        force: args.force,
+        quiet,
+        verify,
+        atomic: !args.no_atomic,
+        backup: backup.as_ref(),
+        hooks: &args.hooks,
+        attrs: &attrs,
+    };
+
+    // Dispatch to watch mode, queuing changes for `flux daemon` with --via-queue
+    #[cfg(feature = "watch")]
+    if via_queue {
+        // Entries run later from the daemon's working directory
+        let queue_dest = if local_dest {
+            std::path::absolute(&dest_template)?.display().to_string()
+        } else {
+            dest_template.clone()
+        };
+        let target = watch::QueueTarget {
+            data_dir: crate::config::paths::flux_data_dir()?,
+            root: std::path::absolute(source)?,
+            dest: queue_dest,
+            verify: args.verify,
+            class: args.queue_class.unwrap_or_default(),
+        };
+        let dest_dir = local_dest.then_some(dest);
+        return watch::watch_and_enqueue(&target, dest_dir, &filter, compare, quiet);
+    }
+    #[cfg(feature = "watch")]
+    if watch {
+        return watch::watch_and_sync(source, dest, &filter, compare, &options);
     }
 
     // Dispatch to schedule mode
@@ -69,43 +205,102 @@ pub fn execute_sync(args: SyncArgs, quiet: bool) -> Result<(), FluxError> {
         return schedule::scheduled_sync(
             cron_expr,
             source,
-            dest,
+            Path::new(&dest_template),
             &filter,
-            args.delete,
-            quiet,
-            args.verify,
-            args.force,
+            compare,
+            &options,
+            mirror.as_ref(),
         );
     }
 
-    // Compute the sync plan
-    let plan = compute_sync_plan(source, dest, &filter, args.delete, args.force)?;
+    // --mirror-only: compare without syncing first
+    if args.mirror_only {
+        if let Some(ref check) = mirror {
+            return run_mirror_check(check, source, dest, &filter, quiet);
+        }
+    }
 
-    if args.dry_run {
-        // Print the plan without executing
-        plan.print_summary();
-        return Ok(());
+    // --low-memory: plan and copy in one walk, keeping only the counts
+    if args.low_memory {
+        if args.dry_run {
+            stream::dry_run(source, dest, &filter, &mut compare, args.delete, args.force)?;
+            print_backup_root(backup.as_ref(), dest);
+            return Ok(());
+        }
+        stream::check_dest(source, dest)?;
     }
+    let mut sync_start = std::time::Instant::now();
+    let (plan, changes, result) = if args.low_memory {
+        let (plan, changes, result) =
+            stream::run_while_walking(source, dest, &filter, &mut compare, &options);
+        if result.is_ok() && !plan.has_changes() {
+            if !quiet {
+                eprintln!("Already in sync. Nothing to do.");
+            }
+            if let Some(ref check) = mirror {
+                return run_mirror_check(check, source, dest, &filter, quiet);
+            }
+            return Ok(());
+        }
+        (plan, changes, result)
+    } else {
+        // Compute the sync plan
+        let plan = compute_sync_plan(source, dest, &filter, &mut compare, args.delete, args.force)?;
+
+        if args.dry_run {
+            // Print the plan without executing
+            plan.print_summary();
+            print_backup_root(backup.as_ref(), dest);
+            return Ok(());
+        }
 
-    if !plan.has_changes() {
         if !quiet {
-            eprintln!("Already in sync. Nothing to do.");
+            plan.print_collisions();
+        }
+
+        if !plan.has_changes() {
+            if !quiet {
+                eprintln!("Already in sync. Nothing to do.");
+            }
+            if let Some(ref check) = mirror {
+                return run_mirror_check(check, source, dest, &filter, quiet);
+            }
+            return Ok(());
         }
-        return Ok(());
-    }
 
-    // Execute the plan
-    let sync_start = std::time::Instant::now();
-    let total_files = plan.files_to_copy + plan.files_to_update + plan.files_to_delete;
-    let result = execute_sync_plan(&plan, quiet, args.verify)?;
+        // Execute the plan
+        sync_start = std::time::Instant::now();
+        let result = if args.transactional {
+            let algorithm = args.checksum.unwrap_or(ChecksumAlgorithm::Blake3);
+            transaction::execute_transactional(&plan, source, dest, algorithm, quiet, &attrs)
+        } else {
+            let backup_run = backup.as_ref().map(|b| b.for_dest(dest));
+            execute_sync_plan(
+                &plan,
+                quiet,
+                verify,
+                !args.no_atomic,
+                backup_run,
+                Some((source, dest)),
+                &attrs,
+            )
+        };
+        let changes = plan.changes(dest);
+        (plan, changes, result)
+    };
+    let verified = args.verify || args.transactional;
+    record_sync(source, dest, changes, sync_start, &result, verified, &args.hooks);
+    let result = result?;
+
+    let mut stats = TransferStats::new(plan.files(), plan.total_copy_bytes);
+    stats.started = sync_start;
+    stats.bytes_done = result.bytes_transferred;
+    stats.files_done =
+        result.files_copied + result.files_updated + result.files_linked + result.files_deleted;
+    stats.files_skipped = result.files_skipped;
 
     // Print summary with throughput
     if !quiet {
-        let mut stats = TransferStats::new(total_files, plan.total_copy_bytes);
-        stats.started = sync_start;
-        stats.bytes_done = result.bytes_transferred;
-        stats.files_done = result.files_copied + result.files_updated + result.files_deleted;
-        stats.files_skipped = result.files_skipped;
         let throughput = ByteSize(stats.throughput_bps());
 
         eprintln!(
@@ -118,7 +313,102 @@ pub fn execute_sync(args: SyncArgs, quiet: bool) -> Result<(), FluxError> {
             stats.elapsed().as_secs_f64(),
             throughput,
         );
+        if result.files_linked > 0 {
+            eprintln!("Recreated {} hard link(s)", result.files_linked);
+        }
+    }
+    if args.stats {
+        stats.peak_bps = crate::transfer::status::take_peak_rate();
+        stats.print_stats();
+    }
+
+    if let Some(ref check) = mirror {
+        run_mirror_check(check, source, dest, &filter, quiet)?;
     }
 
     Ok(())
 }
+
+/// Tell a dry run where replaced and deleted files would go.
+fn print_backup_root(backup: Option<&BackupPolicy>, dest: &Path) {
+    if let Some(backup) = backup {
+        eprintln!(
+            "  Replaced and deleted files go to {}",
+            backup.root(dest).display()
+        );
+    }
+}
+
+/// Backup settings from `--backup-dir`/`--trash`/`--backup-keep`.
+fn backup_policy(args: &SyncArgs, dest: &Path) -> Result<Option<BackupPolicy>, FluxError> {
+    let location = match (&args.backup_dir, args.trash) {
+        (Some(dir), _) => BackupLocation::Dir(std::path::PathBuf::from(dir)),
+        (None, true) => BackupLocation::Trash,
+        (None, false) => {
+            if args.backup_keep.is_some() {
+                return Err(FluxError::SyncError(
+                    "--backup-keep requires --backup-dir or --trash".to_string(),
+                ));
+            }
+            return Ok(None);
+        }
+    };
+    if args.backup_keep == Some(0) {
+        return Err(FluxError::SyncError(
+            "--backup-keep must be at least 1".to_string(),
+        ));
+    }
+    // A backup folder inside the destination would itself be synced over;
+    // --trash is the one location the sync plan knows to leave alone
+    if let BackupLocation::Dir(ref dir) = location {
+        let canonical = |p: &Path| {
+            p.canonicalize()
+                .or_else(|_| std::path::absolute(p))
+                .unwrap_or_else(|_| p.to_path_buf())
+        };
+        if args.schedule.is_none() && canonical(dir).starts_with(canonical(dest)) {
+            return Err(FluxError::SyncError(format!(
+                "Backup directory '{}' is inside the destination. Choose another directory or use --trash.",
+                dir.display()
+            )));
+        }
+    }
+    Ok(Some(BackupPolicy {
+        location,
+        keep: args.backup_keep,
+    }))
+}
+
+/// Record a sync pass in history through the central transfer hook.
+///
+/// Used by one-shot, watch and scheduled syncs alike. Passes with no changes
+/// are not recorded. The entry lists the files the plan touched
+/// (`SyncPlan::changes`), for `flux history diff`; for a failed pass that
+/// list may include files the run never reached.
+pub(crate) fn record_sync(
+    source: &Path,
+    dest: &Path,
+    changes: ChangeSet,
+    started: std::time::Instant,
+    result: &Result<SyncResult, FluxError>,
+    verify: bool,
+    hooks: &HookArgs,
+) {
+    let mut record = HistoryRecord::new(
+        "sync",
+        &source.display().to_string(),
+        &dest.display().to_string(),
+    );
+    record.started = started;
+    record.hooks = hooks.clone();
+    record.changes = Some(changes);
+    match result {
+        Ok(r) => {
+            record.bytes = r.bytes_transferred;
+            record.files = r.files_copied + r.files_updated + r.files_linked + r.files_deleted;
+            record.verified = verify.then_some(true);
+            record_history(&record, None);
+        }
+        Err(e) => record_history(&record, Some(e)),
+    }
+}
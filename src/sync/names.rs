@@ -0,0 +1,185 @@
+//! Name matching between filesystems that compare names differently
+//! (`flux sync --case-insensitive`, `--normalize`).
+//!
+//! macOS keeps names decomposed (NFD) and ignores case; Linux compares the
+//! bytes. So `é` composed and decomposed, or `Photo.JPG` and `photo.jpg`,
+//! are different files on one side and the same file on the other: a plain
+//! sync copies a second file next to the first and, with `--delete`,
+//! removes the "orphan". With a `NameMatching` the planner pairs names by
+//! `key` instead, and reports names that clash within one tree.
+
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+
+use unicode_normalization::UnicodeNormalization;
+
+/// Unicode normalization form (`--normalize`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Normalization {
+    /// Composed, as Linux and Windows tools usually write names
+    Nfc,
+    /// Decomposed, as HFS+ stores names
+    Nfd,
+}
+
+/// How the sync planner matches source names to destination names.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct NameMatching {
+    /// Names differing only in case are the same file
+    pub case_insensitive: bool,
+    /// Names differing only in normalization are the same file; new names
+    /// are written in this form
+    pub normalization: Option<Normalization>,
+}
+
+impl NameMatching {
+    /// Whether names are matched other than byte for byte.
+    pub fn is_active(&self) -> bool {
+        self.case_insensitive || self.normalization.is_some()
+    }
+
+    /// The comparison key of a relative path.
+    pub fn key(&self, relative: &Path) -> String {
+        let joined = relative
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        let normalized = match self.normalization {
+            Some(_) => joined.nfc().collect(),
+            None => joined,
+        };
+        if self.case_insensitive {
+            normalized.to_lowercase()
+        } else {
+            normalized
+        }
+    }
+
+    /// A new name, in the configured normalization form.
+    fn convert(&self, name: &OsStr) -> PathBuf {
+        match (self.normalization, name.to_str()) {
+            (Some(Normalization::Nfc), Some(name)) => PathBuf::from(name.nfc().collect::<String>()),
+            (Some(Normalization::Nfd), Some(name)) => PathBuf::from(name.nfd().collect::<String>()),
+            _ => PathBuf::from(name),
+        }
+    }
+}
+
+/// Relative paths of one tree by key.
+#[derive(Debug, Default)]
+pub struct NameIndex {
+    matching: NameMatching,
+    names: HashMap<String, PathBuf>,
+}
+
+impl NameIndex {
+    pub fn new(matching: NameMatching) -> Self {
+        Self {
+            matching,
+            names: HashMap::new(),
+        }
+    }
+
+    /// Add a relative path. Returns the path added before it under the same
+    /// key, if any (a collision); the first path keeps the key.
+    pub fn insert(&mut self, relative: &Path) -> Option<PathBuf> {
+        match self.names.entry(self.matching.key(relative)) {
+            Entry::Occupied(existing) => {
+                (existing.get() != relative).then(|| existing.get().clone())
+            }
+            Entry::Vacant(slot) => {
+                slot.insert(relative.to_path_buf());
+                None
+            }
+        }
+    }
+
+    /// Whether a path with the same key as `relative` was added.
+    pub fn contains(&self, relative: &Path) -> bool {
+        self.names.contains_key(&self.matching.key(relative))
+    }
+
+    /// The destination path for the source path `relative`.
+    ///
+    /// Each leading part that matches an existing name takes that name;
+    /// the rest are new names in the configured form, and are added so that
+    /// later files under them use the same spelling.
+    pub fn resolve(&mut self, relative: &Path) -> PathBuf {
+        let mut prefix = PathBuf::new();
+        let mut resolved = PathBuf::new();
+        for component in relative.components() {
+            prefix.push(component);
+            let key = self.matching.key(&prefix);
+            match self.names.get(&key) {
+                Some(existing) => resolved = existing.clone(),
+                None => {
+                    resolved.push(self.matching.convert(component.as_os_str()));
+                    self.names.insert(key, resolved.clone());
+                }
+            }
+        }
+        resolved
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NFC: &str = "caf\u{e9}.txt";
+    const NFD: &str = "cafe\u{301}.txt";
+
+    fn matching(case_insensitive: bool, normalization: Option<Normalization>) -> NameMatching {
+        NameMatching {
+            case_insensitive,
+            normalization,
+        }
+    }
+
+    #[test]
+    fn keys_ignore_case_and_form_only_when_asked() {
+        let plain = NameMatching::default();
+        assert!(!plain.is_active());
+        assert_ne!(plain.key(Path::new(NFC)), plain.key(Path::new(NFD)));
+
+        let forms = matching(false, Some(Normalization::Nfd));
+        assert_eq!(forms.key(Path::new(NFC)), forms.key(Path::new(NFD)));
+        assert_ne!(forms.key(Path::new("A.txt")), forms.key(Path::new("a.txt")));
+
+        let case = matching(true, None);
+        assert_eq!(case.key(Path::new("Docs/A.TXT")), case.key(Path::new("docs/a.txt")));
+    }
+
+    #[test]
+    fn insert_reports_collisions() {
+        let mut index = NameIndex::new(matching(true, None));
+        assert_eq!(index.insert(Path::new("README.md")), None);
+        assert_eq!(index.insert(Path::new("README.md")), None);
+        assert_eq!(
+            index.insert(Path::new("readme.md")),
+            Some(PathBuf::from("README.md"))
+        );
+        assert!(index.contains(Path::new("Readme.MD")));
+    }
+
+    #[test]
+    fn resolve_reuses_existing_names_and_converts_new_ones() {
+        let mut index = NameIndex::new(matching(true, Some(Normalization::Nfc)));
+        index.insert(Path::new("Photos"));
+        index.insert(Path::new("Photos/IMG_1.JPG"));
+
+        assert_eq!(
+            index.resolve(Path::new("photos/img_1.jpg")),
+            Path::new("Photos/IMG_1.JPG")
+        );
+        assert_eq!(
+            index.resolve(&Path::new("photos/new").join(NFD)),
+            Path::new("Photos/new").join(NFC)
+        );
+        // Later files under a new folder use the spelling chosen for it
+        assert_eq!(index.resolve(Path::new("PHOTOS/NEW/x")), Path::new("Photos/new/x"));
+    }
+}
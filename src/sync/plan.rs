@@ -1,7 +1,11 @@
 use std::fmt;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use bytesize::ByteSize;
+#[cfg(feature = "net")]
+use serde::{Deserialize, Serialize};
+
+use crate::queue::history::ChangeSet;
 
 /// An individual action determined by comparing source and dest trees.
 #[derive(Debug, Clone)]
@@ -19,6 +23,14 @@ pub enum SyncAction {
         src_size: u64,
         dest_size: u64,
     },
+    /// File is another name of a file synced before it -- hard-link dest to
+    /// `target`, that file's destination (only with --hard-links).
+    Link {
+        src: PathBuf,
+        dest: PathBuf,
+        target: PathBuf,
+        size: u64,
+    },
     /// File exists in dest but not source -- delete it (only with --delete).
     DeleteOrphan {
         path: PathBuf,
@@ -45,6 +57,9 @@ impl fmt::Display for SyncAction {
             SyncAction::UpdateChanged { dest, .. } => {
                 write!(f, "  UPDATE  {} (changed)", dest.display())
             }
+            SyncAction::Link { dest, target, .. } => {
+                write!(f, "  LINK    {} -> {}", dest.display(), target.display())
+            }
             SyncAction::DeleteOrphan { path, .. } => {
                 write!(f, "  DELETE  {}", path.display())
             }
@@ -55,58 +70,108 @@ impl fmt::Display for SyncAction {
     }
 }
 
+/// Two names in one tree that are the same name under `--case-insensitive`
+/// or `--normalize`.
+#[derive(Debug, Clone)]
+pub struct NameCollision {
+    /// "source" or "destination"
+    pub side: &'static str,
+    /// The name that is synced (source) or matched (destination)
+    pub kept: PathBuf,
+    /// The name that is skipped (source) or left alone (destination)
+    pub other: PathBuf,
+}
+
+impl fmt::Display for NameCollision {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let outcome = if self.side == "source" {
+            "skipped"
+        } else {
+            "left alone"
+        };
+        f.pad(&format!(
+            "  {}: {} is the same name as {}, {}",
+            self.side,
+            self.other.display(),
+            self.kept.display(),
+            outcome
+        ))
+    }
+}
+
 /// A computed sync plan: a list of actions with summary counts.
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct SyncPlan {
     pub actions: Vec<SyncAction>,
     pub total_copy_bytes: u64,
     pub files_to_copy: u64,
     pub files_to_update: u64,
+    pub files_to_link: u64,
     pub files_to_delete: u64,
     pub files_to_skip: u64,
+    /// Clashing names found while matching names (see `sync::names`)
+    pub collisions: Vec<NameCollision>,
 }
 
 impl SyncPlan {
     /// Build a SyncPlan from a list of actions, computing summary counts.
     pub fn from_actions(actions: Vec<SyncAction>) -> Self {
-        let mut total_copy_bytes = 0u64;
-        let mut files_to_copy = 0u64;
-        let mut files_to_update = 0u64;
-        let mut files_to_delete = 0u64;
-        let mut files_to_skip = 0u64;
-
+        let mut plan = Self::default();
         for action in &actions {
-            match action {
-                SyncAction::CopyNew { size, .. } => {
-                    files_to_copy += 1;
-                    total_copy_bytes += size;
-                }
-                SyncAction::UpdateChanged { src_size, .. } => {
-                    files_to_update += 1;
-                    total_copy_bytes += src_size;
-                }
-                SyncAction::DeleteOrphan { .. } => {
-                    files_to_delete += 1;
-                }
-                SyncAction::Skip { .. } => {
-                    files_to_skip += 1;
-                }
-            }
+            plan.tally(action);
         }
+        plan.actions = actions;
+        plan
+    }
 
-        Self {
-            actions,
-            total_copy_bytes,
-            files_to_copy,
-            files_to_update,
-            files_to_delete,
-            files_to_skip,
+    /// Add `action` to the summary counts (not to `actions`).
+    pub fn tally(&mut self, action: &SyncAction) {
+        match action {
+            SyncAction::CopyNew { size, .. } => {
+                self.files_to_copy += 1;
+                self.total_copy_bytes += size;
+            }
+            SyncAction::UpdateChanged { src_size, .. } => {
+                self.files_to_update += 1;
+                self.total_copy_bytes += src_size;
+            }
+            SyncAction::Link { .. } => {
+                self.files_to_link += 1;
+            }
+            SyncAction::DeleteOrphan { .. } => {
+                self.files_to_delete += 1;
+            }
+            SyncAction::Skip { .. } => {
+                self.files_to_skip += 1;
+            }
         }
     }
 
+    /// Files the plan covers, unchanged ones included.
+    pub fn files(&self) -> u64 {
+        self.files_to_copy
+            + self.files_to_update
+            + self.files_to_link
+            + self.files_to_delete
+            + self.files_to_skip
+    }
+
     /// Returns true if the plan contains any action that isn't Skip.
     pub fn has_changes(&self) -> bool {
-        self.files_to_copy > 0 || self.files_to_update > 0 || self.files_to_delete > 0
+        self.files_to_copy > 0
+            || self.files_to_update > 0
+            || self.files_to_link > 0
+            || self.files_to_delete > 0
+    }
+
+    /// Paths this plan copies, updates and deletes, relative to `dest_root`,
+    /// for the run's history entry. Links count as copied.
+    pub fn changes(&self, dest_root: &Path) -> ChangeSet {
+        let mut changes = ChangeSet::default();
+        for action in &self.actions {
+            note_change(&mut changes, dest_root, action);
+        }
+        changes
     }
 
     /// Print a human-readable summary of the plan to stderr.
@@ -115,14 +180,89 @@ impl SyncPlan {
         for action in &self.actions {
             eprintln!("{}", action);
         }
+        self.print_totals();
+    }
+
+    /// Print the counts and the collisions below the plan's lines.
+    pub fn print_totals(&self) {
         eprintln!();
         eprintln!(
             "  {} to copy, {} to update, {} to delete, {} unchanged",
             self.files_to_copy, self.files_to_update, self.files_to_delete, self.files_to_skip
         );
+        if self.files_to_link > 0 {
+            eprintln!("  {} to hard-link", self.files_to_link);
+        }
         if self.total_copy_bytes > 0 {
             eprintln!("  Total transfer: {}", ByteSize(self.total_copy_bytes));
         }
+        self.print_collisions();
+    }
+
+    /// Print the name collisions, if any, to stderr.
+    pub fn print_collisions(&self) {
+        if self.collisions.is_empty() {
+            return;
+        }
+        eprintln!("  {} name collision(s):", self.collisions.len());
+        for collision in &self.collisions {
+            eprintln!("{}", collision);
+        }
+    }
+}
+
+/// Add what `action` changes in `dest_root` to `changes`. Links count as
+/// copied.
+pub fn note_change(changes: &mut ChangeSet, dest_root: &Path, action: &SyncAction) {
+    let relative = |path: &Path| {
+        let path = path.strip_prefix(dest_root).unwrap_or(path);
+        path.components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/")
+    };
+    match action {
+        SyncAction::CopyNew { dest, .. } | SyncAction::Link { dest, .. } => {
+            changes.copied.push(relative(dest))
+        }
+        SyncAction::UpdateChanged { dest, .. } => changes.updated.push(relative(dest)),
+        SyncAction::DeleteOrphan { path, .. } => changes.deleted.push(relative(path)),
+        SyncAction::Skip { .. } => {}
+    }
+}
+
+/// A file of a tree that is not on this machine, listed by its path
+/// relative to the tree's root (`/`-separated), for plans between a local
+/// and a remote tree (`flux push`, `flux pull`).
+#[cfg(feature = "net")]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ListedFile {
+    pub path: String,
+    pub size: u64,
+    /// Modification time in seconds since the Unix epoch
+    pub modified: Option<i64>,
+}
+
+#[cfg(feature = "net")]
+impl ListedFile {
+    /// The listing entry for a local file at `path` below its root.
+    pub fn new(path: String, meta: &std::fs::Metadata) -> Self {
+        let modified = meta
+            .modified()
+            .ok()
+            .and_then(|time| time.duration_since(std::time::UNIX_EPOCH).ok())
+            .map(|since| since.as_secs() as i64);
+        Self {
+            path,
+            size: meta.len(),
+            modified,
+        }
+    }
+
+    /// The modification time, if known and after the epoch.
+    pub fn modified_time(&self) -> Option<std::time::SystemTime> {
+        let secs = u64::try_from(self.modified?).ok()?;
+        Some(std::time::UNIX_EPOCH + std::time::Duration::from_secs(secs))
     }
 }
 
@@ -131,6 +271,8 @@ impl SyncPlan {
 pub struct SyncResult {
     pub files_copied: u64,
     pub files_updated: u64,
+    /// Files hard-linked to another synced file (`--hard-links`)
+    pub files_linked: u64,
     pub files_deleted: u64,
     pub files_skipped: u64,
     pub bytes_transferred: u64,
@@ -171,12 +313,36 @@ mod tests {
 
         let plan = SyncPlan::from_actions(actions);
         assert_eq!(plan.files_to_copy, 1);
+        assert_eq!(plan.files_to_link, 0);
         assert_eq!(plan.files_to_update, 1);
         assert_eq!(plan.files_to_delete, 1);
         assert_eq!(plan.files_to_skip, 2);
         assert_eq!(plan.total_copy_bytes, 300); // 100 + 200
     }
 
+    #[test]
+    fn test_sync_plan_changes_are_relative_to_dest() {
+        let actions = vec![
+            SyncAction::CopyNew {
+                src: PathBuf::from("src/a.txt"),
+                dest: PathBuf::from("dest/sub/a.txt"),
+                size: 1,
+            },
+            SyncAction::DeleteOrphan {
+                path: PathBuf::from("dest/old.txt"),
+                size: 1,
+            },
+            SyncAction::Skip {
+                path: PathBuf::from("dest/same.txt"),
+                reason: "unchanged",
+            },
+        ];
+        let changes = SyncPlan::from_actions(actions).changes(Path::new("dest"));
+        assert_eq!(changes.copied.paths, vec!["sub/a.txt"]);
+        assert_eq!(changes.deleted.paths, vec!["old.txt"]);
+        assert!(changes.updated.is_empty());
+    }
+
     #[test]
     fn test_sync_plan_has_changes_true() {
         let actions = vec![SyncAction::CopyNew {
@@ -225,6 +391,16 @@ mod tests {
         assert!(display.contains("UPDATE"));
         assert!(display.contains("changed"));
 
+        let link = SyncAction::Link {
+            src: PathBuf::from("src/b.txt"),
+            dest: PathBuf::from("dest/b.txt"),
+            target: PathBuf::from("dest/a.txt"),
+            size: 50,
+        };
+        let display = format!("{}", link);
+        assert!(display.contains("LINK"));
+        assert!(display.contains("a.txt"));
+
         let delete = SyncAction::DeleteOrphan {
             path: PathBuf::from("dest/old.txt"),
             size: 50,
@@ -1,4 +1,4 @@
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 use std::time::Duration;
 
@@ -6,10 +6,14 @@ use bytesize::ByteSize;
 use chrono::Utc;
 use cron::Schedule;
 
+use crate::config::aliases::expand_variables;
 use crate::error::FluxError;
+use crate::transfer::cancel;
 use crate::transfer::filter::TransferFilter;
 
-use super::engine::{compute_sync_plan, execute_sync_plan};
+use super::engine::{compute_sync_plan, execute_sync_plan, FileComparer};
+use super::mirror::{run_mirror_check, MirrorCheck};
+use super::SyncOptions;
 
 /// Normalize a cron expression to 6+ field format expected by the `cron` crate.
 ///
@@ -32,16 +36,22 @@ fn normalize_cron_expression(expr: &str) -> String {
 /// Parses the cron expression, enters a tokio-based async loop that
 /// calculates the next occurrence, sleeps until then, and runs sync.
 /// Runs forever until Ctrl+C.
+///
+/// `dest` may be a template: variables such as `{date}` are expanded at the
+/// start of every run, and the resulting directory is created if needed.
+///
+/// With `mirror`, every run ends with a mirror check, even when there was
+/// nothing to sync. Drift is reported as a warning and the schedule goes on.
 pub fn scheduled_sync(
     cron_expr: &str,
     source: &Path,
     dest: &Path,
     filter: &TransferFilter,
-    delete_orphans: bool,
-    quiet: bool,
-    verify: bool,
-    force: bool,
+    mut compare: FileComparer,
+    options: &SyncOptions,
+    mirror: Option<&MirrorCheck>,
 ) -> Result<(), FluxError> {
+    let quiet = options.quiet;
     let normalized = normalize_cron_expression(cron_expr);
 
     let schedule = Schedule::from_str(&normalized).map_err(|e| {
@@ -69,20 +79,52 @@ pub fn scheduled_sync(
                 next.format("%Y-%m-%d %H:%M:%S UTC")
             );
 
-            tokio::time::sleep(duration).await;
+            // Ctrl+C while waiting for the next run stops the schedule
+            tokio::select! {
+                _ = tokio::time::sleep(duration) => {}
+                _ = cancel::cancelled() => return Ok(()),
+            }
 
             // Run sync
-            let plan = compute_sync_plan(source, dest, filter, delete_orphans, force)?;
+            let run_dest = PathBuf::from(expand_variables(&dest.to_string_lossy()));
+            if !run_dest.exists() {
+                std::fs::create_dir_all(&run_dest)?;
+            }
+            let dest = run_dest.as_path();
+            let plan = compute_sync_plan(
+                source,
+                dest,
+                filter,
+                &mut compare,
+                options.delete_orphans,
+                options.force,
+            )?;
 
             if !plan.has_changes() {
                 if !quiet {
                     let timestamp = chrono::Local::now().format("%H:%M:%S");
                     eprintln!("[{}] Already in sync. Nothing to do.", timestamp);
                 }
+                check_mirror_after_run(mirror, source, dest, filter, quiet);
                 continue;
             }
 
-            let result = execute_sync_plan(&plan, quiet, verify)?;
+            let started = std::time::Instant::now();
+            let backup_run = options.backup.map(|b| b.for_dest(dest));
+            let status = Some((source, dest));
+            let result = execute_sync_plan(
+                &plan,
+                quiet,
+                options.verify,
+                options.atomic,
+                backup_run,
+                status,
+                options.attrs,
+            );
+            let verified = options.verify.is_some();
+            let changes = plan.changes(dest);
+            super::record_sync(source, dest, changes, started, &result, verified, options.hooks);
+            let result = result?;
 
             if !quiet {
                 let timestamp = chrono::Local::now().format("%H:%M:%S");
@@ -96,13 +138,32 @@ pub fn scheduled_sync(
                     ByteSize(result.bytes_transferred),
                 );
             }
+            check_mirror_after_run(mirror, source, dest, filter, quiet);
         }
     })
 }
 
+/// Run the optional mirror check after a scheduled run. Failures are
+/// printed rather than returned so one drifted run doesn't stop the schedule.
+fn check_mirror_after_run(
+    mirror: Option<&MirrorCheck>,
+    source: &Path,
+    dest: &Path,
+    filter: &TransferFilter,
+    quiet: bool,
+) {
+    if let Some(check) = mirror {
+        if let Err(e) = run_mirror_check(check, source, dest, filter, quiet) {
+            eprintln!("Warning: {}", e);
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::cli::args::HookArgs;
+    use crate::transfer::attrs::AttrCopier;
 
     #[test]
     fn test_cron_expression_parsing_valid() {
@@ -167,15 +228,26 @@ mod tests {
         std::fs::create_dir_all(&dest).unwrap();
 
         let filter = TransferFilter::new(&[], &[]).unwrap();
+        let hooks = HookArgs::default();
+        let attrs = AttrCopier::default();
+        let options = SyncOptions {
+            delete_orphans: false,
+            force: false,
+            quiet: true,
+            verify: None,
+            atomic: true,
+            backup: None,
+            hooks: &hooks,
+            attrs: &attrs,
+        };
         let result = scheduled_sync(
             "not valid",
             &source,
             &dest,
             &filter,
-            false,
-            true,
-            false,
-            false,
+            FileComparer::default(),
+            &options,
+            None,
         );
         assert!(result.is_err());
         let err_msg = format!("{}", result.unwrap_err());
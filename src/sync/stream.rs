@@ -0,0 +1,322 @@
+//! `sync --low-memory`: plan and run a sync while walking the trees.
+//!
+//! `compute_sync_plan` keeps an action for every file until the walk is
+//! done, which for millions of files is gigabytes before the first copy.
+//! Here each action goes to an `ActionRunner` as soon as `walk_sync_plan`
+//! decides it, so memory stays flat: only the plan's counts and the capped
+//! `ChangeSet` for history are kept. Progress totals grow as files are
+//! found.
+//!
+//! A dry run needs the whole listing, and only once the walk has succeeded
+//! (with `--delete` it still fails at the end on an empty source). Its lines
+//! are written to a spill file in the temp directory, printed after the
+//! walk, and the file is removed.
+//!
+//! Name matching (`--case-insensitive`, `--normalize`) indexes whole trees
+//! and `--transactional` stages the whole plan, so the arguments rule them
+//! out with `--low-memory`, as they do `--schedule`; `--watch` is refused
+//! in `execute_sync`.
+
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use crate::error::FluxError;
+use crate::progress::bar::BatchProgress;
+use crate::queue::history::ChangeSet;
+use crate::transfer::cancel;
+use crate::transfer::filter::TransferFilter;
+use crate::transfer::status::StatusPublisher;
+
+use super::engine::{walk_sync_plan, ActionRunner, FileComparer};
+use super::plan::{note_change, SyncAction, SyncPlan, SyncResult};
+use super::SyncOptions;
+
+/// Refuse a destination inside the source: files copied there would be
+/// walked again. (Planning first sees the tree as it was.)
+pub(super) fn check_dest(source: &Path, dest: &Path) -> Result<(), FluxError> {
+    let canonical = |p: &Path| p.canonicalize().unwrap_or_else(|_| p.to_path_buf());
+    if canonical(dest).starts_with(canonical(source)) {
+        return Err(FluxError::SyncError(format!(
+            "Destination '{}' is inside the source, which --low-memory copies from while walking it",
+            dest.display()
+        )));
+    }
+    Ok(())
+}
+
+/// Sync `source` to `dest`, running each action as it is planned. Returns
+/// the plan's counts (no actions), what changed for the history entry, and
+/// the outcome, which stops at the first error of the walk or a copy.
+pub(super) fn run_while_walking(
+    source: &Path,
+    dest: &Path,
+    filter: &TransferFilter,
+    compare: &mut FileComparer,
+    options: &SyncOptions,
+) -> (SyncPlan, ChangeSet, Result<SyncResult, FluxError>) {
+    let SyncOptions {
+        delete_orphans,
+        force,
+        quiet,
+        verify,
+        atomic,
+        backup,
+        attrs,
+        ..
+    } = *options;
+    let progress = BatchProgress::new(0, 0, quiet);
+    let _status = StatusPublisher::start(
+        "sync",
+        &source.display().to_string(),
+        &dest.display().to_string(),
+        progress.sampler(),
+    );
+    let mut runner = ActionRunner {
+        progress,
+        quiet,
+        verify,
+        atomic,
+        backup: backup.map(|b| b.for_dest(dest)),
+        attrs,
+        grow: true,
+        result: SyncResult::default(),
+    };
+    let mut plan = SyncPlan::default();
+    let mut changes = ChangeSet::default();
+    let walked = walk_sync_plan(
+        source,
+        dest,
+        filter,
+        compare,
+        delete_orphans,
+        force,
+        &mut |action| {
+            cancel::check()?;
+            plan.tally(&action);
+            note_change(&mut changes, dest, &action);
+            runner.run(&action)
+        },
+    );
+    let result = walked.and_then(|_| runner.finish());
+    (plan, changes, result)
+}
+
+/// Walk as `run_while_walking` would and print the plan, spilling its lines
+/// to disk until the walk is done. Returns the plan's counts.
+pub(super) fn dry_run(
+    source: &Path,
+    dest: &Path,
+    filter: &TransferFilter,
+    compare: &mut FileComparer,
+    delete_orphans: bool,
+    force: bool,
+) -> Result<SyncPlan, FluxError> {
+    let mut spill = Spill::create()?;
+    let mut plan = SyncPlan::default();
+    plan.collisions = walk_sync_plan(
+        source,
+        dest,
+        filter,
+        compare,
+        delete_orphans,
+        force,
+        &mut |action| {
+            plan.tally(&action);
+            spill.push(&action)
+        },
+    )?;
+    eprintln!("Sync plan:");
+    spill.print()?;
+    plan.print_totals();
+    Ok(plan)
+}
+
+/// Lines of a plan too large to keep in memory, in a temporary file that
+/// is removed when dropped.
+struct Spill {
+    path: PathBuf,
+    writer: BufWriter<File>,
+}
+
+impl Spill {
+    fn create() -> Result<Self, FluxError> {
+        let path = std::env::temp_dir().join(format!(
+            "flux-plan-{}-{}.txt",
+            std::process::id(),
+            chrono::Utc::now().timestamp_nanos_opt().unwrap_or_default()
+        ));
+        let file = File::options().write(true).create_new(true).open(&path)?;
+        Ok(Self {
+            path,
+            writer: BufWriter::new(file),
+        })
+    }
+
+    fn push(&mut self, action: &SyncAction) -> Result<(), FluxError> {
+        writeln!(self.writer, "{}", action)?;
+        Ok(())
+    }
+
+    /// Copy the lines to stderr.
+    fn print(&mut self) -> Result<(), FluxError> {
+        self.writer.flush()?;
+        let stderr = std::io::stderr();
+        let mut out = stderr.lock();
+        for line in BufReader::new(File::open(&self.path)?).lines() {
+            writeln!(out, "{}", line?)?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Spill {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::args::HookArgs;
+    use crate::sync::engine::compute_sync_plan;
+    use crate::transfer::attrs::AttrCopier;
+    use tempfile::TempDir;
+
+    fn tree(root: &Path, files: &[(&str, &str)]) {
+        for (name, content) in files {
+            let path = root.join(name);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, content).unwrap();
+        }
+    }
+
+    #[test]
+    fn running_while_walking_matches_the_planned_sync() {
+        let dir = TempDir::new().unwrap();
+        let (source, dest) = (dir.path().join("src"), dir.path().join("dst"));
+        tree(
+            &source,
+            &[("a.txt", "aaa"), ("sub/b.txt", "bb"), ("same.txt", "s")],
+        );
+        tree(&dest, &[("orphan.txt", "x"), ("sub/b.txt", "old content")]);
+        std::fs::copy(source.join("same.txt"), dest.join("same.txt")).unwrap();
+        let mtime = std::fs::metadata(source.join("same.txt"))
+            .unwrap()
+            .modified()
+            .unwrap();
+        File::options()
+            .write(true)
+            .open(dest.join("same.txt"))
+            .unwrap()
+            .set_modified(mtime)
+            .unwrap();
+        let filter = TransferFilter::new(&[], &[]).unwrap();
+
+        let planned = compute_sync_plan(
+            &source,
+            &dest,
+            &filter,
+            &mut FileComparer::default(),
+            true,
+            false,
+        )
+        .unwrap();
+        let (hooks, attrs) = (HookArgs::default(), AttrCopier::default());
+        let options = SyncOptions {
+            delete_orphans: true,
+            force: false,
+            quiet: true,
+            verify: None,
+            atomic: true,
+            backup: None,
+            hooks: &hooks,
+            attrs: &attrs,
+        };
+        let (plan, changes, result) =
+            run_while_walking(&source, &dest, &filter, &mut FileComparer::default(), &options);
+        let result = result.unwrap();
+
+        assert_eq!(
+            (
+                plan.files_to_copy,
+                plan.files_to_update,
+                plan.files_to_delete,
+                plan.files_to_skip
+            ),
+            (
+                planned.files_to_copy,
+                planned.files_to_update,
+                planned.files_to_delete,
+                planned.files_to_skip
+            )
+        );
+        assert!(plan.actions.is_empty());
+        assert_eq!(changes, planned.changes(&dest));
+        assert_eq!(
+            (
+                result.files_copied,
+                result.files_updated,
+                result.files_deleted,
+                result.files_skipped
+            ),
+            (1, 1, 1, 1)
+        );
+        assert_eq!(
+            std::fs::read_to_string(dest.join("sub/b.txt")).unwrap(),
+            "bb"
+        );
+        assert!(!dest.join("orphan.txt").exists());
+    }
+
+    #[test]
+    fn dry_run_spills_and_leaves_both_trees_alone() {
+        let dir = TempDir::new().unwrap();
+        let (source, dest) = (dir.path().join("src"), dir.path().join("dst"));
+        tree(&source, &[("a.txt", "aaa")]);
+        std::fs::create_dir_all(&dest).unwrap();
+        let filter = TransferFilter::new(&[], &[]).unwrap();
+
+        let plan = dry_run(
+            &source,
+            &dest,
+            &filter,
+            &mut FileComparer::default(),
+            true,
+            false,
+        )
+        .unwrap();
+        assert_eq!(plan.files_to_copy, 1);
+        assert!(!dest.join("a.txt").exists());
+
+        // The empty-source check still applies at the end of the walk
+        let empty = dir.path().join("empty");
+        std::fs::create_dir_all(&empty).unwrap();
+        std::fs::write(dest.join("keep.txt"), "k").unwrap();
+        assert!(dry_run(
+            &empty,
+            &dest,
+            &filter,
+            &mut FileComparer::default(),
+            true,
+            false
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn spill_files_are_removed() {
+        let mut spill = Spill::create().unwrap();
+        spill
+            .push(&SyncAction::Skip {
+                path: PathBuf::from("a"),
+                reason: "unchanged",
+            })
+            .unwrap();
+        let path = spill.path.clone();
+        assert!(path.exists());
+        drop(spill);
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,468 @@
+//! Transactional sync (`flux sync --transactional`).
+//!
+//! The plan runs in three phases so the destination never ends up
+//! half-updated:
+//!
+//! 1. Stage: new and changed files are copied (hard links linked) into a
+//!    staging folder inside the destination, `.flux-staging-<stamp>`, so the
+//!    final renames stay on one filesystem.
+//! 2. Verify: every staged copy is hashed against its source.
+//! 3. Commit: the staged files are renamed into place, the files they replace
+//!    moved into the staging folder first; orphans are moved there last.
+//!
+//! A failure while staging or verifying only removes the staging folder. A
+//! failure while committing undoes the renames done so far, newest first,
+//! which puts the replaced files and orphans back. Once everything is in
+//! place the staging folder is deleted. Ctrl+C is honoured until the commit
+//! starts; the commit itself (renames only) runs to the end or rolls back.
+
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+
+use crate::backend::readonly;
+use crate::error::FluxError;
+use crate::progress::bar::BatchProgress;
+use crate::transfer::attrs::AttrCopier;
+use crate::transfer::cancel;
+use crate::transfer::checksum::ChecksumAlgorithm;
+use crate::transfer::copy::copy_file_with_progress;
+use crate::transfer::hardlink;
+use crate::transfer::status::StatusPublisher;
+
+use super::engine::{file_name, verify_copy};
+use super::plan::{SyncAction, SyncPlan, SyncResult};
+
+/// Name prefix of staging folders; the sync planner never treats them as
+/// orphans.
+pub const STAGING_PREFIX: &str = ".flux-staging-";
+
+/// Whether a folder directly under the destination is a staging folder.
+pub fn is_staging_dir(name: &OsStr) -> bool {
+    name.to_string_lossy().starts_with(STAGING_PREFIX)
+}
+
+/// A file copied or linked into the staging folder.
+struct Staged {
+    /// The source file, `None` for hard links (they share a verified copy)
+    src: Option<PathBuf>,
+    staged: PathBuf,
+    dest: PathBuf,
+}
+
+/// One commit step, as needed to undo it.
+enum Step {
+    /// A directory the commit created in the destination
+    CreatedDir(PathBuf),
+    /// A staged file renamed to `dest`, after moving the old one to `replaced`
+    Placed {
+        dest: PathBuf,
+        replaced: Option<PathBuf>,
+    },
+    /// An orphan moved to `aside`
+    Removed { path: PathBuf, aside: PathBuf },
+}
+
+/// A transactional run of a sync plan against `dest`.
+struct Transaction {
+    dest: PathBuf,
+    staging: PathBuf,
+    staged: Vec<Staged>,
+    done: Vec<Step>,
+}
+
+/// Execute a sync plan transactionally (see the module docs).
+///
+/// Every copy is verified with `algorithm`. On failure the destination is
+/// as it was before, and the error is returned. Attributes (`attrs`) are
+/// copied once the files are in place; losing them does not roll back.
+pub fn execute_transactional(
+    plan: &SyncPlan,
+    source: &Path,
+    dest: &Path,
+    algorithm: ChecksumAlgorithm,
+    quiet: bool,
+    attrs: &AttrCopier,
+) -> Result<SyncResult, FluxError> {
+    let mut tx = Transaction::begin(dest)?;
+    let result = tx.run(plan, source, algorithm, quiet, attrs);
+    match result {
+        Ok(result) => {
+            if let Err(e) = std::fs::remove_dir_all(&tx.staging) {
+                tracing::warn!("Cannot remove {}: {}", tx.staging.display(), e);
+            }
+            Ok(result)
+        }
+        Err(e) => {
+            let rolled_back = !tx.done.is_empty();
+            if let Err(undo) = tx.rollback() {
+                return Err(FluxError::SyncError(format!(
+                    "{}; rolling back failed too ({}), the replaced files are in {}",
+                    e,
+                    undo,
+                    tx.staging.display()
+                )));
+            }
+            if let Err(clean) = std::fs::remove_dir_all(&tx.staging) {
+                tracing::warn!("Cannot remove {}: {}", tx.staging.display(), clean);
+            }
+            if !quiet {
+                if rolled_back {
+                    eprintln!("Transaction failed, destination rolled back");
+                } else {
+                    eprintln!("Transaction failed, destination left unchanged");
+                }
+            }
+            Err(e)
+        }
+    }
+}
+
+impl Transaction {
+    /// Create the staging folder in `dest`.
+    fn begin(dest: &Path) -> Result<Self, FluxError> {
+        let stamp = chrono::Local::now().format("%Y%m%d-%H%M%S");
+        let staging = dest.join(format!("{}{}-{}", STAGING_PREFIX, stamp, std::process::id()));
+        readonly::check(&staging, "create")?;
+        std::fs::create_dir_all(&staging)?;
+        Ok(Self {
+            dest: dest.to_path_buf(),
+            staging,
+            staged: Vec::new(),
+            done: Vec::new(),
+        })
+    }
+
+    fn run(
+        &mut self,
+        plan: &SyncPlan,
+        source: &Path,
+        algorithm: ChecksumAlgorithm,
+        quiet: bool,
+        attrs: &AttrCopier,
+    ) -> Result<SyncResult, FluxError> {
+        let actionable =
+            plan.files_to_copy + plan.files_to_update + plan.files_to_link + plan.files_to_delete;
+        let progress = BatchProgress::new(plan.total_copy_bytes, actionable, quiet);
+        let _status = StatusPublisher::start(
+            "sync",
+            &source.display().to_string(),
+            &self.dest.display().to_string(),
+            progress.sampler(),
+        );
+        let staged = self.stage(plan, &progress);
+        progress.finish();
+        let mut result = staged?;
+
+        if !quiet {
+            eprintln!(
+                "Verifying {} staged file(s) ({})...",
+                self.staged.len(),
+                algorithm.label()
+            );
+        }
+        for staged in &self.staged {
+            if let Some(ref src) = staged.src {
+                cancel::check()?;
+                verify_copy(src, &staged.staged, algorithm)?;
+            }
+        }
+
+        let placed = self.commit(plan)?;
+        for file in &placed {
+            if let Some(ref src) = file.src {
+                attrs.apply(src, &file.dest);
+            }
+        }
+        attrs.report();
+        result.files_deleted = self
+            .done
+            .iter()
+            .filter(|step| matches!(step, Step::Removed { .. }))
+            .count() as u64;
+        Ok(result)
+    }
+
+    /// Copy new and changed files (and make hard links) in the staging folder.
+    fn stage(
+        &mut self,
+        plan: &SyncPlan,
+        progress: &BatchProgress,
+    ) -> Result<SyncResult, FluxError> {
+        let mut result = SyncResult::default();
+        // Where each destination file of this run is staged, for links
+        let mut staged_as: HashMap<&Path, PathBuf> = HashMap::new();
+
+        for action in &plan.actions {
+            cancel::check()?;
+            let (src, dest, size) = match action {
+                SyncAction::CopyNew { src, dest, size } => (src, dest, *size),
+                SyncAction::UpdateChanged {
+                    src,
+                    dest,
+                    src_size,
+                    ..
+                } => (src, dest, *src_size),
+                SyncAction::Link {
+                    src,
+                    dest,
+                    target,
+                    size,
+                } => {
+                    let staged = self.staging_path(dest)?;
+                    ensure_parent(&staged)?;
+                    let existing = staged_as.get(target.as_path()).unwrap_or(target);
+                    match hardlink::link(existing, &staged) {
+                        Ok(()) => {
+                            progress.skip_file(0);
+                            result.files_linked += 1;
+                            self.staged.push(Staged {
+                                src: None,
+                                staged: staged.clone(),
+                                dest: dest.clone(),
+                            });
+                            staged_as.insert(dest, staged);
+                            continue;
+                        }
+                        Err(e) => {
+                            tracing::warn!("Cannot link {}: {}", dest.display(), e);
+                            (src, dest, *size)
+                        }
+                    }
+                }
+                SyncAction::DeleteOrphan { .. } => {
+                    progress.skip_file(0);
+                    continue;
+                }
+                SyncAction::Skip { .. } => {
+                    result.files_skipped += 1;
+                    continue;
+                }
+            };
+
+            let staged = self.staging_path(dest)?;
+            ensure_parent(&staged)?;
+            let file_progress = progress.start_file(&file_name(src), size);
+            let copied = copy_file_with_progress(src, &staged, file_progress.bar());
+            file_progress.done();
+            copied?;
+            if matches!(action, SyncAction::UpdateChanged { .. }) {
+                result.files_updated += 1;
+            } else {
+                result.files_copied += 1;
+            }
+            result.bytes_transferred += size;
+            self.staged.push(Staged {
+                src: Some(src.clone()),
+                staged: staged.clone(),
+                dest: dest.clone(),
+            });
+            staged_as.insert(dest, staged);
+        }
+        Ok(result)
+    }
+
+    /// Rename the staged files into place, then move the orphans aside.
+    /// Returns the files put in place.
+    fn commit(&mut self, plan: &SyncPlan) -> Result<Vec<Staged>, FluxError> {
+        let staged = std::mem::take(&mut self.staged);
+        for file in &staged {
+            readonly::check(&file.dest, "replace")?;
+            self.create_parents(&file.dest)?;
+            let replaced = if file.dest.symlink_metadata().is_ok() {
+                let aside = self.aside_path("replaced", &file.dest)?;
+                ensure_parent(&aside)?;
+                std::fs::rename(&file.dest, &aside)?;
+                Some(aside)
+            } else {
+                None
+            };
+            let placed = std::fs::rename(&file.staged, &file.dest);
+            self.done.push(Step::Placed {
+                dest: file.dest.clone(),
+                replaced,
+            });
+            placed?;
+        }
+
+        for action in &plan.actions {
+            if let SyncAction::DeleteOrphan { path, .. } = action {
+                readonly::check(path, "delete")?;
+                let aside = self.aside_path("deleted", path)?;
+                ensure_parent(&aside)?;
+                std::fs::rename(path, &aside)?;
+                self.done.push(Step::Removed {
+                    path: path.clone(),
+                    aside,
+                });
+            }
+        }
+        Ok(staged)
+    }
+
+    /// Undo the commit steps done so far, newest first.
+    fn rollback(&mut self) -> Result<(), FluxError> {
+        while let Some(step) = self.done.pop() {
+            match step {
+                Step::CreatedDir(dir) => {
+                    // Only if nothing else was put there meanwhile
+                    let _ = std::fs::remove_dir(&dir);
+                }
+                Step::Placed { dest, replaced } => {
+                    if dest.symlink_metadata().is_ok() {
+                        std::fs::remove_file(&dest)?;
+                    }
+                    if let Some(replaced) = replaced {
+                        std::fs::rename(&replaced, &dest)?;
+                    }
+                }
+                Step::Removed { path, aside } => std::fs::rename(&aside, &path)?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Create the missing parent directories of `dest`, recording each.
+    fn create_parents(&mut self, dest: &Path) -> Result<(), FluxError> {
+        let Some(parent) = dest.parent() else {
+            return Ok(());
+        };
+        let mut missing: Vec<&Path> = parent.ancestors().take_while(|p| !p.exists()).collect();
+        while let Some(dir) = missing.pop() {
+            std::fs::create_dir(dir)?;
+            self.done.push(Step::CreatedDir(dir.to_path_buf()));
+        }
+        Ok(())
+    }
+
+    /// Where a destination file is staged.
+    fn staging_path(&self, dest: &Path) -> Result<PathBuf, FluxError> {
+        Ok(self.staging.join("new").join(dest.strip_prefix(&self.dest)?))
+    }
+
+    /// Where a replaced or deleted destination file is kept until the end.
+    fn aside_path(&self, kind: &str, dest: &Path) -> Result<PathBuf, FluxError> {
+        Ok(self.staging.join(kind).join(dest.strip_prefix(&self.dest)?))
+    }
+}
+
+fn ensure_parent(path: &Path) -> Result<(), FluxError> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sync::engine::{compute_sync_plan, FileComparer};
+    use crate::transfer::filter::TransferFilter;
+    use tempfile::TempDir;
+
+    fn plan(src: &Path, dest: &Path) -> SyncPlan {
+        let filter = TransferFilter::new(&[], &[]).unwrap();
+        let mut compare = FileComparer::new(
+            crate::sync::engine::CompareMode::Size,
+            ChecksumAlgorithm::Xxh3,
+        );
+        compute_sync_plan(src, dest, &filter, &mut compare, true, false).unwrap()
+    }
+
+    fn setup() -> (TempDir, PathBuf, PathBuf) {
+        let dir = TempDir::new().unwrap();
+        let src = dir.path().join("src");
+        let dest = dir.path().join("dest");
+        std::fs::create_dir_all(src.join("sub")).unwrap();
+        std::fs::create_dir_all(&dest).unwrap();
+        std::fs::write(src.join("a.txt"), "new a").unwrap();
+        std::fs::write(src.join("sub/b.txt"), "b").unwrap();
+        std::fs::write(dest.join("a.txt"), "old").unwrap();
+        std::fs::write(dest.join("orphan.txt"), "x").unwrap();
+        (dir, src, dest)
+    }
+
+    fn staging_dirs(dest: &Path) -> usize {
+        std::fs::read_dir(dest)
+            .unwrap()
+            .filter(|e| is_staging_dir(&e.as_ref().unwrap().file_name()))
+            .count()
+    }
+
+    #[test]
+    fn commits_copies_and_deletes_orphans() {
+        let (_dir, src, dest) = setup();
+        let plan = plan(&src, &dest);
+        let attrs = AttrCopier::default();
+        let result =
+            execute_transactional(&plan, &src, &dest, ChecksumAlgorithm::Blake3, true, &attrs)
+                .unwrap();
+
+        assert_eq!(result.files_copied, 1);
+        assert_eq!(result.files_updated, 1);
+        assert_eq!(result.files_deleted, 1);
+        assert_eq!(std::fs::read_to_string(dest.join("a.txt")).unwrap(), "new a");
+        assert_eq!(std::fs::read_to_string(dest.join("sub/b.txt")).unwrap(), "b");
+        assert!(!dest.join("orphan.txt").exists());
+        assert_eq!(staging_dirs(&dest), 0);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn attributes_are_copied_once_committed() {
+        use crate::transfer::attrs::AttrOptions;
+        let (_dir, src, dest) = setup();
+        // tmpfs and some container filesystems have no user xattrs
+        if xattr::set(src.join("sub/b.txt"), "user.flux.test", b"tagged").is_err() {
+            return;
+        }
+        let plan = plan(&src, &dest);
+        let attrs = AttrCopier::new(AttrOptions {
+            xattrs: true,
+            acls: false,
+        });
+        execute_transactional(&plan, &src, &dest, ChecksumAlgorithm::Blake3, true, &attrs)
+            .unwrap();
+
+        assert_eq!(
+            xattr::get(dest.join("sub/b.txt"), "user.flux.test").unwrap().as_deref(),
+            Some(&b"tagged"[..])
+        );
+        assert!(attrs.lost().is_empty());
+    }
+
+    #[test]
+    fn failed_staging_leaves_dest_untouched() {
+        let (_dir, src, dest) = setup();
+        let plan = plan(&src, &dest);
+        // The source file vanishes between planning and copying
+        std::fs::remove_file(src.join("sub/b.txt")).unwrap();
+
+        let attrs = AttrCopier::default();
+        let result =
+            execute_transactional(&plan, &src, &dest, ChecksumAlgorithm::Blake3, true, &attrs);
+        assert!(result.is_err());
+        assert_eq!(std::fs::read_to_string(dest.join("a.txt")).unwrap(), "old");
+        assert!(dest.join("orphan.txt").exists());
+        assert!(!dest.join("sub").exists());
+        assert_eq!(staging_dirs(&dest), 0);
+    }
+
+    #[test]
+    fn failed_commit_rolls_back() {
+        let (_dir, src, dest) = setup();
+        let plan = plan(&src, &dest);
+        let mut tx = Transaction::begin(&dest).unwrap();
+        let progress = BatchProgress::new(0, 0, true);
+        tx.stage(&plan, &progress).unwrap();
+        // Lose the last staged file so its rename fails mid-commit
+        let last = tx.staged.last().unwrap().staged.clone();
+        std::fs::remove_file(last).unwrap();
+
+        assert!(tx.commit(&plan).is_err());
+        tx.rollback().unwrap();
+        assert_eq!(std::fs::read_to_string(dest.join("a.txt")).unwrap(), "old");
+        assert!(dest.join("orphan.txt").exists());
+        assert!(!dest.join("sub").exists());
+    }
+}
@@ -1,30 +1,36 @@
-use std::path::Path;
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
 use std::sync::mpsc::RecvTimeoutError;
 use std::time::Duration;
 
 use bytesize::ByteSize;
 use notify::RecursiveMode;
 use notify_debouncer_full::{new_debouncer, DebounceEventResult};
+use walkdir::WalkDir;
 
 use crate::error::FluxError;
+use crate::queue::policy::QueueClass;
+use crate::queue::state::{QueueStatus, QueueStore};
+use crate::transfer::cancel;
 use crate::transfer::filter::TransferFilter;
 
-use super::engine::{compute_sync_plan, execute_sync_plan};
+use super::engine::{compute_sync_plan, execute_sync_plan, FileComparer};
+use super::plan::SyncAction;
+use super::SyncOptions;
 
 /// Watch the source directory for changes and re-sync to dest on each
 /// batch of debounced filesystem events.
 ///
 /// Runs an initial sync immediately, then enters an event loop that
 /// re-computes the sync plan and executes it whenever changes are detected.
-/// The loop uses `recv_timeout` to allow natural Ctrl+C termination.
+/// The loop uses `recv_timeout` to notice Ctrl+C between syncs; Ctrl+C
+/// during a sync cancels it (`FluxError::Cancelled`).
 pub fn watch_and_sync(
     source: &Path,
     dest: &Path,
     filter: &TransferFilter,
-    delete_orphans: bool,
-    quiet: bool,
-    verify: bool,
-    force: bool,
+    mut compare: FileComparer,
+    options: &SyncOptions,
 ) -> Result<(), FluxError> {
     let (tx, rx) = std::sync::mpsc::channel();
 
@@ -49,15 +55,15 @@ pub fn watch_and_sync(
     );
 
     // Initial sync
-    run_sync_cycle(source, dest, filter, delete_orphans, quiet, verify, force)?;
+    run_sync_cycle(source, dest, filter, &mut compare, options)?;
 
-    // Event loop: recv_timeout allows natural Ctrl+C handling
+    // Event loop: recv_timeout lets Ctrl+C stop it while idle
     loop {
         match rx.recv_timeout(Duration::from_millis(500)) {
             Ok(Ok(_events)) => {
                 let timestamp = chrono::Local::now().format("%H:%M:%S");
                 eprintln!("[{}] Changes detected, syncing...", timestamp);
-                run_sync_cycle(source, dest, filter, delete_orphans, quiet, verify, force)?;
+                run_sync_cycle(source, dest, filter, &mut compare, options)?;
             }
             Ok(Err(errors)) => {
                 for e in errors {
@@ -65,7 +71,10 @@ pub fn watch_and_sync(
                 }
             }
             Err(RecvTimeoutError::Timeout) => {
-                // No events, continue loop (allows Ctrl+C)
+                // No events: Ctrl+C between syncs just stops watching
+                if cancel::requested() {
+                    break;
+                }
                 continue;
             }
             Err(RecvTimeoutError::Disconnected) => {
@@ -83,12 +92,18 @@ fn run_sync_cycle(
     source: &Path,
     dest: &Path,
     filter: &TransferFilter,
-    delete_orphans: bool,
-    quiet: bool,
-    verify: bool,
-    force: bool,
+    compare: &mut FileComparer,
+    options: &SyncOptions,
 ) -> Result<(), FluxError> {
-    let plan = compute_sync_plan(source, dest, filter, delete_orphans, force)?;
+    let quiet = options.quiet;
+    let plan = compute_sync_plan(
+        source,
+        dest,
+        filter,
+        compare,
+        options.delete_orphans,
+        options.force,
+    )?;
 
     if !plan.has_changes() {
         if !quiet {
@@ -97,7 +112,22 @@ fn run_sync_cycle(
         return Ok(());
     }
 
-    let result = execute_sync_plan(&plan, quiet, verify)?;
+    let started = std::time::Instant::now();
+    let backup_run = options.backup.map(|b| b.for_dest(dest));
+    let status = Some((source, dest));
+    let result = execute_sync_plan(
+        &plan,
+        quiet,
+        options.verify,
+        options.atomic,
+        backup_run,
+        status,
+        options.attrs,
+    );
+    let changes = plan.changes(dest);
+    let verified = options.verify.is_some();
+    super::record_sync(source, dest, changes, started, &result, verified, options.hooks);
+    let result = result?;
 
     if !quiet {
         eprintln!(
@@ -113,11 +143,236 @@ fn run_sync_cycle(
     Ok(())
 }
 
+/// Where `watch_and_enqueue` queues changed files.
+pub struct QueueTarget {
+    /// Data directory holding the queue.
+    pub data_dir: PathBuf,
+    /// Absolute source directory; queued sources are files under it.
+    pub root: PathBuf,
+    /// Destination directory, local (absolute) or remote; the entries expand
+    /// its variables when they run.
+    pub dest: String,
+    pub verify: bool,
+    pub class: QueueClass,
+}
+
+impl QueueTarget {
+    /// Add a `flux cp` entry per file, unless one for the same source and
+    /// destination is still pending. Returns how many were added.
+    fn enqueue(&self, store: &mut QueueStore, files: &BTreeSet<PathBuf>) -> usize {
+        let mut added = 0;
+        for file in files {
+            let Ok(rel) = file.strip_prefix(&self.root) else {
+                continue;
+            };
+            let source = file.display().to_string();
+            let dest = dest_path(&self.dest, rel);
+            let queued = store.list().iter().any(|e| {
+                e.status == QueueStatus::Pending && e.source == source && e.dest == dest
+            });
+            if queued {
+                continue;
+            }
+            let id = store.add(source, dest, false, self.verify, false);
+            if let Some(entry) = store.get_mut(id) {
+                entry.class = self.class;
+            }
+            added += 1;
+        }
+        added
+    }
+
+    /// Queue the files in `pending` and clear it, or leave them for the next
+    /// try while another process (usually `flux daemon`) holds the queue.
+    fn flush(&self, pending: &mut BTreeSet<PathBuf>, quiet: bool) -> Result<(), FluxError> {
+        let Some(mut store) = QueueStore::try_load(&self.data_dir)? else {
+            tracing::debug!("Queue busy, {} changed file(s) waiting", pending.len());
+            return Ok(());
+        };
+        let added = self.enqueue(&mut store, pending);
+        store.save()?;
+        pending.clear();
+        if !quiet && added > 0 {
+            let timestamp = chrono::Local::now().format("%H:%M:%S");
+            eprintln!("[{}] Queued {} file(s)", timestamp, added);
+        }
+        Ok(())
+    }
+}
+
+/// Watch the source directory and add changed files to the transfer queue,
+/// for `flux daemon` to copy in the background.
+///
+/// With a local `dest_dir`, files that are missing or out of date there are
+/// queued first. Queuing never waits: while the daemon holds the queue,
+/// changes are collected and queued once it is free, so a slow destination
+/// doesn't stall the watcher. Deletions are not mirrored.
+pub fn watch_and_enqueue(
+    target: &QueueTarget,
+    dest_dir: Option<&Path>,
+    filter: &TransferFilter,
+    mut compare: FileComparer,
+    quiet: bool,
+) -> Result<(), FluxError> {
+    let (tx, rx) = std::sync::mpsc::channel();
+
+    let mut debouncer = new_debouncer(
+        Duration::from_secs(2),
+        None,
+        move |result: DebounceEventResult| {
+            let _ = tx.send(result);
+        },
+    )
+    .map_err(|e| FluxError::SyncError(format!("Failed to create file watcher: {}", e)))?;
+
+    let root = &target.root;
+    debouncer
+        .watch(root, RecursiveMode::Recursive)
+        .map_err(|e| FluxError::SyncError(format!("Failed to watch '{}': {}", root.display(), e)))?;
+
+    eprintln!(
+        "Watching {} for changes, queuing them for flux daemon... (press Ctrl+C to stop)",
+        root.display()
+    );
+
+    // Initial batch: what the destination is missing
+    let mut pending = BTreeSet::new();
+    match dest_dir {
+        Some(dest) => {
+            let plan = compute_sync_plan(root, dest, filter, &mut compare, false, false)?;
+            for action in plan.actions {
+                match action {
+                    SyncAction::CopyNew { src, .. }
+                    | SyncAction::UpdateChanged { src, .. }
+                    | SyncAction::Link { src, .. } => {
+                        pending.insert(src);
+                    }
+                    SyncAction::DeleteOrphan { .. } | SyncAction::Skip { .. } => {}
+                }
+            }
+        }
+        None => {
+            if !quiet {
+                eprintln!("Destination is not local: only files changed from now on are queued");
+            }
+        }
+    }
+
+    loop {
+        if !pending.is_empty() {
+            target.flush(&mut pending, quiet)?;
+        }
+        match rx.recv_timeout(Duration::from_millis(500)) {
+            Ok(Ok(events)) => {
+                let paths: Vec<PathBuf> =
+                    events.iter().flat_map(|e| e.paths.iter().cloned()).collect();
+                pending.extend(changed_files(root, &paths, filter));
+            }
+            Ok(Err(errors)) => {
+                for e in errors {
+                    tracing::warn!("Watch error: {}", e);
+                }
+            }
+            Err(RecvTimeoutError::Timeout) => {
+                if cancel::requested() {
+                    break;
+                }
+            }
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+    }
+
+    if !pending.is_empty() {
+        eprintln!(
+            "{} changed file(s) were not queued (the queue was busy)",
+            pending.len()
+        );
+    }
+    Ok(())
+}
+
+/// The files to queue for a batch of changed paths under `root`.
+///
+/// A changed directory (created or moved in) contributes the files in it.
+/// Paths that no longer exist are dropped, and so are paths the filter
+/// excludes, including anything inside an excluded directory.
+pub(super) fn changed_files(
+    root: &Path,
+    paths: &[PathBuf],
+    filter: &TransferFilter,
+) -> BTreeSet<PathBuf> {
+    let mut files = BTreeSet::new();
+    for path in paths {
+        let Ok(rel) = path.strip_prefix(root) else {
+            continue;
+        };
+        let pruned = rel
+            .ancestors()
+            .filter(|a| !a.as_os_str().is_empty() && *a != rel)
+            .any(|a| filter.excludes_dir_path(&root.join(a)));
+        if rel.as_os_str().is_empty() || pruned {
+            continue;
+        }
+        if path.is_dir() {
+            if filter.excludes_dir_path(path) {
+                continue;
+            }
+            let walker = WalkDir::new(path)
+                .into_iter()
+                .filter_entry(|e| !filter.is_excluded_dir(e));
+            for entry in walker.filter_map(Result::ok) {
+                if entry.file_type().is_file() && filter.should_transfer(entry.path()) {
+                    files.insert(entry.into_path());
+                }
+            }
+        } else if path.is_file() && filter.should_transfer(path) {
+            files.insert(path.clone());
+        }
+    }
+    files
+}
+
+/// `dest` joined with the relative path `rel`, in the style of `dest`:
+/// backslashes for Windows paths, forward slashes for everything else.
+pub(super) fn dest_path(dest: &str, rel: &Path) -> String {
+    let sep = if dest.contains('\\') && !dest.contains("://") {
+        "\\"
+    } else {
+        "/"
+    };
+    let parts: Vec<_> = rel
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect();
+    let rel = parts.join(sep);
+    // `host:` is a home directory; a separator would make it the root
+    if dest.ends_with(['/', '\\', ':']) {
+        format!("{}{}", dest, rel)
+    } else {
+        format!("{}{}{}", dest, sep, rel)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::cli::args::HookArgs;
+    use crate::transfer::attrs::AttrCopier;
     use tempfile::TempDir;
 
+    fn test_options<'a>(hooks: &'a HookArgs, attrs: &'a AttrCopier) -> SyncOptions<'a> {
+        SyncOptions {
+            delete_orphans: false,
+            force: false,
+            quiet: true,
+            verify: None,
+            atomic: true,
+            backup: None,
+            hooks,
+            attrs,
+        }
+    }
+
     #[test]
     fn test_watch_debouncer_creation_smoke() {
         // Smoke test: create a debouncer and immediately drop it.
@@ -164,7 +419,13 @@ mod tests {
 
         let filter = TransferFilter::new(&[], &[]).unwrap();
         // Both empty -- should report no changes
-        let result = run_sync_cycle(&source, &dest, &filter, false, true, false, false);
+        let result = run_sync_cycle(
+            &source,
+            &dest,
+            &filter,
+            &mut FileComparer::default(),
+            &test_options(&HookArgs::default(), &AttrCopier::default()),
+        );
         assert!(result.is_ok());
     }
 
@@ -179,11 +440,101 @@ mod tests {
         std::fs::write(source.join("hello.txt"), "world").unwrap();
 
         let filter = TransferFilter::new(&[], &[]).unwrap();
-        let result = run_sync_cycle(&source, &dest, &filter, false, true, false, false);
+        let result = run_sync_cycle(
+            &source,
+            &dest,
+            &filter,
+            &mut FileComparer::default(),
+            &test_options(&HookArgs::default(), &AttrCopier::default()),
+        );
         assert!(result.is_ok());
         assert_eq!(
             std::fs::read_to_string(dest.join("hello.txt")).unwrap(),
             "world"
         );
     }
+
+    #[test]
+    fn changed_files_expands_dirs_and_applies_the_filter() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path();
+        std::fs::create_dir_all(root.join("new/deep")).unwrap();
+        std::fs::create_dir_all(root.join("build")).unwrap();
+        std::fs::write(root.join("a.txt"), "a").unwrap();
+        std::fs::write(root.join("a.log"), "a").unwrap();
+        std::fs::write(root.join("new/deep/b.txt"), "b").unwrap();
+        std::fs::write(root.join("build/c.txt"), "c").unwrap();
+
+        let filter = TransferFilter::new(&["*.log".into(), "build".into()], &[]).unwrap();
+        let paths = [
+            root.join("a.txt"),
+            root.join("a.log"),
+            root.join("new"),
+            root.join("build/c.txt"),
+            root.join("deleted.txt"),
+            root.to_path_buf(),
+        ];
+        let files = changed_files(root, &paths, &filter);
+        let expected: BTreeSet<PathBuf> =
+            [root.join("a.txt"), root.join("new/deep/b.txt")].into();
+        assert_eq!(files, expected);
+    }
+
+    #[test]
+    fn dest_path_follows_the_destination_style() {
+        let rel = Path::new("sub").join("f.txt");
+        assert_eq!(dest_path("/backup", &rel), "/backup/sub/f.txt");
+        assert_eq!(dest_path("/backup/", &rel), "/backup/sub/f.txt");
+        assert_eq!(dest_path("sftp://nas/data", &rel), "sftp://nas/data/sub/f.txt");
+        assert_eq!(dest_path("user@nas:", &rel), "user@nas:sub/f.txt");
+        assert_eq!(dest_path(r"D:\backup", &rel), r"D:\backup\sub\f.txt");
+    }
+
+    #[test]
+    fn enqueue_skips_files_already_pending() {
+        let data = TempDir::new().unwrap();
+        let src = TempDir::new().unwrap();
+        let target = QueueTarget {
+            data_dir: data.path().to_path_buf(),
+            root: src.path().to_path_buf(),
+            dest: "/backup/{date}".into(),
+            verify: true,
+            class: QueueClass::Bulk,
+        };
+        let files: BTreeSet<PathBuf> = [src.path().join("a.txt"), src.path().join("b.txt")].into();
+
+        let mut store = QueueStore::load(data.path()).unwrap();
+        assert_eq!(target.enqueue(&mut store, &files), 2);
+        assert_eq!(target.enqueue(&mut store, &files), 0);
+        let entry = &store.list()[0];
+        assert_eq!(entry.dest, "/backup/{date}/a.txt");
+        assert!(entry.verify && !entry.recursive);
+        assert_eq!(entry.class, QueueClass::Bulk);
+
+        // Once the entry has run, a later change is queued again
+        store.get_mut(entry.id).unwrap().status = QueueStatus::Completed;
+        assert_eq!(target.enqueue(&mut store, &files), 1);
+    }
+
+    #[test]
+    fn flush_keeps_changes_while_the_queue_is_busy() {
+        let data = TempDir::new().unwrap();
+        let target = QueueTarget {
+            data_dir: data.path().to_path_buf(),
+            root: PathBuf::from("/src"),
+            dest: "/backup".into(),
+            verify: false,
+            class: QueueClass::Interactive,
+        };
+        let mut pending: BTreeSet<PathBuf> = [PathBuf::from("/src/a.txt")].into();
+
+        let held = QueueStore::load(data.path()).unwrap();
+        target.flush(&mut pending, true).unwrap();
+        assert_eq!(pending.len(), 1);
+        drop(held);
+
+        target.flush(&mut pending, true).unwrap();
+        assert!(pending.is_empty());
+        assert_eq!(QueueStore::load(data.path()).unwrap().list().len(), 1);
+    }
 }
@@ -0,0 +1,148 @@
+//! Atomic destination writes.
+//!
+//! With `--atomic` (`cp`), by default for `sync`, and always for received
+//! files, data is written to `.<name>.flux-tmp` in the destination directory
+//! and renamed over the destination only once it is complete and verified.
+//! A crash or failed transfer therefore never leaves a truncated file under
+//! the real name, where a later sync would take it for a valid copy.
+
+use std::path::{Path, PathBuf};
+
+use crate::backend::readonly;
+use crate::error::FluxError;
+
+/// Suffix of in-progress temp files.
+pub const TEMP_SUFFIX: &str = ".flux-tmp";
+
+/// Temp file path for `dest`: `.<name>.flux-tmp` next to it.
+pub fn temp_path(dest: &Path) -> PathBuf {
+    let name = dest
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    dest.with_file_name(format!(".{}{}", name, TEMP_SUFFIX))
+}
+
+/// Whether `path` is an in-progress temp file (never copied or synced).
+pub fn is_temp_file(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|n| n.to_str())
+        .is_some_and(|n| n.starts_with('.') && n.ends_with(TEMP_SUFFIX))
+}
+
+/// A destination being written through a temp file.
+///
+/// Write to `path()`, then `commit()` to rename it into place. If the value
+/// is dropped without committing (an error or early return), the temp file
+/// is removed, unless `keep_on_failure` asked to keep it for a resume.
+#[derive(Debug)]
+pub struct AtomicFile {
+    temp: PathBuf,
+    dest: PathBuf,
+    keep_on_failure: bool,
+    committed: bool,
+}
+
+impl AtomicFile {
+    pub fn new(dest: &Path) -> Self {
+        Self {
+            temp: temp_path(dest),
+            dest: dest.to_path_buf(),
+            keep_on_failure: false,
+            committed: false,
+        }
+    }
+
+    /// Keep the temp file when not committed (resumable and paused copies).
+    pub fn keep_on_failure(mut self, keep: bool) -> Self {
+        self.keep_on_failure = keep;
+        self
+    }
+
+    /// Path to write the data to.
+    pub fn path(&self) -> &Path {
+        &self.temp
+    }
+
+    /// Rename the finished temp file over the destination.
+    pub fn commit(mut self) -> Result<(), FluxError> {
+        readonly::check(&self.dest, "replace")?;
+        std::fs::rename(&self.temp, &self.dest).map_err(|e| FluxError::Io {
+            source: std::io::Error::new(
+                e.kind(),
+                format!(
+                    "failed to move '{}' into place as '{}': {}",
+                    self.temp.display(),
+                    self.dest.display(),
+                    e
+                ),
+            ),
+        })?;
+        self.committed = true;
+        Ok(())
+    }
+}
+
+impl Drop for AtomicFile {
+    fn drop(&mut self) {
+        if !self.committed && !self.keep_on_failure {
+            let _ = std::fs::remove_file(&self.temp);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn temp_path_is_hidden_sibling() {
+        assert_eq!(
+            temp_path(Path::new("/backup/photos/img.jpg")),
+            PathBuf::from("/backup/photos/.img.jpg.flux-tmp")
+        );
+        assert!(is_temp_file(&temp_path(Path::new("a/report.pdf"))));
+        assert!(!is_temp_file(Path::new("a/report.pdf")));
+        assert!(!is_temp_file(Path::new("a/.bashrc")));
+    }
+
+    #[test]
+    fn commit_replaces_destination() {
+        let dir = tempfile::tempdir().unwrap();
+        let dest = dir.path().join("out.txt");
+        std::fs::write(&dest, "old").unwrap();
+
+        let file = AtomicFile::new(&dest);
+        std::fs::write(file.path(), "new").unwrap();
+        // Until the commit, the destination keeps its old content
+        assert_eq!(std::fs::read_to_string(&dest).unwrap(), "old");
+        file.commit().unwrap();
+
+        assert_eq!(std::fs::read_to_string(&dest).unwrap(), "new");
+        assert!(!temp_path(&dest).exists());
+    }
+
+    #[test]
+    fn dropping_uncommitted_file_removes_temp() {
+        let dir = tempfile::tempdir().unwrap();
+        let dest = dir.path().join("out.txt");
+        {
+            let file = AtomicFile::new(&dest);
+            std::fs::write(file.path(), "partial").unwrap();
+        }
+        assert!(!temp_path(&dest).exists());
+        assert!(!dest.exists());
+    }
+
+    #[test]
+    fn keep_on_failure_leaves_temp_for_resume() {
+        let dir = tempfile::tempdir().unwrap();
+        let dest = dir.path().join("out.txt");
+        {
+            let file = AtomicFile::new(&dest).keep_on_failure(true);
+            std::fs::write(file.path(), "partial").unwrap();
+        }
+        assert!(temp_path(&dest).exists());
+        assert!(!dest.exists());
+    }
+}
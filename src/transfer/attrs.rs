@@ -0,0 +1,300 @@
+//! Extended attributes and ACLs (`--xattrs`, `--acls`) for local copies and
+//! syncs.
+//!
+//! - `--xattrs` copies `user.*` attributes on Linux, and every attribute on
+//!   macOS and the BSDs (resource forks, Finder info, quarantine flags).
+//! - `--acls` copies the POSIX access ACL on Linux (the
+//!   `system.posix_acl_access` attribute), the extended ACL on macOS and the
+//!   DACL on Windows.
+//!
+//! They are copied after the file's data, so an attribute the destination
+//! can't hold (a filesystem without xattr or ACL support, a name it
+//! rejects, a value over its size limit) never fails the copy: the file and
+//! the reason are collected, and `AttrCopier::report` lists them at the end.
+
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Which attributes to carry over.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AttrOptions {
+    pub xattrs: bool,
+    pub acls: bool,
+}
+
+impl AttrOptions {
+    /// Whether anything is to be copied.
+    pub fn any(&self) -> bool {
+        self.xattrs || self.acls
+    }
+}
+
+/// Copies attributes file by file and collects the files that lost some.
+/// Shared by the workers of a directory copy.
+#[derive(Debug, Default)]
+pub struct AttrCopier {
+    options: AttrOptions,
+    lost: Mutex<Vec<(PathBuf, String)>>,
+}
+
+impl AttrCopier {
+    /// A copier for `options`, minus what this platform cannot copy at all
+    /// (warned about once, here).
+    pub fn new(mut options: AttrOptions) -> Self {
+        if options.xattrs && !cfg!(unix) {
+            tracing::warn!("--xattrs is not supported on this platform; ignoring it");
+            options.xattrs = false;
+        }
+        if options.acls && !cfg!(any(target_os = "linux", target_os = "macos", windows)) {
+            tracing::warn!("--acls is not supported on this platform; ignoring it");
+            options.acls = false;
+        }
+        Self {
+            options,
+            lost: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Give `dest` the attributes of `source`. What cannot be copied is
+    /// recorded for `report` (the data itself has been copied).
+    pub fn apply(&self, source: &Path, dest: &Path) {
+        if self.options.xattrs {
+            if let Err(e) = copy_xattrs(source, dest) {
+                self.record(dest, format!("extended attributes: {}", e));
+            }
+        }
+        if self.options.acls {
+            if let Err(e) = copy_acl(source, dest) {
+                self.record(dest, format!("ACL: {}", e));
+            }
+        }
+    }
+
+    fn record(&self, dest: &Path, reason: String) {
+        tracing::debug!("Attributes of {} not preserved: {}", dest.display(), reason);
+        if let Ok(mut lost) = self.lost.lock() {
+            lost.push((dest.to_path_buf(), reason));
+        }
+    }
+
+    /// The files whose attributes could not all be copied, with the reason.
+    pub fn lost(&self) -> Vec<(PathBuf, String)> {
+        self.lost.lock().map(|lost| lost.clone()).unwrap_or_default()
+    }
+
+    /// List the files whose attributes could not all be copied. Printed even
+    /// when quiet: the copies are not what was asked for.
+    pub fn report(&self) {
+        let lost = self.lost();
+        if lost.is_empty() {
+            return;
+        }
+        eprintln!(
+            "Could not preserve the attributes of {} file(s):",
+            lost.len()
+        );
+        for (path, reason) in &lost {
+            eprintln!("  {}: {}", path.display(), reason);
+        }
+    }
+}
+
+/// Copy the extended attributes selected by `copies_xattr`. Each one that
+/// fails is named in the error; the others are still copied.
+#[cfg(unix)]
+fn copy_xattrs(source: &Path, dest: &Path) -> Result<(), String> {
+    let names = xattr::list(source).map_err(|e| e.to_string())?;
+    let mut failed = Vec::new();
+    for name in names.filter(|name| copies_xattr(name)) {
+        let copied = match xattr::get(source, &name) {
+            Ok(Some(value)) => xattr::set(dest, &name, &value),
+            Ok(None) => Ok(()),
+            Err(e) => Err(e),
+        };
+        if let Err(e) = copied {
+            failed.push(format!("{} ({})", name.to_string_lossy(), e));
+        }
+    }
+    if failed.is_empty() {
+        Ok(())
+    } else {
+        Err(failed.join(", "))
+    }
+}
+
+#[cfg(not(unix))]
+fn copy_xattrs(_source: &Path, _dest: &Path) -> Result<(), String> {
+    Err("not supported on this platform".to_string())
+}
+
+/// Linux attributes outside `user.` are ACLs (`--acls`), security labels
+/// or need privileges; other systems list only what a copy should carry.
+#[cfg(unix)]
+fn copies_xattr(name: &std::ffi::OsStr) -> bool {
+    use std::os::unix::ffi::OsStrExt;
+    !cfg!(target_os = "linux") || name.as_bytes().starts_with(b"user.")
+}
+
+/// Copy the POSIX access ACL, stored as a system attribute.
+#[cfg(target_os = "linux")]
+fn copy_acl(source: &Path, dest: &Path) -> Result<(), String> {
+    const ACL_ACCESS: &str = "system.posix_acl_access";
+    match xattr::get(source, ACL_ACCESS) {
+        Ok(Some(acl)) => xattr::set(dest, ACL_ACCESS, &acl).map_err(|e| e.to_string()),
+        // No ACL beyond the permission bits, or a source without ACLs
+        Ok(None) => Ok(()),
+        Err(e) if e.raw_os_error() == Some(libc::EOPNOTSUPP) => Ok(()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// Copy the extended ACL through the libSystem ACL API.
+#[cfg(target_os = "macos")]
+fn copy_acl(source: &Path, dest: &Path) -> Result<(), String> {
+    use std::ffi::CString;
+    use std::os::raw::{c_char, c_int, c_void};
+    use std::os::unix::ffi::OsStrExt;
+
+    const ACL_TYPE_EXTENDED: c_int = 0x0000_0100;
+    extern "C" {
+        fn acl_get_file(path: *const c_char, kind: c_int) -> *mut c_void;
+        fn acl_set_file(path: *const c_char, kind: c_int, acl: *mut c_void) -> c_int;
+        fn acl_free(obj: *mut c_void) -> c_int;
+    }
+
+    let c_path = |p: &Path| CString::new(p.as_os_str().as_bytes()).map_err(|e| e.to_string());
+    let (src, dst) = (c_path(source)?, c_path(dest)?);
+    // SAFETY: both paths are NUL-terminated; the ACL returned by
+    // acl_get_file is freed once, after acl_set_file is done with it
+    unsafe {
+        let acl = acl_get_file(src.as_ptr(), ACL_TYPE_EXTENDED);
+        if acl.is_null() {
+            let e = std::io::Error::last_os_error();
+            // ENOENT: the file has no extended ACL
+            return match e.raw_os_error() {
+                Some(libc::ENOENT) => Ok(()),
+                _ => Err(e.to_string()),
+            };
+        }
+        let status = acl_set_file(dst.as_ptr(), ACL_TYPE_EXTENDED, acl);
+        let e = std::io::Error::last_os_error();
+        acl_free(acl);
+        if status != 0 {
+            return Err(e.to_string());
+        }
+    }
+    Ok(())
+}
+
+/// Copy the DACL. Inherited entries are recomputed from the destination's
+/// parent, as Explorer does for a copied file.
+#[cfg(windows)]
+fn copy_acl(source: &Path, dest: &Path) -> Result<(), String> {
+    use std::os::windows::ffi::OsStrExt;
+    use std::ptr::{null, null_mut};
+    use windows_sys::Win32::Foundation::{LocalFree, ERROR_SUCCESS};
+    use windows_sys::Win32::Security::Authorization::{
+        GetNamedSecurityInfoW, SetNamedSecurityInfoW, SE_FILE_OBJECT,
+    };
+    use windows_sys::Win32::Security::{ACL, DACL_SECURITY_INFORMATION, PSECURITY_DESCRIPTOR};
+
+    let wide = |p: &Path| -> Vec<u16> { p.as_os_str().encode_wide().chain(Some(0)).collect() };
+    let (src_w, dst_w) = (wide(source), wide(dest));
+    let mut dacl: *mut ACL = null_mut();
+    let mut descriptor: PSECURITY_DESCRIPTOR = null_mut();
+    // SAFETY: both paths are NUL-terminated UTF-16; `dacl` points into
+    // `descriptor`, which is freed after the DACL has been set
+    unsafe {
+        let status = GetNamedSecurityInfoW(
+            src_w.as_ptr(),
+            SE_FILE_OBJECT,
+            DACL_SECURITY_INFORMATION,
+            null_mut(),
+            null_mut(),
+            &mut dacl,
+            null_mut(),
+            &mut descriptor,
+        );
+        if status != ERROR_SUCCESS {
+            return Err(std::io::Error::from_raw_os_error(status as i32).to_string());
+        }
+        let status = SetNamedSecurityInfoW(
+            dst_w.as_ptr(),
+            SE_FILE_OBJECT,
+            DACL_SECURITY_INFORMATION,
+            null_mut(),
+            null_mut(),
+            dacl,
+            null(),
+        );
+        LocalFree(descriptor as _);
+        if status != ERROR_SUCCESS {
+            return Err(std::io::Error::from_raw_os_error(status as i32).to_string());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+fn copy_acl(_source: &Path, _dest: &Path) -> Result<(), String> {
+    Err("not supported on this platform".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nothing_is_copied_or_reported_without_options() {
+        let dir = tempfile::tempdir().unwrap();
+        let (src, dest) = (dir.path().join("a"), dir.path().join("b"));
+        std::fs::write(&src, "a").unwrap();
+        std::fs::write(&dest, "a").unwrap();
+
+        let copier = AttrCopier::new(AttrOptions::default());
+        assert!(!AttrOptions::default().any());
+        copier.apply(&src, &dest);
+        assert!(copier.lost().is_empty());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn user_xattrs_are_copied_or_reported() {
+        let dir = tempfile::tempdir().unwrap();
+        let (src, dest) = (dir.path().join("a"), dir.path().join("b"));
+        std::fs::write(&src, "a").unwrap();
+        std::fs::write(&dest, "a").unwrap();
+        // tmpfs and some container filesystems have no user xattrs
+        if xattr::set(&src, "user.flux.test", b"tagged").is_err() {
+            return;
+        }
+
+        let copier = AttrCopier::new(AttrOptions {
+            xattrs: true,
+            acls: false,
+        });
+        copier.apply(&src, &dest);
+        assert_eq!(
+            xattr::get(&dest, "user.flux.test").unwrap().as_deref(),
+            Some(&b"tagged"[..])
+        );
+        assert!(copier.lost().is_empty());
+
+        // A destination that is gone is reported, not an error
+        copier.apply(&src, &dir.path().join("missing"));
+        let lost = copier.lost();
+        assert_eq!(lost.len(), 1);
+        assert!(lost[0].1.starts_with("extended attributes: "));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn only_user_attributes_are_selected_on_linux() {
+        use std::ffi::OsStr;
+        assert!(copies_xattr(OsStr::new("user.origin")));
+        assert_eq!(
+            copies_xattr(OsStr::new("com.apple.quarantine")),
+            !cfg!(target_os = "linux")
+        );
+    }
+}
@@ -0,0 +1,170 @@
+//! Cancelling a transfer with Ctrl+C.
+//!
+//! Commands that move data (`cp`, `sync`, `send`, `receive`, `queue run`)
+//! call `install_handler`, after which Ctrl+C sets a process-wide flag
+//! instead of killing the process. The copy engines check it between buffers
+//! and return `FluxError::Cancelled`, cleaning up on the way out:
+//!
+//! - partly written destination files are removed (`PartialFile`; `--atomic`
+//!   temp files are removed by `AtomicFile`);
+//! - resumable copies (`cp --resume`, queue entries) keep the partial file
+//!   and save their resume manifest, so the same command continues it;
+//! - `flux send` and `flux receive` tell the peer with a `Cancel` message,
+//!   and the peer deletes its incomplete file.
+//!
+//! The process then exits with code 130. A second Ctrl+C exits at once,
+//! without cleaning up. Other commands keep the default Ctrl+C behaviour.
+
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use crate::error::{ErrorCategory, FluxError};
+
+/// Set once the user has pressed Ctrl+C.
+static CANCELLED: AtomicBool = AtomicBool::new(false);
+
+/// How often `cancelled()` looks at the flag.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Ask every transfer in this process to stop.
+pub fn request() {
+    CANCELLED.store(true, Ordering::SeqCst);
+}
+
+/// Whether the transfer has been cancelled.
+pub fn requested() -> bool {
+    CANCELLED.load(Ordering::SeqCst)
+}
+
+/// `Err(FluxError::Cancelled)` once the transfer has been cancelled.
+pub fn check() -> Result<(), FluxError> {
+    if requested() {
+        Err(FluxError::Cancelled)
+    } else {
+        Ok(())
+    }
+}
+
+/// Completes once the transfer has been cancelled (for `tokio::select!`).
+pub async fn cancelled() {
+    while !requested() {
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+}
+
+/// I/O error returned by readers that notice the cancellation, so it can
+/// travel through `std::io` APIs such as `io::copy`.
+pub fn io_error() -> io::Error {
+    io::Error::other(FluxError::Cancelled)
+}
+
+/// Convert an I/O error to a `FluxError`, restoring `FluxError::Cancelled`
+/// from `io_error`.
+pub fn from_io(err: io::Error) -> FluxError {
+    let cancelled = err
+        .get_ref()
+        .and_then(|inner| inner.downcast_ref::<FluxError>())
+        .is_some_and(|inner| matches!(inner, FluxError::Cancelled));
+    if cancelled {
+        FluxError::Cancelled
+    } else {
+        FluxError::Io { source: err }
+    }
+}
+
+/// Removes a destination file that is being written if the transfer is
+/// cancelled before `done` is called.
+pub struct PartialFile {
+    path: Option<PathBuf>,
+}
+
+impl PartialFile {
+    pub fn new(path: &Path) -> Self {
+        Self {
+            path: Some(path.to_path_buf()),
+        }
+    }
+
+    /// The file is complete: keep it.
+    pub fn done(mut self) {
+        self.path = None;
+    }
+}
+
+impl Drop for PartialFile {
+    fn drop(&mut self) {
+        if let Some(path) = self.path.take() {
+            if requested() && std::fs::remove_file(&path).is_ok() {
+                tracing::debug!("Removed partial file {}", path.display());
+            }
+        }
+    }
+}
+
+/// Make Ctrl+C cancel the transfer: the first press sets the flag, the
+/// second exits immediately.
+pub fn install_handler() {
+    let spawned = std::thread::Builder::new()
+        .name("flux-cancel".to_string())
+        .spawn(|| {
+            let runtime = match tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+            {
+                Ok(runtime) => runtime,
+                Err(e) => {
+                    tracing::warn!("Cannot listen for Ctrl+C: {}", e);
+                    return;
+                }
+            };
+            runtime.block_on(async {
+                while tokio::signal::ctrl_c().await.is_ok() {
+                    if requested() {
+                        std::process::exit(ErrorCategory::Cancelled.code());
+                    }
+                    request();
+                    eprintln!("\nCancelling... (press Ctrl+C again to exit immediately)");
+                }
+            });
+        });
+    if let Err(e) = spawned {
+        tracing::warn!("Cannot listen for Ctrl+C: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    #[test]
+    fn cancelled_io_errors_convert_back() {
+        assert!(matches!(from_io(io_error()), FluxError::Cancelled));
+        let other = from_io(io::Error::from(io::ErrorKind::NotFound));
+        assert!(matches!(other, FluxError::Io { .. }));
+
+        // The error survives io::copy, which retries only `Interrupted`
+        struct Cancelling;
+        impl Read for Cancelling {
+            fn read(&mut self, _buf: &mut [u8]) -> io::Result<usize> {
+                Err(io_error())
+            }
+        }
+        let err = io::copy(&mut Cancelling, &mut io::sink()).unwrap_err();
+        assert!(matches!(from_io(err), FluxError::Cancelled));
+    }
+
+    #[test]
+    fn partial_files_are_kept_when_not_cancelled() {
+        // The flag is process-wide; this test never sets it
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("partial.bin");
+        std::fs::write(&path, "half").unwrap();
+        drop(PartialFile::new(&path));
+        assert!(path.exists());
+        PartialFile::new(&path).done();
+        assert!(path.exists());
+    }
+}
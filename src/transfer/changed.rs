@@ -0,0 +1,249 @@
+//! Source files that change while they are copied (`cp --changed-source`).
+//!
+//! A file that grows or is truncated mid-copy defeats the chunked engine:
+//! the chunks cover the size seen when the copy was planned, so a grown file
+//! is cut short without a word and a truncated one fails at end of file.
+//! The size and modification time of each source are therefore compared
+//! with what the copy started from once it is written, and a change is
+//! handled by the `ChangedSource` policy:
+//!
+//! - `warn` (default) keeps the copy and lists the file at the end
+//! - `retry` copies the file again, up to `RETRIES` times, then fails it
+//! - `fail` fails the file with `FluxError::SourceChanged`
+
+use std::fs::Metadata;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+use bytesize::ByteSize;
+
+use crate::error::FluxError;
+
+/// Copies of a changed file `retry` makes before failing it.
+pub const RETRIES: u32 = 3;
+
+/// What to do with a source that changed during its copy.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum ChangedSource {
+    /// Keep the copy and list the file in the summary
+    #[default]
+    Warn,
+    /// Copy the file again (a few times at most)
+    Retry,
+    /// Fail the file
+    Fail,
+}
+
+/// Size and modification time of a source file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SourceState {
+    pub len: u64,
+    pub modified: Option<SystemTime>,
+}
+
+impl SourceState {
+    pub fn of(meta: &Metadata) -> Self {
+        Self {
+            len: meta.len(),
+            modified: meta.modified().ok(),
+        }
+    }
+
+    pub fn read(path: &Path) -> Result<Self, FluxError> {
+        Ok(Self::of(&std::fs::metadata(path)?))
+    }
+
+    /// How the file went from `self` to `now`, if it changed.
+    pub fn change(&self, now: &SourceState) -> Option<String> {
+        if now.len > self.len {
+            Some(format!("grew from {} to {}", ByteSize(self.len), ByteSize(now.len)))
+        } else if now.len < self.len {
+            Some(format!("shrank from {} to {}", ByteSize(self.len), ByteSize(now.len)))
+        } else if now.modified != self.modified {
+            Some("was modified".to_string())
+        } else {
+            None
+        }
+    }
+}
+
+/// Applies the policy to the files of a copy and collects those that
+/// changed under `warn`. Shared by the workers of a directory copy.
+#[derive(Debug, Default)]
+pub struct SourceWatch {
+    policy: ChangedSource,
+    changed: Mutex<Vec<(PathBuf, String)>>,
+}
+
+impl SourceWatch {
+    pub fn new(policy: ChangedSource) -> Self {
+        Self {
+            policy,
+            changed: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Compare `source`, just copied, with `before`. A change is recorded
+    /// under `warn` and an error otherwise.
+    pub fn check(&self, source: &Path, before: &SourceState) -> Result<(), FluxError> {
+        let Some(change) = before.change(&SourceState::read(source)?) else {
+            return Ok(());
+        };
+        if self.policy == ChangedSource::Warn {
+            tracing::warn!("{} {} while it was copied", source.display(), change);
+            if let Ok(mut changed) = self.changed.lock() {
+                changed.push((source.to_path_buf(), change));
+            }
+            return Ok(());
+        }
+        Err(FluxError::SourceChanged {
+            path: source.to_path_buf(),
+            change,
+        })
+    }
+
+    /// Copy `source`, planned at `planned` bytes, with `copy(size)` and
+    /// check it. Under `retry` a changed file is copied again at its new
+    /// size; `undo` is first given the bytes of the attempt thrown away.
+    pub fn copy<C, U>(
+        &self,
+        source: &Path,
+        planned: u64,
+        mut copy: C,
+        mut undo: U,
+    ) -> Result<u64, FluxError>
+    where
+        C: FnMut(u64) -> Result<u64, FluxError>,
+        U: FnMut(u64),
+    {
+        let mut size = planned;
+        let mut attempt = 0;
+        loop {
+            // A size differing from the plan is a change made before the copy
+            let before = SourceState {
+                len: size,
+                ..SourceState::read(source)?
+            };
+            let bytes = copy(size)?;
+            match self.check(source, &before) {
+                Err(FluxError::SourceChanged { change, .. })
+                    if self.policy == ChangedSource::Retry && attempt < RETRIES =>
+                {
+                    attempt += 1;
+                    tracing::warn!(
+                        "{} {} while it was copied; copying it again ({}/{})",
+                        source.display(),
+                        change,
+                        attempt,
+                        RETRIES
+                    );
+                    undo(bytes);
+                    size = SourceState::read(source)?.len;
+                }
+                result => return result.map(|()| bytes),
+            }
+        }
+    }
+
+    /// The files that changed while they were copied, with how.
+    pub fn changed(&self) -> Vec<(PathBuf, String)> {
+        self.changed.lock().map(|changed| changed.clone()).unwrap_or_default()
+    }
+
+    /// List the files that changed while they were copied. Printed even
+    /// when quiet: their copies may not match any version of the source.
+    pub fn report(&self) {
+        let changed = self.changed();
+        if changed.is_empty() {
+            return;
+        }
+        eprintln!(
+            "{} file(s) changed while they were copied; their copies may be inconsistent:",
+            changed.len()
+        );
+        for (path, change) in &changed {
+            eprintln!("  {}: {}", path.display(), change);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state(len: u64, secs: u64) -> SourceState {
+        SourceState {
+            len,
+            modified: Some(SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(secs)),
+        }
+    }
+
+    #[test]
+    fn changes_are_described() {
+        assert_eq!(state(10, 1).change(&state(10, 1)), None);
+        assert_eq!(state(10, 1).change(&state(10, 2)).as_deref(), Some("was modified"));
+        assert!(state(10, 1).change(&state(2048, 1)).unwrap().starts_with("grew from"));
+        assert!(state(10, 1).change(&state(5, 1)).unwrap().starts_with("shrank from"));
+    }
+
+    #[test]
+    fn warn_records_and_fail_refuses() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("log.txt");
+        std::fs::write(&file, "0123456789").unwrap();
+        let planned = SourceState::read(&file).unwrap();
+        std::fs::write(&file, "0123456789 and more").unwrap();
+
+        let warn = SourceWatch::new(ChangedSource::Warn);
+        assert!(warn.check(&file, &planned).is_ok());
+        assert_eq!(warn.changed().len(), 1);
+
+        let fail = SourceWatch::new(ChangedSource::Fail);
+        let err = fail.check(&file, &planned).unwrap_err();
+        assert!(matches!(err, FluxError::SourceChanged { .. }));
+        assert!(fail.changed().is_empty());
+    }
+
+    #[test]
+    fn retry_copies_again_at_the_new_size() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("growing.bin");
+        std::fs::write(&file, vec![0u8; 100]).unwrap();
+
+        // The first attempt sees the file grow; the second copies all of it
+        let watch = SourceWatch::new(ChangedSource::Retry);
+        let mut sizes = Vec::new();
+        let mut undone = 0;
+        let copied = watch.copy(
+            &file,
+            100,
+            |size| {
+                if sizes.is_empty() {
+                    std::fs::write(&file, vec![0u8; 150]).unwrap();
+                }
+                sizes.push(size);
+                Ok(size)
+            },
+            |bytes| undone += bytes,
+        );
+        assert_eq!(copied.unwrap(), 150);
+        assert_eq!(sizes, vec![100, 150]);
+        assert_eq!(undone, 100);
+
+        // A file that never settles fails after RETRIES more copies
+        let mut attempts = 0;
+        let result = watch.copy(
+            &file,
+            1,
+            |size| {
+                attempts += 1;
+                std::fs::write(&file, vec![0u8; 200 + attempts]).unwrap();
+                Ok(size)
+            },
+            |_| {},
+        );
+        assert!(matches!(result, Err(FluxError::SourceChanged { .. })));
+        assert_eq!(attempts, RETRIES as usize + 1);
+    }
+}
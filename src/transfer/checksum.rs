@@ -1,23 +1,184 @@
-//! BLAKE3 checksum functions for file and chunk integrity verification.
+//! Checksum functions for file and chunk integrity verification.
 //!
-//! Provides `hash_file` for whole-file hashing and `hash_chunk` for hashing
-//! a specific byte range of an open file using positional I/O.
+//! Provides `hash_file_with` for whole-file hashing and `hash_chunk_with`
+//! for hashing a specific byte range of an open file using positional I/O.
+//! Both take a `ChecksumAlgorithm` (`--checksum`); `hash_chunk` is the
+//! BLAKE3 shorthand:
+//!
+//! - `blake3` (default): cryptographic, used wherever integrity matters
+//! - `xxh3`: 128-bit xxHash, non-cryptographic and several times faster,
+//!   for quick comparisons of files that are not under attack
+//! - `sha256`: for matching checksums published elsewhere
+//!
+//! `hash_pair_with` hashes a copy and its source concurrently for
+//! `--verify`.
+//!
+//! Each algorithm implements `ChecksumHasher`, so new ones plug in without
+//! touching the callers.
 
 use std::fs::File;
 use std::io::Read;
 use std::path::Path;
 
+use serde::{Deserialize, Serialize};
+use sha2::Digest;
+
 use crate::error::FluxError;
 use crate::transfer::parallel::read_at;
 
 /// Buffer size for hashing: 64KB.
 const HASH_BUF_SIZE: usize = 64 * 1024;
 
-/// Compute the BLAKE3 hash of an entire file, returning the hex string.
+/// Hash function used for checksums.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum ChecksumAlgorithm {
+    /// BLAKE3 (cryptographic, default)
+    #[default]
+    Blake3,
+    /// XXH3-128 (fast, non-cryptographic)
+    Xxh3,
+    /// SHA-256 (cryptographic, widely published)
+    Sha256,
+}
+
+impl ChecksumAlgorithm {
+    /// Name used on the command line, in manifests and in protocol headers.
+    pub fn name(self) -> &'static str {
+        match self {
+            ChecksumAlgorithm::Blake3 => "blake3",
+            ChecksumAlgorithm::Xxh3 => "xxh3",
+            ChecksumAlgorithm::Sha256 => "sha256",
+        }
+    }
+
+    /// Name as shown in messages ("BLAKE3", "XXH3", "SHA-256").
+    pub fn label(self) -> &'static str {
+        match self {
+            ChecksumAlgorithm::Blake3 => "BLAKE3",
+            ChecksumAlgorithm::Xxh3 => "XXH3",
+            ChecksumAlgorithm::Sha256 => "SHA-256",
+        }
+    }
+
+    /// A fresh streaming hasher.
+    pub fn hasher(self) -> Box<dyn ChecksumHasher> {
+        match self {
+            ChecksumAlgorithm::Blake3 => Box::new(blake3::Hasher::new()),
+            ChecksumAlgorithm::Xxh3 => Box::new(xxhash_rust::xxh3::Xxh3::new()),
+            ChecksumAlgorithm::Sha256 => Box::new(sha2::Sha256::new()),
+        }
+    }
+
+    /// Checksum as sent in protocol headers: `<name>:<hex>`.
+    ///
+    /// BLAKE3 checksums stay bare hex, which is what peers that predate
+    /// algorithm selection send and expect.
+    #[cfg(feature = "net")]
+    pub fn tag(self, hex: &str) -> String {
+        match self {
+            ChecksumAlgorithm::Blake3 => hex.to_string(),
+            other => format!("{}:{}", other.name(), hex),
+        }
+    }
+
+    /// Algorithm of a checksum received in a protocol header (see `tag`).
+    #[cfg(feature = "net")]
+    pub fn of_tagged(checksum: &str) -> Result<Self, FluxError> {
+        let Some((name, _)) = checksum.split_once(':') else {
+            return Ok(ChecksumAlgorithm::Blake3);
+        };
+        match name {
+            "blake3" => Ok(ChecksumAlgorithm::Blake3),
+            "xxh3" => Ok(ChecksumAlgorithm::Xxh3),
+            "sha256" => Ok(ChecksumAlgorithm::Sha256),
+            _ => Err(FluxError::TransferError(format!(
+                "Unsupported checksum algorithm '{}'",
+                name
+            ))),
+        }
+    }
+}
+
+impl std::fmt::Display for ChecksumAlgorithm {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Streaming hash state of one checksum algorithm.
+pub trait ChecksumHasher: Send {
+    /// Feed more data.
+    fn update(&mut self, data: &[u8]);
+
+    /// Hash of everything fed so far, as lowercase hex. Does not reset the
+    /// state, so more data may follow.
+    fn finish_hex(&self) -> String;
+
+    /// Algorithm this hasher implements.
+    fn algorithm(&self) -> ChecksumAlgorithm;
+}
+
+impl std::fmt::Debug for dyn ChecksumHasher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "ChecksumHasher({})", self.algorithm())
+    }
+}
+
+impl ChecksumHasher for blake3::Hasher {
+    fn update(&mut self, data: &[u8]) {
+        blake3::Hasher::update(self, data);
+    }
+
+    fn finish_hex(&self) -> String {
+        self.finalize().to_hex().to_string()
+    }
+
+    fn algorithm(&self) -> ChecksumAlgorithm {
+        ChecksumAlgorithm::Blake3
+    }
+}
+
+impl ChecksumHasher for xxhash_rust::xxh3::Xxh3 {
+    fn update(&mut self, data: &[u8]) {
+        xxhash_rust::xxh3::Xxh3::update(self, data);
+    }
+
+    fn finish_hex(&self) -> String {
+        format!("{:032x}", self.digest128())
+    }
+
+    fn algorithm(&self) -> ChecksumAlgorithm {
+        ChecksumAlgorithm::Xxh3
+    }
+}
+
+impl ChecksumHasher for sha2::Sha256 {
+    fn update(&mut self, data: &[u8]) {
+        Digest::update(self, data);
+    }
+
+    fn finish_hex(&self) -> String {
+        self.clone()
+            .finalize()
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect()
+    }
+
+    fn algorithm(&self) -> ChecksumAlgorithm {
+        ChecksumAlgorithm::Sha256
+    }
+}
+
+/// Compute the hash of an entire file with `algorithm`, returning the hex
+/// string.
 ///
-/// Opens the file, reads it in 64KB chunks through a BLAKE3 Hasher,
+/// Opens the file, reads it in 64KB chunks through the hasher,
 /// and returns the finalized hash as a lowercase hex string.
-pub fn hash_file(path: &Path) -> Result<String, FluxError> {
+pub fn hash_file_with(path: &Path, algorithm: ChecksumAlgorithm) -> Result<String, FluxError> {
     let mut file = File::open(path).map_err(|e| match e.kind() {
         std::io::ErrorKind::NotFound => FluxError::SourceNotFound {
             path: path.to_path_buf(),
@@ -28,7 +189,7 @@ pub fn hash_file(path: &Path) -> Result<String, FluxError> {
         _ => FluxError::Io { source: e },
     })?;
 
-    let mut hasher = blake3::Hasher::new();
+    let mut hasher = algorithm.hasher();
     let mut buf = [0u8; HASH_BUF_SIZE];
 
     loop {
@@ -39,7 +200,23 @@ pub fn hash_file(path: &Path) -> Result<String, FluxError> {
         hasher.update(&buf[..n]);
     }
 
-    Ok(hasher.finalize().to_hex().to_string())
+    Ok(hasher.finish_hex())
+}
+
+/// Hash a copied file and its source with `algorithm` (`--verify`), both
+/// at once with `rayon::join`, and return `(source, dest)` hashes.
+///
+/// Inside a busy pool (a directory copy with every worker on a file) the
+/// two run one after the other on the calling thread.
+pub fn hash_pair_with(
+    source: &Path,
+    dest: &Path,
+    algorithm: ChecksumAlgorithm,
+) -> (Result<String, FluxError>, Result<String, FluxError>) {
+    rayon::join(
+        || hash_file_with(source, algorithm),
+        || hash_file_with(dest, algorithm),
+    )
 }
 
 /// Compute the BLAKE3 hash of a specific byte range of a file.
@@ -55,7 +232,17 @@ pub fn hash_file(path: &Path) -> Result<String, FluxError> {
 /// # Returns
 /// The BLAKE3 hash as a lowercase hex string.
 pub fn hash_chunk(file: &File, offset: u64, length: u64) -> Result<String, FluxError> {
-    let mut hasher = blake3::Hasher::new();
+    hash_chunk_with(file, offset, length, ChecksumAlgorithm::Blake3)
+}
+
+/// Like `hash_chunk`, with `algorithm` instead of BLAKE3.
+pub fn hash_chunk_with(
+    file: &File,
+    offset: u64,
+    length: u64,
+    algorithm: ChecksumAlgorithm,
+) -> Result<String, FluxError> {
+    let mut hasher = algorithm.hasher();
     let mut buf = [0u8; HASH_BUF_SIZE];
     let mut remaining = length;
     let mut pos = offset;
@@ -71,7 +258,7 @@ pub fn hash_chunk(file: &File, offset: u64, length: u64) -> Result<String, FluxE
         remaining -= n as u64;
     }
 
-    Ok(hasher.finalize().to_hex().to_string())
+    Ok(hasher.finish_hex())
 }
 
 #[cfg(test)]
@@ -92,8 +279,8 @@ mod tests {
         let content = b"Hello, BLAKE3! This is a test of file hashing.";
         let tmp = create_temp_file(content);
 
-        let hash1 = hash_file(tmp.path()).unwrap();
-        let hash2 = hash_file(tmp.path()).unwrap();
+        let hash1 = hash_file_with(tmp.path(), ChecksumAlgorithm::Blake3).unwrap();
+        let hash2 = hash_file_with(tmp.path(), ChecksumAlgorithm::Blake3).unwrap();
 
         // Same content produces same hash
         assert_eq!(hash1, hash2);
@@ -108,7 +295,7 @@ mod tests {
         let content = b"Full file hash should match chunk hash of entire range.";
         let tmp = create_temp_file(content);
 
-        let file_hash = hash_file(tmp.path()).unwrap();
+        let file_hash = hash_file_with(tmp.path(), ChecksumAlgorithm::Blake3).unwrap();
 
         let file = File::open(tmp.path()).unwrap();
         let chunk_hash = hash_chunk(&file, 0, content.len() as u64).unwrap();
@@ -136,7 +323,7 @@ mod tests {
     fn hash_file_empty_file_produces_valid_hash() {
         let tmp = create_temp_file(b"");
 
-        let hash = hash_file(tmp.path()).unwrap();
+        let hash = hash_file_with(tmp.path(), ChecksumAlgorithm::Blake3).unwrap();
 
         // Empty file should produce a valid 64-char hex hash
         assert_eq!(hash.len(), 64);
@@ -160,9 +347,23 @@ mod tests {
         assert_eq!(hash, expected);
     }
 
+    #[test]
+    fn hash_pair_matches_hashing_each_file() {
+        let source = create_temp_file(b"source content");
+        let dest = create_temp_file(b"copied content");
+        let (a, b) = hash_pair_with(source.path(), dest.path(), ChecksumAlgorithm::Xxh3);
+        assert_eq!(a.unwrap(), hash_file_with(source.path(), ChecksumAlgorithm::Xxh3).unwrap());
+        assert_eq!(b.unwrap(), hash_file_with(dest.path(), ChecksumAlgorithm::Xxh3).unwrap());
+
+        let missing = source.path().with_extension("missing");
+        let (a, b) = hash_pair_with(source.path(), &missing, ChecksumAlgorithm::Blake3);
+        assert!(a.is_ok());
+        assert!(b.is_err());
+    }
+
     #[test]
     fn hash_file_nonexistent_returns_error() {
-        let result = hash_file(Path::new("/nonexistent/file.bin"));
+        let result = hash_file_with(Path::new("/nonexistent/file.bin"), ChecksumAlgorithm::Blake3);
         assert!(result.is_err());
     }
 
@@ -172,10 +373,77 @@ mod tests {
         let content: Vec<u8> = (0..200_000u32).map(|i| (i % 256) as u8).collect();
         let tmp = create_temp_file(&content);
 
-        let hash = hash_file(tmp.path()).unwrap();
+        let hash = hash_file_with(tmp.path(), ChecksumAlgorithm::Blake3).unwrap();
 
         // Verify against direct blake3 computation
         let expected = blake3::hash(&content).to_hex().to_string();
         assert_eq!(hash, expected);
     }
+
+    #[test]
+    fn algorithms_produce_known_digests() {
+        let tmp = create_temp_file(b"abc");
+
+        let sha = hash_file_with(tmp.path(), ChecksumAlgorithm::Sha256).unwrap();
+        assert_eq!(
+            sha,
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+
+        let xxh = hash_file_with(tmp.path(), ChecksumAlgorithm::Xxh3).unwrap();
+        let expected = format!("{:032x}", xxhash_rust::xxh3::xxh3_128(b"abc"));
+        assert_eq!(xxh, expected);
+        assert_eq!(xxh.len(), 32);
+
+        let blake = hash_file_with(tmp.path(), ChecksumAlgorithm::Blake3).unwrap();
+        assert_eq!(blake, blake3::hash(b"abc").to_hex().to_string());
+    }
+
+    #[test]
+    fn chunk_hash_matches_file_hash_for_every_algorithm() {
+        let content: Vec<u8> = (0..150_000u32).map(|i| (i % 251) as u8).collect();
+        let tmp = create_temp_file(&content);
+        let file = File::open(tmp.path()).unwrap();
+
+        for algorithm in [
+            ChecksumAlgorithm::Blake3,
+            ChecksumAlgorithm::Xxh3,
+            ChecksumAlgorithm::Sha256,
+        ] {
+            let whole = hash_file_with(tmp.path(), algorithm).unwrap();
+            let chunk = hash_chunk_with(&file, 0, content.len() as u64, algorithm).unwrap();
+            assert_eq!(whole, chunk, "{}", algorithm);
+        }
+    }
+
+    #[cfg(feature = "net")]
+    #[test]
+    fn tagged_checksums_roundtrip() {
+        let hex = "00ff";
+        assert_eq!(ChecksumAlgorithm::Blake3.tag(hex), "00ff");
+        assert_eq!(ChecksumAlgorithm::Xxh3.tag(hex), "xxh3:00ff");
+
+        for algorithm in [
+            ChecksumAlgorithm::Blake3,
+            ChecksumAlgorithm::Xxh3,
+            ChecksumAlgorithm::Sha256,
+        ] {
+            let tagged = algorithm.tag(hex);
+            assert_eq!(ChecksumAlgorithm::of_tagged(&tagged).unwrap(), algorithm);
+        }
+        assert!(ChecksumAlgorithm::of_tagged("md5:00ff").is_err());
+    }
+
+    #[test]
+    fn hasher_can_continue_after_finish() {
+        let mut hasher = ChecksumAlgorithm::Sha256.hasher();
+        hasher.update(b"ab");
+        let partial = hasher.finish_hex();
+        hasher.update(b"c");
+        assert_ne!(partial, hasher.finish_hex());
+        assert_eq!(
+            hasher.finish_hex(),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
 }
@@ -0,0 +1,325 @@
+//! Checksums of local files, kept between runs.
+//!
+//! `flux sync --compare checksum` hashes every file that exists on both
+//! sides, and most of them have not changed since the last run. Their
+//! checksums are kept in the `checksums` table of the state database
+//! (`crate::state`), keyed by absolute path, and reused while the file's
+//! size and modification time stay the same. Rows are looked up one at a
+//! time, so a large cache is not read in full for a small sync.
+//!
+//! Files modified in the last few seconds are hashed but not cached: a write
+//! that lands within the same mtime tick would otherwise go unnoticed.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use rusqlite::{params, Connection, OptionalExtension, Transaction};
+use serde::{Deserialize, Serialize};
+
+use crate::error::FluxError;
+use crate::transfer::checksum::{hash_file_with, ChecksumAlgorithm};
+
+/// Entries kept at most; the least recently used are dropped first.
+const MAX_ENTRIES: usize = 200_000;
+
+/// Files modified more recently than this are not cached.
+const SETTLE_TIME: Duration = Duration::from_secs(2);
+
+/// Checksum of one file as it was when hashed.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct CacheEntry {
+    size: u64,
+    /// Modification time in nanoseconds since the Unix epoch
+    mtime_ns: u64,
+    algorithm: ChecksumAlgorithm,
+    checksum: String,
+    /// Unix time of the last lookup, for pruning
+    used: i64,
+}
+
+/// Checksums of files by absolute path.
+#[derive(Debug, Default)]
+pub struct ChecksumCache {
+    /// The state database (`None` keeps the cache in memory)
+    db: Option<Connection>,
+    /// Entries hashed or looked up since the last save; in memory, all of
+    /// them
+    entries: BTreeMap<String, CacheEntry>,
+}
+
+impl ChecksumCache {
+    /// Open the cache in the state database in `data_dir` (if the database
+    /// cannot be opened, checksums are only cached for this run).
+    pub fn load(data_dir: &Path) -> Self {
+        match crate::state::open(data_dir) {
+            Ok(db) => Self {
+                db: Some(db),
+                entries: BTreeMap::new(),
+            },
+            Err(e) => {
+                tracing::warn!("Checksums will not be cached: {}", e);
+                Self::in_memory()
+            }
+        }
+    }
+
+    /// A cache kept in memory only.
+    pub fn in_memory() -> Self {
+        Self::default()
+    }
+
+    /// The cache in the data directory, or an in-memory one if there is none.
+    pub fn open() -> Self {
+        match crate::config::paths::flux_data_dir() {
+            Ok(data_dir) => Self::load(&data_dir),
+            Err(e) => {
+                tracing::warn!("Checksums will not be cached: {}", e);
+                Self::in_memory()
+            }
+        }
+    }
+
+    /// `algorithm` checksum of the file at `path`, from the cache if the file
+    /// has not changed since it was last hashed.
+    pub fn checksum(
+        &mut self,
+        path: &Path,
+        algorithm: ChecksumAlgorithm,
+    ) -> Result<String, FluxError> {
+        let meta = std::fs::metadata(path)?;
+        let key = std::path::absolute(path)
+            .ok()
+            .and_then(|p| p.to_str().map(str::to_string));
+        let modified = meta.modified().ok();
+        let mtime_ns = modified
+            .and_then(|m| m.duration_since(UNIX_EPOCH).ok())
+            .and_then(|d| u64::try_from(d.as_nanos()).ok());
+        let (Some(key), Some(mtime_ns)) = (key, mtime_ns) else {
+            return hash_file_with(path, algorithm);
+        };
+
+        let now = chrono::Utc::now().timestamp();
+        if let Some(mut entry) = self.lookup(&key, algorithm) {
+            if entry.size == meta.len() && entry.mtime_ns == mtime_ns {
+                entry.used = now;
+                let checksum = entry.checksum.clone();
+                self.entries.insert(key, entry);
+                return Ok(checksum);
+            }
+        }
+
+        let checksum = hash_file_with(path, algorithm)?;
+        let settled = modified
+            .and_then(|m| SystemTime::now().duration_since(m).ok())
+            .is_some_and(|age| age >= SETTLE_TIME);
+        if settled {
+            self.entries.insert(
+                key,
+                CacheEntry {
+                    size: meta.len(),
+                    mtime_ns,
+                    algorithm,
+                    checksum: checksum.clone(),
+                    used: now,
+                },
+            );
+        }
+        Ok(checksum)
+    }
+
+    /// The cached `algorithm` entry for `key`, unsaved changes first. A
+    /// database that cannot be read counts as a miss.
+    fn lookup(&self, key: &str, algorithm: ChecksumAlgorithm) -> Option<CacheEntry> {
+        if let Some(entry) = self.entries.get(key) {
+            return (entry.algorithm == algorithm).then(|| entry.clone());
+        }
+        let db = self.db.as_ref()?;
+        let row = db
+            .query_row(
+                "SELECT size, mtime_ns, checksum, used FROM checksums
+                 WHERE path = ?1 AND algorithm = ?2",
+                params![key, algorithm.name()],
+                |row| {
+                    Ok(CacheEntry {
+                        size: row.get(0)?,
+                        mtime_ns: row.get(1)?,
+                        algorithm,
+                        checksum: row.get(2)?,
+                        used: row.get(3)?,
+                    })
+                },
+            )
+            .optional();
+        match row {
+            Ok(entry) => entry,
+            Err(e) => {
+                tracing::warn!("Failed to read checksum cache: {}", e);
+                None
+            }
+        }
+    }
+
+    /// Write new and used entries to the database and drop the least
+    /// recently used past `MAX_ENTRIES`. Failures are logged: the next run
+    /// just hashes again.
+    pub fn save(&mut self) {
+        let Some(ref mut db) = self.db else { return };
+        if self.entries.is_empty() {
+            return;
+        }
+        let result = db.transaction().map_err(FluxError::from).and_then(|tx| {
+            write_entries(&tx, &self.entries)?;
+            tx.execute(
+                "DELETE FROM checksums WHERE path IN
+                    (SELECT path FROM checksums ORDER BY used DESC LIMIT -1 OFFSET ?1)",
+                params![MAX_ENTRIES as i64],
+            )?;
+            tx.commit()?;
+            Ok(())
+        });
+        match result {
+            Ok(()) => self.entries.clear(),
+            Err(e) => tracing::warn!("Failed to save checksum cache: {}", e),
+        }
+    }
+}
+
+/// Insert or replace `entries` in the `checksums` table.
+fn write_entries(
+    db: &Connection,
+    entries: &BTreeMap<String, CacheEntry>,
+) -> Result<(), FluxError> {
+    let mut insert = db.prepare_cached(
+        "INSERT OR REPLACE INTO checksums (path, size, mtime_ns, algorithm, checksum, used)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+    )?;
+    for (path, entry) in entries {
+        insert.execute(params![
+            path,
+            entry.size,
+            entry.mtime_ns,
+            entry.algorithm.name(),
+            entry.checksum,
+            entry.used
+        ])?;
+    }
+    Ok(())
+}
+
+/// Import the `checksum_cache.json` of earlier versions. The cache only
+/// saves time, so a file that cannot be read is skipped.
+pub(crate) fn import(tx: &Transaction, path: &Path) -> Result<(), FluxError> {
+    let entries: BTreeMap<String, CacheEntry> = match std::fs::read_to_string(path)
+        .map_err(FluxError::from)
+        .and_then(|json| Ok(serde_json::from_str(&json)?))
+    {
+        Ok(entries) => entries,
+        Err(e) => {
+            tracing::warn!("Skipped unreadable {}: {}", path.display(), e);
+            return Ok(());
+        }
+    };
+    write_entries(tx, &entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    /// Write `content` and date the file an hour back, so it gets cached.
+    fn write_settled(path: &Path, content: &str) {
+        std::fs::write(path, content).unwrap();
+        let file = std::fs::File::options().write(true).open(path).unwrap();
+        file.set_modified(SystemTime::now() - Duration::from_secs(3600))
+            .unwrap();
+    }
+
+    #[test]
+    fn caches_settled_files_and_notices_changes() {
+        let dir = TempDir::new().unwrap();
+        let file = dir.path().join("a.txt");
+        write_settled(&file, "first");
+
+        let mut cache = ChecksumCache::in_memory();
+        let first = cache.checksum(&file, ChecksumAlgorithm::Xxh3).unwrap();
+        assert_eq!(first, hash_file_with(&file, ChecksumAlgorithm::Xxh3).unwrap());
+        assert_eq!(cache.entries.len(), 1);
+
+        // A cached value is returned as is
+        let key = cache.entries.keys().next().unwrap().clone();
+        cache.entries.get_mut(&key).unwrap().checksum = "cached".into();
+        assert_eq!(cache.checksum(&file, ChecksumAlgorithm::Xxh3).unwrap(), "cached");
+
+        // Another algorithm, or a new size, hashes again
+        let blake3 = cache.checksum(&file, ChecksumAlgorithm::Blake3).unwrap();
+        assert_eq!(blake3, hash_file_with(&file, ChecksumAlgorithm::Blake3).unwrap());
+        write_settled(&file, "second, longer");
+        let second = cache.checksum(&file, ChecksumAlgorithm::Blake3).unwrap();
+        assert_ne!(second, blake3);
+    }
+
+    #[test]
+    fn recently_modified_files_are_not_cached() {
+        let dir = TempDir::new().unwrap();
+        let file = dir.path().join("fresh.txt");
+        std::fs::write(&file, "just written").unwrap();
+
+        let mut cache = ChecksumCache::in_memory();
+        cache.checksum(&file, ChecksumAlgorithm::Blake3).unwrap();
+        assert!(cache.entries.is_empty());
+    }
+
+    #[test]
+    fn save_and_load_roundtrip() {
+        let data = TempDir::new().unwrap();
+        let dir = TempDir::new().unwrap();
+        let file = dir.path().join("a.txt");
+        write_settled(&file, "content");
+
+        let mut cache = ChecksumCache::load(data.path());
+        let checksum = cache.checksum(&file, ChecksumAlgorithm::Sha256).unwrap();
+        cache.save();
+        assert!(cache.entries.is_empty());
+        assert!(data.path().join(crate::state::DB_FILE).exists());
+
+        // A fresh cache reads the saved row instead of hashing again
+        let mut loaded = ChecksumCache::load(data.path());
+        let db = loaded.db.as_ref().unwrap();
+        let stored: String = db
+            .query_row("SELECT checksum FROM checksums", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(stored, checksum);
+        db.execute("UPDATE checksums SET checksum = 'cached'", []).unwrap();
+        assert_eq!(loaded.checksum(&file, ChecksumAlgorithm::Sha256).unwrap(), "cached");
+        assert_ne!(loaded.checksum(&file, ChecksumAlgorithm::Xxh3).unwrap(), "cached");
+    }
+
+    #[test]
+    fn legacy_cache_files_are_imported() {
+        let data = TempDir::new().unwrap();
+        let dir = TempDir::new().unwrap();
+        let file = dir.path().join("a.txt");
+        write_settled(&file, "content");
+
+        let mut cache = ChecksumCache::in_memory();
+        cache.checksum(&file, ChecksumAlgorithm::Blake3).unwrap();
+        let legacy = serde_json::to_string(&cache.entries).unwrap();
+        std::fs::write(data.path().join("checksum_cache.json"), legacy).unwrap();
+
+        let loaded = ChecksumCache::load(data.path());
+        let (key, entry) = cache.entries.iter().next().unwrap();
+        assert_eq!(loaded.lookup(key, ChecksumAlgorithm::Blake3).as_ref(), Some(entry));
+        assert!(data.path().join("checksum_cache.json.imported").exists());
+    }
+
+    #[test]
+    fn corrupt_legacy_cache_is_skipped() {
+        let data = TempDir::new().unwrap();
+        std::fs::write(data.path().join("checksum_cache.json"), "not json").unwrap();
+        let cache = ChecksumCache::load(data.path());
+        assert!(cache.db.is_some());
+        assert!(cache.lookup("/any", ChecksumAlgorithm::Blake3).is_none());
+    }
+}
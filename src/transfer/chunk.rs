@@ -6,6 +6,8 @@
 
 use serde::{Deserialize, Serialize};
 
+use crate::transfer::checksum::ChecksumAlgorithm;
+
 /// Describes one chunk of a file to transfer.
 ///
 /// Each chunk represents a contiguous byte range [offset, offset+length).
@@ -20,7 +22,8 @@ pub struct ChunkPlan {
     pub length: u64,
     /// Whether this chunk has been successfully transferred.
     pub completed: bool,
-    /// BLAKE3 hex hash of this chunk's data (populated after transfer).
+    /// Hex hash of this chunk's data (populated after transfer), using the
+    /// plan's checksum algorithm.
     pub checksum: Option<String>,
 }
 
@@ -36,8 +39,12 @@ pub struct TransferPlan {
     pub dest_path: String,
     /// Total file size in bytes.
     pub total_size: u64,
-    /// Whole-file BLAKE3 hex hash (populated after transfer if --verify).
+    /// Whole-file hex hash (populated after transfer if --verify).
     pub file_checksum: Option<String>,
+    /// Algorithm of `file_checksum` and the chunk checksums (plans written
+    /// before `--checksum` existed are BLAKE3).
+    #[serde(default)]
+    pub checksum_algorithm: ChecksumAlgorithm,
     /// The chunks that make up this transfer.
     pub chunks: Vec<ChunkPlan>,
 }
@@ -268,6 +275,7 @@ mod tests {
             dest_path: "/tmp/dest.bin".to_string(),
             total_size: 1000,
             file_checksum: None,
+            checksum_algorithm: ChecksumAlgorithm::Xxh3,
             chunks: chunk_file(1000, 2),
         };
         let json = serde_json::to_string_pretty(&plan).unwrap();
@@ -276,6 +284,8 @@ mod tests {
         assert_eq!(deserialized.dest_path, "/tmp/dest.bin");
         assert_eq!(deserialized.total_size, 1000);
         assert!(deserialized.file_checksum.is_none());
+        assert_eq!(deserialized.checksum_algorithm, ChecksumAlgorithm::Xxh3);
+        assert!(json.contains("\"checksum_algorithm\": \"xxh3\""));
         assert_eq!(deserialized.chunks.len(), 2);
         assert_eq!(deserialized.chunks[0].length, 500);
         assert_eq!(deserialized.chunks[1].length, 500);
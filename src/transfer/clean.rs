@@ -0,0 +1,293 @@
+//! `flux clean`: find and remove what aborted transfers left behind.
+//!
+//! Two kinds of leftovers are looked for under a directory:
+//!
+//! - Resume manifests (`<name>.flux-resume.json`) that cannot be read or
+//!   whose source is gone or has changed size, so the copy can never be
+//!   resumed. The partial temp file they describe goes with them.
+//! - Temp files (`.<name>.flux-tmp`, from atomic writes and interrupted
+//!   receives) not modified for `--older-than` days, with their manifest.
+//!
+//! A manifest's source may be a relative path, which only resolves from the
+//! directory the copy ran in. Such a source that cannot be found is given
+//! the same `--older-than` grace as temp files. Nothing is removed without
+//! `--yes`.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use bytesize::ByteSize;
+use walkdir::WalkDir;
+
+use crate::cli::args::CleanArgs;
+use crate::error::FluxError;
+use crate::transfer::atomic::is_temp_file;
+use crate::transfer::resume::TransferManifest;
+
+/// Suffix of resume manifests (see `TransferManifest::manifest_path`).
+const MANIFEST_SUFFIX: &str = ".flux-resume.json";
+
+/// A leftover file and why it is considered one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Leftover {
+    pub path: PathBuf,
+    pub size: u64,
+    pub reason: String,
+}
+
+/// Find leftovers under `root`, sorted by path.
+///
+/// Temp files (and sources given as relative paths that cannot be found)
+/// count as stale once they have not been modified for `max_age`.
+pub fn find_leftovers(root: &Path, max_age: Duration) -> Result<Vec<Leftover>, FluxError> {
+    if !root.exists() {
+        return Err(FluxError::SourceNotFound {
+            path: root.to_path_buf(),
+        });
+    }
+    let mut found = BTreeMap::new();
+    for entry in WalkDir::new(root)
+        .follow_links(false)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file())
+    {
+        let path = entry.path();
+        let name = entry.file_name().to_string_lossy();
+        let age = entry
+            .metadata()
+            .ok()
+            .and_then(|m| m.modified().ok())
+            .and_then(|m| SystemTime::now().duration_since(m).ok())
+            .unwrap_or_default();
+
+        if let Some(data) = name.strip_suffix(MANIFEST_SUFFIX) {
+            let Some(reason) = orphaned_manifest(path, age >= max_age) else {
+                continue;
+            };
+            // The partial data of an atomic copy is useless without it
+            let data = path.with_file_name(data);
+            if is_temp_file(&data) && data.is_file() {
+                add(&mut found, &data, "temp file of an orphaned resume manifest");
+            }
+            add(&mut found, path, &reason);
+        } else if is_temp_file(path) && age >= max_age {
+            add(&mut found, path, &format!("not modified for {}", days(age)));
+            // A manifest without its data would resume into an empty file
+            let manifest = TransferManifest::manifest_path(path);
+            if manifest.is_file() {
+                add(&mut found, &manifest, "resume manifest of a stale temp file");
+            }
+        }
+    }
+    Ok(found.into_values().collect())
+}
+
+/// Why the manifest at `path` can never be resumed, if it cannot.
+///
+/// `stale` tells whether the manifest is older than the grace period, for
+/// sources given as relative paths.
+fn orphaned_manifest(path: &Path, stale: bool) -> Option<String> {
+    let manifest = match std::fs::read_to_string(path)
+        .map_err(FluxError::from)
+        .and_then(|json| TransferManifest::parse(path, &json))
+    {
+        Ok(manifest) => manifest,
+        // Left for the newer flux that wrote it
+        Err(FluxError::NewerFormat { .. }) => return None,
+        Err(_) => return Some("unreadable resume manifest".to_string()),
+    };
+    match std::fs::metadata(&manifest.source) {
+        Ok(meta) if manifest.is_compatible(&manifest.source, meta.len()) => None,
+        Ok(_) => Some(format!("source {} has changed size", manifest.source.display())),
+        Err(_) if manifest.source.is_absolute() || stale => Some(format!(
+            "source {} no longer exists",
+            manifest.source.display()
+        )),
+        Err(_) => None,
+    }
+}
+
+/// Record `path` once; the first reason found wins.
+fn add(found: &mut BTreeMap<PathBuf, Leftover>, path: &Path, reason: &str) {
+    found.entry(path.to_path_buf()).or_insert_with(|| Leftover {
+        path: path.to_path_buf(),
+        size: std::fs::metadata(path).map(|m| m.len()).unwrap_or(0),
+        reason: reason.to_string(),
+    });
+}
+
+/// "3 days", rounded down.
+fn days(age: Duration) -> String {
+    match age.as_secs() / 86_400 {
+        1 => "1 day".to_string(),
+        n => format!("{} days", n),
+    }
+}
+
+/// Execute `flux clean`.
+pub fn execute_clean(args: CleanArgs, quiet: bool) -> Result<(), FluxError> {
+    let max_age = Duration::from_secs(args.older_than.saturating_mul(86_400));
+    let leftovers = find_leftovers(&args.path, max_age)?;
+    if leftovers.is_empty() {
+        if !quiet {
+            eprintln!("No leftovers found under {}", args.path.display());
+        }
+        return Ok(());
+    }
+
+    let total: u64 = leftovers.iter().map(|l| l.size).sum();
+    for leftover in &leftovers {
+        let size = ByteSize(leftover.size).to_string();
+        println!("{:>10}  {}  ({})", size, leftover.path.display(), leftover.reason);
+    }
+    if !args.yes {
+        if !quiet {
+            eprintln!(
+                "{} leftover(s), {}. Run again with --yes to remove them.",
+                leftovers.len(),
+                ByteSize(total)
+            );
+        }
+        return Ok(());
+    }
+
+    let mut failed = 0usize;
+    let mut freed = 0u64;
+    for leftover in &leftovers {
+        match std::fs::remove_file(&leftover.path) {
+            Ok(()) => freed += leftover.size,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => {
+                eprintln!("Failed to remove {}: {}", leftover.path.display(), e);
+                failed += 1;
+            }
+        }
+    }
+    if !quiet {
+        eprintln!(
+            "Removed {} leftover(s), freed {}",
+            leftovers.len() - failed,
+            ByteSize(freed)
+        );
+    }
+    if failed > 0 {
+        return Err(FluxError::TransferError(format!(
+            "{} leftover(s) could not be removed",
+            failed
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transfer::atomic::temp_path;
+    use crate::transfer::chunk::chunk_file;
+    use tempfile::TempDir;
+
+    const WEEK: Duration = Duration::from_secs(7 * 86_400);
+
+    /// Date `path` back by `days`.
+    fn backdate(path: &Path, days: u64) {
+        let file = std::fs::File::options().write(true).open(path).unwrap();
+        file.set_modified(SystemTime::now() - Duration::from_secs(days * 86_400))
+            .unwrap();
+    }
+
+    /// Save a manifest for an atomic copy of `source` into `dest`, with its
+    /// partial temp file.
+    fn interrupted_copy(source: &Path, dest: &Path) -> PathBuf {
+        let size = std::fs::metadata(source).map(|m| m.len()).unwrap_or(10);
+        let temp = temp_path(dest);
+        std::fs::write(&temp, "part").unwrap();
+        let chunks = chunk_file(size, 1);
+        TransferManifest::new(source.to_path_buf(), temp.clone(), size, chunks, false)
+            .save(&temp)
+            .unwrap();
+        temp
+    }
+
+    fn names(leftovers: &[Leftover]) -> Vec<String> {
+        leftovers
+            .iter()
+            .map(|l| l.path.file_name().unwrap().to_string_lossy().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn live_transfers_are_left_alone() {
+        let dir = TempDir::new().unwrap();
+        let source = dir.path().join("big.iso");
+        std::fs::write(&source, "0123456789").unwrap();
+        std::fs::create_dir_all(dir.path().join("out")).unwrap();
+        interrupted_copy(&source, &dir.path().join("out/big.iso"));
+        std::fs::write(dir.path().join(".recent.flux-tmp"), "x").unwrap();
+
+        assert!(find_leftovers(dir.path(), WEEK).unwrap().is_empty());
+    }
+
+    #[test]
+    fn manifests_without_source_are_orphaned_with_their_temp_file() {
+        let dir = TempDir::new().unwrap();
+        let source = dir.path().join("gone.bin");
+        interrupted_copy(&source, &dir.path().join("copy.bin"));
+
+        let found = find_leftovers(dir.path(), WEEK).unwrap();
+        assert_eq!(
+            names(&found),
+            [".copy.bin.flux-tmp", ".copy.bin.flux-tmp.flux-resume.json"]
+        );
+        assert!(found[1].reason.contains("no longer exists"));
+        assert_eq!(found[0].size, 4);
+    }
+
+    #[test]
+    fn changed_sources_and_corrupt_manifests_are_orphaned() {
+        let dir = TempDir::new().unwrap();
+        let source = dir.path().join("src.bin");
+        std::fs::write(&source, "short").unwrap();
+        let temp = interrupted_copy(&source, &dir.path().join("a.bin"));
+        std::fs::write(&source, "grown since").unwrap();
+        std::fs::write(dir.path().join("b.bin.flux-resume.json"), "{").unwrap();
+        // A newer flux's manifest is not ours to judge
+        let newer = r#"{"version": 99, "source": "/gone"}"#;
+        std::fs::write(dir.path().join("c.bin.flux-resume.json"), newer).unwrap();
+
+        let found = find_leftovers(dir.path(), WEEK).unwrap();
+        assert_eq!(found.len(), 3);
+        let manifest = TransferManifest::manifest_path(&temp);
+        let reason = |path: &Path| &found.iter().find(|l| l.path == path).unwrap().reason;
+        assert!(reason(&manifest).contains("changed size"));
+        let corrupt = dir.path().join("b.bin.flux-resume.json");
+        assert_eq!(reason(&corrupt), "unreadable resume manifest");
+    }
+
+    #[test]
+    fn stale_temp_files_take_their_manifest() {
+        let dir = TempDir::new().unwrap();
+        let source = dir.path().join("src.bin");
+        std::fs::write(&source, "0123456789").unwrap();
+        let temp = interrupted_copy(&source, &dir.path().join("a.bin"));
+        backdate(&temp, 10);
+        let received = dir.path().join(".photo.jpg.flux-tmp");
+        std::fs::write(&received, "partial").unwrap();
+        backdate(&received, 8);
+
+        let found = find_leftovers(dir.path(), WEEK).unwrap();
+        assert_eq!(
+            names(&found),
+            [".a.bin.flux-tmp", ".a.bin.flux-tmp.flux-resume.json", ".photo.jpg.flux-tmp"]
+        );
+        assert_eq!(found[2].reason, "not modified for 8 days");
+        assert!(find_leftovers(dir.path(), 30 * WEEK).unwrap().is_empty());
+    }
+
+    #[test]
+    fn missing_root_is_an_error() {
+        let dir = TempDir::new().unwrap();
+        assert!(find_leftovers(&dir.path().join("nope"), WEEK).is_err());
+    }
+}
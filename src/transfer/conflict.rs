@@ -3,77 +3,155 @@
 //! Provides conflict strategy application (overwrite/skip/rename/ask)
 //! and unique filename generation for the rename strategy.
 
-use std::io::IsTerminal;
+use std::io::{IsTerminal, Read};
 use std::path::{Path, PathBuf};
+use std::sync::Mutex;
 
 use crate::config::types::ConflictStrategy;
 use crate::error::FluxError;
 
-/// Resolve a file conflict at the destination path.
+/// Resolves destination conflicts for one copy operation.
 ///
-/// Returns `Some(path)` with the final destination to use, or `None` to skip the file.
-///
-/// - If dest does not exist: always returns `Some(dest)`.
-/// - Overwrite: returns `Some(dest)` (caller will overwrite).
-/// - Skip: prints message to stderr, returns `None`.
-/// - Rename: generates a unique name (file_1.txt, file_2.txt, etc.), returns `Some(renamed)`.
-/// - Ask: prompts user interactively if stdin is a TTY; falls back to Skip if not a TTY.
-pub fn resolve_conflict(
-    dest: &Path,
+/// Remembers "overwrite all" / "skip all" answers to the `ask` prompt, so
+/// later files of the same directory copy are not asked again.
+#[derive(Debug)]
+pub struct ConflictResolver {
     strategy: ConflictStrategy,
-) -> Result<Option<PathBuf>, FluxError> {
-    if !dest.exists() {
-        return Ok(Some(dest.to_path_buf()));
+    /// Used by `Ask` when stdin is not a TTY
+    fallback: ConflictStrategy,
+    /// Answer given to every remaining conflict ("all" choices)
+    remembered: Mutex<Option<ConflictStrategy>>,
+}
+
+impl ConflictResolver {
+    pub fn new(strategy: ConflictStrategy, fallback: ConflictStrategy) -> Self {
+        Self {
+            strategy,
+            fallback,
+            remembered: Mutex::new(None),
+        }
     }
 
-    match strategy {
-        ConflictStrategy::Overwrite => Ok(Some(dest.to_path_buf())),
-        ConflictStrategy::Skip => {
-            eprintln!("Skipped (exists): {}", dest.display());
-            Ok(None)
+    /// Resolve a conflict between `source` and the destination path.
+    ///
+    /// Returns `Some(path)` with the final destination to use, or `None` to skip the file.
+    ///
+    /// - If dest does not exist: always returns `Some(dest)`.
+    /// - Overwrite: returns `Some(dest)` (caller will overwrite).
+    /// - Skip: prints message to stderr, returns `None`.
+    /// - Rename: generates a unique name (`file (1).txt`, ...), returns `Some(renamed)`.
+    /// - Ask: skips identical files, otherwise prompts if stdin is a TTY;
+    ///   applies the configured fallback if not.
+    pub fn resolve(&self, source: &Path, dest: &Path) -> Result<Option<PathBuf>, FluxError> {
+        if !dest.exists() {
+            return Ok(Some(dest.to_path_buf()));
         }
+        if self.strategy != ConflictStrategy::Ask {
+            return Ok(apply(dest, self.strategy));
+        }
+
+        if let Some(answer) = *self.remembered.lock().expect("conflict answer lock") {
+            return Ok(apply(dest, answer));
+        }
+        if files_identical(source, dest) {
+            eprintln!("Skipped (identical): {}", dest.display());
+            return Ok(None);
+        }
+        if !std::io::stdin().is_terminal() {
+            // Non-TTY stdin: never block, use the configured answer
+            return Ok(match self.fallback {
+                ConflictStrategy::Ask => {
+                    eprintln!("Skipped (exists, non-interactive): {}", dest.display());
+                    None
+                }
+                fallback => apply(dest, fallback),
+            });
+        }
+
+        eprint!(
+            "{} exists and differs. (o)verwrite / (s)kip / (r)ename / overwrite (a)ll / skip a(l)l? ",
+            dest.display()
+        );
+        let mut input = String::new();
+        std::io::stdin()
+            .read_line(&mut input)
+            .map_err(|e| FluxError::Io { source: e })?;
+        let answer = match input.trim().to_lowercase().as_str() {
+            "o" | "overwrite" => ConflictStrategy::Overwrite,
+            "r" | "rename" => ConflictStrategy::Rename,
+            "a" | "all-overwrite" => self.remember(ConflictStrategy::Overwrite),
+            "l" | "all-skip" => self.remember(ConflictStrategy::Skip),
+            // Default to skip on "s", "skip", or any other input
+            _ => ConflictStrategy::Skip,
+        };
+        Ok(apply(dest, answer))
+    }
+
+    fn remember(&self, answer: ConflictStrategy) -> ConflictStrategy {
+        *self.remembered.lock().expect("conflict answer lock") = Some(answer);
+        answer
+    }
+}
+
+/// Apply a non-interactive strategy to an existing destination.
+fn apply(dest: &Path, strategy: ConflictStrategy) -> Option<PathBuf> {
+    match strategy {
+        ConflictStrategy::Overwrite => Some(dest.to_path_buf()),
         ConflictStrategy::Rename => {
             let renamed = find_unique_name(dest);
             eprintln!("Renamed: {} -> {}", dest.display(), renamed.display());
-            Ok(Some(renamed))
+            Some(renamed)
         }
-        ConflictStrategy::Ask => {
-            if std::io::stdin().is_terminal() {
-                eprint!(
-                    "{} exists. (o)verwrite / (s)kip / (r)ename? ",
-                    dest.display()
-                );
-                let mut input = String::new();
-                std::io::stdin()
-                    .read_line(&mut input)
-                    .map_err(|e| FluxError::Io { source: e })?;
-                match input.trim().to_lowercase().as_str() {
-                    "o" | "overwrite" => Ok(Some(dest.to_path_buf())),
-                    "r" | "rename" => {
-                        let renamed = find_unique_name(dest);
-                        eprintln!("Renamed: {} -> {}", dest.display(), renamed.display());
-                        Ok(Some(renamed))
-                    }
-                    _ => {
-                        // Default to skip on "s", "skip", or any other input
-                        eprintln!("Skipped: {}", dest.display());
-                        Ok(None)
-                    }
-                }
-            } else {
-                // Non-TTY stdin: fall back to Skip to avoid blocking
-                eprintln!("Skipped (exists, non-interactive): {}", dest.display());
-                Ok(None)
-            }
+        ConflictStrategy::Skip | ConflictStrategy::Ask => {
+            eprintln!("Skipped (exists): {}", dest.display());
+            None
         }
     }
 }
 
-/// Generate a unique file name by appending a numeric suffix.
+/// Whether two files have the same contents. Unreadable files count as
+/// different.
+fn files_identical(a: &Path, b: &Path) -> bool {
+    let same_len = match (std::fs::metadata(a), std::fs::metadata(b)) {
+        (Ok(ma), Ok(mb)) => ma.len() == mb.len(),
+        _ => false,
+    };
+    if !same_len {
+        return false;
+    }
+    let (Ok(mut fa), Ok(mut fb)) = (std::fs::File::open(a), std::fs::File::open(b)) else {
+        return false;
+    };
+    let mut buf_a = vec![0u8; 64 * 1024];
+    let mut buf_b = vec![0u8; 64 * 1024];
+    loop {
+        let n = match fa.read(&mut buf_a) {
+            Ok(n) => n,
+            Err(_) => return false,
+        };
+        if n == 0 {
+            // Same length, so `b` is at its end too
+            return true;
+        }
+        if fb.read_exact(&mut buf_b[..n]).is_err() || buf_a[..n] != buf_b[..n] {
+            return false;
+        }
+    }
+}
+
+/// Generate a unique file name for `path` in the style of `file (1).txt`.
 ///
-/// Given `file.txt`, tries `file_1.txt`, `file_2.txt`, ... up to `file_9999.txt`.
-/// If all are taken, falls back to appending a Unix timestamp.
+/// Always numbers the name, even if `path` itself is free.
 pub fn find_unique_name(path: &Path) -> PathBuf {
+    find_unique_path(path, |p| p.exists())
+}
+
+/// Find the first free `stem (N).ext` sibling of `path`, where `taken`
+/// decides whether a candidate is in use.
+///
+/// Tries `file (1).txt`, `file (2).txt`, ... up to `file (9999).txt`.
+/// If all are taken, falls back to appending a Unix timestamp.
+pub fn find_unique_path(path: &Path, taken: impl Fn(&Path) -> bool) -> PathBuf {
     let stem = path
         .file_stem()
         .unwrap_or_default()
@@ -85,8 +163,8 @@ pub fn find_unique_name(path: &Path) -> PathBuf {
     let parent = path.parent().unwrap_or_else(|| Path::new("."));
 
     for i in 1..=9999 {
-        let candidate = parent.join(format!("{}_{}{}", stem, i, ext));
-        if !candidate.exists() {
+        let candidate = parent.join(format!("{} ({}){}", stem, i, ext));
+        if !taken(&candidate) {
             return candidate;
         }
     }
@@ -96,7 +174,7 @@ pub fn find_unique_name(path: &Path) -> PathBuf {
         .duration_since(std::time::UNIX_EPOCH)
         .unwrap_or_default()
         .as_secs();
-    parent.join(format!("{}_{}{}", stem, ts, ext))
+    parent.join(format!("{} ({}){}", stem, ts, ext))
 }
 
 #[cfg(test)]
@@ -104,62 +182,74 @@ mod tests {
     use super::*;
     use std::fs;
 
+    fn resolver(strategy: ConflictStrategy) -> ConflictResolver {
+        ConflictResolver::new(strategy, ConflictStrategy::Skip)
+    }
+
+    /// A source file with different contents than any existing dest.
+    fn source_in(dir: &Path) -> PathBuf {
+        let source = dir.join("source.bin");
+        fs::write(&source, "new data").unwrap();
+        source
+    }
+
     #[test]
     fn resolve_nonexistent_dest_returns_some() {
         let dir = tempfile::tempdir().unwrap();
+        let source = source_in(dir.path());
         let dest = dir.path().join("new_file.txt");
-        let result = resolve_conflict(&dest, ConflictStrategy::Overwrite).unwrap();
+        let result = resolver(ConflictStrategy::Overwrite).resolve(&source, &dest).unwrap();
         assert_eq!(result, Some(dest));
     }
 
     #[test]
     fn resolve_overwrite_returns_same_path() {
         let dir = tempfile::tempdir().unwrap();
+        let source = source_in(dir.path());
         let dest = dir.path().join("existing.txt");
         fs::write(&dest, "data").unwrap();
-        let result = resolve_conflict(&dest, ConflictStrategy::Overwrite).unwrap();
+        let result = resolver(ConflictStrategy::Overwrite).resolve(&source, &dest).unwrap();
         assert_eq!(result, Some(dest));
     }
 
     #[test]
     fn resolve_skip_returns_none() {
         let dir = tempfile::tempdir().unwrap();
+        let source = source_in(dir.path());
         let dest = dir.path().join("existing.txt");
         fs::write(&dest, "data").unwrap();
-        let result = resolve_conflict(&dest, ConflictStrategy::Skip).unwrap();
+        let result = resolver(ConflictStrategy::Skip).resolve(&source, &dest).unwrap();
         assert_eq!(result, None);
     }
 
     #[test]
     fn resolve_rename_returns_unique_name() {
         let dir = tempfile::tempdir().unwrap();
+        let source = source_in(dir.path());
         let dest = dir.path().join("file.txt");
         fs::write(&dest, "data").unwrap();
-        let result = resolve_conflict(&dest, ConflictStrategy::Rename).unwrap();
-        assert!(result.is_some());
-        let renamed = result.unwrap();
-        assert_ne!(renamed, dest);
-        assert!(renamed.to_string_lossy().contains("file_1.txt"));
+        let result = resolver(ConflictStrategy::Rename).resolve(&source, &dest).unwrap();
+        assert_eq!(result, Some(dir.path().join("file (1).txt")));
     }
 
     #[test]
     fn find_unique_name_generates_sequential_names() {
         let dir = tempfile::tempdir().unwrap();
         let base = dir.path().join("test.txt");
-        // base doesn't exist, so _1 is first candidate
+        // base doesn't exist, so (1) is first candidate
         let name = find_unique_name(&base);
-        assert_eq!(name, dir.path().join("test_1.txt"));
+        assert_eq!(name, dir.path().join("test (1).txt"));
     }
 
     #[test]
     fn find_unique_name_skips_existing() {
         let dir = tempfile::tempdir().unwrap();
         let base = dir.path().join("test.txt");
-        // Create _1 and _2, so _3 should be returned
-        fs::write(dir.path().join("test_1.txt"), "a").unwrap();
-        fs::write(dir.path().join("test_2.txt"), "b").unwrap();
+        // Create (1) and (2), so (3) should be returned
+        fs::write(dir.path().join("test (1).txt"), "a").unwrap();
+        fs::write(dir.path().join("test (2).txt"), "b").unwrap();
         let name = find_unique_name(&base);
-        assert_eq!(name, dir.path().join("test_3.txt"));
+        assert_eq!(name, dir.path().join("test (3).txt"));
     }
 
     #[test]
@@ -167,16 +257,70 @@ mod tests {
         let dir = tempfile::tempdir().unwrap();
         let base = dir.path().join("myfile");
         let name = find_unique_name(&base);
-        assert_eq!(name, dir.path().join("myfile_1"));
+        assert_eq!(name, dir.path().join("myfile (1)"));
+    }
+
+    #[test]
+    fn find_unique_path_uses_taken_predicate() {
+        let dir = tempfile::tempdir().unwrap();
+        let base = dir.path().join("a.tar.gz");
+        let name = find_unique_path(&base, |p| p.ends_with("a.tar (1).gz"));
+        assert_eq!(name, dir.path().join("a.tar (2).gz"));
     }
 
     #[test]
     fn resolve_ask_falls_back_to_skip_in_tests() {
-        // In tests, stdin is not a TTY, so Ask should fall back to Skip
+        // In tests, stdin is not a TTY, so Ask should use the fallback
         let dir = tempfile::tempdir().unwrap();
+        let source = source_in(dir.path());
         let dest = dir.path().join("existing.txt");
         fs::write(&dest, "data").unwrap();
-        let result = resolve_conflict(&dest, ConflictStrategy::Ask).unwrap();
+        let result = resolver(ConflictStrategy::Ask).resolve(&source, &dest).unwrap();
         assert_eq!(result, None);
     }
+
+    #[test]
+    fn resolve_ask_uses_configured_fallback() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = source_in(dir.path());
+        let dest = dir.path().join("existing.txt");
+        fs::write(&dest, "data").unwrap();
+        let resolver = ConflictResolver::new(ConflictStrategy::Ask, ConflictStrategy::Rename);
+        let result = resolver.resolve(&source, &dest).unwrap();
+        assert_eq!(result, Some(dir.path().join("existing (1).txt")));
+    }
+
+    #[test]
+    fn resolve_ask_skips_identical_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = source_in(dir.path());
+        let dest = dir.path().join("same.bin");
+        fs::copy(&source, &dest).unwrap();
+        let resolver = ConflictResolver::new(ConflictStrategy::Ask, ConflictStrategy::Overwrite);
+        assert_eq!(resolver.resolve(&source, &dest).unwrap(), None);
+    }
+
+    #[test]
+    fn remembered_answer_applies_to_later_conflicts() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = source_in(dir.path());
+        let dest = dir.path().join("existing.txt");
+        fs::write(&dest, "data").unwrap();
+        let resolver = resolver(ConflictStrategy::Ask);
+        resolver.remember(ConflictStrategy::Overwrite);
+        assert_eq!(resolver.resolve(&source, &dest).unwrap(), Some(dest));
+    }
+
+    #[test]
+    fn files_identical_compares_contents() {
+        let dir = tempfile::tempdir().unwrap();
+        let a = dir.path().join("a");
+        let b = dir.path().join("b");
+        fs::write(&a, "same size 1").unwrap();
+        fs::write(&b, "same size 2").unwrap();
+        assert!(!files_identical(&a, &b));
+        fs::write(&b, "same size 1").unwrap();
+        assert!(files_identical(&a, &b));
+        assert!(!files_identical(&a, &dir.path().join("missing")));
+    }
 }
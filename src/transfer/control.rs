@@ -0,0 +1,384 @@
+//! Control channel for in-flight queue transfers.
+//!
+//! `flux queue run` holds the queue lock for its whole run, so a second
+//! `flux queue pause <id>` cannot reach a running entry through `queue.json`.
+//! Instead, the runner listens on a Unix socket per running entry
+//! (`<data_dir>/control/queue-<id>.sock`). A `pause` command sets a
+//! `PauseSignal`, which the copy engine checks at chunk boundaries; the
+//! interrupted transfer then persists its resume manifest and returns
+//! `FluxError::Paused`.
+//!
+//! The same socket serves the TUI Transfer tab: `stats` replies with a JSON
+//! `MonitorSnapshot` of the entry's `TransferMonitor`, and `limit <BPS|off>`
+//! changes the bandwidth limit of the running copy. Every command is one
+//! line; replies are `ok`, `ok <payload>` or `error: <reason>`.
+//!
+//! The control channel is only available on Unix. On other platforms
+//! `flux queue pause` affects entries that have not started yet.
+
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use crate::error::FluxError;
+#[cfg(feature = "tui")]
+use crate::transfer::monitor::MonitorSnapshot;
+use crate::transfer::monitor::TransferMonitor;
+
+/// Shared flag asking an in-flight transfer to stop at the next chunk boundary.
+#[derive(Debug, Clone, Default)]
+pub struct PauseSignal(Arc<AtomicBool>);
+
+impl PauseSignal {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Ask the transfer to pause.
+    pub fn request(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    /// Whether a pause has been requested.
+    pub fn is_requested(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+/// Path of the control socket for queue entry `id`.
+pub fn control_socket_path(data_dir: &Path, id: u64) -> PathBuf {
+    data_dir.join("control").join(format!("queue-{}.sock", id))
+}
+
+/// Listener bound to a queue entry's control socket.
+///
+/// Accepts commands on a background thread while the entry runs. Dropping
+/// the listener stops the thread and removes the socket file.
+pub struct ControlListener {
+    signal: PauseSignal,
+    #[cfg(unix)]
+    path: PathBuf,
+    #[cfg(unix)]
+    stop: Arc<AtomicBool>,
+    #[cfg(unix)]
+    thread: Option<std::thread::JoinHandle<()>>,
+}
+
+impl ControlListener {
+    /// Bind the control socket for queue entry `id`, reporting `monitor`.
+    ///
+    /// Returns `Ok(None)` on platforms without Unix sockets.
+    #[cfg(unix)]
+    pub fn bind(
+        data_dir: &Path,
+        id: u64,
+        monitor: TransferMonitor,
+    ) -> Result<Option<Self>, FluxError> {
+        use std::os::unix::net::UnixListener;
+
+        let path = control_socket_path(data_dir, id);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        // A leftover socket from a crashed runner would make bind fail
+        if path.exists() {
+            std::fs::remove_file(&path)?;
+        }
+
+        let listener = UnixListener::bind(&path)?;
+        listener.set_nonblocking(true)?;
+
+        let signal = PauseSignal::new();
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread = {
+            let signal = signal.clone();
+            let stop = Arc::clone(&stop);
+            std::thread::spawn(move || accept_loop(listener, signal, monitor, stop))
+        };
+
+        Ok(Some(Self {
+            signal,
+            path,
+            stop,
+            thread: Some(thread),
+        }))
+    }
+
+    #[cfg(not(unix))]
+    pub fn bind(
+        _data_dir: &Path,
+        _id: u64,
+        _monitor: TransferMonitor,
+    ) -> Result<Option<Self>, FluxError> {
+        Ok(None)
+    }
+
+    /// The pause signal set by incoming `pause` commands.
+    pub fn signal(&self) -> &PauseSignal {
+        &self.signal
+    }
+}
+
+#[cfg(unix)]
+impl Drop for ControlListener {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Poll for connections until `stop` is set, handling one command per connection.
+#[cfg(unix)]
+fn accept_loop(
+    listener: std::os::unix::net::UnixListener,
+    signal: PauseSignal,
+    monitor: TransferMonitor,
+    stop: Arc<AtomicBool>,
+) {
+    use std::io::{BufRead, BufReader, Write};
+
+    while !stop.load(Ordering::SeqCst) {
+        match listener.accept() {
+            Ok((stream, _)) => {
+                let _ = stream.set_nonblocking(false);
+                let _ = stream.set_read_timeout(Some(std::time::Duration::from_secs(5)));
+                let mut line = String::new();
+                let mut reader = BufReader::new(&stream);
+                if reader.read_line(&mut line).is_err() {
+                    continue;
+                }
+                let reply = handle_command(line.trim(), &signal, &monitor);
+                let mut stream = &stream;
+                let _ = writeln!(stream, "{}", reply);
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+                std::thread::sleep(std::time::Duration::from_millis(100));
+            }
+            Err(e) => {
+                tracing::warn!("Control socket accept failed: {}", e);
+                std::thread::sleep(std::time::Duration::from_millis(100));
+            }
+        }
+    }
+}
+
+/// Run one control command and build the reply line.
+#[cfg(unix)]
+fn handle_command(command: &str, signal: &PauseSignal, monitor: &TransferMonitor) -> String {
+    let (name, arg) = command.split_once(' ').unwrap_or((command, ""));
+    match name {
+        "pause" => {
+            signal.request();
+            "ok".to_string()
+        }
+        "stats" => match serde_json::to_string(&monitor.snapshot()) {
+            Ok(json) => format!("ok {}", json),
+            Err(e) => format!("error: {}", e),
+        },
+        "limit" => match arg.trim() {
+            "off" => {
+                monitor.set_limit(None);
+                "ok".to_string()
+            }
+            bps => match bps.parse::<u64>() {
+                Ok(bps) if bps > 0 => {
+                    monitor.set_limit(Some(bps));
+                    "ok".to_string()
+                }
+                _ => "error: expected bytes/sec or off".to_string(),
+            },
+        },
+        other => {
+            tracing::debug!("Unknown control command: {:?}", other);
+            "error: unknown command".to_string()
+        }
+    }
+}
+
+/// Send `command` to the runner of queue entry `id` and return its reply.
+///
+/// Returns `Ok(None)` if nothing is listening (the entry is not in flight).
+/// Stale sockets left by a crashed runner are removed.
+#[cfg(unix)]
+fn send_command(data_dir: &Path, id: u64, command: &str) -> Result<Option<String>, FluxError> {
+    use std::io::{BufRead, BufReader, Write};
+    use std::os::unix::net::UnixStream;
+
+    let path = control_socket_path(data_dir, id);
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    let mut stream = match UnixStream::connect(&path) {
+        Ok(stream) => stream,
+        Err(e) => {
+            tracing::debug!("Removing stale control socket {}: {}", path.display(), e);
+            let _ = std::fs::remove_file(&path);
+            return Ok(None);
+        }
+    };
+    stream.set_read_timeout(Some(std::time::Duration::from_secs(5)))?;
+    writeln!(stream, "{}", command)?;
+
+    let mut reply = String::new();
+    BufReader::new(&stream).read_line(&mut reply)?;
+    Ok(Some(reply.trim().to_string()))
+}
+
+#[cfg(not(unix))]
+fn send_command(_data_dir: &Path, _id: u64, _command: &str) -> Result<Option<String>, FluxError> {
+    Ok(None)
+}
+
+/// Check a reply for `ok`, returning its payload.
+fn expect_ok(id: u64, what: &str, reply: &str) -> Result<String, FluxError> {
+    match reply.strip_prefix("ok") {
+        Some(payload) => Ok(payload.trim_start().to_string()),
+        None => Err(FluxError::QueueError(format!(
+            "Transfer #{} rejected {} request: {}",
+            id, what, reply
+        ))),
+    }
+}
+
+/// Ask the runner of queue entry `id` to pause it.
+///
+/// Returns `Ok(true)` if a running transfer acknowledged the request, and
+/// `Ok(false)` if nothing is listening (the entry is not in flight).
+pub fn request_pause(data_dir: &Path, id: u64) -> Result<bool, FluxError> {
+    match send_command(data_dir, id, "pause")? {
+        Some(reply) => expect_ok(id, "pause", &reply).map(|_| true),
+        None => Ok(false),
+    }
+}
+
+/// Live statistics of queue entry `id`, or `None` if it is not in flight.
+#[cfg(feature = "tui")]
+pub fn request_stats(data_dir: &Path, id: u64) -> Result<Option<MonitorSnapshot>, FluxError> {
+    let Some(reply) = send_command(data_dir, id, "stats")? else {
+        return Ok(None);
+    };
+    let json = expect_ok(id, "stats", &reply)?;
+    serde_json::from_str(&json)
+        .map(Some)
+        .map_err(|e| FluxError::QueueError(format!("Invalid stats from transfer #{}: {}", id, e)))
+}
+
+/// Change the bandwidth limit of running queue entry `id` (`None` removes it).
+///
+/// Returns `Ok(false)` if the entry is not in flight.
+#[cfg(feature = "tui")]
+pub fn request_limit(
+    data_dir: &Path,
+    id: u64,
+    bytes_per_sec: Option<u64>,
+) -> Result<bool, FluxError> {
+    let command = match bytes_per_sec {
+        Some(bps) => format!("limit {}", bps.max(1)),
+        None => "limit off".to_string(),
+    };
+    match send_command(data_dir, id, &command)? {
+        Some(reply) => expect_ok(id, "limit", &reply).map(|_| true),
+        None => Ok(false),
+    }
+}
+
+/// Ids of the queue entries that currently have a control socket, ascending.
+#[cfg(feature = "tui")]
+pub fn running_entries(data_dir: &Path) -> Vec<u64> {
+    let Ok(dir) = std::fs::read_dir(data_dir.join("control")) else {
+        return Vec::new();
+    };
+    let mut ids: Vec<u64> = dir
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let name = entry.file_name();
+            let name = name.to_str()?;
+            name.strip_prefix("queue-")?.strip_suffix(".sock")?.parse().ok()
+        })
+        .collect();
+    ids.sort_unstable();
+    ids
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pause_signal_is_shared_between_clones() {
+        let signal = PauseSignal::new();
+        let other = signal.clone();
+        assert!(!other.is_requested());
+        signal.request();
+        assert!(other.is_requested());
+    }
+
+    #[test]
+    fn socket_path_is_under_control_dir() {
+        let path = control_socket_path(Path::new("/data"), 7);
+        assert_eq!(path, PathBuf::from("/data/control/queue-7.sock"));
+    }
+
+    #[test]
+    fn request_pause_without_listener_returns_false() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(!request_pause(dir.path(), 1).unwrap());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn request_pause_sets_listener_signal() {
+        let dir = tempfile::tempdir().unwrap();
+        let monitor = TransferMonitor::new("a", "b");
+        let listener = ControlListener::bind(dir.path(), 3, monitor).unwrap().unwrap();
+        assert!(!listener.signal().is_requested());
+
+        assert!(request_pause(dir.path(), 3).unwrap());
+        assert!(listener.signal().is_requested());
+
+        drop(listener);
+        assert!(!control_socket_path(dir.path(), 3).exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn stale_socket_is_removed() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = control_socket_path(dir.path(), 4);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        // Bind and drop a raw listener so the file remains without a server
+        drop(std::os::unix::net::UnixListener::bind(&path).unwrap());
+        assert!(path.exists());
+
+        assert!(!request_pause(dir.path(), 4).unwrap());
+        assert!(!path.exists());
+    }
+
+    #[cfg(all(unix, feature = "tui"))]
+    #[test]
+    fn stats_and_limit_reach_the_monitor() {
+        let dir = tempfile::tempdir().unwrap();
+        let monitor = TransferMonitor::new("src.bin", "dst.bin");
+        monitor.start(1000, 250);
+        let _listener = ControlListener::bind(dir.path(), 5, monitor.clone())
+            .unwrap()
+            .unwrap();
+        assert_eq!(running_entries(dir.path()), vec![5]);
+
+        let stats = request_stats(dir.path(), 5).unwrap().unwrap();
+        assert_eq!(stats.source, "src.bin");
+        assert_eq!(stats.bytes_done, 250);
+        assert_eq!(stats.limit, None);
+
+        assert!(request_limit(dir.path(), 5, Some(4096)).unwrap());
+        assert_eq!(monitor.snapshot().limit, Some(4096));
+        assert!(request_limit(dir.path(), 5, None).unwrap());
+        assert_eq!(monitor.snapshot().limit, None);
+
+        assert!(request_stats(dir.path(), 6).unwrap().is_none());
+    }
+}
@@ -0,0 +1,301 @@
+//! `cp --dedup`: skip or hard-link files whose content is already in the
+//! destination.
+//!
+//! Before copying, the whole destination tree is indexed by file size. Only
+//! files with the size of some source are hashed (BLAKE3), and only when a
+//! source of that size asks for them; hashes go through the checksum cache,
+//! so a destination indexed once is not read again while it is unchanged.
+//!
+//! A source whose content exists in the destination is either skipped
+//! (`--dedup` / `--dedup skip`) or hard-linked to the existing copy
+//! (`--dedup link`). Sources with the same content as an earlier source of
+//! the same copy are treated alike: the first one is copied, the rest are
+//! skipped or linked to it.
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use bytesize::ByteSize;
+use walkdir::WalkDir;
+
+use crate::error::FluxError;
+use crate::transfer::atomic::is_temp_file;
+use crate::transfer::checksum::ChecksumAlgorithm;
+use crate::transfer::checksum_cache::ChecksumCache;
+
+/// Hash used to compare contents.
+const ALGORITHM: ChecksumAlgorithm = ChecksumAlgorithm::Blake3;
+
+/// What `--dedup` does with a source whose content already exists.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum DedupMode {
+    /// Do not copy it
+    #[default]
+    Skip,
+    /// Hard-link the destination to the existing copy
+    Link,
+}
+
+/// Where the content of a source already is.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Duplicate {
+    /// A file in the destination tree
+    Existing(PathBuf),
+    /// An earlier source of the same copy (index into the sources)
+    InBatch(usize),
+}
+
+/// Files of a destination tree, hashed on demand.
+pub struct DedupIndex {
+    /// Files not hashed yet, by size
+    unhashed: HashMap<u64, Vec<PathBuf>>,
+    /// First file found with each content
+    by_hash: HashMap<String, PathBuf>,
+    cache: ChecksumCache,
+}
+
+impl DedupIndex {
+    /// Index the files under `root` whose size is one of `sizes`.
+    ///
+    /// A missing `root` gives an empty index. Empty files are never
+    /// indexed: there is nothing to save on them.
+    pub fn build(root: &Path, sizes: &HashSet<u64>, cache: ChecksumCache) -> Self {
+        let mut unhashed: HashMap<u64, Vec<PathBuf>> = HashMap::new();
+        for entry in WalkDir::new(root)
+            .follow_links(false)
+            .into_iter()
+            .filter_map(|e| e.ok())
+            .filter(|e| e.file_type().is_file() && !is_temp_file(e.path()))
+        {
+            let Ok(size) = entry.metadata().map(|m| m.len()) else {
+                continue;
+            };
+            if size > 0 && sizes.contains(&size) {
+                unhashed.entry(size).or_default().push(entry.into_path());
+            }
+        }
+        for paths in unhashed.values_mut() {
+            paths.sort();
+        }
+        Self {
+            unhashed,
+            by_hash: HashMap::new(),
+            cache,
+        }
+    }
+
+    /// Number of files that may hold the content of a source.
+    pub fn candidates(&self) -> usize {
+        self.unhashed.values().map(Vec::len).sum::<usize>() + self.by_hash.len()
+    }
+
+    /// Hash of the file at `path`, from the cache when it is unchanged.
+    pub fn hash(&mut self, path: &Path) -> Result<String, FluxError> {
+        self.cache.checksum(path, ALGORITHM)
+    }
+
+    /// An indexed file with content `hash` (of a `size`-byte file).
+    pub fn find(&mut self, size: u64, hash: &str) -> Option<PathBuf> {
+        // Hash the files of this size the first time one is asked for
+        for path in self.unhashed.remove(&size).unwrap_or_default() {
+            match self.cache.checksum(&path, ALGORITHM) {
+                Ok(hash) => {
+                    self.by_hash.entry(hash).or_insert(path);
+                }
+                Err(e) => tracing::warn!("Cannot hash {}: {}", path.display(), e),
+            }
+        }
+        self.by_hash.get(hash).cloned()
+    }
+
+    /// Keep the hashes for the next run.
+    pub fn save(&mut self) {
+        self.cache.save();
+    }
+}
+
+/// Find the duplicates among `sources` (path and size each).
+///
+/// Returns one entry per source: where its content already is, if anywhere.
+/// Only sources that share their size with an indexed file or another
+/// source are hashed; sources that cannot be hashed are never duplicates
+/// (copying them reports the error).
+pub fn find_duplicates(
+    index: &mut DedupIndex,
+    sources: &[(&Path, u64)],
+) -> Vec<Option<Duplicate>> {
+    let mut per_size: HashMap<u64, usize> = HashMap::new();
+    for &(_, size) in sources {
+        *per_size.entry(size).or_default() += 1;
+    }
+    let indexed: HashSet<u64> = index.unhashed.keys().copied().collect();
+
+    let mut seen: HashMap<String, usize> = HashMap::new();
+    let mut duplicates = Vec::with_capacity(sources.len());
+    for (i, &(path, size)) in sources.iter().enumerate() {
+        if size == 0 || (per_size[&size] < 2 && !indexed.contains(&size)) {
+            duplicates.push(None);
+            continue;
+        }
+        let hash = match index.hash(path) {
+            Ok(hash) => hash,
+            Err(e) => {
+                tracing::warn!("Cannot hash {}: {}", path.display(), e);
+                duplicates.push(None);
+                continue;
+            }
+        };
+        let duplicate = match index.find(size, &hash) {
+            Some(existing) => Some(Duplicate::Existing(existing)),
+            None => match seen.get(&hash) {
+                Some(&first) => Some(Duplicate::InBatch(first)),
+                None => {
+                    seen.insert(hash, i);
+                    None
+                }
+            },
+        };
+        duplicates.push(duplicate);
+    }
+    duplicates
+}
+
+/// A file under `root` with the content of `source` (`size` bytes), for
+/// deduplicating a single-file copy.
+pub fn find_existing(
+    root: &Path,
+    source: &Path,
+    size: u64,
+    cache: ChecksumCache,
+) -> Option<PathBuf> {
+    if size == 0 {
+        return None;
+    }
+    let mut index = DedupIndex::build(root, &HashSet::from([size]), cache);
+    if index.candidates() == 0 {
+        return None;
+    }
+    let found = match index.hash(source) {
+        Ok(hash) => index.find(size, &hash),
+        Err(e) => {
+            tracing::warn!("Cannot hash {}: {}", source.display(), e);
+            None
+        }
+    };
+    index.save();
+    found
+}
+
+/// "Deduplicated 3 file(s) (hard-linked), saved 1.2 MB".
+pub fn summary(mode: DedupMode, files: u64, bytes: u64) -> String {
+    let action = match mode {
+        DedupMode::Skip => "skipped",
+        DedupMode::Link => "hard-linked",
+    };
+    format!(
+        "Deduplicated {} file(s) ({}), saved {}",
+        files,
+        action,
+        ByteSize(bytes)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn write(path: &Path, content: &str) -> PathBuf {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, content).unwrap();
+        path.to_path_buf()
+    }
+
+    fn index(root: &Path, sources: &[(&Path, u64)]) -> DedupIndex {
+        let sizes = sources.iter().map(|&(_, size)| size).collect();
+        DedupIndex::build(root, &sizes, ChecksumCache::in_memory())
+    }
+
+    #[test]
+    fn index_keeps_only_source_sizes() {
+        let dir = TempDir::new().unwrap();
+        write(&dir.path().join("dest/a.txt"), "four");
+        write(&dir.path().join("dest/deep/b.txt"), "five!");
+        write(&dir.path().join("dest/empty"), "");
+        write(&dir.path().join("dest/.c.txt.flux-tmp"), "four");
+
+        let sizes = HashSet::from([4, 0]);
+        let build = |root: &str| {
+            DedupIndex::build(&dir.path().join(root), &sizes, ChecksumCache::in_memory())
+        };
+        assert_eq!(build("dest").candidates(), 1);
+
+        let missing = build("nope");
+        assert_eq!(missing.candidates(), 0);
+    }
+
+    #[test]
+    fn duplicates_of_destination_and_batch_are_found() {
+        let dir = TempDir::new().unwrap();
+        let existing = write(&dir.path().join("dest/old/photo.jpg"), "same bytes");
+        write(&dir.path().join("dest/other.jpg"), "diff bytes");
+        let a = write(&dir.path().join("src/a.jpg"), "same bytes");
+        let b = write(&dir.path().join("src/b.jpg"), "new content");
+        let c = write(&dir.path().join("src/c.jpg"), "new content");
+        let d = write(&dir.path().join("src/d.jpg"), "unique");
+        let e = write(&dir.path().join("src/e"), "");
+        let sources = [
+            (a.as_path(), 10),
+            (b.as_path(), 11),
+            (c.as_path(), 11),
+            (d.as_path(), 6),
+            (e.as_path(), 0),
+        ];
+
+        let mut index = index(&dir.path().join("dest"), &sources);
+        let duplicates = find_duplicates(&mut index, &sources);
+        assert_eq!(
+            duplicates,
+            [
+                Some(Duplicate::Existing(existing)),
+                None,
+                Some(Duplicate::InBatch(1)),
+                None,
+                None,
+            ]
+        );
+    }
+
+    #[test]
+    fn unreadable_sources_are_not_duplicates() {
+        let dir = TempDir::new().unwrap();
+        write(&dir.path().join("dest/x"), "abc");
+        let gone = dir.path().join("src/gone");
+        let sources = [(gone.as_path(), 3)];
+        let mut index = index(&dir.path().join("dest"), &sources);
+        assert_eq!(find_duplicates(&mut index, &sources), [None]);
+    }
+
+    #[test]
+    fn single_files_find_their_copy() {
+        let dir = TempDir::new().unwrap();
+        let copy = write(&dir.path().join("dest/2024/report.pdf"), "report");
+        let source = write(&dir.path().join("report.pdf"), "report");
+        let other = write(&dir.path().join("draft.pdf"), "drafts");
+        let root = dir.path().join("dest");
+        let find = |path: &Path| find_existing(&root, path, 6, ChecksumCache::in_memory());
+        assert_eq!(find(&source), Some(copy));
+        assert_eq!(find(&other), None);
+    }
+
+    #[test]
+    fn summary_names_mode_and_savings() {
+        assert_eq!(
+            summary(DedupMode::Link, 2, 2_000_000),
+            format!(
+                "Deduplicated 2 file(s) (hard-linked), saved {}",
+                ByteSize(2_000_000)
+            )
+        );
+    }
+}
@@ -0,0 +1,143 @@
+//! Lists of the files a directory copy failed on (`cp --failed-out`,
+//! `cp --retry-from`).
+//!
+//! When `cp -r` finishes with errors, the absolute source paths of the
+//! failures are written one per line to `--failed-out FILE`, or to
+//! `failed-<time>.txt` in the data directory. Lines starting with `#` are
+//! comments (the list starts with the copy it came from).
+//!
+//! `cp --retry-from FILE` runs the copy again over those paths only: a
+//! `RetryList` limits the directory walk to them. A listed directory (one
+//! that could not be read) brings back everything below it.
+
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::error::FluxError;
+
+/// Prefix of the lists written to the data directory.
+const FILE_PREFIX: &str = "failed-";
+
+/// Write the failed `paths` of the copy from `source` to `dest` to `out`, or
+/// to a new list in the data directory. Returns where the list went.
+pub fn write_list(
+    out: Option<&Path>,
+    source: &Path,
+    dest: &Path,
+    paths: &[&Path],
+) -> Result<PathBuf, FluxError> {
+    let path = match out {
+        Some(out) => out.to_path_buf(),
+        None => {
+            let stamp = chrono::Local::now().format("%Y%m%d-%H%M%S");
+            let dir = crate::config::paths::flux_data_dir()?;
+            dir.join(format!("{}{}.txt", FILE_PREFIX, stamp))
+        }
+    };
+    let mut file = std::io::BufWriter::new(std::fs::File::create(&path)?);
+    writeln!(file, "# flux cp {} {}", absolute(source).display(), dest.display())?;
+    for failed in paths {
+        writeln!(file, "{}", absolute(failed).display())?;
+    }
+    file.flush()?;
+    Ok(path)
+}
+
+/// The paths of a failed-files list.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RetryList {
+    paths: Vec<PathBuf>,
+}
+
+impl RetryList {
+    /// Read a list written by `write_list` (or by hand: one path per line).
+    pub fn load(path: &Path) -> Result<Self, FluxError> {
+        let text = std::fs::read_to_string(path).map_err(|e| {
+            FluxError::Config(format!("Cannot read retry list {}: {}", path.display(), e))
+        })?;
+        let list = Self::parse(&text);
+        if list.paths.is_empty() {
+            return Err(FluxError::Config(format!(
+                "Retry list {} names no files",
+                path.display()
+            )));
+        }
+        Ok(list)
+    }
+
+    fn parse(text: &str) -> Self {
+        let paths = text
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(|line| absolute(Path::new(line)))
+            .collect();
+        Self { paths }
+    }
+
+    /// Whether `path` is listed or lies below a listed directory.
+    pub fn covers(&self, path: &Path) -> bool {
+        let path = absolute(path);
+        self.paths.iter().any(|listed| path.starts_with(listed))
+    }
+
+    /// Whether the walk has to enter the directory `dir`: it is covered, or
+    /// holds a listed path.
+    pub fn reaches(&self, dir: &Path) -> bool {
+        let dir = absolute(dir);
+        self.paths
+            .iter()
+            .any(|listed| dir.starts_with(listed) || listed.starts_with(&dir))
+    }
+
+    pub fn len(&self) -> usize {
+        self.paths.len()
+    }
+}
+
+fn absolute(path: &Path) -> PathBuf {
+    std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn written_lists_load_back() {
+        let dir = tempfile::tempdir().unwrap();
+        let out = dir.path().join("failed.txt");
+        let src = dir.path().join("src");
+        let failed = [src.join("a.bin"), src.join("locked")];
+        let paths: Vec<&Path> = failed.iter().map(PathBuf::as_path).collect();
+
+        let written = write_list(Some(&out), &src, Path::new("dest"), &paths).unwrap();
+        assert_eq!(written, out);
+        let text = std::fs::read_to_string(&out).unwrap();
+        assert!(text.starts_with("# flux cp "));
+
+        let list = RetryList::load(&out).unwrap();
+        assert_eq!(list.len(), 2);
+        assert!(list.covers(&src.join("a.bin")));
+        assert!(!list.covers(&src.join("b.bin")));
+        // A listed directory brings back what is below it
+        assert!(list.covers(&src.join("locked/deep/c.bin")));
+    }
+
+    #[test]
+    fn walk_enters_only_directories_on_the_way() {
+        let list = RetryList::parse("# comment\n\n/data/src/photos/2024/a.jpg\n");
+        assert!(list.reaches(Path::new("/data/src")));
+        assert!(list.reaches(Path::new("/data/src/photos/2024")));
+        assert!(!list.reaches(Path::new("/data/src/music")));
+    }
+
+    #[test]
+    fn empty_lists_are_refused() {
+        let dir = tempfile::tempdir().unwrap();
+        let out = dir.path().join("failed.txt");
+        std::fs::write(&out, "# nothing failed\n").unwrap();
+        assert!(matches!(RetryList::load(&out), Err(FluxError::Config(_))));
+        assert!(RetryList::load(&dir.path().join("missing.txt")).is_err());
+    }
+}
@@ -4,15 +4,34 @@ use globset::{Glob, GlobSet, GlobSetBuilder};
 use walkdir::DirEntry;
 
 use crate::error::FluxError;
+use crate::transfer::atomic::is_temp_file;
+use crate::transfer::failed::RetryList;
+
+/// Folders Windows creates on every volume. Skipped unless `--include-system`.
+const SYSTEM_DIR_NAMES: [&str; 2] = ["$RECYCLE.BIN", "System Volume Information"];
+
+/// Windows `FILE_ATTRIBUTE_HIDDEN`.
+pub(crate) const FILE_ATTRIBUTE_HIDDEN: u32 = 0x2;
+
+/// Windows `FILE_ATTRIBUTE_SYSTEM`.
+const FILE_ATTRIBUTE_SYSTEM: u32 = 0x4;
 
 /// Glob-based file filter for include/exclude pattern matching during transfers.
 ///
 /// Exclude patterns are checked first — any matching file is skipped.
 /// Include patterns (if any) are checked second — file must match at least one.
 /// Directories can be pruned early via `is_excluded_dir` in walkdir's `filter_entry`.
+///
+/// Independently of the patterns, hidden entries can be skipped
+/// (`--exclude-hidden`), and Windows system folders/files are skipped by
+/// default (`--include-system` to keep them). With `--retry-from`, only the
+/// listed paths are transferred.
 pub struct TransferFilter {
     excludes: Option<GlobSet>,
     includes: Option<GlobSet>,
+    skip_hidden: bool,
+    skip_system: bool,
+    only: Option<RetryList>,
 }
 
 impl TransferFilter {
@@ -44,7 +63,33 @@ impl TransferFilter {
             Some(builder.build()?)
         };
 
-        Ok(Self { excludes, includes })
+        Ok(Self {
+            excludes,
+            includes,
+            skip_hidden: false,
+            skip_system: true,
+            only: None,
+        })
+    }
+
+    /// Skip hidden files and directories (dotfiles, Windows hidden attribute).
+    pub fn skip_hidden(mut self, skip: bool) -> Self {
+        self.skip_hidden = skip;
+        self
+    }
+
+    /// Skip Windows system folders (`$RECYCLE.BIN`, `System Volume Information`)
+    /// and files carrying the system attribute. On by default.
+    pub fn skip_system(mut self, skip: bool) -> Self {
+        self.skip_system = skip;
+        self
+    }
+
+    /// Transfer nothing but the paths of `list` (`--retry-from`); the
+    /// patterns and hidden/system settings still apply.
+    pub fn only(mut self, list: Option<RetryList>) -> Self {
+        self.only = list;
+        self
     }
 
     /// Returns true if the file at `path` should be transferred.
@@ -58,6 +103,20 @@ impl TransferFilter {
     /// `*.log` work at any depth (matching the file name) while patterns like
     /// `build/**` work against the full path structure.
     pub fn should_transfer(&self, path: &Path) -> bool {
+        // In-progress atomic writes are never transferred
+        if is_temp_file(path) {
+            return false;
+        }
+        if self.skip_hidden && is_hidden(path) {
+            return false;
+        }
+        if self.skip_system && has_attribute(path, FILE_ATTRIBUTE_SYSTEM) {
+            return false;
+        }
+        if self.only.as_ref().is_some_and(|list| !list.covers(path)) {
+            return false;
+        }
+
         // Check excludes first
         if let Some(ref excludes) = self.excludes {
             if Self::matches_glob(excludes, path) {
@@ -79,12 +138,19 @@ impl TransferFilter {
     /// pruning because a directory might contain files that match an include pattern
     /// even if the directory name itself does not.
     ///
+    /// Hidden and system directories below the walk root are pruned according
+    /// to the hidden/system settings; the root itself is never pruned.
+    ///
     /// Used with `walkdir::IntoIter::filter_entry` for early directory pruning.
     pub fn is_excluded_dir(&self, entry: &DirEntry) -> bool {
         if !entry.file_type().is_dir() {
             return false;
         }
 
+        if entry.depth() > 0 {
+            return self.excludes_dir_path(entry.path());
+        }
+
         if let Some(ref excludes) = self.excludes {
             return Self::matches_glob(excludes, entry.path());
         }
@@ -92,6 +158,26 @@ impl TransferFilter {
         false
     }
 
+    /// `is_excluded_dir` for a directory below the walk root given by path,
+    /// e.g. the parent of a file reported by the sync watcher.
+    pub fn excludes_dir_path(&self, path: &Path) -> bool {
+        if self.skip_system && is_system(path) {
+            return true;
+        }
+        if self.only.as_ref().is_some_and(|list| !list.reaches(path)) {
+            return true;
+        }
+        if self.skip_hidden && is_hidden(path) {
+            return true;
+        }
+
+        if let Some(ref excludes) = self.excludes {
+            return Self::matches_glob(excludes, path);
+        }
+
+        false
+    }
+
     /// Match a glob set against both the full path and just the file name.
     ///
     /// This enables patterns like `*.log` to match files at any depth
@@ -111,6 +197,38 @@ impl TransferFilter {
     }
 }
 
+/// Whether `path` is hidden: a dotfile, or (on Windows) has the hidden attribute.
+pub fn is_hidden(path: &Path) -> bool {
+    let dotfile = path
+        .file_name()
+        .and_then(|n| n.to_str())
+        .is_some_and(|n| n.starts_with('.') && n != "." && n != "..");
+    dotfile || has_attribute(path, FILE_ATTRIBUTE_HIDDEN)
+}
+
+/// Whether `path` is a Windows system folder or carries the system attribute.
+pub fn is_system(path: &Path) -> bool {
+    let system_name = path
+        .file_name()
+        .and_then(|n| n.to_str())
+        .is_some_and(|n| SYSTEM_DIR_NAMES.iter().any(|s| s.eq_ignore_ascii_case(n)));
+    system_name || has_attribute(path, FILE_ATTRIBUTE_SYSTEM)
+}
+
+/// Check a Windows file attribute bit. Always false on other platforms.
+#[cfg(windows)]
+fn has_attribute(path: &Path, attribute: u32) -> bool {
+    use std::os::windows::fs::MetadataExt;
+    std::fs::symlink_metadata(path)
+        .map(|m| m.file_attributes() & attribute != 0)
+        .unwrap_or(false)
+}
+
+#[cfg(not(windows))]
+fn has_attribute(_path: &Path, _attribute: u32) -> bool {
+    false
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -195,4 +313,83 @@ mod tests {
         let result = TransferFilter::new(&["[invalid".to_string()], &[]);
         assert!(result.is_err());
     }
+
+    #[test]
+    fn atomic_temp_files_never_transfer() {
+        let filter = TransferFilter::new(&[], &[]).unwrap();
+        assert!(!filter.should_transfer(Path::new("dir/.big.iso.flux-tmp")));
+    }
+
+    #[test]
+    fn hidden_files_transfer_by_default() {
+        let filter = TransferFilter::new(&[], &[]).unwrap();
+        assert!(filter.should_transfer(Path::new("dir/.env")));
+    }
+
+    #[test]
+    fn exclude_hidden_skips_dotfiles() {
+        let filter = TransferFilter::new(&[], &[]).unwrap().skip_hidden(true);
+        assert!(!filter.should_transfer(Path::new("dir/.env")));
+        assert!(filter.should_transfer(Path::new("dir/env")));
+        assert!(filter.should_transfer(Path::new(".hidden-dir/visible.txt")));
+    }
+
+    #[test]
+    fn system_folder_names_are_recognized() {
+        assert!(is_system(Path::new("D:/$RECYCLE.BIN")));
+        assert!(is_system(Path::new("/mnt/usb/System Volume Information")));
+        assert!(is_system(Path::new("/mnt/usb/$Recycle.Bin")));
+        assert!(!is_system(Path::new("/mnt/usb/photos")));
+    }
+
+    #[test]
+    fn hidden_and_system_dirs_are_pruned_below_root() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join(".root");
+        std::fs::create_dir_all(root.join(".git")).unwrap();
+        std::fs::create_dir_all(root.join("$RECYCLE.BIN")).unwrap();
+        std::fs::create_dir_all(root.join("src")).unwrap();
+
+        let walk = |filter: &TransferFilter| -> Vec<String> {
+            walkdir::WalkDir::new(&root)
+                .into_iter()
+                .filter_entry(|e| !filter.is_excluded_dir(e))
+                .filter_map(|e| e.ok())
+                .map(|e| e.file_name().to_string_lossy().to_string())
+                .collect()
+        };
+
+        // Defaults: hidden kept, system folders skipped
+        let names = walk(&TransferFilter::new(&[], &[]).unwrap());
+        assert!(names.contains(&".git".to_string()));
+        assert!(!names.contains(&"$RECYCLE.BIN".to_string()));
+
+        // Hidden root is still walked, hidden children are pruned
+        let names = walk(&TransferFilter::new(&[], &[]).unwrap().skip_hidden(true));
+        assert!(names.contains(&".root".to_string()));
+        assert!(!names.contains(&".git".to_string()));
+        assert!(names.contains(&"src".to_string()));
+
+        // Override keeps system folders
+        let names = walk(&TransferFilter::new(&[], &[]).unwrap().skip_system(false));
+        assert!(names.contains(&"$RECYCLE.BIN".to_string()));
+    }
+
+    #[test]
+    fn retry_list_limits_files_and_prunes_other_dirs() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("src");
+        std::fs::create_dir_all(root.join("keep")).unwrap();
+        std::fs::create_dir_all(root.join("other")).unwrap();
+        let list = dir.path().join("failed.txt");
+        std::fs::write(&list, format!("{}\n", root.join("keep/a.bin").display())).unwrap();
+
+        let filter = TransferFilter::new(&[], &[])
+            .unwrap()
+            .only(Some(RetryList::load(&list).unwrap()));
+        assert!(filter.should_transfer(&root.join("keep/a.bin")));
+        assert!(!filter.should_transfer(&root.join("keep/b.bin")));
+        assert!(!filter.excludes_dir_path(&root.join("keep")));
+        assert!(filter.excludes_dir_path(&root.join("other")));
+    }
 }
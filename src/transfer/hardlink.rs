@@ -0,0 +1,130 @@
+//! Hard links inside a transfer (`cp --hard-links`, `sync --hard-links`).
+//!
+//! Trees such as rsync snapshot series hold the same file under many names.
+//! Copied name by name, each becomes an independent file and the copy takes
+//! many times the space. With `--hard-links`, the walk remembers the first
+//! name of every file with more than one link (by device and inode) and the
+//! other names are linked to its copy instead of copied again.
+//!
+//! Link identities come from the Unix metadata; elsewhere every name is
+//! copied as before.
+
+use std::collections::HashMap;
+use std::fs::Metadata;
+use std::path::{Path, PathBuf};
+
+use crate::backend::readonly;
+use crate::error::FluxError;
+use crate::transfer::atomic::temp_path;
+
+/// Device and inode of a file.
+pub type LinkId = (u64, u64);
+
+/// The identity of a file with more than one name, `None` for files with a
+/// single name (or where links cannot be told apart).
+pub fn link_id(meta: &Metadata) -> Option<LinkId> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        (meta.nlink() > 1).then(|| (meta.dev(), meta.ino()))
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = meta;
+        None
+    }
+}
+
+/// Whether `a` and `b` are names of the same file.
+pub fn same_file(a: &Path, b: &Path) -> bool {
+    match (std::fs::metadata(a), std::fs::metadata(b)) {
+        (Ok(a), Ok(b)) => link_id(&a).is_some() && link_id(&a) == link_id(&b),
+        _ => false,
+    }
+}
+
+/// The first name of each multiply-linked file seen during a walk.
+#[derive(Debug, Default)]
+pub struct LinkTracker {
+    first: HashMap<LinkId, PathBuf>,
+}
+
+impl LinkTracker {
+    /// For a file with metadata `meta`, the path recorded for the first of
+    /// its names. Returns `None` and records `path` if this is the first
+    /// name seen (or the file has only one).
+    pub fn first_name(&mut self, meta: &Metadata, path: &Path) -> Option<PathBuf> {
+        let id = link_id(meta)?;
+        match self.first.get(&id) {
+            Some(first) => Some(first.clone()),
+            None => {
+                self.first.insert(id, path.to_path_buf());
+                None
+            }
+        }
+    }
+}
+
+/// Make `dest` a hard link to `existing`, replacing whatever is at `dest`.
+///
+/// The link is made under a temp name and renamed into place, so `dest` is
+/// never missing. Fails if the two are on different filesystems.
+pub fn link(existing: &Path, dest: &Path) -> Result<(), FluxError> {
+    readonly::check(dest, "link")?;
+    let temp = temp_path(dest);
+    if temp.exists() {
+        std::fs::remove_file(&temp)?;
+    }
+    std::fs::hard_link(existing, &temp)?;
+    if let Err(e) = std::fs::rename(&temp, dest) {
+        let _ = std::fs::remove_file(&temp);
+        return Err(e.into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn link_replaces_destination() {
+        let dir = TempDir::new().unwrap();
+        let existing = dir.path().join("existing");
+        let dest = dir.path().join("dest");
+        std::fs::write(&existing, "content").unwrap();
+        std::fs::write(&dest, "old").unwrap();
+        link(&existing, &dest).unwrap();
+        assert_eq!(std::fs::read_to_string(&dest).unwrap(), "content");
+        assert!(!temp_path(&dest).exists());
+
+        // Both names now refer to the same file
+        std::fs::write(&existing, "changed").unwrap();
+        assert_eq!(std::fs::read_to_string(&dest).unwrap(), "changed");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn tracker_returns_first_name_of_linked_files() {
+        let dir = TempDir::new().unwrap();
+        let a = dir.path().join("a");
+        let b = dir.path().join("b");
+        let single = dir.path().join("single");
+        std::fs::write(&a, "shared").unwrap();
+        std::fs::hard_link(&a, &b).unwrap();
+        std::fs::write(&single, "alone").unwrap();
+        assert!(same_file(&a, &b));
+        assert!(!same_file(&a, &single));
+
+        let mut tracker = LinkTracker::default();
+        let meta = |path: &Path| std::fs::metadata(path).unwrap();
+        assert_eq!(tracker.first_name(&meta(&a), Path::new("out/a")), None);
+        assert_eq!(tracker.first_name(&meta(&single), &single), None);
+        assert_eq!(tracker.first_name(&meta(&single), &single), None);
+        assert_eq!(
+            tracker.first_name(&meta(&b), Path::new("out/b")),
+            Some(PathBuf::from("out/a"))
+        );
+    }
+}
@@ -0,0 +1,220 @@
+//! Post-transfer hooks.
+//!
+//! Commands from the `[hooks]` table in config.toml (or `--hook-success`,
+//! `--hook-failure`, `--hook-complete` on `cp`/`sync`) run after an operation
+//! is recorded in history. They run through the platform shell with the
+//! outcome in the environment:
+//!
+//! | Variable         | Value                                                     |
+//! |------------------|-----------------------------------------------------------|
+//! | `FLUX_SRC`       | Source path or URI (credentials stripped)                 |
+//! | `FLUX_DEST`      | Destination path or URI (credentials stripped)            |
+//! | `FLUX_BYTES`     | Bytes transferred                                         |
+//! | `FLUX_FILES`     | Files transferred                                         |
+//! | `FLUX_DURATION`  | Elapsed seconds, e.g. `12.345`                            |
+//! | `FLUX_STATUS`    | `completed`, `skipped`, `failed`, `paused` or `cancelled` |
+//! | `FLUX_OPERATION` | `cp`, `sync`, `send`, `receive`, `queue`                  |
+//! | `FLUX_ERROR`     | Error message (failed operations only)                    |
+//!
+//! `on_success` runs for completed and skipped operations, `on_failure` for
+//! failed ones and `on_complete` after either (and after a pause or a
+//! cancellation). A failing hook prints a warning but never changes the
+//! transfer's outcome. Within an async runtime (the P2P receiver records
+//! history from its connection tasks), hooks run on the blocking pool
+//! instead, without holding up the transfer.
+
+use std::process::{Command, Stdio};
+
+use crate::cli::args::HookArgs;
+use crate::config::types::HooksConfig;
+use crate::queue::history::HistoryEntry;
+
+/// Merge CLI hook flags over the configured hooks.
+pub fn resolve(config: &HooksConfig, overrides: &HookArgs) -> HooksConfig {
+    HooksConfig {
+        on_success: overrides.hook_success.clone().or_else(|| config.on_success.clone()),
+        on_failure: overrides.hook_failure.clone().or_else(|| config.on_failure.clone()),
+        on_complete: overrides.hook_complete.clone().or_else(|| config.on_complete.clone()),
+    }
+}
+
+/// Hooks that apply to `status`, in execution order, as `(name, command)`.
+fn selected<'a>(hooks: &'a HooksConfig, status: &str) -> Vec<(&'static str, &'a str)> {
+    let outcome = match status {
+        "completed" | "skipped" => hooks.on_success.as_deref().map(|c| ("on_success", c)),
+        "failed" => hooks.on_failure.as_deref().map(|c| ("on_failure", c)),
+        _ => None,
+    };
+    let complete = hooks.on_complete.as_deref().map(|c| ("on_complete", c));
+    outcome.into_iter().chain(complete).collect()
+}
+
+/// Environment variables describing `entry` for hook commands.
+pub fn hook_env(entry: &HistoryEntry) -> Vec<(&'static str, String)> {
+    let mut env = vec![
+        ("FLUX_SRC", entry.source.clone()),
+        ("FLUX_DEST", entry.dest.clone()),
+        ("FLUX_BYTES", entry.bytes.to_string()),
+        ("FLUX_FILES", entry.files.to_string()),
+        ("FLUX_DURATION", format!("{:.3}", entry.duration_secs)),
+        ("FLUX_STATUS", entry.status.clone()),
+        ("FLUX_OPERATION", entry.operation.clone()),
+    ];
+    if let Some(ref error) = entry.error {
+        env.push(("FLUX_ERROR", error.clone()));
+    }
+    env
+}
+
+/// Run the hooks that apply to `entry`'s status, waiting for each to finish
+/// (or, inside a tokio runtime, in the background on its blocking pool).
+///
+/// Hook output goes to stderr so it never mixes with flux's stdout.
+pub fn run_hooks(hooks: &HooksConfig, entry: &HistoryEntry) {
+    let selected: Vec<(&'static str, String)> = selected(hooks, &entry.status)
+        .into_iter()
+        .map(|(name, command)| (name, command.to_string()))
+        .collect();
+    if selected.is_empty() {
+        return;
+    }
+    let env = hook_env(entry);
+
+    match tokio::runtime::Handle::try_current() {
+        Ok(runtime) => {
+            runtime.spawn_blocking(move || run_selected(&selected, &env));
+        }
+        Err(_) => run_selected(&selected, &env),
+    }
+}
+
+/// Run `selected` hooks one after the other with `env`.
+fn run_selected(selected: &[(&'static str, String)], env: &[(&'static str, String)]) {
+    for (name, command) in selected {
+        let mut cmd = shell_command(command);
+        cmd.envs(env.iter().map(|(k, v)| (*k, v.as_str())))
+            .stdin(Stdio::null())
+            .stdout(Stdio::from(std::io::stderr()));
+        match cmd.status() {
+            Ok(status) if status.success() => {}
+            Ok(status) => eprintln!("Warning: {} hook exited with {}", name, status),
+            Err(e) => eprintln!("Warning: {} hook could not be started: {}", name, e),
+        }
+    }
+}
+
+/// Build a command that runs `command` through the platform shell.
+pub fn shell_command(command: &str) -> Command {
+    #[cfg(windows)]
+    {
+        let mut cmd = Command::new("cmd");
+        cmd.args(["/C", command]);
+        cmd
+    }
+    #[cfg(not(windows))]
+    {
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", command]);
+        cmd
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transfer::history::HistoryRecord;
+
+    fn hooks(success: &str, failure: &str, complete: &str) -> HooksConfig {
+        HooksConfig {
+            on_success: Some(success.to_string()),
+            on_failure: Some(failure.to_string()),
+            on_complete: Some(complete.to_string()),
+        }
+    }
+
+    #[test]
+    fn cli_flags_override_config() {
+        let config = hooks("a", "b", "c");
+        let overrides = HookArgs {
+            hook_success: Some("x".to_string()),
+            ..Default::default()
+        };
+        let resolved = resolve(&config, &overrides);
+        assert_eq!(resolved.on_success.as_deref(), Some("x"));
+        assert_eq!(resolved.on_failure.as_deref(), Some("b"));
+        assert_eq!(resolved.on_complete.as_deref(), Some("c"));
+    }
+
+    #[test]
+    fn hooks_selected_by_status() {
+        let all = hooks("ok", "fail", "done");
+        assert_eq!(
+            selected(&all, "completed"),
+            vec![("on_success", "ok"), ("on_complete", "done")]
+        );
+        assert_eq!(
+            selected(&all, "skipped"),
+            vec![("on_success", "ok"), ("on_complete", "done")]
+        );
+        assert_eq!(
+            selected(&all, "failed"),
+            vec![("on_failure", "fail"), ("on_complete", "done")]
+        );
+        assert_eq!(selected(&all, "paused"), vec![("on_complete", "done")]);
+        assert!(selected(&HooksConfig::default(), "completed").is_empty());
+    }
+
+    #[test]
+    fn env_describes_transfer() {
+        let mut record = HistoryRecord::new("sync", "/data", "/backup");
+        record.bytes = 1024;
+        record.files = 3;
+        let env = hook_env(&record.to_entry(None));
+        let get = |key: &str| env.iter().find(|(k, _)| *k == key).map(|(_, v)| v.as_str());
+        assert_eq!(get("FLUX_SRC"), Some("/data"));
+        assert_eq!(get("FLUX_DEST"), Some("/backup"));
+        assert_eq!(get("FLUX_BYTES"), Some("1024"));
+        assert_eq!(get("FLUX_STATUS"), Some("completed"));
+        assert_eq!(get("FLUX_OPERATION"), Some("sync"));
+        assert!(get("FLUX_DURATION").is_some());
+        assert!(get("FLUX_ERROR").is_none());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn hook_runs_with_environment() {
+        let dir = tempfile::tempdir().unwrap();
+        let out = dir.path().join("hook.out");
+        let config = HooksConfig {
+            on_success: Some(format!(
+                "printf '%s %s' \"$FLUX_STATUS\" \"$FLUX_BYTES\" > '{}'",
+                out.display()
+            )),
+            ..Default::default()
+        };
+        let mut record = HistoryRecord::new("cp", "a", "b");
+        record.bytes = 7;
+        run_hooks(&config, &record.to_entry(None));
+        assert_eq!(std::fs::read_to_string(&out).unwrap(), "completed 7");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn hook_in_a_runtime_runs_in_the_background() {
+        let dir = tempfile::tempdir().unwrap();
+        let out = dir.path().join("hook.out");
+        let config = HooksConfig {
+            on_complete: Some(format!("sleep 0.2; echo done > '{}'", out.display())),
+            ..Default::default()
+        };
+        run_hooks(&config, &HistoryRecord::new("receive", "a", "b").to_entry(None));
+        // Returned before the hook finished
+        assert!(!out.exists());
+
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(10);
+        while !out.exists() && std::time::Instant::now() < deadline {
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+        assert!(out.exists());
+    }
+}
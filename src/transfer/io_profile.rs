@@ -0,0 +1,318 @@
+//! I/O profiles: chunking, buffer sizes, read-ahead and concurrency tuned to
+//! the storage a copy reads and writes.
+//!
+//! Parallel chunks and many files in flight help on SSDs and network
+//! filesystems, but make a spinning disk seek back and forth between them.
+//! `--io-profile` (or `io_profile` in config.toml) picks `hdd`, `ssd` or
+//! `network`; `auto`, the default, detects it from both ends of the copy:
+//! remote URLs and network mounts (NFS, SMB, sshfs, ...) are `network`, and on
+//! Linux a disk whose `/sys/dev/block/<major>:<minor>/queue/rotational` is 1
+//! is `hdd`. The slowest end wins. Storage that cannot be detected gets the
+//! `ssd` tuning, which matches flux's behaviour before profiles existed.
+//!
+//! Chunk and job counts are passed to the copy engine; the buffer size and
+//! read-ahead hint are process-wide (`apply`), like the bandwidth limiter.
+
+use std::fs::File;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use serde::{Deserialize, Serialize};
+
+use crate::transfer::chunk::auto_chunk_count;
+
+/// Read/write buffer size of the `ssd` profile (and of copies with no
+/// profile applied): 256KB.
+pub const DEFAULT_BUFFER_SIZE: usize = 256 * 1024;
+
+/// Most files a directory copy works on at once when `--jobs` is not given.
+/// More workers mostly add seek contention on spinning disks.
+const MAX_AUTO_JOBS: usize = 8;
+
+/// Filesystem types (as in /proc/self/mountinfo) served over the network.
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+const NETWORK_FILESYSTEMS: &[&str] = &[
+    "nfs", "nfs4", "cifs", "smb3", "smbfs", "ceph", "9p", "afs", "fuse.sshfs",
+    "fuse.rclone", "fuse.glusterfs",
+];
+
+static BUFFER_SIZE: AtomicUsize = AtomicUsize::new(DEFAULT_BUFFER_SIZE);
+static SEQUENTIAL: AtomicBool = AtomicBool::new(false);
+
+/// Kind of storage a copy is tuned for (`--io-profile`).
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum IoProfile {
+    /// Detect from the source and destination
+    #[default]
+    Auto,
+    /// Spinning disk: one file and one chunk at a time, large buffers
+    Hdd,
+    /// Solid-state storage: parallel chunks and one file per CPU
+    Ssd,
+    /// Network filesystem or remote backend: a few streams, medium buffers
+    Network,
+}
+
+impl std::fmt::Display for IoProfile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.pad(match self {
+            IoProfile::Auto => "auto",
+            IoProfile::Hdd => "hdd",
+            IoProfile::Ssd => "ssd",
+            IoProfile::Network => "network",
+        })
+    }
+}
+
+/// Copy settings of a profile.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IoTuning {
+    pub profile: IoProfile,
+    /// Most chunks a single file is split into when `--chunks` is not given
+    pub max_chunks: usize,
+    /// Size of read and write buffers
+    pub buffer_size: usize,
+    /// Files a directory copy works on at once when `--jobs` is not given
+    pub jobs: usize,
+    /// Ask the kernel for aggressive read-ahead on sources
+    pub sequential: bool,
+}
+
+impl IoProfile {
+    /// Settings for this profile (`auto` gets the `ssd` ones; call
+    /// `resolve` first).
+    pub fn tuning(self) -> IoTuning {
+        match self {
+            IoProfile::Hdd => IoTuning {
+                profile: self,
+                max_chunks: 1,
+                buffer_size: 4 * 1024 * 1024,
+                jobs: 1,
+                sequential: true,
+            },
+            IoProfile::Network => IoTuning {
+                profile: self,
+                max_chunks: 4,
+                buffer_size: 1024 * 1024,
+                jobs: 4,
+                sequential: true,
+            },
+            IoProfile::Auto | IoProfile::Ssd => IoTuning {
+                profile: IoProfile::Ssd,
+                max_chunks: usize::MAX,
+                buffer_size: DEFAULT_BUFFER_SIZE,
+                jobs: std::thread::available_parallelism()
+                    .map_or(1, |n| n.get())
+                    .min(MAX_AUTO_JOBS),
+                sequential: false,
+            },
+        }
+    }
+}
+
+impl IoTuning {
+    /// Chunks for a file of `size` bytes: the size-based count of
+    /// `auto_chunk_count`, at most `max_chunks`.
+    pub fn chunk_count(&self, size: u64) -> usize {
+        auto_chunk_count(size).min(self.max_chunks)
+    }
+}
+
+/// The profile to use for a copy between `ends` (source and destination;
+/// `None` for a remote end). An explicit profile is kept as is.
+pub fn resolve(requested: IoProfile, ends: [Option<&Path>; 2]) -> IoProfile {
+    if requested != IoProfile::Auto {
+        return requested;
+    }
+    let detected = ends.map(|end| match end {
+        Some(path) => detect(path).unwrap_or(IoProfile::Ssd),
+        None => IoProfile::Network,
+    });
+    slowest(&detected)
+}
+
+/// The profile of the slowest storage: a spinning disk, then the network.
+fn slowest(profiles: &[IoProfile]) -> IoProfile {
+    [IoProfile::Hdd, IoProfile::Network]
+        .into_iter()
+        .find(|p| profiles.contains(p))
+        .unwrap_or(IoProfile::Ssd)
+}
+
+/// Make copies in this process use `tuning`'s buffer size and read-ahead.
+pub fn apply(tuning: &IoTuning) {
+    BUFFER_SIZE.store(tuning.buffer_size, Ordering::Relaxed);
+    SEQUENTIAL.store(tuning.sequential, Ordering::Relaxed);
+}
+
+/// Read/write buffer size of the profile applied last.
+pub fn buffer_size() -> usize {
+    BUFFER_SIZE.load(Ordering::Relaxed)
+}
+
+/// Tell the kernel `file` will be read front to back, if the applied profile
+/// wants read-ahead. Only has an effect on Linux; failures are ignored.
+pub fn advise_sequential(file: &File) {
+    if !SEQUENTIAL.load(Ordering::Relaxed) {
+        return;
+    }
+    #[cfg(target_os = "linux")]
+    {
+        use std::os::unix::io::AsRawFd;
+        // SAFETY: the descriptor stays open for the duration of the call
+        unsafe {
+            libc::posix_fadvise(file.as_raw_fd(), 0, 0, libc::POSIX_FADV_SEQUENTIAL);
+        }
+    }
+    #[cfg(not(target_os = "linux"))]
+    let _ = file;
+}
+
+/// Detect the storage `path` is on. A destination that does not exist yet
+/// is looked up through its nearest existing parent.
+#[cfg(target_os = "linux")]
+fn detect(path: &Path) -> Option<IoProfile> {
+    use std::os::unix::fs::MetadataExt;
+
+    let existing = path.ancestors().find(|p| p.exists())?;
+    let existing = std::fs::canonicalize(existing).ok()?;
+    if let Ok(mountinfo) = std::fs::read_to_string("/proc/self/mountinfo") {
+        let network = mount_fs_type(&mountinfo, &existing)
+            .is_some_and(|fs| NETWORK_FILESYSTEMS.contains(&fs));
+        if network {
+            return Some(IoProfile::Network);
+        }
+    }
+    let (major, minor) = split_dev(std::fs::metadata(&existing).ok()?.dev());
+    if major == 0 {
+        // Anonymous device (tmpfs, overlayfs, btrfs subvolumes): no queue
+        return None;
+    }
+    let device = Path::new("/sys/dev/block").join(format!("{}:{}", major, minor));
+    let device = std::fs::canonicalize(device).ok()?;
+    // A partition has no queue of its own; its disk is the parent directory
+    let rotational = read_rotational(&device).or_else(|| read_rotational(device.parent()?))?;
+    Some(if rotational {
+        IoProfile::Hdd
+    } else {
+        IoProfile::Ssd
+    })
+}
+
+#[cfg(not(target_os = "linux"))]
+fn detect(_path: &Path) -> Option<IoProfile> {
+    None
+}
+
+#[cfg(target_os = "linux")]
+fn read_rotational(device: &Path) -> Option<bool> {
+    let value = std::fs::read_to_string(device.join("queue").join("rotational")).ok()?;
+    Some(value.trim() == "1")
+}
+
+/// Major and minor number of a Linux `st_dev` (glibc encoding).
+#[cfg(target_os = "linux")]
+fn split_dev(dev: u64) -> (u64, u64) {
+    let major = ((dev >> 32) & 0xffff_f000) | ((dev >> 8) & 0x0fff);
+    let minor = ((dev >> 12) & 0xffff_ff00) | (dev & 0x00ff);
+    (major, minor)
+}
+
+/// Filesystem type of the mount holding `path`, from the text of
+/// /proc/self/mountinfo: the longest mount point that is a prefix of it.
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn mount_fs_type<'a>(mountinfo: &'a str, path: &Path) -> Option<&'a str> {
+    mountinfo
+        .lines()
+        .filter_map(|line| {
+            let (mount, rest) = line.split_once(" - ")?;
+            let mount_point = mount.split(' ').nth(4)?;
+            let fs_type = rest.split(' ').next()?;
+            Some((unescape_mount(mount_point), fs_type))
+        })
+        .filter(|(mount_point, _)| path.starts_with(mount_point))
+        .max_by_key(|(mount_point, _)| mount_point.len())
+        .map(|(_, fs_type)| fs_type)
+}
+
+/// Undo the octal escapes (`\040` for a space) of mountinfo paths.
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn unescape_mount(field: &str) -> String {
+    let mut out = String::with_capacity(field.len());
+    let mut rest = field;
+    while let Some(pos) = rest.find('\\') {
+        out.push_str(&rest[..pos]);
+        let code = rest.get(pos + 1..pos + 4).and_then(|o| u8::from_str_radix(o, 8).ok());
+        match code {
+            Some(byte) => {
+                out.push(byte as char);
+                rest = &rest[pos + 4..];
+            }
+            None => {
+                out.push('\\');
+                rest = &rest[pos + 1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MOUNTINFO: &str = "\
+22 1 8:2 / / rw,relatime shared:1 - ext4 /dev/sda2 rw
+40 22 0:45 / /mnt/nas rw,relatime shared:20 - nfs4 nas:/export rw,vers=4.2
+41 22 0:46 / /mnt/my\\040share rw,relatime shared:21 - cifs //srv/share rw
+42 40 8:17 / /mnt/nas/local rw,relatime shared:22 - xfs /dev/sdb1 rw";
+
+    #[test]
+    fn profiles_tune_chunks_buffers_and_jobs() {
+        let hdd = IoProfile::Hdd.tuning();
+        assert_eq!(hdd.chunk_count(50 * 1024 * 1024 * 1024), 1);
+        assert_eq!(hdd.jobs, 1);
+        assert!(hdd.sequential);
+        assert!(hdd.buffer_size > DEFAULT_BUFFER_SIZE);
+
+        let network = IoProfile::Network.tuning();
+        assert!(network.chunk_count(50 * 1024 * 1024 * 1024) <= 4);
+
+        let ssd = IoProfile::Ssd.tuning();
+        assert_eq!(ssd.chunk_count(1024), auto_chunk_count(1024));
+        assert_eq!(ssd.buffer_size, DEFAULT_BUFFER_SIZE);
+        assert!((1..=MAX_AUTO_JOBS).contains(&ssd.jobs));
+        assert_eq!(IoProfile::Auto.tuning(), ssd);
+    }
+
+    #[test]
+    fn explicit_profile_is_kept_and_slowest_end_wins() {
+        assert_eq!(resolve(IoProfile::Hdd, [None, None]), IoProfile::Hdd);
+        assert_eq!(resolve(IoProfile::Auto, [None, None]), IoProfile::Network);
+        assert_eq!(slowest(&[IoProfile::Ssd, IoProfile::Network]), IoProfile::Network);
+        assert_eq!(slowest(&[IoProfile::Network, IoProfile::Hdd]), IoProfile::Hdd);
+        assert_eq!(slowest(&[IoProfile::Ssd, IoProfile::Ssd]), IoProfile::Ssd);
+    }
+
+    #[test]
+    fn mount_type_comes_from_the_longest_mount_point() {
+        let fs = |p: &str| mount_fs_type(MOUNTINFO, Path::new(p));
+        assert_eq!(fs("/home/user"), Some("ext4"));
+        assert_eq!(fs("/mnt/nas/backups"), Some("nfs4"));
+        assert_eq!(fs("/mnt/nas/local/x"), Some("xfs"));
+        assert_eq!(fs("/mnt/my share/docs"), Some("cifs"));
+        // Component prefixes only: /mnt/nasty is not under /mnt/nas
+        assert_eq!(fs("/mnt/nasty"), Some("ext4"));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn split_dev_decodes_major_and_minor() {
+        assert_eq!(split_dev(0x0812), (8, 18));
+        assert_eq!(split_dev((259 << 8) | 3), (259, 3));
+    }
+}
@@ -0,0 +1,279 @@
+//! File listing of a local path or backend URI (`flux ls`).
+//!
+//! Lists a directory (or, with `-R`, everything below it) through the same
+//! `FluxBackend` a copy would use, so a remote can be checked before copying
+//! from or to it. Entries carry size, modification time and type; `-l`
+//! prints them in columns and the global `--json` as an array.
+
+use std::path::Path;
+
+use bytesize::ByteSize;
+use chrono::{DateTime, Local, Utc};
+use serde::Serialize;
+
+use crate::backend::{create_backend, FileStat, FluxBackend};
+use crate::cli::args::LsArgs;
+use crate::config;
+use crate::error::FluxError;
+use crate::protocol::detect_protocol;
+
+/// Order of a listing (`flux ls --sort`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum LsSort {
+    /// By path
+    #[default]
+    Name,
+    /// Largest first
+    Size,
+    /// Most recently modified first
+    Mtime,
+}
+
+/// Whether an entry is a file or a directory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EntryKind {
+    File,
+    Dir,
+}
+
+/// One listed file or directory.
+#[derive(Debug, Clone, Serialize)]
+pub struct LsEntry {
+    /// `/`-separated path relative to the listed directory (the file name
+    /// when a single file was listed)
+    pub path: String,
+    #[serde(rename = "type")]
+    pub kind: EntryKind,
+    /// Size in bytes (as reported by the backend for directories)
+    pub size: u64,
+    /// Last modification, when the backend reports it
+    pub modified: Option<DateTime<Utc>>,
+}
+
+impl LsEntry {
+    fn new(path: String, stat: &FileStat) -> Self {
+        Self {
+            path,
+            kind: if stat.is_dir {
+                EntryKind::Dir
+            } else {
+                EntryKind::File
+            },
+            size: stat.size,
+            modified: stat.modified.map(DateTime::<Utc>::from),
+        }
+    }
+}
+
+/// Execute `flux ls`.
+pub fn execute_ls(args: LsArgs, json: bool) -> Result<(), FluxError> {
+    let resolved = config::aliases::resolve(&args.path);
+    let protocol = detect_protocol(&resolved);
+    let backend = create_backend(&protocol)?;
+
+    // Network backends are rooted at the URI; an empty path means the root
+    let root = protocol.local_path().cloned().unwrap_or_default();
+    let mut entries = list(backend.as_ref(), &root, args.recursive)?;
+    sort_entries(&mut entries, args.sort);
+
+    if json {
+        println!("{}", serde_json::to_string(&entries)?);
+    } else if args.long {
+        for line in render_long(&entries) {
+            println!("{}", line);
+        }
+    } else {
+        for entry in &entries {
+            println!("{}", display_path(entry));
+        }
+    }
+    Ok(())
+}
+
+/// List `root` on `backend`: its entries, everything below it when
+/// `recursive`, or the file itself when `root` is a file.
+///
+/// With `recursive`, subdirectories that cannot be listed are reported on
+/// stderr and skipped; only a missing or unreadable root is an error.
+pub fn list(
+    backend: &dyn FluxBackend,
+    root: &Path,
+    recursive: bool,
+) -> Result<Vec<LsEntry>, FluxError> {
+    let stat = backend.stat(root)?;
+    if !stat.is_dir {
+        let name = root
+            .file_name()
+            .map_or_else(|| root.display().to_string(), |n| n.to_string_lossy().to_string());
+        return Ok(vec![LsEntry::new(name, &stat)]);
+    }
+
+    let mut entries = Vec::new();
+    let mut pending = vec![(root.to_path_buf(), String::new())];
+    while let Some((dir, prefix)) = pending.pop() {
+        let listing = match backend.list_dir(&dir) {
+            Ok(listing) => listing,
+            Err(e) if !prefix.is_empty() => {
+                eprintln!("Cannot list {}: {}", prefix, e);
+                continue;
+            }
+            Err(e) => return Err(e),
+        };
+        for entry in listing {
+            // Backends report entry paths differently (full, absolute or
+            // bare name), so rebuild them from the file name.
+            let Some(name) = entry.path.file_name() else {
+                continue;
+            };
+            let name = name.to_string_lossy();
+            let path = format!("{}{}", prefix, name);
+            if recursive && entry.stat.is_dir {
+                pending.push((dir.join(name.as_ref()), format!("{}/", path)));
+            }
+            entries.push(LsEntry::new(path, &entry.stat));
+        }
+    }
+    Ok(entries)
+}
+
+/// Sort by path, size (largest first) or mtime (newest first); ties by path.
+pub fn sort_entries(entries: &mut [LsEntry], sort: LsSort) {
+    match sort {
+        LsSort::Name => entries.sort_by(|a, b| a.path.cmp(&b.path)),
+        LsSort::Size => {
+            entries.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.path.cmp(&b.path)))
+        }
+        LsSort::Mtime => entries
+            .sort_by(|a, b| b.modified.cmp(&a.modified).then_with(|| a.path.cmp(&b.path))),
+    }
+}
+
+/// `-l` lines: type, size, local modification time and path, in columns.
+pub fn render_long(entries: &[LsEntry]) -> Vec<String> {
+    let sizes: Vec<String> = entries
+        .iter()
+        .map(|e| match e.kind {
+            EntryKind::Dir => "-".to_string(),
+            EntryKind::File => ByteSize(e.size).to_string(),
+        })
+        .collect();
+    let width = sizes.iter().map(String::len).max().unwrap_or(0);
+    entries
+        .iter()
+        .zip(sizes)
+        .map(|(entry, size)| {
+            let kind = match entry.kind {
+                EntryKind::Dir => 'd',
+                EntryKind::File => '-',
+            };
+            let modified = entry.modified.map_or_else(
+                || "-".repeat(16),
+                |t| t.with_timezone(&Local).format("%Y-%m-%d %H:%M").to_string(),
+            );
+            format!(
+                "{}  {:>width$}  {}  {}",
+                kind,
+                size,
+                modified,
+                display_path(entry),
+                width = width
+            )
+        })
+        .collect()
+}
+
+/// Directories end with `/`.
+fn display_path(entry: &LsEntry) -> String {
+    match entry.kind {
+        EntryKind::Dir => format!("{}/", entry.path),
+        EntryKind::File => entry.path.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::local::LocalBackend;
+
+    fn sample() -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        std::fs::create_dir_all(root.join("photos/2024")).unwrap();
+        std::fs::write(root.join("photos/a.jpg"), vec![0u8; 3000]).unwrap();
+        std::fs::write(root.join("photos/2024/b.jpg"), vec![0u8; 5000]).unwrap();
+        std::fs::write(root.join("notes.txt"), b"hello").unwrap();
+        dir
+    }
+
+    fn paths(entries: &[LsEntry]) -> Vec<&str> {
+        entries.iter().map(|e| e.path.as_str()).collect()
+    }
+
+    #[test]
+    fn lists_one_level_or_everything_below() {
+        let dir = sample();
+        let mut entries = list(&LocalBackend::new(), dir.path(), false).unwrap();
+        sort_entries(&mut entries, LsSort::Name);
+        assert_eq!(paths(&entries), vec!["notes.txt", "photos"]);
+        assert_eq!(entries[1].kind, EntryKind::Dir);
+
+        let mut entries = list(&LocalBackend::new(), dir.path(), true).unwrap();
+        sort_entries(&mut entries, LsSort::Name);
+        assert_eq!(
+            paths(&entries),
+            vec!["notes.txt", "photos", "photos/2024", "photos/2024/b.jpg", "photos/a.jpg"]
+        );
+        assert!(entries.iter().all(|e| e.modified.is_some()));
+    }
+
+    #[test]
+    fn sorts_by_size_largest_first() {
+        let dir = sample();
+        let mut entries = list(&LocalBackend::new(), dir.path(), true).unwrap();
+        entries.retain(|e| e.kind == EntryKind::File);
+        sort_entries(&mut entries, LsSort::Size);
+        assert_eq!(paths(&entries), vec!["photos/2024/b.jpg", "photos/a.jpg", "notes.txt"]);
+    }
+
+    #[test]
+    fn a_file_lists_itself_and_a_missing_path_fails() {
+        let dir = sample();
+        let entries = list(&LocalBackend::new(), &dir.path().join("notes.txt"), true).unwrap();
+        assert_eq!(paths(&entries), vec!["notes.txt"]);
+        assert_eq!(entries[0].size, 5);
+        assert!(list(&LocalBackend::new(), &dir.path().join("nope"), false).is_err());
+    }
+
+    #[test]
+    fn long_format_and_json() {
+        let entries = vec![
+            LsEntry {
+                path: "docs".to_string(),
+                kind: EntryKind::Dir,
+                size: 0,
+                modified: None,
+            },
+            LsEntry {
+                path: "docs/a.txt".to_string(),
+                kind: EntryKind::File,
+                size: 10,
+                modified: DateTime::from_timestamp(1_700_000_000, 0),
+            },
+        ];
+        let lines = render_long(&entries);
+        let size = ByteSize(10).to_string();
+        assert_eq!(
+            lines[0],
+            format!("d  {:>w$}  {}  docs/", "-", "-".repeat(16), w = size.len())
+        );
+        assert!(lines[1].starts_with(&format!("-  {}  ", size)));
+        assert!(lines[1].ends_with("  docs/a.txt"));
+
+        let value = serde_json::to_value(&entries).unwrap();
+        assert_eq!(value[0]["type"], "dir");
+        assert_eq!(value[1]["path"], "docs/a.txt");
+        assert_eq!(value[1]["size"], 10);
+        assert_eq!(value[1]["modified"], "2023-11-14T22:13:20Z");
+    }
+}
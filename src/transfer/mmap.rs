@@ -0,0 +1,320 @@
+//! Memory-mapped copy engine (`cp --mmap`).
+//!
+//! The source is mapped read-only and every chunk is hashed and written
+//! straight from the mapping, so the data is never copied into a read
+//! buffer first. Chunks run in parallel with rayon, like
+//! `parallel_copy_chunked_pausable`, with the same pause, cancel, resume and
+//! monitor behaviour.
+//!
+//! A mapped file that is truncated while it is being copied cannot simply
+//! return a short read: touching the missing pages raises SIGBUS. Each
+//! window of the mapping is therefore checked against the file's current
+//! length before it is used, and on Unix a SIGBUS handler turns a fault
+//! inside the mapping (a truncation between the check and the access) into
+//! an error message and exit code 1 instead of a crash. Windows refuses to
+//! truncate a mapped file, so it needs neither.
+
+use std::fs::File;
+use std::io;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use indicatif::ProgressBar;
+use memmap2::Mmap;
+use rayon::prelude::*;
+
+use crate::backend::readonly;
+use crate::error::FluxError;
+use crate::transfer::{cancel, prealloc};
+use crate::transfer::checksum::ChecksumAlgorithm;
+use crate::transfer::chunk::ChunkPlan;
+use crate::transfer::control::PauseSignal;
+use crate::transfer::copy::dest_open_options;
+use crate::transfer::monitor::TransferMonitor;
+use crate::transfer::parallel::{
+    is_positional_write_unsupported, parallel_copy_chunked_pausable, write_at_all,
+};
+
+/// Bytes of the mapping hashed and written per step; the source length is
+/// checked before each.
+const WINDOW: u64 = 8 * 1024 * 1024;
+
+/// Copy `source` to `dest` chunk by chunk from a read-only mapping of the
+/// source, filling in chunk checksums with `algorithm`.
+///
+/// Behaves like `parallel_copy_chunked_pausable`: completed chunks are
+/// skipped, `pause` stops at a chunk boundary with `FluxError::Paused` and
+/// `monitor` sees the chunk map and bytes. A destination that cannot be
+/// pre-allocated is handed to `parallel_copy_chunked_pausable`, which copies
+/// it sequentially. A source that shrinks during the copy fails the copy.
+pub fn mmap_copy_chunked(
+    source: &Path,
+    dest: &Path,
+    chunks: &mut Vec<ChunkPlan>,
+    algorithm: ChecksumAlgorithm,
+    progress: &ProgressBar,
+    pause: Option<&PauseSignal>,
+    monitor: Option<&TransferMonitor>,
+) -> Result<(), FluxError> {
+    readonly::check(dest, "write")?;
+    let src_file = File::open(source).map_err(|e| match e.kind() {
+        io::ErrorKind::NotFound => FluxError::SourceNotFound {
+            path: source.to_path_buf(),
+        },
+        io::ErrorKind::PermissionDenied => FluxError::PermissionDenied {
+            path: source.to_path_buf(),
+        },
+        _ => FluxError::Io { source: e },
+    })?;
+    let src_meta = src_file.metadata()?;
+    let total_size: u64 = chunks.iter().map(|c| c.offset + c.length).max().unwrap_or(0);
+    if src_meta.len() < total_size {
+        return Err(shrank(source));
+    }
+
+    // SAFETY: the mapping is read-only, and a concurrent truncation is
+    // caught by the length checks and the SIGBUS guard below
+    let map = unsafe { Mmap::map(&src_file) }?;
+    #[cfg(unix)]
+    let _ = map.advise(memmap2::Advice::Sequential);
+    #[cfg(unix)]
+    let _guard = sigbus::Guard::new(&map);
+
+    if let Some(parent) = dest.parent() {
+        if !parent.as_os_str().is_empty() && !parent.exists() {
+            std::fs::create_dir_all(parent)?;
+        }
+    }
+    let resuming = chunks.iter().any(|c| c.completed);
+    let dst_file = dest_open_options(&src_meta)
+        .read(true)
+        .truncate(!resuming)
+        .open(dest)
+        .map_err(|e| match e.kind() {
+            io::ErrorKind::PermissionDenied => FluxError::DestinationNotWritable {
+                path: dest.to_path_buf(),
+            },
+            _ => FluxError::Io { source: e },
+        })?;
+    prealloc::allocate(&dst_file, dest, total_size)?;
+    if let Err(e) = dst_file.set_len(total_size) {
+        if is_positional_write_unsupported(&e) {
+            drop(dst_file);
+            tracing::warn!("Cannot pre-allocate {} ({}), not mapping", dest.display(), e);
+            return parallel_copy_chunked_pausable(
+                source, dest, chunks, algorithm, progress, pause, monitor,
+            );
+        }
+        return Err(FluxError::Io { source: e });
+    }
+
+    let generation = monitor.map(|m| m.set_chunks(chunks));
+    let bytes_done = AtomicU64::new(0);
+    chunks
+        .par_iter_mut()
+        .enumerate()
+        .filter(|(_, chunk)| !chunk.completed)
+        .try_for_each(|(index, chunk)| -> Result<(), FluxError> {
+            // Chunk boundary: leave the chunk for a later resume
+            if pause.is_some_and(|p| p.is_requested()) {
+                return Ok(());
+            }
+
+            let mut hasher = algorithm.hasher();
+            let end = chunk.offset + chunk.length;
+            let mut position = chunk.offset;
+            while position < end {
+                // Cancelled: the chunk stays incomplete
+                cancel::check()?;
+                let window_end = end.min(position + WINDOW);
+                if src_file.metadata()?.len() < window_end {
+                    return Err(shrank(source));
+                }
+                let data = &map[position as usize..window_end as usize];
+                write_at_all(&dst_file, position, data)?;
+                hasher.update(data);
+                progress.inc(data.len() as u64);
+                bytes_done.fetch_add(data.len() as u64, Ordering::Relaxed);
+                if let Some(monitor) = monitor {
+                    monitor.add_bytes(data.len() as u64);
+                }
+                position = window_end;
+            }
+
+            chunk.checksum = Some(hasher.finish_hex());
+            chunk.completed = true;
+            if let (Some(monitor), Some(generation)) = (monitor, generation) {
+                monitor.chunk_done(generation, index);
+            }
+            Ok(())
+        })?;
+
+    tracing::debug!(
+        "Copied {} bytes of {} from its mapping",
+        bytes_done.into_inner(),
+        source.display()
+    );
+    if chunks.iter().any(|c| !c.completed) && pause.is_some_and(|p| p.is_requested()) {
+        return Err(FluxError::Paused);
+    }
+    Ok(())
+}
+
+/// Error for a source that got shorter than its planned chunks.
+fn shrank(source: &Path) -> FluxError {
+    FluxError::Io {
+        source: io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            format!("'{}' shrank while it was being copied", source.display()),
+        ),
+    }
+}
+
+/// SIGBUS handling for the mapping being copied.
+///
+/// One mapping is guarded at a time (copies with `--mmap` are single-file);
+/// a fault at any other address keeps the default action.
+#[cfg(unix)]
+mod sigbus {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Once;
+
+    use memmap2::Mmap;
+
+    /// Guarded address range, `[START, END)`; 0 when free.
+    static START: AtomicUsize = AtomicUsize::new(0);
+    static END: AtomicUsize = AtomicUsize::new(0);
+    static INSTALL: Once = Once::new();
+
+    const MESSAGE: &[u8] =
+        b"\nError: the source file was truncated while it was being copied (--mmap); \
+          the destination is incomplete\n";
+
+    /// Registration of a mapping with the handler, removed on drop.
+    pub struct Guard {
+        registered: bool,
+    }
+
+    impl Guard {
+        pub fn new(map: &Mmap) -> Self {
+            INSTALL.call_once(install);
+            let start = map.as_ptr() as usize;
+            let registered = START
+                .compare_exchange(0, start, Ordering::SeqCst, Ordering::SeqCst)
+                .is_ok();
+            if registered {
+                END.store(start + map.len(), Ordering::SeqCst);
+            }
+            Guard { registered }
+        }
+    }
+
+    impl Drop for Guard {
+        fn drop(&mut self) {
+            if self.registered {
+                END.store(0, Ordering::SeqCst);
+                START.store(0, Ordering::SeqCst);
+            }
+        }
+    }
+
+    fn install() {
+        // SAFETY: a zeroed sigaction is valid; the handler only uses
+        // async-signal-safe calls (atomics, write, _exit, signal)
+        unsafe {
+            let mut action: libc::sigaction = std::mem::zeroed();
+            action.sa_sigaction = on_sigbus
+                as extern "C" fn(libc::c_int, *mut libc::siginfo_t, *mut libc::c_void)
+                as libc::sighandler_t;
+            action.sa_flags = libc::SA_SIGINFO;
+            libc::sigemptyset(&mut action.sa_mask);
+            if libc::sigaction(libc::SIGBUS, &action, std::ptr::null_mut()) != 0 {
+                tracing::debug!("Could not install the SIGBUS handler");
+            }
+        }
+    }
+
+    extern "C" fn on_sigbus(_: libc::c_int, info: *mut libc::siginfo_t, _: *mut libc::c_void) {
+        // SAFETY: the kernel passes a valid siginfo_t to SA_SIGINFO handlers
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        let addr = unsafe { (*info).si_addr() } as usize;
+        #[cfg(not(any(target_os = "linux", target_os = "android")))]
+        let addr = unsafe { (*info).si_addr } as usize;
+
+        let start = START.load(Ordering::SeqCst);
+        if start != 0 && addr >= start && addr < END.load(Ordering::SeqCst) {
+            // SAFETY: write and _exit are async-signal-safe
+            unsafe {
+                libc::write(2, MESSAGE.as_ptr().cast(), MESSAGE.len());
+                libc::_exit(1);
+            }
+        }
+        // Not ours: the faulting access repeats with the default action
+        // SAFETY: signal is async-signal-safe
+        unsafe {
+            libc::signal(libc::SIGBUS, libc::SIG_DFL);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transfer::chunk::chunk_file;
+
+    #[test]
+    fn copies_and_checksums_from_the_mapping() {
+        let dir = tempfile::tempdir().unwrap();
+        let src = dir.path().join("source.bin");
+        let dst = dir.path().join("dest.bin");
+        let data: Vec<u8> = (0..3_000_000u32).map(|i| (i % 241) as u8).collect();
+        std::fs::write(&src, &data).unwrap();
+
+        let mut chunks = chunk_file(data.len() as u64, 3);
+        let pb = ProgressBar::hidden();
+        let algorithm = ChecksumAlgorithm::Blake3;
+        mmap_copy_chunked(&src, &dst, &mut chunks, algorithm, &pb, None, None).unwrap();
+
+        assert_eq!(std::fs::read(&dst).unwrap(), data);
+        for chunk in &chunks {
+            let range = chunk.offset as usize..(chunk.offset + chunk.length) as usize;
+            let expected = blake3::hash(&data[range]).to_hex().to_string();
+            assert_eq!(chunk.checksum.as_deref(), Some(expected.as_str()));
+        }
+        assert_eq!(pb.position(), data.len() as u64);
+    }
+
+    #[test]
+    fn completed_chunks_are_kept() {
+        let dir = tempfile::tempdir().unwrap();
+        let src = dir.path().join("source.bin");
+        let dst = dir.path().join("dest.bin");
+        std::fs::write(&src, vec![7u8; 1000]).unwrap();
+        std::fs::write(&dst, vec![1u8; 1000]).unwrap();
+
+        let mut chunks = chunk_file(1000, 2);
+        chunks[0].completed = true;
+        let pb = ProgressBar::hidden();
+        let algorithm = ChecksumAlgorithm::Blake3;
+        mmap_copy_chunked(&src, &dst, &mut chunks, algorithm, &pb, None, None).unwrap();
+
+        let copied = std::fs::read(&dst).unwrap();
+        assert!(copied[..500].iter().all(|&b| b == 1));
+        assert!(copied[500..].iter().all(|&b| b == 7));
+    }
+
+    #[test]
+    fn a_source_shorter_than_planned_is_refused() {
+        let dir = tempfile::tempdir().unwrap();
+        let src = dir.path().join("source.bin");
+        std::fs::write(&src, vec![0u8; 100]).unwrap();
+
+        let mut chunks = chunk_file(200, 2);
+        let pb = ProgressBar::hidden();
+        let dst = dir.path().join("dest.bin");
+        let algorithm = ChecksumAlgorithm::Blake3;
+        let err = mmap_copy_chunked(&src, &dst, &mut chunks, algorithm, &pb, None, None)
+            .unwrap_err();
+        assert!(err.to_string().contains("shrank"), "{}", err);
+    }
+}
@@ -1,38 +1,92 @@
+pub mod atomic;
+pub mod attrs;
+pub mod cancel;
+pub mod changed;
 pub mod checksum;
+pub mod checksum_cache;
 pub mod chunk;
+pub mod clean;
 pub mod compress;
 pub mod conflict;
+pub mod control;
 pub mod copy;
+pub mod dedup;
+pub mod failed;
 pub mod filter;
+pub mod hardlink;
+pub mod history;
+pub mod hooks;
+pub mod io_profile;
+pub mod ls;
+pub mod mmap;
+pub mod monitor;
+pub mod mv;
+#[cfg(feature = "notify")]
+pub mod notification;
 pub mod parallel;
+pub mod prealloc;
 pub mod resume;
+pub mod schedule;
+pub mod snapshot;
 pub mod stats;
+pub mod status;
+pub mod strategy;
+pub mod stream;
 pub mod throttle;
+pub mod tree;
+#[cfg(all(target_os = "linux", feature = "io-uring"))]
+pub mod uring;
+pub mod usage;
 pub mod verify;
 
+use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
+use std::sync::Mutex;
 
 use indicatif::ProgressBar;
+use rayon::prelude::*;
 use walkdir::WalkDir;
+use x25519_dalek::PublicKey;
 
 use crate::backend::create_backend;
+use crate::backend::readonly::{self, ReadOnlyBackend};
 use crate::cli::args::CpArgs;
 use crate::config;
 use crate::config::types::{ConflictStrategy, FailureStrategy};
 use crate::error::FluxError;
-use crate::progress::bar::{create_file_progress, create_transfer_progress};
-use crate::protocol::detect_protocol;
-
-use self::checksum::hash_file;
+use crate::progress::bar::{create_file_progress, suspend, BatchProgress};
+use crate::protocol::{detect_protocol, FluxPath};
+use crate::security::at_rest::{encrypt_file, encrypted_path, load_recipient};
+
+use self::atomic::AtomicFile;
+use self::attrs::{AttrCopier, AttrOptions};
+use self::cancel::PartialFile;
+use self::changed::{ChangedSource, SourceState, SourceWatch};
+use self::checksum::{hash_pair_with, ChecksumAlgorithm};
+use self::checksum_cache::ChecksumCache;
 use self::chunk::{auto_chunk_count, chunk_file};
-use self::conflict::resolve_conflict;
-use self::copy::copy_file_with_progress;
+use self::conflict::ConflictResolver;
+use self::control::PauseSignal;
+use self::copy::{copy_file_with_progress, try_clone_file};
+use self::dedup::{find_duplicates, DedupIndex, DedupMode, Duplicate};
+use self::failed::RetryList;
 use self::filter::TransferFilter;
-use self::parallel::parallel_copy_chunked;
+use self::hardlink::LinkTracker;
+use self::io_profile::IoTuning;
+use self::history::{record_history, HistoryRecord};
+use self::monitor::TransferMonitor;
+use self::parallel::parallel_copy_chunked_pausable;
 use self::resume::TransferManifest;
+use self::snapshot::SourceSnapshot;
 use self::stats::TransferStats;
+use self::status::StatusPublisher;
+use self::strategy::CopyPlan;
 use self::throttle::parse_bandwidth;
 
+/// Largest chunk a pausable single-file copy uses (64 MB), bounding how much
+/// data is still copied after a pause is requested.
+const PAUSE_CHUNK_SIZE: u64 = 64 * 1024 * 1024;
+
 /// Aggregated result of a directory copy operation.
 ///
 /// Tracks successful file copies and collects per-file errors so that
@@ -41,6 +95,15 @@ pub struct TransferResult {
     pub files_copied: u64,
     pub bytes_copied: u64,
     pub errors: Vec<(PathBuf, FluxError)>,
+    /// Files skipped because another program held them locked (`--vss`
+    /// without a shadow copy)
+    pub locked: Vec<PathBuf>,
+    /// Files not copied because their content was already there (`--dedup`)
+    pub deduplicated: u64,
+    /// Bytes of the deduplicated files
+    pub bytes_saved: u64,
+    /// Further names of source files linked to their copy (`--hard-links`)
+    pub hard_links: u64,
 }
 
 impl TransferResult {
@@ -49,6 +112,10 @@ impl TransferResult {
             files_copied: 0,
             bytes_copied: 0,
             errors: Vec::new(),
+            locked: Vec::new(),
+            deduplicated: 0,
+            bytes_saved: 0,
+            hard_links: 0,
         }
     }
 
@@ -60,6 +127,11 @@ impl TransferResult {
     pub fn add_error(&mut self, path: PathBuf, err: FluxError) {
         self.errors.push((path, err));
     }
+
+    pub fn add_deduplicated(&mut self, bytes: u64) {
+        self.deduplicated += 1;
+        self.bytes_saved += bytes;
+    }
 }
 
 /// Execute a copy command based on parsed CLI arguments.
@@ -72,17 +144,93 @@ impl TransferResult {
 /// Config is loaded lazily here (only when transfer commands need it).
 /// CLI flags override config.toml values.
 pub fn execute_copy(args: CpArgs, quiet: bool) -> Result<(), FluxError> {
-    // Track start time for history recording
-    let start_time = std::time::Instant::now();
+    // The monitor feeds `flux status`
+    let monitor = TransferMonitor::new(&args.source, &args.dest);
+    let _status = if args.dry_run {
+        None
+    } else {
+        StatusPublisher::for_monitor("cp", &monitor)
+    };
+    execute_copy_as("cp", args, quiet, None, Some(&monitor))
+}
+
+/// Execute a copy and record it in history under `operation`.
+///
+/// `flux queue run` uses this with `"queue"` so queued copies are
+/// distinguishable from direct `flux cp` invocations. Dry runs are not recorded.
+///
+/// With a `pause` signal the copy can be suspended at a chunk boundary (or
+/// between files for directories), returning `FluxError::Paused` after the
+/// resume manifest has been saved. A `monitor` receives live progress and
+/// applies bandwidth limit changes made while the copy runs.
+pub fn execute_copy_as(
+    operation: &'static str,
+    args: CpArgs,
+    quiet: bool,
+    pause: Option<&PauseSignal>,
+    monitor: Option<&TransferMonitor>,
+) -> Result<(), FluxError> {
+    let dry_run = args.dry_run;
+    let mut record = HistoryRecord::new(operation, &args.source, &args.dest);
+    record.hooks = args.hooks.clone();
+    // --changed-source retry: a single file that changed is copied again from
+    // the start (directory copies retry each file themselves)
+    let mut attempt = 0;
+    let result = loop {
+        match copy_inner(args.clone(), quiet, &mut record, pause, monitor) {
+            Err(FluxError::SourceChanged { path, change })
+                if args.changed_source == ChangedSource::Retry && attempt < changed::RETRIES =>
+            {
+                attempt += 1;
+                tracing::warn!(
+                    "{} {} while it was copied; copying it again ({}/{})",
+                    path.display(),
+                    change,
+                    attempt,
+                    changed::RETRIES
+                );
+            }
+            result => break result,
+        }
+    };
+    let result = result.map_err(|e| {
+        if snapshot::is_locked_file(&e) {
+            FluxError::FileLocked {
+                path: PathBuf::from(&record.source),
+            }
+        } else {
+            e
+        }
+    });
+    if !dry_run {
+        record_history(&record, result.as_ref().err());
+    }
+    result
+}
+
+/// Body of `execute_copy`; fills `record` with what was transferred.
+fn copy_inner(
+    args: CpArgs,
+    quiet: bool,
+    record: &mut HistoryRecord,
+    pause: Option<&PauseSignal>,
+    monitor: Option<&TransferMonitor>,
+) -> Result<(), FluxError> {
+    // Track start time for the completion summary
+    let start_time = record.started;
 
     // Load config (graceful -- use defaults on error)
     let flux_config = config::types::load_config().unwrap_or_default();
 
-    // CLI flags override config
+    // CLI flags override config (`--on-error retry:N` also the retry count)
     let conflict_strategy = args.on_conflict.unwrap_or(flux_config.conflict);
-    let failure_strategy = args.on_error.unwrap_or(flux_config.failure);
-    let retry_count = flux_config.retry_count;
+    let failure_strategy = args.on_error.map_or(flux_config.failure, |p| p.strategy);
+    let retry_count = args
+        .on_error
+        .and_then(|p| p.retries)
+        .unwrap_or(flux_config.retry_count);
     let retry_backoff_ms = flux_config.retry_backoff_ms;
+    let conflicts = ConflictResolver::new(conflict_strategy, flux_config.conflict_fallback);
 
     tracing::debug!(
         "Config: conflict={:?}, failure={:?}, retries={}, backoff={}ms",
@@ -90,30 +238,77 @@ pub fn execute_copy(args: CpArgs, quiet: bool) -> Result<(), FluxError> {
     );
 
     // Resolve aliases before protocol detection
-    let alias_store = match config::paths::flux_config_dir() {
-        Ok(dir) => config::aliases::AliasStore::load(&dir).unwrap_or_default(),
-        Err(_) => config::aliases::AliasStore::default(),
-    };
+    let alias_store = config::aliases::AliasStore::current();
     let source_str = config::aliases::resolve_alias(&args.source, &alias_store);
-    let dest_str = config::aliases::resolve_alias(&args.dest, &alias_store);
+    let dest_str = config::aliases::resolve_destination(&args.dest, &alias_store);
 
     tracing::debug!("Alias resolution: {} -> {}", args.source, strip_url_credentials(&source_str));
     tracing::debug!("Alias resolution: {} -> {}", args.dest, strip_url_credentials(&dest_str));
 
     // Detect protocols from resolved source and destination strings
+    let src_path = FluxPath::parse(&source_str);
+    let dst_path = FluxPath::parse(&dest_str);
     let src_protocol = detect_protocol(&source_str);
     let dst_protocol = detect_protocol(&dest_str);
 
     tracing::debug!("Source protocol: {} ({})", src_protocol.name(), strip_url_credentials(&source_str));
     tracing::debug!("Dest protocol: {} ({})", dst_protocol.name(), strip_url_credentials(&dest_str));
 
-    // For non-local protocols, validate the backend is available (will error with stub message)
-    if !src_protocol.is_local() {
-        let _backend = create_backend(&src_protocol)?;
+    record.source = source_str.clone();
+    record.dest = dest_str.clone();
+    record.peer = match (src_protocol.is_local(), dst_protocol.is_local()) {
+        (true, true) => None,
+        (false, true) => Some(src_protocol.name().to_string()),
+        (true, false) => Some(dst_protocol.name().to_string()),
+        (false, false) => Some(format!("{}->{}", src_protocol.name(), dst_protocol.name())),
+    };
+
+    // A backend over its `[backends.max_monthly_gb]` cap takes no more
+    // transfers this month
+    if !args.dry_run {
+        if let Some(key) = usage::UsageKey::for_copy(&source_str, &dest_str) {
+            usage::check_cap(&key.backend)?;
+        }
+    }
+
+    // --read-only: refuse writes under a local source until the copy is done
+    let _protection = match src_protocol.local_path() {
+        Some(path) if args.read_only && path.exists() => Some(readonly::protect(path)?),
+        _ => None,
+    };
+
+    // `-` as source or destination: stream stdin/stdout through a backend
+    if stream::is_stdio(&args.source) || stream::is_stdio(&args.dest) {
+        return stream::copy_stream(&args, &src_path, &dst_path, quiet, record, monitor);
+    }
+
+    // A network end: files go one at a time through the backends' readers
+    // and writers. The chunked engine below works on local paths only, and
+    // does not decrypt
+    if !(src_path.is_local() && dst_path.is_local()) || args.decrypt {
+        return stream::copy_stream(&args, &src_path, &dst_path, quiet, record, monitor);
     }
-    if !dst_protocol.is_local() {
-        let _backend = create_backend(&dst_protocol)?;
+
+    // Connect both ends (non-local backends fail here if unavailable) and
+    // choose chunking, resume and permission handling from their features
+    let mut src_backend = create_backend(&src_protocol)?;
+    if args.read_only {
+        src_backend = Box::new(ReadOnlyBackend::new(src_backend));
     }
+    let plan = CopyPlan::new(
+        (src_protocol.name(), src_backend),
+        (dst_protocol.name(), create_backend(&dst_protocol)?),
+        args.resume,
+    );
+    let mut attr_options = args.attrs.options();
+    if attr_options.any() && !(src_protocol.is_local() && dst_protocol.is_local()) {
+        tracing::warn!("--xattrs and --acls only apply between local paths; ignoring them");
+        attr_options = AttrOptions::default();
+    }
+    let plan = plan
+        .attributes(AttrCopier::new(attr_options))
+        .changes(SourceWatch::new(args.changed_source));
+    let resume = plan.strategy.resume;
 
     // Extract local paths -- for now, only local-to-local transfers are supported.
     // Once network backends are implemented (Plans 02-04), this will route through
@@ -129,8 +324,32 @@ pub fn execute_copy(args: CpArgs, quiet: bool) -> Result<(), FluxError> {
     let source = &source;
     let dest = &dest;
 
+    // Chunking, buffers and concurrency for the storage at both ends
+    let profile = io_profile::resolve(
+        args.io_profile.unwrap_or(flux_config.io_profile),
+        [
+            src_protocol.is_local().then_some(source.as_path()),
+            dst_protocol.is_local().then_some(dest.as_path()),
+        ],
+    );
+    let io_tuning = profile.tuning();
+    io_profile::apply(&io_tuning);
+    tracing::debug!("I/O profile: {}", io_tuning.profile);
+    if args.no_preallocate {
+        prealloc::disable();
+    }
+
+    // --retry-from: only the paths a failed copy listed
+    let retry_list = args.retry_from.as_deref().map(RetryList::load).transpose()?;
+    if let (Some(list), Some(file), false) = (&retry_list, &args.retry_from, quiet) {
+        eprintln!("Retrying {} path(s) from {}", list.len(), file.display());
+    }
+
     // Build the filter from CLI patterns
-    let filter = TransferFilter::new(&args.exclude, &args.include)?;
+    let filter = TransferFilter::new(&args.exclude, &args.include)?
+        .skip_hidden(args.hidden.skip_hidden(flux_config.exclude_hidden))
+        .skip_system(!args.hidden.include_system)
+        .only(retry_list);
 
     // Validate: source must exist
     let source_meta = std::fs::metadata(source).map_err(|e| match e.kind() {
@@ -149,6 +368,11 @@ pub fn execute_copy(args: CpArgs, quiet: bool) -> Result<(), FluxError> {
             path: source.clone(),
         });
     }
+    if args.retry_from.is_some() && !source_meta.is_dir() {
+        return Err(FluxError::Config(
+            "--retry-from needs the directory the failed copy was made from".into(),
+        ));
+    }
 
     // Validate: source != dest (canonicalize to resolve symlinks and relative paths)
     if let (Ok(canon_src), Ok(canon_dst)) = (
@@ -188,10 +412,64 @@ pub fn execute_copy(args: CpArgs, quiet: bool) -> Result<(), FluxError> {
     // Phase 3 optimization: shared limiter across parallel threads.
     let chunk_count = if _bandwidth_limit.is_some() {
         1 // Sequential for throttled transfers
+    } else if !plan.strategy.parallel {
+        1 // One end cannot read or write ranges independently
     } else if args.chunks > 0 {
         args.chunks
     } else {
-        auto_chunk_count(source_meta.len())
+        io_tuning.chunk_count(source_meta.len())
+    };
+
+    // --encrypt-to: files are sealed to the recipient key as they are written
+    // (see security::at_rest), so they are streamed rather than cloned
+    let recipient = args.encrypt_to.as_deref().map(load_recipient).transpose()?;
+
+    let clone_allowed = !args.no_clone && _bandwidth_limit.is_none() && recipient.is_none();
+
+    // --snapshot-source: read from a point-in-time snapshot of the source
+    // volume. It is removed again when `snapshot` drops at the end of the copy.
+    // --vss falls back to the live files when no shadow copy can be made,
+    // skipping locked files instead of failing on them.
+    let mut skip_locked = false;
+    let snapshot = if (args.snapshot_source || args.vss) && !args.dry_run {
+        let flag = if args.vss { "--vss" } else { "--snapshot-source" };
+        if !src_protocol.is_local() {
+            return Err(FluxError::SnapshotError(format!(
+                "{} needs a local source",
+                flag
+            )));
+        }
+        if args.vss && !cfg!(windows) {
+            return Err(FluxError::SnapshotError(
+                "--vss is only available on Windows. Use --snapshot-source for btrfs/LVM snapshots"
+                    .into(),
+            ));
+        }
+        match SourceSnapshot::create(source) {
+            Ok(snapshot) => {
+                if !quiet {
+                    eprintln!("Reading from {}", snapshot.description());
+                }
+                Some(snapshot)
+            }
+            Err(e) if args.vss => {
+                eprintln!("Warning: {}", e);
+                eprintln!("Copying the live files; locked files will be skipped");
+                skip_locked = true;
+                None
+            }
+            Err(e) => return Err(e),
+        }
+    } else {
+        None
+    };
+    let redirected = snapshot
+        .as_ref()
+        .map(|s| s.redirect(source, dest, source_meta.is_dir()))
+        .transpose()?;
+    let (source, dest) = match redirected {
+        Some((ref source, ref dest)) => (source, dest),
+        None => (source, dest),
     };
 
     if source_meta.is_file() {
@@ -201,6 +479,7 @@ pub fn execute_copy(args: CpArgs, quiet: bool) -> Result<(), FluxError> {
                 "Skipped {} (excluded by filter)",
                 source.display()
             );
+            record.skipped = true;
             return Ok(());
         }
 
@@ -215,9 +494,25 @@ pub fn execute_copy(args: CpArgs, quiet: bool) -> Result<(), FluxError> {
         } else {
             dest.clone()
         };
+        // Encrypted copies get `.fluxenc` unless the file name was given
+        let final_dest = match recipient {
+            Some(_) if dest.is_dir() => encrypted_path(&final_dest),
+            _ => final_dest,
+        };
 
         let size = source_meta.len();
 
+        // A pausable copy needs chunk boundaries to stop at, so split large
+        // files into chunks of at most PAUSE_CHUNK_SIZE bytes.
+        let chunk_count = match pause {
+            Some(_) if _bandwidth_limit.is_none() && plan.strategy.parallel => {
+                chunk_count.max(size.div_ceil(PAUSE_CHUNK_SIZE) as usize)
+            }
+            _ => chunk_count,
+        };
+        // Paused copies must leave a manifest behind to resume from
+        let persist_manifest = resume || pause.is_some();
+
         // --- Dry-run mode for single file ---
         if args.dry_run {
             let action = if final_dest.exists() {
@@ -240,16 +535,69 @@ pub fn execute_copy(args: CpArgs, quiet: bool) -> Result<(), FluxError> {
             return Ok(());
         }
 
+        // --dedup: look for the content anywhere in the destination
+        if let Some(mode) = args.dedup {
+            let root = if dest.is_dir() {
+                dest.as_path()
+            } else {
+                final_dest
+                    .parent()
+                    .filter(|p| !p.as_os_str().is_empty())
+                    .unwrap_or(Path::new("."))
+            };
+            if deduplicate_file(source, size, root, &final_dest, mode, &conflicts)? {
+                if !quiet {
+                    eprintln!("{}", dedup::summary(mode, 1, size));
+                }
+                record.skipped = mode == DedupMode::Skip;
+                return Ok(());
+            }
+        }
+
         // --- Conflict resolution for single file ---
-        let final_dest = match resolve_conflict(&final_dest, conflict_strategy)? {
+        let final_dest = match conflicts.resolve(source, &final_dest)? {
             Some(path) => path,
-            None => return Ok(()), // Skip
+            None => {
+                record.skipped = true;
+                return Ok(());
+            }
         };
 
-        // Resume support: load existing manifest if --resume is set
-        let mut resume_chunks = if args.resume {
-            match TransferManifest::load(&final_dest)? {
-                Some(manifest) if manifest.is_compatible(source, size) => {
+        // --atomic: write to a temp file next to the destination and rename
+        // it into place at the end. Resumable copies keep the temp file.
+        let atomic_file = args
+            .atomic
+            .then(|| AtomicFile::new(&final_dest).keep_on_failure(persist_manifest));
+        let write_dest = atomic_file
+            .as_ref()
+            .map_or_else(|| final_dest.clone(), |file| file.path().to_path_buf());
+
+        // Resume support: load existing manifest if --resume is set. Its
+        // chunks keep the checksum algorithm they were started with.
+        let mut chunk_algorithm = args.checksum;
+        let mut resume_chunks = if resume {
+            match TransferManifest::load(&write_dest)? {
+                Some(mut manifest) if manifest.is_compatible(source, size) => {
+                    if args.resume_verify {
+                        let check = manifest.reverify(&write_dest)?;
+                        tracing::info!(
+                            "Resume check: chunks {:?} of {} changed",
+                            check.invalid,
+                            check.checked
+                        );
+                        if !quiet && check.checked > 0 {
+                            if check.invalid.is_empty() {
+                                eprintln!("Resume check: {} chunks intact", check.checked);
+                            } else {
+                                eprintln!(
+                                    "Resume check: {}/{} chunks changed, copying them again ({})",
+                                    check.invalid.len(),
+                                    check.checked,
+                                    bytesize::ByteSize(check.invalid_bytes)
+                                );
+                            }
+                        }
+                    }
                     let completed = manifest.completed_count();
                     let total = manifest.chunk_count;
                     let completed_bytes = manifest.completed_bytes();
@@ -265,6 +613,7 @@ pub fn execute_copy(args: CpArgs, quiet: bool) -> Result<(), FluxError> {
                             completed, total
                         );
                     }
+                    chunk_algorithm = manifest.checksum_algorithm;
                     Some(manifest.chunks)
                 }
                 Some(_manifest) => {
@@ -272,7 +621,7 @@ pub fn execute_copy(args: CpArgs, quiet: bool) -> Result<(), FluxError> {
                     tracing::warn!(
                         "Existing manifest incompatible (source/size changed), starting fresh"
                     );
-                    TransferManifest::cleanup(&final_dest)?;
+                    TransferManifest::cleanup(&write_dest)?;
                     None
                 }
                 None => None,
@@ -281,8 +630,48 @@ pub fn execute_copy(args: CpArgs, quiet: bool) -> Result<(), FluxError> {
             None
         };
 
-        if chunk_count > 1 && size > 0 {
-            // Parallel chunked copy path
+        if let Some(monitor) = monitor {
+            monitor.start(size, 0);
+        }
+
+        // A cancelled copy leaves nothing behind unless it can be resumed
+        // (--atomic temp files are removed by AtomicFile)
+        let partial = (atomic_file.is_none() && !persist_manifest)
+            .then(|| PartialFile::new(&write_dest));
+
+        // Clone fast path (reflink / block clone). Throttled copies must move
+        // real bytes, and pausable copies need chunk boundaries to stop at.
+        let cloned = if clone_allowed && pause.is_none() && size > 0 {
+            let progress = create_file_progress(size, quiet);
+            let cloned = try_clone_file(source, &write_dest, &progress)?;
+            if cloned {
+                progress.finish_with_message("cloned");
+            } else {
+                progress.finish_and_clear();
+            }
+            cloned
+        } else {
+            false
+        };
+
+        if cloned {
+            if resume {
+                TransferManifest::cleanup(&write_dest)?;
+            }
+            if let Some(monitor) = monitor {
+                monitor.add_bytes(size);
+            }
+            tracing::info!("Cloned {} bytes", size);
+        } else if let Some(ref recipient) = recipient {
+            let progress = create_file_progress(size, quiet);
+            encrypt_file(source, &write_dest, recipient, &progress)?;
+            progress.finish_with_message("encrypted");
+            if let Some(monitor) = monitor {
+                monitor.add_bytes(size);
+            }
+            tracing::info!("Encrypted {} bytes", size);
+        } else if (chunk_count > 1 || args.mmap) && size > 0 {
+            // Parallel chunked copy path (from a mapping of the source with --mmap)
             let progress = create_file_progress(size, quiet);
 
             let chunks = if let Some(ref mut existing) = resume_chunks {
@@ -292,6 +681,9 @@ pub fn execute_copy(args: CpArgs, quiet: bool) -> Result<(), FluxError> {
                     .map(|c| c.length)
                     .sum();
                 progress.set_position(completed_bytes);
+                if let Some(monitor) = monitor {
+                    monitor.start(size, completed_bytes);
+                }
                 existing
             } else {
                 // Fresh chunk plan
@@ -300,23 +692,67 @@ pub fn execute_copy(args: CpArgs, quiet: bool) -> Result<(), FluxError> {
             };
 
             // Save initial manifest if --resume
-            if args.resume {
+            if persist_manifest {
                 let manifest = TransferManifest::new(
                     source.clone(),
-                    final_dest.clone(),
+                    write_dest.clone(),
                     size,
                     chunks.clone(),
                     args.compress,
-                );
-                manifest.save(&final_dest)?;
+                )
+                .with_checksum_algorithm(chunk_algorithm);
+                manifest.save(&write_dest)?;
             }
 
-            parallel_copy_chunked(source, &final_dest, chunks, &progress)?;
+            let engine = if args.mmap {
+                mmap::mmap_copy_chunked
+            } else {
+                parallel_copy_chunked_pausable
+            };
+            let copied = engine(
+                source,
+                &write_dest,
+                chunks,
+                chunk_algorithm,
+                &progress,
+                pause,
+                monitor,
+            );
+            if let Err(e @ (FluxError::Paused | FluxError::Cancelled)) = copied {
+                progress.abandon();
+                if persist_manifest {
+                    // Record which chunks finished so the next run continues from here
+                    let manifest = TransferManifest::new(
+                        source.clone(),
+                        write_dest.clone(),
+                        size,
+                        chunks.clone(),
+                        args.compress,
+                    )
+                    .with_checksum_algorithm(chunk_algorithm);
+                    manifest.save(&write_dest)?;
+                    record.bytes = manifest.completed_bytes();
+                    if !quiet {
+                        let stopped = match e {
+                            FluxError::Paused => "Paused",
+                            _ => "Cancelled",
+                        };
+                        eprintln!(
+                            "{}: {}/{} chunks complete",
+                            stopped,
+                            manifest.completed_count(),
+                            manifest.chunk_count
+                        );
+                    }
+                }
+                return Err(e);
+            }
+            copied?;
             progress.finish_with_message("done");
 
             // Save completed manifest and then clean up
-            if args.resume {
-                TransferManifest::cleanup(&final_dest)?;
+            if persist_manifest {
+                TransferManifest::cleanup(&write_dest)?;
             }
 
             tracing::info!(
@@ -329,16 +765,17 @@ pub fn execute_copy(args: CpArgs, quiet: bool) -> Result<(), FluxError> {
             let progress = create_file_progress(size, quiet);
 
             // Save initial manifest if --resume (even for sequential)
-            if args.resume && size > 0 {
+            if resume && size > 0 {
                 let fresh_chunks = resume_chunks.unwrap_or_else(|| chunk_file(size, 1));
                 let manifest = TransferManifest::new(
                     source.clone(),
-                    final_dest.clone(),
+                    write_dest.clone(),
                     size,
                     fresh_chunks,
                     args.compress,
-                );
-                manifest.save(&final_dest)?;
+                )
+                .with_checksum_algorithm(chunk_algorithm);
+                manifest.save(&write_dest)?;
             }
 
             if let Some(bps) = _bandwidth_limit {
@@ -347,22 +784,27 @@ pub fn execute_copy(args: CpArgs, quiet: bool) -> Result<(), FluxError> {
                 use self::throttle::ThrottledReader;
 
                 let src_file = std::fs::File::open(source).map_err(|e| FluxError::Io { source: e })?;
-                let reader = BufReader::with_capacity(256 * 1024, src_file);
+                let src_meta = src_file.metadata()?;
+                io_profile::advise_sequential(&src_file);
+                let reader = BufReader::with_capacity(io_profile::buffer_size(), src_file);
                 let mut throttled = ThrottledReader::new(reader, bps);
 
                 // Ensure parent dir exists
-                if let Some(parent) = final_dest.parent() {
+                if let Some(parent) = write_dest.parent() {
                     if !parent.as_os_str().is_empty() && !parent.exists() {
                         std::fs::create_dir_all(parent)?;
                     }
                 }
 
-                let dst_file = std::fs::File::create(&final_dest).map_err(|e| FluxError::Io { source: e })?;
-                let mut writer = BufWriter::with_capacity(256 * 1024, dst_file);
+                let dst_file = copy::dest_open_options(&src_meta)
+                    .open(&write_dest)
+                    .map_err(|e| FluxError::Io { source: e })?;
+                let mut writer = BufWriter::with_capacity(io_profile::buffer_size(), dst_file);
 
                 let mut buf = [0u8; 256 * 1024];
                 let mut total_bytes = 0u64;
                 loop {
+                    cancel::check()?;
                     let n = throttled.read(&mut buf)?;
                     if n == 0 {
                         break;
@@ -370,27 +812,37 @@ pub fn execute_copy(args: CpArgs, quiet: bool) -> Result<(), FluxError> {
                     writer.write_all(&buf[..n])?;
                     total_bytes += n as u64;
                     progress.set_position(total_bytes);
+                    if let Some(monitor) = monitor {
+                        monitor.add_bytes(n as u64);
+                    }
                 }
                 writer.flush()?;
                 progress.finish_with_message("done");
                 tracing::info!("Copied {} bytes (throttled to {} B/s)", total_bytes, bps);
             } else {
-                let bytes = copy_file_with_progress(source, &final_dest, &progress)?;
+                let bytes = copy_file_with_progress(source, &write_dest, &progress)?;
+                if let Some(monitor) = monitor {
+                    monitor.add_bytes(bytes);
+                }
                 tracing::info!("Copied {} bytes", bytes);
             }
 
             // Clean up resume manifest on success
-            if args.resume {
-                TransferManifest::cleanup(&final_dest)?;
+            if resume {
+                TransferManifest::cleanup(&write_dest)?;
             }
         }
 
+        // The copy only holds the file as it was planned
+        plan.changes.check(source, &SourceState::of(&source_meta))?;
+
         // Post-transfer verification if --verify is set
         if args.verify && source_meta.len() > 0 {
-            let source_hash = hash_file(source)?;
-            let dest_hash = hash_file(&final_dest)?;
+            let (source_hash, dest_hash) = hash_pair_with(source, &write_dest, args.checksum);
+            let (source_hash, dest_hash) = (source_hash?, dest_hash?);
 
             if source_hash != dest_hash {
+                record.verified = Some(false);
                 return Err(FluxError::ChecksumMismatch {
                     path: final_dest.clone(),
                     expected: source_hash,
@@ -398,40 +850,56 @@ pub fn execute_copy(args: CpArgs, quiet: bool) -> Result<(), FluxError> {
                 });
             }
 
-            tracing::info!("Integrity verified (BLAKE3)");
+            record.verified = Some(true);
+            tracing::info!("Integrity verified ({})", args.checksum.label());
             if !quiet {
-                eprintln!("Integrity verified (BLAKE3)");
+                suspend(|| {
+                    eprintln!("Integrity verified ({})", args.checksum.label())
+                });
             }
         }
 
+        // Only a complete (and verified) file takes the destination name
+        if let Some(file) = atomic_file {
+            file.commit()?;
+        }
+        if let Some(partial) = partial {
+            partial.done();
+        }
+        plan.restore_permissions(source, &final_dest);
+        plan.attributes.apply(source, &final_dest);
+        plan.attributes.report();
+        plan.changes.report();
+
         // Print completion summary with throughput
         {
             let mut stats = TransferStats::new(1, size);
             stats.started = start_time;
             stats.add_done(size);
+            stats.retries = crate::backend::retry::take_counts();
             let filename = source
                 .file_name()
                 .map(|n| n.to_string_lossy().to_string())
                 .unwrap_or_else(|| source.display().to_string());
-            stats.print_file_summary(&filename, quiet);
-        }
-
-        // Record in history (best-effort, don't fail the transfer on history error)
-        record_history(
-            &source_str,
-            &dest_str,
-            source_meta.len(),
-            1,
-            start_time.elapsed().as_secs_f64(),
-            "completed",
-            None,
-        );
+            suspend(|| stats.print_file_summary(&filename, quiet));
+            if args.stats {
+                if recipient.is_some() {
+                    stats.bytes_written = std::fs::metadata(&final_dest).ok().map(|m| m.len());
+                }
+                stats.peak_bps = status::take_peak_rate();
+                stats.compress = args.compress;
+                suspend(|| stats.print_stats());
+            }
+        }
+
+        record.bytes = size;
+        record.files = 1;
 
         Ok(())
     } else if source_meta.is_dir() {
         // --- Dry-run mode for directory ---
         if args.dry_run {
-            return dry_run_directory(source, dest, &filter, conflict_strategy);
+            return dry_run_directory(source, dest, &filter, conflict_strategy, recipient.as_ref());
         }
 
         // Directory copy with filtering, conflict resolution, failure handling,
@@ -441,32 +909,66 @@ pub fn execute_copy(args: CpArgs, quiet: bool) -> Result<(), FluxError> {
             dest,
             &filter,
             quiet,
+            args.stats.then_some(args.compress),
             chunk_count,
-            args.verify,
-            conflict_strategy,
+            args.verify.then_some(args.checksum),
+            &conflicts,
             failure_strategy,
             retry_count,
             retry_backoff_ms,
+            clone_allowed,
+            args.atomic,
+            recipient.as_ref(),
+            pause,
+            monitor,
+            directory_jobs(args.jobs, &io_tuning, conflict_strategy, failure_strategy),
+            skip_locked,
+            args.dedup,
+            args.hard_links,
+            &plan,
         )?;
+        plan.attributes.report();
+        plan.changes.report();
 
         tracing::info!(
             "Copied {} file(s), {} bytes",
             result.files_copied,
             result.bytes_copied
         );
+        if let (Some(mode), false) = (args.dedup, quiet) {
+            if result.deduplicated > 0 {
+                eprintln!(
+                    "{}",
+                    dedup::summary(mode, result.deduplicated, result.bytes_saved)
+                );
+            }
+        }
+        if result.hard_links > 0 && !quiet {
+            eprintln!("Recreated {} hard link(s)", result.hard_links);
+        }
 
-        if !result.errors.is_empty() {
-            // Record partial success in history
-            record_history(
-                &source_str,
-                &dest_str,
-                result.bytes_copied,
-                result.files_copied,
-                start_time.elapsed().as_secs_f64(),
-                "failed",
-                Some(format!("{} file(s) failed to copy", result.errors.len())),
+        record.bytes = result.bytes_copied;
+        record.files = result.files_copied;
+        if args.verify {
+            let mismatches = result
+                .errors
+                .iter()
+                .any(|(_, e)| matches!(e, FluxError::ChecksumMismatch { .. }));
+            record.verified = Some(!mismatches);
+        }
+
+        if !result.locked.is_empty() {
+            // Skipped rather than failed: report them even in quiet mode
+            eprintln!(
+                "Skipped {} locked file(s) (in use by another program):",
+                result.locked.len()
             );
+            for path in &result.locked {
+                eprintln!("  {}", path.display());
+            }
+        }
 
+        if !result.errors.is_empty() {
             // Report errors to stderr
             if !quiet {
                 eprintln!(
@@ -477,29 +979,25 @@ pub fn execute_copy(args: CpArgs, quiet: bool) -> Result<(), FluxError> {
                     eprintln!("  {}: {}", path.display(), err);
                 }
             }
-            // Return an error summarizing the failures
-            return Err(FluxError::Io {
-                source: std::io::Error::new(
-                    std::io::ErrorKind::Other,
-                    format!(
-                        "{} file(s) failed to copy",
-                        result.errors.len()
-                    ),
+            // Listed for `--retry-from`, even when quiet: it is the way back
+            let paths: Vec<&Path> = result.errors.iter().map(|(path, _)| path.as_path()).collect();
+            match failed::write_list(args.failed_out.as_deref(), source, dest, &paths) {
+                Ok(list) => eprintln!(
+                    "Failed paths saved to {} (copy only them with --retry-from)",
+                    list.display()
                 ),
+                Err(e) => tracing::warn!("Failed paths not saved: {}", e),
+            }
+            // Return an error summarizing the failures
+            return Err(FluxError::PartialFailure {
+                failed: result
+                    .errors
+                    .iter()
+                    .map(|(path, err)| (path.clone(), err.to_string()))
+                    .collect(),
             });
         }
 
-        // Record in history (best-effort)
-        record_history(
-            &source_str,
-            &dest_str,
-            result.bytes_copied,
-            result.files_copied,
-            start_time.elapsed().as_secs_f64(),
-            "completed",
-            None,
-        );
-
         Ok(())
     } else {
         Err(FluxError::Io {
@@ -520,26 +1018,11 @@ fn dry_run_directory(
     dest: &Path,
     filter: &TransferFilter,
     conflict_strategy: ConflictStrategy,
+    recipient: Option<&PublicKey>,
 ) -> Result<(), FluxError> {
-    let source_str = source.to_string_lossy();
-    let has_trailing_slash = source_str.ends_with('/') || source_str.ends_with('\\');
-
-    let source_clean = if has_trailing_slash {
-        let trimmed = source_str.trim_end_matches(|c| c == '/' || c == '\\');
-        PathBuf::from(trimmed)
-    } else {
-        source.to_path_buf()
-    };
-
-    let dest_base = if has_trailing_slash {
-        dest.to_path_buf()
-    } else {
-        if let Some(dir_name) = source_clean.file_name() {
-            dest.join(dir_name)
-        } else {
-            dest.to_path_buf()
-        }
-    };
+    let source = FluxPath::local(source);
+    let source_clean = source.path.clone();
+    let dest_base = source.target_in(dest);
 
     let mut total_files = 0u64;
     let mut total_bytes = 0u64;
@@ -572,6 +1055,10 @@ fn dry_run_directory(
         }
 
         let dest_path = dest_base.join(relative);
+        let dest_path = match recipient {
+            Some(_) if entry.file_type().is_file() => encrypted_path(&dest_path),
+            _ => dest_path,
+        };
         let file_size = entry.metadata().map(|m| m.len()).unwrap_or(0);
 
         let action = if dest_path.exists() {
@@ -612,46 +1099,43 @@ fn dry_run_directory(
 /// - Source path has no trailing separator: copy source directory itself into dest
 ///   (creates dest/source_dirname/)
 ///
-/// Individual file errors are collected in TransferResult, not fatal.
-/// Progress bar tracks file count (not bytes).
+/// The tree is walked once to build the file list (with sizes), then up to
+/// `jobs` files are copied concurrently; large files are still split into
+/// chunks. Individual file errors are collected in TransferResult, not
+/// fatal. Progress counts bytes across all workers, with a line and ETA of
+/// its own for each large file in flight. With `stats_block` (`--stats`,
+/// holding whether `--compress` was given) the statistics block follows the
+/// summary.
 #[allow(clippy::too_many_arguments)]
 fn copy_directory(
     source: &Path,
     dest: &Path,
     filter: &TransferFilter,
     quiet: bool,
+    stats_block: Option<bool>,
     chunks: usize,
-    verify: bool,
-    conflict_strategy: ConflictStrategy,
+    verify: Option<ChecksumAlgorithm>,
+    conflicts: &ConflictResolver,
     failure_strategy: FailureStrategy,
     retry_count: u32,
     retry_backoff_ms: u64,
+    clone: bool,
+    atomic: bool,
+    recipient: Option<&PublicKey>,
+    pause: Option<&PauseSignal>,
+    monitor: Option<&TransferMonitor>,
+    jobs: usize,
+    skip_locked: bool,
+    dedup: Option<DedupMode>,
+    hard_links: bool,
+    plan: &CopyPlan,
 ) -> Result<TransferResult, FluxError> {
-    // Detect trailing slash before normalizing the path
-    let source_str = source.to_string_lossy();
-    let has_trailing_slash = source_str.ends_with('/') || source_str.ends_with('\\');
-
-    // Normalize: remove trailing separator for walkdir (it needs a clean path)
-    let source_clean = if has_trailing_slash {
-        let trimmed = source_str.trim_end_matches(|c| c == '/' || c == '\\');
-        PathBuf::from(trimmed)
-    } else {
-        source.to_path_buf()
-    };
-
-    // Determine the base destination path
-    let dest_base = if has_trailing_slash {
-        // Trailing slash: copy contents directly into dest
-        dest.to_path_buf()
-    } else {
-        // No trailing slash: copy directory itself into dest
-        // e.g., source="mydir", dest="/tmp/out" -> "/tmp/out/mydir/"
-        if let Some(dir_name) = source_clean.file_name() {
-            dest.join(dir_name)
-        } else {
-            dest.to_path_buf()
-        }
-    };
+    // Split off the trailing slash (walkdir needs a clean path); it decides
+    // whether the directory itself or only its contents land in dest,
+    // e.g. source="mydir", dest="/tmp/out" -> "/tmp/out/mydir/"
+    let source = FluxPath::local(source);
+    let source_clean = source.path.clone();
+    let dest_base = source.target_in(dest);
 
     // Validate: dest must not be inside source to avoid infinite recursion
     if let (Ok(canon_src), Ok(canon_dst)) = (
@@ -666,26 +1150,12 @@ fn copy_directory(
         }
     }
 
-    // First pass: count files and total bytes for progress bar
-    let mut file_count = 0u64;
-    let mut total_bytes = 0u64;
-    for entry in WalkDir::new(&source_clean)
-        .follow_links(false)
-        .into_iter()
-        .filter_entry(|e| !filter.is_excluded_dir(e))
-        .filter_map(|e| e.ok())
-        .filter(|e| e.file_type().is_file())
-        .filter(|e| filter.should_transfer(e.path()))
-    {
-        file_count += 1;
-        total_bytes += entry.metadata().map(|m| m.len()).unwrap_or(0);
-    }
-
-    let progress = create_transfer_progress(total_bytes, quiet);
-    let dir_start = std::time::Instant::now();
+    // Walk once: create the directory structure and collect the files
     let mut result = TransferResult::new();
-
-    // Second pass: actual copy
+    let mut files: Vec<DirectoryFile> = Vec::new();
+    // Files linked to the copy of an earlier source once it is written
+    let mut link_later: Vec<LaterLink> = Vec::new();
+    let mut links = LinkTracker::default();
     for entry in WalkDir::new(&source_clean)
         .follow_links(false)
         .into_iter()
@@ -721,101 +1191,253 @@ fn copy_directory(
                     FluxError::Io { source: e },
                 );
             }
-        } else if entry.file_type().is_file() {
-            if !filter.should_transfer(entry.path()) {
-                continue;
+        } else if entry.file_type().is_file() && filter.should_transfer(entry.path()) {
+            let meta = entry.metadata().ok();
+            // --hard-links: further names of a file are linked, not copied
+            let first = meta
+                .as_ref()
+                .filter(|_| hard_links)
+                .and_then(|meta| links.first_name(meta, entry.path()));
+            let file = DirectoryFile {
+                size: meta.map_or(0, |m| m.len()),
+                dest: match recipient {
+                    Some(_) => encrypted_path(&dest_path),
+                    None => dest_path,
+                },
+                source: entry.into_path(),
+            };
+            match first {
+                Some(first) => link_later.push(LaterLink {
+                    first,
+                    file,
+                    duplicate: false,
+                }),
+                None => files.push(file),
             }
+        }
+    }
 
-            // Determine file size early (needed for progress tracking)
-            let file_size = entry.metadata().map(|m| m.len()).unwrap_or(0);
+    // --dedup: leave out files whose content is already in the destination.
+    // Copies of another source are linked once that source is copied.
+    if let Some(mode) = dedup {
+        let duplicates;
+        (files, duplicates) = deduplicate(files, dest, mode, conflicts, &mut result)?;
+        link_later.extend(duplicates);
+    }
+    // Where the sources others are linked to were copied to
+    let link_sources: HashSet<&Path> = link_later.iter().map(|l| l.first.as_path()).collect();
+    let written: Mutex<HashMap<PathBuf, PathBuf>> = Mutex::new(HashMap::new());
 
-            // Show current filename in progress bar
-            progress.set_message(
-                entry.path()
-                    .file_name()
-                    .map(|n| n.to_string_lossy().to_string())
-                    .unwrap_or_default(),
-            );
+    let file_count = files.len() as u64;
+    let total_bytes: u64 = files.iter().map(|f| f.size).sum();
+    let progress = BatchProgress::new(total_bytes, file_count, quiet);
+    let dir_start = std::time::Instant::now();
+    if let Some(monitor) = monitor {
+        monitor.start(total_bytes, 0);
+    }
 
-            // --- Conflict resolution ---
-            let actual_dest = match resolve_conflict(&dest_path, conflict_strategy)? {
-                Some(path) => path,
-                None => {
-                    // Skip this file
-                    progress.inc(file_size);
-                    continue;
+    // Copy one file: conflict resolution, copy with failure handling,
+    // verification and atomic commit. Only conflict errors are fatal.
+    let copy_one = |file: &DirectoryFile, bar: &ProgressBar| -> Result<FileOutcome, FluxError> {
+        // --- Conflict resolution ---
+        let actual_dest = match conflicts.resolve(&file.source, &file.dest)? {
+            Some(path) => path,
+            None => return Ok(FileOutcome::Skipped),
+        };
+        if let Some(monitor) = monitor {
+            let name = file.source.strip_prefix(&source_clean).unwrap_or(&file.source);
+            monitor.set_current_file(&name.to_string_lossy());
+        }
+
+        // Ensure parent directory exists
+        if let Some(parent) = actual_dest.parent() {
+            if !parent.exists() {
+                if let Err(e) = std::fs::create_dir_all(parent) {
+                    return Ok(FileOutcome::Failed(FluxError::Io { source: e }));
                 }
-            };
+            }
+        }
+        let file_chunk_count = if chunks > 0 {
+            chunks
+        } else {
+            auto_chunk_count(file.size)
+        };
 
-            // Ensure parent directory exists
-            if let Some(parent) = actual_dest.parent() {
-                if !parent.exists() {
-                    if let Err(e) = std::fs::create_dir_all(parent) {
-                        result.add_error(
-                            entry.path().to_path_buf(),
-                            FluxError::Io { source: e },
-                        );
-                        progress.inc(file_size);
-                        continue;
-                    }
+        // --atomic: copy to a temp file, renamed into place once verified
+        let atomic_file = atomic.then(|| AtomicFile::new(&actual_dest));
+        let write_dest = atomic_file
+            .as_ref()
+            .map_or_else(|| actual_dest.clone(), |f| f.path().to_path_buf());
+        let partial = (!atomic).then(|| PartialFile::new(&actual_dest));
+
+        // --- Copy with failure handling, again if the source changed ---
+        let copy_file = |size: u64| {
+            copy_with_failure_handling(
+                (&file.source, &write_dest),
+                (size, file_chunk_count),
+                (failure_strategy, retry_count, retry_backoff_ms),
+                clone,
+                recipient,
+                bar,
+                monitor,
+            )
+        };
+        let undo = |bytes: u64| {
+            if let Some(monitor) = monitor {
+                monitor.sub_bytes(bytes);
+            }
+        };
+        let bytes = match plan.changes.copy(&file.source, file.size, copy_file, undo) {
+            Ok(bytes) => bytes,
+            Err(e) => return Ok(FileOutcome::Failed(e)),
+        };
+
+        // Post-transfer verification for this file if --verify
+        if let Some(algorithm) = verify.filter(|_| file.size > 0) {
+            match hash_pair_with(&file.source, &write_dest, algorithm) {
+                (Ok(src_hash), Ok(dst_hash)) if src_hash != dst_hash => {
+                    return Ok(FileOutcome::Failed(FluxError::ChecksumMismatch {
+                        path: actual_dest,
+                        expected: src_hash,
+                        actual: dst_hash,
+                    }));
                 }
+                (Err(e), _) | (_, Err(e)) => return Ok(FileOutcome::Failed(e)),
+                _ => {}
             }
-            let file_chunk_count = if chunks > 0 {
-                // Use explicit chunk setting, but only if file is non-empty
-                // and chunk count > 1 and file is large enough
-                let effective = if chunks > 1 { chunks } else { 1 };
-                effective
-            } else {
-                auto_chunk_count(file_size)
-            };
+        }
 
-            // --- Copy with failure handling ---
-            let copy_result = copy_with_failure_handling(
-                entry.path(),
-                &actual_dest,
-                file_size,
-                file_chunk_count,
-                failure_strategy,
-                retry_count,
-                retry_backoff_ms,
-            );
+        // Only a complete (and verified) file takes the destination name
+        match atomic_file.map_or(Ok(()), AtomicFile::commit) {
+            Ok(()) => {
+                if let Some(partial) = partial {
+                    partial.done();
+                }
+                plan.restore_permissions(&file.source, &actual_dest);
+                plan.attributes.apply(&file.source, &actual_dest);
+                Ok(FileOutcome::Copied(bytes, actual_dest))
+            }
+            Err(e) => Ok(FileOutcome::Failed(e)),
+        }
+    };
 
-            match copy_result {
-                Ok(bytes) => {
-                    // Post-transfer verification for this file if --verify
-                    if verify && file_size > 0 {
-                        match (hash_file(entry.path()), hash_file(&actual_dest)) {
-                            (Ok(src_hash), Ok(dst_hash)) if src_hash != dst_hash => {
-                                result.add_error(
-                                    entry.path().to_path_buf(),
-                                    FluxError::ChecksumMismatch {
-                                        path: actual_dest.clone(),
-                                        expected: src_hash,
-                                        actual: dst_hash,
-                                    },
-                                );
-                            }
-                            (Err(e), _) | (_, Err(e)) => {
-                                result.add_error(entry.path().to_path_buf(), e);
-                            }
-                            _ => {
-                                // Hashes match, file verified
-                                result.add_success(bytes);
-                            }
-                        }
-                    } else {
-                        result.add_success(bytes);
+    // Workers share the result; each file is recorded as soon as it is done
+    let shared = Mutex::new(result);
+    let run = |file: &DirectoryFile| -> Result<(), FluxError> {
+        // File boundary: stop here if a pause was requested
+        if pause.is_some_and(|p| p.is_requested()) {
+            return Err(FluxError::Paused);
+        }
+        cancel::check()?;
+        let name = file
+            .source
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let file_progress = progress.start_file(&name, file.size);
+        let outcome = copy_one(file, file_progress.bar());
+        file_progress.done();
+        let outcome = outcome?;
+        let mut result = shared.lock().unwrap_or_else(|e| e.into_inner());
+        match outcome {
+            FileOutcome::Copied(bytes, dest) => {
+                result.add_success(bytes);
+                if link_sources.contains(file.source.as_path()) {
+                    let mut written = written.lock().unwrap_or_else(|e| e.into_inner());
+                    written.insert(file.source.clone(), dest);
+                }
+            }
+            FileOutcome::Skipped => {}
+            FileOutcome::Failed(FluxError::Cancelled) => return Err(FluxError::Cancelled),
+            FileOutcome::Failed(e) if skip_locked && snapshot::is_locked_file(&e) => {
+                tracing::warn!("{} is locked by another program", file.source.display());
+                result.locked.push(file.source.clone());
+            }
+            FileOutcome::Failed(e) => {
+                let e = if snapshot::is_locked_file(&e) {
+                    FluxError::FileLocked {
+                        path: file.source.clone(),
                     }
+                } else {
+                    e
+                };
+                // --on-error abort: the first failure ends the whole copy
+                if failure_strategy == FailureStrategy::Abort {
+                    return Err(FluxError::Aborted {
+                        path: file.source.clone(),
+                        source: Box::new(e),
+                    });
                 }
-                Err(e) => {
-                    result.add_error(entry.path().to_path_buf(), e);
+                result.add_error(file.source.clone(), e);
+            }
+        }
+        Ok(())
+    };
+    let run_result = if jobs > 1 {
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(jobs)
+            .build()
+            .map_err(|e| FluxError::TransferError(format!("Cannot start copy workers: {}", e)))?;
+        pool.install(|| files.par_iter().try_for_each(run))
+    } else {
+        files.iter().try_for_each(run)
+    };
+    // Link to the copy of the first source; if that was not copied (failed,
+    // or skipped by --on-conflict or --dedup), copy this file as well
+    let link_one = |later: &LaterLink| -> Result<(), FluxError> {
+        let file = &later.file;
+        let copy = written
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(&later.first)
+            .cloned();
+        let Some(copy) = copy else {
+            return run(file);
+        };
+        let Some(target) = conflicts.resolve(&file.source, &file.dest)? else {
+            return Ok(());
+        };
+        match hardlink::link(&copy, &target) {
+            Ok(()) => {
+                let mut result = shared.lock().unwrap_or_else(|e| e.into_inner());
+                if later.duplicate {
+                    result.add_deduplicated(file.size);
+                } else {
+                    result.hard_links += 1;
+                }
+                Ok(())
+            }
+            Err(e) => {
+                tracing::warn!("Cannot link {}: {}", target.display(), e);
+                run(&DirectoryFile {
+                    source: file.source.clone(),
+                    dest: target,
+                    size: file.size,
+                })
+            }
+        }
+    };
+    let run_result = run_result.and_then(|()| link_later.iter().try_for_each(link_one));
+    if let Err(e) = run_result {
+        progress.abandon();
+        if !quiet {
+            let copied = shared.lock().unwrap_or_else(|e| e.into_inner()).files_copied;
+            match e {
+                FluxError::Aborted { .. } => {
+                    eprintln!("Stopped after {} file(s) copied (--on-error abort)", copied)
                 }
+                FluxError::Cancelled => eprintln!("Cancelled after {} file(s) copied", copied),
+                _ => {}
             }
-            progress.inc(file_size);
         }
+        return Err(e);
     }
+    let mut result = shared.into_inner().unwrap_or_else(|e| e.into_inner());
+    // Workers finish in any order; report failures in path order
+    result.errors.sort_by(|a, b| a.0.cmp(&b.0));
+    result.locked.sort();
 
-    progress.finish_and_clear();
+    progress.finish();
 
     // Print completion summary with throughput
     {
@@ -824,36 +1446,218 @@ fn copy_directory(
         stats.bytes_done = result.bytes_copied;
         stats.files_done = result.files_copied;
         stats.files_failed = result.errors.len() as u64;
-        stats.print_summary(quiet);
+        stats.files_skipped = result.locked.len() as u64;
+        stats.retries = crate::backend::retry::take_counts();
+        suspend(|| stats.print_summary(quiet));
+        if let Some(compress) = stats_block {
+            stats.peak_bps = status::take_peak_rate();
+            stats.compress = compress;
+            suspend(|| stats.print_stats());
+        }
     }
 
     Ok(result)
 }
 
-/// Copy a single file with failure handling (retry/skip/pause).
+/// `--dedup` for a single-file copy to `dest`: skip it, or hard-link `dest`
+/// to a file under `root` with the same content.
+///
+/// Returns false if no such file exists (or it cannot be linked to), so the
+/// file has to be copied.
+fn deduplicate_file(
+    source: &Path,
+    size: u64,
+    root: &Path,
+    dest: &Path,
+    mode: DedupMode,
+    conflicts: &ConflictResolver,
+) -> Result<bool, FluxError> {
+    let Some(existing) = dedup::find_existing(root, source, size, ChecksumCache::open()) else {
+        return Ok(false);
+    };
+    if mode == DedupMode::Skip || existing == dest {
+        return Ok(true);
+    }
+    let Some(target) = conflicts.resolve(source, dest)? else {
+        return Ok(true);
+    };
+    match hardlink::link(&existing, &target) {
+        Ok(()) => Ok(true),
+        Err(e) => {
+            tracing::warn!("Cannot link {}: {}", target.display(), e);
+            Ok(false)
+        }
+    }
+}
+
+/// Take the files of a directory copy whose content is already in `dest`
+/// out of `files` (`--dedup`).
+///
+/// Duplicates of destination files are skipped or hard-linked right away.
+/// Duplicates of another source are skipped, or returned to be linked to
+/// the copy of that source. Files that cannot be linked are left in to be
+/// copied.
+fn deduplicate(
+    files: Vec<DirectoryFile>,
+    dest: &Path,
+    mode: DedupMode,
+    conflicts: &ConflictResolver,
+    result: &mut TransferResult,
+) -> Result<(Vec<DirectoryFile>, Vec<LaterLink>), FluxError> {
+    let sizes = files.iter().map(|f| f.size).collect();
+    let mut index = DedupIndex::build(dest, &sizes, ChecksumCache::open());
+    let sources: Vec<(&Path, u64)> = files
+        .iter()
+        .map(|f| (f.source.as_path(), f.size))
+        .collect();
+    let duplicates = find_duplicates(&mut index, &sources);
+    index.save();
+    let paths: Vec<PathBuf> = files.iter().map(|f| f.source.clone()).collect();
+
+    let mut copy = Vec::new();
+    let mut link_later = Vec::new();
+    for (file, duplicate) in files.into_iter().zip(duplicates) {
+        match (duplicate, mode) {
+            (None, _) => copy.push(file),
+            (Some(Duplicate::Existing(existing)), _) if existing == file.dest => {
+                // Copied by an earlier run
+                result.add_deduplicated(file.size);
+            }
+            (Some(_), DedupMode::Skip) => {
+                tracing::debug!("Skipped {} (duplicate)", file.source.display());
+                result.add_deduplicated(file.size);
+            }
+            (Some(Duplicate::Existing(existing)), DedupMode::Link) => {
+                let Some(target) = conflicts.resolve(&file.source, &file.dest)? else {
+                    continue;
+                };
+                match hardlink::link(&existing, &target) {
+                    Ok(()) => result.add_deduplicated(file.size),
+                    Err(e) => {
+                        tracing::warn!("Cannot link {}: {}", target.display(), e);
+                        copy.push(DirectoryFile {
+                            dest: target,
+                            ..file
+                        });
+                    }
+                }
+            }
+            (Some(Duplicate::InBatch(first)), DedupMode::Link) => {
+                link_later.push(LaterLink {
+                    first: paths[first].clone(),
+                    file,
+                    duplicate: true,
+                });
+            }
+        }
+    }
+    Ok((copy, link_later))
+}
+
+/// A file found by `copy_directory`'s walk, waiting to be copied.
+struct DirectoryFile {
+    source: PathBuf,
+    /// Destination before conflict resolution
+    dest: PathBuf,
+    size: u64,
+}
+
+/// A file of a directory copy to hard-link to the copy of another source
+/// (`--hard-links`, `--dedup=link`).
+struct LaterLink {
+    /// Source whose copy to link to
+    first: PathBuf,
+    file: DirectoryFile,
+    /// Same content as `first` (`--dedup`) rather than another name of it
+    duplicate: bool,
+}
+
+/// What happened to one file of a directory copy.
+enum FileOutcome {
+    /// Bytes copied and the destination written
+    Copied(u64, PathBuf),
+    /// Skipped by `--on-conflict`
+    Skipped,
+    Failed(FluxError),
+}
+
+/// Number of files a directory copy works on at once.
+///
+/// `requested` is `--jobs` (0 = the I/O profile's default: one per CPU up
+/// to 8 on SSDs, one on spinning disks). Interactive strategies
+/// (`--on-conflict ask`, `--on-error pause`) prompt on the terminal, so they
+/// always copy one file at a time.
+fn directory_jobs(
+    requested: usize,
+    tuning: &IoTuning,
+    conflict_strategy: ConflictStrategy,
+    failure_strategy: FailureStrategy,
+) -> usize {
+    if matches!(conflict_strategy, ConflictStrategy::Ask)
+        || matches!(failure_strategy, FailureStrategy::Pause)
+    {
+        return 1;
+    }
+    match requested {
+        0 => tuning.jobs,
+        n => n,
+    }
+}
+
+/// Copy a single file with failure handling (retry/skip/pause/abort).
 ///
 /// Applies the configured failure strategy when a copy operation fails:
 /// - Retry: retries up to `retry_count` times with exponential backoff
 /// - Skip: returns the error immediately (caller adds to TransferResult)
 /// - Pause: prompts user to continue or abort, then returns the error
+/// - Abort: returns the error immediately (caller stops the whole copy)
+///
+/// With `clone`, a same-filesystem clone is tried before copying bytes. With
+/// a `recipient`, the file is encrypted to it instead of copied as-is.
+/// `progress` tracks this file's bytes and restarts with each retry.
 fn copy_with_failure_handling(
-    source: &Path,
-    dest: &Path,
-    file_size: u64,
-    chunk_count: usize,
-    failure_strategy: FailureStrategy,
-    retry_count: u32,
-    retry_backoff_ms: u64,
+    (source, dest): (&Path, &Path),
+    (file_size, chunk_count): (u64, usize),
+    (failure_strategy, retry_count, retry_backoff_ms): (FailureStrategy, u32, u64),
+    clone: bool,
+    recipient: Option<&PublicKey>,
+    progress: &ProgressBar,
+    monitor: Option<&TransferMonitor>,
 ) -> Result<u64, FluxError> {
+    // Chunked copies report to the monitor as they go, the rest once done
+    let counted = |bytes: u64| {
+        if let Some(monitor) = monitor {
+            monitor.add_bytes(bytes);
+        }
+        bytes
+    };
     let do_copy = |src: &Path, dst: &Path| -> Result<u64, FluxError> {
+        progress.set_position(0);
+        if let Some(recipient) = recipient {
+            return encrypt_file(src, dst, recipient, progress).map(counted);
+        }
+        if clone && file_size > 0 && try_clone_file(src, dst, progress)? {
+            return Ok(counted(file_size));
+        }
         if chunk_count > 1 && file_size > 0 {
-            let file_progress = ProgressBar::hidden();
             let mut file_chunks = chunk_file(file_size, chunk_count);
-            parallel_copy_chunked(src, dst, &mut file_chunks, &file_progress)?;
+            let copied = parallel_copy_chunked_pausable(
+                src,
+                dst,
+                &mut file_chunks,
+                ChecksumAlgorithm::Blake3,
+                progress,
+                None,
+                monitor,
+            );
+            if let (Err(_), Some(monitor)) = (&copied, monitor) {
+                // The attempt's bytes are copied again on retry
+                monitor.sub_bytes(progress.position());
+            }
+            copied?;
             Ok(file_size)
         } else {
-            let file_progress = ProgressBar::hidden();
-            copy_file_with_progress(src, dst, &file_progress)
+            copy_file_with_progress(src, dst, progress).map(counted)
         }
     };
 
@@ -863,8 +1667,13 @@ fn copy_with_failure_handling(
             for attempt in 0..=retry_count {
                 match do_copy(source, dest) {
                     Ok(bytes) => return Ok(bytes),
+                    Err(FluxError::Cancelled) => return Err(FluxError::Cancelled),
                     Err(e) => {
                         if attempt < retry_count {
+                            crate::backend::retry::record(source);
+                            if let Some(monitor) = monitor {
+                                monitor.record_retry();
+                            }
                             let delay_ms = retry_backoff_ms * (1u64 << attempt);
                             tracing::warn!(
                                 "Copy failed (attempt {}/{}): {}. Retrying in {}ms...",
@@ -881,13 +1690,14 @@ fn copy_with_failure_handling(
             }
             Err(last_err.expect("last_err is Some after at least one retry attempt"))
         }
-        FailureStrategy::Skip => {
+        FailureStrategy::Skip | FailureStrategy::Abort => {
             // Just try once; on failure, return the error
             do_copy(source, dest)
         }
         FailureStrategy::Pause => {
             match do_copy(source, dest) {
                 Ok(bytes) => Ok(bytes),
+                Err(FluxError::Cancelled) => Err(FluxError::Cancelled),
                 Err(e) => {
                     use std::io::IsTerminal;
                     if std::io::stdin().is_terminal() {
@@ -907,44 +1717,11 @@ fn copy_with_failure_handling(
     }
 }
 
-/// Record a transfer in history (best-effort; errors are silently ignored).
-///
-/// This ensures that transfer failures don't compound with history write failures.
-/// Dry-run operations should NOT call this function.
-fn record_history(
-    source: &str,
-    dest: &str,
-    bytes: u64,
-    files: u64,
-    duration_secs: f64,
-    status: &str,
-    error: Option<String>,
-) {
-    use crate::queue::history::{HistoryEntry, HistoryStore};
-
-    if let Ok(data_dir) = config::paths::flux_data_dir() {
-        let flux_config = config::types::load_config().unwrap_or_default();
-        if let Ok(mut history) = HistoryStore::load(&data_dir, flux_config.history_limit) {
-            let entry = HistoryEntry {
-                source: strip_url_credentials(source),
-                dest: strip_url_credentials(dest),
-                bytes,
-                files,
-                duration_secs,
-                timestamp: chrono::Utc::now(),
-                status: status.to_string(),
-                error,
-            };
-            let _ = history.append(entry); // Ignore history write errors
-        }
-    }
-}
-
 /// Strip credentials (username:password@) from URLs before persisting to history.
 ///
 /// Prevents passwords embedded in WebDAV/SFTP URLs from being stored in plaintext
 /// on disk. Non-URL strings are returned as-is.
-fn strip_url_credentials(s: &str) -> String {
+pub(crate) fn strip_url_credentials(s: &str) -> String {
     if let Ok(mut parsed) = url::Url::parse(s) {
         if !parsed.username().is_empty() || parsed.password().is_some() {
             parsed.set_username("").ok();
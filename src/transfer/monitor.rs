@@ -0,0 +1,213 @@
+//! Live statistics of an in-flight transfer.
+//!
+//! A `TransferMonitor` is shared between the copy engine and the control
+//! socket of a running queue entry (see `control`). The engine reports bytes,
+//! chunk completions and retries; the control socket answers `stats` with a
+//! `MonitorSnapshot` (used by the TUI Transfer tab) and `limit` changes the
+//! bandwidth limit of the running copy.
+
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use serde::{Deserialize, Serialize};
+
+use crate::transfer::chunk::ChunkPlan;
+use crate::transfer::throttle::SharedLimiter;
+
+/// Shared, cheaply cloneable handle to a transfer's live statistics.
+#[derive(Debug, Clone)]
+pub struct TransferMonitor(Arc<MonitorState>);
+
+#[derive(Debug)]
+struct MonitorState {
+    source: String,
+    dest: String,
+    total_bytes: AtomicU64,
+    bytes_done: AtomicU64,
+    retries: AtomicU32,
+    /// Plan generation and completion of each chunk of the file most
+    /// recently started
+    chunks: Mutex<(u64, Vec<bool>)>,
+    /// File started last, for directory copies
+    current_file: Mutex<Option<String>>,
+    limiter: SharedLimiter,
+}
+
+/// Point-in-time copy of a monitor, as sent over the control socket.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MonitorSnapshot {
+    pub source: String,
+    pub dest: String,
+    pub total_bytes: u64,
+    pub bytes_done: u64,
+    /// Chunk map of the file being copied (empty for unchunked copies)
+    pub chunks: Vec<bool>,
+    pub retries: u32,
+    /// Bandwidth limit in bytes/sec, `None` if unlimited
+    pub limit: Option<u64>,
+    /// File started last (directory copies only)
+    #[serde(default)]
+    pub current_file: Option<String>,
+}
+
+impl TransferMonitor {
+    pub fn new(source: &str, dest: &str) -> Self {
+        Self(Arc::new(MonitorState {
+            source: source.to_string(),
+            dest: dest.to_string(),
+            total_bytes: AtomicU64::new(0),
+            bytes_done: AtomicU64::new(0),
+            retries: AtomicU32::new(0),
+            chunks: Mutex::new((0, Vec::new())),
+            current_file: Mutex::new(None),
+            limiter: SharedLimiter::new(None),
+        }))
+    }
+
+    /// Set the size of the whole transfer and what a resume already covers.
+    pub fn start(&self, total_bytes: u64, already_done: u64) {
+        self.0.total_bytes.store(total_bytes, Ordering::Relaxed);
+        self.0.bytes_done.store(already_done, Ordering::Relaxed);
+    }
+
+    /// Replace the chunk map with the plan of the file about to be copied.
+    ///
+    /// Returns the plan's generation for `chunk_done`. Directory copies
+    /// with several workers show the file started last; completions from
+    /// older plans are ignored.
+    pub fn set_chunks(&self, chunks: &[ChunkPlan]) -> u64 {
+        let mut map = self.0.chunks.lock().unwrap_or_else(|e| e.into_inner());
+        map.0 += 1;
+        map.1 = chunks.iter().map(|c| c.completed).collect();
+        map.0
+    }
+
+    /// Mark chunk `index` of plan `generation` as done.
+    pub fn chunk_done(&self, generation: u64, index: usize) {
+        let mut map = self.0.chunks.lock().unwrap_or_else(|e| e.into_inner());
+        if map.0 != generation {
+            return;
+        }
+        if let Some(done) = map.1.get_mut(index) {
+            *done = true;
+        }
+    }
+
+    /// Add a chunk to plan `generation`, split off a running one
+    /// (`transfer::schedule`). It goes at the end of the map.
+    pub fn add_chunk(&self, generation: u64) {
+        let mut map = self.0.chunks.lock().unwrap_or_else(|e| e.into_inner());
+        if map.0 == generation {
+            map.1.push(false);
+        }
+    }
+
+    /// Set the file being copied, shown by `flux status`.
+    pub fn set_current_file(&self, name: &str) {
+        *self.0.current_file.lock().unwrap_or_else(|e| e.into_inner()) = Some(name.to_string());
+    }
+
+    /// Count `bytes` as transferred, waiting if over the bandwidth limit.
+    pub fn add_bytes(&self, bytes: u64) {
+        self.0.bytes_done.fetch_add(bytes, Ordering::Relaxed);
+        self.0.limiter.acquire(bytes);
+    }
+
+    /// Take back bytes of an attempt that is being redone.
+    pub fn sub_bytes(&self, bytes: u64) {
+        let _ = self
+            .0
+            .bytes_done
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |done| {
+                Some(done.saturating_sub(bytes))
+            });
+    }
+
+    pub fn record_retry(&self) {
+        self.0.retries.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Change the bandwidth limit of the running copy (`None` = unlimited).
+    pub fn set_limit(&self, bytes_per_sec: Option<u64>) {
+        self.0.limiter.set_rate(bytes_per_sec);
+    }
+
+    pub fn snapshot(&self) -> MonitorSnapshot {
+        MonitorSnapshot {
+            source: self.0.source.clone(),
+            dest: self.0.dest.clone(),
+            total_bytes: self.0.total_bytes.load(Ordering::Relaxed),
+            bytes_done: self.0.bytes_done.load(Ordering::Relaxed),
+            chunks: self
+                .0
+                .chunks
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .1
+                .clone(),
+            retries: self.0.retries.load(Ordering::Relaxed),
+            limit: self.0.limiter.rate(),
+            current_file: self
+                .0
+                .current_file
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transfer::chunk::chunk_file;
+
+    #[test]
+    fn snapshot_reflects_progress() {
+        let monitor = TransferMonitor::new("a.bin", "b.bin");
+        let mut chunks = chunk_file(400, 4);
+        chunks[0].completed = true;
+        monitor.start(400, 100);
+        let generation = monitor.set_chunks(&chunks);
+        monitor.add_bytes(100);
+        monitor.chunk_done(generation, 2);
+        monitor.record_retry();
+        monitor.set_current_file("photos/a.jpg");
+
+        let snap = monitor.clone().snapshot();
+        assert_eq!(snap.source, "a.bin");
+        assert_eq!(snap.bytes_done, 200);
+        assert_eq!(snap.total_bytes, 400);
+        assert_eq!(snap.chunks, vec![true, false, true, false]);
+        assert_eq!(snap.retries, 1);
+        assert_eq!(snap.limit, None);
+        assert_eq!(snap.current_file.as_deref(), Some("photos/a.jpg"));
+    }
+
+    #[test]
+    fn stale_chunk_completions_are_ignored() {
+        let monitor = TransferMonitor::new("dir", "out");
+        let first = monitor.set_chunks(&chunk_file(200, 2));
+        let second = monitor.set_chunks(&chunk_file(300, 3));
+        monitor.chunk_done(first, 0);
+        monitor.chunk_done(second, 1);
+        assert_eq!(monitor.snapshot().chunks, vec![false, true, false]);
+
+        // Chunks split off while copying join the current map only
+        monitor.add_chunk(first);
+        monitor.add_chunk(second);
+        monitor.chunk_done(second, 3);
+        assert_eq!(monitor.snapshot().chunks, vec![false, true, false, true]);
+    }
+
+    #[test]
+    fn limit_and_sub_bytes() {
+        let monitor = TransferMonitor::new("a", "b");
+        monitor.set_limit(Some(1_000_000));
+        monitor.add_bytes(10);
+        monitor.sub_bytes(50);
+        let snap = monitor.snapshot();
+        assert_eq!(snap.limit, Some(1_000_000));
+        assert_eq!(snap.bytes_done, 0);
+    }
+}
@@ -0,0 +1,520 @@
+//! Moving files and directories (`flux mv`).
+//!
+//! A move within one filesystem, or within one connection of a backend that
+//! can rename, is a rename. Anything else (another disk, another backend, a
+//! backend without rename, or a rename the server refuses) is a `flux cp
+//! --verify` through the usual engine, after which the source is deleted.
+//! A copy that fails or does not verify leaves the source untouched, and a
+//! source file is only deleted once it is found at the destination with its
+//! size.
+
+use std::path::{Path, PathBuf};
+
+use crate::backend::{create_backend, FileStat, FluxBackend};
+use crate::cli::args::{AttrArgs, CpArgs, HiddenArgs, HookArgs, MvArgs};
+use crate::config;
+use crate::config::types::ConflictStrategy;
+use crate::error::FluxError;
+use crate::protocol::{FluxPath, Protocol};
+
+use super::cancel;
+use super::changed::ChangedSource;
+use super::history::{record_history, HistoryRecord};
+use super::monitor::TransferMonitor;
+use super::status::StatusPublisher;
+
+/// Execute `flux mv`.
+pub fn execute_move(args: MvArgs, quiet: bool) -> Result<(), FluxError> {
+    let alias_store = config::aliases::AliasStore::current();
+    let source_str = config::aliases::resolve_alias(&args.source, &alias_store);
+    let dest_str = config::aliases::resolve_destination(&args.dest, &alias_store);
+    let src = FluxPath::parse(&source_str);
+    let dst = FluxPath::parse(&dest_str);
+
+    let src_backend = create_backend(&src.protocol)?;
+    let stat = src_backend.stat(&src.path)?;
+    let Some(name) = src.name() else {
+        return Err(FluxError::MoveError(format!(
+            "{} is a root and cannot be moved",
+            source_str
+        )));
+    };
+    let dst_backend = create_backend(&dst.protocol)?;
+
+    // Like mv: an existing directory receives the source under its name
+    let into_dir = dst_backend.stat(&dst.path).is_ok_and(|s| s.is_dir);
+    let target = if into_dir {
+        dst.path.join(&name)
+    } else {
+        dst.path.clone()
+    };
+    let same_location = same_location(&src.protocol, &dst.protocol);
+    check_target(&src, stat.is_dir, dst_backend.as_ref(), &target, same_location, args.force)?;
+
+    // Within one location a rename is instant. It is tried even when the
+    // target exists (--force): a rename that cannot replace it falls back
+    // to copying
+    if same_location && src_backend.features().supports_rename {
+        if args.dry_run {
+            eprintln!(
+                "[dry-run] move {} -> {}",
+                src.path.display(),
+                target.display()
+            );
+            return Ok(());
+        }
+        match src_backend.rename(&src.path, &target) {
+            Ok(()) => {
+                let mut record = HistoryRecord::new("mv", &source_str, &dest_str);
+                if !stat.is_dir {
+                    record.files = 1;
+                    record.bytes = stat.size;
+                }
+                record_history(&record, None);
+                if !quiet {
+                    eprintln!("Moved {} -> {}", src.path.display(), target.display());
+                }
+                return Ok(());
+            }
+            Err(e) => tracing::debug!("Rename failed ({}); copying instead", e),
+        }
+    }
+    if !src_backend.features().supports_remove {
+        return Err(FluxError::MoveError(format!(
+            "{} cannot delete files, so nothing can be moved from it (use flux cp)",
+            src.protocol.name()
+        )));
+    }
+
+    // Copy a directory's contents straight into the target, not below it
+    let source_arg = if stat.is_dir {
+        format!("{}/", trim_separators(&source_str))
+    } else {
+        source_str.clone()
+    };
+    let dest_arg = if into_dir {
+        join_arg(&dest_str, &name, dst.is_local())
+    } else {
+        trim_separators(&dest_str).to_string()
+    };
+    let monitor = TransferMonitor::new(&args.source, &args.dest);
+    let _status = if args.dry_run {
+        None
+    } else {
+        StatusPublisher::for_monitor("mv", &monitor)
+    };
+    let copy = copy_args(source_arg, dest_arg, stat.is_dir, &args);
+    if let Err(e) = super::execute_copy_as("mv", copy, quiet, None, Some(&monitor)) {
+        if !quiet && !args.dry_run {
+            eprintln!("Nothing was deleted; {} is unchanged", source_str);
+        }
+        return Err(e);
+    }
+    if args.dry_run {
+        eprintln!("[dry-run] delete {}", src.path.display());
+        return Ok(());
+    }
+
+    remove_moved(src_backend.as_ref(), &src.path, stat.is_dir, dst_backend.as_ref(), &target)
+}
+
+/// Refuse a move that would replace an existing `target` (unless `force`),
+/// replace a file with a directory or the other way round, or put a
+/// directory inside itself.
+fn check_target(
+    src: &FluxPath,
+    is_dir: bool,
+    dst_backend: &dyn FluxBackend,
+    target: &Path,
+    same_location: bool,
+    force: bool,
+) -> Result<(), FluxError> {
+    if same_location {
+        let (source, target) = if src.is_local() {
+            (
+                super::canonicalize_best_effort(&src.path).unwrap_or_else(|_| src.path.clone()),
+                super::canonicalize_best_effort(target).unwrap_or_else(|_| target.to_path_buf()),
+            )
+        } else {
+            (src.path.clone(), target.to_path_buf())
+        };
+        if source == target {
+            return Err(FluxError::MoveError(format!(
+                "{} and {} are the same",
+                src.path.display(),
+                target.display()
+            )));
+        }
+        if is_dir && target.starts_with(&source) {
+            return Err(FluxError::DestinationIsSubdirectory {
+                src: source,
+                dst: target,
+            });
+        }
+    }
+
+    if let Ok(existing) = dst_backend.stat(target) {
+        if !force {
+            return Err(FluxError::DestinationExists {
+                path: target.to_path_buf(),
+            });
+        }
+        if existing.is_dir != is_dir {
+            let (what, with) = if is_dir {
+                ("file", "a directory")
+            } else {
+                ("directory", "a file")
+            };
+            return Err(FluxError::MoveError(format!(
+                "Cannot replace the {} {} with {}",
+                what,
+                target.display(),
+                with
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Whether two paths are on the same filesystem tree or backend connection,
+/// so that a rename between them can work.
+fn same_location(a: &Protocol, b: &Protocol) -> bool {
+    match (a, b) {
+        (Protocol::Local { .. }, Protocol::Local { .. }) => true,
+        (
+            Protocol::Sftp { user, host, port, .. },
+            Protocol::Sftp {
+                user: user_b,
+                host: host_b,
+                port: port_b,
+                ..
+            },
+        ) => user == user_b && host.eq_ignore_ascii_case(host_b) && port == port_b,
+        (
+            Protocol::Smb { server, share, .. },
+            Protocol::Smb {
+                server: server_b,
+                share: share_b,
+                ..
+            },
+        ) => server.eq_ignore_ascii_case(server_b) && share.eq_ignore_ascii_case(share_b),
+        // Backends for these are rooted at the collection or remote
+        (Protocol::WebDav { url, .. }, Protocol::WebDav { url: url_b, .. }) => url == url_b,
+        (Protocol::Rclone { remote, .. }, Protocol::Rclone { remote: remote_b, .. }) => {
+            remote == remote_b
+        }
+        _ => false,
+    }
+}
+
+/// `input` without trailing separators, keeping a root whole.
+fn trim_separators(input: &str) -> &str {
+    match input.trim_end_matches(['/', '\\']) {
+        "" => input,
+        trimmed => trimmed,
+    }
+}
+
+/// `name` inside the directory `dir` as typed: a path join for local paths,
+/// else the URI's own separator.
+fn join_arg(dir: &str, name: &str, local: bool) -> String {
+    if local {
+        return Path::new(dir).join(name).display().to_string();
+    }
+    let dir = trim_separators(dir);
+    let separator = if dir.contains('/') { '/' } else { '\\' };
+    format!("{}{}{}", dir, separator, name)
+}
+
+/// `CpArgs` for the copy of a move: verified, atomic, overwriting (the
+/// target was checked before), with hidden and system files, since all of
+/// the source is deleted afterwards.
+fn copy_args(source: String, dest: String, recursive: bool, args: &MvArgs) -> CpArgs {
+    CpArgs {
+        source,
+        dest,
+        recursive,
+        verify: true,
+        checksum: args.checksum,
+        compress: false,
+        chunks: 0,
+        exclude: vec![],
+        include: vec![],
+        hidden: HiddenArgs {
+            include_hidden: true,
+            exclude_hidden: false,
+            include_system: true,
+        },
+        attrs: AttrArgs::default(),
+        limit: None,
+        resume: false,
+        resume_verify: false,
+        on_conflict: Some(ConflictStrategy::Overwrite),
+        on_error: None,
+        dry_run: args.dry_run,
+        no_clone: false,
+        atomic: true,
+        mmap: false,
+        no_preallocate: false,
+        encrypt_to: None,
+        decrypt: false,
+        snapshot_source: false,
+        vss: false,
+        jobs: 0,
+        io_profile: None,
+        dedup: None,
+        hard_links: false,
+        read_only: false,
+        stats: false,
+        failed_out: None,
+        retry_from: None,
+        changed_source: ChangedSource::Fail,
+        hooks: HookArgs::default(),
+    }
+}
+
+/// Delete the copied source `root` (a directory when `is_dir`) from
+/// `src_backend`, each file only once `dst_backend` has a file of the same
+/// size at its place below `target`. Directories are recreated at the
+/// target first (network copies skip empty ones) and removed once empty;
+/// files that are not found at the target are kept and reported.
+///
+/// Moved files keep their modification time where `dst_backend` can set
+/// it; a time that cannot be set is only logged.
+pub fn remove_moved(
+    src_backend: &dyn FluxBackend,
+    root: &Path,
+    is_dir: bool,
+    dst_backend: &dyn FluxBackend,
+    target: &Path,
+) -> Result<(), FluxError> {
+    let set_mtime = dst_backend.features().supports_set_mtime;
+    // Whether the file is at the target, with its modification time set
+    let copied = |relative: &Path, source: &FileStat| {
+        let at = if relative.as_os_str().is_empty() {
+            target.to_path_buf()
+        } else {
+            target.join(relative)
+        };
+        let found = dst_backend
+            .stat(&at)
+            .is_ok_and(|stat| !stat.is_dir && stat.size == source.size);
+        if let (true, true, Some(mtime)) = (found, set_mtime, source.modified) {
+            if let Err(e) = dst_backend.set_mtime(&at, mtime) {
+                tracing::warn!("Could not keep the modification time of {}: {}", at.display(), e);
+            }
+        }
+        found
+    };
+
+    if !is_dir {
+        let stat = src_backend.stat(root)?;
+        if !copied(Path::new(""), &stat) {
+            eprintln!(
+                "{} was not found at {} after the copy and was kept",
+                root.display(),
+                target.display()
+            );
+            return Err(FluxError::MoveIncomplete {
+                root: root.to_path_buf(),
+                kept: vec![root.to_path_buf()],
+            });
+        }
+        return src_backend.remove(root);
+    }
+
+    let SourceTree { files, mut dirs } = walk(src_backend, root)?;
+    for dir in &dirs {
+        dst_backend.create_dir_all(&target.join(dir))?;
+    }
+    let mut kept = Vec::new();
+    for (relative, stat) in &files {
+        cancel::check()?;
+        if copied(relative, stat) {
+            src_backend.remove(&root.join(relative))?;
+        } else {
+            kept.push(relative.clone());
+        }
+    }
+    if !kept.is_empty() {
+        eprintln!(
+            "{} file(s) were not found at {} after the copy and were kept:",
+            kept.len(),
+            target.display()
+        );
+        let kept: Vec<PathBuf> = kept.iter().map(|relative| root.join(relative)).collect();
+        for path in &kept {
+            eprintln!("  {}", path.display());
+        }
+        return Err(FluxError::MoveIncomplete {
+            root: root.to_path_buf(),
+            kept,
+        });
+    }
+
+    // Deepest first, then the directory itself
+    dirs.sort_by_key(|dir| std::cmp::Reverse(dir.components().count()));
+    for dir in &dirs {
+        src_backend.remove(&root.join(dir))?;
+    }
+    src_backend.remove(root)
+}
+
+/// What is below a moved directory, relative to it.
+struct SourceTree {
+    /// Files with their sizes and modification times
+    files: Vec<(PathBuf, FileStat)>,
+    dirs: Vec<PathBuf>,
+}
+
+/// Files and directories below `root` on `backend`.
+fn walk(backend: &dyn FluxBackend, root: &Path) -> Result<SourceTree, FluxError> {
+    let mut files = Vec::new();
+    let mut dirs = Vec::new();
+    let mut pending = vec![PathBuf::new()];
+    while let Some(dir) = pending.pop() {
+        for entry in backend.list_dir(&root.join(&dir))? {
+            // Backends report entry paths differently (full, absolute or
+            // bare name), so rebuild them from the file name
+            let Some(name) = entry.path.file_name() else {
+                continue;
+            };
+            let relative = dir.join(name);
+            if entry.stat.is_dir {
+                pending.push(relative.clone());
+                dirs.push(relative);
+            } else {
+                files.push((relative, entry.stat));
+            }
+        }
+    }
+    Ok(SourceTree { files, dirs })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::local::LocalBackend;
+
+    fn mv_args(source: &Path, dest: &Path) -> MvArgs {
+        MvArgs {
+            source: source.display().to_string(),
+            dest: dest.display().to_string(),
+            force: false,
+            checksum: crate::transfer::checksum::ChecksumAlgorithm::Blake3,
+            dry_run: false,
+        }
+    }
+
+    #[test]
+    fn renames_into_an_existing_directory_or_to_a_new_name() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("a.txt");
+        let dest = dir.path().join("dest");
+        std::fs::write(&file, "a").unwrap();
+        std::fs::create_dir(&dest).unwrap();
+
+        execute_move(mv_args(&file, &dest), true).unwrap();
+        assert!(!file.exists());
+        assert_eq!(std::fs::read_to_string(dest.join("a.txt")).unwrap(), "a");
+
+        let renamed = dir.path().join("moved");
+        execute_move(mv_args(&dest, &renamed), true).unwrap();
+        assert!(!dest.exists());
+        assert!(renamed.join("a.txt").exists());
+    }
+
+    #[test]
+    fn refuses_an_existing_target_and_a_move_into_itself() {
+        let dir = tempfile::tempdir().unwrap();
+        let (a, b) = (dir.path().join("a.txt"), dir.path().join("b.txt"));
+        std::fs::write(&a, "a").unwrap();
+        std::fs::write(&b, "b").unwrap();
+
+        assert!(matches!(
+            execute_move(mv_args(&a, &b), true),
+            Err(FluxError::DestinationExists { .. })
+        ));
+        assert_eq!(std::fs::read_to_string(&b).unwrap(), "b");
+        let mut args = mv_args(&a, &b);
+        args.force = true;
+        execute_move(args, true).unwrap();
+        assert!(!a.exists());
+        assert_eq!(std::fs::read_to_string(&b).unwrap(), "a");
+
+        let sub = dir.path().join("sub");
+        std::fs::create_dir_all(sub.join("inner")).unwrap();
+        assert!(matches!(
+            execute_move(mv_args(&sub, &sub.join("inner")), true),
+            Err(FluxError::DestinationIsSubdirectory { .. })
+        ));
+        assert!(sub.join("inner").is_dir());
+    }
+
+    #[test]
+    fn copied_sources_are_deleted_only_where_the_copy_matches() {
+        let dir = tempfile::tempdir().unwrap();
+        let (src, dst) = (dir.path().join("src"), dir.path().join("dst"));
+        std::fs::create_dir_all(src.join("docs/empty")).unwrap();
+        std::fs::write(src.join("docs/a.txt"), "aaa").unwrap();
+        std::fs::write(src.join("b.txt"), "bb").unwrap();
+        std::fs::create_dir_all(dst.join("docs")).unwrap();
+        std::fs::write(dst.join("docs/a.txt"), "aaa").unwrap();
+        // A short copy of b.txt
+        std::fs::write(dst.join("b.txt"), "b").unwrap();
+
+        let local = LocalBackend::new();
+        match remove_moved(&local, &src, true, &local, &dst) {
+            Err(FluxError::MoveIncomplete { kept, .. }) => assert_eq!(kept, [src.join("b.txt")]),
+            other => panic!("expected MoveIncomplete, got {:?}", other),
+        }
+        assert!(!src.join("docs/a.txt").exists());
+        assert!(src.join("b.txt").exists());
+        assert!(dst.join("docs/empty").is_dir());
+
+        std::fs::write(dst.join("b.txt"), "bb").unwrap();
+        remove_moved(&local, &src, true, &local, &dst).unwrap();
+        assert!(!src.exists());
+    }
+
+    #[test]
+    fn moved_files_keep_their_modification_time() {
+        let dir = tempfile::tempdir().unwrap();
+        let (src, dst) = (dir.path().join("src"), dir.path().join("dst"));
+        std::fs::create_dir_all(src.join("docs")).unwrap();
+        std::fs::create_dir_all(dst.join("docs")).unwrap();
+        std::fs::write(src.join("docs/a.txt"), "aaa").unwrap();
+        std::fs::write(dst.join("docs/a.txt"), "aaa").unwrap();
+        let local = LocalBackend::new();
+        let mtime =
+            std::time::SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1_600_000_000);
+        local.set_mtime(&src.join("docs/a.txt"), mtime).unwrap();
+
+        remove_moved(&local, &src, true, &local, &dst).unwrap();
+        assert_eq!(local.stat(&dst.join("docs/a.txt")).unwrap().modified, Some(mtime));
+    }
+
+    #[test]
+    fn locations_that_can_rename_between_each_other() {
+        let parse = |s: &str| FluxPath::parse(s).protocol;
+        assert!(same_location(&parse("/a"), &parse("/b/c")));
+        assert!(same_location(
+            &parse("sftp://u@nas/srv/a"),
+            &parse("sftp://u@NAS/home/b")
+        ));
+        assert!(!same_location(&parse("sftp://u@nas/a"), &parse("sftp://v@nas/a")));
+        assert!(!same_location(&parse("/a"), &parse("sftp://u@nas/a")));
+        assert!(same_location(
+            &parse("https://dav.example.com/files/a"),
+            &parse("https://dav.example.com/files/b")
+        ));
+        assert!(!same_location(
+            &parse("https://dav.example.com/files/a"),
+            &parse("https://dav.example.com/other/b")
+        ));
+
+        assert_eq!(join_arg("sftp://nas/srv/", "a", false), "sftp://nas/srv/a");
+        assert_eq!(join_arg("\\\\server\\share", "a", false), "\\\\server\\share\\a");
+        assert_eq!(trim_separators("/"), "/");
+    }
+}
@@ -0,0 +1,92 @@
+//! Desktop notifications for long-running transfers.
+//!
+//! Opt-in with `--notify` on `cp`/`sync` or `notify = true` in config.toml.
+//! Operations that finish (or fail) after running at least
+//! `notify_after_secs` seconds show a native notification with the bytes
+//! transferred and the elapsed time, so a transfer left in a background
+//! terminal doesn't need watching.
+
+use std::time::Duration;
+
+use bytesize::ByteSize;
+use indicatif::HumanDuration;
+
+use crate::queue::history::HistoryEntry;
+
+/// Whether a notification is due for an operation that ran `elapsed_secs`.
+pub fn should_notify(enabled: bool, threshold_secs: u64, elapsed_secs: f64) -> bool {
+    enabled && elapsed_secs >= threshold_secs as f64
+}
+
+/// Notification title and body describing `entry`.
+pub fn message(entry: &HistoryEntry) -> (String, String) {
+    let outcome = match entry.status.as_str() {
+        "completed" | "skipped" => "finished",
+        "paused" => "paused",
+        _ => "failed",
+    };
+    let title = format!("flux {} {}", entry.operation, outcome);
+
+    let mut body = format!(
+        "{} -> {}\n{} in {}",
+        entry.source,
+        entry.dest,
+        ByteSize(entry.bytes),
+        HumanDuration(Duration::from_secs_f64(entry.duration_secs.max(0.0))),
+    );
+    if let Some(ref error) = entry.error {
+        body.push('\n');
+        body.push_str(error);
+    }
+    (title, body)
+}
+
+/// Show a desktop notification for `entry` (best-effort).
+///
+/// A missing notification daemon or unsupported platform is logged, not
+/// reported as an error: the transfer itself already succeeded or failed.
+pub fn notify(entry: &HistoryEntry) {
+    let (title, body) = message(entry);
+    if let Err(e) = notify_rust::Notification::new()
+        .appname("flux")
+        .summary(&title)
+        .body(&body)
+        .show()
+    {
+        tracing::debug!("Desktop notification failed: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::FluxError;
+    use crate::transfer::history::HistoryRecord;
+
+    #[test]
+    fn notifies_only_when_enabled_and_slow() {
+        assert!(!should_notify(false, 30, 120.0));
+        assert!(!should_notify(true, 30, 5.0));
+        assert!(should_notify(true, 30, 30.0));
+        assert!(should_notify(true, 0, 0.1));
+    }
+
+    #[test]
+    fn message_for_completed_transfer() {
+        let mut record = HistoryRecord::new("sync", "/data", "/backup");
+        record.bytes = 2048;
+        let (title, body) = message(&record.to_entry(None));
+        assert_eq!(title, "flux sync finished");
+        assert!(body.contains("/data -> /backup"));
+        assert!(body.contains(&ByteSize(2048).to_string()));
+    }
+
+    #[test]
+    fn message_for_failed_transfer_includes_error() {
+        let record = HistoryRecord::new("cp", "a", "b");
+        let err = FluxError::TransferError("disk full".to_string());
+        let (title, body) = message(&record.to_entry(Some(&err)));
+        assert_eq!(title, "flux cp failed");
+        assert!(body.contains("disk full"));
+    }
+}
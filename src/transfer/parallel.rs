@@ -7,19 +7,35 @@
 //! Also provides `read_at_exact` and `write_at_all` wrappers that handle
 //! partial reads/writes, analogous to `Read::read_exact` and `Write::write_all`.
 //!
-//! The `parallel_copy_chunked` function uses rayon to copy file chunks in
-//! parallel, computing per-chunk BLAKE3 checksums during transfer.
+//! The `parallel_copy_chunked_pausable` function uses rayon to copy file chunks in
+//! parallel, computing per-chunk checksums (BLAKE3 unless `--checksum` picks
+//! another algorithm) during transfer. Destinations
+//! that reject pre-allocation or positional writes (some FUSE mounts and
+//! network shares) are copied sequentially instead, chunk by chunk.
+//!
+//! Built with the `io-uring` feature on Linux, each chunk's reads and writes
+//! are batched through io_uring (`transfer::uring`) when the kernel allows.
 
-use std::fs::{File, OpenOptions};
-use std::io;
+use std::fs::File;
+use std::io::{self, BufWriter, Read, Write};
 use std::path::Path;
-use std::sync::Arc;
+use std::time::Instant;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 
 use indicatif::ProgressBar;
 use rayon::prelude::*;
 
+use crate::backend::readonly;
 use crate::error::FluxError;
+use crate::transfer::{cancel, io_profile, prealloc};
+use crate::transfer::checksum::ChecksumAlgorithm;
 use crate::transfer::chunk::ChunkPlan;
+use crate::transfer::control::PauseSignal;
+use crate::transfer::copy::dest_open_options;
+use crate::transfer::monitor::TransferMonitor;
+use crate::transfer::schedule::{ChunkSchedule, MIN_SPLIT};
+#[cfg(all(target_os = "linux", feature = "io-uring"))]
+use crate::transfer::uring;
 
 /// Read bytes from `file` at the given byte `offset` into `buf`.
 ///
@@ -125,9 +141,6 @@ pub fn write_at_all(file: &File, offset: u64, buf: &[u8]) -> io::Result<()> {
     Ok(())
 }
 
-/// Buffer size for per-chunk I/O during parallel copy: 256KB.
-const CHUNK_BUF_SIZE: usize = 256 * 1024;
-
 /// Copy a file using parallel chunked I/O with per-chunk BLAKE3 checksums.
 ///
 /// Opens the source file for reading and creates/pre-allocates the destination
@@ -144,16 +157,55 @@ const CHUNK_BUF_SIZE: usize = 256 * 1024;
 /// * `chunks` - Mutable slice of ChunkPlans describing byte ranges to copy
 /// * `progress` - Progress bar to update with bytes transferred
 ///
+/// If the destination rejects `set_len` or positional writes with an
+/// "unsupported" error, the file is copied again sequentially (see
+/// `sequential_copy_chunked`) instead of failing.
+///
 /// # Errors
 /// Returns `FluxError` if any I/O operation fails. If a chunk fails, the
 /// entire operation is aborted (rayon's `try_for_each` short-circuits).
+#[cfg(test)]
 pub fn parallel_copy_chunked(
     source: &Path,
     dest: &Path,
-    chunks: &mut [ChunkPlan],
+    chunks: &mut Vec<ChunkPlan>,
     progress: &ProgressBar,
 ) -> Result<(), FluxError> {
-    // Open source file (read-only), wrap in Arc for sharing across threads
+    parallel_copy_chunked_pausable(
+        source,
+        dest,
+        chunks,
+        ChecksumAlgorithm::Blake3,
+        progress,
+        None,
+        None,
+    )
+}
+
+/// Like `parallel_copy_chunked`, but stops at a chunk boundary when `pause`
+/// is requested.
+///
+/// Chunks already in progress finish; chunks not yet started are left
+/// incomplete and `FluxError::Paused` is returned, so the caller can persist
+/// `chunks` in a resume manifest. Chunks marked completed are skipped and the
+/// destination is not truncated, so resumed data is preserved.
+///
+/// A `monitor` gets the chunk map and every buffer written, and holds the
+/// copy to its bandwidth limit. Chunk checksums use `algorithm`.
+///
+/// On cancellation (`transfer::cancel`) the copy stops within a buffer and
+/// returns `FluxError::Cancelled`; only whole chunks are marked completed.
+pub fn parallel_copy_chunked_pausable(
+    source: &Path,
+    dest: &Path,
+    chunks: &mut Vec<ChunkPlan>,
+    algorithm: ChecksumAlgorithm,
+    progress: &ProgressBar,
+    pause: Option<&PauseSignal>,
+    monitor: Option<&TransferMonitor>,
+) -> Result<(), FluxError> {
+    readonly::check(dest, "write")?;
+    // Open source file (read-only), shared by the workers
     let src_file = File::open(source).map_err(|e| match e.kind() {
         io::ErrorKind::NotFound => FluxError::SourceNotFound {
             path: source.to_path_buf(),
@@ -163,7 +215,7 @@ pub fn parallel_copy_chunked(
         },
         _ => FluxError::Io { source: e },
     })?;
-    let src_file = Arc::new(src_file);
+    io_profile::advise_sequential(&src_file);
 
     // Compute total size from chunks for pre-allocation
     let total_size: u64 = chunks.iter().map(|c| c.offset + c.length).max().unwrap_or(0);
@@ -180,12 +232,13 @@ pub fn parallel_copy_chunked(
         }
     }
 
-    // Create dest file with read+write permissions, pre-allocate to full size
-    let dst_file = OpenOptions::new()
+    // Create dest file with read+write permissions, pre-allocate to full size.
+    // When resuming, keep the data written by completed chunks.
+    let resuming = chunks.iter().any(|c| c.completed);
+    let src_meta = src_file.metadata().map_err(|e| FluxError::Io { source: e })?;
+    let dst_file = dest_open_options(&src_meta)
         .read(true)
-        .write(true)
-        .create(true)
-        .truncate(true)
+        .truncate(!resuming)
         .open(dest)
         .map_err(|e| match e.kind() {
             io::ErrorKind::PermissionDenied => FluxError::DestinationNotWritable {
@@ -194,46 +247,347 @@ pub fn parallel_copy_chunked(
             _ => FluxError::Io { source: e },
         })?;
 
-    // Pre-allocate destination file to full size
-    dst_file
-        .set_len(total_size)
-        .map_err(|e| FluxError::Io { source: e })?;
-
-    let dst_file = Arc::new(dst_file);
-
-    // Process chunks in parallel using rayon
-    chunks
-        .par_iter_mut()
-        .filter(|chunk| !chunk.completed)
-        .try_for_each(|chunk| -> Result<(), FluxError> {
-            let mut buf = vec![0u8; CHUNK_BUF_SIZE];
-            let mut remaining = chunk.length;
-            let mut chunk_offset = chunk.offset;
-            let mut hasher = blake3::Hasher::new();
-
-            while remaining > 0 {
-                let to_read = std::cmp::min(remaining, CHUNK_BUF_SIZE as u64) as usize;
-                let n = read_at(&src_file, chunk_offset, &mut buf[..to_read])?;
-                if n == 0 {
+    // Reserve the space (failing now if it is not there), then size the file
+    prealloc::allocate(&dst_file, dest, total_size)?;
+    if let Err(e) = dst_file.set_len(total_size) {
+        if is_positional_write_unsupported(&e) {
+            drop(dst_file);
+            tracing::warn!(
+                "Cannot pre-allocate {} ({}), copying sequentially",
+                dest.display(),
+                e
+            );
+            return sequential_copy_chunked(
+                source, dest, chunks, algorithm, progress, pause, monitor,
+            );
+        }
+        return Err(FluxError::Io { source: e });
+    }
+
+    let generation = monitor.map(|m| m.set_chunks(chunks));
+    let copy = ChunkCopy {
+        src: &src_file,
+        dst: &dst_file,
+        algorithm,
+        // Per-worker buffer size, from the I/O profile
+        buf_size: io_profile::buffer_size(),
+        progress,
+        monitor,
+        bytes_done: AtomicU64::new(0),
+        write_unsupported: AtomicBool::new(false),
+    };
+
+    // One worker per chunk to copy; idle workers split the slowest chunk
+    let workers = chunks.iter().filter(|c| !c.completed).count();
+    let schedule = ChunkSchedule::new(chunks, MIN_SPLIT, pause, monitor.zip(generation));
+    (0..workers).into_par_iter().for_each(|_| {
+        if let Err(e) = copy.run_worker(&schedule) {
+            schedule.fail(e);
+        }
+    });
+    let result = schedule.into_result();
+    let ChunkCopy {
+        bytes_done,
+        write_unsupported,
+        ..
+    } = copy;
+
+    if let Err(e) = result {
+        if !write_unsupported.into_inner() {
+            return Err(e);
+        }
+        drop(dst_file);
+        tracing::warn!(
+            "Positional writes not supported on {} ({}), copying sequentially",
+            dest.display(),
+            e
+        );
+        // Take back what the parallel attempt reported; the copy restarts
+        let reported = bytes_done.into_inner();
+        progress.set_position(progress.position().saturating_sub(reported));
+        if let Some(monitor) = monitor {
+            monitor.sub_bytes(reported);
+        }
+        return sequential_copy_chunked(source, dest, chunks, algorithm, progress, pause, monitor);
+    }
+
+    if chunks.iter().any(|c| !c.completed) && pause.is_some_and(|p| p.is_requested()) {
+        return Err(FluxError::Paused);
+    }
+
+    Ok(())
+}
+
+/// What every worker of one parallel copy shares.
+struct ChunkCopy<'a> {
+    src: &'a File,
+    dst: &'a File,
+    algorithm: ChecksumAlgorithm,
+    buf_size: usize,
+    progress: &'a ProgressBar,
+    monitor: Option<&'a TransferMonitor>,
+    /// Bytes reported so far, taken back if the copy restarts sequentially
+    bytes_done: AtomicU64,
+    write_unsupported: AtomicBool,
+}
+
+impl ChunkCopy<'_> {
+    /// Copy the chunks `schedule` hands out until there are none left.
+    fn run_worker(&self, schedule: &ChunkSchedule) -> Result<(), FluxError> {
+        #[cfg(all(target_os = "linux", feature = "io-uring"))]
+        let mut ring = uring::Ring::new();
+        #[cfg(all(target_os = "linux", feature = "io-uring"))]
+        let batch = if ring.is_some() { uring::QUEUE_DEPTH } else { 1 };
+        #[cfg(not(all(target_os = "linux", feature = "io-uring")))]
+        let batch = 1;
+        let mut bufs = vec![vec![0u8; self.buf_size]; batch];
+
+        let mut rate = None;
+        while let Some(index) = schedule.next_job(rate) {
+            let started = Instant::now();
+            let mut hasher = self.algorithm.hasher();
+            let mut copied = |data: &[u8]| {
+                hasher.update(data);
+                self.progress.inc(data.len() as u64);
+                self.bytes_done.fetch_add(data.len() as u64, Ordering::Relaxed);
+                if let Some(monitor) = self.monitor {
+                    monitor.add_bytes(data.len() as u64);
+                }
+            };
+
+            let mut bytes = 0;
+            let mut eof = false;
+            let claim_size = (self.buf_size * batch) as u64;
+            while let Some(range) = schedule.claim(index, claim_size) {
+                #[cfg(all(target_os = "linux", feature = "io-uring"))]
+                let n = match ring {
+                    Some(_) => self.copy_range_batched(&mut ring, range, &mut bufs, &mut copied)?,
+                    None => self.copy_range(range, &mut bufs[0], &mut copied)?,
+                };
+                #[cfg(not(all(target_os = "linux", feature = "io-uring")))]
+                let n = self.copy_range(range, &mut bufs[0], &mut copied)?;
+                bytes += n;
+                if n < range.1 {
+                    eof = true;
                     break;
                 }
+            }
 
-                write_at_all(&dst_file, chunk_offset, &buf[..n])?;
-                hasher.update(&buf[..n]);
-                progress.inc(n as u64);
+            schedule.finish(index, hasher.finish_hex(), eof);
+            rate = Some(bytes as f64 / started.elapsed().as_secs_f64().max(0.001));
+        }
+        Ok(())
+    }
+
+    /// Copy the `(offset, length)` range of the source to the same range of
+    /// the destination one buffer at a time with `pread`/`pwrite`, handing
+    /// each buffer written to `copied`. Returns the bytes copied, fewer if
+    /// the source ended; stops within a buffer on cancellation.
+    fn copy_range(
+        &self,
+        (offset, length): (u64, u64),
+        buf: &mut [u8],
+        copied: &mut dyn FnMut(&[u8]),
+    ) -> Result<u64, FluxError> {
+        let mut remaining = length;
+        let mut position = offset;
+
+        while remaining > 0 {
+            // Cancelled: the chunk stays incomplete
+            cancel::check()?;
+            let to_read = std::cmp::min(remaining, buf.len() as u64) as usize;
+            let n = read_at(self.src, position, &mut buf[..to_read])?;
+            if n == 0 {
+                break;
+            }
 
-                chunk_offset += n as u64;
-                remaining -= n as u64;
+            if let Err(e) = write_at_all(self.dst, position, &buf[..n]) {
+                if is_positional_write_unsupported(&e) {
+                    self.write_unsupported.store(true, Ordering::Relaxed);
+                }
+                return Err(FluxError::Io { source: e });
             }
+            copied(&buf[..n]);
 
-            chunk.checksum = Some(hasher.finalize().to_hex().to_string());
-            chunk.completed = true;
-            Ok(())
+            position += n as u64;
+            remaining -= n as u64;
+        }
+        Ok(position - offset)
+    }
+
+    /// `copy_range` through io_uring: the buffers of `bufs` are read in one
+    /// submission and written back in the next. Should the ring itself fail,
+    /// it is dropped for good and the batch and rest of the range are copied
+    /// with `copy_range`.
+    #[cfg(all(target_os = "linux", feature = "io-uring"))]
+    fn copy_range_batched(
+        &self,
+        ring: &mut Option<uring::Ring>,
+        (offset, length): (u64, u64),
+        bufs: &mut [Vec<u8>],
+        copied: &mut dyn FnMut(&[u8]),
+    ) -> Result<u64, FluxError> {
+        let mut remaining = length;
+        let mut position = offset;
+
+        while remaining > 0 {
+            cancel::check()?;
+            let Some(batched) = ring.as_mut() else {
+                let rest = self.copy_range((position, remaining), &mut bufs[0], copied)?;
+                return Ok(position - offset + rest);
+            };
+            let lens = {
+                let mut want = remaining;
+                let mut slices: Vec<&mut [u8]> = Vec::with_capacity(bufs.len());
+                for buf in bufs.iter_mut() {
+                    if want == 0 {
+                        break;
+                    }
+                    let len = std::cmp::min(want, buf.len() as u64) as usize;
+                    slices.push(&mut buf[..len]);
+                    want -= len as u64;
+                }
+                match batched.read_at_batch(self.src, position, &mut slices) {
+                    Ok(lens) => lens,
+                    Err(_) if batched.failed() => {
+                        *ring = None;
+                        continue;
+                    }
+                    Err(e) => return Err(e.into()),
+                }
+            };
+            let read: u64 = lens.iter().map(|&n| n as u64).sum();
+            if read == 0 {
+                break;
+            }
+
+            let data: Vec<&[u8]> = bufs.iter().zip(&lens).map(|(buf, &n)| &buf[..n]).collect();
+            if let Err(e) = batched.write_at_batch(self.dst, position, &data) {
+                if batched.failed() {
+                    *ring = None;
+                    continue;
+                }
+                if is_positional_write_unsupported(&e) {
+                    self.write_unsupported.store(true, Ordering::Relaxed);
+                }
+                return Err(FluxError::Io { source: e });
+            }
+            for buf in data {
+                copied(buf);
+            }
+
+            position += read;
+            remaining -= read;
+        }
+        Ok(position - offset)
+    }
+}
+
+/// Sequential fallback for destinations without positional write support.
+///
+/// Rewrites the destination from the start with plain streaming I/O, filling
+/// in the same per-chunk checksums as the parallel path. Chunks are copied in
+/// offset order, so on pause the completed chunks form a prefix of the file.
+fn sequential_copy_chunked(
+    source: &Path,
+    dest: &Path,
+    chunks: &mut [ChunkPlan],
+    algorithm: ChecksumAlgorithm,
+    progress: &ProgressBar,
+    pause: Option<&PauseSignal>,
+    monitor: Option<&TransferMonitor>,
+) -> Result<(), FluxError> {
+    let mut src_file = File::open(source).map_err(|e| FluxError::Io { source: e })?;
+    let src_meta = src_file.metadata().map_err(|e| FluxError::Io { source: e })?;
+    let dst_file = dest_open_options(&src_meta)
+        .open(dest)
+        .map_err(|e| match e.kind() {
+            io::ErrorKind::PermissionDenied => FluxError::DestinationNotWritable {
+                path: dest.to_path_buf(),
+            },
+            _ => FluxError::Io { source: e },
         })?;
+    let buf_size = io_profile::buffer_size();
+    let mut writer = BufWriter::with_capacity(buf_size, dst_file);
+
+    // Everything is rewritten, including chunks a previous run completed
+    for chunk in chunks.iter_mut() {
+        chunk.completed = false;
+        chunk.checksum = None;
+    }
+    let mut order: Vec<usize> = (0..chunks.len()).collect();
+    order.sort_by_key(|&i| chunks[i].offset);
+    let generation = monitor.map(|m| m.set_chunks(chunks));
+
+    let mut buf = vec![0u8; buf_size];
+    for i in order {
+        if pause.is_some_and(|p| p.is_requested()) {
+            writer.flush().map_err(|e| FluxError::Io { source: e })?;
+            return Err(FluxError::Paused);
+        }
+
+        let chunk = &mut chunks[i];
+        let mut remaining = chunk.length;
+        let mut hasher = algorithm.hasher();
+        while remaining > 0 {
+            if cancel::requested() {
+                writer.flush().map_err(|e| FluxError::Io { source: e })?;
+                return Err(FluxError::Cancelled);
+            }
+            let to_read = std::cmp::min(remaining, buf_size as u64) as usize;
+            let n = match src_file.read(&mut buf[..to_read]) {
+                Ok(0) => break,
+                Ok(n) => n,
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(FluxError::Io { source: e }),
+            };
+            writer
+                .write_all(&buf[..n])
+                .map_err(|e| FluxError::Io { source: e })?;
+            hasher.update(&buf[..n]);
+            progress.inc(n as u64);
+            if let Some(monitor) = monitor {
+                monitor.add_bytes(n as u64);
+            }
+            remaining -= n as u64;
+        }
+
+        chunk.checksum = Some(hasher.finish_hex());
+        chunk.completed = true;
+        if let (Some(monitor), Some(generation)) = (monitor, generation) {
+            monitor.chunk_done(generation, i);
+        }
+    }
 
+    writer.flush().map_err(|e| FluxError::Io { source: e })?;
     Ok(())
 }
 
+/// Whether `err` means the destination cannot pre-allocate or write at an
+/// offset (as opposed to a genuine I/O failure such as a full disk).
+///
+/// Some FUSE filesystems and network shares answer `ftruncate`/`pwrite` with
+/// EPERM, ENOTSUP/EOPNOTSUPP or ESPIPE while still accepting streaming writes.
+pub(crate) fn is_positional_write_unsupported(err: &io::Error) -> bool {
+    if err.kind() == io::ErrorKind::Unsupported {
+        return true;
+    }
+    err.raw_os_error()
+        .is_some_and(|code| POSITIONAL_UNSUPPORTED_OS_ERRORS.contains(&code))
+}
+
+/// EPERM, ESPIPE, ENOTSUP/EOPNOTSUPP.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+const POSITIONAL_UNSUPPORTED_OS_ERRORS: &[i32] = &[1, 29, 95];
+
+/// EPERM, ESPIPE, ENOTSUP, EOPNOTSUPP.
+#[cfg(all(unix, not(any(target_os = "linux", target_os = "android"))))]
+const POSITIONAL_UNSUPPORTED_OS_ERRORS: &[i32] = &[1, 29, 45, 102];
+
+/// ERROR_INVALID_FUNCTION, ERROR_NOT_SUPPORTED.
+#[cfg(windows)]
+const POSITIONAL_UNSUPPORTED_OS_ERRORS: &[i32] = &[1, 50];
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -614,4 +968,167 @@ mod tests {
         assert!(chunks[1].checksum.is_some());
         assert_ne!(chunks[1].checksum.as_deref(), Some("already_done"));
     }
+
+    #[test]
+    fn parallel_copy_pauses_before_pending_chunks() {
+        use crate::transfer::chunk::chunk_file;
+
+        let dir = tempfile::tempdir().unwrap();
+        let src_path = dir.path().join("source.bin");
+        let dst_path = dir.path().join("dest.bin");
+
+        let data: Vec<u8> = (0..1000u32).map(|i| (i % 256) as u8).collect();
+        std::fs::write(&src_path, &data).unwrap();
+
+        let mut chunks = chunk_file(data.len() as u64, 4);
+        let pb = ProgressBar::hidden();
+        let pause = PauseSignal::new();
+        pause.request();
+
+        let result = parallel_copy_chunked_pausable(
+            &src_path,
+            &dst_path,
+            &mut chunks,
+            ChecksumAlgorithm::Blake3,
+            &pb,
+            Some(&pause),
+            None,
+        );
+        assert!(matches!(result, Err(FluxError::Paused)));
+        assert!(chunks.iter().all(|c| !c.completed));
+    }
+
+    #[test]
+    fn parallel_copy_reports_to_monitor() {
+        use crate::transfer::chunk::chunk_file;
+
+        let dir = tempfile::tempdir().unwrap();
+        let src_path = dir.path().join("source.bin");
+        let dst_path = dir.path().join("dest.bin");
+        std::fs::write(&src_path, vec![3u8; 4000]).unwrap();
+
+        let mut chunks = chunk_file(4000, 4);
+        chunks[1].completed = true;
+        let monitor = TransferMonitor::new("source.bin", "dest.bin");
+        monitor.start(4000, 1000);
+        let pb = ProgressBar::hidden();
+        parallel_copy_chunked_pausable(
+            &src_path,
+            &dst_path,
+            &mut chunks,
+            ChecksumAlgorithm::Blake3,
+            &pb,
+            None,
+            Some(&monitor),
+        )
+        .unwrap();
+
+        let snap = monitor.snapshot();
+        assert_eq!(snap.bytes_done, 4000);
+        assert_eq!(snap.chunks, vec![true; 4]);
+    }
+
+    #[test]
+    fn parallel_copy_resume_preserves_completed_data() {
+        use crate::transfer::chunk::chunk_file;
+
+        let dir = tempfile::tempdir().unwrap();
+        let src_path = dir.path().join("source.bin");
+        let dst_path = dir.path().join("dest.bin");
+
+        let data: Vec<u8> = (0..1000u32).map(|i| (i % 256) as u8).collect();
+        std::fs::write(&src_path, &data).unwrap();
+
+        // Simulate an interrupted run that only wrote the first chunk
+        let mut chunks = chunk_file(data.len() as u64, 2);
+        let first_len = chunks[0].length as usize;
+        std::fs::write(&dst_path, &data[..first_len]).unwrap();
+        chunks[0].completed = true;
+
+        let pb = ProgressBar::hidden();
+        parallel_copy_chunked(&src_path, &dst_path, &mut chunks, &pb).unwrap();
+
+        assert_eq!(std::fs::read(&dst_path).unwrap(), data);
+    }
+
+    #[test]
+    fn sequential_fallback_matches_parallel_checksums() {
+        use crate::transfer::chunk::chunk_file;
+
+        let dir = tempfile::tempdir().unwrap();
+        let src_path = dir.path().join("source.bin");
+        let data: Vec<u8> = (0..300_000u32).map(|i| (i % 251) as u8).collect();
+        std::fs::write(&src_path, &data).unwrap();
+
+        let mut parallel = chunk_file(data.len() as u64, 4);
+        let pb = ProgressBar::hidden();
+        parallel_copy_chunked(&src_path, &dir.path().join("a.bin"), &mut parallel, &pb).unwrap();
+
+        // A stale partial destination and chunk state are rewritten from scratch
+        let seq_path = dir.path().join("b.bin");
+        std::fs::write(&seq_path, vec![0xFFu8; 400_000]).unwrap();
+        let mut sequential = chunk_file(data.len() as u64, 4);
+        sequential[1].completed = true;
+        sequential[1].checksum = Some("stale".to_string());
+        let pb = ProgressBar::hidden();
+        sequential_copy_chunked(
+            &src_path,
+            &seq_path,
+            &mut sequential,
+            ChecksumAlgorithm::Blake3,
+            &pb,
+            None,
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(std::fs::read(&seq_path).unwrap(), data);
+        assert_eq!(pb.position(), data.len() as u64);
+        for (p, s) in parallel.iter().zip(&sequential) {
+            assert!(s.completed);
+            assert_eq!(p.checksum, s.checksum);
+        }
+    }
+
+    #[test]
+    fn sequential_fallback_pauses_between_chunks() {
+        use crate::transfer::chunk::chunk_file;
+
+        let dir = tempfile::tempdir().unwrap();
+        let src_path = dir.path().join("source.bin");
+        std::fs::write(&src_path, vec![7u8; 1000]).unwrap();
+
+        let mut chunks = chunk_file(1000, 2);
+        let pause = PauseSignal::new();
+        pause.request();
+        let pb = ProgressBar::hidden();
+        let result = sequential_copy_chunked(
+            &src_path,
+            &dir.path().join("dest.bin"),
+            &mut chunks,
+            ChecksumAlgorithm::Blake3,
+            &pb,
+            Some(&pause),
+            None,
+        );
+        assert!(matches!(result, Err(FluxError::Paused)));
+        assert!(chunks.iter().all(|c| !c.completed));
+    }
+
+    #[test]
+    fn unsupported_positional_write_errors_are_recognized() {
+        assert!(is_positional_write_unsupported(&io::Error::from(
+            io::ErrorKind::Unsupported
+        )));
+        for &code in POSITIONAL_UNSUPPORTED_OS_ERRORS {
+            assert!(is_positional_write_unsupported(&io::Error::from_raw_os_error(code)));
+        }
+        assert!(!is_positional_write_unsupported(&io::Error::from(
+            io::ErrorKind::WriteZero
+        )));
+        assert!(!is_positional_write_unsupported(&io::Error::new(
+            io::ErrorKind::Other,
+            "disk full"
+        )));
+    }
 }
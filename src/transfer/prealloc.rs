@@ -0,0 +1,145 @@
+//! Preallocation of destination files for chunked copies.
+//!
+//! `set_len` alone leaves a sparse file that the filesystem fills in
+//! whatever order the parallel chunks arrive, which fragments large files on
+//! ext4 and NTFS, and a full disk only shows up partway through the copy.
+//! `allocate` reserves the blocks first (`fallocate` on Linux, through `fs2`
+//! elsewhere: `F_PREALLOCATE` on macOS, `SetFileInformationByHandle` with
+//! `FileAllocationInfo` on Windows), so a destination without room fails
+//! straight away with `FluxError::DiskFull`.
+//!
+//! Filesystems that cannot preallocate, and `cp --no-preallocate` (some
+//! network and FUSE filesystems emulate it by writing zeros, which is slow),
+//! get a free-space check instead.
+
+use std::fs::File;
+use std::io;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::error::FluxError;
+
+/// Set by `--no-preallocate`.
+static DISABLED: AtomicBool = AtomicBool::new(false);
+
+/// Only check free space from now on (`--no-preallocate`).
+pub fn disable() {
+    DISABLED.store(true, Ordering::Relaxed);
+}
+
+/// Reserve space for `file` (being written at `path`) to grow to `size`
+/// bytes. The file may be extended to `size` on the way; callers still
+/// `set_len` it.
+///
+/// Fails with `FluxError::DiskFull` when the space is not there. Other
+/// preallocation failures fall back to the free-space check.
+pub fn allocate(file: &File, path: &Path, size: u64) -> Result<(), FluxError> {
+    let current = file.metadata()?.len();
+    if size <= current {
+        return Ok(());
+    }
+    if !DISABLED.load(Ordering::Relaxed) {
+        match reserve(file, size) {
+            Ok(()) => return Ok(()),
+            Err(e) if is_disk_full(&e) => return Err(disk_full(path, size - current)),
+            Err(e) => tracing::debug!(
+                "Cannot preallocate {} ({}), checking free space",
+                path.display(),
+                e
+            ),
+        }
+    }
+    check_space(path, size - current)
+}
+
+/// Allocate blocks up to `size`. Unlike `posix_fallocate`, fails where the
+/// filesystem does not support it instead of writing zeros.
+#[cfg(target_os = "linux")]
+fn reserve(file: &File, size: u64) -> io::Result<()> {
+    use std::os::unix::io::AsRawFd;
+
+    loop {
+        // SAFETY: the descriptor is open for the duration of the call
+        let result = unsafe { libc::fallocate(file.as_raw_fd(), 0, 0, size as libc::off_t) };
+        if result == 0 {
+            return Ok(());
+        }
+        let error = io::Error::last_os_error();
+        if error.kind() != io::ErrorKind::Interrupted {
+            return Err(error);
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn reserve(file: &File, size: u64) -> io::Result<()> {
+    fs2::FileExt::allocate(file, size)
+}
+
+/// ENOSPC, or ERROR_DISK_FULL / ERROR_HANDLE_DISK_FULL on Windows.
+fn is_disk_full(error: &io::Error) -> bool {
+    #[cfg(unix)]
+    let codes = [libc::ENOSPC];
+    #[cfg(windows)]
+    let codes = [112, 39];
+    error.raw_os_error().is_some_and(|code| codes.contains(&code))
+}
+
+/// Fail if `needed` more bytes do not fit next to `path`. Free space that
+/// cannot be read counts as enough.
+fn check_space(path: &Path, needed: u64) -> Result<(), FluxError> {
+    match available_space(path) {
+        Some(available) if available < needed => Err(FluxError::DiskFull {
+            path: path.to_path_buf(),
+            needed,
+            available,
+        }),
+        _ => Ok(()),
+    }
+}
+
+fn disk_full(path: &Path, needed: u64) -> FluxError {
+    FluxError::DiskFull {
+        path: path.to_path_buf(),
+        needed,
+        available: available_space(path).unwrap_or(0),
+    }
+}
+
+/// Free space on the filesystem holding `path` (or its closest existing
+/// parent).
+fn available_space(path: &Path) -> Option<u64> {
+    let existing = path.ancestors().skip(1).find(|p| p.exists())?;
+    let existing = if existing.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        existing
+    };
+    fs2::available_space(existing).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn allocate_reserves_the_destination_size() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("dest.bin");
+        let file = File::create(&path).unwrap();
+        allocate(&file, &path, 1024 * 1024).unwrap();
+        file.set_len(1024 * 1024).unwrap();
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), 1024 * 1024);
+    }
+
+    #[test]
+    fn impossible_sizes_are_refused_up_front() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("dest.bin");
+        let file = File::create(&path).unwrap();
+        let err = allocate(&file, &path, u64::MAX / 2).unwrap_err();
+        assert!(matches!(err, FluxError::DiskFull { .. }), "{}", err);
+        assert!(check_space(&path, u64::MAX).is_err());
+        assert!(check_space(&path, 1).is_ok());
+    }
+}
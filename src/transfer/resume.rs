@@ -3,16 +3,37 @@
 //! When `--resume` is active, a `.flux-resume.json` sidecar file is created
 //! next to the destination file. It tracks which chunks have been completed,
 //! allowing interrupted transfers to continue from where they left off.
+//!
+//! With `--resume-verify`, completed chunks are re-hashed in the destination
+//! before they are skipped (`TransferManifest::reverify`), so chunks changed
+//! or corrupted since the interruption are copied again.
+//!
+//! Manifests are versioned (see `config::versioned`): one left by an older
+//! flux is migrated when loaded, and one from a newer flux is refused.
 
 use std::fs;
-use std::io::Write;
+use std::io::{self, Write};
 use std::path::{Path, PathBuf};
 
+use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 
+use crate::config::versioned::Format;
 use crate::error::FluxError;
+use crate::transfer::checksum::{hash_chunk_with, ChecksumAlgorithm};
 use crate::transfer::chunk::ChunkPlan;
 
+/// Manifest format versions:
+///
+/// 1. Original format; `checksum_algorithm` was added later and may be absent
+///    (BLAKE3).
+/// 2. `checksum_algorithm` is always present.
+pub const MANIFEST_FORMAT: Format = Format {
+    current: 2,
+    migrations: &[name_checksum_algorithm],
+};
+
 /// Persistent manifest for resumable transfers.
 ///
 /// Serialized to JSON and saved as a sidecar file next to the destination.
@@ -20,7 +41,7 @@ use crate::transfer::chunk::ChunkPlan;
 /// transferred.
 #[derive(Debug, Serialize, Deserialize)]
 pub struct TransferManifest {
-    /// Manifest format version (currently 1).
+    /// Manifest format version (`MANIFEST_FORMAT.current` when written).
     pub version: u32,
     /// Original source path.
     pub source: PathBuf,
@@ -34,8 +55,10 @@ pub struct TransferManifest {
     pub chunks: Vec<ChunkPlan>,
     /// Whether compression was enabled for this transfer.
     pub compress: bool,
-    /// Whole-file BLAKE3 checksum (populated after completion if --verify).
+    /// Whole-file checksum (populated after completion if --verify).
     pub file_checksum: Option<String>,
+    /// Algorithm of `file_checksum` and the chunk checksums (`--checksum`).
+    pub checksum_algorithm: ChecksumAlgorithm,
 }
 
 impl TransferManifest {
@@ -62,7 +85,7 @@ impl TransferManifest {
     ) -> Self {
         let chunk_count = chunks.len();
         Self {
-            version: 1,
+            version: MANIFEST_FORMAT.current,
             source,
             dest,
             total_size,
@@ -70,9 +93,16 @@ impl TransferManifest {
             chunks,
             compress,
             file_checksum: None,
+            checksum_algorithm: ChecksumAlgorithm::default(),
         }
     }
 
+    /// Record the algorithm the checksums are computed with.
+    pub fn with_checksum_algorithm(mut self, algorithm: ChecksumAlgorithm) -> Self {
+        self.checksum_algorithm = algorithm;
+        self
+    }
+
     /// Save the manifest to disk as a JSON sidecar file.
     ///
     /// Uses write + flush + sync_all for crash safety.
@@ -102,7 +132,8 @@ impl TransferManifest {
 
     /// Load a manifest from disk if one exists for the given destination.
     ///
-    /// Returns `Ok(None)` if no manifest file is found.
+    /// Returns `Ok(None)` if no manifest file is found, and
+    /// `FluxError::NewerFormat` if a newer flux wrote it.
     pub fn load(dest: &Path) -> Result<Option<Self>, FluxError> {
         let path = Self::manifest_path(dest);
         if !path.exists() {
@@ -115,10 +146,16 @@ impl TransferManifest {
                 e
             ))
         })?;
-        let manifest: Self = serde_json::from_str(&json).map_err(|e| {
-            FluxError::ResumeError(format!("Failed to parse manifest: {}", e))
-        })?;
-        Ok(Some(manifest))
+        Self::parse(&path, &json).map(Some)
+    }
+
+    /// Parse the manifest file at `path`, read into `json`, migrating an
+    /// older format.
+    pub fn parse(path: &Path, json: &str) -> Result<Self, FluxError> {
+        MANIFEST_FORMAT.load(path, json).map_err(|e| match e {
+            FluxError::NewerFormat { .. } => e,
+            e => FluxError::ResumeError(format!("Failed to parse manifest: {}", e)),
+        })
     }
 
     /// Delete the manifest sidecar file if it exists.
@@ -161,6 +198,72 @@ impl TransferManifest {
     }
 }
 
+/// Outcome of `TransferManifest::reverify`.
+#[derive(Debug, Default, PartialEq)]
+pub struct ReverifySummary {
+    /// Completed chunks that were checked.
+    pub checked: usize,
+    /// Indices of the chunks that no longer match and are copied again.
+    pub invalid: Vec<usize>,
+    /// Bytes in the `invalid` chunks.
+    pub invalid_bytes: u64,
+}
+
+impl TransferManifest {
+    /// Re-hash the completed chunks in `data` (the partial destination file)
+    /// in parallel, and mark the ones whose checksum no longer matches as
+    /// incomplete.
+    ///
+    /// Chunks without a checksum, and all of them when `data` is missing,
+    /// count as changed.
+    pub fn reverify(&mut self, data: &Path) -> Result<ReverifySummary, FluxError> {
+        let file = match fs::File::open(data) {
+            Ok(file) => Some(file),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+            Err(e) => return Err(FluxError::Io { source: e }),
+        };
+        let algorithm = self.checksum_algorithm;
+        let intact: Vec<bool> = self
+            .chunks
+            .par_iter()
+            .map(|chunk| -> Result<bool, FluxError> {
+                if !chunk.completed {
+                    return Ok(true);
+                }
+                let (Some(file), Some(expected)) = (&file, &chunk.checksum) else {
+                    return Ok(false);
+                };
+                let actual = hash_chunk_with(file, chunk.offset, chunk.length, algorithm)?;
+                Ok(actual == *expected)
+            })
+            .collect::<Result<_, _>>()?;
+
+        let mut summary = ReverifySummary {
+            checked: self.completed_count(),
+            ..ReverifySummary::default()
+        };
+        for (index, chunk) in self.chunks.iter_mut().enumerate() {
+            if !intact[index] {
+                chunk.completed = false;
+                chunk.checksum = None;
+                summary.invalid.push(index);
+                summary.invalid_bytes += chunk.length;
+            }
+        }
+        Ok(summary)
+    }
+}
+
+/// Version 1 to 2: name the checksum algorithm of manifests written before
+/// `--checksum` existed.
+fn name_checksum_algorithm(mut doc: Value) -> Result<Value, String> {
+    let fields = doc.as_object_mut().ok_or("expected an object")?;
+    fields
+        .entry("checksum_algorithm")
+        .or_insert_with(|| ChecksumAlgorithm::Blake3.name().into());
+    Ok(doc)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -212,13 +315,66 @@ mod tests {
         let loaded = TransferManifest::load(&dest).unwrap();
         assert!(loaded.is_some());
         let loaded = loaded.unwrap();
-        assert_eq!(loaded.version, 1);
+        assert_eq!(loaded.version, 2);
         assert_eq!(loaded.source, PathBuf::from("/tmp/source.bin"));
         assert_eq!(loaded.total_size, 1000);
         assert_eq!(loaded.chunk_count, 4);
         assert_eq!(loaded.chunks.len(), 4);
         assert!(!loaded.compress);
         assert!(loaded.file_checksum.is_none());
+        assert_eq!(loaded.checksum_algorithm, ChecksumAlgorithm::Blake3);
+    }
+
+    #[test]
+    fn checksum_algorithm_is_saved_and_defaults_to_blake3() {
+        let dir = tempfile::tempdir().unwrap();
+        let dest = dir.path().join("output.bin");
+        let manifest = TransferManifest::new(
+            PathBuf::from("/tmp/source.bin"),
+            dest.clone(),
+            1000,
+            chunk_file(1000, 2),
+            false,
+        )
+        .with_checksum_algorithm(ChecksumAlgorithm::Sha256);
+        manifest.save(&dest).unwrap();
+
+        let json = fs::read_to_string(TransferManifest::manifest_path(&dest)).unwrap();
+        assert!(json.contains("\"checksum_algorithm\": \"sha256\""));
+        let loaded = TransferManifest::load(&dest).unwrap().unwrap();
+        assert_eq!(loaded.checksum_algorithm, ChecksumAlgorithm::Sha256);
+
+        // A version 1 manifest may have no algorithm field
+        let old = json
+            .replace("\"checksum_algorithm\": \"sha256\"", "\"legacy\": true")
+            .replace("\"version\": 2", "\"version\": 1");
+        fs::write(TransferManifest::manifest_path(&dest), old).unwrap();
+        let loaded = TransferManifest::load(&dest).unwrap().unwrap();
+        assert_eq!(loaded.version, 2);
+        assert_eq!(loaded.checksum_algorithm, ChecksumAlgorithm::Blake3);
+    }
+
+    #[test]
+    fn manifests_from_a_newer_flux_are_refused() {
+        let dir = tempfile::tempdir().unwrap();
+        let dest = dir.path().join("output.bin");
+        TransferManifest::new(
+            PathBuf::from("/tmp/source.bin"),
+            dest.clone(),
+            1000,
+            chunk_file(1000, 2),
+            false,
+        )
+        .save(&dest)
+        .unwrap();
+        let path = TransferManifest::manifest_path(&dest);
+        let json = fs::read_to_string(&path).unwrap().replace("\"version\": 2", "\"version\": 9");
+        fs::write(&path, json).unwrap();
+
+        let err = TransferManifest::load(&dest).unwrap_err();
+        assert!(matches!(err, FluxError::NewerFormat { found: 9, .. }), "{:?}", err);
+        // Left for the flux that wrote it
+        assert!(path.exists());
     }
 
     #[test]
@@ -366,4 +522,52 @@ mod tests {
         );
         assert_eq!(manifest.chunk_count, 5);
     }
+
+    #[test]
+    fn reverify_resets_chunks_changed_in_the_destination() {
+        let dir = tempfile::tempdir().unwrap();
+        let dest = dir.path().join("output.bin");
+        let data: Vec<u8> = (0..4000u32).map(|i| (i % 251) as u8).collect();
+        fs::write(&dest, &data).unwrap();
+
+        let mut chunks = chunk_file(4000, 4);
+        for chunk in &mut chunks[..3] {
+            let range = chunk.offset as usize..(chunk.offset + chunk.length) as usize;
+            chunk.checksum = Some(blake3::hash(&data[range]).to_hex().to_string());
+            chunk.completed = true;
+        }
+        let source = PathBuf::from("/tmp/source.bin");
+        let mut manifest = TransferManifest::new(source, dest.clone(), 4000, chunks, false);
+
+        let summary = manifest.reverify(&dest).unwrap();
+        assert_eq!(summary, ReverifySummary { checked: 3, ..ReverifySummary::default() });
+
+        // Corrupt chunk 1
+        let mut corrupted = data.clone();
+        corrupted[1500] ^= 0xFF;
+        fs::write(&dest, &corrupted).unwrap();
+        let summary = manifest.reverify(&dest).unwrap();
+        assert_eq!(summary.checked, 3);
+        assert_eq!(summary.invalid, vec![1]);
+        assert_eq!(summary.invalid_bytes, 1000);
+        assert!(manifest.chunks[0].completed && manifest.chunks[2].completed);
+        assert!(!manifest.chunks[1].completed);
+        assert!(manifest.chunks[1].checksum.is_none());
+    }
+
+    #[test]
+    fn reverify_without_destination_resets_everything() {
+        let dir = tempfile::tempdir().unwrap();
+        let dest = dir.path().join("gone.bin");
+        let mut chunks = chunk_file(1000, 2);
+        chunks[0].completed = true;
+        chunks[0].checksum = Some("abc".into());
+        chunks[1].completed = true;
+        let source = PathBuf::from("/tmp/source.bin");
+        let mut manifest = TransferManifest::new(source, dest.clone(), 1000, chunks, false);
+
+        let summary = manifest.reverify(&dest).unwrap();
+        assert_eq!(summary.invalid, vec![0, 1]);
+        assert_eq!(manifest.completed_count(), 0);
+    }
 }
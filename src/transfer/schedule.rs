@@ -0,0 +1,307 @@
+//! Work-stealing chunk schedule for the parallel copy engine.
+//!
+//! `chunk_file` splits a file into a fixed number of chunks up front, so a
+//! chunk that happens to be slow (a fragmented range, a busy disk) used to
+//! hold the copy up while the other workers sat idle. The schedule hands
+//! out whole chunks while any are left. After that, a worker that runs out
+//! of work measures the running chunks and steals the tail of the one
+//! expected to finish last, splitting it into a new `ChunkPlan` sized by
+//! the two workers' throughput. Every chunk is still copied and hashed in
+//! order by a single worker, so chunk checksums and resume manifests keep
+//! their meaning; split chunks simply show up as extra entries.
+//!
+//! Workers claim their chunk a buffer (or an io_uring batch) at a time,
+//! which is what lets a chunk be cut short while it is being copied.
+
+use std::sync::Mutex;
+use std::time::Instant;
+
+use crate::error::FluxError;
+use crate::transfer::chunk::ChunkPlan;
+use crate::transfer::control::PauseSignal;
+use crate::transfer::monitor::TransferMonitor;
+
+/// Smallest piece a split leaves on either side. Below this, finishing the
+/// chunk costs less than handing half of it to another worker.
+pub const MIN_SPLIT: u64 = 8 * 1024 * 1024;
+
+/// Chunks of one parallel copy and who is working on which.
+pub struct ChunkSchedule<'a> {
+    state: Mutex<State<'a>>,
+    min_split: u64,
+    pause: Option<&'a PauseSignal>,
+    /// Monitor and chunk map generation, for the TUI chunk map
+    monitor: Option<(&'a TransferMonitor, u64)>,
+}
+
+struct State<'a> {
+    chunks: &'a mut Vec<ChunkPlan>,
+    slots: Vec<Slot>,
+    /// First error of any worker; stops the others
+    error: Option<FluxError>,
+}
+
+/// Progress of one chunk.
+#[derive(Debug, Clone, Copy)]
+struct Slot {
+    status: Status,
+    /// Next byte not yet claimed by the worker
+    next: u64,
+    /// End of the chunk, moved back when its tail is stolen
+    end: u64,
+    started: Option<Instant>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Status {
+    Pending,
+    Running,
+    Done,
+}
+
+impl<'a> ChunkSchedule<'a> {
+    /// Schedule the chunks not yet completed. No chunk is started once
+    /// `pause` is requested; pieces smaller than `min_split` are not split.
+    pub fn new(
+        chunks: &'a mut Vec<ChunkPlan>,
+        min_split: u64,
+        pause: Option<&'a PauseSignal>,
+        monitor: Option<(&'a TransferMonitor, u64)>,
+    ) -> Self {
+        let slots = chunks
+            .iter()
+            .map(|c| Slot {
+                status: if c.completed { Status::Done } else { Status::Pending },
+                next: c.offset,
+                end: c.offset + c.length,
+                started: None,
+            })
+            .collect();
+        ChunkSchedule {
+            state: Mutex::new(State {
+                chunks,
+                slots,
+                error: None,
+            }),
+            min_split,
+            pause,
+            monitor,
+        }
+    }
+
+    /// The next chunk for a worker whose last chunk went at `rate` bytes/s:
+    /// a pending chunk, else the stolen tail of a running one. `None` when
+    /// there is nothing worth taking, on pause, or after an error.
+    pub fn next_job(&self, rate: Option<f64>) -> Option<usize> {
+        let mut state = self.lock();
+        if state.error.is_some() || self.pause.is_some_and(|p| p.is_requested()) {
+            return None;
+        }
+        if let Some(index) = state.slots.iter().position(|s| s.status == Status::Pending) {
+            let slot = &mut state.slots[index];
+            slot.status = Status::Running;
+            slot.started = Some(Instant::now());
+            return Some(index);
+        }
+        self.steal(&mut state, rate)
+    }
+
+    /// Split the running chunk expected to finish last and return the new
+    /// chunk holding its tail.
+    fn steal(&self, state: &mut State<'a>, rate: Option<f64>) -> Option<usize> {
+        let now = Instant::now();
+        // (index, seconds left, owner's bytes/s); an owner without progress
+        // yet counts as never finishing
+        let mut victim: Option<(usize, f64, Option<f64>)> = None;
+        for (index, slot) in state.slots.iter().enumerate() {
+            if slot.status != Status::Running || slot.end - slot.next < 2 * self.min_split {
+                continue;
+            }
+            let done = slot.next - state.chunks[index].offset;
+            let elapsed = slot.started.map_or(0.0, |s| (now - s).as_secs_f64());
+            let owner_rate = (done > 0 && elapsed > 0.0).then(|| done as f64 / elapsed);
+            let left = match owner_rate {
+                Some(owner_rate) => (slot.end - slot.next) as f64 / owner_rate,
+                None => f64::INFINITY,
+            };
+            let slower = match victim {
+                Some((_, latest, _)) => left > latest,
+                None => true,
+            };
+            if slower {
+                victim = Some((index, left, owner_rate));
+            }
+        }
+        let (index, _, owner_rate) = victim?;
+
+        let slot = state.slots[index];
+        let remaining = slot.end - slot.next;
+        // Share the rest so both workers should finish together
+        let share = match (rate, owner_rate) {
+            (Some(thief), Some(owner)) if thief + owner > 0.0 => thief / (thief + owner),
+            _ => 0.5,
+        };
+        let taken = ((remaining as f64 * share) as u64)
+            .clamp(self.min_split, remaining - self.min_split);
+        let split = slot.end - taken;
+
+        state.slots[index].end = split;
+        let owner = &mut state.chunks[index];
+        owner.length = split - owner.offset;
+        let stolen = state.chunks.len();
+        state.chunks.push(ChunkPlan {
+            index: stolen,
+            offset: split,
+            length: taken,
+            completed: false,
+            checksum: None,
+        });
+        state.slots.push(Slot {
+            status: Status::Running,
+            next: split,
+            end: slot.end,
+            started: Some(now),
+        });
+        if let Some((monitor, generation)) = self.monitor {
+            monitor.add_chunk(generation);
+        }
+        tracing::debug!(
+            "Split chunk {} at {}: {} bytes moved to chunk {}",
+            index,
+            split,
+            taken,
+            stolen
+        );
+        Some(stolen)
+    }
+
+    /// Claim up to `max` more bytes of chunk `index` as `(offset, length)`;
+    /// `None` once the chunk (as split so far) is claimed, or after an error.
+    pub fn claim(&self, index: usize, max: u64) -> Option<(u64, u64)> {
+        let mut state = self.lock();
+        if state.error.is_some() {
+            return None;
+        }
+        let slot = &mut state.slots[index];
+        if slot.next >= slot.end {
+            return None;
+        }
+        let length = max.min(slot.end - slot.next);
+        let offset = slot.next;
+        slot.next += length;
+        Some((offset, length))
+    }
+
+    /// Record chunk `index` as copied with `checksum` if all of it was
+    /// claimed, or the source ended early (`eof`). A chunk stopped by an
+    /// error elsewhere stays incomplete.
+    pub fn finish(&self, index: usize, checksum: String, eof: bool) {
+        let mut state = self.lock();
+        let slot = state.slots[index];
+        if slot.next < slot.end && !eof {
+            return;
+        }
+        state.slots[index].status = Status::Done;
+        let chunk = &mut state.chunks[index];
+        chunk.checksum = Some(checksum);
+        chunk.completed = true;
+        if let Some((monitor, generation)) = self.monitor {
+            monitor.chunk_done(generation, index);
+        }
+    }
+
+    /// Stop every worker because of `error`; the first one is kept.
+    pub fn fail(&self, error: FluxError) {
+        let mut state = self.lock();
+        if state.error.is_none() {
+            state.error = Some(error);
+        }
+    }
+
+    /// The first worker error, if any.
+    pub fn into_result(self) -> Result<(), FluxError> {
+        let state = self.state.into_inner().unwrap_or_else(|e| e.into_inner());
+        match state.error {
+            Some(error) => Err(error),
+            None => Ok(()),
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, State<'a>> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transfer::chunk::chunk_file;
+
+    const MB: u64 = 1024 * 1024;
+
+    #[test]
+    fn pending_chunks_are_handed_out_first() {
+        let mut chunks = chunk_file(4 * MB, 2);
+        chunks[0].completed = true;
+        let schedule = ChunkSchedule::new(&mut chunks, MB, None, None);
+        assert_eq!(schedule.next_job(None), Some(1));
+        // 1 MB left is less than a split on either side
+        assert_eq!(schedule.claim(1, MB), Some((2 * MB, MB)));
+        assert_eq!(schedule.next_job(None), None);
+    }
+
+    #[test]
+    fn idle_worker_steals_the_tail_of_a_running_chunk() {
+        let mut chunks = chunk_file(100 * MB, 1);
+        let schedule = ChunkSchedule::new(&mut chunks, MB, None, None);
+        assert_eq!(schedule.next_job(None), Some(0));
+        assert_eq!(schedule.claim(0, 10 * MB), Some((0, 10 * MB)));
+
+        // No rates known: the remaining 90 MB are shared evenly
+        assert_eq!(schedule.next_job(None), Some(1));
+        assert_eq!(schedule.claim(0, 100 * MB), Some((10 * MB, 45 * MB)));
+        assert_eq!(schedule.claim(0, MB), None);
+        assert_eq!(schedule.claim(1, 100 * MB), Some((55 * MB, 45 * MB)));
+
+        schedule.finish(0, "a".into(), false);
+        schedule.finish(1, "b".into(), false);
+        schedule.into_result().unwrap();
+        assert_eq!(chunks.len(), 2);
+        assert_eq!((chunks[0].offset, chunks[0].length), (0, 55 * MB));
+        assert_eq!((chunks[1].offset, chunks[1].length), (55 * MB, 45 * MB));
+        assert!(chunks.iter().all(|c| c.completed));
+    }
+
+    #[test]
+    fn small_remainders_are_not_split() {
+        let mut chunks = chunk_file(10 * MB, 1);
+        let schedule = ChunkSchedule::new(&mut chunks, 4 * MB, None, None);
+        assert_eq!(schedule.next_job(None), Some(0));
+        schedule.claim(0, 3 * MB);
+        assert_eq!(schedule.next_job(None), None);
+    }
+
+    #[test]
+    fn unfinished_chunks_stay_incomplete_after_an_error() {
+        let mut chunks = chunk_file(4 * MB, 2);
+        let schedule = ChunkSchedule::new(&mut chunks, MB, None, None);
+        assert_eq!(schedule.next_job(None), Some(0));
+        assert_eq!(schedule.claim(0, MB), Some((0, MB)));
+        schedule.fail(FluxError::Cancelled);
+        assert_eq!(schedule.claim(0, MB), None);
+        assert_eq!(schedule.next_job(None), None);
+        schedule.finish(0, "partial".into(), false);
+        assert!(matches!(schedule.into_result(), Err(FluxError::Cancelled)));
+        assert!(chunks.iter().all(|c| !c.completed));
+    }
+
+    #[test]
+    fn pause_stops_handing_out_chunks() {
+        let mut chunks = chunk_file(4 * MB, 2);
+        let pause = PauseSignal::new();
+        let schedule = ChunkSchedule::new(&mut chunks, MB, Some(&pause), None);
+        assert_eq!(schedule.next_job(None), Some(0));
+        pause.request();
+        assert_eq!(schedule.next_job(None), None);
+    }
+}
@@ -0,0 +1,515 @@
+//! Point-in-time source snapshots (`cp --snapshot-source`).
+//!
+//! Copying files that an application is writing (databases, VM images, mail
+//! stores) can produce torn copies. With `--snapshot-source`, the volume
+//! holding the source is snapshotted first and the copy reads from the
+//! snapshot instead of the live files:
+//!
+//! - Linux, btrfs: a read-only snapshot of the enclosing subvolume
+//!   (`btrfs subvolume snapshot -r`), created at its top as
+//!   `.flux-snapshot-<pid>`.
+//! - Linux, LVM: a copy-on-write snapshot of the logical volume
+//!   (`lvcreate --snapshot`), mounted read-only in a temp directory.
+//! - Windows: a VSS shadow copy of the volume, read through its
+//!   `\\?\GLOBALROOT\Device\HarddiskVolumeShadowCopyN` device path.
+//!
+//! All of these need root or an elevated prompt. The snapshot is removed when
+//! the `SourceSnapshot` is dropped, whether or not the copy succeeded.
+//!
+//! `cp --vss` (Windows) is the lenient variant: when no shadow copy can be
+//! made it copies the live files instead, skipping files another program
+//! holds locked (`is_locked_file`) and reporting them at the end.
+
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::error::FluxError;
+use crate::protocol::FluxPath;
+
+/// One step of tearing a snapshot down.
+#[derive(Debug)]
+enum Cleanup {
+    /// Run a command (program and arguments)
+    Run(Vec<OsString>),
+    /// Remove an empty directory (a mount point)
+    RemoveDir(PathBuf),
+}
+
+/// A snapshot of the volume holding a copy source.
+#[derive(Debug)]
+pub struct SourceSnapshot {
+    /// Directory of the live filesystem the snapshot was taken of
+    live_root: PathBuf,
+    /// Where `live_root` appears inside the snapshot
+    snap_root: PathBuf,
+    /// Human-readable description for progress messages
+    description: String,
+    /// Teardown steps, run in reverse order on drop
+    cleanup: Vec<Cleanup>,
+}
+
+impl SourceSnapshot {
+    /// Snapshot the volume holding `source` (which must exist).
+    pub fn create(source: &Path) -> Result<Self, FluxError> {
+        let source = std::fs::canonicalize(source)?;
+        platform::create(&source)
+    }
+
+    /// What was snapshotted, e.g. "btrfs snapshot of /home".
+    pub fn description(&self) -> &str {
+        &self.description
+    }
+
+    /// Path of the live `path` inside the snapshot.
+    pub fn path_for(&self, path: &Path) -> Result<PathBuf, FluxError> {
+        let path = std::fs::canonicalize(path)?;
+        let relative = path.strip_prefix(&self.live_root)?;
+        if relative.as_os_str().is_empty() {
+            Ok(self.snap_root.clone())
+        } else {
+            Ok(self.snap_root.join(relative))
+        }
+    }
+
+    /// Source and destination to copy with when `source` is read from the
+    /// snapshot.
+    ///
+    /// A directory copied without a trailing slash lands in `dest/<name>`.
+    /// The snapshot may name that directory differently (when `source` is the
+    /// snapshotted root itself), so its contents are copied into
+    /// `dest/<name>` instead.
+    pub fn redirect(
+        &self,
+        source: &Path,
+        dest: &Path,
+        is_dir: bool,
+    ) -> Result<(PathBuf, PathBuf), FluxError> {
+        let mapped = self.path_for(source)?;
+        if !is_dir {
+            return Ok((mapped, dest.to_path_buf()));
+        }
+
+        let contents_only = FluxPath::local(source).trailing_slash;
+        let dest = match std::fs::canonicalize(source)?.file_name() {
+            Some(name) if !contents_only => dest.join(name),
+            _ => dest.to_path_buf(),
+        };
+        let mut contents = mapped.into_os_string();
+        contents.push(std::path::MAIN_SEPARATOR_STR);
+        Ok((PathBuf::from(contents), dest))
+    }
+
+    #[cfg(test)]
+    fn fake(live_root: &Path, snap_root: &Path) -> Self {
+        Self {
+            live_root: std::fs::canonicalize(live_root).unwrap(),
+            snap_root: snap_root.to_path_buf(),
+            description: "test snapshot".to_string(),
+            cleanup: Vec::new(),
+        }
+    }
+}
+
+impl Drop for SourceSnapshot {
+    fn drop(&mut self) {
+        while let Some(step) = self.cleanup.pop() {
+            let result = match step {
+                Cleanup::Run(ref argv) => run(argv).map(|_| ()),
+                Cleanup::RemoveDir(ref dir) => std::fs::remove_dir(dir).map_err(FluxError::from),
+            };
+            if let Err(e) = result {
+                eprintln!("Warning: failed to clean up {}: {}", self.description, e);
+            }
+        }
+    }
+}
+
+/// Whether an I/O error means another program holds the file open without
+/// sharing it (Windows `ERROR_SHARING_VIOLATION`/`ERROR_LOCK_VIOLATION`).
+/// Always false elsewhere, where file locks are advisory.
+pub fn is_lock_violation(err: &std::io::Error) -> bool {
+    const ERROR_SHARING_VIOLATION: i32 = 32;
+    const ERROR_LOCK_VIOLATION: i32 = 33;
+    cfg!(windows)
+        && matches!(
+            err.raw_os_error(),
+            Some(ERROR_SHARING_VIOLATION | ERROR_LOCK_VIOLATION)
+        )
+}
+
+/// Whether a copy failed because the source file is locked.
+pub fn is_locked_file(err: &FluxError) -> bool {
+    match err {
+        FluxError::Io { source } => is_lock_violation(source),
+        FluxError::FileLocked { .. } => true,
+        _ => false,
+    }
+}
+
+/// Run a command and return its stdout, failing on a non-zero exit.
+fn run(argv: &[OsString]) -> Result<String, FluxError> {
+    let (program, args) = argv
+        .split_first()
+        .expect("snapshot commands are never empty");
+    let output = Command::new(program).args(args).output().map_err(|e| {
+        FluxError::SnapshotError(format!(
+            "Cannot run '{}': {}",
+            program.to_string_lossy(),
+            e
+        ))
+    })?;
+    if !output.status.success() {
+        return Err(FluxError::SnapshotError(format!(
+            "'{}' failed: {}",
+            program.to_string_lossy(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+/// Build an argument vector from anything path- or string-like.
+fn argv<I, S>(args: I) -> Vec<OsString>
+where
+    I: IntoIterator<Item = S>,
+    S: Into<OsString>,
+{
+    args.into_iter().map(Into::into).collect()
+}
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use std::ffi::OsStr;
+    use std::os::unix::fs::MetadataExt;
+    use std::path::{Path, PathBuf};
+
+    use super::{argv, run, Cleanup, SourceSnapshot};
+    use crate::error::FluxError;
+
+    /// Inode number of every btrfs subvolume root.
+    const BTRFS_SUBVOLUME_ROOT_INODE: u64 = 256;
+
+    pub fn create(source: &Path) -> Result<SourceSnapshot, FluxError> {
+        // SAFETY: geteuid has no preconditions and cannot fail
+        if unsafe { libc::geteuid() } != 0 {
+            return Err(FluxError::SnapshotError(
+                "LVM and btrfs snapshots need root".into(),
+            ));
+        }
+        let mountinfo = std::fs::read_to_string("/proc/self/mountinfo")?;
+        let mounts = parse_mountinfo(&mountinfo);
+        let mount = find_mount(&mounts, source).ok_or_else(|| {
+            FluxError::SnapshotError(format!("No mount found for '{}'", source.display()))
+        })?;
+        if mount.fs_type == "btrfs" {
+            btrfs_snapshot(source)
+        } else {
+            lvm_snapshot(mount)
+        }
+    }
+
+    /// Read-only snapshot of the btrfs subvolume holding `source`, created
+    /// as a hidden directory at the top of that subvolume.
+    fn btrfs_snapshot(source: &Path) -> Result<SourceSnapshot, FluxError> {
+        let mut subvolume = if source.is_dir() {
+            source.to_path_buf()
+        } else {
+            source.parent().unwrap_or(source).to_path_buf()
+        };
+        while std::fs::metadata(&subvolume)?.ino() != BTRFS_SUBVOLUME_ROOT_INODE {
+            subvolume = match subvolume.parent() {
+                Some(parent) => parent.to_path_buf(),
+                None => break,
+            };
+        }
+
+        let snapshot = subvolume.join(format!(".flux-snapshot-{}", std::process::id()));
+        run(&argv([
+            OsStr::new("btrfs"),
+            OsStr::new("subvolume"),
+            OsStr::new("snapshot"),
+            OsStr::new("-r"),
+            subvolume.as_os_str(),
+            snapshot.as_os_str(),
+        ]))?;
+        Ok(SourceSnapshot {
+            description: format!("btrfs snapshot of {}", subvolume.display()),
+            live_root: subvolume,
+            snap_root: snapshot.clone(),
+            cleanup: vec![Cleanup::Run(argv([
+                OsStr::new("btrfs"),
+                OsStr::new("subvolume"),
+                OsStr::new("delete"),
+                snapshot.as_os_str(),
+            ]))],
+        })
+    }
+
+    /// Copy-on-write snapshot of the logical volume mounted at `mount`,
+    /// mounted read-only in a temp directory.
+    fn lvm_snapshot(mount: &MountEntry) -> Result<SourceSnapshot, FluxError> {
+        let lv = run(&argv([
+            "lvs",
+            "--noheadings",
+            "--separator",
+            "/",
+            "-o",
+            "vg_name,lv_name",
+            mount.device.as_str(),
+        ]))
+        .map_err(|_| {
+            FluxError::SnapshotError(format!(
+                "{} ({}) is neither btrfs nor an LVM volume",
+                mount.mount_point.display(),
+                mount.fs_type
+            ))
+        })?;
+        let lv = lv.trim();
+        let vg = lv.split('/').next().unwrap_or_default();
+        let name = format!("flux-snap-{}", std::process::id());
+
+        let mut snapshot = SourceSnapshot {
+            live_root: mount.mount_point.clone(),
+            snap_root: PathBuf::new(),
+            description: format!("LVM snapshot of {}", lv),
+            cleanup: Vec::new(),
+        };
+        run(&argv([
+            "lvcreate",
+            "--snapshot",
+            "--extents",
+            "10%ORIGIN",
+            "--name",
+            name.as_str(),
+            lv,
+        ]))?;
+        snapshot.cleanup.push(Cleanup::Run(argv([
+            "lvremove".to_string(),
+            "--force".to_string(),
+            format!("{}/{}", vg, name),
+        ])));
+
+        let mount_point = std::env::temp_dir().join(&name);
+        std::fs::create_dir_all(&mount_point)?;
+        snapshot.cleanup.push(Cleanup::RemoveDir(mount_point.clone()));
+        // XFS refuses to mount a second filesystem with the same UUID
+        let options = if mount.fs_type == "xfs" { "ro,nouuid" } else { "ro" };
+        let device = format!("/dev/{}/{}", vg, name);
+        run(&argv([
+            OsStr::new("mount"),
+            OsStr::new("-o"),
+            OsStr::new(options),
+            OsStr::new(&device),
+            mount_point.as_os_str(),
+        ]))?;
+        snapshot.cleanup.push(Cleanup::Run(argv([
+            OsStr::new("umount"),
+            mount_point.as_os_str(),
+        ])));
+
+        // A bind mount of a subdirectory shows that directory at the mount point
+        snapshot.snap_root = mount_point.join(mount.root.strip_prefix("/").unwrap_or(&mount.root));
+        Ok(snapshot)
+    }
+
+    /// One line of `/proc/self/mountinfo`.
+    #[derive(Debug, PartialEq)]
+    pub(super) struct MountEntry {
+        /// Directory of the device mounted (for bind mounts)
+        pub root: PathBuf,
+        pub mount_point: PathBuf,
+        pub fs_type: String,
+        pub device: String,
+    }
+
+    pub(super) fn parse_mountinfo(content: &str) -> Vec<MountEntry> {
+        content
+            .lines()
+            .filter_map(|line| {
+                let (fields, tail) = line.split_once(" - ")?;
+                let fields: Vec<&str> = fields.split(' ').collect();
+                let mut tail = tail.split(' ');
+                Some(MountEntry {
+                    root: PathBuf::from(unescape(fields.get(3)?)),
+                    mount_point: PathBuf::from(unescape(fields.get(4)?)),
+                    fs_type: tail.next()?.to_string(),
+                    device: unescape(tail.next()?),
+                })
+            })
+            .collect()
+    }
+
+    /// The mount holding `path`: the longest matching mount point, the most
+    /// recent one if several were mounted over each other.
+    pub(super) fn find_mount<'a>(mounts: &'a [MountEntry], path: &Path) -> Option<&'a MountEntry> {
+        mounts
+            .iter()
+            .filter(|m| path.starts_with(&m.mount_point))
+            .max_by_key(|m| m.mount_point.components().count())
+    }
+
+    /// Decode the octal escapes (`\040` for a space) used in mountinfo.
+    fn unescape(field: &str) -> String {
+        let bytes = field.as_bytes();
+        let mut out = Vec::with_capacity(bytes.len());
+        let mut i = 0;
+        while i < bytes.len() {
+            let escaped = bytes.get(i + 1..i + 4).filter(|_| bytes[i] == b'\\');
+            match escaped.and_then(|d| u8::from_str_radix(std::str::from_utf8(d).ok()?, 8).ok()) {
+                Some(b) => {
+                    out.push(b);
+                    i += 4;
+                }
+                None => {
+                    out.push(bytes[i]);
+                    i += 1;
+                }
+            }
+        }
+        String::from_utf8_lossy(&out).to_string()
+    }
+}
+
+#[cfg(windows)]
+mod platform {
+    use std::path::{Path, PathBuf};
+
+    use super::{argv, run, Cleanup, SourceSnapshot};
+    use crate::error::FluxError;
+
+    pub fn create(source: &Path) -> Result<SourceSnapshot, FluxError> {
+        // The canonical path starts with the volume, e.g. `\\?\C:\`
+        let volume: PathBuf = source.components().take(2).collect();
+        let drive = volume
+            .to_string_lossy()
+            .trim_start_matches(r"\\?\")
+            .to_string();
+        let script = format!(
+            "$r = Invoke-CimMethod -ClassName Win32_ShadowCopy -MethodName Create \
+             -Arguments @{{Volume='{}'; Context='ClientAccessible'}}; \
+             if ($r.ReturnValue -ne 0) {{ Write-Error \"VSS error $($r.ReturnValue)\"; exit 1 }}; \
+             $s = Get-CimInstance Win32_ShadowCopy | Where-Object {{ $_.ID -eq $r.ShadowID }}; \
+             \"$($s.ID)|$($s.DeviceObject)\"",
+            drive
+        );
+        let output = run(&powershell(&script)).map_err(|e| {
+            FluxError::SnapshotError(format!(
+                "Cannot create a VSS shadow copy of {} (run from an elevated prompt): {}",
+                drive, e
+            ))
+        })?;
+        let (id, device) = output.trim().split_once('|').ok_or_else(|| {
+            FluxError::SnapshotError(format!("Unexpected VSS output: {}", output.trim()))
+        })?;
+
+        Ok(SourceSnapshot {
+            live_root: volume,
+            snap_root: PathBuf::from(format!(r"{}\", device)),
+            description: format!("VSS shadow copy of {}", drive),
+            cleanup: vec![Cleanup::Run(powershell(&format!(
+                "Get-CimInstance Win32_ShadowCopy | Where-Object {{ $_.ID -eq '{}' }} | Remove-CimInstance",
+                id
+            )))],
+        })
+    }
+
+    fn powershell(script: &str) -> Vec<std::ffi::OsString> {
+        argv(["powershell", "-NoProfile", "-NonInteractive", "-Command", script])
+    }
+}
+
+#[cfg(not(any(target_os = "linux", windows)))]
+mod platform {
+    use std::path::Path;
+
+    use super::SourceSnapshot;
+    use crate::error::FluxError;
+
+    pub fn create(_source: &Path) -> Result<SourceSnapshot, FluxError> {
+        Err(FluxError::SnapshotError(
+            "--snapshot-source is supported on Linux (btrfs, LVM) and Windows (VSS)".into(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn paths_map_into_the_snapshot() {
+        let live = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(live.path().join("db")).unwrap();
+        std::fs::write(live.path().join("db/data.sqlite"), "x").unwrap();
+        let snapshot = SourceSnapshot::fake(live.path(), Path::new("/snap"));
+
+        assert_eq!(
+            snapshot.path_for(&live.path().join("db/data.sqlite")).unwrap(),
+            Path::new("/snap/db/data.sqlite")
+        );
+        assert_eq!(snapshot.path_for(live.path()).unwrap(), Path::new("/snap"));
+    }
+
+    #[test]
+    fn redirect_keeps_the_destination_folder_name() {
+        let live = tempfile::tempdir().unwrap();
+        let name = live.path().file_name().unwrap().to_owned();
+        let snapshot = SourceSnapshot::fake(live.path(), Path::new("/snap"));
+
+        // The snapshotted root is named differently inside the snapshot
+        let (source, dest) = snapshot.redirect(live.path(), Path::new("/out"), true).unwrap();
+        assert!(source.to_string_lossy().ends_with(std::path::MAIN_SEPARATOR));
+        assert_eq!(dest, Path::new("/out").join(name));
+
+        // Trailing slash: contents go straight into dest
+        let with_slash = PathBuf::from(format!("{}/", live.path().display()));
+        let (_, dest) = snapshot.redirect(&with_slash, Path::new("/out"), true).unwrap();
+        assert_eq!(dest, Path::new("/out"));
+    }
+
+    #[test]
+    fn sharing_violations_count_as_locked_only_on_windows() {
+        let err = FluxError::Io {
+            source: std::io::Error::from_raw_os_error(32),
+        };
+        assert_eq!(is_locked_file(&err), cfg!(windows));
+        let other = FluxError::Io {
+            source: std::io::Error::from_raw_os_error(2),
+        };
+        assert!(!is_locked_file(&other));
+    }
+
+    #[test]
+    fn paths_outside_the_snapshot_are_rejected() {
+        let live = tempfile::tempdir().unwrap();
+        let other = tempfile::tempdir().unwrap();
+        let snapshot = SourceSnapshot::fake(live.path(), Path::new("/snap"));
+        assert!(snapshot.path_for(other.path()).is_err());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn mountinfo_lookup_picks_the_deepest_mount() {
+        use super::platform::{find_mount, parse_mountinfo};
+
+        let mounts = parse_mountinfo(
+            "22 1 253:0 / / rw,relatime shared:1 - ext4 /dev/mapper/vg0-root rw\n\
+             30 22 253:1 / /srv/my\\040data rw shared:2 - xfs /dev/mapper/vg0-data rw\n\
+             31 22 0:40 /@home /home rw shared:3 - btrfs /dev/sda2 rw,subvol=/@home\n",
+        );
+        assert_eq!(mounts.len(), 3);
+
+        let data = find_mount(&mounts, Path::new("/srv/my data/db")).unwrap();
+        assert_eq!(data.mount_point, Path::new("/srv/my data"));
+        assert_eq!(data.fs_type, "xfs");
+        assert_eq!(data.device, "/dev/mapper/vg0-data");
+
+        let home = find_mount(&mounts, Path::new("/home/alice")).unwrap();
+        assert_eq!(home.root, Path::new("/@home"));
+        assert_eq!(
+            find_mount(&mounts, Path::new("/etc/hosts")).unwrap().device,
+            "/dev/mapper/vg0-root"
+        );
+    }
+}
@@ -3,10 +3,24 @@
 //! Provides `TransferStats` for collecting metrics during transfers and
 //! printing consistent completion summaries across all transfer commands
 //! (cp, send, receive, sync, verify).
+//!
+//! `cp --stats` and `sync --stats` add a detailed block after the summary
+//! (`print_stats`), as JSON on stdout with `--json`.
 
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::time::{Duration, Instant};
 
 use bytesize::ByteSize;
+use serde::Serialize;
+
+/// Set by `--json`.
+static JSON: AtomicBool = AtomicBool::new(false);
+
+/// Print `--stats` blocks as JSON on stdout from now on (`--json`).
+pub fn use_json() {
+    JSON.store(true, Ordering::Relaxed);
+}
 
 /// Aggregated transfer statistics for any operation.
 ///
@@ -20,6 +34,36 @@ pub struct TransferStats {
     pub bytes_total: u64,
     pub bytes_done: u64,
     pub started: Instant,
+    /// Files whose transfer was retried, with how often (see `backend::retry`)
+    pub retries: Vec<(PathBuf, u32)>,
+    /// Bytes written to the destination, when not `bytes_done`
+    /// (`--encrypt-to` writes more than it reads)
+    pub bytes_written: Option<u64>,
+    /// Highest rate sampled while the transfer ran (see
+    /// `status::take_peak_rate`); 0 if none was sampled
+    pub peak_bps: u64,
+    /// `--compress` was given. Local copies move the data as it is, so there
+    /// is no ratio to report
+    pub compress: bool,
+}
+
+/// The `--stats` block, as printed with `--json`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StatsReport {
+    pub files_considered: u64,
+    pub files_copied: u64,
+    pub files_skipped: u64,
+    pub files_failed: u64,
+    pub bytes_read: u64,
+    pub bytes_written: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub compression: Option<&'static str>,
+    pub average_bytes_per_sec: u64,
+    pub peak_bytes_per_sec: u64,
+    pub wall_time_secs: f64,
+    /// Highest resident memory of the process, where the OS reports it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub peak_memory_bytes: Option<u64>,
 }
 
 impl TransferStats {
@@ -33,6 +77,10 @@ impl TransferStats {
             bytes_total,
             bytes_done: 0,
             started: Instant::now(),
+            retries: Vec::new(),
+            bytes_written: None,
+            peak_bps: 0,
+            compress: false,
         }
     }
 
@@ -106,6 +154,7 @@ impl TransferStats {
                 self.files_skipped,
             );
         }
+        self.print_retries();
     }
 
     /// Print a single-file completion summary with the filename.
@@ -124,9 +173,135 @@ impl TransferStats {
             secs,
             throughput,
         );
+        self.print_retries();
+    }
+
+    /// The figures of the `--stats` block. The peak is at least the average:
+    /// transfers shorter than a rate sample have no peak of their own.
+    pub fn report(&self) -> StatsReport {
+        let average = self.throughput_bps();
+        StatsReport {
+            files_considered: self.files_total,
+            files_copied: self.files_done,
+            files_skipped: self.files_skipped,
+            files_failed: self.files_failed,
+            bytes_read: self.bytes_done,
+            bytes_written: self.bytes_written.unwrap_or(self.bytes_done),
+            compression: self.compress.then_some("not applied (local copy)"),
+            average_bytes_per_sec: average,
+            peak_bytes_per_sec: self.peak_bps.max(average),
+            wall_time_secs: self.elapsed().as_secs_f64(),
+            peak_memory_bytes: peak_memory(),
+        }
+    }
+
+    /// Print the `--stats` block: to stderr, or as JSON on stdout after
+    /// `use_json`. Printed even when quiet, since it was asked for.
+    /// ```text
+    /// Statistics:
+    ///   Files:        42 considered, 39 copied, 3 skipped, 0 failed
+    ///   Read:         1.2 GB
+    ///   Written:      1.2 GB
+    ///   Throughput:   148.2 MB/s average, 210.5 MB/s peak
+    ///   Wall time:    8.3s
+    ///   Peak memory:  23.4 MB
+    /// ```
+    pub fn print_stats(&self) {
+        let report = self.report();
+        if JSON.load(Ordering::Relaxed) {
+            match serde_json::to_string_pretty(&report) {
+                Ok(json) => println!("{}", json),
+                Err(e) => tracing::warn!("Statistics not printed: {}", e),
+            }
+        } else {
+            eprint!("{}", report);
+        }
+    }
+
+    /// Print which files needed retries, if any:
+    /// ```text
+    /// Retried 2 files: a.bin (3), b.bin (1)
+    /// ```
+    fn print_retries(&self) {
+        if !self.retries.is_empty() {
+            eprintln!("{}", self.retries_line());
+        }
+    }
+
+    fn retries_line(&self) -> String {
+        let files: Vec<String> = self
+            .retries
+            .iter()
+            .map(|(path, count)| format!("{} ({})", path.display(), count))
+            .collect();
+        let noun = if files.len() == 1 { "file" } else { "files" };
+        format!("Retried {} {}: {}", files.len(), noun, files.join(", "))
+    }
+}
+
+impl std::fmt::Display for StatsReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Statistics:")?;
+        writeln!(
+            f,
+            "  Files:        {} considered, {} copied, {} skipped, {} failed",
+            self.files_considered, self.files_copied, self.files_skipped, self.files_failed
+        )?;
+        writeln!(f, "  Read:         {}", ByteSize(self.bytes_read))?;
+        writeln!(f, "  Written:      {}", ByteSize(self.bytes_written))?;
+        if let Some(compression) = self.compression {
+            writeln!(f, "  Compression:  {}", compression)?;
+        }
+        writeln!(
+            f,
+            "  Throughput:   {}/s average, {}/s peak",
+            ByteSize(self.average_bytes_per_sec),
+            ByteSize(self.peak_bytes_per_sec)
+        )?;
+        writeln!(f, "  Wall time:    {:.1}s", self.wall_time_secs)?;
+        if let Some(bytes) = self.peak_memory_bytes {
+            writeln!(f, "  Peak memory:  {}", ByteSize(bytes))?;
+        }
+        Ok(())
+    }
+}
+
+/// Highest resident memory of this process so far: `VmHWM` on Linux, the
+/// `getrusage` maximum on other Unixes, unknown elsewhere.
+pub fn peak_memory() -> Option<u64> {
+    #[cfg(target_os = "linux")]
+    {
+        vm_hwm(&std::fs::read_to_string("/proc/self/status").ok()?)
+    }
+    #[cfg(all(unix, not(target_os = "linux")))]
+    {
+        let mut usage = std::mem::MaybeUninit::<libc::rusage>::zeroed();
+        // SAFETY: getrusage only writes the struct it is given
+        if unsafe { libc::getrusage(libc::RUSAGE_SELF, usage.as_mut_ptr()) } != 0 {
+            return None;
+        }
+        let max = unsafe { usage.assume_init() }.ru_maxrss as u64;
+        // Bytes on macOS, kilobytes on the BSDs
+        Some(if cfg!(target_os = "macos") {
+            max
+        } else {
+            max * 1024
+        })
+    }
+    #[cfg(not(unix))]
+    {
+        None
     }
 }
 
+/// The `VmHWM` line of `/proc/self/status`, in bytes.
+#[cfg(target_os = "linux")]
+fn vm_hwm(status: &str) -> Option<u64> {
+    let line = status.lines().find(|line| line.starts_with("VmHWM:"))?;
+    let kib: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kib * 1024)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -166,6 +341,24 @@ mod tests {
         assert_eq!(stats.files_skipped, 1);
     }
 
+    #[test]
+    fn retries_are_listed_per_file() {
+        let mut stats = TransferStats::new(2, 500);
+        stats.retries = vec![(PathBuf::from("a.bin"), 3), (PathBuf::from("b.bin"), 1)];
+        assert_eq!(stats.retries_line(), "Retried 2 files: a.bin (3), b.bin (1)");
+        stats.retries.pop();
+        assert_eq!(stats.retries_line(), "Retried 1 file: a.bin (3)");
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn peak_memory_comes_from_vm_hwm() {
+        let status = "Name:\tflux\nVmPeak:\t  20000 kB\nVmHWM:\t    8192 kB\nVmRSS:\t 4096 kB\n";
+        assert_eq!(vm_hwm(status), Some(8192 * 1024));
+        assert_eq!(vm_hwm("Name:\tflux\n"), None);
+        assert!(peak_memory().is_some_and(|bytes| bytes > 0));
+    }
+
     #[test]
     fn throughput_zero_on_no_bytes() {
         let stats = TransferStats::new(0, 0);
@@ -173,6 +366,40 @@ mod tests {
         assert_eq!(stats.throughput_bps(), 0);
     }
 
+    #[test]
+    fn report_fills_in_written_bytes_and_peak() {
+        let mut stats = TransferStats::new(3, 300);
+        stats.add_done(100);
+        stats.add_done(200);
+        stats.add_skipped();
+        let report = stats.report();
+        assert_eq!(report.files_considered, 3);
+        assert_eq!(report.files_copied, 2);
+        assert_eq!(report.files_skipped, 1);
+        assert_eq!((report.bytes_read, report.bytes_written), (300, 300));
+        assert!(report.peak_bytes_per_sec >= report.average_bytes_per_sec);
+        assert_eq!(report.compression, None);
+
+        stats.bytes_written = Some(364);
+        stats.peak_bps = u64::MAX;
+        stats.compress = true;
+        let report = stats.report();
+        assert_eq!(report.bytes_written, 364);
+        assert_eq!(report.peak_bytes_per_sec, u64::MAX);
+        let text = report.to_string();
+        assert!(text.contains("3 considered, 2 copied, 1 skipped, 0 failed"));
+        assert!(text.contains("Compression:  not applied"));
+    }
+
+    #[test]
+    fn report_serializes_without_compression_when_not_asked() {
+        let stats = TransferStats::new(1, 10);
+        let json = serde_json::to_value(stats.report()).unwrap();
+        assert_eq!(json["files_considered"], 1);
+        assert!(json.get("compression").is_none());
+        assert!(json["wall_time_secs"].is_f64());
+    }
+
     #[test]
     fn quiet_suppresses_output() {
         let stats = TransferStats::new(1, 100);
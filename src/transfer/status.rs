@@ -0,0 +1,483 @@
+//! Live status of running transfers, for `flux status`.
+//!
+//! While a copy, sync, send or receive runs, a `StatusPublisher` rewrites a
+//! small JSON file in `<data_dir>/status/` once a second (one file per
+//! transfer, named `<pid>-<n>.json`) with the bytes done, the rate over the
+//! last few seconds, the ETA and the file being copied. The file is removed
+//! when the transfer ends. `flux status` lists every file it finds.
+//!
+//! A process that dies cannot remove its file; one that has not been
+//! rewritten for `STALE_AFTER` is ignored and removed by the reader.
+
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+use bytesize::ByteSize;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::cli::args::StatusArgs;
+use crate::error::FluxError;
+use crate::transfer::monitor::TransferMonitor;
+
+/// Directory of the status files in the data directory.
+const STATUS_DIR: &str = "status";
+
+/// How often a running transfer rewrites its status file.
+const PUBLISH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Status files not rewritten for this long belong to a dead process.
+const STALE_AFTER: Duration = Duration::from_secs(10);
+
+/// Samples the rate is averaged over.
+const RATE_SAMPLES: usize = 5;
+
+/// Numbers the status files of one process.
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+/// Highest rate published since the last `take_peak_rate`.
+static PEAK_RATE: AtomicU64 = AtomicU64::new(0);
+
+/// The highest rate, in bytes/sec, any transfer of this process has
+/// published since the last call (for `--stats`), and start over.
+pub fn take_peak_rate() -> u64 {
+    PEAK_RATE.swap(0, Ordering::Relaxed)
+}
+
+/// Progress of a transfer at one point in time.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Sample {
+    pub bytes_done: u64,
+    pub total_bytes: u64,
+    /// File being copied, for transfers of several files
+    pub current_file: Option<String>,
+}
+
+/// Contents of a status file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TransferStatus {
+    pub pid: u32,
+    /// Command running the transfer (`cp`, `sync`, `send`, ...)
+    pub command: String,
+    pub source: String,
+    pub dest: String,
+    pub started: DateTime<Utc>,
+    /// When the file was last rewritten
+    pub updated: DateTime<Utc>,
+    pub total_bytes: u64,
+    pub bytes_done: u64,
+    /// Average over the last few seconds
+    pub bytes_per_sec: u64,
+    /// Seconds left at the current rate, `None` while stalled
+    pub eta_secs: Option<u64>,
+    pub current_file: Option<String>,
+}
+
+impl TransferStatus {
+    fn new(command: &str, source: &str, dest: &str) -> Self {
+        let now = Utc::now();
+        Self {
+            pid: std::process::id(),
+            command: command.to_string(),
+            source: source.to_string(),
+            dest: dest.to_string(),
+            started: now,
+            updated: now,
+            total_bytes: 0,
+            bytes_done: 0,
+            bytes_per_sec: 0,
+            eta_secs: None,
+            current_file: None,
+        }
+    }
+
+    /// Take over `sample`, moving at `bytes_per_sec`.
+    fn update(&mut self, sample: Sample, bytes_per_sec: u64) {
+        self.updated = Utc::now();
+        self.total_bytes = sample.total_bytes;
+        self.bytes_done = sample.bytes_done;
+        self.current_file = sample.current_file;
+        self.bytes_per_sec = bytes_per_sec;
+        self.eta_secs = (bytes_per_sec > 0)
+            .then(|| self.total_bytes.saturating_sub(self.bytes_done).div_ceil(bytes_per_sec));
+    }
+
+    /// Whether the publisher has stopped rewriting the file.
+    fn is_stale(&self, now: DateTime<Utc>) -> bool {
+        now.signed_duration_since(self.updated)
+            .to_std()
+            .is_ok_and(|age| age > STALE_AFTER)
+    }
+
+    /// Two lines: what is transferred, then how far it is.
+    fn render(&self) -> String {
+        let percent = if self.total_bytes > 0 {
+            self.bytes_done as f64 * 100.0 / self.total_bytes as f64
+        } else {
+            0.0
+        };
+        let eta = match self.eta_secs {
+            Some(secs) => format_eta(secs),
+            None => "-".to_string(),
+        };
+        let mut out = format!(
+            "{} (pid {}): {} -> {}\n  {:5.1}%  {} / {}  {}/s  ETA {}",
+            self.command,
+            self.pid,
+            self.source,
+            self.dest,
+            percent,
+            ByteSize(self.bytes_done),
+            ByteSize(self.total_bytes),
+            ByteSize(self.bytes_per_sec),
+            eta
+        );
+        if let Some(ref file) = self.current_file {
+            out.push_str("  ");
+            out.push_str(file);
+        }
+        out
+    }
+}
+
+/// Format an ETA as `1h 02m`, `3m 05s` or `42s`.
+fn format_eta(secs: u64) -> String {
+    if secs >= 3600 {
+        format!("{}h {:02}m", secs / 3600, (secs % 3600) / 60)
+    } else if secs >= 60 {
+        format!("{}m {:02}s", secs / 60, secs % 60)
+    } else {
+        format!("{}s", secs)
+    }
+}
+
+/// Rate over the last `RATE_SAMPLES` samples.
+#[derive(Debug, Default)]
+struct RateWindow {
+    samples: VecDeque<(Instant, u64)>,
+}
+
+impl RateWindow {
+    /// Add the bytes done at `at` and return the rate in bytes/sec.
+    fn push(&mut self, at: Instant, bytes: u64) -> u64 {
+        // A retried file counts down again; measure from there
+        if self.samples.back().is_some_and(|&(_, last)| bytes < last) {
+            self.samples.clear();
+        }
+        self.samples.push_back((at, bytes));
+        if self.samples.len() > RATE_SAMPLES + 1 {
+            self.samples.pop_front();
+        }
+        let (first_at, first) = self.samples[0];
+        let secs = at.duration_since(first_at).as_secs_f64();
+        if secs > 0.0 {
+            ((bytes - first) as f64 / secs) as u64
+        } else {
+            0
+        }
+    }
+}
+
+/// Publishes a transfer's status file while it runs.
+///
+/// Dropping the publisher stops the background thread and removes the file.
+pub struct StatusPublisher {
+    path: PathBuf,
+    stop: Option<mpsc::Sender<()>>,
+    thread: Option<std::thread::JoinHandle<()>>,
+}
+
+impl StatusPublisher {
+    /// Publish the transfer of `source` to `dest` by `command`, taking its
+    /// progress from `sample` once a second.
+    ///
+    /// Returns `None` (after logging why) if the status directory cannot be
+    /// created: the transfer itself goes ahead.
+    pub fn start<F>(command: &str, source: &str, dest: &str, sample: F) -> Option<Self>
+    where
+        F: Fn() -> Sample + Send + 'static,
+    {
+        match crate::config::paths::flux_data_dir() {
+            Ok(data_dir) => Self::start_in(&data_dir, command, source, dest, sample),
+            Err(e) => {
+                tracing::debug!("Transfer status not published: {}", e);
+                None
+            }
+        }
+    }
+
+    fn start_in<F>(
+        data_dir: &Path,
+        command: &str,
+        source: &str,
+        dest: &str,
+        sample: F,
+    ) -> Option<Self>
+    where
+        F: Fn() -> Sample + Send + 'static,
+    {
+        let dir = data_dir.join(STATUS_DIR);
+        if let Err(e) = std::fs::create_dir_all(&dir) {
+            tracing::debug!("Transfer status not published: {}", e);
+            return None;
+        }
+        let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+        let path = dir.join(format!("{}-{}.json", std::process::id(), id));
+
+        let mut status = TransferStatus::new(command, source, dest);
+        let (stop, stopped) = mpsc::channel::<()>();
+        let thread = {
+            let path = path.clone();
+            std::thread::spawn(move || {
+                let mut rate = RateWindow::default();
+                loop {
+                    let sample = sample();
+                    let bytes_per_sec = rate.push(Instant::now(), sample.bytes_done);
+                    PEAK_RATE.fetch_max(bytes_per_sec, Ordering::Relaxed);
+                    status.update(sample, bytes_per_sec);
+                    write_status(&path, &status);
+                    // Stops when the publisher drops its sender
+                    let next = stopped.recv_timeout(PUBLISH_INTERVAL);
+                    if !matches!(next, Err(mpsc::RecvTimeoutError::Timeout)) {
+                        break;
+                    }
+                }
+            })
+        };
+        Some(Self {
+            path,
+            stop: Some(stop),
+            thread: Some(thread),
+        })
+    }
+
+    /// Publish a transfer reporting to `monitor` (`flux cp`, queue entries).
+    pub fn for_monitor(command: &str, monitor: &TransferMonitor) -> Option<Self> {
+        let snapshot = monitor.snapshot();
+        let monitor = monitor.clone();
+        Self::start(command, &snapshot.source, &snapshot.dest, move || {
+            let snapshot = monitor.snapshot();
+            Sample {
+                bytes_done: snapshot.bytes_done,
+                total_bytes: snapshot.total_bytes,
+                current_file: snapshot.current_file,
+            }
+        })
+    }
+
+    /// Publish a single-file transfer tracked by `bar` (send and receive).
+    #[cfg(feature = "net")]
+    pub fn for_bar(
+        command: &str,
+        source: &str,
+        dest: &str,
+        bar: &indicatif::ProgressBar,
+    ) -> Option<Self> {
+        let bar = bar.clone();
+        Self::start(command, source, dest, move || Sample {
+            bytes_done: bar.position(),
+            total_bytes: bar.length().unwrap_or(0),
+            current_file: None,
+        })
+    }
+}
+
+impl Drop for StatusPublisher {
+    fn drop(&mut self) {
+        drop(self.stop.take());
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Write `status` to `path` atomically, so readers never see half a file.
+fn write_status(path: &Path, status: &TransferStatus) {
+    let result = serde_json::to_string(status)
+        .map_err(FluxError::from)
+        .and_then(|json| {
+            let tmp = path.with_extension("json.tmp");
+            std::fs::write(&tmp, json)?;
+            std::fs::rename(&tmp, path)?;
+            Ok(())
+        });
+    if let Err(e) = result {
+        tracing::debug!("Failed to write transfer status {}: {}", path.display(), e);
+    }
+}
+
+/// Transfers running on this machine, oldest first.
+///
+/// Status files left by processes that died are removed.
+pub fn active_transfers(data_dir: &Path) -> Vec<TransferStatus> {
+    let Ok(dir) = std::fs::read_dir(data_dir.join(STATUS_DIR)) else {
+        return Vec::new();
+    };
+    let now = Utc::now();
+    let mut transfers = Vec::new();
+    for entry in dir.filter_map(|e| e.ok()) {
+        let path = entry.path();
+        if path.extension().and_then(|ext| ext.to_str()) != Some("json") {
+            continue;
+        }
+        let status = std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|json| serde_json::from_str::<TransferStatus>(&json).ok());
+        match status {
+            Some(status) if !status.is_stale(now) => transfers.push(status),
+            _ => {
+                tracing::debug!("Removing stale transfer status {}", path.display());
+                let _ = std::fs::remove_file(&path);
+            }
+        }
+    }
+    transfers.sort_by(|a, b| a.started.cmp(&b.started).then(a.pid.cmp(&b.pid)));
+    transfers
+}
+
+/// Execute `flux status`.
+///
+/// With `json`, prints the transfers as a JSON array instead; `--watch`
+/// redraws the list every second until interrupted.
+pub fn execute_status(args: StatusArgs, json: bool) -> Result<(), FluxError> {
+    let data_dir = crate::config::paths::flux_data_dir()?;
+    if !args.watch {
+        return print_transfers(&active_transfers(&data_dir), json);
+    }
+    loop {
+        let transfers = active_transfers(&data_dir);
+        if !json {
+            use crossterm::{cursor, execute, terminal};
+            let mut stdout = std::io::stdout();
+            execute!(
+                stdout,
+                terminal::Clear(terminal::ClearType::All),
+                cursor::MoveTo(0, 0)
+            )?;
+        }
+        print_transfers(&transfers, json)?;
+        std::thread::sleep(PUBLISH_INTERVAL);
+    }
+}
+
+fn print_transfers(transfers: &[TransferStatus], json: bool) -> Result<(), FluxError> {
+    if json {
+        let json = serde_json::to_string(transfers)?;
+        println!("{}", json);
+    } else if transfers.is_empty() {
+        println!("No transfers running");
+    } else {
+        for status in transfers {
+            println!("{}", status.render());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use tempfile::TempDir;
+
+    fn status_files(data_dir: &Path) -> usize {
+        std::fs::read_dir(data_dir.join(STATUS_DIR))
+            .map(|dir| dir.count())
+            .unwrap_or(0)
+    }
+
+    #[test]
+    fn rate_is_averaged_and_restarts_on_retry() {
+        let mut rate = RateWindow::default();
+        let t0 = Instant::now();
+        assert_eq!(rate.push(t0, 0), 0);
+        assert_eq!(rate.push(t0 + Duration::from_secs(1), 1000), 1000);
+        assert_eq!(rate.push(t0 + Duration::from_secs(2), 4000), 2000);
+        // Older samples fall out of the window
+        for secs in 3..=7 {
+            rate.push(t0 + Duration::from_secs(secs), 4000 + (secs - 2) * 500);
+        }
+        assert_eq!(rate.push(t0 + Duration::from_secs(8), 7000), 500);
+        assert_eq!(rate.push(t0 + Duration::from_secs(9), 100), 0);
+    }
+
+    #[test]
+    fn eta_follows_rate() {
+        let mut status = TransferStatus::new("cp", "a", "b");
+        let sample = Sample {
+            bytes_done: 250,
+            total_bytes: 1000,
+            current_file: Some("x.bin".into()),
+        };
+        status.update(sample.clone(), 100);
+        assert_eq!(status.eta_secs, Some(8));
+        status.update(sample, 0);
+        assert_eq!(status.eta_secs, None);
+
+        let rendered = status.render();
+        assert!(rendered.starts_with("cp (pid "), "{}", rendered);
+        assert!(rendered.contains(" 25.0%"), "{}", rendered);
+        assert!(rendered.contains("ETA -"), "{}", rendered);
+        assert!(rendered.ends_with("x.bin"), "{}", rendered);
+    }
+
+    #[test]
+    fn publisher_writes_and_removes_its_file() {
+        let data = TempDir::new().unwrap();
+        let done = Arc::new(Mutex::new(0u64));
+        let publisher = {
+            let done = Arc::clone(&done);
+            StatusPublisher::start_in(data.path(), "sync", "src", "dst", move || Sample {
+                bytes_done: *done.lock().unwrap(),
+                total_bytes: 100,
+                current_file: None,
+            })
+            .unwrap()
+        };
+        *done.lock().unwrap() = 40;
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let status = loop {
+            let found = active_transfers(data.path());
+            if found.first().is_some_and(|s| s.bytes_done == 40) {
+                break found[0].clone();
+            }
+            assert!(Instant::now() < deadline, "status never updated");
+            std::thread::sleep(Duration::from_millis(50));
+        };
+        assert_eq!(status.command, "sync");
+        assert_eq!(status.pid, std::process::id());
+        assert_eq!(status.total_bytes, 100);
+
+        drop(publisher);
+        assert!(active_transfers(data.path()).is_empty());
+        assert_eq!(status_files(data.path()), 0);
+    }
+
+    #[test]
+    fn stale_and_corrupt_files_are_removed() {
+        let data = TempDir::new().unwrap();
+        let dir = data.path().join(STATUS_DIR);
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let mut dead = TransferStatus::new("send", "a", "b");
+        dead.updated = Utc::now() - chrono::Duration::seconds(60);
+        write_status(&dir.join("1-0.json"), &dead);
+        std::fs::write(dir.join("2-0.json"), "{").unwrap();
+        let live = TransferStatus::new("receive", "peer", "out/a");
+        write_status(&dir.join("3-0.json"), &live);
+
+        assert_eq!(active_transfers(data.path()), vec![live]);
+        assert_eq!(status_files(data.path()), 1);
+    }
+
+    #[test]
+    fn no_status_dir_means_nothing_running() {
+        let data = TempDir::new().unwrap();
+        assert!(active_transfers(data.path()).is_empty());
+    }
+}
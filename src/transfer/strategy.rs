@@ -0,0 +1,256 @@
+//! How a copy moves its bytes, chosen from what the backends on both ends
+//! support (`FluxBackend::features`).
+//!
+//! - Parallel chunks read ranges of the source and write them at their
+//!   offsets in the destination, so both ends must support seeking and
+//!   parallel access. Otherwise each file is copied as a single stream.
+//! - `--resume` continues from a manifest's completed chunks, which needs
+//!   seeking on both ends. Without it the copy starts over.
+//! - Permission bits are restored on the copy only when the source reports
+//!   them and the destination can set them. Extended attributes and ACLs
+//!   (`--xattrs`, `--acls`) are copied by the plan's `AttrCopier`.
+//! - Sources that change while they are copied are handled by the plan's
+//!   `SourceWatch` (`--changed-source`).
+//!
+//! The plan is logged at debug level (`-v`) with the reason for each choice;
+//! a `--resume` that cannot be honoured is a warning.
+
+use std::path::Path;
+
+use crate::backend::{BackendFeatures, FluxBackend};
+use crate::transfer::attrs::AttrCopier;
+use crate::transfer::changed::SourceWatch;
+
+/// What a copy does, given the features of its two ends.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Strategy {
+    /// Split large files into chunks copied in parallel
+    pub parallel: bool,
+    /// Continue from the completed chunks of a resume manifest
+    pub resume: bool,
+    /// Restore the source's permission bits on the copy
+    pub permissions: bool,
+}
+
+impl Strategy {
+    /// Choose for a copy from a `source` to a `dest` backend. `resume` is
+    /// whether `--resume` was asked for.
+    pub fn choose(source: &BackendFeatures, dest: &BackendFeatures, resume: bool) -> Self {
+        Self {
+            parallel: ranged(source) && ranged(dest),
+            resume: resume && source.supports_seek && dest.supports_seek,
+            permissions: source.supports_permissions && dest.supports_permissions,
+        }
+    }
+
+    /// One line per choice, naming the end that ruled out the faster option.
+    /// The resume line is only there when `resume` was asked for.
+    fn explain(
+        &self,
+        (source_name, source): (&str, &BackendFeatures),
+        (dest_name, _dest): (&str, &BackendFeatures),
+        resume: bool,
+    ) -> Vec<String> {
+        let lacking = |supported: fn(&BackendFeatures) -> bool| {
+            if supported(source) {
+                dest_name
+            } else {
+                source_name
+            }
+        };
+        let mut lines = vec![if self.parallel {
+            "chunks: parallel (both ends support ranged reads and writes)".to_string()
+        } else {
+            format!("chunks: single stream ({} has no parallel ranged I/O)", lacking(ranged))
+        }];
+        if resume {
+            lines.push(if self.resume {
+                "resume: from completed chunks".to_string()
+            } else {
+                format!(
+                    "resume: not possible, {} cannot seek; copying from the start",
+                    lacking(|f| f.supports_seek)
+                )
+            });
+        }
+        lines.push(if self.permissions {
+            "permissions: restored from the source".to_string()
+        } else {
+            format!(
+                "permissions: not restored ({} has no permission bits)",
+                lacking(|f| f.supports_permissions)
+            )
+        });
+        lines
+    }
+}
+
+/// Whether ranges of a file can be read or written independently.
+fn ranged(features: &BackendFeatures) -> bool {
+    features.supports_seek && features.supports_parallel
+}
+
+/// The backends of a copy and the strategy chosen for them.
+pub struct CopyPlan {
+    source: Box<dyn FluxBackend>,
+    dest: Box<dyn FluxBackend>,
+    pub strategy: Strategy,
+    /// Extended attributes and ACLs copied after each file
+    pub attributes: AttrCopier,
+    /// What a source changing mid-copy leads to
+    pub changes: SourceWatch,
+}
+
+impl CopyPlan {
+    /// Choose the strategy for a copy between the `source` and `dest`
+    /// backends (named by protocol) and log it.
+    pub fn new(
+        (source_name, source): (&str, Box<dyn FluxBackend>),
+        (dest_name, dest): (&str, Box<dyn FluxBackend>),
+        resume: bool,
+    ) -> Self {
+        let (source_features, dest_features) = (source.features(), dest.features());
+        let strategy = Strategy::choose(&source_features, &dest_features, resume);
+        tracing::debug!("Copy plan for {} -> {}:", source_name, dest_name);
+        let lines = strategy.explain(
+            (source_name, &source_features),
+            (dest_name, &dest_features),
+            resume,
+        );
+        for line in lines {
+            if resume && !strategy.resume && line.starts_with("resume:") {
+                tracing::warn!("{}", line);
+            } else {
+                tracing::debug!("  {}", line);
+            }
+        }
+        Self {
+            source,
+            dest,
+            strategy,
+            attributes: AttrCopier::default(),
+            changes: SourceWatch::default(),
+        }
+    }
+
+    /// Copy extended attributes and ACLs with `attributes` (local copies).
+    pub fn attributes(mut self, attributes: AttrCopier) -> Self {
+        self.attributes = attributes;
+        self
+    }
+
+    /// Handle sources that change while they are copied with `changes`.
+    pub fn changes(mut self, changes: SourceWatch) -> Self {
+        self.changes = changes;
+        self
+    }
+
+    /// Give `dest` the permission bits of `source`, if the strategy restores
+    /// them. Failures are logged: the data itself has been copied.
+    pub fn restore_permissions(&self, source: &Path, dest: &Path) {
+        if !self.strategy.permissions {
+            return;
+        }
+        let result = self.source.stat(source).and_then(|stat| match stat.permissions {
+            Some(mode) => self.dest.set_permissions(dest, mode & 0o7777),
+            None => Ok(()),
+        });
+        if let Err(e) = result {
+            tracing::warn!("Cannot restore permissions of {}: {}", dest.display(), e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn features(seek: bool, parallel: bool, permissions: bool) -> BackendFeatures {
+        BackendFeatures {
+            supports_seek: seek,
+            supports_parallel: parallel,
+            supports_permissions: permissions,
+            supports_remove: true,
+            supports_rename: true,
+            supports_set_mtime: true,
+        }
+    }
+
+    #[test]
+    fn local_copies_use_every_capability() {
+        let local = features(true, true, true);
+        let strategy = Strategy::choose(&local, &local, true);
+        assert_eq!(
+            strategy,
+            Strategy {
+                parallel: true,
+                resume: true,
+                permissions: true,
+            }
+        );
+        let lines = strategy.explain(("local", &local), ("local", &local), true);
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("chunks: parallel"));
+        assert_eq!(lines[1], "resume: from completed chunks");
+    }
+
+    #[test]
+    fn missing_capabilities_fall_back_and_name_the_backend() {
+        let local = features(true, true, true);
+        let webdav = features(false, false, false);
+        let strategy = Strategy::choose(&local, &webdav, true);
+        assert_eq!(
+            strategy,
+            Strategy {
+                parallel: false,
+                resume: false,
+                permissions: false,
+            }
+        );
+        let lines = strategy.explain(("local", &local), ("webdav", &webdav), true);
+        assert_eq!(
+            lines,
+            [
+                "chunks: single stream (webdav has no parallel ranged I/O)",
+                "resume: not possible, webdav cannot seek; copying from the start",
+                "permissions: not restored (webdav has no permission bits)",
+            ]
+        );
+    }
+
+    #[test]
+    fn sequential_backends_still_resume_and_keep_permissions() {
+        let local = features(true, true, true);
+        let sftp = features(true, false, true);
+        let strategy = Strategy::choose(&sftp, &local, false);
+        assert!(!strategy.parallel);
+        assert!(!strategy.resume, "resume was not asked for");
+        assert!(strategy.permissions);
+        let lines = strategy.explain(("sftp", &sftp), ("local", &local), false);
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].contains("sftp"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn permissions_are_restored_through_the_backends() {
+        use crate::backend::local::LocalBackend;
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let source = dir.path().join("run.sh");
+        let dest = dir.path().join("copy.sh");
+        std::fs::write(&source, "#!/bin/sh").unwrap();
+        std::fs::write(&dest, "#!/bin/sh").unwrap();
+        std::fs::set_permissions(&source, std::fs::Permissions::from_mode(0o750)).unwrap();
+
+        let plan = CopyPlan::new(
+            ("local", Box::new(LocalBackend::new())),
+            ("local", Box::new(LocalBackend::new())),
+            false,
+        );
+        plan.restore_permissions(&source, &dest);
+        let mode = std::fs::metadata(&dest).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o750);
+    }
+}
@@ -0,0 +1,615 @@
+//! Piped copies: `-` as the source (stdin) or destination (stdout) of
+//! `flux cp`.
+//!
+//! `pg_dump | flux cp - sftp://host/backups/db.sql` and
+//! `flux cp webdav://server/file.bin - | tar x` move one stream of bytes,
+//! so the other end is a single file on any backend, read or written
+//! through `FluxBackend::open_read`/`open_write`. Nothing can seek in a pipe:
+//! there are no chunks and no resume, and progress counts bytes (with a
+//! percentage only when the source's size is known).
+//!
+//! An existing destination file is replaced, as with shell redirection.
+//! A local destination directory gets the source's file name; stdin has
+//! none, so it must be written to a named file.
+//!
+//! Single-file copies to or from a network backend (`flux cp big.iso
+//! sftp://nas/isos/big.iso`) take the same path, with a file at both ends,
+//! and so do `cp -r` directory copies with a network end, one file after
+//! the other. Ends are `FluxPath`s, so a source directory with a trailing
+//! slash has its contents copied on every backend, as locally.
+//!
+//! `--encrypt-to` seals the stream before it reaches the backend's writer,
+//! so a plaintext protocol (WebDAV over HTTP, SMB without signing) only
+//! ever carries ciphertext; `--decrypt` opens it again on a later read
+//! through the same backend. Both use the at-rest format of
+//! `security::at_rest`, so `flux decrypt` also reads what was sent.
+//!
+//! `--atomic` writes a backend file to its `atomic::temp_path` and renames
+//! it into place once complete; on a backend that cannot rename, the file
+//! is written in place with a warning.
+
+use std::io::{BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+
+use indicatif::ProgressBar;
+use x25519_dalek::PublicKey;
+
+use crate::backend::{create_backend, FluxBackend};
+use crate::cli::args::CpArgs;
+use crate::config::paths::flux_config_dir;
+use crate::error::FluxError;
+use crate::progress::bar::{create_stream_progress, create_transfer_progress};
+use crate::protocol::FluxPath;
+use crate::security::at_rest::{self, DecryptingReader, EncryptingReader};
+use crate::security::crypto::DeviceIdentity;
+use crate::transfer::atomic::{temp_path, AtomicFile};
+use crate::transfer::cancel::{self, PartialFile};
+use crate::transfer::checksum::ChecksumHasher;
+use crate::transfer::filter::TransferFilter;
+use crate::transfer::history::HistoryRecord;
+use crate::transfer::monitor::TransferMonitor;
+use crate::transfer::stats::TransferStats;
+use crate::transfer::throttle::{parse_bandwidth, ThrottledReader};
+
+/// The argument that stands for stdin (as source) or stdout (as destination).
+pub const STDIO: &str = "-";
+
+/// Read and write buffer size.
+const BUF_SIZE: usize = 256 * 1024;
+
+/// Whether a `cp` source or destination argument is `-`.
+pub fn is_stdio(arg: &str) -> bool {
+    arg == STDIO
+}
+
+/// Copy between stdin/stdout and a file, for a `cp` whose source or
+/// destination is `-`, or between two files (or with `-r` two directories)
+/// when one is on a network backend. `record` gets the bytes copied.
+pub fn copy_stream(
+    args: &CpArgs,
+    src: &FluxPath,
+    dst: &FluxPath,
+    quiet: bool,
+    record: &mut HistoryRecord,
+    monitor: Option<&TransferMonitor>,
+) -> Result<(), FluxError> {
+    check_options(args)?;
+    let from_stdin = is_stdio(&args.source);
+    let to_stdout = is_stdio(&args.dest);
+    if args.verify && to_stdout {
+        return Err(invalid("--verify cannot read back a copy written to stdout"));
+    }
+    let crypt = Crypt::from_args(args)?;
+
+    // Source: stdin, or one file on any backend (whose size is then known)
+    let source = if from_stdin {
+        None
+    } else {
+        let (backend, path) = open_end(src)?;
+        let stat = backend.stat(&path)?;
+        if stat.is_dir {
+            if !args.recursive {
+                return Err(FluxError::IsDirectory { path });
+            }
+            if to_stdout {
+                return Err(invalid("A directory cannot be copied to stdout"));
+            }
+            let from = (src, backend.as_ref());
+            return copy_tree(args, from, dst, &crypt, quiet, record, monitor);
+        }
+        Some((backend, path, stat.size))
+    };
+    let (name, from) = match &source {
+        Some((_, path, _)) => (
+            src.name().unwrap_or_else(|| record.source.clone()),
+            path.display().to_string(),
+        ),
+        None => ("stdin".to_string(), "stdin".to_string()),
+    };
+    // Progress counts the bytes written
+    let total = source.as_ref().and_then(|&(_, _, size)| crypt.written_len(size));
+
+    // Destination: stdout, or one file on any backend
+    let dest = if to_stdout {
+        None
+    } else {
+        let (backend, mut path) = open_end(dst)?;
+        if dst.is_local() && path.is_dir() {
+            if from_stdin {
+                return Err(invalid(&format!(
+                    "{} is a directory; name the file to write stdin to",
+                    path.display()
+                )));
+            }
+            path = crypt.dest_name(&path.join(&name))?;
+        }
+        warn_if_not_atomic(args, backend.as_ref(), dst.is_local());
+        Some((backend, path))
+    };
+
+    if args.dry_run {
+        let size = total.map_or_else(|| "size unknown".to_string(), |s| format!("{} bytes", s));
+        let to = dest
+            .as_ref()
+            .map_or_else(|| "stdout".to_string(), |(_, path)| path.display().to_string());
+        eprintln!("[dry-run] copy {} -> {} ({})", from, to, size);
+        return Ok(());
+    }
+    if args.compress {
+        tracing::debug!("--compress has no effect on piped copies");
+    }
+
+    let reader: Box<dyn Read + Send> = match &source {
+        Some((backend, path, _)) => backend.open_read(path)?,
+        None => Box::new(std::io::stdin()),
+    };
+    let mut reader = crypt.wrap(throttle(args, reader)?)?;
+
+    let progress = create_stream_progress(total, quiet);
+    if let Some(monitor) = monitor {
+        monitor.start(total.unwrap_or(0), 0);
+    }
+    let bytes = match &dest {
+        Some((backend, path)) => write_file(
+            args,
+            &mut reader,
+            (backend.as_ref(), path, dst.is_local()),
+            &progress,
+            monitor,
+            record,
+        )?,
+        None => {
+            let mut stdout = BufWriter::with_capacity(BUF_SIZE, std::io::stdout());
+            pump(&mut reader, &mut stdout, None, &progress, monitor)?
+        }
+    };
+    progress.finish_and_clear();
+    if record.verified == Some(true) && !quiet {
+        eprintln!("Integrity verified ({})", args.checksum.label());
+    }
+
+    let mut stats = TransferStats::new(1, bytes);
+    stats.started = record.started;
+    stats.add_done(bytes);
+    stats.retries = crate::backend::retry::take_counts();
+    stats.print_file_summary(&name, quiet);
+    if args.stats {
+        stats.peak_bps = crate::transfer::status::take_peak_rate();
+        if to_stdout {
+            // stdout carries the data; the block goes to stderr even with --json
+            eprint!("{}", stats.report());
+        } else {
+            stats.print_stats();
+        }
+    }
+    record.bytes = bytes;
+    record.files = 1;
+    Ok(())
+}
+
+/// Copy the directory `src` into `dst` one file at a time, for `cp -r`
+/// with a network end.
+///
+/// As with local copies, `src` without a trailing slash lands in
+/// `dst/<name>`, and with one has its contents copied into `dst`. Empty
+/// directories are not created, and the first file that fails stops the
+/// copy.
+fn copy_tree(
+    args: &CpArgs,
+    (src, src_backend): (&FluxPath, &dyn FluxBackend),
+    dst: &FluxPath,
+    crypt: &Crypt,
+    quiet: bool,
+    record: &mut HistoryRecord,
+    monitor: Option<&TransferMonitor>,
+) -> Result<(), FluxError> {
+    let flux_config = crate::config::types::load_config().unwrap_or_default();
+    let filter = TransferFilter::new(&args.exclude, &args.include)?
+        .skip_hidden(args.hidden.skip_hidden(flux_config.exclude_hidden))
+        .skip_system(!args.hidden.include_system);
+    let mut files = walk(src_backend, &src.path, &filter)?;
+    if let Crypt::Decrypt(_) = crypt {
+        // As `flux decrypt DIR`: only the encrypted files
+        files.retain(|(relative, _)| at_rest::decrypted_path(relative).is_some());
+    }
+    let (dst_backend, dst_root) = open_end(dst)?;
+    warn_if_not_atomic(args, dst_backend.as_ref(), dst.is_local());
+    let root = src.target_in(&dst_root);
+    let source_bytes: u64 = files.iter().map(|&(_, size)| size).sum();
+
+    if args.dry_run {
+        for (relative, size) in &files {
+            eprintln!(
+                "[dry-run] copy {} -> {} ({} bytes)",
+                src.path.join(relative).display(),
+                crypt.dest_name(&root.join(relative))?.display(),
+                size
+            );
+        }
+        eprintln!(
+            "[dry-run] Would copy {} file(s) ({} bytes total)",
+            files.len(),
+            source_bytes
+        );
+        return Ok(());
+    }
+
+    let total: u64 = files
+        .iter()
+        .map(|&(_, size)| crypt.written_len(size).unwrap_or(size))
+        .sum();
+    let progress = create_transfer_progress(total, quiet);
+    if let Some(monitor) = monitor {
+        monitor.start(total, 0);
+    }
+    let mut stats = TransferStats::new(files.len() as u64, total);
+    stats.started = record.started;
+    for (relative, _) in &files {
+        let to = crypt.dest_name(&root.join(relative))?;
+        if let Some(parent) = to.parent().filter(|p| !p.as_os_str().is_empty()) {
+            dst_backend.create_dir_all(parent)?;
+        }
+        if let Some(monitor) = monitor {
+            monitor.set_current_file(&relative.to_string_lossy());
+        }
+        let reader = src_backend.open_read(&src.path.join(relative))?;
+        let mut reader = crypt.wrap(throttle(args, reader)?)?;
+        let bytes = write_file(
+            args,
+            &mut reader,
+            (dst_backend.as_ref(), &to, dst.is_local()),
+            &progress,
+            monitor,
+            record,
+        )?;
+        stats.add_done(bytes);
+    }
+    progress.finish_and_clear();
+    if record.verified == Some(true) && !quiet {
+        eprintln!("Integrity verified ({})", args.checksum.label());
+    }
+
+    stats.retries = crate::backend::retry::take_counts();
+    stats.print_summary(quiet);
+    if args.stats {
+        stats.peak_bps = crate::transfer::status::take_peak_rate();
+        stats.print_stats();
+    }
+    record.bytes = stats.bytes_done;
+    record.files = stats.files_done;
+    Ok(())
+}
+
+/// Files below `root` on `backend` that `filter` lets through, relative to
+/// `root` and with their sizes, in path order.
+fn walk(
+    backend: &dyn FluxBackend,
+    root: &Path,
+    filter: &TransferFilter,
+) -> Result<Vec<(PathBuf, u64)>, FluxError> {
+    let mut files = Vec::new();
+    let mut dirs = vec![PathBuf::new()];
+    while let Some(dir) = dirs.pop() {
+        let listed = if dir.as_os_str().is_empty() {
+            root.to_path_buf()
+        } else {
+            root.join(&dir)
+        };
+        for entry in backend.list_dir(&listed)? {
+            // Backends report entry paths differently (full, absolute or
+            // bare name), so rebuild them from the file name
+            let Some(name) = entry.path.file_name() else {
+                continue;
+            };
+            let relative = dir.join(name);
+            if entry.stat.is_dir {
+                if !filter.excludes_dir_path(&relative) {
+                    dirs.push(relative);
+                }
+            } else if filter.should_transfer(&relative) {
+                files.push((relative, entry.stat.size));
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Write everything from `reader` to a file on a backend (`dest` is the
+/// backend, the path and whether it is local): through a temp file with
+/// `--atomic` (on a backend, only if it can rename), and read back and
+/// compared with `--verify`, which sets `record.verified`. Returns the bytes
+/// written.
+fn write_file(
+    args: &CpArgs,
+    reader: &mut dyn Read,
+    dest: (&dyn FluxBackend, &Path, bool),
+    progress: &ProgressBar,
+    monitor: Option<&TransferMonitor>,
+    record: &mut HistoryRecord,
+) -> Result<u64, FluxError> {
+    let (backend, path, local) = dest;
+    // --atomic: the destination file appears only once complete
+    let atomic_file = (args.atomic && local).then(|| AtomicFile::new(path));
+    let backend_temp = (args.atomic && !local && backend.features().supports_rename)
+        .then(|| BackendTemp::new(backend, path));
+    let write_path = match (&atomic_file, &backend_temp) {
+        (Some(file), _) => file.path(),
+        (None, Some(temp)) => &temp.temp,
+        (None, None) => path,
+    }
+    .to_path_buf();
+    // A cancelled copy to a local file removes what it wrote
+    let partial = (atomic_file.is_none() && local).then(|| PartialFile::new(&write_path));
+
+    let mut writer = backend.open_write(&write_path)?;
+    let mut hasher = args.verify.then(|| args.checksum.hasher());
+    let bytes = pump(
+        reader,
+        &mut writer,
+        hasher.as_mut().map(|h| h.as_mut() as &mut dyn ChecksumHasher),
+        progress,
+        monitor,
+    )?;
+    drop(writer);
+
+    // --verify: read the written file back and compare with what was sent
+    if let Some(hasher) = hasher {
+        let mut copy = args.checksum.hasher();
+        let mut read_back = backend.open_read(&write_path)?;
+        let hidden = ProgressBar::hidden();
+        pump(&mut read_back, &mut std::io::sink(), Some(copy.as_mut()), &hidden, None)?;
+        let (expected, actual) = (hasher.finish_hex(), copy.finish_hex());
+        if expected != actual {
+            record.verified = Some(false);
+            return Err(FluxError::ChecksumMismatch {
+                path: write_path,
+                expected,
+                actual,
+            });
+        }
+        record.verified = Some(true);
+    }
+    if let Some(file) = atomic_file {
+        file.commit()?;
+    }
+    if let Some(temp) = backend_temp {
+        temp.commit()?;
+    }
+    if let Some(partial) = partial {
+        partial.done();
+    }
+    Ok(bytes)
+}
+
+/// `--atomic` through a backend: the file is written to its temp path and
+/// renamed over the destination by `commit`. Dropped uncommitted (a failed
+/// write or verification), the temp file is removed.
+struct BackendTemp<'a> {
+    backend: &'a dyn FluxBackend,
+    temp: PathBuf,
+    dest: &'a Path,
+    committed: bool,
+}
+
+impl<'a> BackendTemp<'a> {
+    fn new(backend: &'a dyn FluxBackend, dest: &'a Path) -> Self {
+        BackendTemp {
+            backend,
+            temp: temp_path(dest),
+            dest,
+            committed: false,
+        }
+    }
+
+    /// Move the complete file into place.
+    fn commit(mut self) -> Result<(), FluxError> {
+        self.backend.rename(&self.temp, self.dest)?;
+        self.committed = true;
+        Ok(())
+    }
+}
+
+impl Drop for BackendTemp<'_> {
+    fn drop(&mut self) {
+        if self.committed || !self.backend.features().supports_remove {
+            return;
+        }
+        if let Err(e) = self.backend.remove(&self.temp) {
+            tracing::debug!("Could not remove {}: {}", self.temp.display(), e);
+        }
+    }
+}
+
+/// `--atomic` to a backend that cannot rename: say the files are written
+/// in place.
+fn warn_if_not_atomic(args: &CpArgs, backend: &dyn FluxBackend, local: bool) {
+    if args.atomic && !local && !backend.features().supports_rename {
+        eprintln!("Warning: the destination cannot rename files, so --atomic writes them in place");
+    }
+}
+
+/// `--limit`: throttle `reader` to the given rate.
+fn throttle(
+    args: &CpArgs,
+    reader: Box<dyn Read + Send>,
+) -> Result<Box<dyn Read + Send>, FluxError> {
+    match args.limit {
+        Some(ref limit) => Ok(Box::new(ThrottledReader::new(reader, parse_bandwidth(limit)?))),
+        None => Ok(reader),
+    }
+}
+
+/// What `--encrypt-to` or `--decrypt` does to the bytes on the way.
+enum Crypt {
+    Plain,
+    /// Seal to this recipient key before the destination's writer
+    Encrypt(PublicKey),
+    /// Open with this device's identity after the source's reader
+    Decrypt(DeviceIdentity),
+}
+
+impl Crypt {
+    fn from_args(args: &CpArgs) -> Result<Self, FluxError> {
+        if let Some(key) = &args.encrypt_to {
+            Ok(Crypt::Encrypt(at_rest::load_recipient(key)?))
+        } else if args.decrypt {
+            Ok(Crypt::Decrypt(DeviceIdentity::load_or_create(&flux_config_dir()?)?))
+        } else {
+            Ok(Crypt::Plain)
+        }
+    }
+
+    /// Bytes written for a source of `size` bytes (`None` for a size no
+    /// encrypted file has).
+    fn written_len(&self, size: u64) -> Option<u64> {
+        match self {
+            Crypt::Plain => Some(size),
+            Crypt::Encrypt(_) => Some(at_rest::encrypted_len(size)),
+            Crypt::Decrypt(_) => at_rest::plaintext_len(size),
+        }
+    }
+
+    /// Name of a file written into a directory: with `.fluxenc` added when
+    /// encrypting, removed when decrypting.
+    fn dest_name(&self, path: &Path) -> Result<PathBuf, FluxError> {
+        match self {
+            Crypt::Plain => Ok(path.to_path_buf()),
+            Crypt::Encrypt(_) => Ok(at_rest::encrypted_path(path)),
+            Crypt::Decrypt(_) => at_rest::decrypted_path(path).ok_or_else(|| {
+                invalid(&format!(
+                    "'{}' does not end in {}; give an output file name",
+                    path.file_name().unwrap_or_default().to_string_lossy(),
+                    at_rest::ENCRYPTED_SUFFIX
+                ))
+            }),
+        }
+    }
+
+    fn wrap(&self, reader: Box<dyn Read + Send>) -> Result<Box<dyn Read + Send>, FluxError> {
+        Ok(match self {
+            Crypt::Plain => reader,
+            Crypt::Encrypt(recipient) => Box::new(EncryptingReader::new(reader, recipient)?),
+            Crypt::Decrypt(identity) => Box::new(DecryptingReader::new(reader, identity)?),
+        })
+    }
+}
+
+/// Reject options that need to seek in or walk the source or destination,
+/// or that only the local engine implements.
+fn check_options(args: &CpArgs) -> Result<(), FluxError> {
+    let unsupported = [
+        ("--resume", args.resume),
+        ("--dedup", args.dedup.is_some()),
+        ("--hard-links", args.hard_links),
+        ("--snapshot-source", args.snapshot_source),
+        ("--vss", args.vss),
+        ("--retry-from", args.retry_from.is_some()),
+    ];
+    match unsupported.iter().find(|&&(_, set)| set) {
+        Some((flag, _)) => Err(invalid(&format!(
+            "{} cannot be used when copying from stdin or to stdout ('-') or over a network backend",
+            flag
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// Connect to the backend holding `path`, and the path on it.
+fn open_end(path: &FluxPath) -> Result<(Box<dyn FluxBackend>, PathBuf), FluxError> {
+    Ok((create_backend(&path.protocol)?, path.path.clone()))
+}
+
+/// Copy everything from `reader` to `writer`, feeding `hasher` and the
+/// progress. Returns the number of bytes copied.
+fn pump(
+    reader: &mut dyn Read,
+    writer: &mut dyn Write,
+    mut hasher: Option<&mut dyn ChecksumHasher>,
+    progress: &ProgressBar,
+    monitor: Option<&TransferMonitor>,
+) -> Result<u64, FluxError> {
+    let mut buf = vec![0u8; BUF_SIZE];
+    let mut bytes = 0u64;
+    loop {
+        cancel::check()?;
+        let n = match reader.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(at_rest::from_io(e)),
+        };
+        writer.write_all(&buf[..n])?;
+        if let Some(hasher) = hasher.as_mut() {
+            hasher.update(&buf[..n]);
+        }
+        bytes += n as u64;
+        progress.inc(n as u64);
+        if let Some(monitor) = monitor {
+            monitor.add_bytes(n as u64);
+        }
+    }
+    // Ctrl+C in a pipeline also ends the process writing to stdin
+    cancel::check()?;
+    writer.flush()?;
+    Ok(bytes)
+}
+
+/// Error for a piped copy that cannot be done as asked.
+fn invalid(message: &str) -> FluxError {
+    FluxError::Io {
+        source: std::io::Error::new(std::io::ErrorKind::InvalidInput, message.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_a_lone_dash_is_stdio() {
+        assert!(is_stdio("-"));
+        assert!(!is_stdio("--"));
+        assert!(!is_stdio("./-"));
+        assert!(!is_stdio("file-name"));
+    }
+
+    #[test]
+    fn pump_counts_and_hashes_bytes() {
+        let data = b"streamed through a pipe".repeat(50_000);
+        let mut out = Vec::new();
+        let mut hasher = crate::transfer::checksum::ChecksumAlgorithm::Blake3.hasher();
+        let bytes = pump(
+            &mut data.as_slice(),
+            &mut out,
+            Some(hasher.as_mut()),
+            &ProgressBar::hidden(),
+            None,
+        )
+        .unwrap();
+        assert_eq!(bytes, data.len() as u64);
+        assert_eq!(out, data);
+        assert_eq!(hasher.finish_hex(), blake3::hash(&data).to_hex().to_string());
+    }
+
+    #[test]
+    fn backend_temp_renames_on_commit_and_removes_otherwise() {
+        let dir = tempfile::tempdir().unwrap();
+        let backend = crate::backend::local::LocalBackend::new();
+        let dest = dir.path().join("out.bin");
+        std::fs::write(&dest, b"old").unwrap();
+
+        let temp = BackendTemp::new(&backend, &dest);
+        std::fs::write(&temp.temp, b"new").unwrap();
+        assert_eq!(std::fs::read(&dest).unwrap(), b"old");
+        temp.commit().unwrap();
+        assert_eq!(std::fs::read(&dest).unwrap(), b"new");
+        assert!(!temp_path(&dest).exists());
+
+        let temp = BackendTemp::new(&backend, &dest);
+        std::fs::write(&temp.temp, b"torn").unwrap();
+        drop(temp);
+        assert!(!temp_path(&dest).exists());
+        assert_eq!(std::fs::read(&dest).unwrap(), b"new");
+    }
+}
@@ -6,9 +6,17 @@
 //! worth), and each read/write consumes tokens. When tokens are exhausted, the
 //! thread sleeps until enough tokens accumulate.
 //!
+//! `SharedLimiter` is the same bucket behind a mutex, shared by the threads of
+//! a chunked copy, with a rate that can be changed while the copy runs.
+//! `AdaptiveRate` picks that rate from RTT samples for `--adaptive-limit`.
+//!
 //! `parse_bandwidth` converts human-readable strings like "10MB/s" into bytes/sec.
 
+#[cfg(feature = "net")]
+use std::collections::VecDeque;
 use std::io::{self, Read, Write};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
 use std::time::{Duration, Instant};
 
 use crate::error::FluxError;
@@ -173,6 +181,165 @@ impl<W: Write> Write for ThrottledWriter<W> {
     }
 }
 
+/// Longest single sleep in `SharedLimiter::acquire`, so a raised or removed
+/// limit takes effect quickly.
+const MAX_LIMITER_SLEEP: Duration = Duration::from_millis(100);
+
+/// Token bucket shared across threads, with an adjustable rate.
+///
+/// A rate of 0 means unlimited. Unlike `ThrottledReader`, `acquire` is called
+/// after the bytes moved: the bucket may go into debt, and later callers wait
+/// until it is paid off. That keeps the average rate without splitting reads.
+#[derive(Debug)]
+pub struct SharedLimiter {
+    bytes_per_sec: AtomicU64,
+    /// Available tokens (negative while in debt) and the last refill time
+    bucket: Mutex<(i64, Instant)>,
+}
+
+impl SharedLimiter {
+    pub fn new(bytes_per_sec: Option<u64>) -> Self {
+        let rate = bytes_per_sec.unwrap_or(0);
+        Self {
+            bytes_per_sec: AtomicU64::new(rate),
+            bucket: Mutex::new((rate.min(i64::MAX as u64) as i64, Instant::now())),
+        }
+    }
+
+    /// Current limit, `None` if unlimited.
+    pub fn rate(&self) -> Option<u64> {
+        match self.bytes_per_sec.load(Ordering::Relaxed) {
+            0 => None,
+            rate => Some(rate),
+        }
+    }
+
+    /// Change the limit; applies to the next `acquire`.
+    pub fn set_rate(&self, bytes_per_sec: Option<u64>) {
+        self.bytes_per_sec
+            .store(bytes_per_sec.unwrap_or(0), Ordering::Relaxed);
+        // Start the new rate with a clean bucket instead of old debt
+        let mut bucket = self.bucket.lock().unwrap_or_else(|e| e.into_inner());
+        *bucket = (0, Instant::now());
+    }
+
+    /// Account for `bytes` just transferred, sleeping while over the limit.
+    pub fn acquire(&self, bytes: u64) {
+        let mut wait = self.charge(bytes);
+        while !wait.is_zero() {
+            std::thread::sleep(wait);
+            wait = self.charge(0);
+        }
+    }
+
+    /// Take `bytes` from the bucket without waiting.
+    ///
+    /// Returns how long to sleep before calling again with 0 (at most
+    /// `MAX_LIMITER_SLEEP`), or zero once the bucket is out of debt. Async
+    /// callers use this with their own timer instead of `acquire`.
+    pub fn charge(&self, bytes: u64) -> Duration {
+        let rate = self.bytes_per_sec.load(Ordering::Relaxed);
+        if rate == 0 {
+            return Duration::ZERO;
+        }
+        let rate_i = rate.min(i64::MAX as u64) as i64;
+        let mut bucket = self.bucket.lock().unwrap_or_else(|e| e.into_inner());
+        let (tokens, last) = &mut *bucket;
+        let refill = (last.elapsed().as_secs_f64() * rate as f64) as i64;
+        if refill > 0 {
+            // Burst cap: 2 seconds worth, as for ThrottledReader
+            *tokens = tokens.saturating_add(refill).min(rate_i.saturating_mul(2));
+            *last = Instant::now();
+        }
+        *tokens = tokens.saturating_sub(bytes.min(i64::MAX as u64) as i64);
+        if *tokens >= 0 {
+            return Duration::ZERO;
+        }
+        Duration::from_secs_f64(-*tokens as f64 / rate as f64).min(MAX_LIMITER_SLEEP)
+    }
+}
+
+/// Queueing delay over the path's base RTT at which `AdaptiveRate` backs off.
+/// Low enough to keep a video call on the same link usable.
+#[cfg(feature = "net")]
+const QUEUE_DELAY_TARGET: Duration = Duration::from_millis(25);
+
+/// Factor applied to the rate on each back-off.
+#[cfg(feature = "net")]
+const BACKOFF_FACTOR: f64 = 0.7;
+
+/// Lowest rate `AdaptiveRate` backs off to, in bytes/sec.
+#[cfg(feature = "net")]
+pub const MIN_ADAPTIVE_RATE: u64 = 64 * 1024;
+
+/// RTT samples the base RTT is the minimum of, so that a route change is
+/// picked up after a while.
+#[cfg(feature = "net")]
+const BASE_RTT_WINDOW: usize = 30;
+
+/// Delay-based rate controller (`--adaptive-limit`).
+///
+/// Fed one RTT sample to the peer at a time, it tracks the base RTT (the
+/// minimum of recent samples) and treats anything above it as queueing in
+/// some buffer on the path. When the queueing delay exceeds
+/// `QUEUE_DELAY_TARGET` the rate drops multiplicatively from what is
+/// actually getting through; otherwise it grows by 10% per sample, up to
+/// the configured ceiling (or until it stops being the bottleneck when
+/// there is none).
+#[cfg(feature = "net")]
+#[derive(Debug, Clone)]
+pub struct AdaptiveRate {
+    ceiling: Option<u64>,
+    rate: Option<u64>,
+    samples: VecDeque<Duration>,
+}
+
+#[cfg(feature = "net")]
+impl AdaptiveRate {
+    /// Start at `ceiling` (`None`: unlimited until the first back-off).
+    pub fn new(ceiling: Option<u64>) -> Self {
+        Self {
+            ceiling,
+            rate: ceiling,
+            samples: VecDeque::with_capacity(BASE_RTT_WINDOW),
+        }
+    }
+
+    /// Current limit, `None` if unlimited.
+    pub fn rate(&self) -> Option<u64> {
+        self.rate
+    }
+
+    /// Feed an RTT sample and the throughput (bytes/sec) since the last one.
+    /// Returns the new limit.
+    pub fn on_sample(&mut self, rtt: Duration, throughput: u64) -> Option<u64> {
+        if self.samples.len() == BASE_RTT_WINDOW {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(rtt);
+        let base = self.samples.iter().min().copied().unwrap_or(rtt);
+
+        if rtt.saturating_sub(base) > QUEUE_DELAY_TARGET {
+            let current = match (self.rate, throughput) {
+                (Some(rate), 0) => rate,
+                (Some(rate), moving) => rate.min(moving),
+                (None, 0) => return None,
+                (None, moving) => moving,
+            };
+            self.rate = Some(((current as f64 * BACKOFF_FACTOR) as u64).max(MIN_ADAPTIVE_RATE));
+        } else if let Some(rate) = self.rate {
+            let raised = rate.saturating_add((rate / 10).max(MIN_ADAPTIVE_RATE));
+            self.rate = match self.ceiling {
+                Some(ceiling) => Some(raised.min(ceiling)),
+                // Lift the limit once it no longer holds the transfer back
+                None if throughput > 0 && raised > throughput.saturating_mul(2) => None,
+                None => Some(raised),
+            };
+        }
+        self.rate
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -298,4 +465,81 @@ mod tests {
         std::io::copy(&mut reader, &mut output).unwrap();
         assert!(output.is_empty());
     }
+
+    #[test]
+    fn shared_limiter_unlimited_does_not_wait() {
+        let limiter = SharedLimiter::new(None);
+        let start = Instant::now();
+        limiter.acquire(1_000_000_000);
+        assert!(start.elapsed() < Duration::from_millis(50));
+        assert_eq!(limiter.rate(), None);
+    }
+
+    #[test]
+    fn shared_limiter_waits_off_debt() {
+        // 1s of initial tokens, then 50KB of debt at 100KB/s takes ~0.5s
+        let limiter = SharedLimiter::new(Some(100_000));
+        let start = Instant::now();
+        limiter.acquire(150_000);
+        assert!(start.elapsed() >= Duration::from_millis(400));
+    }
+
+    #[test]
+    fn shared_limiter_rate_change_applies() {
+        let limiter = SharedLimiter::new(Some(1_000));
+        limiter.set_rate(None);
+        let start = Instant::now();
+        limiter.acquire(1_000_000);
+        assert!(start.elapsed() < Duration::from_millis(50));
+        limiter.set_rate(Some(2_000));
+        assert_eq!(limiter.rate(), Some(2_000));
+    }
+
+    #[test]
+    fn shared_limiter_charge_reports_debt() {
+        let limiter = SharedLimiter::new(Some(100_000));
+        assert_eq!(limiter.charge(50_000), Duration::ZERO);
+        let wait = limiter.charge(100_000);
+        assert!(wait > Duration::ZERO && wait <= MAX_LIMITER_SLEEP);
+    }
+
+    #[cfg(feature = "net")]
+    #[test]
+    fn adaptive_rate_backs_off_when_rtt_rises() {
+        let mut control = AdaptiveRate::new(Some(10_000_000));
+        let base = Duration::from_millis(5);
+        assert_eq!(control.on_sample(base, 9_000_000), Some(10_000_000));
+        // 60ms of queueing: drop to 70% of what was getting through
+        assert_eq!(control.on_sample(Duration::from_millis(65), 8_000_000), Some(5_600_000));
+        // Queue drained: grow by 10% per sample, never past the ceiling
+        assert_eq!(control.on_sample(base, 5_000_000), Some(6_160_000));
+        for _ in 0..20 {
+            control.on_sample(base, 5_000_000);
+        }
+        assert_eq!(control.rate(), Some(10_000_000));
+    }
+
+    #[cfg(feature = "net")]
+    #[test]
+    fn adaptive_rate_has_a_floor() {
+        let mut control = AdaptiveRate::new(Some(100_000));
+        control.on_sample(Duration::from_millis(2), 0);
+        for _ in 0..10 {
+            control.on_sample(Duration::from_millis(200), 50_000);
+        }
+        assert_eq!(control.rate(), Some(MIN_ADAPTIVE_RATE));
+    }
+
+    #[cfg(feature = "net")]
+    #[test]
+    fn adaptive_rate_without_ceiling_lifts_the_limit() {
+        let mut control = AdaptiveRate::new(None);
+        assert_eq!(control.on_sample(Duration::from_millis(5), 1_000_000), None);
+        assert_eq!(
+            control.on_sample(Duration::from_millis(80), 1_000_000),
+            Some(700_000)
+        );
+        // Limit well above what the transfer reaches: unlimited again
+        assert_eq!(control.on_sample(Duration::from_millis(5), 300_000), None);
+    }
 }
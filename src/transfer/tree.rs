@@ -0,0 +1,379 @@
+//! Tree view of a local path or backend URI (`flux tree`).
+//!
+//! Lists directories recursively, optionally (`--du`) annotating every
+//! directory with the cumulative size and file count of its subtree. Sibling
+//! subtrees are scanned in parallel with rayon, and a spinner on stderr shows
+//! the running totals. With `--du`, children are ordered largest first so the
+//! candidates for `--exclude` stand out before a big sync.
+
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use bytesize::ByteSize;
+use indicatif::ProgressBar;
+use rayon::prelude::*;
+
+use crate::backend::{create_backend, FileEntry, FluxBackend};
+use crate::cli::args::TreeArgs;
+use crate::config;
+use crate::error::FluxError;
+use crate::progress::bar::create_scan_progress;
+use crate::protocol::detect_protocol;
+use crate::transfer::strip_url_credentials;
+
+/// One file or directory in the tree.
+#[derive(Debug)]
+pub struct TreeNode {
+    pub name: String,
+    pub is_dir: bool,
+    /// File size, or the cumulative size of a directory's subtree
+    pub size: u64,
+    /// Files in the subtree (1 for a file)
+    pub files: u64,
+    /// Children within the depth limit
+    pub children: Vec<TreeNode>,
+    /// Why a directory could not be listed (its totals are then incomplete)
+    pub error: Option<String>,
+}
+
+/// What to scan and how to order it.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TreeOptions {
+    /// Levels below the root to keep (`None` = unlimited)
+    pub max_depth: Option<usize>,
+    /// Walk whole subtrees to compute cumulative sizes
+    pub du: bool,
+}
+
+/// Execute `flux tree`.
+pub fn execute_tree(args: TreeArgs, quiet: bool) -> Result<(), FluxError> {
+    let resolved = config::aliases::resolve(&args.path);
+    let protocol = detect_protocol(&resolved);
+    let backend = create_backend(&protocol)?;
+
+    // Network backends are rooted at the URI; an empty path means the root
+    let root = protocol.local_path().cloned().unwrap_or_default();
+    let options = TreeOptions {
+        max_depth: args.depth,
+        du: args.du,
+    };
+
+    let progress = create_scan_progress(quiet || !args.du);
+    let mut tree = scan(backend.as_ref(), &root, options, &progress)?;
+    progress.finish_and_clear();
+
+    tree.name = strip_url_credentials(&resolved);
+    for line in render(&tree, options.du) {
+        println!("{}", line);
+    }
+    Ok(())
+}
+
+/// Scan `root` on `backend` into a tree.
+///
+/// Unreadable subdirectories are recorded on their node rather than failing
+/// the scan; only a missing or unreadable root is an error.
+pub fn scan(
+    backend: &dyn FluxBackend,
+    root: &Path,
+    options: TreeOptions,
+    progress: &ProgressBar,
+) -> Result<TreeNode, FluxError> {
+    let stat = backend.stat(root)?;
+    let name = root.display().to_string();
+    if !stat.is_dir {
+        return Ok(TreeNode {
+            name,
+            is_dir: false,
+            size: stat.size,
+            files: 1,
+            children: Vec::new(),
+            error: None,
+        });
+    }
+
+    let scanner = Scanner {
+        backend,
+        options,
+        progress,
+        bytes: AtomicU64::new(0),
+    };
+    let listing = backend.list_dir(root)?;
+    Ok(scanner.dir_from_listing(root, name, 0, listing))
+}
+
+/// Shared state for one scan.
+struct Scanner<'a> {
+    backend: &'a dyn FluxBackend,
+    options: TreeOptions,
+    progress: &'a ProgressBar,
+    /// Bytes found so far, for the progress message
+    bytes: AtomicU64,
+}
+
+impl Scanner<'_> {
+    /// Whether children of a directory at `depth` are kept in the tree.
+    fn keeps_children(&self, depth: usize) -> bool {
+        !matches!(self.options.max_depth, Some(max) if depth >= max)
+    }
+
+    fn dir(&self, path: &Path, name: String, depth: usize) -> TreeNode {
+        // Without --du there is nothing to learn below the depth limit
+        if !self.options.du && !self.keeps_children(depth) {
+            return TreeNode::dir(name);
+        }
+        match self.backend.list_dir(path) {
+            Ok(listing) => self.dir_from_listing(path, name, depth, listing),
+            Err(e) => TreeNode {
+                error: Some(e.to_string()),
+                ..TreeNode::dir(name)
+            },
+        }
+    }
+
+    fn dir_from_listing(
+        &self,
+        path: &Path,
+        name: String,
+        depth: usize,
+        listing: Vec<FileEntry>,
+    ) -> TreeNode {
+        // Backends report entry paths differently (full, absolute or bare
+        // name), so rebuild child paths from the file name.
+        let (dirs, files): (Vec<_>, Vec<_>) = listing
+            .into_iter()
+            .filter_map(|entry| {
+                let file_name = entry.path.file_name()?.to_string_lossy().to_string();
+                Some((path.join(&file_name), file_name, entry.stat))
+            })
+            .partition(|(_, _, stat)| stat.is_dir);
+
+        let mut children: Vec<TreeNode> = files
+            .into_iter()
+            .map(|(_, file_name, stat)| TreeNode {
+                name: file_name,
+                is_dir: false,
+                size: stat.size,
+                files: 1,
+                children: Vec::new(),
+                error: None,
+            })
+            .collect();
+
+        let file_bytes: u64 = children.iter().map(|c| c.size).sum();
+        self.progress.inc(children.len() as u64);
+        let total = self.bytes.fetch_add(file_bytes, Ordering::Relaxed) + file_bytes;
+        self.progress.set_message(format!("({})", ByteSize(total)));
+
+        children.par_extend(
+            dirs.into_par_iter()
+                .map(|(child_path, file_name, _)| self.dir(&child_path, file_name, depth + 1)),
+        );
+
+        let mut node = TreeNode::dir(name);
+        node.size = children.iter().map(|c| c.size).sum();
+        node.files = children.iter().map(|c| c.files).sum();
+        if self.keeps_children(depth) {
+            sort_children(&mut children, self.options.du);
+            node.children = children;
+        }
+        node
+    }
+}
+
+impl TreeNode {
+    fn dir(name: String) -> Self {
+        Self {
+            name,
+            is_dir: true,
+            size: 0,
+            files: 0,
+            children: Vec::new(),
+            error: None,
+        }
+    }
+}
+
+/// Largest first with `--du`, otherwise by name.
+fn sort_children(children: &mut [TreeNode], by_size: bool) {
+    if by_size {
+        children.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.name.cmp(&b.name)));
+    } else {
+        children.sort_by(|a, b| a.name.cmp(&b.name));
+    }
+}
+
+/// Render the tree as lines with box-drawing connectors.
+///
+/// Files always show their size; directories show their cumulative size and
+/// file count only with `du`, since the totals are incomplete otherwise.
+pub fn render(root: &TreeNode, du: bool) -> Vec<String> {
+    let mut lines = vec![format!("{}{}", display_name(root), annotation(root, du))];
+    render_children(root, du, "", &mut lines);
+    lines
+}
+
+fn render_children(node: &TreeNode, du: bool, prefix: &str, lines: &mut Vec<String>) {
+    let count = node.children.len();
+    for (i, child) in node.children.iter().enumerate() {
+        let last = i + 1 == count;
+        let connector = if last { "└── " } else { "├── " };
+        lines.push(format!(
+            "{}{}{}{}",
+            prefix,
+            connector,
+            display_name(child),
+            annotation(child, du)
+        ));
+        let child_prefix = format!("{}{}", prefix, if last { "    " } else { "│   " });
+        render_children(child, du, &child_prefix, lines);
+    }
+}
+
+fn display_name(node: &TreeNode) -> String {
+    if node.is_dir && !node.name.ends_with('/') {
+        format!("{}/", node.name)
+    } else {
+        node.name.clone()
+    }
+}
+
+fn annotation(node: &TreeNode, du: bool) -> String {
+    let mut text = if !node.is_dir {
+        format!("  [{}]", ByteSize(node.size))
+    } else if du {
+        format!(
+            "  [{}, {} file{}]",
+            ByteSize(node.size),
+            node.files,
+            if node.files == 1 { "" } else { "s" }
+        )
+    } else {
+        String::new()
+    };
+    if let Some(ref error) = node.error {
+        text.push_str(&format!("  (error: {})", error));
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::local::LocalBackend;
+
+    fn sample() -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        std::fs::create_dir_all(root.join("big/nested")).unwrap();
+        std::fs::create_dir_all(root.join("small")).unwrap();
+        std::fs::write(root.join("big/a.bin"), vec![0u8; 3000]).unwrap();
+        std::fs::write(root.join("big/nested/b.bin"), vec![0u8; 2000]).unwrap();
+        std::fs::write(root.join("small/c.txt"), b"tiny").unwrap();
+        std::fs::write(root.join("readme.md"), vec![0u8; 100]).unwrap();
+        dir
+    }
+
+    fn child_names(node: &TreeNode) -> Vec<&str> {
+        node.children.iter().map(|c| c.name.as_str()).collect()
+    }
+
+    fn scan_local(root: &Path, options: TreeOptions) -> TreeNode {
+        scan(&LocalBackend::new(), root, options, &ProgressBar::hidden()).unwrap()
+    }
+
+    #[test]
+    fn du_rolls_up_sizes_and_counts() {
+        let dir = sample();
+        let tree = scan_local(
+            dir.path(),
+            TreeOptions {
+                max_depth: None,
+                du: true,
+            },
+        );
+        assert_eq!(tree.size, 5104);
+        assert_eq!(tree.files, 4);
+        assert_eq!(child_names(&tree), vec!["big", "readme.md", "small"]);
+        let big = &tree.children[0];
+        assert_eq!((big.size, big.files), (5000, 2));
+        assert_eq!(child_names(big), vec!["a.bin", "nested"]);
+    }
+
+    #[test]
+    fn depth_limit_keeps_totals_but_drops_children() {
+        let dir = sample();
+        let tree = scan_local(
+            dir.path(),
+            TreeOptions {
+                max_depth: Some(1),
+                du: true,
+            },
+        );
+        assert_eq!(tree.size, 5104);
+        let big = &tree.children[0];
+        assert_eq!(big.size, 5000);
+        assert!(big.children.is_empty());
+    }
+
+    #[test]
+    fn without_du_children_are_sorted_by_name() {
+        let dir = sample();
+        let tree = scan_local(dir.path(), TreeOptions::default());
+        assert_eq!(child_names(&tree), vec!["big", "readme.md", "small"]);
+        assert_eq!(child_names(&tree.children[0]), vec!["a.bin", "nested"]);
+    }
+
+    #[test]
+    fn single_file_root() {
+        let dir = sample();
+        let tree = scan_local(&dir.path().join("readme.md"), TreeOptions::default());
+        assert!(!tree.is_dir);
+        assert_eq!(tree.size, 100);
+    }
+
+    #[test]
+    fn missing_root_is_an_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let result = scan(
+            &LocalBackend::new(),
+            &dir.path().join("nope"),
+            TreeOptions::default(),
+            &ProgressBar::hidden(),
+        );
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn render_draws_connectors_and_totals() {
+        let mut root = TreeNode::dir("data".to_string());
+        let mut sub = TreeNode::dir("sub".to_string());
+        sub.children.push(TreeNode {
+            name: "x.bin".to_string(),
+            is_dir: false,
+            size: 10,
+            files: 1,
+            children: Vec::new(),
+            error: None,
+        });
+        sub.size = 10;
+        sub.files = 1;
+        root.children.push(sub);
+        root.children.push(TreeNode {
+            error: Some("permission denied".to_string()),
+            ..TreeNode::dir("locked".to_string())
+        });
+        root.size = 10;
+        root.files = 1;
+
+        let lines = render(&root, true);
+        assert_eq!(lines[0], format!("data/  [{}, 1 file]", ByteSize(10)));
+        assert_eq!(lines[1], format!("├── sub/  [{}, 1 file]", ByteSize(10)));
+        assert_eq!(lines[2], format!("│   └── x.bin  [{}]", ByteSize(10)));
+        assert!(lines[3].starts_with("└── locked/"));
+        assert!(lines[3].ends_with("(error: permission denied)"));
+
+        let plain = render(&root, false);
+        assert_eq!(plain[1], "├── sub/");
+    }
+}
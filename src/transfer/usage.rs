@@ -0,0 +1,461 @@
+//! Bandwidth usage per day, host and backend (`flux usage`), and monthly
+//! caps for metered connections.
+//!
+//! `record_history` adds the bytes of every transfer with a network end to
+//! the `usage` table of `state.db`: one row per local day, backend (`sftp`,
+//! `smb`, `webdav`, `rclone`, or `p2p` for Flux devices) and host, with
+//! bytes sent and received kept apart. Local copies are not counted.
+//!
+//! `[backends.max_monthly_gb]` caps what a backend may move (both ways) in
+//! a calendar month. Once the month's total reaches the cap, `check_cap`
+//! fails new transfers to it with `FluxError::UsageCapReached`, which exits
+//! like a pause and leaves queue entries pending until the month changes or
+//! the cap is raised. `flux daemon` passes over such entries meanwhile.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use bytesize::ByteSize;
+use chrono::{Datelike, Local, NaiveDate};
+use rusqlite::{params, Connection};
+use serde::Serialize;
+
+use crate::cli::args::UsageArgs;
+use crate::error::FluxError;
+use crate::protocol::{detect_protocol, Protocol};
+
+use super::history::HistoryRecord;
+
+/// Backend name under which transfers between Flux devices are counted.
+pub const P2P: &str = "p2p";
+
+/// Bytes in a GB of `max_monthly_gb` (decimal, as sizes are printed).
+const GB: u64 = 1_000_000_000;
+
+/// What a transfer is counted against.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UsageKey {
+    pub backend: String,
+    /// Server, remote or device at the other end
+    pub host: String,
+    /// Data left this machine (else it arrived)
+    pub outgoing: bool,
+}
+
+impl UsageKey {
+    /// The key of a finished operation, `None` for local-only ones.
+    pub fn for_record(record: &HistoryRecord) -> Option<Self> {
+        let device = |fallback: &str| record.peer.clone().unwrap_or_else(|| fallback.to_string());
+        match record.operation {
+            "send" | "push" => Some(Self {
+                backend: P2P.to_string(),
+                host: device(&record.dest),
+                outgoing: true,
+            }),
+            "receive" | "pull" => Some(Self {
+                backend: P2P.to_string(),
+                host: device(&record.source),
+                outgoing: false,
+            }),
+            _ => Self::for_copy(&record.source, &record.dest),
+        }
+    }
+
+    /// The network end of a copy from `source` to `dest` (alias-resolved),
+    /// the destination if both are remote; `None` if both are local.
+    pub fn for_copy(source: &str, dest: &str) -> Option<Self> {
+        let dest = detect_protocol(dest);
+        if !dest.is_local() {
+            return Some(Self::remote(&dest, true));
+        }
+        let source = detect_protocol(source);
+        (!source.is_local()).then(|| Self::remote(&source, false))
+    }
+
+    fn remote(protocol: &Protocol, outgoing: bool) -> Self {
+        let host = match protocol {
+            Protocol::Local { .. } => String::new(),
+            Protocol::Sftp { host, .. } => host.clone(),
+            Protocol::Smb { server, .. } => server.clone(),
+            Protocol::WebDav { url, .. } => url::Url::parse(url)
+                .ok()
+                .and_then(|u| u.host_str().map(str::to_string))
+                .unwrap_or_default(),
+            Protocol::Rclone { remote, .. } => remote.clone(),
+        };
+        Self {
+            backend: protocol.name().to_string(),
+            host,
+            outgoing,
+        }
+    }
+}
+
+/// Bytes moved on one day with one host.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UsageRow {
+    pub day: NaiveDate,
+    pub backend: String,
+    pub host: String,
+    pub bytes_out: u64,
+    pub bytes_in: u64,
+    pub transfers: u64,
+}
+
+/// How `flux usage` totals the rows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UsageGroup {
+    Day,
+    Host,
+    Backend,
+}
+
+/// Usage rows in the state database.
+pub struct UsageStore {
+    db: Connection,
+}
+
+impl UsageStore {
+    /// Open the usage table in the state database in `data_dir`.
+    pub fn load(data_dir: &Path) -> Result<Self, FluxError> {
+        Ok(Self {
+            db: crate::state::open(data_dir)?,
+        })
+    }
+
+    /// Add `bytes` moved on `day` to the row of `key`.
+    pub fn add(&self, day: NaiveDate, key: &UsageKey, bytes: u64) -> Result<(), FluxError> {
+        let (out, incoming) = if key.outgoing { (bytes, 0) } else { (0, bytes) };
+        self.db.execute(
+            "INSERT INTO usage (day, backend, host, bytes_out, bytes_in, transfers)
+             VALUES (?1, ?2, ?3, ?4, ?5, 1)
+             ON CONFLICT (day, backend, host) DO UPDATE SET
+                bytes_out = bytes_out + excluded.bytes_out,
+                bytes_in = bytes_in + excluded.bytes_in,
+                transfers = transfers + 1",
+            params![
+                day.to_string(),
+                key.backend,
+                key.host,
+                out as i64,
+                incoming as i64
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Rows from `since` on, oldest day first.
+    pub fn rows(&self, since: NaiveDate) -> Result<Vec<UsageRow>, FluxError> {
+        let mut stmt = self.db.prepare(
+            "SELECT day, backend, host, bytes_out, bytes_in, transfers FROM usage
+             WHERE day >= ?1 ORDER BY day, backend, host",
+        )?;
+        let rows = stmt
+            .query_map(params![since.to_string()], |row| {
+                let day: String = row.get(0)?;
+                Ok(UsageRow {
+                    day: day.parse().unwrap_or_default(),
+                    backend: row.get(1)?,
+                    host: row.get(2)?,
+                    bytes_out: row.get::<_, i64>(3)? as u64,
+                    bytes_in: row.get::<_, i64>(4)? as u64,
+                    transfers: row.get::<_, i64>(5)? as u64,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(rows)
+    }
+
+    /// Bytes `backend` moved (both ways) in the calendar month of `day`.
+    pub fn month_total(&self, backend: &str, day: NaiveDate) -> Result<u64, FluxError> {
+        let total: i64 = self.db.query_row(
+            "SELECT COALESCE(SUM(bytes_out + bytes_in), 0) FROM usage
+             WHERE backend = ?1 AND day >= ?2 AND day <= ?3",
+            params![backend, month_start(day).to_string(), day.to_string()],
+            |row| row.get(0),
+        )?;
+        Ok(total as u64)
+    }
+}
+
+/// First day of the month of `day`.
+fn month_start(day: NaiveDate) -> NaiveDate {
+    day.with_day(1).unwrap_or(day)
+}
+
+fn today() -> NaiveDate {
+    Local::now().date_naive()
+}
+
+/// Count a finished operation's bytes (best-effort, like history).
+pub fn record(record: &HistoryRecord) {
+    if record.bytes == 0 {
+        return;
+    }
+    let Some(key) = UsageKey::for_record(record) else {
+        return;
+    };
+    let result = crate::config::paths::flux_data_dir()
+        .and_then(|dir| UsageStore::load(&dir))
+        .and_then(|store| store.add(today(), &key, record.bytes));
+    if let Err(e) = result {
+        tracing::warn!("Transfer not counted in usage: {}", e);
+    }
+}
+
+/// Fail with `FluxError::UsageCapReached` if `backend` has used its
+/// `[backends.max_monthly_gb]` cap this month.
+pub fn check_cap(backend: &str) -> Result<(), FluxError> {
+    let config = crate::config::types::load_config().unwrap_or_default();
+    let Some(&cap_gb) = config.backends.max_monthly_gb.get(backend) else {
+        return Ok(());
+    };
+    let store = UsageStore::load(&crate::config::paths::flux_data_dir()?)?;
+    check_cap_in(&store, backend, cap_gb, today())
+}
+
+fn check_cap_in(
+    store: &UsageStore,
+    backend: &str,
+    cap_gb: u64,
+    day: NaiveDate,
+) -> Result<(), FluxError> {
+    let used = store.month_total(backend, day)?;
+    let cap = cap_gb.saturating_mul(GB);
+    if used >= cap {
+        return Err(FluxError::UsageCapReached {
+            backend: backend.to_string(),
+            used,
+            cap,
+        });
+    }
+    Ok(())
+}
+
+/// One line of the report.
+#[derive(Debug, Serialize)]
+struct UsageTotal {
+    key: String,
+    bytes_out: u64,
+    bytes_in: u64,
+    transfers: u64,
+}
+
+/// A capped backend's month so far.
+#[derive(Debug, Serialize)]
+struct CapStatus {
+    backend: String,
+    used: u64,
+    cap: u64,
+}
+
+/// `flux usage --json`.
+#[derive(Debug, Serialize)]
+struct UsageReport {
+    since: NaiveDate,
+    by: UsageGroup,
+    totals: Vec<UsageTotal>,
+    caps: Vec<CapStatus>,
+}
+
+/// Total `rows` by `group`, in key order.
+fn totals(rows: &[UsageRow], group: UsageGroup) -> Vec<UsageTotal> {
+    let mut by_key: BTreeMap<String, UsageTotal> = BTreeMap::new();
+    for row in rows {
+        let key = match group {
+            UsageGroup::Day => row.day.to_string(),
+            UsageGroup::Host => format!("{} ({})", row.host, row.backend),
+            UsageGroup::Backend => row.backend.clone(),
+        };
+        let total = by_key.entry(key.clone()).or_insert(UsageTotal {
+            key,
+            bytes_out: 0,
+            bytes_in: 0,
+            transfers: 0,
+        });
+        total.bytes_out += row.bytes_out;
+        total.bytes_in += row.bytes_in;
+        total.transfers += row.transfers;
+    }
+    by_key.into_values().collect()
+}
+
+/// Execute `flux usage`.
+pub fn execute_usage(args: UsageArgs, json: bool) -> Result<(), FluxError> {
+    let config = crate::config::types::load_config().unwrap_or_default();
+    let store = UsageStore::load(&crate::config::paths::flux_data_dir()?)?;
+    let today = today();
+    let since = today - chrono::Duration::days(i64::from(args.days.max(1)) - 1);
+    let rows = store.rows(since)?;
+    let caps = config
+        .backends
+        .max_monthly_gb
+        .iter()
+        .map(|(backend, &gb)| {
+            Ok(CapStatus {
+                backend: backend.clone(),
+                used: store.month_total(backend, today)?,
+                cap: gb.saturating_mul(GB),
+            })
+        })
+        .collect::<Result<Vec<_>, FluxError>>()?;
+    let report = UsageReport {
+        since,
+        by: args.by,
+        totals: totals(&rows, args.by),
+        caps,
+    };
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        return Ok(());
+    }
+    for line in render(&report) {
+        println!("{}", line);
+    }
+    Ok(())
+}
+
+/// The report as a table, followed by the caps.
+fn render(report: &UsageReport) -> Vec<String> {
+    let mut lines = Vec::new();
+    if report.totals.is_empty() {
+        lines.push(format!("No network transfers since {}", report.since));
+    } else {
+        let heading = match report.by {
+            UsageGroup::Day => "DAY",
+            UsageGroup::Host => "HOST",
+            UsageGroup::Backend => "BACKEND",
+        };
+        lines.push(format!(
+            "{:<32} {:>12} {:>12} {:>9}",
+            heading, "SENT", "RECEIVED", "TRANSFERS"
+        ));
+        for total in &report.totals {
+            lines.push(format!(
+                "{:<32} {:>12} {:>12} {:>9}",
+                total.key,
+                ByteSize(total.bytes_out).to_string(),
+                ByteSize(total.bytes_in).to_string(),
+                total.transfers
+            ));
+        }
+        let (out, incoming, transfers) = report.totals.iter().fold((0, 0, 0), |acc, t| {
+            (acc.0 + t.bytes_out, acc.1 + t.bytes_in, acc.2 + t.transfers)
+        });
+        lines.push(format!(
+            "{:<32} {:>12} {:>12} {:>9}",
+            format!("Total since {}", report.since),
+            ByteSize(out).to_string(),
+            ByteSize(incoming).to_string(),
+            transfers
+        ));
+    }
+    for cap in &report.caps {
+        let state = if cap.used >= cap.cap {
+            " (reached)"
+        } else {
+            ""
+        };
+        lines.push(format!(
+            "{}: {} of {} this month{}",
+            cap.backend,
+            ByteSize(cap.used),
+            ByteSize(cap.cap),
+            state
+        ));
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn day(s: &str) -> NaiveDate {
+        s.parse().unwrap()
+    }
+
+    fn key(backend: &str, host: &str, outgoing: bool) -> UsageKey {
+        UsageKey {
+            backend: backend.to_string(),
+            host: host.to_string(),
+            outgoing,
+        }
+    }
+
+    #[test]
+    fn keys_come_from_the_network_end() {
+        assert_eq!(
+            UsageKey::for_copy("big.iso", "sftp://me@nas.local/isos/"),
+            Some(key("sftp", "nas.local", true))
+        );
+        assert_eq!(
+            UsageKey::for_copy("https://dav.example.com/files/a.bin", "."),
+            Some(key("webdav", "dav.example.com", false))
+        );
+        assert_eq!(UsageKey::for_copy("a", "b"), None);
+
+        let mut record = HistoryRecord::new("receive", "192.168.1.5:50122", "/tmp/in");
+        record.peer = Some("laptop".to_string());
+        assert_eq!(
+            UsageKey::for_record(&record),
+            Some(key(P2P, "laptop", false))
+        );
+    }
+
+    #[test]
+    fn rows_add_up_per_day_and_host() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = UsageStore::load(dir.path()).unwrap();
+        store
+            .add(day("2026-03-01"), &key("sftp", "nas", true), 100)
+            .unwrap();
+        store
+            .add(day("2026-03-01"), &key("sftp", "nas", false), 30)
+            .unwrap();
+        store
+            .add(day("2026-03-02"), &key("smb", "files", true), 7)
+            .unwrap();
+
+        let rows = store.rows(day("2026-03-01")).unwrap();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(
+            (rows[0].bytes_out, rows[0].bytes_in, rows[0].transfers),
+            (100, 30, 2)
+        );
+        assert_eq!(store.rows(day("2026-03-02")).unwrap().len(), 1);
+
+        let by_backend = totals(&rows, UsageGroup::Backend);
+        assert_eq!(by_backend.len(), 2);
+        assert_eq!(by_backend[0].key, "sftp");
+    }
+
+    #[test]
+    fn caps_count_the_calendar_month() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = UsageStore::load(dir.path()).unwrap();
+        store
+            .add(day("2026-02-27"), &key("sftp", "nas", true), 3 * GB)
+            .unwrap();
+        store
+            .add(day("2026-03-02"), &key("sftp", "nas", true), GB)
+            .unwrap();
+        store
+            .add(day("2026-03-03"), &key("sftp", "nas", false), GB)
+            .unwrap();
+
+        assert_eq!(
+            store.month_total("sftp", day("2026-03-10")).unwrap(),
+            2 * GB
+        );
+        assert!(check_cap_in(&store, "sftp", 3, day("2026-03-10")).is_ok());
+        assert!(matches!(
+            check_cap_in(&store, "sftp", 2, day("2026-03-10")),
+            Err(FluxError::UsageCapReached { used, .. }) if used == 2 * GB
+        ));
+        // Another backend has its own total
+        assert!(check_cap_in(&store, "smb", 1, day("2026-03-10")).is_ok());
+    }
+}
@@ -1,8 +1,9 @@
 //! Directory verification: compare two locations and report differences.
 //!
-//! Walks both source and destination trees, compares file sizes and BLAKE3
-//! hashes, and produces a structured `VerifyResult` with matched, differing,
-//! source-only, and dest-only files.
+//! Walks both source and destination trees, compares file sizes and content
+//! hashes (BLAKE3 unless `--checksum` picks another algorithm), and produces
+//! a structured `VerifyResult` with matched, differing, source-only, and
+//! dest-only files.
 
 use std::collections::HashSet;
 use std::path::{Path, PathBuf};
@@ -12,7 +13,7 @@ use walkdir::WalkDir;
 
 use crate::error::FluxError;
 use crate::progress::bar::create_transfer_progress;
-use crate::transfer::checksum::hash_file;
+use crate::transfer::checksum::{hash_file_with, ChecksumAlgorithm};
 use crate::transfer::filter::TransferFilter;
 
 /// Reason two files differ.
@@ -20,7 +21,7 @@ use crate::transfer::filter::TransferFilter;
 pub enum DiffReason {
     /// Files have different sizes.
     SizeMismatch { src_size: u64, dst_size: u64 },
-    /// Files have same size but different BLAKE3 content hashes.
+    /// Files have same size but different content hashes.
     ContentMismatch,
 }
 
@@ -34,7 +35,7 @@ pub struct DiffEntry {
 /// Result of a directory verification.
 #[derive(Debug)]
 pub struct VerifyResult {
-    /// Number of files that are identical (same BLAKE3 hash).
+    /// Number of files that are identical (same content hash).
     pub matched: u64,
     /// Files that exist in both but differ.
     pub differs: Vec<DiffEntry>,
@@ -54,13 +55,14 @@ pub struct VerifyResult {
 /// 1. Walk source tree, collect all relative paths
 /// 2. For each source file, check if dest has it:
 ///    - Missing -> source_only
-///    - Present -> compare size, then BLAKE3 hash if sizes match
+///    - Present -> compare size, then the `algorithm` hash if sizes match
 /// 3. Walk dest tree for files not in source -> dest_only
 /// 4. Shows progress bar during verification
 pub fn verify_directories(
     source: &Path,
     dest: &Path,
     filter: &TransferFilter,
+    algorithm: ChecksumAlgorithm,
     quiet: bool,
 ) -> Result<VerifyResult, FluxError> {
     // Validate inputs
@@ -183,8 +185,11 @@ pub fn verify_directories(
             continue;
         }
 
-        // Sizes match -- compare BLAKE3 hashes
-        match (hash_file(&src_path), hash_file(&dst_path)) {
+        // Sizes match -- compare content hashes
+        match (
+            hash_file_with(&src_path, algorithm),
+            hash_file_with(&dst_path, algorithm),
+        ) {
             (Ok(src_hash), Ok(dst_hash)) => {
                 if src_hash == dst_hash {
                     result.matched += 1;
@@ -345,7 +350,8 @@ mod tests {
         create_file(&src, "sub/b.txt", "world");
         create_file(&dst, "sub/b.txt", "world");
 
-        let result = verify_directories(&src, &dst, &no_filter(), true).unwrap();
+        let result =
+            verify_directories(&src, &dst, &no_filter(), ChecksumAlgorithm::Blake3, true).unwrap();
         assert_eq!(result.matched, 2);
         assert!(result.differs.is_empty());
         assert!(result.source_only.is_empty());
@@ -362,7 +368,8 @@ mod tests {
 
         create_file(&src, "only-in-src.txt", "data");
 
-        let result = verify_directories(&src, &dst, &no_filter(), true).unwrap();
+        let result =
+            verify_directories(&src, &dst, &no_filter(), ChecksumAlgorithm::Blake3, true).unwrap();
         assert_eq!(result.source_only.len(), 1);
         assert_eq!(result.matched, 0);
     }
@@ -377,7 +384,8 @@ mod tests {
 
         create_file(&dst, "only-in-dst.txt", "data");
 
-        let result = verify_directories(&src, &dst, &no_filter(), true).unwrap();
+        let result =
+            verify_directories(&src, &dst, &no_filter(), ChecksumAlgorithm::Blake3, true).unwrap();
         assert_eq!(result.dest_only.len(), 1);
         assert_eq!(result.matched, 0);
     }
@@ -393,7 +401,8 @@ mod tests {
         create_file(&src, "file.txt", "short");
         create_file(&dst, "file.txt", "much longer content");
 
-        let result = verify_directories(&src, &dst, &no_filter(), true).unwrap();
+        let result =
+            verify_directories(&src, &dst, &no_filter(), ChecksumAlgorithm::Blake3, true).unwrap();
         assert_eq!(result.differs.len(), 1);
         assert!(matches!(
             result.differs[0].reason,
@@ -413,12 +422,14 @@ mod tests {
         create_file(&src, "file.txt", "aaaa");
         create_file(&dst, "file.txt", "bbbb");
 
-        let result = verify_directories(&src, &dst, &no_filter(), true).unwrap();
-        assert_eq!(result.differs.len(), 1);
-        assert!(matches!(
-            result.differs[0].reason,
-            DiffReason::ContentMismatch
-        ));
+        for algorithm in [ChecksumAlgorithm::Blake3, ChecksumAlgorithm::Xxh3] {
+            let result = verify_directories(&src, &dst, &no_filter(), algorithm, true).unwrap();
+            assert_eq!(result.differs.len(), 1);
+            assert!(matches!(
+                result.differs[0].reason,
+                DiffReason::ContentMismatch
+            ));
+        }
     }
 
     #[test]
@@ -434,7 +445,8 @@ mod tests {
         create_file(&src, "skip.log", "log data");
 
         let filter = TransferFilter::new(&["*.log".to_string()], &[]).unwrap();
-        let result = verify_directories(&src, &dst, &filter, true).unwrap();
+        let result =
+            verify_directories(&src, &dst, &filter, ChecksumAlgorithm::Blake3, true).unwrap();
 
         assert_eq!(result.matched, 1);
         assert!(result.source_only.is_empty()); // .log excluded
@@ -450,6 +462,7 @@ mod tests {
             &dir.path().join("nonexistent"),
             &dst,
             &no_filter(),
+            ChecksumAlgorithm::Blake3,
             true,
         );
         assert!(result.is_err());
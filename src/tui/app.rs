@@ -10,10 +10,13 @@ use ratatui::widgets::{Block, Borders, Tabs};
 use super::action::Action;
 use super::components::Component;
 use super::components::dashboard::DashboardComponent;
+use super::components::devices::DevicesComponent;
 use super::components::file_browser::FileBrowserComponent;
 use super::components::history_view::HistoryViewComponent;
 use super::components::queue_view::QueueViewComponent;
+use super::components::saved_view::SavedViewComponent;
 use super::components::status_bar::StatusBar;
+use super::components::transfer_detail::TransferDetailComponent;
 use super::event::{Event, EventHandler};
 use super::terminal;
 
@@ -24,15 +27,21 @@ pub enum ActiveTab {
     FileBrowser,
     Queue,
     History,
+    Transfer,
+    Devices,
+    Saved,
 }
 
 impl ActiveTab {
     /// All tabs in order.
-    const ALL: [ActiveTab; 4] = [
+    const ALL: [ActiveTab; 7] = [
         ActiveTab::Dashboard,
         ActiveTab::FileBrowser,
         ActiveTab::Queue,
         ActiveTab::History,
+        ActiveTab::Transfer,
+        ActiveTab::Devices,
+        ActiveTab::Saved,
     ];
 
     /// Tab display name.
@@ -42,6 +51,9 @@ impl ActiveTab {
             ActiveTab::FileBrowser => "Files",
             ActiveTab::Queue => "Queue",
             ActiveTab::History => "History",
+            ActiveTab::Transfer => "Transfer",
+            ActiveTab::Devices => "Devices",
+            ActiveTab::Saved => "Saved",
         }
     }
 
@@ -52,6 +64,9 @@ impl ActiveTab {
             ActiveTab::FileBrowser => 1,
             ActiveTab::Queue => 2,
             ActiveTab::History => 3,
+            ActiveTab::Transfer => 4,
+            ActiveTab::Devices => 5,
+            ActiveTab::Saved => 6,
         }
     }
 
@@ -93,6 +108,12 @@ pub struct App {
     queue_view: QueueViewComponent,
     /// Transfer history tab component.
     history_view: HistoryViewComponent,
+    /// Live detail of a running transfer.
+    transfer_detail: TransferDetailComponent,
+    /// Nearby receivers and sending to them.
+    devices: DevicesComponent,
+    /// Saved transfers, launched with one key.
+    saved_view: SavedViewComponent,
 }
 
 impl App {
@@ -109,6 +130,9 @@ impl App {
             file_browser: FileBrowserComponent::new(),
             queue_view: QueueViewComponent::new(),
             history_view: HistoryViewComponent::new(),
+            transfer_detail: TransferDetailComponent::new(),
+            devices: DevicesComponent::new(),
+            saved_view: SavedViewComponent::new(),
         }
     }
 
@@ -135,6 +159,18 @@ impl App {
                 self.active_tab = ActiveTab::History;
                 Action::Noop
             }
+            KeyCode::Char('5') => {
+                self.active_tab = ActiveTab::Transfer;
+                Action::Noop
+            }
+            KeyCode::Char('6') => {
+                self.active_tab = ActiveTab::Devices;
+                Action::Noop
+            }
+            KeyCode::Char('7') => {
+                self.active_tab = ActiveTab::Saved;
+                Action::Noop
+            }
             KeyCode::Tab => {
                 self.active_tab = self.active_tab.next();
                 Action::Noop
@@ -154,6 +190,9 @@ impl App {
                     ActiveTab::FileBrowser => self.file_browser.handle_key_event(key),
                     ActiveTab::Queue => self.queue_view.handle_key_event(key),
                     ActiveTab::History => self.history_view.handle_key_event(key),
+                    ActiveTab::Transfer => self.transfer_detail.handle_key_event(key),
+                    ActiveTab::Devices => self.devices.handle_key_event(key),
+                    ActiveTab::Saved => self.saved_view.handle_key_event(key),
                 }
             }
         }
@@ -164,6 +203,9 @@ impl App {
         self.dashboard.update();
         self.queue_view.update();
         self.history_view.update();
+        self.transfer_detail.update();
+        self.devices.update();
+        self.saved_view.update();
     }
 
     /// Render the entire application UI.
@@ -214,6 +256,15 @@ impl App {
             ActiveTab::History => {
                 self.history_view.render(frame, chunks[1]);
             }
+            ActiveTab::Transfer => {
+                self.transfer_detail.render(frame, chunks[1]);
+            }
+            ActiveTab::Devices => {
+                self.devices.render(frame, chunks[1]);
+            }
+            ActiveTab::Saved => {
+                self.saved_view.render(frame, chunks[1]);
+            }
         }
 
         // -- Status bar with tab-appropriate hints --
@@ -221,7 +272,7 @@ impl App {
         status_bar.hints = match self.active_tab {
             ActiveTab::Dashboard => vec![
                 ("j/k".into(), "Navigate".into()),
-                ("1-4".into(), "Tabs".into()),
+                ("1-7".into(), "Tabs".into()),
                 ("q".into(), "Quit".into()),
             ],
             ActiveTab::FileBrowser => vec![
@@ -236,11 +287,31 @@ impl App {
                 ("r".into(), "Resume".into()),
                 ("c".into(), "Cancel".into()),
                 ("x".into(), "Clear".into()),
+                ("S-↑/↓".into(), "Move".into()),
                 ("q".into(), "Quit".into()),
             ],
             ActiveTab::History => vec![
                 ("j/k".into(), "Navigate".into()),
-                ("1-4".into(), "Tabs".into()),
+                ("f".into(), "Filter".into()),
+                ("Enter".into(), "Re-queue".into()),
+                ("q".into(), "Quit".into()),
+            ],
+            ActiveTab::Transfer => vec![
+                ("j/k".into(), "Transfer".into()),
+                ("+/-".into(), "Limit".into()),
+                ("u".into(), "Unlimited".into()),
+                ("q".into(), "Quit".into()),
+            ],
+            ActiveTab::Devices => vec![
+                ("j/k".into(), "Device".into()),
+                ("Enter".into(), "Send file".into()),
+                ("Esc".into(), "Close picker".into()),
+                ("q".into(), "Quit".into()),
+            ],
+            ActiveTab::Saved => vec![
+                ("j/k".into(), "Navigate".into()),
+                ("a-z".into(), "Run".into()),
+                ("Enter".into(), "Run selected".into()),
                 ("q".into(), "Quit".into()),
             ],
         };
@@ -330,6 +401,15 @@ mod tests {
         app.handle_key_event(key_event(KeyCode::Char('4')));
         assert_eq!(app.active_tab, ActiveTab::History);
 
+        app.handle_key_event(key_event(KeyCode::Char('5')));
+        assert_eq!(app.active_tab, ActiveTab::Transfer);
+
+        app.handle_key_event(key_event(KeyCode::Char('6')));
+        assert_eq!(app.active_tab, ActiveTab::Devices);
+
+        app.handle_key_event(key_event(KeyCode::Char('7')));
+        assert_eq!(app.active_tab, ActiveTab::Saved);
+
         app.handle_key_event(key_event(KeyCode::Char('1')));
         assert_eq!(app.active_tab, ActiveTab::Dashboard);
     }
@@ -348,6 +428,15 @@ mod tests {
         app.handle_key_event(key_event(KeyCode::Tab));
         assert_eq!(app.active_tab, ActiveTab::History);
 
+        app.handle_key_event(key_event(KeyCode::Tab));
+        assert_eq!(app.active_tab, ActiveTab::Transfer);
+
+        app.handle_key_event(key_event(KeyCode::Tab));
+        assert_eq!(app.active_tab, ActiveTab::Devices);
+
+        app.handle_key_event(key_event(KeyCode::Tab));
+        assert_eq!(app.active_tab, ActiveTab::Saved);
+
         // Wraps around
         app.handle_key_event(key_event(KeyCode::Tab));
         assert_eq!(app.active_tab, ActiveTab::Dashboard);
@@ -358,7 +447,16 @@ mod tests {
         let mut app = App::new();
         assert_eq!(app.active_tab, ActiveTab::Dashboard);
 
-        // Wraps to History
+        // Wraps to Saved
+        app.handle_key_event(key_event(KeyCode::BackTab));
+        assert_eq!(app.active_tab, ActiveTab::Saved);
+
+        app.handle_key_event(key_event(KeyCode::BackTab));
+        assert_eq!(app.active_tab, ActiveTab::Devices);
+
+        app.handle_key_event(key_event(KeyCode::BackTab));
+        assert_eq!(app.active_tab, ActiveTab::Transfer);
+
         app.handle_key_event(key_event(KeyCode::BackTab));
         assert_eq!(app.active_tab, ActiveTab::History);
 
@@ -378,6 +476,9 @@ mod tests {
         assert_eq!(ActiveTab::FileBrowser.name(), "Files");
         assert_eq!(ActiveTab::Queue.name(), "Queue");
         assert_eq!(ActiveTab::History.name(), "History");
+        assert_eq!(ActiveTab::Transfer.name(), "Transfer");
+        assert_eq!(ActiveTab::Devices.name(), "Devices");
+        assert_eq!(ActiveTab::Saved.name(), "Saved");
     }
 
     #[test]
@@ -386,6 +487,9 @@ mod tests {
         assert_eq!(ActiveTab::FileBrowser.index(), 1);
         assert_eq!(ActiveTab::Queue.index(), 2);
         assert_eq!(ActiveTab::History.index(), 3);
+        assert_eq!(ActiveTab::Transfer.index(), 4);
+        assert_eq!(ActiveTab::Devices.index(), 5);
+        assert_eq!(ActiveTab::Saved.index(), 6);
     }
 
     #[test]
@@ -394,7 +498,10 @@ mod tests {
         assert_eq!(ActiveTab::from_index(1), Some(ActiveTab::FileBrowser));
         assert_eq!(ActiveTab::from_index(2), Some(ActiveTab::Queue));
         assert_eq!(ActiveTab::from_index(3), Some(ActiveTab::History));
-        assert_eq!(ActiveTab::from_index(4), None);
+        assert_eq!(ActiveTab::from_index(4), Some(ActiveTab::Transfer));
+        assert_eq!(ActiveTab::from_index(5), Some(ActiveTab::Devices));
+        assert_eq!(ActiveTab::from_index(6), Some(ActiveTab::Saved));
+        assert_eq!(ActiveTab::from_index(7), None);
     }
 
     #[test]
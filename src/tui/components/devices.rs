@@ -0,0 +1,598 @@
+//! Devices component: nearby flux receivers and sending files to them.
+//!
+//! mDNS discovery runs on a background thread that browses for a few
+//! seconds, hands the devices over and starts again, so the list follows
+//! receivers coming and going. Each device shows whether the key it
+//! advertises is in the trust store. Enter opens a file picker (the Files
+//! tab's browser); choosing a file sends it like `flux send @name`, with
+//! progress shown here.
+
+use std::path::PathBuf;
+#[cfg(feature = "net")]
+use std::sync::mpsc;
+use std::sync::mpsc::{Receiver, TryRecvError};
+
+use indicatif::ProgressBar;
+use ratatui::Frame;
+use ratatui::crossterm::event::{KeyCode, KeyEvent};
+use ratatui::layout::{Constraint, Direction, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::Span;
+use ratatui::widgets::{Block, Borders, Cell, Gauge, Paragraph, Row, Table, TableState};
+
+use super::Component;
+use super::file_browser::FileBrowserComponent;
+#[cfg(feature = "net")]
+use crate::security::trust::{TrustStatus, TrustStore};
+use crate::tui::action::Action;
+use crate::tui::theme;
+
+/// Seconds each discovery round browses for.
+#[cfg(feature = "net")]
+const DISCOVERY_SECS: u64 = 3;
+
+/// Pause between discovery rounds.
+#[cfg(feature = "net")]
+const DISCOVERY_PAUSE: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// Whether a device's advertised key matches the trust store.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(not(feature = "net"), allow(dead_code))]
+pub enum Trust {
+    Trusted,
+    Unknown,
+    KeyChanged,
+    /// The device advertises no public key
+    NoKey,
+}
+
+impl Trust {
+    /// Look up a device in the trust store (`None` if it could not be read).
+    #[cfg(feature = "net")]
+    pub fn of(store: Option<&TrustStore>, name: &str, public_key: Option<&str>) -> Self {
+        let Some(key) = public_key else {
+            return Trust::NoKey;
+        };
+        match store.map(|s| s.is_trusted(name, key)) {
+            Some(TrustStatus::Trusted) => Trust::Trusted,
+            Some(TrustStatus::KeyChanged) => Trust::KeyChanged,
+            Some(TrustStatus::Unknown) | None => Trust::Unknown,
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            Trust::Trusted => "trusted",
+            Trust::Unknown => "unknown",
+            Trust::KeyChanged => "KEY CHANGED",
+            Trust::NoKey => "no key",
+        }
+    }
+
+    fn style(self) -> Style {
+        match self {
+            Trust::Trusted => Style::default().fg(Color::Green),
+            Trust::Unknown => Style::default().fg(Color::Yellow),
+            Trust::KeyChanged => Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
+            Trust::NoKey => Style::default().fg(Color::Gray).add_modifier(Modifier::DIM),
+        }
+    }
+}
+
+/// A discovered receiver as listed in the table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeviceRow {
+    pub name: String,
+    pub host: String,
+    pub port: u16,
+    pub version: Option<String>,
+    pub trust: Trust,
+}
+
+/// Result of a discovery round, or why it failed.
+type DiscoveryResult = Result<Vec<DeviceRow>, String>;
+
+/// A send running on a background thread.
+struct SendJob {
+    device: String,
+    file: String,
+    /// Hidden bar the sender advances
+    progress: ProgressBar,
+    /// Bytes received and checksum verdict, once done
+    result: Receiver<Result<(u64, Option<bool>), String>>,
+}
+
+/// Devices view component for the TUI.
+pub struct DevicesComponent {
+    devices: Vec<DeviceRow>,
+    table_state: TableState,
+    discovery: Option<Receiver<DiscoveryResult>>,
+    /// No discovery round has finished yet
+    searching: bool,
+    discovery_error: Option<String>,
+    /// Device a file is being picked for, and the picker
+    picker: Option<(DeviceRow, FileBrowserComponent)>,
+    job: Option<SendJob>,
+    status_message: Option<String>,
+    message_ttl: u8,
+}
+
+impl DevicesComponent {
+    /// Create a devices view and start background discovery.
+    pub fn new() -> Self {
+        let mut component = Self::idle();
+        #[cfg(feature = "net")]
+        {
+            component.discovery = Some(spawn_discovery());
+            component.searching = true;
+        }
+        #[cfg(not(feature = "net"))]
+        {
+            component.discovery_error = Some("This build has no network support".into());
+        }
+        component
+    }
+
+    /// Create a devices view listing `devices`, without discovery (for testing).
+    #[cfg(test)]
+    pub fn with_devices(devices: Vec<DeviceRow>) -> Self {
+        let mut component = Self::idle();
+        component.set_devices(devices);
+        component
+    }
+
+    fn idle() -> Self {
+        Self {
+            devices: Vec::new(),
+            table_state: TableState::default(),
+            discovery: None,
+            searching: false,
+            discovery_error: None,
+            picker: None,
+            job: None,
+            status_message: None,
+            message_ttl: 0,
+        }
+    }
+
+    /// Replace the device list, keeping the same device selected.
+    fn set_devices(&mut self, devices: Vec<DeviceRow>) {
+        let selected_name = self.selected_device().map(|d| d.name.clone());
+        self.devices = devices;
+        let index = selected_name
+            .and_then(|name| self.devices.iter().position(|d| d.name == name))
+            .or_else(|| (!self.devices.is_empty()).then_some(0));
+        self.table_state.select(index);
+    }
+
+    fn selected_device(&self) -> Option<&DeviceRow> {
+        self.table_state.selected().and_then(|i| self.devices.get(i))
+    }
+
+    fn set_message(&mut self, message: String, ttl: u8) {
+        self.status_message = Some(message);
+        self.message_ttl = ttl;
+    }
+
+    /// Open the file picker for the selected device.
+    fn open_picker(&mut self) {
+        if self.job.is_some() {
+            self.set_message("A send is already running".into(), 12);
+            return;
+        }
+        let Some(device) = self.selected_device().cloned() else {
+            self.set_message("No device selected".into(), 12);
+            return;
+        };
+        self.picker = Some((device, FileBrowserComponent::new()));
+    }
+
+    /// Keys while the picker is open: Esc closes it, Enter on a file sends
+    /// it, everything else browses.
+    fn handle_picker_key(&mut self, key: KeyEvent) -> Action {
+        let Some((_, browser)) = self.picker.as_mut() else {
+            return Action::Noop;
+        };
+        match key.code {
+            KeyCode::Esc => {
+                self.picker = None;
+                Action::Back
+            }
+            KeyCode::Enter => {
+                let file = browser
+                    .selected_entry()
+                    .filter(|e| !e.is_dir)
+                    .map(|e| e.full_path.clone());
+                match file {
+                    Some(path) => {
+                        if let Some((device, _)) = self.picker.take() {
+                            self.start_send(device, path);
+                        }
+                        Action::Select
+                    }
+                    None => browser.handle_key_event(key),
+                }
+            }
+            _ => browser.handle_key_event(key),
+        }
+    }
+
+    /// Send `path` to `device` on a background thread.
+    #[cfg(feature = "net")]
+    fn start_send(&mut self, device: DeviceRow, path: PathBuf) {
+        let progress = ProgressBar::hidden();
+        let (tx, rx) = mpsc::channel();
+        let file = path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| path.display().to_string());
+        let bar = progress.clone();
+        let target = device.clone();
+        std::thread::spawn(move || {
+            let our_name = gethostname::gethostname().to_string_lossy().to_string();
+            let result = crate::net::sender::send_file_quiet(
+                &format!("@{}", target.name),
+                &target.host,
+                target.port,
+                &path,
+                true,
+                &our_name,
+                &bar,
+            )
+            .map(|report| (report.bytes, report.checksum_verified))
+            .map_err(|e| e.to_string());
+            let _ = tx.send(result);
+        });
+        self.job = Some(SendJob {
+            device: device.name,
+            file,
+            progress,
+            result: rx,
+        });
+    }
+
+    #[cfg(not(feature = "net"))]
+    fn start_send(&mut self, _device: DeviceRow, _path: PathBuf) {
+        self.set_message("Error: this build has no network support".into(), 20);
+    }
+
+    /// Take the latest discovery result, if a round finished.
+    fn poll_discovery(&mut self) {
+        let Some(rx) = self.discovery.as_ref() else {
+            return;
+        };
+        let mut latest = None;
+        loop {
+            match rx.try_recv() {
+                Ok(result) => latest = Some(result),
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    self.discovery = None;
+                    break;
+                }
+            }
+        }
+        match latest {
+            Some(Ok(devices)) => {
+                self.searching = false;
+                self.discovery_error = None;
+                self.set_devices(devices);
+            }
+            Some(Err(e)) => {
+                self.searching = false;
+                self.discovery_error = Some(e);
+            }
+            None => {}
+        }
+    }
+
+    /// Report the running send once it finishes.
+    fn poll_job(&mut self) {
+        let Some(job) = self.job.as_ref() else {
+            return;
+        };
+        let message = match job.result.try_recv() {
+            Ok(Ok((bytes, verified))) => {
+                let check = match verified {
+                    Some(true) => ", checksum verified",
+                    Some(false) => ", CHECKSUM MISMATCH",
+                    None => "",
+                };
+                format!(
+                    "Sent {} to {} ({}{})",
+                    job.file,
+                    job.device,
+                    bytesize::ByteSize(bytes),
+                    check
+                )
+            }
+            Ok(Err(e)) => format!("Error: sending {} to {} failed: {}", job.file, job.device, e),
+            Err(TryRecvError::Empty) => return,
+            Err(TryRecvError::Disconnected) => {
+                format!("Error: sending {} to {} stopped", job.file, job.device)
+            }
+        };
+        self.job = None;
+        self.set_message(message, 40);
+    }
+
+    fn render_table(&self, frame: &mut Frame, area: Rect) {
+        if self.devices.is_empty() {
+            let text = if let Some(ref e) = self.discovery_error {
+                format!("Discovery failed: {}", e)
+            } else if self.searching {
+                "Searching for flux receivers...".to_string()
+            } else {
+                "No flux receivers found (start one with `flux receive`)".to_string()
+            };
+            let empty = Paragraph::new(text)
+                .style(
+                    Style::default()
+                        .fg(Color::DarkGray)
+                        .add_modifier(Modifier::DIM),
+                )
+                .block(Block::default().borders(Borders::ALL).title(" Devices (0) "));
+            frame.render_widget(empty, area);
+            return;
+        }
+
+        let header_cells = ["Name", "Address", "Version", "Trust"]
+            .iter()
+            .map(|h| Cell::from(*h).style(theme::HEADER));
+        let header = Row::new(header_cells).height(1);
+
+        let rows: Vec<Row> = self
+            .devices
+            .iter()
+            .map(|d| {
+                Row::new(vec![
+                    Cell::from(d.name.clone()),
+                    Cell::from(format!("{}:{}", d.host, d.port)),
+                    Cell::from(d.version.clone().unwrap_or_else(|| "?".into())),
+                    Cell::from(Span::styled(d.trust.label(), d.trust.style())),
+                ])
+            })
+            .collect();
+
+        let title = format!(" Devices ({}) ", self.devices.len());
+        let table = Table::new(
+            rows,
+            [
+                Constraint::Percentage(35),
+                Constraint::Percentage(30),
+                Constraint::Length(10),
+                Constraint::Length(12),
+            ],
+        )
+        .header(header)
+        .block(Block::default().borders(Borders::ALL).title(title))
+        .row_highlight_style(theme::SELECTED);
+
+        let mut table_state = self.table_state;
+        frame.render_stateful_widget(table, area, &mut table_state);
+    }
+}
+
+/// Start the background discovery loop. It stops once the receiver (the
+/// TUI) is gone.
+#[cfg(feature = "net")]
+fn spawn_discovery() -> Receiver<DiscoveryResult> {
+    let (tx, rx) = mpsc::channel();
+    std::thread::spawn(move || loop {
+        // Reloaded each round so `flux trust` changes show up
+        let store = crate::config::paths::flux_config_dir()
+            .and_then(|dir| TrustStore::load(&dir))
+            .ok();
+        let result = crate::discovery::mdns::discover_flux_devices(DISCOVERY_SECS)
+            .map(|devices| {
+                devices
+                    .into_iter()
+                    .map(|d| DeviceRow {
+                        trust: Trust::of(store.as_ref(), &d.name, d.public_key.as_deref()),
+                        name: d.name,
+                        host: d.host,
+                        port: d.port,
+                        version: d.version,
+                    })
+                    .collect()
+            })
+            .map_err(|e| e.to_string());
+        if tx.send(result).is_err() {
+            break;
+        }
+        std::thread::sleep(DISCOVERY_PAUSE);
+    });
+    rx
+}
+
+impl Component for DevicesComponent {
+    fn handle_key_event(&mut self, key: KeyEvent) -> Action {
+        if self.picker.is_some() {
+            return self.handle_picker_key(key);
+        }
+        match key.code {
+            KeyCode::Up | KeyCode::Char('k') => {
+                if !self.devices.is_empty() {
+                    let current = self.table_state.selected().unwrap_or(0);
+                    let prev = if current == 0 {
+                        self.devices.len() - 1
+                    } else {
+                        current - 1
+                    };
+                    self.table_state.select(Some(prev));
+                }
+                Action::ScrollUp
+            }
+            KeyCode::Down | KeyCode::Char('j') => {
+                if !self.devices.is_empty() {
+                    let current = self.table_state.selected().unwrap_or(0);
+                    let next = (current + 1) % self.devices.len();
+                    self.table_state.select(Some(next));
+                }
+                Action::ScrollDown
+            }
+            KeyCode::Enter | KeyCode::Char('s') => {
+                self.open_picker();
+                Action::Select
+            }
+            _ => Action::Noop,
+        }
+    }
+
+    fn update(&mut self) {
+        self.poll_discovery();
+        self.poll_job();
+
+        // Decrement message TTL
+        if self.message_ttl > 0 {
+            self.message_ttl -= 1;
+            if self.message_ttl == 0 {
+                self.status_message = None;
+            }
+        }
+    }
+
+    fn render(&self, frame: &mut Frame, area: Rect) {
+        // Layout: table or picker, send progress, optional status line
+        let mut constraints = vec![Constraint::Min(3)];
+        if self.job.is_some() {
+            constraints.push(Constraint::Length(3));
+        }
+        if self.status_message.is_some() {
+            constraints.push(Constraint::Length(1));
+        }
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints(constraints)
+            .split(area);
+
+        if let Some((ref device, ref browser)) = self.picker {
+            let parts = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([Constraint::Length(1), Constraint::Min(3)])
+                .split(chunks[0]);
+            let hint = format!(
+                " Pick a file to send to {} (Enter: send, Esc: cancel)",
+                device.name
+            );
+            frame.render_widget(
+                Paragraph::new(hint).style(Style::default().fg(Color::Cyan)),
+                parts[0],
+            );
+            browser.render(frame, parts[1]);
+        } else {
+            self.render_table(frame, chunks[0]);
+        }
+
+        let mut next = 1;
+        if let Some(ref job) = self.job {
+            let total = job.progress.length().unwrap_or(0);
+            let done = job.progress.position();
+            let ratio = if total == 0 {
+                0.0
+            } else {
+                (done as f64 / total as f64).min(1.0)
+            };
+            let gauge = Gauge::default()
+                .block(
+                    Block::default()
+                        .borders(Borders::ALL)
+                        .title(format!(" Sending {} to {} ", job.file, job.device)),
+                )
+                .gauge_style(Style::default().fg(Color::Green))
+                .ratio(ratio)
+                .label(format!(
+                    "{} / {}",
+                    bytesize::ByteSize(done),
+                    bytesize::ByteSize(total)
+                ));
+            frame.render_widget(gauge, chunks[next]);
+            next += 1;
+        }
+
+        if let Some(ref msg) = self.status_message {
+            let style = if msg.starts_with("Error") {
+                Style::default().fg(Color::Red)
+            } else {
+                Style::default().fg(Color::Green)
+            };
+            frame.render_widget(Paragraph::new(msg.as_str()).style(style), chunks[next]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn device(name: &str) -> DeviceRow {
+        DeviceRow {
+            name: name.into(),
+            host: "192.168.1.20".into(),
+            port: 9741,
+            version: Some("1.0.0".into()),
+            trust: Trust::Unknown,
+        }
+    }
+
+    #[cfg(feature = "net")]
+    #[test]
+    fn trust_follows_the_store() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut store = TrustStore::load(dir.path()).unwrap();
+        store.add_device("laptop".into(), "a2V5".into(), "laptop".into());
+
+        assert_eq!(Trust::of(Some(&store), "laptop", Some("a2V5")), Trust::Trusted);
+        assert_eq!(Trust::of(Some(&store), "laptop", Some("b3Ro")), Trust::KeyChanged);
+        assert_eq!(Trust::of(Some(&store), "desktop", Some("a2V5")), Trust::Unknown);
+        assert_eq!(Trust::of(None, "laptop", Some("a2V5")), Trust::Unknown);
+        assert_eq!(Trust::of(Some(&store), "laptop", None), Trust::NoKey);
+    }
+
+    #[test]
+    fn refresh_keeps_the_selected_device() {
+        let mut view = DevicesComponent::with_devices(vec![device("a"), device("b")]);
+        view.handle_key_event(test_key(KeyCode::Char('j')));
+        assert_eq!(view.selected_device().unwrap().name, "b");
+
+        view.set_devices(vec![device("0"), device("a"), device("b")]);
+        assert_eq!(view.selected_device().unwrap().name, "b");
+
+        view.set_devices(vec![device("c")]);
+        assert_eq!(view.selected_device().unwrap().name, "c");
+
+        view.set_devices(Vec::new());
+        assert!(view.selected_device().is_none());
+    }
+
+    #[test]
+    fn enter_opens_and_esc_closes_the_picker() {
+        let mut view = DevicesComponent::with_devices(vec![device("laptop")]);
+        view.handle_key_event(test_key(KeyCode::Enter));
+        assert_eq!(view.picker.as_ref().unwrap().0.name, "laptop");
+
+        // Browsing keys go to the picker
+        assert_eq!(view.handle_key_event(test_key(KeyCode::Char('j'))), Action::ScrollDown);
+
+        view.handle_key_event(test_key(KeyCode::Esc));
+        assert!(view.picker.is_none());
+    }
+
+    #[test]
+    fn picker_needs_a_device() {
+        let mut view = DevicesComponent::with_devices(Vec::new());
+        view.handle_key_event(test_key(KeyCode::Char('s')));
+        assert!(view.picker.is_none());
+        assert_eq!(view.status_message.as_deref(), Some("No device selected"));
+    }
+
+    fn test_key(code: KeyCode) -> KeyEvent {
+        use ratatui::crossterm::event::{KeyEventKind, KeyEventState, KeyModifiers};
+        KeyEvent {
+            code,
+            modifiers: KeyModifiers::empty(),
+            kind: KeyEventKind::Press,
+            state: KeyEventState::empty(),
+        }
+    }
+}
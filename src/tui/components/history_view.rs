@@ -1,13 +1,16 @@
 //! History view component displaying past transfer records.
 //!
 //! Shows a scrollable table of transfer history entries with
-//! timestamp, status, source, destination, size, and duration.
+//! timestamp, status, source, destination, size, and duration, a status
+//! filter, and a footer with the bytes transferred today and this week.
+//! Enter re-queues the selected copy.
 
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
+use chrono::{DateTime, Datelike, TimeZone};
 use ratatui::Frame;
 use ratatui::crossterm::event::{KeyCode, KeyEvent};
-use ratatui::layout::{Constraint, Rect};
+use ratatui::layout::{Constraint, Direction, Layout, Rect};
 use ratatui::style::{Color, Modifier, Style};
 use ratatui::text::Span;
 use ratatui::widgets::{Block, Borders, Cell, Paragraph, Row, Table, TableState};
@@ -15,39 +18,114 @@ use ratatui::widgets::{Block, Borders, Cell, Paragraph, Row, Table, TableState};
 use super::Component;
 use crate::config::paths::flux_data_dir;
 use crate::queue::history::{HistoryEntry, HistoryStore};
+use crate::queue::state::QueueStore;
 use crate::tui::action::Action;
 use crate::tui::theme;
 
+/// Rows moved by PageUp/PageDown.
+const PAGE_ROWS: usize = 10;
+
+/// Which entries the history table shows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatusFilter {
+    All,
+    Completed,
+    Failed,
+    Cancelled,
+}
+
+impl StatusFilter {
+    /// The filter `f` switches to next.
+    pub fn next(self) -> Self {
+        match self {
+            StatusFilter::All => StatusFilter::Completed,
+            StatusFilter::Completed => StatusFilter::Failed,
+            StatusFilter::Failed => StatusFilter::Cancelled,
+            StatusFilter::Cancelled => StatusFilter::All,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            StatusFilter::All => "all",
+            StatusFilter::Completed => "completed",
+            StatusFilter::Failed => "failed",
+            StatusFilter::Cancelled => "cancelled",
+        }
+    }
+
+    pub fn matches(self, status: &str) -> bool {
+        self == StatusFilter::All || status == self.label()
+    }
+}
+
+/// Bytes and transfer counts for the footer.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Totals {
+    pub today_bytes: u64,
+    pub today_count: usize,
+    pub week_bytes: u64,
+    pub week_count: usize,
+}
+
+/// Sum the entries of the current day and week (from Monday) in `now`'s
+/// timezone.
+pub fn totals<Tz: TimeZone>(entries: &[HistoryEntry], now: &DateTime<Tz>) -> Totals {
+    let today = now.date_naive();
+    let week_start =
+        today - chrono::Duration::days(i64::from(now.weekday().num_days_from_monday()));
+    let mut totals = Totals::default();
+    for entry in entries {
+        let day = entry.timestamp.with_timezone(&now.timezone()).date_naive();
+        if day > today || day < week_start {
+            continue;
+        }
+        totals.week_bytes += entry.bytes;
+        totals.week_count += 1;
+        if day == today {
+            totals.today_bytes += entry.bytes;
+            totals.today_count += 1;
+        }
+    }
+    totals
+}
+
 /// History view component for the TUI.
 ///
 /// Displays recent transfer history entries in a scrollable table,
 /// showing most recent transfers first.
 pub struct HistoryViewComponent {
     entries: Vec<HistoryEntry>,
+    /// Indices into `entries` that pass the filter
+    visible: Vec<usize>,
+    filter: StatusFilter,
     table_state: TableState,
     data_dir: Option<PathBuf>,
+    status_message: Option<String>,
+    message_ttl: u8,
 }
 
 impl HistoryViewComponent {
     /// Create a new history view, loading initial data from disk.
     pub fn new() -> Self {
-        let data_dir = flux_data_dir().ok();
-        let mut component = Self {
-            entries: Vec::new(),
-            table_state: TableState::default(),
-            data_dir,
-        };
-        component.reload();
-        component
+        Self::from_data_dir(flux_data_dir().ok())
     }
 
     /// Create a history view with an explicit data directory (for testing).
     #[cfg(test)]
     pub fn with_data_dir(data_dir: std::path::PathBuf) -> Self {
+        Self::from_data_dir(Some(data_dir))
+    }
+
+    fn from_data_dir(data_dir: Option<PathBuf>) -> Self {
         let mut component = Self {
             entries: Vec::new(),
+            visible: Vec::new(),
+            filter: StatusFilter::All,
             table_state: TableState::default(),
-            data_dir: Some(data_dir),
+            data_dir,
+            status_message: None,
+            message_ttl: 0,
         };
         component.reload();
         component
@@ -64,14 +142,26 @@ impl HistoryViewComponent {
                 self.entries = entries;
             }
         }
+        self.apply_filter();
+    }
 
-        // Keep selection valid
-        if !self.entries.is_empty() {
+    /// Recompute the visible rows and keep the selection valid.
+    fn apply_filter(&mut self) {
+        let filter = self.filter;
+        self.visible = self
+            .entries
+            .iter()
+            .enumerate()
+            .filter(|(_, e)| filter.matches(&e.status))
+            .map(|(i, _)| i)
+            .collect();
+
+        if !self.visible.is_empty() {
             if self.table_state.selected().is_none() {
                 self.table_state.select(Some(0));
             } else if let Some(sel) = self.table_state.selected() {
-                if sel >= self.entries.len() {
-                    self.table_state.select(Some(self.entries.len() - 1));
+                if sel >= self.visible.len() {
+                    self.table_state.select(Some(self.visible.len() - 1));
                 }
             }
         } else {
@@ -79,6 +169,71 @@ impl HistoryViewComponent {
         }
     }
 
+    fn selected_entry(&self) -> Option<&HistoryEntry> {
+        self.table_state
+            .selected()
+            .and_then(|i| self.visible.get(i))
+            .map(|&i| &self.entries[i])
+    }
+
+    fn set_message(&mut self, message: String, ttl: u8) {
+        self.status_message = Some(message);
+        self.message_ttl = ttl;
+    }
+
+    /// Add the selected transfer to the queue again.
+    ///
+    /// Only local and backend copies (`cp` and earlier queue runs) can be
+    /// replayed; sends, receives and syncs need their own commands.
+    fn requeue_selected(&mut self) {
+        let entry = match self.selected_entry() {
+            Some(entry) => entry.clone(),
+            None => {
+                self.set_message("No entry selected".into(), 12);
+                return;
+            }
+        };
+        if entry.operation != "cp" && entry.operation != "queue" {
+            self.set_message(
+                format!("Error: only copies can be re-queued (this is a {})", entry.operation),
+                20,
+            );
+            return;
+        }
+        let Some(dir) = self.data_dir.clone() else {
+            return;
+        };
+
+        let recursive = Path::new(&entry.source).is_dir();
+        let verify = entry.verified.is_some();
+        let result = QueueStore::load(&dir).and_then(|mut store| {
+            let id = store.add(entry.source.clone(), entry.dest.clone(), recursive, verify, false);
+            store.save().map(|()| id)
+        });
+        match result {
+            Ok(id) => self.set_message(
+                format!("Queued #{}: {} -> {}", id, entry.source, entry.dest),
+                12,
+            ),
+            Err(e) => self.set_message(format!("Error: {}", e), 20),
+        }
+    }
+
+    /// Move the selection by `delta` rows, wrapping for single steps and
+    /// stopping at the ends for page jumps.
+    fn move_selection(&mut self, delta: isize) {
+        let len = self.visible.len();
+        if len == 0 {
+            return;
+        }
+        let current = self.table_state.selected().unwrap_or(0) as isize;
+        let target = if delta.unsigned_abs() == 1 {
+            (current + delta).rem_euclid(len as isize)
+        } else {
+            (current + delta).clamp(0, len as isize - 1)
+        };
+        self.table_state.select(Some(target as usize));
+    }
     /// Format a duration in seconds as a human-readable string.
     fn format_duration(secs: f64) -> String {
         if secs < 1.0 {
@@ -103,99 +258,163 @@ impl HistoryViewComponent {
             _ => Style::default(),
         }
     }
+
+    /// Footer line: today's and this week's totals plus the active filter.
+    fn footer_text(&self) -> String {
+        let totals = totals(&self.entries, &chrono::Local::now());
+        format!(
+            " Today: {} in {} transfer(s) │ This week: {} in {} transfer(s) │ Filter: {} ({} of {})",
+            bytesize::ByteSize(totals.today_bytes),
+            totals.today_count,
+            bytesize::ByteSize(totals.week_bytes),
+            totals.week_count,
+            self.filter.label(),
+            self.visible.len(),
+            self.entries.len()
+        )
+    }
 }
 
 impl Component for HistoryViewComponent {
     fn handle_key_event(&mut self, key: KeyEvent) -> Action {
         match key.code {
             KeyCode::Up | KeyCode::Char('k') => {
-                if !self.entries.is_empty() {
-                    let current = self.table_state.selected().unwrap_or(0);
-                    let prev = if current == 0 {
-                        self.entries.len() - 1
-                    } else {
-                        current - 1
-                    };
-                    self.table_state.select(Some(prev));
-                }
+                self.move_selection(-1);
                 Action::ScrollUp
             }
             KeyCode::Down | KeyCode::Char('j') => {
-                if !self.entries.is_empty() {
-                    let current = self.table_state.selected().unwrap_or(0);
-                    let next = (current + 1) % self.entries.len();
-                    self.table_state.select(Some(next));
-                }
+                self.move_selection(1);
+                Action::ScrollDown
+            }
+            KeyCode::PageUp => {
+                self.move_selection(-(PAGE_ROWS as isize));
+                Action::ScrollUp
+            }
+            KeyCode::PageDown => {
+                self.move_selection(PAGE_ROWS as isize);
                 Action::ScrollDown
             }
+            KeyCode::Char('f') => {
+                self.filter = self.filter.next();
+                self.table_state.select(None);
+                self.apply_filter();
+                Action::Noop
+            }
+            KeyCode::Enter => {
+                self.requeue_selected();
+                Action::Noop
+            }
             _ => Action::Noop,
         }
     }
 
     fn update(&mut self) {
         self.reload();
+
+        // Decrement message TTL
+        if self.message_ttl > 0 {
+            self.message_ttl -= 1;
+            if self.message_ttl == 0 {
+                self.status_message = None;
+            }
+        }
     }
 
     fn render(&self, frame: &mut Frame, area: Rect) {
-        if self.entries.is_empty() {
-            let empty = Paragraph::new("No transfer history")
+        // Layout: table, totals footer, optional status line
+        let mut constraints = vec![Constraint::Min(3), Constraint::Length(1)];
+        if self.status_message.is_some() {
+            constraints.push(Constraint::Length(1));
+        }
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints(constraints)
+            .split(area);
+
+        if self.visible.is_empty() {
+            let (text, title) = if self.entries.is_empty() {
+                ("No transfer history".to_string(), " Transfer History (0 entries) ".to_string())
+            } else {
+                (
+                    format!("No {} transfers (press f to change the filter)", self.filter.label()),
+                    format!(" Transfer History (0 of {} entries) ", self.entries.len()),
+                )
+            };
+            let empty = Paragraph::new(text)
                 .style(
                     Style::default()
                         .fg(Color::DarkGray)
                         .add_modifier(Modifier::DIM),
                 )
-                .block(
-                    Block::default()
-                        .borders(Borders::ALL)
-                        .title(" Transfer History (0 entries) "),
-                );
-            frame.render_widget(empty, area);
-            return;
+                .block(Block::default().borders(Borders::ALL).title(title));
+            frame.render_widget(empty, chunks[0]);
+        } else {
+            let header_cells = ["Timestamp", "Status", "Source", "Dest", "Size", "Duration"]
+                .iter()
+                .map(|h| Cell::from(*h).style(theme::HEADER));
+            let header = Row::new(header_cells).height(1);
+
+            let rows: Vec<Row> = self
+                .visible
+                .iter()
+                .map(|&i| {
+                    let e = &self.entries[i];
+                    let ts = e.timestamp.format("%Y-%m-%d %H:%M:%S").to_string();
+                    let style = Self::status_style(&e.status);
+                    let size_str = format!("{}", bytesize::ByteSize(e.bytes));
+                    let dur_str = Self::format_duration(e.duration_secs);
+
+                    Row::new(vec![
+                        Cell::from(ts),
+                        Cell::from(Span::styled(e.status.clone(), style)),
+                        Cell::from(truncate_str(&e.source, 25)),
+                        Cell::from(truncate_str(&e.dest, 25)),
+                        Cell::from(size_str),
+                        Cell::from(dur_str),
+                    ])
+                })
+                .collect();
+
+            let title = if self.filter == StatusFilter::All {
+                format!(" Transfer History ({} entries) ", self.entries.len())
+            } else {
+                format!(
+                    " Transfer History ({} of {} entries, {}) ",
+                    self.visible.len(),
+                    self.entries.len(),
+                    self.filter.label()
+                )
+            };
+            let table = Table::new(
+                rows,
+                [
+                    Constraint::Length(20),
+                    Constraint::Length(10),
+                    Constraint::Percentage(25),
+                    Constraint::Percentage(25),
+                    Constraint::Length(10),
+                    Constraint::Length(10),
+                ],
+            )
+            .header(header)
+            .block(Block::default().borders(Borders::ALL).title(title))
+            .row_highlight_style(theme::SELECTED);
+
+            let mut table_state = self.table_state;
+            frame.render_stateful_widget(table, chunks[0], &mut table_state);
         }
 
-        let header_cells = ["Timestamp", "Status", "Source", "Dest", "Size", "Duration"]
-            .iter()
-            .map(|h| Cell::from(*h).style(theme::HEADER));
-        let header = Row::new(header_cells).height(1);
-
-        let rows: Vec<Row> = self
-            .entries
-            .iter()
-            .map(|e| {
-                let ts = e.timestamp.format("%Y-%m-%d %H:%M:%S").to_string();
-                let style = Self::status_style(&e.status);
-                let size_str = format!("{}", bytesize::ByteSize(e.bytes));
-                let dur_str = Self::format_duration(e.duration_secs);
-
-                Row::new(vec![
-                    Cell::from(ts),
-                    Cell::from(Span::styled(e.status.clone(), style)),
-                    Cell::from(truncate_str(&e.source, 25)),
-                    Cell::from(truncate_str(&e.dest, 25)),
-                    Cell::from(size_str),
-                    Cell::from(dur_str),
-                ])
-            })
-            .collect();
-
-        let title = format!(" Transfer History ({} entries) ", self.entries.len());
-        let table = Table::new(
-            rows,
-            [
-                Constraint::Length(20),
-                Constraint::Length(10),
-                Constraint::Percentage(25),
-                Constraint::Percentage(25),
-                Constraint::Length(10),
-                Constraint::Length(10),
-            ],
-        )
-        .header(header)
-        .block(Block::default().borders(Borders::ALL).title(title))
-        .row_highlight_style(theme::SELECTED);
+        let footer = Paragraph::new(self.footer_text()).style(Style::default().fg(Color::Cyan));
+        frame.render_widget(footer, chunks[1]);
 
-        let mut table_state = self.table_state.clone();
-        frame.render_stateful_widget(table, area, &mut table_state);
+        if let Some(ref msg) = self.status_message {
+            let style = if msg.starts_with("Error") {
+                Style::default().fg(Color::Red)
+            } else {
+                Style::default().fg(Color::Green)
+            };
+            frame.render_widget(Paragraph::new(msg.as_str()).style(style), chunks[2]);
+        }
     }
 }
 
@@ -237,6 +456,12 @@ mod tests {
                 timestamp: Utc::now(),
                 status: "completed".into(),
                 error: None,
+                operation: "cp".into(),
+                peer: None,
+                verified: None,
+                receipt: None,
+                id: 0,
+                changes: None,
             })
             .unwrap();
         store
@@ -249,8 +474,15 @@ mod tests {
                 timestamp: Utc::now(),
                 status: "completed".into(),
                 error: None,
+                operation: "cp".into(),
+                peer: None,
+                verified: None,
+                receipt: None,
+                id: 0,
+                changes: None,
             })
             .unwrap();
+        drop(store);
 
         let view = HistoryViewComponent::with_data_dir(dir.path().to_path_buf());
         assert_eq!(view.entries.len(), 2);
@@ -275,9 +507,16 @@ mod tests {
                     timestamp: Utc::now(),
                     status: "completed".into(),
                     error: None,
+                    operation: "cp".into(),
+                    peer: None,
+                    verified: None,
+                    receipt: None,
+                    id: 0,
+                    changes: None,
                 })
                 .unwrap();
         }
+        drop(store);
 
         let mut view = HistoryViewComponent::with_data_dir(dir.path().to_path_buf());
         assert_eq!(view.table_state.selected(), Some(0));
@@ -307,6 +546,132 @@ mod tests {
         assert_eq!(HistoryViewComponent::format_duration(125.0), "2m 5s");
     }
 
+    fn entry(source: &str, status: &str, bytes: u64, timestamp: DateTime<Utc>) -> HistoryEntry {
+        HistoryEntry {
+            source: source.into(),
+            dest: format!("{}.out", source),
+            bytes,
+            files: 1,
+            duration_secs: 0.1,
+            timestamp,
+            status: status.into(),
+            error: None,
+            operation: "cp".into(),
+            peer: None,
+            verified: None,
+            receipt: None,
+            id: 0,
+            changes: None,
+        }
+    }
+
+    #[test]
+    fn status_filter_cycles_and_hides_rows() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut store = HistoryStore::load(dir.path(), 1000).unwrap();
+        store.append(entry("a", "completed", 1, Utc::now())).unwrap();
+        store.append(entry("b", "failed", 1, Utc::now())).unwrap();
+        store.append(entry("c", "completed", 1, Utc::now())).unwrap();
+        drop(store);
+
+        let mut view = HistoryViewComponent::with_data_dir(dir.path().to_path_buf());
+        assert_eq!(view.visible.len(), 3);
+
+        view.handle_key_event(test_key(KeyCode::Char('f')));
+        assert_eq!(view.filter, StatusFilter::Completed);
+        assert_eq!(view.visible.len(), 2);
+
+        view.handle_key_event(test_key(KeyCode::Char('f')));
+        assert_eq!(view.filter, StatusFilter::Failed);
+        assert_eq!(view.selected_entry().unwrap().source, "b");
+
+        view.handle_key_event(test_key(KeyCode::Char('f')));
+        assert!(view.visible.is_empty());
+        assert_eq!(view.table_state.selected(), None);
+
+        view.handle_key_event(test_key(KeyCode::Char('f')));
+        assert_eq!(view.filter, StatusFilter::All);
+        assert_eq!(view.visible.len(), 3);
+    }
+
+    #[test]
+    fn page_keys_stop_at_the_ends() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut store = HistoryStore::load(dir.path(), 1000).unwrap();
+        for i in 0..15 {
+            store
+                .append(entry(&format!("src_{}", i), "completed", 1, Utc::now()))
+                .unwrap();
+        }
+        drop(store);
+
+        let mut view = HistoryViewComponent::with_data_dir(dir.path().to_path_buf());
+        view.handle_key_event(test_key(KeyCode::PageDown));
+        assert_eq!(view.table_state.selected(), Some(10));
+        view.handle_key_event(test_key(KeyCode::PageDown));
+        assert_eq!(view.table_state.selected(), Some(14));
+        view.handle_key_event(test_key(KeyCode::PageUp));
+        view.handle_key_event(test_key(KeyCode::PageUp));
+        assert_eq!(view.table_state.selected(), Some(0));
+    }
+
+    #[test]
+    fn totals_count_today_and_this_week() {
+        // Wednesday 2025-06-11, noon
+        let now = Utc.with_ymd_and_hms(2025, 6, 11, 12, 0, 0).unwrap();
+        let entries = vec![
+            entry("today", "completed", 100, now - chrono::Duration::hours(2)),
+            entry("monday", "failed", 20, Utc.with_ymd_and_hms(2025, 6, 9, 8, 0, 0).unwrap()),
+            entry("last-week", "completed", 5, Utc.with_ymd_and_hms(2025, 6, 8, 23, 0, 0).unwrap()),
+        ];
+        assert_eq!(
+            totals(&entries, &now),
+            Totals {
+                today_bytes: 100,
+                today_count: 1,
+                week_bytes: 120,
+                week_count: 2,
+            }
+        );
+    }
+
+    #[test]
+    fn enter_requeues_a_copy() {
+        let dir = tempfile::tempdir().unwrap();
+        let src = dir.path().join("tree");
+        std::fs::create_dir(&src).unwrap();
+        let mut copy = entry(src.to_str().unwrap(), "failed", 0, Utc::now());
+        copy.verified = Some(false);
+        let mut store = HistoryStore::load(dir.path(), 1000).unwrap();
+        store.append(copy).unwrap();
+        drop(store);
+
+        let mut view = HistoryViewComponent::with_data_dir(dir.path().to_path_buf());
+        view.handle_key_event(test_key(KeyCode::Enter));
+        assert!(view.status_message.as_deref().unwrap().starts_with("Queued #1"));
+
+        let queue = QueueStore::load(dir.path()).unwrap();
+        let queued = &queue.list()[0];
+        assert_eq!(queued.source, src.to_str().unwrap());
+        assert!(queued.recursive);
+        assert!(queued.verify);
+    }
+
+    #[test]
+    fn enter_refuses_non_copies() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut send = entry("a.bin", "completed", 10, Utc::now());
+        send.operation = "send".into();
+        let mut store = HistoryStore::load(dir.path(), 1000).unwrap();
+        store.append(send).unwrap();
+        drop(store);
+
+        let mut view = HistoryViewComponent::with_data_dir(dir.path().to_path_buf());
+        view.handle_key_event(test_key(KeyCode::Enter));
+        assert!(view.status_message.as_deref().unwrap().contains("this is a send"));
+        assert!(QueueStore::load(dir.path()).unwrap().list().is_empty());
+    }
+
     fn test_key(code: KeyCode) -> KeyEvent {
         use ratatui::crossterm::event::{KeyEventKind, KeyEventState, KeyModifiers};
         KeyEvent {
@@ -4,10 +4,13 @@
 //! key event handling, state updates, and rendering.
 
 pub mod dashboard;
+pub mod devices;
 pub mod file_browser;
 pub mod history_view;
 pub mod queue_view;
+pub mod saved_view;
 pub mod status_bar;
+pub mod transfer_detail;
 
 use ratatui::Frame;
 use ratatui::layout::Rect;
@@ -1,12 +1,12 @@
 //! Queue management component for viewing and managing transfer jobs.
 //!
 //! Displays queue entries in a scrollable table with pause/resume/cancel
-//! key bindings and status feedback.
+//! key bindings, reordering with Shift+Up/Down, and status feedback.
 
 use std::path::PathBuf;
 
 use ratatui::Frame;
-use ratatui::crossterm::event::{KeyCode, KeyEvent};
+use ratatui::crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
 use ratatui::layout::{Constraint, Direction, Layout, Rect};
 use ratatui::style::{Color, Modifier, Style};
 use ratatui::text::Span;
@@ -130,6 +130,27 @@ impl QueueViewComponent {
         self.reload();
     }
 
+    /// Move the selected entry one place up or down, keeping it selected.
+    ///
+    /// It takes over the priority of the entry it passes, so the new order
+    /// is also the order they run in.
+    fn move_selected(&mut self, up: bool) {
+        let Some(index) = self.table_state.selected() else {
+            return;
+        };
+        let neighbour = if up { index.checked_sub(1) } else { Some(index + 1) };
+        let (Some(id), Some(target)) = (
+            self.selected_id(),
+            neighbour.and_then(|i| self.entries.get(i)).map(|e| e.id),
+        ) else {
+            return;
+        };
+        self.perform_action(|store, id| store.move_entry(id, target, !up), "Moved");
+        if let Some(moved) = self.entries.iter().position(|e| e.id == id) {
+            self.table_state.select(Some(moved));
+        }
+    }
+
     /// Clear all completed/failed/cancelled entries.
     fn clear_completed(&mut self) {
         if let Some(ref dir) = self.data_dir {
@@ -166,7 +187,16 @@ impl QueueViewComponent {
 
 impl Component for QueueViewComponent {
     fn handle_key_event(&mut self, key: KeyEvent) -> Action {
+        let shift = key.modifiers.contains(KeyModifiers::SHIFT);
         match key.code {
+            KeyCode::Up if shift => {
+                self.move_selected(true);
+                Action::Noop
+            }
+            KeyCode::Down if shift => {
+                self.move_selected(false);
+                Action::Noop
+            }
             KeyCode::Up | KeyCode::Char('k') => {
                 if !self.entries.is_empty() {
                     let current = self.table_state.selected().unwrap_or(0);
@@ -248,7 +278,7 @@ impl Component for QueueViewComponent {
                 );
             frame.render_widget(empty, chunks[0]);
         } else {
-            let header_cells = ["ID", "Status", "Source", "Dest", "Added"]
+            let header_cells = ["ID", "Status", "Priority", "Source", "Dest", "Added"]
                 .iter()
                 .map(|h| Cell::from(*h).style(theme::HEADER));
             let header = Row::new(header_cells).height(1);
@@ -264,6 +294,7 @@ impl Component for QueueViewComponent {
                     Row::new(vec![
                         Cell::from(format!("{}", e.id)),
                         Cell::from(Span::styled(status_str, style)),
+                        Cell::from(e.priority.to_string()),
                         Cell::from(truncate_str(&e.source, 35)),
                         Cell::from(truncate_str(&e.dest, 35)),
                         Cell::from(added),
@@ -277,6 +308,7 @@ impl Component for QueueViewComponent {
                 [
                     Constraint::Length(6),
                     Constraint::Length(12),
+                    Constraint::Length(10),
                     Constraint::Percentage(35),
                     Constraint::Percentage(35),
                     Constraint::Length(16),
@@ -336,6 +368,7 @@ mod tests {
         store.add("src1".into(), "dst1".into(), false, false, false);
         store.add("src2".into(), "dst2".into(), false, false, false);
         store.save().unwrap();
+        drop(store);
 
         let view = QueueViewComponent::with_data_dir(dir.path().to_path_buf());
         assert_eq!(view.entries.len(), 2);
@@ -350,6 +383,7 @@ mod tests {
         store.add("a".into(), "b".into(), false, false, false);
         store.add("c".into(), "d".into(), false, false, false);
         store.save().unwrap();
+        drop(store);
 
         let mut view = QueueViewComponent::with_data_dir(dir.path().to_path_buf());
         assert_eq!(view.table_state.selected(), Some(0));
@@ -368,6 +402,7 @@ mod tests {
         let mut store = QueueStore::load(dir.path()).unwrap();
         store.add("a".into(), "b".into(), false, false, false);
         store.save().unwrap();
+        drop(store);
 
         let mut view = QueueViewComponent::with_data_dir(dir.path().to_path_buf());
 
@@ -412,6 +447,7 @@ mod tests {
         store.add("c".into(), "d".into(), false, false, false); // 2: will complete
         store.get_mut(2).unwrap().status = QueueStatus::Completed;
         store.save().unwrap();
+        drop(store);
 
         let mut view = QueueViewComponent::with_data_dir(dir.path().to_path_buf());
         assert_eq!(view.entries.len(), 2);
@@ -421,6 +457,34 @@ mod tests {
         assert_eq!(view.entries.len(), 1);
     }
 
+    #[test]
+    fn queue_view_shift_arrows_reorder() {
+        use crate::queue::policy::QueuePriority;
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut store = QueueStore::load(dir.path()).unwrap();
+        store.add("a".into(), "b".into(), false, false, false);
+        store.add("c".into(), "d".into(), false, false, false);
+        store.get_mut(1).unwrap().priority = QueuePriority::High;
+        store.save().unwrap();
+        drop(store);
+
+        let mut view = QueueViewComponent::with_data_dir(dir.path().to_path_buf());
+        view.table_state.select(Some(1));
+        let mut shift_up = test_key(KeyCode::Up);
+        shift_up.modifiers = KeyModifiers::SHIFT;
+        view.handle_key_event(shift_up);
+
+        let ids: Vec<u64> = view.entries.iter().map(|e| e.id).collect();
+        assert_eq!(ids, vec![2, 1]);
+        assert_eq!(view.entries[0].priority, QueuePriority::High);
+        assert_eq!(view.table_state.selected(), Some(0));
+
+        // Nothing above the first entry
+        view.handle_key_event(shift_up);
+        assert_eq!(view.entries[0].id, 2);
+    }
+
     #[test]
     fn queue_view_message_ttl_decrements() {
         let dir = tempfile::tempdir().unwrap();
@@ -438,7 +502,7 @@ mod tests {
     }
 
     fn test_key(code: KeyCode) -> KeyEvent {
-        use ratatui::crossterm::event::{KeyEventKind, KeyEventState, KeyModifiers};
+        use ratatui::crossterm::event::{KeyEventKind, KeyEventState};
         KeyEvent {
             code,
             modifiers: KeyModifiers::empty(),
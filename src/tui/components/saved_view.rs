@@ -0,0 +1,325 @@
+//! Saved transfers tab: one-key shortcuts for `flux run`.
+//!
+//! Lists the transfers in `saved.toml`, each with a shortcut letter. The
+//! letter (or Enter on the selected row) starts `flux run <name>` as a
+//! background process (its progress shows in `flux status`); the row shows
+//! it as running until it exits. Runs keep going if the TUI is closed.
+
+use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
+
+use ratatui::Frame;
+use ratatui::crossterm::event::{KeyCode, KeyEvent};
+use ratatui::layout::{Constraint, Direction, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::Span;
+use ratatui::widgets::{Block, Borders, Cell, Paragraph, Row, Table, TableState};
+
+use super::Component;
+use crate::config::paths::flux_config_dir;
+use crate::config::saved::SavedStore;
+use crate::tui::action::Action;
+use crate::tui::theme;
+
+/// Shortcut letters, in list order (j/k navigate and q quits).
+const SHORTCUTS: &str = "abcdefghilmnoprstuvwxyz";
+
+/// A `flux run` started from this tab.
+struct RunningTransfer {
+    name: String,
+    child: Child,
+}
+
+/// Saved transfers view component for the TUI.
+pub struct SavedViewComponent {
+    /// Saved transfers as (name, command line), by name.
+    entries: Vec<(String, String)>,
+    table_state: TableState,
+    config_dir: Option<PathBuf>,
+    running: Vec<RunningTransfer>,
+    status_message: Option<String>,
+    message_ttl: u8,
+}
+
+impl SavedViewComponent {
+    /// Create a new saved transfers view, loading them from disk.
+    pub fn new() -> Self {
+        Self::with_config_dir(flux_config_dir().ok())
+    }
+
+    /// Create a view of the saved transfers in `config_dir`.
+    fn with_config_dir(config_dir: Option<PathBuf>) -> Self {
+        let mut component = Self {
+            entries: Vec::new(),
+            table_state: TableState::default(),
+            config_dir,
+            running: Vec::new(),
+            status_message: None,
+            message_ttl: 0,
+        };
+        component.reload();
+        component
+    }
+
+    /// Reload saved transfers from disk (best-effort).
+    fn reload(&mut self) {
+        if let Some(ref dir) = self.config_dir {
+            if let Ok(store) = SavedStore::load(dir) {
+                self.entries = store
+                    .list()
+                    .iter()
+                    .map(|(name, transfer)| (name.clone(), transfer.command_line()))
+                    .collect();
+            }
+        }
+
+        // Keep selection valid
+        if self.entries.is_empty() {
+            self.table_state.select(None);
+        } else {
+            let selected = self.table_state.selected().unwrap_or(0);
+            self.table_state.select(Some(selected.min(self.entries.len() - 1)));
+        }
+    }
+
+    /// The entry whose shortcut is `key`.
+    fn shortcut_index(&self, key: char) -> Option<usize> {
+        SHORTCUTS
+            .chars()
+            .position(|c| c == key)
+            .filter(|&index| index < self.entries.len())
+    }
+
+    fn set_message(&mut self, message: String, ttl: u8) {
+        self.status_message = Some(message);
+        self.message_ttl = ttl;
+    }
+
+    /// Start `flux run` for the entry at `index`.
+    fn launch(&mut self, index: usize) {
+        let Some((name, _)) = self.entries.get(index) else {
+            return;
+        };
+        let name = name.clone();
+        self.table_state.select(Some(index));
+        if self.running.iter().any(|r| r.name == name) {
+            self.set_message(format!("{} is already running", name), 12);
+            return;
+        }
+        let spawned = std::env::current_exe().and_then(|exe| {
+            Command::new(exe)
+                .args(["--quiet", "run", &name])
+                .stdin(Stdio::null())
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .spawn()
+        });
+        match spawned {
+            Ok(child) => {
+                self.set_message(format!("Started {}", name), 12);
+                self.running.push(RunningTransfer { name, child });
+            }
+            Err(e) => self.set_message(format!("Error: cannot start {}: {}", name, e), 20),
+        }
+    }
+
+    /// Report runs that have exited.
+    fn poll_running(&mut self) {
+        let mut finished = Vec::new();
+        self.running.retain_mut(|run| match run.child.try_wait() {
+            Ok(Some(status)) if status.success() => {
+                finished.push(format!("{} finished", run.name));
+                false
+            }
+            Ok(Some(status)) => {
+                finished.push(format!("Error: {} failed ({})", run.name, status));
+                false
+            }
+            Ok(None) => true,
+            Err(e) => {
+                finished.push(format!("Error: {}: {}", run.name, e));
+                false
+            }
+        });
+        if let Some(message) = finished.pop() {
+            self.set_message(message, 20);
+        }
+    }
+
+    fn is_running(&self, name: &str) -> bool {
+        self.running.iter().any(|r| r.name == name)
+    }
+}
+
+impl Component for SavedViewComponent {
+    fn handle_key_event(&mut self, key: KeyEvent) -> Action {
+        match key.code {
+            KeyCode::Up | KeyCode::Char('k') => {
+                if !self.entries.is_empty() {
+                    let current = self.table_state.selected().unwrap_or(0);
+                    let prev = if current == 0 {
+                        self.entries.len() - 1
+                    } else {
+                        current - 1
+                    };
+                    self.table_state.select(Some(prev));
+                }
+                Action::ScrollUp
+            }
+            KeyCode::Down | KeyCode::Char('j') => {
+                if !self.entries.is_empty() {
+                    let current = self.table_state.selected().unwrap_or(0);
+                    let next = (current + 1) % self.entries.len();
+                    self.table_state.select(Some(next));
+                }
+                Action::ScrollDown
+            }
+            KeyCode::Enter => {
+                if let Some(index) = self.table_state.selected() {
+                    self.launch(index);
+                }
+                Action::Select
+            }
+            KeyCode::Char(c) => match self.shortcut_index(c) {
+                Some(index) => {
+                    self.launch(index);
+                    Action::Select
+                }
+                None => Action::Noop,
+            },
+            _ => Action::Noop,
+        }
+    }
+
+    fn update(&mut self) {
+        self.reload();
+        self.poll_running();
+
+        // Decrement message TTL
+        if self.message_ttl > 0 {
+            self.message_ttl -= 1;
+            if self.message_ttl == 0 {
+                self.status_message = None;
+            }
+        }
+    }
+
+    fn render(&self, frame: &mut Frame, area: Rect) {
+        let has_message = self.status_message.is_some();
+        let constraints = if has_message {
+            vec![Constraint::Min(3), Constraint::Length(1)]
+        } else {
+            vec![Constraint::Min(3)]
+        };
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints(constraints)
+            .split(area);
+
+        if self.entries.is_empty() {
+            let empty = Paragraph::new(
+                "No saved transfers. Run a cp or sync, then `flux save <name>`.",
+            )
+            .style(
+                Style::default()
+                    .fg(Color::DarkGray)
+                    .add_modifier(Modifier::DIM),
+            )
+            .block(Block::default().borders(Borders::ALL).title(" Saved "));
+            frame.render_widget(empty, chunks[0]);
+        } else {
+            let header_cells = ["Key", "Name", "Command", "Status"]
+                .iter()
+                .map(|h| Cell::from(*h).style(theme::HEADER));
+            let header = Row::new(header_cells).height(1);
+
+            let mut keys = SHORTCUTS.chars();
+            let rows: Vec<Row> = self
+                .entries
+                .iter()
+                .map(|(name, line)| {
+                    let key = keys.next().map(String::from).unwrap_or_default();
+                    let status = if self.is_running(name) {
+                        Span::styled("running", theme::SUCCESS)
+                    } else {
+                        Span::raw("")
+                    };
+                    Row::new(vec![
+                        Cell::from(Span::styled(key, theme::WARNING)),
+                        Cell::from(name.as_str()),
+                        Cell::from(line.as_str()),
+                        Cell::from(status),
+                    ])
+                })
+                .collect();
+
+            let title = format!(" Saved ({} transfers) ", self.entries.len());
+            let table = Table::new(
+                rows,
+                [
+                    Constraint::Length(5),
+                    Constraint::Length(18),
+                    Constraint::Min(20),
+                    Constraint::Length(9),
+                ],
+            )
+            .header(header)
+            .block(Block::default().borders(Borders::ALL).title(title))
+            .row_highlight_style(theme::SELECTED);
+
+            let mut table_state = self.table_state;
+            frame.render_stateful_widget(table, chunks[0], &mut table_state);
+        }
+
+        if let Some(ref msg) = self.status_message {
+            let style = if msg.starts_with("Error") {
+                theme::ERROR
+            } else {
+                theme::SUCCESS
+            };
+            frame.render_widget(Paragraph::new(msg.as_str()).style(style), chunks[1]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::saved::SavedTransfer;
+    use ratatui::crossterm::event::{KeyEventKind, KeyEventState, KeyModifiers};
+
+    fn test_key(code: KeyCode) -> KeyEvent {
+        KeyEvent {
+            code,
+            modifiers: KeyModifiers::empty(),
+            kind: KeyEventKind::Press,
+            state: KeyEventState::empty(),
+        }
+    }
+
+    #[test]
+    fn entries_get_shortcuts_in_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut store = SavedStore::load(dir.path()).unwrap();
+        for name in ["docs", "photos"] {
+            let transfer = SavedTransfer {
+                args: vec!["cp".into(), name.into(), format!("nas:{}", name)],
+                dir: PathBuf::from("/"),
+            };
+            store.add(name.into(), transfer);
+        }
+        store.save().unwrap();
+
+        let mut view = SavedViewComponent::with_config_dir(Some(dir.path().to_path_buf()));
+        assert_eq!(view.entries.len(), 2);
+        assert_eq!(view.entries[1].1, "flux cp photos nas:photos");
+        assert_eq!(view.shortcut_index('a'), Some(0));
+        assert_eq!(view.shortcut_index('b'), Some(1));
+        assert_eq!(view.shortcut_index('c'), None);
+        assert_eq!(view.shortcut_index('j'), None);
+
+        view.handle_key_event(test_key(KeyCode::Char('j')));
+        assert_eq!(view.table_state.selected(), Some(1));
+        assert_eq!(view.handle_key_event(test_key(KeyCode::Char('z'))), Action::Noop);
+    }
+}
@@ -0,0 +1,451 @@
+//! Transfer Detail component: live view of one in-flight queue transfer.
+//!
+//! Finds running entries by their control sockets and polls the selected one
+//! for a `MonitorSnapshot` (see `transfer::control`). Shows a throughput graph
+//! of the last 60 seconds, the chunk map, ETA and retries; `+`/`-` change the
+//! bandwidth limit of the running copy.
+
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use ratatui::Frame;
+use ratatui::crossterm::event::{KeyCode, KeyEvent};
+use ratatui::layout::{Constraint, Direction, Layout, Rect};
+use ratatui::style::{Color, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, Gauge, Paragraph, Sparkline, Wrap};
+
+use super::Component;
+use super::dashboard::SpeedHistory;
+use crate::config::paths::flux_data_dir;
+use crate::transfer::control::{request_limit, request_stats, running_entries};
+use crate::transfer::monitor::MonitorSnapshot;
+use crate::tui::action::Action;
+use crate::tui::theme;
+
+/// Seconds of throughput shown in the graph (one sample per second).
+const GRAPH_SECONDS: usize = 60;
+
+/// Lowest limit `-` goes down to (64 KiB/s).
+const MIN_LIMIT: u64 = 64 * 1024;
+
+/// Limit `-` starts from when the transfer is unlimited and idle (1 MB/s).
+const DEFAULT_LIMIT: u64 = 1_000_000;
+
+/// Transfer Detail view component for the TUI.
+pub struct TransferDetailComponent {
+    data_dir: Option<PathBuf>,
+    /// Ids of the queue entries in flight
+    running: Vec<u64>,
+    selected: usize,
+    snapshot: Option<MonitorSnapshot>,
+    speed_history: SpeedHistory,
+    /// Time and byte count of the last graph sample
+    last_sample: Option<(Instant, u64)>,
+    status_message: Option<String>,
+    message_ttl: u8,
+}
+
+impl TransferDetailComponent {
+    /// Create a new transfer detail view for the default data directory.
+    pub fn new() -> Self {
+        Self::with_optional_data_dir(flux_data_dir().ok())
+    }
+
+    /// Create a transfer detail view with an explicit data directory (for testing).
+    #[cfg(test)]
+    pub fn with_data_dir(data_dir: PathBuf) -> Self {
+        Self::with_optional_data_dir(Some(data_dir))
+    }
+
+    fn with_optional_data_dir(data_dir: Option<PathBuf>) -> Self {
+        Self {
+            data_dir,
+            running: Vec::new(),
+            selected: 0,
+            snapshot: None,
+            speed_history: SpeedHistory::new(GRAPH_SECONDS),
+            last_sample: None,
+            status_message: None,
+            message_ttl: 0,
+        }
+    }
+
+    /// Id of the transfer being shown.
+    fn selected_id(&self) -> Option<u64> {
+        self.running.get(self.selected).copied()
+    }
+
+    /// Show another transfer; its graph starts empty.
+    fn select(&mut self, index: usize) {
+        if index != self.selected {
+            self.selected = index;
+            self.clear();
+        }
+    }
+
+    fn clear(&mut self) {
+        self.snapshot = None;
+        self.speed_history = SpeedHistory::new(GRAPH_SECONDS);
+        self.last_sample = None;
+    }
+
+    /// Throughput over the last few seconds, in bytes/sec.
+    fn current_speed(&self) -> u64 {
+        let samples = self.speed_history.as_slice();
+        let recent = &samples[samples.len().saturating_sub(3)..];
+        if recent.is_empty() {
+            return 0;
+        }
+        recent.iter().sum::<u64>() / recent.len() as u64
+    }
+
+    /// Record a throughput sample once a second.
+    fn sample(&mut self, bytes_done: u64) {
+        let now = Instant::now();
+        match self.last_sample {
+            Some((at, bytes)) => {
+                let elapsed = now.duration_since(at);
+                if elapsed >= Duration::from_secs(1) {
+                    let delta = bytes_done.saturating_sub(bytes);
+                    self.speed_history
+                        .push((delta as f64 / elapsed.as_secs_f64()) as u64);
+                    self.last_sample = Some((now, bytes_done));
+                }
+            }
+            None => self.last_sample = Some((now, bytes_done)),
+        }
+    }
+
+    /// Send a new bandwidth limit to the selected transfer.
+    fn change_limit(&mut self, raise: bool) {
+        let (Some(id), Some(dir), Some(snapshot)) =
+            (self.selected_id(), self.data_dir.clone(), self.snapshot.as_ref())
+        else {
+            return;
+        };
+        let limit = next_limit(snapshot.limit, self.current_speed(), raise);
+        self.set_limit(id, dir, limit);
+    }
+
+    fn set_limit(&mut self, id: u64, dir: PathBuf, limit: Option<u64>) {
+        self.status_message = Some(match request_limit(&dir, id, limit) {
+            Ok(true) => format!("Limit for #{}: {}", id, format_limit(limit)),
+            Ok(false) => format!("#{} is no longer running", id),
+            Err(e) => format!("Error: {}", e),
+        });
+        self.message_ttl = 12;
+        if let Some(snapshot) = self.snapshot.as_mut() {
+            snapshot.limit = limit;
+        }
+    }
+}
+
+/// The limit after pressing `+` (`raise`) or `-`: 25% steps, never below
+/// `MIN_LIMIT`. `-` on an unlimited transfer starts from its current speed;
+/// `+` leaves an unlimited transfer alone.
+pub fn next_limit(current: Option<u64>, speed: u64, raise: bool) -> Option<u64> {
+    match (current, raise) {
+        (None, true) => None,
+        (Some(limit), true) => Some(limit.saturating_add(limit / 4).max(MIN_LIMIT)),
+        (current, false) => {
+            let base = current.unwrap_or(if speed > 0 { speed } else { DEFAULT_LIMIT });
+            Some((base - base / 5).max(MIN_LIMIT))
+        }
+    }
+}
+
+/// Seconds left at `speed`, `None` if unknown.
+pub fn eta_secs(snapshot: &MonitorSnapshot, speed: u64) -> Option<u64> {
+    if speed == 0 || snapshot.total_bytes == 0 {
+        return None;
+    }
+    Some(snapshot.total_bytes.saturating_sub(snapshot.bytes_done).div_ceil(speed))
+}
+
+/// Format an ETA as `1h 02m`, `3m 05s` or `42s`.
+fn format_eta(secs: u64) -> String {
+    if secs >= 3600 {
+        format!("{}h {:02}m", secs / 3600, (secs % 3600) / 60)
+    } else if secs >= 60 {
+        format!("{}m {:02}s", secs / 60, secs % 60)
+    } else {
+        format!("{}s", secs)
+    }
+}
+
+fn format_limit(limit: Option<u64>) -> String {
+    match limit {
+        Some(bps) => format!("{}/s", bytesize::ByteSize(bps)),
+        None => "unlimited".to_string(),
+    }
+}
+
+/// One cell per chunk: filled when done.
+fn chunk_map(chunks: &[bool]) -> String {
+    chunks.iter().map(|&done| if done { '█' } else { '░' }).collect()
+}
+
+impl Component for TransferDetailComponent {
+    fn handle_key_event(&mut self, key: KeyEvent) -> Action {
+        match key.code {
+            KeyCode::Up | KeyCode::Char('k') => {
+                if !self.running.is_empty() {
+                    let prev = (self.selected + self.running.len() - 1) % self.running.len();
+                    self.select(prev);
+                }
+                Action::ScrollUp
+            }
+            KeyCode::Down | KeyCode::Char('j') => {
+                if !self.running.is_empty() {
+                    self.select((self.selected + 1) % self.running.len());
+                }
+                Action::ScrollDown
+            }
+            KeyCode::Char('+') | KeyCode::Char('=') => {
+                self.change_limit(true);
+                Action::Noop
+            }
+            KeyCode::Char('-') => {
+                self.change_limit(false);
+                Action::Noop
+            }
+            KeyCode::Char('u') => {
+                if let (Some(id), Some(dir)) = (self.selected_id(), self.data_dir.clone()) {
+                    self.set_limit(id, dir, None);
+                }
+                Action::Noop
+            }
+            _ => Action::Noop,
+        }
+    }
+
+    fn update(&mut self) {
+        if self.message_ttl > 0 {
+            self.message_ttl -= 1;
+            if self.message_ttl == 0 {
+                self.status_message = None;
+            }
+        }
+
+        let Some(dir) = self.data_dir.clone() else {
+            return;
+        };
+        let previous = self.selected_id();
+        self.running = running_entries(&dir);
+        // Follow the shown transfer when others start or finish
+        match previous.and_then(|id| self.running.iter().position(|&r| r == id)) {
+            Some(index) => self.selected = index,
+            None => {
+                self.selected = 0;
+                self.clear();
+            }
+        }
+
+        let Some(id) = self.selected_id() else {
+            self.snapshot = None;
+            return;
+        };
+        match request_stats(&dir, id) {
+            Ok(Some(snapshot)) => {
+                self.sample(snapshot.bytes_done);
+                self.snapshot = Some(snapshot);
+            }
+            Ok(None) => self.snapshot = None,
+            Err(e) => tracing::debug!("Stats for #{} unavailable: {}", id, e),
+        }
+    }
+
+    fn render(&self, frame: &mut Frame, area: Rect) {
+        let (Some(id), Some(snapshot)) = (self.selected_id(), self.snapshot.as_ref()) else {
+            let empty = Paragraph::new(
+                "No transfer in flight. Queued transfers appear here while \
+                 `flux queue run` or `flux daemon` runs them.",
+            )
+            .style(Style::default().fg(Color::DarkGray))
+            .wrap(Wrap { trim: true })
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title(" Transfer Detail "),
+            );
+            frame.render_widget(empty, area);
+            return;
+        };
+
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Length(5), // Summary
+                Constraint::Length(3), // Progress gauge
+                Constraint::Length(8), // Throughput graph
+                Constraint::Min(3),    // Chunk map
+            ])
+            .split(area);
+
+        // -- Summary --
+        let speed = self.current_speed();
+        let eta = eta_secs(snapshot, speed).map_or_else(|| "-".to_string(), format_eta);
+        let speed_str = if speed > 0 {
+            format!("{}/s", bytesize::ByteSize(speed))
+        } else {
+            "-".to_string()
+        };
+        let retries_style = if snapshot.retries > 0 {
+            theme::WARNING
+        } else {
+            Style::default()
+        };
+        let summary = vec![
+            Line::from(format!("{} -> {}", snapshot.source, snapshot.dest)),
+            Line::from(vec![
+                Span::raw("Speed: "),
+                Span::styled(speed_str, theme::SPEED),
+                Span::raw(format!("   ETA: {}", eta)),
+            ]),
+            Line::from(vec![
+                Span::styled(format!("Retries: {}", snapshot.retries), retries_style),
+                Span::raw(format!("   Limit: {}", format_limit(snapshot.limit))),
+            ]),
+        ];
+        let position = format!("{}/{}", self.selected + 1, self.running.len());
+        let mut title = format!(" Transfer #{} ({}) ", id, position);
+        if let Some(ref msg) = self.status_message {
+            title.push_str(&format!("- {} ", msg));
+        }
+        let summary = Paragraph::new(summary)
+            .block(Block::default().borders(Borders::ALL).title(title));
+        frame.render_widget(summary, chunks[0]);
+
+        // -- Progress --
+        let ratio = if snapshot.total_bytes > 0 {
+            (snapshot.bytes_done as f64 / snapshot.total_bytes as f64).min(1.0)
+        } else {
+            0.0
+        };
+        let gauge = Gauge::default()
+            .block(Block::default().borders(Borders::ALL))
+            .gauge_style(theme::SUCCESS)
+            .ratio(ratio)
+            .label(format!(
+                "{:.1}%  {} / {}",
+                ratio * 100.0,
+                bytesize::ByteSize(snapshot.bytes_done),
+                bytesize::ByteSize(snapshot.total_bytes)
+            ));
+        frame.render_widget(gauge, chunks[1]);
+
+        // -- Throughput graph --
+        let speed_data = self.speed_history.as_slice();
+        let graph_title = format!(
+            " Throughput, last {}s (peak: {}/s) ",
+            GRAPH_SECONDS,
+            bytesize::ByteSize(self.speed_history.peak())
+        );
+        let sparkline = Sparkline::default()
+            .block(Block::default().borders(Borders::ALL).title(graph_title))
+            .data(&speed_data)
+            .style(theme::SPEED);
+        frame.render_widget(sparkline, chunks[2]);
+
+        // -- Chunk map --
+        let done = snapshot.chunks.iter().filter(|&&c| c).count();
+        let (map, map_title) = if snapshot.chunks.is_empty() {
+            ("Not a chunked copy".to_string(), " Chunks ".to_string())
+        } else {
+            (
+                chunk_map(&snapshot.chunks),
+                format!(" Chunks ({}/{}) ", done, snapshot.chunks.len()),
+            )
+        };
+        let map = Paragraph::new(map)
+            .style(theme::SUCCESS)
+            .wrap(Wrap { trim: false })
+            .block(Block::default().borders(Borders::ALL).title(map_title));
+        frame.render_widget(map, chunks[3]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(total: u64, done: u64) -> MonitorSnapshot {
+        MonitorSnapshot {
+            source: "a.bin".into(),
+            dest: "b.bin".into(),
+            total_bytes: total,
+            bytes_done: done,
+            chunks: vec![true, false],
+            retries: 0,
+            limit: None,
+            current_file: None,
+        }
+    }
+
+    #[test]
+    fn next_limit_steps_and_floor() {
+        assert_eq!(next_limit(None, 0, true), None);
+        assert_eq!(next_limit(Some(1_000_000), 0, true), Some(1_250_000));
+        assert_eq!(next_limit(Some(1_000_000), 0, false), Some(800_000));
+        // Unlimited: start from the current speed, or a default when idle
+        assert_eq!(next_limit(None, 10_000_000, false), Some(8_000_000));
+        assert_eq!(next_limit(None, 0, false), Some(800_000));
+        assert_eq!(next_limit(Some(MIN_LIMIT), 0, false), Some(MIN_LIMIT));
+    }
+
+    #[test]
+    fn eta_from_remaining_bytes() {
+        assert_eq!(eta_secs(&snapshot(1000, 400), 100), Some(6));
+        assert_eq!(eta_secs(&snapshot(1000, 400), 0), None);
+        assert_eq!(eta_secs(&snapshot(0, 0), 100), None);
+    }
+
+    #[test]
+    fn format_eta_units() {
+        assert_eq!(format_eta(42), "42s");
+        assert_eq!(format_eta(185), "3m 05s");
+        assert_eq!(format_eta(3720), "1h 02m");
+    }
+
+    #[test]
+    fn chunk_map_cells() {
+        assert_eq!(chunk_map(&[true, false, true]), "█░█");
+    }
+
+    #[test]
+    fn no_running_transfers_without_sockets() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut detail = TransferDetailComponent::with_data_dir(dir.path().to_path_buf());
+        detail.update();
+        assert!(detail.running.is_empty());
+        assert!(detail.snapshot.is_none());
+        // Limit keys are ignored with nothing selected
+        detail.change_limit(true);
+        assert!(detail.status_message.is_none());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn polls_and_limits_running_transfer() {
+        use crate::transfer::control::ControlListener;
+        use crate::transfer::monitor::TransferMonitor;
+
+        let dir = tempfile::tempdir().unwrap();
+        let monitor = TransferMonitor::new("big.iso", "/backup/big.iso");
+        monitor.start(10_000, 2_500);
+        let _listener = ControlListener::bind(dir.path(), 9, monitor.clone())
+            .unwrap()
+            .unwrap();
+
+        let mut detail = TransferDetailComponent::with_data_dir(dir.path().to_path_buf());
+        detail.update();
+        assert_eq!(detail.running, vec![9]);
+        assert_eq!(detail.snapshot.as_ref().unwrap().bytes_done, 2_500);
+
+        detail.handle_key_event(KeyEvent::from(KeyCode::Char('-')));
+        assert_eq!(monitor.snapshot().limit, Some(800_000));
+        detail.handle_key_event(KeyEvent::from(KeyCode::Char('u')));
+        assert_eq!(monitor.snapshot().limit, None);
+    }
+}
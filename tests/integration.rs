@@ -285,3 +285,656 @@ fn test_binary_copy_preserves_content() {
     );
     assert_eq!(dest_data, data, "Binary file content should match exactly");
 }
+
+// ============================================================================
+// Test 11: Hidden and system file filtering
+// ============================================================================
+#[test]
+fn test_exclude_hidden_and_system_dirs() {
+    let dir = TempDir::new().unwrap();
+    let source_dir = dir.path().join("src_dir");
+    fs::create_dir_all(source_dir.join(".git")).unwrap();
+    fs::create_dir_all(source_dir.join("$RECYCLE.BIN")).unwrap();
+    fs::write(source_dir.join("keep.txt"), "kept").unwrap();
+    fs::write(source_dir.join(".env"), "secret").unwrap();
+    fs::write(source_dir.join(".git").join("HEAD"), "ref").unwrap();
+    fs::write(source_dir.join("$RECYCLE.BIN").join("deleted.txt"), "gone").unwrap();
+
+    let source_arg = format!("{}/", source_dir.to_str().unwrap());
+
+    // Default: hidden files copied, system folders skipped
+    let dest = dir.path().join("dest_default");
+    flux()
+        .args(["cp", "-r", &source_arg, dest.to_str().unwrap()])
+        .assert()
+        .success();
+    assert!(dest.join(".env").exists());
+    assert!(dest.join(".git").join("HEAD").exists());
+    assert!(!dest.join("$RECYCLE.BIN").exists());
+
+    // --exclude-hidden skips dotfiles and dot-directories
+    let dest = dir.path().join("dest_no_hidden");
+    flux()
+        .args(["cp", "-r", "--exclude-hidden", &source_arg, dest.to_str().unwrap()])
+        .assert()
+        .success();
+    assert!(dest.join("keep.txt").exists());
+    assert!(!dest.join(".env").exists());
+    assert!(!dest.join(".git").exists());
+
+    // --include-system keeps system folders
+    let dest = dir.path().join("dest_system");
+    flux()
+        .args(["cp", "-r", "--include-system", &source_arg, dest.to_str().unwrap()])
+        .assert()
+        .success();
+    assert!(dest.join("$RECYCLE.BIN").join("deleted.txt").exists());
+}
+
+// ============================================================================
+// Test 12: Tree view with cumulative sizes
+// ============================================================================
+#[test]
+fn test_tree_du_rolls_up_sizes() {
+    let dir = TempDir::new().unwrap();
+    let root = dir.path().join("data");
+    fs::create_dir_all(root.join("big").join("nested")).unwrap();
+    fs::create_dir_all(root.join("small")).unwrap();
+    fs::write(root.join("big").join("a.bin"), vec![0u8; 3000]).unwrap();
+    fs::write(root.join("big").join("nested").join("b.bin"), vec![0u8; 2000]).unwrap();
+    fs::write(root.join("small").join("c.txt"), "tiny").unwrap();
+
+    let output = flux()
+        .args(["tree", root.to_str().unwrap(), "--du", "--depth", "1"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let lines: Vec<&str> = stdout.lines().collect();
+
+    // Root carries the total; depth 1 hides nested entries; largest first
+    assert!(lines[0].contains("3 files"), "root line: {}", lines[0]);
+    assert!(lines[1].contains("big/") && lines[1].contains("2 files"));
+    assert!(lines[2].contains("small/") && lines[2].contains("1 file"));
+    assert!(!stdout.contains("nested"));
+    assert!(!stdout.contains("a.bin"));
+}
+
+// ============================================================================
+// Test 13: Destination template variables
+// ============================================================================
+#[test]
+fn test_destination_template_variables() {
+    let dir = TempDir::new().unwrap();
+    let source = create_file_in(&dir, "report.txt", "quarterly");
+    let dest = dir.path().join("{user}-{unknown}.txt");
+
+    flux()
+        .env("USER", "tester")
+        .env("USERNAME", "tester")
+        .args(["cp", source.to_str().unwrap(), dest.to_str().unwrap()])
+        .assert()
+        .success();
+
+    let expanded = dir.path().join("tester-{unknown}.txt");
+    assert_eq!(fs::read_to_string(expanded).unwrap(), "quarterly");
+}
+
+// ============================================================================
+// Test 14: Atomic writes leave no temp files behind
+// ============================================================================
+#[test]
+fn test_cp_atomic_replaces_destination() {
+    let dir = TempDir::new().unwrap();
+    let source = create_file_in(&dir, "new.txt", "fresh content");
+    let dest = create_file_in(&dir, "dest.txt", "stale");
+
+    flux()
+        .args([
+            "cp",
+            source.to_str().unwrap(),
+            dest.to_str().unwrap(),
+            "--atomic",
+            "--verify",
+            "--on-conflict",
+            "overwrite",
+        ])
+        .assert()
+        .success();
+
+    assert_eq!(fs::read_to_string(&dest).unwrap(), "fresh content");
+    assert!(!dir.path().join(".dest.txt.flux-tmp").exists());
+}
+
+// ============================================================================
+// Test 15: Concurrent directory copy with --jobs
+// ============================================================================
+#[test]
+fn test_cp_directory_with_jobs() {
+    let dir = TempDir::new().unwrap();
+    let source = dir.path().join("many");
+    for i in 0..200 {
+        let sub = source.join(format!("d{}", i % 7));
+        fs::create_dir_all(&sub).unwrap();
+        fs::write(sub.join(format!("f{}.txt", i)), format!("file {}", i)).unwrap();
+    }
+    let dest = dir.path().join("out");
+
+    flux()
+        .args([
+            "cp",
+            "-r",
+            "--jobs",
+            "4",
+            "--verify",
+            source.to_str().unwrap(),
+            dest.to_str().unwrap(),
+        ])
+        .assert()
+        .success();
+
+    for i in 0..200 {
+        let copied = dest
+            .join("many")
+            .join(format!("d{}", i % 7))
+            .join(format!("f{}.txt", i));
+        assert_eq!(fs::read_to_string(copied).unwrap(), format!("file {}", i));
+    }
+}
+
+// ============================================================================
+// Test 15b: --dedup skips or links content already in the destination
+// ============================================================================
+#[test]
+fn test_cp_dedup_skips_and_links_existing_content() {
+    let dir = TempDir::new().unwrap();
+    create_file_in(&dir, "photos/a.jpg", "holiday picture");
+    create_file_in(&dir, "photos/b.jpg", "another picture");
+    create_file_in(&dir, "photos/c.jpg", "another picture");
+    create_file_in(&dir, "photos/d.jpg", "unique content!");
+    let existing = create_file_in(&dir, "backup/2023/old.jpg", "holiday picture");
+    let source = format!("{}/", dir.path().join("photos").display());
+    let dest = dir.path().join("backup");
+    let data = dir.path().join("data");
+
+    flux()
+        .env("FLUX_DATA_DIR", &data)
+        .args(["cp", "-r", "--dedup", &source, dest.to_str().unwrap()])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("Deduplicated 2 file(s) (skipped)"));
+    assert!(!dest.join("a.jpg").exists());
+    assert!(!dest.join("c.jpg").exists());
+    assert_eq!(fs::read_to_string(dest.join("b.jpg")).unwrap(), "another picture");
+    assert_eq!(fs::read_to_string(dest.join("d.jpg")).unwrap(), "unique content!");
+
+    fs::remove_file(dest.join("b.jpg")).unwrap();
+    flux()
+        .env("FLUX_DATA_DIR", &data)
+        .args(["cp", "-r", "--dedup=link", &source, dest.to_str().unwrap()])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("Deduplicated 3 file(s) (hard-linked)"));
+    assert_eq!(fs::read_to_string(dest.join("a.jpg")).unwrap(), "holiday picture");
+    assert_eq!(fs::read_to_string(dest.join("c.jpg")).unwrap(), "another picture");
+
+    // a.jpg is the old backup under a second name
+    fs::write(&existing, "edited picture!").unwrap();
+    assert_eq!(fs::read_to_string(dest.join("a.jpg")).unwrap(), "edited picture!");
+}
+
+// ============================================================================
+// Test 15c: --hard-links copies a multiply-linked file once
+// ============================================================================
+#[cfg(unix)]
+#[test]
+fn test_cp_hard_links_are_recreated() {
+    use std::os::unix::fs::MetadataExt;
+
+    let dir = TempDir::new().unwrap();
+    let first = create_file_in(&dir, "snapshots/day1/db.bin", "database contents");
+    fs::create_dir_all(dir.path().join("snapshots/day2")).unwrap();
+    let second = dir.path().join("snapshots/day2/db.bin");
+    fs::hard_link(&first, &second).unwrap();
+    let source = dir.path().join("snapshots");
+    let dest = dir.path().join("copy");
+
+    flux()
+        .args(["cp", "-r", "--hard-links", source.to_str().unwrap(), dest.to_str().unwrap()])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("Recreated 1 hard link(s)"));
+    let inode = |day: &str| {
+        fs::metadata(dest.join("snapshots").join(day).join("db.bin"))
+            .unwrap()
+            .ino()
+    };
+    assert_eq!(inode("day1"), inode("day2"));
+
+    // Without the flag each name becomes its own file
+    let plain = dir.path().join("plain");
+    flux()
+        .args(["cp", "-r", source.to_str().unwrap(), plain.to_str().unwrap()])
+        .assert()
+        .success();
+    let day = |day: &str| plain.join("snapshots").join(day).join("db.bin");
+    assert_ne!(
+        fs::metadata(day("day1")).unwrap().ino(),
+        fs::metadata(day("day2")).unwrap().ino()
+    );
+    assert_eq!(fs::read_to_string(day("day2")).unwrap(), "database contents");
+}
+
+#[test]
+fn test_cp_retry_from_copies_only_listed_paths() {
+    let dir = TempDir::new().unwrap();
+    let failed = create_file_in(&dir, "src/photos/a.jpg", "a");
+    create_file_in(&dir, "src/photos/b.jpg", "b");
+    create_file_in(&dir, "src/docs/c.txt", "c");
+    let list = dir.path().join("failed.txt");
+    fs::write(&list, format!("# earlier copy\n{}\n", failed.display())).unwrap();
+    let dest = dir.path().join("dest");
+
+    flux()
+        .args(["cp", "-r", "--retry-from", list.to_str().unwrap()])
+        .arg(dir.path().join("src/"))
+        .arg(&dest)
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("Retrying 1 path(s)"));
+    assert_eq!(fs::read_to_string(dest.join("photos/a.jpg")).unwrap(), "a");
+    assert!(!dest.join("photos/b.jpg").exists());
+    assert!(!dest.join("docs/c.txt").exists());
+
+    // Files are not a directory copy to retry
+    flux()
+        .args(["cp", "--retry-from", list.to_str().unwrap()])
+        .arg(&failed)
+        .arg(dir.path().join("single.jpg"))
+        .assert()
+        .failure()
+        .code(7);
+}
+
+#[test]
+fn test_cp_stats_block_in_text_and_json() {
+    let dir = TempDir::new().unwrap();
+    create_file_in(&dir, "src/a.txt", "alpha");
+    create_file_in(&dir, "src/sub/b.txt", "bravo!");
+    let source = dir.path().join("src");
+
+    flux()
+        .args(["-q", "cp", "-r", "--stats"])
+        .arg(&source)
+        .arg(dir.path().join("text"))
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("Statistics:"))
+        .stderr(predicate::str::contains("2 considered, 2 copied, 0 skipped, 0 failed"))
+        .stderr(predicate::str::contains("Wall time:"));
+
+    let output = flux()
+        .args(["--json", "-q", "cp", "-r", "--stats"])
+        .arg(&source)
+        .arg(dir.path().join("json"))
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stats: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(stats["files_copied"], 2);
+    assert_eq!(stats["bytes_read"], 11);
+    assert_eq!(stats["bytes_written"], 11);
+    assert!(stats["peak_bytes_per_sec"].as_u64() >= stats["average_bytes_per_sec"].as_u64());
+}
+
+#[cfg(unix)]
+#[test]
+fn test_cp_local_restores_permissions_and_logs_plan() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = TempDir::new().unwrap();
+    let script = create_file_in(&dir, "tools/run.sh", "#!/bin/sh\necho hi\n");
+    fs::set_permissions(&script, fs::Permissions::from_mode(0o754)).unwrap();
+    let dest = dir.path().join("copy");
+
+    flux()
+        .args(["-v", "cp", "-r", dir.path().join("tools").to_str().unwrap()])
+        .arg(dest.to_str().unwrap())
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("permissions: restored from the source"));
+    let mode = fs::metadata(dest.join("tools/run.sh")).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o754);
+}
+
+// ============================================================================
+// Test 16: Minimal builds hide compiled-out commands
+// (cargo test --no-default-features)
+// ============================================================================
+#[cfg(not(feature = "net"))]
+#[test]
+fn test_help_hides_net_commands_without_net_feature() {
+    flux()
+        .arg("--help")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("send").not())
+        .stdout(predicate::str::contains("discover").not());
+
+    flux()
+        .args(["send", "file.txt"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("unrecognized subcommand"));
+}
+
+#[cfg(not(feature = "backends-sftp"))]
+#[test]
+fn test_sftp_without_backend_feature_explains_rebuild() {
+    let dir = TempDir::new().unwrap();
+    let source = create_file_in(&dir, "a.txt", "a");
+
+    flux()
+        .args(["cp", source.to_str().unwrap(), "sftp://host/tmp/a.txt"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--features backends-sftp"));
+}
+
+// ============================================================================
+// Test 17: --vss is Windows-only and excludes --snapshot-source
+// ============================================================================
+#[cfg(not(windows))]
+#[test]
+fn test_vss_outside_windows_points_to_snapshot_source() {
+    let dir = TempDir::new().unwrap();
+    let source = create_file_in(&dir, "a.txt", "a");
+    let dest = dir.path().join("b.txt");
+
+    flux()
+        .args(["cp", "--vss", source.to_str().unwrap(), dest.to_str().unwrap()])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("only available on Windows"));
+    assert!(!dest.exists());
+}
+
+#[test]
+fn test_vss_conflicts_with_snapshot_source() {
+    flux()
+        .args(["cp", "--vss", "--snapshot-source", "a", "b"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("cannot be used with"));
+}
+
+// ============================================================================
+// Test 18: Exit codes by error category, --json error output
+// ============================================================================
+#[test]
+fn test_exit_codes_follow_error_category() {
+    let dir = TempDir::new().unwrap();
+    let missing = dir.path().join("missing.txt");
+    let dest = dir.path().join("out.txt");
+
+    flux()
+        .args(["cp", missing.to_str().unwrap(), dest.to_str().unwrap()])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("Source not found"));
+
+    flux()
+        .args(["cp", "--bogus-flag", "a", "b"])
+        .assert()
+        .code(7);
+}
+
+#[test]
+fn test_json_error_output() {
+    let dir = TempDir::new().unwrap();
+    let missing = dir.path().join("missing.txt");
+    let dest = dir.path().join("out.txt");
+
+    let output = flux()
+        .args(["--json", "cp", missing.to_str().unwrap(), dest.to_str().unwrap()])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(2));
+    let value: serde_json::Value = serde_json::from_slice(&output.stderr).unwrap();
+    assert_eq!(value["error"]["code"], 2);
+    assert_eq!(value["error"]["kind"], "source_not_found");
+    assert!(value["error"]["message"]
+        .as_str()
+        .unwrap()
+        .contains("missing.txt"));
+}
+
+// ============================================================================
+// Test 19: --on-error continue collects failures, abort stops at the first
+// ============================================================================
+fn tree_with_blocked_file() -> (TempDir, std::path::PathBuf, std::path::PathBuf) {
+    let dir = TempDir::new().unwrap();
+    let src = dir.path().join("src");
+    for name in ["a.txt", "b.txt", "c.txt"] {
+        create_file_in(&dir, &format!("src/{}", name), name);
+    }
+    // A directory where b.txt should go makes that one file fail
+    let out = dir.path().join("out");
+    create_file_in(&dir, "out/src/b.txt/keep", "x");
+    (dir, src, out)
+}
+
+#[test]
+fn test_on_error_continue_reports_partial_failure() {
+    let (_dir, src, out) = tree_with_blocked_file();
+
+    flux()
+        .args(["cp", "-r", "-j", "1", "--on-conflict", "overwrite"])
+        .args(["--on-error", "continue", "--json"])
+        .arg(&src)
+        .arg(&out)
+        .assert()
+        .code(6)
+        .stderr(predicate::str::contains("\"kind\":\"partial_failure\""))
+        .stderr(predicate::str::contains("b.txt"));
+
+    assert!(out.join("src/a.txt").is_file());
+    assert!(out.join("src/c.txt").is_file());
+}
+
+#[test]
+fn test_on_error_abort_stops_the_copy() {
+    let (_dir, src, out) = tree_with_blocked_file();
+
+    flux()
+        .args(["cp", "-r", "-j", "1", "--on-conflict", "overwrite", "--on-error", "abort"])
+        .arg(&src)
+        .arg(&out)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Copy aborted at"))
+        .stderr(predicate::str::contains("b.txt"));
+}
+
+#[test]
+fn test_on_error_rejects_count_on_non_retry_policy() {
+    flux()
+        .args(["cp", "--on-error", "abort:2", "a", "b"])
+        .assert()
+        .code(7)
+        .stderr(predicate::str::contains("only retry takes a count"));
+}
+
+#[test]
+fn test_clean_lists_then_removes_leftovers() {
+    let dir = TempDir::new().unwrap();
+    let manifest = create_file_in(&dir, "out/.a.bin.flux-tmp.flux-resume.json", "{");
+    let partial = create_file_in(&dir, "out/.a.bin.flux-tmp", "part");
+    let received = create_file_in(&dir, "in/.photo.jpg.flux-tmp", "partial");
+    let keep = create_file_in(&dir, "in/photo-2.jpg", "done");
+    let root = dir.path().to_str().unwrap();
+
+    // Only the fresh temp file is spared by the default 7-day grace
+    flux()
+        .args(["clean", root])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("unreadable resume manifest"))
+        .stdout(predicate::str::contains(".a.bin.flux-tmp"))
+        .stdout(predicate::str::contains("photo.jpg").not())
+        .stderr(predicate::str::contains("--yes"));
+    assert!(manifest.exists() && partial.exists());
+
+    flux()
+        .args(["clean", root, "--older-than", "0", "--yes"])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("Removed 3 leftover(s)"));
+    assert!(!manifest.exists() && !partial.exists() && !received.exists());
+    assert!(keep.exists());
+}
+
+#[test]
+fn test_status_lists_running_transfers() {
+    let data = TempDir::new().unwrap();
+    flux()
+        .env("FLUX_DATA_DIR", data.path())
+        .arg("status")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("No transfers running"));
+
+    // A transfer in another process publishes a file like this one
+    let now = chrono::Utc::now().to_rfc3339();
+    let status = serde_json::json!({
+        "pid": 4242,
+        "command": "sync",
+        "source": "/home/me/photos",
+        "dest": "/mnt/backup/photos",
+        "started": now,
+        "updated": now,
+        "total_bytes": 4000,
+        "bytes_done": 1000,
+        "bytes_per_sec": 100,
+        "eta_secs": 30,
+        "current_file": "2024/beach.jpg",
+    });
+    let path = create_file_in(&data, "status/4242-0.json", &status.to_string());
+
+    flux()
+        .env("FLUX_DATA_DIR", data.path())
+        .arg("status")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("sync (pid 4242): /home/me/photos -> /mnt/backup/photos"))
+        .stdout(predicate::str::contains("25.0%"))
+        .stdout(predicate::str::contains("ETA 30s"))
+        .stdout(predicate::str::contains("2024/beach.jpg"));
+    flux()
+        .env("FLUX_DATA_DIR", data.path())
+        .args(["status", "--json"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("\"bytes_done\":1000"));
+    assert!(path.exists());
+}
+
+#[test]
+fn test_cp_streams_stdin_and_stdout() {
+    let dir = TempDir::new().unwrap();
+    let data = dir.path().join("FLUX_DATA");
+    let dump = dir.path().join("db.sql");
+
+    flux()
+        .env("FLUX_DATA_DIR", &data)
+        .args(["cp", "-", dump.to_str().unwrap(), "--verify"])
+        .write_stdin("CREATE TABLE t (id int);\n")
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("Integrity verified"));
+    assert_eq!(fs::read_to_string(&dump).unwrap(), "CREATE TABLE t (id int);\n");
+
+    // Only data goes to stdout; progress and the summary stay on stderr
+    flux()
+        .env("FLUX_DATA_DIR", &data)
+        .args(["cp", dump.to_str().unwrap(), "-"])
+        .assert()
+        .success()
+        .stdout("CREATE TABLE t (id int);\n");
+
+    // stdin has no file name to put into a directory
+    flux()
+        .env("FLUX_DATA_DIR", &data)
+        .args(["cp", "-", dir.path().to_str().unwrap()])
+        .write_stdin("data")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("name the file to write stdin to"));
+    flux()
+        .env("FLUX_DATA_DIR", &data)
+        .args(["cp", "--resume", "-", dump.to_str().unwrap()])
+        .write_stdin("data")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--resume cannot be used"));
+}
+
+// ============================================================================
+// Ctrl+C cancels a copy: partial output removed, or kept with --resume
+// ============================================================================
+
+/// Start a throttled copy, press Ctrl+C (SIGINT) while it runs and return
+/// its exit code and stderr.
+#[cfg(unix)]
+fn interrupted_copy(data: &std::path::Path, args: &[&str]) -> (Option<i32>, String) {
+    use std::process::{Command as StdCommand, Stdio};
+
+    let child = StdCommand::new(assert_cmd::cargo::cargo_bin("flux"))
+        .env("FLUX_DATA_DIR", data)
+        .args(["cp", "--limit", "1MB/s"])
+        .args(args)
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    std::thread::sleep(std::time::Duration::from_millis(800));
+    let signalled = StdCommand::new("kill")
+        .args(["-INT", &child.id().to_string()])
+        .status()
+        .unwrap();
+    assert!(signalled.success());
+    let output = child.wait_with_output().unwrap();
+    (
+        output.status.code(),
+        String::from_utf8_lossy(&output.stderr).to_string(),
+    )
+}
+
+#[cfg(unix)]
+#[test]
+fn test_cp_ctrl_c_cancels_and_cleans_up() {
+    let dir = TempDir::new().unwrap();
+    let data = dir.path().join("FLUX_DATA");
+    let source = dir.path().join("big.bin");
+    fs::write(&source, vec![7u8; 4 * 1024 * 1024]).unwrap();
+    let dest = dir.path().join("copy.bin");
+
+    let (code, stderr) =
+        interrupted_copy(&data, &[source.to_str().unwrap(), dest.to_str().unwrap()]);
+    assert_eq!(code, Some(130), "stderr: {}", stderr);
+    assert!(stderr.contains("Transfer cancelled"), "stderr: {}", stderr);
+    assert!(!dest.exists(), "the partial copy should be removed");
+    let db = rusqlite::Connection::open(data.join("state.db")).unwrap();
+    let history: String = db
+        .query_row("SELECT entry FROM history", [], |row| row.get(0))
+        .unwrap();
+    assert!(history.contains("\"cancelled\""), "{}", history);
+
+    // A resumable copy keeps its partial file and manifest
+    let (code, _) = interrupted_copy(
+        &data,
+        &["--resume", source.to_str().unwrap(), dest.to_str().unwrap()],
+    );
+    assert_eq!(code, Some(130));
+    assert!(fs::metadata(&dest).unwrap().len() < 4 * 1024 * 1024);
+    assert!(dir.path().join("copy.bin.flux-resume.json").exists());
+}
@@ -83,6 +83,78 @@ fn test_resume_with_existing_manifest() {
     assert_eq!(fs::read(&dest).unwrap(), content);
 }
 
+/// Test that --resume-verify copies again a completed chunk that changed in
+/// the destination since the interruption.
+#[test]
+fn test_resume_verify_recopies_changed_chunks() {
+    let dir = TempDir::new().unwrap();
+    let content: Vec<u8> = (0..100_000u32).map(|i| (i % 251) as u8).collect();
+    let source = create_file_in(&dir, "source.bin", &content);
+    let mut partial = content.clone();
+    partial[75_000] ^= 0xFF;
+    let dest = create_file_in(&dir, "dest.bin", &partial);
+
+    // Both chunks recorded as done, with the checksums of the source
+    let chunk = |index: usize, offset: usize| {
+        serde_json::json!({
+            "index": index,
+            "offset": offset,
+            "length": 50_000,
+            "completed": true,
+            "checksum": blake3::hash(&content[offset..offset + 50_000]).to_hex().to_string(),
+        })
+    };
+    let manifest = serde_json::json!({
+        "version": 2,
+        "source": source,
+        "dest": dest,
+        "total_size": 100_000,
+        "chunk_count": 2,
+        "chunks": [chunk(0, 0), chunk(1, 50_000)],
+        "compress": false,
+        "file_checksum": null,
+        "checksum_algorithm": "blake3",
+    });
+    fs::write(dir.path().join("dest.bin.flux-resume.json"), manifest.to_string()).unwrap();
+
+    flux()
+        .args([
+            "cp",
+            "--chunks",
+            "2",
+            "--resume",
+            "--resume-verify",
+            "--on-conflict",
+            "overwrite",
+            source.to_str().unwrap(),
+            dest.to_str().unwrap(),
+        ])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("1/2 chunks changed"));
+
+    assert_eq!(fs::read(&dest).unwrap(), content);
+}
+
+/// Test that --resume-verify is refused without --resume.
+#[test]
+fn test_resume_verify_requires_resume() {
+    let dir = TempDir::new().unwrap();
+    let source = create_file_in(&dir, "source.txt", b"data");
+    let dest = dir.path().join("dest.txt");
+
+    flux()
+        .args([
+            "cp",
+            "--resume-verify",
+            source.to_str().unwrap(),
+            dest.to_str().unwrap(),
+        ])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--resume"));
+}
+
 // ============================================================================
 // Compress tests
 // ============================================================================
@@ -409,3 +481,41 @@ fn test_verify_directory() {
         "beta file content"
     );
 }
+
+/// Test that --checksum picks the algorithm --verify uses.
+#[test]
+fn test_verify_with_checksum_algorithm() {
+    let dir = TempDir::new().unwrap();
+    let content: Vec<u8> = (0..50_000u32).map(|i| (i % 256) as u8).collect();
+    let source = create_file_in(&dir, "source.bin", &content);
+
+    for (algorithm, label) in [("sha256", "SHA-256"), ("xxh3", "XXH3")] {
+        let dest = dir.path().join(format!("dest-{}.bin", algorithm));
+        flux()
+            .args([
+                "cp",
+                "--chunks",
+                "2",
+                "--verify",
+                "--checksum",
+                algorithm,
+                source.to_str().unwrap(),
+                dest.to_str().unwrap(),
+            ])
+            .assert()
+            .success()
+            .stderr(predicate::str::contains(format!("Integrity verified ({})", label)));
+        assert_eq!(fs::read(&dest).unwrap(), content);
+    }
+
+    flux()
+        .args([
+            "cp",
+            "--checksum",
+            "md5",
+            source.to_str().unwrap(),
+            dir.path().join("never.bin").to_str().unwrap(),
+        ])
+        .assert()
+        .failure();
+}
@@ -85,6 +85,57 @@ fn test_alias_name_validation() {
         .stderr(predicate::str::contains("at least 2 characters"));
 }
 
+#[test]
+fn test_alias_resolve_and_nesting() {
+    let iso = TempDir::new().unwrap();
+    let data = TempDir::new().unwrap();
+
+    flux_isolated(iso.path(), data.path())
+        .args(["add", "nas", "smb://server/share"])
+        .assert()
+        .success();
+    flux_isolated(iso.path(), data.path())
+        .args(["alias", "resolve", "nas:/photos"])
+        .assert()
+        .success()
+        .stdout("smb://server/share/photos\n")
+        .stderr(predicate::str::contains("(smb)"));
+    flux_isolated(iso.path(), data.path())
+        .args(["alias", "resolve", "nas"])
+        .assert()
+        .success()
+        .stdout("smb://server/share\n");
+    flux_isolated(iso.path(), data.path())
+        .args(["alias", "resolve", "missing"])
+        .assert()
+        .code(7);
+
+    flux_isolated(iso.path(), data.path())
+        .args(["add", "photos", "nas:photos"])
+        .assert()
+        .code(7)
+        .stderr(predicate::str::contains("cannot point at other aliases"));
+}
+
+#[test]
+fn test_alias_resolution_in_sync_source() {
+    let iso = TempDir::new().unwrap();
+    let data = TempDir::new().unwrap();
+    let src = TempDir::new().unwrap();
+    let dst = TempDir::new().unwrap();
+    create_file_in(&src, "photos/a.jpg", "jpeg");
+
+    flux_isolated(iso.path(), data.path())
+        .args(["add", "media", src.path().to_str().unwrap()])
+        .assert()
+        .success();
+    flux_isolated(iso.path(), data.path())
+        .args(["sync", "media:/photos/", dst.path().to_str().unwrap()])
+        .assert()
+        .success();
+    assert_eq!(fs::read_to_string(dst.path().join("a.jpg")).unwrap(), "jpeg");
+}
+
 #[test]
 fn test_alias_resolution_in_copy() {
     let iso = TempDir::new().unwrap();
@@ -120,6 +171,76 @@ fn test_alias_resolution_in_copy() {
     );
 }
 
+// ============================================================================
+// SAVED TRANSFER TESTS
+// ============================================================================
+
+#[test]
+fn test_save_and_run_last_copy() {
+    let iso = TempDir::new().unwrap();
+    let data = TempDir::new().unwrap();
+    let work = TempDir::new().unwrap();
+    create_file_in(&work, "notes.txt", "first");
+
+    // Relative paths and global flags: only the cp part is saved
+    flux_isolated(iso.path(), data.path())
+        .current_dir(work.path())
+        .args(["-q", "cp", "notes.txt", "copy.txt"])
+        .assert()
+        .success();
+    flux_isolated(iso.path(), data.path())
+        .args(["save", "notes"])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("Saved notes: flux cp notes.txt copy.txt"));
+
+    fs::write(work.path().join("notes.txt"), "second").unwrap();
+    fs::remove_file(work.path().join("copy.txt")).unwrap();
+    flux_isolated(iso.path(), data.path())
+        .args(["run", "notes"])
+        .assert()
+        .success();
+    assert_eq!(fs::read_to_string(work.path().join("copy.txt")).unwrap(), "second");
+
+    flux_isolated(iso.path(), data.path())
+        .args(["saved"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("notes"))
+        .stdout(predicate::str::contains("flux cp notes.txt copy.txt"));
+}
+
+#[test]
+fn test_saved_add_rm_and_errors() {
+    let iso = TempDir::new().unwrap();
+    let data = TempDir::new().unwrap();
+
+    flux_isolated(iso.path(), data.path())
+        .args(["save", "nothing-yet"])
+        .assert()
+        .code(7)
+        .stderr(predicate::str::contains("No cp or sync command"));
+    flux_isolated(iso.path(), data.path())
+        .args(["saved", "add", "hist", "--", "history"])
+        .assert()
+        .code(7)
+        .stderr(predicate::str::contains("not a cp or sync command"));
+    flux_isolated(iso.path(), data.path())
+        .args(["saved", "add", "mirror", "--", "sync", "/tmp/a", "/tmp/b", "--delete"])
+        .assert()
+        .success();
+    flux_isolated(iso.path(), data.path())
+        .args(["saved", "rm", "mirror"])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("Removed saved transfer: mirror"));
+    flux_isolated(iso.path(), data.path())
+        .args(["run", "mirror"])
+        .assert()
+        .code(7)
+        .stderr(predicate::str::contains("No saved transfer 'mirror'"));
+}
+
 // ============================================================================
 // CONFIG / CONFLICT TESTS
 // ============================================================================
@@ -230,11 +351,11 @@ fn test_on_conflict_rename() {
         "Original file should be unchanged"
     );
 
-    // A renamed copy should exist (dest_1.txt)
-    let renamed = work.path().join("dest_1.txt");
+    // A renamed copy should exist (dest (1).txt)
+    let renamed = work.path().join("dest (1).txt");
     assert!(
         renamed.exists(),
-        "Renamed file (dest_1.txt) should exist"
+        "Renamed file (dest (1).txt) should exist"
     );
     assert_eq!(
         fs::read_to_string(&renamed).unwrap(),
@@ -243,6 +364,44 @@ fn test_on_conflict_rename() {
     );
 }
 
+#[test]
+fn test_on_conflict_prompt_uses_configured_fallback() {
+    let iso = TempDir::new().unwrap();
+    let data = TempDir::new().unwrap();
+    let work = TempDir::new().unwrap();
+    fs::write(iso.path().join("config.toml"), "conflict_fallback = \"rename\"\n").unwrap();
+
+    let src = create_file_in(&work, "src/changed.txt", "new content");
+    create_file_in(&work, "src/same.txt", "same content");
+    let out = work.path().join("out");
+    create_file_in(&work, "out/changed.txt", "original content");
+    create_file_in(&work, "out/same.txt", "same content");
+
+    // stdin is not a terminal, so the prompt falls back to config
+    flux_isolated(iso.path(), data.path())
+        .args([
+            "cp",
+            "-r",
+            "--on-conflict",
+            "prompt",
+            &format!("{}/", src.parent().unwrap().display()),
+            out.to_str().unwrap(),
+        ])
+        .assert()
+        .success();
+
+    assert_eq!(
+        fs::read_to_string(out.join("changed.txt")).unwrap(),
+        "original content"
+    );
+    assert_eq!(
+        fs::read_to_string(out.join("changed (1).txt")).unwrap(),
+        "new content"
+    );
+    // Identical files are not conflicts
+    assert!(!out.join("same (1).txt").exists());
+}
+
 // ============================================================================
 // QUEUE TESTS
 // ============================================================================
@@ -269,6 +428,70 @@ fn test_queue_add_and_list() {
         .stdout(predicate::str::contains("pending"));
 }
 
+#[test]
+fn test_queue_add_bulk_class() {
+    let iso = TempDir::new().unwrap();
+    let data = TempDir::new().unwrap();
+
+    flux_isolated(iso.path(), data.path())
+        .args(["queue", "add", "/tmp/a.txt", "/tmp/b.txt", "--class", "bulk"])
+        .assert()
+        .success();
+
+    let db = rusqlite::Connection::open(data.path().join("state.db")).unwrap();
+    let entry: String = db
+        .query_row("SELECT entry FROM queue", [], |row| row.get(0))
+        .unwrap();
+    assert!(entry.contains("\"class\":\"bulk\""), "{}", entry);
+}
+
+#[test]
+fn test_queue_priority_and_move() {
+    let iso = TempDir::new().unwrap();
+    let data = TempDir::new().unwrap();
+
+    for source in ["/tmp/a.txt", "/tmp/b.txt"] {
+        flux_isolated(iso.path(), data.path())
+            .args(["queue", "add", source, "/tmp/out"])
+            .assert()
+            .success();
+    }
+    flux_isolated(iso.path(), data.path())
+        .args(["queue", "add", "/tmp/c.txt", "/tmp/out", "--priority", "high"])
+        .assert()
+        .success();
+    flux_isolated(iso.path(), data.path())
+        .args(["queue", "list"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("high"));
+
+    // #2 jumps ahead of #1 in the stored order
+    flux_isolated(iso.path(), data.path())
+        .args(["queue", "move", "2", "--before", "1"])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("Moved transfer #2 before #1"));
+    let output = flux_isolated(iso.path(), data.path())
+        .args(["queue", "list"])
+        .output()
+        .unwrap();
+    let listing = String::from_utf8_lossy(&output.stdout);
+    let first = listing.find("/tmp/a.txt").unwrap();
+    let second = listing.find("/tmp/b.txt").unwrap();
+    assert!(second < first, "{}", listing);
+
+    flux_isolated(iso.path(), data.path())
+        .args(["queue", "move", "2", "--before", "9"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("not found"));
+    flux_isolated(iso.path(), data.path())
+        .args(["queue", "move", "2"])
+        .assert()
+        .failure();
+}
+
 #[test]
 fn test_queue_lifecycle() {
     let iso = TempDir::new().unwrap();
@@ -316,6 +539,105 @@ fn test_queue_lifecycle() {
         .stdout(predicate::str::contains("cancelled"));
 }
 
+#[test]
+fn test_concurrent_queue_adds_are_all_kept() {
+    let iso = TempDir::new().unwrap();
+    let data = TempDir::new().unwrap();
+
+    std::thread::scope(|scope| {
+        for i in 0..8 {
+            let (iso, data) = (iso.path(), data.path());
+            scope.spawn(move || {
+                flux_isolated(iso, data)
+                    .args(["queue", "add", &format!("/tmp/src{}", i), "/tmp/dst"])
+                    .assert()
+                    .success();
+            });
+        }
+    });
+
+    let output = flux_isolated(iso.path(), data.path())
+        .args(["queue", "list"])
+        .output()
+        .unwrap();
+    let listing = String::from_utf8_lossy(&output.stdout);
+    for i in 0..8 {
+        assert!(listing.contains(&format!("/tmp/src{}", i)), "{}", listing);
+    }
+}
+
+#[test]
+fn test_daemon_passes_over_entries_for_a_capped_backend() {
+    let iso = TempDir::new().unwrap();
+    let data = TempDir::new().unwrap();
+    let work = TempDir::new().unwrap();
+    fs::write(
+        iso.path().join("config.toml"),
+        "[backends.max_monthly_gb]\nsftp = 0\n",
+    )
+    .unwrap();
+    let source = create_file_in(&work, "a.txt", "queued");
+    let dest = work.path().join("b.txt");
+
+    // #1 goes to the capped backend and is ahead of the local copy #2
+    flux_isolated(iso.path(), data.path())
+        .args(["queue", "add", source.to_str().unwrap(), "sftp://nas/a.txt"])
+        .assert()
+        .success();
+    flux_isolated(iso.path(), data.path())
+        .args(["queue", "add", source.to_str().unwrap(), dest.to_str().unwrap()])
+        .assert()
+        .success();
+
+    // The daemon never exits; stop it after a few passes
+    flux_isolated(iso.path(), data.path())
+        .args(["daemon", "--interval", "1"])
+        .timeout(std::time::Duration::from_secs(3))
+        .output()
+        .unwrap();
+
+    assert_eq!(fs::read_to_string(&dest).unwrap(), "queued");
+    let output = flux_isolated(iso.path(), data.path())
+        .args(["queue", "list"])
+        .output()
+        .unwrap();
+    let listing = String::from_utf8_lossy(&output.stdout);
+    assert!(listing.contains("pending"), "{}", listing);
+    assert!(listing.contains("completed"), "{}", listing);
+    // The capped entry was not retried on every pass
+    let db = rusqlite::Connection::open(data.path().join("state.db")).unwrap();
+    let rows: i64 = db
+        .query_row("SELECT COUNT(*) FROM history", [], |row| row.get(0))
+        .unwrap();
+    assert_eq!(rows, 1);
+}
+
+#[test]
+fn test_corrupt_state_db_is_backed_up() {
+    let iso = TempDir::new().unwrap();
+    let data = TempDir::new().unwrap();
+    fs::write(data.path().join("state.db"), "not a database, cut short").unwrap();
+
+    flux_isolated(iso.path(), data.path())
+        .args(["queue", "add", "/tmp/a.txt", "/tmp/b.txt"])
+        .assert()
+        .success();
+
+    let backups: Vec<_> = fs::read_dir(data.path())
+        .unwrap()
+        .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+        .filter(|name| name.starts_with("state.db.corrupt-"))
+        .collect();
+    assert_eq!(backups.len(), 1);
+    let backup = fs::read_to_string(data.path().join(&backups[0])).unwrap();
+    assert!(backup.ends_with("cut short"));
+    flux_isolated(iso.path(), data.path())
+        .args(["queue", "list"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("/tmp/a.txt"));
+}
+
 #[test]
 fn test_queue_clear() {
     let iso = TempDir::new().unwrap();
@@ -376,6 +698,69 @@ fn test_history_after_copy() {
         .stdout(predicate::str::contains("SOURCE"));
 }
 
+#[test]
+fn test_history_diff_lists_sync_changes() {
+    let iso = TempDir::new().unwrap();
+    let data = TempDir::new().unwrap();
+    let src = TempDir::new().unwrap();
+    let dst = TempDir::new().unwrap();
+
+    create_file_in(&src, "new.txt", "new");
+    create_file_in(&dst, "orphan.txt", "old");
+
+    flux_isolated(iso.path(), data.path())
+        .args([
+            "sync",
+            src.path().to_str().unwrap(),
+            dst.path().to_str().unwrap(),
+            "--delete",
+        ])
+        .assert()
+        .success();
+
+    flux_isolated(iso.path(), data.path())
+        .args(["history", "diff", "1"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("+ new.txt"))
+        .stdout(predicate::str::contains("- orphan.txt"));
+
+    flux_isolated(iso.path(), data.path())
+        .args(["history", "diff", "2"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Entry #2 not found"));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_hook_success_receives_transfer_env() {
+    let iso = TempDir::new().unwrap();
+    let data = TempDir::new().unwrap();
+    let work = TempDir::new().unwrap();
+
+    let source = create_file_in(&work, "hook_source.txt", "hook");
+    let dest = work.path().join("hook_dest.txt");
+    let marker = work.path().join("hook.out");
+    let hook = format!(
+        "printf '%s %s' \"$FLUX_STATUS\" \"$FLUX_BYTES\" > '{}'",
+        marker.display()
+    );
+
+    flux_isolated(iso.path(), data.path())
+        .args([
+            "cp",
+            "--hook-success",
+            &hook,
+            source.to_str().unwrap(),
+            dest.to_str().unwrap(),
+        ])
+        .assert()
+        .success();
+
+    assert_eq!(fs::read_to_string(&marker).unwrap(), "completed 4");
+}
+
 #[test]
 fn test_history_clear() {
     let iso = TempDir::new().unwrap();
@@ -488,3 +873,101 @@ fn test_history_empty_shows_message() {
         .success()
         .stderr(predicate::str::contains("No transfer history"));
 }
+
+#[test]
+fn test_service_install_print_pins_config_dir() {
+    let iso = TempDir::new().unwrap();
+    let data = TempDir::new().unwrap();
+
+    let output = flux_isolated(iso.path(), data.path())
+        .args(["service", "install", "queue", "--print", "--", "--interval", "60"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let definition = String::from_utf8_lossy(&output.stdout);
+    assert!(definition.contains("daemon"));
+    assert!(definition.contains("--interval"));
+    assert!(definition.contains("FLUX_CONFIG_DIR"));
+    assert!(definition.contains(iso.path().file_name().unwrap().to_str().unwrap()));
+}
+
+#[test]
+fn test_config_set_get_keeps_comments() {
+    let iso = TempDir::new().unwrap();
+    let data = TempDir::new().unwrap();
+    fs::write(iso.path().join("config.toml"), "# my settings\nretry_count = 5\n").unwrap();
+
+    flux_isolated(iso.path(), data.path())
+        .args(["config", "set", "receive.daily_quota", "50GB"])
+        .assert()
+        .success();
+    flux_isolated(iso.path(), data.path())
+        .args(["config", "get", "receive.daily_quota"])
+        .assert()
+        .success()
+        .stdout("50GB\n");
+    // Defaults apply to keys missing from the file
+    flux_isolated(iso.path(), data.path())
+        .args(["config", "get", "history_limit"])
+        .assert()
+        .success()
+        .stdout("1000\n");
+
+    let contents = fs::read_to_string(iso.path().join("config.toml")).unwrap();
+    assert!(contents.contains("# my settings"));
+    assert!(contents.contains("[receive]"));
+}
+
+#[test]
+fn test_config_set_rejects_bad_values() {
+    let iso = TempDir::new().unwrap();
+    let data = TempDir::new().unwrap();
+
+    flux_isolated(iso.path(), data.path())
+        .args(["config", "set", "receive.daily_quota", "lots"])
+        .assert()
+        .code(7)
+        .stderr(predicate::str::contains("a size like 10GB"));
+    flux_isolated(iso.path(), data.path())
+        .args(["config", "set", "retrys", "2"])
+        .assert()
+        .code(7)
+        .stderr(predicate::str::contains("Unknown config key"));
+    assert!(!iso.path().join("config.toml").exists());
+}
+
+#[test]
+fn test_config_env_override_and_validate() {
+    let iso = TempDir::new().unwrap();
+    let data = TempDir::new().unwrap();
+    fs::write(iso.path().join("config.toml"), "retry_count = 5\n").unwrap();
+
+    flux_isolated(iso.path(), data.path())
+        .env("FLUX_RETRY_COUNT", "9")
+        .args(["config", "get", "retry_count"])
+        .assert()
+        .success()
+        .stdout("9\n");
+
+    fs::write(iso.path().join("config.toml"), "retry_count = -1\nretries = 2\n").unwrap();
+    flux_isolated(iso.path(), data.path())
+        .args(["config", "validate"])
+        .assert()
+        .code(7)
+        .stderr(predicate::str::contains("retry_count: expected a whole number"))
+        .stderr(predicate::str::contains("Unknown config key 'retries'"));
+}
+
+#[test]
+fn test_global_config_flag_selects_file() {
+    let iso = TempDir::new().unwrap();
+    let data = TempDir::new().unwrap();
+    let custom = iso.path().join("custom.toml");
+    fs::write(&custom, "conflict = \"rename\"\n").unwrap();
+
+    flux_isolated(iso.path(), data.path())
+        .args(["--config", custom.to_str().unwrap(), "config", "get", "conflict"])
+        .assert()
+        .success()
+        .stdout("rename\n");
+}
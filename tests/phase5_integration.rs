@@ -1,3 +1,6 @@
+// Peer-to-peer commands (send, receive, discover, trust) need the `net` feature
+#![cfg(feature = "net")]
+
 use assert_cmd::Command;
 use predicates::prelude::*;
 use std::fs;
@@ -39,9 +42,36 @@ fn test_send_help() {
         .success()
         .stdout(predicate::str::contains("Send"))
         .stdout(predicate::str::contains("--no-encrypt"))
+        .stdout(predicate::str::contains("--receipt"))
         .stdout(predicate::str::contains("TARGET"));
 }
 
+#[test]
+fn test_history_receipts_without_receipts() {
+    let iso = TempDir::new().unwrap();
+    let data = TempDir::new().unwrap();
+    let work = TempDir::new().unwrap();
+    let source = work.path().join("a.txt");
+    std::fs::write(&source, "receipt").unwrap();
+
+    flux_isolated(iso.path(), data.path())
+        .args([
+            "cp",
+            source.to_str().unwrap(),
+            work.path().join("b.txt").to_str().unwrap(),
+        ])
+        .assert()
+        .success();
+
+    // Local copies carry no receipts, so nothing is exported
+    flux_isolated(iso.path(), data.path())
+        .args(["history", "--receipts"])
+        .assert()
+        .success()
+        .stdout(predicate::str::is_empty())
+        .stderr(predicate::str::contains("No receipts"));
+}
+
 #[test]
 fn test_receive_help() {
     let iso = TempDir::new().unwrap();
@@ -127,6 +157,18 @@ fn test_send_missing_file() {
         .stderr(predicate::str::contains("Source not found"));
 }
 
+#[test]
+fn test_send_stall_timeout_too_short() {
+    let iso = TempDir::new().unwrap();
+    let data = TempDir::new().unwrap();
+
+    flux_isolated(iso.path(), data.path())
+        .args(["send", "--stall-timeout", "1", "file.txt", "127.0.0.1:9999"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--stall-timeout"));
+}
+
 // ============================================================================
 // DISCOVER TESTS
 // ============================================================================
@@ -295,3 +337,759 @@ fn test_send_receive_encrypted() {
     let received_content = fs::read_to_string(&received).unwrap();
     assert_eq!(received_content, content);
 }
+
+#[test]
+#[ignore]
+fn test_repeated_send_reuses_chunks() {
+    let iso = TempDir::new().unwrap();
+    let data = TempDir::new().unwrap();
+    let work = TempDir::new().unwrap();
+
+    // Large enough to be chunked; varied bytes so chunk boundaries differ
+    let mut state = 7u64;
+    let original: Vec<u8> = (0..6 * 1024 * 1024)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as u8
+        })
+        .collect();
+    let mut edited = original.clone();
+    edited[3_000_000..3_000_010].copy_from_slice(b"edited....");
+    let first = work.path().join("first.bin");
+    let second = work.path().join("second.bin");
+    fs::write(&first, &original).unwrap();
+    fs::write(&second, &edited).unwrap();
+
+    let output_dir = work.path().join("received");
+    fs::create_dir_all(&output_dir).unwrap();
+
+    let port = 19746;
+
+    let recv_iso = iso.path().to_path_buf();
+    let recv_data = data.path().to_path_buf();
+    let recv_output = output_dir.clone();
+    let handle = std::thread::spawn(move || {
+        let mut cmd = Command::cargo_bin("flux").expect("flux binary not found");
+        cmd.env("FLUX_CONFIG_DIR", recv_iso.to_str().unwrap());
+        cmd.env("FLUX_DATA_DIR", recv_data.to_str().unwrap());
+        cmd.args([
+            "receive",
+            "--port",
+            &port.to_string(),
+            "--output",
+            recv_output.to_str().unwrap(),
+        ]);
+        cmd.timeout(std::time::Duration::from_secs(20));
+        // The receiver runs until the timeout kills it
+        cmd.assert().interrupted();
+    });
+
+    std::thread::sleep(std::time::Duration::from_secs(2));
+
+    let target = format!("127.0.0.1:{}", port);
+    flux_isolated(iso.path(), data.path())
+        .args(["send", first.to_str().unwrap(), &target])
+        .timeout(std::time::Duration::from_secs(10))
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("Receiver already has").not());
+
+    // The edited copy only needs the chunks around the edit
+    flux_isolated(iso.path(), data.path())
+        .args(["send", second.to_str().unwrap(), &target])
+        .timeout(std::time::Duration::from_secs(10))
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("Receiver already has"));
+
+    handle.join().unwrap();
+
+    assert_eq!(fs::read(output_dir.join("first.bin")).unwrap(), original);
+    assert_eq!(fs::read(output_dir.join("second.bin")).unwrap(), edited);
+}
+
+// ============================================================================
+// PROTOCOL CONFORMANCE TESTS
+// ============================================================================
+
+#[test]
+fn test_protocol_conformance_roundtrip() {
+    let iso = TempDir::new().unwrap();
+    let data = TempDir::new().unwrap();
+
+    let output = flux_isolated(iso.path(), data.path())
+        .args(["protocol", "conformance"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("\"protocol_version\": 2"));
+
+    let vectors = iso.path().join("vectors.json");
+    fs::write(&vectors, &stdout).unwrap();
+    flux_isolated(iso.path(), data.path())
+        .args(["protocol", "conformance", "--verify", vectors.to_str().unwrap()])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("match protocol v2"));
+
+    // A corrupted vector is reported and fails the command
+    let corrupted = stdout.replacen("\"encoded\": \"00", "\"encoded\": \"ff", 1);
+    fs::write(&vectors, corrupted).unwrap();
+    flux_isolated(iso.path(), data.path())
+        .args(["protocol", "conformance", "--verify", vectors.to_str().unwrap()])
+        .assert()
+        .code(1)
+        .stderr(predicate::str::contains("FAIL"))
+        .stderr(predicate::str::contains("1 conformance check(s) failed"));
+}
+
+#[test]
+fn test_protocol_command_is_hidden() {
+    let iso = TempDir::new().unwrap();
+    let data = TempDir::new().unwrap();
+
+    flux_isolated(iso.path(), data.path())
+        .arg("--help")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("protocol").not());
+}
+
+// ============================================================================
+// RECEIVE QUOTA TESTS
+// ============================================================================
+
+#[test]
+fn test_receive_show_quota_reports_limits_and_usage() {
+    let iso = TempDir::new().unwrap();
+    let data = TempDir::new().unwrap();
+    fs::write(
+        iso.path().join("config.toml"),
+        "[receive]\ndaily_quota = \"10GB\"\n\n[receive.device_quotas]\nlaptop = \"2GB\"\n",
+    )
+    .unwrap();
+
+    flux_isolated(iso.path(), data.path())
+        .args(["receive", "--show-quota"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("all devices: 0 B of 10.0 GB"))
+        .stdout(predicate::str::contains("laptop: 0 B of 2.0 GB"));
+}
+
+#[test]
+fn test_receive_show_quota_rejects_invalid_size() {
+    let iso = TempDir::new().unwrap();
+    let data = TempDir::new().unwrap();
+    fs::write(
+        iso.path().join("config.toml"),
+        "[receive]\ndaily_quota = \"plenty\"\n",
+    )
+    .unwrap();
+
+    flux_isolated(iso.path(), data.path())
+        .args(["receive", "--show-quota"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Invalid receive quota"));
+}
+
+#[test]
+fn test_receive_daemon_rejects_short_allowlist_fingerprint() {
+    let iso = TempDir::new().unwrap();
+    let data = TempDir::new().unwrap();
+    let out = TempDir::new().unwrap();
+    fs::write(
+        iso.path().join("config.toml"),
+        "[receive.devices.nas]\nfingerprint = \"q83v\"\n",
+    )
+    .unwrap();
+
+    flux_isolated(iso.path(), data.path())
+        .args(["receive", "--daemon", "--port", "0", "-o", out.path().to_str().unwrap()])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("[receive.devices.nas]"));
+}
+
+#[test]
+fn test_receive_rejects_invalid_free_space_margin() {
+    let iso = TempDir::new().unwrap();
+    let data = TempDir::new().unwrap();
+    let out = TempDir::new().unwrap();
+    fs::write(
+        iso.path().join("config.toml"),
+        "[receive]\nfree_space_margin = \"plenty\"\n",
+    )
+    .unwrap();
+
+    flux_isolated(iso.path(), data.path())
+        .args(["receive", "--daemon", "--port", "0", "-o", out.path().to_str().unwrap()])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("free_space_margin"));
+}
+
+#[test]
+fn test_receive_daemon_requires_encryption() {
+    let iso = TempDir::new().unwrap();
+    let data = TempDir::new().unwrap();
+
+    flux_isolated(iso.path(), data.path())
+        .args(["receive", "--daemon", "--no-encrypt"])
+        .assert()
+        .code(7);
+}
+
+#[test]
+fn test_adaptive_limit_needs_a_probe_target() {
+    let iso = TempDir::new().unwrap();
+    let data = TempDir::new().unwrap();
+    let work = TempDir::new().unwrap();
+    let file = work.path().join("a.txt");
+    fs::write(&file, "data").unwrap();
+
+    // Code-phrase mode has no listening peer to measure latency against
+    flux_isolated(iso.path(), data.path())
+        .args(["send", "--adaptive-limit", file.to_str().unwrap()])
+        .assert()
+        .code(7);
+    flux_isolated(iso.path(), data.path())
+        .args(["receive", "3847-ace-dog-elk", "--adaptive-limit"])
+        .assert()
+        .code(7);
+}
+
+#[test]
+fn test_send_rejects_invalid_limit_up() {
+    let iso = TempDir::new().unwrap();
+    let data = TempDir::new().unwrap();
+    let work = TempDir::new().unwrap();
+    let file = work.path().join("a.txt");
+    fs::write(&file, "data").unwrap();
+
+    flux_isolated(iso.path(), data.path())
+        .args(["send", "--limit-up", "fast", file.to_str().unwrap(), "127.0.0.1:1"])
+        .assert()
+        .code(7)
+        .stderr(predicate::str::contains("Invalid bandwidth format"));
+}
+
+#[test]
+fn test_psk_file_needs_encryption_and_a_direct_target() {
+    let iso = TempDir::new().unwrap();
+    let data = TempDir::new().unwrap();
+    let work = TempDir::new().unwrap();
+    let file = work.path().join("a.txt");
+    let key = work.path().join("fleet.key");
+    fs::write(&file, "data").unwrap();
+    fs::write(&key, "0123456789abcdef0123456789abcdef").unwrap();
+    let key = key.to_str().unwrap();
+
+    flux_isolated(iso.path(), data.path())
+        .args(["send", "--psk-file", key, "--no-encrypt"])
+        .args([file.to_str().unwrap(), "127.0.0.1:1"])
+        .assert()
+        .code(7);
+    // Code-phrase mode has its own shared secret
+    flux_isolated(iso.path(), data.path())
+        .args(["send", "--psk-file", key, file.to_str().unwrap()])
+        .assert()
+        .code(7);
+    flux_isolated(iso.path(), data.path())
+        .args(["receive", "3847-ace-dog-elk", "--psk-file", key])
+        .assert()
+        .code(7);
+}
+
+#[test]
+fn test_receive_rejects_short_psk() {
+    let iso = TempDir::new().unwrap();
+    let data = TempDir::new().unwrap();
+    let out = TempDir::new().unwrap();
+    let key = out.path().join("fleet.key");
+    fs::write(&key, "hunter2\n").unwrap();
+
+    flux_isolated(iso.path(), data.path())
+        .args(["receive", "--daemon", "--port", "0", "-o", out.path().to_str().unwrap()])
+        .args(["--psk-file", key.to_str().unwrap()])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("use at least 16"));
+}
+
+#[test]
+#[ignore]
+fn test_send_receive_with_psk() {
+    let recv_iso = TempDir::new().unwrap();
+    let send_iso = TempDir::new().unwrap();
+    let data = TempDir::new().unwrap();
+    let work = TempDir::new().unwrap();
+
+    let source_path = work.path().join("kiosk.txt");
+    let content = "Provisioned with one key file\n".repeat(50);
+    fs::write(&source_path, &content).unwrap();
+    let key = work.path().join("fleet.key");
+    fs::write(&key, "c2VjcmV0IGZsZWV0IGtleSBmb3Iga2lvc2tz\n").unwrap();
+    let wrong_key = work.path().join("other.key");
+    fs::write(&wrong_key, "bm90IHRoZSBmbGVldCBrZXkgYXQgYWxs\n").unwrap();
+
+    let output_dir = work.path().join("received");
+    fs::create_dir_all(&output_dir).unwrap();
+    let port = 19743;
+
+    // --daemon never prompts: without the key, the sender would be refused
+    let recv_config = recv_iso.path().to_path_buf();
+    let recv_data = data.path().to_path_buf();
+    let recv_output = output_dir.clone();
+    let recv_key = key.clone();
+    let handle = std::thread::spawn(move || {
+        let mut cmd = Command::cargo_bin("flux").expect("flux binary not found");
+        cmd.env("FLUX_CONFIG_DIR", recv_config.to_str().unwrap());
+        cmd.env("FLUX_DATA_DIR", recv_data.to_str().unwrap());
+        cmd.args(["receive", "--daemon", "--port", &port.to_string()]);
+        cmd.args(["--output", recv_output.to_str().unwrap()]);
+        cmd.args(["--psk-file", recv_key.to_str().unwrap()]);
+        cmd.timeout(std::time::Duration::from_secs(15));
+        // The receiver runs until the timeout kills it
+        cmd.assert().interrupted();
+    });
+    std::thread::sleep(std::time::Duration::from_secs(2));
+
+    let target = format!("127.0.0.1:{}", port);
+    flux_isolated(send_iso.path(), data.path())
+        .args(["send", "--psk-file", wrong_key.to_str().unwrap()])
+        .args([source_path.to_str().unwrap(), &target])
+        .timeout(std::time::Duration::from_secs(10))
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("pre-shared key"));
+    assert!(!output_dir.join("kiosk.txt").exists());
+
+    flux_isolated(send_iso.path(), data.path())
+        .args(["send", "--psk-file", key.to_str().unwrap()])
+        .args([source_path.to_str().unwrap(), &target])
+        .timeout(std::time::Duration::from_secs(10))
+        .assert()
+        .success();
+
+    handle.join().unwrap();
+    assert_eq!(fs::read_to_string(output_dir.join("kiosk.txt")).unwrap(), content);
+    // Nothing was added to the receiver's trust store
+    assert!(!recv_iso.path().join("trusted_devices.json").exists());
+}
+
+#[test]
+fn test_receive_web_options_need_web_mode() {
+    let iso = TempDir::new().unwrap();
+    let data = TempDir::new().unwrap();
+    let out = TempDir::new().unwrap();
+    let out_dir = out.path().to_str().unwrap();
+
+    flux_isolated(iso.path(), data.path())
+        .args(["receive", "--offer", "notes.txt", "-o", out_dir])
+        .assert()
+        .code(7);
+    flux_isolated(iso.path(), data.path())
+        .args(["receive", "3847-ace-dog-elk", "--web", "-o", out_dir])
+        .assert()
+        .code(7);
+    // Offered files must exist before anything is started
+    flux_isolated(iso.path(), data.path())
+        .args(["receive", "--web", "--port", "0", "--web-port", "0", "-o", out_dir])
+        .args(["--offer", out.path().join("missing.txt").to_str().unwrap()])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("missing.txt"));
+}
+
+#[test]
+#[ignore]
+fn test_receive_web_upload() {
+    use std::io::{BufRead, Read, Write};
+
+    let iso = TempDir::new().unwrap();
+    let data = TempDir::new().unwrap();
+    let out = TempDir::new().unwrap();
+
+    use assert_cmd::cargo::CommandCargoExt;
+
+    /// Kills the receiver when the test ends, passing or not.
+    struct KillOnDrop(std::process::Child);
+    impl Drop for KillOnDrop {
+        fn drop(&mut self) {
+            let _ = self.0.kill();
+            let _ = self.0.wait();
+        }
+    }
+
+    let mut child = KillOnDrop(
+        std::process::Command::cargo_bin("flux")
+            .unwrap()
+            .env("FLUX_CONFIG_DIR", iso.path())
+            .env("FLUX_DATA_DIR", data.path())
+            .args(["receive", "--bind", "127.0.0.1", "--port", "19748"])
+            .args(["--web", "--web-port", "19749", "-o", out.path().to_str().unwrap()])
+            .stderr(std::process::Stdio::piped())
+            .spawn()
+            .unwrap(),
+    );
+    let stderr = std::io::BufReader::new(child.0.stderr.take().unwrap());
+    let code = stderr
+        .lines()
+        .map_while(Result::ok)
+        .find_map(|line| line.strip_prefix("Web code phrase: ").map(str::to_string))
+        .expect("code phrase printed");
+
+    let upload = |code: &str| {
+        let body = b"dropped from a phone";
+        let mut stream = std::net::TcpStream::connect("127.0.0.1:19749").unwrap();
+        write!(
+            stream,
+            "POST /upload?name=note%201.txt HTTP/1.1\r\nHost: localhost\r\n\
+             X-Flux-Code: {}\r\nContent-Length: {}\r\n\r\n",
+            code,
+            body.len()
+        )
+        .unwrap();
+        stream.write_all(body).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    };
+    assert!(upload("1000-wrong-code-phrase-here").starts_with("HTTP/1.1 403"));
+    assert!(upload(&code).starts_with("HTTP/1.1 200"));
+    drop(child);
+
+    assert_eq!(
+        fs::read_to_string(out.path().join("note 1.txt")).unwrap(),
+        "dropped from a phone"
+    );
+}
+
+#[test]
+fn test_send_all_trusted_needs_trusted_devices() {
+    let iso = TempDir::new().unwrap();
+    let data = TempDir::new().unwrap();
+    let work = TempDir::new().unwrap();
+    let file = work.path().join("a.txt");
+    fs::write(&file, "data").unwrap();
+
+    flux_isolated(iso.path(), data.path())
+        .args(["send", "--all-trusted", file.to_str().unwrap()])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("no devices are trusted"));
+    flux_isolated(iso.path(), data.path())
+        .args(["send", "--all-trusted", "--code", "3847-ace-dog-elk"])
+        .arg(file.to_str().unwrap())
+        .assert()
+        .code(7);
+}
+
+#[test]
+fn test_group_send_reports_each_unreachable_device() {
+    let iso = TempDir::new().unwrap();
+    let data = TempDir::new().unwrap();
+    let work = TempDir::new().unwrap();
+    let file = work.path().join("a.txt");
+    fs::write(&file, "data").unwrap();
+
+    // Targets first, file last; nothing listens on these ports
+    flux_isolated(iso.path(), data.path())
+        .args(["send", "127.0.0.1:1", "127.0.0.1:2", file.to_str().unwrap()])
+        .timeout(std::time::Duration::from_secs(30))
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("2 of 2 devices did not receive a.txt"));
+}
+
+#[test]
+#[ignore]
+fn test_group_send_to_two_receivers() {
+    let send_iso = TempDir::new().unwrap();
+    let data = TempDir::new().unwrap();
+    let work = TempDir::new().unwrap();
+
+    let source_path = work.path().join("fanout.bin");
+    let content: Vec<u8> = (0..3_000_000u32).map(|i| (i % 251) as u8).collect();
+    fs::write(&source_path, &content).unwrap();
+    let key = work.path().join("fleet.key");
+    fs::write(&key, "c2VjcmV0IGZsZWV0IGtleSBmb3Iga2lvc2tz\n").unwrap();
+
+    let mut receivers = Vec::new();
+    let mut targets = Vec::new();
+    for port in [19744u16, 19745] {
+        let recv_iso = TempDir::new().unwrap();
+        let output_dir = work.path().join(format!("received-{}", port));
+        fs::create_dir_all(&output_dir).unwrap();
+        let recv_config = recv_iso.path().to_path_buf();
+        let recv_data = data.path().to_path_buf();
+        let recv_output = output_dir.clone();
+        let recv_key = key.clone();
+        let handle = std::thread::spawn(move || {
+            let mut cmd = Command::cargo_bin("flux").expect("flux binary not found");
+            cmd.env("FLUX_CONFIG_DIR", recv_config.to_str().unwrap());
+            cmd.env("FLUX_DATA_DIR", recv_data.to_str().unwrap());
+            cmd.args(["receive", "--daemon", "--port", &port.to_string()]);
+            cmd.args(["--output", recv_output.to_str().unwrap()]);
+            cmd.args(["--psk-file", recv_key.to_str().unwrap()]);
+            cmd.timeout(std::time::Duration::from_secs(15));
+            // The receiver runs until the timeout kills it
+            cmd.assert().interrupted();
+        });
+        receivers.push((recv_iso, output_dir, handle));
+        targets.push(format!("127.0.0.1:{}", port));
+    }
+    std::thread::sleep(std::time::Duration::from_secs(2));
+
+    flux_isolated(send_iso.path(), data.path())
+        .args(["send", "--psk-file", key.to_str().unwrap()])
+        .arg(source_path.to_str().unwrap())
+        .args(&targets)
+        .timeout(std::time::Duration::from_secs(10))
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("to 2 of 2 devices"));
+
+    for (_iso, output_dir, handle) in receivers {
+        handle.join().unwrap();
+        assert_eq!(fs::read(output_dir.join("fanout.bin")).unwrap(), content);
+    }
+}
+
+// ============================================================================
+// ENCRYPTION AT REST TESTS
+// ============================================================================
+
+#[test]
+fn test_cp_encrypt_to_and_decrypt_roundtrip() {
+    let iso = TempDir::new().unwrap();
+    let data = TempDir::new().unwrap();
+    let work = TempDir::new().unwrap();
+    let key = work.path().join("recipient.key");
+    let src = work.path().join("src");
+    let out = work.path().join("out");
+    fs::create_dir_all(src.join("docs")).unwrap();
+    fs::create_dir_all(&out).unwrap();
+    fs::write(src.join("notes.txt"), "meet at noon").unwrap();
+    fs::write(src.join("docs/report.txt"), "quarterly numbers").unwrap();
+
+    flux_isolated(iso.path(), data.path())
+        .args(["decrypt", "--export-key", key.to_str().unwrap()])
+        .assert()
+        .success();
+
+    flux_isolated(iso.path(), data.path())
+        .args(["cp", "-r", "--encrypt-to", key.to_str().unwrap()])
+        .arg(src.to_str().unwrap())
+        .arg(out.to_str().unwrap())
+        .assert()
+        .success();
+
+    let encrypted = out.join("src/docs/report.txt.fluxenc");
+    assert!(encrypted.exists());
+    assert!(!out.join("src/docs/report.txt").exists());
+    let sealed = fs::read(&encrypted).unwrap();
+    assert!(sealed.starts_with(b"FLUXENC1"));
+    assert!(!String::from_utf8_lossy(&sealed).contains("quarterly"));
+
+    flux_isolated(iso.path(), data.path())
+        .args(["decrypt", out.join("src").to_str().unwrap()])
+        .assert()
+        .success();
+
+    assert_eq!(
+        fs::read_to_string(out.join("src/docs/report.txt")).unwrap(),
+        "quarterly numbers"
+    );
+    assert_eq!(
+        fs::read_to_string(out.join("src/notes.txt")).unwrap(),
+        "meet at noon"
+    );
+}
+
+#[test]
+fn test_decrypt_with_another_identity_fails() {
+    let iso = TempDir::new().unwrap();
+    let other = TempDir::new().unwrap();
+    let data = TempDir::new().unwrap();
+    let work = TempDir::new().unwrap();
+    let key = work.path().join("recipient.key");
+    let file = work.path().join("secret.txt");
+    fs::write(&file, "top secret").unwrap();
+
+    flux_isolated(iso.path(), data.path())
+        .args(["decrypt", "--export-key", key.to_str().unwrap()])
+        .assert()
+        .success();
+    flux_isolated(iso.path(), data.path())
+        .args(["cp", "--encrypt-to", key.to_str().unwrap()])
+        .arg(file.to_str().unwrap())
+        .arg(work.path().join("secret.bin.fluxenc").to_str().unwrap())
+        .assert()
+        .success();
+
+    // A device with a different identity cannot decrypt, and leaves nothing behind
+    flux_isolated(other.path(), data.path())
+        .args(["decrypt", work.path().join("secret.bin.fluxenc").to_str().unwrap()])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Decryption failed"));
+    assert!(!work.path().join("secret.bin").exists());
+}
+
+#[test]
+fn test_streamed_copies_encrypt_and_decrypt() {
+    let iso = TempDir::new().unwrap();
+    let data = TempDir::new().unwrap();
+    let work = TempDir::new().unwrap();
+    let key = work.path().join("recipient.key");
+    let sealed = work.path().join("db.sql.fluxenc");
+    let out = work.path().join("out");
+    fs::create_dir_all(&out).unwrap();
+    let dump = "INSERT INTO t VALUES (1);\n".repeat(10_000);
+
+    flux_isolated(iso.path(), data.path())
+        .args(["decrypt", "--export-key", key.to_str().unwrap()])
+        .assert()
+        .success();
+
+    // The streamed path (as for network backends) seals before writing
+    flux_isolated(iso.path(), data.path())
+        .args(["cp", "--encrypt-to", key.to_str().unwrap(), "-"])
+        .arg(sealed.to_str().unwrap())
+        .write_stdin(dump.clone())
+        .assert()
+        .success();
+    let bytes = fs::read(&sealed).unwrap();
+    assert!(bytes.starts_with(b"FLUXENC1"));
+    assert!(!String::from_utf8_lossy(&bytes).contains("INSERT"));
+
+    // --decrypt opens it on the way back, naming the copy without .fluxenc
+    flux_isolated(iso.path(), data.path())
+        .args(["cp", "--decrypt", "--verify"])
+        .arg(sealed.to_str().unwrap())
+        .arg(out.to_str().unwrap())
+        .assert()
+        .success();
+    assert_eq!(fs::read_to_string(out.join("db.sql")).unwrap(), dump);
+
+    flux_isolated(iso.path(), data.path())
+        .args(["cp", "--decrypt"])
+        .arg(sealed.to_str().unwrap())
+        .arg("-")
+        .assert()
+        .success()
+        .stdout(predicate::eq(dump.as_str()));
+
+    // Plain files are refused, and leave nothing behind
+    let plain = work.path().join("plain.txt");
+    fs::write(&plain, "not encrypted").unwrap();
+    flux_isolated(iso.path(), data.path())
+        .args(["cp", "--decrypt"])
+        .arg(plain.to_str().unwrap())
+        .arg(work.path().join("plain.out").to_str().unwrap())
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Not a Flux-encrypted file"));
+    assert!(!work.path().join("plain.out").exists());
+}
+
+#[test]
+fn test_push_and_pull_check_their_paths() {
+    let iso = TempDir::new().unwrap();
+    let data = TempDir::new().unwrap();
+    let work = TempDir::new().unwrap();
+    let dir = work.path().to_str().unwrap();
+    let file = work.path().join("notes.txt");
+    fs::write(&file, "notes").unwrap();
+
+    // Remote paths stay inside the receiver's output directory
+    flux_isolated(iso.path(), data.path())
+        .args(["push", "127.0.0.1:9", dir, "../outside"])
+        .assert()
+        .code(7);
+    flux_isolated(iso.path(), data.path())
+        .args(["pull", "127.0.0.1:9", "/etc", dir])
+        .assert()
+        .code(7);
+    // Both sides are directories
+    flux_isolated(iso.path(), data.path())
+        .args(["push", "127.0.0.1:9", file.to_str().unwrap(), "backup"])
+        .assert()
+        .code(7)
+        .stderr(predicate::str::contains("flux send"));
+    flux_isolated(iso.path(), data.path())
+        .args(["push", "127.0.0.1:9", "--psk-file", "fleet.key", "--no-encrypt", dir, "b"])
+        .assert()
+        .code(7);
+}
+
+#[test]
+#[ignore]
+fn test_push_and_pull_mirror_changes() {
+    let recv_iso = TempDir::new().unwrap();
+    let send_iso = TempDir::new().unwrap();
+    let data = TempDir::new().unwrap();
+    let work = TempDir::new().unwrap();
+
+    let local = work.path().join("photos");
+    fs::create_dir_all(local.join("2024")).unwrap();
+    fs::write(local.join("a.jpg"), "first photo").unwrap();
+    fs::write(local.join("2024/b.jpg"), "second photo").unwrap();
+    let key = work.path().join("fleet.key");
+    fs::write(&key, "c2VjcmV0IGZsZWV0IGtleSBmb3Iga2lvc2tz\n").unwrap();
+    let output_dir = work.path().join("inbox");
+    fs::create_dir_all(output_dir.join("backup/photos")).unwrap();
+    fs::write(output_dir.join("backup/photos/stale.jpg"), "gone from the source").unwrap();
+    let port = 19750;
+
+    let recv_config = recv_iso.path().to_path_buf();
+    let recv_data = data.path().to_path_buf();
+    let recv_output = output_dir.clone();
+    let recv_key = key.clone();
+    let handle = std::thread::spawn(move || {
+        let mut cmd = Command::cargo_bin("flux").expect("flux binary not found");
+        cmd.env("FLUX_CONFIG_DIR", recv_config.to_str().unwrap());
+        cmd.env("FLUX_DATA_DIR", recv_data.to_str().unwrap());
+        cmd.args(["receive", "--daemon", "--allow-mirror", "--port", &port.to_string()]);
+        cmd.args(["--output", recv_output.to_str().unwrap()]);
+        cmd.args(["--psk-file", recv_key.to_str().unwrap()]);
+        cmd.timeout(std::time::Duration::from_secs(20));
+        // The receiver runs until the timeout kills it
+        cmd.assert().interrupted();
+    });
+    std::thread::sleep(std::time::Duration::from_secs(2));
+
+    let target = format!("127.0.0.1:{}", port);
+    let mirror = |args: &[&str]| {
+        flux_isolated(send_iso.path(), data.path())
+            .args(args)
+            .args(["--psk-file", key.to_str().unwrap()])
+            .timeout(std::time::Duration::from_secs(10))
+            .assert()
+            .success()
+    };
+    let local_dir = local.to_str().unwrap();
+    mirror(&["push", &target, local_dir, "backup/photos", "--delete"])
+        .stderr(predicate::str::contains("2 copied"))
+        .stderr(predicate::str::contains("1 deleted"));
+    let remote = output_dir.join("backup/photos");
+    assert_eq!(fs::read_to_string(remote.join("2024/b.jpg")).unwrap(), "second photo");
+    assert!(!remote.join("stale.jpg").exists());
+
+    // Unchanged files are not sent again
+    mirror(&["push", &target, local_dir, "backup/photos"])
+        .stderr(predicate::str::contains("Already in sync"));
+
+    let pulled = work.path().join("pulled");
+    mirror(&["pull", &target, "backup/photos", pulled.to_str().unwrap()])
+        .stderr(predicate::str::contains("2 copied"));
+    assert_eq!(fs::read_to_string(pulled.join("a.jpg")).unwrap(), "first photo");
+
+    handle.join().unwrap();
+}
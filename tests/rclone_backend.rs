@@ -0,0 +1,198 @@
+//! Integration tests for the rclone backend (`rclone:remote:path`).
+//!
+//! A stand-in `rclone` shell script, set as `backends.rclone_binary`,
+//! answers the commands flux runs, so no rclone install is needed.
+
+#![cfg(all(feature = "backends-rclone", unix))]
+
+use assert_cmd::Command;
+use predicates::prelude::*;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use tempfile::TempDir;
+
+/// Helper: get a Command for the flux binary.
+fn flux() -> Command {
+    Command::cargo_bin("flux").expect("flux binary not found")
+}
+
+/// A config dir whose config.toml points `rclone_binary` at `script`.
+fn config_with_rclone(dir: &TempDir, script: &str) -> std::path::PathBuf {
+    let binary = dir.path().join("rclone");
+    fs::write(&binary, format!("#!/bin/sh\n{}", script)).unwrap();
+    fs::set_permissions(&binary, fs::Permissions::from_mode(0o755)).unwrap();
+    let config_dir = dir.path().join("config");
+    fs::create_dir_all(&config_dir).unwrap();
+    fs::write(
+        config_dir.join("config.toml"),
+        format!("[backends]\nrclone_binary = \"{}\"\n", binary.display()),
+    )
+    .unwrap();
+    config_dir
+}
+
+#[test]
+fn tree_lists_an_rclone_remote() {
+    let dir = TempDir::new().unwrap();
+    let config_dir = config_with_rclone(
+        &dir,
+        r#"case "$1" in
+  version) echo "rclone v1.66.0" ;;
+  lsjson)
+    if [ "$2" = "--stat" ]; then
+      echo '{"Path":"","Name":"","Size":-1,"IsDir":true}'
+    elif [ "$2" = "fake:" ]; then
+      echo '[{"Name":"docs","Size":-1,"IsDir":true},{"Name":"notes.txt","Size":5,"IsDir":false}]'
+    else
+      echo '[]'
+    fi ;;
+  *) exit 1 ;;
+esac"#,
+    );
+
+    flux()
+        .env("FLUX_CONFIG_DIR", &config_dir)
+        .args(["tree", "rclone:fake:"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("notes.txt"))
+        .stdout(predicate::str::contains("docs"));
+}
+
+#[test]
+fn missing_rclone_binary_is_reported() {
+    let dir = TempDir::new().unwrap();
+    let config_dir = dir.path().join("config");
+    fs::create_dir_all(&config_dir).unwrap();
+    fs::write(
+        config_dir.join("config.toml"),
+        "[backends]\nrclone_binary = \"/nonexistent/rclone\"\n",
+    )
+    .unwrap();
+
+    flux()
+        .env("FLUX_CONFIG_DIR", &config_dir)
+        .args(["tree", "rclone:fake:"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("rclone"));
+}
+
+/// A stand-in rclone serving the `fake:` remote from the directory in
+/// `$FAKE_ROOT`.
+const FAKE_REMOTE: &str = r#"on_disk() { echo "$FAKE_ROOT/${1#fake:}"; }
+entry() {
+  if [ -d "$2" ]; then
+    printf '{"Name":"%s","Size":-1,"IsDir":true}' "$1"
+  else
+    printf '{"Name":"%s","Size":%s,"IsDir":false}' "$1" "$(wc -c < "$2" | tr -d ' ')"
+  fi
+}
+case "$1" in
+  version) echo "rclone v1.66.0" ;;
+  lsjson)
+    if [ "$2" = "--stat" ]; then
+      f=$(on_disk "$3"); [ -e "$f" ] || exit 3
+      entry "$(basename "$f")" "$f"; echo
+    else
+      sep=''; printf '['
+      for f in "$(on_disk "$2")"/*; do
+        [ -e "$f" ] || continue
+        printf '%s' "$sep"; entry "$(basename "$f")" "$f"; sep=','
+      done
+      echo ']'
+    fi ;;
+  cat) cat "$(on_disk "$2")" ;;
+  *) exit 1 ;;
+esac"#;
+
+#[test]
+fn directory_copies_from_a_remote_follow_the_trailing_slash() {
+    let dir = TempDir::new().unwrap();
+    let config_dir = config_with_rclone(&dir, FAKE_REMOTE);
+    let remote = dir.path().join("remote");
+    fs::create_dir_all(remote.join("photos/2024")).unwrap();
+    fs::write(remote.join("photos/a.jpg"), "aaa").unwrap();
+    fs::write(remote.join("photos/2024/b.jpg"), "bbbb").unwrap();
+    fs::write(remote.join("photos/2024/skip.tmp"), "x").unwrap();
+    let out = dir.path().join("out");
+    fs::create_dir_all(&out).unwrap();
+
+    // Without a trailing slash the directory itself is copied
+    flux()
+        .env("FLUX_CONFIG_DIR", &config_dir)
+        .env("FLUX_DATA_DIR", dir.path().join("data"))
+        .env("FAKE_ROOT", &remote)
+        .args(["cp", "-r", "--exclude", "*.tmp", "rclone:fake:photos"])
+        .arg(out.to_str().unwrap())
+        .assert()
+        .success();
+    assert_eq!(fs::read_to_string(out.join("photos/a.jpg")).unwrap(), "aaa");
+    assert_eq!(fs::read_to_string(out.join("photos/2024/b.jpg")).unwrap(), "bbbb");
+    assert!(!out.join("photos/2024/skip.tmp").exists());
+
+    // With one, its contents
+    let contents = dir.path().join("contents");
+    flux()
+        .env("FLUX_CONFIG_DIR", &config_dir)
+        .env("FLUX_DATA_DIR", dir.path().join("data"))
+        .env("FAKE_ROOT", &remote)
+        .args(["cp", "-r", "rclone:fake:photos/"])
+        .arg(contents.to_str().unwrap())
+        .assert()
+        .success();
+    assert_eq!(fs::read_to_string(contents.join("a.jpg")).unwrap(), "aaa");
+    assert!(contents.join("2024/skip.tmp").exists());
+
+    // A directory needs -r
+    flux()
+        .env("FLUX_CONFIG_DIR", &config_dir)
+        .env("FLUX_DATA_DIR", dir.path().join("data"))
+        .env("FAKE_ROOT", &remote)
+        .args(["cp", "rclone:fake:photos"])
+        .arg(out.to_str().unwrap())
+        .assert()
+        .failure();
+}
+
+#[test]
+fn copies_from_a_remote_are_counted_in_usage() {
+    let dir = TempDir::new().unwrap();
+    let config_dir = config_with_rclone(&dir, FAKE_REMOTE);
+    let remote = dir.path().join("remote");
+    fs::create_dir_all(remote.join("photos")).unwrap();
+    fs::write(remote.join("photos/a.jpg"), "aaa").unwrap();
+    let out = dir.path().join("out");
+    fs::create_dir_all(&out).unwrap();
+
+    flux()
+        .env("FLUX_CONFIG_DIR", &config_dir)
+        .env("FLUX_DATA_DIR", dir.path().join("data"))
+        .env("FAKE_ROOT", &remote)
+        .args(["cp", "rclone:fake:photos/a.jpg"])
+        .arg(out.to_str().unwrap())
+        .assert()
+        .success();
+
+    let output = flux()
+        .env("FLUX_CONFIG_DIR", &config_dir)
+        .env("FLUX_DATA_DIR", dir.path().join("data"))
+        .args(["usage", "--by", "host", "--json"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let totals = report["totals"].as_array().unwrap();
+    assert_eq!(totals.len(), 1);
+    assert_eq!(totals[0]["key"], "fake (rclone)");
+    assert_eq!(totals[0]["bytes_in"], 3);
+    assert_eq!(totals[0]["bytes_out"], 0);
+
+    flux()
+        .env("FLUX_CONFIG_DIR", &config_dir)
+        .env("FLUX_DATA_DIR", dir.path().join("data"))
+        .arg("usage")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("rclone").and(predicate::str::contains("3 B")));
+}
@@ -5,6 +5,8 @@
 //! `cargo test`, but can be run with `cargo test -- --ignored` when an SFTP
 //! server is available.
 
+#![cfg(feature = "backends-sftp")]
+
 use assert_cmd::Command;
 use predicates::prelude::*;
 use std::fs;
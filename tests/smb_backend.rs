@@ -4,6 +4,8 @@
 //! Network-dependent tests are marked with `#[ignore]` and require
 //! SMB_TEST_HOST and SMB_TEST_SHARE environment variables.
 
+#![cfg(feature = "backends-smb")]
+
 use assert_cmd::Command;
 use predicates::prelude::*;
 use std::fs;
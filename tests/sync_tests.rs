@@ -229,6 +229,7 @@ fn test_sync_creates_dest_directory() {
     );
 }
 
+#[cfg(feature = "watch")]
 #[test]
 fn test_sync_watch_schedule_mutex() {
     let dir = TempDir::new().unwrap();
@@ -253,6 +254,7 @@ fn test_sync_watch_schedule_mutex() {
 
 // ---- Plan 02 integration tests ----
 
+#[cfg(feature = "watch")]
 #[test]
 fn test_sync_watch_initial_sync() {
     // Watch mode should perform an initial sync immediately on start.
@@ -465,3 +467,394 @@ fn test_sync_force_empty_source_delete() {
     // File should be deleted
     assert!(!dest.join("doomed.txt").exists());
 }
+
+#[test]
+fn test_sync_delete_backup_dir_keeps_removed_files() {
+    let dir = TempDir::new().unwrap();
+    let source = dir.path().join("src");
+    let dest = dir.path().join("dst");
+    let backups = dir.path().join("backups");
+    std::fs::create_dir_all(&source).unwrap();
+    std::fs::create_dir_all(&dest).unwrap();
+
+    create_file(&source, "report.txt", "new report");
+    create_file(&dest, "report.txt", "old");
+    create_file(&dest, "notes/orphan.txt", "orphan");
+
+    flux()
+        .args([
+            "sync",
+            "--delete",
+            "--backup-dir",
+            backups.to_str().unwrap(),
+            "--backup-keep",
+            "3",
+            source.to_str().unwrap(),
+            dest.to_str().unwrap(),
+        ])
+        .assert()
+        .success();
+
+    assert!(!dest.join("notes/orphan.txt").exists());
+    assert_eq!(
+        std::fs::read_to_string(dest.join("report.txt")).unwrap(),
+        "new report"
+    );
+
+    // One timestamped folder holding the old versions at their relative paths
+    let runs: Vec<_> = std::fs::read_dir(&backups)
+        .unwrap()
+        .map(|e| e.unwrap().path())
+        .collect();
+    assert_eq!(runs.len(), 1);
+    assert_eq!(
+        std::fs::read_to_string(runs[0].join("report.txt")).unwrap(),
+        "old"
+    );
+    assert_eq!(
+        std::fs::read_to_string(runs[0].join("notes/orphan.txt")).unwrap(),
+        "orphan"
+    );
+}
+
+#[test]
+fn test_sync_trash_is_not_deleted_as_orphan() {
+    let dir = TempDir::new().unwrap();
+    let source = dir.path().join("src");
+    let dest = dir.path().join("dst");
+    std::fs::create_dir_all(&source).unwrap();
+    std::fs::create_dir_all(&dest).unwrap();
+
+    create_file(&source, "keep.txt", "keep");
+    create_file(&dest, "orphan.txt", "orphan");
+
+    for _ in 0..2 {
+        flux()
+            .args([
+                "sync",
+                "--delete",
+                "--trash",
+                source.to_str().unwrap(),
+                dest.to_str().unwrap(),
+            ])
+            .assert()
+            .success();
+    }
+
+    assert!(!dest.join("orphan.txt").exists());
+    let trash = dest.join(".flux-trash");
+    let runs: Vec<_> = std::fs::read_dir(&trash).unwrap().collect();
+    assert_eq!(runs.len(), 1);
+}
+
+#[test]
+fn test_sync_backup_keep_requires_backup_location() {
+    let dir = TempDir::new().unwrap();
+    let source = dir.path().join("src");
+    let dest = dir.path().join("dst");
+    std::fs::create_dir_all(&source).unwrap();
+    create_file(&source, "a.txt", "a");
+
+    flux()
+        .args([
+            "sync",
+            "--backup-keep",
+            "2",
+            source.to_str().unwrap(),
+            dest.to_str().unwrap(),
+        ])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--backup-keep requires"));
+}
+
+#[test]
+fn test_sync_verify_mirror_reports_drift() {
+    let dir = TempDir::new().unwrap();
+    let source = dir.path().join("src");
+    let dest = dir.path().join("dst");
+    let report = dir.path().join("drift.json");
+    std::fs::create_dir_all(&source).unwrap();
+    create_file(&source, "a.txt", "aaaa");
+    create_file(&source, "b.txt", "bbbb");
+
+    // Sync and verify: mirror is clean
+    flux()
+        .args([
+            "sync",
+            "--verify-mirror",
+            "--report",
+            report.to_str().unwrap(),
+            source.to_str().unwrap(),
+            dest.to_str().unwrap(),
+        ])
+        .assert()
+        .success();
+    let clean = std::fs::read_to_string(&report).unwrap();
+    assert!(clean.contains("\"drift\": []"), "report: {}", clean);
+    assert!(clean.contains("\"algorithm\": \"xxh3\""), "report: {}", clean);
+
+    // Silent corruption in dest (same size) is caught by a check-only run
+    create_file(&dest, "b.txt", "BBBB");
+    flux()
+        .args([
+            "sync",
+            "--verify-mirror",
+            "--mirror-only",
+            "--report",
+            report.to_str().unwrap(),
+            source.to_str().unwrap(),
+            dest.to_str().unwrap(),
+        ])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Mirror drift: 1 file(s) differ"));
+    let drift = std::fs::read_to_string(&report).unwrap();
+    assert!(drift.contains("\"content_mismatch\""), "report: {}", drift);
+    assert!(drift.contains("\"b.txt\""), "report: {}", drift);
+    // --mirror-only did not repair the file
+    assert_eq!(std::fs::read_to_string(dest.join("b.txt")).unwrap(), "BBBB");
+}
+
+#[test]
+fn test_sync_compare_checksum_catches_same_size_edits() {
+    let dir = TempDir::new().unwrap();
+    let data = dir.path().join("data");
+    let source = dir.path().join("src");
+    let dest = dir.path().join("dst");
+    std::fs::create_dir_all(&source).unwrap();
+    create_file(&source, "notes.txt", "draft one");
+
+    let sync = |compare: &str| {
+        flux()
+            .env("FLUX_DATA_DIR", &data)
+            .args([
+                "sync",
+                "--compare",
+                compare,
+                source.to_str().unwrap(),
+                dest.to_str().unwrap(),
+            ])
+            .assert()
+            .success();
+    };
+    sync("checksum");
+
+    // Same size, dest still newer: only a content comparison notices
+    create_file(&dest, "notes.txt", "draft two");
+    sync("size");
+    assert_eq!(std::fs::read_to_string(dest.join("notes.txt")).unwrap(), "draft two");
+    sync("checksum");
+    assert_eq!(std::fs::read_to_string(dest.join("notes.txt")).unwrap(), "draft one");
+
+    flux()
+        .args(["sync", "--compare", "content", source.to_str().unwrap(), dest.to_str().unwrap()])
+        .assert()
+        .failure();
+}
+
+#[test]
+fn test_sync_transactional_commits_and_cleans_up() {
+    let dir = TempDir::new().unwrap();
+    let source = dir.path().join("src");
+    let dest = dir.path().join("dst");
+    create_file(&source, "app.bin", "v2");
+    create_file(&source, "lib/core.bin", "core v2");
+    // A different size: same-size files written together count as unchanged
+    create_file(&dest, "app.bin", "old v1");
+    create_file(&dest, "old.bin", "orphan");
+
+    flux()
+        .args([
+            "sync",
+            "--transactional",
+            "--delete",
+            source.to_str().unwrap(),
+            dest.to_str().unwrap(),
+        ])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("Verifying 2 staged file(s)"));
+
+    assert_eq!(std::fs::read_to_string(dest.join("app.bin")).unwrap(), "v2");
+    assert_eq!(std::fs::read_to_string(dest.join("lib/core.bin")).unwrap(), "core v2");
+    assert!(!dest.join("old.bin").exists());
+    let leftovers: Vec<_> = std::fs::read_dir(&dest)
+        .unwrap()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_name().to_string_lossy().starts_with(".flux-staging-"))
+        .collect();
+    assert!(leftovers.is_empty(), "staging folder should be removed");
+
+    flux()
+        .args([
+            "sync",
+            "--transactional",
+            "--trash",
+            source.to_str().unwrap(),
+            dest.to_str().unwrap(),
+        ])
+        .assert()
+        .failure();
+}
+
+#[test]
+fn test_sync_xattrs_never_fails_the_copy() {
+    let dir = TempDir::new().unwrap();
+    let source = dir.path().join("src");
+    let dest = dir.path().join("dst");
+    create_file(&source, "notes.txt", "tagged");
+
+    // Copied or listed as lost, depending on the filesystem: the sync succeeds
+    flux()
+        .args([
+            "sync",
+            "--xattrs",
+            "--acls",
+            source.to_str().unwrap(),
+            dest.to_str().unwrap(),
+        ])
+        .assert()
+        .success();
+
+    assert_eq!(std::fs::read_to_string(dest.join("notes.txt")).unwrap(), "tagged");
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_sync_transactional_copies_xattrs() {
+    let dir = TempDir::new().unwrap();
+    let source = dir.path().join("src");
+    let dest = dir.path().join("dst");
+    create_file(&source, "notes.txt", "tagged");
+    // tmpfs and some container filesystems have no user xattrs
+    if xattr::set(source.join("notes.txt"), "user.flux.test", b"kept").is_err() {
+        return;
+    }
+
+    flux()
+        .args([
+            "sync",
+            "--transactional",
+            "--xattrs",
+            source.to_str().unwrap(),
+            dest.to_str().unwrap(),
+        ])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("Could not preserve").not());
+
+    assert_eq!(
+        xattr::get(dest.join("notes.txt"), "user.flux.test").unwrap().as_deref(),
+        Some(&b"kept"[..])
+    );
+}
+
+#[test]
+fn test_sync_read_only_refuses_writes_into_the_source() {
+    let dir = TempDir::new().unwrap();
+    let source = dir.path().join("src");
+    create_file(&source, "a.txt", "a");
+
+    // A destination inside the source is refused before anything is written
+    let inside = source.join("mirror");
+    flux()
+        .args(["sync", "--read-only", source.to_str().unwrap(), inside.to_str().unwrap()])
+        .assert()
+        .code(3)
+        .stderr(predicate::str::contains("read-only source"));
+    assert!(!inside.exists());
+
+    let dest = dir.path().join("dst");
+    flux()
+        .args(["sync", "--read-only", source.to_str().unwrap(), dest.to_str().unwrap()])
+        .assert()
+        .success();
+    assert_eq!(std::fs::read_to_string(dest.join("a.txt")).unwrap(), "a");
+}
+
+#[test]
+fn test_diff_reports_without_changing_either_side() {
+    let dir = TempDir::new().unwrap();
+    let data = dir.path().join("data");
+    let a = dir.path().join("a");
+    let b = dir.path().join("b");
+    create_file(&a, "same.txt", "same");
+    create_file(&b, "same.txt", "same");
+    create_file(&a, "new.txt", "only in a");
+    create_file(&b, "old.txt", "only in b");
+
+    flux()
+        .env("FLUX_DATA_DIR", &data)
+        .args(["diff", "--flat", a.to_str().unwrap(), b.to_str().unwrap()])
+        .assert()
+        .code(8)
+        .stdout(predicate::str::contains("A ONLY"))
+        .stdout(predicate::str::contains("old.txt"))
+        .stdout(predicate::str::contains("1 only in A, 1 only in B, 0 differ, 1 identical"));
+    assert!(!b.join("new.txt").exists());
+    assert!(b.join("old.txt").exists());
+
+    let output = flux()
+        .env("FLUX_DATA_DIR", &data)
+        .args(["--json", "diff", a.to_str().unwrap(), b.to_str().unwrap()])
+        .output()
+        .unwrap();
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["entries"][0]["status"], "only_in_a");
+    assert_eq!(report["identical"], 1);
+
+    std::fs::remove_file(a.join("new.txt")).unwrap();
+    std::fs::remove_file(b.join("old.txt")).unwrap();
+    flux()
+        .env("FLUX_DATA_DIR", &data)
+        .args(["diff", a.to_str().unwrap(), b.to_str().unwrap()])
+        .assert()
+        .success();
+}
+
+#[test]
+fn test_sync_low_memory_dry_run_then_sync() {
+    let dir = TempDir::new().unwrap();
+    let source = dir.path().join("src");
+    let dest = dir.path().join("dst");
+    create_file(&source, "a.txt", "aaa");
+    create_file(&source, "sub/b.txt", "bb");
+    create_file(&dest, "orphan.txt", "x");
+
+    // The dry run lists the plan from its spill file and changes nothing
+    flux()
+        .args(["sync", "--low-memory", "--dry-run", "--delete"])
+        .args([source.to_str().unwrap(), dest.to_str().unwrap()])
+        .assert()
+        .success()
+        .stderr(
+            predicate::str::contains("Sync plan:")
+                .and(predicate::str::contains("COPY"))
+                .and(predicate::str::contains("DELETE"))
+                .and(predicate::str::contains("2 to copy, 0 to update, 1 to delete")),
+        );
+    assert!(!dest.join("a.txt").exists());
+    assert!(dest.join("orphan.txt").exists());
+
+    flux()
+        .args(["sync", "--low-memory", "--delete", "--stats"])
+        .args([source.to_str().unwrap(), dest.to_str().unwrap()])
+        .assert()
+        .success()
+        .stderr(
+            predicate::str::contains("2 copied, 0 updated, 1 deleted")
+                .and(predicate::str::contains("Peak memory:")),
+        );
+    assert_eq!(std::fs::read_to_string(dest.join("sub/b.txt")).unwrap(), "bb");
+    assert!(!dest.join("orphan.txt").exists());
+
+    // A destination inside the source would be walked while it is written
+    flux()
+        .args(["sync", "--low-memory"])
+        .arg(source.to_str().unwrap())
+        .arg(source.join("mirror").to_str().unwrap())
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("inside the source"));
+}
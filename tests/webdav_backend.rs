@@ -4,6 +4,8 @@
 //! Network-dependent tests are marked with #[ignore] and require a real
 //! WebDAV server specified via WEBDAV_TEST_URL env var.
 
+#![cfg(feature = "backends-webdav")]
+
 use assert_cmd::Command;
 use std::fs;
 use tempfile::TempDir;
@@ -92,6 +94,25 @@ fn webdav_explicit_scheme_routes_to_backend() {
         .failure();
 }
 
+/// Test that a WebDAV destination is never written as a local path.
+#[test]
+fn webdav_destination_is_not_created_locally() {
+    let dir = TempDir::new().unwrap();
+    let source = create_file_in(&dir, "upload.txt", b"webdav test data");
+
+    flux()
+        .current_dir(dir.path())
+        .args([
+            "cp",
+            source.to_str().unwrap(),
+            "dav://127.0.0.1:9/files/upload.txt",
+        ])
+        .assert()
+        .failure();
+
+    assert!(!dir.path().join("dav:").exists());
+}
+
 /// Test that local file copies still work alongside WebDAV changes.
 #[test]
 fn local_copy_still_works_with_webdav_backend() {